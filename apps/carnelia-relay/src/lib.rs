@@ -0,0 +1,499 @@
+//! # Carnelia Relay
+//!
+//! Forwards MDCS SDK [`Frame`]s between the members of a session over
+//! WebSocket, for clients using `WebSocketTransport`.
+//!
+//! ## Protocol
+//!
+//! Every WebSocket message is one encoded [`Frame`]. A connection's first
+//! frame is a [`Message::Join`] naming its session and, in `from`, its
+//! peer ID. From then on the relay:
+//!
+//! - sends the newcomer a `Join` from each member already there, and each
+//!   member a `Join` from the newcomer;
+//! - forwards every frame to its `to` member, or to every other member,
+//!   with `from` set to the sender;
+//! - on a `Leave` or a dropped connection, sends the remaining members a
+//!   `Leave` from the member that went.
+//!
+//! Document contents are never looked at. The one exception to pure
+//! forwarding is [`Message::Snapshot`]: with snapshot storage configured,
+//! the latest snapshot of each document is kept per session and sent, from
+//! [`RELAY_PEER_ID`], to everyone who joins, so state survives the session
+//! emptying out. The relay can't merge snapshots, so "latest" is the last
+//! one received.
+//!
+//! ## Limits
+//!
+//! Rate limits apply to presence messages only, per connection and per
+//! session; excess ones are dropped. Document and control messages always
+//! pass. A member whose outgoing queue fills up is disconnected rather
+//! than silently missing document messages.
+
+pub mod limit;
+pub mod storage;
+
+pub use limit::RateLimit;
+pub use storage::FileDocStorage;
+
+use axum::extract::ws::{Message as WsMessage, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use futures::{SinkExt, StreamExt};
+use limit::Bucket;
+use mdcs_sdk::{Channel, DocStorage, Frame, Message, PeerId};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, Notify};
+
+/// Peer ID the relay sends cached snapshots from.
+pub const RELAY_PEER_ID: &str = "relay";
+
+/// Default number of frames queued for a member before it is disconnected.
+pub const DEFAULT_QUEUE: usize = 1024;
+
+// ─── Configuration ─────────────────────────────────────────────────────────
+
+/// Relay settings.
+#[derive(Clone)]
+pub struct RelayConfig {
+    /// Presence messages one connection may send.
+    pub connection_rate: Option<RateLimit>,
+    /// Presence messages all members of a session may send together.
+    pub session_rate: Option<RateLimit>,
+    /// Where the latest snapshots are kept; `None` disables caching.
+    pub storage: Option<Arc<dyn DocStorage>>,
+    /// Frames queued for a member before it is disconnected.
+    pub queue: usize,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            connection_rate: None,
+            session_rate: None,
+            storage: None,
+            queue: DEFAULT_QUEUE,
+        }
+    }
+}
+
+impl RelayConfig {
+    pub fn with_connection_rate(mut self, limit: RateLimit) -> Self {
+        self.connection_rate = Some(limit);
+        self
+    }
+
+    pub fn with_session_rate(mut self, limit: RateLimit) -> Self {
+        self.session_rate = Some(limit);
+        self
+    }
+
+    pub fn with_storage(mut self, storage: Arc<dyn DocStorage>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    pub fn with_queue(mut self, queue: usize) -> Self {
+        self.queue = queue.max(1);
+        self
+    }
+}
+
+// ─── Sessions ──────────────────────────────────────────────────────────────
+
+/// A member's connection, as seen by the rest of its session.
+struct Member {
+    connection: u64,
+    tx: mpsc::Sender<Vec<u8>>,
+    /// Tells the connection to close once it is no longer a member.
+    evict: Arc<Notify>,
+}
+
+#[derive(Default)]
+struct Session {
+    members: HashMap<PeerId, Member>,
+    presence: Option<Bucket>,
+}
+
+/// Counters behind [`Relay::metrics_text`].
+#[derive(Default)]
+struct Metrics {
+    connections: AtomicU64,
+    messages_relayed: AtomicU64,
+    bytes_relayed: AtomicU64,
+    messages_dropped: AtomicU64,
+    members_evicted: AtomicU64,
+    snapshots_stored: AtomicU64,
+}
+
+/// The state shared by every connection.
+pub struct Relay {
+    config: RelayConfig,
+    sessions: Mutex<HashMap<String, Session>>,
+    next_connection: AtomicU64,
+    metrics: Metrics,
+}
+
+impl Relay {
+    pub fn new(config: RelayConfig) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            sessions: Mutex::new(HashMap::new()),
+            next_connection: AtomicU64::new(0),
+            metrics: Metrics::default(),
+        })
+    }
+
+    pub fn config(&self) -> &RelayConfig {
+        &self.config
+    }
+
+    /// Number of sessions with at least one member.
+    pub fn session_count(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    /// Members of a session.
+    pub fn members(&self, session_id: &str) -> Vec<PeerId> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(session_id)
+            .map(|session| session.members.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Serve one WebSocket connection until it closes.
+    pub async fn serve(self: Arc<Self>, socket: WebSocket) {
+        let connection = self.next_connection.fetch_add(1, Ordering::Relaxed);
+        self.metrics.connections.fetch_add(1, Ordering::Relaxed);
+
+        let (mut sink, mut stream) = socket.split();
+        let (tx, mut rx) = mpsc::channel::<Vec<u8>>(self.config.queue);
+        let writer = tokio::spawn(async move {
+            while let Some(bytes) = rx.recv().await {
+                if sink.send(WsMessage::Binary(bytes)).await.is_err() {
+                    break;
+                }
+            }
+            let _ = sink.close().await;
+        });
+
+        let evict = Arc::new(Notify::new());
+        let mut evicted = false;
+        let mut joined: Option<(String, PeerId)> = None;
+        let mut presence = self.config.connection_rate.map(Bucket::new);
+        loop {
+            let message = tokio::select! {
+                message = stream.next() => message,
+                _ = evict.notified() => {
+                    evicted = true;
+                    break;
+                }
+            };
+            let Some(Ok(message)) = message else {
+                break;
+            };
+            let WsMessage::Binary(bytes) = message else {
+                continue;
+            };
+            let Ok(frame) = Frame::decode(&bytes) else {
+                continue;
+            };
+            match (&joined, frame.message) {
+                (None, Message::Join { session_id }) => {
+                    let member = Member {
+                        connection,
+                        tx: tx.clone(),
+                        evict: evict.clone(),
+                    };
+                    self.join(&session_id, &frame.from, member);
+                    joined = Some((session_id, frame.from));
+                }
+                (Some(_), Message::Leave { .. }) => break,
+                // Frames before a join, and further joins, are ignored
+                (None, _) | (Some(_), Message::Join { .. }) => {}
+                (Some((session_id, from)), message) => {
+                    if message.channel() == Channel::Presence
+                        && presence.as_mut().is_some_and(|bucket| !bucket.take())
+                    {
+                        self.metrics
+                            .messages_dropped
+                            .fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                    self.route(session_id, from, connection, frame.to, message);
+                }
+            }
+        }
+
+        if let Some((session_id, from)) = joined {
+            self.leave(&session_id, &from, connection);
+        }
+        drop(tx);
+        if evicted {
+            // Don't wait for a member that stopped reading to drain its queue
+            writer.abort();
+        }
+        let _ = writer.await;
+        self.metrics.connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Add a member, introduce it to the others and hand it the cached
+    /// snapshots.
+    fn join(&self, session_id: &str, peer: &PeerId, member: Member) {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .entry(session_id.to_string())
+            .or_insert_with(|| Session {
+                members: HashMap::new(),
+                presence: self.config.session_rate.map(Bucket::new),
+            });
+
+        let join = |from: &PeerId| {
+            Frame {
+                from: from.clone(),
+                to: None,
+                message: Message::Join {
+                    session_id: session_id.to_string(),
+                },
+            }
+            .encode()
+        };
+        let announcement = join(peer);
+        for (id, other) in &session.members {
+            if id != peer {
+                let _ = other.tx.try_send(announcement.clone());
+                let _ = member.tx.try_send(join(id));
+            }
+        }
+
+        for (document_id, state) in self.snapshots(session_id) {
+            let frame = Frame {
+                from: PeerId::new(RELAY_PEER_ID),
+                to: Some(peer.clone()),
+                message: Message::Snapshot { document_id, state },
+            };
+            let _ = member.tx.try_send(frame.encode());
+        }
+
+        // A reconnect under the same peer ID replaces the old connection
+        if let Some(old) = session.members.insert(peer.clone(), member) {
+            old.evict.notify_one();
+        }
+    }
+
+    fn snapshots(&self, session_id: &str) -> Vec<(String, Vec<u8>)> {
+        let Some(storage) = &self.config.storage else {
+            return Vec::new();
+        };
+        storage
+            .documents(session_id)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|id| Some((id.clone(), storage.load(session_id, &id).ok()??)))
+            .collect()
+    }
+
+    /// Forward a member's message and cache it if it is a snapshot.
+    ///
+    /// Frames from a connection that is no longer a member, because it was
+    /// evicted or replaced, are dropped.
+    fn route(
+        &self,
+        session_id: &str,
+        from: &PeerId,
+        connection: u64,
+        to: Option<PeerId>,
+        message: Message,
+    ) {
+        let mut sessions = self.sessions.lock().unwrap();
+        let Some(session) = sessions.get_mut(session_id) else {
+            return;
+        };
+        if session
+            .members
+            .get(from)
+            .is_none_or(|member| member.connection != connection)
+        {
+            return;
+        }
+        if message.channel() == Channel::Presence
+            && session
+                .presence
+                .as_mut()
+                .is_some_and(|bucket| !bucket.take())
+        {
+            self.metrics
+                .messages_dropped
+                .fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        let frame = Frame {
+            from: from.clone(),
+            to: to.clone(),
+            message,
+        };
+        let bytes = frame.encode();
+        let mut full = Vec::new();
+        for (id, member) in &session.members {
+            if id == from || to.as_ref().is_some_and(|to| to != id) {
+                continue;
+            }
+            if member.tx.try_send(bytes.clone()).is_ok() {
+                self.metrics
+                    .messages_relayed
+                    .fetch_add(1, Ordering::Relaxed);
+                self.metrics
+                    .bytes_relayed
+                    .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+            } else {
+                full.push(id.clone());
+            }
+        }
+
+        for id in full {
+            if let Some(member) = session.members.remove(&id) {
+                member.evict.notify_one();
+            }
+            self.metrics.members_evicted.fetch_add(1, Ordering::Relaxed);
+            announce_leave(session_id, session, &id);
+        }
+        if session.members.is_empty() {
+            sessions.remove(session_id);
+        }
+        drop(sessions);
+
+        if let (Message::Snapshot { document_id, state }, Some(storage)) =
+            (&frame.message, &self.config.storage)
+        {
+            if storage.save(session_id, document_id, state).is_ok() {
+                self.metrics
+                    .snapshots_stored
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Remove a member unless it already reconnected on another connection.
+    fn leave(&self, session_id: &str, peer: &PeerId, connection: u64) {
+        let mut sessions = self.sessions.lock().unwrap();
+        let Some(session) = sessions.get_mut(session_id) else {
+            return;
+        };
+        if session
+            .members
+            .get(peer)
+            .is_some_and(|member| member.connection == connection)
+        {
+            session.members.remove(peer);
+            announce_leave(session_id, session, peer);
+        }
+        if session.members.is_empty() {
+            sessions.remove(session_id);
+        }
+    }
+
+    /// Metrics in the Prometheus text format.
+    pub fn metrics_text(&self) -> String {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let metrics = [
+            (
+                "carnelia_relay_sessions",
+                "gauge",
+                "Sessions with at least one member.",
+                self.session_count() as u64,
+            ),
+            (
+                "carnelia_relay_connections",
+                "gauge",
+                "Open WebSocket connections.",
+                load(&self.metrics.connections),
+            ),
+            (
+                "carnelia_relay_messages_relayed_total",
+                "counter",
+                "Frames delivered to members.",
+                load(&self.metrics.messages_relayed),
+            ),
+            (
+                "carnelia_relay_bytes_relayed_total",
+                "counter",
+                "Bytes of frames delivered to members.",
+                load(&self.metrics.bytes_relayed),
+            ),
+            (
+                "carnelia_relay_messages_dropped_total",
+                "counter",
+                "Presence messages dropped by rate limits.",
+                load(&self.metrics.messages_dropped),
+            ),
+            (
+                "carnelia_relay_members_evicted_total",
+                "counter",
+                "Members disconnected because their queue was full.",
+                load(&self.metrics.members_evicted),
+            ),
+            (
+                "carnelia_relay_snapshots_stored_total",
+                "counter",
+                "Document snapshots written to storage.",
+                load(&self.metrics.snapshots_stored),
+            ),
+        ];
+
+        let mut text = String::new();
+        for (name, kind, help, value) in metrics {
+            let _ = writeln!(text, "# HELP {} {}", name, help);
+            let _ = writeln!(text, "# TYPE {} {}", name, kind);
+            let _ = writeln!(text, "{} {}", name, value);
+        }
+        text
+    }
+}
+
+/// Tell the remaining members of a session that `peer` left.
+fn announce_leave(session_id: &str, session: &Session, peer: &PeerId) {
+    let leave = Frame {
+        from: peer.clone(),
+        to: None,
+        message: Message::Leave {
+            session_id: session_id.to_string(),
+        },
+    }
+    .encode();
+    for member in session.members.values() {
+        let _ = member.tx.try_send(leave.clone());
+    }
+}
+
+// ─── HTTP ──────────────────────────────────────────────────────────────────
+
+/// Routes for clients: WebSocket connections on `/ws`.
+pub fn router(relay: Arc<Relay>) -> Router {
+    Router::new().route("/ws", get(upgrade)).with_state(relay)
+}
+
+/// Routes for operators: metrics on `/metrics`.
+pub fn admin_router(relay: Arc<Relay>) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics))
+        .with_state(relay)
+}
+
+async fn upgrade(State(relay): State<Arc<Relay>>, upgrade: WebSocketUpgrade) -> Response {
+    upgrade.on_upgrade(move |socket| relay.serve(socket))
+}
+
+async fn metrics(State(relay): State<Arc<Relay>>) -> impl IntoResponse {
+    (
+        [("content-type", "text/plain; version=0.0.4")],
+        relay.metrics_text(),
+    )
+}
@@ -0,0 +1,65 @@
+//! Token-bucket rate limits.
+
+use std::time::Instant;
+
+/// Allow bursts of up to `burst` messages, refilled at `per_second`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub per_second: f64,
+}
+
+impl RateLimit {
+    pub fn new(burst: u32, per_second: f64) -> Self {
+        Self { burst, per_second }
+    }
+}
+
+/// The tokens left under a [`RateLimit`].
+#[derive(Debug)]
+pub(crate) struct Bucket {
+    limit: RateLimit,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst as f64,
+            refilled: Instant::now(),
+        }
+    }
+
+    /// Take a token if one is left.
+    pub(crate) fn take(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.per_second).min(self.limit.burst as f64);
+        self.refilled = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_allows_a_burst() {
+        let mut bucket = Bucket::new(RateLimit::new(3, 0.0));
+        assert!((0..3).all(|_| bucket.take()));
+        assert!(!bucket.take());
+
+        let mut bucket = Bucket::new(RateLimit::new(1, 1000.0));
+        assert!(bucket.take());
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert!(bucket.take());
+    }
+}
@@ -0,0 +1,79 @@
+//! # Carnelia Relay
+//!
+//! Serves SDK clients on `--listen` and metrics on `--admin`.
+
+use carnelia_relay::{admin_router, router, FileDocStorage, RateLimit, Relay, RelayConfig};
+use clap::Parser;
+use mdcs_sdk::MemoryDocStorage;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Parser)]
+#[command(name = "carnelia-relay")]
+#[command(about = "Forwards MDCS SDK messages between the members of a session")]
+#[command(version)]
+struct Cli {
+    /// Address clients connect to, at /ws
+    #[arg(long, default_value = "0.0.0.0:9090")]
+    listen: SocketAddr,
+
+    /// Address serving /metrics
+    #[arg(long, default_value = "127.0.0.1:9091")]
+    admin: SocketAddr,
+
+    /// Keep the latest snapshots in this directory instead of in memory
+    #[arg(long, conflicts_with = "no_snapshots")]
+    snapshot_dir: Option<PathBuf>,
+
+    /// Don't keep snapshots at all
+    #[arg(long)]
+    no_snapshots: bool,
+
+    /// Presence messages a connection may send per second
+    #[arg(long)]
+    connection_rate: Option<f64>,
+
+    /// Presence messages a session may send per second
+    #[arg(long)]
+    session_rate: Option<f64>,
+
+    /// Seconds of presence messages allowed in a burst
+    #[arg(long, default_value_t = 2.0)]
+    burst_seconds: f64,
+
+    /// Frames queued for a member before it is disconnected
+    #[arg(long, default_value_t = carnelia_relay::DEFAULT_QUEUE)]
+    queue: usize,
+}
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let cli = Cli::parse();
+
+    let limit = |rate: f64| RateLimit::new((rate * cli.burst_seconds).ceil().max(1.0) as u32, rate);
+    let mut config = RelayConfig::default().with_queue(cli.queue);
+    if let Some(rate) = cli.connection_rate {
+        config = config.with_connection_rate(limit(rate));
+    }
+    if let Some(rate) = cli.session_rate {
+        config = config.with_session_rate(limit(rate));
+    }
+    if let Some(dir) = cli.snapshot_dir {
+        config = config.with_storage(Arc::new(FileDocStorage::new(dir)));
+    } else if !cli.no_snapshots {
+        config = config.with_storage(Arc::new(MemoryDocStorage::new()));
+    }
+
+    let relay = Relay::new(config);
+    let listener = tokio::net::TcpListener::bind(cli.listen).await?;
+    let admin = tokio::net::TcpListener::bind(cli.admin).await?;
+    println!("relay listening on ws://{}/ws", listener.local_addr()?);
+    println!("metrics on http://{}/metrics", admin.local_addr()?);
+
+    let admin = axum::serve(admin, admin_router(relay.clone()));
+    tokio::select! {
+        result = axum::serve(listener, router(relay)) => result,
+        result = admin => result,
+    }
+}
@@ -0,0 +1,112 @@
+//! Snapshot storage on disk.
+
+use mdcs_sdk::{DocStorage, SdkError};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Stores each snapshot in `<root>/<session>/<document>`, with both names
+/// hex-encoded so any ID is a valid file name.
+#[derive(Debug, Clone)]
+pub struct FileDocStorage {
+    root: PathBuf,
+}
+
+impl FileDocStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn session_dir(&self, session_id: &str) -> PathBuf {
+        self.root.join(hex(session_id))
+    }
+}
+
+impl DocStorage for FileDocStorage {
+    fn save(&self, session_id: &str, document_id: &str, state: &[u8]) -> Result<(), SdkError> {
+        let dir = self.session_dir(session_id);
+        fs::create_dir_all(&dir).map_err(storage_error)?;
+        // Write aside and rename, so a crash never leaves half a snapshot
+        let path = dir.join(hex(document_id));
+        let partial = path.with_extension("partial");
+        fs::write(&partial, state).map_err(storage_error)?;
+        fs::rename(&partial, &path).map_err(storage_error)
+    }
+
+    fn load(&self, session_id: &str, document_id: &str) -> Result<Option<Vec<u8>>, SdkError> {
+        match fs::read(self.session_dir(session_id).join(hex(document_id))) {
+            Ok(state) => Ok(Some(state)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(storage_error(e)),
+        }
+    }
+
+    fn documents(&self, session_id: &str) -> Result<Vec<String>, SdkError> {
+        let entries = match fs::read_dir(self.session_dir(session_id)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(storage_error(e)),
+        };
+        let mut documents = Vec::new();
+        for entry in entries {
+            let name = entry.map_err(storage_error)?.file_name();
+            if let Some(document_id) = name.to_str().and_then(unhex) {
+                documents.push(document_id);
+            }
+        }
+        documents.sort();
+        Ok(documents)
+    }
+}
+
+fn storage_error(e: std::io::Error) -> SdkError {
+    SdkError::Internal(format!("snapshot storage: {}", e))
+}
+
+fn hex(name: &str) -> String {
+    name.bytes().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decode a name written by [`hex`]; anything else, such as a partial
+/// write, is `None`.
+fn unhex(name: &str) -> Option<String> {
+    let bytes = (0..name.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(name.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_doc_storage() {
+        let root = std::env::temp_dir().join(format!("carnelia-relay-{}", std::process::id()));
+        let storage = FileDocStorage::new(&root);
+
+        assert_eq!(storage.load("s/1", "notes").unwrap(), None);
+        assert!(storage.documents("s/1").unwrap().is_empty());
+
+        storage.save("s/1", "notes", b"one").unwrap();
+        storage.save("s/1", "../draft", b"two").unwrap();
+        storage.save("s/1", "notes", b"three").unwrap();
+        storage.save("s/2", "other", b"four").unwrap();
+
+        assert_eq!(
+            storage.load("s/1", "notes").unwrap(),
+            Some(b"three".to_vec())
+        );
+        assert_eq!(storage.documents("s/1").unwrap(), ["../draft", "notes"]);
+        assert_eq!(
+            FileDocStorage::new(&root).documents("s/2").unwrap(),
+            ["other"]
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
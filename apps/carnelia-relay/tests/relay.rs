@@ -0,0 +1,308 @@
+//! SDK clients talking through a relay on an ephemeral port.
+
+use carnelia_relay::{admin_router, router, RateLimit, Relay, RelayConfig};
+use mdcs_sdk::document::TextDoc;
+use mdcs_sdk::{
+    ChannelCapacity, DocStorage, Inbox, MemoryDocStorage, Message, NetworkTransport, PeerId,
+    Session, WebSocketTransport,
+};
+use parking_lot::RwLock;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+const SESSION: &str = "team";
+
+/// Serve `router` on an ephemeral port and return its address.
+async fn serve(router: axum::Router) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await });
+    addr.to_string()
+}
+
+async fn start(config: RelayConfig) -> (Arc<Relay>, String) {
+    let relay = Relay::new(config);
+    let addr = serve(router(relay.clone())).await;
+    (relay, format!("ws://{}/ws", addr))
+}
+
+async fn connect(url: &str, name: &str) -> Arc<WebSocketTransport> {
+    Arc::new(
+        WebSocketTransport::connect(url, PeerId::new(name), SESSION)
+            .await
+            .unwrap(),
+    )
+}
+
+/// A session with `notes` open, handling everything its transport receives.
+struct Client {
+    transport: Arc<WebSocketTransport>,
+    session: Arc<Session<WebSocketTransport>>,
+    notes: Arc<RwLock<TextDoc>>,
+    pump: JoinHandle<()>,
+}
+
+impl Client {
+    async fn join(url: &str, name: &str) -> Self {
+        let transport = connect(url, name).await;
+        let mut inbox = transport.subscribe();
+        let session = Arc::new(Session::new(
+            SESSION,
+            PeerId::new(name),
+            name,
+            transport.clone(),
+        ));
+        let notes = session.open_text_doc("notes");
+        let pump = {
+            let session = session.clone();
+            tokio::spawn(async move {
+                while let Some((from, message)) = inbox.recv().await {
+                    let _ = session.handle_message(&from, &message).await;
+                }
+            })
+        };
+        Self {
+            transport,
+            session,
+            notes,
+            pump,
+        }
+    }
+
+    fn text(&self) -> String {
+        self.notes.read().get_text()
+    }
+
+    /// Drop the connection without leaving, as a crash would.
+    fn kill(self) {
+        self.pump.abort();
+    }
+}
+
+/// Poll `check` until it holds, failing after a few seconds.
+async fn eventually(what: &str, mut check: impl FnMut() -> bool) {
+    for _ in 0..500 {
+        if check() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("timed out waiting for {}", what);
+}
+
+async fn wait_for_peers(transport: &WebSocketTransport, count: usize) {
+    for _ in 0..500 {
+        if transport.connected_peers().await.len() == count {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("timed out waiting for {} peers", count);
+}
+
+async fn get_metrics(addr: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /metrics HTTP/1.1\r\nHost: relay\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    response
+}
+
+#[tokio::test]
+async fn test_late_joiners_get_the_cached_snapshot() {
+    let storage = Arc::new(MemoryDocStorage::new());
+    let (relay, url) = start(RelayConfig::default().with_storage(storage.clone())).await;
+
+    let alice = Client::join(&url, "alice").await;
+    let bob = Client::join(&url, "bob").await;
+    wait_for_peers(&alice.transport, 1).await;
+    wait_for_peers(&bob.transport, 1).await;
+
+    alice.notes.write().insert(0, "Hello");
+    bob.notes.write().insert(0, "World");
+    alice.session.publish().await.unwrap();
+    bob.session.publish().await.unwrap();
+    eventually("convergence", || {
+        alice.text().len() == 10 && alice.text() == bob.text()
+    })
+    .await;
+    let text = alice.text();
+
+    alice.session.publish_snapshots().await.unwrap();
+    bob.session.publish_snapshots().await.unwrap();
+    eventually("the snapshot", || {
+        storage.documents(SESSION).unwrap() == ["notes"]
+    })
+    .await;
+
+    bob.kill();
+    eventually("bob to be gone", || relay.members(SESSION).len() == 1).await;
+    wait_for_peers(&alice.transport, 0).await;
+
+    let carol = Client::join(&url, "carol").await;
+    eventually("carol to catch up", || carol.text() == text).await;
+    assert_eq!(relay.members(SESSION).len(), 2);
+
+    // Nobody left in the session; the snapshot outlives it
+    alice.transport.close().await.unwrap();
+    carol.kill();
+    eventually("an empty relay", || relay.session_count() == 0).await;
+
+    let dave = Client::join(&url, "dave").await;
+    eventually("dave to catch up", || dave.text() == text).await;
+}
+
+#[tokio::test]
+async fn test_without_storage_nothing_is_cached() {
+    let (relay, url) = start(RelayConfig::default()).await;
+
+    let alice = Client::join(&url, "alice").await;
+    eventually("alice to join", || relay.members(SESSION).len() == 1).await;
+    alice.notes.write().insert(0, "Hello");
+    alice.session.publish_snapshots().await.unwrap();
+    alice.transport.close().await.unwrap();
+    eventually("an empty relay", || relay.session_count() == 0).await;
+
+    let bob = Client::join(&url, "bob").await;
+    eventually("bob to join", || relay.members(SESSION).len() == 1).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(bob.text(), "");
+}
+
+fn presence(n: usize) -> Message {
+    Message::Presence {
+        user_id: "u".to_string(),
+        document_id: "notes".to_string(),
+        cursor_pos: Some(n),
+    }
+}
+
+fn update(n: u64) -> Message {
+    Message::Update {
+        document_id: "notes".to_string(),
+        delta: vec![n as u8],
+        version: n,
+    }
+}
+
+/// Receive until `updates` updates arrived and count the presence messages
+/// that came with them.
+async fn receive(inbox: &mut Inbox, updates: usize) -> usize {
+    let (mut received, mut presence) = (0, 0);
+    loop {
+        let message = if received < updates {
+            let next = tokio::time::timeout(Duration::from_secs(5), inbox.recv());
+            next.await.expect("an update went missing").unwrap().1
+        } else if let Ok((_, message)) = inbox.try_recv() {
+            message
+        } else {
+            return presence;
+        };
+        match message {
+            Message::Update { .. } => received += 1,
+            Message::Presence { .. } => presence += 1,
+            _ => {}
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_excess_presence_is_dropped() {
+    const UPDATES: u64 = 20;
+    let config = RelayConfig::default()
+        .with_connection_rate(RateLimit::new(5, 0.0))
+        .with_session_rate(RateLimit::new(8, 0.0));
+    let (relay, url) = start(config).await;
+    let admin = serve(admin_router(relay.clone())).await;
+
+    let alice = connect(&url, "alice").await;
+    let bob = connect(&url, "bob").await;
+    let mut alice_inbox = alice.subscribe();
+    let mut bob_inbox = bob.subscribe();
+    wait_for_peers(&alice, 1).await;
+    wait_for_peers(&bob, 1).await;
+
+    // Each sender gets its 5 from a session budget of 8
+    for (sender, receiver, expected) in [(&alice, &mut bob_inbox, 5), (&bob, &mut alice_inbox, 3)] {
+        for n in 0..UPDATES {
+            for cursor in 0..3 {
+                sender.broadcast(presence(cursor)).await.unwrap();
+            }
+            sender.broadcast(update(n)).await.unwrap();
+        }
+        assert_eq!(receive(receiver, UPDATES as usize).await, expected);
+    }
+
+    let metrics = get_metrics(&admin).await;
+    for line in [
+        "carnelia_relay_sessions 1",
+        "carnelia_relay_connections 2",
+        "carnelia_relay_messages_dropped_total 112",
+        "carnelia_relay_members_evicted_total 0",
+    ] {
+        assert!(metrics.lines().any(|l| l == line), "{}\n{}", line, metrics);
+    }
+    assert!(metrics.contains("# TYPE carnelia_relay_bytes_relayed_total counter"));
+}
+
+#[tokio::test]
+async fn test_member_with_full_queue_is_disconnected() {
+    let (relay, url) = start(RelayConfig::default().with_queue(1)).await;
+    let admin = serve(admin_router(relay.clone())).await;
+
+    let alice = connect(&url, "alice").await;
+    let _alice_inbox = alice.subscribe();
+    // Bob's inbox holds one update and is never read, so the relay's queue
+    // for him fills up
+    let capacity = ChannelCapacity {
+        document: 1,
+        ..Default::default()
+    };
+    let bob = Arc::new(
+        WebSocketTransport::connect_with_capacity(&url, PeerId::new("bob"), SESSION, capacity)
+            .await
+            .unwrap(),
+    );
+    let mut bob_inbox = bob.subscribe();
+    wait_for_peers(&alice, 1).await;
+    wait_for_peers(&bob, 1).await;
+
+    let delta = vec![0; 64 * 1024];
+    for n in 0..200 {
+        let message = Message::Update {
+            document_id: "notes".to_string(),
+            delta: delta.clone(),
+            version: n,
+        };
+        alice.broadcast(message).await.unwrap();
+        if relay.members(SESSION).len() == 1 {
+            break;
+        }
+    }
+    eventually("bob to be evicted", || {
+        relay.members(SESSION) == [PeerId::new("alice")]
+    })
+    .await;
+    wait_for_peers(&alice, 0).await;
+
+    // The relay closed bob's connection: his inbox ends once drained
+    let drained = async { while bob_inbox.recv().await.is_some() {} };
+    tokio::time::timeout(Duration::from_secs(5), drained)
+        .await
+        .expect("bob's connection stayed open");
+
+    let metrics = get_metrics(&admin).await;
+    for line in [
+        "carnelia_relay_connections 1",
+        "carnelia_relay_members_evicted_total 1",
+    ] {
+        assert!(metrics.lines().any(|l| l == line), "{}\n{}", line, metrics);
+    }
+}
@@ -0,0 +1,299 @@
+//! # Carnelia Todo
+//!
+//! A collaborative todo list built on the MDCS SDK.
+//!
+//! ## Data model (JsonDoc)
+//!
+//! ```text
+//! path: order               →  array of item ids (RGA list, display order)
+//! path: items.<id>.title    →  JsonValue::String   (LWW)
+//! path: items.<id>.done     →  JsonValue::Bool     (LWW)
+//! path: items.<id>.deleted  →  JsonValue::Bool     (tombstone)
+//! ```
+//!
+//! Reordering moves an id within `order` with `array_move`, so the id keeps
+//! its identity: two replicas moving the same item concurrently settle on
+//! one position instead of duplicating it.
+//!
+//! ## Sync
+//!
+//! Each [`TodoPeer`] is an SDK [`Client`] over a `MemoryTransport`, with
+//! one [`Session`] holding the list's `JsonDoc`. Edits travel as deltas
+//! that peers acknowledge, and a peer cut off gets them queued until it is
+//! back. Who is editing which item travels as awareness updates.
+//! [`TodoPeer::publish`] sends local edits and presence;
+//! [`TodoPeer::receive`] hands whatever has arrived to the client.
+
+use std::sync::Arc;
+
+use mdcs_sdk::document::JsonDoc;
+use mdcs_sdk::presence::Awareness;
+use mdcs_sdk::{
+    Client, ClientConfig, Inbox, JsonValue, MemoryTransport, NetworkTransport, PeerId, SdkError,
+    Session,
+};
+use parking_lot::RwLock;
+
+/// Session the peers share.
+pub const SESSION_ID: &str = "todo";
+
+/// Document ID of the todo list.
+pub const DOC_ID: &str = "todo";
+
+/// Presence state key naming the item a user is editing.
+const EDITING_KEY: &str = "editing";
+
+/// A todo item as shown to the user.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TodoItem {
+    pub id: String,
+    pub title: String,
+    pub done: bool,
+}
+
+// ─── TodoList: the replicated list ─────────────────────────────────────────
+
+/// A todo list stored in a session's `JsonDoc`.
+pub struct TodoList {
+    replica_id: String,
+    doc: Arc<RwLock<JsonDoc>>,
+    next_id: u64,
+}
+
+impl TodoList {
+    /// Wrap a document that has not been initialized.
+    ///
+    /// Exactly one replica should call [`init`](Self::init); the others
+    /// receive the list by syncing.
+    pub fn new(replica_id: &str, doc: Arc<RwLock<JsonDoc>>) -> Self {
+        Self {
+            replica_id: replica_id.to_string(),
+            doc,
+            next_id: 0,
+        }
+    }
+
+    /// Create the shared `order` array and `items` object.
+    ///
+    /// Containers created concurrently on two replicas do not merge, so
+    /// they are created once here rather than lazily by [`add`](Self::add).
+    pub fn init(&mut self) {
+        let mut doc = self.doc.write();
+        doc.set_array("order");
+        doc.set_object("items");
+    }
+
+    /// Replica ID of this list.
+    pub fn replica_id(&self) -> &str {
+        &self.replica_id
+    }
+
+    /// Add an item at the end of the list and return its id.
+    pub fn add(&mut self, title: &str) -> String {
+        self.next_id += 1;
+        let id = format!("{}-{}", self.replica_id, self.next_id);
+        let mut doc = self.doc.write();
+        doc.set(
+            &format!("items.{}.title", id),
+            JsonValue::String(title.to_string()),
+        );
+        doc.set(&format!("items.{}.done", id), JsonValue::Bool(false));
+        doc.array_push("order", JsonValue::String(id.clone()));
+        id
+    }
+
+    /// Mark an item as done or not done.
+    pub fn complete(&mut self, id: &str, done: bool) {
+        self.doc
+            .write()
+            .set(&format!("items.{}.done", id), JsonValue::Bool(done));
+    }
+
+    /// Change an item's title.
+    pub fn rename(&mut self, id: &str, title: &str) {
+        self.doc.write().set(
+            &format!("items.{}.title", id),
+            JsonValue::String(title.to_string()),
+        );
+    }
+
+    /// Move an item to a position in the visible list.
+    ///
+    /// Does nothing if the item is no longer in the list.
+    pub fn move_item(&mut self, id: &str, to: usize) {
+        let raw = self.raw_order();
+        let Some(from) = raw.iter().position(|r| r == id) else {
+            return;
+        };
+
+        // Land before the item visible at `to` once this one is out of the way
+        let others: Vec<_> = raw.iter().filter(|r| *r != id).collect();
+        let visible: Vec<_> = self.items().into_iter().filter(|i| i.id != id).collect();
+        let index = match visible.get(to) {
+            Some(item) => others
+                .iter()
+                .position(|r| **r == item.id)
+                .unwrap_or(others.len()),
+            None => others.len(),
+        };
+        self.doc.write().array_move("order", from, index);
+    }
+
+    /// Delete an item.
+    pub fn delete(&mut self, id: &str) {
+        let mut doc = self.doc.write();
+        doc.set(&format!("items.{}.deleted", id), JsonValue::Bool(true));
+        let index = order_of(&doc).iter().position(|r| r == id);
+        if let Some(index) = index {
+            doc.array_remove("order", index);
+        }
+    }
+
+    /// Visible items in display order.
+    pub fn items(&self) -> Vec<TodoItem> {
+        let root = self.doc.read().root();
+
+        self.raw_order()
+            .into_iter()
+            .filter_map(|id| {
+                let item = root.get("items")?.get(&id)?;
+                if item.get("deleted").and_then(|v| v.as_bool()) == Some(true) {
+                    return None;
+                }
+                Some(TodoItem {
+                    title: item.get("title")?.as_str()?.to_string(),
+                    done: item.get("done").and_then(|v| v.as_bool()).unwrap_or(false),
+                    id,
+                })
+            })
+            .collect()
+    }
+
+    /// The `order` array including deleted items.
+    fn raw_order(&self) -> Vec<String> {
+        order_of(&self.doc.read())
+    }
+}
+
+/// The ids in a document's `order` array.
+fn order_of(doc: &JsonDoc) -> Vec<String> {
+    match doc.root().get("order").and_then(|o| o.as_array()) {
+        Some(values) => values
+            .iter()
+            .filter_map(|v| v.as_str().map(String::from))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+// ─── TodoPeer: list + presence + transport ─────────────────────────────────
+
+/// A user with a todo list, presence, and a network connection.
+pub struct TodoPeer {
+    pub list: TodoList,
+    client: Client<MemoryTransport>,
+    session: Arc<Session<MemoryTransport>>,
+    inbox: Inbox,
+}
+
+impl TodoPeer {
+    /// Create a peer with its own transport and session.
+    pub fn new(user_id: &str, user_name: &str) -> Self {
+        let peer_id = PeerId::new(user_id);
+        let transport = Arc::new(MemoryTransport::new(peer_id.clone()));
+        let inbox = transport.subscribe();
+        let config = ClientConfig {
+            user_name: user_name.to_string(),
+            ..Default::default()
+        };
+        let client = Client::new(peer_id, transport, config);
+        let session = client.create_session(SESSION_ID);
+        Self {
+            list: TodoList::new(user_id, session.open_json_doc(DOC_ID)),
+            client,
+            session,
+            inbox,
+        }
+    }
+
+    /// Peer ID on the network.
+    pub fn peer_id(&self) -> &PeerId {
+        self.client.peer_id()
+    }
+
+    /// The session the list is synced in.
+    pub fn session(&self) -> &Arc<Session<MemoryTransport>> {
+        &self.session
+    }
+
+    /// The local user's presence and what is known of the others'.
+    pub fn awareness(&self) -> &Arc<Awareness> {
+        self.session.awareness()
+    }
+
+    /// Connect two peers.
+    pub fn connect(&self, other: &TodoPeer) {
+        self.client.transport().connect_to(other.client.transport());
+    }
+
+    /// Cut the connection between two peers.
+    pub async fn partition(&self, other: &TodoPeer) {
+        let _ = self.client.disconnect_peer(other.peer_id()).await;
+        let _ = other.client.disconnect_peer(self.peer_id()).await;
+    }
+
+    /// Mark an item as being edited by the local user.
+    pub fn start_editing(&self, id: &str) {
+        self.awareness().set_state(EDITING_KEY, id);
+    }
+
+    /// Clear the local user's editing marker.
+    pub fn stop_editing(&self) {
+        self.awareness().set_state(EDITING_KEY, "");
+    }
+
+    /// Other users and the item each is editing.
+    pub fn editors(&self) -> Vec<(String, String)> {
+        let awareness = self.awareness();
+        let mut editors: Vec<_> = awareness
+            .get_users()
+            .into_iter()
+            .filter(|u| u.user_id != awareness.local_user_id())
+            .filter_map(|u| {
+                let item = u.state.get(EDITING_KEY).filter(|s| !s.is_empty())?;
+                Some((u.name.clone(), item.clone()))
+            })
+            .collect();
+        editors.sort();
+        editors
+    }
+
+    /// Send pending list edits and presence to connected peers.
+    ///
+    /// Peers that were cut off get the edits queued for them while away.
+    pub async fn publish(&self) -> Result<(), SdkError> {
+        self.session.publish().await?;
+        self.session.publish_presence().await
+    }
+
+    /// Handle every message that has arrived: deltas are applied and
+    /// acknowledged, acks recorded and presence merged. Returns how many
+    /// were handled.
+    pub async fn receive(&mut self) -> Result<usize, SdkError> {
+        let mut handled = 0;
+        while let Ok((from, message)) = self.inbox.try_recv() {
+            self.client.handle_message(&from, &message).await?;
+            handled += 1;
+        }
+        Ok(handled)
+    }
+}
+
+/// Exchange edits and presence between two peers in both directions, until
+/// every delta has been acknowledged or nothing more arrives.
+pub async fn sync_pair(a: &mut TodoPeer, b: &mut TodoPeer) -> Result<(), SdkError> {
+    a.publish().await?;
+    b.publish().await?;
+    while a.receive().await? + b.receive().await? > 0 {}
+    Ok(())
+}
@@ -0,0 +1,193 @@
+//! # Carnelia Todo
+//!
+//! A CLI walkthrough of two users sharing a todo list over an in-memory
+//! network. Every scenario ends with a convergence check.
+
+use carnelia_todo::{sync_pair, TodoPeer};
+use clap::{Parser, Subcommand};
+use colored::*;
+
+// ─── CLI ───────────────────────────────────────────────────────────────────
+
+#[derive(Parser)]
+#[command(name = "carnelia-todo")]
+#[command(about = "Collaborative todo list built on the MDCS SDK")]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Two users add, complete, reorder and delete items concurrently
+    Demo,
+    /// Network partition: split, edit independently, heal, converge
+    Partition,
+}
+
+// ─── Pretty printing ──────────────────────────────────────────────────────
+
+fn header(text: &str) {
+    let bar = "═".repeat(60);
+    println!("\n{}", bar.bright_cyan());
+    println!("  {}", text.bold().bright_white());
+    println!("{}", bar.bright_cyan());
+}
+
+fn section(text: &str) {
+    println!("\n{} {}", "▸".bright_yellow(), text.bold());
+}
+
+fn step(text: &str) {
+    println!("  {} {}", "•".bright_green(), text);
+}
+
+fn show_peer(peer: &TodoPeer) {
+    let border = "─".repeat(44);
+    println!("  ┌{}┐", border);
+    println!(
+        "  │ {:^42} │",
+        format!("List: {}", peer.list.replica_id())
+            .bright_yellow()
+            .to_string()
+    );
+    println!("  ├{}┤", border);
+
+    let items = peer.list.items();
+    if items.is_empty() {
+        println!("  │ {:^42} │", "(empty)".dimmed().to_string());
+    }
+    let editors = peer.editors();
+    for item in &items {
+        let check = if item.done { "[x]" } else { "[ ]" };
+        let mut line = format!("{} {}", check, item.title);
+        for (name, _) in editors.iter().filter(|(_, id)| id == &item.id) {
+            line.push_str(&format!("  ✎ {}", name));
+        }
+        println!("  │ {:<42} │", line);
+    }
+    println!("  └{}┘", border);
+}
+
+fn convergence_result(a: &TodoPeer, b: &TodoPeer) {
+    if a.list.items() == b.list.items() {
+        println!(
+            "\n  {} {}",
+            "✓".bright_green().bold(),
+            "LISTS CONVERGED — both users see the same items!"
+                .bright_green()
+                .bold()
+        );
+    } else {
+        println!(
+            "\n  {} {}",
+            "✗".bright_red().bold(),
+            "DIVERGENCE DETECTED — lists differ!".bright_red().bold()
+        );
+    }
+}
+
+async fn sync(alice: &mut TodoPeer, bob: &mut TodoPeer) {
+    sync_pair(alice, bob).await.expect("sync failed");
+    println!(
+        "  {} {} {}",
+        "alice".bright_magenta(),
+        "◀──sync──▶".bright_cyan(),
+        "bob".bright_magenta()
+    );
+}
+
+async fn connected_pair() -> (TodoPeer, TodoPeer) {
+    let mut alice = TodoPeer::new("alice", "Alice");
+    let mut bob = TodoPeer::new("bob", "Bob");
+    alice.connect(&bob);
+    alice.list.init();
+    sync_pair(&mut alice, &mut bob).await.expect("sync failed");
+    (alice, bob)
+}
+
+// ─── Demo ──────────────────────────────────────────────────────────────────
+
+async fn run_demo() {
+    header("DEMO — Shared Todo List");
+    let (mut alice, mut bob) = connected_pair().await;
+
+    section("Phase 1: Both users add items");
+    let milk = alice.list.add("milk");
+    step("alice: add \"milk\"");
+    let eggs = bob.list.add("eggs");
+    step("bob:   add \"eggs\"");
+    sync(&mut alice, &mut bob).await;
+    show_peer(&alice);
+
+    section("Phase 2: Concurrent edits with presence");
+    let bread = alice.list.add("bread");
+    step("alice: add \"bread\"");
+    bob.start_editing(&milk);
+    bob.list.complete(&milk, true);
+    step("bob:   editing \"milk\", marks it done");
+    sync(&mut alice, &mut bob).await;
+    show_peer(&alice);
+
+    section("Phase 3: Reorder and delete");
+    bob.stop_editing();
+    alice.list.move_item(&bread, 0);
+    step("alice: move \"bread\" to the top");
+    bob.list.add("coffee");
+    step("bob:   add \"coffee\"");
+    bob.list.delete(&eggs);
+    step("bob:   delete \"eggs\"");
+    sync(&mut alice, &mut bob).await;
+
+    section("Final state");
+    show_peer(&alice);
+    show_peer(&bob);
+    convergence_result(&alice, &bob);
+}
+
+// ─── Partition ─────────────────────────────────────────────────────────────
+
+async fn run_partition() {
+    header("PARTITION — Offline Edits and Healing");
+    let (mut alice, mut bob) = connected_pair().await;
+
+    section("Phase 1: Shared starting point");
+    let report = alice.list.add("write report");
+    alice.list.add("book flights");
+    sync(&mut alice, &mut bob).await;
+    show_peer(&bob);
+
+    section("Phase 2: Network partition");
+    alice.partition(&bob).await;
+    step("link between alice and bob is down");
+    alice.list.rename(&report, "write Q3 report");
+    step("alice: rename \"write report\" → \"write Q3 report\"");
+    alice.list.add("pack bags");
+    step("alice: add \"pack bags\"");
+    bob.list.complete(&report, true);
+    step("bob:   complete \"write report\"");
+    bob.list.move_item(&report, 1);
+    step("bob:   move \"write report\" below \"book flights\"");
+    sync_pair(&mut alice, &mut bob).await.expect("sync failed");
+    show_peer(&alice);
+    show_peer(&bob);
+
+    section("Phase 3: Heal and sync");
+    alice.connect(&bob);
+    step("link restored");
+    sync(&mut alice, &mut bob).await;
+    show_peer(&alice);
+    show_peer(&bob);
+    convergence_result(&alice, &bob);
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Demo => run_demo().await,
+        Commands::Partition => run_partition().await,
+    }
+}
@@ -0,0 +1,155 @@
+//! Scripted two-client scenarios driving the JSON array + sync stack.
+//!
+//! Every sync goes through the SDK: deltas are acknowledged, so once a
+//! sync is done neither side has anything left unacknowledged.
+
+use carnelia_todo::{sync_pair, TodoItem, TodoPeer};
+
+fn titles(items: &[TodoItem]) -> Vec<&str> {
+    items.iter().map(|i| i.title.as_str()).collect()
+}
+
+async fn connected_pair() -> (TodoPeer, TodoPeer) {
+    let mut alice = TodoPeer::new("alice", "Alice");
+    let mut bob = TodoPeer::new("bob", "Bob");
+    alice.connect(&bob);
+    alice.list.init();
+    sync_pair(&mut alice, &mut bob).await.unwrap();
+    (alice, bob)
+}
+
+fn assert_all_acked(peers: [&TodoPeer; 2]) {
+    for peer in peers {
+        assert!(peer.session().sync().unacked().is_empty());
+    }
+}
+
+#[tokio::test]
+async fn scripted_interleaving_converges() {
+    let (mut alice, mut bob) = connected_pair().await;
+
+    let milk = alice.list.add("milk");
+    let eggs = bob.list.add("eggs");
+    sync_pair(&mut alice, &mut bob).await.unwrap();
+
+    let bread = alice.list.add("bread");
+    bob.list.complete(&milk, true);
+    sync_pair(&mut alice, &mut bob).await.unwrap();
+
+    alice.list.move_item(&bread, 0);
+    bob.list.add("coffee");
+    bob.list.delete(&eggs);
+    sync_pair(&mut alice, &mut bob).await.unwrap();
+
+    let final_a = alice.list.items();
+    assert_eq!(final_a, bob.list.items());
+    assert_eq!(titles(&final_a), ["bread", "milk", "coffee"]);
+    assert!(final_a.iter().any(|i| i.id == milk && i.done));
+    assert_all_acked([&alice, &bob]);
+}
+
+#[tokio::test]
+async fn concurrent_moves_of_same_item_keep_one_copy() {
+    let (mut alice, mut bob) = connected_pair().await;
+
+    let a = alice.list.add("a");
+    alice.list.add("b");
+    alice.list.add("c");
+    alice.list.add("d");
+    sync_pair(&mut alice, &mut bob).await.unwrap();
+
+    alice.list.move_item(&a, 3);
+    bob.list.move_item(&a, 1);
+    assert_eq!(titles(&alice.list.items()), ["b", "c", "d", "a"]);
+    assert_eq!(titles(&bob.list.items()), ["b", "a", "c", "d"]);
+    sync_pair(&mut alice, &mut bob).await.unwrap();
+
+    let final_a = alice.list.items();
+    assert_eq!(final_a, bob.list.items());
+    assert_eq!(final_a.len(), 4);
+    assert_eq!(final_a.iter().filter(|i| i.id == a).count(), 1);
+
+    // Moving it again from either side still leaves one copy
+    bob.list.move_item(&a, 0);
+    sync_pair(&mut alice, &mut bob).await.unwrap();
+    assert_eq!(titles(&alice.list.items()), ["a", "b", "c", "d"]);
+    assert_eq!(alice.list.items(), bob.list.items());
+}
+
+#[tokio::test]
+async fn concurrent_edits_of_same_item_converge() {
+    let (mut alice, mut bob) = connected_pair().await;
+
+    let task = alice.list.add("write report");
+    alice.list.add("review");
+    sync_pair(&mut alice, &mut bob).await.unwrap();
+
+    // Both rename, toggle and move the same item without syncing
+    alice.list.rename(&task, "write Q3 report");
+    bob.list.rename(&task, "write annual report");
+    alice.list.complete(&task, true);
+    bob.list.complete(&task, false);
+    alice.list.move_item(&task, 1);
+    bob.list.move_item(&task, 1);
+    sync_pair(&mut alice, &mut bob).await.unwrap();
+
+    let final_a = alice.list.items();
+    assert_eq!(final_a, bob.list.items());
+    // Concurrent moves must not duplicate the item
+    assert_eq!(final_a.len(), 2);
+    assert_eq!(final_a.iter().filter(|i| i.id == task).count(), 1);
+}
+
+#[tokio::test]
+async fn concurrent_move_and_delete_converge() {
+    let (mut alice, mut bob) = connected_pair().await;
+
+    let a = alice.list.add("a");
+    alice.list.add("b");
+    alice.list.add("c");
+    sync_pair(&mut alice, &mut bob).await.unwrap();
+
+    alice.list.move_item(&a, 2);
+    bob.list.delete(&a);
+    sync_pair(&mut alice, &mut bob).await.unwrap();
+
+    assert_eq!(alice.list.items(), bob.list.items());
+    assert_eq!(titles(&alice.list.items()), ["b", "c"]);
+}
+
+#[tokio::test]
+async fn partition_and_heal_converge() {
+    let (mut alice, mut bob) = connected_pair().await;
+
+    let shared = alice.list.add("shared");
+    alice.list.rename(&shared, "shared task");
+    sync_pair(&mut alice, &mut bob).await.unwrap();
+
+    alice.partition(&bob).await;
+    alice.list.add("from alice");
+    bob.list.add("from bob");
+    bob.list.complete(&shared, true);
+    sync_pair(&mut alice, &mut bob).await.unwrap();
+    assert_ne!(alice.list.items(), bob.list.items());
+
+    alice.connect(&bob);
+    sync_pair(&mut alice, &mut bob).await.unwrap();
+    assert_eq!(alice.list.items(), bob.list.items());
+    assert_eq!(alice.list.items().len(), 3);
+    assert!(alice.list.items().iter().any(|i| i.id == shared && i.done));
+    assert_all_acked([&alice, &bob]);
+}
+
+#[tokio::test]
+async fn presence_shows_editors() {
+    let (mut alice, mut bob) = connected_pair().await;
+
+    let item = alice.list.add("plan trip");
+    alice.start_editing(&item);
+    sync_pair(&mut alice, &mut bob).await.unwrap();
+    assert_eq!(bob.editors(), vec![("Alice".to_string(), item.clone())]);
+
+    alice.stop_editing();
+    sync_pair(&mut alice, &mut bob).await.unwrap();
+    assert!(bob.editors().is_empty());
+}
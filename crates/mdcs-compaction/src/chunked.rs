@@ -0,0 +1,480 @@
+//! Chunked snapshots for transferring large states.
+//!
+//! A [`SnapshotWriter`] splits a snapshot's state into fixed-size chunks
+//! and describes them in a [`SnapshotManifest`]: every other field of the
+//! snapshot plus the hash of each chunk. A [`SnapshotReader`] collects the
+//! chunks in any order and checks each against the manifest as it arrives,
+//! so an interrupted transfer resumes by fetching only the
+//! [`missing`](SnapshotReader::missing) ones. Once every chunk is in,
+//! [`assemble`](SnapshotReader::assemble) rebuilds the snapshot and checks
+//! its content hash like that of any other snapshot.
+
+use crate::snapshot::{Snapshot, SnapshotError, SnapshotSignature, SNAPSHOT_VERSION};
+use crate::version_vector::VersionVector;
+use mdcs_merkle::{Hash, Hasher};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Default chunk size, in bytes.
+pub const DEFAULT_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Everything in a snapshot except its state, plus the hash of each chunk
+/// of the state.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    /// ID of the snapshot the chunks rebuild.
+    pub snapshot_id: Hash,
+
+    /// Format version of the snapshot.
+    pub version: u8,
+
+    /// The version vector at the time of the snapshot.
+    pub version_vector: VersionVector,
+
+    /// The CIDs of DAG nodes that the snapshot supersedes.
+    pub superseded_roots: Vec<Hash>,
+
+    /// Timestamp when the snapshot was created.
+    pub created_at: u64,
+
+    /// The replica that created the snapshot.
+    pub creator: String,
+
+    /// Metadata about the snapshot.
+    pub metadata: HashMap<String, String>,
+
+    /// Content hash of the whole snapshot.
+    pub content_hash: Hash,
+
+    /// Signature over `content_hash`, if the snapshot was signed.
+    #[serde(default)]
+    pub signature: Option<SnapshotSignature>,
+
+    /// Size of every chunk but the last, in bytes.
+    pub chunk_size: usize,
+
+    /// Size of the whole state, in bytes.
+    pub state_size: usize,
+
+    /// Hash of each chunk's data, by index.
+    pub chunk_hashes: Vec<Hash>,
+}
+
+impl SnapshotManifest {
+    /// Number of chunks the state is split into.
+    pub fn chunk_count(&self) -> usize {
+        self.chunk_hashes.len()
+    }
+
+    /// Check that the chunk list fits the state size and that every chunk
+    /// has a `u32` index.
+    ///
+    /// The sizes are only the sender's word: the state is assembled from
+    /// the chunks' own data, not preallocated from `state_size`.
+    fn validate(&self) -> Result<(), SnapshotError> {
+        if self.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::VersionMismatch {
+                expected: SNAPSHOT_VERSION,
+                actual: self.version,
+            });
+        }
+        if self.chunk_size == 0 {
+            return Err(SnapshotError::InvalidData(
+                "manifest has a chunk size of zero".to_string(),
+            ));
+        }
+        if self.chunk_count() > u32::MAX as usize {
+            return Err(SnapshotError::InvalidData(format!(
+                "manifest lists {} chunks, more than chunk indices can address",
+                self.chunk_count()
+            )));
+        }
+        let expected = self.state_size.div_ceil(self.chunk_size);
+        if self.chunk_hashes.len() != expected {
+            return Err(SnapshotError::InvalidData(format!(
+                "manifest lists {} chunks for {} bytes in chunks of {}",
+                self.chunk_hashes.len(),
+                self.state_size,
+                self.chunk_size
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// One piece of a snapshot's state.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotChunk {
+    /// ID of the snapshot this chunk belongs to.
+    pub snapshot_id: Hash,
+
+    /// Position of the chunk in the state, from zero.
+    pub index: u32,
+
+    /// The chunk's bytes.
+    pub data: Vec<u8>,
+
+    /// Hash of `data`.
+    pub hash: Hash,
+}
+
+impl SnapshotChunk {
+    /// Check that the data still matches the chunk's own hash.
+    pub fn verify(&self) -> Result<(), SnapshotError> {
+        let actual = Hasher::hash(&self.data);
+        if actual == self.hash {
+            Ok(())
+        } else {
+            Err(SnapshotError::CorruptChunk {
+                index: self.index,
+                expected: self.hash,
+                actual,
+            })
+        }
+    }
+}
+
+/// A snapshot split into a manifest and all of its chunks.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChunkedSnapshot {
+    pub manifest: SnapshotManifest,
+    pub chunks: Vec<SnapshotChunk>,
+}
+
+impl ChunkedSnapshot {
+    /// Split a snapshot into chunks of `chunk_size` bytes.
+    pub fn new(snapshot: &Snapshot, chunk_size: usize) -> Self {
+        SnapshotWriter::new(snapshot, chunk_size).finish()
+    }
+
+    /// Rebuild the snapshot, checking every chunk against the manifest.
+    pub fn assemble(self) -> Result<Snapshot, SnapshotError> {
+        let mut reader = SnapshotReader::new(self.manifest)?;
+        for chunk in self.chunks {
+            reader.receive(chunk)?;
+        }
+        reader.assemble()
+    }
+}
+
+/// Splits a snapshot's state into chunks.
+///
+/// Chunks are cut from the snapshot when asked for, so a sender can stream
+/// them one at a time and send any of them again.
+pub struct SnapshotWriter<'a> {
+    snapshot: &'a Snapshot,
+    chunk_size: usize,
+}
+
+impl<'a> SnapshotWriter<'a> {
+    /// Split `snapshot` into chunks of `chunk_size` bytes; the last chunk
+    /// may be shorter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    pub fn new(snapshot: &'a Snapshot, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "snapshot chunk size must be positive");
+        Self {
+            snapshot,
+            chunk_size,
+        }
+    }
+
+    /// Number of chunks the state is split into.
+    pub fn chunk_count(&self) -> usize {
+        self.snapshot.state_data.len().div_ceil(self.chunk_size)
+    }
+
+    /// The manifest a reader needs to check and assemble the chunks.
+    pub fn manifest(&self) -> SnapshotManifest {
+        let snapshot = self.snapshot;
+        SnapshotManifest {
+            snapshot_id: snapshot.id,
+            version: snapshot.version,
+            version_vector: snapshot.version_vector.clone(),
+            superseded_roots: snapshot.superseded_roots.clone(),
+            created_at: snapshot.created_at,
+            creator: snapshot.creator.clone(),
+            metadata: snapshot.metadata.clone(),
+            content_hash: snapshot.content_hash,
+            signature: snapshot.signature.clone(),
+            chunk_size: self.chunk_size,
+            state_size: snapshot.state_data.len(),
+            chunk_hashes: snapshot
+                .state_data
+                .chunks(self.chunk_size)
+                .map(Hasher::hash)
+                .collect(),
+        }
+    }
+
+    /// The chunk at `index`, if the state has that many.
+    pub fn chunk(&self, index: u32) -> Option<SnapshotChunk> {
+        let data = self
+            .snapshot
+            .state_data
+            .chunks(self.chunk_size)
+            .nth(index as usize)?
+            .to_vec();
+        Some(SnapshotChunk {
+            snapshot_id: self.snapshot.id,
+            index,
+            hash: Hasher::hash(&data),
+            data,
+        })
+    }
+
+    /// Every chunk, in order.
+    pub fn chunks(&self) -> impl Iterator<Item = SnapshotChunk> + '_ {
+        (0..self.chunk_count() as u32).filter_map(|index| self.chunk(index))
+    }
+
+    /// The manifest together with every chunk.
+    pub fn finish(&self) -> ChunkedSnapshot {
+        ChunkedSnapshot {
+            manifest: self.manifest(),
+            chunks: self.chunks().collect(),
+        }
+    }
+}
+
+/// Collects the chunks of a snapshot and assembles it.
+///
+/// A reader can be serialized with the chunks it has so far, to resume the
+/// transfer later.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SnapshotReader {
+    manifest: SnapshotManifest,
+    received: BTreeMap<u32, Vec<u8>>,
+}
+
+impl SnapshotReader {
+    /// Start collecting the chunks a manifest lists.
+    pub fn new(manifest: SnapshotManifest) -> Result<Self, SnapshotError> {
+        manifest.validate()?;
+        Ok(Self {
+            manifest,
+            received: BTreeMap::new(),
+        })
+    }
+
+    /// Get the manifest.
+    pub fn manifest(&self) -> &SnapshotManifest {
+        &self.manifest
+    }
+
+    /// Check a chunk against the manifest and keep it.
+    ///
+    /// Returns `false` if the chunk was already received. A chunk whose
+    /// data doesn't match the manifest is rejected with
+    /// [`SnapshotError::CorruptChunk`] and can be received again.
+    pub fn receive(&mut self, chunk: SnapshotChunk) -> Result<bool, SnapshotError> {
+        if chunk.snapshot_id != self.manifest.snapshot_id {
+            return Err(SnapshotError::InvalidData(format!(
+                "chunk of snapshot {} given to snapshot {}",
+                chunk.snapshot_id.short(),
+                self.manifest.snapshot_id.short()
+            )));
+        }
+        let expected = *self.manifest.chunk_hashes.get(chunk.index as usize).ok_or(
+            SnapshotError::ChunkOutOfRange {
+                index: chunk.index,
+                count: self.manifest.chunk_count(),
+            },
+        )?;
+        let actual = Hasher::hash(&chunk.data);
+        if actual != expected {
+            return Err(SnapshotError::CorruptChunk {
+                index: chunk.index,
+                expected,
+                actual,
+            });
+        }
+        Ok(self.received.insert(chunk.index, chunk.data).is_none())
+    }
+
+    /// Indices of the chunks not received yet, in order.
+    pub fn missing(&self) -> Vec<u32> {
+        (0..=u32::MAX)
+            .take(self.manifest.chunk_count())
+            .filter(|index| !self.received.contains_key(index))
+            .collect()
+    }
+
+    /// Check if every chunk has been received.
+    pub fn is_complete(&self) -> bool {
+        self.received.len() == self.manifest.chunk_count()
+    }
+
+    /// Rebuild the snapshot from the received chunks.
+    ///
+    /// Fails with [`SnapshotError::MissingChunks`] until every chunk is in,
+    /// and with [`SnapshotError::IntegrityFailure`] if the assembled
+    /// snapshot doesn't match the manifest's content hash.
+    pub fn assemble(&self) -> Result<Snapshot, SnapshotError> {
+        let missing = self.missing();
+        if !missing.is_empty() {
+            return Err(SnapshotError::MissingChunks { missing });
+        }
+
+        let size: usize = self.received.values().map(Vec::len).sum();
+        if size != self.manifest.state_size {
+            return Err(SnapshotError::InvalidData(format!(
+                "chunks hold {} bytes of a {}-byte state",
+                size, self.manifest.state_size
+            )));
+        }
+        let mut state_data = Vec::with_capacity(size);
+        for data in self.received.values() {
+            state_data.extend_from_slice(data);
+        }
+        let manifest = &self.manifest;
+        let snapshot = Snapshot {
+            version: manifest.version,
+            id: manifest.snapshot_id,
+            version_vector: manifest.version_vector.clone(),
+            superseded_roots: manifest.superseded_roots.clone(),
+            state_data,
+            created_at: manifest.created_at,
+            creator: manifest.creator.clone(),
+            metadata: manifest.metadata.clone(),
+            content_hash: manifest.content_hash,
+            signature: manifest.signature.clone(),
+        };
+        snapshot.verify_integrity()?;
+        Ok(snapshot)
+    }
+}
+
+/// A snapshot as received from a peer, whole or in chunks.
+#[derive(Clone, Debug)]
+pub enum SnapshotForm {
+    Monolithic(Snapshot),
+    Chunked(ChunkedSnapshot),
+}
+
+impl SnapshotForm {
+    /// The whole snapshot, assembled and checked if it came in chunks.
+    pub fn into_snapshot(self) -> Result<Snapshot, SnapshotError> {
+        match self {
+            SnapshotForm::Monolithic(snapshot) => Ok(snapshot),
+            SnapshotForm::Chunked(chunked) => chunked.assemble(),
+        }
+    }
+}
+
+impl From<Snapshot> for SnapshotForm {
+    fn from(snapshot: Snapshot) -> Self {
+        SnapshotForm::Monolithic(snapshot)
+    }
+}
+
+impl From<ChunkedSnapshot> for SnapshotForm {
+    fn from(chunked: ChunkedSnapshot) -> Self {
+        SnapshotForm::Chunked(chunked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(state_data: Vec<u8>) -> Snapshot {
+        let vv = VersionVector::from_entries([("r1".to_string(), 10)]);
+        Snapshot::new(vv, vec![Hasher::hash(b"root")], state_data, "r1", 100)
+            .with_metadata("kind", "test")
+    }
+
+    #[test]
+    fn test_chunks_round_trip_in_any_order() {
+        let original = snapshot((0..=255).cycle().take(1000).collect());
+        let writer = SnapshotWriter::new(&original, 64);
+        assert_eq!(writer.chunk_count(), 16);
+
+        let mut chunks: Vec<_> = writer.chunks().collect();
+        assert_eq!(chunks.last().unwrap().data.len(), 1000 - 15 * 64);
+        chunks.reverse();
+
+        let mut reader = SnapshotReader::new(writer.manifest()).unwrap();
+        for chunk in chunks {
+            chunk.verify().unwrap();
+            assert!(reader.receive(chunk).unwrap());
+        }
+        assert!(reader.is_complete());
+
+        let assembled = reader.assemble().unwrap();
+        assert_eq!(assembled.id, original.id);
+        assert_eq!(assembled.state_data, original.state_data);
+        assert_eq!(assembled.metadata, original.metadata);
+        assert_eq!(assembled.superseded_roots, original.superseded_roots);
+    }
+
+    #[test]
+    fn test_empty_state_has_no_chunks() {
+        let original = snapshot(Vec::new());
+        let chunked = ChunkedSnapshot::new(&original, 64);
+        assert!(chunked.chunks.is_empty());
+
+        let assembled = chunked.assemble().unwrap();
+        assert!(assembled.state_data.is_empty());
+        assert_eq!(assembled.content_hash, original.content_hash);
+    }
+
+    #[test]
+    fn test_duplicate_and_foreign_chunks() {
+        let original = snapshot(vec![7; 100]);
+        let writer = SnapshotWriter::new(&original, 40);
+        let mut reader = SnapshotReader::new(writer.manifest()).unwrap();
+
+        assert!(reader.receive(writer.chunk(1).unwrap()).unwrap());
+        assert!(!reader.receive(writer.chunk(1).unwrap()).unwrap());
+        assert!(writer.chunk(3).is_none());
+
+        let mut stray = writer.chunk(0).unwrap();
+        stray.index = 3;
+        assert!(matches!(
+            reader.receive(stray),
+            Err(SnapshotError::ChunkOutOfRange { index: 3, count: 3 })
+        ));
+
+        let other = snapshot(vec![8; 100]);
+        let foreign = SnapshotWriter::new(&other, 40).chunk(0).unwrap();
+        assert!(matches!(
+            reader.receive(foreign),
+            Err(SnapshotError::InvalidData(_))
+        ));
+        assert_eq!(reader.missing(), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_inconsistent_manifest_rejected() {
+        let original = snapshot(vec![1; 100]);
+        let mut manifest = SnapshotWriter::new(&original, 40).manifest();
+        manifest.chunk_hashes.pop();
+        assert!(matches!(
+            SnapshotReader::new(manifest.clone()),
+            Err(SnapshotError::InvalidData(_))
+        ));
+
+        manifest.chunk_size = 0;
+        assert!(SnapshotReader::new(manifest).is_err());
+    }
+
+    #[test]
+    fn test_oversized_manifest_state_rejected() {
+        let original = snapshot(vec![1; 100]);
+        let writer = SnapshotWriter::new(&original, 100);
+        let mut manifest = writer.manifest();
+        // One chunk is consistent with any state size up to the chunk size
+        manifest.chunk_size = usize::MAX;
+        manifest.state_size = usize::MAX;
+
+        let mut reader = SnapshotReader::new(manifest).unwrap();
+        reader.receive(writer.chunk(0).unwrap()).unwrap();
+        assert!(matches!(
+            reader.assemble(),
+            Err(SnapshotError::InvalidData(_))
+        ));
+    }
+}
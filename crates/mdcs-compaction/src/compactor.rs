@@ -3,10 +3,14 @@
 //! The Compactor coordinates snapshotting, stability monitoring, and
 //! pruning to manage metadata growth over time.
 
+use crate::chunked::SnapshotForm;
 use crate::pruning::{PrunableStore, Pruner, PruningPolicy, PruningResult};
-use crate::snapshot::{Snapshot, SnapshotConfig, SnapshotManager};
-use crate::stability::{FrontierUpdate, StabilityConfig, StabilityMonitor};
+use crate::snapshot::{
+    Snapshot, SnapshotConfig, SnapshotError, SnapshotManager, SnapshotSigner, TrustSet,
+};
+use crate::stability::{FrontierUpdate, PeerLiveness, StabilityConfig, StabilityMonitor};
 use crate::version_vector::VersionVector;
+use mdcs_core::clock::SharedClock;
 use mdcs_merkle::{DAGStore, Hash};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -31,6 +35,9 @@ pub enum CompactionError {
 
     #[error("Verification failed: {0}")]
     VerificationFailed(String),
+
+    #[error("Snapshot rejected: {0}")]
+    Snapshot(#[from] SnapshotError),
 }
 
 /// Configuration for the compactor.
@@ -56,6 +63,18 @@ pub struct CompactionConfig {
 
     /// Whether to verify after compaction.
     pub verify_after_compaction: bool,
+
+    /// Whether snapshots created by `compact` are anchored in the DAG.
+    #[serde(default)]
+    pub anchor_snapshots: bool,
+
+    /// Milliseconds between rounds of a `CompactionScheduler`.
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+}
+
+fn default_interval() -> u64 {
+    30_000
 }
 
 /// Serializable version of SnapshotConfig.
@@ -96,6 +115,8 @@ pub struct StabilityConfigSerializable {
     pub max_frontier_age: u64,
     pub require_all_peers: bool,
     pub quorum_fraction: f64,
+    #[serde(default)]
+    pub peer_timeout: Option<u64>,
 }
 
 impl Default for StabilityConfigSerializable {
@@ -105,6 +126,7 @@ impl Default for StabilityConfigSerializable {
             max_frontier_age: 10000,
             require_all_peers: true,
             quorum_fraction: 0.67,
+            peer_timeout: None,
         }
     }
 }
@@ -116,6 +138,7 @@ impl From<StabilityConfigSerializable> for StabilityConfig {
             max_frontier_age: s.max_frontier_age,
             require_all_peers: s.require_all_peers,
             quorum_fraction: s.quorum_fraction,
+            peer_timeout: s.peer_timeout,
         }
     }
 }
@@ -129,6 +152,8 @@ impl Default for CompactionConfig {
             auto_compact: true,
             min_ops_for_compaction: 500,
             verify_after_compaction: true,
+            anchor_snapshots: false,
+            interval: default_interval(),
         }
     }
 }
@@ -177,6 +202,9 @@ pub struct Compactor {
 
     /// Current logical time.
     current_time: u64,
+
+    /// Clock that supplies the time instead of `current_time`, if set.
+    clock: Option<SharedClock>,
 }
 
 impl Compactor {
@@ -191,6 +219,7 @@ impl Compactor {
             config: CompactionConfig::default(),
             stats: CompactionStats::default(),
             current_time: 0,
+            clock: None,
             replica_id,
         }
     }
@@ -209,6 +238,7 @@ impl Compactor {
             config,
             stats: CompactionStats::default(),
             current_time: 0,
+            clock: None,
             replica_id,
         }
     }
@@ -258,30 +288,80 @@ impl Compactor {
         &self.stats
     }
 
+    /// Read the time from `clock` instead of [`set_time`](Self::set_time)
+    /// and the `time` passed to [`tick`](Self::tick).
+    pub fn with_clock(mut self, clock: impl Into<SharedClock>) -> Self {
+        self.clock = Some(clock.into());
+        self
+    }
+
+    /// Sign created snapshots with `signer`, and use it to verify snapshots
+    /// passed to [`bootstrap_from_snapshot_verified`](Self::bootstrap_from_snapshot_verified).
+    pub fn with_signer(mut self, signer: Box<dyn SnapshotSigner>) -> Self {
+        self.snapshots = std::mem::take(&mut self.snapshots).with_signer(signer);
+        self
+    }
+
     /// Update the current time.
     pub fn set_time(&mut self, time: u64) {
         self.current_time = time;
     }
 
+    /// Get the current time.
+    pub fn now(&self) -> u64 {
+        match &self.clock {
+            Some(clock) => clock.now_millis(),
+            None => self.current_time,
+        }
+    }
+
     /// Update local frontier (call after state changes).
     pub fn update_local_frontier(&mut self, vv: VersionVector, heads: Vec<Hash>) {
         self.stability.update_local_frontier(vv, heads);
     }
 
     /// Process a frontier update from a peer.
-    pub fn process_peer_update(&mut self, update: FrontierUpdate) {
-        self.stability.update_peer_frontier(update);
+    ///
+    /// Returns the peer's liveness after the update, or `None` for an
+    /// observer; see [`StabilityMonitor::update_peer_frontier`].
+    pub fn process_peer_update(&mut self, update: FrontierUpdate) -> Option<PeerLiveness> {
+        self.stability.update_peer_frontier(update)
+    }
+
+    /// Track a read-only replica that must not hold back compaction.
+    pub fn register_observer(&mut self, peer_id: impl Into<String>) {
+        self.stability.register_observer(peer_id);
     }
 
     /// Create a frontier update for broadcasting.
     pub fn create_frontier_update(&self) -> FrontierUpdate {
-        self.stability.create_frontier_update(self.current_time)
+        self.stability.create_frontier_update(self.now())
+    }
+
+    /// Create a frontier update for one peer, as a delta against the last
+    /// one sent to it; see [`StabilityMonitor::create_frontier_delta`].
+    pub fn create_frontier_delta(&mut self, peer_id: &str) -> FrontierUpdate {
+        let now = self.now();
+        self.stability.create_frontier_delta(peer_id, now)
+    }
+
+    /// Operations in the local frontier that the latest snapshot doesn't
+    /// cover.
+    pub fn ops_since_snapshot(&self) -> u64 {
+        let taken = self
+            .snapshots
+            .latest()
+            .map_or(0, |snapshot| snapshot.version_vector.total_operations());
+        self.stability
+            .local_frontier()
+            .total_operations()
+            .saturating_sub(taken)
     }
 
     /// Check if a snapshot should be created.
     pub fn should_snapshot(&self) -> bool {
         self.snapshots
-            .should_snapshot(self.stability.local_frontier(), self.current_time)
+            .should_snapshot(self.stability.local_frontier(), self.now())
     }
 
     /// Create a snapshot from the current state.
@@ -297,13 +377,14 @@ impl Compactor {
     {
         let state_data = state_serializer().map_err(CompactionError::SerializationFailed)?;
 
-        let snapshot = Snapshot::new(
+        let mut snapshot = Snapshot::new(
             self.stability.local_frontier().clone(),
             superseded_roots,
             state_data,
             &self.replica_id,
-            self.current_time,
+            self.now(),
         );
+        self.snapshots.sign(&mut snapshot);
 
         let id = self.snapshots.store(snapshot);
         self.stats.snapshots_created += 1;
@@ -312,6 +393,34 @@ impl Compactor {
         Ok(id)
     }
 
+    /// Create a snapshot of the current state and anchor it in the DAG.
+    ///
+    /// The snapshot supersedes the store's current heads, and a snapshot
+    /// anchor node is added as their child. Returns the snapshot ID and the
+    /// anchor's CID.
+    pub fn create_anchored_snapshot<S, F>(
+        &mut self,
+        store: &mut S,
+        state_serializer: F,
+    ) -> Result<(Hash, Hash), CompactionError>
+    where
+        S: DAGStore,
+        F: FnOnce() -> Result<Vec<u8>, String>,
+    {
+        let id = self.create_snapshot(store.heads(), state_serializer)?;
+        let node = self
+            .snapshots
+            .get(&id)
+            .ok_or_else(|| CompactionError::SnapshotFailed("snapshot not retained".to_string()))?
+            .to_anchor_node()
+            .map_err(|e| CompactionError::SnapshotFailed(e.to_string()))?;
+        let anchor = store
+            .put(node)
+            .map_err(|e| CompactionError::SnapshotFailed(e.to_string()))?;
+
+        Ok((id, anchor))
+    }
+
     /// Check if compaction should be performed.
     pub fn should_compact<S: DAGStore>(&self, _store: &S) -> bool {
         if !self.config.auto_compact {
@@ -350,17 +459,64 @@ impl Compactor {
 
         // Create snapshot if needed
         if self.should_snapshot() {
+            self.snapshot_into(&mut result, store, state_serializer)?;
+        }
+
+        self.prune_and_verify(store, result)
+    }
+
+    /// Snapshot the current state whether or not the snapshot thresholds
+    /// are met, then prune and verify as [`compact`](Self::compact) does.
+    pub fn compact_now<S, F>(
+        &mut self,
+        store: &mut S,
+        state_serializer: F,
+    ) -> Result<CompactionResult, CompactionError>
+    where
+        S: DAGStore + PrunableStore,
+        F: FnOnce() -> Result<Vec<u8>, String>,
+    {
+        let mut result = CompactionResult::default();
+        self.snapshot_into(&mut result, store, state_serializer)?;
+        self.prune_and_verify(store, result)
+    }
+
+    /// Create a snapshot, anchored if configured, and record it in `result`.
+    fn snapshot_into<S, F>(
+        &mut self,
+        result: &mut CompactionResult,
+        store: &mut S,
+        state_serializer: F,
+    ) -> Result<(), CompactionError>
+    where
+        S: DAGStore,
+        F: FnOnce() -> Result<Vec<u8>, String>,
+    {
+        if self.config.anchor_snapshots {
+            let (snapshot_id, anchor) = self.create_anchored_snapshot(store, state_serializer)?;
+            result.snapshot_created = Some(snapshot_id);
+            result.anchor_created = Some(anchor);
+        } else {
             let superseded = store.heads();
             let snapshot_id = self.create_snapshot(superseded, state_serializer)?;
             result.snapshot_created = Some(snapshot_id);
         }
+        Ok(())
+    }
 
+    /// Prune up to the latest snapshot if it is stable, then verify.
+    fn prune_and_verify<S>(
+        &mut self,
+        store: &mut S,
+        mut result: CompactionResult,
+    ) -> Result<CompactionResult, CompactionError>
+    where
+        S: DAGStore + PrunableStore,
+    {
         // Prune if we have a stable snapshot
         if let Some(snapshot) = self.snapshots.latest() {
             if self.stability.is_stable(&snapshot.version_vector) {
-                let prune_result = self
-                    .pruner
-                    .execute_prune(store, snapshot, self.current_time);
+                let prune_result = self.pruner.execute_prune(store, snapshot, self.now());
                 result.nodes_pruned = prune_result.nodes_pruned;
                 result.pruning_result = Some(prune_result);
                 self.stats.nodes_pruned += result.nodes_pruned as u64;
@@ -373,13 +529,14 @@ impl Compactor {
                 .map_err(CompactionError::VerificationFailed)?;
         }
 
-        self.stats.last_compaction = Some(self.current_time);
+        self.stats.last_compaction = Some(self.now());
         self.stats.current_dag_size = store.len();
 
         Ok(result)
     }
 
-    /// Perform automatic maintenance (GC stale peers, auto-compact if needed).
+    /// Perform automatic maintenance (evict timed-out peers, GC stale peers,
+    /// auto-compact if needed).
     pub fn tick<S, F>(
         &mut self,
         store: &mut S,
@@ -392,8 +549,9 @@ impl Compactor {
     {
         self.current_time = time;
 
-        // GC stale peers
-        self.stability.gc_stale_peers(time);
+        // Evict peers that stopped reporting, then GC stale peers
+        self.stability.check_liveness(self.now());
+        self.stability.gc_stale_peers(self.now());
 
         // Auto-compact if needed
         if self.should_compact(store) {
@@ -404,13 +562,22 @@ impl Compactor {
         }
     }
 
-    /// Bootstrap from a snapshot.
+    /// Bootstrap from a snapshot, whole or in chunks.
     ///
-    /// Returns the deserialized state data and the version vector.
+    /// Returns the deserialized state data and the version vector. Fails
+    /// with [`SnapshotError::IntegrityFailure`] if the snapshot's contents do
+    /// not match its content hash, and with the error
+    /// [`ChunkedSnapshot::assemble`](crate::ChunkedSnapshot::assemble)
+    /// gives for a missing or corrupt chunk. Signatures are not checked; use
+    /// [`bootstrap_from_snapshot_verified`](Self::bootstrap_from_snapshot_verified)
+    /// to only accept snapshots from trusted peers.
     pub fn bootstrap_from_snapshot(
         &mut self,
-        snapshot: Snapshot,
+        snapshot: impl Into<SnapshotForm>,
     ) -> Result<(Vec<u8>, VersionVector), CompactionError> {
+        let snapshot = snapshot.into().into_snapshot()?;
+        snapshot.verify_integrity()?;
+
         let state_data = snapshot.state_data.clone();
         let vv = snapshot.version_vector.clone();
 
@@ -420,6 +587,20 @@ impl Compactor {
         Ok((state_data, vv))
     }
 
+    /// Bootstrap from a snapshot signed by a peer in `trusted`.
+    ///
+    /// The signature is checked with this compactor's signer (see
+    /// [`with_signer`](Self::with_signer)).
+    pub fn bootstrap_from_snapshot_verified(
+        &mut self,
+        snapshot: impl Into<SnapshotForm>,
+        trusted: &TrustSet,
+    ) -> Result<(Vec<u8>, VersionVector), CompactionError> {
+        let snapshot = snapshot.into().into_snapshot()?;
+        self.snapshots.verify(&snapshot, trusted)?;
+        self.bootstrap_from_snapshot(snapshot)
+    }
+
     /// Get the best snapshot for bootstrapping a new replica.
     pub fn get_bootstrap_snapshot(&self) -> Option<&Snapshot> {
         self.snapshots.latest()
@@ -432,6 +613,9 @@ pub struct CompactionResult {
     /// ID of snapshot created, if any.
     pub snapshot_created: Option<Hash>,
 
+    /// CID of the DAG node anchoring the created snapshot, if any.
+    pub anchor_created: Option<Hash>,
+
     /// Number of nodes pruned.
     pub nodes_pruned: usize,
 
@@ -532,6 +716,86 @@ mod tests {
         assert!(!compactor.should_compact(&store));
     }
 
+    #[test]
+    fn test_silent_observer_does_not_block_compaction() {
+        let config = CompactionConfig {
+            auto_compact: true,
+            min_ops_for_compaction: 5,
+            ..Default::default()
+        };
+        let mut compactor = Compactor::with_config("test", config);
+        compactor.register_observer("watcher");
+        let (store, _) = MemoryDAGStore::with_genesis("test");
+
+        for (time, seq) in [(0, 10), (100, 20)] {
+            compactor.set_time(time);
+            let vv = VersionVector::from_entries([("test".to_string(), seq)]);
+            compactor.update_local_frontier(vv, vec![]);
+            compactor
+                .create_snapshot(vec![], || Ok(b"state".to_vec()))
+                .unwrap();
+        }
+
+        let update = |peer_id: &str, seq| FrontierUpdate {
+            peer_id: peer_id.to_string(),
+            version_vector: VersionVector::from_entries([("test".to_string(), seq)]),
+            heads: vec![],
+            timestamp: 100,
+            is_delta: false,
+        };
+        compactor.process_peer_update(update("r2", 20));
+        compactor.process_peer_update(update("watcher", 0));
+        assert!(compactor.should_compact(&store));
+
+        // A full peer that lags does block it
+        compactor.process_peer_update(update("r3", 0));
+        assert!(!compactor.should_compact(&store));
+    }
+
+    #[test]
+    fn test_timed_out_peer_stops_blocking_compaction() {
+        let mut config = CompactionConfig {
+            auto_compact: true,
+            min_ops_for_compaction: 5,
+            ..Default::default()
+        };
+        config.stability.peer_timeout = Some(1000);
+        let mut compactor = Compactor::with_config("test", config);
+        let (mut store, _) = MemoryDAGStore::with_genesis("test");
+
+        let update = |peer_id: &str, seq, timestamp| FrontierUpdate {
+            peer_id: peer_id.to_string(),
+            version_vector: VersionVector::from_entries([("test".to_string(), seq)]),
+            heads: vec![],
+            timestamp,
+            is_delta: false,
+        };
+        compactor.process_peer_update(update("r2", 0, 0));
+        compactor.process_peer_update(update("r3", 0, 0));
+
+        for (time, seq) in [(0, 10), (100, 20)] {
+            compactor.set_time(time);
+            let vv = VersionVector::from_entries([("test".to_string(), seq)]);
+            compactor.update_local_frontier(vv, vec![]);
+            compactor
+                .create_snapshot(vec![], || Ok(b"state".to_vec()))
+                .unwrap();
+        }
+
+        // r2 keeps up, r3 went away
+        compactor.process_peer_update(update("r2", 20, 1500));
+        let state = || Ok(b"state".to_vec());
+        assert!(compactor.tick(&mut store, state, 900).unwrap().is_none());
+        assert!(compactor.tick(&mut store, state, 1500).unwrap().is_some());
+
+        // Coming back behind what was compacted, it has to bootstrap
+        assert_eq!(
+            compactor.process_peer_update(update("r3", 0, 1600)),
+            Some(PeerLiveness::NeedsBootstrap)
+        );
+        assert!(compactor.should_compact(&store));
+    }
+
     #[test]
     fn test_bootstrap_from_snapshot() {
         let mut compactor = Compactor::new("new_replica");
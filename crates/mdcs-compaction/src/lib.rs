@@ -4,9 +4,14 @@
 //!
 //! This crate provides:
 //! - Snapshotting: Serialize full CRDT state at stable frontiers
+//! - Snapshot integrity: Content hashes and optional signatures
+//! - Chunked snapshots: Verifiable, resumable transfer of large states
 //! - DAG pruning: Remove nodes older than the last snapshot
 //! - Stability monitoring: Track delivered and stable frontiers
 //! - Version vectors: Compact representation of causal context
+//! - Compacting replicas: Causal replicas that snapshot themselves
+//! - Scheduled compaction: Background rounds on a tokio interval
+//!   (`scheduler` feature)
 //!
 //! ## Architecture
 //!
@@ -34,14 +39,36 @@
 //! }
 //! ```
 
+mod chunked;
 mod compactor;
 mod pruning;
+mod replica;
+#[cfg(feature = "scheduler")]
+mod scheduler;
 mod snapshot;
 mod stability;
 mod version_vector;
 
-pub use compactor::{CompactionConfig, CompactionError, CompactionStats, Compactor};
+pub use chunked::{
+    ChunkedSnapshot, SnapshotChunk, SnapshotForm, SnapshotManifest, SnapshotReader, SnapshotWriter,
+    DEFAULT_CHUNK_SIZE,
+};
+pub use compactor::{
+    CompactionConfig, CompactionError, CompactionResult, CompactionStats, Compactor,
+};
 pub use pruning::{PrunableStore, Pruner, PruningPolicy, PruningResult, PruningVerifier};
-pub use snapshot::{Snapshot, SnapshotError, SnapshotManager};
-pub use stability::{FrontierUpdate, StabilityConfig, StabilityMonitor, StabilityState};
-pub use version_vector::{VectorEntry, VersionVector};
+pub use replica::{CompactingReplica, StateSerializer};
+#[cfg(feature = "scheduler")]
+pub use scheduler::{
+    AsyncStateSerializer, CompactionScheduler, EventCallback, FrontierSource, SchedulerEvent,
+    SchedulerHandle,
+};
+#[cfg(feature = "crypto")]
+pub use snapshot::Ed25519Signer;
+pub use snapshot::{
+    Snapshot, SnapshotError, SnapshotManager, SnapshotSignature, SnapshotSigner, TrustSet,
+};
+pub use stability::{
+    FrontierUpdate, PeerLiveness, StabilityConfig, StabilityMonitor, StabilityState,
+};
+pub use version_vector::{VectorDecodeError, VectorEntry, VersionVector};
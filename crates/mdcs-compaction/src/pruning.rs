@@ -6,7 +6,7 @@
 use crate::snapshot::Snapshot;
 use crate::stability::StabilityMonitor;
 use crate::version_vector::VersionVector;
-use mdcs_merkle::{DAGStore, Hash};
+use mdcs_merkle::{DAGStore, FileDAGStore, Hash, MemoryDAGStore};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
@@ -25,7 +25,11 @@ pub struct PruningPolicy {
     /// Whether to require stability before pruning.
     pub require_stability: bool,
 
-    /// Whether to keep at least one path to genesis.
+    /// Whether to keep the first-parent path from a head back to genesis.
+    ///
+    /// When false, genesis is pruned like any other node below the snapshot.
+    /// The store then reports it in its pruned boundary, and peers that lack
+    /// it bootstrap from the snapshot instead of fetching it.
     pub preserve_genesis_path: bool,
 
     /// Depth of history to preserve beyond the snapshot.
@@ -191,6 +195,15 @@ impl Pruner {
                 continue;
             }
 
+            // Snapshot anchors are always preserved: bootstrapping replicas
+            // stop syncing at them
+            if store
+                .get(&cid)
+                .is_some_and(|n| n.payload.is_snapshot_anchor())
+            {
+                continue;
+            }
+
             // Skip if not an ancestor of the snapshot
             if !snapshot_ancestors.contains(&cid) {
                 continue;
@@ -344,6 +357,10 @@ impl Default for Pruner {
 /// Extension trait for stores that support node removal.
 pub trait PrunableStore: DAGStore {
     /// Remove a node from the store.
+    ///
+    /// The node must afterwards be reported by
+    /// [`DAGStore::pruned_boundary`], so it is neither treated as missing
+    /// nor fetched from peers again.
     fn remove(&mut self, cid: &Hash) -> Result<(), String>;
 
     /// Remove multiple nodes.
@@ -358,9 +375,17 @@ pub trait PrunableStore: DAGStore {
     }
 }
 
-// Note: MemoryDAGStore doesn't actually support removal (immutable by design).
-// For testing purposes, we use wrapper types that track "pruned" nodes.
-// In production, a proper store implementation would handle removal.
+impl PrunableStore for MemoryDAGStore {
+    fn remove(&mut self, cid: &Hash) -> Result<(), String> {
+        self.prune(cid).map_err(|e| e.to_string())
+    }
+}
+
+impl PrunableStore for FileDAGStore {
+    fn remove(&mut self, cid: &Hash) -> Result<(), String> {
+        self.prune(cid).map_err(|e| e.to_string())
+    }
+}
 
 /// Verification utilities for pruning safety.
 pub struct PruningVerifier;
@@ -383,6 +408,11 @@ impl PruningVerifier {
 
         for cid in store.topological_order() {
             if let Some(node) = store.get(&cid) {
+                // Anchors only order their snapshot after its roots, whose
+                // history the snapshot already captures
+                if node.payload.is_snapshot_anchor() {
+                    continue;
+                }
                 for parent in &node.parents {
                     if pruned_set.contains(parent) && !pruned_set.contains(&cid) {
                         return Err(format!(
@@ -427,7 +457,7 @@ impl PruningVerifier {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use mdcs_merkle::{MemoryDAGStore, NodeBuilder, Payload};
+    use mdcs_merkle::{NodeBuilder, Payload};
 
     #[test]
     fn test_pruner_creation() {
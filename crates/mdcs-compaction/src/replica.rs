@@ -0,0 +1,241 @@
+//! Automatic compaction for causal replicas.
+//!
+//! A [`CompactingReplica`] wraps a [`CausalReplica`] and a [`Compactor`].
+//! After each mutation, received interval or ack it consults the
+//! [`CompactionConfig`]: once `min_ops_for_compaction` operations have been
+//! applied since the last snapshot and every tracked peer has acknowledged
+//! every local delta, it serializes the state with the closure it was given
+//! and records a [`Snapshot`] whose version vector is the replica's
+//! [`digest`](CausalReplica::digest).
+//!
+//! The deltas a snapshot supersedes are gone by then: a peer buffer drops
+//! deltas as they are acknowledged, and a snapshot is only taken once every
+//! buffer has been acknowledged in full. A replica that bootstraps from the
+//! snapshot with [`CompactingReplica::bootstrap`] counts everything in its
+//! version vector as received, so a delta from before the snapshot that
+//! arrives late is acknowledged as a duplicate instead of being applied on
+//! top of it.
+
+use crate::chunked::SnapshotForm;
+use crate::compactor::{CompactionConfig, CompactionError, Compactor};
+use crate::snapshot::Snapshot;
+use crate::version_vector::VersionVector;
+use mdcs_core::lattice::Lattice;
+use mdcs_delta::{AckEvent, CausalReplica, DeltaInterval, Digest, IntervalAck};
+use mdcs_merkle::Hash;
+
+/// Serializes a replica's state for a snapshot.
+pub type StateSerializer<S> = Box<dyn Fn(&S) -> Result<Vec<u8>, String> + Send + Sync>;
+
+/// A causal replica that snapshots its state as configured.
+pub struct CompactingReplica<S: Lattice + Clone> {
+    /// The wrapped replica.
+    replica: CausalReplica<S>,
+
+    /// Creates and keeps the snapshots.
+    compactor: Compactor,
+
+    /// Serializes the state for a snapshot.
+    serializer: StateSerializer<S>,
+
+    /// Operations in the replica's digest when the last snapshot was taken.
+    compacted_ops: u64,
+}
+
+impl<S: Lattice + Clone> CompactingReplica<S> {
+    /// Wrap a replica, snapshotting it as `config` asks.
+    pub fn new<F>(replica: CausalReplica<S>, config: CompactionConfig, serializer: F) -> Self
+    where
+        F: Fn(&S) -> Result<Vec<u8>, String> + Send + Sync + 'static,
+    {
+        CompactingReplica {
+            compactor: Compactor::with_config(replica.id().clone(), config),
+            replica,
+            serializer: Box::new(serializer),
+            compacted_ops: 0,
+        }
+    }
+
+    /// Start `replica` from a snapshot taken by another replica.
+    ///
+    /// The snapshot is checked and kept as this replica's first; its state
+    /// is decoded with `deserialize` and its version vector becomes the
+    /// replica's digest (see [`CausalReplica::with_snapshot`]).
+    pub fn bootstrap<D, F>(
+        replica: CausalReplica<S>,
+        config: CompactionConfig,
+        snapshot: impl Into<SnapshotForm>,
+        deserialize: D,
+        serializer: F,
+    ) -> Result<Self, CompactionError>
+    where
+        D: FnOnce(&[u8]) -> Result<S, String>,
+        F: Fn(&S) -> Result<Vec<u8>, String> + Send + Sync + 'static,
+    {
+        let mut compactor = Compactor::with_config(replica.id().clone(), config);
+        let (state_data, vv) = compactor.bootstrap_from_snapshot(snapshot)?;
+        let state = deserialize(&state_data).map_err(CompactionError::SerializationFailed)?;
+        let digest: Digest = vv.iter().map(|(id, seq)| (id.clone(), *seq)).collect();
+        let compacted_ops = vv.total_operations();
+        compactor.update_local_frontier(vv, Vec::new());
+
+        Ok(CompactingReplica {
+            replica: replica.with_snapshot(state, digest),
+            compactor,
+            serializer: Box::new(serializer),
+            compacted_ops,
+        })
+    }
+
+    /// Get the wrapped replica.
+    pub fn replica(&self) -> &CausalReplica<S> {
+        &self.replica
+    }
+
+    /// Get the wrapped replica mutably, e.g. to register peers or prepare
+    /// intervals.
+    pub fn replica_mut(&mut self) -> &mut CausalReplica<S> {
+        &mut self.replica
+    }
+
+    /// Get the compactor holding the snapshots.
+    pub fn compactor(&self) -> &Compactor {
+        &self.compactor
+    }
+
+    /// Get the latest snapshot, for bootstrapping a new replica.
+    pub fn latest_snapshot(&self) -> Option<&Snapshot> {
+        self.compactor.get_bootstrap_snapshot()
+    }
+
+    /// Apply a local mutation, then compact if due.
+    pub fn mutate<F>(&mut self, mutator: F) -> S
+    where
+        F: FnOnce(&S) -> S,
+    {
+        let delta = self.replica.mutate(mutator);
+        let _ = self.maybe_compact();
+        delta
+    }
+
+    /// Receive a delta-interval, then compact if due.
+    pub fn receive_interval(&mut self, interval: DeltaInterval<S>) -> Option<IntervalAck> {
+        let ack = self.replica.receive_interval(interval);
+        let _ = self.maybe_compact();
+        ack
+    }
+
+    /// Process an acknowledgment, then compact if due.
+    pub fn receive_ack(&mut self, ack: &IntervalAck) -> Option<AckEvent> {
+        let event = self.replica.receive_ack(ack);
+        let _ = self.maybe_compact();
+        event
+    }
+
+    /// Operations applied since the last snapshot, local and received.
+    pub fn ops_since_snapshot(&self) -> u64 {
+        self.frontier()
+            .total_operations()
+            .saturating_sub(self.compacted_ops)
+    }
+
+    /// Check whether every tracked peer has acknowledged every local delta.
+    pub fn is_stable(&self) -> bool {
+        let counter = self.replica.counter();
+        self.replica
+            .peers()
+            .all(|peer| self.replica.ack_barrier(peer, counter))
+    }
+
+    /// Take a snapshot if auto-compaction is on, enough operations were
+    /// applied since the last one and the replica is stable.
+    ///
+    /// The wrapped operations call this and drop its error; a snapshot
+    /// that failed is tried again on the next one.
+    pub fn maybe_compact(&mut self) -> Result<Option<Hash>, CompactionError> {
+        let config = self.compactor.config();
+        if !config.auto_compact
+            || self.ops_since_snapshot() < config.min_ops_for_compaction
+            || !self.is_stable()
+        {
+            return Ok(None);
+        }
+        self.compact().map(Some)
+    }
+
+    /// Take a snapshot of the current state now.
+    ///
+    /// Fails with [`CompactionError::StabilityNotMet`] while a peer has yet
+    /// to acknowledge a local delta.
+    pub fn compact(&mut self) -> Result<Hash, CompactionError> {
+        if !self.is_stable() {
+            return Err(CompactionError::StabilityNotMet(format!(
+                "peers of {} have not acknowledged delta {}",
+                self.replica.id(),
+                self.replica.counter()
+            )));
+        }
+
+        let frontier = self.frontier();
+        let ops = frontier.total_operations();
+        self.compactor.update_local_frontier(frontier, Vec::new());
+        let (state, serializer) = (self.replica.state(), &self.serializer);
+        let id = self
+            .compactor
+            .create_snapshot(Vec::new(), || serializer(state))?;
+        self.compacted_ops = ops;
+        Ok(id)
+    }
+
+    /// The replica's digest as a version vector.
+    fn frontier(&self) -> VersionVector {
+        VersionVector::from_entries(self.replica.digest())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mdcs_core::gset::GSet;
+
+    fn config(min_ops: u64) -> CompactionConfig {
+        CompactionConfig {
+            min_ops_for_compaction: min_ops,
+            ..CompactionConfig::default()
+        }
+    }
+
+    fn insert(value: i32) -> impl FnOnce(&GSet<i32>) -> GSet<i32> {
+        move |_| {
+            let mut delta = GSet::new();
+            delta.insert(value);
+            delta
+        }
+    }
+
+    #[test]
+    fn test_snapshot_waits_for_acks() {
+        let mut a = CompactingReplica::new(CausalReplica::new("a"), config(2), |s: &GSet<i32>| {
+            serde_json::to_vec(s).map_err(|e| e.to_string())
+        });
+        let mut b: CausalReplica<GSet<i32>> = CausalReplica::new("b");
+        a.replica_mut().register_peer("b".to_string());
+
+        a.mutate(insert(1));
+        a.mutate(insert(2));
+        assert_eq!(a.ops_since_snapshot(), 2);
+        assert!(a.latest_snapshot().is_none());
+        assert!(matches!(
+            a.compact(),
+            Err(CompactionError::StabilityNotMet(_))
+        ));
+
+        let interval = a.replica_mut().prepare_interval("b").unwrap();
+        let ack = b.receive_interval(interval).unwrap();
+        a.receive_ack(&ack);
+
+        let snapshot = a.latest_snapshot().expect("snapshot once b acked");
+        assert_eq!(snapshot.version_vector.get("a"), 2);
+        assert_eq!(a.ops_since_snapshot(), 0);
+    }
+}
@@ -0,0 +1,310 @@
+//! Background compaction on a tokio interval.
+//!
+//! A [`CompactionScheduler`] owns a [`Compactor`] and runs a round every
+//! [`CompactionConfig::interval`](crate::CompactionConfig::interval)
+//! milliseconds. A round reads the local frontier from a callback, evicts
+//! peers that stopped reporting, and once `min_ops_for_compaction`
+//! operations are past the latest snapshot and stable across a quorum of
+//! peers, awaits the state from an async closure, snapshots it and prunes
+//! the store. Each snapshot is reported to the event callback with the
+//! stats it added, and the running totals are published on a
+//! [`watch`] channel.
+//!
+//! A failed round, whether the closure or the compactor failed, is reported
+//! as [`SchedulerEvent::Failed`] and the next round tries again.
+//!
+//! Peer frontiers reach the scheduler through a [`SchedulerHandle`], which
+//! also stops it:
+//!
+//! ```rust,ignore
+//! let scheduler = CompactionScheduler::new(compactor, store.clone(), frontier, || async {
+//!     serialize_state().await
+//! });
+//! let handle = scheduler.handle();
+//! let task = scheduler.spawn();
+//!
+//! handle.process_peer_update(update);
+//! handle.shutdown();
+//! let compactor = task.await?;
+//! ```
+
+use crate::compactor::{CompactionError, CompactionResult, CompactionStats, Compactor};
+use crate::pruning::PrunableStore;
+use crate::stability::FrontierUpdate;
+use crate::version_vector::VersionVector;
+use mdcs_core::clock::SharedClock;
+use mdcs_merkle::DAGStore;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+/// Reads the local frontier at the start of a round.
+pub type FrontierSource = Box<dyn Fn() -> VersionVector + Send>;
+
+/// Serializes the state for a snapshot, asynchronously.
+pub type AsyncStateSerializer =
+    Box<dyn Fn() -> Pin<Box<dyn Future<Output = Result<Vec<u8>, String>> + Send>> + Send>;
+
+/// Called with what each round did.
+pub type EventCallback = Box<dyn Fn(&SchedulerEvent) + Send>;
+
+/// What a round of a [`CompactionScheduler`] did.
+#[derive(Debug)]
+pub enum SchedulerEvent {
+    /// A snapshot was taken and the store pruned.
+    Compacted {
+        /// The snapshot and pruning done.
+        result: CompactionResult,
+        /// Snapshots and pruned nodes this round added; the other fields
+        /// are as of its end.
+        delta: CompactionStats,
+    },
+    /// The round failed and will be retried on the next.
+    Failed(CompactionError),
+}
+
+/// Messages from a [`SchedulerHandle`].
+enum Command {
+    PeerUpdate(FrontierUpdate),
+    Observer(String),
+    Shutdown,
+}
+
+/// Controls a running [`CompactionScheduler`].
+#[derive(Clone)]
+pub struct SchedulerHandle {
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+impl SchedulerHandle {
+    /// Pass on a frontier update from a peer.
+    pub fn process_peer_update(&self, update: FrontierUpdate) {
+        let _ = self.commands.send(Command::PeerUpdate(update));
+    }
+
+    /// Track a read-only replica that must not hold back compaction.
+    pub fn register_observer(&self, peer_id: impl Into<String>) {
+        let _ = self.commands.send(Command::Observer(peer_id.into()));
+    }
+
+    /// Stop the scheduler after the round in progress, if any.
+    pub fn shutdown(&self) {
+        let _ = self.commands.send(Command::Shutdown);
+    }
+}
+
+/// Runs compaction rounds for a [`Compactor`] on an interval.
+pub struct CompactionScheduler<S> {
+    /// Snapshots, stability and pruning.
+    compactor: Compactor,
+
+    /// The DAG store pruned after each snapshot.
+    store: Arc<Mutex<S>>,
+
+    /// Reads the local frontier.
+    frontier: FrontierSource,
+
+    /// Serializes the state for a snapshot.
+    serializer: AsyncStateSerializer,
+
+    /// Called with what each round did.
+    on_event: Option<EventCallback>,
+
+    /// Supplies the compactor's time at the start of each round.
+    clock: SharedClock,
+
+    /// Running totals, published after each snapshot.
+    stats: watch::Sender<CompactionStats>,
+
+    /// Cloned into handles.
+    sender: mpsc::UnboundedSender<Command>,
+
+    /// Commands from handles.
+    commands: mpsc::UnboundedReceiver<Command>,
+
+    /// Whether a handle asked the scheduler to stop.
+    stopping: bool,
+}
+
+impl<S> CompactionScheduler<S>
+where
+    S: DAGStore + PrunableStore + Send + 'static,
+{
+    /// Schedule compaction of `store`, with the local frontier read from
+    /// `frontier` and the state from `serializer`.
+    pub fn new<V, F, Fut>(
+        compactor: Compactor,
+        store: Arc<Mutex<S>>,
+        frontier: V,
+        serializer: F,
+    ) -> Self
+    where
+        V: Fn() -> VersionVector + Send + 'static,
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<Vec<u8>, String>> + Send + 'static,
+    {
+        let (stats, _) = watch::channel(compactor.stats().clone());
+        let (sender, commands) = mpsc::unbounded_channel();
+        CompactionScheduler {
+            compactor,
+            store,
+            frontier: Box::new(frontier),
+            serializer: Box::new(move || Box::pin(serializer())),
+            on_event: None,
+            clock: SharedClock::default(),
+            stats,
+            sender,
+            commands,
+            stopping: false,
+        }
+    }
+
+    /// Call `callback` with what each round did.
+    pub fn on_event(mut self, callback: impl Fn(&SchedulerEvent) + Send + 'static) -> Self {
+        self.on_event = Some(Box::new(callback));
+        self
+    }
+
+    /// Read the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: impl Into<SharedClock>) -> Self {
+        self.clock = clock.into();
+        self
+    }
+
+    /// Get a handle for passing on peer frontiers and stopping the
+    /// scheduler.
+    pub fn handle(&self) -> SchedulerHandle {
+        SchedulerHandle {
+            commands: self.sender.clone(),
+        }
+    }
+
+    /// Watch the compactor's running totals.
+    pub fn stats(&self) -> watch::Receiver<CompactionStats> {
+        self.stats.subscribe()
+    }
+
+    /// Get the compactor.
+    pub fn compactor(&self) -> &Compactor {
+        &self.compactor
+    }
+
+    /// Run on the current tokio runtime until shut down, then hand back
+    /// the compactor.
+    pub fn spawn(self) -> JoinHandle<Compactor> {
+        tokio::spawn(self.run())
+    }
+
+    /// Run a round every interval until shut down, then hand back the
+    /// compactor.
+    ///
+    /// Peer updates are applied as they arrive. The first round runs
+    /// straight away.
+    pub async fn run(mut self) -> Compactor {
+        let period = Duration::from_millis(self.compactor.config().interval.max(1));
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        while !self.stopping {
+            tokio::select! {
+                _ = interval.tick() => {
+                    self.round().await;
+                }
+                Some(command) = self.commands.recv() => self.apply(command),
+            }
+        }
+        self.compactor
+    }
+
+    /// Run one round now, returning what it compacted, if anything.
+    ///
+    /// Failures are reported to the event callback rather than returned.
+    pub async fn round(&mut self) -> Option<CompactionResult> {
+        while let Ok(command) = self.commands.try_recv() {
+            self.apply(command);
+        }
+
+        self.compactor.set_time(self.clock.now_millis());
+        let now = self.compactor.now();
+        let heads = self.lock_store().heads();
+        self.compactor
+            .update_local_frontier((self.frontier)(), heads);
+        self.compactor.stability_mut().check_liveness(now);
+        self.compactor.stability_mut().gc_stale_peers(now);
+        if !self.is_due() {
+            return None;
+        }
+
+        // The state may run ahead of the frontier read above; the snapshot
+        // then covers more than it claims, which joining again undoes
+        let state = match (self.serializer)().await {
+            Ok(state) => state,
+            Err(e) => {
+                self.report(SchedulerEvent::Failed(
+                    CompactionError::SerializationFailed(e),
+                ));
+                return None;
+            }
+        };
+
+        let before = self.compactor.stats().clone();
+        let store = Arc::clone(&self.store);
+        let compacted = {
+            let mut store = store.lock().unwrap_or_else(PoisonError::into_inner);
+            self.compactor.compact_now(&mut *store, || Ok(state))
+        };
+        match compacted {
+            Ok(result) => {
+                let stats = self.compactor.stats().clone();
+                let delta = CompactionStats {
+                    snapshots_created: stats.snapshots_created - before.snapshots_created,
+                    nodes_pruned: stats.nodes_pruned - before.nodes_pruned,
+                    ..stats.clone()
+                };
+                self.stats.send_replace(stats);
+                self.report(SchedulerEvent::Compacted {
+                    result: result.clone(),
+                    delta,
+                });
+                Some(result)
+            }
+            Err(e) => {
+                self.report(SchedulerEvent::Failed(e));
+                None
+            }
+        }
+    }
+
+    /// Whether enough operations are past the latest snapshot, and stable.
+    fn is_due(&self) -> bool {
+        let config = self.compactor.config();
+        let stability = self.compactor.stability();
+        config.auto_compact
+            && self.compactor.ops_since_snapshot() >= config.min_ops_for_compaction.max(1)
+            && stability.has_quorum()
+            && stability.is_stable(stability.local_frontier())
+    }
+
+    fn apply(&mut self, command: Command) {
+        match command {
+            Command::PeerUpdate(update) => {
+                self.compactor.process_peer_update(update);
+            }
+            Command::Observer(peer_id) => self.compactor.register_observer(peer_id),
+            Command::Shutdown => self.stopping = true,
+        }
+    }
+
+    fn report(&self, event: SchedulerEvent) {
+        if let Some(callback) = &self.on_event {
+            callback(&event);
+        }
+    }
+
+    fn lock_store(&self) -> std::sync::MutexGuard<'_, S> {
+        self.store.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
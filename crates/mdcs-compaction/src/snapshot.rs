@@ -2,7 +2,13 @@
 //!
 //! Snapshots capture the full state of a CRDT at a stable point,
 //! allowing for efficient bootstrapping and DAG pruning.
+//!
+//! Snapshots travel between replicas, so each carries a content hash that is
+//! checked before it is used to bootstrap a replica. Replicas can also sign
+//! snapshots with a [`SnapshotSigner`], letting a bootstrapping replica only
+//! accept snapshots from peers in its [`TrustSet`].
 
+use crate::chunked::{ChunkedSnapshot, SnapshotForm};
 use crate::version_vector::VersionVector;
 use mdcs_merkle::{Hash, Hasher, MerkleNode, NodeBuilder, Payload};
 use serde::{Deserialize, Serialize};
@@ -26,6 +32,34 @@ pub enum SnapshotError {
 
     #[error("Snapshot too old: {0}")]
     TooOld(String),
+
+    #[error("Snapshot integrity check failed: {0}")]
+    IntegrityFailure(String),
+
+    #[error("Snapshot is not signed")]
+    Unsigned,
+
+    #[error("Snapshot signed by untrusted peer: {0}")]
+    UntrustedSigner(String),
+
+    #[error("Invalid snapshot signature from {0}")]
+    InvalidSignature(String),
+
+    #[error("No snapshot signer configured to verify signatures")]
+    NoSigner,
+
+    #[error("Snapshot chunk {index} is corrupt: hash {actual}, expected {expected}")]
+    CorruptChunk {
+        index: u32,
+        expected: Hash,
+        actual: Hash,
+    },
+
+    #[error("Snapshot chunk {index} is out of range; the snapshot has {count} chunks")]
+    ChunkOutOfRange { index: u32, count: usize },
+
+    #[error("Snapshot is missing chunks {missing:?}")]
+    MissingChunks { missing: Vec<u32> },
 }
 
 /// Current snapshot format version.
@@ -59,6 +93,23 @@ pub struct Snapshot {
 
     /// Optional metadata about the snapshot.
     pub metadata: HashMap<String, String>,
+
+    /// Hash of the version vector, superseded roots and state data.
+    pub content_hash: Hash,
+
+    /// Signature over `content_hash`, if the snapshot was signed.
+    #[serde(default)]
+    pub signature: Option<SnapshotSignature>,
+}
+
+/// A signature over a snapshot's content hash.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotSignature {
+    /// The signing peer, as named in a [`TrustSet`].
+    pub signer: String,
+
+    /// The signature bytes.
+    pub bytes: Vec<u8>,
 }
 
 impl Snapshot {
@@ -83,6 +134,8 @@ impl Snapshot {
         hasher.update(&created_at.to_le_bytes());
         hasher.update(creator.as_bytes());
         let id = hasher.finalize();
+        let content_hash =
+            Self::compute_content_hash(&version_vector, &superseded_roots, &state_data);
 
         Snapshot {
             version: SNAPSHOT_VERSION,
@@ -93,7 +146,63 @@ impl Snapshot {
             created_at,
             creator,
             metadata: HashMap::new(),
+            content_hash,
+            signature: None,
+        }
+    }
+
+    /// Hash the parts of a snapshot a replica bootstraps from.
+    ///
+    /// Every field is length-prefixed so that moving bytes between fields
+    /// changes the hash.
+    pub fn compute_content_hash(
+        version_vector: &VersionVector,
+        superseded_roots: &[Hash],
+        state_data: &[u8],
+    ) -> Hash {
+        let entries = version_vector.to_entries();
+        let mut hasher = Hasher::new();
+        hasher.update(b"mdcs-snapshot-content");
+        hasher.update(&(entries.len() as u64).to_le_bytes());
+        for entry in &entries {
+            hasher.update(&(entry.replica_id.len() as u64).to_le_bytes());
+            hasher.update(entry.replica_id.as_bytes());
+            hasher.update(&entry.sequence.to_le_bytes());
+        }
+        hasher.update(&(superseded_roots.len() as u64).to_le_bytes());
+        for root in superseded_roots {
+            hasher.update(root.as_bytes());
         }
+        hasher.update(&(state_data.len() as u64).to_le_bytes());
+        hasher.update(state_data);
+        hasher.finalize()
+    }
+
+    /// Check that the contents still match `content_hash`.
+    pub fn verify_integrity(&self) -> Result<(), SnapshotError> {
+        let actual = Self::compute_content_hash(
+            &self.version_vector,
+            &self.superseded_roots,
+            &self.state_data,
+        );
+        if actual == self.content_hash {
+            Ok(())
+        } else {
+            Err(SnapshotError::IntegrityFailure(format!(
+                "snapshot {} has content hash {}, expected {}",
+                self.id.short(),
+                actual.short(),
+                self.content_hash.short()
+            )))
+        }
+    }
+
+    /// Sign the content hash, replacing any previous signature.
+    pub fn sign(&mut self, signer: &dyn SnapshotSigner) {
+        self.signature = Some(SnapshotSignature {
+            signer: signer.signer_id().to_string(),
+            bytes: signer.sign(&self.content_hash),
+        });
     }
 
     /// Add metadata to the snapshot.
@@ -137,6 +246,40 @@ impl Snapshot {
         }
     }
 
+    /// Build a DAG node anchoring this snapshot.
+    ///
+    /// Unlike [`to_merkle_node`](Self::to_merkle_node), the node only
+    /// references the snapshot by ID; the state itself stays in the
+    /// [`SnapshotManager`]. It is a child of the superseded roots.
+    pub fn to_anchor_node(&self) -> Result<MerkleNode, SnapshotError> {
+        let vv_data = serde_json::to_vec(&self.version_vector)
+            .map_err(|e| SnapshotError::SerializationError(e.to_string()))?;
+
+        Ok(NodeBuilder::new()
+            .with_parents(self.superseded_roots.clone())
+            .with_payload(Payload::snapshot_anchor(self.id, vv_data))
+            .with_timestamp(self.created_at)
+            .with_creator(&self.creator)
+            .build())
+    }
+
+    /// Read the snapshot ID and version vector from a snapshot anchor node.
+    pub fn read_anchor(node: &MerkleNode) -> Result<(Hash, VersionVector), SnapshotError> {
+        match &node.payload {
+            Payload::SnapshotAnchor {
+                snapshot_hash,
+                version_vector_bytes,
+            } => {
+                let vv = serde_json::from_slice(version_vector_bytes)
+                    .map_err(|e| SnapshotError::SerializationError(e.to_string()))?;
+                Ok((*snapshot_hash, vv))
+            }
+            _ => Err(SnapshotError::InvalidData(
+                "Node does not contain snapshot anchor payload".to_string(),
+            )),
+        }
+    }
+
     /// Check if this snapshot covers a given version vector.
     pub fn covers(&self, vv: &VersionVector) -> bool {
         self.version_vector.dominates(vv)
@@ -148,6 +291,115 @@ impl Snapshot {
     }
 }
 
+/// Signs snapshot content hashes and checks peers' signatures.
+pub trait SnapshotSigner: Send + Sync {
+    /// The name signatures are recorded under, usually the replica ID.
+    fn signer_id(&self) -> &str;
+
+    /// Sign a snapshot's content hash.
+    fn sign(&self, hash: &Hash) -> Vec<u8>;
+
+    /// Check a signature over `hash` against a peer's public key.
+    fn verify(&self, peer: &[u8], hash: &Hash, signature: &[u8]) -> bool;
+}
+
+/// Peers whose signed snapshots a replica accepts, with their public keys.
+#[derive(Clone, Debug, Default)]
+pub struct TrustSet {
+    keys: HashMap<String, Vec<u8>>,
+}
+
+impl TrustSet {
+    /// Create an empty trust set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a trusted peer.
+    pub fn with_peer(mut self, peer: impl Into<String>, public_key: impl Into<Vec<u8>>) -> Self {
+        self.trust(peer, public_key);
+        self
+    }
+
+    /// Trust a peer's key, replacing any previous key for it.
+    pub fn trust(&mut self, peer: impl Into<String>, public_key: impl Into<Vec<u8>>) {
+        self.keys.insert(peer.into(), public_key.into());
+    }
+
+    /// Stop trusting a peer.
+    pub fn revoke(&mut self, peer: &str) -> bool {
+        self.keys.remove(peer).is_some()
+    }
+
+    /// Get a trusted peer's public key.
+    pub fn key(&self, peer: &str) -> Option<&[u8]> {
+        self.keys.get(peer).map(|k| k.as_slice())
+    }
+
+    /// Number of trusted peers.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Check if no peers are trusted.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// Ed25519 snapshot signatures.
+///
+/// Public keys in a [`TrustSet`] are the 32-byte verifying keys returned by
+/// [`public_key`](Self::public_key).
+#[cfg(feature = "crypto")]
+pub struct Ed25519Signer {
+    signer_id: String,
+    key: ed25519_dalek::SigningKey,
+}
+
+#[cfg(feature = "crypto")]
+impl Ed25519Signer {
+    /// Create a signer from a signing key.
+    pub fn new(signer_id: impl Into<String>, key: ed25519_dalek::SigningKey) -> Self {
+        Self {
+            signer_id: signer_id.into(),
+            key,
+        }
+    }
+
+    /// Create a signer from a 32-byte secret key.
+    pub fn from_seed(signer_id: impl Into<String>, seed: &[u8; 32]) -> Self {
+        Self::new(signer_id, ed25519_dalek::SigningKey::from_bytes(seed))
+    }
+
+    /// The public key peers add to their trust set.
+    pub fn public_key(&self) -> Vec<u8> {
+        self.key.verifying_key().to_bytes().to_vec()
+    }
+}
+
+#[cfg(feature = "crypto")]
+impl SnapshotSigner for Ed25519Signer {
+    fn signer_id(&self) -> &str {
+        &self.signer_id
+    }
+
+    fn sign(&self, hash: &Hash) -> Vec<u8> {
+        use ed25519_dalek::Signer;
+        self.key.sign(hash.as_bytes()).to_bytes().to_vec()
+    }
+
+    fn verify(&self, peer: &[u8], hash: &Hash, signature: &[u8]) -> bool {
+        let Ok(key) = ed25519_dalek::VerifyingKey::try_from(peer) else {
+            return false;
+        };
+        let Ok(signature) = ed25519_dalek::Signature::from_slice(signature) else {
+            return false;
+        };
+        key.verify_strict(hash.as_bytes(), &signature).is_ok()
+    }
+}
+
 /// Manages snapshot creation and retrieval.
 pub struct SnapshotManager {
     /// All known snapshots, indexed by ID.
@@ -161,6 +413,9 @@ pub struct SnapshotManager {
 
     /// Configuration for snapshot creation.
     config: SnapshotConfig,
+
+    /// Signs created snapshots and verifies peers' signatures, if set.
+    signer: Option<Box<dyn SnapshotSigner>>,
 }
 
 /// Configuration for snapshot management.
@@ -198,6 +453,7 @@ impl SnapshotManager {
             by_creator: HashMap::new(),
             latest: None,
             config: SnapshotConfig::default(),
+            signer: None,
         }
     }
 
@@ -208,14 +464,51 @@ impl SnapshotManager {
             by_creator: HashMap::new(),
             latest: None,
             config,
+            signer: None,
         }
     }
 
+    /// Sign snapshots with `signer` and use it to verify peers' signatures.
+    pub fn with_signer(mut self, signer: Box<dyn SnapshotSigner>) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
     /// Get the configuration.
     pub fn config(&self) -> &SnapshotConfig {
         &self.config
     }
 
+    /// Get the signer, if one is configured.
+    pub fn signer(&self) -> Option<&dyn SnapshotSigner> {
+        self.signer.as_deref()
+    }
+
+    /// Sign a snapshot if a signer is configured.
+    pub fn sign(&self, snapshot: &mut Snapshot) {
+        if let Some(signer) = &self.signer {
+            snapshot.sign(signer.as_ref());
+        }
+    }
+
+    /// Check a snapshot's integrity and that it was signed by a peer in
+    /// `trusted`.
+    pub fn verify(&self, snapshot: &Snapshot, trusted: &TrustSet) -> Result<(), SnapshotError> {
+        snapshot.verify_integrity()?;
+
+        let signature = snapshot.signature.as_ref().ok_or(SnapshotError::Unsigned)?;
+        let key = trusted
+            .key(&signature.signer)
+            .ok_or_else(|| SnapshotError::UntrustedSigner(signature.signer.clone()))?;
+        let signer = self.signer.as_ref().ok_or(SnapshotError::NoSigner)?;
+
+        if signer.verify(key, &snapshot.content_hash, &signature.bytes) {
+            Ok(())
+        } else {
+            Err(SnapshotError::InvalidSignature(signature.signer.clone()))
+        }
+    }
+
     /// Store a new snapshot.
     pub fn store(&mut self, snapshot: Snapshot) -> Hash {
         let id = snapshot.id;
@@ -244,11 +537,28 @@ impl SnapshotManager {
         id
     }
 
+    /// Check a snapshot received whole or in chunks, and store it.
+    ///
+    /// Chunks are checked against their manifest as the snapshot is
+    /// assembled; either way its content hash must match.
+    pub fn import(&mut self, snapshot: impl Into<SnapshotForm>) -> Result<Hash, SnapshotError> {
+        let snapshot = snapshot.into().into_snapshot()?;
+        snapshot.verify_integrity()?;
+        Ok(self.store(snapshot))
+    }
+
     /// Get a snapshot by ID.
     pub fn get(&self, id: &Hash) -> Option<&Snapshot> {
         self.snapshots.get(id)
     }
 
+    /// Split a stored snapshot into chunks of `chunk_size` bytes, for
+    /// sending to a peer.
+    pub fn chunked(&self, id: &Hash, chunk_size: usize) -> Option<ChunkedSnapshot> {
+        self.get(id)
+            .map(|snapshot| ChunkedSnapshot::new(snapshot, chunk_size))
+    }
+
     /// Get the latest snapshot.
     pub fn latest(&self) -> Option<&Snapshot> {
         self.latest.and_then(|id| self.snapshots.get(&id))
@@ -366,6 +676,30 @@ mod tests {
         assert_eq!(snapshot.creator, "r1");
     }
 
+    #[test]
+    fn test_content_hash_covers_bootstrap_fields() {
+        let vv = VersionVector::from_entries([("r1".to_string(), 10)]);
+        let roots = vec![Hasher::hash(b"root1")];
+        let a = Snapshot::new(vv.clone(), roots.clone(), b"ab".to_vec(), "r1", 100);
+        let b = Snapshot::new(vv.clone(), roots.clone(), b"ab".to_vec(), "r2", 200)
+            .with_metadata("note", "copy");
+
+        // Creator, time and metadata are not part of the content
+        assert_ne!(a.id, b.id);
+        assert_eq!(a.content_hash, b.content_hash);
+        a.verify_integrity().unwrap();
+
+        let c = Snapshot::new(vv, vec![], b"ab".to_vec(), "r1", 100);
+        assert_ne!(a.content_hash, c.content_hash);
+
+        let mut tampered = a.clone();
+        tampered.superseded_roots.push(Hasher::hash(b"root2"));
+        assert!(matches!(
+            tampered.verify_integrity(),
+            Err(SnapshotError::IntegrityFailure(_))
+        ));
+    }
+
     #[test]
     fn test_snapshot_covers() {
         let vv1 = VersionVector::from_entries([("r1".to_string(), 10), ("r2".to_string(), 5)]);
@@ -391,6 +725,20 @@ mod tests {
         assert_eq!(recovered.version_vector, snapshot.version_vector);
     }
 
+    #[test]
+    fn test_snapshot_to_anchor_node() {
+        let vv = VersionVector::from_entries([("r1".to_string(), 10)]);
+        let snapshot = Snapshot::new(vv, vec![], b"data".to_vec(), "r1", 100);
+
+        let node = snapshot.to_anchor_node().unwrap();
+        assert!(node.payload.is_snapshot_anchor());
+
+        let (id, vv) = Snapshot::read_anchor(&node).unwrap();
+        assert_eq!(id, snapshot.id);
+        assert_eq!(vv, snapshot.version_vector);
+        assert!(Snapshot::from_merkle_node(&node).is_err());
+    }
+
     #[test]
     fn test_snapshot_manager_store_and_get() {
         let mut manager = SnapshotManager::new();
@@ -2,6 +2,25 @@
 //!
 //! The stability monitor tracks which updates have been delivered to
 //! all known replicas, enabling safe pruning of the DAG history.
+//!
+//! A peer that goes away for good would pin the stable frontier at its last
+//! report. With [`StabilityConfig::peer_timeout`] set,
+//! [`check_liveness`](StabilityMonitor::check_liveness) evicts peers that
+//! stopped reporting, and [`evict_peer`](StabilityMonitor::evict_peer) does
+//! so by hand. An evicted peer counts again once it reports a frontier at
+//! or past the stable one; one that comes back behind it may have missed
+//! history that was compacted since, and is flagged as
+//! [`PeerLiveness::NeedsBootstrap`] instead.
+//!
+//! Frontier updates can carry just what changed since the last vector sent
+//! to a peer, from
+//! [`create_frontier_delta`](StabilityMonitor::create_frontier_delta). The
+//! receiver merges a delta into the frontier it knows for the sender. If
+//! it has none, the delta alone stands for the frontier; that understates
+//! it, which holds back stability but never makes it unsafe. Senders start
+//! over with a full vector after
+//! [`forget_sent_frontier`](StabilityMonitor::forget_sent_frontier), as
+//! when a peer reconnects.
 
 use crate::version_vector::VersionVector;
 use mdcs_merkle::Hash;
@@ -14,7 +33,8 @@ pub struct FrontierUpdate {
     /// The peer that sent this update.
     pub peer_id: String,
 
-    /// The peer's current version vector.
+    /// The peer's current version vector, or with `is_delta` the entries
+    /// that grew since the last one it sent us.
     pub version_vector: VersionVector,
 
     /// The peer's current DAG heads.
@@ -22,6 +42,10 @@ pub struct FrontierUpdate {
 
     /// Timestamp of the update.
     pub timestamp: u64,
+
+    /// Whether `version_vector` is a delta against the last one sent.
+    #[serde(default)]
+    pub is_delta: bool,
 }
 
 /// State of stability tracking for a single item.
@@ -43,6 +67,20 @@ pub enum StabilityState {
     Unknown,
 }
 
+/// Whether a tracked peer's frontier counts towards stability.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PeerLiveness {
+    /// Reporting; its frontier holds back the stable frontier.
+    Live,
+
+    /// Timed out or evicted by hand; left out of stability and quorum.
+    Evicted,
+
+    /// Came back behind the stable frontier, so it must bootstrap from a
+    /// snapshot before it counts again.
+    NeedsBootstrap,
+}
+
 /// Monitors stability across replicas for safe compaction decisions.
 ///
 /// Stability is achieved when an update has been delivered to all
@@ -60,6 +98,15 @@ pub struct StabilityMonitor {
     /// Timestamp of last update from each peer.
     last_update: HashMap<String, u64>,
 
+    /// Read-only replicas, left out of stability and quorum.
+    observers: HashSet<String>,
+
+    /// Tracked peers left out of stability and quorum, and why.
+    evicted: HashMap<String, PeerLiveness>,
+
+    /// The local frontier last sent to each peer, for deltas.
+    sent_frontiers: HashMap<String, VersionVector>,
+
     /// Our current version vector.
     local_frontier: VersionVector,
 
@@ -87,6 +134,11 @@ pub struct StabilityConfig {
 
     /// Quorum fraction (0.0 - 1.0) if not requiring all peers.
     pub quorum_fraction: f64,
+
+    /// How long a peer may go without a frontier update before
+    /// [`StabilityMonitor::check_liveness`] evicts it. `None` keeps peers
+    /// until they are removed.
+    pub peer_timeout: Option<u64>,
 }
 
 impl Default for StabilityConfig {
@@ -96,6 +148,7 @@ impl Default for StabilityConfig {
             max_frontier_age: 10000,
             require_all_peers: true,
             quorum_fraction: 0.67,
+            peer_timeout: None,
         }
     }
 }
@@ -108,6 +161,9 @@ impl StabilityMonitor {
             peer_frontiers: HashMap::new(),
             peer_heads: HashMap::new(),
             last_update: HashMap::new(),
+            observers: HashSet::new(),
+            evicted: HashMap::new(),
+            sent_frontiers: HashMap::new(),
             local_frontier: VersionVector::new(),
             local_heads: Vec::new(),
             stable_frontier: VersionVector::new(),
@@ -122,6 +178,9 @@ impl StabilityMonitor {
             peer_frontiers: HashMap::new(),
             peer_heads: HashMap::new(),
             last_update: HashMap::new(),
+            observers: HashSet::new(),
+            evicted: HashMap::new(),
+            sent_frontiers: HashMap::new(),
             local_frontier: VersionVector::new(),
             local_heads: Vec::new(),
             stable_frontier: VersionVector::new(),
@@ -142,21 +201,135 @@ impl StabilityMonitor {
     }
 
     /// Update a peer's frontier.
-    pub fn update_peer_frontier(&mut self, update: FrontierUpdate) {
+    ///
+    /// Updates from observers are ignored. An evicted peer is reinstated if
+    /// its frontier dominates the stable frontier; otherwise it stays left
+    /// out and is flagged as needing a bootstrap, so it can't roll the
+    /// stable frontier back. Returns the peer's liveness after the update,
+    /// or `None` for an observer.
+    pub fn update_peer_frontier(&mut self, mut update: FrontierUpdate) -> Option<PeerLiveness> {
+        if self.observers.contains(&update.peer_id) {
+            return None;
+        }
+        if update.is_delta {
+            if let Some(known) = self.peer_frontiers.get(&update.peer_id) {
+                update.version_vector = known.merged(&update.version_vector);
+            }
+        }
+        let liveness = match self.evicted.get_mut(&update.peer_id) {
+            Some(_) if update.version_vector.dominates(&self.stable_frontier) => {
+                self.evicted.remove(&update.peer_id);
+                PeerLiveness::Live
+            }
+            Some(liveness) => {
+                *liveness = PeerLiveness::NeedsBootstrap;
+                PeerLiveness::NeedsBootstrap
+            }
+            None => PeerLiveness::Live,
+        };
         self.peer_frontiers
             .insert(update.peer_id.clone(), update.version_vector);
         self.peer_heads.insert(update.peer_id.clone(), update.heads);
         self.last_update
             .insert(update.peer_id.clone(), update.timestamp);
         self.recompute_stable_frontier();
+        Some(liveness)
     }
 
     /// Remove a peer from tracking.
     pub fn remove_peer(&mut self, peer_id: &str) {
         self.peer_frontiers.remove(peer_id);
+        self.sent_frontiers.remove(peer_id);
         self.peer_heads.remove(peer_id);
         self.last_update.remove(peer_id);
+        self.evicted.remove(peer_id);
+        self.recompute_stable_frontier();
+    }
+
+    /// Leave a peer out of stability and quorum until it reports a frontier
+    /// that dominates the stable frontier.
+    ///
+    /// Returns `false` if the peer is not tracked or already evicted.
+    pub fn evict_peer(&mut self, peer_id: &str) -> bool {
+        if !self.peer_frontiers.contains_key(peer_id) || self.evicted.contains_key(peer_id) {
+            return false;
+        }
+        self.evicted
+            .insert(peer_id.to_string(), PeerLiveness::Evicted);
         self.recompute_stable_frontier();
+        true
+    }
+
+    /// Evict live peers whose last frontier update is older than
+    /// `peer_timeout`, returning them sorted so callers can warn about them.
+    ///
+    /// Does nothing without a `peer_timeout`.
+    pub fn check_liveness(&mut self, current_time: u64) -> Vec<String> {
+        let Some(timeout) = self.config.peer_timeout else {
+            return Vec::new();
+        };
+        let mut timed_out: Vec<_> = self
+            .last_update
+            .iter()
+            .filter(|(peer_id, _)| !self.evicted.contains_key(*peer_id))
+            .filter(|(_, &update_time)| current_time.saturating_sub(update_time) > timeout)
+            .map(|(peer_id, _)| peer_id.clone())
+            .collect();
+        timed_out.sort();
+        for peer_id in &timed_out {
+            self.evicted.insert(peer_id.clone(), PeerLiveness::Evicted);
+        }
+        if !timed_out.is_empty() {
+            self.recompute_stable_frontier();
+        }
+        timed_out
+    }
+
+    /// Get a tracked peer's liveness.
+    pub fn liveness(&self, peer_id: &str) -> Option<PeerLiveness> {
+        if !self.peer_frontiers.contains_key(peer_id) {
+            return None;
+        }
+        Some(
+            self.evicted
+                .get(peer_id)
+                .copied()
+                .unwrap_or(PeerLiveness::Live),
+        )
+    }
+
+    /// Get the tracked peers that count towards stability.
+    pub fn live_peers(&self) -> Vec<&String> {
+        self.live_frontiers().map(|(peer_id, _)| peer_id).collect()
+    }
+
+    /// Get the tracked peers that are evicted.
+    pub fn evicted_peers(&self) -> Vec<&String> {
+        self.evicted.keys().collect()
+    }
+
+    /// Track a replica as an observer.
+    ///
+    /// Observers only receive updates, so they never hold back the stable
+    /// frontier or count towards quorum. Any frontier already tracked for
+    /// the replica is dropped.
+    pub fn register_observer(&mut self, peer_id: impl Into<String>) {
+        let peer_id = peer_id.into();
+        self.remove_peer(&peer_id);
+        self.observers.insert(peer_id);
+    }
+
+    /// Start tracking an observer as a full peer, from its next frontier
+    /// update.
+    ///
+    /// Returns `false` if the replica was not an observer.
+    pub fn promote_observer(&mut self, peer_id: &str) -> bool {
+        self.observers.remove(peer_id)
+    }
+
+    /// Check if a replica is tracked as an observer.
+    pub fn is_observer(&self, peer_id: &str) -> bool {
+        self.observers.contains(peer_id)
     }
 
     /// Get the list of tracked peers.
@@ -215,7 +388,7 @@ impl StabilityMonitor {
         }
 
         // Check peer delivery
-        for (peer_id, frontier) in &self.peer_frontiers {
+        for (peer_id, frontier) in self.live_frontiers() {
             if frontier.dominates(vv) {
                 delivered_to.insert(peer_id.clone());
             } else {
@@ -236,8 +409,10 @@ impl StabilityMonitor {
     }
 
     /// Check if we have enough peers for meaningful stability.
+    ///
+    /// Evicted peers don't count.
     pub fn has_quorum(&self) -> bool {
-        let total_peers = self.peer_frontiers.len() + 1; // +1 for self
+        let total_peers = self.live_frontiers().count() + 1; // +1 for self
 
         if total_peers < self.config.min_peers_for_stability {
             return false;
@@ -270,19 +445,20 @@ impl StabilityMonitor {
         }
     }
 
+    /// Frontiers of the peers that aren't evicted.
+    fn live_frontiers(&self) -> impl Iterator<Item = (&String, &VersionVector)> {
+        self.peer_frontiers
+            .iter()
+            .filter(|(peer_id, _)| !self.evicted.contains_key(*peer_id))
+    }
+
     /// Recompute the stable frontier.
     fn recompute_stable_frontier(&mut self) {
-        if self.peer_frontiers.is_empty() {
-            // No peers - stable frontier is local frontier
-            self.stable_frontier = self.local_frontier.clone();
-            return;
-        }
-
-        // Start with local frontier
+        // Start with local frontier; with no live peers it is stable
         let mut stable = self.local_frontier.clone();
 
-        // Compute minimum with all peer frontiers
-        for frontier in self.peer_frontiers.values() {
+        // Compute minimum with all live peer frontiers
+        for (_, frontier) in self.live_frontiers() {
             stable = stable.min_with(frontier);
         }
 
@@ -298,6 +474,7 @@ impl StabilityMonitor {
 
         StabilityStats {
             peer_count: self.peer_frontiers.len(),
+            evicted_count: self.evicted.len(),
             local_operations: self.local_frontier.total_operations(),
             stable_operations: self.stable_frontier.total_operations(),
             unstable_operations: unstable_ops,
@@ -312,7 +489,27 @@ impl StabilityMonitor {
             version_vector: self.local_frontier.clone(),
             heads: self.local_heads.clone(),
             timestamp,
+            is_delta: false,
+        }
+    }
+
+    /// Create a frontier update for one peer, carrying only the entries
+    /// that grew since the last one sent to it, if any was.
+    pub fn create_frontier_delta(&mut self, peer_id: &str, timestamp: u64) -> FrontierUpdate {
+        let mut update = self.create_frontier_update(timestamp);
+        if let Some(sent) = self.sent_frontiers.get(peer_id) {
+            update.version_vector = self.local_frontier.difference(sent);
+            update.is_delta = true;
         }
+        self.sent_frontiers
+            .insert(peer_id.to_string(), self.local_frontier.clone());
+        update
+    }
+
+    /// Send the next frontier update to a peer in full, as when it
+    /// reconnects and may have lost the frontiers sent before.
+    pub fn forget_sent_frontier(&mut self, peer_id: &str) {
+        self.sent_frontiers.remove(peer_id);
     }
 }
 
@@ -320,6 +517,7 @@ impl StabilityMonitor {
 #[derive(Clone, Debug)]
 pub struct StabilityStats {
     pub peer_count: usize,
+    pub evicted_count: usize,
     pub local_operations: u64,
     pub stable_operations: u64,
     pub unstable_operations: u64,
@@ -356,6 +554,7 @@ mod tests {
             version_vector: peer_vv,
             heads: vec![],
             timestamp: 100,
+            is_delta: false,
         });
 
         // Operations up to (r1:7, r2:5) should be stable
@@ -381,6 +580,7 @@ mod tests {
             version_vector: peer_vv,
             heads: vec![],
             timestamp: 100,
+            is_delta: false,
         });
 
         // Check state for operation r1:3 (stable)
@@ -410,6 +610,7 @@ mod tests {
             version_vector: VersionVector::new(),
             heads: vec![],
             timestamp: 100,
+            is_delta: false,
         });
 
         // At time 200, peer is not stale (within max_frontier_age of 10000)
@@ -445,10 +646,43 @@ mod tests {
             version_vector: VersionVector::new(),
             heads: vec![],
             timestamp: 100,
+            is_delta: false,
         });
         assert!(monitor.has_quorum());
     }
 
+    #[test]
+    fn test_observers_are_left_out() {
+        let mut monitor = StabilityMonitor::new("r1");
+        monitor.update_local_frontier(
+            VersionVector::from_entries([("r1".to_string(), 10)]),
+            vec![],
+        );
+        monitor.register_observer("watcher");
+
+        let update = |peer_id: &str, seq| FrontierUpdate {
+            peer_id: peer_id.to_string(),
+            version_vector: VersionVector::from_entries([("r1".to_string(), seq)]),
+            heads: vec![],
+            timestamp: 100,
+            is_delta: false,
+        };
+        monitor.update_peer_frontier(update("r2", 10));
+        monitor.update_peer_frontier(update("watcher", 0));
+
+        // The observer lagging behind doesn't hold back stability
+        assert_eq!(monitor.peer_count(), 1);
+        assert!(monitor.is_operation_stable("r1", 10));
+        assert!(monitor.has_quorum());
+
+        // Once promoted, it counts from its next update
+        assert!(monitor.promote_observer("watcher"));
+        assert!(!monitor.is_observer("watcher"));
+        monitor.update_peer_frontier(update("watcher", 4));
+        assert_eq!(monitor.peer_count(), 2);
+        assert!(!monitor.is_operation_stable("r1", 5));
+    }
+
     #[test]
     fn test_create_frontier_update() {
         let mut monitor = StabilityMonitor::new("r1");
@@ -463,4 +697,124 @@ mod tests {
         assert_eq!(update.heads, heads);
         assert_eq!(update.timestamp, 100);
     }
+
+    fn report(peer_id: &str, seq: u64, timestamp: u64) -> FrontierUpdate {
+        FrontierUpdate {
+            peer_id: peer_id.to_string(),
+            version_vector: VersionVector::from_entries([("r1".to_string(), seq)]),
+            heads: vec![],
+            timestamp,
+            is_delta: false,
+        }
+    }
+
+    fn three_replicas(peer_timeout: u64) -> StabilityMonitor {
+        let config = StabilityConfig {
+            peer_timeout: Some(peer_timeout),
+            ..Default::default()
+        };
+        let mut monitor = StabilityMonitor::with_config("r1", config);
+        monitor.update_local_frontier(
+            VersionVector::from_entries([("r1".to_string(), 10)]),
+            vec![],
+        );
+        monitor.update_peer_frontier(report("r2", 5, 0));
+        monitor.update_peer_frontier(report("r3", 5, 0));
+        monitor
+    }
+
+    #[test]
+    fn test_silent_peer_is_evicted_after_timeout() {
+        let mut monitor = three_replicas(1000);
+
+        // r3 stops reporting while r2 catches up
+        monitor.update_peer_frontier(report("r2", 10, 900));
+        assert!(!monitor.is_operation_stable("r1", 10));
+        assert!(monitor.check_liveness(1000).is_empty());
+
+        assert_eq!(monitor.check_liveness(1001), vec!["r3".to_string()]);
+        assert_eq!(monitor.liveness("r3"), Some(PeerLiveness::Evicted));
+        assert!(monitor.is_operation_stable("r1", 10));
+        assert_eq!(monitor.live_peers(), vec!["r2"]);
+        assert_eq!(monitor.stats().evicted_count, 1);
+
+        // Already evicted peers aren't reported again
+        assert!(monitor.check_liveness(5000).contains(&"r2".to_string()));
+        assert!(!monitor.check_liveness(5000).contains(&"r3".to_string()));
+    }
+
+    #[test]
+    fn test_evicted_peer_returns() {
+        let mut monitor = three_replicas(1000);
+        assert!(monitor.evict_peer("r3"));
+        assert!(!monitor.evict_peer("r3"));
+        assert!(!monitor.evict_peer("unknown"));
+        monitor.update_peer_frontier(report("r2", 10, 100));
+        assert!(monitor.is_operation_stable("r1", 10));
+
+        // Back with the frontier it had: it may have missed compacted
+        // history, so it stays out and the stable frontier holds
+        assert_eq!(
+            monitor.update_peer_frontier(report("r3", 5, 200)),
+            Some(PeerLiveness::NeedsBootstrap)
+        );
+        assert!(monitor.is_operation_stable("r1", 10));
+        assert_eq!(monitor.liveness("r3"), Some(PeerLiveness::NeedsBootstrap));
+
+        // Once bootstrapped to the stable frontier, it counts again
+        assert_eq!(
+            monitor.update_peer_frontier(report("r3", 10, 300)),
+            Some(PeerLiveness::Live)
+        );
+        assert_eq!(monitor.live_peers().len(), 2);
+        monitor.update_local_frontier(
+            VersionVector::from_entries([("r1".to_string(), 12)]),
+            vec![],
+        );
+        monitor.update_peer_frontier(report("r2", 12, 400));
+        assert!(!monitor.is_operation_stable("r1", 11));
+    }
+
+    #[test]
+    fn test_quorum_counts_live_peers() {
+        let config = StabilityConfig {
+            min_peers_for_stability: 3,
+            ..Default::default()
+        };
+        let mut monitor = StabilityMonitor::with_config("r1", config);
+        monitor.update_peer_frontier(report("r2", 0, 0));
+        monitor.update_peer_frontier(report("r3", 0, 0));
+        assert!(monitor.has_quorum());
+
+        monitor.evict_peer("r3");
+        assert!(!monitor.has_quorum());
+    }
+
+    #[test]
+    fn test_frontier_deltas() {
+        let mut sender = StabilityMonitor::new("r1");
+        let mut receiver = StabilityMonitor::new("r2");
+        let frontier =
+            |r1, r3| VersionVector::from_entries([("r1".to_string(), r1), ("r3".to_string(), r3)]);
+
+        // The first update is in full
+        sender.update_local_frontier(frontier(5, 2), vec![]);
+        let update = sender.create_frontier_delta("r2", 100);
+        assert!(!update.is_delta);
+        receiver.update_peer_frontier(update);
+
+        // Later ones only carry what grew
+        sender.update_local_frontier(frontier(8, 2), vec![]);
+        let update = sender.create_frontier_delta("r2", 200);
+        assert!(update.is_delta);
+        assert_eq!(
+            update.version_vector,
+            VersionVector::from_entries([("r1".to_string(), 8)])
+        );
+        receiver.update_peer_frontier(update);
+        assert_eq!(receiver.peer_frontiers["r1"], frontier(8, 2));
+
+        sender.forget_sent_frontier("r2");
+        assert!(!sender.create_frontier_delta("r2", 300).is_delta);
+    }
 }
@@ -3,9 +3,39 @@
 //! A version vector summarizes the causal context by tracking the highest
 //! sequence number seen from each replica. This is more compact than storing
 //! all individual dots.
+//!
+//! Vectors travel in every frontier update and snapshot, so besides serde
+//! they have a compact binary form, [`VersionVector::to_bytes`]: the entry
+//! count, then for each entry in replica order the length of the prefix
+//! its replica ID shares with the previous one, the rest of the ID, and
+//! the sequence number, all lengths and numbers as LEB128 varints. Replica
+//! IDs usually share long prefixes, so most of each is left out.
 
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Why bytes could not be decoded as a version vector.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum VectorDecodeError {
+    #[error("Version vector ends early")]
+    Truncated,
+
+    #[error("Varint does not fit in 64 bits")]
+    Overflow,
+
+    #[error("Replica ID shares {shared} bytes with a {previous}-byte predecessor")]
+    InvalidPrefix { shared: usize, previous: usize },
+
+    #[error("Replica ID is not valid UTF-8")]
+    InvalidUtf8,
+
+    #[error("Replica ID {0} is out of order")]
+    Unsorted(String),
+
+    #[error("{0} bytes left after the version vector")]
+    TrailingBytes(usize),
+}
 
 /// A single entry in a version vector.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -54,6 +84,14 @@ impl VersionVector {
         }
     }
 
+    /// Record that `sequence` from a replica has been seen, keeping the
+    /// entry at the highest sequence number so far.
+    pub fn observe(&mut self, replica_id: &str, sequence: u64) {
+        if sequence > self.get(replica_id) {
+            self.entries.insert(replica_id.to_string(), sequence);
+        }
+    }
+
     /// Increment the sequence number for a replica, returning the new value.
     pub fn increment(&mut self, replica_id: impl Into<String>) -> u64 {
         let replica_id = replica_id.into();
@@ -81,10 +119,16 @@ impl VersionVector {
     }
 
     /// Check if two vectors are concurrent (neither dominates the other).
-    pub fn is_concurrent_with(&self, other: &VersionVector) -> bool {
+    pub fn concurrent_with(&self, other: &VersionVector) -> bool {
         !self.dominates(other) && !other.dominates(self)
     }
 
+    /// Check if two vectors are concurrent (neither dominates the other).
+    #[deprecated(note = "use `concurrent_with`")]
+    pub fn is_concurrent_with(&self, other: &VersionVector) -> bool {
+        self.concurrent_with(other)
+    }
+
     /// Merge with another version vector (component-wise max).
     pub fn merge(&mut self, other: &VersionVector) {
         for (replica_id, &seq) in &other.entries {
@@ -93,13 +137,20 @@ impl VersionVector {
         }
     }
 
-    /// Create a merged version vector without modifying self.
-    pub fn merged_with(&self, other: &VersionVector) -> VersionVector {
+    /// Create a merged version vector without modifying self: the least
+    /// vector dominating both.
+    pub fn merged(&self, other: &VersionVector) -> VersionVector {
         let mut result = self.clone();
         result.merge(other);
         result
     }
 
+    /// Create a merged version vector without modifying self.
+    #[deprecated(note = "use `merged`")]
+    pub fn merged_with(&self, other: &VersionVector) -> VersionVector {
+        self.merged(other)
+    }
+
     /// Get the minimum across all replicas in both vectors.
     /// This represents the "stable" point that all replicas have seen.
     pub fn min_with(&self, other: &VersionVector) -> VersionVector {
@@ -171,6 +222,21 @@ impl VersionVector {
         self.get(replica_id) >= sequence
     }
 
+    /// Get the entries where self is ahead of other.
+    ///
+    /// Merging the result into `other` gives `self.merged(other)`, so a
+    /// peer known to have `other` can be sent just the difference.
+    pub fn difference(&self, other: &VersionVector) -> VersionVector {
+        VersionVector {
+            entries: self
+                .entries
+                .iter()
+                .filter(|(replica_id, &seq)| seq > other.get(replica_id))
+                .map(|(replica_id, &seq)| (replica_id.clone(), seq))
+                .collect(),
+        }
+    }
+
     /// Get the difference: operations in self but not in other.
     /// Returns (replica_id, start_seq, end_seq) ranges.
     pub fn diff(&self, other: &VersionVector) -> Vec<(String, u64, u64)> {
@@ -185,6 +251,87 @@ impl VersionVector {
 
         diffs
     }
+
+    /// Encode the vector in its compact binary form; see the
+    /// [module docs](self).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, self.entries.len() as u64);
+        let mut previous: &[u8] = &[];
+        for (replica_id, &sequence) in &self.entries {
+            let id = replica_id.as_bytes();
+            let shared = id.iter().zip(previous).take_while(|(a, b)| a == b).count();
+            write_varint(&mut bytes, shared as u64);
+            write_varint(&mut bytes, (id.len() - shared) as u64);
+            bytes.extend_from_slice(&id[shared..]);
+            write_varint(&mut bytes, sequence);
+            previous = id;
+        }
+        bytes
+    }
+
+    /// Decode a vector encoded with [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, VectorDecodeError> {
+        let mut input = bytes;
+        let count = read_varint(&mut input)?;
+        let mut entries = BTreeMap::new();
+        let mut previous: Vec<u8> = Vec::new();
+        for _ in 0..count {
+            let shared = read_varint(&mut input)? as usize;
+            if shared > previous.len() {
+                return Err(VectorDecodeError::InvalidPrefix {
+                    shared,
+                    previous: previous.len(),
+                });
+            }
+            let suffix_len = read_varint(&mut input)? as usize;
+            if suffix_len > input.len() {
+                return Err(VectorDecodeError::Truncated);
+            }
+            let (suffix, rest) = input.split_at(suffix_len);
+            input = rest;
+            let mut id = previous[..shared].to_vec();
+            id.extend_from_slice(suffix);
+            let sequence = read_varint(&mut input)?;
+
+            let replica_id =
+                String::from_utf8(id.clone()).map_err(|_| VectorDecodeError::InvalidUtf8)?;
+            if !entries.is_empty() && id <= previous {
+                return Err(VectorDecodeError::Unsorted(replica_id));
+            }
+            entries.insert(replica_id, sequence);
+            previous = id;
+        }
+        if !input.is_empty() {
+            return Err(VectorDecodeError::TrailingBytes(input.len()));
+        }
+        Ok(VersionVector { entries })
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<u64, VectorDecodeError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = input.split_first().ok_or(VectorDecodeError::Truncated)?;
+        *input = rest;
+        let bits = u64::from(byte & 0x7f);
+        if shift == 63 && bits > 1 {
+            return Err(VectorDecodeError::Overflow);
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(VectorDecodeError::Overflow)
 }
 
 #[cfg(test)]
@@ -221,8 +368,8 @@ mod tests {
         let vv1 = VersionVector::from_entries([("r1".to_string(), 5), ("r2".to_string(), 3)]);
         let vv2 = VersionVector::from_entries([("r1".to_string(), 3), ("r2".to_string(), 5)]);
 
-        assert!(vv1.is_concurrent_with(&vv2));
-        assert!(vv2.is_concurrent_with(&vv1));
+        assert!(vv1.concurrent_with(&vv2));
+        assert!(vv2.concurrent_with(&vv1));
     }
 
     #[test]
@@ -230,7 +377,7 @@ mod tests {
         let vv1 = VersionVector::from_entries([("r1".to_string(), 5), ("r2".to_string(), 3)]);
         let vv2 = VersionVector::from_entries([("r1".to_string(), 3), ("r2".to_string(), 7)]);
 
-        let merged = vv1.merged_with(&vv2);
+        let merged = vv1.merged(&vv2);
         assert_eq!(merged.get("r1"), 5);
         assert_eq!(merged.get("r2"), 7);
     }
@@ -250,9 +397,63 @@ mod tests {
         let vv1 = VersionVector::from_entries([("r1".to_string(), 10), ("r2".to_string(), 5)]);
         let vv2 = VersionVector::from_entries([("r1".to_string(), 7), ("r2".to_string(), 5)]);
 
-        let diff = vv1.diff(&vv2);
-        assert_eq!(diff.len(), 1);
-        assert_eq!(diff[0], ("r1".to_string(), 8, 10));
+        let ranges = vv1.diff(&vv2);
+        assert_eq!(ranges, vec![("r1".to_string(), 8, 10)]);
+
+        let diff = vv1.difference(&vv2);
+        assert_eq!(diff, VersionVector::from_entries([("r1".to_string(), 10)]));
+        assert_eq!(vv2.merged(&diff), vv1);
+        assert!(vv2.difference(&vv1).is_empty());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_deprecated_aliases() {
+        let vv1 = VersionVector::from_entries([("r1".to_string(), 2)]);
+        let vv2 = VersionVector::from_entries([("r2".to_string(), 1)]);
+        assert!(vv1.is_concurrent_with(&vv2));
+        assert_eq!(vv1.merged_with(&vv2), vv1.merged(&vv2));
+    }
+
+    #[test]
+    fn test_version_vector_bytes() {
+        let vv = VersionVector::from_entries([
+            ("replica-a".to_string(), 5),
+            ("replica-ab".to_string(), 300),
+            ("replica-b".to_string(), u64::MAX),
+        ]);
+
+        let bytes = vv.to_bytes();
+        // The shared "replica-" prefix is written once
+        assert_eq!(bytes.len(), 1 + (2 + 9 + 1) + (2 + 1 + 2) + (2 + 1 + 10));
+        assert_eq!(VersionVector::from_bytes(&bytes).unwrap(), vv);
+        assert_eq!(
+            VersionVector::from_bytes(&VersionVector::new().to_bytes()).unwrap(),
+            VersionVector::new()
+        );
+
+        assert_eq!(
+            VersionVector::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(VectorDecodeError::Truncated)
+        );
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            VersionVector::from_bytes(&trailing),
+            Err(VectorDecodeError::TrailingBytes(1))
+        );
+        // The first entry can't share a prefix
+        assert_eq!(
+            VersionVector::from_bytes(&[1, 3, 1, b'a', 1]),
+            Err(VectorDecodeError::InvalidPrefix {
+                shared: 3,
+                previous: 0
+            })
+        );
+        assert_eq!(
+            VersionVector::from_bytes(&[2, 0, 1, b'b', 1, 0, 1, b'a', 1]),
+            Err(VectorDecodeError::Unsorted("a".to_string()))
+        );
     }
 
     #[test]
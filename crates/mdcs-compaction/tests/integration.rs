@@ -5,113 +5,20 @@
 //! - Deterministic rebuild: State rebuilt from snapshot + deltas matches full replay
 //! - Stability tracking across replicas
 //! - Safe pruning with verification
+//! - Compacting replicas and late joiners bootstrapping from their snapshots
 
 use mdcs_compaction::{
-    CompactionConfig, Compactor, FrontierUpdate, Pruner, PruningPolicy, PruningVerifier, Snapshot,
-    StabilityConfig, StabilityMonitor, VersionVector,
+    CompactingReplica, CompactionConfig, Compactor, FrontierUpdate, Pruner, PruningPolicy,
+    PruningVerifier, Snapshot, StabilityConfig, StabilityMonitor, VersionVector,
+};
+use mdcs_core::clock::ManualClock;
+use mdcs_core::orset::ORSet;
+use mdcs_delta::{orset_mutators, CausalReplica, DeltaInterval};
+use mdcs_merkle::{
+    DAGStore, DAGSyncer, Hash, MemoryDAGStore, NodeBuilder, Payload, SyncConfig, SyncError,
 };
-use mdcs_merkle::{DAGStore, Hash, NodeBuilder, Payload};
 use std::collections::HashSet;
-
-/// Helper to implement PrunableStore for tests
-mod prunable {
-    use mdcs_compaction::PrunableStore;
-    use mdcs_merkle::{DAGError, DAGStore, Hash, MemoryDAGStore, MerkleNode};
-
-    /// Wrapper that tracks "pruned" nodes
-    pub struct PrunableMemoryStore {
-        pub inner: MemoryDAGStore,
-        pub pruned: std::collections::HashSet<Hash>,
-    }
-
-    impl PrunableMemoryStore {
-        pub fn with_genesis(creator: &str) -> (Self, Hash) {
-            let (inner, genesis) = MemoryDAGStore::with_genesis(creator);
-            (
-                PrunableMemoryStore {
-                    inner,
-                    pruned: std::collections::HashSet::new(),
-                },
-                genesis,
-            )
-        }
-    }
-
-    impl DAGStore for PrunableMemoryStore {
-        fn get(&self, cid: &Hash) -> Option<&MerkleNode> {
-            if self.pruned.contains(cid) {
-                None
-            } else {
-                self.inner.get(cid)
-            }
-        }
-
-        fn put(&mut self, node: MerkleNode) -> Result<Hash, DAGError> {
-            self.inner.put(node)
-        }
-
-        fn put_unchecked(&mut self, node: MerkleNode) -> Result<Hash, DAGError> {
-            self.inner.put_unchecked(node)
-        }
-
-        fn heads(&self) -> Vec<Hash> {
-            self.inner
-                .heads()
-                .into_iter()
-                .filter(|h| !self.pruned.contains(h))
-                .collect()
-        }
-
-        fn contains(&self, cid: &Hash) -> bool {
-            !self.pruned.contains(cid) && self.inner.contains(cid)
-        }
-
-        fn ancestors(&self, cid: &Hash) -> std::collections::HashSet<Hash> {
-            self.inner
-                .ancestors(cid)
-                .into_iter()
-                .filter(|h| !self.pruned.contains(h))
-                .collect()
-        }
-
-        fn children(&self, cid: &Hash) -> Vec<Hash> {
-            self.inner
-                .children(cid)
-                .into_iter()
-                .filter(|h| !self.pruned.contains(h))
-                .collect()
-        }
-
-        fn topological_order(&self) -> Vec<Hash> {
-            self.inner
-                .topological_order()
-                .into_iter()
-                .filter(|h| !self.pruned.contains(h))
-                .collect()
-        }
-
-        fn missing_nodes(&self) -> std::collections::HashSet<Hash> {
-            self.inner.missing_nodes()
-        }
-
-        fn len(&self) -> usize {
-            self.inner.len().saturating_sub(self.pruned.len())
-        }
-    }
-
-    impl PrunableStore for PrunableMemoryStore {
-        fn remove(&mut self, cid: &Hash) -> Result<(), String> {
-            if self.inner.contains(cid) {
-                self.pruned.insert(*cid);
-                Ok(())
-            } else {
-                Err("Node not found".to_string())
-            }
-        }
-    }
-}
-
-use prunable::PrunableMemoryStore;
+use std::sync::Arc;
 
 // ============================================================================
 // No Resurrection Tests
@@ -127,7 +34,7 @@ fn test_no_resurrection_basic() {
     // 4. Node A is pruned
     // 5. Late-arriving add delta should NOT resurrect the item
 
-    let (mut store, genesis) = PrunableMemoryStore::with_genesis("test");
+    let (mut store, genesis) = MemoryDAGStore::with_genesis("test");
 
     // Node A: Add item
     let node_a = NodeBuilder::new()
@@ -158,7 +65,7 @@ fn test_no_resurrection_basic() {
     );
 
     // Prune node A
-    store.pruned.insert(cid_a);
+    store.prune(&cid_a).unwrap();
 
     // Verify A is no longer accessible
     assert!(!store.contains(&cid_a));
@@ -173,7 +80,7 @@ fn test_no_resurrection_basic() {
 /// Test no resurrection with concurrent branches.
 #[test]
 fn test_no_resurrection_concurrent_branches() {
-    let (mut store, genesis) = PrunableMemoryStore::with_genesis("test");
+    let (mut store, genesis) = MemoryDAGStore::with_genesis("test");
 
     // Branch 1: Add item
     let branch1 = NodeBuilder::new()
@@ -214,8 +121,8 @@ fn test_no_resurrection_concurrent_branches() {
     let snapshot = Snapshot::new(vv, vec![cid_merge], b"resolved_state".to_vec(), "test", 200);
 
     // Prune old branches
-    store.pruned.insert(cid_b1);
-    store.pruned.insert(cid_b2);
+    store.prune(&cid_b1).unwrap();
+    store.prune(&cid_b2).unwrap();
 
     // Merge still accessible
     assert!(store.contains(&cid_merge));
@@ -234,7 +141,7 @@ fn test_no_resurrection_concurrent_branches() {
 /// Test that state can be deterministically rebuilt from snapshot + deltas.
 #[test]
 fn test_deterministic_rebuild_from_snapshot() {
-    let (mut store, genesis) = PrunableMemoryStore::with_genesis("test");
+    let (mut store, genesis) = MemoryDAGStore::with_genesis("test");
 
     // Create initial chain: genesis -> a -> b -> c
     let node_a = NodeBuilder::new()
@@ -272,11 +179,17 @@ fn test_deterministic_rebuild_from_snapshot() {
         .with_timestamp(400)
         .with_creator("test")
         .build();
-    let _cid_d = store.put(node_d.clone()).unwrap();
+    let cid_d = store.put(node_d.clone()).unwrap();
 
     // Prune nodes before snapshot
-    store.pruned.insert(genesis);
-    store.pruned.insert(cid_a);
+    store.prune(&genesis).unwrap();
+    store.prune(&cid_a).unwrap();
+
+    // The pruned nodes are a boundary, not a gap
+    assert_eq!(store.pruned_boundary(), HashSet::from([genesis, cid_a]));
+    assert!(store.missing_nodes().is_empty());
+    assert_eq!(store.ancestors(&cid_d), HashSet::from([cid_b, cid_c]));
+    assert_eq!(store.topological_order(), vec![cid_b, cid_c, cid_d]);
 
     // Rebuild: Load snapshot state, then apply deltas c and d
     let mut rebuilt_state = snapshot.state_data.clone();
@@ -321,13 +234,13 @@ fn test_rebuild_matches_full_replay() {
         }
     }
 
-    let (mut store, genesis) = PrunableMemoryStore::with_genesis("test");
+    let (mut store, genesis) = MemoryDAGStore::with_genesis("test");
 
     // Full replay path
     let mut full_replay_state: i64 = 0;
 
     // Create operations
-    let ops = vec![
+    let ops = [
         b"inc:5".to_vec(),
         b"inc:3".to_vec(),
         b"dec:2".to_vec(),
@@ -403,6 +316,7 @@ fn test_stability_multi_replica() {
         ]),
         heads: vec![],
         timestamp: 100,
+        is_delta: false,
     });
 
     // Peer r3 is behind on both r1 and r2
@@ -415,6 +329,7 @@ fn test_stability_multi_replica() {
         ]),
         heads: vec![],
         timestamp: 100,
+        is_delta: false,
     });
 
     // Stable frontier should be min of all
@@ -450,6 +365,7 @@ fn test_stability_quorum() {
         version_vector: VersionVector::new(),
         heads: vec![],
         timestamp: 100,
+        is_delta: false,
     });
     assert!(monitor.has_quorum()); // 2 replicas, 50% quorum met
 }
@@ -469,7 +385,7 @@ fn test_compactor_full_workflow() {
     };
 
     let mut compactor = Compactor::with_config("test", config);
-    let (mut store, genesis) = PrunableMemoryStore::with_genesis("test");
+    let (mut store, genesis) = MemoryDAGStore::with_genesis("test");
 
     // Create some operations
     let mut prev = genesis;
@@ -497,6 +413,54 @@ fn test_compactor_full_workflow() {
     assert_eq!(compactor.stats().snapshots_created, 1);
 }
 
+/// Test that retention follows the compactor's clock.
+#[test]
+fn test_compactor_retention_follows_clock() {
+    let config = CompactionConfig {
+        pruning: PruningPolicy {
+            min_node_age: 1_000,
+            preserve_depth: 1,
+            preserve_genesis_path: false,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let clock = Arc::new(ManualClock::new(600));
+    let mut compactor = Compactor::with_config("test", config).with_clock(clock.clone());
+    let (mut store, genesis) = MemoryDAGStore::with_genesis("test");
+
+    let mut prev = genesis;
+    for i in 1..=5 {
+        let node = NodeBuilder::new()
+            .with_parent(prev)
+            .with_payload(Payload::delta(format!("op{}", i).into_bytes()))
+            .with_timestamp(i * 100)
+            .with_creator("test")
+            .build();
+        prev = store.put(node).unwrap();
+    }
+    compactor.update_local_frontier(
+        VersionVector::from_entries([("test".to_string(), 5)]),
+        vec![prev],
+    );
+
+    // Everything is younger than the retention period
+    let result = compactor
+        .compact(&mut store, || Ok(b"state".to_vec()))
+        .unwrap();
+    assert!(result.snapshot_created.is_some());
+    assert_eq!(result.nodes_pruned, 0);
+    assert_eq!(compactor.stats().last_compaction, Some(600));
+
+    // Once the clock passes it, the history below the snapshot goes
+    clock.advance(10_000);
+    let result = compactor
+        .compact(&mut store, || Ok(b"state".to_vec()))
+        .unwrap();
+    assert!(result.nodes_pruned > 0);
+    assert_eq!(compactor.stats().last_compaction, Some(10_600));
+}
+
 /// Test compactor bootstrap from snapshot.
 #[test]
 fn test_compactor_bootstrap() {
@@ -564,7 +528,7 @@ fn test_pruning_preserve_depth() {
     };
 
     let pruner = Pruner::with_policy(policy);
-    let (mut store, genesis) = PrunableMemoryStore::with_genesis("test");
+    let (mut store, genesis) = MemoryDAGStore::with_genesis("test");
 
     // Create chain: genesis -> a -> b -> c -> d -> e
     let mut prev = genesis;
@@ -588,7 +552,7 @@ fn test_pruning_preserve_depth() {
     let snapshot = Snapshot::new(vv, vec![snapshot_cid], b"state".to_vec(), "test", 400);
 
     // Identify prunable nodes
-    let prunable = pruner.identify_prunable(&store.inner, &snapshot, 1000);
+    let prunable = pruner.identify_prunable(&store, &snapshot, 1000);
 
     // With preserve_depth=2, nodes within 2 of head (e) should be preserved
     // Head is e (index 5), so d (4) and c (3) should be preserved
@@ -610,7 +574,7 @@ fn test_pruning_preserve_depth() {
 /// Test pruning verification catches missing references.
 #[test]
 fn test_pruning_verification() {
-    let (mut store, genesis) = PrunableMemoryStore::with_genesis("test");
+    let (mut store, genesis) = MemoryDAGStore::with_genesis("test");
 
     // Create a -> b
     let node_a = NodeBuilder::new()
@@ -636,7 +600,7 @@ fn test_pruning_verification() {
     let snapshot = Snapshot::new(vv, vec![cid_b], b"state".to_vec(), "test", 200);
 
     // This should fail because b references pruned a
-    let result = PruningVerifier::verify_no_resurrection(&store.inner, &pruned, &snapshot);
+    let result = PruningVerifier::verify_no_resurrection(&store, &pruned, &snapshot);
     assert!(result.is_err());
 }
 
@@ -688,3 +652,419 @@ fn test_version_vector_strictly_dominates() {
     assert!(vv1.dominates(&vv3));
     assert!(vv1.strictly_dominates(&vv3));
 }
+
+// ============================================================================
+// Snapshot Anchor Tests
+// ============================================================================
+
+/// Test that a late joiner bootstraps from a snapshot anchor in a pruned DAG.
+#[test]
+fn test_late_joiner_bootstraps_from_snapshot_anchor() {
+    /// Materialize a counter by summing deltas in topological order,
+    /// starting from an anchored snapshot if there is one.
+    fn materialize<S: DAGStore>(store: &S, snapshot_state: &dyn Fn(&Hash) -> i64) -> i64 {
+        let mut state = 0;
+        for cid in store.topological_order() {
+            match &store.get(&cid).unwrap().payload {
+                Payload::Delta(delta) => {
+                    state += i64::from_le_bytes(delta.as_slice().try_into().unwrap())
+                }
+                Payload::SnapshotAnchor { snapshot_hash, .. } => {
+                    state = snapshot_state(snapshot_hash)
+                }
+                Payload::Genesis | Payload::Snapshot(_) => {}
+            }
+        }
+        state
+    }
+
+    let (mut store, genesis) = MemoryDAGStore::with_genesis("old");
+    let mut compactor = Compactor::new("old");
+    let mut prev = genesis;
+    let mut counter: i64 = 0;
+    for i in 1..=10 {
+        counter += i;
+        let node = NodeBuilder::new()
+            .with_parent(prev)
+            .with_payload(Payload::delta(i.to_le_bytes().to_vec()))
+            .with_timestamp(i as u64)
+            .with_creator("old")
+            .build();
+        prev = store.put(node).unwrap();
+    }
+
+    compactor.update_local_frontier(
+        VersionVector::from_entries([("old".to_string(), 10)]),
+        vec![prev],
+    );
+    compactor.set_time(10);
+    let (snapshot_id, anchor) = compactor
+        .create_anchored_snapshot(&mut store, || Ok(counter.to_le_bytes().to_vec()))
+        .unwrap();
+    assert_eq!(store.heads(), vec![anchor]);
+
+    // Keep writing on top of the anchor
+    prev = anchor;
+    for i in 11..=13 {
+        counter += i;
+        let node = NodeBuilder::new()
+            .with_parent(prev)
+            .with_payload(Payload::delta(i.to_le_bytes().to_vec()))
+            .with_timestamp(i as u64)
+            .with_creator("old")
+            .build();
+        prev = store.put(node).unwrap();
+    }
+
+    // Prune the history below the snapshot; the anchor must survive
+    let pruner = Pruner::with_policy(PruningPolicy {
+        min_node_age: 0,
+        preserve_depth: 1,
+        preserve_genesis_path: false,
+        ..Default::default()
+    });
+    let snapshot = compactor.snapshots().get(&snapshot_id).unwrap();
+    let pruned = pruner.execute_prune(&mut store, snapshot, 100).pruned_cids;
+    assert!(pruned.contains(&genesis));
+    assert!(store.contains(&anchor));
+
+    let old = DAGSyncer::new(store);
+    let stored_state = |id: &Hash| {
+        let bytes = &compactor.snapshots().get(id).unwrap().state_data;
+        i64::from_le_bytes(bytes.as_slice().try_into().unwrap())
+    };
+    let old_state = materialize(old.store(), &stored_state);
+    assert_eq!(old_state, counter);
+
+    // The late joiner syncs until it has the old replica's heads
+    let config = SyncConfig {
+        snapshot_bootstrap: true,
+        ..Default::default()
+    };
+    let mut joiner = DAGSyncer::with_config(MemoryDAGStore::new(), config);
+    let mut requested = HashSet::new();
+    let mut snapshot_state = None;
+    for _ in 0..5 {
+        if joiner.is_synced_with(&old.heads()) {
+            break;
+        }
+        let mut request = joiner.create_request(&old.heads());
+        request
+            .want
+            .extend(joiner.find_missing_ancestors(&joiner.heads()));
+        requested.extend(request.want.iter().copied());
+
+        let response = old.handle_request(&request);
+        joiner
+            .apply_response_with_snapshots(response, |node| {
+                let (id, _vv) = Snapshot::read_anchor(node).map_err(|e| e.to_string())?;
+                snapshot_state = Some((id, stored_state(&id)));
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    assert!(joiner.is_synced_with(&old.heads()));
+    assert_eq!(joiner.heads(), old.heads());
+    assert!(requested.iter().all(|cid| !pruned.contains(cid)));
+    assert!(pruned.iter().all(|cid| !joiner.store().contains(cid)));
+
+    let (fetched_id, fetched_state) = snapshot_state.expect("snapshot was fetched");
+    assert_eq!(fetched_id, snapshot_id);
+    let joiner_state = materialize(joiner.store(), &|id| {
+        assert_eq!(*id, fetched_id);
+        fetched_state
+    });
+    assert_eq!(joiner_state, old_state);
+}
+
+/// Extend a chain with one delta node per timestamp, returning the new head.
+fn extend_chain(
+    store: &mut MemoryDAGStore,
+    mut prev: Hash,
+    creator: &str,
+    times: std::ops::RangeInclusive<u64>,
+) -> Hash {
+    for t in times {
+        let node = NodeBuilder::new()
+            .with_parent(prev)
+            .with_payload(Payload::delta(t.to_le_bytes().to_vec()))
+            .with_timestamp(t)
+            .with_creator(creator)
+            .build();
+        prev = store.put(node).unwrap();
+    }
+    prev
+}
+
+/// A chain of ten deltas under an anchored snapshot with one delta on top.
+fn anchored_chain() -> (MemoryDAGStore, Compactor, Hash) {
+    let (mut store, genesis) = MemoryDAGStore::with_genesis("shared");
+    let prev = extend_chain(&mut store, genesis, "shared", 1..=10);
+
+    let mut compactor = Compactor::new("shared");
+    compactor.update_local_frontier(
+        VersionVector::from_entries([("shared".to_string(), 10)]),
+        vec![prev],
+    );
+    compactor.set_time(10);
+    let (snapshot_id, anchor) = compactor
+        .create_anchored_snapshot(&mut store, || Ok(b"state".to_vec()))
+        .unwrap();
+    extend_chain(&mut store, anchor, "shared", 11..=11);
+
+    (store, compactor, snapshot_id)
+}
+
+/// Prune the history below the snapshot, returning the pruned CIDs.
+fn prune_below(store: &mut MemoryDAGStore, compactor: &Compactor, snapshot_id: &Hash) -> Vec<Hash> {
+    let pruner = Pruner::with_policy(PruningPolicy {
+        min_node_age: 0,
+        preserve_depth: 1,
+        preserve_genesis_path: false,
+        ..Default::default()
+    });
+    let snapshot = compactor.snapshots().get(snapshot_id).unwrap();
+    let result = pruner.execute_prune(store, snapshot, 100);
+    assert!(result.completed);
+    assert_eq!(
+        store.pruned_boundary(),
+        result.pruned_cids.iter().copied().collect()
+    );
+    assert!(store.missing_nodes().is_empty());
+    result.pruned_cids
+}
+
+/// One sync round pulling `from`'s nodes into `to`, recording requested CIDs.
+fn pull(
+    from: &DAGSyncer<MemoryDAGStore>,
+    to: &mut DAGSyncer<MemoryDAGStore>,
+    requested: &mut HashSet<Hash>,
+) {
+    let mut request = to.create_request(&from.heads());
+    request.want.extend(to.find_missing_ancestors(&to.heads()));
+    requested.extend(request.want.iter().copied());
+    let response = from.handle_request(&request);
+    to.apply_response(response).unwrap();
+}
+
+/// Test that replicas pruned below the same snapshot never ask each other
+/// for the pruned history.
+#[test]
+fn test_pruned_replicas_sync_without_requesting_pruned_history() {
+    let (store, compactor, snapshot_id) = anchored_chain();
+
+    let mut store_a = store.clone();
+    let mut store_b = store;
+    let pruned = prune_below(&mut store_a, &compactor, &snapshot_id);
+    assert_eq!(
+        prune_below(&mut store_b, &compactor, &snapshot_id).len(),
+        pruned.len()
+    );
+
+    // Both keep writing after pruning
+    let head = store_a.heads()[0];
+    extend_chain(&mut store_a, head, "a", 12..=13);
+    extend_chain(&mut store_b, head, "b", 12..=14);
+
+    let mut a = DAGSyncer::new(store_a);
+    let mut b = DAGSyncer::new(store_b);
+    let mut requested = HashSet::new();
+    for _ in 0..3 {
+        pull(&a, &mut b, &mut requested);
+        pull(&b, &mut a, &mut requested);
+    }
+
+    assert_eq!(a.heads(), b.heads());
+    assert_eq!(a.heads().len(), 2);
+    assert!(a.is_synced_with(&b.heads()));
+    assert!(b.is_synced_with(&a.heads()));
+    assert!(requested.iter().all(|cid| !pruned.contains(cid)));
+    assert!(pruned
+        .iter()
+        .all(|cid| !a.store().contains(cid) && !b.store().contains(cid)));
+}
+
+/// Test that a replica behind a peer's pruned history is told to bootstrap
+/// from the snapshot instead of asking for missing ancestors.
+#[test]
+fn test_unpruned_replica_is_told_to_bootstrap_from_snapshot() {
+    let (mut store, compactor, snapshot_id) = anchored_chain();
+
+    // The old replica stopped early in the history the peer prunes
+    let (mut old_store, genesis) = MemoryDAGStore::with_genesis("shared");
+    let old_head = extend_chain(&mut old_store, genesis, "shared", 1..=3);
+    let pruned = prune_below(&mut store, &compactor, &snapshot_id);
+    assert!(pruned.contains(&old_head));
+    let peer = DAGSyncer::new(store);
+
+    let mut old = DAGSyncer::new(old_store);
+    let mut request = old.create_request(&peer.heads());
+    request
+        .want
+        .extend(old.find_missing_ancestors(&peer.heads()));
+    let response = peer.handle_request(&request);
+    assert!(!response.pruned.is_empty());
+    match old.apply_response(response) {
+        Err(SyncError::SnapshotRequired(needed)) => {
+            assert!(needed.iter().all(|cid| pruned.contains(cid)));
+        }
+        other => panic!("expected SnapshotRequired, got {:?}", other),
+    }
+
+    // Taking the snapshot path completes the sync
+    let config = SyncConfig {
+        snapshot_bootstrap: true,
+        ..Default::default()
+    };
+    let mut old = DAGSyncer::with_config(old.store().clone(), config);
+    let request = old.create_request(&peer.heads());
+    let response = peer.handle_request(&request);
+    let mut fetched = Vec::new();
+    old.apply_response_with_snapshots(response, |anchor| {
+        fetched.push(*anchor.payload.snapshot_hash().unwrap());
+        Ok(())
+    })
+    .unwrap();
+
+    assert_eq!(fetched.len(), 1);
+    assert!(old.is_synced_with(&peer.heads()));
+}
+
+// ============================================================================
+// Compacting Replica Tests
+// ============================================================================
+
+type Set = ORSet<i32>;
+
+fn compacting(id: &str, peers: &[&str]) -> CompactingReplica<Set> {
+    let mut replica = CausalReplica::new(id);
+    for peer in peers {
+        replica.register_peer(peer.to_string());
+    }
+    CompactingReplica::new(replica, compacting_config(), encode_set)
+}
+
+fn compacting_config() -> CompactionConfig {
+    CompactionConfig {
+        min_ops_for_compaction: 10,
+        ..CompactionConfig::default()
+    }
+}
+
+fn encode_set(state: &Set) -> Result<Vec<u8>, String> {
+    serde_json::to_vec(state).map_err(|e| e.to_string())
+}
+
+fn decode_set(bytes: &[u8]) -> Result<Set, String> {
+    serde_json::from_slice(bytes).map_err(|e| e.to_string())
+}
+
+/// Deliver intervals and acks between the replicas until none are left.
+fn sync_all(replicas: &mut [CompactingReplica<Set>]) {
+    loop {
+        let intervals: Vec<_> = replicas
+            .iter_mut()
+            .flat_map(|r| r.replica_mut().prepare_all_intervals())
+            .collect();
+        if intervals.is_empty() {
+            return;
+        }
+        for interval in intervals {
+            let to = replicas
+                .iter()
+                .position(|r| *r.replica().id() == interval.to);
+            let ack = to.and_then(|to| replicas[to].receive_interval(interval));
+            if let Some(ack) = ack {
+                let from = replicas.iter().position(|r| *r.replica().id() == ack.to);
+                replicas[from.unwrap()].receive_ack(&ack);
+            }
+        }
+    }
+}
+
+/// Test that replicas compact as they go and a late joiner bootstrapped
+/// from the latest snapshot neither loses state nor lets a delta from before
+/// the snapshot bring back a removed element.
+#[test]
+fn test_compacting_replicas_bootstrap_late_joiner() {
+    let ids = ["r0", "r1", "r2"];
+    let mut replicas: Vec<_> = ids
+        .iter()
+        .map(|id| {
+            let peers: Vec<&str> = ids.iter().copied().filter(|p| p != id).collect();
+            compacting(id, &peers)
+        })
+        .collect();
+
+    // r0's add of 7, as r1 first received it
+    let id0 = "r0".to_string();
+    replicas[0].mutate(|s| orset_mutators::add_element_delta(s, &id0, 7));
+    let stale = replicas[0].replica_mut().prepare_interval("r1").unwrap();
+    let ack = replicas[1].receive_interval(stale.clone()).unwrap();
+    replicas[0].receive_ack(&ack);
+    sync_all(&mut replicas);
+
+    for round in 0..12 {
+        for (i, replica) in replicas.iter_mut().enumerate() {
+            let id = replica.replica().id().clone();
+            let value = (i as i32 + 1) * 100 + round;
+            replica.mutate(|s| orset_mutators::add_element_delta(s, &id, value));
+        }
+        if round == 3 {
+            replicas[1].mutate(|s| orset_mutators::remove_element_delta(s, &7));
+        }
+        sync_all(&mut replicas);
+    }
+
+    for replica in &replicas {
+        assert!(replica.compactor().stats().snapshots_created >= 2);
+        assert!(replica.ops_since_snapshot() < 10);
+        assert!(!replica.replica().has_unacked_deltas());
+        assert_eq!(replica.replica().state(), replicas[0].replica().state());
+    }
+    assert!(!replicas[0].replica().state().contains(&7));
+
+    // The late joiner starts from r2's latest snapshot
+    let snapshot = replicas[2].latest_snapshot().unwrap().clone();
+    assert!(snapshot.version_vector.get("r0") > stale.to_seq);
+    let mut joiner = CompactingReplica::bootstrap(
+        CausalReplica::new("r3"),
+        compacting_config(),
+        snapshot,
+        decode_set,
+        encode_set,
+    )
+    .unwrap();
+    let bootstrapped = joiner.replica().state().clone();
+    assert!(bootstrapped.contains(&100) && !bootstrapped.contains(&7));
+
+    // The pre-snapshot add arrives late and is only acked
+    let ack = joiner
+        .receive_interval(DeltaInterval {
+            to: "r3".to_string(),
+            ..stale
+        })
+        .unwrap();
+    assert_eq!(ack.to, "r0");
+    assert_eq!(joiner.replica().state(), &bootstrapped);
+
+    // Joined up, everyone converges and keeps compacting
+    for replica in &mut replicas {
+        replica.replica_mut().register_peer("r3".to_string());
+    }
+    replicas.push(joiner);
+    for round in 12..24 {
+        for (i, replica) in replicas.iter_mut().enumerate() {
+            let id = replica.replica().id().clone();
+            let value = (i as i32 + 1) * 100 + round;
+            replica.mutate(|s| orset_mutators::add_element_delta(s, &id, value));
+        }
+        sync_all(&mut replicas);
+    }
+    for replica in &replicas {
+        assert_eq!(replica.replica().state(), replicas[0].replica().state());
+        assert!(!replica.replica().state().contains(&7));
+    }
+    assert!(replicas[3].compactor().stats().snapshots_created >= 1);
+}
@@ -0,0 +1,145 @@
+//! Scheduled compaction tests
+//!
+//! Rounds are run directly against a manual clock, or on tokio's paused
+//! clock so intervals elapse without waiting.
+
+#![cfg(feature = "scheduler")]
+
+use mdcs_compaction::{
+    CompactionConfig, CompactionScheduler, Compactor, FrontierUpdate, SchedulerEvent, VersionVector,
+};
+use mdcs_core::clock::ManualClock;
+use mdcs_merkle::MemoryDAGStore;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn config(min_ops: u64) -> CompactionConfig {
+    CompactionConfig {
+        min_ops_for_compaction: min_ops,
+        interval: 1000,
+        ..CompactionConfig::default()
+    }
+}
+
+fn frontier(ops: &Arc<AtomicU64>) -> impl Fn() -> VersionVector + Send + 'static {
+    let ops = Arc::clone(ops);
+    move || VersionVector::from_entries([("local".to_string(), ops.load(Ordering::SeqCst))])
+}
+
+fn update(peer_id: &str, seq: u64, timestamp: u64) -> FrontierUpdate {
+    FrontierUpdate {
+        peer_id: peer_id.to_string(),
+        version_vector: VersionVector::from_entries([("local".to_string(), seq)]),
+        heads: vec![],
+        timestamp,
+        is_delta: false,
+    }
+}
+
+#[tokio::test]
+async fn test_snapshot_waits_for_op_threshold_and_stable_quorum() {
+    let mut config = config(5);
+    config.stability.min_peers_for_stability = 2;
+    let (store, _) = MemoryDAGStore::with_genesis("local");
+    let store = Arc::new(Mutex::new(store));
+    let ops = Arc::new(AtomicU64::new(3));
+    let clock = Arc::new(ManualClock::new(1_000));
+
+    let mut scheduler = CompactionScheduler::new(
+        Compactor::with_config("local", config),
+        store,
+        frontier(&ops),
+        || async { Ok(b"state".to_vec()) },
+    )
+    .with_clock(clock.clone());
+    let handle = scheduler.handle();
+    let stats = scheduler.stats();
+
+    // Below the threshold
+    assert!(scheduler.round().await.is_none());
+
+    // Enough operations, but no peer to make a quorum
+    ops.store(10, Ordering::SeqCst);
+    assert!(scheduler.round().await.is_none());
+
+    // A quorum, but the peer hasn't seen the operations
+    handle.process_peer_update(update("r2", 0, 1_000));
+    assert!(scheduler.round().await.is_none());
+    assert_eq!(stats.borrow().snapshots_created, 0);
+
+    clock.advance(500);
+    handle.process_peer_update(update("r2", 10, 1_500));
+    let result = scheduler.round().await.expect("compacted once stable");
+    assert!(result.snapshot_created.is_some());
+    assert_eq!(stats.borrow().snapshots_created, 1);
+    let snapshot = scheduler.compactor().snapshots().latest().unwrap();
+    assert_eq!(snapshot.version_vector.get("local"), 10);
+
+    // Nothing new since the snapshot
+    assert!(scheduler.round().await.is_none());
+    assert_eq!(scheduler.compactor().stats().snapshots_created, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_failed_round_is_reported_and_retried() {
+    let (store, _) = MemoryDAGStore::with_genesis("local");
+    let ops = Arc::new(AtomicU64::new(10));
+    let calls = Arc::new(AtomicUsize::new(0));
+    let events = Arc::new(Mutex::new(Vec::new()));
+
+    let serializer = {
+        let calls = Arc::clone(&calls);
+        move || {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                match call {
+                    0 => Err("state is busy".to_string()),
+                    _ => Ok(b"state".to_vec()),
+                }
+            }
+        }
+    };
+    let scheduler = CompactionScheduler::new(
+        Compactor::with_config("local", config(5)),
+        Arc::new(Mutex::new(store)),
+        frontier(&ops),
+        serializer,
+    )
+    .on_event({
+        let events = Arc::clone(&events);
+        move |event| {
+            let delta = match event {
+                SchedulerEvent::Compacted { delta, .. } => Ok(delta.snapshots_created),
+                SchedulerEvent::Failed(e) => Err(e.to_string()),
+            };
+            events.lock().unwrap().push(delta);
+        }
+    });
+    let handle = scheduler.handle();
+    let mut stats = scheduler.stats();
+    let task = scheduler.spawn();
+
+    stats.changed().await.unwrap();
+    assert_eq!(stats.borrow().snapshots_created, 1);
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            Err("State serialization failed: state is busy".to_string()),
+            Ok(1)
+        ]
+    );
+
+    // Rounds keep running, but without new operations take no snapshot
+    tokio::time::sleep(Duration::from_millis(5_000)).await;
+    assert_eq!(events.lock().unwrap().len(), 2);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    ops.store(20, Ordering::SeqCst);
+    stats.changed().await.unwrap();
+    assert_eq!(stats.borrow().snapshots_created, 2);
+
+    handle.shutdown();
+    let compactor = task.await.unwrap();
+    assert_eq!(compactor.stats().snapshots_created, 2);
+}
@@ -0,0 +1,288 @@
+//! Snapshot integrity and signature tests.
+//!
+//! `fixtures/snapshot_v1.json` is a serialized snapshot whose content hash
+//! is pinned below; if the hash changes, snapshots already shared between
+//! replicas would stop verifying.
+
+use mdcs_compaction::{
+    ChunkedSnapshot, CompactionError, Compactor, Snapshot, SnapshotError, SnapshotManager,
+    SnapshotReader, SnapshotWriter, VersionVector,
+};
+
+const FIXTURE: &str = include_str!("fixtures/snapshot_v1.json");
+const FIXTURE_CONTENT_HASH: &str =
+    "b089d82c10158e3d905cf9e0aac37fe0215d299c1d203aea6329432e0f673692";
+
+fn sample_snapshot() -> Snapshot {
+    let vv = VersionVector::from_entries([("origin".to_string(), 100)]);
+    Snapshot::new(vv, vec![], b"state data".to_vec(), "origin", 1000)
+}
+
+#[test]
+fn test_content_hash_golden_fixture() {
+    let snapshot: Snapshot = serde_json::from_str(FIXTURE).unwrap();
+    assert_eq!(snapshot.content_hash.to_hex(), FIXTURE_CONTENT_HASH);
+    snapshot.verify_integrity().unwrap();
+
+    // Recomputing from the decoded fields gives the same hash
+    let recomputed = Snapshot::compute_content_hash(
+        &snapshot.version_vector,
+        &snapshot.superseded_roots,
+        &snapshot.state_data,
+    );
+    assert_eq!(recomputed, snapshot.content_hash);
+
+    // And so does another serialization round trip
+    let bytes = serde_json::to_vec(&snapshot).unwrap();
+    let restored: Snapshot = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(restored.content_hash.to_hex(), FIXTURE_CONTENT_HASH);
+    restored.verify_integrity().unwrap();
+}
+
+#[test]
+fn test_bootstrap_detects_bit_flip() {
+    let mut snapshot = sample_snapshot();
+    snapshot.state_data[3] ^= 0x01;
+
+    let mut compactor = Compactor::new("new_replica");
+    let err = compactor.bootstrap_from_snapshot(snapshot).unwrap_err();
+    assert!(matches!(
+        err,
+        CompactionError::Snapshot(SnapshotError::IntegrityFailure(_))
+    ));
+    assert_eq!(compactor.snapshots().stats().count, 0);
+}
+
+#[test]
+fn test_bootstrap_detects_tampered_frontier() {
+    let mut snapshot = sample_snapshot();
+    snapshot.version_vector.increment("origin");
+
+    let mut compactor = Compactor::new("new_replica");
+    assert!(matches!(
+        compactor.bootstrap_from_snapshot(snapshot),
+        Err(CompactionError::Snapshot(SnapshotError::IntegrityFailure(
+            _
+        )))
+    ));
+}
+
+/// A snapshot of a few kilobytes of state, to split into many chunks.
+fn large_snapshot() -> Snapshot {
+    let vv = VersionVector::from_entries([("origin".to_string(), 100)]);
+    let state: Vec<u8> = (0..10_000u32).map(|i| (i * 31 % 251) as u8).collect();
+    Snapshot::new(vv, vec![], state, "origin", 1000)
+}
+
+#[test]
+fn test_bootstrap_from_chunked_snapshot() {
+    let snapshot = large_snapshot();
+    let chunked = ChunkedSnapshot::new(&snapshot, 1024);
+    assert_eq!(chunked.chunks.len(), 10);
+
+    // The chunks survive the wire
+    let bytes = serde_json::to_vec(&chunked).unwrap();
+    let chunked: ChunkedSnapshot = serde_json::from_slice(&bytes).unwrap();
+
+    let mut compactor = Compactor::new("new_replica");
+    let (state, vv) = compactor.bootstrap_from_snapshot(chunked).unwrap();
+    assert_eq!(state, snapshot.state_data);
+    assert_eq!(vv, snapshot.version_vector);
+    assert_eq!(compactor.snapshots().latest_id(), Some(snapshot.id));
+
+    // And a stored snapshot can be chunked again for the next peer
+    let again = compactor.snapshots().chunked(&snapshot.id, 4096).unwrap();
+    let mut manager = SnapshotManager::new();
+    assert_eq!(manager.import(again).unwrap(), snapshot.id);
+    assert_eq!(manager.import(snapshot.clone()).unwrap(), snapshot.id);
+}
+
+#[test]
+fn test_corrupt_chunk_rejected() {
+    let mut chunked = ChunkedSnapshot::new(&large_snapshot(), 1024);
+    let expected = chunked.manifest.chunk_hashes[4];
+    chunked.chunks[4].data[100] ^= 0x01;
+
+    let mut compactor = Compactor::new("new_replica");
+    let err = compactor.bootstrap_from_snapshot(chunked).unwrap_err();
+    let CompactionError::Snapshot(SnapshotError::CorruptChunk {
+        index,
+        expected: reported,
+        actual,
+    }) = err
+    else {
+        panic!("expected a corrupt chunk, got {:?}", err);
+    };
+    assert_eq!(index, 4);
+    assert_eq!(reported, expected);
+    assert_ne!(actual, expected);
+    assert_eq!(compactor.snapshots().stats().count, 0);
+
+    // A dropped chunk is reported as missing
+    let mut chunked = ChunkedSnapshot::new(&large_snapshot(), 1024);
+    chunked.chunks.remove(7);
+    assert!(matches!(
+        compactor.bootstrap_from_snapshot(chunked),
+        Err(CompactionError::Snapshot(SnapshotError::MissingChunks { ref missing }))
+            if missing == &[7]
+    ));
+}
+
+#[test]
+fn test_resume_assembly_with_half_the_chunks() {
+    let snapshot = large_snapshot();
+    let writer = SnapshotWriter::new(&snapshot, 1024);
+    let mut reader = SnapshotReader::new(writer.manifest()).unwrap();
+
+    // The transfer is cut off after every other chunk arrived
+    for chunk in writer.chunks().step_by(2) {
+        reader.receive(chunk).unwrap();
+    }
+    assert_eq!(reader.missing(), vec![1, 3, 5, 7, 9]);
+    assert!(matches!(
+        reader.assemble(),
+        Err(SnapshotError::MissingChunks { ref missing }) if missing.len() == 5
+    ));
+
+    // The partial assembly is saved, restored, and only asks for the rest
+    let saved = serde_json::to_vec(&reader).unwrap();
+    let mut resumed: SnapshotReader = serde_json::from_slice(&saved).unwrap();
+    for index in resumed.missing() {
+        assert!(resumed.receive(writer.chunk(index).unwrap()).unwrap());
+    }
+    assert!(resumed.is_complete());
+
+    let assembled = resumed.assemble().unwrap();
+    assert_eq!(assembled.state_data, snapshot.state_data);
+    assert_eq!(assembled.content_hash, snapshot.content_hash);
+}
+
+#[test]
+fn test_tampered_manifest_rejected() {
+    let snapshot = large_snapshot();
+    let mut chunked = ChunkedSnapshot::new(&snapshot, 1024);
+
+    // Chunks that match a doctored manifest still fail the content hash
+    chunked.chunks[0].data[0] ^= 0xff;
+    chunked.manifest.chunk_hashes[0] = mdcs_merkle::Hasher::hash(&chunked.chunks[0].data);
+    assert!(matches!(
+        chunked.assemble(),
+        Err(SnapshotError::IntegrityFailure(_))
+    ));
+}
+
+#[cfg(feature = "crypto")]
+mod signed {
+    use super::*;
+    use mdcs_compaction::{Ed25519Signer, TrustSet};
+
+    fn signer(id: &str, seed: u8) -> Ed25519Signer {
+        Ed25519Signer::from_seed(id, &[seed; 32])
+    }
+
+    /// A snapshot created by `origin`, signed with the key from `seed`.
+    fn signed_snapshot(origin: &str, seed: u8) -> Snapshot {
+        let mut compactor = Compactor::new(origin).with_signer(Box::new(signer(origin, seed)));
+        compactor.update_local_frontier(
+            VersionVector::from_entries([("origin".to_string(), 100)]),
+            vec![],
+        );
+        let id = compactor
+            .create_snapshot(vec![], || Ok(b"state data".to_vec()))
+            .unwrap();
+        compactor.snapshots().get(&id).unwrap().clone()
+    }
+
+    fn bootstrapper() -> Compactor {
+        Compactor::new("new_replica").with_signer(Box::new(signer("new_replica", 9)))
+    }
+
+    #[test]
+    fn test_valid_signature_accepted() {
+        let origin = signer("origin", 1);
+        let trusted = TrustSet::new().with_peer("origin", origin.public_key());
+        let snapshot = signed_snapshot("origin", 1);
+        assert_eq!(snapshot.signature.as_ref().unwrap().signer, "origin");
+
+        let mut compactor = bootstrapper();
+        let (state, vv) = compactor
+            .bootstrap_from_snapshot_verified(snapshot, &trusted)
+            .unwrap();
+        assert_eq!(state, b"state data");
+        assert_eq!(vv.get("origin"), 100);
+    }
+
+    #[test]
+    fn test_signature_survives_chunking() {
+        let origin = signer("origin", 1);
+        let trusted = TrustSet::new().with_peer("origin", origin.public_key());
+        let chunked = ChunkedSnapshot::new(&signed_snapshot("origin", 1), 4);
+
+        let mut compactor = bootstrapper();
+        let (state, _) = compactor
+            .bootstrap_from_snapshot_verified(chunked, &trusted)
+            .unwrap();
+        assert_eq!(state, b"state data");
+
+        let mut stripped = ChunkedSnapshot::new(&signed_snapshot("origin", 1), 4);
+        stripped.manifest.signature = None;
+        assert!(matches!(
+            compactor.bootstrap_from_snapshot_verified(stripped, &trusted),
+            Err(CompactionError::Snapshot(SnapshotError::Unsigned))
+        ));
+    }
+
+    #[test]
+    fn test_untrusted_signer_rejected() {
+        let origin = signer("origin", 1);
+        let trusted = TrustSet::new().with_peer("origin", origin.public_key());
+
+        let mut compactor = bootstrapper();
+        let err = compactor
+            .bootstrap_from_snapshot_verified(signed_snapshot("mallory", 2), &trusted)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            CompactionError::Snapshot(SnapshotError::UntrustedSigner(ref peer)) if peer == "mallory"
+        ));
+        assert_eq!(compactor.snapshots().stats().count, 0);
+    }
+
+    #[test]
+    fn test_impersonation_rejected() {
+        let origin = signer("origin", 1);
+        let trusted = TrustSet::new().with_peer("origin", origin.public_key());
+
+        // Signed under origin's name with someone else's key
+        let mut compactor = bootstrapper();
+        let err = compactor
+            .bootstrap_from_snapshot_verified(signed_snapshot("origin", 2), &trusted)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            CompactionError::Snapshot(SnapshotError::InvalidSignature(_))
+        ));
+    }
+
+    #[test]
+    fn test_unsigned_and_tampered_rejected() {
+        let origin = signer("origin", 1);
+        let trusted = TrustSet::new().with_peer("origin", origin.public_key());
+        let mut compactor = bootstrapper();
+
+        assert!(matches!(
+            compactor.bootstrap_from_snapshot_verified(sample_snapshot(), &trusted),
+            Err(CompactionError::Snapshot(SnapshotError::Unsigned))
+        ));
+
+        // Tampering is caught before the signature is looked at
+        let mut snapshot = signed_snapshot("origin", 1);
+        snapshot.state_data[0] ^= 0x80;
+        assert!(matches!(
+            compactor.bootstrap_from_snapshot_verified(snapshot, &trusted),
+            Err(CompactionError::Snapshot(SnapshotError::IntegrityFailure(
+                _
+            )))
+        ));
+    }
+}
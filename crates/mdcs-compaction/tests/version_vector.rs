@@ -0,0 +1,95 @@
+//! Property-based tests for version vectors
+//!
+//! Dominance must be a partial order with `merged` as the least upper
+//! bound, `diff` must carry everything a peer is missing, and the compact
+//! encoding must round-trip.
+
+use mdcs_compaction::VersionVector;
+use proptest::prelude::*;
+
+/// Vectors over a few replicas, so that they often overlap.
+fn vv_strategy() -> impl Strategy<Value = VersionVector> {
+    prop::collection::btree_map("r[0-5]", 1u64..20, 0..6).prop_map(VersionVector::from_entries)
+}
+
+/// Vectors with hundreds of replicas sharing long ID prefixes.
+fn large_vv_strategy() -> impl Strategy<Value = VersionVector> {
+    prop::collection::btree_map(
+        "(replica|replica-eu|node)-[0-9a-f]{1,8}",
+        1u64..=u64::MAX,
+        200..400,
+    )
+    .prop_map(VersionVector::from_entries)
+}
+
+proptest! {
+    #[test]
+    fn dominance_is_reflexive(a in vv_strategy()) {
+        prop_assert!(a.dominates(&a));
+    }
+
+    #[test]
+    fn dominance_is_antisymmetric(a in vv_strategy(), b in vv_strategy()) {
+        if a.dominates(&b) && b.dominates(&a) {
+            prop_assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn dominance_is_transitive(
+        a in vv_strategy(),
+        b in vv_strategy(),
+        c in vv_strategy()
+    ) {
+        // Build a chain so the premise holds
+        let b = b.merged(&c);
+        let a = a.merged(&b);
+        prop_assert!(a.dominates(&b) && b.dominates(&c));
+        prop_assert!(a.dominates(&c));
+    }
+
+    #[test]
+    fn concurrent_means_incomparable(a in vv_strategy(), b in vv_strategy()) {
+        prop_assert_eq!(
+            a.concurrent_with(&b),
+            !a.dominates(&b) && !b.dominates(&a)
+        );
+        prop_assert_eq!(a.concurrent_with(&b), b.concurrent_with(&a));
+    }
+
+    #[test]
+    fn merged_is_least_upper_bound(
+        a in vv_strategy(),
+        b in vv_strategy(),
+        upper in vv_strategy()
+    ) {
+        let merged = a.merged(&b);
+        prop_assert!(merged.dominates(&a));
+        prop_assert!(merged.dominates(&b));
+        prop_assert_eq!(&merged, &b.merged(&a));
+
+        // Any other upper bound dominates it
+        let upper = upper.merged(&a).merged(&b);
+        prop_assert!(upper.dominates(&merged));
+    }
+
+    #[test]
+    fn difference_fills_in_what_other_is_missing(a in vv_strategy(), b in vv_strategy()) {
+        let diff = a.difference(&b);
+        prop_assert_eq!(b.merged(&diff), a.merged(&b));
+        prop_assert!(a.dominates(&diff));
+        prop_assert_eq!(diff.is_empty(), b.dominates(&a));
+    }
+
+    #[test]
+    fn bytes_round_trip(a in vv_strategy()) {
+        prop_assert_eq!(VersionVector::from_bytes(&a.to_bytes()).unwrap(), a);
+    }
+
+    #[test]
+    fn large_vectors_round_trip_compactly(a in large_vv_strategy()) {
+        let bytes = a.to_bytes();
+        prop_assert!(bytes.len() < serde_json::to_vec(&a).unwrap().len());
+        prop_assert_eq!(VersionVector::from_bytes(&bytes).unwrap(), a);
+    }
+}
@@ -0,0 +1,389 @@
+//! Bounded Counter CRDT
+//!
+//! A counter whose value never drops below a lower bound, using escrow:
+//! the room above the bound is split into per-replica quotas. Increments
+//! add to the incrementing replica's quota, decrements spend it, and
+//! `transfer` moves quota between replicas. A replica only ever spends
+//! quota it holds, so no merge of any replicas' states can push the value
+//! below the bound, without coordinating at decrement time.
+//!
+//! State per replica: its increments, its decrements and the quota it has
+//! transferred to each other replica. All of them only grow, and the join
+//! performs component-wise max.
+
+use crate::lattice::{DeltaCRDT, Lattice};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Error returned when a replica spends more quota than it holds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InsufficientQuota {
+    /// Amount the operation needed
+    pub requested: u64,
+    /// Quota the replica held
+    pub available: u64,
+}
+
+impl fmt::Display for InsufficientQuota {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "insufficient quota: requested {}, available {}",
+            self.requested, self.available
+        )
+    }
+}
+
+impl std::error::Error for InsufficientQuota {}
+
+/// A counter with an enforced lower bound
+///
+/// Value = bound + sum(increments) - sum(decrements), and the room above
+/// the bound is the sum of every replica's quota.
+///
+/// The counter is its own delta. A mutation's delta holds the mutated
+/// replica's entries along with those of every replica it received quota
+/// from, transitively, so a replica never sees a decrement without the
+/// quota that paid for it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BoundedCounter<K: Ord + Clone> {
+    /// Lower bound of the value, unset in the bottom element
+    bound: Option<i64>,
+    /// Per-replica increment counters
+    increments: BTreeMap<K, u64>,
+    /// Per-replica decrement counters
+    decrements: BTreeMap<K, u64>,
+    /// Quota each replica has transferred to each other one
+    transfers: BTreeMap<K, BTreeMap<K, u64>>,
+    /// Pending delta for delta-state replication
+    #[serde(skip)]
+    pending_delta: Option<Box<BoundedCounter<K>>>,
+}
+
+impl<K: Ord + Clone> PartialEq for BoundedCounter<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.bound == other.bound
+            && self.increments == other.increments
+            && self.decrements == other.decrements
+            && self.transfers == other.transfers
+    }
+}
+
+impl<K: Ord + Clone> Eq for BoundedCounter<K> {}
+
+impl<K: Ord + Clone> BoundedCounter<K> {
+    /// Create a counter at its lower bound
+    ///
+    /// All replicas of a counter must use the same bound. The bottom
+    /// element has none and adopts the bound of whatever it joins.
+    pub fn new(lower_bound: i64) -> Self {
+        Self {
+            bound: Some(lower_bound),
+            increments: BTreeMap::new(),
+            decrements: BTreeMap::new(),
+            transfers: BTreeMap::new(),
+            pending_delta: None,
+        }
+    }
+
+    /// Get the lower bound, 0 if none was set
+    pub fn lower_bound(&self) -> i64 {
+        self.bound.unwrap_or(0)
+    }
+
+    /// Get the current value, never below the lower bound
+    pub fn value(&self) -> i64 {
+        let inc_sum: u64 = self.increments.values().sum();
+        let dec_sum: u64 = self.decrements.values().sum();
+        self.lower_bound()
+            .saturating_add(inc_sum as i64)
+            .saturating_sub(dec_sum as i64)
+    }
+
+    /// Get the quota a replica may still decrement or transfer
+    pub fn quota(&self, replica_id: &K) -> u64 {
+        let received: u64 = self
+            .transfers
+            .iter()
+            .filter(|(from, _)| *from != replica_id)
+            .filter_map(|(_, to)| to.get(replica_id))
+            .sum();
+        let sent: u64 = self
+            .transfers
+            .get(replica_id)
+            .map_or(0, |to| to.values().sum());
+        (get(&self.increments, replica_id) + received)
+            .saturating_sub(sent + get(&self.decrements, replica_id))
+    }
+
+    /// Increment the counter for a specific replica, adding to its quota
+    pub fn increment(&mut self, replica_id: K, amount: u64) {
+        add(&mut self.increments, replica_id.clone(), amount);
+        self.record(&replica_id);
+    }
+
+    /// Decrement the counter for a specific replica, spending its quota
+    pub fn decrement(&mut self, replica_id: K, amount: u64) -> Result<(), InsufficientQuota> {
+        self.spend(&replica_id, amount)?;
+        add(&mut self.decrements, replica_id.clone(), amount);
+        self.record(&replica_id);
+        Ok(())
+    }
+
+    /// Move quota from one replica to another
+    ///
+    /// Only `from` can give its quota away, so this runs at `from`; `to`
+    /// can spend it once it has merged the result.
+    pub fn transfer(&mut self, from: K, to: K, amount: u64) -> Result<(), InsufficientQuota> {
+        self.spend(&from, amount)?;
+        if from != to {
+            add(self.transfers.entry(from.clone()).or_default(), to, amount);
+            self.record(&from);
+        }
+        Ok(())
+    }
+
+    /// Get the increment counter for a replica
+    pub fn get_increment(&self, replica_id: &K) -> u64 {
+        get(&self.increments, replica_id)
+    }
+
+    /// Get the decrement counter for a replica
+    pub fn get_decrement(&self, replica_id: &K) -> u64 {
+        get(&self.decrements, replica_id)
+    }
+
+    /// Get the quota one replica has transferred to another
+    pub fn get_transfer(&self, from: &K, to: &K) -> u64 {
+        self.transfers.get(from).map_or(0, |t| get(t, to))
+    }
+
+    /// The entries a replica's quota depends on, as a delta
+    ///
+    /// Holds the replica's own entries and those of every replica that
+    /// transferred quota to one already included.
+    pub fn delta_for(&self, replica_id: &K) -> Self {
+        let mut delta = Self {
+            bound: self.bound,
+            ..Self::bottom()
+        };
+        let mut included = BTreeSet::new();
+        let mut stack = vec![replica_id.clone()];
+        while let Some(replica) = stack.pop() {
+            if !included.insert(replica.clone()) {
+                continue;
+            }
+            if let Some(&n) = self.increments.get(&replica) {
+                delta.increments.insert(replica.clone(), n);
+            }
+            if let Some(&n) = self.decrements.get(&replica) {
+                delta.decrements.insert(replica.clone(), n);
+            }
+            if let Some(to) = self.transfers.get(&replica) {
+                delta.transfers.insert(replica.clone(), to.clone());
+            }
+            stack.extend(
+                self.transfers
+                    .iter()
+                    .filter(|(_, to)| to.get(&replica).is_some_and(|&n| n > 0))
+                    .map(|(from, _)| from.clone()),
+            );
+        }
+        delta
+    }
+
+    fn spend(&self, replica_id: &K, amount: u64) -> Result<(), InsufficientQuota> {
+        let available = self.quota(replica_id);
+        if amount > available {
+            return Err(InsufficientQuota {
+                requested: amount,
+                available,
+            });
+        }
+        Ok(())
+    }
+
+    /// Add the entries of a mutated replica to the pending delta
+    fn record(&mut self, replica_id: &K) {
+        let delta = self.delta_for(replica_id);
+        self.pending_delta = Some(Box::new(match self.pending_delta.take() {
+            Some(pending) => pending.join(&delta),
+            None => delta,
+        }));
+    }
+}
+
+fn get<K: Ord>(counts: &BTreeMap<K, u64>, key: &K) -> u64 {
+    counts.get(key).copied().unwrap_or(0)
+}
+
+fn add<K: Ord>(counts: &mut BTreeMap<K, u64>, key: K, amount: u64) {
+    let entry = counts.entry(key).or_insert(0);
+    *entry = entry.saturating_add(amount);
+}
+
+fn join_max<K: Ord + Clone>(into: &mut BTreeMap<K, u64>, other: &BTreeMap<K, u64>) {
+    for (k, v) in other {
+        into.entry(k.clone())
+            .and_modify(|e| *e = (*e).max(*v))
+            .or_insert(*v);
+    }
+}
+
+impl<K: Ord + Clone> Default for BoundedCounter<K> {
+    fn default() -> Self {
+        Self::bottom()
+    }
+}
+
+impl<K: Ord + Clone> Lattice for BoundedCounter<K> {
+    fn bottom() -> Self {
+        Self {
+            bound: None,
+            increments: BTreeMap::new(),
+            decrements: BTreeMap::new(),
+            transfers: BTreeMap::new(),
+            pending_delta: None,
+        }
+    }
+
+    /// Join operation performs component-wise max on every counter
+    fn join(&self, other: &Self) -> Self {
+        // An unset bound adopts the other's; mismatched bounds keep the
+        // greater one so the result is independent of merge order
+        let bound = self.bound.max(other.bound);
+
+        let mut increments = self.increments.clone();
+        join_max(&mut increments, &other.increments);
+        let mut decrements = self.decrements.clone();
+        join_max(&mut decrements, &other.decrements);
+        let mut transfers = self.transfers.clone();
+        for (from, to) in &other.transfers {
+            join_max(transfers.entry(from.clone()).or_default(), to);
+        }
+
+        Self {
+            bound,
+            increments,
+            decrements,
+            transfers,
+            pending_delta: self.pending_delta.clone(),
+        }
+    }
+}
+
+impl<K: Ord + Clone> DeltaCRDT for BoundedCounter<K> {
+    type Delta = BoundedCounter<K>;
+
+    fn split_delta(&mut self) -> Option<Self::Delta> {
+        self.pending_delta.take().map(|delta| *delta)
+    }
+
+    fn apply_delta(&mut self, delta: &Self::Delta) {
+        self.join_assign(delta);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounded_counter_basic_operations() {
+        let mut counter = BoundedCounter::new(0);
+        counter.increment("A", 10);
+        assert_eq!(counter.value(), 10);
+        assert_eq!(counter.quota(&"A"), 10);
+
+        counter.decrement("A", 4).unwrap();
+        assert_eq!(counter.value(), 6);
+        assert_eq!(
+            counter.decrement("A", 7),
+            Err(InsufficientQuota {
+                requested: 7,
+                available: 6
+            })
+        );
+        assert_eq!(counter.value(), 6);
+
+        // B holds no quota until A hands some over
+        assert!(counter.decrement("B", 1).is_err());
+        counter.transfer("A", "B", 5).unwrap();
+        assert_eq!(counter.quota(&"A"), 1);
+        assert_eq!(counter.quota(&"B"), 5);
+        counter.decrement("B", 5).unwrap();
+        assert_eq!(counter.value(), 1);
+        assert!(counter.transfer("A", "B", 2).is_err());
+    }
+
+    #[test]
+    fn test_bounded_counter_negative_bound() {
+        let mut counter = BoundedCounter::new(-3);
+        assert_eq!(counter.value(), -3);
+        counter.increment("A", 5);
+        counter.decrement("A", 5).unwrap();
+        assert_eq!(counter.value(), -3);
+
+        // An empty replica adopts the bound
+        let joined = BoundedCounter::bottom().join(&counter);
+        assert_eq!(joined.lower_bound(), -3);
+        assert_eq!(joined, counter);
+    }
+
+    #[test]
+    fn test_bounded_counter_concurrent_decrements_stay_bounded() {
+        let mut a = BoundedCounter::new(0);
+        a.increment("A", 10);
+        a.transfer("A", "B", 4).unwrap();
+        let mut b = BoundedCounter::new(0).join(&a);
+
+        // Both spend all their quota concurrently
+        a.decrement("A", 6).unwrap();
+        b.decrement("B", 4).unwrap();
+        assert!(a.decrement("A", 1).is_err());
+        assert!(b.decrement("B", 1).is_err());
+
+        let merged = a.join(&b);
+        assert_eq!(merged, b.join(&a));
+        assert_eq!(merged.value(), 0);
+    }
+
+    #[test]
+    fn test_bounded_counter_delta_carries_quota_source() {
+        let mut a = BoundedCounter::new(0);
+        a.increment("A", 10);
+        a.transfer("A", "B", 3).unwrap();
+        let mut b = BoundedCounter::new(0).join(&a);
+        let _ = b.split_delta();
+
+        b.decrement("B", 3).unwrap();
+        let delta = b.split_delta().unwrap();
+        assert_eq!(delta.get_decrement(&"B"), 3);
+        assert_eq!(delta.get_transfer(&"A", &"B"), 3);
+        assert_eq!(delta.get_increment(&"A"), 10);
+
+        // A replica that has seen nothing else still stays above the bound
+        let mut c = BoundedCounter::bottom();
+        c.apply_delta(&delta);
+        assert_eq!(c.value(), 7);
+        assert_eq!(c, c.join(&delta));
+    }
+
+    #[test]
+    fn test_bounded_counter_serialization() {
+        let mut counter = BoundedCounter::new(-5);
+        counter.increment("replica1".to_string(), 100);
+        counter
+            .transfer("replica1".to_string(), "replica2".to_string(), 30)
+            .unwrap();
+        counter.decrement("replica2".to_string(), 25).unwrap();
+
+        let serialized = serde_json::to_string(&counter).unwrap();
+        let deserialized: BoundedCounter<String> = serde_json::from_str(&serialized).unwrap();
+
+        assert_eq!(deserialized, counter);
+        assert_eq!(deserialized.value(), 70);
+        assert_eq!(deserialized.quota(&"replica2".to_string()), 5);
+    }
+}
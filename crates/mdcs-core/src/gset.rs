@@ -2,6 +2,7 @@
 //!  This is the simplest useful CRDT and a good starting point.
 
 use crate::lattice::Lattice;
+use crate::observer::SetObserver;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
 // use std::hash:: Hash;
@@ -46,6 +47,11 @@ impl<T: Ord + Clone> GSet<T> {
         self.elements.insert(value);
     }
 
+    /// Add many elements at once
+    pub fn insert_all(&mut self, values: impl IntoIterator<Item = T>) {
+        self.elements.extend(values);
+    }
+
     /// Check whether `value` is a member of this set.
     pub fn contains(&self, value: &T) -> bool {
         self.elements.contains(value)
@@ -65,6 +71,14 @@ impl<T: Ord + Clone> GSet<T> {
     pub fn is_empty(&self) -> bool {
         self.elements.is_empty()
     }
+
+    /// Join with `other`, reporting each element it adds to `observer`.
+    pub fn join_observed(&self, other: &Self, observer: &mut impl SetObserver<T>) -> Self {
+        for value in other.elements.difference(&self.elements) {
+            observer.on_added(value);
+        }
+        self.join(other)
+    }
 }
 
 impl<T: Ord + Clone> Default for GSet<T> {
@@ -73,6 +87,21 @@ impl<T: Ord + Clone> Default for GSet<T> {
     }
 }
 
+/// Insert many elements in one pass.
+impl<T: Ord + Clone> Extend<T> for GSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, values: I) {
+        self.insert_all(values);
+    }
+}
+
+impl<T: Ord + Clone> FromIterator<T> for GSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(values: I) -> Self {
+        Self {
+            elements: values.into_iter().collect(),
+        }
+    }
+}
+
 impl<T: Ord + Clone> Lattice for GSet<T> {
     fn bottom() -> Self {
         Self::new()
@@ -90,6 +119,20 @@ mod tests {
     use super::*;
     use proptest::prelude::*;
 
+    #[test]
+    fn test_gset_bulk_insert_matches_elementwise() {
+        let mut elementwise = GSet::new();
+        for value in 0..100 {
+            elementwise.insert(value);
+        }
+
+        let mut bulk = GSet::new();
+        bulk.insert_all(0..50);
+        bulk.extend(50..100);
+        assert_eq!(bulk, elementwise);
+        assert_eq!((0..100).collect::<GSet<_>>(), elementwise);
+    }
+
     // Property-based tests for lattice laws
     proptest! {
         #[test]
@@ -127,5 +170,29 @@ mod tests {
 
             prop_assert_eq!(set_a.join(&set_a), set_a);
         }
+
+        #[test]
+        fn gset_join_observed_reports_new_elements(
+            a in prop::collection::btree_set(0i32..100, 0..20),
+            b in prop::collection::btree_set(0i32..100, 0..20)
+        ) {
+            let set_a = GSet { elements: a };
+            let set_b = GSet { elements: b };
+
+            let mut members = set_a.elements.clone();
+            let joined = set_a.join_observed(&set_b, &mut members);
+            prop_assert_eq!(&joined, &set_a.join(&set_b));
+            prop_assert_eq!(members, joined.elements);
+        }
+    }
+
+    impl SetObserver<i32> for std::collections::BTreeSet<i32> {
+        fn on_added(&mut self, value: &i32) {
+            assert!(self.insert(*value));
+        }
+
+        fn on_removed(&mut self, value: &i32) {
+            assert!(self.remove(value));
+        }
     }
 }
@@ -0,0 +1,207 @@
+//! Hybrid logical clocks.
+//!
+//! A hybrid logical clock (HLC) stamps events with wall-clock time, but
+//! never hands out a timestamp lower than one it has already issued or
+//! observed. When the wall clock lags behind, a logical counter advances
+//! instead, so a replica whose clock is skewed still orders its writes after
+//! everything it has seen from others. Ties between replicas are broken by
+//! replica ID, which makes the order total.
+//!
+//! Timestamps pack into a `u64` (48 bits of milliseconds, 16 bits of
+//! counter) for types like [`LWWRegister`](crate::lwwreg::LWWRegister) that
+//! compare plain integer timestamps.
+//!
+//! # Example
+//!
+//! ```rust
+//! use mdcs_core::clock::ManualClock;
+//! use mdcs_core::hlc::Hlc;
+//! use std::sync::Arc;
+//!
+//! let mut ahead = Hlc::new("a").with_clock(Arc::new(ManualClock::new(5_000)));
+//! let mut behind = Hlc::new("b").with_clock(Arc::new(ManualClock::new(1_000)));
+//!
+//! let remote = ahead.now();
+//! behind.observe(&remote);
+//! assert!(behind.now() > remote);
+//! ```
+
+use crate::clock::SharedClock;
+use serde::{Deserialize, Serialize};
+
+/// Bits of a packed timestamp that hold the logical counter.
+const COUNTER_BITS: u32 = 16;
+
+/// A hybrid logical clock timestamp.
+///
+/// Ordered by wall time, then logical counter, then replica ID.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct HlcTimestamp<K> {
+    /// Milliseconds since the Unix epoch.
+    pub wall_millis: u64,
+    /// Events stamped within the same millisecond.
+    pub counter: u16,
+    /// The replica that issued the timestamp.
+    pub replica_id: K,
+}
+
+impl<K> HlcTimestamp<K> {
+    /// Unpack a timestamp issued by `replica_id`.
+    pub fn from_packed(packed: u64, replica_id: K) -> Self {
+        Self {
+            wall_millis: packed >> COUNTER_BITS,
+            counter: packed as u16,
+            replica_id,
+        }
+    }
+
+    /// Pack wall time and counter into one integer with the same order.
+    ///
+    /// The replica ID is not included; compare it separately on ties.
+    pub fn packed(&self) -> u64 {
+        (self.wall_millis << COUNTER_BITS) | u64::from(self.counter)
+    }
+}
+
+/// A hybrid logical clock owned by one replica.
+///
+/// Only the last issued or observed time is serialized; the wall-clock
+/// source defaults to the system clock after deserializing.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hlc<K> {
+    replica_id: K,
+    wall_millis: u64,
+    counter: u16,
+    #[serde(skip)]
+    clock: SharedClock,
+}
+
+impl<K: Clone> Hlc<K> {
+    /// Create a clock for `replica_id`, reading wall time from the system.
+    pub fn new(replica_id: K) -> Self {
+        Self {
+            replica_id,
+            wall_millis: 0,
+            counter: 0,
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Read wall time from `clock` instead.
+    pub fn with_clock(mut self, clock: impl Into<SharedClock>) -> Self {
+        self.clock = clock.into();
+        self
+    }
+
+    /// Get the replica this clock stamps events for.
+    pub fn replica_id(&self) -> &K {
+        &self.replica_id
+    }
+
+    /// Get the last timestamp issued or observed, without advancing.
+    pub fn last(&self) -> HlcTimestamp<K> {
+        HlcTimestamp {
+            wall_millis: self.wall_millis,
+            counter: self.counter,
+            replica_id: self.replica_id.clone(),
+        }
+    }
+
+    /// Stamp a local event.
+    ///
+    /// The result is greater than every timestamp this clock has issued or
+    /// observed, even if the wall clock went backwards.
+    pub fn now(&mut self) -> HlcTimestamp<K> {
+        let wall = self.clock.now_millis();
+        if wall > self.wall_millis {
+            self.wall_millis = wall;
+            self.counter = 0;
+        } else if self.counter == u16::MAX {
+            self.wall_millis += 1;
+            self.counter = 0;
+        } else {
+            self.counter += 1;
+        }
+        self.last()
+    }
+
+    /// Advance past a timestamp received from another replica, so the next
+    /// local event is ordered after it.
+    pub fn observe(&mut self, remote: &HlcTimestamp<K>) {
+        self.observe_packed(remote.packed());
+    }
+
+    /// Advance past a [packed](HlcTimestamp::packed) timestamp.
+    pub fn observe_packed(&mut self, packed: u64) {
+        let ours = (self.wall_millis << COUNTER_BITS) | u64::from(self.counter);
+        if packed > ours {
+            self.wall_millis = packed >> COUNTER_BITS;
+            self.counter = packed as u16;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_hlc_monotonic_when_wall_clock_stalls_or_goes_back() {
+        let manual = Arc::new(ManualClock::new(1_000));
+        let mut hlc = Hlc::new("a").with_clock(manual.clone());
+
+        let first = hlc.now();
+        let second = hlc.now();
+        assert_eq!((second.wall_millis, second.counter), (1_000, 1));
+
+        manual.set(500);
+        let third = hlc.now();
+        assert!(first < second && second < third);
+
+        manual.set(2_000);
+        assert_eq!(hlc.now().wall_millis, 2_000);
+        assert_eq!(hlc.last().counter, 0);
+    }
+
+    #[test]
+    fn test_hlc_counter_overflow_moves_to_next_millisecond() {
+        let mut hlc = Hlc::new("a").with_clock(Arc::new(ManualClock::new(7)));
+        hlc.observe(&HlcTimestamp {
+            wall_millis: 7,
+            counter: u16::MAX,
+            replica_id: "b",
+        });
+
+        let next = hlc.now();
+        assert_eq!((next.wall_millis, next.counter), (8, 0));
+    }
+
+    #[test]
+    fn test_hlc_packing_preserves_order() {
+        let a = HlcTimestamp::from_packed(0, 1);
+        let b = HlcTimestamp {
+            wall_millis: 3,
+            counter: 9,
+            replica_id: 1,
+        };
+        let c = HlcTimestamp {
+            wall_millis: 4,
+            counter: 0,
+            replica_id: 1,
+        };
+        assert!(a.packed() < b.packed() && b.packed() < c.packed());
+        assert_eq!(HlcTimestamp::from_packed(b.packed(), 1), b);
+    }
+
+    #[test]
+    fn test_hlc_serialization() {
+        let mut hlc = Hlc::new("a".to_string()).with_clock(Arc::new(ManualClock::new(42)));
+        hlc.now();
+
+        let json = serde_json::to_string(&hlc).unwrap();
+        let restored: Hlc<String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.last(), hlc.last());
+    }
+}
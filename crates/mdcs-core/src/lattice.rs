@@ -8,6 +8,7 @@
 //!  These properties guarantee convergence regardless of message order.
 
 use std::cmp::Ordering;
+use std::fmt;
 
 /// The core CRDT trait.  All state-based CRDTs implement this.
 pub trait Lattice: Clone + PartialEq {
@@ -56,4 +57,43 @@ pub trait DeltaCRDT: Lattice {
 
     /// Apply a delta to the state
     fn apply_delta(&mut self, delta: &Self::Delta);
+
+    /// Check that a delta was generated against history this state has
+    /// observed
+    ///
+    /// The default accepts every delta. Types whose deltas remove observed
+    /// entries reject a delta that removes entries never seen here.
+    fn check_delta(&self, _delta: &Self::Delta) -> Result<(), DeltaRejected> {
+        Ok(())
+    }
+
+    /// Apply a delta if [`check_delta`](Self::check_delta) accepts it,
+    /// leaving the state unchanged otherwise
+    fn try_apply_delta(&mut self, delta: &Self::Delta) -> Result<(), DeltaRejected> {
+        self.check_delta(delta)?;
+        self.apply_delta(delta);
+        Ok(())
+    }
+}
+
+/// Error returned when a delta depends on history the receiver has not
+/// observed.
+///
+/// The receiver should catch up with a full-state join instead.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeltaRejected {
+    /// Number of entries the delta removes that the receiver never saw
+    pub unobserved: usize,
 }
+
+impl fmt::Display for DeltaRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "delta removes {} entries this replica has not observed",
+            self.unobserved
+        )
+    }
+}
+
+impl std::error::Error for DeltaRejected {}
@@ -27,6 +27,7 @@
 //! | [`GSet`] | [`gset`] | Grow-only set — elements can only be added |
 //! | [`ORSet`] | [`orset`] | Observed-Remove set — add-wins semantics |
 //! | [`PNCounter`] | [`pncounter`] | Increment/decrement counter |
+//! | [`BoundedCounter`] | [`bounded_counter`] | Counter that never drops below a lower bound |
 //! | [`LWWRegister`] | [`lwwreg`] | Last-Writer-Wins register |
 //! | [`MVRegister`] | [`mvreg`] | Multi-Value register — preserves concurrent writes |
 //! | [`CRDTMap`] | [`map`] | Composable map with shared causal context |
@@ -55,30 +56,48 @@
 //! replication. Instead of shipping full state, only incremental changes
 //! (deltas) are transmitted. See the [`mdcs-delta`](https://docs.rs/mdcs-delta)
 //! crate for the anti-entropy protocol that drives synchronization.
+//!
+//! ## Feature: Testing
+//!
+//! The `testing` feature adds the `testing` module: a
+//! property harness that checks the lattice laws, delta-mutators and
+//! replica convergence of any CRDT, with generated states for the types
+//! above.
 
+pub mod bounded_counter;
+pub mod clock;
 pub mod gset;
+pub mod hlc;
 pub mod lattice;
 pub mod lwwreg;
 pub mod map;
 pub mod mvreg;
+pub mod observer;
 pub mod orset;
 pub mod pncounter;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 // Re-exports for convenience
+pub use bounded_counter::{BoundedCounter, InsufficientQuota};
+pub use clock::{Clock, ManualClock, SharedClock, SystemClock};
 pub use gset::GSet;
-pub use lattice::{DeltaCRDT, Lattice};
-pub use lwwreg::LWWRegister;
-pub use map::{CRDTMap, CausalContext, MapValue};
+pub use hlc::{Hlc, HlcTimestamp};
+pub use lattice::{DeltaCRDT, DeltaRejected, Lattice};
+pub use lwwreg::{LWWRegister, TieBreak, TieBreakMismatch, WriteRecord};
+pub use map::{CRDTMap, CausalContext, CausalResettable, MapRegister, MapValue};
 pub use mvreg::MVRegister;
+pub use observer::SetObserver;
 pub use orset::ORSet;
-pub use pncounter::PNCounter;
+pub use pncounter::{Contribution, PNCounter};
 
 /// Prelude module — import everything you need with `use mdcs_core::prelude::*`.
 pub mod prelude {
+    pub use crate::bounded_counter::BoundedCounter;
     pub use crate::gset::GSet;
     pub use crate::lattice::{DeltaCRDT, Lattice};
     pub use crate::lwwreg::LWWRegister;
-    pub use crate::map::{CRDTMap, CausalContext, MapValue};
+    pub use crate::map::{CRDTMap, CausalContext, CausalResettable, MapValue};
     pub use crate::mvreg::MVRegister;
     pub use crate::orset::ORSet;
     pub use crate::pncounter::PNCounter;
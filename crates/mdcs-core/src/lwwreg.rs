@@ -1,20 +1,99 @@
 //! Last-Write-Wins (LWW) Register CRDT
 //!
 //! The LWW Register always retains the value with the highest timestamp.
-//! In case of a tie, the replica with the highest ID wins.
+//! In case of a tie, the configured [`TieBreak`] policy decides; by default
+//! the replica with the highest ID wins.
 //!
 //! This is a simple eventual-consistency mechanism that resolves concurrent
 //! writes by always choosing the "latest" update based on timestamp and
 //! replica ordering.
-
+//!
+//! # Tie-break policies
+//!
+//! **Every replica of a register must use the same tie-break policy.**
+//! Replicas with different policies resolve the same tie differently and
+//! will never converge. The policy is serialized with the state so that a
+//! mismatch is detectable: [`LWWRegister::try_join`] reports it as an
+//! error, while [`Lattice::join`] (which cannot fail) deterministically
+//! falls back to [`TieBreak::ReplicaIdMax`] for that join.
+//!
+//! Custom policies are identified by a registry key rather than a function
+//! pointer. The function itself is not serialized; after deserializing a
+//! register with a custom policy, re-attach it with
+//! [`LWWRegister::attach_resolver`]. Until then ties fall back to
+//! [`TieBreak::ReplicaIdMax`].
+//!
+//! # Hybrid logical clocks
+//!
+//! A replica whose wall clock runs ahead wins every conflict until the
+//! others catch up, and one that runs behind loses even its latest writes.
+//! A register created with [`LWWRegister::with_hlc`] stamps writes made with
+//! [`set_now`](LWWRegister::set_now) from a [hybrid logical clock](crate::hlc)
+//! instead, and every join advances that clock past the remote write, so a
+//! local write always supersedes the writes this replica has observed.
+//! HLC timestamps are packed integers that dwarf small explicit ones; don't
+//! mix the two styles across replicas of the same register.
+
+use crate::clock::SharedClock;
+use crate::hlc::Hlc;
 use crate::lattice::Lattice;
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::fmt;
+
+/// A custom tie-break comparator over `(value, timestamp, replica_id)`.
+///
+/// Returns `Greater` if the left write should win.
+pub type TieBreakFn<T, K> = fn(&(T, u64, K), &(T, u64, K)) -> Ordering;
+
+/// Policy for resolving writes with equal timestamps.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum TieBreak {
+    /// The higher replica ID wins (the default).
+    #[default]
+    ReplicaIdMax,
+    /// The lower replica ID wins.
+    ReplicaIdMin,
+    /// A named custom comparator, attached per replica.
+    Custom(String),
+}
+
+/// Error returned when joining registers with different tie-break policies.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TieBreakMismatch {
+    pub ours: TieBreak,
+    pub theirs: TieBreak,
+}
+
+impl fmt::Display for TieBreakMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "tie-break policy mismatch: {:?} vs {:?}",
+            self.ours, self.theirs
+        )
+    }
+}
+
+impl std::error::Error for TieBreakMismatch {}
+
+/// A write accepted by the register, kept for auditing.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriteRecord<T, K> {
+    pub value: T,
+    pub timestamp: u64,
+    pub replica_id: K,
+}
 
 /// A Last-Write-Wins Register CRDT
 ///
 /// Stores a value along with a timestamp and replica ID.
-/// The value with the highest timestamp (tie-break on replica_id) always wins.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// The value with the highest timestamp (tie-break per [`TieBreak`]) always wins.
+///
+/// Equality compares the value, the winning write and the policy; the
+/// write history is audit metadata and is ignored.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LWWRegister<T: Ord + Clone, K: Ord + Clone> {
     /// The current value
     value: Option<T>,
@@ -22,8 +101,34 @@ pub struct LWWRegister<T: Ord + Clone, K: Ord + Clone> {
     timestamp: u64,
     /// The replica ID that wrote this value (for tie-breaking)
     replica_id: K,
+    /// Tie-break policy
+    #[serde(default)]
+    tiebreak: TieBreak,
+    /// Comparator for a custom policy (not serialized)
+    #[serde(skip)]
+    resolver: Option<TieBreakFn<T, K>>,
+    /// Last accepted writes, oldest first
+    #[serde(default)]
+    history: VecDeque<WriteRecord<T, K>>,
+    /// Maximum history length (0 disables history)
+    #[serde(default)]
+    history_limit: usize,
+    /// This replica's clock for [`set_now`](Self::set_now)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hlc: Option<Hlc<K>>,
+}
+
+impl<T: Ord + Clone, K: Ord + Clone> PartialEq for LWWRegister<T, K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+            && self.timestamp == other.timestamp
+            && self.replica_id == other.replica_id
+            && self.tiebreak == other.tiebreak
+    }
 }
 
+impl<T: Ord + Clone, K: Ord + Clone> Eq for LWWRegister<T, K> {}
+
 impl<T: Ord + Clone, K: Ord + Clone> LWWRegister<T, K> {
     /// Create a new LWW Register with no value
     pub fn new(replica_id: K) -> Self {
@@ -31,20 +136,135 @@ impl<T: Ord + Clone, K: Ord + Clone> LWWRegister<T, K> {
             value: None,
             timestamp: 0,
             replica_id,
+            tiebreak: TieBreak::default(),
+            resolver: None,
+            history: VecDeque::new(),
+            history_limit: 0,
+            hlc: None,
         }
     }
 
+    /// Create a register whose [`set_now`](Self::set_now) stamps writes
+    /// with a hybrid logical clock for `replica_id`.
+    pub fn with_hlc(replica_id: K) -> Self {
+        let mut register = Self::new(replica_id.clone());
+        register.hlc = Some(Hlc::new(replica_id));
+        register
+    }
+
+    /// Read the hybrid logical clock's wall time from `clock`.
+    ///
+    /// Has no effect on a register created without [`with_hlc`](Self::with_hlc).
+    pub fn with_clock(mut self, clock: impl Into<SharedClock>) -> Self {
+        self.hlc = self.hlc.map(|hlc| hlc.with_clock(clock));
+        self
+    }
+
+    /// Get this replica's hybrid logical clock, if it has one.
+    pub fn hlc(&self) -> Option<&Hlc<K>> {
+        self.hlc.as_ref()
+    }
+
+    /// Use the given tie-break policy.
+    ///
+    /// All replicas of this register must use the same policy. For
+    /// [`TieBreak::Custom`] prefer [`with_custom_tiebreak`](Self::with_custom_tiebreak),
+    /// which also attaches the comparator.
+    pub fn with_tiebreak(mut self, tiebreak: TieBreak) -> Self {
+        self.tiebreak = tiebreak;
+        self.resolver = None;
+        self
+    }
+
+    /// Use a named custom tie-break comparator.
+    ///
+    /// The name is serialized with the state; every replica must register
+    /// the same comparator under the same name.
+    pub fn with_custom_tiebreak(mut self, name: impl Into<String>, f: TieBreakFn<T, K>) -> Self {
+        self.tiebreak = TieBreak::Custom(name.into());
+        self.resolver = Some(f);
+        self
+    }
+
+    /// Keep the last `n` accepted writes for auditing.
+    pub fn with_write_history(mut self, n: usize) -> Self {
+        self.history_limit = n;
+        self.truncate_history();
+        self
+    }
+
+    /// Re-attach the comparator for a custom policy, e.g. after deserializing.
+    pub fn attach_resolver(
+        &mut self,
+        name: &str,
+        f: TieBreakFn<T, K>,
+    ) -> Result<(), TieBreakMismatch> {
+        match &self.tiebreak {
+            TieBreak::Custom(current) if current == name => {
+                self.resolver = Some(f);
+                Ok(())
+            }
+            other => Err(TieBreakMismatch {
+                ours: other.clone(),
+                theirs: TieBreak::Custom(name.to_string()),
+            }),
+        }
+    }
+
+    /// Get the tie-break policy.
+    pub fn tiebreak(&self) -> &TieBreak {
+        &self.tiebreak
+    }
+
     /// Set a new value with the given timestamp
     pub fn set(&mut self, value: T, timestamp: u64, replica_id: K) {
-        if timestamp > self.timestamp
-            || (timestamp == self.timestamp && replica_id >= self.replica_id)
-        {
+        let wins = match timestamp.cmp(&self.timestamp) {
+            Ordering::Greater => true,
+            Ordering::Less => false,
+            // A rewrite by the same replica at the same timestamp replaces the value
+            Ordering::Equal if replica_id == self.replica_id => true,
+            Ordering::Equal => match &self.value {
+                None => self.tie_order(&replica_id, &self.replica_id) != Ordering::Less,
+                Some(current) => {
+                    let incoming = (value.clone(), timestamp, replica_id.clone());
+                    let existing = (current.clone(), self.timestamp, self.replica_id.clone());
+                    self.compare_writes(&incoming, &existing) != Ordering::Less
+                }
+            },
+        };
+
+        if wins {
+            self.record(WriteRecord {
+                value: value.clone(),
+                timestamp,
+                replica_id: replica_id.clone(),
+            });
             self.value = Some(value);
             self.timestamp = timestamp;
             self.replica_id = replica_id;
         }
     }
 
+    /// Set a new value stamped by the hybrid logical clock, returning the
+    /// packed timestamp.
+    ///
+    /// The write supersedes the current value, whichever replica wrote it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the register was not created with [`with_hlc`](Self::with_hlc).
+    pub fn set_now(&mut self, value: T) -> u64 {
+        let hlc = self
+            .hlc
+            .as_mut()
+            .expect("set_now requires a register created with LWWRegister::with_hlc");
+        hlc.observe_packed(self.timestamp);
+        let stamp = hlc.now();
+        let timestamp = stamp.packed();
+        self.set(value, timestamp, stamp.replica_id);
+        timestamp
+    }
+
     /// Get the current value if it exists
     pub fn get(&self) -> Option<&T> {
         self.value.as_ref()
@@ -60,6 +280,21 @@ impl<T: Ord + Clone, K: Ord + Clone> LWWRegister<T, K> {
         &self.replica_id
     }
 
+    /// Get the timestamp and replica of the winning write.
+    pub fn last_write_info(&self) -> (u64, &K) {
+        (self.timestamp, &self.replica_id)
+    }
+
+    /// Get the recorded write history, oldest first.
+    ///
+    /// History is empty unless enabled with
+    /// [`with_write_history`](Self::with_write_history). Joins merge both
+    /// sides' histories, ordered like the writes themselves and truncated
+    /// to the larger limit, so the result does not depend on merge order.
+    pub fn write_history(&self) -> impl Iterator<Item = &WriteRecord<T, K>> {
+        self.history.iter()
+    }
+
     /// Check if the register is empty (no value set)
     pub fn is_empty(&self) -> bool {
         self.value.is_none()
@@ -70,6 +305,135 @@ impl<T: Ord + Clone, K: Ord + Clone> LWWRegister<T, K> {
         self.value = None;
         self.timestamp = 0;
     }
+
+    /// Join, failing if the two registers use different tie-break policies.
+    ///
+    /// Empty registers carry no writes and never conflict.
+    pub fn try_join(&self, other: &Self) -> Result<Self, TieBreakMismatch> {
+        if self.conflicts_with(other) {
+            return Err(TieBreakMismatch {
+                ours: self.tiebreak.clone(),
+                theirs: other.tiebreak.clone(),
+            });
+        }
+        Ok(self.join_inner(other))
+    }
+
+    fn conflicts_with(&self, other: &Self) -> bool {
+        !self.is_empty() && !other.is_empty() && self.tiebreak != other.tiebreak
+    }
+
+    /// Order two replica IDs under a built-in policy.
+    fn tie_order(&self, a: &K, b: &K) -> Ordering {
+        match self.tiebreak {
+            TieBreak::ReplicaIdMin => b.cmp(a),
+            _ => a.cmp(b),
+        }
+    }
+
+    /// Order two writes: timestamp first, then the tie-break policy.
+    /// Falls back to replica ID and value so the order is always total.
+    fn compare_writes(&self, a: &(T, u64, K), b: &(T, u64, K)) -> Ordering {
+        a.1.cmp(&b.1)
+            .then_with(|| match (&self.tiebreak, self.resolver) {
+                (TieBreak::Custom(_), Some(f)) => f(a, b),
+                _ => Ordering::Equal,
+            })
+            .then_with(|| self.tie_order(&a.2, &b.2))
+            .then_with(|| a.0.cmp(&b.0))
+    }
+
+    fn record(&mut self, write: WriteRecord<T, K>) {
+        if self.history_limit == 0 {
+            return;
+        }
+        self.history.push_back(write);
+        self.truncate_history();
+    }
+
+    fn truncate_history(&mut self) {
+        while self.history.len() > self.history_limit {
+            self.history.pop_front();
+        }
+    }
+
+    fn join_inner(&self, other: &Self) -> Self {
+        // Empty registers adopt the other side's policy. Otherwise the
+        // greater policy is kept so the result is independent of merge
+        // order, and a genuine mismatch resolves with the default policy.
+        let tiebreak = if self.is_empty() && !other.is_empty() {
+            other.tiebreak.clone()
+        } else if other.is_empty() && !self.is_empty() {
+            self.tiebreak.clone()
+        } else {
+            self.tiebreak.clone().max(other.tiebreak.clone())
+        };
+        let conflict = self.conflicts_with(other);
+        let resolver = if conflict {
+            None
+        } else if self.tiebreak == tiebreak && self.resolver.is_some() {
+            self.resolver
+        } else if other.tiebreak == tiebreak {
+            other.resolver
+        } else {
+            None
+        };
+        let mut base = Self::new(self.replica_id.clone());
+        if !conflict {
+            base.tiebreak = tiebreak.clone();
+            base.resolver = resolver;
+        }
+
+        let self_wins = match (&self.value, &other.value) {
+            (Some(a), Some(b)) => {
+                let a = (a.clone(), self.timestamp, self.replica_id.clone());
+                let b = (b.clone(), other.timestamp, other.replica_id.clone());
+                base.compare_writes(&a, &b) != Ordering::Less
+            }
+            _ => match self.timestamp.cmp(&other.timestamp) {
+                Ordering::Equal => {
+                    base.tie_order(&self.replica_id, &other.replica_id) != Ordering::Less
+                        && self.value >= other.value
+                }
+                ord => ord == Ordering::Greater,
+            },
+        };
+        let winner = if self_wins { self } else { other };
+
+        let mut history: Vec<WriteRecord<T, K>> = self
+            .history
+            .iter()
+            .chain(other.history.iter())
+            .cloned()
+            .collect();
+        history.sort_by(|a, b| {
+            base.compare_writes(
+                &(a.value.clone(), a.timestamp, a.replica_id.clone()),
+                &(b.value.clone(), b.timestamp, b.replica_id.clone()),
+            )
+        });
+        history.dedup();
+
+        // The clock is local to this replica: keep ours, moved past the
+        // remote write.
+        let mut hlc = self.hlc.clone();
+        if let Some(hlc) = &mut hlc {
+            hlc.observe_packed(other.timestamp);
+        }
+
+        let mut result = Self {
+            value: winner.value.clone(),
+            timestamp: winner.timestamp,
+            replica_id: winner.replica_id.clone(),
+            tiebreak,
+            resolver,
+            history: history.into(),
+            history_limit: self.history_limit.max(other.history_limit),
+            hlc,
+        };
+        result.truncate_history();
+        result
+    }
 }
 
 impl<T: Ord + Clone, K: Ord + Clone + Default> Default for LWWRegister<T, K> {
@@ -80,43 +444,23 @@ impl<T: Ord + Clone, K: Ord + Clone + Default> Default for LWWRegister<T, K> {
 
 impl<T: Ord + Clone, K: Ord + Clone + Default> Lattice for LWWRegister<T, K> {
     fn bottom() -> Self {
-        Self {
-            value: None,
-            timestamp: 0,
-            replica_id: K::default(),
-        }
+        Self::new(K::default())
     }
 
     /// Join operation: keep the value with the highest timestamp
-    /// Tie-break on replica_id (higher wins), then on value (higher wins)
+    /// Tie-break per policy, then on value (higher wins). Mismatched
+    /// policies fall back to [`TieBreak::ReplicaIdMax`]; use
+    /// [`LWWRegister::try_join`] to detect them instead.
     fn join(&self, other: &Self) -> Self {
-        // Compare by (timestamp, replica_id, value) tuple
-        let self_wins = match self.timestamp.cmp(&other.timestamp) {
-            std::cmp::Ordering::Greater => true,
-            std::cmp::Ordering::Less => false,
-            std::cmp::Ordering::Equal => {
-                match self.replica_id.cmp(&other.replica_id) {
-                    std::cmp::Ordering::Greater => true,
-                    std::cmp::Ordering::Less => false,
-                    std::cmp::Ordering::Equal => {
-                        // Same timestamp and replica_id: compare values for determinism
-                        self.value >= other.value
-                    }
-                }
-            }
-        };
-
-        if self_wins {
-            self.clone()
-        } else {
-            other.clone()
-        }
+        self.join_inner(other)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::ManualClock;
+    use std::sync::Arc;
 
     #[test]
     fn test_lwwreg_basic_operations() {
@@ -229,4 +573,224 @@ mod tests {
         assert_eq!(deserialized.get(), Some(&42));
         assert_eq!(deserialized.timestamp(), 100);
     }
+
+    fn owner_wins(a: &(i32, u64, String), b: &(i32, u64, String)) -> Ordering {
+        (a.2 == "owner").cmp(&(b.2 == "owner"))
+    }
+
+    #[test]
+    fn test_lwwreg_tiebreak_min() {
+        let mut r1: LWWRegister<i32, String> =
+            LWWRegister::new("a".to_string()).with_tiebreak(TieBreak::ReplicaIdMin);
+        let mut r2 = r1.clone();
+        r1.set(1, 100, "a".to_string());
+        r2.set(2, 100, "b".to_string());
+
+        let joined1 = r1.join(&r2);
+        let joined2 = r2.join(&r1);
+        assert_eq!(joined1, joined2);
+        assert_eq!(joined1.get(), Some(&1));
+        assert_eq!(joined1.last_write_info(), (100, &"a".to_string()));
+
+        // Local set follows the policy too
+        r2.set(1, 100, "a".to_string());
+        assert_eq!(r2.get(), Some(&1));
+    }
+
+    #[test]
+    fn test_lwwreg_custom_tiebreak_all_merge_orders() {
+        let base: LWWRegister<i32, String> =
+            LWWRegister::new(String::new()).with_custom_tiebreak("owner-wins", owner_wins);
+        let writes = [(1, "zeta"), (2, "owner"), (3, "alpha")];
+        let regs: Vec<_> = writes
+            .iter()
+            .map(|(v, r)| {
+                let mut reg = base.clone();
+                reg.set(*v, 100, r.to_string());
+                reg
+            })
+            .collect();
+
+        let orders = [
+            [0, 1, 2],
+            [0, 2, 1],
+            [1, 0, 2],
+            [1, 2, 0],
+            [2, 0, 1],
+            [2, 1, 0],
+        ];
+        for order in orders {
+            let joined = order.iter().fold(
+                LWWRegister::bottom(),
+                |acc: LWWRegister<i32, String>, &i| acc.join(&regs[i]),
+            );
+            assert_eq!(joined.get(), Some(&2));
+            assert_eq!(joined.replica_id(), "owner");
+            assert_eq!(
+                joined.tiebreak(),
+                &TieBreak::Custom("owner-wins".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_lwwreg_custom_tiebreak_survives_serialization() {
+        let mut reg: LWWRegister<i32, String> =
+            LWWRegister::new("owner".to_string()).with_custom_tiebreak("owner-wins", owner_wins);
+        reg.set(1, 100, "owner".to_string());
+
+        let json = serde_json::to_string(&reg).unwrap();
+        let mut restored: LWWRegister<i32, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            restored.tiebreak(),
+            &TieBreak::Custom("owner-wins".to_string())
+        );
+        assert!(restored.attach_resolver("other", owner_wins).is_err());
+        restored.attach_resolver("owner-wins", owner_wins).unwrap();
+
+        restored.set(2, 100, "zeta".to_string());
+        assert_eq!(restored.get(), Some(&1));
+    }
+
+    #[test]
+    fn test_lwwreg_mismatched_policies() {
+        let mut max: LWWRegister<i32, String> = LWWRegister::new("a".to_string());
+        let mut min: LWWRegister<i32, String> =
+            LWWRegister::new("b".to_string()).with_tiebreak(TieBreak::ReplicaIdMin);
+        max.set(1, 100, "a".to_string());
+        min.set(2, 100, "b".to_string());
+
+        let err = max.try_join(&min).unwrap_err();
+        assert_eq!(err.ours, TieBreak::ReplicaIdMax);
+        assert_eq!(err.theirs, TieBreak::ReplicaIdMin);
+
+        // join falls back to the default policy, identically in both orders
+        let joined1 = max.join(&min);
+        let joined2 = min.join(&max);
+        assert_eq!(joined1, joined2);
+        assert_eq!(joined1.get(), Some(&2));
+
+        // Empty registers never conflict
+        let empty: LWWRegister<i32, String> = LWWRegister::new("c".to_string());
+        assert_eq!(empty.try_join(&min).unwrap(), min);
+    }
+
+    #[test]
+    fn test_lwwreg_write_history_survives_join() {
+        let mut r1: LWWRegister<i32, String> =
+            LWWRegister::new("a".to_string()).with_write_history(3);
+        let mut r2: LWWRegister<i32, String> =
+            LWWRegister::new("b".to_string()).with_write_history(3);
+
+        r1.set(1, 10, "a".to_string());
+        r1.set(3, 30, "a".to_string());
+        r2.set(2, 20, "b".to_string());
+        r2.set(4, 40, "b".to_string());
+        // Stale write is not accepted, so not recorded
+        r2.set(0, 5, "b".to_string());
+
+        let joined1 = r1.join(&r2);
+        let joined2 = r2.join(&r1);
+        let history: Vec<_> = joined1.write_history().map(|w| w.value).collect();
+        assert_eq!(history, vec![2, 3, 4]);
+        assert!(joined1.write_history().eq(joined2.write_history()));
+        assert_eq!(joined1.last_write_info(), (40, &"b".to_string()));
+    }
+
+    const HOUR: u64 = 60 * 60 * 1_000;
+
+    fn hlc_replica(id: &str, millis: u64) -> LWWRegister<String, String> {
+        LWWRegister::with_hlc(id.to_string()).with_clock(Arc::new(ManualClock::new(millis)))
+    }
+
+    #[test]
+    fn test_lwwreg_hlc_lagging_clock_wins_after_observing() {
+        let now = 1_700_000_000_000;
+        let mut ahead = hlc_replica("a", now);
+        let mut behind = hlc_replica("b", now - HOUR);
+
+        ahead.set_now("from a".to_string());
+        behind = behind.join(&ahead);
+        behind.set_now("from b".to_string());
+
+        assert_eq!(behind.get(), Some(&"from b".to_string()));
+        let merged = ahead.join(&behind);
+        assert_eq!(merged.get(), Some(&"from b".to_string()));
+        assert_eq!(merged, behind.join(&ahead));
+
+        // The lagging replica's clock keeps ordering its writes after a's.
+        behind.set_now("again".to_string());
+        assert!(behind.timestamp() > ahead.timestamp());
+    }
+
+    #[test]
+    fn test_lwwreg_hlc_same_instant_tie_breaks_by_replica_id() {
+        let now = 1_700_000_000_000;
+        let mut a = hlc_replica("a", now);
+        let mut b = hlc_replica("b", now);
+
+        let ta = a.set_now("from a".to_string());
+        let tb = b.set_now("from b".to_string());
+        assert_eq!(ta, tb);
+
+        let ab = a.join(&b);
+        let ba = b.join(&a);
+        assert_eq!(ab, ba);
+        assert_eq!(ab.get(), Some(&"from b".to_string()));
+        assert_eq!(ab.replica_id(), "b");
+    }
+
+    #[test]
+    fn test_lwwreg_hlc_three_replicas_converge() {
+        let now = 1_700_000_000_000;
+        let clocks = [
+            Arc::new(ManualClock::new(now)),
+            Arc::new(ManualClock::new(now - HOUR)),
+            Arc::new(ManualClock::new(now + 5_000)),
+        ];
+        let mut replicas: Vec<LWWRegister<String, String>> = ["a", "b", "c"]
+            .iter()
+            .zip(&clocks)
+            .map(|(id, clock)| LWWRegister::with_hlc(id.to_string()).with_clock(clock.clone()))
+            .collect();
+
+        for round in 0..5 {
+            for (i, replica) in replicas.iter_mut().enumerate() {
+                replica.set_now(format!("{}-{}", i, round));
+                clocks[i].advance(10);
+            }
+            // Gossip in a ring: each replica observes its predecessor.
+            let snapshot = replicas.clone();
+            for i in 0..replicas.len() {
+                let from = &snapshot[(i + 2) % 3];
+                replicas[i] = replicas[i].join(from);
+            }
+        }
+
+        let a = replicas[0].join(&replicas[1]).join(&replicas[2]);
+        let b = replicas[2].join(&replicas[0]).join(&replicas[1]);
+        let c = replicas[1].join(&replicas[2]).join(&replicas[0]);
+        assert_eq!(a, b);
+        assert_eq!(b, c);
+
+        // A write after full sync wins everywhere, even from the slow clock.
+        let mut slow = replicas[1].join(&a);
+        slow.set_now("last".to_string());
+        for replica in &replicas {
+            assert_eq!(replica.join(&slow).get(), Some(&"last".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_lwwreg_hlc_clock_not_shared_by_join() {
+        let mut a = hlc_replica("a", 1_000);
+        let b: LWWRegister<String, String> = LWWRegister::new("b".to_string());
+        a.set_now("x".to_string());
+
+        assert!(b.join(&a).hlc().is_none());
+        assert_eq!(a.join(&b).hlc().unwrap().replica_id(), "a");
+        let json = serde_json::to_string(&a).unwrap();
+        let restored: LWWRegister<String, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.hlc().unwrap().last(), a.hlc().unwrap().last());
+    }
 }
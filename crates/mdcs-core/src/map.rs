@@ -5,10 +5,83 @@
 //!
 //! Key design: A single shared causal context ensures that causality is
 //! tracked consistently across the entire map and all nested CRDTs.
+//!
+//! # Storing your own CRDTs
+//!
+//! `CRDTMap<K, V>` holds any `V: Lattice + CausalResettable`; the default
+//! `V` is [`MapRegister`], a multi-value register of [`MapValue`]s. A value
+//! type must keep to this contract:
+//!
+//! - **Dotted updates.** Every update is stamped with the fresh [`Dot`] the
+//!   map hands to [`CRDTMap::update`], and the value remembers it until it
+//!   is reset. The map records every dot in its shared [`CausalContext`].
+//! - **Join is a union of updates.** The dots of `a ⊔ b` are the dots of
+//!   `a` and of `b`, and joining never invents or drops one.
+//! - **Reset forgets exactly the given dots.** After `reset(dots)` the
+//!   value is what it would be had those updates never happened, and
+//!   updates with other dots are untouched.
+//! - **Bottom is an empty slot.** A value without dots must equal
+//!   `V::bottom()`; the map drops such a slot and reports the key absent.
+//!
+//! Removing a key resets the value with every dot the remover observed, so
+//! a concurrent update (whose dot it had not seen) survives the merge and
+//! the key reappears holding only that update: observed-remove semantics.
+//! Value types don't need [`DeltaCRDT`](crate::lattice::DeltaCRDT): the
+//! deltas returned by [`CRDTMap::update`] and [`CRDTMap::remove`] are maps
+//! themselves, so `DeltaReplica<CRDTMap<K, V>>` can ship them as is.
+//!
+//! ```rust
+//! use mdcs_core::lattice::Lattice;
+//! use mdcs_core::map::{CRDTMap, CausalResettable, Dot};
+//! use std::collections::{BTreeMap, BTreeSet};
+//!
+//! /// A counter whose increments are tagged with their dots
+//! #[derive(Clone, Debug, Default, PartialEq)]
+//! struct Counter(BTreeMap<Dot, u64>);
+//!
+//! impl Lattice for Counter {
+//!     fn bottom() -> Self {
+//!         Self::default()
+//!     }
+//!
+//!     fn join(&self, other: &Self) -> Self {
+//!         let mut joined = self.clone();
+//!         joined.0.extend(other.0.clone());
+//!         joined
+//!     }
+//! }
+//!
+//! impl CausalResettable for Counter {
+//!     fn dots(&self) -> BTreeSet<Dot> {
+//!         self.0.keys().cloned().collect()
+//!     }
+//!
+//!     fn reset(&mut self, dots: &BTreeSet<Dot>) {
+//!         self.0.retain(|dot, _| !dots.contains(dot));
+//!     }
+//! }
+//!
+//! let mut a: CRDTMap<&str, Counter> = CRDTMap::new();
+//! a.update("a", "likes", |counter, dot| {
+//!     counter.0.insert(dot, 3);
+//! });
+//! let mut b = a.clone();
+//!
+//! // a removes the key while b concurrently increments it
+//! a.remove(&"likes");
+//! b.update("b", "likes", |counter, dot| {
+//!     counter.0.insert(dot, 2);
+//! });
+//!
+//! let merged = a.join(&b);
+//! assert_eq!(merged, b.join(&a));
+//! let total: u64 = merged.value(&"likes").unwrap().0.values().sum();
+//! assert_eq!(total, 2);
+//! ```
 
 use crate::lattice::Lattice;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 /// A unique identifier for a write operation (dot)
 /// Tracks which replica created this value and when
@@ -31,13 +104,13 @@ impl Dot {
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CausalContext {
     /// Set of all dots that have been created
-    dots: std::collections::BTreeSet<Dot>,
+    dots: BTreeSet<Dot>,
 }
 
 impl CausalContext {
     pub fn new() -> Self {
         Self {
-            dots: std::collections::BTreeSet::new(),
+            dots: BTreeSet::new(),
         }
     }
 
@@ -49,6 +122,16 @@ impl CausalContext {
         self.dots.contains(dot)
     }
 
+    /// The next dot for `replica_id`: one past the highest it has seen
+    pub fn next_dot(&self, replica_id: &str) -> Dot {
+        let seq = self
+            .dots
+            .range(Dot::new(replica_id, 0)..=Dot::new(replica_id, u64::MAX))
+            .next_back()
+            .map_or(1, |dot| dot.seq + 1);
+        Dot::new(replica_id, seq)
+    }
+
     pub fn join(&self, other: &CausalContext) -> CausalContext {
         let mut joined = self.clone();
         for dot in &other.dots {
@@ -64,53 +147,123 @@ impl Default for CausalContext {
     }
 }
 
+/// A CRDT made of dotted updates, which a [`CRDTMap`] can hold
+///
+/// See the [module documentation](self) for the contract implementations
+/// must keep.
+pub trait CausalResettable {
+    /// Dots of the updates this value is made of
+    fn dots(&self) -> BTreeSet<Dot>;
+
+    /// Forget the updates whose dots are in `dots`
+    fn reset(&mut self, dots: &BTreeSet<Dot>);
+}
+
 /// A generic value that can be stored in the map
-/// This enables composing different CRDT types
+/// Held through a [`MapRegister`]; other CRDTs are stored by implementing
+/// [`CausalResettable`] for them
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MapValue {
     Int(i64),
     Text(String),
     Bytes(Vec<u8>),
-    // For nested maps: Box<CRDTMap>
-    // For other CRDTs: Box<dyn Lattice>
+}
+
+/// Multi-value register of [`MapValue`]s, the default map value
+///
+/// A write replaces the values it observed; concurrent writes coexist.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MapRegister {
+    values: BTreeMap<Dot, MapValue>,
+}
+
+// Serialized as Vec<(Dot, MapValue)> since JSON keys must be strings
+impl Serialize for MapRegister {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let values: Vec<(&Dot, &MapValue)> = self.values.iter().collect();
+        values.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MapRegister {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let values: Vec<(Dot, MapValue)> = Vec::deserialize(deserializer)?;
+        Ok(Self {
+            values: values.into_iter().collect(),
+        })
+    }
+}
+
+impl MapRegister {
+    /// Replace the current values with `value`
+    pub fn write(&mut self, dot: Dot, value: MapValue) {
+        self.values.clear();
+        self.values.insert(dot, value);
+    }
+
+    /// Current values, one per concurrent write
+    pub fn values(&self) -> impl Iterator<Item = &MapValue> {
+        self.values.values()
+    }
+}
+
+impl Lattice for MapRegister {
+    fn bottom() -> Self {
+        Self::default()
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        let mut values = self.values.clone();
+        for (dot, value) in &other.values {
+            values.insert(dot.clone(), value.clone());
+        }
+        Self { values }
+    }
+}
+
+impl CausalResettable for MapRegister {
+    fn dots(&self) -> BTreeSet<Dot> {
+        self.values.keys().cloned().collect()
+    }
+
+    fn reset(&mut self, dots: &BTreeSet<Dot>) {
+        self.values.retain(|dot, _| !dots.contains(dot));
+    }
 }
 
 /// Map CRDT - composable container for nested CRDTs
 ///
-/// Maps keys to values, each value is tagged with a dot.
-/// A value is "live" if its dot is in the context.
-/// A value is "removed" if its dot is in the context but not in the store.
+/// Maps keys to values made of dotted updates.
+/// An update is "live" if its dot is in a value.
+/// An update is "removed" if its dot is in the context but not in the store.
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct CRDTMap<K: Ord + Clone> {
-    /// Maps keys to dots that have been written to this key
-    entries: BTreeMap<K, BTreeMap<Dot, MapValue>>,
+pub struct CRDTMap<K: Ord + Clone, V = MapRegister> {
+    /// Maps keys to their values; a missing key is an empty slot
+    entries: BTreeMap<K, V>,
     /// Shared causal context: all dots that have been created or seen
     context: CausalContext,
-    /// Sequence number for generating dots on this replica
-    local_seq: u64,
 }
 
-// Custom serialization for CRDTMap to handle nested BTreeMap with Dot keys
-impl<K: Ord + Clone + Serialize> Serialize for CRDTMap<K> {
+// Custom serialization for CRDTMap: JSON maps need string keys
+impl<K: Ord + Clone + Serialize, V: Serialize> Serialize for CRDTMap<K, V> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        // Convert entries to a serializable format
         #[derive(Serialize)]
-        struct SerializableCRDTMap<'a, K: Ord + Clone + Serialize> {
-            entries: Vec<(&'a K, Vec<(&'a Dot, &'a MapValue)>)>,
+        struct SerializableCRDTMap<'a, K, V> {
+            entries: Vec<(&'a K, &'a V)>,
             context: &'a CausalContext,
         }
 
-        let entries: Vec<_> = self
-            .entries
-            .iter()
-            .map(|(k, v)| (k, v.iter().collect::<Vec<_>>()))
-            .collect();
-
         let serializable = SerializableCRDTMap {
-            entries,
+            entries: self.entries.iter().collect(),
             context: &self.context,
         };
 
@@ -118,98 +271,106 @@ impl<K: Ord + Clone + Serialize> Serialize for CRDTMap<K> {
     }
 }
 
-impl<'de, K: Ord + Clone + Deserialize<'de>> Deserialize<'de> for CRDTMap<K> {
+impl<'de, K: Ord + Clone + Deserialize<'de>, V: Deserialize<'de>> Deserialize<'de>
+    for CRDTMap<K, V>
+{
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
         #[derive(Deserialize)]
-        struct DeserializableCRDTMap<K: Ord + Clone> {
-            entries: Vec<(K, Vec<(Dot, MapValue)>)>,
+        struct DeserializableCRDTMap<K, V> {
+            entries: Vec<(K, V)>,
             context: CausalContext,
         }
 
-        let deserialized = DeserializableCRDTMap::<K>::deserialize(deserializer)?;
-
-        let entries: BTreeMap<K, BTreeMap<Dot, MapValue>> = deserialized
-            .entries
-            .into_iter()
-            .map(|(k, v)| (k, v.into_iter().collect()))
-            .collect();
+        let deserialized = DeserializableCRDTMap::<K, V>::deserialize(deserializer)?;
 
         Ok(Self {
-            entries,
+            entries: deserialized.entries.into_iter().collect(),
             context: deserialized.context,
-            local_seq: 0,
         })
     }
 }
 
-impl<K: Ord + Clone> CRDTMap<K> {
+impl<K: Ord + Clone, V: Lattice + CausalResettable> CRDTMap<K, V> {
     /// Create a new empty map
     pub fn new() -> Self {
         Self {
             entries: BTreeMap::new(),
             context: CausalContext::new(),
-            local_seq: 0,
         }
     }
 
-    /// Put a value at a key (from this replica)
-    pub fn put(&mut self, replica_id: &str, key: K, value: MapValue) -> Dot {
-        let dot = Dot::new(replica_id, self.local_seq);
-        self.local_seq += 1;
-
-        // Create entry for this key if it doesn't exist
-        let entry = self.entries.entry(key).or_default();
-
-        // Clear previous values for this key and insert new one
-        entry.clear();
-        entry.insert(dot.clone(), value);
-
-        // Track dot in causal context
-        self.context.add_dot(dot.clone());
+    /// The value at a key, if it holds any live update
+    pub fn value(&self, key: &K) -> Option<&V> {
+        self.entries.get(key)
+    }
 
-        dot
+    /// Delta-mutator for [`update`](Self::update)
+    ///
+    /// Runs `mutate` on a copy of the value at `key` (bottom if absent)
+    /// with a fresh dot for `replica_id`. The delta holds the updates the
+    /// mutation added and, in its context, the ones it replaced.
+    pub fn update_delta<F>(&self, replica_id: &str, key: K, mutate: F) -> Self
+    where
+        F: FnOnce(&mut V, Dot),
+    {
+        let old = self.entries.get(&key).cloned().unwrap_or_else(V::bottom);
+        let mut new = old.clone();
+        mutate(&mut new, self.context.next_dot(replica_id));
+
+        let old_dots = old.dots();
+        let new_dots = new.dots();
+        let mut delta = Self::new();
+        for dot in old_dots.symmetric_difference(&new_dots) {
+            delta.context.add_dot(dot.clone());
+        }
+        new.reset(&old_dots.intersection(&new_dots).cloned().collect());
+        delta.insert_slot(key, new);
+        delta
     }
 
-    /// Get the current value at a key
-    /// Returns the value if the key exists and has live entries
-    pub fn get(&self, key: &K) -> Option<&MapValue> {
-        self.entries
-            .get(key)
-            .and_then(|entry| entry.values().next())
+    /// Update the value at a key (from this replica)
+    ///
+    /// Returns the delta to ship to other replicas.
+    pub fn update<F>(&mut self, replica_id: &str, key: K, mutate: F) -> Self
+    where
+        F: FnOnce(&mut V, Dot),
+    {
+        let delta = self.update_delta(replica_id, key, mutate);
+        self.join_assign(&delta);
+        delta
     }
 
-    /// Get all values at a key (for concurrent writes)
-    pub fn get_all(&self, key: &K) -> Vec<&MapValue> {
-        self.entries
-            .get(key)
-            .map(|entry| entry.values().collect())
-            .unwrap_or_default()
+    /// Delta-mutator for [`remove`](Self::remove): the key's dots, as removed
+    pub fn remove_delta(&self, key: &K) -> Self {
+        let mut delta = Self::new();
+        if let Some(value) = self.entries.get(key) {
+            for dot in value.dots() {
+                delta.context.add_dot(dot);
+            }
+        }
+        delta
     }
 
     /// Remove a key by recording all its current dots as removed
-    pub fn remove(&mut self, key: &K) {
-        if let Some(entry) = self.entries.get_mut(key) {
-            // Mark all dots as removed by clearing them but keeping them in context
-            entry.clear();
-        }
+    ///
+    /// Returns the delta to ship to other replicas.
+    pub fn remove(&mut self, key: &K) -> Self {
+        let delta = self.remove_delta(key);
+        self.entries.remove(key);
+        delta
     }
 
     /// Check if a key exists with live values
     pub fn contains_key(&self, key: &K) -> bool {
-        self.entries
-            .get(key)
-            .map(|entry| !entry.is_empty())
-            .unwrap_or(false)
+        self.entries.contains_key(key)
     }
 
     /// Get all keys that have live values
     pub fn keys(&self) -> impl Iterator<Item = &K> {
-        self.entries
-            .iter()
-            .filter_map(|(k, v)| if !v.is_empty() { Some(k) } else { None })
+        self.entries.keys()
     }
 
     /// Get the causal context
@@ -217,48 +378,108 @@ impl<K: Ord + Clone> CRDTMap<K> {
         &self.context
     }
 
+    /// Store a slot, dropping it if it holds no updates
+    fn insert_slot(&mut self, key: K, value: V) {
+        let dots = value.dots();
+        if dots.is_empty() {
+            self.entries.remove(&key);
+        } else {
+            for dot in dots {
+                self.context.add_dot(dot);
+            }
+            self.entries.insert(key, value);
+        }
+    }
+}
+
+impl<K: Ord + Clone> CRDTMap<K> {
+    /// Put a value at a key (from this replica)
+    pub fn put(&mut self, replica_id: &str, key: K, value: MapValue) -> Dot {
+        // The dot `update` hands to the write
+        let dot = self.context.next_dot(replica_id);
+        self.update(replica_id, key, |register, next| {
+            register.write(next, value)
+        });
+        dot
+    }
+
+    /// Get the current value at a key
+    /// Returns the value if the key exists and has live entries
+    pub fn get(&self, key: &K) -> Option<&MapValue> {
+        self.entries
+            .get(key)
+            .and_then(|register| register.values().next())
+    }
+
+    /// Get all values at a key (for concurrent writes)
+    pub fn get_all(&self, key: &K) -> Vec<&MapValue> {
+        self.entries
+            .get(key)
+            .map(|register| register.values().collect())
+            .unwrap_or_default()
+    }
+
     /// Add a value with a specific dot (for merging)
     pub fn put_with_dot(&mut self, key: K, dot: Dot, value: MapValue) {
-        let entry = self.entries.entry(key).or_default();
-        entry.insert(dot.clone(), value);
+        let register = self.entries.entry(key).or_default();
+        register.values.insert(dot.clone(), value);
         self.context.add_dot(dot);
     }
 }
 
-impl<K: Ord + Clone> Default for CRDTMap<K> {
+impl<K: Ord + Clone, V: Lattice + CausalResettable> Default for CRDTMap<K, V> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<K: Ord + Clone> Lattice for CRDTMap<K> {
+impl<K: Ord + Clone, V: Lattice + CausalResettable> Lattice for CRDTMap<K, V> {
     fn bottom() -> Self {
         Self::new()
     }
 
-    /// Join operation: merge all entries and contexts
-    /// For each key, union all the dots and their values
+    /// Join operation: merge contexts, and per key keep the updates both
+    /// sides hold or that the other side has not observed
     fn join(&self, other: &Self) -> Self {
-        let mut entries = self.entries.clone();
-        let mut context = self.context.clone();
-
-        // Merge other's entries
-        for (key, other_entry) in &other.entries {
-            let entry = entries.entry(key.clone()).or_default();
-            for (dot, value) in other_entry {
-                entry.insert(dot.clone(), value.clone());
-            }
-        }
-
-        // Merge contexts
-        context = context.join(&other.context);
+        let mut joined = Self {
+            entries: BTreeMap::new(),
+            context: self.context.join(&other.context),
+        };
 
-        Self {
-            entries,
-            context,
-            local_seq: self.local_seq.max(other.local_seq),
+        let keys: BTreeSet<&K> = self.entries.keys().chain(other.entries.keys()).collect();
+        for key in keys {
+            let ours = self.entries.get(key);
+            let theirs = other.entries.get(key);
+            let value = match (ours, theirs) {
+                (Some(ours), Some(theirs)) => survivors(ours, &theirs.dots(), &other.context)
+                    .join(&survivors(theirs, &ours.dots(), &self.context)),
+                (Some(ours), None) => survivors(ours, &BTreeSet::new(), &other.context),
+                (None, Some(theirs)) => survivors(theirs, &BTreeSet::new(), &self.context),
+                (None, None) => continue,
+            };
+            joined.insert_slot(key.clone(), value);
         }
+        joined
+    }
+}
+
+/// `value` without the updates `context` saw removed: those it observed
+/// that are not among the `kept` dots of the other side
+fn survivors<V: CausalResettable + Clone>(
+    value: &V,
+    kept: &BTreeSet<Dot>,
+    context: &CausalContext,
+) -> V {
+    let removed: BTreeSet<Dot> = value
+        .dots()
+        .into_iter()
+        .filter(|dot| context.contains(dot) && !kept.contains(dot))
+        .collect();
+    let mut value = value.clone();
+    if !removed.is_empty() {
+        value.reset(&removed);
     }
+    value
 }
 
 #[cfg(test)]
@@ -367,6 +588,125 @@ mod tests {
         assert_eq!(merged.get(&"key2".to_string()), Some(&MapValue::Int(20)));
     }
 
+    #[test]
+    fn test_map_remove_wins_over_observed_writes() {
+        let mut map1: CRDTMap<String> = CRDTMap::new();
+        map1.put("replica1", "key1".to_string(), MapValue::Int(42));
+        let map2 = map1.clone();
+
+        map1.remove(&"key1".to_string());
+        assert!(!map1.join(&map2).contains_key(&"key1".to_string()));
+        assert!(!map2.join(&map1).contains_key(&"key1".to_string()));
+    }
+
+    #[test]
+    fn test_map_concurrent_write_survives_remove() {
+        let mut map1: CRDTMap<String> = CRDTMap::new();
+        map1.put("replica1", "key1".to_string(), MapValue::Int(1));
+        let mut map2 = map1.clone();
+
+        map1.remove(&"key1".to_string());
+        map2.put("replica2", "key1".to_string(), MapValue::Int(2));
+
+        let merged = map1.join(&map2);
+        assert_eq!(merged, map2.join(&map1));
+        assert_eq!(merged.get_all(&"key1".to_string()), vec![&MapValue::Int(2)]);
+    }
+
+    #[test]
+    fn test_map_remove_vs_concurrent_update_three_replicas() {
+        let key = "key1".to_string();
+        let gone = "key2".to_string();
+        let mut map1: CRDTMap<String> = CRDTMap::new();
+        map1.put("replica1", key.clone(), MapValue::Int(1));
+        map1.put("replica1", gone.clone(), MapValue::Int(2));
+        let mut map2 = map1.clone();
+        let map3 = map1.clone();
+
+        // replica1 removes both keys; replica2 concurrently updates one of
+        // them; replica3 still holds the old values
+        let mut deltas = vec![map1.remove(&key), map1.remove(&gone)];
+        deltas.push(map2.update("replica2", key.clone(), |register, dot| {
+            register.write(dot, MapValue::Int(3))
+        }));
+
+        let replicas = [&map1, &map2, &map3];
+        let orders = [
+            [0, 1, 2],
+            [0, 2, 1],
+            [1, 0, 2],
+            [1, 2, 0],
+            [2, 0, 1],
+            [2, 1, 0],
+        ];
+        let merged: Vec<_> = orders
+            .iter()
+            .map(|order| {
+                order
+                    .iter()
+                    .fold(CRDTMap::new(), |acc, &i| acc.join(replicas[i]))
+            })
+            .collect();
+        assert!(merged.iter().all(|map| map == &merged[0]));
+        assert_eq!(merged[0].get_all(&key), vec![&MapValue::Int(3)]);
+        assert!(!merged[0].contains_key(&gone));
+
+        // The deltas alone bring the stale replica to the same state, in any order
+        for order in orders {
+            let mut replica = map3.clone();
+            for i in order {
+                replica.join_assign(&deltas[i]);
+            }
+            assert_eq!(replica, merged[0]);
+        }
+    }
+
+    #[test]
+    fn test_map_readded_key_does_not_resurrect_old_value() {
+        let key = "key1".to_string();
+        let mut map1: CRDTMap<String> = CRDTMap::new();
+        map1.put("replica1", key.clone(), MapValue::Int(1));
+        let stale = map1.clone();
+
+        map1.remove(&key);
+        map1.put("replica1", key.clone(), MapValue::Int(2));
+
+        let merged = map1.join(&stale);
+        assert_eq!(merged, stale.join(&map1));
+        assert_eq!(merged.get_all(&key), vec![&MapValue::Int(2)]);
+    }
+
+    #[test]
+    fn test_map_write_replaces_observed_value() {
+        let mut map1: CRDTMap<String> = CRDTMap::new();
+        map1.put("replica1", "key1".to_string(), MapValue::Int(1));
+        let mut map2 = map1.clone();
+        map2.put("replica2", "key1".to_string(), MapValue::Int(2));
+
+        let merged = map1.join(&map2);
+        assert_eq!(merged.get_all(&"key1".to_string()), vec![&MapValue::Int(2)]);
+    }
+
+    #[test]
+    fn test_map_deltas_rebuild_the_state() {
+        let mut map: CRDTMap<String> = CRDTMap::new();
+        let mut replica: CRDTMap<String> = CRDTMap::new();
+
+        let delta = map.update("replica1", "key1".to_string(), |register, dot| {
+            register.write(dot, MapValue::Int(1))
+        });
+        replica.join_assign(&delta);
+        let delta = map.update("replica1", "key1".to_string(), |register, dot| {
+            register.write(dot, MapValue::Int(2))
+        });
+        replica.join_assign(&delta);
+        assert_eq!(replica, map);
+
+        replica.join_assign(&map.remove(&"key1".to_string()));
+        assert_eq!(replica, map);
+        assert!(!replica.contains_key(&"key1".to_string()));
+    }
+
     #[test]
     fn test_map_serialization() {
         let mut map: CRDTMap<String> = CRDTMap::new();
@@ -0,0 +1,18 @@
+//! Observers for maintaining derived state across merges
+//!
+//! A `join_observed` variant of `join` reports what the merge changed, so
+//! a view derived from the state (a count, an index) can be updated instead
+//! of recomputed. Applying the callbacks, in order, to the view of the old
+//! state gives the view of the joined state. Plain `join` is unaffected.
+
+/// Receives membership changes from a set's `join_observed`.
+///
+/// Callbacks come in element order, one per element whose membership
+/// changed; an element merely gaining another tag is not reported.
+pub trait SetObserver<T> {
+    /// `value` was not a member before the join and is now.
+    fn on_added(&mut self, value: &T);
+
+    /// `value` was a member before the join and no longer is.
+    fn on_removed(&mut self, value: &T);
+}
@@ -3,9 +3,11 @@
 //! Each add generates a unique tag.  Remove only removes currently observed tags.
 //!  Concurrent add and remove of the same element:  add wins.
 
-use crate::lattice::{DeltaCRDT, Lattice};
+use crate::lattice::{DeltaCRDT, DeltaRejected, Lattice};
+use crate::observer::SetObserver;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
 use ulid::Ulid;
 
 /// A unique tag for each add operation
@@ -33,7 +35,10 @@ impl Tag {
 /// a concurrent add and remove results in the element being present (add wins).
 ///
 /// Supports delta-state replication via the [`DeltaCRDT`] trait.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+///
+/// Elements iterate in ascending order. The `Debug` output lists each
+/// element with its number of live tags and only counts the tombstones.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ORSet<T: Ord + Clone> {
     /// Maps elements to their active tags
     entries: BTreeMap<T, BTreeSet<Tag>>,
@@ -46,6 +51,12 @@ pub struct ORSet<T: Ord + Clone> {
 }
 
 /// Delta payload for [`ORSet`] replication.
+///
+/// The removals double as the delta's causal context: a replica only
+/// removes tags it has observed, so a receiver that has seen neither the
+/// add nor the removal of one of them is missing history the delta was
+/// generated against. [`ORSet::check_delta`](DeltaCRDT::check_delta)
+/// rejects such deltas.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ORSetDelta<T: Ord + Clone> {
     /// New element additions with their tags.
@@ -81,6 +92,50 @@ impl<T: Ord + Clone> ORSet<T> {
         delta.additions.entry(value).or_default().insert(tag);
     }
 
+    /// Add many elements at once, recording a single delta
+    ///
+    /// Each element still gets its own tag, but tag ids are allocated as a
+    /// consecutive ULID range instead of one `Ulid::new()` per element.
+    pub fn add_all(&mut self, replica_id: &str, values: impl IntoIterator<Item = T>) {
+        let mut additions: BTreeMap<T, BTreeSet<Tag>> = BTreeMap::new();
+        let mut next_id = Ulid::new();
+        for value in values {
+            let tag = Tag {
+                replica_id: replica_id.to_string(),
+                unique_id: next_id,
+            };
+            // On overflow of the random part, start a fresh range
+            next_id = match next_id.increment() {
+                Some(id) => id,
+                None => Ulid::new(),
+            };
+
+            self.entries
+                .entry(value.clone())
+                .or_default()
+                .insert(tag.clone());
+            additions.entry(value).or_default().insert(tag);
+        }
+        if additions.is_empty() {
+            return;
+        }
+
+        // Record in delta
+        match &mut self.pending_delta {
+            Some(delta) => {
+                for (value, tags) in additions {
+                    delta.additions.entry(value).or_default().extend(tags);
+                }
+            }
+            None => {
+                self.pending_delta = Some(ORSetDelta {
+                    additions,
+                    removals: BTreeSet::new(),
+                })
+            }
+        }
+    }
+
     /// Remove all observed instances of an element
     pub fn remove(&mut self, value: &T) {
         if let Some(tags) = self.entries.remove(value) {
@@ -98,18 +153,95 @@ impl<T: Ord + Clone> ORSet<T> {
         }
     }
 
+    /// Remove all observed instances of many elements, recording a single delta
+    pub fn remove_all<'a>(&mut self, values: impl IntoIterator<Item = &'a T>)
+    where
+        T: 'a,
+    {
+        let mut removals = BTreeSet::new();
+        for value in values {
+            if let Some(tags) = self.entries.remove(value) {
+                removals.extend(tags);
+            }
+        }
+        self.record_removals(removals);
+    }
+
+    /// Remove every element matching `predicate` in one pass, recording a
+    /// single delta. Returns the number of elements removed.
+    ///
+    /// Like [`remove`](Self::remove), this only removes the tags observed
+    /// now: a concurrent add of a matching element on another replica
+    /// survives the merge.
+    pub fn remove_where(&mut self, mut predicate: impl FnMut(&T) -> bool) -> usize {
+        let mut removals = BTreeSet::new();
+        let mut removed = 0;
+        self.entries.retain(|value, tags| {
+            if predicate(value) {
+                removals.append(tags);
+                removed += 1;
+                false
+            } else {
+                true
+            }
+        });
+        self.record_removals(removals);
+        removed
+    }
+
+    /// Keep only the elements matching `predicate`, removing the rest as
+    /// [`remove_where`](Self::remove_where) does.
+    pub fn retain(&mut self, mut predicate: impl FnMut(&T) -> bool) {
+        self.remove_where(|value| !predicate(value));
+    }
+
+    /// Tombstone removed tags and record them in the pending delta
+    fn record_removals(&mut self, removals: BTreeSet<Tag>) {
+        if removals.is_empty() {
+            return;
+        }
+        self.tombstones.extend(removals.iter().cloned());
+
+        // Record in delta
+        let delta = self.pending_delta.get_or_insert_with(|| ORSetDelta {
+            additions: BTreeMap::new(),
+            removals: BTreeSet::new(),
+        });
+        delta.removals.extend(removals);
+    }
+
+    /// Iterate over the live tags of an element, one per add that no
+    /// remove has observed. Empty if the element is absent.
+    pub fn tags(&self, value: &T) -> impl Iterator<Item = &Tag> {
+        self.entries.get(value).into_iter().flatten()
+    }
+
+    /// Get the replicas whose adds keep `value` in the set.
+    ///
+    /// After concurrent adds this names every replica that added it; a
+    /// replica whose add was removed is no longer listed.
+    pub fn added_by(&self, value: &T) -> HashSet<&str> {
+        self.tags(value)
+            .map(|tag| tag.replica_id.as_str())
+            .collect()
+    }
+
     /// Check whether `value` is present in the set (has at least one live tag).
     pub fn contains(&self, value: &T) -> bool {
-        self.entries
-            .get(value)
-            .is_some_and(|tags| !tags.is_empty())
+        self.entries.get(value).is_some_and(|tags| !tags.is_empty())
     }
 
-    /// Iterate over all elements currently in the set.
+    /// Iterate over all elements currently in the set, in ascending order.
     pub fn iter(&self) -> impl Iterator<Item = &T> {
         self.entries.keys()
     }
 
+    /// Iterate over the elements in ascending order, each with its live
+    /// tags.
+    pub fn iter_with_tags(&self) -> impl Iterator<Item = (&T, &BTreeSet<Tag>)> {
+        self.entries.iter()
+    }
+
     /// Return the number of distinct elements in the set.
     pub fn len(&self) -> usize {
         self.entries.len()
@@ -119,8 +251,76 @@ impl<T: Ord + Clone> ORSet<T> {
     pub fn is_empty(&self) -> bool {
         self.entries.is_empty()
     }
-}
 
+    /// Check a delta shipped as an ORSet, the state-as-delta counterpart
+    /// of [`check_delta`](DeltaCRDT::check_delta).
+    ///
+    /// Rejects `delta` if it tombstones tags this set has never seen and
+    /// the delta doesn't add itself. A delta that adds and removes the
+    /// same tag keeps only the tombstone, so it is rejected too, which
+    /// costs a full-state sync but is never wrong. Only meant for deltas:
+    /// a full state legitimately carries tombstones for tags removed long
+    /// ago.
+    pub fn check_join(&self, delta: &Self) -> Result<(), DeltaRejected> {
+        let added: BTreeSet<&Tag> = delta.entries.values().flatten().collect();
+        self.check_removals(&delta.tombstones, &added)
+    }
+
+    /// Reject `removals` that name tags neither observed here nor in `added`
+    fn check_removals(
+        &self,
+        removals: &BTreeSet<Tag>,
+        added: &BTreeSet<&Tag>,
+    ) -> Result<(), DeltaRejected> {
+        if removals.is_empty() {
+            return Ok(());
+        }
+        let live: BTreeSet<&Tag> = self.entries.values().flatten().collect();
+        let unobserved = removals
+            .iter()
+            .filter(|tag| {
+                !self.tombstones.contains(tag) && !live.contains(tag) && !added.contains(tag)
+            })
+            .count();
+        if unobserved > 0 {
+            return Err(DeltaRejected { unobserved });
+        }
+        Ok(())
+    }
+
+    /// Join with `other`, reporting each element whose membership changed
+    /// to `observer`.
+    ///
+    /// An element is removed when `other` tombstones all of its tags here
+    /// and added when it gains a live tag while absent.
+    pub fn join_observed(&self, other: &Self, observer: &mut impl SetObserver<T>) -> Self {
+        let result = self.join(other);
+        let mut before = self.entries.keys().peekable();
+        let mut after = result.entries.keys().peekable();
+        loop {
+            match (before.peek(), after.peek()) {
+                (Some(old), Some(new)) if old == new => {
+                    before.next();
+                    after.next();
+                }
+                (Some(old), Some(new)) if old > new => {
+                    observer.on_added(new);
+                    after.next();
+                }
+                (Some(old), _) => {
+                    observer.on_removed(old);
+                    before.next();
+                }
+                (None, Some(new)) => {
+                    observer.on_added(new);
+                    after.next();
+                }
+                (None, None) => break,
+            }
+        }
+        result
+    }
+}
 
 impl<T: Ord + Clone> Default for ORSet<T> {
     fn default() -> Self {
@@ -128,6 +328,51 @@ impl<T: Ord + Clone> Default for ORSet<T> {
     }
 }
 
+impl<'a, T: Ord + Clone> IntoIterator for &'a ORSet<T> {
+    type Item = &'a T;
+    type IntoIter = std::collections::btree_map::Keys<'a, T, BTreeSet<Tag>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.keys()
+    }
+}
+
+impl<T: Ord + Clone + fmt::Debug> fmt::Debug for ORSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct LiveTags<'a, T: Ord + Clone>(&'a ORSet<T>);
+
+        impl<T: Ord + Clone + fmt::Debug> fmt::Debug for LiveTags<'_, T> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_map()
+                    .entries(
+                        self.0
+                            .entries
+                            .iter()
+                            .map(|(value, tags)| (value, tags.len())),
+                    )
+                    .finish()
+            }
+        }
+
+        f.debug_struct("ORSet")
+            .field("entries", &LiveTags(self))
+            .field("tombstones", &self.tombstones.len())
+            .finish()
+    }
+}
+
+/// Collect `(replica_id, value)` pairs, each added with a fresh tag of its
+/// replica. The adds are recorded as one pending delta.
+impl<T: Ord + Clone, R: AsRef<str>> FromIterator<(R, T)> for ORSet<T> {
+    fn from_iter<I: IntoIterator<Item = (R, T)>>(adds: I) -> Self {
+        let mut set = Self::new();
+        for (replica_id, value) in adds {
+            set.add(replica_id.as_ref(), value);
+        }
+        set
+    }
+}
+
 impl<T: Ord + Clone> Lattice for ORSet<T> {
     fn bottom() -> Self {
         Self::new()
@@ -195,8 +440,13 @@ impl<T: Ord + Clone> DeltaCRDT for ORSet<T> {
     }
 
     fn apply_delta(&mut self, delta: &Self::Delta) {
-        // Apply removals to tombstones
+        // Apply removals to tombstones and drop them from live entries
         self.tombstones.extend(delta.removals.iter().cloned());
+        if !delta.removals.is_empty() {
+            for tags in self.entries.values_mut() {
+                tags.retain(|tag| !delta.removals.contains(tag));
+            }
+        }
 
         // Apply additions, filtering tombstones
         for (value, tags) in &delta.additions {
@@ -211,4 +461,206 @@ impl<T: Ord + Clone> DeltaCRDT for ORSet<T> {
         // Clean up empty entries
         self.entries.retain(|_, tags| !tags.is_empty());
     }
+
+    fn check_delta(&self, delta: &Self::Delta) -> Result<(), DeltaRejected> {
+        let added: BTreeSet<&Tag> = delta.additions.values().flatten().collect();
+        self.check_removals(&delta.removals, &added)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta_of(set: &mut ORSet<&'static str>) -> ORSetDelta<&'static str> {
+        set.split_delta().expect("pending delta")
+    }
+
+    #[test]
+    fn test_apply_delta_removes_live_tags() {
+        let mut a = ORSet::new();
+        let mut b = ORSet::new();
+        a.add("a", "x");
+        b.apply_delta(&delta_of(&mut a));
+        assert!(b.contains(&"x"));
+
+        a.remove(&"x");
+        b.apply_delta(&delta_of(&mut a));
+        assert!(!b.contains(&"x"));
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_check_delta_rejects_unobserved_removal() {
+        let mut a = ORSet::new();
+        a.add("a", "x");
+        let add = delta_of(&mut a);
+        a.remove(&"x");
+        let remove = delta_of(&mut a);
+
+        // The receiver never saw the add the removal refers to
+        let mut b = ORSet::new();
+        assert_eq!(
+            b.try_apply_delta(&remove),
+            Err(DeltaRejected { unobserved: 1 })
+        );
+        assert_eq!(b, ORSet::new());
+
+        // Once it has, the removal applies, and applying it again is fine
+        b.try_apply_delta(&add).unwrap();
+        b.try_apply_delta(&remove).unwrap();
+        b.try_apply_delta(&remove).unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_check_join_rejects_unobserved_tombstone() {
+        let mut a = ORSet::new();
+        a.add("a", "x");
+        let mut remove = ORSet::new();
+        remove.tombstones.extend(a.tags(&"x").cloned());
+
+        assert_eq!(
+            ORSet::new().check_join(&remove),
+            Err(DeltaRejected { unobserved: 1 })
+        );
+        assert_eq!(a.check_join(&remove), Ok(()));
+        assert_eq!(ORSet::new().check_join(&a), Ok(()));
+    }
+
+    /// Member count kept up to date from join callbacks
+    #[derive(Default)]
+    struct MemberCount(usize);
+
+    impl<T> SetObserver<T> for MemberCount {
+        fn on_added(&mut self, _value: &T) {
+            self.0 += 1;
+        }
+
+        fn on_removed(&mut self, _value: &T) {
+            self.0 -= 1;
+        }
+    }
+
+    #[test]
+    fn test_join_observed_keeps_member_count() {
+        let mut replicas: Vec<ORSet<u64>> = vec![ORSet::new(); 3];
+        let mut counts: Vec<MemberCount> = (0..3).map(|_| MemberCount::default()).collect();
+        let mut rng = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move |n: u64| {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            rng % n
+        };
+
+        for _ in 0..500 {
+            let i = next(3) as usize;
+            let value = next(8);
+            match next(3) {
+                0 => {
+                    let present = replicas[i].contains(&value);
+                    replicas[i].add(&format!("r{}", i), value);
+                    counts[i].0 += usize::from(!present);
+                }
+                1 => {
+                    let present = replicas[i].contains(&value);
+                    replicas[i].remove(&value);
+                    counts[i].0 -= usize::from(present);
+                }
+                _ => {
+                    let j = next(3) as usize;
+                    let expected = replicas[i].join(&replicas[j]);
+                    replicas[i] = replicas[i].join_observed(&replicas[j], &mut counts[i]);
+                    assert_eq!(replicas[i], expected);
+                }
+            }
+            assert_eq!(counts[i].0, replicas[i].len());
+        }
+    }
+
+    #[test]
+    fn test_check_delta_accepts_self_contained_delta() {
+        // An add and remove in one delta carries its own context
+        let mut a = ORSet::new();
+        a.add("a", "x");
+        a.remove(&"x");
+        a.add("a", "y");
+        let delta = delta_of(&mut a);
+
+        let mut b = ORSet::new();
+        b.try_apply_delta(&delta).unwrap();
+        assert_eq!(b.iter().collect::<Vec<_>>(), vec![&"y"]);
+    }
+
+    #[test]
+    fn test_from_iter_records_one_delta() {
+        let mut set: ORSet<&str> = [("a", "x"), ("b", "x"), ("a", "y")].into_iter().collect();
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.tags(&"x").count(), 2);
+
+        let delta = delta_of(&mut set);
+        assert_eq!(delta.additions.len(), 2);
+        assert!(set.split_delta().is_none());
+    }
+
+    #[test]
+    fn test_tags_after_concurrent_add_remove_readd() {
+        let mut a = ORSet::new();
+        a.add("a", "x");
+        let mut b = a.clone();
+        let mut c = a.clone();
+
+        // b removes the add it saw while c adds again concurrently
+        b.remove(&"x");
+        c.add("c", "x");
+        assert_eq!(c.added_by(&"x"), HashSet::from(["a", "c"]));
+        assert!(a.join(&b).added_by(&"x").is_empty());
+
+        // Only c's add survives the removal
+        let mut merged = a.join(&b).join(&c);
+        assert_eq!(merged.tags(&"x").count(), 1);
+        assert_eq!(merged.added_by(&"x"), HashSet::from(["c"]));
+        assert_eq!(merged, c.join(&b).join(&a));
+
+        // A re-add after the remove is visible with its own tag
+        b.add("b", "x");
+        merged = merged.join(&b);
+        assert_eq!(merged.added_by(&"x"), HashSet::from(["b", "c"]));
+        let tags: Vec<&Tag> = merged.tags(&"x").collect();
+        assert!(tags.iter().all(|tag| !merged.tombstones.contains(tag)));
+
+        merged.remove(&"x");
+        assert!(!merged.contains(&"x"));
+        assert_eq!(merged.tags(&"x").count(), 0);
+        assert!(merged.added_by(&"x").is_empty());
+    }
+
+    #[test]
+    fn test_iteration_and_debug() {
+        let mut set = ORSet::new();
+        for value in [3, 1, 2] {
+            set.add("a", value);
+        }
+        set.add("b", 2);
+        set.remove(&3);
+
+        assert_eq!(set.len(), 2);
+        assert_eq!((&set).into_iter().collect::<Vec<_>>(), vec![&1, &2]);
+        let counts: Vec<(i32, usize)> = set
+            .iter_with_tags()
+            .map(|(value, tags)| (*value, tags.len()))
+            .collect();
+        assert_eq!(counts, vec![(1, 1), (2, 2)]);
+
+        let mut sum = 0;
+        for value in &set {
+            sum += value;
+        }
+        assert_eq!(sum, 3);
+        assert_eq!(
+            format!("{:?}", set),
+            "ORSet { entries: {1: 1, 2: 2}, tombstones: 1 }"
+        );
+    }
 }
@@ -6,6 +6,11 @@
 //!
 //! Each replica has its own counter entry, and the join operation performs
 //! component-wise max across all replicas.
+//!
+//! A replica that is gone for good can be pruned: its counts are folded into
+//! a surviving replica's entry and its own entries are dropped. The counter
+//! remembers how much it folded, so a stale state that still carries the
+//! pruned replica's entries doesn't count them a second time when joined.
 
 use crate::lattice::Lattice;
 use serde::{Deserialize, Serialize};
@@ -21,6 +26,25 @@ pub struct PNCounter<K: Ord + Clone> {
     increments: BTreeMap<K, u64>,
     /// Per-replica decrement counters
     decrements: BTreeMap<K, u64>,
+    /// Counts of pruned replicas already folded into a surviving one
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pruned: BTreeMap<K, Contribution>,
+}
+
+/// What one replica has added to and taken from a [`PNCounter`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Contribution {
+    /// Total increments
+    pub increments: u64,
+    /// Total decrements
+    pub decrements: u64,
+}
+
+impl Contribution {
+    /// Increments minus decrements
+    pub fn net(&self) -> i64 {
+        (self.increments as i64).saturating_sub(self.decrements as i64)
+    }
 }
 
 impl<K: Ord + Clone> PNCounter<K> {
@@ -29,39 +53,83 @@ impl<K: Ord + Clone> PNCounter<K> {
         Self {
             increments: BTreeMap::new(),
             decrements: BTreeMap::new(),
+            pruned: BTreeMap::new(),
         }
     }
 
     /// Increment the counter for a specific replica
+    ///
+    /// A pruned replica counts on from what was folded, so the increment
+    /// isn't hidden by it.
     pub fn increment(&mut self, replica_id: K, amount: u64) {
-        let entry = self.increments.entry(replica_id).or_insert(0);
+        let folded = self.folded(&replica_id).increments;
+        let entry = self.increments.entry(replica_id).or_insert(folded);
         *entry = entry.saturating_add(amount);
     }
 
     /// Decrement the counter for a specific replica
     pub fn decrement(&mut self, replica_id: K, amount: u64) {
-        let entry = self.decrements.entry(replica_id).or_insert(0);
+        let folded = self.folded(&replica_id).decrements;
+        let entry = self.decrements.entry(replica_id).or_insert(folded);
         *entry = entry.saturating_add(amount);
     }
 
     /// Get the current value (sum of increments - sum of decrements)
     pub fn value(&self) -> i64 {
-        let inc_sum: u64 = self.increments.values().sum();
-        let dec_sum: u64 = self.decrements.values().sum();
+        let (inc_sum, dec_sum) = self
+            .contributions()
+            .fold((0u64, 0u64), |(inc, dec), (_, c)| {
+                (
+                    inc.saturating_add(c.increments),
+                    dec.saturating_add(c.decrements),
+                )
+            });
         (inc_sum as i64).saturating_sub(dec_sum as i64)
     }
 
     /// Get the increment counter for a replica
+    ///
+    /// For a pruned replica, only increments beyond what was folded count.
     pub fn get_increment(&self, replica_id: &K) -> u64 {
-        self.increments.get(replica_id).copied().unwrap_or(0)
+        self.contribution(replica_id).increments
     }
 
     /// Get the decrement counter for a replica
+    ///
+    /// For a pruned replica, only decrements beyond what was folded count.
     pub fn get_decrement(&self, replica_id: &K) -> u64 {
-        self.decrements.get(replica_id).copied().unwrap_or(0)
+        self.contribution(replica_id).decrements
+    }
+
+    /// Get what a replica contributes to the value
+    pub fn contribution(&self, replica_id: &K) -> Contribution {
+        let folded = self.folded(replica_id);
+        let count = |counts: &BTreeMap<K, u64>, folded: u64| {
+            counts
+                .get(replica_id)
+                .map_or(0, |count| count.saturating_sub(folded))
+        };
+        Contribution {
+            increments: count(&self.increments, folded.increments),
+            decrements: count(&self.decrements, folded.decrements),
+        }
+    }
+
+    /// Iterate over the replicas that contribute to the value, in order
+    pub fn contributions(&self) -> impl Iterator<Item = (&K, Contribution)> + '_ {
+        let mut actors: Vec<&K> = self.increments.keys().collect();
+        actors.extend(self.decrements.keys());
+        actors.sort();
+        actors.dedup();
+        actors
+            .into_iter()
+            .map(move |actor| (actor, self.contribution(actor)))
     }
 
     /// Get a reference to all increment counters
+    ///
+    /// A pruned replica's entry still includes what was folded; see
+    /// [`contribution`](Self::contribution).
     pub fn increments(&self) -> &BTreeMap<K, u64> {
         &self.increments
     }
@@ -70,6 +138,73 @@ impl<K: Ord + Clone> PNCounter<K> {
     pub fn decrements(&self) -> &BTreeMap<K, u64> {
         &self.decrements
     }
+
+    /// Check whether a replica has been pruned
+    pub fn is_pruned(&self, replica_id: &K) -> bool {
+        self.pruned.contains_key(replica_id)
+    }
+
+    /// Fold a decommissioned replica's counts into a surviving replica's
+    /// and drop its entries; the value doesn't change.
+    ///
+    /// Only `into` itself may call this, since it adds to `into`'s entries,
+    /// and only once `replica_id` has stopped for good. Returns the delta to
+    /// send to the other replicas. Joining a state that still has the
+    /// pruned replica's entries, up to the folded counts, adds nothing;
+    /// counts beyond them, from updates this replica hadn't seen, still
+    /// count towards the pruned replica.
+    pub fn prune_actor(&mut self, replica_id: &K, into: K) -> Self {
+        let mut delta = Self::new();
+        if *replica_id == into {
+            return delta;
+        }
+        let moved = self.contribution(replica_id);
+        let folded = self.pruned.entry(replica_id.clone()).or_default();
+        folded.increments += moved.increments;
+        folded.decrements += moved.decrements;
+        delta.pruned.insert(replica_id.clone(), *folded);
+        self.increments.remove(replica_id);
+        self.decrements.remove(replica_id);
+
+        if moved.increments > 0 {
+            self.increment(into.clone(), moved.increments);
+            delta
+                .increments
+                .insert(into.clone(), self.increments[&into]);
+        }
+        if moved.decrements > 0 {
+            self.decrement(into.clone(), moved.decrements);
+            delta
+                .decrements
+                .insert(into.clone(), self.decrements[&into]);
+        }
+        delta
+    }
+
+    /// What has been folded from a replica, zero unless it was pruned
+    fn folded(&self, replica_id: &K) -> Contribution {
+        self.pruned.get(replica_id).copied().unwrap_or_default()
+    }
+
+    /// Drop entries of pruned replicas that were folded in full
+    fn drop_folded(&mut self) {
+        for (actor, folded) in &self.pruned {
+            if self
+                .increments
+                .get(actor)
+                .is_some_and(|&n| n <= folded.increments)
+            {
+                self.increments.remove(actor);
+            }
+            if self
+                .decrements
+                .get(actor)
+                .is_some_and(|&n| n <= folded.decrements)
+            {
+                self.decrements.remove(actor);
+            }
+        }
+    }
 }
 
 impl<K: Ord + Clone> Default for PNCounter<K> {
@@ -78,6 +213,20 @@ impl<K: Ord + Clone> Default for PNCounter<K> {
     }
 }
 
+/// Apply a batch of `(replica_id, adjustment)` pairs: positive amounts
+/// increment, negative ones decrement.
+impl<K: Ord + Clone> Extend<(K, i64)> for PNCounter<K> {
+    fn extend<I: IntoIterator<Item = (K, i64)>>(&mut self, adjustments: I) {
+        for (replica_id, amount) in adjustments {
+            if amount >= 0 {
+                self.increment(replica_id, amount.unsigned_abs());
+            } else {
+                self.decrement(replica_id, amount.unsigned_abs());
+            }
+        }
+    }
+}
+
 impl<K: Ord + Clone> Lattice for PNCounter<K> {
     fn bottom() -> Self {
         Self::new()
@@ -85,9 +234,13 @@ impl<K: Ord + Clone> Lattice for PNCounter<K> {
 
     /// Join operation performs component-wise max on both counters
     /// This ensures that concurrent updates always converge to the same value
+    ///
+    /// Folded counts of pruned replicas are joined the same way, and the
+    /// entries they cover are dropped.
     fn join(&self, other: &Self) -> Self {
         let mut increments = self.increments.clone();
         let mut decrements = self.decrements.clone();
+        let mut pruned = self.pruned.clone();
 
         // Merge other's increments (take max for each replica)
         for (k, v) in &other.increments {
@@ -105,10 +258,19 @@ impl<K: Ord + Clone> Lattice for PNCounter<K> {
                 .or_insert(*v);
         }
 
-        Self {
+        for (k, v) in &other.pruned {
+            let folded = pruned.entry(k.clone()).or_default();
+            folded.increments = folded.increments.max(v.increments);
+            folded.decrements = folded.decrements.max(v.decrements);
+        }
+
+        let mut joined = Self {
             increments,
             decrements,
-        }
+            pruned,
+        };
+        joined.drop_folded();
+        joined
     }
 }
 
@@ -226,4 +388,95 @@ mod tests {
         assert_eq!(deserialized.get_increment(&"replica1".to_string()), 100);
         assert_eq!(deserialized.get_decrement(&"replica2".to_string()), 25);
     }
+
+    #[test]
+    fn test_pncounter_breakdown() {
+        let mut counter = PNCounter::new();
+        counter.increment("A", 5);
+        counter.decrement("A", 1);
+        counter.decrement("B", 2);
+        counter.increment("C", 4);
+
+        assert_eq!(counter.get_increment(&"A"), 5);
+        assert_eq!(counter.get_decrement(&"B"), 2);
+        assert_eq!(counter.contribution(&"A").net(), 4);
+        assert_eq!(counter.contribution(&"D"), Contribution::default());
+
+        let breakdown: Vec<_> = counter
+            .contributions()
+            .map(|(actor, c)| (*actor, c.net()))
+            .collect();
+        assert_eq!(breakdown, vec![("A", 4), ("B", -2), ("C", 4)]);
+        assert_eq!(
+            breakdown.iter().map(|(_, net)| net).sum::<i64>(),
+            counter.value()
+        );
+    }
+
+    #[test]
+    fn test_pncounter_prune_actor() {
+        let mut survivor = PNCounter::new();
+        survivor.increment("A", 3);
+        survivor.increment("B", 5);
+        survivor.decrement("B", 2);
+
+        // A stale copy from before the prune still has B's entries
+        let stale = survivor.clone();
+
+        let delta = survivor.prune_actor(&"B", "A");
+        assert_eq!(survivor.value(), 6);
+        assert!(survivor.is_pruned(&"B"));
+        assert!(!survivor.increments().contains_key(&"B"));
+        assert_eq!(survivor.contribution(&"A").increments, 8);
+        assert_eq!(survivor.contribution(&"A").decrements, 2);
+
+        // Merging the stale state doesn't count B twice
+        let merged = survivor.join(&stale);
+        assert_eq!(merged.value(), 6);
+        assert_eq!(merged, survivor);
+
+        // The delta prunes B on the stale replica too
+        let caught_up = stale.join(&delta);
+        assert_eq!(caught_up, survivor);
+    }
+
+    #[test]
+    fn test_pncounter_prune_keeps_unseen_updates() {
+        let mut a = PNCounter::new();
+        let mut b = PNCounter::new();
+        b.increment("B", 2);
+        a.join_assign(&b);
+
+        // B makes a last update that A hasn't seen before pruning it
+        b.increment("B", 3);
+        let delta = a.prune_actor(&"B", "A");
+        assert_eq!(a.value(), 2);
+
+        let mut c = PNCounter::new();
+        c.increment("C", 1);
+
+        // Every merge order ends up at the same value
+        let left = a.join(&b).join(&c);
+        let right = c.join(&b).join(&delta).join(&a);
+        assert_eq!(left, right);
+        assert_eq!(left.value(), 6);
+        assert_eq!(left.get_increment(&"B"), 3);
+
+        // Pruning B again folds the rest
+        let mut folded = left.clone();
+        folded.prune_actor(&"B", "A");
+        assert_eq!(folded.value(), 6);
+        assert_eq!(folded.contributions().count(), 2);
+        assert_eq!(folded.join(&b).join(&left), folded);
+    }
+
+    #[test]
+    fn test_pncounter_extend_applies_adjustments() {
+        let mut counter = PNCounter::new();
+        counter.extend([("A", 5), ("B", -2), ("A", -1), ("B", 0)]);
+
+        assert_eq!(counter.value(), 2);
+        assert_eq!(counter.contribution(&"A").net(), 4);
+        assert_eq!(counter.get_decrement(&"B"), 2);
+    }
 }
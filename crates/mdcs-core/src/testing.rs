@@ -0,0 +1,432 @@
+//! Property harness for CRDT implementations
+//!
+//! Checks the laws every CRDT must keep on states generated by
+//! [proptest](https://docs.rs/proptest):
+//! - [`assert_lattice_laws`]: join is commutative, associative and
+//!   idempotent, bottom is its identity and it bounds both sides
+//! - [`assert_delta_crdt_laws`]: each delta-mutator `mδ` agrees with its
+//!   mutator `m`, `m(X) = X ⊔ mδ(X)`
+//! - [`assert_convergence`]: replicas running a random script of
+//!   operations and merges agree once each has merged every state, in
+//!   whatever order it merges them
+//!
+//! Each takes its states or operations from [`Arbitrary`]; the `_with`
+//! variants take strategies instead, for types defined elsewhere or states
+//! that need a particular shape. A failing law panics with the minimal
+//! failing input, so the functions can be called straight from a `#[test]`.
+//!
+//! The CRDTs of this crate implement [`Arbitrary`] below, as states reached
+//! through their own operations by a few replicas. Enabled by the `testing`
+//! feature:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! mdcs-core = { version = "0.1", features = ["testing"] }
+//! ```
+//!
+//! ```rust
+//! use mdcs_core::gset::GSet;
+//! use mdcs_core::testing::{assert_convergence, assert_lattice_laws};
+//!
+//! assert_lattice_laws::<GSet<u8>>(64);
+//! assert_convergence(3, 64, |_| GSet::new(), |set: &mut GSet<u8>, _, value: &u8| {
+//!     set.insert(*value)
+//! });
+//! ```
+
+use crate::gset::GSet;
+use crate::lattice::{DeltaCRDT, Lattice};
+use crate::lwwreg::LWWRegister;
+use crate::map::{CRDTMap, Dot, MapRegister, MapValue};
+use crate::mvreg::MVRegister;
+use crate::orset::ORSet;
+use crate::pncounter::PNCounter;
+use proptest::prelude::*;
+use proptest::strategy::BoxedStrategy;
+use proptest::test_runner::{Config, TestCaseResult, TestRunner};
+use std::fmt::Debug;
+
+/// Replicas the generated states are written by
+const REPLICAS: usize = 3;
+
+/// Id of one of the replicas the generated states are written by
+fn replica(index: usize) -> String {
+    format!("replica{}", index)
+}
+
+/// Run `test` on `cases` values, panicking with the minimal failing one
+fn check<S: Strategy>(
+    law: &str,
+    cases: u32,
+    strategy: S,
+    test: impl Fn(S::Value) -> TestCaseResult,
+) {
+    let mut runner = TestRunner::new(Config {
+        cases,
+        failure_persistence: None,
+        ..Config::default()
+    });
+    if let Err(e) = runner.run(&strategy, test) {
+        panic!("{}: {}", law, e);
+    }
+}
+
+// ============================================================================
+// Lattice Laws
+// ============================================================================
+
+/// Check the join-semilattice laws on `cases` random triples of states.
+pub fn assert_lattice_laws<T>(cases: u32)
+where
+    T: Lattice + Arbitrary + Debug,
+{
+    assert_lattice_laws_with(any::<T>(), cases);
+}
+
+/// [`assert_lattice_laws`] on states drawn from `states`.
+pub fn assert_lattice_laws_with<T>(states: impl Strategy<Value = T>, cases: u32)
+where
+    T: Lattice + Debug,
+{
+    let triples = prop::collection::vec(states, 3);
+    check("lattice laws", cases, triples, |states| {
+        let (a, b, c) = (&states[0], &states[1], &states[2]);
+        let ab = a.join(b);
+        prop_assert_eq!(&ab, &b.join(a), "join is not commutative");
+        prop_assert_eq!(ab.join(c), a.join(&b.join(c)), "join is not associative");
+        prop_assert_eq!(&a.join(a), a, "join is not idempotent");
+        prop_assert_eq!(&a.join(&T::bottom()), a, "bottom is not a right identity");
+        prop_assert_eq!(&T::bottom().join(a), a, "bottom is not a left identity");
+        prop_assert!(a.leq(&ab) && b.leq(&ab), "join is not an upper bound");
+        Ok(())
+    });
+}
+
+// ============================================================================
+// Delta-Mutator Laws
+// ============================================================================
+
+/// A function from a state and an operation to a state
+type Mutation<S, O> = Box<dyn Fn(&S, &O) -> S>;
+
+/// A mutator `m` and its delta-mutator `mδ`, for
+/// [`assert_delta_crdt_laws`].
+pub struct DeltaMutator<S, O> {
+    name: String,
+    mutate: Mutation<S, O>,
+    delta: Mutation<S, O>,
+}
+
+impl<S, O> DeltaMutator<S, O> {
+    /// `mutate` returns the state after applying an operation; `delta`
+    /// returns only the delta-state the operation adds to it.
+    pub fn new(
+        name: impl Into<String>,
+        mutate: impl Fn(&S, &O) -> S + 'static,
+        delta: impl Fn(&S, &O) -> S + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            mutate: Box::new(mutate),
+            delta: Box::new(delta),
+        }
+    }
+}
+
+/// Check `m(X) = X ⊔ mδ(X)` for each mutator, on `cases` random states and
+/// operations.
+pub fn assert_delta_crdt_laws<S, O>(mutators: &[DeltaMutator<S, O>], cases: u32)
+where
+    S: Lattice + Arbitrary + Debug,
+    O: Arbitrary + Debug,
+{
+    assert_delta_crdt_laws_with(any::<S>(), any::<O>(), mutators, cases);
+}
+
+/// [`assert_delta_crdt_laws`] on states and operations drawn from
+/// `states` and `ops`.
+pub fn assert_delta_crdt_laws_with<S, O>(
+    states: impl Strategy<Value = S>,
+    ops: impl Strategy<Value = O>,
+    mutators: &[DeltaMutator<S, O>],
+    cases: u32,
+) where
+    S: Lattice + Debug,
+    O: Debug,
+{
+    check("delta-mutator laws", cases, (states, ops), |(state, op)| {
+        for mutator in mutators {
+            let mutated = (mutator.mutate)(&state, &op);
+            let delta = (mutator.delta)(&state, &op);
+            prop_assert_eq!(
+                &state.join(&delta),
+                &mutated,
+                "{}: m(X) differs from X ⊔ mδ(X)",
+                &mutator.name
+            );
+            prop_assert!(
+                delta.leq(&mutated),
+                "{}: mδ(X) is not below m(X)",
+                &mutator.name
+            );
+        }
+        Ok(())
+    });
+}
+
+// ============================================================================
+// Convergence
+// ============================================================================
+
+/// One step of a convergence script.
+#[derive(Clone, Debug)]
+pub enum Step<O> {
+    /// Apply an operation at a replica
+    Apply(usize, O),
+    /// Join one replica's state into another's
+    Merge { from: usize, to: usize },
+}
+
+/// Check that `replicas` replicas converge, on `cases` random scripts.
+///
+/// Every replica starts from `init(index)` and runs the operations of the
+/// script addressed to it through `apply(state, index, op)`, merging other
+/// replicas' states in between. Each merge must only move a replica up
+/// the lattice. At the end every replica merges all the states in its own
+/// random order, and they must all agree.
+pub fn assert_convergence<S, O>(
+    replicas: usize,
+    cases: u32,
+    init: impl Fn(usize) -> S,
+    apply: impl Fn(&mut S, usize, &O),
+) where
+    S: Lattice + Debug,
+    O: Arbitrary + Clone + Debug,
+{
+    assert_convergence_with(any::<O>(), replicas, cases, init, apply);
+}
+
+/// [`assert_convergence`] with operations drawn from `ops`.
+pub fn assert_convergence_with<S, O>(
+    ops: impl Strategy<Value = O>,
+    replicas: usize,
+    cases: u32,
+    init: impl Fn(usize) -> S,
+    apply: impl Fn(&mut S, usize, &O),
+) where
+    S: Lattice + Debug,
+    O: Clone + Debug,
+{
+    assert!(replicas > 0, "convergence needs a replica");
+    let step = prop_oneof![
+        3 => (0..replicas, ops).prop_map(|(at, op)| Step::Apply(at, op)),
+        1 => (0..replicas, 0..replicas).prop_map(|(from, to)| Step::Merge { from, to }),
+    ];
+    let script = prop::collection::vec(step, 0..64);
+    let schedule = Just((0..replicas).collect::<Vec<_>>()).prop_shuffle();
+
+    check(
+        "convergence",
+        cases,
+        (script, schedule),
+        |(script, schedule)| {
+            let mut states: Vec<S> = (0..replicas).map(&init).collect();
+            for step in &script {
+                match step {
+                    Step::Apply(at, op) => apply(&mut states[*at], *at, op),
+                    Step::Merge { from, to } => {
+                        let merged = states[*to].join(&states[*from]);
+                        prop_assert!(
+                            states[*to].leq(&merged),
+                            "merging replica {} into {} moved it down the lattice",
+                            from,
+                            to
+                        );
+                        states[*to] = merged;
+                    }
+                }
+            }
+
+            let finals: Vec<S> = (0..replicas)
+                .map(|i| {
+                    (0..replicas)
+                        .map(|k| &states[schedule[(i + k) % replicas]])
+                        .fold(states[i].clone(), |acc, state| acc.join(state))
+                })
+                .collect();
+            for (i, state) in finals.iter().enumerate().skip(1) {
+                prop_assert_eq!(state, &finals[0], "replica {} disagrees with replica 0", i);
+            }
+            Ok(())
+        },
+    );
+}
+
+// ============================================================================
+// Arbitrary States
+// ============================================================================
+
+impl<T> Arbitrary for GSet<T>
+where
+    T: Arbitrary + Ord + Clone + 'static,
+{
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop::collection::vec(any::<T>(), 0..16)
+            .prop_map(|values| values.into_iter().collect())
+            .boxed()
+    }
+}
+
+/// Adds by a few replicas, with some of the elements removed again.
+impl<T> Arbitrary for ORSet<T>
+where
+    T: Arbitrary + Ord + Clone + 'static,
+{
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        let op = (any::<T>(), 0..REPLICAS, prop::bool::weighted(0.3));
+        prop::collection::vec(op, 0..16)
+            .prop_map(|ops| {
+                let mut set = ORSet::new();
+                for (value, at, remove) in ops {
+                    if remove {
+                        set.remove(&value);
+                    } else {
+                        set.add(&replica(at), value);
+                    }
+                }
+                // Equality counts the pending delta, which joins drop
+                let _ = set.split_delta();
+                set
+            })
+            .boxed()
+    }
+}
+
+impl<K> Arbitrary for PNCounter<K>
+where
+    K: Arbitrary + Ord + Clone + 'static,
+{
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        let op = (any::<K>(), 0u64..1_000, any::<bool>());
+        prop::collection::vec(op, 0..8)
+            .prop_map(|ops| {
+                let mut counter = PNCounter::new();
+                for (at, amount, up) in ops {
+                    if up {
+                        counter.increment(at, amount);
+                    } else {
+                        counter.decrement(at, amount);
+                    }
+                }
+                counter
+            })
+            .boxed()
+    }
+}
+
+/// A register of one replica, after a few writes stamped by any replica.
+impl<T, K> Arbitrary for LWWRegister<T, K>
+where
+    T: Arbitrary + Ord + Clone + 'static,
+    K: Arbitrary + Ord + Clone + 'static,
+{
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        let write = (any::<T>(), 0u64..1_000, any::<K>());
+        (any::<K>(), prop::collection::vec(write, 0..4))
+            .prop_map(|(owner, writes)| {
+                let mut register = LWWRegister::new(owner);
+                for (value, timestamp, writer) in writes {
+                    register.set(value, timestamp, writer);
+                }
+                register
+            })
+            .boxed()
+    }
+}
+
+/// The merge of a few concurrent writes.
+impl<T> Arbitrary for MVRegister<T>
+where
+    T: Arbitrary + Ord + Clone + 'static,
+{
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop::collection::vec((0..REPLICAS, any::<T>()), 0..4)
+            .prop_map(|writes| {
+                writes
+                    .into_iter()
+                    .map(|(at, value)| {
+                        let mut register = MVRegister::new();
+                        register.write(&replica(at), value);
+                        register
+                    })
+                    .fold(MVRegister::new(), |acc, register| acc.join(&register))
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for MapValue {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop_oneof![
+            any::<i64>().prop_map(MapValue::Int),
+            "[a-z]{0,4}".prop_map(MapValue::Text),
+            prop::collection::vec(any::<u8>(), 0..4).prop_map(MapValue::Bytes),
+        ]
+        .boxed()
+    }
+}
+
+/// Puts and removes by a few replicas.
+///
+/// Writes take random dots rather than each replica's next one, so maps
+/// generated independently don't reuse a dot for different writes, which
+/// no real replicas could do.
+impl<K> Arbitrary for CRDTMap<K>
+where
+    K: Arbitrary + Ord + Clone + 'static,
+{
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        let write = (0..REPLICAS, 1..u64::from(u32::MAX), any::<MapValue>());
+        let op = (any::<K>(), prop::option::weighted(0.75, write));
+        prop::collection::vec(op, 0..12)
+            .prop_map(|ops| {
+                let mut map = CRDTMap::new();
+                for (key, write) in ops {
+                    match write {
+                        Some((at, seq, value)) => {
+                            let dot = Dot::new(replica(at), seq);
+                            map.update(&replica(at), key, |register: &mut MapRegister, _| {
+                                register.write(dot, value)
+                            });
+                        }
+                        None => {
+                            map.remove(&key);
+                        }
+                    }
+                }
+                map
+            })
+            .boxed()
+    }
+}
@@ -5,16 +5,29 @@
 //!  - Associativity: (a ⊔ b) ⊔ c = a ⊔ (b ⊔ c)
 //!  - Idempotence:  a ⊔ a = a
 //!  - Bottom is identity: a ⊔ ⊥ = a
+//!
+//! The laws, along with delta-mutators and replica convergence, are checked
+//! through the `mdcs_core::testing` harness, on the states built by the
+//! strategies here and on each type's generated states.
 
+use mdcs_core::bounded_counter::BoundedCounter;
 use mdcs_core::gset::GSet;
 use mdcs_core::lattice::{DeltaCRDT, Lattice};
 use mdcs_core::lwwreg::LWWRegister;
+use mdcs_core::map::{CRDTMap, MapRegister, MapValue};
 use mdcs_core::mvreg::MVRegister;
 use mdcs_core::orset::ORSet;
 use mdcs_core::pncounter::PNCounter;
+use mdcs_core::testing::{
+    assert_convergence, assert_delta_crdt_laws, assert_delta_crdt_laws_with, assert_lattice_laws,
+    assert_lattice_laws_with, DeltaMutator,
+};
 use proptest::prelude::*;
 
-/// Generate strategies for prop-testing
+/// Cases per law, proptest's default
+const CASES: u32 = 256;
+
+// Generate strategies for prop-testing
 
 fn gset_i32_strategy() -> impl Strategy<Value = GSet<i32>> {
     prop::collection::btree_set(0i32..100, 0..20).prop_map(|elements| {
@@ -47,6 +60,47 @@ fn pncounter_strategy() -> impl Strategy<Value = PNCounter<String>> {
     })
 }
 
+/// An operation on one of the replicas of a bounded counter
+#[derive(Clone, Debug)]
+enum CounterOp {
+    Increment(usize, u64),
+    Decrement(usize, u64),
+    Transfer(usize, usize, u64),
+    /// Join one replica's full state into another
+    Merge(usize, usize),
+    /// Deliver one of the deltas produced so far, in any order
+    Deliver(usize, usize),
+}
+
+const COUNTER_REPLICAS: usize = 5;
+
+fn counter_op_strategy() -> impl Strategy<Value = CounterOp> {
+    let r = 0..COUNTER_REPLICAS;
+    prop_oneof![
+        (r.clone(), 0u64..20).prop_map(|(i, n)| CounterOp::Increment(i, n)),
+        (r.clone(), 0u64..20).prop_map(|(i, n)| CounterOp::Decrement(i, n)),
+        (r.clone(), r.clone(), 0u64..20).prop_map(|(i, j, n)| CounterOp::Transfer(i, j, n)),
+        (r.clone(), r.clone()).prop_map(|(i, j)| CounterOp::Merge(i, j)),
+        (any::<usize>(), r).prop_map(|(d, j)| CounterOp::Deliver(d, j)),
+    ]
+}
+
+fn bounded_counter_strategy() -> impl Strategy<Value = BoundedCounter<String>> {
+    prop::collection::vec((0usize..3, 0u64..30, any::<bool>()), 0..10).prop_map(|ops| {
+        let mut counter = BoundedCounter::new(-10);
+        for (replica, amount, up) in ops {
+            let id = format!("replica{}", replica);
+            if up {
+                counter.increment(id, amount);
+            } else {
+                let _ = counter.decrement(id.clone(), amount);
+                let _ = counter.transfer(id, format!("replica{}", (replica + 1) % 3), amount / 2);
+            }
+        }
+        counter
+    })
+}
+
 fn lwwreg_strategy() -> impl Strategy<Value = LWWRegister<i32, String>> {
     (0i32..100, 0u64..1000).prop_map(|(value, timestamp)| {
         let mut reg = LWWRegister::new("replica1".to_string());
@@ -67,112 +121,95 @@ fn mvreg_strategy() -> impl Strategy<Value = MVRegister<i32>> {
 // GSet Property Tests
 // ============================================================================
 
-proptest! {
-    #[test]
-    fn gset_join_is_commutative(
-        a in gset_i32_strategy(),
-        b in gset_i32_strategy()
-    ) {
-        prop_assert_eq!(a.join(&b), b.join(&a));
-    }
-
-    #[test]
-    fn gset_join_is_associative(
-        a in gset_i32_strategy(),
-        b in gset_i32_strategy(),
-        c in gset_i32_strategy()
-    ) {
-        let left = a.join(&b).join(&c);
-        let right = a.join(&b.join(&c));
-        prop_assert_eq!(left, right);
-    }
+#[test]
+fn gset_lattice_laws() {
+    assert_lattice_laws_with(gset_i32_strategy(), CASES);
+    assert_lattice_laws::<GSet<u8>>(CASES);
+}
 
-    #[test]
-    fn gset_join_is_idempotent(a in gset_i32_strategy()) {
-        prop_assert_eq!(a.join(&a), a);
-    }
+#[test]
+fn gset_insert_delta_laws() {
+    let insert = DeltaMutator::new(
+        "insert",
+        |set: &GSet<u8>, value: &u8| {
+            let mut set = set.clone();
+            set.insert(*value);
+            set
+        },
+        |_, value| {
+            let mut delta = GSet::new();
+            delta.insert(*value);
+            delta
+        },
+    );
+    assert_delta_crdt_laws(&[insert], CASES);
+}
 
-    #[test]
-    fn gset_bottom_is_identity(a in gset_i32_strategy()) {
-        let bottom = GSet::bottom();
-        prop_assert_eq!(a.join(&bottom), a.clone());
-        prop_assert_eq!(bottom.join(&a), a);
-    }
+#[test]
+fn gset_convergence() {
+    assert_convergence(
+        3,
+        CASES,
+        |_| GSet::new(),
+        |set, _, value: &u8| set.insert(*value),
+    );
 }
 
 // ============================================================================
 // ORSet Property Tests
 // ============================================================================
 
-proptest! {
-    #[test]
-    fn orset_join_is_commutative(
-        a in orset_string_strategy(),
-        b in orset_string_strategy()
-    ) {
-        prop_assert_eq!(a.join(&b), b.join(&a));
-    }
-
-    #[test]
-    fn orset_join_is_associative(
-        a in orset_string_strategy(),
-        b in orset_string_strategy(),
-        c in orset_string_strategy()
-    ) {
-        let left = a.join(&b).join(&c);
-        let right = a.join(&b.join(&c));
-        prop_assert_eq!(left, right);
-    }
-
-    #[test]
-    fn orset_join_is_idempotent(a in orset_string_strategy()) {
-        prop_assert_eq!(a.join(&a), a);
-    }
+#[test]
+fn orset_lattice_laws() {
+    assert_lattice_laws_with(orset_string_strategy(), CASES);
+    assert_lattice_laws::<ORSet<u8>>(CASES);
+}
 
-    #[test]
-    fn orset_bottom_is_identity(a in orset_string_strategy()) {
-        let bottom = ORSet::bottom();
-        prop_assert_eq!(a.join(&bottom), a.clone());
-        prop_assert_eq!(bottom.join(&a), a);
-    }
+#[test]
+fn orset_convergence() {
+    assert_convergence(
+        3,
+        CASES,
+        |_| ORSet::new(),
+        |set, replica, &(value, remove): &(u8, bool)| {
+            if remove {
+                set.remove(&value);
+            } else {
+                set.add(&format!("replica{}", replica), value);
+            }
+            // Shipped with the next merge; equality counts it
+            let _ = set.split_delta();
+        },
+    );
 }
 
 // ============================================================================
 // PNCounter Property Tests
 // ============================================================================
 
-proptest! {
-    #[test]
-    fn pncounter_join_is_commutative(
-        a in pncounter_strategy(),
-        b in pncounter_strategy()
-    ) {
-        prop_assert_eq!(a.join(&b), b.join(&a));
-    }
-
-    #[test]
-    fn pncounter_join_is_associative(
-        a in pncounter_strategy(),
-        b in pncounter_strategy(),
-        c in pncounter_strategy()
-    ) {
-        let left = a.join(&b).join(&c);
-        let right = a.join(&b.join(&c));
-        prop_assert_eq!(left, right);
-    }
-
-    #[test]
-    fn pncounter_join_is_idempotent(a in pncounter_strategy()) {
-        prop_assert_eq!(a.join(&a), a);
-    }
+#[test]
+fn pncounter_lattice_laws() {
+    assert_lattice_laws_with(pncounter_strategy(), CASES);
+    assert_lattice_laws::<PNCounter<u8>>(CASES);
+}
 
-    #[test]
-    fn pncounter_bottom_is_identity(a in pncounter_strategy()) {
-        let bottom = PNCounter::bottom();
-        prop_assert_eq!(a.join(&bottom), a.clone());
-        prop_assert_eq!(bottom.join(&a), a);
-    }
+#[test]
+fn pncounter_convergence() {
+    assert_convergence(
+        4,
+        CASES,
+        |_| PNCounter::new(),
+        |counter, replica, &(amount, up): &(u8, bool)| {
+            if up {
+                counter.increment(replica, amount.into());
+            } else {
+                counter.decrement(replica, amount.into());
+            }
+        },
+    );
+}
 
+proptest! {
     #[test]
     fn pncounter_value_convergence(
         a in pncounter_strategy(),
@@ -186,77 +223,165 @@ proptest! {
 }
 
 // ============================================================================
-// LWWRegister Property Tests
+// BoundedCounter Property Tests
 // ============================================================================
 
+#[test]
+fn bounded_counter_lattice_laws() {
+    assert_lattice_laws_with(bounded_counter_strategy(), CASES);
+}
+
 proptest! {
     #[test]
-    fn lwwreg_join_is_commutative(
-        a in lwwreg_strategy(),
-        b in lwwreg_strategy()
+    fn bounded_counter_never_observed_below_bound(
+        bound in -50i64..50,
+        ops in prop::collection::vec(counter_op_strategy(), 1..80)
     ) {
-        prop_assert_eq!(a.join(&b), b.join(&a));
-    }
+        let ids: Vec<String> = (0..COUNTER_REPLICAS).map(|i| format!("replica{}", i)).collect();
+        let mut replicas: Vec<_> = (0..COUNTER_REPLICAS)
+            .map(|_| BoundedCounter::new(bound))
+            .collect();
+        let mut deltas: Vec<BoundedCounter<String>> = Vec::new();
+
+        for op in ops {
+            match op {
+                CounterOp::Increment(i, n) => replicas[i].increment(ids[i].clone(), n),
+                CounterOp::Decrement(i, n) => {
+                    let available = replicas[i].quota(&ids[i]);
+                    prop_assert_eq!(
+                        replicas[i].decrement(ids[i].clone(), n).is_ok(),
+                        n <= available
+                    );
+                }
+                CounterOp::Transfer(i, j, n) => {
+                    let _ = replicas[i].transfer(ids[i].clone(), ids[j].clone(), n);
+                }
+                CounterOp::Merge(i, j) => {
+                    let state = replicas[i].clone();
+                    replicas[j].join_assign(&state);
+                }
+                CounterOp::Deliver(d, j) => {
+                    if !deltas.is_empty() {
+                        replicas[j].apply_delta(&deltas[d % deltas.len()]);
+                    }
+                }
+            }
+            for replica in &mut replicas {
+                deltas.extend(replica.split_delta());
+            }
+            for replica in &replicas {
+                prop_assert!(replica.value() >= bound);
+            }
+            // Any mix of deltas is a state some replica could reach
+            let mut partial = BoundedCounter::bottom();
+            for delta in deltas.iter().rev().step_by(2) {
+                partial.apply_delta(delta);
+                prop_assert!(partial.value() >= bound);
+            }
+        }
 
-    #[test]
-    fn lwwreg_join_is_associative(
-        a in lwwreg_strategy(),
-        b in lwwreg_strategy(),
-        c in lwwreg_strategy()
-    ) {
-        let left = a.join(&b).join(&c);
-        let right = a.join(&b.join(&c));
-        prop_assert_eq!(left, right);
+        // Full sync converges, still bounded
+        let merged = replicas.iter().fold(BoundedCounter::bottom(), |acc, r| acc.join(r));
+        let reversed = replicas.iter().rev().fold(BoundedCounter::bottom(), |acc, r| acc.join(r));
+        prop_assert_eq!(&merged, &reversed);
+        prop_assert!(merged.value() >= bound);
+        let by_deltas = deltas
+            .iter()
+            .fold(BoundedCounter::new(bound), |acc, d| acc.join(d));
+        prop_assert_eq!(by_deltas.value(), merged.value());
     }
+}
 
-    #[test]
-    fn lwwreg_join_is_idempotent(a in lwwreg_strategy()) {
-        prop_assert_eq!(a.join(&a), a);
-    }
+// ============================================================================
+// LWWRegister Property Tests
+// ============================================================================
 
-    #[test]
-    fn lwwreg_bottom_is_identity(a in lwwreg_strategy()) {
-        let bottom = LWWRegister::bottom();
-        prop_assert_eq!(a.join(&bottom), a.clone());
-        prop_assert_eq!(bottom.join(&a), a);
-    }
+#[test]
+fn lwwreg_lattice_laws() {
+    assert_lattice_laws_with(lwwreg_strategy(), CASES);
+    assert_lattice_laws::<LWWRegister<u8, u8>>(CASES);
 }
 
 // ============================================================================
 // MVRegister Property Tests
 // ============================================================================
 
-proptest! {
-    #[test]
-    fn mvreg_join_is_commutative(
-        a in mvreg_strategy(),
-        b in mvreg_strategy()
-    ) {
-        prop_assert_eq!(a.join(&b), b.join(&a));
-    }
+#[test]
+fn mvreg_lattice_laws() {
+    assert_lattice_laws_with(mvreg_strategy(), CASES);
+    assert_lattice_laws::<MVRegister<u8>>(CASES);
+}
 
-    #[test]
-    fn mvreg_join_is_associative(
-        a in mvreg_strategy(),
-        b in mvreg_strategy(),
-        c in mvreg_strategy()
-    ) {
-        let left = a.join(&b).join(&c);
-        let right = a.join(&b.join(&c));
-        prop_assert_eq!(left, right);
-    }
+#[test]
+fn mvreg_convergence() {
+    assert_convergence(
+        3,
+        CASES,
+        |_| MVRegister::new(),
+        |register, replica, value: &u8| {
+            register.write(&format!("replica{}", replica), *value);
+        },
+    );
+}
 
-    #[test]
-    fn mvreg_join_is_idempotent(a in mvreg_strategy()) {
-        prop_assert_eq!(a.join(&a), a);
-    }
+// ============================================================================
+// CRDTMap Property Tests
+// ============================================================================
 
-    #[test]
-    fn mvreg_bottom_is_identity(a in mvreg_strategy()) {
-        let bottom = MVRegister::bottom();
-        prop_assert_eq!(a.join(&bottom), a.clone());
-        prop_assert_eq!(bottom.join(&a), a);
-    }
+#[test]
+fn map_lattice_laws() {
+    assert_lattice_laws::<CRDTMap<u8>>(CASES);
+}
+
+#[test]
+fn map_delta_laws() {
+    let update = DeltaMutator::new(
+        "update",
+        |map: &CRDTMap<u8>, (key, replica, value): &(u8, String, MapValue)| {
+            let mut map = map.clone();
+            map.update(replica, *key, |register: &mut MapRegister, dot| {
+                register.write(dot, value.clone())
+            });
+            map
+        },
+        |map, (key, replica, value)| {
+            map.update_delta(replica, *key, |register: &mut MapRegister, dot| {
+                register.write(dot, value.clone())
+            })
+        },
+    );
+    let remove = DeltaMutator::new(
+        "remove",
+        |map: &CRDTMap<u8>, (key, _, _): &(u8, String, MapValue)| {
+            let mut map = map.clone();
+            map.remove(key);
+            map
+        },
+        |map, (key, _, _)| map.remove_delta(key),
+    );
+    let ops = (any::<u8>(), "replica[0-2]", any::<MapValue>());
+    assert_delta_crdt_laws_with(any::<CRDTMap<u8>>(), ops, &[update, remove], CASES);
+}
+
+#[test]
+fn map_convergence() {
+    assert_convergence(
+        3,
+        CASES,
+        |_| CRDTMap::new(),
+        |map, replica, (key, value): &(u8, Option<MapValue>)| match value {
+            Some(value) => {
+                map.update(
+                    &format!("replica{}", replica),
+                    *key,
+                    |register: &mut MapRegister, dot| register.write(dot, value.clone()),
+                );
+            }
+            None => {
+                map.remove(key);
+            }
+        },
+    );
 }
 
 // ============================================================================
@@ -303,6 +428,22 @@ fn pncounter_serialization_roundtrip() {
     assert_eq!(counter.value(), deserialized.value());
 }
 
+#[test]
+fn bounded_counter_serialization_roundtrip() {
+    let mut counter = BoundedCounter::new(0);
+    counter.increment("replica1".to_string(), 42);
+    counter
+        .transfer("replica1".to_string(), "replica2".to_string(), 12)
+        .unwrap();
+    counter.decrement("replica2".to_string(), 10).unwrap();
+
+    let serialized = serde_json::to_string(&counter).unwrap();
+    let deserialized: BoundedCounter<String> = serde_json::from_str(&serialized).unwrap();
+
+    assert_eq!(counter, deserialized);
+    assert_eq!(counter.value(), deserialized.value());
+}
+
 #[test]
 fn lwwreg_serialization_roundtrip() {
     let mut reg = LWWRegister::new("replica1".to_string());
@@ -5,14 +5,18 @@
 //! - Path-based queries
 //! - Document versioning and snapshots
 //! - Prefix scans and queries
+//! - Hierarchical collections (folders)
+//! - Full-text search (see [`crate::search`])
 
 use crate::error::DbError;
 use crate::json_crdt::{JsonCrdt, JsonCrdtDelta, JsonPath, JsonValue};
-use crate::rga_text::{RGAText, RGATextDelta};
-use crate::rich_text::{RichText, RichTextDelta};
+use crate::rga_text::{RGAText, RGATextDelta, TextId};
+use crate::rich_text::{BlockType, RichText, RichTextDelta};
+use crate::search::{self, SearchConfig, SearchHit, SearchIndex};
+use mdcs_core::clock::{Clock, SharedClock, SystemClock};
 use mdcs_core::lattice::Lattice;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use ulid::Ulid;
 
 /// Unique identifier for a document.
@@ -41,6 +45,109 @@ impl std::fmt::Display for DocumentId {
     }
 }
 
+/// Unique identifier for a collection.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct CollectionId(pub String);
+
+impl CollectionId {
+    pub fn new() -> Self {
+        Self(Ulid::new().to_string())
+    }
+
+    pub fn from_string(s: impl Into<String>) -> Self {
+        Self(s.into())
+    }
+}
+
+impl Default for CollectionId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Display for CollectionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Lamport timestamp ordering last-writer-wins collection updates.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct LwwStamp {
+    pub counter: u64,
+    pub replica: String,
+}
+
+/// Replicated state of a collection.
+///
+/// Name and parent are independent LWW registers. Deletion is a sticky
+/// tombstone.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct CollectionState {
+    name: String,
+    name_stamp: LwwStamp,
+    parent: Option<CollectionId>,
+    parent_stamp: LwwStamp,
+    deleted: bool,
+}
+
+impl CollectionState {
+    /// State for a collection known only from a change that arrived
+    /// before its creation.
+    fn placeholder() -> Self {
+        Self {
+            name: String::new(),
+            name_stamp: LwwStamp::default(),
+            parent: None,
+            parent_stamp: LwwStamp::default(),
+            deleted: false,
+        }
+    }
+
+    fn set_name(&mut self, name: &str, stamp: &LwwStamp) {
+        if *stamp > self.name_stamp {
+            self.name = name.to_string();
+            self.name_stamp = stamp.clone();
+        }
+    }
+
+    fn set_parent(&mut self, parent: &Option<CollectionId>, stamp: &LwwStamp) {
+        if *stamp > self.parent_stamp {
+            self.parent = parent.clone();
+            self.parent_stamp = stamp.clone();
+        }
+    }
+}
+
+/// An item in a collection listing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CollectionEntry {
+    /// A child collection.
+    Collection { id: CollectionId, name: String },
+    /// A document.
+    Document { id: DocumentId, title: String },
+}
+
+/// A collection and everything below it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CollectionNode {
+    pub id: CollectionId,
+    pub name: String,
+    /// Child collections, sorted by name then ID.
+    pub children: Vec<CollectionNode>,
+    /// Documents directly in this collection, sorted by ID.
+    pub documents: Vec<DocumentId>,
+}
+
+/// The full collection hierarchy of a store.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CollectionTree {
+    /// Top-level collections, sorted by name then ID.
+    pub collections: Vec<CollectionNode>,
+    /// Documents not in any collection, sorted by ID.
+    pub documents: Vec<DocumentId>,
+}
+
 /// The type of a document.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DocumentType {
@@ -58,7 +165,7 @@ pub enum CrdtValue {
     /// Plain text.
     Text(RGAText),
     /// Rich text with formatting.
-    RichText(RichText),
+    RichText(Box<RichText>),
     /// Structured JSON data.
     Json(JsonCrdt),
 }
@@ -113,6 +220,27 @@ impl CrdtValue {
             _ => None,
         }
     }
+
+    /// Rough size of the visible content in bytes: a byte per character of
+    /// text, or the length of the JSON it renders to.
+    pub fn size_estimate(&self) -> usize {
+        #[cfg(test)]
+        tests::PAYLOAD_READS.with(|reads| reads.set(reads.get() + 1));
+        match self {
+            CrdtValue::Text(t) => t.len(),
+            CrdtValue::RichText(rt) => rt.len(),
+            CrdtValue::Json(j) => j.to_json().to_string().len(),
+        }
+    }
+
+    /// Copy this value for a new replica (see [`RGAText::fork`]).
+    pub fn fork(&self, replica_id: &str) -> Self {
+        match self {
+            CrdtValue::Text(t) => CrdtValue::Text(t.fork(replica_id)),
+            CrdtValue::RichText(rt) => CrdtValue::RichText(Box::new(rt.fork(replica_id))),
+            CrdtValue::Json(j) => CrdtValue::Json(j.fork(replica_id)),
+        }
+    }
 }
 
 impl Lattice for CrdtValue {
@@ -123,7 +251,9 @@ impl Lattice for CrdtValue {
     fn join(&self, other: &Self) -> Self {
         match (self, other) {
             (CrdtValue::Text(a), CrdtValue::Text(b)) => CrdtValue::Text(a.join(b)),
-            (CrdtValue::RichText(a), CrdtValue::RichText(b)) => CrdtValue::RichText(a.join(b)),
+            (CrdtValue::RichText(a), CrdtValue::RichText(b)) => {
+                CrdtValue::RichText(Box::new(a.join(b)))
+            }
             (CrdtValue::Json(a), CrdtValue::Json(b)) => CrdtValue::Json(a.join(b)),
             // Type mismatch - prefer self
             _ => self.clone(),
@@ -139,6 +269,26 @@ pub enum DocumentDelta {
     Json(JsonCrdtDelta),
 }
 
+impl DocumentDelta {
+    /// Compose with a later delta to the same document.
+    ///
+    /// Returns `None` if the deltas are for different document types.
+    pub fn join(&self, other: &DocumentDelta) -> Option<DocumentDelta> {
+        match (self, other) {
+            (DocumentDelta::Text(a), DocumentDelta::Text(b)) => {
+                Some(DocumentDelta::Text(a.join(b)))
+            }
+            (DocumentDelta::RichText(a), DocumentDelta::RichText(b)) => {
+                Some(DocumentDelta::RichText(a.join(b)))
+            }
+            (DocumentDelta::Json(a), DocumentDelta::Json(b)) => {
+                Some(DocumentDelta::Json(a.join(b)))
+            }
+            _ => None,
+        }
+    }
+}
+
 /// A document with metadata.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Document {
@@ -159,10 +309,7 @@ pub struct Document {
 impl Document {
     /// Create a new text document.
     pub fn new_text(id: DocumentId, title: impl Into<String>, replica_id: &str) -> Self {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as u64;
+        let now = SystemClock.now_millis();
 
         Self {
             id,
@@ -176,15 +323,12 @@ impl Document {
 
     /// Create a new rich text document.
     pub fn new_rich_text(id: DocumentId, title: impl Into<String>, replica_id: &str) -> Self {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as u64;
+        let now = SystemClock.now_millis();
 
         Self {
             id,
             title: title.into(),
-            value: CrdtValue::RichText(RichText::new(replica_id)),
+            value: CrdtValue::RichText(Box::new(RichText::new(replica_id))),
             created_at: now,
             modified_at: now,
             metadata: HashMap::new(),
@@ -193,10 +337,7 @@ impl Document {
 
     /// Create a new JSON document.
     pub fn new_json(id: DocumentId, title: impl Into<String>, replica_id: &str) -> Self {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as u64;
+        let now = SystemClock.now_millis();
 
         Self {
             id,
@@ -208,6 +349,20 @@ impl Document {
         }
     }
 
+    /// Create a document holding an existing value.
+    pub fn from_value(id: DocumentId, title: impl Into<String>, value: CrdtValue) -> Self {
+        let now = SystemClock.now_millis();
+
+        Self {
+            id,
+            title: title.into(),
+            value,
+            created_at: now,
+            modified_at: now,
+            metadata: HashMap::new(),
+        }
+    }
+
     /// Get the document type.
     pub fn document_type(&self) -> DocumentType {
         self.value.document_type()
@@ -215,10 +370,12 @@ impl Document {
 
     /// Touch the modified timestamp.
     pub fn touch(&mut self) {
-        self.modified_at = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as u64;
+        self.touch_at(SystemClock.now_millis());
+    }
+
+    /// Set the modified timestamp.
+    pub fn touch_at(&mut self, millis: u64) {
+        self.modified_at = millis;
     }
 
     /// Set metadata.
@@ -230,6 +387,35 @@ impl Document {
     pub fn get_metadata(&self, key: &str) -> Option<&String> {
         self.metadata.get(key)
     }
+
+    /// Summarize the document for a list view.
+    pub fn meta(&self) -> DocumentMeta {
+        DocumentMeta {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            doc_type: self.document_type(),
+            created_at: self.created_at,
+            modified_at: self.modified_at,
+            size_estimate: self.value.size_estimate(),
+        }
+    }
+}
+
+/// A document's header, without its CRDT value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DocumentMeta {
+    /// Document ID.
+    pub id: DocumentId,
+    /// Document title/name.
+    pub title: String,
+    /// Document type.
+    pub doc_type: DocumentType,
+    /// Creation timestamp.
+    pub created_at: u64,
+    /// Last modified timestamp.
+    pub modified_at: u64,
+    /// Rough size of the value (see [`CrdtValue::size_estimate`]).
+    pub size_estimate: usize,
 }
 
 /// Options for querying documents.
@@ -247,6 +433,15 @@ pub struct QueryOptions {
     pub limit: Option<usize>,
     /// Skip results.
     pub offset: Option<usize>,
+    /// Filter by collection.
+    pub collection: Option<CollectionId>,
+    /// Include documents in descendants of `collection`.
+    pub include_descendants: bool,
+    /// Require every key/value pair to be in the document's metadata.
+    pub metadata_filters: Vec<(String, String)>,
+    /// Filter by text content, ignoring case: the text of text and rich
+    /// text documents, and the string values of JSON documents.
+    pub content_contains: Option<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -256,6 +451,10 @@ pub enum SortField {
     ModifiedAt,
 }
 
+/// Default run of pending updates to one document that triggers
+/// [`DocumentStore::coalesce_pending`].
+pub const DEFAULT_COALESCE_THRESHOLD: usize = 32;
+
 /// A document store for managing multiple CRDT documents.
 #[derive(Clone, Debug)]
 pub struct DocumentStore {
@@ -263,10 +462,33 @@ pub struct DocumentStore {
     replica_id: String,
     /// All documents indexed by ID.
     documents: BTreeMap<DocumentId, Document>,
+    /// IDs of deleted documents (tombstones).
+    deleted: BTreeSet<DocumentId>,
     /// Index by title for prefix queries.
     title_index: BTreeMap<String, DocumentId>,
     /// Pending changes for replication.
     pending_changes: Vec<StoreChange>,
+    /// Run of pending updates to one document that triggers coalescing.
+    coalesce_threshold: usize,
+    /// Collections indexed by ID, including tombstones.
+    collections: BTreeMap<CollectionId, CollectionState>,
+    /// Collection membership per document (LWW; `None` is the root).
+    memberships: BTreeMap<DocumentId, (Option<CollectionId>, LwwStamp)>,
+    /// Lamport clock for collection updates.
+    clock: u64,
+    /// Fork points of branch documents.
+    forks: BTreeMap<DocumentId, ForkPoint>,
+    /// Full-text index, if search is enabled.
+    search: Option<SearchIndex>,
+    /// Source of document timestamps.
+    wall_clock: SharedClock,
+}
+
+/// The document a branch was forked from and its state at that moment.
+#[derive(Clone, Debug)]
+struct ForkPoint {
+    source: DocumentId,
+    base: CrdtValue,
 }
 
 /// A change to the store.
@@ -291,16 +513,136 @@ pub enum StoreChange {
         key: String,
         value: Option<String>,
     },
+    /// A collection was created.
+    CreateCollection {
+        id: CollectionId,
+        name: String,
+        parent: Option<CollectionId>,
+        stamp: LwwStamp,
+    },
+    /// A collection was renamed.
+    RenameCollection {
+        id: CollectionId,
+        name: String,
+        stamp: LwwStamp,
+    },
+    /// A collection was moved under a new parent.
+    MoveCollection {
+        id: CollectionId,
+        parent: Option<CollectionId>,
+        stamp: LwwStamp,
+    },
+    /// A collection was deleted.
+    DeleteCollection { id: CollectionId },
+    /// A document was moved to a collection.
+    MoveDocument {
+        id: DocumentId,
+        collection: Option<CollectionId>,
+        stamp: LwwStamp,
+    },
+    /// A branch was forked from a document.
+    Fork {
+        id: DocumentId,
+        source: DocumentId,
+        title: String,
+        base: CrdtValue,
+    },
+    /// The full state of a branch was merged into a document.
+    Merge { id: DocumentId, value: CrdtValue },
+}
+
+/// A change made on a branch since its fork point.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BranchChange {
+    /// Text inserted on the branch.
+    TextInsert {
+        /// Position of the text in the branch.
+        position: usize,
+        /// The inserted text.
+        text: String,
+        /// The fork-point character the text follows (genesis at the start).
+        after: TextId,
+    },
+    /// Fork-point text deleted on the branch.
+    TextDelete {
+        /// Position of the text in the fork point.
+        position: usize,
+        /// The deleted text.
+        text: String,
+        /// IDs of the deleted characters.
+        ids: Vec<TextId>,
+    },
+    /// A JSON leaf was added or changed.
+    JsonSet {
+        path: String,
+        value: serde_json::Value,
+    },
+    /// A JSON leaf was removed.
+    JsonRemove { path: String },
+}
+
+/// Reference to a change by its index in a [`BranchDiff`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ChangeRef(pub usize);
+
+/// The changes a branch made relative to its fork point.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BranchDiff {
+    /// The branch document.
+    pub branch: DocumentId,
+    /// The document the branch was forked from.
+    pub source: DocumentId,
+    /// Changes in document order.
+    pub changes: Vec<BranchChange>,
+}
+
+impl BranchDiff {
+    /// Get a change by reference.
+    pub fn get(&self, change: ChangeRef) -> Option<&BranchChange> {
+        self.changes.get(change.0)
+    }
+
+    /// References to all changes, in order.
+    pub fn refs(&self) -> impl Iterator<Item = ChangeRef> + '_ {
+        (0..self.changes.len()).map(ChangeRef)
+    }
+
+    /// Check if the branch has no changes.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// How [`DocumentStore::merge_branch`] merges a branch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Join the full branch state; the result equals the lattice join.
+    All,
+    /// Re-apply only the chosen changes from [`DocumentStore::branch_changes`].
+    Selected(Vec<ChangeRef>),
 }
 
 impl DocumentStore {
     /// Create a new document store.
     pub fn new(replica_id: impl Into<String>) -> Self {
+        Self::with_clock(replica_id, SharedClock::default())
+    }
+
+    /// Create a document store that timestamps documents with `clock`.
+    pub fn with_clock(replica_id: impl Into<String>, clock: impl Into<SharedClock>) -> Self {
         Self {
             replica_id: replica_id.into(),
             documents: BTreeMap::new(),
+            deleted: BTreeSet::new(),
             title_index: BTreeMap::new(),
             pending_changes: Vec::new(),
+            coalesce_threshold: DEFAULT_COALESCE_THRESHOLD,
+            collections: BTreeMap::new(),
+            memberships: BTreeMap::new(),
+            clock: 0,
+            forks: BTreeMap::new(),
+            search: None,
+            wall_clock: clock.into(),
         }
     }
 
@@ -315,7 +657,7 @@ impl DocumentStore {
     pub fn create_text(&mut self, title: impl Into<String>) -> DocumentId {
         let id = DocumentId::new();
         let title = title.into();
-        let doc = Document::new_text(id.clone(), &title, &self.replica_id);
+        let doc = self.stamped(Document::new_text(id.clone(), &title, &self.replica_id));
 
         self.title_index.insert(title.clone(), id.clone());
         self.documents.insert(id.clone(), doc);
@@ -333,7 +675,11 @@ impl DocumentStore {
     pub fn create_rich_text(&mut self, title: impl Into<String>) -> DocumentId {
         let id = DocumentId::new();
         let title = title.into();
-        let doc = Document::new_rich_text(id.clone(), &title, &self.replica_id);
+        let doc = self.stamped(Document::new_rich_text(
+            id.clone(),
+            &title,
+            &self.replica_id,
+        ));
 
         self.title_index.insert(title.clone(), id.clone());
         self.documents.insert(id.clone(), doc);
@@ -351,7 +697,7 @@ impl DocumentStore {
     pub fn create_json(&mut self, title: impl Into<String>) -> DocumentId {
         let id = DocumentId::new();
         let title = title.into();
-        let doc = Document::new_json(id.clone(), &title, &self.replica_id);
+        let doc = self.stamped(Document::new_json(id.clone(), &title, &self.replica_id));
 
         self.title_index.insert(title.clone(), id.clone());
         self.documents.insert(id.clone(), doc);
@@ -376,15 +722,33 @@ impl DocumentStore {
     }
 
     /// Delete a document.
+    ///
+    /// The ID is tombstoned: replicated changes and [`merge`](Self::merge)
+    /// never bring the document back, even with edits made concurrently.
     pub fn delete(&mut self, id: &DocumentId) -> Option<Document> {
-        if let Some(doc) = self.documents.remove(id) {
+        let doc = self.remove_document(id)?;
+        self.pending_changes
+            .push(StoreChange::Delete { id: id.clone() });
+        Some(doc)
+    }
+
+    /// Remove a document and tombstone its ID.
+    fn remove_document(&mut self, id: &DocumentId) -> Option<Document> {
+        self.deleted.insert(id.clone());
+        self.forks.remove(id);
+        if let Some(index) = &mut self.search {
+            index.remove(id);
+        }
+        let doc = self.documents.remove(id)?;
+        if self.title_index.get(&doc.title) == Some(id) {
             self.title_index.remove(&doc.title);
-            self.pending_changes
-                .push(StoreChange::Delete { id: id.clone() });
-            Some(doc)
-        } else {
-            None
         }
+        Some(doc)
+    }
+
+    /// Check if a document was deleted.
+    pub fn is_deleted(&self, id: &DocumentId) -> bool {
+        self.deleted.contains(id)
     }
 
     /// Check if a document exists.
@@ -424,14 +788,12 @@ impl DocumentStore {
 
         rga_text.insert(position, text);
         let delta = rga_text.take_delta();
-        doc.touch();
+        doc.touch_at(self.wall_clock.now_millis());
 
         if let Some(delta) = delta {
-            self.pending_changes.push(StoreChange::Update {
-                id: id.clone(),
-                delta: DocumentDelta::Text(delta),
-            });
+            self.push_update(id, DocumentDelta::Text(delta));
         }
+        self.reindex(id);
 
         Ok(())
     }
@@ -456,14 +818,12 @@ impl DocumentStore {
 
         rga_text.delete(start, length);
         let delta = rga_text.take_delta();
-        doc.touch();
+        doc.touch_at(self.wall_clock.now_millis());
 
         if let Some(delta) = delta {
-            self.pending_changes.push(StoreChange::Update {
-                id: id.clone(),
-                delta: DocumentDelta::Text(delta),
-            });
+            self.push_update(id, DocumentDelta::Text(delta));
         }
+        self.reindex(id);
 
         Ok(())
     }
@@ -505,14 +865,12 @@ impl DocumentStore {
 
         rich_text.insert(position, text);
         let delta = rich_text.take_delta();
-        doc.touch();
+        doc.touch_at(self.wall_clock.now_millis());
 
         if let Some(delta) = delta {
-            self.pending_changes.push(StoreChange::Update {
-                id: id.clone(),
-                delta: DocumentDelta::RichText(delta),
-            });
+            self.push_update(id, DocumentDelta::RichText(delta));
         }
+        self.reindex(id);
 
         Ok(())
     }
@@ -537,13 +895,10 @@ impl DocumentStore {
 
         rich_text.bold(start, end);
         let delta = rich_text.take_delta();
-        doc.touch();
+        doc.touch_at(self.wall_clock.now_millis());
 
         if let Some(delta) = delta {
-            self.pending_changes.push(StoreChange::Update {
-                id: id.clone(),
-                delta: DocumentDelta::RichText(delta),
-            });
+            self.push_update(id, DocumentDelta::RichText(delta));
         }
 
         Ok(())
@@ -569,13 +924,39 @@ impl DocumentStore {
 
         rich_text.italic(start, end);
         let delta = rich_text.take_delta();
-        doc.touch();
+        doc.touch_at(self.wall_clock.now_millis());
 
         if let Some(delta) = delta {
-            self.pending_changes.push(StoreChange::Update {
-                id: id.clone(),
-                delta: DocumentDelta::RichText(delta),
-            });
+            self.push_update(id, DocumentDelta::RichText(delta));
+        }
+
+        Ok(())
+    }
+
+    /// Set the block type of the paragraph containing `position`.
+    pub fn rich_text_set_block_type(
+        &mut self,
+        id: &DocumentId,
+        position: usize,
+        block_type: BlockType,
+    ) -> Result<(), DbError> {
+        let doc = self
+            .documents
+            .get_mut(id)
+            .ok_or_else(|| DbError::DocumentNotFound(id.to_string()))?;
+
+        let doc_type = doc.value.document_type();
+        let rich_text = doc.value.as_rich_text_mut().ok_or(DbError::TypeMismatch {
+            expected: "RichText".to_string(),
+            found: format!("{:?}", doc_type),
+        })?;
+
+        rich_text.set_block_type(position, block_type);
+        let delta = rich_text.take_delta();
+        doc.touch_at(self.wall_clock.now_millis());
+
+        if let Some(delta) = delta {
+            self.push_update(id, DocumentDelta::RichText(delta));
         }
 
         Ok(())
@@ -619,13 +1000,10 @@ impl DocumentStore {
 
         json.set(&JsonPath::parse(path), value)?;
         let delta = json.take_delta();
-        doc.touch();
+        doc.touch_at(self.wall_clock.now_millis());
 
         if let Some(delta) = delta {
-            self.pending_changes.push(StoreChange::Update {
-                id: id.clone(),
-                delta: DocumentDelta::Json(delta),
-            });
+            self.push_update(id, DocumentDelta::Json(delta));
         }
 
         Ok(())
@@ -678,53 +1056,124 @@ impl DocumentStore {
 
     /// Query documents with options.
     pub fn query(&self, options: &QueryOptions) -> Vec<&Document> {
-        let mut results: Vec<_> = self
-            .documents
-            .values()
-            .filter(|doc| {
-                // Type filter
-                if let Some(ref doc_type) = options.document_type {
-                    if &doc.document_type() != doc_type {
-                        return false;
+        self.query_iter(options).collect()
+    }
+
+    /// Query documents lazily.
+    ///
+    /// Without `sort_by`, documents are filtered as the iterator advances
+    /// and `offset`/`limit` stop the scan early. Sorting has no index to
+    /// read from, so a sorted query first collects the matching documents
+    /// (references only) and sorts them.
+    pub fn query_iter<'a: 'o, 'o>(
+        &'a self,
+        options: &'o QueryOptions,
+    ) -> impl Iterator<Item = &'a Document> + 'o {
+        let filter = self.query_filter(options);
+        let matching = self.documents.values().filter(move |doc| filter(doc));
+
+        let docs: Box<dyn Iterator<Item = &'a Document> + 'o> = match &options.sort_by {
+            None => Box::new(matching),
+            Some(sort_by) => {
+                let mut results: Vec<_> = matching.collect();
+                match sort_by {
+                    SortField::Title => {
+                        results.sort_by(|a, b| a.title.cmp(&b.title));
                     }
-                }
-                // Title prefix filter
-                if let Some(ref prefix) = options.title_prefix {
-                    if !doc.title.starts_with(prefix) {
-                        return false;
+                    SortField::CreatedAt => {
+                        results.sort_by_key(|a| a.created_at);
+                    }
+                    SortField::ModifiedAt => {
+                        results.sort_by_key(|a| a.modified_at);
                     }
                 }
-                true
-            })
-            .collect();
+                if options.sort_desc {
+                    results.reverse();
+                }
+                Box::new(results.into_iter())
+            }
+        };
+
+        // Pagination
+        docs.skip(options.offset.unwrap_or(0))
+            .take(options.limit.unwrap_or(usize::MAX))
+    }
+
+    /// Query document headers.
+    ///
+    /// Filtering, sorting and pagination only read headers; the CRDT value
+    /// of a document is read to estimate its size once it is on the page.
+    pub fn query_meta(&self, options: &QueryOptions) -> Vec<DocumentMeta> {
+        self.query_iter(options).map(Document::meta).collect()
+    }
 
-        // Sort
-        if let Some(ref sort_by) = options.sort_by {
-            match sort_by {
-                SortField::Title => {
-                    results.sort_by(|a, b| a.title.cmp(&b.title));
+    /// Number of documents [`query`](Self::query) would return.
+    ///
+    /// Nothing is sorted or collected, and the scan stops once `offset` plus
+    /// `limit` documents have matched.
+    pub fn count(&self, options: &QueryOptions) -> usize {
+        let filter = self.query_filter(options);
+        let wanted = options
+            .offset
+            .unwrap_or(0)
+            .saturating_add(options.limit.unwrap_or(usize::MAX));
+        let matching = self
+            .documents
+            .values()
+            .filter(|doc| filter(doc))
+            .take(wanted)
+            .count();
+        matching.saturating_sub(options.offset.unwrap_or(0))
+    }
+
+    /// The filters of `options` as a predicate on documents.
+    ///
+    /// Only `content_contains` reads a document's value, and only once its
+    /// header passed every other filter.
+    fn query_filter<'a>(&'a self, options: &'a QueryOptions) -> impl Fn(&Document) -> bool + 'a {
+        let parents = options.collection.as_ref().map(|_| self.resolved_parents());
+        let needle = options.content_contains.as_ref().map(|s| s.to_lowercase());
+        move |doc| {
+            // Type filter
+            if let Some(ref doc_type) = options.document_type {
+                if &doc.document_type() != doc_type {
+                    return false;
                 }
-                SortField::CreatedAt => {
-                    results.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+            }
+            // Title prefix filter
+            if let Some(ref prefix) = options.title_prefix {
+                if !doc.title.starts_with(prefix) {
+                    return false;
                 }
-                SortField::ModifiedAt => {
-                    results.sort_by(|a, b| a.modified_at.cmp(&b.modified_at));
+            }
+            // Metadata filter
+            if !options
+                .metadata_filters
+                .iter()
+                .all(|(key, value)| doc.metadata.get(key) == Some(value))
+            {
+                return false;
+            }
+            // Collection filter
+            if let Some(ref parents) = parents {
+                let target = options.collection.as_ref();
+                let current = self.effective_collection(&doc.id, parents);
+                let in_collection = if options.include_descendants {
+                    std::iter::successors(current, |c| parents.get(c).cloned().flatten())
+                        .any(|c| Some(&c) == target)
+                } else {
+                    current.as_ref() == target
+                };
+                if !in_collection {
+                    return false;
                 }
             }
-            if options.sort_desc {
-                results.reverse();
+            // Content filter
+            if let Some(ref needle) = needle {
+                return content_contains(&doc.value, needle);
             }
+            true
         }
-
-        // Pagination
-        if let Some(offset) = options.offset {
-            results = results.into_iter().skip(offset).collect();
-        }
-        if let Some(limit) = options.limit {
-            results.truncate(limit);
-        }
-
-        results
     }
 
     /// Prefix scan for titles.
@@ -736,85 +1185,1195 @@ impl DocumentStore {
             .collect()
     }
 
-    // === Replication ===
+    // === Collections ===
+    //
+    // Collection names, collection parents and document membership are
+    // last-writer-wins registers ordered by Lamport stamps, so concurrent
+    // moves of the same item converge to a single parent.
+    //
+    // Deleting a non-empty collection is rejected locally. Items a
+    // concurrent move places into a deleted collection appear in its
+    // nearest live ancestor.
+    //
+    // Moves that would create a cycle are rejected locally. Concurrent
+    // moves can still form one (A into B while B moves into A); the
+    // collection in the cycle with the newest move is then shown at the
+    // top level, which every replica computes identically.
+
+    fn tick(&mut self) -> LwwStamp {
+        self.clock += 1;
+        LwwStamp {
+            counter: self.clock,
+            replica: self.replica_id.clone(),
+        }
+    }
 
-    /// Take pending changes for replication.
-    pub fn take_changes(&mut self) -> Vec<StoreChange> {
-        std::mem::take(&mut self.pending_changes)
+    fn observe(&mut self, stamp: &LwwStamp) {
+        self.clock = self.clock.max(stamp.counter);
     }
 
-    /// Apply changes from another replica.
-    pub fn apply_changes(&mut self, changes: &[StoreChange]) {
-        for change in changes {
-            match change {
-                StoreChange::Create {
-                    id,
-                    doc_type,
-                    title,
-                } => {
-                    if !self.documents.contains_key(id) {
-                        let doc = match doc_type {
-                            DocumentType::Text => {
-                                Document::new_text(id.clone(), title, &self.replica_id)
-                            }
-                            DocumentType::RichText => {
-                                Document::new_rich_text(id.clone(), title, &self.replica_id)
-                            }
-                            DocumentType::Json => {
-                                Document::new_json(id.clone(), title, &self.replica_id)
-                            }
-                        };
-                        self.title_index.insert(title.clone(), id.clone());
-                        self.documents.insert(id.clone(), doc);
-                    }
-                }
-                StoreChange::Update { id, delta } => {
-                    if let Some(doc) = self.documents.get_mut(id) {
-                        match (delta, &mut doc.value) {
-                            (DocumentDelta::Text(d), CrdtValue::Text(t)) => {
-                                t.apply_delta(d);
-                            }
-                            (DocumentDelta::RichText(d), CrdtValue::RichText(rt)) => {
-                                rt.apply_delta(d);
-                            }
-                            (DocumentDelta::Json(d), CrdtValue::Json(j)) => {
-                                j.apply_delta(d);
-                            }
-                            _ => {} // Type mismatch, ignore
-                        }
-                        doc.touch();
-                    }
-                }
-                StoreChange::Delete { id } => {
-                    if let Some(doc) = self.documents.remove(id) {
-                        self.title_index.remove(&doc.title);
-                    }
-                }
-                StoreChange::MetadataChange { id, key, value } => {
-                    if let Some(doc) = self.documents.get_mut(id) {
-                        match value {
-                            Some(v) => {
-                                doc.metadata.insert(key.clone(), v.clone());
-                            }
-                            None => {
-                                doc.metadata.remove(key);
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    /// Timestamp a new document with the store's clock.
+    fn stamped(&self, mut doc: Document) -> Document {
+        let now = self.wall_clock.now_millis();
+        doc.created_at = now;
+        doc.modified_at = now;
+        doc
     }
 
-    /// Get all document IDs.
-    pub fn document_ids(&self) -> impl Iterator<Item = &DocumentId> + '_ {
-        self.documents.keys()
+    fn live_collection(&self, id: &CollectionId) -> Result<&CollectionState, DbError> {
+        self.collections
+            .get(id)
+            .filter(|c| !c.deleted)
+            .ok_or_else(|| DbError::CollectionNotFound(id.to_string()))
     }
-}
 
-#[cfg(test)]
-mod tests {
+    /// Create a collection, optionally inside another collection.
+    pub fn create_collection(
+        &mut self,
+        name: impl Into<String>,
+        parent: Option<CollectionId>,
+    ) -> Result<CollectionId, DbError> {
+        if let Some(ref p) = parent {
+            self.live_collection(p)?;
+        }
+        let id = CollectionId::new();
+        let name = name.into();
+        let stamp = self.tick();
+
+        self.collections.insert(
+            id.clone(),
+            CollectionState {
+                name: name.clone(),
+                name_stamp: stamp.clone(),
+                parent: parent.clone(),
+                parent_stamp: stamp.clone(),
+                deleted: false,
+            },
+        );
+        self.pending_changes.push(StoreChange::CreateCollection {
+            id: id.clone(),
+            name,
+            parent,
+            stamp,
+        });
+
+        Ok(id)
+    }
+
+    /// Rename a collection.
+    pub fn rename_collection(
+        &mut self,
+        id: &CollectionId,
+        name: impl Into<String>,
+    ) -> Result<(), DbError> {
+        self.live_collection(id)?;
+        let name = name.into();
+        let stamp = self.tick();
+
+        if let Some(col) = self.collections.get_mut(id) {
+            col.set_name(&name, &stamp);
+        }
+        self.pending_changes.push(StoreChange::RenameCollection {
+            id: id.clone(),
+            name,
+            stamp,
+        });
+
+        Ok(())
+    }
+
+    /// Move a document into a collection (`None` moves it to the top level).
+    pub fn move_document(
+        &mut self,
+        doc: &DocumentId,
+        collection: Option<CollectionId>,
+    ) -> Result<(), DbError> {
+        if !self.documents.contains_key(doc) {
+            return Err(DbError::DocumentNotFound(doc.to_string()));
+        }
+        if let Some(ref c) = collection {
+            self.live_collection(c)?;
+        }
+        let stamp = self.tick();
+
+        self.memberships
+            .insert(doc.clone(), (collection.clone(), stamp.clone()));
+        self.pending_changes.push(StoreChange::MoveDocument {
+            id: doc.clone(),
+            collection,
+            stamp,
+        });
+
+        Ok(())
+    }
+
+    /// Move a collection under a new parent (`None` moves it to the top level).
+    ///
+    /// Fails if the new parent is the collection itself or one of its
+    /// descendants.
+    pub fn move_collection(
+        &mut self,
+        id: &CollectionId,
+        new_parent: Option<CollectionId>,
+    ) -> Result<(), DbError> {
+        self.live_collection(id)?;
+        if let Some(ref p) = new_parent {
+            self.live_collection(p)?;
+            if self.is_descendant_or_self(p, id) {
+                return Err(DbError::InvalidMove(format!(
+                    "cannot move collection {} into its own subtree",
+                    id
+                )));
+            }
+        }
+        let stamp = self.tick();
+
+        if let Some(col) = self.collections.get_mut(id) {
+            col.set_parent(&new_parent, &stamp);
+        }
+        self.pending_changes.push(StoreChange::MoveCollection {
+            id: id.clone(),
+            parent: new_parent,
+            stamp,
+        });
+
+        Ok(())
+    }
+
+    /// Delete an empty collection.
+    pub fn delete_collection(&mut self, id: &CollectionId) -> Result<(), DbError> {
+        self.live_collection(id)?;
+        if !self.list_collection(Some(id)).is_empty() {
+            return Err(DbError::CollectionNotEmpty(id.to_string()));
+        }
+
+        if let Some(col) = self.collections.get_mut(id) {
+            col.deleted = true;
+        }
+        self.pending_changes
+            .push(StoreChange::DeleteCollection { id: id.clone() });
+
+        Ok(())
+    }
+
+    /// Get a collection's name.
+    pub fn collection_name(&self, id: &CollectionId) -> Option<&str> {
+        self.live_collection(id).ok().map(|c| c.name.as_str())
+    }
+
+    /// Get the collection a document is in (`None` for the top level).
+    pub fn document_collection(&self, doc: &DocumentId) -> Option<CollectionId> {
+        let parents = self.resolved_parents();
+        self.effective_collection(doc, &parents)
+    }
+
+    /// List the direct children of a collection (`None` lists the top level).
+    ///
+    /// Collections come first, sorted by name, then documents sorted by title.
+    pub fn list_collection(&self, collection: Option<&CollectionId>) -> Vec<CollectionEntry> {
+        let parents = self.resolved_parents();
+
+        let mut cols: Vec<_> = parents
+            .iter()
+            .filter(|(_, parent)| parent.as_ref() == collection)
+            .map(|(id, _)| (self.collections[id].name.clone(), id.clone()))
+            .collect();
+        cols.sort();
+
+        let mut docs: Vec<_> = self
+            .documents
+            .values()
+            .filter(|doc| self.effective_collection(&doc.id, &parents).as_ref() == collection)
+            .map(|doc| (doc.title.clone(), doc.id.clone()))
+            .collect();
+        docs.sort();
+
+        cols.into_iter()
+            .map(|(name, id)| CollectionEntry::Collection { id, name })
+            .chain(
+                docs.into_iter()
+                    .map(|(title, id)| CollectionEntry::Document { id, title }),
+            )
+            .collect()
+    }
+
+    /// Build the full collection tree.
+    pub fn collection_tree(&self) -> CollectionTree {
+        let parents = self.resolved_parents();
+
+        let mut children: BTreeMap<Option<CollectionId>, Vec<CollectionId>> = BTreeMap::new();
+        for (id, parent) in &parents {
+            children.entry(parent.clone()).or_default().push(id.clone());
+        }
+        let mut docs: BTreeMap<Option<CollectionId>, Vec<DocumentId>> = BTreeMap::new();
+        for id in self.documents.keys() {
+            docs.entry(self.effective_collection(id, &parents))
+                .or_default()
+                .push(id.clone());
+        }
+
+        CollectionTree {
+            collections: self.build_nodes(None, &children, &mut docs),
+            documents: docs.remove(&None).unwrap_or_default(),
+        }
+    }
+
+    fn build_nodes(
+        &self,
+        parent: Option<CollectionId>,
+        children: &BTreeMap<Option<CollectionId>, Vec<CollectionId>>,
+        docs: &mut BTreeMap<Option<CollectionId>, Vec<DocumentId>>,
+    ) -> Vec<CollectionNode> {
+        let mut nodes: Vec<_> = children
+            .get(&parent)
+            .into_iter()
+            .flatten()
+            .map(|id| CollectionNode {
+                id: id.clone(),
+                name: self.collections[id].name.clone(),
+                children: self.build_nodes(Some(id.clone()), children, docs),
+                documents: docs.remove(&Some(id.clone())).unwrap_or_default(),
+            })
+            .collect();
+        nodes.sort_by(|a, b| (&a.name, &a.id).cmp(&(&b.name, &b.id)));
+        nodes
+    }
+
+    /// Check whether `id` is `ancestor` or lies below it.
+    fn is_descendant_or_self(&self, id: &CollectionId, ancestor: &CollectionId) -> bool {
+        let parents = self.resolved_parents();
+        let mut current = Some(id.clone());
+        while let Some(c) = current {
+            if &c == ancestor {
+                return true;
+            }
+            current = parents.get(&c).cloned().flatten();
+        }
+        false
+    }
+
+    /// Effective parent of every live collection, with cycles broken and
+    /// deleted or unknown parents replaced by their nearest live ancestor.
+    fn resolved_parents(&self) -> BTreeMap<CollectionId, Option<CollectionId>> {
+        let mut raw: BTreeMap<&CollectionId, Option<&CollectionId>> = self
+            .collections
+            .iter()
+            .map(|(id, c)| {
+                let parent = c
+                    .parent
+                    .as_ref()
+                    .filter(|p| self.collections.contains_key(*p));
+                (id, parent)
+            })
+            .collect();
+
+        // Break cycles: detach the member with the newest parent stamp
+        loop {
+            let mut cycle = None;
+            for &start in raw.keys() {
+                let mut seen = BTreeSet::new();
+                let mut current = Some(start);
+                while let Some(c) = current {
+                    if !seen.insert(c) {
+                        cycle = Some(c);
+                        break;
+                    }
+                    current = raw.get(c).copied().flatten();
+                }
+                if cycle.is_some() {
+                    break;
+                }
+            }
+            let Some(entry) = cycle else { break };
+
+            let mut members = vec![entry];
+            let mut current = raw[entry].unwrap();
+            while current != entry {
+                members.push(current);
+                current = raw[current].unwrap();
+            }
+            let newest = members
+                .into_iter()
+                .max_by(|a, b| {
+                    self.collections[*a]
+                        .parent_stamp
+                        .cmp(&self.collections[*b].parent_stamp)
+                })
+                .unwrap();
+            raw.insert(newest, None);
+        }
+
+        let mut resolved = BTreeMap::new();
+        for (id, col) in &self.collections {
+            if col.deleted {
+                continue;
+            }
+            let mut parent = raw[id];
+            while let Some(p) = parent {
+                if !self.collections[p].deleted {
+                    break;
+                }
+                parent = raw[p];
+            }
+            resolved.insert(id.clone(), parent.cloned());
+        }
+        resolved
+    }
+
+    fn effective_collection(
+        &self,
+        doc: &DocumentId,
+        parents: &BTreeMap<CollectionId, Option<CollectionId>>,
+    ) -> Option<CollectionId> {
+        let mut current = self.memberships.get(doc).and_then(|(c, _)| c.clone());
+        let mut seen = BTreeSet::new();
+        while let Some(c) = current {
+            if parents.contains_key(&c) {
+                return Some(c);
+            }
+            // Deleted collections can still form a cycle of raw parents
+            if !seen.insert(c.clone()) {
+                break;
+            }
+            // Deleted or not yet known: fall back to its parent
+            current = self.collections.get(&c).and_then(|col| col.parent.clone());
+        }
+        None
+    }
+
+    // === Branches ===
+
+    /// Fork a document into a new branch and return the branch ID.
+    ///
+    /// The branch is an ordinary document that starts as a copy of the
+    /// source. Its state at this moment is kept as the fork point, which
+    /// [`branch_changes`](Self::branch_changes) diffs against.
+    pub fn fork(&mut self, id: &DocumentId) -> Result<DocumentId, DbError> {
+        let doc = self
+            .documents
+            .get(id)
+            .ok_or_else(|| DbError::DocumentNotFound(id.to_string()))?;
+        let title = format!("{} (fork)", doc.title);
+        let base = doc.value.clone();
+
+        let branch = DocumentId::new();
+        self.insert_branch(&branch, id, &title, &base);
+        self.pending_changes.push(StoreChange::Fork {
+            id: branch.clone(),
+            source: id.clone(),
+            title,
+            base,
+        });
+
+        Ok(branch)
+    }
+
+    /// Get the document a branch was forked from.
+    pub fn fork_source(&self, branch: &DocumentId) -> Option<&DocumentId> {
+        self.forks.get(branch).map(|f| &f.source)
+    }
+
+    fn insert_branch(
+        &mut self,
+        id: &DocumentId,
+        source: &DocumentId,
+        title: &str,
+        base: &CrdtValue,
+    ) {
+        // Branch edits get their own replica ID so they never collide with
+        // IDs generated on the source document.
+        let value = base.fork(&format!("{}@{}", self.replica_id, id));
+        let mut doc = self.stamped(Document::from_value(id.clone(), title, value));
+        doc.set_metadata("forked_from", source.to_string());
+
+        self.title_index.insert(title.to_string(), id.clone());
+        self.documents.insert(id.clone(), doc);
+        self.forks.insert(
+            id.clone(),
+            ForkPoint {
+                source: source.clone(),
+                base: base.clone(),
+            },
+        );
+        self.reindex(id);
+    }
+
+    /// List the changes made on a branch since its fork point.
+    ///
+    /// Text branches report inserted and deleted ranges. JSON branches
+    /// report changed leaf paths in dotted form; arrays are compared as
+    /// whole values and a `null` leaf counts as removed. Rich text
+    /// branches can be merged with [`MergePolicy::All`] but not diffed.
+    pub fn branch_changes(&self, branch: &DocumentId) -> Result<BranchDiff, DbError> {
+        let fork = self
+            .forks
+            .get(branch)
+            .ok_or_else(|| DbError::NotABranch(branch.to_string()))?;
+        let doc = self
+            .documents
+            .get(branch)
+            .ok_or_else(|| DbError::DocumentNotFound(branch.to_string()))?;
+
+        let changes = match (&fork.base, &doc.value) {
+            (CrdtValue::Text(base), CrdtValue::Text(text)) => text_changes(base, text),
+            (CrdtValue::Json(base), CrdtValue::Json(json)) => {
+                json_changes(&base.to_json(), &json.to_json())
+            }
+            (CrdtValue::RichText(_), CrdtValue::RichText(_)) => {
+                return Err(DbError::UnsupportedOperation(
+                    "Branch diff for rich text".to_string(),
+                ))
+            }
+            (base, value) => {
+                return Err(DbError::TypeMismatch {
+                    expected: format!("{:?}", base.document_type()),
+                    found: format!("{:?}", value.document_type()),
+                })
+            }
+        };
+
+        Ok(BranchDiff {
+            branch: branch.clone(),
+            source: fork.source.clone(),
+            changes,
+        })
+    }
+
+    /// Merge a branch into another document, usually its source.
+    ///
+    /// [`MergePolicy::All`] joins the whole branch state into the target,
+    /// exactly like syncing the two. [`MergePolicy::Selected`] re-applies
+    /// only the chosen changes as new edits on the target. Refs index the
+    /// diff returned by [`branch_changes`](Self::branch_changes), so compute
+    /// that diff without editing the branch in between. Because the target
+    /// never receives the branch's own operations, changes left out stay
+    /// out, even as both documents keep syncing with other replicas; a
+    /// later `Selected` merge can still pick them up.
+    ///
+    /// Text inserts land after the fork-point character they followed on
+    /// the branch; deletes skip characters already gone from the target.
+    pub fn merge_branch(
+        &mut self,
+        branch: &DocumentId,
+        into: &DocumentId,
+        policy: MergePolicy,
+    ) -> Result<(), DbError> {
+        let selected = match policy {
+            MergePolicy::All => return self.merge_branch_state(branch, into),
+            MergePolicy::Selected(refs) => refs,
+        };
+
+        let diff = self.branch_changes(branch)?;
+        let mut refs = selected;
+        refs.sort();
+        refs.dedup();
+        let mut changes = Vec::with_capacity(refs.len());
+        for r in refs {
+            let change = diff.get(r).ok_or(DbError::IndexOutOfBounds {
+                index: r.0,
+                length: diff.changes.len(),
+            })?;
+            changes.push(change);
+        }
+
+        let doc = self
+            .documents
+            .get_mut(into)
+            .ok_or_else(|| DbError::DocumentNotFound(into.to_string()))?;
+
+        let delta = match &mut doc.value {
+            CrdtValue::Text(text) => {
+                for change in changes {
+                    apply_text_change(text, change)?;
+                }
+                text.take_delta().map(DocumentDelta::Text)
+            }
+            CrdtValue::Json(json) => {
+                for change in changes {
+                    apply_json_change(json, change)?;
+                }
+                json.take_delta().map(DocumentDelta::Json)
+            }
+            CrdtValue::RichText(_) => {
+                return Err(DbError::UnsupportedOperation(
+                    "Selected merge into rich text".to_string(),
+                ))
+            }
+        };
+        doc.touch_at(self.wall_clock.now_millis());
+
+        if let Some(delta) = delta {
+            self.push_update(into, delta);
+        }
+        self.reindex(into);
+
+        Ok(())
+    }
+
+    fn merge_branch_state(
+        &mut self,
+        branch: &DocumentId,
+        into: &DocumentId,
+    ) -> Result<(), DbError> {
+        let value = self
+            .documents
+            .get(branch)
+            .ok_or_else(|| DbError::DocumentNotFound(branch.to_string()))?
+            .value
+            .clone();
+        let doc = self
+            .documents
+            .get_mut(into)
+            .ok_or_else(|| DbError::DocumentNotFound(into.to_string()))?;
+        if doc.document_type() != value.document_type() {
+            return Err(DbError::TypeMismatch {
+                expected: format!("{:?}", doc.document_type()),
+                found: format!("{:?}", value.document_type()),
+            });
+        }
+
+        doc.value = doc.value.join(&value);
+        doc.touch_at(self.wall_clock.now_millis());
+        self.pending_changes.push(StoreChange::Merge {
+            id: into.clone(),
+            value,
+        });
+        self.reindex(into);
+
+        Ok(())
+    }
+
+    // === Replication ===
+
+    /// Record a local update for replication.
+    ///
+    /// Once a document's run of consecutive pending updates exceeds the
+    /// coalesce threshold, the pending changes are coalesced.
+    fn push_update(&mut self, id: &DocumentId, delta: DocumentDelta) {
+        self.pending_changes.push(StoreChange::Update {
+            id: id.clone(),
+            delta,
+        });
+        let run = self
+            .pending_changes
+            .iter()
+            .rev()
+            .take_while(
+                |change| matches!(change, StoreChange::Update { id: other, .. } if other == id),
+            )
+            .count();
+        if run > self.coalesce_threshold {
+            self.coalesce_pending();
+        }
+    }
+
+    /// Merge consecutive pending updates to the same document into one.
+    ///
+    /// Applying the coalesced changes has the same effect as applying the
+    /// original ones.
+    pub fn coalesce_pending(&mut self) {
+        let mut coalesced: Vec<StoreChange> = Vec::with_capacity(self.pending_changes.len());
+        for change in self.pending_changes.drain(..) {
+            if let (
+                Some(StoreChange::Update { id, delta }),
+                StoreChange::Update {
+                    id: next_id,
+                    delta: next,
+                },
+            ) = (coalesced.last_mut(), &change)
+            {
+                if id == next_id {
+                    if let Some(joined) = delta.join(next) {
+                        *delta = joined;
+                        continue;
+                    }
+                }
+            }
+            coalesced.push(change);
+        }
+        self.pending_changes = coalesced;
+    }
+
+    /// Set how many consecutive pending updates to one document trigger
+    /// [`coalesce_pending`](Self::coalesce_pending).
+    pub fn set_coalesce_threshold(&mut self, threshold: usize) {
+        self.coalesce_threshold = threshold;
+    }
+
+    /// Number of changes waiting for replication.
+    pub fn pending_len(&self) -> usize {
+        self.pending_changes.len()
+    }
+
+    /// Take pending changes for replication.
+    pub fn take_changes(&mut self) -> Vec<StoreChange> {
+        std::mem::take(&mut self.pending_changes)
+    }
+
+    /// Apply changes from another replica.
+    pub fn apply_changes(&mut self, changes: &[StoreChange]) {
+        for change in changes {
+            match change {
+                StoreChange::Create {
+                    id,
+                    doc_type,
+                    title,
+                } => {
+                    if !self.documents.contains_key(id) && !self.deleted.contains(id) {
+                        let doc = self.stamped(match doc_type {
+                            DocumentType::Text => {
+                                Document::new_text(id.clone(), title, &self.replica_id)
+                            }
+                            DocumentType::RichText => {
+                                Document::new_rich_text(id.clone(), title, &self.replica_id)
+                            }
+                            DocumentType::Json => {
+                                Document::new_json(id.clone(), title, &self.replica_id)
+                            }
+                        });
+                        self.title_index.insert(title.clone(), id.clone());
+                        self.documents.insert(id.clone(), doc);
+                    }
+                }
+                StoreChange::Update { id, delta } => {
+                    if let Some(doc) = self.documents.get_mut(id) {
+                        match (delta, &mut doc.value) {
+                            (DocumentDelta::Text(d), CrdtValue::Text(t)) => {
+                                t.apply_delta(d);
+                            }
+                            (DocumentDelta::RichText(d), CrdtValue::RichText(rt)) => {
+                                rt.apply_delta(d);
+                            }
+                            (DocumentDelta::Json(d), CrdtValue::Json(j)) => {
+                                j.apply_delta(d);
+                            }
+                            _ => {} // Type mismatch, ignore
+                        }
+                        doc.touch_at(self.wall_clock.now_millis());
+                        self.reindex(id);
+                    }
+                }
+                StoreChange::Delete { id } => {
+                    self.remove_document(id);
+                }
+                StoreChange::MetadataChange { id, key, value } => {
+                    if let Some(doc) = self.documents.get_mut(id) {
+                        match value {
+                            Some(v) => {
+                                doc.metadata.insert(key.clone(), v.clone());
+                            }
+                            None => {
+                                doc.metadata.remove(key);
+                            }
+                        }
+                    }
+                }
+                StoreChange::CreateCollection {
+                    id,
+                    name,
+                    parent,
+                    stamp,
+                } => {
+                    self.observe(stamp);
+                    let col = self
+                        .collections
+                        .entry(id.clone())
+                        .or_insert_with(CollectionState::placeholder);
+                    col.set_name(name, stamp);
+                    col.set_parent(parent, stamp);
+                }
+                StoreChange::RenameCollection { id, name, stamp } => {
+                    self.observe(stamp);
+                    self.collections
+                        .entry(id.clone())
+                        .or_insert_with(CollectionState::placeholder)
+                        .set_name(name, stamp);
+                }
+                StoreChange::MoveCollection { id, parent, stamp } => {
+                    self.observe(stamp);
+                    self.collections
+                        .entry(id.clone())
+                        .or_insert_with(CollectionState::placeholder)
+                        .set_parent(parent, stamp);
+                }
+                StoreChange::DeleteCollection { id } => {
+                    let col = self
+                        .collections
+                        .entry(id.clone())
+                        .or_insert_with(CollectionState::placeholder);
+                    col.deleted = true;
+                }
+                StoreChange::MoveDocument {
+                    id,
+                    collection,
+                    stamp,
+                } => {
+                    self.observe(stamp);
+                    let newer = self
+                        .memberships
+                        .get(id)
+                        .is_none_or(|(_, current)| stamp > current);
+                    if newer {
+                        self.memberships
+                            .insert(id.clone(), (collection.clone(), stamp.clone()));
+                    }
+                }
+                StoreChange::Fork {
+                    id,
+                    source,
+                    title,
+                    base,
+                } => {
+                    if !self.documents.contains_key(id) && !self.deleted.contains(id) {
+                        self.insert_branch(id, source, title, base);
+                    }
+                }
+                StoreChange::Merge { id, value } => {
+                    if let Some(doc) = self.documents.get_mut(id) {
+                        doc.value = doc.value.join(value);
+                        doc.touch_at(self.wall_clock.now_millis());
+                        self.reindex(id);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Merge the full state of another replica's store into this one.
+    ///
+    /// Unlike [`apply_changes`](Self::apply_changes) this needs no change
+    /// log, so a replica that joins late can catch up on documents whose
+    /// changes were taken long ago. Merging is commutative, associative and
+    /// idempotent:
+    ///
+    /// - Documents from either store are kept, unless either deleted them:
+    ///   tombstones win over concurrent edits.
+    /// - A document in both stores joins its values. Title and conflicting
+    ///   metadata come from the copy modified last, ties going to the
+    ///   higher replica ID; metadata keys only one copy has are kept.
+    /// - Collections, document memberships and fork points merge as their
+    ///   replicated changes do.
+    ///
+    /// Nothing is added to the pending changes.
+    pub fn merge(&mut self, other: &DocumentStore) {
+        for id in &other.deleted {
+            self.remove_document(id);
+        }
+
+        for (id, theirs) in &other.documents {
+            if self.deleted.contains(id) {
+                continue;
+            }
+            match self.documents.get_mut(id) {
+                Some(ours) => {
+                    let ours_newer = (ours.modified_at, self.replica_id.as_str())
+                        >= (theirs.modified_at, other.replica_id.as_str());
+                    ours.value = ours.value.join(&theirs.value);
+                    ours.created_at = ours.created_at.min(theirs.created_at);
+                    for (key, value) in &theirs.metadata {
+                        if !ours_newer || !ours.metadata.contains_key(key) {
+                            ours.metadata.insert(key.clone(), value.clone());
+                        }
+                    }
+                    if !ours_newer {
+                        ours.title = theirs.title.clone();
+                        ours.modified_at = theirs.modified_at;
+                    }
+                }
+                None => {
+                    // Our edits to the copy must not reuse their replica ID
+                    let replica_id = if other.forks.contains_key(id) {
+                        format!("{}@{}", self.replica_id, id)
+                    } else {
+                        self.replica_id.clone()
+                    };
+                    let mut doc = theirs.clone();
+                    doc.value = theirs.value.fork(&replica_id);
+                    self.documents.insert(id.clone(), doc);
+                }
+            }
+            self.reindex(id);
+        }
+
+        for (id, fork) in &other.forks {
+            if self.documents.contains_key(id) {
+                self.forks.entry(id.clone()).or_insert_with(|| fork.clone());
+            }
+        }
+
+        for (id, theirs) in &other.collections {
+            let ours = self
+                .collections
+                .entry(id.clone())
+                .or_insert_with(CollectionState::placeholder);
+            ours.set_name(&theirs.name, &theirs.name_stamp);
+            ours.set_parent(&theirs.parent, &theirs.parent_stamp);
+            ours.deleted |= theirs.deleted;
+        }
+        for (id, (collection, stamp)) in &other.memberships {
+            let newer = self
+                .memberships
+                .get(id)
+                .is_none_or(|(_, current)| stamp > current);
+            if newer {
+                self.memberships
+                    .insert(id.clone(), (collection.clone(), stamp.clone()));
+            }
+        }
+        self.clock = self.clock.max(other.clock);
+
+        self.title_index = self
+            .documents
+            .iter()
+            .map(|(id, doc)| (doc.title.clone(), id.clone()))
+            .collect();
+    }
+
+    /// Get all document IDs.
+    pub fn document_ids(&self) -> impl Iterator<Item = &DocumentId> + '_ {
+        self.documents.keys()
+    }
+
+    // === Search ===
+
+    /// Index every text and rich text document for [`search`](Self::search).
+    ///
+    /// From then on the index follows each change to a document's text,
+    /// local or replicated. Edits made through [`get_mut`](Self::get_mut)
+    /// bypass it; call [`reindex`](Self::reindex) after them.
+    pub fn enable_search(&mut self, config: SearchConfig) {
+        let mut index = SearchIndex::new(config);
+        for (id, doc) in &self.documents {
+            if let Some(text) = searchable_text(&doc.value) {
+                index.update(id, &text);
+            }
+        }
+        self.search = Some(index);
+    }
+
+    /// Use a previously saved index instead of building one.
+    ///
+    /// The index must have been saved together with the current documents;
+    /// documents changed since are only picked up when they next change.
+    pub fn restore_search(&mut self, index: SearchIndex) {
+        self.search = Some(index);
+    }
+
+    /// Stop maintaining the search index and drop it.
+    pub fn disable_search(&mut self) {
+        self.search = None;
+    }
+
+    /// Get the search index, if search is enabled.
+    pub fn search_index(&self) -> Option<&SearchIndex> {
+        self.search.as_ref()
+    }
+
+    /// Re-index a document's text after it was edited directly.
+    pub fn reindex(&mut self, id: &DocumentId) {
+        let Some(index) = &mut self.search else {
+            return;
+        };
+        match self
+            .documents
+            .get(id)
+            .and_then(|d| searchable_text(&d.value))
+        {
+            Some(text) => index.update(id, &text),
+            None => index.remove(id),
+        }
+    }
+
+    /// Find text and rich text documents containing every word of `query`.
+    ///
+    /// Returns a hit for each occurrence of the query's first word in a
+    /// matching document. Empty when search is not enabled. Hits follow
+    /// this replica's current text, so they are only eventually consistent
+    /// across replicas.
+    pub fn search(&self, query: &str) -> Vec<SearchHit> {
+        let Some(index) = &self.search else {
+            return Vec::new();
+        };
+        let mut hits = Vec::new();
+        let mut text = None;
+        for (doc, offset, length) in index.matches(query) {
+            if text.as_ref().is_none_or(|(id, _)| id != &doc) {
+                let content = self
+                    .documents
+                    .get(&doc)
+                    .and_then(|d| searchable_text(&d.value))
+                    .unwrap_or_default();
+                text = Some((doc.clone(), content));
+            }
+            let (_, content) = text.as_ref().expect("set above");
+            hits.push(search::hit(doc, content, offset, length));
+        }
+        hits
+    }
+}
+
+/// The plain text of a text or rich text value.
+fn searchable_text(value: &CrdtValue) -> Option<String> {
+    match value {
+        CrdtValue::Text(text) => Some(text.to_string()),
+        CrdtValue::RichText(text) => Some(text.to_string()),
+        CrdtValue::Json(_) => None,
+    }
+}
+
+/// Check whether a value's text, or one of its JSON string values,
+/// contains `needle`, which must be lowercase.
+fn content_contains(value: &CrdtValue, needle: &str) -> bool {
+    #[cfg(test)]
+    tests::PAYLOAD_READS.with(|reads| reads.set(reads.get() + 1));
+    match value {
+        CrdtValue::Text(text) => text.to_string().to_lowercase().contains(needle),
+        CrdtValue::RichText(text) => text.to_string().to_lowercase().contains(needle),
+        CrdtValue::Json(json) => json_contains(&json.to_json(), needle),
+    }
+}
+
+fn json_contains(value: &serde_json::Value, needle: &str) -> bool {
+    match value {
+        serde_json::Value::String(s) => s.to_lowercase().contains(needle),
+        serde_json::Value::Array(items) => items.iter().any(|v| json_contains(v, needle)),
+        serde_json::Value::Object(fields) => fields.values().any(|v| json_contains(v, needle)),
+        _ => false,
+    }
+}
+
+/// Diff a text branch against its fork point.
+fn text_changes(base: &RGAText, branch: &RGAText) -> Vec<BranchChange> {
+    let mut changes = Vec::new();
+    let mut anchor = TextId::genesis();
+    // Visible positions in the branch and in the fork point
+    let mut branch_pos = 0;
+    let mut base_pos = 0;
+    let mut insert: Option<(usize, String, TextId)> = None;
+    let mut delete: Option<(usize, String, Vec<TextId>)> = None;
+
+    for (id, ch) in branch.iter_with_tombstones() {
+        let in_base = base.contains_id(id);
+        let base_char = base.char_of(id);
+
+        match (in_base, base_char, ch) {
+            // New on the branch and still visible
+            (false, _, Some(c)) => {
+                if let Some((position, text, ids)) = delete.take() {
+                    changes.push(BranchChange::TextDelete {
+                        position,
+                        text,
+                        ids,
+                    });
+                }
+                insert
+                    .get_or_insert_with(|| (branch_pos, String::new(), anchor.clone()))
+                    .1
+                    .push(c);
+                branch_pos += 1;
+            }
+            // New on the branch but deleted again: no net change
+            (false, _, None) => {}
+            // Visible at the fork point, deleted on the branch
+            (true, Some(c), None) => {
+                if let Some((position, text, after)) = insert.take() {
+                    changes.push(BranchChange::TextInsert {
+                        position,
+                        text,
+                        after,
+                    });
+                }
+                let run = delete.get_or_insert_with(|| (base_pos, String::new(), Vec::new()));
+                run.1.push(c);
+                run.2.push(id.clone());
+                base_pos += 1;
+                anchor = id.clone();
+            }
+            // Unchanged fork-point character (visible or tombstone)
+            (true, base_char, ch) => {
+                if let Some((position, text, after)) = insert.take() {
+                    changes.push(BranchChange::TextInsert {
+                        position,
+                        text,
+                        after,
+                    });
+                }
+                if let Some((position, text, ids)) = delete.take() {
+                    changes.push(BranchChange::TextDelete {
+                        position,
+                        text,
+                        ids,
+                    });
+                }
+                if base_char.is_some() {
+                    base_pos += 1;
+                }
+                if ch.is_some() {
+                    branch_pos += 1;
+                }
+                anchor = id.clone();
+            }
+        }
+    }
+
+    if let Some((position, text, after)) = insert {
+        changes.push(BranchChange::TextInsert {
+            position,
+            text,
+            after,
+        });
+    }
+    if let Some((position, text, ids)) = delete {
+        changes.push(BranchChange::TextDelete {
+            position,
+            text,
+            ids,
+        });
+    }
+    changes
+}
+
+/// Diff two JSON values leaf by leaf.
+fn json_changes(base: &serde_json::Value, branch: &serde_json::Value) -> Vec<BranchChange> {
+    let mut before = BTreeMap::new();
+    let mut after = BTreeMap::new();
+    flatten_json("", base, &mut before);
+    flatten_json("", branch, &mut after);
+
+    let mut changes = Vec::new();
+    for (path, value) in &after {
+        if before.get(path) != Some(value) {
+            changes.push(BranchChange::JsonSet {
+                path: path.clone(),
+                value: value.clone(),
+            });
+        }
+    }
+    for path in before.keys() {
+        if !after.contains_key(path) {
+            changes.push(BranchChange::JsonRemove { path: path.clone() });
+        }
+    }
+    changes
+}
+
+/// Collect the non-null leaves of a JSON value by dotted path.
+fn flatten_json(
+    path: &str,
+    value: &serde_json::Value,
+    out: &mut BTreeMap<String, serde_json::Value>,
+) {
+    match value {
+        serde_json::Value::Object(map) if !map.is_empty() || path.is_empty() => {
+            for (key, child) in map {
+                let child_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                flatten_json(&child_path, child, out);
+            }
+        }
+        serde_json::Value::Null => {}
+        _ => {
+            out.insert(path.to_string(), value.clone());
+        }
+    }
+}
+
+/// Re-apply a branch change to a text document.
+fn apply_text_change(text: &mut RGAText, change: &BranchChange) -> Result<(), DbError> {
+    match change {
+        BranchChange::TextInsert {
+            position,
+            text: inserted,
+            after,
+        } => {
+            let at = text
+                .position_after(after)
+                .unwrap_or_else(|| (*position).min(text.len()));
+            text.insert(at, inserted);
+        }
+        BranchChange::TextDelete { ids, .. } => {
+            for id in ids {
+                if let Some(at) = text.id_to_position(id) {
+                    text.delete(at, 1);
+                }
+            }
+        }
+        other => {
+            return Err(DbError::TypeMismatch {
+                expected: "Text".to_string(),
+                found: format!("{:?}", other),
+            })
+        }
+    }
+    Ok(())
+}
+
+/// Re-apply a branch change to a JSON document.
+fn apply_json_change(json: &mut JsonCrdt, change: &BranchChange) -> Result<(), DbError> {
+    match change {
+        BranchChange::JsonSet { path, value } => {
+            let path = JsonPath::parse(path);
+            match value {
+                serde_json::Value::Object(_) => {
+                    json.set_object(&path)?;
+                }
+                serde_json::Value::Array(items) => {
+                    let array = json.set_array(&path)?;
+                    for item in items {
+                        json.array_push(&array, scalar_to_json_value(item)?)?;
+                    }
+                }
+                scalar => json.set(&path, scalar_to_json_value(scalar)?)?,
+            }
+        }
+        BranchChange::JsonRemove { path } => {
+            let path = JsonPath::parse(path);
+            if json.get(&path).is_some() {
+                json.delete(&path)?;
+            }
+        }
+        other => {
+            return Err(DbError::TypeMismatch {
+                expected: "Json".to_string(),
+                found: format!("{:?}", other),
+            })
+        }
+    }
+    Ok(())
+}
+
+fn scalar_to_json_value(value: &serde_json::Value) -> Result<JsonValue, DbError> {
+    Ok(match value {
+        serde_json::Value::Null => JsonValue::Null,
+        serde_json::Value::Bool(b) => JsonValue::Bool(*b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => JsonValue::Int(i),
+            None => JsonValue::Float(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => JsonValue::String(s.clone()),
+        _ => {
+            return Err(DbError::UnsupportedOperation(
+                "Merging nested containers inside arrays".to_string(),
+            ))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
     use super::*;
+    use mdcs_core::clock::ManualClock;
+    use std::cell::Cell;
+    use std::sync::Arc;
+
+    thread_local! {
+        /// Calls to `CrdtValue::size_estimate` and `content_contains`, the
+        /// only reads of a value's content on the query paths.
+        pub(super) static PAYLOAD_READS: Cell<usize> = const { Cell::new(0) };
+    }
 
     #[test]
     fn test_create_documents() {
@@ -895,6 +2454,147 @@ mod tests {
         assert_eq!(results.len(), 2);
     }
 
+    #[test]
+    fn test_query_meta_reads_only_matching_values() {
+        let mut store = DocumentStore::new("r1");
+        for i in 0..20 {
+            let id = store.create_json(format!("Json {}", i));
+            store.json_set(&id, "n", JsonValue::Int(i)).unwrap();
+        }
+        for i in 0..5 {
+            let id = store.create_text(format!("Text {}", i));
+            store.text_insert(&id, 0, "hello").unwrap();
+        }
+
+        let options = QueryOptions {
+            document_type: Some(DocumentType::Text),
+            ..Default::default()
+        };
+        PAYLOAD_READS.with(|reads| reads.set(0));
+        let meta = store.query_meta(&options);
+        assert_eq!(meta.len(), 5);
+        assert!(meta
+            .iter()
+            .all(|m| m.doc_type == DocumentType::Text && m.size_estimate == 5));
+        assert_eq!(PAYLOAD_READS.with(Cell::get), 5);
+
+        // Pagination applies before any value is read
+        let page = QueryOptions {
+            sort_by: Some(SortField::Title),
+            offset: Some(1),
+            limit: Some(2),
+            ..options
+        };
+        PAYLOAD_READS.with(|reads| reads.set(0));
+        let titles: Vec<_> = store
+            .query_meta(&page)
+            .into_iter()
+            .map(|m| m.title)
+            .collect();
+        assert_eq!(titles, ["Text 1", "Text 2"]);
+        assert_eq!(PAYLOAD_READS.with(Cell::get), 2);
+
+        assert_eq!(store.count(&page), 2);
+        assert_eq!(PAYLOAD_READS.with(Cell::get), 2);
+    }
+
+    #[test]
+    fn test_query_metadata_and_content_filters() {
+        let mut store = DocumentStore::new("r1");
+        let docs = [
+            ("Notes", "Meeting NOTES for Monday", "draft"),
+            ("Plan", "nothing to see", "draft"),
+            ("Recap", "more notes", "final"),
+            ("Todo", "Write notes", "draft"),
+        ];
+        for (title, content, status) in docs {
+            let id = store.create_text(title);
+            store.text_insert(&id, 0, content).unwrap();
+            store.get_mut(&id).unwrap().set_metadata("status", status);
+        }
+        let rich = store.create_rich_text("Rich");
+        store.rich_text_insert(&rich, 0, "Release notes").unwrap();
+        store
+            .get_mut(&rich)
+            .unwrap()
+            .set_metadata("status", "draft");
+        let json = store.create_json("Board");
+        store
+            .json_set(&json, "card.title", JsonValue::String("Notes board".into()))
+            .unwrap();
+        store.json_set(&json, "notes", JsonValue::Int(3)).unwrap();
+        store
+            .get_mut(&json)
+            .unwrap()
+            .set_metadata("status", "draft");
+
+        let titles = |options: &QueryOptions| -> Vec<String> {
+            store
+                .query(options)
+                .into_iter()
+                .map(|d| d.title.clone())
+                .collect()
+        };
+
+        // Content matches ignore case and cover JSON string values only
+        let notes = QueryOptions {
+            content_contains: Some("notes".to_string()),
+            sort_by: Some(SortField::Title),
+            ..Default::default()
+        };
+        assert_eq!(titles(&notes), ["Board", "Notes", "Recap", "Rich", "Todo"]);
+
+        // Every filter has to match
+        let drafts = QueryOptions {
+            document_type: Some(DocumentType::Text),
+            metadata_filters: vec![("status".to_string(), "draft".to_string())],
+            ..notes.clone()
+        };
+        assert_eq!(titles(&drafts), ["Notes", "Todo"]);
+        let missing_key = QueryOptions {
+            metadata_filters: vec![
+                ("status".to_string(), "draft".to_string()),
+                ("owner".to_string(), "ann".to_string()),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(store.count(&missing_key), 0);
+
+        // Pagination counts matching documents only, in sorted order
+        let page = QueryOptions {
+            metadata_filters: vec![("status".to_string(), "draft".to_string())],
+            sort_desc: true,
+            offset: Some(1),
+            limit: Some(2),
+            ..notes.clone()
+        };
+        assert_eq!(titles(&page), ["Rich", "Notes"]);
+        assert_eq!(store.count(&page), 2);
+        let last = QueryOptions {
+            offset: Some(3),
+            ..page
+        };
+        assert_eq!(titles(&last), ["Board"]);
+        assert_eq!(store.count(&last), 1);
+
+        // Metadata filters alone never read the content
+        let metadata_only = QueryOptions {
+            metadata_filters: vec![("status".to_string(), "final".to_string())],
+            ..Default::default()
+        };
+        PAYLOAD_READS.with(|reads| reads.set(0));
+        assert_eq!(titles(&metadata_only), ["Recap"]);
+        assert_eq!(PAYLOAD_READS.with(Cell::get), 0);
+
+        // With both, only documents passing the metadata filter are read
+        let both = QueryOptions {
+            content_contains: Some("NOTES".to_string()),
+            ..metadata_only
+        };
+        assert_eq!(titles(&both), ["Recap"]);
+        assert_eq!(PAYLOAD_READS.with(Cell::get), 1);
+    }
+
     #[test]
     fn test_prefix_scan() {
         let mut store = DocumentStore::new("r1");
@@ -937,6 +2637,169 @@ mod tests {
         assert_eq!(content, "Hello");
     }
 
+    #[test]
+    fn test_merge_catches_up_late_joiner() {
+        let mut a = DocumentStore::new("a");
+        let notes = a.create_text("Notes");
+        a.text_insert(&notes, 0, "hello").unwrap();
+        let config = a.create_json("Config");
+        a.json_set(&config, "theme", JsonValue::String("dark".into()))
+            .unwrap();
+        let folder = a.create_collection("Work", None).unwrap();
+        a.move_document(&notes, Some(folder.clone())).unwrap();
+        // The change log is gone before the new replica shows up
+        a.take_changes();
+
+        let mut c = DocumentStore::new("c");
+        c.merge(&a);
+        assert_eq!(c.len(), 2);
+        assert_eq!(c.text_content(&notes).unwrap(), "hello");
+        assert_eq!(c.json_to_value(&config).unwrap()["theme"], "dark");
+        assert_eq!(c.find_by_title("Notes").unwrap().id, notes);
+        assert_eq!(c.document_collection(&notes), Some(folder));
+        assert!(c.take_changes().is_empty());
+
+        // Concurrent edits after the catch-up converge
+        c.text_insert(&notes, 5, " world").unwrap();
+        a.text_insert(&notes, 0, ">").unwrap();
+        a.merge(&c);
+        c.merge(&a);
+        assert_eq!(a.text_content(&notes).unwrap(), ">hello world");
+        assert_eq!(c.text_content(&notes).unwrap(), ">hello world");
+
+        c.merge(&a.clone());
+        assert_eq!(c.text_content(&notes).unwrap(), ">hello world");
+        assert_eq!(c.len(), 2);
+    }
+
+    #[test]
+    fn test_merge_delete_wins_over_concurrent_edit() {
+        let mut a = DocumentStore::new("a");
+        let id = a.create_text("Doomed");
+        let kept = a.create_text("Kept");
+        let create = a.take_changes();
+        let mut b = DocumentStore::new("b");
+        b.merge(&a);
+
+        a.delete(&id);
+        b.text_insert(&id, 0, "still editing").unwrap();
+        b.text_insert(&kept, 0, "fine").unwrap();
+
+        a.merge(&b);
+        b.merge(&a);
+        for store in [&a, &b] {
+            assert!(!store.contains(&id));
+            assert!(store.is_deleted(&id));
+            assert!(store.find_by_title("Doomed").is_none());
+            assert_eq!(store.text_content(&kept).unwrap(), "fine");
+        }
+
+        // Replaying the creation doesn't bring it back either
+        b.apply_changes(&create);
+        assert!(!b.contains(&id));
+    }
+
+    #[test]
+    fn test_merge_metadata_last_modified_wins() {
+        let clock_a = Arc::new(ManualClock::new(1_000));
+        let clock_b = Arc::new(ManualClock::new(1_000));
+        let mut a = DocumentStore::with_clock("a", clock_a.clone());
+        let mut b = DocumentStore::with_clock("b", clock_b.clone());
+        let id = a.create_text("Report");
+        b.merge(&a);
+
+        clock_a.set(2_000);
+        clock_b.set(3_000);
+        a.text_insert(&id, 0, "a").unwrap();
+        let doc = a.get_mut(&id).unwrap();
+        doc.set_metadata("status", "draft");
+        doc.set_metadata("owner", "ann");
+        b.text_insert(&id, 0, "b").unwrap();
+        b.get_mut(&id).unwrap().set_metadata("status", "final");
+
+        let mut ab = a.clone();
+        ab.merge(&b);
+        b.merge(&a);
+        for store in [&ab, &b] {
+            let doc = store.get(&id).unwrap();
+            assert_eq!(doc.get_metadata("status"), Some(&"final".to_string()));
+            assert_eq!(doc.get_metadata("owner"), Some(&"ann".to_string()));
+            assert_eq!((doc.created_at, doc.modified_at), (1_000, 3_000));
+        }
+        assert_eq!(ab.text_content(&id).unwrap(), b.text_content(&id).unwrap());
+
+        // Equal timestamps go to the higher replica ID
+        clock_a.set(4_000);
+        clock_b.set(4_000);
+        ab.get_mut(&id).unwrap().set_metadata("status", "from a");
+        ab.text_insert(&id, 0, "!").unwrap();
+        b.get_mut(&id).unwrap().set_metadata("status", "from b");
+        b.text_insert(&id, 0, "?").unwrap();
+        ab.merge(&b);
+        b.merge(&ab);
+        for store in [&ab, &b] {
+            let status = store.get(&id).unwrap().get_metadata("status");
+            assert_eq!(status, Some(&"from b".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_timestamps_follow_clock() {
+        let clock = Arc::new(ManualClock::new(1_000));
+        let mut store = DocumentStore::with_clock("r1", clock.clone());
+
+        let first = store.create_text("First");
+        clock.advance(10);
+        let second = store.create_text("Second");
+        clock.advance(10);
+        store.text_insert(&first, 0, "edited").unwrap();
+
+        let first_doc = store.get(&first).unwrap();
+        assert_eq!(
+            (first_doc.created_at, first_doc.modified_at),
+            (1_000, 1_020)
+        );
+        assert_eq!(store.get(&second).unwrap().created_at, 1_010);
+
+        let options = QueryOptions {
+            sort_by: Some(SortField::ModifiedAt),
+            sort_desc: true,
+            ..Default::default()
+        };
+        let titles: Vec<_> = store
+            .query(&options)
+            .iter()
+            .map(|d| d.title.as_str())
+            .collect();
+        assert_eq!(titles, vec!["First", "Second"]);
+    }
+
+    #[test]
+    fn test_same_clock_sequence_same_timestamps() {
+        fn run(replica: &str) -> Vec<(u64, u64)> {
+            let clock = Arc::new(ManualClock::new(5_000));
+            let mut store = DocumentStore::with_clock(replica, clock.clone());
+            let text = store.create_text("Notes");
+            clock.advance(7);
+            let json = store.create_json("Config");
+            clock.set(9_000);
+            store.text_insert(&text, 0, "hi").unwrap();
+            clock.advance(1);
+            store.json_set(&json, "k", JsonValue::Int(1)).unwrap();
+
+            [text, json]
+                .iter()
+                .map(|id| {
+                    let doc = store.get(id).unwrap();
+                    (doc.created_at, doc.modified_at)
+                })
+                .collect()
+        }
+
+        assert_eq!(run("r1"), run("r2"));
+        assert_eq!(run("r1"), vec![(5_000, 9_000), (5_007, 9_001)]);
+    }
+
     #[test]
     fn test_metadata() {
         let mut store = DocumentStore::new("r1");
@@ -950,4 +2813,486 @@ mod tests {
         assert_eq!(doc.get_metadata("author"), Some(&"Alice".to_string()));
         assert_eq!(doc.get_metadata("version"), Some(&"1.0".to_string()));
     }
+
+    #[test]
+    fn test_collections() {
+        let mut store = DocumentStore::new("r1");
+        let projects = store.create_collection("Projects", None).unwrap();
+        let alpha = store
+            .create_collection("Alpha", Some(projects.clone()))
+            .unwrap();
+        let doc = store.create_text("Spec");
+        store.move_document(&doc, Some(alpha.clone())).unwrap();
+
+        assert_eq!(
+            store.list_collection(None),
+            vec![CollectionEntry::Collection {
+                id: projects.clone(),
+                name: "Projects".to_string()
+            }]
+        );
+        assert_eq!(
+            store.list_collection(Some(&alpha)),
+            vec![CollectionEntry::Document {
+                id: doc.clone(),
+                title: "Spec".to_string()
+            }]
+        );
+
+        // Empty collections are representable
+        let empty = store.create_collection("Empty", None).unwrap();
+        assert!(store.list_collection(Some(&empty)).is_empty());
+        store.delete_collection(&empty).unwrap();
+
+        // Non-empty collections cannot be deleted
+        assert!(matches!(
+            store.delete_collection(&alpha),
+            Err(DbError::CollectionNotEmpty(_))
+        ));
+
+        // Cannot move a collection into its own subtree
+        assert!(matches!(
+            store.move_collection(&projects, Some(alpha.clone())),
+            Err(DbError::InvalidMove(_))
+        ));
+
+        let options = QueryOptions {
+            collection: Some(projects.clone()),
+            ..Default::default()
+        };
+        assert!(store.query(&options).is_empty());
+        let options = QueryOptions {
+            collection: Some(projects),
+            include_descendants: true,
+            ..Default::default()
+        };
+        assert_eq!(store.query(&options).len(), 1);
+    }
+
+    #[test]
+    fn test_concurrent_document_move_converges() {
+        let mut store1 = DocumentStore::new("r1");
+        let mut store2 = DocumentStore::new("r2");
+
+        let a = store1.create_collection("A", None).unwrap();
+        let b = store1.create_collection("B", None).unwrap();
+        let doc = store1.create_text("Doc");
+        store2.apply_changes(&store1.take_changes());
+
+        store1.move_document(&doc, Some(a)).unwrap();
+        store2.move_document(&doc, Some(b)).unwrap();
+
+        let changes1 = store1.take_changes();
+        let changes2 = store2.take_changes();
+        store1.apply_changes(&changes2);
+        store2.apply_changes(&changes1);
+
+        assert_eq!(
+            store1.document_collection(&doc),
+            store2.document_collection(&doc)
+        );
+        assert_eq!(store1.collection_tree(), store2.collection_tree());
+    }
+
+    #[test]
+    fn test_collection_rename_replicates() {
+        let mut store1 = DocumentStore::new("r1");
+        let mut store2 = DocumentStore::new("r2");
+
+        let col = store1.create_collection("Drafts", None).unwrap();
+        store2.apply_changes(&store1.take_changes());
+
+        store2.rename_collection(&col, "Final").unwrap();
+        store1.apply_changes(&store2.take_changes());
+
+        assert_eq!(store1.collection_name(&col), Some("Final"));
+        assert_eq!(store2.collection_name(&col), Some("Final"));
+    }
+
+    #[test]
+    fn test_collection_tree_after_partition_heal() {
+        let mut store1 = DocumentStore::new("r1");
+        let mut store2 = DocumentStore::new("r2");
+
+        let a = store1.create_collection("A", None).unwrap();
+        let b = store1.create_collection("B", None).unwrap();
+        let doc = store1.create_text("Doc");
+        store1.move_document(&doc, Some(a.clone())).unwrap();
+        store2.apply_changes(&store1.take_changes());
+
+        // Partition: concurrent moves form a cycle, plus independent edits
+        store1.move_collection(&a, Some(b.clone())).unwrap();
+        store2.move_collection(&b, Some(a.clone())).unwrap();
+        store1.create_collection("C", Some(a.clone())).unwrap();
+        let d = store2.create_collection("D", None).unwrap();
+        store2.move_document(&doc, Some(d)).unwrap();
+
+        // Heal
+        let changes1 = store1.take_changes();
+        let changes2 = store2.take_changes();
+        store1.apply_changes(&changes2);
+        store2.apply_changes(&changes1);
+
+        let tree = store1.collection_tree();
+        assert_eq!(tree, store2.collection_tree());
+        // The cycle is broken: exactly one of A and B is at the top level
+        let top: Vec<_> = tree.collections.iter().map(|c| c.name.as_str()).collect();
+        assert!(top == ["A", "D"] || top == ["B", "D"]);
+    }
+
+    #[test]
+    fn test_document_in_deleted_collection_cycle() {
+        let mut store1 = DocumentStore::new("r1");
+        let mut store2 = DocumentStore::new("r2");
+
+        let a = store1.create_collection("A", None).unwrap();
+        let b = store1.create_collection("B", None).unwrap();
+        let doc = store1.create_text("Doc");
+        store2.apply_changes(&store1.take_changes());
+
+        // Concurrent cross-moves leave A -> B and B -> A as raw parents
+        store1.move_collection(&a, Some(b.clone())).unwrap();
+        store2.move_collection(&b, Some(a.clone())).unwrap();
+        let changes1 = store1.take_changes();
+        let changes2 = store2.take_changes();
+        store1.apply_changes(&changes2);
+        store2.apply_changes(&changes1);
+
+        // Delete both while the other replica moves the document into A
+        let tree = store1.collection_tree();
+        let top = tree.collections[0].id.clone();
+        let child = tree.collections[0].children[0].id.clone();
+        store1.delete_collection(&child).unwrap();
+        store1.delete_collection(&top).unwrap();
+        store2.move_document(&doc, Some(a)).unwrap();
+        let changes1 = store1.take_changes();
+        let changes2 = store2.take_changes();
+        store1.apply_changes(&changes2);
+        store2.apply_changes(&changes1);
+
+        for store in [&store1, &store2] {
+            assert_eq!(store.document_collection(&doc), None);
+            assert_eq!(
+                store.list_collection(None),
+                vec![CollectionEntry::Document {
+                    id: doc.clone(),
+                    title: "Doc".to_string(),
+                }]
+            );
+            assert_eq!(store.collection_tree().documents, vec![doc.clone()]);
+        }
+    }
+
+    #[test]
+    fn test_fork_merge_all_equals_join() {
+        let mut store1 = DocumentStore::new("r1");
+        let mut store2 = DocumentStore::new("r2");
+        let doc = store1.create_text("Notes");
+        store1.text_insert(&doc, 0, "hello world").unwrap();
+
+        let branch = store1.fork(&doc).unwrap();
+        assert_eq!(store1.fork_source(&branch), Some(&doc));
+        assert_eq!(store1.text_content(&branch).unwrap(), "hello world");
+        store2.apply_changes(&store1.take_changes());
+        assert_eq!(store2.fork_source(&branch), Some(&doc));
+
+        // Edit both sides
+        store1.text_insert(&doc, 0, "Say: ").unwrap();
+        store1.text_delete(&branch, 0, 6).unwrap();
+        store1.text_insert(&branch, 5, "!").unwrap();
+
+        let expected = store1
+            .get(&doc)
+            .unwrap()
+            .value
+            .join(&store1.get(&branch).unwrap().value);
+        store1
+            .merge_branch(&branch, &doc, MergePolicy::All)
+            .unwrap();
+
+        assert_eq!(store1.get(&doc).unwrap().value, expected);
+        assert_eq!(store1.text_content(&doc).unwrap(), "Say: world!");
+
+        store2.apply_changes(&store1.take_changes());
+        assert_eq!(store2.text_content(&doc).unwrap(), "Say: world!");
+        assert_eq!(store2.text_content(&branch).unwrap(), "world!");
+    }
+
+    #[test]
+    fn test_branch_changes_text() {
+        let mut store = DocumentStore::new("r1");
+        let doc = store.create_text("Notes");
+        store.text_insert(&doc, 0, "hello world").unwrap();
+        let branch = store.fork(&doc).unwrap();
+
+        store.text_delete(&branch, 0, 6).unwrap();
+        store.text_insert(&branch, 5, "!").unwrap();
+        // Inserted and deleted again: not a change
+        store.text_insert(&branch, 0, "x").unwrap();
+        store.text_delete(&branch, 0, 1).unwrap();
+
+        let diff = store.branch_changes(&branch).unwrap();
+        assert_eq!(diff.source, doc);
+        assert_eq!(diff.changes.len(), 2);
+        assert!(matches!(
+            &diff.changes[0],
+            BranchChange::TextDelete { position: 0, text, ids } if text == "hello " && ids.len() == 6
+        ));
+        assert!(matches!(
+            &diff.changes[1],
+            BranchChange::TextInsert { position: 5, text, .. } if text == "!"
+        ));
+
+        assert!(matches!(
+            store.branch_changes(&doc),
+            Err(DbError::NotABranch(_))
+        ));
+    }
+
+    #[test]
+    fn test_merge_selected_rich_text_is_unsupported() {
+        let mut store = DocumentStore::new("r1");
+        let plan = store.create_rich_text("Plan");
+        store.rich_text_insert(&plan, 0, "Budget review").unwrap();
+        let branch = store.fork(&plan).unwrap();
+        store.rich_text_insert(&branch, 0, "Draft: ").unwrap();
+
+        assert!(matches!(
+            store.merge_branch(&branch, &plan, MergePolicy::Selected(vec![])),
+            Err(DbError::UnsupportedOperation(_))
+        ));
+
+        // A text branch cannot be picked into a rich text document either
+        let notes = store.create_text("Notes");
+        let notes_branch = store.fork(&notes).unwrap();
+        store.text_insert(&notes_branch, 0, "hello").unwrap();
+        let diff = store.branch_changes(&notes_branch).unwrap();
+        let refs = diff.refs().collect();
+        assert!(matches!(
+            store.merge_branch(&notes_branch, &plan, MergePolicy::Selected(refs)),
+            Err(DbError::UnsupportedOperation(_))
+        ));
+        let value = &store.get(&plan).unwrap().value;
+        assert_eq!(value.as_rich_text().unwrap().to_string(), "Budget review");
+    }
+
+    #[test]
+    fn test_merge_selected_skips_rejected_changes() {
+        let mut store1 = DocumentStore::new("r1");
+        let mut store2 = DocumentStore::new("r2");
+        let doc = store1.create_text("Notes");
+        store1.text_insert(&doc, 0, "hello world").unwrap();
+        let branch = store1.fork(&doc).unwrap();
+        store2.apply_changes(&store1.take_changes());
+
+        // The branch deletes "hello " and appends "!"; meanwhile the source
+        // is edited on the other replica
+        store1.text_delete(&branch, 0, 6).unwrap();
+        store1.text_insert(&branch, 5, "!").unwrap();
+        store2.text_insert(&doc, 0, "Say: ").unwrap();
+        let changes1 = store1.take_changes();
+        let changes2 = store2.take_changes();
+        store1.apply_changes(&changes2);
+        store2.apply_changes(&changes1);
+
+        // Take only the insert
+        let diff = store1.branch_changes(&branch).unwrap();
+        let insert = diff
+            .refs()
+            .find(|r| matches!(diff.get(*r), Some(BranchChange::TextInsert { .. })))
+            .unwrap();
+        store1
+            .merge_branch(&branch, &doc, MergePolicy::Selected(vec![insert]))
+            .unwrap();
+        assert_eq!(store1.text_content(&doc).unwrap(), "Say: hello world!");
+
+        // Keep syncing, with more edits on both documents
+        store2.text_insert(&doc, 0, ">").unwrap();
+        store2.text_insert(&branch, 0, "new ").unwrap();
+        for _ in 0..2 {
+            let changes1 = store1.take_changes();
+            let changes2 = store2.take_changes();
+            store1.apply_changes(&changes2);
+            store2.apply_changes(&changes1);
+        }
+
+        assert_eq!(store1.text_content(&doc).unwrap(), ">Say: hello world!");
+        assert_eq!(store2.text_content(&doc).unwrap(), ">Say: hello world!");
+        assert_eq!(store1.text_content(&branch).unwrap(), "new world!");
+        assert_eq!(store2.text_content(&branch).unwrap(), "new world!");
+    }
+
+    #[test]
+    fn test_merge_selected_json() {
+        let mut store = DocumentStore::new("r1");
+        let doc = store.create_json("Config");
+        store
+            .json_set(&doc, "name", JsonValue::String("Alice".to_string()))
+            .unwrap();
+        store.json_set(&doc, "age", JsonValue::Int(30)).unwrap();
+        let branch = store.fork(&doc).unwrap();
+
+        store.json_set(&branch, "age", JsonValue::Int(31)).unwrap();
+        store
+            .json_set(
+                &branch,
+                "address.city",
+                JsonValue::String("Paris".to_string()),
+            )
+            .unwrap();
+        store
+            .get_mut(&branch)
+            .unwrap()
+            .value
+            .as_json_mut()
+            .unwrap()
+            .delete(&JsonPath::parse("name"))
+            .unwrap();
+
+        let diff = store.branch_changes(&branch).unwrap();
+        assert_eq!(
+            diff.changes,
+            vec![
+                BranchChange::JsonSet {
+                    path: "address.city".to_string(),
+                    value: serde_json::json!("Paris"),
+                },
+                BranchChange::JsonSet {
+                    path: "age".to_string(),
+                    value: serde_json::json!(31),
+                },
+                BranchChange::JsonRemove {
+                    path: "name".to_string(),
+                },
+            ]
+        );
+
+        store
+            .merge_branch(&branch, &doc, MergePolicy::Selected(vec![ChangeRef(0)]))
+            .unwrap();
+        assert_eq!(
+            store.json_to_value(&doc).unwrap(),
+            serde_json::json!({"name": "Alice", "age": 30, "address": {"city": "Paris"}})
+        );
+    }
+
+    fn hit_docs(store: &DocumentStore, query: &str) -> Vec<DocumentId> {
+        store.search(query).into_iter().map(|h| h.doc).collect()
+    }
+
+    #[test]
+    fn test_search_follows_local_edits() {
+        let mut store = DocumentStore::new("r1");
+        let notes = store.create_text("Notes");
+        store.text_insert(&notes, 0, "Draft the budget").unwrap();
+        let plan = store.create_rich_text("Plan");
+        store
+            .rich_text_insert(&plan, 0, "Budget review on Friday")
+            .unwrap();
+        store.enable_search(SearchConfig::default());
+
+        let hits = store.search("budget");
+        assert_eq!(hits.len(), 2);
+        let hit = hits.iter().find(|h| h.doc == notes).unwrap();
+        assert_eq!(hit.offset, 10);
+        assert_eq!(hit.snippet, "Draft the budget");
+        assert_eq!(hit_docs(&store, "budget friday"), vec![plan.clone()]);
+
+        // Deleting "budget" removes the hit; typing it again restores it
+        store.text_delete(&notes, 10, 6).unwrap();
+        assert_eq!(hit_docs(&store, "budget"), vec![plan.clone()]);
+        store.text_insert(&notes, 0, "Budget: ").unwrap();
+        assert_eq!(store.search("budget").len(), 2);
+
+        store.delete(&plan);
+        assert_eq!(hit_docs(&store, "budget"), vec![notes.clone()]);
+        assert!(store.search("friday").is_empty());
+
+        store.disable_search();
+        assert!(store.search("budget").is_empty());
+    }
+
+    #[test]
+    fn test_search_follows_replicated_edits() {
+        let mut store1 = DocumentStore::new("r1");
+        let mut store2 = DocumentStore::new("r2");
+        store2.enable_search(SearchConfig::default());
+
+        let doc = store1.create_text("Notes");
+        store1.text_insert(&doc, 0, "quarterly budget").unwrap();
+        store2.apply_changes(&store1.take_changes());
+        assert_eq!(hit_docs(&store2, "budget"), vec![doc.clone()]);
+
+        store1.text_delete(&doc, 10, 6).unwrap();
+        store1.text_insert(&doc, 10, "forecast").unwrap();
+        store2.apply_changes(&store1.take_changes());
+        assert!(store2.search("budget").is_empty());
+        assert_eq!(store2.search("forecast")[0].offset, 10);
+
+        let branch = store1.fork(&doc).unwrap();
+        store1.text_insert(&branch, 0, "revised ").unwrap();
+        store1
+            .merge_branch(&branch, &doc, MergePolicy::All)
+            .unwrap();
+        store2.apply_changes(&store1.take_changes());
+        let mut both = vec![doc.clone(), branch.clone()];
+        both.sort();
+        assert_eq!(hit_docs(&store2, "revised"), both);
+
+        store2.apply_changes(&[StoreChange::Delete { id: branch }]);
+        assert_eq!(hit_docs(&store2, "revised"), vec![doc]);
+    }
+
+    #[test]
+    fn test_incremental_search_index_matches_rebuild() {
+        let words = ["budget", "Plan", "q3", "a", "review", "draft"];
+        let mut store1 = DocumentStore::new("r1");
+        let mut store2 = DocumentStore::new("r2");
+        store1.enable_search(SearchConfig::default());
+        store2.enable_search(SearchConfig::default());
+        let docs = [store1.create_text("a"), store1.create_rich_text("b")];
+
+        let mut x: u64 = 11;
+        let mut rand = |n: usize| {
+            x = x
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (x >> 33) as usize % n
+        };
+        for round in 0..300 {
+            let id = &docs[rand(docs.len())];
+            let len = store1
+                .get(id)
+                .and_then(|d| searchable_text(&d.value))
+                .unwrap()
+                .chars()
+                .count();
+            let pos = rand(len + 1);
+            match (&store1.get(id).unwrap().value, rand(3)) {
+                (CrdtValue::Text(_), 0) if len > 0 => store1
+                    .text_delete(id, pos.min(len - 1), rand(8) + 1)
+                    .unwrap(),
+                (CrdtValue::Text(_), _) => {
+                    let text = format!("{} ", words[rand(words.len())]);
+                    store1.text_insert(id, pos, &text).unwrap()
+                }
+                _ => {
+                    let text = format!("{}.", words[rand(words.len())]);
+                    store1.rich_text_insert(id, pos, &text).unwrap()
+                }
+            }
+            if round % 7 == 0 {
+                store2.apply_changes(&store1.take_changes());
+            }
+        }
+        store2.apply_changes(&store1.take_changes());
+
+        for store in [&mut store1, &mut store2] {
+            let incremental = store.search_index().unwrap().clone();
+            store.enable_search(SearchConfig::default());
+            assert_eq!(store.search_index().unwrap(), &incremental);
+        }
+        assert_eq!(store1.search("budget"), store2.search("budget"));
+        assert!(!store1.search("budget").is_empty());
+    }
 }
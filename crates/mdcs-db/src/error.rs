@@ -1,8 +1,12 @@
 //! Error types for the database layer.
 
+use std::sync::Arc;
 use thiserror::Error;
 
 /// Errors that can occur in database operations.
+///
+/// Each variant has a stable [`code`](DbError::code) for callers that need
+/// to tell errors apart without matching on their messages.
 #[derive(Error, Debug, Clone)]
 pub enum DbError {
     #[error("Document not found: {0}")]
@@ -23,17 +27,95 @@ pub enum DbError {
     #[error("Serialization error: {0}")]
     SerializationError(String),
 
+    /// Encoding or decoding JSON failed; the `serde_json` error is the
+    /// [`source`](std::error::Error::source).
+    #[error("Serialization error: {0}")]
+    Json(#[source] Arc<serde_json::Error>),
+
     #[error("Operation not supported: {0}")]
     UnsupportedOperation(String),
 
+    #[error("Collection not found: {0}")]
+    CollectionNotFound(String),
+
+    #[error("Collection not empty: {0}")]
+    CollectionNotEmpty(String),
+
+    #[error("Invalid move: {0}")]
+    InvalidMove(String),
+
+    #[error("Not a branch: {0}")]
+    NotABranch(String),
+
     #[error("Concurrent modification detected")]
     ConcurrentModification,
 }
 
+impl DbError {
+    /// A stable identifier for the kind of error, e.g.
+    /// `"DOCUMENT_NOT_FOUND"`.
+    ///
+    /// Codes don't change when messages are reworded.
+    /// [`SerializationError`](DbError::SerializationError) and
+    /// [`Json`](DbError::Json) share `"SERIALIZATION_ERROR"`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            DbError::DocumentNotFound(_) => "DOCUMENT_NOT_FOUND",
+            DbError::PathNotFound(_) => "PATH_NOT_FOUND",
+            DbError::TypeMismatch { .. } => "TYPE_MISMATCH",
+            DbError::IndexOutOfBounds { .. } => "INDEX_OUT_OF_BOUNDS",
+            DbError::InvalidPath(_) => "INVALID_PATH",
+            DbError::SerializationError(_) | DbError::Json(_) => "SERIALIZATION_ERROR",
+            DbError::UnsupportedOperation(_) => "UNSUPPORTED_OPERATION",
+            DbError::CollectionNotFound(_) => "COLLECTION_NOT_FOUND",
+            DbError::CollectionNotEmpty(_) => "COLLECTION_NOT_EMPTY",
+            DbError::InvalidMove(_) => "INVALID_MOVE",
+            DbError::NotABranch(_) => "NOT_A_BRANCH",
+            DbError::ConcurrentModification => "CONCURRENT_MODIFICATION",
+        }
+    }
+}
+
 impl From<serde_json::Error> for DbError {
     fn from(err: serde_json::Error) -> Self {
-        DbError::SerializationError(err.to_string())
+        DbError::Json(Arc::new(err))
     }
 }
 
 pub type Result<T> = std::result::Result<T, DbError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn test_codes_are_stable() {
+        assert_eq!(
+            DbError::DocumentNotFound("doc".into()).code(),
+            "DOCUMENT_NOT_FOUND"
+        );
+        let mismatch = DbError::TypeMismatch {
+            expected: "array".into(),
+            found: "map".into(),
+        };
+        assert_eq!(mismatch.code(), "TYPE_MISMATCH");
+        assert_eq!(
+            DbError::ConcurrentModification.code(),
+            "CONCURRENT_MODIFICATION"
+        );
+    }
+
+    #[test]
+    fn test_json_error_keeps_its_source() {
+        let json = serde_json::from_str::<u32>("nope").unwrap_err();
+        let message = json.to_string();
+        let err = DbError::from(json);
+        assert_eq!(err.code(), "SERIALIZATION_ERROR");
+        let source = err.source().expect("serde_json error as source");
+        assert_eq!(source.to_string(), message);
+        assert_eq!(err.to_string(), format!("Serialization error: {}", message));
+
+        assert!(DbError::SerializationError("bad".into()).source().is_none());
+    }
+}
@@ -0,0 +1,1061 @@
+//! HTML import for [`RichText`], with sanitization.
+//!
+//! HTML pasted from Word or Google Docs comes with thousands of styled
+//! spans, comments and conditional markup, and HTML from anywhere else may
+//! be hostile. [`sanitize_html`] reduces it to plain text plus the marks a
+//! [`SanitizePolicy`] allows before any of it reaches a CRDT:
+//!
+//! - `<script>`, `<style>`, `<head>`, embedded objects and similar elements
+//!   are dropped together with their content. Comments, doctypes and
+//!   conditional comments are skipped.
+//! - Formatting tags, and the inline styles Word and Google Docs use
+//!   instead of them, become marks. The innermost setting wins, so the
+//!   `<b style="font-weight:normal">` Google Docs wraps pastes in is not
+//!   bold. Every other tag is unwrapped and its text kept.
+//! - Block elements end a line when their [`BlockKind`] is allowed and are
+//!   joined with a space otherwise.
+//! - Links are kept only if their URL has a scheme on the allowlist.
+//!
+//! What was removed is counted in a [`SanitizeReport`], so a UI can tell
+//! the user.
+//!
+//! Tags map to marks as follows:
+//!
+//! | Tag                                    | Style                                | Mark kind                   |
+//! |----------------------------------------|--------------------------------------|-----------------------------|
+//! | `b`, `strong`                          | `font-weight: bold` / `600`–`900`    | [`MarkKind::Bold`]          |
+//! | `i`, `em`, `cite`, `dfn`, `var`        | `font-style: italic`                 | [`MarkKind::Italic`]        |
+//! | `u`, `ins`                             | `text-decoration: underline`         | [`MarkKind::Underline`]     |
+//! | `s`, `strike`, `del`                   | `text-decoration: line-through`      | [`MarkKind::Strikethrough`] |
+//! | `code`, `kbd`, `samp`, `tt`            |                                      | [`MarkKind::Code`]          |
+//! | `a href`                               |                                      | [`MarkKind::Link`]          |
+//! | `mark`                                 | `background-color`                   | [`MarkKind::Highlight`]     |
+//!
+//! # Example
+//!
+//! ```rust
+//! use mdcs_db::{MarkType, RichText, SanitizePolicy};
+//!
+//! let html = r#"<p>Hello <b>world</b><script>alert(1)</script></p>
+//!               <p><a href="javascript:alert(2)">click</a></p>"#;
+//! let (doc, report) = RichText::from_html_sanitized("r1", html, &SanitizePolicy::default());
+//!
+//! assert_eq!(doc.text_content(), "Hello world\nclick");
+//! assert!(doc.has_mark(6, &MarkType::Bold));
+//! assert_eq!(report.removed_tags["script"], 1);
+//! assert_eq!(report.removed_attrs["href"], 1);
+//! ```
+
+use crate::rich_text::{MarkType, RichText};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Default [`SanitizePolicy::max_length`], in characters.
+pub const DEFAULT_MAX_PASTE_LENGTH: usize = 100_000;
+
+/// Elements dropped together with their content.
+const DROPPED: &[&str] = &[
+    "applet", "audio", "button", "canvas", "embed", "frame", "frameset", "head", "iframe", "math",
+    "noembed", "noframes", "noscript", "object", "script", "select", "style", "svg", "template",
+    "textarea", "title", "video", "xml",
+];
+
+/// Elements whose content is raw text rather than markup.
+const RAW_TEXT: &[&str] = &[
+    "iframe", "noembed", "noframes", "noscript", "script", "style", "textarea", "title", "xmp",
+];
+
+/// Elements that never have content or an end tag.
+const VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Wrappers unwrapped without being reported.
+const STRUCTURAL: &[&str] = &["body", "html"];
+
+/// The kinds of mark HTML import produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum MarkKind {
+    Bold,
+    Italic,
+    Underline,
+    Strikethrough,
+    Code,
+    Link,
+    Highlight,
+}
+
+impl MarkKind {
+    /// The kind of a mark type, if HTML import can produce it.
+    pub fn of(mark_type: &MarkType) -> Option<Self> {
+        match mark_type {
+            MarkType::Bold => Some(Self::Bold),
+            MarkType::Italic => Some(Self::Italic),
+            MarkType::Underline => Some(Self::Underline),
+            MarkType::Strikethrough => Some(Self::Strikethrough),
+            MarkType::Code => Some(Self::Code),
+            MarkType::Link { .. } => Some(Self::Link),
+            MarkType::Highlight { .. } => Some(Self::Highlight),
+            MarkType::Comment { .. } | MarkType::Custom { .. } => None,
+        }
+    }
+}
+
+/// Block structure HTML import can keep, as line breaks.
+///
+/// An allowed block becomes a line of its own; import doesn't set its
+/// [`BlockType`](crate::rich_text::BlockType).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum BlockKind {
+    /// `p`, `div`, tables, lists and other generic blocks.
+    Paragraph,
+    /// `h1` to `h6`.
+    Heading,
+    /// `li`, `dt` and `dd`.
+    ListItem,
+    /// `blockquote`.
+    Quote,
+    /// `pre`, whose whitespace is kept as-is.
+    Preformatted,
+    /// `br`.
+    LineBreak,
+}
+
+impl BlockKind {
+    fn of(tag: &str) -> Option<Self> {
+        match tag {
+            "p" | "div" | "section" | "article" | "aside" | "header" | "footer" | "main"
+            | "nav" | "address" | "figure" | "figcaption" | "table" | "thead" | "tbody"
+            | "tfoot" | "tr" | "caption" | "ul" | "ol" | "dl" | "hr" | "center" | "fieldset" => {
+                Some(Self::Paragraph)
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => Some(Self::Heading),
+            "li" | "dt" | "dd" => Some(Self::ListItem),
+            "blockquote" => Some(Self::Quote),
+            "pre" | "listing" | "xmp" => Some(Self::Preformatted),
+            "br" => Some(Self::LineBreak),
+            _ => None,
+        }
+    }
+}
+
+/// What HTML import keeps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SanitizePolicy {
+    /// Marks to keep; other formatting is dropped and its text kept.
+    pub allowed_marks: BTreeSet<MarkKind>,
+    /// Blocks that end a line; the others are joined with a space.
+    pub allowed_block_types: BTreeSet<BlockKind>,
+    /// Most characters to import; the rest is cut off.
+    pub max_length: Option<usize>,
+    /// Collapse runs of whitespace to one space, as browsers render them.
+    pub collapse_whitespace: bool,
+    /// Drop marks that only cover whitespace.
+    pub strip_empty_marks: bool,
+    /// URL schemes links may use, compared case-insensitively. URLs
+    /// without a scheme are dropped too.
+    pub url_scheme_allowlist: Vec<String>,
+}
+
+impl Default for SanitizePolicy {
+    /// Basic formatting, all blocks, web and mail links, and at most
+    /// [`DEFAULT_MAX_PASTE_LENGTH`] characters.
+    fn default() -> Self {
+        Self {
+            allowed_marks: BTreeSet::from([
+                MarkKind::Bold,
+                MarkKind::Italic,
+                MarkKind::Underline,
+                MarkKind::Strikethrough,
+                MarkKind::Code,
+                MarkKind::Link,
+            ]),
+            allowed_block_types: BTreeSet::from([
+                BlockKind::Paragraph,
+                BlockKind::Heading,
+                BlockKind::ListItem,
+                BlockKind::Quote,
+                BlockKind::Preformatted,
+                BlockKind::LineBreak,
+            ]),
+            max_length: Some(DEFAULT_MAX_PASTE_LENGTH),
+            collapse_whitespace: true,
+            strip_empty_marks: true,
+            url_scheme_allowlist: vec!["http".into(), "https".into(), "mailto".into()],
+        }
+    }
+}
+
+/// What sanitization removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SanitizeReport {
+    /// Tags dropped with their content or unwrapped, by name, with how
+    /// many of each. Comments are counted as `!--`.
+    pub removed_tags: BTreeMap<String, usize>,
+    /// Attributes dropped, by name, with how many of each.
+    pub removed_attrs: BTreeMap<String, usize>,
+    /// Whether the text was cut off at [`SanitizePolicy::max_length`].
+    pub truncated: bool,
+}
+
+impl SanitizeReport {
+    /// Whether nothing was removed.
+    pub fn is_empty(&self) -> bool {
+        self.removed_tags.is_empty() && self.removed_attrs.is_empty() && !self.truncated
+    }
+
+    fn remove_tag(&mut self, name: &str) {
+        *self.removed_tags.entry(name.to_string()).or_default() += 1;
+    }
+
+    fn remove_attr(&mut self, name: &str) {
+        *self.removed_attrs.entry(name.to_string()).or_default() += 1;
+    }
+}
+
+/// Sanitized HTML, ready to insert into rich text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HtmlFragment {
+    /// The plain text.
+    pub text: String,
+    /// `(start, end, mark)` spans in characters of `text`, by start.
+    pub marks: Vec<(usize, usize, MarkType)>,
+}
+
+/// Reduce HTML to text and the marks `policy` allows.
+///
+/// Never fails: malformed markup is read the way a lenient browser would,
+/// and anything not understood is dropped.
+pub fn sanitize_html(html: &str, policy: &SanitizePolicy) -> (HtmlFragment, SanitizeReport) {
+    let mut sanitizer = Sanitizer::new(policy);
+    let mut tokens = Tokenizer::new(html);
+    while let Some(token) = tokens.next_token() {
+        sanitizer.token(token);
+    }
+    sanitizer.finish()
+}
+
+impl RichText {
+    /// Import HTML with the default [`SanitizePolicy`].
+    pub fn from_html(replica_id: impl Into<String>, html: &str) -> Self {
+        Self::from_html_sanitized(replica_id, html, &SanitizePolicy::default()).0
+    }
+
+    /// Import HTML, keeping what `policy` allows.
+    pub fn from_html_sanitized(
+        replica_id: impl Into<String>,
+        html: &str,
+        policy: &SanitizePolicy,
+    ) -> (Self, SanitizeReport) {
+        let (fragment, report) = sanitize_html(html, policy);
+        let mut rich = RichText::new(replica_id);
+        rich.insert_fragment(0, &fragment);
+        (rich, report)
+    }
+
+    /// Insert HTML at `position`, keeping what `policy` allows.
+    pub fn paste_html(
+        &mut self,
+        position: usize,
+        html: &str,
+        policy: &SanitizePolicy,
+    ) -> SanitizeReport {
+        let (fragment, report) = sanitize_html(html, policy);
+        self.insert_fragment(position, &fragment);
+        report
+    }
+
+    /// Insert sanitized HTML at `position`.
+    pub fn insert_fragment(&mut self, position: usize, fragment: &HtmlFragment) {
+        if fragment.text.is_empty() {
+            return;
+        }
+        let position = position.min(self.len());
+        self.insert(position, &fragment.text);
+        for (start, end, mark_type) in &fragment.marks {
+            self.add_mark(position + start, position + end, mark_type.clone());
+        }
+    }
+}
+
+// ============================================================================
+// Tokenizer
+// ============================================================================
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    /// Text with its character references still encoded.
+    Text(&'a str),
+    Start {
+        name: String,
+        attrs: Vec<(String, String)>,
+    },
+    End {
+        name: String,
+    },
+    Comment,
+    /// A doctype, processing instruction or conditional comment marker.
+    Declaration,
+}
+
+/// Splits HTML into tokens, the way a browser would for well-formed input
+/// and without ever failing on malformed input.
+struct Tokenizer<'a> {
+    html: &'a str,
+    pos: usize,
+    /// The raw text element whose content comes next.
+    raw_text: Option<String>,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(html: &'a str) -> Self {
+        Self {
+            html,
+            pos: 0,
+            raw_text: None,
+        }
+    }
+
+    fn next_token(&mut self) -> Option<Token<'a>> {
+        let rest = &self.html[self.pos..];
+        if rest.is_empty() {
+            return None;
+        }
+        if let Some(name) = self.raw_text.take() {
+            let end = find_ignore_case(rest, &format!("</{}", name)).unwrap_or(rest.len());
+            if end > 0 {
+                self.pos += end;
+                return Some(Token::Text(&rest[..end]));
+            }
+        }
+
+        let bytes = rest.as_bytes();
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment.find("-->").map_or(rest.len(), |i| i + 7);
+            self.pos += end;
+            return Some(Token::Comment);
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            self.pos += rest.find('>').map_or(rest.len(), |i| i + 1);
+            return Some(Token::Declaration);
+        }
+        if bytes.len() > 2 && bytes[0] == b'<' && bytes[1] == b'/' && bytes[2].is_ascii_alphabetic()
+        {
+            let (name, len) = tag_name(&rest[2..]);
+            let end = rest[2 + len..]
+                .find('>')
+                .map_or(rest.len(), |i| 2 + len + i + 1);
+            self.pos += end;
+            return Some(Token::End { name });
+        }
+        if bytes.len() > 1 && bytes[0] == b'<' && bytes[1].is_ascii_alphabetic() {
+            return Some(self.start_tag());
+        }
+
+        // Text runs to the next `<`, which may turn out not to start a tag
+        let first = rest.chars().next().map_or(1, char::len_utf8);
+        let end = rest[first..].find('<').map_or(rest.len(), |i| i + first);
+        self.pos += end;
+        Some(Token::Text(&rest[..end]))
+    }
+
+    fn start_tag(&mut self) -> Token<'a> {
+        let rest = &self.html[self.pos + 1..];
+        let bytes = rest.as_bytes();
+        let (name, mut i) = tag_name(rest);
+        let mut attrs = Vec::new();
+
+        loop {
+            while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+                i += 1;
+            }
+            if i >= bytes.len() {
+                break;
+            }
+            if bytes[i] == b'>' {
+                i += 1;
+                break;
+            }
+
+            let start = i;
+            while i < bytes.len() && !matches!(bytes[i], b'=' | b'>' | b'/') {
+                if bytes[i].is_ascii_whitespace() {
+                    break;
+                }
+                i += 1;
+            }
+            if i == start {
+                // A stray `=`
+                i += 1;
+                continue;
+            }
+            let attr = rest[start..i].to_ascii_lowercase();
+
+            let mut j = i;
+            while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+                j += 1;
+            }
+            if j >= bytes.len() || bytes[j] != b'=' {
+                attrs.push((attr, String::new()));
+                continue;
+            }
+            j += 1;
+            while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+                j += 1;
+            }
+            let value = match bytes.get(j) {
+                Some(&quote) if quote == b'"' || quote == b'\'' => {
+                    let end = rest[j + 1..]
+                        .find(quote as char)
+                        .map_or(rest.len(), |k| j + 1 + k);
+                    let value = &rest[j + 1..end];
+                    i = (end + 1).min(rest.len());
+                    value
+                }
+                _ => {
+                    let end = rest[j..]
+                        .find(|c: char| c.is_ascii_whitespace() || c == '>')
+                        .map_or(rest.len(), |k| j + k);
+                    i = end;
+                    &rest[j..end]
+                }
+            };
+            attrs.push((attr, decode_entities(value).into_owned()));
+        }
+
+        self.pos += 1 + i;
+        if RAW_TEXT.contains(&name.as_str()) {
+            self.raw_text = Some(name.clone());
+        }
+        Token::Start { name, attrs }
+    }
+}
+
+/// A lowercased tag name and its length in bytes.
+fn tag_name(s: &str) -> (String, usize) {
+    let len = s
+        .find(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>')
+        .unwrap_or(s.len());
+    (s[..len].to_ascii_lowercase(), len)
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    let needle = needle.as_bytes();
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle))
+}
+
+/// Decode character references.
+///
+/// Numeric references and the named ones pasted content commonly uses are
+/// decoded; anything else is left as written.
+fn decode_entities(s: &str) -> Cow<'_, str> {
+    if !s.contains('&') {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        match decode_entity(rest) {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+/// Decode the reference at the start of `s`, returning the character and
+/// the reference's length in bytes.
+fn decode_entity(s: &str) -> Option<(char, usize)> {
+    let semi = 1 + s.as_bytes()[1..].iter().take(32).position(|&b| b == b';')?;
+    let body = &s[1..semi];
+    let c = if let Some(number) = body.strip_prefix('#') {
+        let (digits, radix) = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => (hex, 16),
+            None => (number, 10),
+        };
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+            return None;
+        }
+        // Out of range, including overflow, decodes as U+FFFD
+        u32::from_str_radix(digits, radix)
+            .ok()
+            .filter(|&code| code != 0)
+            .and_then(char::from_u32)
+            .unwrap_or(char::REPLACEMENT_CHARACTER)
+    } else {
+        match body {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            "nbsp" => '\u{a0}',
+            "shy" => '\u{ad}',
+            "ndash" => '\u{2013}',
+            "mdash" => '\u{2014}',
+            "lsquo" => '\u{2018}',
+            "rsquo" => '\u{2019}',
+            "ldquo" => '\u{201c}',
+            "rdquo" => '\u{201d}',
+            "bull" => '\u{2022}',
+            "middot" => '\u{b7}',
+            "hellip" => '\u{2026}',
+            "copy" => '\u{a9}',
+            "reg" => '\u{ae}',
+            "trade" => '\u{2122}',
+            "euro" => '\u{20ac}',
+            _ => return None,
+        }
+    };
+    Some((c, semi + 1))
+}
+
+// ============================================================================
+// Sanitizer
+// ============================================================================
+
+/// Formatting an element sets: a mark, or `None` to turn a kind off.
+type Format = Vec<(MarkKind, Option<MarkType>)>;
+
+/// An open element.
+#[derive(Debug)]
+struct Element {
+    name: String,
+    /// The allowed block it starts, if any.
+    block: Option<BlockKind>,
+    /// The marks in effect inside it: for each kind, the innermost setting.
+    marks: BTreeMap<MarkKind, MarkType>,
+    /// Whether it is inside an allowed `pre`.
+    preformatted: bool,
+}
+
+struct Sanitizer<'p> {
+    policy: &'p SanitizePolicy,
+    report: SanitizeReport,
+    out: Output,
+    stack: Vec<Element>,
+    /// The element being dropped, and how deeply it is nested in itself.
+    dropping: Option<(String, usize)>,
+}
+
+impl<'p> Sanitizer<'p> {
+    fn new(policy: &'p SanitizePolicy) -> Self {
+        Self {
+            policy,
+            report: SanitizeReport::default(),
+            out: Output::new(policy.max_length),
+            stack: Vec::new(),
+            dropping: None,
+        }
+    }
+
+    fn token(&mut self, token: Token<'_>) {
+        if let Some((dropped, depth)) = &mut self.dropping {
+            match &token {
+                Token::Start { name, .. } if name == dropped => *depth += 1,
+                Token::End { name } if name == dropped => {
+                    *depth -= 1;
+                    if *depth == 0 {
+                        self.dropping = None;
+                    }
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        match token {
+            Token::Text(text) => self.text(&decode_entities(text)),
+            Token::Start { name, attrs } => self.start(name, attrs),
+            Token::End { name } => self.end(&name),
+            Token::Comment => self.report.remove_tag("!--"),
+            Token::Declaration => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        let (marks, preformatted) = match self.stack.last() {
+            Some(parent) => (
+                parent.marks.values().cloned().collect(),
+                parent.preformatted,
+            ),
+            None => (Vec::new(), false),
+        };
+        for c in text.chars() {
+            if c.is_ascii_whitespace() && self.policy.collapse_whitespace && !preformatted {
+                self.out.space();
+            } else {
+                self.out.push(c, &marks);
+            }
+        }
+    }
+
+    fn start(&mut self, name: String, attrs: Vec<(String, String)>) {
+        if DROPPED.contains(&name.as_str()) {
+            self.report.remove_tag(&name);
+            if !VOID.contains(&name.as_str()) {
+                self.dropping = Some((name, 1));
+            }
+            return;
+        }
+
+        let block = BlockKind::of(&name);
+        let allowed_block = block.filter(|b| self.policy.allowed_block_types.contains(b));
+        let mut format = Format::new();
+        let mut consumed = BTreeSet::new();
+
+        if let Some(kind) = tag_kind(&name) {
+            match kind {
+                MarkKind::Link => {
+                    let href = attrs.iter().find(|(attr, _)| attr == "href");
+                    let url = href.and_then(|(_, url)| allowed_url(url, self.policy));
+                    if let Some(url) = url.filter(|_| self.allows(kind)) {
+                        format.push((kind, Some(MarkType::Link { url })));
+                        consumed.insert("href");
+                    }
+                }
+                MarkKind::Highlight => format.push((
+                    kind,
+                    Some(MarkType::Highlight {
+                        color: "yellow".to_string(),
+                    }),
+                )),
+                _ => format.push((kind, Some(simple_mark(kind)))),
+            }
+        }
+        if let Some((_, style)) = attrs.iter().find(|(attr, _)| attr == "style") {
+            let styled: Format = style_format(style)
+                .into_iter()
+                .filter(|(kind, _)| self.allows(*kind))
+                .collect();
+            if !styled.is_empty() {
+                consumed.insert("style");
+            }
+            for (kind, mark) in styled {
+                format.retain(|(k, _)| *k != kind);
+                format.push((kind, mark));
+            }
+        }
+        format.retain(|(kind, _)| self.allows(*kind));
+
+        for (attr, _) in &attrs {
+            if !consumed.contains(attr.as_str()) {
+                self.report.remove_attr(attr);
+            }
+        }
+        let kept = allowed_block.is_some()
+            || format
+                .iter()
+                .any(|(kind, mark)| mark.is_some() && tag_kind(&name) == Some(*kind));
+        if !kept && !STRUCTURAL.contains(&name.as_str()) {
+            self.report.remove_tag(&name);
+        }
+
+        match (block, allowed_block) {
+            (Some(BlockKind::LineBreak), Some(_)) => self.out.line_break(),
+            (Some(_), Some(_)) => self.out.block_break(),
+            (Some(_), None) => self.out.space(),
+            (None, _) => {}
+        }
+        if !VOID.contains(&name.as_str()) {
+            let (mut marks, preformatted) = match self.stack.last() {
+                Some(parent) => (parent.marks.clone(), parent.preformatted),
+                None => (BTreeMap::new(), false),
+            };
+            for (kind, mark) in format {
+                match mark {
+                    Some(mark) => marks.insert(kind, mark),
+                    None => marks.remove(&kind),
+                };
+            }
+            self.stack.push(Element {
+                name,
+                block: allowed_block,
+                marks,
+                preformatted: preformatted || allowed_block == Some(BlockKind::Preformatted),
+            });
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        // Stray end tags are ignored; misnested ones close everything
+        // opened since, as browsers mostly do
+        let Some(index) = self.stack.iter().rposition(|e| e.name == name) else {
+            if name == "br" {
+                // `</br>` is read as `<br>`
+                self.start(name.to_string(), Vec::new());
+            }
+            return;
+        };
+        for element in self.stack.drain(index..).rev() {
+            match element.block {
+                Some(_) => self.out.block_break(),
+                None if BlockKind::of(&element.name).is_some() => self.out.space(),
+                None => {}
+            }
+        }
+    }
+
+    fn allows(&self, kind: MarkKind) -> bool {
+        self.policy.allowed_marks.contains(&kind)
+    }
+
+    fn finish(self) -> (HtmlFragment, SanitizeReport) {
+        let mut report = self.report;
+        report.truncated = self.out.truncated;
+        (self.out.finish(self.policy.strip_empty_marks), report)
+    }
+}
+
+fn tag_kind(tag: &str) -> Option<MarkKind> {
+    match tag {
+        "b" | "strong" => Some(MarkKind::Bold),
+        "i" | "em" | "cite" | "dfn" | "var" => Some(MarkKind::Italic),
+        "u" | "ins" => Some(MarkKind::Underline),
+        "s" | "strike" | "del" => Some(MarkKind::Strikethrough),
+        "code" | "kbd" | "samp" | "tt" => Some(MarkKind::Code),
+        "a" => Some(MarkKind::Link),
+        "mark" => Some(MarkKind::Highlight),
+        _ => None,
+    }
+}
+
+fn simple_mark(kind: MarkKind) -> MarkType {
+    match kind {
+        MarkKind::Bold => MarkType::Bold,
+        MarkKind::Italic => MarkType::Italic,
+        MarkKind::Underline => MarkType::Underline,
+        MarkKind::Strikethrough => MarkType::Strikethrough,
+        _ => MarkType::Code,
+    }
+}
+
+/// The formatting an inline `style` attribute sets.
+fn style_format(style: &str) -> Format {
+    let mut format = Format::new();
+    for declaration in style.split(';') {
+        let Some((property, value)) = declaration.split_once(':') else {
+            continue;
+        };
+        let property = property.trim().to_ascii_lowercase();
+        let value = value.to_ascii_lowercase();
+        let value = value.trim().trim_end_matches("!important").trim();
+
+        match property.as_str() {
+            "font-weight" => {
+                let bold = match value {
+                    "bold" | "bolder" => true,
+                    "normal" | "lighter" => false,
+                    _ => match value.parse::<u32>() {
+                        Ok(weight) => weight >= 600,
+                        Err(_) => continue,
+                    },
+                };
+                format.push((MarkKind::Bold, bold.then_some(MarkType::Bold)));
+            }
+            "font-style" => {
+                let italic = value.starts_with("italic") || value.starts_with("oblique");
+                format.push((MarkKind::Italic, italic.then_some(MarkType::Italic)));
+            }
+            "text-decoration" | "text-decoration-line" => {
+                let underline = value.contains("underline");
+                let strike = value.contains("line-through");
+                if underline || value.starts_with("none") {
+                    format.push((
+                        MarkKind::Underline,
+                        underline.then_some(MarkType::Underline),
+                    ));
+                }
+                if strike || value.starts_with("none") {
+                    format.push((
+                        MarkKind::Strikethrough,
+                        strike.then_some(MarkType::Strikethrough),
+                    ));
+                }
+            }
+            "background-color" | "background" => {
+                let color = safe_color(value).map(|color| MarkType::Highlight { color });
+                format.push((MarkKind::Highlight, color));
+            }
+            _ => {}
+        }
+    }
+    format
+}
+
+/// A color that can be written into a style attribute, unless it paints
+/// nothing.
+fn safe_color(value: &str) -> Option<String> {
+    let safe = !value.is_empty()
+        && value.len() <= 32
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "#(),.% ".contains(c));
+    let invisible = matches!(
+        value,
+        "transparent" | "none" | "inherit" | "initial" | "unset" | "white" | "#fff" | "#ffffff"
+    ) || value.starts_with("rgba(0, 0, 0, 0)")
+        || value.starts_with("rgba(0,0,0,0)");
+    (safe && !invisible).then(|| value.to_string())
+}
+
+/// The URL to link to, if its scheme is allowed.
+fn allowed_url(raw: &str, policy: &SanitizePolicy) -> Option<String> {
+    // Browsers drop tabs and newlines anywhere in a URL and trim spaces and
+    // control characters around it
+    let url: String = raw
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .collect();
+    let url = url.trim_matches(|c: char| c <= ' ');
+    let (scheme, _) = url.split_once(':')?;
+    let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    let allowed = policy
+        .url_scheme_allowlist
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(scheme));
+    (valid && allowed).then(|| url.to_string())
+}
+
+// ============================================================================
+// Output
+// ============================================================================
+
+/// Whitespace owed before the next character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pending {
+    None,
+    Space,
+    /// Newlines, for block boundaries and `<br>`s
+    Breaks(usize),
+}
+
+/// Text and mark spans as they are written.
+struct Output {
+    text: String,
+    len: usize,
+    max_length: usize,
+    truncated: bool,
+    /// Dropped if nothing follows, so trailing breaks and spaces never make
+    /// it into the text.
+    pending: Pending,
+    open: Vec<(MarkType, usize)>,
+    spans: Vec<(usize, usize, MarkType)>,
+}
+
+impl Output {
+    fn new(max_length: Option<usize>) -> Self {
+        Self {
+            text: String::new(),
+            len: 0,
+            max_length: max_length.unwrap_or(usize::MAX),
+            truncated: false,
+            pending: Pending::None,
+            open: Vec::new(),
+            spans: Vec::new(),
+        }
+    }
+
+    fn last_is_break(&self) -> bool {
+        self.len == 0 || self.text.ends_with('\n')
+    }
+
+    fn space(&mut self) {
+        if self.pending == Pending::None && !self.last_is_break() && !self.text.ends_with(' ') {
+            self.pending = Pending::Space;
+        }
+    }
+
+    fn block_break(&mut self) {
+        if !self.last_is_break() && !matches!(self.pending, Pending::Breaks(_)) {
+            self.pending = Pending::Breaks(1);
+        }
+    }
+
+    /// A `<br>` ends the current line, or adds an empty one after a block.
+    fn line_break(&mut self) {
+        if self.len == 0 {
+            return;
+        }
+        self.pending = match self.pending {
+            Pending::Breaks(n) => Pending::Breaks(n + 1),
+            _ => Pending::Breaks(1),
+        };
+    }
+
+    /// Write a character with `marks`, after any whitespace owed.
+    ///
+    /// Owed whitespace only carries the marks on both sides of it.
+    fn push(&mut self, c: char, marks: &[MarkType]) {
+        if self.truncated {
+            return;
+        }
+        let owed = match std::mem::replace(&mut self.pending, Pending::None) {
+            Pending::None => String::new(),
+            Pending::Space => " ".to_string(),
+            Pending::Breaks(n) => "\n".repeat(n),
+        };
+        if self.len + owed.len() + 1 > self.max_length {
+            self.truncated = true;
+            return;
+        }
+
+        let mut i = 0;
+        while i < self.open.len() {
+            if marks.contains(&self.open[i].0) {
+                i += 1;
+            } else {
+                let (mark, start) = self.open.remove(i);
+                self.spans.push((start, self.len, mark));
+            }
+        }
+        self.text.push_str(&owed);
+        self.len += owed.len();
+        for mark in marks {
+            if !self.open.iter().any(|(open, _)| open == mark) {
+                self.open.push((mark.clone(), self.len));
+            }
+        }
+        self.text.push(c);
+        self.len += 1;
+    }
+
+    fn finish(mut self, strip_empty_marks: bool) -> HtmlFragment {
+        for (mark, start) in std::mem::take(&mut self.open) {
+            self.spans.push((start, self.len, mark));
+        }
+        let chars: Vec<char> = self.text.chars().collect();
+        let mut marks: Vec<_> = self
+            .spans
+            .into_iter()
+            .filter(|(start, end, _)| {
+                start < end
+                    && !(strip_empty_marks && chars[*start..*end].iter().all(|c| c.is_whitespace()))
+            })
+            .collect();
+        marks.sort_by_key(|(start, end, _)| (*start, *end));
+        HtmlFragment {
+            text: self.text,
+            marks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sanitize(html: &str) -> (HtmlFragment, SanitizeReport) {
+        sanitize_html(html, &SanitizePolicy::default())
+    }
+
+    #[test]
+    fn test_tokenizer() {
+        let mut tokens = Tokenizer::new(r#"a<B class=x id='y' hidden>b</b ><!-- c -->1 < 2"#);
+        assert_eq!(tokens.next_token(), Some(Token::Text("a")));
+        assert_eq!(
+            tokens.next_token(),
+            Some(Token::Start {
+                name: "b".into(),
+                attrs: vec![
+                    ("class".into(), "x".into()),
+                    ("id".into(), "y".into()),
+                    ("hidden".into(), String::new()),
+                ],
+            })
+        );
+        assert_eq!(tokens.next_token(), Some(Token::Text("b")));
+        assert_eq!(tokens.next_token(), Some(Token::End { name: "b".into() }));
+        assert_eq!(tokens.next_token(), Some(Token::Comment));
+        assert_eq!(tokens.next_token(), Some(Token::Text("1 ")));
+        assert_eq!(tokens.next_token(), Some(Token::Text("< 2")));
+        assert_eq!(tokens.next_token(), None);
+    }
+
+    #[test]
+    fn test_entities() {
+        assert_eq!(
+            decode_entities("a &amp; b &lt;&#x41;&#66;&nbsp;&bogus; &#0; &"),
+            "a & b <AB\u{a0}&bogus; \u{fffd} &"
+        );
+    }
+
+    #[test]
+    fn test_marks_and_blocks() {
+        let (fragment, report) =
+            sanitize("<h1>Title</h1><p>Some <b>bold <i>and</i></b>   <u>more</u></p>");
+        assert_eq!(fragment.text, "Title\nSome bold and more");
+        assert_eq!(
+            fragment.marks,
+            vec![
+                (11, 19, MarkType::Bold),
+                (16, 19, MarkType::Italic),
+                (20, 24, MarkType::Underline),
+            ]
+        );
+        assert!(report.is_empty());
+    }
+
+    #[test]
+    fn test_innermost_style_wins() {
+        let (fragment, report) = sanitize(
+            r#"<b style="font-weight:normal;" id="docs-internal-guid-1"><span style="font-weight:700">A</span>B</b>"#,
+        );
+        assert_eq!(fragment.text, "AB");
+        assert_eq!(fragment.marks, vec![(0, 1, MarkType::Bold)]);
+        assert_eq!(report.removed_tags["span"], 1);
+        assert_eq!(report.removed_attrs["id"], 1);
+    }
+
+    #[test]
+    fn test_url_allowlist() {
+        let policy = SanitizePolicy::default();
+        assert_eq!(
+            allowed_url(" https://example.com/a:b ", &policy).as_deref(),
+            Some("https://example.com/a:b")
+        );
+        assert_eq!(
+            allowed_url("MAILTO:a@b.c", &policy).as_deref(),
+            Some("MAILTO:a@b.c")
+        );
+        for url in [
+            "javascript:alert(1)",
+            "java\tscript:alert(1)",
+            " JaVaScRiPt:alert(1)",
+            "\u{1}javascript:alert(1)",
+            "data:text/html,x",
+            "/relative/path",
+            "javascript%3Aalert(1)",
+        ] {
+            assert_eq!(allowed_url(url, &policy), None, "{:?}", url);
+        }
+    }
+
+    #[test]
+    fn test_paste_at_position() {
+        let mut doc = RichText::new("r1");
+        doc.insert(0, "Hello world");
+        let report = doc.paste_html(6, "<b>big</b>&nbsp;", &SanitizePolicy::default());
+        assert!(report.is_empty());
+        assert_eq!(doc.text_content(), "Hello big\u{a0}world");
+        assert!(doc.has_mark(7, &MarkType::Bold));
+        assert!(!doc.has_mark(9, &MarkType::Bold));
+    }
+}
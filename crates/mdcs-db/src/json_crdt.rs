@@ -5,12 +5,16 @@
 //! - Path-based operations
 //! - Conflict-free concurrent edits
 //! - Multi-value registers for concurrent writes
+//! - Counters whose concurrent increments add up
+//! - Garbage collection of replaced objects and arrays
 //!
 //! Uses a shared causal context for correct semantics.
 
 use crate::error::DbError;
-use crate::rga_list::{RGAList, RGAListDelta};
+use crate::rga_list::{ListId, RGAList, RGAListDelta};
+use mdcs_compaction::VersionVector;
 use mdcs_core::lattice::Lattice;
+use mdcs_core::pncounter::PNCounter;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use ulid::Ulid;
@@ -90,6 +94,11 @@ impl JsonPath {
         new.push(PathSegment::Index(index));
         new
     }
+
+    /// Check if this path is `prefix` or nested under it.
+    pub fn starts_with(&self, prefix: &JsonPath) -> bool {
+        self.0.starts_with(&prefix.0)
+    }
 }
 
 impl std::fmt::Display for JsonPath {
@@ -135,6 +144,9 @@ pub enum JsonValue {
     Array(ArrayId),
     /// Object reference (points to an ObjectMap).
     Object(ObjectId),
+    /// Counter reference (points to a PN-counter); see
+    /// [`JsonCrdt::increment`].
+    Counter(CounterId),
 }
 
 impl JsonValue {
@@ -169,6 +181,39 @@ impl JsonValue {
             _ => None,
         }
     }
+
+    /// The type of this value, or `None` for null (an absent key).
+    pub fn json_type(&self) -> Option<JsonType> {
+        match self {
+            JsonValue::Null => None,
+            JsonValue::Bool(_) => Some(JsonType::Bool),
+            JsonValue::Int(_) => Some(JsonType::Int),
+            JsonValue::Float(_) => Some(JsonType::Float),
+            JsonValue::String(_) => Some(JsonType::String),
+            JsonValue::Array(_) => Some(JsonType::Array),
+            JsonValue::Object(_) => Some(JsonType::Object),
+            JsonValue::Counter(_) => Some(JsonType::Counter),
+        }
+    }
+}
+
+/// The type of a value in the document, as reported by [`JsonCrdt::type_at`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum JsonType {
+    /// Boolean value.
+    Bool,
+    /// Integer value.
+    Int,
+    /// Floating point value.
+    Float,
+    /// String value.
+    String,
+    /// Array.
+    Array,
+    /// Object.
+    Object,
+    /// Counter.
+    Counter,
 }
 
 /// Unique identifier for an array in the document.
@@ -207,6 +252,28 @@ impl Default for ObjectId {
     }
 }
 
+/// Identifier for a counter in the document.
+///
+/// Derived from the field the counter was started in and the write it
+/// replaced there, so replicas that start a counter in the same field from
+/// the same value share it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CounterId {
+    object: ObjectId,
+    key: String,
+    replaces: Option<ValueId>,
+}
+
+impl CounterId {
+    fn new(object: &ObjectId, key: &str, replaces: Option<&ValueId>) -> Self {
+        Self {
+            object: object.clone(),
+            key: key.to_string(),
+            replaces: replaces.cloned(),
+        }
+    }
+}
+
 /// A unique identifier for a field value (for multi-value tracking).
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ValueId {
@@ -227,6 +294,7 @@ impl ValueId {
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 struct ObjectField {
     /// All concurrent values for this field (multi-value register).
+    #[serde(with = "crate::serde_map")]
     values: HashMap<ValueId, JsonValue>,
     /// Deleted value IDs (tombstones).
     deleted: HashSet<ValueId>,
@@ -241,6 +309,16 @@ impl ObjectField {
     }
 
     fn set(&mut self, id: ValueId, value: JsonValue) {
+        // A late delta must not undo a newer write from the same replica,
+        // nor bring back a value that was deleted
+        if self.deleted.contains(&id)
+            || self
+                .values
+                .keys()
+                .any(|k| k.replica == id.replica && k.seq > id.seq)
+        {
+            return;
+        }
         // Setting a new value obsoletes previous values from this replica
         let to_delete: Vec<_> = self
             .values
@@ -260,11 +338,15 @@ impl ObjectField {
     }
 
     fn get_winner(&self) -> Option<&JsonValue> {
-        // Return the value with the highest ValueId (LWW semantics)
+        self.winner().map(|(_, v)| v)
+    }
+
+    fn winner(&self) -> Option<(&ValueId, &JsonValue)> {
+        // Return the value with the highest ValueId (LWW semantics): the
+        // higher seq wins, ties go to the greater replica ID
         self.values
             .iter()
             .max_by(|(a, _), (b, _)| a.seq.cmp(&b.seq).then_with(|| a.replica.cmp(&b.replica)))
-            .map(|(_, v)| v)
     }
 
     fn is_deleted(&self) -> bool {
@@ -363,7 +445,6 @@ impl JsonArray {
         }
     }
 
-    #[allow(dead_code)]
     fn get(&self, index: usize) -> Option<&JsonValue> {
         self.list.get(index)
     }
@@ -384,6 +465,10 @@ impl JsonArray {
         self.list.push_back(value);
     }
 
+    fn move_item(&mut self, from: usize, to: usize) -> bool {
+        self.list.move_item(from, to)
+    }
+
     fn iter(&self) -> impl Iterator<Item = &JsonValue> + '_ {
         self.list.iter()
     }
@@ -393,6 +478,36 @@ impl JsonArray {
     }
 }
 
+/// A counter in the JSON document.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct JsonCounter {
+    /// Value of the integer the counter replaced, 0 if none.
+    base: i64,
+    /// Increments and decrements by replica.
+    counts: PNCounter<String>,
+    /// Sequence number of each replica's latest update.
+    seen: VersionVector,
+}
+
+impl JsonCounter {
+    fn new(base: i64) -> Self {
+        Self {
+            base,
+            counts: PNCounter::new(),
+            seen: VersionVector::new(),
+        }
+    }
+
+    fn value(&self) -> i64 {
+        self.base.saturating_add(self.counts.value())
+    }
+
+    fn merge(&mut self, counts: &PNCounter<String>, seen: &VersionVector) {
+        self.counts.join_assign(counts);
+        self.seen.merge(seen);
+    }
+}
+
 /// Delta for JSON CRDT operations.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct JsonCrdtDelta {
@@ -404,6 +519,9 @@ pub struct JsonCrdtDelta {
     pub new_objects: Vec<ObjectId>,
     /// New arrays created.
     pub new_arrays: Vec<ArrayId>,
+    /// Counters updated.
+    #[serde(default)]
+    pub counter_changes: Vec<CounterChange>,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -420,6 +538,15 @@ pub struct ArrayChange {
     pub delta: RGAListDelta<JsonValue>,
 }
 
+/// The state of a counter, joined into the receiver's copy.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CounterChange {
+    pub counter_id: CounterId,
+    pub base: i64,
+    pub counts: PNCounter<String>,
+    pub seen: VersionVector,
+}
+
 impl JsonCrdtDelta {
     pub fn new() -> Self {
         Self {
@@ -427,6 +554,7 @@ impl JsonCrdtDelta {
             array_changes: Vec::new(),
             new_objects: Vec::new(),
             new_arrays: Vec::new(),
+            counter_changes: Vec::new(),
         }
     }
 
@@ -435,6 +563,22 @@ impl JsonCrdtDelta {
             && self.array_changes.is_empty()
             && self.new_objects.is_empty()
             && self.new_arrays.is_empty()
+            && self.counter_changes.is_empty()
+    }
+
+    /// Record a counter's state, replacing an older one of the same counter.
+    fn record_counter(&mut self, change: CounterChange) {
+        match self
+            .counter_changes
+            .iter_mut()
+            .find(|c| c.counter_id == change.counter_id)
+        {
+            Some(existing) => {
+                existing.counts.join_assign(&change.counts);
+                existing.seen.merge(&change.seen);
+            }
+            None => self.counter_changes.push(change),
+        }
     }
 }
 
@@ -444,6 +588,66 @@ impl Default for JsonCrdtDelta {
     }
 }
 
+/// Deltas compose by union: applying `a.join(&b)` has the same effect as
+/// applying `a` then `b`.
+impl Lattice for JsonCrdtDelta {
+    fn bottom() -> Self {
+        Self::new()
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        let mut joined = self.clone();
+        for id in &other.new_objects {
+            if !joined.new_objects.contains(id) {
+                joined.new_objects.push(id.clone());
+            }
+        }
+        for id in &other.new_arrays {
+            if !joined.new_arrays.contains(id) {
+                joined.new_arrays.push(id.clone());
+            }
+        }
+        let changed: HashSet<&ValueId> = self.object_changes.iter().map(|c| &c.value_id).collect();
+        joined.object_changes.extend(
+            other
+                .object_changes
+                .iter()
+                .filter(|c| !changed.contains(&c.value_id))
+                .cloned(),
+        );
+        // One change per array, so every insert lands before any delete
+        // that targets it
+        let mut array_changes: Vec<ArrayChange> = Vec::new();
+        for change in self.array_changes.iter().chain(&other.array_changes) {
+            match array_changes
+                .iter_mut()
+                .find(|c| c.array_id == change.array_id)
+            {
+                Some(existing) => existing.delta = existing.delta.join(&change.delta),
+                None => array_changes.push(change.clone()),
+            }
+        }
+        joined.array_changes = array_changes;
+        for change in &other.counter_changes {
+            joined.record_counter(change.clone());
+        }
+        joined
+    }
+}
+
+/// What a [`JsonCrdt::gc`] run removed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GcStats {
+    /// Objects removed.
+    pub objects_removed: usize,
+    /// Arrays removed.
+    pub arrays_removed: usize,
+    /// Counters removed.
+    pub counters_removed: usize,
+    /// Serialized size of what was removed.
+    pub bytes_reclaimed: usize,
+}
+
 /// Collaborative JSON document CRDT.
 ///
 /// Provides Automerge-like semantics for editing nested
@@ -460,9 +664,21 @@ pub struct JsonCrdt {
     objects: HashMap<ObjectId, JsonObject>,
     /// All arrays in the document.
     arrays: HashMap<ArrayId, JsonArray>,
+    /// All counters in the document.
+    #[serde(default, with = "crate::serde_map")]
+    counters: HashMap<CounterId, JsonCounter>,
     /// Pending delta.
     #[serde(skip)]
     pending_delta: Option<JsonCrdtDelta>,
+    /// Ratio of dead to live containers that triggers a collection.
+    #[serde(skip)]
+    gc_threshold: Option<f64>,
+    /// Containers left by the last collection.
+    #[serde(skip)]
+    live_after_gc: usize,
+    /// Containers created since the last collection, kept by the next one.
+    #[serde(skip)]
+    fresh: Vec<JsonValue>,
 }
 
 impl JsonCrdt {
@@ -481,15 +697,87 @@ impl JsonCrdt {
             root_id,
             objects,
             arrays: HashMap::new(),
+            counters: HashMap::new(),
             pending_delta: None,
+            gc_threshold: None,
+            live_after_gc: 1,
+            fresh: Vec::new(),
         }
     }
 
+    /// Collect garbage automatically once dead objects and arrays
+    /// outnumber live ones by `ratio`.
+    ///
+    /// Checked after local writes, applied deltas and joins. Everything
+    /// stored since the last collection counts as dead until the next one
+    /// finds out, so a document that only grows is collected each time it
+    /// grows by `ratio`, which keeps the cost linear.
+    pub fn with_gc_threshold(mut self, ratio: f64) -> Self {
+        self.gc_threshold = Some(ratio);
+        self
+    }
+
+    /// The automatic collection threshold, if any.
+    pub fn gc_threshold(&self) -> Option<f64> {
+        self.gc_threshold
+    }
+
     /// Get the replica ID.
     pub fn replica_id(&self) -> &str {
         &self.replica_id
     }
 
+    /// Copy this document for a new replica.
+    ///
+    /// Objects and arrays keep their IDs, so the copy still joins with the
+    /// original; new values and array elements are stamped with `replica_id`.
+    pub fn fork(&self, replica_id: impl Into<String>) -> Self {
+        let replica_id = replica_id.into();
+        let arrays = self
+            .arrays
+            .iter()
+            .map(|(id, arr)| {
+                let arr = JsonArray {
+                    id: arr.id.clone(),
+                    list: arr.list.fork(&replica_id),
+                };
+                (id.clone(), arr)
+            })
+            .collect();
+        Self {
+            replica_id,
+            seq: self.seq,
+            root_id: self.root_id.clone(),
+            objects: self.objects.clone(),
+            arrays,
+            counters: self.counters.clone(),
+            pending_delta: None,
+            gc_threshold: self.gc_threshold,
+            live_after_gc: self.live_after_gc,
+            fresh: self.fresh.clone(),
+        }
+    }
+
+    /// Highest sequence number seen from each replica.
+    ///
+    /// Derived from the IDs of field values, deletions included, of array
+    /// elements, and of counter updates.
+    pub fn version_vector(&self) -> VersionVector {
+        let mut vv = VersionVector::new();
+        for field in self.objects.values().flat_map(|obj| obj.fields.values()) {
+            for id in field.values.keys().chain(&field.deleted) {
+                vv.observe(&id.replica, id.seq);
+            }
+        }
+        for array in self.arrays.values() {
+            vv.merge(&array.list.version_vector());
+        }
+        for counter in self.counters.values() {
+            vv.merge(&counter.seen);
+        }
+        vv
+    }
+
     /// Generate a new value ID.
     fn next_value_id(&mut self) -> ValueId {
         self.seq += 1;
@@ -497,47 +785,54 @@ impl JsonCrdt {
     }
 
     /// Get a value at a path.
+    ///
+    /// Keys look up object fields and indexes look up array elements, so
+    /// `items.1.name` reads the `name` field of the second element of
+    /// `items`. Returns `None` for the root path, for a missing key, for an
+    /// index past the end of its array, and for a segment that doesn't fit
+    /// its container.
     pub fn get(&self, path: &JsonPath) -> Option<&JsonValue> {
-        let mut current_obj_id = &self.root_id;
-        let segments = path.segments();
-
-        for (i, segment) in segments.iter().enumerate() {
-            let is_last = i == segments.len() - 1;
+        self.lookup(path).ok().flatten()
+    }
 
-            match segment {
-                PathSegment::Key(key) => {
-                    let obj = self.objects.get(current_obj_id)?;
-                    let value = obj.get(key)?;
+    /// Set a value at a path.
+    ///
+    /// Missing parents, and parents that are not objects, are replaced with
+    /// new objects. Each replaced parent is an ordinary write to its own key,
+    /// so a concurrent write to that key (say, a scalar) is resolved like any
+    /// other conflict: the value with the higher `(seq, replica)` wins on
+    /// every replica, and the losing subtree is unreachable from the root.
+    /// Use [`set_strict`](Self::set_strict) to refuse instead.
+    pub fn set(&mut self, path: &JsonPath, value: JsonValue) -> Result<(), DbError> {
+        self.set_at(path, value, true)
+    }
 
-                    if is_last {
-                        return Some(value);
-                    }
+    /// Set a value at a path whose parent must already be an object.
+    ///
+    /// Fails with [`DbError::PathNotFound`] rather than creating or replacing
+    /// the parent.
+    ///
+    /// With either method the parent path may go through array elements;
+    /// an index past the end of its array fails with
+    /// [`DbError::IndexOutOfBounds`] rather than creating anything.
+    pub fn set_strict(&mut self, path: &JsonPath, value: JsonValue) -> Result<(), DbError> {
+        self.set_at(path, value, false)
+    }
 
-                    match value {
-                        JsonValue::Object(id) => current_obj_id = id,
-                        JsonValue::Array(_) if !is_last => {
-                            // Next segment should be an index
-                            continue;
-                        }
-                        _ => return None,
-                    }
-                }
-                PathSegment::Index(_idx) => {
-                    // Need to be at an array
-                    let _obj = self.objects.get(current_obj_id)?;
-                    // Find the array value
-                    // This is a simplification; in practice we'd track which field is the array
-                    return None; // Simplified - would need array traversal
-                }
-            }
+    /// Get the type of the value at a path, or `None` if nothing is there.
+    pub fn type_at(&self, path: &JsonPath) -> Option<JsonType> {
+        if path.is_root() {
+            return Some(JsonType::Object);
         }
-
-        // Root path returns None - use to_json() instead
-        None
+        self.get(path)?.json_type()
     }
 
-    /// Set a value at a path.
-    pub fn set(&mut self, path: &JsonPath, value: JsonValue) -> Result<(), DbError> {
+    fn set_at(
+        &mut self,
+        path: &JsonPath,
+        value: JsonValue,
+        create_parents: bool,
+    ) -> Result<(), DbError> {
         if path.is_root() {
             return Err(DbError::InvalidPath("Cannot set root".to_string()));
         }
@@ -548,7 +843,12 @@ impl JsonCrdt {
             .ok_or_else(|| DbError::InvalidPath("Empty path".to_string()))?;
 
         // Ensure parent exists and is an object
-        let parent_obj_id = self.ensure_object_at(&parent_path)?;
+        let parent_obj_id = if create_parents {
+            self.ensure_object_at(&parent_path)?
+        } else {
+            self.get_object_id_at(&parent_path)?
+                .ok_or_else(|| DbError::PathNotFound(parent_path.to_string()))?
+        };
 
         let value_id = self.next_value_id();
 
@@ -580,10 +880,14 @@ impl JsonCrdt {
             }
         }
 
+        self.maybe_gc();
         Ok(())
     }
 
     /// Delete a value at a path.
+    ///
+    /// Like [`set`](Self::set), the parent path may go through array
+    /// elements.
     pub fn delete(&mut self, path: &JsonPath) -> Result<(), DbError> {
         if path.is_root() {
             return Err(DbError::InvalidPath("Cannot delete root".to_string()));
@@ -595,7 +899,7 @@ impl JsonCrdt {
             .ok_or_else(|| DbError::InvalidPath("Empty path".to_string()))?;
 
         let parent_obj_id = self
-            .get_object_id_at(&parent_path)
+            .get_object_id_at(&parent_path)?
             .ok_or_else(|| DbError::PathNotFound(parent_path.to_string()))?;
 
         let value_id = self.next_value_id();
@@ -622,6 +926,7 @@ impl JsonCrdt {
             }
         }
 
+        self.maybe_gc();
         Ok(())
     }
 
@@ -630,6 +935,7 @@ impl JsonCrdt {
         let id = ObjectId::new();
         let obj = JsonObject::new(id.clone());
         self.objects.insert(id.clone(), obj);
+        self.fresh.push(JsonValue::Object(id.clone()));
 
         let delta = self.pending_delta.get_or_insert_with(JsonCrdtDelta::new);
         delta.new_objects.push(id.clone());
@@ -642,6 +948,7 @@ impl JsonCrdt {
         let id = ArrayId::new();
         let arr = JsonArray::new(id.clone(), &self.replica_id);
         self.arrays.insert(id.clone(), arr);
+        self.fresh.push(JsonValue::Array(id.clone()));
 
         let delta = self.pending_delta.get_or_insert_with(JsonCrdtDelta::new);
         delta.new_arrays.push(id.clone());
@@ -744,11 +1051,181 @@ impl JsonCrdt {
         Ok(value)
     }
 
+    /// Move the element at `from` so that it ends up at `to`.
+    ///
+    /// The element keeps its identity: concurrent moves of it settle on one
+    /// position instead of duplicating it (see [`RGAList::move_item`]).
+    pub fn array_move(
+        &mut self,
+        array_id: &ArrayId,
+        from: usize,
+        to: usize,
+    ) -> Result<(), DbError> {
+        let arr = self
+            .arrays
+            .get_mut(array_id)
+            .ok_or_else(|| DbError::PathNotFound(format!("Array {:?}", array_id)))?;
+
+        let arr_len = arr.len();
+        if to >= arr_len || !arr.move_item(from, to) {
+            return Err(DbError::IndexOutOfBounds {
+                index: if from >= arr_len { from } else { to },
+                length: arr_len,
+            });
+        }
+
+        if let Some(delta) = arr.list.take_delta() {
+            let doc_delta = self.pending_delta.get_or_insert_with(JsonCrdtDelta::new);
+            doc_delta.array_changes.push(ArrayChange {
+                array_id: array_id.clone(),
+                delta,
+            });
+        }
+
+        Ok(())
+    }
+
     /// Get array length.
     pub fn array_len(&self, array_id: &ArrayId) -> Option<usize> {
         self.arrays.get(array_id).map(|a| a.len())
     }
 
+    /// Get the stable ID of the element at `index` in an array.
+    pub fn array_element_id(&self, array_id: &ArrayId, index: usize) -> Option<ListId> {
+        self.get_array(array_id)?.list.id_at_index(index)
+    }
+
+    /// Get the current index of an array element, or `None` if it was deleted.
+    pub fn array_element_index(&self, array_id: &ArrayId, id: &ListId) -> Option<usize> {
+        self.get_array(array_id)?.list.index_of_id(id)
+    }
+
+    /// Add `amount` to the counter at a path.
+    ///
+    /// Unlike setting `count + 1`, concurrent increments all count: each
+    /// replica adds to its own entry of a PN-counter. A path with nothing
+    /// at it, or a deleted value, gets a counter starting at 0, and a path
+    /// holding an [`Int`](JsonValue::Int) gets one starting at that value;
+    /// any other value fails with [`DbError::TypeMismatch`]. Replicas that
+    /// start a counter concurrently over the same value share it. Missing
+    /// parents are created as with [`set`](Self::set), so replicas that
+    /// create the same parent concurrently each get their own and only one
+    /// survives; create shared parents on one replica first.
+    ///
+    /// The field holding the counter is an ordinary field, so a plain value
+    /// set there is resolved against the write that started the counter
+    /// like any other conflict (type-tagged last-writer-wins): if the plain
+    /// value wins, the counter is gone, along with increments made to it
+    /// concurrently, and the next increment starts a new counter from it.
+    pub fn increment(&mut self, path: &JsonPath, amount: u64) -> Result<(), DbError> {
+        self.update_counter(path, |counts, replica| counts.increment(replica, amount))
+    }
+
+    /// Subtract `amount` from the counter at a path.
+    ///
+    /// See [`increment`](Self::increment).
+    pub fn decrement(&mut self, path: &JsonPath, amount: u64) -> Result<(), DbError> {
+        self.update_counter(path, |counts, replica| counts.decrement(replica, amount))
+    }
+
+    /// Get the value of a counter by ID.
+    pub fn counter_value(&self, counter_id: &CounterId) -> Option<i64> {
+        self.counters.get(counter_id).map(JsonCounter::value)
+    }
+
+    fn update_counter(
+        &mut self,
+        path: &JsonPath,
+        update: impl FnOnce(&mut PNCounter<String>, String),
+    ) -> Result<(), DbError> {
+        let Some(PathSegment::Key(key)) = path.last() else {
+            return Err(DbError::InvalidPath(format!(
+                "Counters are object fields: {}",
+                path
+            )));
+        };
+        let parent_path = path.parent().unwrap_or(JsonPath::root());
+        let parent_obj_id = self.ensure_object_at(&parent_path)?;
+
+        let current = self
+            .objects
+            .get(&parent_obj_id)
+            .and_then(|obj| obj.fields.get(key))
+            .and_then(ObjectField::winner);
+        let (counter_id, base, started) = match current {
+            Some((_, JsonValue::Counter(id))) if self.counters.contains_key(id) => {
+                (id.clone(), 0, false)
+            }
+            None => (CounterId::new(&parent_obj_id, key, None), 0, true),
+            Some((id, JsonValue::Null)) => (CounterId::new(&parent_obj_id, key, Some(id)), 0, true),
+            Some((id, JsonValue::Int(n))) => {
+                (CounterId::new(&parent_obj_id, key, Some(id)), *n, true)
+            }
+            Some((_, other)) => {
+                return Err(DbError::TypeMismatch {
+                    expected: "Counter".to_string(),
+                    found: format!("{:?}", other.json_type()),
+                })
+            }
+        };
+
+        let value_id = self.next_value_id();
+        let counter = self
+            .counters
+            .entry(counter_id.clone())
+            .or_insert_with(|| JsonCounter::new(base));
+        update(&mut counter.counts, self.replica_id.clone());
+        counter.seen.observe(&self.replica_id, value_id.seq);
+        let change = CounterChange {
+            counter_id: counter_id.clone(),
+            base: counter.base,
+            counts: counter.counts.clone(),
+            seen: counter.seen.clone(),
+        };
+
+        let delta = self.pending_delta.get_or_insert_with(JsonCrdtDelta::new);
+        delta.record_counter(change);
+        if started {
+            let value = JsonValue::Counter(counter_id);
+            delta.object_changes.push(ObjectChange {
+                object_id: parent_obj_id.clone(),
+                key: key.clone(),
+                value_id: value_id.clone(),
+                value: value.clone(),
+            });
+            if let Some(obj) = self.objects.get_mut(&parent_obj_id) {
+                obj.set(key.clone(), value_id, value);
+            }
+        }
+
+        self.maybe_gc();
+        Ok(())
+    }
+
+    /// Find the path of an array, or `None` if it is no longer reachable
+    /// from the root.
+    pub fn array_path(&self, array_id: &ArrayId) -> Option<JsonPath> {
+        let root = JsonValue::Object(self.root_id.clone());
+        self.find_array(&root, JsonPath::root(), array_id)
+    }
+
+    fn find_array(&self, value: &JsonValue, path: JsonPath, target: &ArrayId) -> Option<JsonPath> {
+        match value {
+            JsonValue::Array(id) if id == target => Some(path),
+            JsonValue::Array(id) => self
+                .get_array(id)?
+                .iter()
+                .enumerate()
+                .find_map(|(i, v)| self.find_array(v, path.child_index(i), target)),
+            JsonValue::Object(id) => {
+                let obj = self.objects.get(id)?;
+                obj.keys()
+                    .find_map(|k| self.find_array(obj.get(k)?, path.child_key(k), target))
+            }
+            _ => None,
+        }
+    }
+
     /// Get all keys in the root object.
     pub fn keys(&self) -> Vec<String> {
         self.objects
@@ -767,15 +1244,56 @@ impl JsonCrdt {
 
     // === Helper Methods ===
 
-    fn get_object_id_at(&self, path: &JsonPath) -> Option<ObjectId> {
+    /// Resolve a path, failing only on an index past the end of an array.
+    fn lookup(&self, path: &JsonPath) -> Result<Option<&JsonValue>, DbError> {
+        let root = JsonValue::Object(self.root_id.clone());
+        let Some((first, rest)) = path.segments().split_first() else {
+            // Root path returns None - use to_json() instead
+            return Ok(None);
+        };
+        let Some(mut value) = self.child(&root, first)? else {
+            return Ok(None);
+        };
+        for segment in rest {
+            match self.child(value, segment)? {
+                Some(child) => value = child,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(value))
+    }
+
+    /// The value under `segment` in `parent`.
+    fn child(
+        &self,
+        parent: &JsonValue,
+        segment: &PathSegment,
+    ) -> Result<Option<&JsonValue>, DbError> {
+        match (parent, segment) {
+            (JsonValue::Object(id), PathSegment::Key(key)) => {
+                Ok(self.objects.get(id).and_then(|obj| obj.get(key)))
+            }
+            (JsonValue::Array(id), PathSegment::Index(index)) => {
+                let Some(arr) = self.arrays.get(id) else {
+                    return Ok(None);
+                };
+                arr.get(*index).map(Some).ok_or(DbError::IndexOutOfBounds {
+                    index: *index,
+                    length: arr.len(),
+                })
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn get_object_id_at(&self, path: &JsonPath) -> Result<Option<ObjectId>, DbError> {
         if path.is_root() {
-            return Some(self.root_id.clone());
+            return Ok(Some(self.root_id.clone()));
         }
 
-        let value = self.get(path)?;
-        match value {
-            JsonValue::Object(id) => Some(id.clone()),
-            _ => None,
+        match self.lookup(path)? {
+            Some(JsonValue::Object(id)) => Ok(Some(id.clone())),
+            _ => Ok(None),
         }
     }
 
@@ -785,7 +1303,7 @@ impl JsonCrdt {
         }
 
         // Try to get existing
-        if let Some(id) = self.get_object_id_at(path) {
+        if let Some(id) = self.get_object_id_at(path)? {
             return Ok(id);
         }
 
@@ -804,20 +1322,27 @@ impl JsonCrdt {
     pub fn apply_delta(&mut self, delta: &JsonCrdtDelta) {
         // Create new objects
         for obj_id in &delta.new_objects {
-            self.objects
-                .entry(obj_id.clone())
-                .or_insert_with(|| JsonObject::new(obj_id.clone()));
+            if !self.objects.contains_key(obj_id) {
+                self.objects
+                    .insert(obj_id.clone(), JsonObject::new(obj_id.clone()));
+                self.fresh.push(JsonValue::Object(obj_id.clone()));
+            }
         }
 
         // Create new arrays
         for arr_id in &delta.new_arrays {
-            self.arrays
-                .entry(arr_id.clone())
-                .or_insert_with(|| JsonArray::new(arr_id.clone(), &self.replica_id));
+            if !self.arrays.contains_key(arr_id) {
+                self.arrays.insert(
+                    arr_id.clone(),
+                    JsonArray::new(arr_id.clone(), &self.replica_id),
+                );
+                self.fresh.push(JsonValue::Array(arr_id.clone()));
+            }
         }
 
         // Apply object changes
         for change in &delta.object_changes {
+            self.seq = self.seq.max(change.value_id.seq);
             if let Some(obj) = self.objects.get_mut(&change.object_id) {
                 obj.set(
                     change.key.clone(),
@@ -833,6 +1358,141 @@ impl JsonCrdt {
                 arr.list.apply_delta(&change.delta);
             }
         }
+
+        // Apply counter changes
+        for change in &delta.counter_changes {
+            if let Some(seq) = change.seen.iter().map(|(_, seq)| *seq).max() {
+                self.seq = self.seq.max(seq);
+            }
+            self.counters
+                .entry(change.counter_id.clone())
+                .or_insert_with(|| JsonCounter::new(change.base))
+                .merge(&change.counts, &change.seen);
+        }
+
+        self.maybe_gc();
+    }
+
+    // === Garbage Collection ===
+
+    /// Number of objects and arrays stored, reachable or not.
+    pub fn container_count(&self) -> usize {
+        self.objects.len() + self.arrays.len()
+    }
+
+    /// Remove the objects and arrays that are no longer reachable from the
+    /// root.
+    ///
+    /// A container is reachable if the winning value of a field of a
+    /// reachable object, or an element of a reachable array, refers to it.
+    /// Values that lost don't count: a field's winner only ever moves
+    /// forward, because each way a value leaves a field (a newer write from
+    /// the same replica, or a delete, which tombstones what it saw and
+    /// writes a newer null) leaves a value that beats it, and a tombstoned
+    /// value never comes back. Containers created since the previous
+    /// collection, locally or by a delta, are kept: one made with
+    /// [`create_object`](Self::create_object) has until the next
+    /// collection to be attached.
+    ///
+    /// Collection is local and needs no coordination. A join with a replica
+    /// that hasn't collected, or an old delta, can bring a collected
+    /// container back, but only losing values refer to it: it never shows
+    /// up in [`to_json`](Self::to_json) and the next collection removes it
+    /// again. Referring to a container again after it was detached (moving
+    /// it) is not supported; the move reads as `null` once the container
+    /// has been collected.
+    pub fn gc(&mut self) -> GcStats {
+        let (objects, arrays, counters, live) = self.reachable();
+        self.fresh.clear();
+        let mut stats = GcStats::default();
+        self.objects.retain(|id, obj| {
+            let keep = objects.contains(id);
+            if !keep {
+                stats.objects_removed += 1;
+                stats.bytes_reclaimed += serialized_size(obj);
+            }
+            keep
+        });
+        self.arrays.retain(|id, arr| {
+            let keep = arrays.contains(id);
+            if !keep {
+                stats.arrays_removed += 1;
+                stats.bytes_reclaimed += serialized_size(arr);
+            }
+            keep
+        });
+        self.counters.retain(|id, counter| {
+            let keep = counters.contains(id);
+            if !keep {
+                stats.counters_removed += 1;
+                stats.bytes_reclaimed += serialized_size(counter);
+            }
+            keep
+        });
+        self.live_after_gc = live;
+        stats
+    }
+
+    fn maybe_gc(&mut self) {
+        let Some(ratio) = self.gc_threshold else {
+            return;
+        };
+        let live = self.live_after_gc.max(1);
+        let dead = self.container_count().saturating_sub(live);
+        if dead as f64 > ratio * live as f64 {
+            self.gc();
+        }
+    }
+
+    /// Containers and counters reachable from the root or from a fresh
+    /// container, and how many containers are reachable from the root
+    /// alone.
+    #[allow(clippy::type_complexity)]
+    fn reachable(
+        &self,
+    ) -> (
+        HashSet<ObjectId>,
+        HashSet<ArrayId>,
+        HashSet<CounterId>,
+        usize,
+    ) {
+        let root = JsonValue::Object(self.root_id.clone());
+        let mut objects = HashSet::new();
+        let mut arrays = HashSet::new();
+        let mut counters = HashSet::new();
+        let mut live = None;
+        let mut stack = vec![&root];
+        loop {
+            let Some(value) = stack.pop() else {
+                if live.is_some() {
+                    break;
+                }
+                live = Some(objects.len() + arrays.len());
+                stack.extend(&self.fresh);
+                continue;
+            };
+            match value {
+                JsonValue::Object(id) => {
+                    if let Some(obj) = self.objects.get(id) {
+                        if objects.insert(id.clone()) {
+                            stack.extend(obj.fields.values().filter_map(ObjectField::get_winner));
+                        }
+                    }
+                }
+                JsonValue::Array(id) => {
+                    if let Some(arr) = self.arrays.get(id) {
+                        if arrays.insert(id.clone()) {
+                            stack.extend(arr.iter());
+                        }
+                    }
+                }
+                JsonValue::Counter(id) => {
+                    counters.insert(id.clone());
+                }
+                _ => {}
+            }
+        }
+        (objects, arrays, counters, live.unwrap_or_default())
     }
 
     // === Conversion ===
@@ -878,6 +1538,66 @@ impl JsonCrdt {
             JsonValue::String(s) => serde_json::Value::String(s.clone()),
             JsonValue::Object(id) => self.object_to_json(id),
             JsonValue::Array(id) => self.array_to_json(id),
+            JsonValue::Counter(id) => self.counter_value(id).unwrap_or_default().into(),
+        }
+    }
+}
+
+fn serialized_size(value: &impl Serialize) -> usize {
+    serde_json::to_vec(value).map_or(0, |bytes| bytes.len())
+}
+
+/// Receives the values a merge changed, from [`JsonCrdt::join_observed`].
+///
+/// Paths come depth first in key order. Only leaves of the change are
+/// reported: a path whose object was replaced by another value, or the
+/// reverse, is reported once with the new value rather than key by key. Arrays are reported as a
+/// whole. Writing each value at its path in the old document, or removing
+/// the path for `None`, yields the merged document.
+pub trait JsonObserver {
+    /// The value at `path` changed to `value`, or was removed for `None`.
+    fn on_path_changed(&mut self, path: &JsonPath, value: Option<&serde_json::Value>);
+}
+
+impl JsonCrdt {
+    /// Join with `other`, reporting each changed path to `observer`.
+    pub fn join_observed(&self, other: &Self, observer: &mut impl JsonObserver) -> Self {
+        let result = self.join(other);
+        self.report_changes(&result, observer);
+        result
+    }
+
+    /// Report each path whose value differs in `newer` to `observer`, as
+    /// [`join_observed`](Self::join_observed) does for a merge.
+    pub fn report_changes(&self, newer: &Self, observer: &mut impl JsonObserver) {
+        diff_json(
+            &JsonPath::root(),
+            &self.to_json(),
+            &newer.to_json(),
+            observer,
+        );
+    }
+}
+
+/// Report the differences between two objects' fields to `observer`.
+fn diff_json(
+    path: &JsonPath,
+    old: &serde_json::Value,
+    new: &serde_json::Value,
+    observer: &mut impl JsonObserver,
+) {
+    let (serde_json::Value::Object(old), serde_json::Value::Object(new)) = (old, new) else {
+        return;
+    };
+    let keys: std::collections::BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        let child = path.child_key(key.as_str());
+        match (old.get(key), new.get(key)) {
+            (Some(a), Some(b)) if a == b => {}
+            (Some(a @ serde_json::Value::Object(_)), Some(b @ serde_json::Value::Object(_))) => {
+                diff_json(&child, a, b, observer)
+            }
+            (_, value) => observer.on_path_changed(&child, value),
         }
     }
 }
@@ -889,6 +1609,8 @@ impl Lattice for JsonCrdt {
 
     fn join(&self, other: &Self) -> Self {
         let mut result = self.clone();
+        // Keep local writes ahead of everything already observed
+        result.seq = self.seq.max(other.seq);
 
         // Merge objects
         for (id, other_obj) in &other.objects {
@@ -905,9 +1627,23 @@ impl Lattice for JsonCrdt {
                 .arrays
                 .entry(id.clone())
                 .and_modify(|arr| arr.merge(other_arr))
-                .or_insert_with(|| other_arr.clone());
+                .or_insert_with(|| JsonArray {
+                    id: other_arr.id.clone(),
+                    // New elements must be stamped with our replica ID
+                    list: other_arr.list.fork(&self.replica_id),
+                });
+        }
+
+        // Merge counters
+        for (id, other_counter) in &other.counters {
+            result
+                .counters
+                .entry(id.clone())
+                .and_modify(|counter| counter.merge(&other_counter.counts, &other_counter.seen))
+                .or_insert_with(|| other_counter.clone());
         }
 
+        result.maybe_gc();
         result
     }
 }
@@ -981,6 +1717,35 @@ mod tests {
         assert_eq!(doc.array_len(&arr_id), Some(2));
     }
 
+    #[test]
+    fn test_array_move_replicates() {
+        let mut doc1 = JsonCrdt::new("r1");
+        let cards = doc1.set_array(&JsonPath::parse("cards")).unwrap();
+        for card in ["todo", "doing", "done"] {
+            doc1.array_push(&cards, JsonValue::String(card.to_string()))
+                .unwrap();
+        }
+        let mut doc2 = JsonCrdt::new("r2");
+        doc2.apply_delta(&doc1.take_delta().unwrap());
+
+        doc1.array_move(&cards, 0, 2).unwrap();
+        doc2.array_move(&cards, 0, 1).unwrap();
+        assert!(matches!(
+            doc1.array_move(&cards, 3, 0),
+            Err(DbError::IndexOutOfBounds {
+                index: 3,
+                length: 3
+            })
+        ));
+        let delta1 = doc1.take_delta().unwrap();
+        let delta2 = doc2.take_delta().unwrap();
+        doc1.apply_delta(&delta2);
+        doc2.apply_delta(&delta1);
+
+        assert_eq!(doc1.to_json(), doc2.to_json());
+        assert_eq!(doc1.array_len(&cards), Some(3));
+    }
+
     #[test]
     fn test_delete() {
         let mut doc = JsonCrdt::new("r1");
@@ -1026,6 +1791,228 @@ mod tests {
         assert_eq!(json1, json2);
     }
 
+    #[test]
+    fn test_write_after_merge_wins() {
+        let mut doc1 = JsonCrdt::new("r1");
+        let mut doc2 = JsonCrdt::new("r2");
+
+        for n in 0..5 {
+            doc1.set(&JsonPath::parse("value"), JsonValue::Int(n))
+                .unwrap();
+        }
+        doc2 = doc2.join(&doc1);
+
+        // r2 has seen every r1 write, so its overwrite must win
+        doc2.set(&JsonPath::parse("value"), JsonValue::Int(100))
+            .unwrap();
+        doc1 = doc1.join(&doc2);
+
+        assert_eq!(
+            doc1.get(&JsonPath::parse("value")),
+            Some(&JsonValue::Int(100))
+        );
+        assert_eq!(
+            doc2.get(&JsonPath::parse("value")),
+            Some(&JsonValue::Int(100))
+        );
+    }
+
+    #[test]
+    fn test_auto_created_parent_conflicts_with_scalar() {
+        let mut doc_a = JsonCrdt::new("A");
+        let mut doc_b = JsonCrdt::new("B");
+
+        // A auto-creates object "a"; B concurrently makes "a" a scalar
+        doc_a
+            .set(&JsonPath::parse("a.b"), JsonValue::Int(1))
+            .unwrap();
+        doc_b.set(&JsonPath::parse("a"), JsonValue::Int(5)).unwrap();
+
+        let delta_a = doc_a.take_delta().unwrap();
+        let delta_b = doc_b.take_delta().unwrap();
+
+        // Both writes to "a" have seq 1, so the greater replica ID wins
+        let expected = serde_json::json!({ "a": 5 });
+
+        let mut a_then_b = doc_a.clone();
+        a_then_b.apply_delta(&delta_b);
+        let mut b_then_a = doc_b.clone();
+        b_then_a.apply_delta(&delta_a);
+        assert_eq!(a_then_b.to_json(), expected);
+        assert_eq!(b_then_a.to_json(), expected);
+
+        assert_eq!(doc_a.join(&doc_b).to_json(), expected);
+        assert_eq!(doc_b.join(&doc_a).to_json(), expected);
+
+        // The losing object is unreachable
+        assert_eq!(a_then_b.get(&JsonPath::parse("a.b")), None);
+        assert_eq!(a_then_b.type_at(&JsonPath::parse("a")), Some(JsonType::Int));
+    }
+
+    #[test]
+    fn test_stale_delta_does_not_undo_newer_write() {
+        let mut doc1 = JsonCrdt::new("r1");
+        let mut doc2 = JsonCrdt::new("r2");
+
+        doc1.set(&JsonPath::parse("value"), JsonValue::Int(1))
+            .unwrap();
+        let old = doc1.take_delta().unwrap();
+        doc1.set(&JsonPath::parse("value"), JsonValue::Int(2))
+            .unwrap();
+        let new = doc1.take_delta().unwrap();
+
+        doc2.apply_delta(&new);
+        doc2.apply_delta(&old);
+
+        assert_eq!(doc2.to_json(), doc1.to_json());
+        assert_eq!(
+            doc2.get(&JsonPath::parse("value")),
+            Some(&JsonValue::Int(2))
+        );
+    }
+
+    #[test]
+    fn test_set_strict() {
+        let mut doc = JsonCrdt::new("r1");
+
+        let err = doc
+            .set_strict(&JsonPath::parse("a.b"), JsonValue::Int(1))
+            .unwrap_err();
+        assert!(matches!(err, DbError::PathNotFound(_)));
+        assert!(doc.take_delta().is_none());
+        assert_eq!(doc.type_at(&JsonPath::parse("a")), None);
+
+        doc.set_strict(&JsonPath::parse("a"), JsonValue::Int(5))
+            .unwrap();
+        let err = doc
+            .set_strict(&JsonPath::parse("a.b"), JsonValue::Int(1))
+            .unwrap_err();
+        assert!(matches!(err, DbError::PathNotFound(_)));
+        assert_eq!(doc.to_json(), serde_json::json!({ "a": 5 }));
+
+        doc.set_object(&JsonPath::parse("a")).unwrap();
+        doc.set_strict(&JsonPath::parse("a.b"), JsonValue::Int(1))
+            .unwrap();
+        assert_eq!(doc.to_json(), serde_json::json!({ "a": { "b": 1 } }));
+    }
+
+    #[test]
+    fn test_type_at() {
+        let mut doc = JsonCrdt::new("r1");
+        doc.set(
+            &JsonPath::parse("user.name"),
+            JsonValue::String("Ada".into()),
+        )
+        .unwrap();
+        doc.set_array(&JsonPath::parse("user.tags")).unwrap();
+
+        assert_eq!(doc.type_at(&JsonPath::root()), Some(JsonType::Object));
+        assert_eq!(
+            doc.type_at(&JsonPath::parse("user")),
+            Some(JsonType::Object)
+        );
+        assert_eq!(
+            doc.type_at(&JsonPath::parse("user.name")),
+            Some(JsonType::String)
+        );
+        assert_eq!(
+            doc.type_at(&JsonPath::parse("user.tags")),
+            Some(JsonType::Array)
+        );
+        assert_eq!(doc.type_at(&JsonPath::parse("user.age")), None);
+
+        doc.delete(&JsonPath::parse("user.name")).unwrap();
+        assert_eq!(doc.type_at(&JsonPath::parse("user.name")), None);
+    }
+
+    #[test]
+    fn test_paths_through_arrays() {
+        let mut doc1 = JsonCrdt::new("r1");
+        let items = doc1.set_array(&JsonPath::parse("items")).unwrap();
+        for name in ["a", "b"] {
+            let obj = doc1.create_object();
+            doc1.array_push(&items, JsonValue::Object(obj)).unwrap();
+            let index = doc1.array_len(&items).unwrap() - 1;
+            doc1.set(
+                &JsonPath::parse("items")
+                    .child_index(index)
+                    .child_key("name"),
+                JsonValue::String(name.into()),
+            )
+            .unwrap();
+        }
+        let mut doc2 = doc1.fork("r2");
+
+        let name = JsonPath::parse("items.1.name");
+        assert_eq!(doc1.get(&name), Some(&JsonValue::String("b".into())));
+        assert_eq!(doc1.to_json()["items"][1]["name"], "b");
+
+        // r1 renames the second item while r2 inserts a new first item
+        doc1.set(&name, JsonValue::String("c".into())).unwrap();
+        let obj = doc2.create_object();
+        doc2.array_insert(&items, 0, JsonValue::Object(obj))
+            .unwrap();
+        doc2.set(
+            &JsonPath::parse("items.0.name"),
+            JsonValue::String("z".into()),
+        )
+        .unwrap();
+
+        // The renamed item moves along to index 2
+        let merged = doc1.join(&doc2);
+        assert_eq!(merged.to_json(), doc2.join(&doc1).to_json());
+        assert_eq!(
+            merged.get(&JsonPath::parse("items.0.name")),
+            Some(&JsonValue::String("z".into()))
+        );
+        assert_eq!(
+            merged.get(&JsonPath::parse("items.2.name")),
+            Some(&JsonValue::String("c".into()))
+        );
+
+        let mut merged = merged;
+        merged.delete(&JsonPath::parse("items.1.name")).unwrap();
+        assert_eq!(merged.type_at(&JsonPath::parse("items.1.name")), None);
+        assert_eq!(
+            merged.type_at(&JsonPath::parse("items.1")),
+            Some(JsonType::Object)
+        );
+        assert_eq!(merged.get(&JsonPath::parse("items.name")), None);
+    }
+
+    #[test]
+    fn test_paths_past_the_end_of_an_array() {
+        let mut doc = JsonCrdt::new("r1");
+        let items = doc.set_array(&JsonPath::parse("items")).unwrap();
+        let obj = doc.create_object();
+        doc.array_push(&items, JsonValue::Object(obj)).unwrap();
+
+        let path = JsonPath::parse("items.3.name");
+        assert_eq!(doc.get(&path), None);
+        assert!(matches!(
+            doc.set(&path, JsonValue::Int(1)),
+            Err(DbError::IndexOutOfBounds {
+                index: 3,
+                length: 1
+            })
+        ));
+        assert!(matches!(
+            doc.set_strict(&path, JsonValue::Int(1)),
+            Err(DbError::IndexOutOfBounds {
+                index: 3,
+                length: 1
+            })
+        ));
+        assert!(matches!(
+            doc.delete(&path),
+            Err(DbError::IndexOutOfBounds {
+                index: 3,
+                length: 1
+            })
+        ));
+        assert_eq!(doc.array_len(&items), Some(1));
+    }
+
     #[test]
     fn test_to_json() {
         let mut doc = JsonCrdt::new("r1");
@@ -1083,6 +2070,75 @@ mod tests {
         assert!(merged.contains_key("b"));
     }
 
+    #[test]
+    fn test_gc_removes_replaced_subtrees() {
+        let mut doc = JsonCrdt::new("r1");
+        for i in 0..3 {
+            let layout = doc.set_object(&JsonPath::parse("layout")).unwrap();
+            doc.set(&JsonPath::parse("layout.width"), JsonValue::Int(i))
+                .unwrap();
+            let panes = doc.set_array(&JsonPath::parse("layout.panes")).unwrap();
+            let pane = doc.create_object();
+            doc.array_push(&panes, JsonValue::Object(pane)).unwrap();
+            assert!(doc.objects.contains_key(&layout));
+        }
+        doc.take_delta();
+        let before = doc.to_json();
+        assert_eq!(doc.container_count(), 1 + 3 * 3);
+
+        // Everything was created since the last collection
+        assert_eq!(doc.gc(), GcStats::default());
+        let stats = doc.gc();
+        assert_eq!(stats.objects_removed, 4);
+        assert_eq!(stats.arrays_removed, 2);
+        assert!(stats.bytes_reclaimed > 0);
+        assert_eq!(doc.container_count(), 4);
+        assert_eq!(doc.to_json(), before);
+        assert_eq!(doc.gc(), GcStats::default());
+    }
+
+    #[test]
+    fn test_gc_gives_new_containers_until_the_next_collection() {
+        let mut doc = JsonCrdt::new("r1");
+        let obj = doc.create_object();
+        doc.gc();
+        doc.set(&JsonPath::parse("a"), JsonValue::Object(obj.clone()))
+            .unwrap();
+        doc.set(&JsonPath::parse("a.x"), JsonValue::Int(1)).unwrap();
+        doc.gc();
+        assert_eq!(doc.to_json(), serde_json::json!({"a": {"x": 1}}));
+
+        let unattached = doc.create_array();
+        doc.gc();
+        assert!(doc.arrays.contains_key(&unattached));
+        doc.gc();
+        assert!(!doc.arrays.contains_key(&unattached));
+    }
+
+    #[test]
+    fn test_gc_threshold_triggers_collection() {
+        let mut doc = JsonCrdt::new("r1").with_gc_threshold(1.0);
+        assert_eq!(doc.gc_threshold(), Some(1.0));
+        for _ in 0..100 {
+            doc.set_object(&JsonPath::parse("layout")).unwrap();
+            assert!(doc.container_count() <= 4, "{}", doc.container_count());
+        }
+        assert_eq!(doc.to_json(), serde_json::json!({"layout": {}}));
+    }
+
+    #[test]
+    fn test_stale_delta_does_not_restore_deleted_value() {
+        let mut doc_a = JsonCrdt::new("r1");
+        let mut doc_b = JsonCrdt::new("r2");
+        doc_b.set(&JsonPath::parse("x"), JsonValue::Int(1)).unwrap();
+        let delta = doc_b.take_delta().unwrap();
+
+        doc_a.apply_delta(&delta);
+        doc_a.delete(&JsonPath::parse("x")).unwrap();
+        doc_a.apply_delta(&delta);
+        assert!(doc_a.keys().is_empty());
+    }
+
     #[test]
     fn test_keys() {
         let mut doc = JsonCrdt::new("r1");
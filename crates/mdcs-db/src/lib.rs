@@ -5,9 +5,12 @@
 //! This crate provides:
 //! - Document-based API with path operations
 //! - Collaborative text (RGAText, RichText)
-//! - JSON/Object CRDT for flexible schemas
+//! - Sanitized HTML import and paste for rich text
+//! - JSON/Object CRDT for flexible schemas, with garbage collection
 //! - Presence and awareness for real-time collaboration
 //! - Undo/Redo support
+//! - Full-text search across documents
+//! - Yjs snapshot import and delta export for rich text (`yjs` feature)
 //!
 //! ## Example
 //!
@@ -32,45 +35,67 @@
 
 pub mod document;
 pub mod error;
+pub mod html;
 pub mod json_crdt;
 pub mod presence;
 pub mod rga_list;
 pub mod rga_text;
 pub mod rich_text;
+pub mod search;
+mod serde_map;
 pub mod undo;
+#[cfg(feature = "yjs")]
+pub mod yjs;
 
 // RGA List exports
-pub use rga_list::{ListId, ListNode, RGAList, RGAListDelta};
+pub use rga_list::{ListId, ListMove, ListNode, RGAList, RGAListDelta};
 
 // RGA Text exports
-pub use rga_text::{RGAText, RGATextDelta, TextId};
+pub use rga_text::{RGAText, RGATextDelta, TextId, TextObserver};
 
 // Rich Text exports
-pub use rich_text::{Anchor, Mark, MarkId, MarkType, RichText, RichTextDelta};
+pub use rich_text::{
+    Anchor, Block, BlockType, HtmlOptions, Mark, MarkId, MarkType, RichText, RichTextDelta,
+};
+
+// HTML import exports
+pub use html::{
+    sanitize_html, BlockKind, HtmlFragment, MarkKind, SanitizePolicy, SanitizeReport,
+    DEFAULT_MAX_PASTE_LENGTH,
+};
 
 // JSON CRDT exports
 pub use json_crdt::{
-    ArrayChange, ArrayId, JsonCrdt, JsonCrdtDelta, JsonPath, JsonValue, ObjectChange, ObjectId,
-    PathSegment,
+    ArrayChange, ArrayId, CounterChange, CounterId, GcStats, JsonCrdt, JsonCrdtDelta, JsonObserver,
+    JsonPath, JsonType, JsonValue, ObjectChange, ObjectId, PathSegment,
 };
 
 // Document Store exports
 pub use document::{
-    CrdtValue, Document, DocumentDelta, DocumentId, DocumentStore, DocumentType, QueryOptions,
-    SortField, StoreChange,
+    BranchChange, BranchDiff, ChangeRef, CollectionEntry, CollectionId, CollectionNode,
+    CollectionTree, CrdtValue, Document, DocumentDelta, DocumentId, DocumentMeta, DocumentStore,
+    DocumentType, LwwStamp, MergePolicy, QueryOptions, SortField, StoreChange,
+    DEFAULT_COALESCE_THRESHOLD,
 };
 
+// Search exports
+pub use search::{SearchConfig, SearchHit, SearchIndex};
+
 // Presence exports
 pub use presence::{
-    Cursor, CursorBuilder, CursorColors, PresenceDelta, PresenceTracker, UserId, UserInfo,
-    UserPresence, UserStatus,
+    Cursor, CursorBuilder, CursorColors, CursorLocation, DeviceId, ElementRef, PresenceConfig,
+    PresenceDelta, PresenceEvent, PresenceRemoval, PresenceTracker, UserId, UserInfo, UserPresence,
+    UserStatus, Viewport, ViewportAnchor,
 };
 
 // Undo/Redo exports
 pub use undo::{
     CollaborativeUndoManager, FormatOperation, GroupId, JsonOperation, Operation, OperationId,
-    TextOperation, UndoManager, UndoableOperation,
+    TextOperation, UndoConfig, UndoHistory, UndoManager, UndoOutcome, UndoableOperation,
 };
 
 // Error exports
 pub use error::DbError;
+
+// Causal context of the text and JSON CRDTs
+pub use mdcs_compaction::VersionVector;
@@ -2,16 +2,24 @@
 //!
 //! Provides collaborative awareness features:
 //! - Cursor positions and selections
-//! - User online/offline status
+//! - Structured locations in JSON documents and lists
+//! - User online/offline status, combined across a user's devices
+//! - User information merged field by field
 //! - Custom user state (e.g., "typing", "away")
 //! - Automatic expiration of stale presence
+//! - Bounded retention of users who have departed
 
+use crate::document::LwwStamp;
+use crate::json_crdt::{ArrayId, JsonCrdt, JsonPath};
+use crate::rga_list::ListId;
+use crate::rich_text::Anchor;
+use mdcs_core::clock::{Clock, SharedClock, SystemClock};
 use mdcs_core::lattice::Lattice;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// Unique identifier for a user.
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct UserId(pub String);
 
 impl UserId {
@@ -26,6 +34,80 @@ impl std::fmt::Display for UserId {
     }
 }
 
+/// Identifier for one of a user's devices (or sessions).
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct DeviceId(pub String);
+
+impl DeviceId {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+}
+
+impl Default for DeviceId {
+    fn default() -> Self {
+        Self::new("default")
+    }
+}
+
+impl std::fmt::Display for DeviceId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A stable reference to an element of a list, by its list ID.
+pub type ElementRef = ListId;
+
+/// Where a user is in a document.
+///
+/// Text documents use offsets or anchors; structured documents point at a
+/// JSON path or at a list element, e.g. "card X in column Y".
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CursorLocation {
+    /// A plain character offset.
+    TextOffset(usize),
+    /// A stable text anchor that survives concurrent edits.
+    TextAnchor(Anchor),
+    /// A value in a JSON document.
+    JsonPath(JsonPath),
+    /// An element of a JSON array, followed across concurrent moves.
+    ListElement {
+        array_id: ArrayId,
+        element: ElementRef,
+    },
+}
+
+impl CursorLocation {
+    /// The JSON path of a [`JsonPath`](Self::JsonPath) location.
+    pub fn json_path(&self) -> Option<&JsonPath> {
+        match self {
+            CursorLocation::JsonPath(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Resolve this location to a path in `doc`.
+    ///
+    /// A list element resolves to its current index in the array. If the
+    /// element has since been deleted it falls back to the array itself, so
+    /// the user stays highlighted on the enclosing list. Text locations, and
+    /// arrays no longer reachable from the root, resolve to `None`.
+    pub fn resolve_path(&self, doc: &JsonCrdt) -> Option<JsonPath> {
+        match self {
+            CursorLocation::JsonPath(path) => Some(path.clone()),
+            CursorLocation::ListElement { array_id, element } => {
+                let path = doc.array_path(array_id)?;
+                match doc.array_element_index(array_id, element) {
+                    Some(index) => Some(path.child_index(index)),
+                    None => Some(path),
+                }
+            }
+            CursorLocation::TextOffset(_) | CursorLocation::TextAnchor(_) => None,
+        }
+    }
+}
+
 /// A cursor position in a document.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Cursor {
@@ -33,6 +115,9 @@ pub struct Cursor {
     pub position: usize,
     /// Optional anchor for selection (selection goes from anchor to position).
     pub anchor: Option<usize>,
+    /// Structured location, for cursors that are not a text offset.
+    #[serde(default)]
+    pub location: Option<CursorLocation>,
 }
 
 impl Cursor {
@@ -41,6 +126,7 @@ impl Cursor {
         Self {
             position,
             anchor: None,
+            location: None,
         }
     }
 
@@ -49,9 +135,29 @@ impl Cursor {
         Self {
             position,
             anchor: Some(anchor),
+            location: None,
+        }
+    }
+
+    /// Create a cursor at a location.
+    pub fn at_location(location: CursorLocation) -> Self {
+        match location {
+            CursorLocation::TextOffset(position) => Self::at(position),
+            location => Self {
+                position: 0,
+                anchor: None,
+                location: Some(location),
+            },
         }
     }
 
+    /// Get the cursor's location.
+    pub fn location(&self) -> CursorLocation {
+        self.location
+            .clone()
+            .unwrap_or(CursorLocation::TextOffset(self.position))
+    }
+
     /// Check if this cursor has a selection.
     pub fn has_selection(&self) -> bool {
         self.anchor.is_some() && self.anchor != Some(self.position)
@@ -76,6 +182,55 @@ impl Cursor {
     }
 }
 
+/// Where a viewport starts in a document.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ViewportAnchor {
+    /// A plain character offset.
+    Offset(usize),
+    /// A stable text anchor that survives concurrent edits.
+    Text(Anchor),
+}
+
+/// The visible region of a document for a user.
+///
+/// Viewports are ephemeral: they ride along with the rest of the user's
+/// presence and disappear when the user does.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Viewport {
+    /// The first visible position.
+    pub anchor: ViewportAnchor,
+    /// Number of characters visible from the anchor.
+    pub extent: usize,
+    /// Zoom level in percent (100 = unscaled).
+    pub zoom: u32,
+}
+
+impl Viewport {
+    /// Create a viewport starting at a character offset.
+    pub fn at_offset(offset: usize, extent: usize) -> Self {
+        Self {
+            anchor: ViewportAnchor::Offset(offset),
+            extent,
+            zoom: 100,
+        }
+    }
+
+    /// Create a viewport starting at a text anchor.
+    pub fn at_anchor(anchor: Anchor, extent: usize) -> Self {
+        Self {
+            anchor: ViewportAnchor::Text(anchor),
+            extent,
+            zoom: 100,
+        }
+    }
+
+    /// Set the zoom level (percent).
+    pub fn with_zoom(mut self, zoom: u32) -> Self {
+        self.zoom = zoom;
+        self
+    }
+}
+
 /// User status.
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum UserStatus {
@@ -94,7 +249,30 @@ pub enum UserStatus {
     Custom(String),
 }
 
+impl UserStatus {
+    /// Precedence when combining the devices of one user.
+    ///
+    /// A user shows the highest-precedence status among their live devices,
+    /// so a device going idle or offline never hides another device's
+    /// activity.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            UserStatus::Typing => 5,
+            UserStatus::Online => 4,
+            UserStatus::Custom(_) => 3,
+            UserStatus::Idle => 2,
+            UserStatus::Away => 1,
+            UserStatus::Offline => 0,
+        }
+    }
+}
+
 /// User information for display.
+///
+/// Each field is a last-writer-wins register with its own stamp, so
+/// concurrent updates to different fields (or from different devices of the
+/// same user) merge instead of replacing each other. Fields that were never
+/// set carry the default stamp and lose to any update.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct UserInfo {
     /// User's display name.
@@ -103,6 +281,15 @@ pub struct UserInfo {
     pub color: String,
     /// Optional avatar URL.
     pub avatar: Option<String>,
+    /// When `name` was last set.
+    #[serde(default)]
+    pub name_stamp: LwwStamp,
+    /// When `color` was last set.
+    #[serde(default)]
+    pub color_stamp: LwwStamp,
+    /// When `avatar` was last set.
+    #[serde(default)]
+    pub avatar_stamp: LwwStamp,
 }
 
 impl UserInfo {
@@ -111,6 +298,9 @@ impl UserInfo {
             name: name.into(),
             color: color.into(),
             avatar: None,
+            name_stamp: LwwStamp::default(),
+            color_stamp: LwwStamp::default(),
+            avatar_stamp: LwwStamp::default(),
         }
     }
 
@@ -118,24 +308,69 @@ impl UserInfo {
         self.avatar = Some(avatar.into());
         self
     }
+
+    /// Merge another copy field by field. Returns `true` if any field
+    /// changed.
+    ///
+    /// Equal stamps only arise from unset defaults; the larger value wins
+    /// so every replica picks the same one.
+    pub fn merge(&mut self, other: &UserInfo) -> bool {
+        let name = merge_field(
+            (&mut self.name, &mut self.name_stamp),
+            (&other.name, &other.name_stamp),
+        );
+        let color = merge_field(
+            (&mut self.color, &mut self.color_stamp),
+            (&other.color, &other.color_stamp),
+        );
+        let avatar = merge_field(
+            (&mut self.avatar, &mut self.avatar_stamp),
+            (&other.avatar, &other.avatar_stamp),
+        );
+        name || color || avatar
+    }
+}
+
+/// Last-writer-wins merge of one stamped field.
+fn merge_field<T: Ord + Clone>(ours: (&mut T, &mut LwwStamp), theirs: (&T, &LwwStamp)) -> bool {
+    let (value, stamp) = ours;
+    let newer = match theirs.1.cmp(stamp) {
+        std::cmp::Ordering::Greater => true,
+        std::cmp::Ordering::Equal => theirs.0 > value,
+        std::cmp::Ordering::Less => false,
+    };
+    if newer {
+        *value = theirs.0.clone();
+        *stamp = theirs.1.clone();
+    }
+    newer
 }
 
-/// Presence data for a single user.
+/// Presence data for one device of a user.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct UserPresence {
     /// The user ID.
     pub user_id: UserId,
-    /// User information.
+    /// The device this presence belongs to.
+    #[serde(default)]
+    pub device_id: DeviceId,
+    /// User information, merged across the user's devices.
     pub info: UserInfo,
-    /// Current status.
+    /// Current status of this device.
     pub status: UserStatus,
     /// Cursor positions by document ID.
     pub cursors: HashMap<String, Cursor>,
+    /// Visible regions by document ID.
+    #[serde(default)]
+    pub viewports: HashMap<String, Viewport>,
     /// Custom user state data.
     pub state: HashMap<String, String>,
     /// Last update timestamp (milliseconds since epoch).
     pub last_updated: u64,
     /// Lamport timestamp for ordering.
+    ///
+    /// Local updates keep it at or above the wall clock, so a device that
+    /// restarts with a fresh tracker still orders after its last session.
     pub timestamp: u64,
 }
 
@@ -144,15 +379,23 @@ impl UserPresence {
     pub fn new(user_id: UserId, info: UserInfo) -> Self {
         Self {
             user_id,
+            device_id: DeviceId::default(),
             info,
             status: UserStatus::Online,
             cursors: HashMap::new(),
+            viewports: HashMap::new(),
             state: HashMap::new(),
-            last_updated: now_millis(),
+            last_updated: SystemClock.now_millis(),
             timestamp: 0,
         }
     }
 
+    /// Set the device this presence belongs to.
+    pub fn with_device(mut self, device_id: DeviceId) -> Self {
+        self.device_id = device_id;
+        self
+    }
+
     /// Update the cursor for a document.
     pub fn set_cursor(&mut self, document_id: impl Into<String>, cursor: Cursor) {
         self.cursors.insert(document_id.into(), cursor);
@@ -170,6 +413,17 @@ impl UserPresence {
         self.cursors.get(document_id)
     }
 
+    /// Update the viewport for a document.
+    pub fn set_viewport(&mut self, document_id: impl Into<String>, viewport: Viewport) {
+        self.viewports.insert(document_id.into(), viewport);
+        self.touch();
+    }
+
+    /// Get the viewport for a document.
+    pub fn get_viewport(&self, document_id: &str) -> Option<&Viewport> {
+        self.viewports.get(document_id)
+    }
+
     /// Set the status.
     pub fn set_status(&mut self, status: UserStatus) {
         self.status = status;
@@ -189,24 +443,38 @@ impl UserPresence {
 
     /// Touch the update timestamp.
     fn touch(&mut self) {
-        self.last_updated = now_millis();
+        self.last_updated = SystemClock.now_millis();
         self.timestamp += 1;
     }
 
     /// Check if this presence is stale (not updated within timeout).
     pub fn is_stale(&self, timeout_ms: u64) -> bool {
-        let now = now_millis();
+        self.is_stale_at(SystemClock.now_millis(), timeout_ms)
+    }
+
+    /// Check if this presence is stale at time `now` (milliseconds).
+    pub fn is_stale_at(&self, now: u64, timeout_ms: u64) -> bool {
         now.saturating_sub(self.last_updated) > timeout_ms
     }
 }
 
+/// A device presence removed by leaving or by expiry.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresenceRemoval {
+    pub user_id: UserId,
+    pub device_id: DeviceId,
+    /// Timestamp of the removed record; replicas holding a newer record
+    /// from the device keep it.
+    pub timestamp: u64,
+}
+
 /// Delta for presence updates.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct PresenceDelta {
     /// Updated presence records.
     pub updates: Vec<UserPresence>,
-    /// Users that have left.
-    pub removals: Vec<UserId>,
+    /// Devices that have left or expired.
+    pub removals: Vec<PresenceRemoval>,
 }
 
 impl PresenceDelta {
@@ -228,19 +496,77 @@ impl Default for PresenceDelta {
     }
 }
 
+/// A change in a user's presence, reported by
+/// [`PresenceTracker::expire_stale`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PresenceEvent {
+    /// The user has not been heard from within the away timeout.
+    Away(UserId),
+    /// A user reported away has been heard from again.
+    Active(UserId),
+    /// The user's last device expired and the user was dropped.
+    Left(UserId),
+}
+
+/// How long a [`PresenceTracker`] keeps users who have departed.
+///
+/// A user has departed once every one of their devices is offline or
+/// stale; their departure time is the latest update from any of them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PresenceConfig {
+    /// Most users kept. Departed users beyond it are evicted, earliest
+    /// departure first; active users never are, so the limit is exceeded
+    /// while they are all active.
+    pub max_users: usize,
+    /// How long a departed user is kept after departing (milliseconds).
+    pub departed_retention_ms: u64,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        Self {
+            max_users: 1_000,
+            departed_retention_ms: 5 * 60_000,
+        }
+    }
+}
+
 /// Presence tracker for a collaborative session.
 ///
-/// Tracks all users' cursors, selections, and status.
+/// Tracks all users' cursors, selections, and status. Presence is kept per
+/// device, so a user signed in on several devices has one record for each;
+/// user information is kept per user and merged field by field.
+///
+/// Departed users are dropped once they fall outside the
+/// [`PresenceConfig`]. Which ones depends only on the records and the
+/// clock, so replicas with the same config, clock and deltas keep the same
+/// users.
 #[derive(Clone, Debug, PartialEq)]
 pub struct PresenceTracker {
     /// The local user's ID.
     local_user: UserId,
-    /// All user presence records.
-    users: HashMap<UserId, UserPresence>,
+    /// The local device's ID.
+    local_device: DeviceId,
+    /// Presence records by user, then device.
+    users: HashMap<UserId, HashMap<DeviceId, UserPresence>>,
+    /// Merged user information, copied into each of the user's records.
+    infos: HashMap<UserId, UserInfo>,
     /// Timeout for stale presence (milliseconds).
     stale_timeout: u64,
+    /// Quiet time after which a live device shows as away (milliseconds).
+    away_timeout: u64,
+    /// Users last reported away by `expire_stale`.
+    away: HashSet<UserId>,
     /// Pending delta for replication.
     pending_delta: Option<PresenceDelta>,
+    /// Source of update times and staleness checks.
+    clock: SharedClock,
+    /// Retention limits for departed users.
+    config: PresenceConfig,
+    /// Departure times of dropped users. Records from before a user's
+    /// departure are ignored until the tombstone expires, so a delayed
+    /// heartbeat can't bring back part of the user.
+    tombstones: HashMap<UserId, u64>,
 }
 
 impl PresenceTracker {
@@ -248,125 +574,382 @@ impl PresenceTracker {
     pub fn new(local_user: UserId, info: UserInfo) -> Self {
         let mut tracker = Self {
             local_user: local_user.clone(),
+            local_device: DeviceId::default(),
             users: HashMap::new(),
+            infos: HashMap::new(),
             stale_timeout: 30_000, // 30 seconds default
+            away_timeout: 15_000,
+            away: HashSet::new(),
             pending_delta: None,
+            clock: SharedClock::default(),
+            config: PresenceConfig::default(),
+            tombstones: HashMap::new(),
         };
 
         // Add local user
+        tracker.infos.insert(local_user.clone(), info.clone());
         let presence = UserPresence::new(local_user, info);
-        tracker.users.insert(presence.user_id.clone(), presence);
+        tracker
+            .users
+            .entry(presence.user_id.clone())
+            .or_default()
+            .insert(presence.device_id.clone(), presence);
 
         tracker
     }
 
+    /// Track the local user on `device_id`.
+    ///
+    /// Each device of a user needs its own ID, or their presence records
+    /// overwrite each other.
+    pub fn with_device(mut self, device_id: DeviceId) -> Self {
+        if let Some(devices) = self.users.get_mut(&self.local_user) {
+            if let Some(presence) = devices.remove(&self.local_device) {
+                devices.insert(device_id.clone(), presence.with_device(device_id.clone()));
+            }
+        }
+        self.local_device = device_id;
+        self
+    }
+
     /// Get the local user ID.
     pub fn local_user(&self) -> &UserId {
         &self.local_user
     }
 
+    /// Get the local device ID.
+    pub fn local_device(&self) -> &DeviceId {
+        &self.local_device
+    }
+
     /// Set the stale timeout.
     pub fn set_stale_timeout(&mut self, timeout_ms: u64) {
         self.stale_timeout = timeout_ms;
     }
 
-    /// Get the local user's presence.
+    /// Set how long a device can go without an update before it shows as
+    /// [`UserStatus::Away`].
+    pub fn set_away_timeout(&mut self, timeout_ms: u64) {
+        self.away_timeout = timeout_ms;
+    }
+
+    /// Set the retention limits for departed users, dropping any users
+    /// already outside them.
+    pub fn set_config(&mut self, config: PresenceConfig) {
+        self.config = config;
+        self.retire_departed();
+    }
+
+    /// Get the retention limits for departed users.
+    pub fn config(&self) -> &PresenceConfig {
+        &self.config
+    }
+
+    /// Use `clock` for update times and staleness checks.
+    pub fn set_clock(&mut self, clock: impl Into<SharedClock>) {
+        self.clock = clock.into();
+        let now = self.clock.now_millis();
+        if let Some(presence) = self
+            .users
+            .get_mut(&self.local_user)
+            .and_then(|devices| devices.get_mut(&self.local_device))
+        {
+            presence.last_updated = now;
+        }
+    }
+
+    /// Get the local device's presence.
     pub fn local_presence(&self) -> Option<&UserPresence> {
-        self.users.get(&self.local_user)
+        self.get_device(&self.local_user, &self.local_device)
     }
 
     // === Local User Operations ===
 
-    /// Update the local user's cursor.
-    pub fn set_cursor(&mut self, document_id: impl Into<String>, cursor: Cursor) {
-        let doc_id = document_id.into();
-        let local_user = self.local_user.clone();
-        if let Some(presence) = self.users.get_mut(&local_user) {
-            presence.set_cursor(&doc_id, cursor);
+    /// Apply `update` to the local presence and queue it for replication.
+    fn update_local(&mut self, update: impl FnOnce(&mut UserPresence)) {
+        let now = self.clock.now_millis();
+        if let Some(presence) = self
+            .users
+            .get_mut(&self.local_user)
+            .and_then(|devices| devices.get_mut(&self.local_device))
+        {
+            update(presence);
+            presence.last_updated = now;
+            presence.timestamp = presence.timestamp.max(now);
             let presence_clone = presence.clone();
             let delta = self.pending_delta.get_or_insert_with(PresenceDelta::new);
             delta.updates.push(presence_clone);
         }
     }
 
+    /// Update the local user's cursor.
+    pub fn set_cursor(&mut self, document_id: impl Into<String>, cursor: Cursor) {
+        let doc_id = document_id.into();
+        self.update_local(|presence| presence.set_cursor(doc_id, cursor));
+    }
+
     /// Remove the local user's cursor from a document.
     pub fn remove_cursor(&mut self, document_id: &str) {
-        let local_user = self.local_user.clone();
-        if let Some(presence) = self.users.get_mut(&local_user) {
-            presence.remove_cursor(document_id);
-            let presence_clone = presence.clone();
-            let delta = self.pending_delta.get_or_insert_with(PresenceDelta::new);
-            delta.updates.push(presence_clone);
-        }
+        self.update_local(|presence| presence.remove_cursor(document_id));
     }
 
-    /// Set the local user's status.
+    /// Update the local user's viewport for a document.
+    pub fn set_viewport(&mut self, document_id: impl Into<String>, viewport: Viewport) {
+        let doc_id = document_id.into();
+        self.update_local(|presence| presence.set_viewport(doc_id, viewport));
+    }
+
+    /// Set the local device's status.
     pub fn set_status(&mut self, status: UserStatus) {
-        let local_user = self.local_user.clone();
-        if let Some(presence) = self.users.get_mut(&local_user) {
-            presence.set_status(status);
-            let presence_clone = presence.clone();
-            let delta = self.pending_delta.get_or_insert_with(PresenceDelta::new);
-            delta.updates.push(presence_clone);
-        }
+        self.update_local(|presence| presence.set_status(status));
     }
 
     /// Set local user's custom state.
     pub fn set_state(&mut self, key: impl Into<String>, value: impl Into<String>) {
-        let local_user = self.local_user.clone();
-        if let Some(presence) = self.users.get_mut(&local_user) {
-            presence.set_state(key, value);
-            let presence_clone = presence.clone();
-            let delta = self.pending_delta.get_or_insert_with(PresenceDelta::new);
-            delta.updates.push(presence_clone);
-        }
+        self.update_local(|presence| presence.set_state(key, value));
     }
 
     /// Send a heartbeat to keep presence alive.
     pub fn heartbeat(&mut self) {
+        self.update_local(|presence| presence.touch());
+    }
+
+    /// Change the local user's information.
+    ///
+    /// Only fields that differ from the current information are stamped,
+    /// so renaming on one device does not overwrite a color change made
+    /// concurrently on another.
+    pub fn set_user_info(&mut self, info: UserInfo) {
+        let now = self.clock.now_millis();
+        let device = self.local_device.0.clone();
+        let stamp = |previous: &LwwStamp| LwwStamp {
+            counter: now.max(previous.counter + 1),
+            replica: device.clone(),
+        };
+
+        let mut updated = self
+            .infos
+            .get(&self.local_user)
+            .cloned()
+            .unwrap_or_else(|| UserInfo::new("", ""));
+        if info.name != updated.name {
+            updated.name_stamp = stamp(&updated.name_stamp);
+            updated.name = info.name;
+        }
+        if info.color != updated.color {
+            updated.color_stamp = stamp(&updated.color_stamp);
+            updated.color = info.color;
+        }
+        if info.avatar != updated.avatar {
+            updated.avatar_stamp = stamp(&updated.avatar_stamp);
+            updated.avatar = info.avatar;
+        }
+
         let local_user = self.local_user.clone();
-        if let Some(presence) = self.users.get_mut(&local_user) {
-            presence.touch();
-            let presence_clone = presence.clone();
-            let delta = self.pending_delta.get_or_insert_with(PresenceDelta::new);
-            delta.updates.push(presence_clone);
+        if self.update_user_info(&local_user, &updated) {
+            self.update_local(|presence| presence.touch());
+        }
+    }
+
+    /// Merge user information into a user's, field by field.
+    ///
+    /// Returns `true` if any field changed.
+    pub fn update_user_info(&mut self, user_id: &UserId, info: &UserInfo) -> bool {
+        let (merged, changed) = match self.infos.get_mut(user_id) {
+            Some(existing) => {
+                let changed = existing.merge(info);
+                (existing.clone(), changed)
+            }
+            None => {
+                self.infos.insert(user_id.clone(), info.clone());
+                (info.clone(), true)
+            }
+        };
+
+        if changed {
+            if let Some(devices) = self.users.get_mut(user_id) {
+                for presence in devices.values_mut() {
+                    presence.info = merged.clone();
+                }
+            }
         }
+        changed
     }
 
     // === Query Operations ===
 
-    /// Get a user's presence.
+    /// Check if a record is within the stale timeout at `now`.
+    fn is_live(&self, presence: &UserPresence, now: u64) -> bool {
+        !presence.is_stale_at(now, self.stale_timeout)
+    }
+
+    /// A record's status at `now`: offline once stale, away once quiet for
+    /// the away timeout.
+    fn status_at(&self, presence: &UserPresence, now: u64) -> UserStatus {
+        if !self.is_live(presence, now) {
+            UserStatus::Offline
+        } else if presence.status != UserStatus::Offline
+            && presence.is_stale_at(now, self.away_timeout)
+        {
+            UserStatus::Away
+        } else {
+            presence.status.clone()
+        }
+    }
+
+    /// The record representing a user: their live device with the highest
+    /// status precedence, most recently updated first.
+    fn primary<'a>(
+        &self,
+        devices: &'a HashMap<DeviceId, UserPresence>,
+        now: u64,
+    ) -> Option<&'a UserPresence> {
+        let rank = |p: &UserPresence| {
+            let live = self.is_live(p, now);
+            (live, self.status_at(p, now).precedence(), p.last_updated)
+        };
+        devices.values().max_by(|a, b| {
+            rank(a)
+                .cmp(&rank(b))
+                .then_with(|| a.device_id.cmp(&b.device_id))
+        })
+    }
+
+    /// When a user departed: the latest update from any of their devices,
+    /// once all of them are offline or stale at `now`.
+    fn departed_at(&self, devices: &HashMap<DeviceId, UserPresence>, now: u64) -> Option<u64> {
+        let active = devices
+            .values()
+            .any(|p| self.is_live(p, now) && !matches!(p.status, UserStatus::Offline));
+        if active {
+            return None;
+        }
+        devices.values().map(|p| p.last_updated).max()
+    }
+
+    /// Check if a user is active or departed within the retention window.
+    fn is_retained(&self, devices: &HashMap<DeviceId, UserPresence>, now: u64) -> bool {
+        self.departed_at(devices, now)
+            .is_none_or(|at| now.saturating_sub(at) <= self.config.departed_retention_ms)
+    }
+
+    /// Presence records of live devices that are not offline.
+    fn online_devices(&self) -> impl Iterator<Item = &UserPresence> + '_ {
+        let now = self.clock.now_millis();
+        self.users
+            .values()
+            .flat_map(|devices| devices.values())
+            .filter(move |p| self.is_live(p, now) && !matches!(p.status, UserStatus::Offline))
+    }
+
+    /// Get a user's presence, from the device that best represents them.
     pub fn get_user(&self, user_id: &UserId) -> Option<&UserPresence> {
-        self.users.get(user_id)
+        let now = self.clock.now_millis();
+        self.primary(self.users.get(user_id)?, now)
     }
 
-    /// Get all users.
+    /// Get the presence of one device of a user.
+    pub fn get_device(&self, user_id: &UserId, device_id: &DeviceId) -> Option<&UserPresence> {
+        self.users.get(user_id)?.get(device_id)
+    }
+
+    /// Get a user's merged information.
+    pub fn user_info(&self, user_id: &UserId) -> Option<&UserInfo> {
+        self.infos.get(user_id)
+    }
+
+    /// Get the user's live devices, sorted.
+    pub fn devices(&self, user_id: &UserId) -> Vec<DeviceId> {
+        let now = self.clock.now_millis();
+        let mut devices: Vec<_> = self
+            .users
+            .get(user_id)
+            .into_iter()
+            .flat_map(|devices| devices.values())
+            .filter(|p| self.is_live(p, now))
+            .map(|p| p.device_id.clone())
+            .collect();
+        devices.sort();
+        devices
+    }
+
+    /// Get a user's status, combined over their live devices by
+    /// [`UserStatus::precedence`]. A device quiet for the away timeout
+    /// counts as away; Offline if no device is live.
+    pub fn user_status(&self, user_id: &UserId) -> UserStatus {
+        let now = self.clock.now_millis();
+        self.get_user(user_id)
+            .map(|p| self.status_at(p, now))
+            .unwrap_or(UserStatus::Offline)
+    }
+
+    /// Get all users, one record each.
     pub fn all_users(&self) -> impl Iterator<Item = &UserPresence> + '_ {
-        self.users.values()
+        let now = self.clock.now_millis();
+        self.users
+            .values()
+            .filter_map(move |devices| self.primary(devices, now))
     }
 
     /// Get all online users.
     pub fn online_users(&self) -> impl Iterator<Item = &UserPresence> + '_ {
-        self.users
-            .values()
-            .filter(|p| !p.is_stale(self.stale_timeout) && !matches!(p.status, UserStatus::Offline))
+        let now = self.clock.now_millis();
+        self.all_users()
+            .filter(move |p| self.is_live(p, now) && !matches!(p.status, UserStatus::Offline))
     }
 
     /// Get users with cursors in a document.
     pub fn users_in_document(&self, document_id: &str) -> Vec<&UserPresence> {
-        self.online_users()
-            .filter(|p| p.cursors.contains_key(document_id))
-            .collect()
+        dedup_users(
+            self.online_devices()
+                .filter(|p| p.cursors.contains_key(document_id)),
+        )
     }
 
-    /// Get all cursors in a document (excluding local user).
+    /// Get all cursors in a document (excluding the local device).
+    ///
+    /// A user on several devices has a cursor for each.
     pub fn cursors_in_document(&self, document_id: &str) -> Vec<(&UserPresence, &Cursor)> {
-        self.online_users()
-            .filter(|p| p.user_id != self.local_user)
+        self.online_devices()
+            .filter(|p| p.user_id != self.local_user || p.device_id != self.local_device)
             .filter_map(|p| p.get_cursor(document_id).map(|c| (p, c)))
             .collect()
     }
 
+    /// Get online users located at or under `path` in a JSON document.
+    ///
+    /// Only [`CursorLocation::JsonPath`] locations can be matched without
+    /// the document; use [`users_at_path_in`](Self::users_at_path_in) to
+    /// include list elements.
+    pub fn users_at_path(&self, document_id: &str, path: &JsonPath) -> Vec<&UserPresence> {
+        self.users_at_path_with(document_id, path, |location| location.json_path().cloned())
+    }
+
+    /// Like [`users_at_path`](Self::users_at_path), resolving list element
+    /// locations against `doc`.
+    pub fn users_at_path_in(
+        &self,
+        document_id: &str,
+        path: &JsonPath,
+        doc: &JsonCrdt,
+    ) -> Vec<&UserPresence> {
+        self.users_at_path_with(document_id, path, |location| location.resolve_path(doc))
+    }
+
+    fn users_at_path_with(
+        &self,
+        document_id: &str,
+        path: &JsonPath,
+        resolve: impl Fn(&CursorLocation) -> Option<JsonPath>,
+    ) -> Vec<&UserPresence> {
+        dedup_users(self.online_devices().filter(|p| {
+            p.get_cursor(document_id)
+                .and_then(|c| c.location.as_ref())
+                .and_then(&resolve)
+                .is_some_and(|at| at.starts_with(path))
+        }))
+    }
+
     /// Count online users.
     pub fn online_count(&self) -> usize {
         self.online_users().count()
@@ -379,93 +962,260 @@ impl PresenceTracker {
         self.pending_delta.take()
     }
 
+    /// Presence records to send a late joiner.
+    ///
+    /// Holds every device of the active and recently departed users;
+    /// users past the retention window are left out even before they are
+    /// dropped.
+    pub fn snapshot(&self) -> PresenceDelta {
+        let now = self.clock.now_millis();
+        let mut updates: Vec<_> = self
+            .users
+            .values()
+            .filter(|devices| self.is_retained(devices, now))
+            .flat_map(|devices| devices.values().cloned())
+            .collect();
+        updates.sort_by(|a, b| (&a.user_id, &a.device_id).cmp(&(&b.user_id, &b.device_id)));
+        PresenceDelta {
+            updates,
+            removals: Vec::new(),
+        }
+    }
+
+    /// Store a device's presence unless we hold a newer record for it,
+    /// merging its user information either way.
+    ///
+    /// Records of a dropped user from before they departed are ignored; a
+    /// later one starts the user afresh.
+    fn insert_presence(&mut self, presence: &UserPresence) {
+        if let Some(&departed) = self.tombstones.get(&presence.user_id) {
+            if presence.last_updated <= departed {
+                return;
+            }
+            self.tombstones.remove(&presence.user_id);
+        }
+        self.update_user_info(&presence.user_id, &presence.info);
+
+        let devices = self.users.entry(presence.user_id.clone()).or_default();
+        // Don't overwrite with older data
+        if devices
+            .get(&presence.device_id)
+            .is_some_and(|existing| presence.timestamp <= existing.timestamp)
+        {
+            return;
+        }
+        let mut presence = presence.clone();
+        presence.info = self.infos[&presence.user_id].clone();
+        devices.insert(presence.device_id.clone(), presence);
+    }
+
+    /// Remove a device's presence, dropping the user once no devices remain.
+    fn remove_device(&mut self, user_id: &UserId, device_id: &DeviceId) {
+        if let Some(devices) = self.users.get_mut(user_id) {
+            devices.remove(device_id);
+            if devices.is_empty() {
+                self.users.remove(user_id);
+                self.infos.remove(user_id);
+            }
+        }
+    }
+
     /// Apply a delta from another replica.
     pub fn apply_delta(&mut self, delta: &PresenceDelta) {
         // Apply updates
         for presence in &delta.updates {
-            // Don't overwrite with older data
-            if let Some(existing) = self.users.get(&presence.user_id) {
-                if presence.timestamp <= existing.timestamp {
-                    continue;
-                }
-            }
-            self.users
-                .insert(presence.user_id.clone(), presence.clone());
+            self.insert_presence(presence);
         }
 
-        // Apply removals
-        for user_id in &delta.removals {
-            if *user_id != self.local_user {
-                self.users.remove(user_id);
+        // Apply removals, unless the device has been heard from since
+        for removal in &delta.removals {
+            if removal.user_id == self.local_user && removal.device_id == self.local_device {
+                continue;
+            }
+            let superseded = self
+                .get_device(&removal.user_id, &removal.device_id)
+                .is_some_and(|p| p.timestamp <= removal.timestamp);
+            if superseded {
+                self.remove_device(&removal.user_id, &removal.device_id);
             }
         }
+
+        self.retire_departed();
     }
 
-    /// Clean up stale presence records.
+    /// Drop departed users outside the retention limits.
+    ///
+    /// Users departed for longer than the retention window go first, then
+    /// the earliest departed (ties broken by user ID) until at most
+    /// `max_users` remain. The local user is always kept. Returns the
+    /// dropped users.
+    fn retire_departed(&mut self) -> Vec<UserId> {
+        let now = self.clock.now_millis();
+        let tombstone_lifetime = self.config.departed_retention_ms + self.stale_timeout;
+        self.tombstones
+            .retain(|_, departed| now.saturating_sub(*departed) <= tombstone_lifetime);
+
+        let mut departed: Vec<(u64, UserId)> = self
+            .users
+            .iter()
+            .filter(|(user_id, _)| **user_id != self.local_user)
+            .filter_map(|(user_id, devices)| {
+                Some((self.departed_at(devices, now)?, user_id.clone()))
+            })
+            .collect();
+        departed.sort();
+
+        let expired = departed
+            .iter()
+            .take_while(|(at, _)| now.saturating_sub(*at) > self.config.departed_retention_ms)
+            .count();
+        let excess = self.users.len().saturating_sub(self.config.max_users);
+        let count = expired.max(excess).min(departed.len());
+
+        departed
+            .drain(..count)
+            .map(|(at, user_id)| {
+                self.users.remove(&user_id);
+                self.infos.remove(&user_id);
+                self.tombstones.insert(user_id.clone(), at);
+                user_id
+            })
+            .collect()
+    }
+
+    /// Clean up stale presence records and departed users outside the
+    /// retention limits.
+    ///
+    /// Returns the users left with no devices.
     pub fn cleanup_stale(&mut self) -> Vec<UserId> {
+        let now = self.clock.now_millis();
         let stale: Vec<_> = self
             .users
-            .iter()
-            .filter(|(id, p)| *id != &self.local_user && p.is_stale(self.stale_timeout))
-            .map(|(id, _)| id.clone())
+            .values()
+            .flat_map(|devices| devices.values())
+            .filter(|p| {
+                (p.user_id != self.local_user || p.device_id != self.local_device)
+                    && !self.is_live(p, now)
+            })
+            .map(|p| PresenceRemoval {
+                user_id: p.user_id.clone(),
+                device_id: p.device_id.clone(),
+                timestamp: p.timestamp,
+            })
             .collect();
 
-        for id in &stale {
-            self.users.remove(id);
+        let mut departed = Vec::new();
+        for removal in &stale {
+            self.remove_device(&removal.user_id, &removal.device_id);
+            if !self.users.contains_key(&removal.user_id) && !departed.contains(&removal.user_id) {
+                departed.push(removal.user_id.clone());
+            }
         }
 
         if !stale.is_empty() {
             let delta = self.pending_delta.get_or_insert_with(PresenceDelta::new);
-            delta.removals.extend(stale.clone());
+            delta.removals.extend(stale);
+        }
+
+        departed.extend(self.retire_departed());
+        departed
+    }
+
+    /// Report users who went away or came back since the last call, then
+    /// expire stale devices as [`cleanup_stale`](Self::cleanup_stale) does
+    /// and report the users that left.
+    ///
+    /// Statuses follow the last update times in the records, not when they
+    /// arrived, so replicas holding the same records agree on who is away
+    /// and who has left at the same clock time. The local user is never
+    /// reported.
+    pub fn expire_stale(&mut self) -> Vec<PresenceEvent> {
+        let mut users: Vec<UserId> = self
+            .users
+            .keys()
+            .filter(|user_id| **user_id != self.local_user)
+            .cloned()
+            .collect();
+        users.sort();
+
+        let mut events = Vec::new();
+        for user_id in users {
+            match self.user_status(&user_id) {
+                UserStatus::Away => {
+                    if self.away.insert(user_id.clone()) {
+                        events.push(PresenceEvent::Away(user_id));
+                    }
+                }
+                UserStatus::Offline => {}
+                _ => {
+                    if self.away.remove(&user_id) {
+                        events.push(PresenceEvent::Active(user_id));
+                    }
+                }
+            }
         }
 
-        stale
+        events.extend(self.cleanup_stale().into_iter().map(PresenceEvent::Left));
+        let users = &self.users;
+        self.away.retain(|user_id| users.contains_key(user_id));
+        events
     }
 
-    /// Leave (remove local user).
+    /// Leave (remove the local device).
     pub fn leave(&mut self) {
+        let timestamp = self.local_presence().map_or(0, |p| p.timestamp);
         let delta = self.pending_delta.get_or_insert_with(PresenceDelta::new);
-        delta.removals.push(self.local_user.clone());
+        delta.removals.push(PresenceRemoval {
+            user_id: self.local_user.clone(),
+            device_id: self.local_device.clone(),
+            timestamp,
+        });
+    }
+}
+
+/// Keep the first record of each user.
+fn dedup_users<'a>(presences: impl Iterator<Item = &'a UserPresence>) -> Vec<&'a UserPresence> {
+    let mut seen = Vec::new();
+    let mut result = Vec::new();
+    for presence in presences {
+        if !seen.contains(&&presence.user_id) {
+            seen.push(&presence.user_id);
+            result.push(presence);
+        }
     }
+    result
 }
 
 impl Lattice for PresenceTracker {
     fn bottom() -> Self {
         Self {
             local_user: UserId::new(""),
+            local_device: DeviceId::default(),
             users: HashMap::new(),
+            infos: HashMap::new(),
             stale_timeout: 30_000,
+            away_timeout: 15_000,
+            away: HashSet::new(),
             pending_delta: None,
+            clock: SharedClock::default(),
+            config: PresenceConfig::default(),
+            tombstones: HashMap::new(),
         }
     }
 
     fn join(&self, other: &Self) -> Self {
         let mut result = self.clone();
 
-        for (user_id, other_presence) in &other.users {
-            result
-                .users
-                .entry(user_id.clone())
-                .and_modify(|p| {
-                    if other_presence.timestamp > p.timestamp {
-                        *p = other_presence.clone();
-                    }
-                })
-                .or_insert_with(|| other_presence.clone());
+        for devices in other.users.values() {
+            for presence in devices.values() {
+                result.insert_presence(presence);
+            }
         }
-
+        result.retire_departed();
         result
     }
 }
 
-/// Get current time in milliseconds.
-fn now_millis() -> u64 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64
-}
-
 /// Builder for creating cursors from selections.
 pub struct CursorBuilder {
     document_id: String,
@@ -485,6 +1235,10 @@ impl CursorBuilder {
     pub fn selection(self, anchor: usize, head: usize) -> (String, Cursor) {
         (self.document_id, Cursor::with_selection(anchor, head))
     }
+
+    pub fn location(self, location: CursorLocation) -> (String, Cursor) {
+        (self.document_id, Cursor::at_location(location))
+    }
 }
 
 /// Color palette for user cursors.
@@ -516,6 +1270,9 @@ impl CursorColors {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::json_crdt::JsonValue;
+    use mdcs_core::clock::ManualClock;
+    use std::sync::Arc;
 
     #[test]
     fn test_cursor_creation() {
@@ -592,6 +1349,102 @@ mod tests {
         assert_eq!(users[0].user_id, user1);
     }
 
+    #[test]
+    fn test_stale_presence_expires() {
+        let clock = Arc::new(ManualClock::new(0));
+        let mut tracker1 =
+            PresenceTracker::new(UserId::new("user1"), UserInfo::new("Alice", "#E91E63"));
+        let mut tracker2 =
+            PresenceTracker::new(UserId::new("user2"), UserInfo::new("Bob", "#2196F3"));
+        tracker1.set_clock(clock.clone());
+        tracker2.set_clock(clock.clone());
+        tracker2.set_stale_timeout(1_000);
+
+        tracker1.set_cursor("doc1", Cursor::at(3));
+        tracker2.apply_delta(&tracker1.take_delta().unwrap());
+        assert_eq!(tracker2.cursors_in_document("doc1").len(), 1);
+
+        // A heartbeat inside the timeout keeps the user online
+        clock.advance(800);
+        tracker1.heartbeat();
+        tracker2.apply_delta(&tracker1.take_delta().unwrap());
+        clock.advance(800);
+        assert_eq!(tracker2.cursors_in_document("doc1").len(), 1);
+        assert!(tracker2.cleanup_stale().is_empty());
+
+        clock.advance(201);
+        assert!(tracker2.cursors_in_document("doc1").is_empty());
+        assert_eq!(tracker2.cleanup_stale(), vec![UserId::new("user1")]);
+        assert_eq!(tracker2.take_delta().unwrap().removals.len(), 1);
+    }
+
+    #[test]
+    fn test_silent_user_goes_away_then_leaves() {
+        let clock = Arc::new(ManualClock::new(1_000));
+        let alice = UserId::new("alice");
+        let mut laptop = PresenceTracker::new(alice.clone(), UserInfo::new("Alice", "#E91E63"));
+        let mut observers = [
+            PresenceTracker::new(UserId::new("bob"), UserInfo::new("Bob", "#2196F3")),
+            PresenceTracker::new(UserId::new("carol"), UserInfo::new("Carol", "#4CAF50")),
+        ];
+        laptop.set_clock(clock.clone());
+        laptop.heartbeat();
+        let first = laptop.take_delta().unwrap();
+        for observer in &mut observers {
+            observer.set_clock(clock.clone());
+            observer.set_away_timeout(500);
+            observer.set_stale_timeout(1_000);
+            observer.apply_delta(&first);
+        }
+
+        // The heartbeats stop: both replicas see Alice away, then gone
+        clock.advance(600);
+        for observer in &mut observers {
+            assert_eq!(observer.user_status(&alice), UserStatus::Away);
+            assert_eq!(
+                observer.expire_stale(),
+                vec![PresenceEvent::Away(alice.clone())]
+            );
+            assert!(observer.expire_stale().is_empty());
+        }
+
+        // A heartbeat sent before she went quiet, merged late, changes nothing
+        let [early, late] = &mut observers;
+        early.apply_delta(&first);
+        assert!(early.expire_stale().is_empty());
+
+        // One from after brings her back to active
+        laptop.heartbeat();
+        late.apply_delta(&laptop.take_delta().unwrap());
+        assert_eq!(late.user_status(&alice), UserStatus::Online);
+        assert_eq!(
+            late.expire_stale(),
+            vec![PresenceEvent::Active(alice.clone())]
+        );
+
+        clock.advance(501);
+        assert_eq!(
+            early.expire_stale(),
+            vec![PresenceEvent::Left(alice.clone())]
+        );
+        assert!(early.get_user(&alice).is_none());
+        assert_eq!(
+            late.expire_stale(),
+            vec![PresenceEvent::Away(alice.clone())]
+        );
+        clock.advance(500);
+        assert_eq!(
+            late.expire_stale(),
+            vec![PresenceEvent::Left(alice.clone())]
+        );
+
+        // A heartbeat after she was dropped starts her afresh
+        laptop.heartbeat();
+        early.apply_delta(&laptop.take_delta().unwrap());
+        assert_eq!(early.user_status(&alice), UserStatus::Online);
+        assert!(early.expire_stale().is_empty());
+    }
+
     #[test]
     fn test_multiple_users() {
         let user1 = UserId::new("user1");
@@ -601,11 +1454,11 @@ mod tests {
         // Simulate other users joining
         let user2 = UserId::new("user2");
         let presence2 = UserPresence::new(user2.clone(), UserInfo::new("Bob", "#2196F3"));
-        tracker.users.insert(user2.clone(), presence2);
+        tracker.insert_presence(&presence2);
 
         let user3 = UserId::new("user3");
         let presence3 = UserPresence::new(user3.clone(), UserInfo::new("Charlie", "#4CAF50"));
-        tracker.users.insert(user3.clone(), presence3);
+        tracker.insert_presence(&presence3);
 
         assert_eq!(tracker.online_count(), 3);
     }
@@ -620,7 +1473,7 @@ mod tests {
         let user2 = UserId::new("user2");
         let mut presence2 = UserPresence::new(user2.clone(), UserInfo::new("Bob", "#2196F3"));
         presence2.set_cursor("doc1", Cursor::at(50));
-        tracker.users.insert(user2, presence2);
+        tracker.insert_presence(&presence2);
 
         // Get cursors (excluding local user)
         let cursors = tracker.cursors_in_document("doc1");
@@ -644,6 +1497,28 @@ mod tests {
         assert_eq!(color1, CursorColors::color_for_user(&user1));
     }
 
+    #[test]
+    fn test_viewport_sync() {
+        let user1 = UserId::new("user1");
+        let user2 = UserId::new("user2");
+
+        let mut tracker1 = PresenceTracker::new(user1.clone(), UserInfo::new("Alice", "#E91E63"));
+        let mut tracker2 = PresenceTracker::new(user2, UserInfo::new("Bob", "#2196F3"));
+
+        tracker1.set_viewport("doc1", Viewport::at_offset(120, 40).with_zoom(150));
+
+        let delta = tracker1.take_delta().unwrap();
+        tracker2.apply_delta(&delta);
+
+        let viewport = tracker2
+            .get_user(&user1)
+            .and_then(|p| p.get_viewport("doc1"))
+            .unwrap();
+        assert_eq!(viewport.anchor, ViewportAnchor::Offset(120));
+        assert_eq!(viewport.extent, 40);
+        assert_eq!(viewport.zoom, 150);
+    }
+
     #[test]
     fn test_custom_state() {
         let user_id = UserId::new("user1");
@@ -668,4 +1543,374 @@ mod tests {
         assert_eq!(doc, "doc2");
         assert_eq!(cursor.selection_range(), Some((10, 20)));
     }
+
+    #[test]
+    fn test_users_at_path() {
+        let mut tracker1 =
+            PresenceTracker::new(UserId::new("user1"), UserInfo::new("Alice", "#E91E63"));
+        let mut tracker2 =
+            PresenceTracker::new(UserId::new("user2"), UserInfo::new("Bob", "#2196F3"));
+        let mut observer =
+            PresenceTracker::new(UserId::new("user3"), UserInfo::new("Carol", "#4CAF50"));
+
+        let (doc, cursor) = CursorBuilder::for_document("board").location(
+            CursorLocation::JsonPath(JsonPath::parse("columns.todo.title")),
+        );
+        tracker1.set_cursor(doc, cursor);
+        tracker2.set_cursor(
+            "board",
+            Cursor::at_location(CursorLocation::JsonPath(JsonPath::parse("columns.done"))),
+        );
+        observer.apply_delta(&tracker1.take_delta().unwrap());
+        observer.apply_delta(&tracker2.take_delta().unwrap());
+
+        let names = |users: Vec<&UserPresence>| {
+            let mut names: Vec<_> = users.iter().map(|p| p.info.name.clone()).collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            names(observer.users_at_path("board", &JsonPath::parse("columns"))),
+            vec!["Alice", "Bob"]
+        );
+        assert_eq!(
+            names(observer.users_at_path("board", &JsonPath::parse("columns.done"))),
+            vec!["Bob"]
+        );
+        assert!(observer
+            .users_at_path("board", &JsonPath::parse("columns.doing"))
+            .is_empty());
+        assert!(observer
+            .users_at_path("other", &JsonPath::root())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_deleted_list_element_falls_back_to_array() {
+        let mut doc = JsonCrdt::new("r1");
+        let cards = doc.set_array(&JsonPath::parse("columns.todo")).unwrap();
+        for title in ["a", "b", "c"] {
+            doc.array_push(&cards, JsonValue::String(title.into()))
+                .unwrap();
+        }
+        let element = doc.array_element_id(&cards, 1).unwrap();
+        let location = CursorLocation::ListElement {
+            array_id: cards.clone(),
+            element,
+        };
+
+        let mut tracker =
+            PresenceTracker::new(UserId::new("user1"), UserInfo::new("Alice", "#E91E63"));
+        tracker.set_cursor("board", Cursor::at_location(location.clone()));
+        assert_eq!(
+            location.resolve_path(&doc),
+            Some(JsonPath::parse("columns.todo.1"))
+        );
+
+        // An element inserted before it moves the cursor along
+        doc.array_insert(&cards, 0, JsonValue::Null).unwrap();
+        assert_eq!(
+            location.resolve_path(&doc),
+            Some(JsonPath::parse("columns.todo.2"))
+        );
+
+        doc.array_remove(&cards, 2).unwrap();
+        assert_eq!(
+            location.resolve_path(&doc),
+            Some(JsonPath::parse("columns.todo"))
+        );
+        let at_column = tracker.users_at_path_in("board", &JsonPath::parse("columns.todo"), &doc);
+        assert_eq!(at_column.len(), 1);
+        assert!(tracker
+            .users_at_path_in("board", &JsonPath::parse("columns.todo.2"), &doc)
+            .is_empty());
+    }
+
+    /// Trackers for two devices of one user and an observer, on one clock.
+    fn two_devices(clock: &Arc<ManualClock>) -> [PresenceTracker; 3] {
+        let alice = UserId::new("alice");
+        let info = UserInfo::new("Alice", "#E91E63");
+        let mut laptop =
+            PresenceTracker::new(alice.clone(), info.clone()).with_device(DeviceId::new("laptop"));
+        let mut phone = PresenceTracker::new(alice, info).with_device(DeviceId::new("phone"));
+        let mut observer =
+            PresenceTracker::new(UserId::new("bob"), UserInfo::new("Bob", "#2196F3"));
+        for tracker in [&mut laptop, &mut phone, &mut observer] {
+            tracker.set_clock(clock.clone());
+            tracker.set_stale_timeout(1_000);
+        }
+
+        laptop.heartbeat();
+        phone.heartbeat();
+        let from_laptop = laptop.take_delta().unwrap();
+        let from_phone = phone.take_delta().unwrap();
+        phone.apply_delta(&from_laptop);
+        laptop.apply_delta(&from_phone);
+        observer.apply_delta(&from_laptop);
+        observer.apply_delta(&from_phone);
+        [laptop, phone, observer]
+    }
+
+    #[test]
+    fn test_rename_survives_stale_heartbeat() {
+        let clock = Arc::new(ManualClock::new(1_000));
+        let [mut laptop, mut phone, mut observer] = two_devices(&clock);
+        let alice = UserId::new("alice");
+        assert_eq!(observer.devices(&alice).len(), 2);
+
+        // The laptop renames while the phone heartbeats with the old name
+        clock.advance(100);
+        laptop.set_user_info(UserInfo::new("Alice Smith", "#E91E63"));
+        let rename = laptop.take_delta().unwrap();
+        clock.advance(100);
+        phone.heartbeat();
+        let heartbeat = phone.take_delta().unwrap();
+        assert_eq!(heartbeat.updates[0].info.name, "Alice");
+
+        observer.apply_delta(&rename);
+        observer.apply_delta(&heartbeat);
+        laptop.apply_delta(&heartbeat);
+        phone.apply_delta(&rename);
+
+        for tracker in [&laptop, &phone, &observer] {
+            assert_eq!(tracker.user_info(&alice).unwrap().name, "Alice Smith");
+            assert_eq!(tracker.get_user(&alice).unwrap().info.name, "Alice Smith");
+        }
+
+        // The phone's next heartbeat carries the new name, and a replica
+        // that only saw the old heartbeat picks up the rename in any order
+        phone.heartbeat();
+        let mut late =
+            PresenceTracker::new(UserId::new("carol"), UserInfo::new("Carol", "#4CAF50"));
+        late.set_clock(clock.clone());
+        late.apply_delta(&phone.take_delta().unwrap());
+        late.apply_delta(&heartbeat);
+        assert_eq!(late.user_info(&alice).unwrap().name, "Alice Smith");
+
+        // Concurrent changes to different fields both survive
+        laptop.set_user_info(UserInfo::new("Alice S.", "#E91E63"));
+        phone.set_user_info(UserInfo::new("Alice Smith", "#009688"));
+        observer.apply_delta(&phone.take_delta().unwrap());
+        observer.apply_delta(&laptop.take_delta().unwrap());
+        let info = observer.user_info(&alice).unwrap();
+        assert_eq!(
+            (info.name.as_str(), info.color.as_str()),
+            ("Alice S.", "#009688")
+        );
+    }
+
+    #[test]
+    fn test_stale_device_keeps_user_online() {
+        let clock = Arc::new(ManualClock::new(1_000));
+        let [mut laptop, mut phone, mut observer] = two_devices(&clock);
+        let alice = UserId::new("alice");
+
+        // Only the phone stays active
+        for _ in 0..3 {
+            clock.advance(400);
+            phone.set_status(UserStatus::Typing);
+            observer.apply_delta(&phone.take_delta().unwrap());
+            observer.heartbeat();
+        }
+        assert_eq!(observer.devices(&alice), vec![DeviceId::new("phone")]);
+        assert_eq!(observer.user_status(&alice), UserStatus::Typing);
+        assert_eq!(observer.online_count(), 2);
+
+        // Expiring the laptop does not take the user offline
+        observer.take_delta();
+        assert!(observer.cleanup_stale().is_empty());
+        let expiry = observer.take_delta().unwrap();
+        assert_eq!(expiry.removals.len(), 1);
+        assert_eq!(expiry.removals[0].device_id, DeviceId::new("laptop"));
+        assert_eq!(observer.user_status(&alice), UserStatus::Typing);
+
+        // A replica that has heard from the laptop since keeps it
+        laptop.heartbeat();
+        phone.apply_delta(&laptop.take_delta().unwrap());
+        phone.apply_delta(&expiry);
+        assert_eq!(phone.devices(&alice).len(), 2);
+
+        // An explicitly offline device yields to a live one
+        laptop.set_status(UserStatus::Offline);
+        phone.apply_delta(&laptop.take_delta().unwrap());
+        assert_eq!(phone.user_status(&alice), UserStatus::Typing);
+        assert_eq!(
+            phone.get_user(&alice).unwrap().device_id,
+            DeviceId::new("phone")
+        );
+    }
+
+    /// A visitor who heartbeats once and goes offline, as two deltas.
+    fn visit(clock: &Arc<ManualClock>, id: &str) -> [PresenceDelta; 2] {
+        let mut visitor = PresenceTracker::new(UserId::new(id), UserInfo::new(id, "#4CAF50"));
+        visitor.set_clock(clock.clone());
+        visitor.heartbeat();
+        let joined = visitor.take_delta().unwrap();
+        clock.advance(5);
+        visitor.set_status(UserStatus::Offline);
+        [joined, visitor.take_delta().unwrap()]
+    }
+
+    fn observer(clock: &Arc<ManualClock>, config: PresenceConfig) -> PresenceTracker {
+        let mut tracker = PresenceTracker::new(UserId::new("host"), UserInfo::new("Host", "#000"));
+        tracker.set_clock(clock.clone());
+        tracker.set_config(config);
+        tracker
+    }
+
+    fn user_ids(tracker: &PresenceTracker) -> Vec<UserId> {
+        let mut ids: Vec<_> = tracker.users.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_departed_users_are_bounded() {
+        let clock = Arc::new(ManualClock::new(1_000));
+        let config = PresenceConfig {
+            max_users: 50,
+            departed_retention_ms: 10_000,
+        };
+        let mut host = observer(&clock, config);
+        let mut regulars: Vec<_> = (0..10)
+            .map(|i| {
+                let name = format!("regular{}", i);
+                let mut tracker =
+                    PresenceTracker::new(UserId::new(&name), UserInfo::new(&name, ""));
+                tracker.set_clock(clock.clone());
+                tracker
+            })
+            .collect();
+
+        for i in 0..1_000 {
+            for delta in visit(&clock, &format!("visitor{}", i)) {
+                host.apply_delta(&delta);
+            }
+            for regular in &mut regulars {
+                regular.heartbeat();
+                host.apply_delta(&regular.take_delta().unwrap());
+            }
+            assert!(host.snapshot().updates.len() <= 50);
+        }
+
+        // The host, the regulars and the latest visitors remain
+        let ids = user_ids(&host);
+        assert_eq!(ids.len(), 50);
+        assert!(regulars.iter().all(|r| ids.contains(r.local_user())));
+        assert!(ids.contains(&UserId::new("visitor999")));
+        assert!(!ids.contains(&UserId::new("visitor960")));
+
+        // Departed users leave the snapshot once past the retention window,
+        // and are dropped on the next cleanup
+        clock.advance(10_001);
+        for regular in &mut regulars {
+            regular.heartbeat();
+            host.apply_delta(&regular.take_delta().unwrap());
+        }
+        assert_eq!(host.snapshot().updates.len(), 11);
+        assert!(host.cleanup_stale().is_empty());
+        assert_eq!(host.users.len(), 11);
+    }
+
+    #[test]
+    fn test_evicted_user_rejoins_fresh() {
+        let clock = Arc::new(ManualClock::new(1_000));
+        let mut host = observer(
+            &clock,
+            PresenceConfig {
+                max_users: 10,
+                departed_retention_ms: 1_000,
+            },
+        );
+        let alice = UserId::new("alice");
+        let mut session = PresenceTracker::new(alice.clone(), UserInfo::new("Alice", "#E91E63"));
+        session.set_clock(clock.clone());
+        session.set_cursor("doc1", Cursor::at(3));
+        session.set_user_info(UserInfo::new("Alice (old)", "#E91E63"));
+        let delayed = session.take_delta().unwrap();
+        clock.advance(10);
+        session.set_status(UserStatus::Offline);
+        host.apply_delta(&delayed);
+        host.apply_delta(&session.take_delta().unwrap());
+
+        clock.advance(1_001);
+        host.cleanup_stale();
+        assert!(host.user_info(&alice).is_none());
+
+        // A heartbeat sent before departing doesn't bring back part of her
+        host.apply_delta(&delayed);
+        assert!(host.get_user(&alice).is_none());
+        assert!(host.user_info(&alice).is_none());
+
+        // A new session starts from a clean entry
+        clock.advance(10);
+        let mut session = PresenceTracker::new(alice.clone(), UserInfo::new("Alice", "#E91E63"));
+        session.set_clock(clock.clone());
+        session.heartbeat();
+        host.apply_delta(&session.take_delta().unwrap());
+        let presence = host.get_user(&alice).unwrap();
+        assert_eq!(presence.status, UserStatus::Online);
+        assert!(presence.cursors.is_empty());
+        assert_eq!(host.user_info(&alice).unwrap().name, "Alice");
+    }
+
+    #[test]
+    fn test_restarted_device_overrides_its_last_session() {
+        let clock = Arc::new(ManualClock::new(1_000));
+        let mut host = observer(&clock, PresenceConfig::default());
+        let alice = UserId::new("alice");
+        let mut session = PresenceTracker::new(alice.clone(), UserInfo::new("Alice", "#E91E63"));
+        session.set_clock(clock.clone());
+        for position in 0..20 {
+            session.set_cursor("doc1", Cursor::at(position));
+        }
+        host.apply_delta(&session.take_delta().unwrap());
+
+        // The same device comes back without the old session's counter
+        clock.advance(100);
+        let mut session = PresenceTracker::new(alice.clone(), UserInfo::new("Alice", "#E91E63"));
+        session.set_clock(clock.clone());
+        session.set_cursor("doc1", Cursor::at(3));
+        host.apply_delta(&session.take_delta().unwrap());
+        let presence = host.get_user(&alice).unwrap();
+        assert_eq!(presence.get_cursor("doc1").unwrap().position, 3);
+    }
+
+    #[test]
+    fn test_replicas_retain_the_same_users() {
+        use rand::rngs::StdRng;
+        use rand::seq::SliceRandom;
+        use rand::SeedableRng;
+
+        let clock = Arc::new(ManualClock::new(1_000));
+        let config = PresenceConfig {
+            max_users: 20,
+            departed_retention_ms: 2_000,
+        };
+        let mut a = observer(&clock, config.clone());
+        let mut b = observer(&clock, config);
+        let mut rng = StdRng::seed_from_u64(11);
+
+        for batch in 0..40 {
+            // Replicas see each batch in a different order
+            let mut deltas: Vec<_> = (0..10)
+                .flat_map(|i| visit(&clock, &format!("visitor{}-{}", batch, i)))
+                .collect();
+            for delta in &deltas {
+                a.apply_delta(delta);
+            }
+            deltas.shuffle(&mut rng);
+            for delta in &deltas {
+                b.apply_delta(delta);
+            }
+            assert_eq!(user_ids(&a), user_ids(&b));
+            if batch % 10 == 0 {
+                clock.advance(2_500);
+                a.cleanup_stale();
+                b.cleanup_stale();
+                assert_eq!(user_ids(&a), user_ids(&b));
+            }
+        }
+        assert!(a.users.len() <= 20);
+    }
 }
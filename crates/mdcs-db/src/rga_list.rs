@@ -3,13 +3,24 @@
 //! RGA provides a CRDT list that supports:
 //! - Insert at any position
 //! - Delete at any position
-//! - Move elements (delete + insert)
+//! - Move elements, keeping their identity
 //!
 //! Uses unique IDs to maintain consistent ordering across replicas.
-
+//!
+//! # Moves
+//!
+//! A move doesn't delete and re-insert the element. It inserts an empty
+//! *slot* node at the new position and points the element at it; the
+//! element keeps its [`ListId`] and is shown at whichever of its slots has
+//! the greatest ID, so concurrent moves of the same element resolve
+//! last-writer-wins and the element is never duplicated. Slots an element
+//! has moved away from stay in the tree like tombstones. A deleted element
+//! stays deleted, wherever a concurrent move put it.
+
+use mdcs_compaction::VersionVector;
 use mdcs_core::lattice::Lattice;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use ulid::Ulid;
 
 /// Unique identifier for a list element.
@@ -83,6 +94,17 @@ impl<T> ListNode<T> {
     }
 }
 
+/// A move of an element to a new slot.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListMove {
+    /// The element being moved.
+    pub element: ListId,
+    /// The slot created for it; the greatest slot of an element wins.
+    pub slot: ListId,
+    /// The slot the new one was inserted after.
+    pub origin: ListId,
+}
+
 /// Delta for RGA list operations.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct RGAListDelta<T: Clone + PartialEq> {
@@ -90,6 +112,9 @@ pub struct RGAListDelta<T: Clone + PartialEq> {
     pub inserts: Vec<ListNode<T>>,
     /// IDs of nodes to delete.
     pub deletes: Vec<ListId>,
+    /// Elements moved to new slots.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub moves: Vec<ListMove>,
 }
 
 impl<T: Clone + PartialEq> RGAListDelta<T> {
@@ -97,11 +122,12 @@ impl<T: Clone + PartialEq> RGAListDelta<T> {
         Self {
             inserts: Vec::new(),
             deletes: Vec::new(),
+            moves: Vec::new(),
         }
     }
 
     pub fn is_empty(&self) -> bool {
-        self.inserts.is_empty() && self.deletes.is_empty()
+        self.inserts.is_empty() && self.deletes.is_empty() && self.moves.is_empty()
     }
 }
 
@@ -111,17 +137,63 @@ impl<T: Clone + PartialEq> Default for RGAListDelta<T> {
     }
 }
 
+/// Deltas compose by union: applying `a.join(&b)` has the same effect as
+/// applying `a` then `b`.
+impl<T: Clone + PartialEq> Lattice for RGAListDelta<T> {
+    fn bottom() -> Self {
+        Self::new()
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        let mut joined = self.clone();
+        let inserted: HashSet<&ListId> = self.inserts.iter().map(|node| &node.id).collect();
+        joined.inserts.extend(
+            other
+                .inserts
+                .iter()
+                .filter(|node| !inserted.contains(&node.id))
+                .cloned(),
+        );
+        let deleted: HashSet<&ListId> = self.deletes.iter().collect();
+        joined.deletes.extend(
+            other
+                .deletes
+                .iter()
+                .filter(|id| !deleted.contains(id))
+                .cloned(),
+        );
+        let moved: HashSet<&ListId> = self.moves.iter().map(|m| &m.slot).collect();
+        joined.moves.extend(
+            other
+                .moves
+                .iter()
+                .filter(|m| !moved.contains(&m.slot))
+                .cloned(),
+        );
+        joined
+    }
+}
+
 /// Replicated Growable Array - an ordered list CRDT.
 ///
 /// Supports insert, delete, and move operations with
 /// deterministic conflict resolution.
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound(serialize = "T: Serialize", deserialize = "T: Deserialize<'de>"))]
 pub struct RGAList<T: Clone + PartialEq> {
     /// All nodes indexed by their ID.
+    #[serde(with = "crate::serde_map")]
     nodes: HashMap<ListId, ListNode<T>>,
     /// Children of each node (for ordering).
     /// Maps origin -> list of children sorted by ID.
+    #[serde(with = "crate::serde_map")]
     children: HashMap<ListId, Vec<ListId>>,
+    /// The element each slot created by a move positions.
+    #[serde(default, with = "crate::serde_map")]
+    slots: HashMap<ListId, ListId>,
+    /// The winning slot of each moved element.
+    #[serde(default, with = "crate::serde_map")]
+    positions: HashMap<ListId, ListId>,
     /// The replica ID for this instance.
     replica_id: String,
     /// Sequence counter for generating IDs.
@@ -138,6 +210,8 @@ impl<T: Clone + PartialEq> RGAList<T> {
         let mut list = Self {
             nodes: HashMap::new(),
             children: HashMap::new(),
+            slots: HashMap::new(),
+            positions: HashMap::new(),
             replica_id,
             seq: 0,
             pending_delta: None,
@@ -155,6 +229,28 @@ impl<T: Clone + PartialEq> RGAList<T> {
         &self.replica_id
     }
 
+    /// Highest sequence number seen from each replica, from the IDs of all
+    /// elements, deleted ones included.
+    pub fn version_vector(&self) -> VersionVector {
+        let mut vv = VersionVector::new();
+        for id in self.nodes.keys() {
+            vv.observe(&id.replica, id.seq);
+        }
+        vv
+    }
+
+    /// Copy this list for a new replica.
+    ///
+    /// The copy keeps all elements and tombstones but generates IDs under
+    /// `replica_id`.
+    pub fn fork(&self, replica_id: impl Into<String>) -> Self {
+        Self {
+            replica_id: replica_id.into(),
+            pending_delta: None,
+            ..self.clone()
+        }
+    }
+
     /// Generate a new unique ID.
     fn next_id(&mut self) -> ListId {
         self.seq += 1;
@@ -163,16 +259,18 @@ impl<T: Clone + PartialEq> RGAList<T> {
 
     /// Insert a value at the given index.
     pub fn insert(&mut self, index: usize, value: T) {
-        let origin = self
-            .id_at_index(index.saturating_sub(1))
-            .unwrap_or(ListId::genesis());
+        let origin = match index {
+            0 => ListId::genesis(),
+            _ => self.id_at_index(index - 1).unwrap_or(ListId::genesis()),
+        };
         self.insert_after(&origin, value);
     }
 
-    /// Insert a value after the given origin ID.
+    /// Insert a value after the given element, wherever it has moved.
     pub fn insert_after(&mut self, origin: &ListId, value: T) {
+        let origin = self.slot_of(origin).clone();
         let id = self.next_id();
-        let node = ListNode::new(id.clone(), value, origin.clone());
+        let node = ListNode::new(id.clone(), value, origin);
 
         self.integrate_node(node.clone());
 
@@ -215,15 +313,78 @@ impl<T: Clone + PartialEq> RGAList<T> {
         None
     }
 
-    /// Move an element from one index to another.
+    /// Move an element in front of the element currently at `to` (or to
+    /// the end if `to` is the length).
     pub fn move_element(&mut self, from: usize, to: usize) -> bool {
-        if let Some(value) = self.delete(from) {
-            // Adjust target index if moving forward
-            let adjusted_to = if to > from { to - 1 } else { to };
-            self.insert(adjusted_to, value);
-            true
-        } else {
-            false
+        // Adjust target index if moving forward
+        let adjusted_to = if to > from { to - 1 } else { to };
+        self.move_item(from, adjusted_to)
+    }
+
+    /// Move the element at `from_index` so that it ends up at `to_index`,
+    /// as `Vec::remove` followed by `Vec::insert` would.
+    ///
+    /// The element keeps its ID; see the [module docs](self) for how
+    /// concurrent moves resolve. Returns `false` if `from_index` is out of
+    /// bounds. A `to_index` past the end moves the element to the end.
+    pub fn move_item(&mut self, from_index: usize, to_index: usize) -> bool {
+        let Some(element) = self.id_at_index(from_index) else {
+            return false;
+        };
+        if from_index == to_index {
+            return true;
+        }
+
+        // The slot of the element that will precede it
+        let origin = match to_index {
+            0 => ListId::genesis(),
+            _ => self
+                .iter_visible()
+                .filter(|(_, node)| node.id != element)
+                .take(to_index)
+                .last()
+                .map(|(slot, _)| slot.id.clone())
+                .unwrap_or(ListId::genesis()),
+        };
+
+        let slot = self.next_id();
+        let record = ListMove {
+            element,
+            slot,
+            origin,
+        };
+        self.integrate_move(&record);
+
+        let delta = self.pending_delta.get_or_insert_with(RGAListDelta::new);
+        delta.moves.push(record);
+        true
+    }
+
+    /// Get the slot an element is shown at.
+    fn slot_of<'a>(&'a self, element: &'a ListId) -> &'a ListId {
+        self.positions.get(element).unwrap_or(element)
+    }
+
+    /// Record a move, creating its slot.
+    fn integrate_move(&mut self, record: &ListMove) {
+        self.seq = self.seq.max(record.slot.seq);
+        if self.slots.contains_key(&record.slot) {
+            return;
+        }
+        self.integrate_node(ListNode {
+            id: record.slot.clone(),
+            value: None,
+            origin: record.origin.clone(),
+            deleted: true,
+        });
+        self.slots
+            .insert(record.slot.clone(), record.element.clone());
+        let position = self
+            .positions
+            .entry(record.element.clone())
+            .or_insert_with(|| record.slot.clone());
+        if *position < record.slot {
+            *position = record.slot.clone();
         }
     }
 
@@ -251,9 +412,7 @@ impl<T: Clone + PartialEq> RGAList<T> {
 
     /// Iterate over values in order.
     pub fn iter(&self) -> impl Iterator<Item = &T> {
-        self.iter_nodes()
-            .filter(|n| !n.deleted)
-            .filter_map(|n| n.value.as_ref())
+        self.iter_visible().filter_map(|(_, n)| n.value.as_ref())
     }
 
     /// Iterate over (index, value) pairs.
@@ -267,18 +426,26 @@ impl<T: Clone + PartialEq> RGAList<T> {
     }
 
     /// Get the ID at a given visible index.
-    fn id_at_index(&self, index: usize) -> Option<ListId> {
-        self.iter_nodes()
-            .filter(|n| !n.deleted)
-            .nth(index)
-            .map(|n| n.id.clone())
+    pub fn id_at_index(&self, index: usize) -> Option<ListId> {
+        self.iter_visible().nth(index).map(|(_, n)| n.id.clone())
     }
 
     /// Get the visible index for an ID.
     pub fn index_of_id(&self, id: &ListId) -> Option<usize> {
-        self.iter_nodes()
-            .filter(|n| !n.deleted)
-            .position(|n| &n.id == id)
+        self.iter_visible().position(|(_, n)| &n.id == id)
+    }
+
+    /// Iterate over the non-deleted elements in order, each with the slot
+    /// it is shown at (the element's own node unless it was moved).
+    fn iter_visible(&self) -> impl Iterator<Item = (&ListNode<T>, &ListNode<T>)> {
+        self.iter_nodes().filter_map(|slot| {
+            let element = self.slots.get(&slot.id).unwrap_or(&slot.id);
+            if self.slot_of(element) != &slot.id {
+                return None;
+            }
+            let node = self.nodes.get(element)?;
+            (!node.deleted).then_some((slot, node))
+        })
     }
 
     /// Iterate over all nodes in order (including tombstones).
@@ -321,11 +488,17 @@ impl<T: Clone + PartialEq> RGAList<T> {
     pub fn apply_delta(&mut self, delta: &RGAListDelta<T>) {
         // Apply inserts
         for node in &delta.inserts {
+            self.seq = self.seq.max(node.id.seq);
             if !self.nodes.contains_key(&node.id) {
                 self.integrate_node(node.clone());
             }
         }
 
+        // Apply moves
+        for record in &delta.moves {
+            self.integrate_move(record);
+        }
+
         // Apply deletes
         for id in &delta.deletes {
             if let Some(node) = self.nodes.get_mut(id) {
@@ -387,8 +560,19 @@ impl<T: Clone + PartialEq> Lattice for RGAList<T> {
 
     fn join(&self, other: &Self) -> Self {
         let mut result = self.clone();
-
-        // Merge all nodes from other
+        // Keep new IDs ahead of everything already observed
+        result.seq = self.seq.max(other.seq);
+
+        // Merge all nodes from other, with the slots of its moves
+        for (slot, element) in &other.slots {
+            if let Some(node) = other.nodes.get(slot) {
+                result.integrate_move(&ListMove {
+                    element: element.clone(),
+                    slot: slot.clone(),
+                    origin: node.origin.clone(),
+                });
+            }
+        }
         for (id, node) in &other.nodes {
             if let Some(existing) = result.nodes.get_mut(id) {
                 // If deleted in either, mark as deleted
@@ -439,6 +623,9 @@ mod tests {
         list.insert(1, 2);
 
         assert_eq!(list.to_vec(), vec![1, 2, 3]);
+
+        list.insert(0, 0);
+        assert_eq!(list.to_vec(), vec![0, 1, 2, 3]);
     }
 
     #[test]
@@ -515,4 +702,97 @@ mod tests {
         let collected: Vec<_> = list.iter().cloned().collect();
         assert_eq!(collected, vec![1, 2, 3]);
     }
+
+    /// Two replicas holding the same cards.
+    fn synced(cards: &[&'static str]) -> (RGAList<&'static str>, RGAList<&'static str>) {
+        let mut r1 = RGAList::new("r1");
+        let mut r2 = RGAList::new("r2");
+        for card in cards {
+            r1.push_back(*card);
+        }
+        r2.apply_delta(&r1.take_delta().unwrap());
+        (r1, r2)
+    }
+
+    fn exchange(r1: &mut RGAList<&'static str>, r2: &mut RGAList<&'static str>) {
+        let d1 = r1.take_delta().unwrap();
+        let d2 = r2.take_delta().unwrap();
+        r1.apply_delta(&d2);
+        r2.apply_delta(&d1);
+    }
+
+    #[test]
+    fn test_move_item_keeps_identity() {
+        let mut list: RGAList<i32> = RGAList::new("r1");
+        for i in 1..=4 {
+            list.push_back(i);
+        }
+        let id = list.id_at_index(0).unwrap();
+
+        assert!(list.move_item(0, 2));
+        assert_eq!(list.to_vec(), vec![2, 3, 1, 4]);
+        assert_eq!(list.index_of_id(&id), Some(2));
+
+        assert!(list.move_item(2, 10));
+        assert_eq!(list.to_vec(), vec![2, 3, 4, 1]);
+        assert!(list.move_item(3, 0));
+        assert_eq!(list.to_vec(), vec![1, 2, 3, 4]);
+        assert_eq!(list.id_at_index(0), Some(id));
+        assert_eq!(list.len(), 4);
+        assert!(!list.move_item(4, 0));
+
+        let json = serde_json::to_string(&list).unwrap();
+        let restored: RGAList<i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.to_vec(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_concurrent_moves_of_same_card() {
+        let (mut r1, mut r2) = synced(&["a", "b", "c", "d"]);
+
+        r1.move_item(0, 3);
+        r2.move_item(0, 1);
+        let (s1, s2) = (r1.clone(), r2.clone());
+        exchange(&mut r1, &mut r2);
+
+        assert_eq!(r1.to_vec(), r2.to_vec());
+        assert_eq!(r1.len(), 4);
+        assert_eq!(r1.iter().filter(|c| **c == "a").count(), 1);
+        // r2's move has the greater slot ID and wins
+        assert_eq!(r1.to_vec(), vec!["b", "a", "c", "d"]);
+        assert_eq!(s1.join(&s2).to_vec(), r1.to_vec());
+        assert_eq!(s2.join(&s1).to_vec(), r1.to_vec());
+
+        // A later move supersedes both
+        r1.move_item(1, 3);
+        r2.apply_delta(&r1.take_delta().unwrap());
+        assert_eq!(r2.to_vec(), vec!["b", "c", "d", "a"]);
+    }
+
+    #[test]
+    fn test_concurrent_moves_past_each_other() {
+        let (mut r1, mut r2) = synced(&["a", "b", "c", "d"]);
+
+        r1.move_item(0, 3);
+        r2.move_item(3, 0);
+        exchange(&mut r1, &mut r2);
+
+        assert_eq!(r1.to_vec(), vec!["d", "b", "c", "a"]);
+        assert_eq!(r2.to_vec(), r1.to_vec());
+    }
+
+    #[test]
+    fn test_move_concurrent_with_delete() {
+        let (mut r1, mut r2) = synced(&["a", "b", "c", "d"]);
+
+        r1.move_item(1, 3);
+        r2.delete(1);
+        let (s1, s2) = (r1.clone(), r2.clone());
+        exchange(&mut r1, &mut r2);
+
+        assert_eq!(r1.to_vec(), vec!["a", "c", "d"]);
+        assert_eq!(r2.to_vec(), r1.to_vec());
+        assert_eq!(r1.len(), 3);
+        assert_eq!(s1.join(&s2).to_vec(), r1.to_vec());
+    }
 }
@@ -4,9 +4,11 @@
 //! - Insert at any position
 //! - Delete ranges
 //! - Stable position anchors for cursor sync
+//! - Position and line/column lookups in O(log n)
 //!
 //! Based on the RGA algorithm but optimized for text.
 
+use mdcs_compaction::VersionVector;
 use mdcs_core::lattice::Lattice;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
@@ -72,6 +74,10 @@ struct TextNode {
     origin: TextId,
     /// Whether this node is deleted (tombstone).
     deleted: bool,
+    /// The ID this node sorts by among its siblings, if not its own: that
+    /// of the compacted node it was inserted after.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key: Option<TextId>,
 }
 
 impl TextNode {
@@ -81,6 +87,7 @@ impl TextNode {
             char: Some(ch),
             origin,
             deleted: false,
+            key: None,
         }
     }
 }
@@ -92,6 +99,10 @@ pub struct RGATextDelta {
     pub inserts: Vec<(TextId, char, TextId)>, // (id, char, origin)
     /// IDs of characters to delete.
     pub deletes: Vec<TextId>,
+    /// Highest sequence number of each replica's edits in the delta,
+    /// including those that stamp no character ID.
+    #[serde(default)]
+    pub clock: VersionVector,
 }
 
 impl RGATextDelta {
@@ -99,9 +110,11 @@ impl RGATextDelta {
         Self {
             inserts: Vec::new(),
             deletes: Vec::new(),
+            clock: VersionVector::new(),
         }
     }
 
+    /// Whether the delta changes no characters; it may still carry a clock.
     pub fn is_empty(&self) -> bool {
         self.inserts.is_empty() && self.deletes.is_empty()
     }
@@ -113,24 +126,411 @@ impl Default for RGATextDelta {
     }
 }
 
+/// Deltas compose by union: applying `a.join(&b)` has the same effect as
+/// applying `a` then `b`.
+impl Lattice for RGATextDelta {
+    fn bottom() -> Self {
+        Self::new()
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        let mut joined = self.clone();
+        let inserted: HashSet<&TextId> = self.inserts.iter().map(|(id, _, _)| id).collect();
+        joined.inserts.extend(
+            other
+                .inserts
+                .iter()
+                .filter(|(id, _, _)| !inserted.contains(id))
+                .cloned(),
+        );
+        let deleted: HashSet<&TextId> = self.deletes.iter().collect();
+        joined.deletes.extend(
+            other
+                .deletes
+                .iter()
+                .filter(|id| !deleted.contains(id))
+                .cloned(),
+        );
+        joined.clock.merge(&other.clock);
+        joined
+    }
+}
+
+/// Receives the edits a merge made, from [`RGAText::join_observed`].
+///
+/// Edits come in document order with positions in characters, each
+/// relative to the text with the earlier edits already applied, so
+/// replaying them on the old text yields the merged text.
+pub trait TextObserver {
+    /// `text` was inserted at `position`.
+    fn on_insert(&mut self, position: usize, text: &str);
+
+    /// `length` characters were deleted at `position`.
+    fn on_delete(&mut self, position: usize, length: usize);
+}
+
+/// Marks an unlinked slot in [`OrderIndex`], or a missing tree link.
+const NIL: usize = usize::MAX;
+
+/// Visible characters and line breaks in a run of slots.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Counts {
+    chars: usize,
+    newlines: usize,
+}
+
+impl Counts {
+    fn of(ch: Option<char>) -> Self {
+        Self {
+            chars: ch.is_some() as usize,
+            newlines: (ch == Some('\n')) as usize,
+        }
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            chars: self.chars + other.chars,
+            newlines: self.newlines + other.newlines,
+        }
+    }
+}
+
+/// Document order of the nodes, kept alongside the tree.
+///
+/// Every node gets a slot; slots reachable from genesis (slot 0) are linked
+/// to their neighbours, tombstones included. Integrating a node next to its
+/// anchor is O(1) and ordered traversal is a walk over plain vectors.
+///
+/// The linked slots also form a treap in document order, each slot counting
+/// the visible characters and line breaks under it, so converting between
+/// positions and slots takes O(log n) instead of a walk from the start.
+/// Priorities are a hash of the slot, which keeps the shape deterministic.
+/// Derived state: never serialized, rebuilt with [`RGAText::rebuild_index`].
+#[derive(Clone, Debug)]
+struct OrderIndex {
+    /// Slot of each node.
+    slots: HashMap<TextId, usize>,
+    /// Node of each slot.
+    ids: Vec<TextId>,
+    /// Character of each slot, `None` once deleted.
+    chars: Vec<Option<char>>,
+    /// The slot after each slot, or `NIL` at the end.
+    next: Vec<usize>,
+    /// The slot before each slot, or `NIL` while unlinked.
+    prev: Vec<usize>,
+    /// Treap links of each slot, `NIL` where there is none.
+    left: Vec<usize>,
+    right: Vec<usize>,
+    parent: Vec<usize>,
+    /// Counts of the treap subtree under each slot.
+    below: Vec<Counts>,
+    /// Root of the treap.
+    root: usize,
+}
+
+impl Default for OrderIndex {
+    fn default() -> Self {
+        Self {
+            slots: HashMap::new(),
+            ids: vec![TextId::genesis()],
+            chars: vec![None],
+            next: vec![NIL],
+            prev: vec![NIL],
+            left: vec![NIL],
+            right: vec![NIL],
+            parent: vec![NIL],
+            below: vec![Counts::default()],
+            root: 0,
+        }
+    }
+}
+
+impl OrderIndex {
+    /// Allocate an unlinked slot for a node.
+    fn add(&mut self, node: &TextNode) -> usize {
+        let slot = self.ids.len();
+        self.slots.insert(node.id.clone(), slot);
+        self.ids.push(node.id.clone());
+        self.chars.push(if node.deleted { None } else { node.char });
+        self.next.push(NIL);
+        self.prev.push(NIL);
+        self.left.push(NIL);
+        self.right.push(NIL);
+        self.parent.push(NIL);
+        self.below.push(Counts::default());
+        slot
+    }
+
+    /// Slot of a node; genesis is always slot 0.
+    fn slot(&self, id: &TextId) -> Option<usize> {
+        if *id == TextId::genesis() {
+            return Some(0);
+        }
+        self.slots.get(id).copied()
+    }
+
+    /// Check whether a slot is reachable from genesis.
+    fn is_linked(&self, slot: usize) -> bool {
+        slot == 0 || self.prev[slot] != NIL
+    }
+
+    /// Number of visible characters in linked slots.
+    fn visible(&self) -> usize {
+        self.below[self.root].chars
+    }
+
+    /// Visible characters and line breaks in all linked slots.
+    fn total(&self) -> Counts {
+        self.below[self.root]
+    }
+
+    fn below(&self, slot: usize) -> Counts {
+        if slot == NIL {
+            Counts::default()
+        } else {
+            self.below[slot]
+        }
+    }
+
+    fn own(&self, slot: usize) -> Counts {
+        Counts::of(self.chars[slot])
+    }
+
+    fn priority(slot: usize) -> u64 {
+        // splitmix64 finalizer
+        let mut z = (slot as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Place an unlinked slot directly after a linked one.
+    fn link_after(&mut self, after: usize, slot: usize) {
+        let next = self.next[after];
+        if next != NIL {
+            self.prev[next] = slot;
+        }
+        self.next[slot] = next;
+        self.next[after] = slot;
+        self.prev[slot] = after;
+
+        // In the treap the slot becomes the leftmost leaf of the subtree
+        // right of `after`, then rises to its priority
+        let mut parent = self.right[after];
+        if parent == NIL {
+            self.right[after] = slot;
+            parent = after;
+        } else {
+            while self.left[parent] != NIL {
+                parent = self.left[parent];
+            }
+            self.left[parent] = slot;
+        }
+        self.parent[slot] = parent;
+        self.below[slot] = self.own(slot);
+        let added = self.own(slot);
+        let mut ancestor = parent;
+        while ancestor != NIL {
+            self.below[ancestor] = self.below[ancestor].add(added);
+            ancestor = self.parent[ancestor];
+        }
+        while self.parent[slot] != NIL && Self::priority(slot) > Self::priority(self.parent[slot]) {
+            self.rotate_up(slot);
+        }
+    }
+
+    /// Rotate a slot above its treap parent, keeping document order.
+    fn rotate_up(&mut self, slot: usize) {
+        let parent = self.parent[slot];
+        let grandparent = self.parent[parent];
+        if self.left[parent] == slot {
+            let inner = self.right[slot];
+            self.left[parent] = inner;
+            if inner != NIL {
+                self.parent[inner] = parent;
+            }
+            self.right[slot] = parent;
+        } else {
+            let inner = self.left[slot];
+            self.right[parent] = inner;
+            if inner != NIL {
+                self.parent[inner] = parent;
+            }
+            self.left[slot] = parent;
+        }
+        self.parent[parent] = slot;
+        self.parent[slot] = grandparent;
+        if grandparent == NIL {
+            self.root = slot;
+        } else if self.left[grandparent] == parent {
+            self.left[grandparent] = slot;
+        } else {
+            self.right[grandparent] = slot;
+        }
+        for node in [parent, slot] {
+            self.below[node] = self
+                .below(self.left[node])
+                .add(self.own(node))
+                .add(self.below(self.right[node]));
+        }
+    }
+
+    /// Mark a slot's character deleted.
+    fn hide(&mut self, slot: usize) {
+        let removed = self.own(slot);
+        self.chars[slot] = None;
+        if !self.is_linked(slot) {
+            return;
+        }
+        let mut ancestor = slot;
+        while ancestor != NIL {
+            let below = &mut self.below[ancestor];
+            below.chars -= removed.chars;
+            below.newlines -= removed.newlines;
+            ancestor = self.parent[ancestor];
+        }
+    }
+
+    /// Visible characters and line breaks before a linked slot.
+    fn rank(&self, slot: usize) -> Counts {
+        let mut before = self.below(self.left[slot]);
+        let mut node = slot;
+        while self.parent[node] != NIL {
+            let parent = self.parent[node];
+            if self.right[parent] == node {
+                before = before
+                    .add(self.below(self.left[parent]))
+                    .add(self.own(parent));
+            }
+            node = parent;
+        }
+        before
+    }
+
+    /// The linked slot holding the `n`th visible character, or the `n`th
+    /// line break with `newlines` set, counting from 0.
+    fn select(&self, mut n: usize, newlines: bool) -> Option<usize> {
+        let count = |counts: Counts| {
+            if newlines {
+                counts.newlines
+            } else {
+                counts.chars
+            }
+        };
+        if n >= count(self.total()) {
+            return None;
+        }
+        let mut node = self.root;
+        loop {
+            let left = count(self.below(self.left[node]));
+            if n < left {
+                node = self.left[node];
+                continue;
+            }
+            n -= left;
+            let own = count(self.own(node));
+            if n < own {
+                return Some(node);
+            }
+            n -= own;
+            node = self.right[node];
+        }
+    }
+
+    /// Linked slots in document order, excluding genesis.
+    fn order(&self) -> impl Iterator<Item = usize> + '_ {
+        self.order_from(self.next[0])
+    }
+
+    /// Linked slots in document order, starting at `slot`.
+    fn order_from(&self, slot: usize) -> impl Iterator<Item = usize> + '_ {
+        let mut cursor = slot;
+        std::iter::from_fn(move || {
+            let slot = cursor;
+            if slot == NIL {
+                return None;
+            }
+            cursor = self.next[slot];
+            Some(slot)
+        })
+    }
+
+    /// Slots of the visible characters from `position` on.
+    fn visible_from(&self, position: usize) -> impl Iterator<Item = usize> + '_ {
+        let start = self.select(position, false).unwrap_or(NIL);
+        self.order_from(start)
+            .filter(|&slot| self.chars[slot].is_some())
+    }
+}
+
 /// Collaborative text CRDT using RGA algorithm.
 ///
 /// Supports character-level insert and delete with
 /// deterministic conflict resolution.
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "RGATextState")]
 pub struct RGAText {
     /// All nodes indexed by their ID.
+    #[serde(with = "crate::serde_map")]
     nodes: HashMap<TextId, TextNode>,
     /// Children of each node (characters inserted after it).
     /// Maps origin -> list of children sorted by ID (descending for RGA).
+    #[serde(with = "crate::serde_map")]
     children: HashMap<TextId, Vec<TextId>>,
     /// The replica ID for this instance.
     replica_id: String,
     /// Sequence counter for generating IDs.
     seq: u64,
+    /// Frontier up to which tombstones have been compacted away.
+    #[serde(default)]
+    compacted: VersionVector,
+    /// Highest sequence number seen from each replica.
+    #[serde(default)]
+    clock: VersionVector,
     /// Pending delta for replication.
     #[serde(skip)]
     pending_delta: Option<RGATextDelta>,
+    /// Document order of the nodes.
+    #[serde(skip)]
+    index: OrderIndex,
+}
+
+/// Serialized form of [`RGAText`]; the order index is rebuilt on load.
+#[derive(Deserialize)]
+struct RGATextState {
+    #[serde(with = "crate::serde_map")]
+    nodes: HashMap<TextId, TextNode>,
+    #[serde(with = "crate::serde_map")]
+    children: HashMap<TextId, Vec<TextId>>,
+    replica_id: String,
+    seq: u64,
+    #[serde(default)]
+    compacted: VersionVector,
+    #[serde(default)]
+    clock: VersionVector,
+}
+
+impl From<RGATextState> for RGAText {
+    fn from(state: RGATextState) -> Self {
+        let mut text = Self {
+            nodes: state.nodes,
+            children: state.children,
+            replica_id: state.replica_id,
+            seq: state.seq,
+            compacted: state.compacted,
+            clock: state.clock,
+            pending_delta: None,
+            index: OrderIndex::default(),
+        };
+        // Texts saved without a clock still cover their inserts
+        text.clock.merge(&text.compacted);
+        for id in text.nodes.keys() {
+            text.clock.observe(&id.replica, id.seq);
+        }
+        text.children.entry(TextId::genesis()).or_default();
+        text.rebuild_index();
+        text
+    }
 }
 
 impl RGAText {
@@ -142,7 +542,10 @@ impl RGAText {
             children: HashMap::new(),
             replica_id,
             seq: 0,
+            compacted: VersionVector::new(),
+            clock: VersionVector::new(),
             pending_delta: None,
+            index: OrderIndex::default(),
         };
 
         // Initialize with genesis node's children list
@@ -156,17 +559,62 @@ impl RGAText {
         &self.replica_id
     }
 
+    /// Copy this text for a new replica.
+    ///
+    /// The copy keeps all characters and tombstones but generates IDs under
+    /// `replica_id`, so its edits never collide with the original's.
+    pub fn fork(&self, replica_id: impl Into<String>) -> Self {
+        Self {
+            replica_id: replica_id.into(),
+            pending_delta: None,
+            ..self.clone()
+        }
+    }
+
+    /// Highest sequence number seen from each replica.
+    ///
+    /// Every edit takes a sequence number: an insert one per character, as
+    /// its ID, and a deletion one for the whole range. The vector is kept
+    /// up to date as edits are made, applied and joined, so a replica
+    /// missing another's deletions is behind it.
+    pub fn version_vector(&self) -> VersionVector {
+        self.clock.clone()
+    }
+
+    /// Take the next sequence number for an edit that stamps no character
+    /// ID, such as a deletion or a formatting change in
+    /// [`RichText`](crate::rich_text::RichText).
+    ///
+    /// The pending delta carries it, so replicas that apply the delta see
+    /// the edit in the [`version_vector`](Self::version_vector).
+    pub fn stamp(&mut self) -> u64 {
+        self.seq += 1;
+        self.observe_own();
+        self.seq
+    }
+
     /// Generate a new unique ID.
     fn next_id(&mut self) -> TextId {
         self.seq += 1;
+        self.observe_own();
         TextId::new(&self.replica_id, self.seq)
     }
 
+    /// Record our latest sequence number in the clock and the pending delta.
+    fn observe_own(&mut self) {
+        self.clock.observe(&self.replica_id, self.seq);
+        self.pending_delta
+            .get_or_insert_with(RGATextDelta::new)
+            .clock
+            .observe(&self.replica_id, self.seq);
+    }
+
     /// Insert a string at the given position.
     pub fn insert(&mut self, position: usize, text: &str) {
-        let mut origin = self
-            .id_at_index(position.saturating_sub(1))
-            .unwrap_or(TextId::genesis());
+        let mut origin = match position {
+            0 => TextId::genesis(),
+            _ => self.id_at_index(position - 1).unwrap_or(TextId::genesis()),
+        };
 
         for ch in text.chars() {
             let id = self.next_id();
@@ -185,12 +633,16 @@ impl RGAText {
     /// Delete characters from start to start+length.
     pub fn delete(&mut self, start: usize, length: usize) {
         let ids: Vec<_> = self
-            .visible_ids()
-            .skip(start)
+            .index
+            .visible_from(start)
             .take(length)
-            .cloned()
+            .map(|slot| self.index.ids[slot].clone())
             .collect();
 
+        if ids.is_empty() {
+            return;
+        }
+        self.stamp();
         for id in ids {
             self.delete_by_id(&id);
         }
@@ -198,19 +650,26 @@ impl RGAText {
 
     /// Delete a character by its ID.
     fn delete_by_id(&mut self, id: &TextId) -> Option<char> {
-        if let Some(node) = self.nodes.get_mut(id) {
-            if !node.deleted {
-                node.deleted = true;
-                let ch = node.char.take();
+        let ch = self.tombstone(id)?;
 
-                // Record delta
-                let delta = self.pending_delta.get_or_insert_with(RGATextDelta::new);
-                delta.deletes.push(id.clone());
+        // Record delta
+        let delta = self.pending_delta.get_or_insert_with(RGATextDelta::new);
+        delta.deletes.push(id.clone());
 
-                return ch;
-            }
+        Some(ch)
+    }
+
+    /// Mark a node deleted, returning its character if it was visible.
+    fn tombstone(&mut self, id: &TextId) -> Option<char> {
+        let node = self.nodes.get_mut(id)?;
+        if node.deleted {
+            return None;
+        }
+        node.deleted = true;
+        if let Some(slot) = self.index.slot(id) {
+            self.index.hide(slot);
         }
-        None
+        node.char.take()
     }
 
     /// Replace a range with new text.
@@ -227,7 +686,7 @@ impl RGAText {
 
     /// Get the length (number of visible characters).
     pub fn len(&self) -> usize {
-        self.nodes.values().filter(|n| !n.deleted).count()
+        self.index.visible()
     }
 
     /// Check if empty.
@@ -235,36 +694,145 @@ impl RGAText {
         self.len() == 0
     }
 
+    /// Number of visible characters; the same as [`len`](Self::len).
+    pub fn live_count(&self) -> usize {
+        self.len()
+    }
+
+    /// Number of deleted characters still stored as tombstones.
+    pub fn tombstone_count(&self) -> usize {
+        self.nodes.values().filter(|n| n.deleted).count()
+    }
+
+    /// Physically remove the tombstones every replica has seen.
+    ///
+    /// `stable` must be causally stable, like the frontier of a
+    /// [`StabilityMonitor`](mdcs_compaction::StabilityMonitor) fed with each
+    /// replica's [`version_vector`](Self::version_vector): every replica has
+    /// applied the inserts it covers, and the deletions of those characters
+    /// too, so no edit still to arrive can be anchored to one of them.
+    /// Tombstones are picked by their insert's ID, not the deletion's, so
+    /// take the frontiers after the deleting deltas have been delivered
+    /// everywhere.
+    ///
+    /// Characters inserted after a removed one take its place under its
+    /// origin and sort by its ID there, so concurrent inserts land where they
+    /// would have next to the tombstone. The frontier is remembered, so an
+    /// old delta or a join with a replica that hasn't compacted can't bring a
+    /// removed character back. Returns the number of tombstones removed.
+    pub fn compact(&mut self, stable: &VersionVector) -> usize {
+        let stable = stable.min_with(&self.version_vector());
+        let removable: Vec<TextId> = self
+            .nodes
+            .values()
+            .filter(|n| n.deleted && stable.contains(&n.id.replica, n.id.seq))
+            .map(|n| n.id.clone())
+            .collect();
+
+        for id in &removable {
+            let node = self.nodes.remove(id).expect("collected above");
+            let key = node.key.unwrap_or_else(|| id.clone());
+            let children = self.children.remove(id).unwrap_or_default();
+            for child in &children {
+                if let Some(child) = self.nodes.get_mut(child) {
+                    child.origin = node.origin.clone();
+                    child.key = Some(key.clone());
+                }
+            }
+            let siblings = self.children.entry(node.origin).or_default();
+            if let Some(pos) = siblings.iter().position(|c| c == id) {
+                siblings.splice(pos..=pos, children);
+            }
+        }
+
+        self.compacted.merge(&stable);
+        if !removable.is_empty() {
+            self.rebuild_index();
+        }
+        removable.len()
+    }
+
+    /// Check whether a node is gone because it was compacted.
+    fn is_compacted(&self, id: &TextId) -> bool {
+        !self.nodes.contains_key(id) && self.compacted.contains(&id.replica, id.seq)
+    }
+
     /// Get character at position.
     pub fn char_at(&self, position: usize) -> Option<char> {
-        self.iter().nth(position)
+        let slot = self.index.select(position, false)?;
+        self.index.chars[slot]
     }
 
     /// Get a substring.
     pub fn slice(&self, start: usize, end: usize) -> String {
-        self.iter().skip(start).take(end - start).collect()
+        self.index
+            .visible_from(start)
+            .take(end - start)
+            .filter_map(|slot| self.index.chars[slot])
+            .collect()
+    }
+
+    /// Number of lines: one more than the number of `'\n'` characters.
+    pub fn line_count(&self) -> usize {
+        self.index.total().newlines + 1
+    }
+
+    /// Line and column of a position, both counted from 0 in characters.
+    ///
+    /// A line ends with its `'\n'`, so the position of a line break is on
+    /// the line it ends. The end of the text, [`len`](Self::len), is a
+    /// valid position; anything past it returns `None`.
+    pub fn char_to_line_col(&self, position: usize) -> Option<(usize, usize)> {
+        let before = match self.index.select(position, false) {
+            Some(slot) => self.index.rank(slot),
+            None if position == self.len() => self.index.total(),
+            None => return None,
+        };
+        let line = before.newlines;
+        Some((line, position - self.line_start(line)?))
+    }
+
+    /// Position of a line and column, the inverse of
+    /// [`char_to_line_col`](Self::char_to_line_col).
+    ///
+    /// The column may be the line's length, the position of its `'\n'` or
+    /// the end of the text; `None` past that or past the last line.
+    pub fn line_col_to_char(&self, line: usize, col: usize) -> Option<usize> {
+        let start = self.line_start(line)?;
+        let end = match self.index.select(line, true) {
+            Some(newline) => self.index.rank(newline).chars,
+            None => self.len(),
+        };
+        (start + col <= end).then_some(start + col)
+    }
+
+    /// Position of the first character of a line.
+    fn line_start(&self, line: usize) -> Option<usize> {
+        if line == 0 {
+            return Some(0);
+        }
+        let newline = self.index.select(line - 1, true)?;
+        Some(self.index.rank(newline).chars + 1)
     }
 
     /// Iterate over visible characters.
     pub fn iter(&self) -> impl Iterator<Item = char> + '_ {
-        self.iter_nodes()
-            .filter(|n| !n.deleted)
-            .filter_map(|n| n.char)
+        self.index.order().filter_map(|slot| self.index.chars[slot])
     }
 
     /// Get the ID at a visible index.
     fn id_at_index(&self, index: usize) -> Option<TextId> {
-        self.visible_ids().nth(index).cloned()
-    }
-
-    /// Iterate over visible IDs.
-    fn visible_ids(&self) -> impl Iterator<Item = &TextId> + '_ {
-        self.iter_nodes().filter(|n| !n.deleted).map(|n| &n.id)
+        let slot = self.index.select(index, false)?;
+        Some(self.index.ids[slot].clone())
     }
 
     /// Convert a TextId to a visible position.
     pub fn id_to_position(&self, id: &TextId) -> Option<usize> {
-        self.visible_ids().position(|i| i == id)
+        let slot = self.index.slot(id)?;
+        if slot == 0 || !self.index.is_linked(slot) || self.index.chars[slot].is_none() {
+            return None;
+        }
+        Some(self.index.rank(slot).chars)
     }
 
     /// Convert a visible position to a TextId.
@@ -272,33 +840,143 @@ impl RGAText {
         self.id_at_index(position)
     }
 
-    /// Iterate over all nodes in order.
-    fn iter_nodes(&self) -> impl Iterator<Item = &TextNode> + '_ {
-        TextIterator {
-            text: self,
-            stack: vec![TextId::genesis()],
-            visited: HashSet::new(),
+    /// Iterate over all node IDs in order, including tombstones.
+    ///
+    /// Visible nodes carry their character; deleted ones yield `None`.
+    pub(crate) fn iter_with_tombstones(
+        &self,
+    ) -> impl Iterator<Item = (&TextId, Option<char>)> + '_ {
+        self.index
+            .order()
+            .map(|slot| (&self.index.ids[slot], self.index.chars[slot]))
+    }
+
+    /// Check whether a node exists, deleted or not.
+    pub(crate) fn contains_id(&self, id: &TextId) -> bool {
+        self.nodes.contains_key(id)
+    }
+
+    /// Get the character of a visible node.
+    pub(crate) fn char_of(&self, id: &TextId) -> Option<char> {
+        self.nodes
+            .get(id)
+            .filter(|n| !n.deleted)
+            .and_then(|n| n.char)
+    }
+
+    /// Visible position directly after a node, whether or not it is deleted.
+    ///
+    /// Text inserted at this position lands next to the node. The genesis ID
+    /// maps to 0; unknown IDs return `None`.
+    pub(crate) fn position_after(&self, id: &TextId) -> Option<usize> {
+        if *id == TextId::genesis() {
+            return Some(0);
+        }
+        let slot = self.index.slot(id)?;
+        if !self.index.is_linked(slot) {
+            return None;
         }
+        Some(self.index.rank(slot).chars + self.index.own(slot).chars)
     }
 
     /// Integrate a node into the text.
     fn integrate_node(&mut self, node: TextNode) {
         let id = node.id.clone();
         let origin = node.origin.clone();
+        let slot = self.index.add(&node);
 
         // Add to nodes map
         self.nodes.insert(id.clone(), node);
 
         // Add to children of origin, maintaining sort order (descending by ID for RGA)
-        let children = self.children.entry(origin).or_default();
+        let children = self
+            .children
+            .get(&origin)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
         let pos = children
             .iter()
-            .position(|c| c < &id)
+            .position(|c| self.sort_key(c) < &id)
             .unwrap_or(children.len());
-        children.insert(pos, id.clone());
+        self.children
+            .entry(origin.clone())
+            .or_default()
+            .insert(pos, id.clone());
 
         // Ensure this node has a children entry
         self.children.entry(id).or_default();
+
+        // Nodes whose origin hasn't arrived yet are linked along with it
+        let Some(origin_slot) = self.index.slot(&origin) else {
+            return;
+        };
+        if self.index.is_linked(origin_slot) {
+            let after = match pos.checked_sub(1) {
+                Some(prev_sibling) => {
+                    let last = self.last_in_subtree(&self.children[&origin][prev_sibling]);
+                    self.index.slots[&last]
+                }
+                None => origin_slot,
+            };
+            self.link_subtree(after, slot);
+        }
+    }
+
+    /// The ID a node sorts by among its siblings.
+    fn sort_key<'a>(&'a self, id: &'a TextId) -> &'a TextId {
+        self.nodes
+            .get(id)
+            .and_then(|n| n.key.as_ref())
+            .unwrap_or(id)
+    }
+
+    /// The last node, in document order, of the subtree rooted at `id`.
+    fn last_in_subtree(&self, id: &TextId) -> TextId {
+        let mut last = id;
+        while let Some(child) = self.children.get(last).and_then(|c| c.last()) {
+            last = child;
+        }
+        last.clone()
+    }
+
+    /// Link the slot `root` and any descendants already present directly
+    /// after the slot `after` in document order.
+    fn link_subtree(&mut self, mut after: usize, root: usize) {
+        let mut stack = vec![root];
+        while let Some(slot) = stack.pop() {
+            if let Some(children) = self.children.get(&self.index.ids[slot]) {
+                stack.extend(children.iter().rev().map(|c| self.index.slots[c]));
+            }
+            self.index.link_after(after, slot);
+            after = slot;
+        }
+    }
+
+    /// Recompute the order index from the tree.
+    fn rebuild_index(&mut self) {
+        self.index = OrderIndex::default();
+        for node in self.nodes.values() {
+            self.index.add(node);
+        }
+        let roots: Vec<_> = self.children[&TextId::genesis()]
+            .iter()
+            .map(|id| self.index.slots[id])
+            .collect();
+        for root in roots.into_iter().rev() {
+            self.link_subtree(0, root);
+        }
+    }
+
+    /// Check whether `other` holds every node of this text, with at least
+    /// the same deletions.
+    fn is_extended_by(&self, other: &Self) -> bool {
+        self.nodes.len() <= other.nodes.len()
+            && self.nodes.iter().all(|(id, node)| {
+                other
+                    .nodes
+                    .get(id)
+                    .is_some_and(|o| o.deleted || !node.deleted)
+            })
     }
 
     /// Take the pending delta.
@@ -308,9 +986,12 @@ impl RGAText {
 
     /// Apply a delta from another replica.
     pub fn apply_delta(&mut self, delta: &RGATextDelta) {
+        self.clock.merge(&delta.clock);
+
         // Apply inserts
         for (id, ch, origin) in &delta.inserts {
-            if !self.nodes.contains_key(id) {
+            self.clock.observe(&id.replica, id.seq);
+            if !self.nodes.contains_key(id) && !self.is_compacted(id) {
                 let node = TextNode::new(id.clone(), *ch, origin.clone());
                 self.integrate_node(node);
             }
@@ -318,48 +999,65 @@ impl RGAText {
 
         // Apply deletes
         for id in &delta.deletes {
-            if let Some(node) = self.nodes.get_mut(id) {
-                node.deleted = true;
-                node.char = None;
-            }
+            self.tombstone(id);
         }
     }
-}
-
-/// Iterator for traversing text nodes in order.
-struct TextIterator<'a> {
-    text: &'a RGAText,
-    stack: Vec<TextId>,
-    visited: HashSet<TextId>,
-}
-
-impl<'a> Iterator for TextIterator<'a> {
-    type Item = &'a TextNode;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        while let Some(id) = self.stack.pop() {
-            if self.visited.contains(&id) {
-                continue;
-            }
-            self.visited.insert(id.clone());
 
-            // Push children in reverse order
-            if let Some(children) = self.text.children.get(&id) {
-                for child in children.iter().rev() {
-                    if !self.visited.contains(child) {
-                        self.stack.push(child.clone());
+    /// Join with `other`, reporting the edits it makes to `observer`.
+    ///
+    /// Adjacent inserted or deleted characters are reported as one run.
+    pub fn join_observed(&self, other: &Self, observer: &mut impl TextObserver) -> Self {
+        let result = self.join(other);
+
+        let mut position = 0;
+        let mut inserted = String::new();
+        let mut deleted = 0;
+        for (id, ch) in result.iter_with_tombstones() {
+            let was_visible = self
+                .index
+                .slot(id)
+                .is_some_and(|slot| self.index.is_linked(slot) && self.index.chars[slot].is_some());
+            match (was_visible, ch) {
+                (true, Some(_)) => {
+                    flush_edits(observer, &mut position, &mut inserted, &mut deleted);
+                    position += 1;
+                }
+                (true, None) => {
+                    if !inserted.is_empty() {
+                        flush_edits(observer, &mut position, &mut inserted, &mut deleted);
                     }
+                    deleted += 1;
                 }
-            }
-
-            // Return the node (skip genesis)
-            if id != TextId::genesis() {
-                if let Some(node) = self.text.nodes.get(&id) {
-                    return Some(node);
+                (false, Some(ch)) => {
+                    if deleted > 0 {
+                        flush_edits(observer, &mut position, &mut inserted, &mut deleted);
+                    }
+                    inserted.push(ch);
                 }
+                (false, None) => {}
             }
         }
-        None
+        flush_edits(observer, &mut position, &mut inserted, &mut deleted);
+
+        result
+    }
+}
+
+/// Report a pending run of inserted or deleted characters.
+fn flush_edits(
+    observer: &mut impl TextObserver,
+    position: &mut usize,
+    inserted: &mut String,
+    deleted: &mut usize,
+) {
+    if *deleted > 0 {
+        observer.on_delete(*position, *deleted);
+        *deleted = 0;
+    }
+    if !inserted.is_empty() {
+        observer.on_insert(*position, inserted);
+        *position += inserted.chars().count();
+        inserted.clear();
     }
 }
 
@@ -386,16 +1084,33 @@ impl Lattice for RGAText {
     }
 
     fn join(&self, other: &Self) -> Self {
-        let mut result = self.clone();
+        // Fast path: other already contains everything we have
+        if self.is_extended_by(other) && other.compacted.dominates(&self.compacted) {
+            return Self {
+                replica_id: self.replica_id.clone(),
+                seq: self.seq.max(other.seq),
+                clock: self.clock.merged(&other.clock),
+                pending_delta: self.pending_delta.clone(),
+                ..other.clone()
+            };
+        }
 
-        // Merge all nodes from other
-        for (id, node) in &other.nodes {
-            if let Some(existing) = result.nodes.get_mut(id) {
+        let mut result = self.clone();
+        // Keep new IDs ahead of everything already observed
+        result.seq = self.seq.max(other.seq);
+        result.compacted.merge(&other.compacted);
+        result.clock.merge(&other.clock);
+
+        // Merge nodes from other in document order, so each one's anchor is
+        // already linked when it is integrated
+        let unlinked = (1..other.index.ids.len()).filter(|&slot| !other.index.is_linked(slot));
+        let slots = other.index.order().chain(unlinked);
+        for node in slots.filter_map(|slot| other.nodes.get(&other.index.ids[slot])) {
+            if result.nodes.contains_key(&node.id) {
                 if node.deleted {
-                    existing.deleted = true;
-                    existing.char = None;
+                    result.tombstone(&node.id);
                 }
-            } else {
+            } else if !result.is_compacted(&node.id) {
                 result.integrate_node(node.clone());
             }
         }
@@ -428,6 +1143,9 @@ mod tests {
         text.insert(0, "Hello");
         text.insert(5, " World");
         assert_eq!(text.to_string(), "Hello World");
+
+        text.insert(0, ">> ");
+        assert_eq!(text.to_string(), ">> Hello World");
     }
 
     #[test]
@@ -550,4 +1268,303 @@ mod tests {
         // Both texts should be somehow combined
         assert!(merged.len() >= 5);
     }
+
+    #[test]
+    fn test_join_keeps_ids_fresh() {
+        // A replica that only ever joins edits made on forks of its state
+        // must not hand out the same IDs twice
+        let mut state = RGAText::bottom();
+        let mut edit = state.fork("r1");
+        edit.insert(0, "ab");
+        state = state.join(&edit);
+
+        let mut edit = state.fork("r1");
+        edit.insert(2, "cd");
+        state = state.join(&edit);
+
+        assert_eq!(state.to_string(), "abcd");
+    }
+
+    /// Node order from a plain walk of the tree.
+    fn tree_order(text: &RGAText) -> Vec<TextId> {
+        let mut order = Vec::new();
+        let mut stack = vec![TextId::genesis()];
+        while let Some(id) = stack.pop() {
+            stack.extend(text.children[&id].iter().rev().cloned());
+            if id != TextId::genesis() {
+                order.push(id);
+            }
+        }
+        order
+    }
+
+    /// Check the treap under `slot` and return its slots in order.
+    fn treap_order(index: &OrderIndex, slot: usize, order: &mut Vec<usize>) -> Counts {
+        if slot == NIL {
+            return Counts::default();
+        }
+        for child in [index.left[slot], index.right[slot]] {
+            if child != NIL {
+                assert_eq!(index.parent[child], slot);
+                assert!(OrderIndex::priority(child) <= OrderIndex::priority(slot));
+            }
+        }
+        let left = treap_order(index, index.left[slot], order);
+        order.push(slot);
+        let right = treap_order(index, index.right[slot], order);
+        let below = left.add(index.own(slot)).add(right);
+        assert_eq!(index.below[slot], below);
+        below
+    }
+
+    fn assert_index_consistent(text: &RGAText) {
+        let indexed: Vec<_> = text
+            .iter_with_tombstones()
+            .map(|(id, _)| id.clone())
+            .collect();
+        assert_eq!(indexed, tree_order(text));
+        assert_eq!(text.len(), text.iter().count());
+
+        // The treap holds the linked slots in the same order, and finds
+        // each visible one by position
+        let index = &text.index;
+        let mut order = Vec::new();
+        assert_eq!(index.parent[index.root], NIL);
+        treap_order(index, index.root, &mut order);
+        assert_eq!(
+            order,
+            std::iter::once(0).chain(index.order()).collect::<Vec<_>>()
+        );
+        let visible: Vec<usize> = order
+            .into_iter()
+            .filter(|&slot| index.chars[slot].is_some())
+            .collect();
+        for (position, &slot) in visible.iter().enumerate() {
+            assert_eq!(index.select(position, false), Some(slot));
+            assert_eq!(index.rank(slot).chars, position);
+        }
+        assert_eq!(index.select(visible.len(), false), None);
+    }
+
+    #[test]
+    fn test_index_follows_concurrent_merges() {
+        let mut replicas: Vec<_> = ["a", "b", "c"].iter().map(|r| RGAText::new(*r)).collect();
+        let mut x: u64 = 7;
+        let mut rand = |n: usize| {
+            x = x
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (x >> 33) as usize % n
+        };
+
+        for round in 0..40 {
+            for (i, text) in replicas.iter_mut().enumerate() {
+                let pos = rand(text.len() + 1);
+                if text.len() > 2 && rand(3) == 0 {
+                    text.delete(pos.min(text.len() - 2), 2);
+                } else {
+                    text.insert(pos, &format!("{}{}", i, round % 10));
+                }
+            }
+            // Mix deltas and full-state joins in both directions
+            let (a, b) = (rand(3), rand(3));
+            if a != b {
+                if let Some(delta) = replicas[a].take_delta() {
+                    replicas[b].apply_delta(&delta);
+                }
+                replicas[a] = replicas[a].join(&replicas[b]);
+            }
+            for text in &replicas {
+                assert_index_consistent(text);
+            }
+        }
+
+        let merged = replicas[0].join(&replicas[1]).join(&replicas[2]);
+        let reverse = replicas[2].join(&replicas[1]).join(&replicas[0]);
+        assert_index_consistent(&merged);
+        assert_eq!(merged.to_string(), reverse.to_string());
+    }
+
+    #[test]
+    fn test_index_links_out_of_order_nodes() {
+        let mut source = RGAText::new("r1");
+        source.insert(0, "abc");
+        let delta = source.take_delta().unwrap();
+
+        // Children arrive before the node they were inserted after
+        let mut text = RGAText::new("r2");
+        for insert in delta.inserts.iter().rev() {
+            text.apply_delta(&RGATextDelta {
+                inserts: vec![insert.clone()],
+                ..RGATextDelta::new()
+            });
+        }
+
+        assert_eq!(text.to_string(), "abc");
+        assert_index_consistent(&text);
+    }
+
+    #[test]
+    fn test_deletions_advance_version_vector() {
+        let mut a = RGAText::new("r1");
+        a.insert(0, "abc");
+        let mut b = a.clone();
+        a.take_delta();
+
+        a.delete(0, 2);
+        assert_eq!(a.version_vector().get("r1"), 4);
+        assert!(a.version_vector().strictly_dominates(&b.version_vector()));
+
+        b.apply_delta(&a.take_delta().unwrap());
+        assert_eq!(b.version_vector(), a.version_vector());
+        assert_eq!(b.to_string(), "c");
+
+        // Deleting nothing is no edit
+        a.delete(5, 1);
+        assert_eq!(a.version_vector().get("r1"), 4);
+        assert!(a.take_delta().is_none());
+    }
+
+    #[test]
+    fn test_join_with_extension() {
+        let mut text = RGAText::new("r1");
+        text.insert(0, "Hello");
+        let mut ahead = text.fork("r2");
+        ahead.insert(5, " World");
+        ahead.delete(0, 1);
+
+        let merged = text.join(&ahead);
+        assert_eq!(merged.to_string(), "ello World");
+        assert_eq!(merged.replica_id(), "r1");
+        assert_index_consistent(&merged);
+    }
+
+    #[test]
+    fn test_compact_keeps_replicas_converging() {
+        let mut a = RGAText::new("a");
+        a.insert(0, "Hello brave new world");
+        let old_insert = a.take_delta().unwrap();
+        let mut b = a.fork("b");
+        a.delete(6, 6);
+        b = b.join(&a);
+
+        let stable = a.version_vector().min_with(&b.version_vector());
+        assert_eq!(a.tombstone_count(), 6);
+        assert_eq!(a.compact(&stable), 6);
+        assert_eq!(a.tombstone_count(), 0);
+        assert_eq!(a.live_count(), 15);
+        assert_eq!(a.to_string(), "Hello new world");
+        assert_index_consistent(&a);
+
+        // The other replica keeps editing around the removed characters
+        b.insert(6, "bold ");
+        b.delete(0, 1);
+        b.insert(0, "J");
+        let edits = b.take_delta().unwrap();
+        a.insert(a.len(), "!");
+
+        let ab = a.join(&b);
+        let ba = b.join(&a);
+        assert_eq!(ab.to_string(), "Jello bold new world!");
+        assert_eq!(ba.to_string(), ab.to_string());
+        assert_eq!(ab.tombstone_count(), 1);
+        assert_index_consistent(&ab);
+        assert_index_consistent(&ba);
+
+        let mut by_delta = a.clone();
+        by_delta.apply_delta(&edits);
+        assert_eq!(by_delta.to_string(), ab.to_string());
+
+        // A delta from before compaction doesn't bring the characters back
+        by_delta.apply_delta(&old_insert);
+        assert_eq!(by_delta.to_string(), ab.to_string());
+        assert_eq!(by_delta.tombstone_count(), 1);
+
+        let restored: RGAText = serde_json::from_str(&serde_json::to_string(&a).unwrap()).unwrap();
+        assert_eq!(restored.join(&b).to_string(), ab.to_string());
+    }
+
+    #[test]
+    fn test_compact_keeps_sibling_order() {
+        let mut a = RGAText::new("a");
+        let mut b = RGAText::new("b");
+        let mut c = RGAText::new("c");
+        let send = |delta: Option<RGATextDelta>, to: &mut [&mut RGAText]| {
+            let delta = delta.unwrap();
+            for text in to {
+                text.apply_delta(&delta);
+            }
+        };
+
+        a.insert(0, "x");
+        send(a.take_delta(), &mut [&mut b, &mut c]);
+        // b's next ID is far ahead of c's
+        b.insert(1, "--------");
+        b.delete(1, 8);
+        b.insert(1, "s");
+        send(b.take_delta(), &mut [&mut a, &mut c]);
+        c.insert(2, "t");
+        send(c.take_delta(), &mut [&mut a, &mut b]);
+        b.delete(1, 1);
+        send(b.take_delta(), &mut [&mut a, &mut c]);
+
+        let stable = a.version_vector().min_with(&c.version_vector());
+        assert_eq!(a.compact(&stable), 9);
+
+        // An insert after "x" with a lower ID than the removed "s" still
+        // sorts after everything that was inserted after "s"
+        c.insert(1, "y");
+        a.apply_delta(&c.take_delta().unwrap());
+        assert_eq!(c.to_string(), "xty");
+        assert_eq!(a.to_string(), c.to_string());
+        assert_index_consistent(&a);
+    }
+
+    #[test]
+    fn test_line_col() {
+        let mut text = RGAText::new("r1");
+        text.insert(0, "ab\ncde\n\nf");
+        text.delete(0, 1);
+        // "b\ncde\n\nf"
+        assert_eq!(text.line_count(), 4);
+        let expected = [
+            (0, 0),
+            (0, 1),
+            (1, 0),
+            (1, 1),
+            (1, 2),
+            (1, 3),
+            (2, 0),
+            (3, 0),
+            (3, 1),
+        ];
+        for (position, &(line, col)) in expected.iter().enumerate() {
+            assert_eq!(text.char_to_line_col(position), Some((line, col)));
+            assert_eq!(text.line_col_to_char(line, col), Some(position));
+        }
+        assert_eq!(text.char_to_line_col(9), None);
+        assert_eq!(text.line_col_to_char(0, 2), None);
+        assert_eq!(text.line_col_to_char(2, 1), None);
+        assert_eq!(text.line_col_to_char(4, 0), None);
+
+        let empty = RGAText::new("r1");
+        assert_eq!(empty.char_to_line_col(0), Some((0, 0)));
+        assert_eq!(empty.line_col_to_char(0, 0), Some(0));
+    }
+
+    #[test]
+    fn test_serde_rebuilds_index() {
+        let mut text = RGAText::new("r1");
+        text.insert(0, "Hello World");
+        text.delete(5, 6);
+
+        let json = serde_json::to_string(&text).unwrap();
+        let mut restored: RGAText = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.to_string(), "Hello");
+        assert_eq!(restored.len(), 5);
+        restored.insert(5, "!");
+        assert_eq!(restored.to_string(), "Hello!");
+        assert_index_consistent(&restored);
+    }
 }
@@ -5,13 +5,17 @@
 //! - Links and references
 //! - Comments and annotations
 //! - Custom marks for extensibility
+//! - Block types for paragraphs (headings, lists, quotes, code blocks)
 //!
-//! Uses anchor-based marks that reference TextIds for stability.
+//! Uses anchor-based marks that reference TextIds for stability. A
+//! paragraph's block type is keyed by the newline it starts after, and
+//! concurrent changes to it resolve last-writer-wins.
 
-use crate::rga_text::{RGAText, RGATextDelta, TextId};
+use crate::rga_text::{RGAText, RGATextDelta, TextId, TextObserver};
+use mdcs_compaction::VersionVector;
 use mdcs_core::lattice::Lattice;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use ulid::Ulid;
 
 /// Unique identifier for a mark (formatting span).
@@ -32,6 +36,12 @@ impl MarkId {
     }
 }
 
+impl std::fmt::Display for MarkId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.replica, self.ulid)
+    }
+}
+
 /// The type/style of a formatting mark.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum MarkType {
@@ -70,8 +80,26 @@ impl MarkType {
                 | (Link { .. }, Link { .. })
         )
     }
+
+    /// The span tree of a [`MarkIndex`] holding marks of this type.
+    fn slot(&self) -> usize {
+        match self {
+            MarkType::Bold => 0,
+            MarkType::Italic => 1,
+            MarkType::Underline => 2,
+            MarkType::Strikethrough => 3,
+            MarkType::Code => 4,
+            MarkType::Link { .. } => 5,
+            MarkType::Comment { .. } => 6,
+            MarkType::Highlight { .. } => 7,
+            MarkType::Custom { .. } => 8,
+        }
+    }
 }
 
+/// Number of [`MarkType`] variants.
+const MARK_TYPES: usize = 9;
+
 /// An anchor specifying a position in the text.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Anchor {
@@ -143,6 +171,91 @@ impl Mark {
     }
 }
 
+/// Block-level formatting of a paragraph.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BlockType {
+    /// Plain paragraph.
+    #[default]
+    Paragraph,
+    /// Heading of level 1 to 6.
+    Heading(u8),
+    /// Item of a bulleted list.
+    BulletList,
+    /// Item of a numbered list.
+    OrderedList,
+    /// Line of a block quote.
+    Quote,
+    /// Line of a code block.
+    CodeBlock,
+}
+
+impl BlockType {
+    /// Whether the paragraphs split off one of this type keep it, as list
+    /// items, quotes and code do. A heading is followed by paragraphs.
+    fn continues(&self) -> bool {
+        !matches!(self, BlockType::Paragraph | BlockType::Heading(_))
+    }
+
+    /// Tags around each paragraph of this type.
+    fn item_tags(&self) -> (String, String) {
+        match self {
+            BlockType::Paragraph => ("<p>".into(), "</p>".into()),
+            BlockType::Heading(level) => {
+                let level = (*level).clamp(1, 6);
+                (format!("<h{}>", level), format!("</h{}>", level))
+            }
+            BlockType::BulletList | BlockType::OrderedList => ("<li>".into(), "</li>".into()),
+            BlockType::Quote => ("<p>".into(), "</p>".into()),
+            BlockType::CodeBlock => (String::new(), String::new()),
+        }
+    }
+
+    /// Tags around a run of consecutive paragraphs of this type, if they
+    /// are grouped.
+    fn group_tags(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            BlockType::BulletList => Some(("<ul>", "</ul>")),
+            BlockType::OrderedList => Some(("<ol>", "</ol>")),
+            BlockType::Quote => Some(("<blockquote>", "</blockquote>")),
+            BlockType::CodeBlock => Some(("<pre><code>", "</code></pre>")),
+            BlockType::Paragraph | BlockType::Heading(_) => None,
+        }
+    }
+}
+
+/// The block type of one paragraph.
+///
+/// Each change gets a Lamport clock higher than any block change seen so
+/// far; the highest `(clock, replica)` wins.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Block {
+    /// The newline the paragraph starts after, or `None` for the first one.
+    pub after: Option<TextId>,
+    /// The paragraph's type.
+    pub block_type: BlockType,
+    /// Lamport clock of the change.
+    pub clock: u64,
+    /// The replica that made the change.
+    pub replica: String,
+}
+
+impl Block {
+    /// Whether this change wins over `other` for the same paragraph.
+    fn supersedes(&self, other: &Block) -> bool {
+        (self.clock, &self.replica) > (other.clock, &other.replica)
+    }
+}
+
+/// Keep the winning change of `block`'s paragraph in `blocks`.
+fn merge_block(blocks: &mut HashMap<Option<TextId>, Block>, block: &Block) {
+    match blocks.get(&block.after) {
+        Some(existing) if !block.supersedes(existing) => {}
+        _ => {
+            blocks.insert(block.after.clone(), block.clone());
+        }
+    }
+}
+
 /// Delta for rich text operations.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RichTextDelta {
@@ -152,6 +265,9 @@ pub struct RichTextDelta {
     pub add_marks: Vec<Mark>,
     /// Marks to remove (by ID).
     pub remove_marks: Vec<MarkId>,
+    /// Block type changes.
+    #[serde(default)]
+    pub set_blocks: Vec<Block>,
 }
 
 impl RichTextDelta {
@@ -160,11 +276,15 @@ impl RichTextDelta {
             text_delta: None,
             add_marks: Vec::new(),
             remove_marks: Vec::new(),
+            set_blocks: Vec::new(),
         }
     }
 
     pub fn is_empty(&self) -> bool {
-        self.text_delta.is_none() && self.add_marks.is_empty() && self.remove_marks.is_empty()
+        self.text_delta.is_none()
+            && self.add_marks.is_empty()
+            && self.remove_marks.is_empty()
+            && self.set_blocks.is_empty()
     }
 }
 
@@ -174,21 +294,380 @@ impl Default for RichTextDelta {
     }
 }
 
+/// Deltas compose by union: applying `a.join(&b)` has the same effect as
+/// applying `a` then `b`.
+impl Lattice for RichTextDelta {
+    fn bottom() -> Self {
+        Self::new()
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        let mut joined = self.clone();
+        joined.text_delta = match (&self.text_delta, &other.text_delta) {
+            (Some(ours), Some(theirs)) => Some(ours.join(theirs)),
+            (ours, theirs) => ours.clone().or_else(|| theirs.clone()),
+        };
+        for mark in &other.add_marks {
+            match joined.add_marks.iter_mut().find(|m| m.id == mark.id) {
+                Some(existing) => existing.deleted |= mark.deleted,
+                None => joined.add_marks.push(mark.clone()),
+            }
+        }
+        let removed: HashSet<&MarkId> = self.remove_marks.iter().collect();
+        joined.remove_marks.extend(
+            other
+                .remove_marks
+                .iter()
+                .filter(|id| !removed.contains(id))
+                .cloned(),
+        );
+        for block in &other.set_blocks {
+            match joined
+                .set_blocks
+                .iter_mut()
+                .find(|b| b.after == block.after)
+            {
+                Some(existing) if !block.supersedes(existing) => {}
+                Some(existing) => *existing = block.clone(),
+                None => joined.set_blocks.push(block.clone()),
+            }
+        }
+        joined
+    }
+}
+
+/// How a resolved anchor moves when text is edited around it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Edge {
+    Start,
+    End,
+    /// One past the position of a character.
+    After,
+    /// The position of a character.
+    Before,
+}
+
+impl Edge {
+    fn of(anchor: &Anchor) -> Self {
+        match anchor {
+            Anchor::Start => Edge::Start,
+            Anchor::End => Edge::End,
+            Anchor::After(_) => Edge::After,
+            Anchor::Before(_) => Edge::Before,
+        }
+    }
+
+    /// The position after `count` characters are inserted at `at`.
+    fn inserted(self, position: usize, at: usize, count: usize) -> usize {
+        let moves = match self {
+            Edge::Start => false,
+            Edge::End => true,
+            Edge::After => position > at,
+            Edge::Before => position >= at,
+        };
+        if moves {
+            position + count
+        } else {
+            position
+        }
+    }
+
+    /// The position after `count` characters are deleted from `at`, or
+    /// `None` if the character anchoring it was one of them.
+    fn deleted(self, position: usize, at: usize, count: usize) -> Option<usize> {
+        let end = at + count;
+        match self {
+            Edge::Start => Some(position),
+            Edge::End => Some(position - count),
+            Edge::After if position > end => Some(position - count),
+            Edge::After if position > at => None,
+            Edge::Before if position >= end => Some(position - count),
+            Edge::Before if position >= at => None,
+            Edge::After | Edge::Before => Some(position),
+        }
+    }
+}
+
+/// A mark with its anchors resolved.
+#[derive(Clone, Debug)]
+struct Span {
+    start: usize,
+    end: usize,
+    edges: (Edge, Edge),
+    id: MarkId,
+}
+
+impl Span {
+    fn new(mark: &Mark, start: usize, end: usize) -> Self {
+        Self {
+            start,
+            end,
+            edges: (Edge::of(&mark.start), Edge::of(&mark.end)),
+            id: mark.id.clone(),
+        }
+    }
+
+    /// Sort key: by start, ties broken so every replica agrees.
+    fn key(&self) -> (usize, usize, &str, &str) {
+        (self.start, self.end, &self.id.replica, &self.id.ulid)
+    }
+}
+
+/// The spans of one mark type as an implicit interval tree.
+///
+/// Spans are sorted by start; the middle span of every range roots it, and
+/// `max_end` holds the furthest end under each root, so a query skips whole
+/// ranges that end before it.
+#[derive(Clone, Debug, Default)]
+struct SpanTree {
+    spans: Vec<Span>,
+    max_end: Vec<usize>,
+}
+
+impl SpanTree {
+    fn insert(&mut self, span: Span) {
+        let at = self.spans.partition_point(|s| s.key() < span.key());
+        self.spans.insert(at, span);
+        self.refresh();
+    }
+
+    fn remove(&mut self, id: &MarkId) {
+        let before = self.spans.len();
+        self.spans.retain(|s| &s.id != id);
+        if self.spans.len() != before {
+            self.refresh();
+        }
+    }
+
+    fn inserted(&mut self, at: usize, count: usize) {
+        for span in &mut self.spans {
+            span.start = span.edges.0.inserted(span.start, at, count);
+            span.end = span.edges.1.inserted(span.end, at, count);
+        }
+        self.sort();
+    }
+
+    /// Shift spans past a deletion, dropping those anchored inside it.
+    fn deleted(&mut self, at: usize, count: usize) {
+        self.spans.retain_mut(|span| {
+            match (
+                span.edges.0.deleted(span.start, at, count),
+                span.edges.1.deleted(span.end, at, count),
+            ) {
+                (Some(start), Some(end)) => {
+                    span.start = start;
+                    span.end = end;
+                    true
+                }
+                _ => false,
+            }
+        });
+        self.sort();
+    }
+
+    /// Restore the order after spans moved; edits only reorder ties, so
+    /// this is a single pass.
+    fn sort(&mut self) {
+        self.spans.sort_by(|a, b| a.key().cmp(&b.key()));
+        self.refresh();
+    }
+
+    fn refresh(&mut self) {
+        self.max_end = vec![0; self.spans.len()];
+        self.fill(0, self.spans.len());
+    }
+
+    fn fill(&mut self, lo: usize, hi: usize) -> usize {
+        if lo >= hi {
+            return 0;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let max = self.spans[mid]
+            .end
+            .max(self.fill(lo, mid))
+            .max(self.fill(mid + 1, hi));
+        self.max_end[mid] = max;
+        max
+    }
+
+    /// Collect the spans overlapping `start..end`, by start.
+    fn overlapping<'a>(&'a self, start: usize, end: usize, out: &mut Vec<&'a Span>) {
+        self.visit(0, self.spans.len(), start, end, out);
+    }
+
+    fn visit<'a>(
+        &'a self,
+        lo: usize,
+        hi: usize,
+        start: usize,
+        end: usize,
+        out: &mut Vec<&'a Span>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        if self.max_end[mid] <= start {
+            return;
+        }
+        self.visit(lo, mid, start, end, out);
+        let span = &self.spans[mid];
+        if span.start >= end {
+            return;
+        }
+        if span.end > start {
+            out.push(span);
+        }
+        self.visit(mid + 1, hi, start, end, out);
+    }
+}
+
+/// Active marks by type, with their anchors resolved.
+///
+/// Local edits shift the spans in place. A mark leaves the index once it is
+/// removed or a character it is anchored to is deleted; it stays in
+/// `RichText::marks`, which is what merges read. Derived state: never
+/// serialized, rebuilt with [`RichText::rebuild_index`].
+#[derive(Clone, Debug, Default)]
+struct MarkIndex {
+    trees: Box<[SpanTree; MARK_TYPES]>,
+}
+
+impl MarkIndex {
+    /// Resolve every active mark in a single walk over the text.
+    fn build(marks: &HashMap<MarkId, Mark>, text: &RGAText) -> Self {
+        let anchored: HashSet<&TextId> = marks
+            .values()
+            .filter(|m| !m.deleted)
+            .flat_map(|m| [&m.start, &m.end])
+            .filter_map(|anchor| match anchor {
+                Anchor::After(id) | Anchor::Before(id) => Some(id),
+                Anchor::Start | Anchor::End => None,
+            })
+            .collect();
+        let mut positions = HashMap::with_capacity(anchored.len());
+        let mut len = 0;
+        for (id, ch) in text.iter_with_tombstones() {
+            if ch.is_some() {
+                if anchored.contains(id) {
+                    positions.insert(id, len);
+                }
+                len += 1;
+            }
+        }
+        let resolve = |anchor: &Anchor| match anchor {
+            Anchor::Start => Some(0),
+            Anchor::End => Some(len),
+            Anchor::After(id) => positions.get(id).map(|p| p + 1),
+            Anchor::Before(id) => positions.get(id).copied(),
+        };
+
+        let mut index = Self::default();
+        for mark in marks.values().filter(|m| !m.deleted) {
+            if let (Some(start), Some(end)) = (resolve(&mark.start), resolve(&mark.end)) {
+                index.trees[mark.mark_type.slot()]
+                    .spans
+                    .push(Span::new(mark, start, end));
+            }
+        }
+        for tree in index.trees.iter_mut() {
+            tree.sort();
+        }
+        index
+    }
+
+    fn insert(&mut self, mark: &Mark, start: usize, end: usize) {
+        self.trees[mark.mark_type.slot()].insert(Span::new(mark, start, end));
+    }
+
+    fn remove(&mut self, mark: &Mark) {
+        self.trees[mark.mark_type.slot()].remove(&mark.id);
+    }
+
+    fn inserted(&mut self, at: usize, count: usize) {
+        if count > 0 {
+            self.trees.iter_mut().for_each(|t| t.inserted(at, count));
+        }
+    }
+
+    fn deleted(&mut self, at: usize, count: usize) {
+        if count > 0 {
+            self.trees.iter_mut().for_each(|t| t.deleted(at, count));
+        }
+    }
+
+    /// Spans overlapping `start..end`, of one type or of all of them.
+    fn overlapping(&self, start: usize, end: usize, mark_type: Option<&MarkType>) -> Vec<&Span> {
+        let mut out = Vec::new();
+        match mark_type {
+            Some(mark_type) => self.trees[mark_type.slot()].overlapping(start, end, &mut out),
+            None => {
+                for tree in self.trees.iter() {
+                    tree.overlapping(start, end, &mut out);
+                }
+            }
+        }
+        out
+    }
+
+    /// Every span, by start.
+    fn spans(&self) -> Vec<&Span> {
+        let mut spans: Vec<&Span> = self.trees.iter().flat_map(|t| &t.spans).collect();
+        spans.sort_by(|a, b| a.key().cmp(&b.key()));
+        spans
+    }
+}
+
 /// Collaborative rich text with formatting support.
 ///
 /// Combines RGAText for the text content with a set of
 /// anchor-based marks for formatting.
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "RichTextState")]
 pub struct RichText {
     /// The underlying plain text.
     text: RGAText,
     /// All marks indexed by their ID.
+    #[serde(with = "crate::serde_map")]
     marks: HashMap<MarkId, Mark>,
+    /// Block types of paragraphs, by the newline they start after.
+    /// Paragraphs without one are plain.
+    #[serde(with = "crate::serde_map")]
+    blocks: HashMap<Option<TextId>, Block>,
     /// The replica ID for this instance.
     replica_id: String,
     /// Pending delta for replication.
     #[serde(skip)]
     pending_delta: Option<RichTextDelta>,
+    /// Active marks by type and position.
+    #[serde(skip)]
+    index: MarkIndex,
+}
+
+/// Serialized form of [`RichText`]; the mark index is rebuilt on load.
+#[derive(Deserialize)]
+struct RichTextState {
+    text: RGAText,
+    #[serde(with = "crate::serde_map")]
+    marks: HashMap<MarkId, Mark>,
+    #[serde(default, with = "crate::serde_map")]
+    blocks: HashMap<Option<TextId>, Block>,
+    replica_id: String,
+}
+
+impl From<RichTextState> for RichText {
+    fn from(state: RichTextState) -> Self {
+        let mut rich = Self {
+            text: state.text,
+            marks: state.marks,
+            blocks: state.blocks,
+            replica_id: state.replica_id,
+            pending_delta: None,
+            index: MarkIndex::default(),
+        };
+        rich.rebuild_index();
+        rich
+    }
 }
 
 impl RichText {
@@ -198,8 +677,10 @@ impl RichText {
         Self {
             text: RGAText::new(&replica_id),
             marks: HashMap::new(),
+            blocks: HashMap::new(),
             replica_id,
             pending_delta: None,
+            index: MarkIndex::default(),
         }
     }
 
@@ -208,6 +689,20 @@ impl RichText {
         &self.replica_id
     }
 
+    /// Copy this rich text, text, marks and blocks included, for a new
+    /// replica.
+    pub fn fork(&self, replica_id: impl Into<String>) -> Self {
+        let replica_id = replica_id.into();
+        Self {
+            text: self.text.fork(&replica_id),
+            marks: self.marks.clone(),
+            blocks: self.blocks.clone(),
+            replica_id,
+            pending_delta: None,
+            index: self.index.clone(),
+        }
+    }
+
     /// Get the underlying text as a String.
     pub fn text_content(&self) -> String {
         self.text.to_string()
@@ -228,38 +723,211 @@ impl RichText {
         &self.text
     }
 
+    /// Highest sequence number seen from each replica.
+    ///
+    /// Text edits, mark additions and removals, and block type changes all
+    /// take a sequence number from the text's counter, so any of them
+    /// advances it; see [`RGAText::version_vector`].
+    pub fn version_vector(&self) -> VersionVector {
+        self.text.version_vector()
+    }
+
     // === Text Operations ===
 
     /// Insert plain text at a position.
+    ///
+    /// Newlines in `text` split the paragraph at `position`; see
+    /// [`set_block_type`](Self::set_block_type) for the types the new
+    /// paragraphs get.
     pub fn insert(&mut self, position: usize, text: &str) {
+        let at = self.insert_position(position, self.len());
+        let split = self.split_point(at, text);
         self.text.insert(position, text);
+        self.index.inserted(at, text.chars().count());
 
-        // Capture text delta
-        if let Some(text_delta) = self.text.take_delta() {
-            let delta = self.pending_delta.get_or_insert_with(RichTextDelta::new);
-            delta.text_delta = Some(text_delta);
+        self.capture_text_delta();
+        if let Some(split) = split {
+            self.carry_block_type(split, at, text);
         }
     }
 
     /// Delete text range.
     pub fn delete(&mut self, start: usize, length: usize) {
+        let at = start.min(self.len());
+        let count = length.min(self.len() - at);
         self.text.delete(start, length);
+        self.index.deleted(at, count);
 
-        // Capture text delta
-        if let Some(text_delta) = self.text.take_delta() {
-            let delta = self.pending_delta.get_or_insert_with(RichTextDelta::new);
-            delta.text_delta = Some(text_delta);
-        }
+        self.capture_text_delta();
     }
 
     /// Replace text range.
     pub fn replace(&mut self, start: usize, end: usize, text: &str) {
+        let at = start.min(self.len());
+        let count = (end - start).min(self.len() - at);
+        let split = self.split_point(self.insert_position(start, self.len()), text);
         self.text.replace(start, end, text);
+        self.index.deleted(at, count);
+        let at = self.insert_position(start, self.len() - count);
+        self.index.inserted(at, text.chars().count());
+
+        self.capture_text_delta();
+        if let Some(split) = split {
+            self.carry_block_type(split, at, text);
+        }
+    }
 
-        // Capture text delta
+    /// Stamp a formatting change with the next sequence number.
+    fn stamp(&mut self) {
+        self.text.stamp();
+        self.capture_text_delta();
+    }
+
+    /// Add the text edit just made to the pending delta.
+    fn capture_text_delta(&mut self) {
         if let Some(text_delta) = self.text.take_delta() {
             let delta = self.pending_delta.get_or_insert_with(RichTextDelta::new);
-            delta.text_delta = Some(text_delta);
+            delta.text_delta = Some(match delta.text_delta.take() {
+                Some(pending) => pending.join(&text_delta),
+                None => text_delta,
+            });
+        }
+    }
+
+    /// Where text inserted at `position` lands in a text of `len`
+    /// characters: RGAText inserts past the end at the start.
+    fn insert_position(&self, position: usize, len: usize) -> usize {
+        if position <= len {
+            position
+        } else {
+            0
+        }
+    }
+
+    // === Block Operations ===
+
+    /// Set the block type of the paragraph containing `position`.
+    ///
+    /// Concurrent changes to the same paragraph resolve last-writer-wins.
+    /// A paragraph split by inserting a newline keeps its type, and the
+    /// paragraphs split off it get the same type if it is a list item,
+    /// quote or code block and are plain otherwise. Splitting at the very
+    /// start of a heading moves the heading down, leaving a plain
+    /// paragraph above it. Deleting the newline a paragraph starts after
+    /// joins it to the one before, whose type it takes.
+    pub fn set_block_type(&mut self, position: usize, block_type: BlockType) {
+        let (start, _) = self.paragraph_at(position);
+        let after = self.block_key(start);
+        self.write_block(after, block_type);
+    }
+
+    /// Get the block type of the paragraph containing `position`.
+    pub fn block_type_at(&self, position: usize) -> BlockType {
+        let (start, _) = self.paragraph_at(position);
+        self.block_type_of(start)
+    }
+
+    /// Get every paragraph as `(start, end, type)`, in order.
+    ///
+    /// `end` is exclusive and doesn't include the newline that ends the
+    /// paragraph.
+    pub fn blocks(&self) -> Vec<(usize, usize, BlockType)> {
+        let mut result = Vec::new();
+        let mut start = 0;
+        for (i, c) in self.text.iter().enumerate() {
+            if c == '\n' {
+                result.push((start, i, self.block_type_of(start)));
+                start = i + 1;
+            }
+        }
+        result.push((start, self.len(), self.block_type_of(start)));
+        result
+    }
+
+    /// The paragraph containing `position`, as `(start, end)`.
+    fn paragraph_at(&self, position: usize) -> (usize, usize) {
+        let position = position.min(self.len());
+        let mut start = 0;
+        for (i, c) in self.text.iter().enumerate() {
+            if c != '\n' {
+                continue;
+            }
+            if i >= position {
+                return (start, i);
+            }
+            start = i + 1;
+        }
+        (start, self.len())
+    }
+
+    /// The key of the paragraph starting at `start`.
+    fn block_key(&self, start: usize) -> Option<TextId> {
+        match start {
+            0 => None,
+            _ => self.text.position_to_id(start - 1),
+        }
+    }
+
+    /// The type of the paragraph starting at `start`.
+    fn block_type_of(&self, start: usize) -> BlockType {
+        self.blocks
+            .get(&self.block_key(start))
+            .map(|block| block.block_type.clone())
+            .unwrap_or_default()
+    }
+
+    /// Record a block type change, locally and in the pending delta.
+    fn write_block(&mut self, after: Option<TextId>, block_type: BlockType) {
+        let clock = self.blocks.values().map(|b| b.clock).max().unwrap_or(0) + 1;
+        let block = Block {
+            after,
+            block_type,
+            clock,
+            replica: self.replica_id.clone(),
+        };
+        merge_block(&mut self.blocks, &block);
+        self.stamp();
+        self.pending_delta
+            .get_or_insert_with(RichTextDelta::new)
+            .set_blocks
+            .push(block);
+    }
+
+    /// If inserting `text` at `at` splits a paragraph, its start and type.
+    fn split_point(&self, at: usize, text: &str) -> Option<(usize, BlockType)> {
+        if !text.contains('\n') {
+            return None;
+        }
+        let (start, _) = self.paragraph_at(at);
+        Some((start, self.block_type_of(start)))
+    }
+
+    /// Give the paragraphs that inserting `text` at `at` split off the
+    /// paragraph at `split` their types.
+    fn carry_block_type(&mut self, split: (usize, BlockType), at: usize, text: &str) {
+        let (start, block_type) = split;
+        if block_type == BlockType::Paragraph {
+            return;
+        }
+        let newlines: Vec<usize> = text
+            .chars()
+            .enumerate()
+            .filter(|(_, c)| *c == '\n')
+            .map(|(i, _)| at + i)
+            .collect();
+        let last = newlines.len() - 1;
+        // Split at its start, the paragraph's text ends up after the last
+        // newline
+        let moved = at == start;
+        for (n, &newline) in newlines.iter().enumerate() {
+            if block_type.continues() || (moved && n == last) {
+                let after = self.text.position_to_id(newline);
+                self.write_block(after, block_type.clone());
+            }
+        }
+        if moved && !block_type.continues() {
+            let after = self.block_key(start);
+            self.write_block(after, BlockType::Paragraph);
         }
     }
 
@@ -290,9 +958,13 @@ impl RichText {
 
         let mark = Mark::new(id.clone(), mark_type, start_anchor, end_anchor);
 
+        if let Some((start, end)) = mark.range(&self.text) {
+            self.index.insert(&mark, start, end);
+        }
         self.marks.insert(id.clone(), mark.clone());
 
         // Record delta
+        self.stamp();
         let delta = self.pending_delta.get_or_insert_with(RichTextDelta::new);
         delta.add_marks.push(mark);
 
@@ -320,6 +992,9 @@ impl RichText {
     }
 
     /// Add a comment/annotation.
+    ///
+    /// The returned mark ID identifies the comment for [`Self::remove_comment`]
+    /// and in the `data-comment-id` attribute of the HTML export.
     pub fn comment(
         &mut self,
         start: usize,
@@ -351,9 +1026,13 @@ impl RichText {
     /// Remove a mark by ID.
     pub fn remove_mark(&mut self, id: &MarkId) -> bool {
         if let Some(mark) = self.marks.get_mut(id) {
+            if !mark.deleted {
+                self.index.remove(mark);
+            }
             mark.deleted = true;
 
             // Record delta
+            self.stamp();
             let delta = self.pending_delta.get_or_insert_with(RichTextDelta::new);
             delta.remove_marks.push(id.clone());
 
@@ -363,23 +1042,26 @@ impl RichText {
         }
     }
 
+    /// Remove a comment by its mark ID.
+    ///
+    /// Returns `false` if the ID doesn't name a comment; other marks are left alone.
+    pub fn remove_comment(&mut self, id: &MarkId) -> bool {
+        let is_comment = self
+            .marks
+            .get(id)
+            .is_some_and(|mark| matches!(mark.mark_type, MarkType::Comment { .. }));
+        is_comment && self.remove_mark(id)
+    }
+
     /// Remove all marks of a type from a range.
     pub fn remove_marks_in_range(&mut self, start: usize, end: usize, mark_type: &MarkType) {
         let to_remove: Vec<_> = self
-            .marks
-            .iter()
-            .filter(|(_, mark)| {
-                if mark.deleted || &mark.mark_type != mark_type {
-                    return false;
-                }
-                if let Some((ms, me)) = mark.range(&self.text) {
-                    // Overlaps with range
-                    ms < end && me > start
-                } else {
-                    false
-                }
-            })
-            .map(|(id, _)| id.clone())
+            .index
+            .overlapping(start, end, Some(mark_type))
+            .into_iter()
+            .map(|span| &self.marks[&span.id])
+            .filter(|mark| &mark.mark_type == mark_type)
+            .map(|mark| mark.id.clone())
             .collect();
 
         for id in to_remove {
@@ -389,34 +1071,42 @@ impl RichText {
 
     /// Get all marks at a position.
     pub fn marks_at(&self, position: usize) -> Vec<&Mark> {
-        self.marks
-            .values()
-            .filter(|m| m.covers(&self.text, position))
-            .collect()
+        self.marks_in_range(position, position.saturating_add(1))
     }
 
     /// Get all marks in a range.
     pub fn marks_in_range(&self, start: usize, end: usize) -> Vec<&Mark> {
-        self.marks
-            .values()
-            .filter(|mark| {
-                if mark.deleted {
-                    return false;
-                }
-                if let Some((ms, me)) = mark.range(&self.text) {
-                    ms < end && me > start
-                } else {
-                    false
-                }
-            })
+        self.index
+            .overlapping(start, end, None)
+            .into_iter()
+            .map(|span| &self.marks[&span.id])
             .collect()
     }
 
     /// Check if a position has a specific mark type.
     pub fn has_mark(&self, position: usize, mark_type: &MarkType) -> bool {
-        self.marks_at(position)
-            .iter()
-            .any(|m| &m.mark_type == mark_type)
+        self.index
+            .overlapping(position, position.saturating_add(1), Some(mark_type))
+            .into_iter()
+            .any(|span| &self.marks[&span.id].mark_type == mark_type)
+    }
+
+    /// Get the active marks that resolve, with their `(start, end)`
+    /// ranges, by start.
+    pub fn spans(&self) -> Vec<(usize, usize, &Mark)> {
+        self.index
+            .spans()
+            .into_iter()
+            .map(|span| (span.start, span.end, &self.marks[&span.id]))
+            .collect()
+    }
+
+    /// Get the active comments with their resolved `[start, end)` ranges.
+    pub fn comments(&self) -> Vec<(usize, usize, &Mark)> {
+        self.spans()
+            .into_iter()
+            .filter(|(_, _, mark)| matches!(mark.mark_type, MarkType::Comment { .. }))
+            .collect()
     }
 
     /// Get all marks (including deleted for debugging).
@@ -438,6 +1128,10 @@ impl RichText {
 
     /// Apply a delta from another replica.
     pub fn apply_delta(&mut self, delta: &RichTextDelta) {
+        // Anything but removals can move or resolve anchors
+        let rebuild =
+            delta.text_delta.as_ref().is_some_and(|d| !d.is_empty()) || !delta.add_marks.is_empty();
+
         // Apply text changes
         if let Some(text_delta) = &delta.text_delta {
             self.text.apply_delta(text_delta);
@@ -458,80 +1152,250 @@ impl RichText {
         // Apply mark removals
         for id in &delta.remove_marks {
             if let Some(mark) = self.marks.get_mut(id) {
+                if !mark.deleted && !rebuild {
+                    self.index.remove(mark);
+                }
                 mark.deleted = true;
             }
         }
+
+        for block in &delta.set_blocks {
+            merge_block(&mut self.blocks, block);
+        }
+
+        if rebuild {
+            self.rebuild_index();
+        }
+    }
+
+    /// Join with `other`, reporting the text edits it makes to `observer`.
+    ///
+    /// Marks are merged as in `join`; only text edits are reported.
+    pub fn join_observed(&self, other: &Self, observer: &mut impl TextObserver) -> Self {
+        let mut result = self.clone();
+        result.text = self.text.join_observed(&other.text, observer);
+        result.join_marks(other);
+        result
+    }
+
+    /// Merge `other`'s marks and blocks into ours, once the text has been
+    /// joined.
+    fn join_marks(&mut self, other: &Self) {
+        for block in other.blocks.values() {
+            merge_block(&mut self.blocks, block);
+        }
+        for (id, mark) in &other.marks {
+            self.marks
+                .entry(id.clone())
+                .and_modify(|m| {
+                    if mark.deleted {
+                        m.deleted = true;
+                    }
+                })
+                .or_insert_with(|| mark.clone());
+        }
+        self.rebuild_index();
+    }
+
+    /// Recompute the mark index from the marks and the text.
+    fn rebuild_index(&mut self) {
+        self.index = MarkIndex::build(&self.marks, &self.text);
     }
 
     // === Rendering ===
 
-    /// Render as HTML (basic implementation).
+    /// Render as HTML with the default [`HtmlOptions`].
+    ///
+    /// Text and attribute values are escaped, so the output never contains
+    /// markup that wasn't produced by a mark.
     pub fn to_html(&self) -> String {
-        let text = self.to_string();
-        if text.is_empty() {
+        self.to_html_with_options(&HtmlOptions::default())
+    }
+
+    /// Render as HTML.
+    ///
+    /// The text is cut at every mark boundary and each piece is wrapped in
+    /// the marks covering it. Marks that start earlier (or, from the same
+    /// place, end later) are opened outside the others, and a mark that
+    /// ends inside another is closed and reopened around the rest, so
+    /// overlapping marks always come out properly nested.
+    ///
+    /// Once any paragraph has been given a block type, every paragraph is
+    /// wrapped in its block's tags instead of being separated by newlines:
+    /// `<p>`, `<h1>` to `<h6>`, and `<li>` items, quote paragraphs and code
+    /// lines grouped into one `<ul>`, `<ol>`, `<blockquote>` or
+    /// `<pre><code>` per run. Marks are closed at the end of each paragraph.
+    pub fn to_html_with_options(&self, options: &HtmlOptions) -> String {
+        let chars: Vec<char> = self.text.to_string().chars().collect();
+        if chars.is_empty() {
             return String::new();
         }
 
-        // Collect marks and their ranges
-        let mut events: Vec<(usize, i8, &Mark)> = Vec::new();
-        for mark in self.active_marks() {
-            if let Some((start, end)) = mark.range(&self.text) {
-                events.push((start, 1, mark)); // 1 = open
-                events.push((end, -1, mark)); // -1 = close
+        let mut spans = self.spans();
+        spans.retain(|(start, end, _)| start < end);
+        // Outermost first; the sort is stable so ties keep the index order
+        spans.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+
+        let mut result = String::new();
+        if self.blocks.is_empty() {
+            push_inline_html(&mut result, &chars, &spans, 0, chars.len(), options);
+            return result;
+        }
+
+        let blocks = self.blocks();
+        for (i, (start, end, block_type)) in blocks.iter().enumerate() {
+            let group = block_type.group_tags();
+            let grouped_with = |other: Option<&(usize, usize, BlockType)>| {
+                group.is_some() && other.is_some_and(|(_, _, t)| t == block_type)
+            };
+            let first = !grouped_with(i.checked_sub(1).map(|p| &blocks[p]));
+            let last = !grouped_with(blocks.get(i + 1));
+
+            if let (Some((open, _)), true) = (group, first) {
+                result.push_str(open);
+            }
+            let (open, close) = block_type.item_tags();
+            result.push_str(&open);
+            push_inline_html(&mut result, &chars, &spans, *start, *end, options);
+            result.push_str(&close);
+            match (group, last) {
+                (Some((_, close)), true) => result.push_str(close),
+                (Some(_), false) if *block_type == BlockType::CodeBlock => result.push('\n'),
+                _ => {}
             }
         }
+        result
+    }
+}
 
-        // Sort: by position, then closes before opens at same position
-        events.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+/// Render `chars[from..to]` wrapped in the `spans` covering it, which are
+/// sorted outermost first.
+fn push_inline_html(
+    out: &mut String,
+    chars: &[char],
+    spans: &[(usize, usize, &Mark)],
+    from: usize,
+    to: usize,
+    options: &HtmlOptions,
+) {
+    let mut bounds: Vec<usize> = spans
+        .iter()
+        .flat_map(|(start, end, _)| [*start, *end])
+        .chain([from, to])
+        .map(|pos| pos.clamp(from, to))
+        .collect();
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    // Indices into `spans` of the tags currently open, outermost first
+    let mut open: Vec<usize> = Vec::new();
+    for piece in bounds.windows(2) {
+        let (from, to) = (piece[0], piece[1]);
+        let covering: Vec<usize> = (0..spans.len())
+            .filter(|&i| spans[i].0 <= from && spans[i].1 >= to)
+            .collect();
 
-        let mut result = String::new();
-        let chars: Vec<char> = text.chars().collect();
-        let mut pos = 0;
+        let kept = open
+            .iter()
+            .zip(&covering)
+            .take_while(|(a, b)| a == b)
+            .count();
+        for &i in open[kept..].iter().rev() {
+            out.push_str(&mark_close_tag(&spans[i].2.mark_type));
+        }
+        for &i in &covering[kept..] {
+            out.push_str(&mark_open_tag(spans[i].2, options));
+        }
+        open = covering;
 
-        let mut open_tags: Vec<&Mark> = Vec::new();
+        for &c in &chars[from..to] {
+            push_escaped(out, c);
+        }
+    }
+    for &i in open.iter().rev() {
+        out.push_str(&mark_close_tag(&spans[i].2.mark_type));
+    }
+}
 
-        for (event_pos, event_type, mark) in events {
-            // Output text before this event
-            while pos < event_pos && pos < chars.len() {
-                result.push(chars[pos]);
-                pos += 1;
-            }
+/// How [`RichText::to_html_with_options`] renders marks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HtmlOptions {
+    /// `rel` attribute written on links, e.g. `"noopener noreferrer"`.
+    pub link_rel: Option<String>,
+    /// `target` attribute written on links, e.g. `"_blank"`.
+    pub link_target: Option<String>,
+}
 
-            if event_type > 0 {
-                // Open tag
-                result.push_str(&mark_open_tag(&mark.mark_type));
-                open_tags.push(mark);
-            } else {
-                // Close tag
-                result.push_str(&mark_close_tag(&mark.mark_type));
-                open_tags.retain(|m| m.id != mark.id);
-            }
+impl HtmlOptions {
+    /// Links open in a new tab without access to the opening page.
+    pub fn external_links() -> Self {
+        Self {
+            link_rel: Some("noopener noreferrer".into()),
+            link_target: Some("_blank".into()),
         }
+    }
+}
 
-        // Output remaining text
-        while pos < chars.len() {
-            result.push(chars[pos]);
-            pos += 1;
-        }
+fn push_escaped(out: &mut String, c: char) {
+    match c {
+        '&' => out.push_str("&amp;"),
+        '<' => out.push_str("&lt;"),
+        '>' => out.push_str("&gt;"),
+        '"' => out.push_str("&quot;"),
+        _ => out.push(c),
+    }
+}
 
-        result
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        push_escaped(&mut out, c);
     }
+    out
 }
 
-fn mark_open_tag(mark_type: &MarkType) -> String {
-    match mark_type {
+fn mark_open_tag(mark: &Mark, options: &HtmlOptions) -> String {
+    match &mark.mark_type {
         MarkType::Bold => "<strong>".to_string(),
         MarkType::Italic => "<em>".to_string(),
         MarkType::Underline => "<u>".to_string(),
         MarkType::Strikethrough => "<s>".to_string(),
         MarkType::Code => "<code>".to_string(),
-        MarkType::Link { url } => format!("<a href=\"{}\">", url),
+        MarkType::Link { url } => {
+            let mut tag = format!("<a href=\"{}\"", escape(url));
+            if let Some(rel) = &options.link_rel {
+                tag.push_str(&format!(" rel=\"{}\"", escape(rel)));
+            }
+            if let Some(target) = &options.link_target {
+                tag.push_str(&format!(" target=\"{}\"", escape(target)));
+            }
+            tag.push('>');
+            tag
+        }
         MarkType::Comment { author, content } => format!(
-            "<span data-comment-author=\"{}\" data-comment=\"{}\">",
-            author, content
+            "<span data-comment-id=\"{}\" data-comment-author=\"{}\" data-comment=\"{}\">",
+            escape(&mark.id.to_string()),
+            escape(author),
+            escape(content)
         ),
-        MarkType::Highlight { color } => format!("<mark style=\"background-color:{}\">", color),
-        MarkType::Custom { name, value } => format!("<span data-{}=\"{}\">", name, value),
+        MarkType::Highlight { color } => format!(
+            "<mark style=\"background-color:{}\">",
+            escape(&color.replace(';', ""))
+        ),
+        MarkType::Custom { name, value } => {
+            // Only letters, digits and dashes make a safe attribute name
+            let name: String = name
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() {
+                        c.to_ascii_lowercase()
+                    } else {
+                        '-'
+                    }
+                })
+                .collect();
+            format!("<span data-{}=\"{}\">", name, escape(value))
+        }
     }
 }
 
@@ -557,7 +1421,9 @@ impl std::fmt::Display for RichText {
 
 impl PartialEq for RichText {
     fn eq(&self, other: &Self) -> bool {
-        self.to_string() == other.to_string() && self.marks.len() == other.marks.len()
+        self.to_string() == other.to_string()
+            && self.marks.len() == other.marks.len()
+            && self.blocks == other.blocks
     }
 }
 
@@ -574,19 +1440,7 @@ impl Lattice for RichText {
         // Merge text
         result.text = self.text.join(&other.text);
 
-        // Merge marks
-        for (id, mark) in &other.marks {
-            result
-                .marks
-                .entry(id.clone())
-                .and_modify(|m| {
-                    if mark.deleted {
-                        m.deleted = true;
-                    }
-                })
-                .or_insert_with(|| mark.clone());
-        }
-
+        result.join_marks(other);
         result
     }
 }
@@ -659,6 +1513,83 @@ mod tests {
             .any(|m| matches!(&m.mark_type, MarkType::Comment { .. })));
     }
 
+    #[test]
+    fn test_comment_anchors_converge_under_concurrent_edits() {
+        let mut doc1 = RichText::new("r1");
+        doc1.insert(0, "The quick brown fox");
+        let comment = doc1.comment(4, 15, "Alice", "Too many adjectives");
+        // A bold mark over the same range shows the anchor semantics comments must share
+        doc1.bold(4, 15);
+        doc1.take_delta();
+        let mut doc2 = doc1.fork("r2");
+
+        // Concurrently: r1 types inside the range, r2 deletes inside it and
+        // types at its end edge, which extends it
+        doc1.insert(10, "very ");
+        doc2.delete(4, 6);
+        doc2.insert(9, "ish");
+        let delta1 = doc1.take_delta().unwrap();
+        let delta2 = doc2.take_delta().unwrap();
+        doc1.apply_delta(&delta2);
+        doc2.apply_delta(&delta1);
+
+        assert_eq!(doc1.to_string(), doc2.to_string());
+        assert_eq!(doc1.to_html(), doc2.to_html());
+        for doc in [&doc1, &doc2] {
+            let comments = doc.comments();
+            assert_eq!(comments.len(), 1);
+            let (start, end, mark) = comments[0];
+            assert_eq!(mark.id, comment);
+            assert_eq!(doc.to_string(), "The very brownish fox");
+            assert_eq!(&doc.to_string()[start..end], "very brownish");
+            let bold: Vec<_> = doc
+                .spans()
+                .into_iter()
+                .filter(|(_, _, m)| m.mark_type == MarkType::Bold)
+                .map(|(s, e, _)| (s, e))
+                .collect();
+            assert_eq!(bold, [(start, end)]);
+        }
+    }
+
+    #[test]
+    fn test_remove_comment() {
+        let mut doc1 = RichText::new("r1");
+        doc1.insert(0, "Hello World");
+        let bold = doc1.bold(0, 5);
+        let comment = doc1.comment(6, 11, "Bob", "Which world?");
+        let mut doc2 = doc1.fork("r2");
+
+        // Only comments are removed through this path
+        assert!(!doc1.remove_comment(&bold));
+        assert!(doc1.has_mark(0, &MarkType::Bold));
+
+        // One replica resolves the comment while the other edits under it
+        assert!(doc1.remove_comment(&comment));
+        doc2.insert(11, "!");
+        let merged = doc1.join(&doc2);
+        assert_eq!(merged.to_html(), doc2.join(&doc1).to_html());
+        assert!(merged.comments().is_empty());
+        assert_eq!(merged.to_string(), "Hello World!");
+    }
+
+    #[test]
+    fn test_comment_html_carries_id() {
+        let mut doc = RichText::new("r1");
+        doc.insert(0, "Hi");
+        let id = doc.comment(0, 2, "Alice", "a \"quote\"");
+
+        assert_eq!(
+            doc.to_html(),
+            format!(
+                "<span data-comment-id=\"{}\" data-comment-author=\"Alice\" \
+                 data-comment=\"a &quot;quote&quot;\">Hi</span>",
+                id
+            )
+        );
+        assert_eq!(id.to_string(), format!("r1:{}", id.ulid));
+    }
+
     #[test]
     fn test_remove_mark() {
         let mut doc = RichText::new("r1");
@@ -699,6 +1630,50 @@ mod tests {
         assert!(doc2.has_mark(8, &MarkType::Italic));
     }
 
+    #[test]
+    fn test_join_observed_reports_text_edits() {
+        #[derive(Default)]
+        struct Edits(Vec<String>);
+        impl TextObserver for Edits {
+            fn on_insert(&mut self, position: usize, text: &str) {
+                self.0.push(format!("+{}:{}", position, text));
+            }
+            fn on_delete(&mut self, position: usize, length: usize) {
+                self.0.push(format!("-{}:{}", position, length));
+            }
+        }
+
+        let mut doc1 = RichText::new("r1");
+        doc1.insert(0, "Hello World");
+        let mut doc2 = doc1.fork("r2");
+        doc2.delete(0, 6);
+        doc2.insert(5, "!");
+        doc2.bold(0, 5);
+
+        let mut edits = Edits::default();
+        let merged = doc1.join_observed(&doc2, &mut edits);
+        assert_eq!(edits.0, ["-0:6", "+5:!"]);
+        assert_eq!(merged.to_string(), "World!");
+        assert_eq!(merged.to_html(), doc1.join(&doc2).to_html());
+        assert!(merged.has_mark(0, &MarkType::Bold));
+    }
+
+    #[test]
+    fn test_delta_covers_every_edit_since_taken() {
+        let mut doc1 = RichText::new("r1");
+        let mut doc2 = RichText::new("r2");
+
+        doc1.insert(0, "Hello");
+        doc1.insert(5, " World");
+        doc1.delete(0, 1);
+        doc1.bold(0, 4);
+        doc2.apply_delta(&doc1.take_delta().unwrap());
+
+        assert_eq!(doc2.to_string(), "ello World");
+        assert!(doc2.has_mark(2, &MarkType::Bold));
+        assert!(doc1.take_delta().is_none());
+    }
+
     #[test]
     fn test_html_rendering() {
         let mut doc = RichText::new("r1");
@@ -710,6 +1685,207 @@ mod tests {
         assert!(html.contains("World"));
     }
 
+    #[test]
+    fn test_html_escaping() {
+        let mut doc = RichText::new("r1");
+        doc.insert(0, "<script>a & b</script>");
+        doc.link(0, 8, "x\" onclick=\"alert(1)");
+        doc.add_mark(
+            9,
+            10,
+            MarkType::Custom {
+                name: "x onload=y".into(),
+                value: "\"".into(),
+            },
+        );
+
+        assert_eq!(
+            doc.to_html(),
+            "<a href=\"x&quot; onclick=&quot;alert(1)\">&lt;script&gt;</a>a\
+             <span data-x-onload-y=\"&quot;\"> </span>&amp; b&lt;/script&gt;"
+        );
+    }
+
+    #[test]
+    fn test_html_overlapping_marks_nest() {
+        let mut doc = RichText::new("r1");
+        doc.insert(0, "aaaaabbbbbccccc");
+        doc.bold(0, 10);
+        doc.italic(5, 15);
+
+        assert_eq!(
+            doc.to_html(),
+            "<strong>aaaaa<em>bbbbb</em></strong><em>ccccc</em>"
+        );
+    }
+
+    #[test]
+    fn test_html_link_options() {
+        let mut doc = RichText::new("r1");
+        doc.insert(0, "say \"hi\"");
+        doc.link(0, 8, "https://x.test/?q=\"a\"&b");
+
+        assert_eq!(
+            doc.to_html(),
+            "<a href=\"https://x.test/?q=&quot;a&quot;&amp;b\">say &quot;hi&quot;</a>"
+        );
+        assert_eq!(
+            doc.to_html_with_options(&HtmlOptions::external_links()),
+            "<a href=\"https://x.test/?q=&quot;a&quot;&amp;b\" rel=\"noopener noreferrer\" \
+             target=\"_blank\">say &quot;hi&quot;</a>"
+        );
+    }
+
+    #[test]
+    fn test_html_preserves_visible_text() {
+        let mut doc = RichText::new("r1");
+        doc.insert(0, "<script>x</script> & more text");
+        doc.bold(2, 12);
+        doc.italic(6, 20);
+        doc.link(10, 25, "https://x.test");
+        doc.underline(0, 30);
+
+        let html = doc.to_html();
+        assert!(!html.contains("<script>"));
+        let copy = RichText::from_html("r2", &html);
+        assert_eq!(copy.to_string(), doc.to_string());
+        for pos in 0..doc.len() {
+            assert_eq!(
+                copy.has_mark(pos, &MarkType::Bold),
+                doc.has_mark(pos, &MarkType::Bold)
+            );
+            assert_eq!(
+                copy.has_mark(pos, &MarkType::Italic),
+                doc.has_mark(pos, &MarkType::Italic)
+            );
+        }
+    }
+
+    #[test]
+    fn test_html_blocks() {
+        let mut doc = RichText::new("r1");
+        doc.insert(0, "Title\none\ntwo\nbody\nfirst\nsecond");
+        doc.bold(10, 13);
+        doc.set_block_type(0, BlockType::Heading(1));
+        doc.set_block_type(6, BlockType::BulletList);
+        doc.set_block_type(11, BlockType::BulletList);
+        doc.set_block_type(20, BlockType::OrderedList);
+        doc.set_block_type(27, BlockType::OrderedList);
+
+        assert_eq!(
+            doc.to_html(),
+            "<h1>Title</h1><ul><li>one</li><li><strong>two</strong></li></ul><p>body</p>\
+             <ol><li>first</li><li>second</li></ol>"
+        );
+        assert_eq!(doc.block_type_at(8), BlockType::BulletList);
+        assert_eq!(doc.blocks()[3], (14, 18, BlockType::Paragraph));
+
+        let restored: RichText =
+            serde_json::from_str(&serde_json::to_string(&doc).unwrap()).unwrap();
+        assert_eq!(restored.to_html(), doc.to_html());
+    }
+
+    #[test]
+    fn test_html_code_and_quote_blocks() {
+        let mut doc = RichText::new("r1");
+        doc.insert(0, "a < b\nc\nsaid");
+        doc.set_block_type(0, BlockType::CodeBlock);
+        doc.set_block_type(6, BlockType::CodeBlock);
+        doc.set_block_type(8, BlockType::Quote);
+
+        assert_eq!(
+            doc.to_html(),
+            "<pre><code>a &lt; b\nc</code></pre><blockquote><p>said</p></blockquote>"
+        );
+    }
+
+    #[test]
+    fn test_split_carries_block_type() {
+        let mut doc = RichText::new("r1");
+        doc.insert(0, "Title\nitem");
+        doc.set_block_type(0, BlockType::Heading(2));
+        doc.set_block_type(6, BlockType::BulletList);
+
+        // Enter in a list item starts another item
+        doc.insert(10, "\nnext");
+        // Enter at the end of a heading starts a paragraph
+        doc.insert(5, "\nintro");
+        assert_eq!(
+            doc.blocks(),
+            vec![
+                (0, 5, BlockType::Heading(2)),
+                (6, 11, BlockType::Paragraph),
+                (12, 16, BlockType::BulletList),
+                (17, 21, BlockType::BulletList),
+            ]
+        );
+
+        // Enter at the start of a heading moves it down
+        doc.insert(0, "\n");
+        assert_eq!(doc.block_type_at(0), BlockType::Paragraph);
+        assert_eq!(doc.block_type_at(1), BlockType::Heading(2));
+
+        // Deleting the newline joins the item to the paragraph before
+        doc.delete(12, 1);
+        assert_eq!(doc.blocks()[2], (7, 16, BlockType::Paragraph));
+    }
+
+    #[test]
+    fn test_concurrent_block_types_converge() {
+        let mut a = RichText::new("a");
+        a.insert(0, "one\ntwo");
+        let mut b = a.fork("b");
+
+        a.set_block_type(0, BlockType::Heading(1));
+        b.set_block_type(1, BlockType::BulletList);
+        b.set_block_type(5, BlockType::Quote);
+
+        let ab = a.join(&b);
+        let ba = b.join(&a);
+        assert_eq!(ab.blocks(), ba.blocks());
+        assert_eq!(ab.to_html(), ba.to_html());
+        // Equal clocks: the higher replica ID wins
+        assert_eq!(ab.block_type_at(0), BlockType::BulletList);
+        assert_eq!(ab.block_type_at(5), BlockType::Quote);
+
+        // A change made after seeing the other wins
+        let mut a = ab.fork("a");
+        a.set_block_type(0, BlockType::Heading(1));
+        let delta = a.take_delta().unwrap();
+        let mut b = ba.fork("b");
+        b.apply_delta(&delta);
+        assert_eq!(b.block_type_at(0), BlockType::Heading(1));
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_concurrent_block_splits_converge() {
+        let mut a = RichText::new("a");
+        a.insert(0, "first item");
+        a.set_block_type(0, BlockType::BulletList);
+        let mut b = a.fork("b");
+
+        // Both split the same item; one also makes it a numbered one
+        a.insert(5, "\n");
+        b.insert(10, "\nthird");
+        b.set_block_type(0, BlockType::OrderedList);
+        let (da, db) = (a.take_delta().unwrap(), b.take_delta().unwrap());
+        a.apply_delta(&db);
+        b.apply_delta(&da);
+
+        assert_eq!(a.to_string(), "first\n item\nthird");
+        assert_eq!(a.blocks(), b.blocks());
+        assert_eq!(a.to_html(), b.to_html());
+        assert_eq!(
+            a.blocks(),
+            vec![
+                (0, 5, BlockType::OrderedList),
+                (6, 11, BlockType::BulletList),
+                (12, 17, BlockType::BulletList),
+            ]
+        );
+    }
+
     #[test]
     fn test_insert_expands_mark() {
         let mut doc = RichText::new("r1");
@@ -756,4 +1932,168 @@ mod tests {
         // Should include Bold (ends at 5), Italic (6-11), and Underline (starts at 12)
         assert!(marks.len() >= 2);
     }
+
+    #[test]
+    fn test_version_vector() {
+        let mut a = RichText::new("a");
+        let mut b = RichText::new("b");
+        a.insert(0, "Hi");
+        assert_eq!(a.version_vector().get("a"), 2);
+
+        // Marks advance it like text edits
+        let bold = a.bold(0, 2);
+        let before = a.version_vector();
+        assert_eq!(before.get("a"), 3);
+
+        b = b.join(&a);
+        b.insert(2, "!");
+        assert!(b.version_vector().strictly_dominates(&before));
+
+        // Divergent replicas each miss the other's edits, deletions and
+        // formatting included
+        a.take_delta();
+        a.delete(0, 1);
+        a.remove_mark(&bold);
+        let (va, vb) = (a.version_vector(), b.version_vector());
+        assert!(va.concurrent_with(&vb));
+        assert_eq!(vb.diff(&va), vec![("b".to_string(), 1, 4)]);
+        assert_eq!(va.diff(&vb), vec![("a".to_string(), 4, 5)]);
+
+        // Applying the delta catches the other replica up
+        let mut c = b.clone();
+        c.apply_delta(&a.take_delta().unwrap());
+        assert_eq!(c.version_vector(), va.merged(&vb));
+
+        a.set_block_type(0, BlockType::Heading(1));
+        assert_eq!(a.version_vector().get("a"), 6);
+
+        // The vector survives serialization and merging
+        let va = a.version_vector();
+        let restored: RichText = serde_json::from_str(&serde_json::to_string(&a).unwrap()).unwrap();
+        assert_eq!(restored.version_vector(), va);
+        assert_eq!(restored.join(&b).version_vector(), va.merged(&vb));
+    }
+
+    /// Active marks resolved one at a time, as the index should hold them.
+    fn resolved(doc: &RichText) -> Vec<(usize, usize, MarkId)> {
+        let mut spans: Vec<_> = doc
+            .active_marks()
+            .filter_map(|m| {
+                let (start, end) = m.range(doc.text())?;
+                Some((start, end, m.id.clone()))
+            })
+            .collect();
+        spans.sort_by_key(|(start, end, id)| (*start, *end, id.replica.clone(), id.ulid.clone()));
+        spans
+    }
+
+    fn assert_indexed(doc: &RichText) {
+        let spans: Vec<_> = doc
+            .spans()
+            .into_iter()
+            .map(|(start, end, mark)| (start, end, mark.id.clone()))
+            .collect();
+        assert_eq!(spans, resolved(doc));
+        for position in 0..=doc.len() {
+            let mut indexed: Vec<_> = doc.marks_at(position).iter().map(|m| &m.id).collect();
+            let mut scanned: Vec<_> = doc
+                .all_marks()
+                .filter(|m| m.covers(doc.text(), position))
+                .map(|m| &m.id)
+                .collect();
+            indexed.sort_by_key(|id| &id.ulid);
+            scanned.sort_by_key(|id| &id.ulid);
+            assert_eq!(indexed, scanned, "marks at {}", position);
+        }
+    }
+
+    #[test]
+    fn test_spans() {
+        let mut doc = RichText::new("r1");
+        doc.insert(0, "Hello World");
+        let italic = doc.italic(6, 11);
+        let bold = doc.bold(0, 5);
+
+        let spans: Vec<_> = doc
+            .spans()
+            .into_iter()
+            .map(|(start, end, mark)| (start, end, mark.id.clone()))
+            .collect();
+        assert_eq!(spans, vec![(0, 5, bold), (6, 11, italic.clone())]);
+
+        // Both are anchored to the space; deleting it drops them from rendering
+        doc.delete(5, 1);
+        assert!(doc.spans().is_empty());
+        assert_eq!(doc.to_html(), "HelloWorld");
+        assert!(!doc.all_marks().find(|m| m.id == italic).unwrap().deleted);
+    }
+
+    #[test]
+    fn test_index_follows_edits_and_merges() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let types = [
+            MarkType::Bold,
+            MarkType::Italic,
+            MarkType::Link { url: "u".into() },
+            MarkType::Comment {
+                author: "a".into(),
+                content: "c".into(),
+            },
+        ];
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut docs = [RichText::new("r1"), RichText::new("r2")];
+        for step in 0..200 {
+            let doc = &mut docs[rng.gen_range(0..2)];
+            let len = doc.len();
+            match rng.gen_range(0..6) {
+                0 | 1 => doc.insert(rng.gen_range(0..=len + 1), "ab"),
+                2 if len > 0 => doc.delete(rng.gen_range(0..len), rng.gen_range(1..4)),
+                3 if len > 0 => {
+                    let start = rng.gen_range(0..len);
+                    doc.replace(start, (start + 2).min(len), "xyz");
+                }
+                4 => {
+                    let start = rng.gen_range(0..=len);
+                    let end = rng.gen_range(start..=len);
+                    doc.add_mark(start, end, types[rng.gen_range(0..types.len())].clone());
+                }
+                _ => {
+                    let id = doc.active_marks().next().map(|m| m.id.clone());
+                    if let Some(id) = id {
+                        doc.remove_mark(&id);
+                    }
+                }
+            }
+            assert_indexed(doc);
+
+            if step % 10 == 9 {
+                let [a, b] = &mut docs;
+                if let Some(delta) = a.take_delta() {
+                    b.apply_delta(&delta);
+                }
+                assert_indexed(b);
+                *a = a.join(b);
+                assert_indexed(a);
+            }
+        }
+        assert_eq!(docs[0].to_string(), docs[1].join(&docs[0]).to_string());
+    }
+
+    #[test]
+    fn test_index_rebuilt_on_deserialize() {
+        let mut doc = RichText::new("r1");
+        doc.insert(0, "one two three");
+        doc.bold(0, 7);
+        let removed = doc.italic(4, 13);
+        doc.remove_mark(&removed);
+        doc.link(8, 13, "https://example.com");
+
+        let restored: RichText =
+            serde_json::from_str(&serde_json::to_string(&doc).unwrap()).unwrap();
+        assert_indexed(&restored);
+        assert_eq!(restored.to_html(), doc.to_html());
+        assert_eq!(restored.marks_at(5).len(), 1);
+    }
 }
@@ -0,0 +1,284 @@
+//! Full-text search over text and rich text documents.
+//!
+//! [`SearchIndex`] is an inverted index from tokens to the documents and
+//! character offsets where they occur. A [`DocumentStore`] with search
+//! enabled keeps it current after every change to a document's text, local
+//! or replicated: only the touched document is re-tokenized, and only the
+//! tokens it gained or lost are updated in the shared index.
+//!
+//! Hits are eventually consistent with the documents: they reflect each
+//! replica's current text, so replicas that have not yet seen the same
+//! deltas return different hits, and agree again once they converge.
+//!
+//! [`DocumentStore`]: crate::document::DocumentStore
+
+use crate::document::DocumentId;
+use crate::error::DbError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Characters of context on each side of a hit in its snippet.
+const SNIPPET_CONTEXT: usize = 20;
+
+/// How text is split into searchable tokens.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchConfig {
+    /// Shorter tokens are not indexed.
+    pub min_token_len: usize,
+    /// Fold tokens and queries to lowercase.
+    pub case_insensitive: bool,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            min_token_len: 2,
+            case_insensitive: true,
+        }
+    }
+}
+
+/// A search result.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchHit {
+    /// The matching document.
+    pub doc: DocumentId,
+    /// Character offset of the match in the document's text.
+    pub offset: usize,
+    /// The text around the match.
+    pub snippet: String,
+}
+
+/// Inverted index from tokens to the documents containing them.
+///
+/// Serializable, so it can be saved next to the documents and restored with
+/// [`DocumentStore::restore_search`](crate::document::DocumentStore::restore_search)
+/// instead of being rebuilt.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchIndex {
+    config: SearchConfig,
+    /// Documents containing each token.
+    postings: BTreeMap<String, BTreeSet<DocumentId>>,
+    /// Offsets of every token in each indexed document.
+    documents: BTreeMap<DocumentId, BTreeMap<String, Vec<usize>>>,
+}
+
+impl SearchIndex {
+    /// Create an empty index.
+    pub fn new(config: SearchConfig) -> Self {
+        Self {
+            config,
+            postings: BTreeMap::new(),
+            documents: BTreeMap::new(),
+        }
+    }
+
+    /// Get the tokenizer configuration.
+    pub fn config(&self) -> &SearchConfig {
+        &self.config
+    }
+
+    /// Number of distinct indexed tokens.
+    pub fn token_count(&self) -> usize {
+        self.postings.len()
+    }
+
+    /// Number of documents with at least one indexed token.
+    pub fn document_count(&self) -> usize {
+        self.documents.len()
+    }
+
+    /// Index a document's current text, replacing what was indexed for it.
+    pub fn update(&mut self, id: &DocumentId, text: &str) {
+        let mut tokens: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (offset, token) in self.tokenize(text) {
+            tokens.entry(token).or_default().push(offset);
+        }
+
+        let old = self.documents.remove(id).unwrap_or_default();
+        for token in old.keys().filter(|t| !tokens.contains_key(*t)) {
+            self.unpost(token, id);
+        }
+        for token in tokens.keys().filter(|t| !old.contains_key(*t)) {
+            self.postings
+                .entry(token.clone())
+                .or_default()
+                .insert(id.clone());
+        }
+
+        if !tokens.is_empty() {
+            self.documents.insert(id.clone(), tokens);
+        }
+    }
+
+    /// Drop a document from the index.
+    pub fn remove(&mut self, id: &DocumentId) {
+        if let Some(tokens) = self.documents.remove(id) {
+            for token in tokens.keys() {
+                self.unpost(token, id);
+            }
+        }
+    }
+
+    fn unpost(&mut self, token: &str, id: &DocumentId) {
+        if let Some(docs) = self.postings.get_mut(token) {
+            docs.remove(id);
+            if docs.is_empty() {
+                self.postings.remove(token);
+            }
+        }
+    }
+
+    /// Find documents containing every token of `query`.
+    ///
+    /// Returns `(document, offset, length)` for each occurrence of the
+    /// query's first token in a matching document, in document ID order.
+    pub fn matches(&self, query: &str) -> Vec<(DocumentId, usize, usize)> {
+        let mut tokens: Vec<String> = Vec::new();
+        for (_, token) in self.tokenize(query) {
+            if !tokens.contains(&token) {
+                tokens.push(token);
+            }
+        }
+        let Some(first) = tokens.first() else {
+            return Vec::new();
+        };
+        let Some(candidates) = self.postings.get(first) else {
+            return Vec::new();
+        };
+
+        let mut matches = Vec::new();
+        for doc in candidates {
+            let doc_tokens = &self.documents[doc];
+            if !tokens.iter().all(|t| doc_tokens.contains_key(t)) {
+                continue;
+            }
+            let length = first.chars().count();
+            for &offset in &doc_tokens[first] {
+                matches.push((doc.clone(), offset, length));
+            }
+        }
+        matches
+    }
+
+    /// Split text into `(char offset, token)` pairs.
+    fn tokenize(&self, text: &str) -> Vec<(usize, String)> {
+        let mut tokens = Vec::new();
+        let mut current = String::new();
+        let mut start = 0;
+        let mut len = 0;
+
+        for (offset, ch) in text.chars().chain(std::iter::once(' ')).enumerate() {
+            if ch.is_alphanumeric() {
+                if current.is_empty() {
+                    start = offset;
+                }
+                if self.config.case_insensitive {
+                    current.extend(ch.to_lowercase());
+                } else {
+                    current.push(ch);
+                }
+                len += 1;
+            } else if !current.is_empty() {
+                let token = std::mem::take(&mut current);
+                if len >= self.config.min_token_len {
+                    tokens.push((start, token));
+                }
+                len = 0;
+            }
+        }
+        tokens
+    }
+
+    /// Encode the index for storage.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, DbError> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    /// Decode an index produced by [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DbError> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+/// Build a hit with the text around `offset`.
+pub(crate) fn hit(doc: DocumentId, text: &str, offset: usize, length: usize) -> SearchHit {
+    let start = offset.saturating_sub(SNIPPET_CONTEXT);
+    let snippet = text
+        .chars()
+        .skip(start)
+        .take(offset - start + length + SNIPPET_CONTEXT)
+        .collect();
+    SearchHit {
+        doc,
+        offset,
+        snippet,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(id: &str) -> DocumentId {
+        DocumentId::from_string(id)
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let index = SearchIndex::new(SearchConfig::default());
+        assert_eq!(
+            index.tokenize("Q3 Budget, a draft!"),
+            vec![
+                (0, "q3".to_string()),
+                (3, "budget".to_string()),
+                (13, "draft".to_string())
+            ]
+        );
+
+        let index = SearchIndex::new(SearchConfig {
+            min_token_len: 1,
+            case_insensitive: false,
+        });
+        assert_eq!(index.tokenize("Ünï a")[0], (0, "Ünï".to_string()));
+        assert_eq!(index.tokenize("Ünï a")[1], (4, "a".to_string()));
+    }
+
+    #[test]
+    fn test_update_replaces_document_tokens() {
+        let mut index = SearchIndex::new(SearchConfig::default());
+        index.update(&doc("a"), "budget review");
+        index.update(&doc("b"), "budget draft");
+        assert_eq!(index.matches("budget").len(), 2);
+        assert_eq!(index.matches("BUDGET draft"), vec![(doc("b"), 0, 6)]);
+
+        index.update(&doc("b"), "final draft");
+        assert_eq!(index.matches("budget"), vec![(doc("a"), 0, 6)]);
+        assert_eq!(index.matches("draft"), vec![(doc("b"), 6, 5)]);
+
+        index.remove(&doc("a"));
+        assert!(index.matches("budget").is_empty());
+        assert_eq!(index.token_count(), 2);
+        assert_eq!(index.document_count(), 1);
+    }
+
+    #[test]
+    fn test_index_round_trip() {
+        let mut index = SearchIndex::new(SearchConfig::default());
+        index.update(&doc("a"), "budget review budget");
+
+        let restored = SearchIndex::from_bytes(&index.to_bytes().unwrap()).unwrap();
+        assert_eq!(restored, index);
+        assert_eq!(restored.matches("budget").len(), 2);
+    }
+
+    #[test]
+    fn test_snippet() {
+        let text = "The quarterly budget for the marketing team is overdue again.";
+        let hit = hit(doc("a"), text, 14, 6);
+        assert_eq!(hit.snippet, "The quarterly budget for the marketing t");
+
+        let hit = super::hit(doc("a"), "budget", 0, 6);
+        assert_eq!(hit.snippet, "budget");
+    }
+}
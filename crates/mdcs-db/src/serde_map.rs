@@ -0,0 +1,27 @@
+//! Serialize maps with structured keys as lists of pairs.
+//!
+//! Formats like JSON only allow string map keys, so CRDT states keyed by
+//! IDs such as `TextId` or `ListId` use `#[serde(with = "crate::serde_map")]`.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+pub fn serialize<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    K: Serialize,
+    V: Serialize,
+    S: Serializer,
+{
+    serializer.collect_seq(map.iter())
+}
+
+pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let pairs = Vec::<(K, V)>::deserialize(deserializer)?;
+    Ok(pairs.into_iter().collect())
+}
@@ -5,9 +5,18 @@
 //! - Operation grouping for atomic undo
 //! - Causal tracking to handle concurrent edits
 //! - Inverse operation generation
+//! - Bounded history that never splits a group
+//! - Bursts of edits grouped by time, for typing
+//! - Text positions kept current through remote edits
+//! - Export and import of the undo/redo stacks, for persistence
+//!
+//! An imported history may be older than the document it is applied to, so
+//! operations are checked against the document when they are undone or
+//! redone (see [`UndoManager::undo_with`]).
 
+use mdcs_core::clock::SharedClock;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use ulid::Ulid;
 
 /// Unique identifier for an operation.
@@ -58,6 +67,15 @@ pub enum TextOperation {
 }
 
 impl TextOperation {
+    /// Position of the text this operation touches.
+    fn position_mut(&mut self) -> &mut usize {
+        match self {
+            TextOperation::Insert { position, .. }
+            | TextOperation::Delete { position, .. }
+            | TextOperation::Replace { position, .. } => position,
+        }
+    }
+
     /// Create the inverse operation.
     pub fn inverse(&self) -> Self {
         match self {
@@ -226,6 +244,9 @@ pub struct Operation {
     pub group_id: Option<GroupId>,
     /// Whether this operation has been undone.
     pub undone: bool,
+    /// Wall-clock time in milliseconds the operation entered the history.
+    #[serde(default)]
+    pub recorded_at: u64,
 }
 
 impl Operation {
@@ -243,6 +264,7 @@ impl Operation {
             timestamp,
             group_id: None,
             undone: false,
+            recorded_at: 0,
         }
     }
 
@@ -252,6 +274,58 @@ impl Operation {
     }
 }
 
+/// Limits on the history an [`UndoManager`] keeps.
+///
+/// When any limit is exceeded, the oldest operations are evicted together
+/// with the rest of their group, so a group is either undone whole or not
+/// at all. The group still being recorded is never evicted.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UndoConfig {
+    /// Most operations kept, local and remote.
+    pub max_operations: usize,
+    /// Most groups kept; an ungrouped operation counts as its own group.
+    pub max_groups: usize,
+    /// Operations older than this are evicted, if set.
+    pub max_age_ms: Option<u64>,
+    /// If set, an operation recorded within this many milliseconds of the
+    /// previous one, outside an explicit group, joins its group.
+    #[serde(default)]
+    pub group_window_ms: Option<u64>,
+}
+
+impl Default for UndoConfig {
+    fn default() -> Self {
+        Self {
+            max_operations: 1000,
+            max_groups: 1000,
+            max_age_ms: None,
+            group_window_ms: None,
+        }
+    }
+}
+
+/// The undo/redo state of an [`UndoManager`], for persisting it.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct UndoHistory {
+    /// Lamport clock.
+    pub clock: u64,
+    /// Operation history, oldest first.
+    pub operations: Vec<Operation>,
+    /// Undo stack, most recent last.
+    pub undo_stack: Vec<OperationId>,
+    /// Redo stack, most recent last.
+    pub redo_stack: Vec<OperationId>,
+}
+
+/// What [`UndoManager::undo_with`] or [`UndoManager::redo_with`] did.
+#[derive(Clone, Debug, Default)]
+pub struct UndoOutcome {
+    /// Operations applied to the document, in the order applied.
+    pub applied: Vec<UndoableOperation>,
+    /// Operations the document rejected. They are dropped from the history.
+    pub skipped: Vec<UndoableOperation>,
+}
+
 /// An undo manager for a single document.
 #[derive(Clone, Debug)]
 pub struct UndoManager {
@@ -262,15 +336,20 @@ pub struct UndoManager {
     /// Lamport clock.
     clock: u64,
     /// Operation history (all operations, including from other replicas).
-    history: Vec<Operation>,
+    history: VecDeque<Operation>,
     /// Undo stack (local operations that can be undone).
     undo_stack: VecDeque<OperationId>,
     /// Redo stack (local operations that can be redone).
     redo_stack: VecDeque<OperationId>,
     /// Current group being built.
     current_group: Option<GroupId>,
-    /// Maximum history size.
-    max_history: usize,
+    /// Group of the last burst of operations and when it last grew, under
+    /// `group_window_ms`.
+    burst: Option<(GroupId, u64)>,
+    /// History limits.
+    config: UndoConfig,
+    /// Time source for `max_age_ms`.
+    wall_clock: SharedClock,
 }
 
 impl UndoManager {
@@ -280,17 +359,42 @@ impl UndoManager {
             document_id: document_id.into(),
             replica_id: replica_id.into(),
             clock: 0,
-            history: Vec::new(),
+            history: VecDeque::new(),
             undo_stack: VecDeque::new(),
             redo_stack: VecDeque::new(),
             current_group: None,
-            max_history: 1000,
+            burst: None,
+            config: UndoConfig::default(),
+            wall_clock: SharedClock::default(),
         }
     }
 
+    /// Limit the history with `config`.
+    pub fn with_config(mut self, config: UndoConfig) -> Self {
+        self.set_config(config);
+        self
+    }
+
+    /// Use `clock` to time operations for `max_age_ms`.
+    pub fn with_clock(mut self, clock: impl Into<SharedClock>) -> Self {
+        self.wall_clock = clock.into();
+        self
+    }
+
+    /// Get the history limits.
+    pub fn config(&self) -> &UndoConfig {
+        &self.config
+    }
+
+    /// Change the history limits, evicting what no longer fits.
+    pub fn set_config(&mut self, config: UndoConfig) {
+        self.config = config;
+        self.trim_history();
+    }
+
     /// Set the maximum history size.
     pub fn set_max_history(&mut self, max: usize) {
-        self.max_history = max;
+        self.config.max_operations = max;
         self.trim_history();
     }
 
@@ -299,13 +403,14 @@ impl UndoManager {
         self.clock += 1;
 
         let mut op = Operation::new(&self.document_id, &self.replica_id, operation, self.clock);
-
-        if let Some(group_id) = &self.current_group {
-            op.group_id = Some(group_id.clone());
-        }
+        op.recorded_at = self.wall_clock.now_millis();
+        op.group_id = self
+            .current_group
+            .clone()
+            .or_else(|| self.burst_group(op.recorded_at));
 
         let op_id = op.id.clone();
-        self.history.push(op);
+        self.history.push_back(op);
         self.undo_stack.push_back(op_id);
 
         // Clear redo stack when new operation is recorded
@@ -313,27 +418,71 @@ impl UndoManager {
 
         self.trim_history();
 
-        self.history.last().unwrap()
+        self.history.back().unwrap()
+    }
+
+    /// Group for an operation recorded at `now` outside an explicit group:
+    /// the current burst's, if it is recent enough, or a new one.
+    fn burst_group(&mut self, now: u64) -> Option<GroupId> {
+        let window = self.config.group_window_ms?;
+        let group_id = match self.burst.take() {
+            Some((group_id, last)) if now.saturating_sub(last) <= window => group_id,
+            _ => GroupId::new(),
+        };
+        self.burst = Some((group_id.clone(), now));
+        Some(group_id)
+    }
+
+    /// Shift recorded text operations past a remote edit that replaced
+    /// `deleted` characters at `position` with `inserted` new ones.
+    ///
+    /// Call it for each edit a merge makes, in order, so undo and redo
+    /// still find their text. Operations inside the replaced run move to
+    /// its start; undoing them then fails the document's check.
+    pub fn transform_text(&mut self, position: usize, deleted: usize, inserted: usize) {
+        for op in &mut self.history {
+            let UndoableOperation::Text(text_op) = &mut op.operation else {
+                continue;
+            };
+            let at = text_op.position_mut();
+            if *at < position {
+                continue;
+            }
+            *at = if *at < position + deleted {
+                position
+            } else {
+                *at - deleted + inserted
+            };
+        }
     }
 
     /// Record a remote operation (from another replica).
-    pub fn record_remote(&mut self, operation: Operation) {
+    pub fn record_remote(&mut self, mut operation: Operation) {
         // Update clock
         self.clock = self.clock.max(operation.timestamp) + 1;
-        self.history.push(operation);
+        operation.recorded_at = self.wall_clock.now_millis();
+        self.history.push_back(operation);
         self.trim_history();
     }
 
     /// Start a new operation group.
     pub fn start_group(&mut self) -> GroupId {
+        self.burst = None;
         let group_id = GroupId::new();
         self.current_group = Some(group_id.clone());
         group_id
     }
 
+    /// Check if an operation group is open.
+    pub fn in_group(&self) -> bool {
+        self.current_group.is_some()
+    }
+
     /// End the current operation group.
     pub fn end_group(&mut self) {
         self.current_group = None;
+        self.burst = None;
+        self.trim_history();
     }
 
     /// Check if we can undo.
@@ -349,89 +498,127 @@ impl UndoManager {
     /// Undo the last operation (or group).
     /// Returns the inverse operations to apply.
     pub fn undo(&mut self) -> Vec<UndoableOperation> {
-        if self.undo_stack.is_empty() {
-            return Vec::new();
-        }
+        self.undo_with(|_| true).applied
+    }
 
-        let op_id = self.undo_stack.pop_back().unwrap();
-        let mut inverses = Vec::new();
+    /// Undo the last operation (or group), applying each inverse with
+    /// `apply`, last operation first.
+    ///
+    /// `apply` returns `false` if the document no longer allows the
+    /// operation, e.g. the text an inverse would delete was changed by a
+    /// remote edit since. Such operations are skipped and dropped from the
+    /// history, so they can't be redone either; the rest of the group is
+    /// still undone. Expired operations are evicted first.
+    pub fn undo_with(&mut self, mut apply: impl FnMut(&UndoableOperation) -> bool) -> UndoOutcome {
+        self.trim_history();
+        self.burst = None;
+        let mut outcome = UndoOutcome::default();
+        let Some(op_id) = self.undo_stack.pop_back() else {
+            return outcome;
+        };
 
-        // Find the operation's group id first
-        let group_id = self
-            .history
-            .iter()
-            .find(|o| o.id == op_id)
-            .and_then(|o| o.group_id.clone());
-
-        if let Some(group_id) = group_id {
-            // Find all operations in this group and undo them
-            for op in self.history.iter_mut() {
-                if op.group_id.as_ref() == Some(&group_id) && !op.undone {
-                    inverses.push(op.operation.inverse());
-                    op.undone = true;
-                }
+        // Remove all group operations from undo stack
+        let unit = self.unit_of(&op_id);
+        let unit_ids: HashSet<_> = unit.iter().map(|&i| self.history[i].id.clone()).collect();
+        self.undo_stack.retain(|id| !unit_ids.contains(id));
+
+        let mut redo_id = None;
+        let mut skipped = HashSet::new();
+        // Last operation first
+        for &i in unit.iter().rev() {
+            let op = &mut self.history[i];
+            if op.undone {
+                continue;
             }
-
-            // Remove all group operations from undo stack
-            let group_id_ref = &group_id;
-            self.undo_stack.retain(|id| {
-                self.history
-                    .iter()
-                    .find(|o| &o.id == id)
-                    .map(|o| o.group_id.as_ref() != Some(group_id_ref))
-                    .unwrap_or(true)
-            });
-
-            self.redo_stack.push_back(op_id);
-        } else {
-            // Single operation
-            if let Some(op) = self.history.iter_mut().find(|o| o.id == op_id) {
-                inverses.push(op.operation.inverse());
+            let inverse = op.operation.inverse();
+            if apply(&inverse) {
                 op.undone = true;
+                redo_id.get_or_insert_with(|| op.id.clone());
+                outcome.applied.push(inverse);
+            } else {
+                skipped.insert(op.id.clone());
+                outcome.skipped.push(inverse);
             }
-            self.redo_stack.push_back(op_id);
         }
 
-        // Return in reverse order (last operation first)
-        inverses.reverse();
-        inverses
+        self.remove_operations(&skipped);
+        self.redo_stack.extend(redo_id);
+        outcome
     }
 
     /// Redo the last undone operation.
     /// Returns the operations to reapply.
     pub fn redo(&mut self) -> Vec<UndoableOperation> {
-        if self.redo_stack.is_empty() {
-            return Vec::new();
-        }
+        self.redo_with(|_| true).applied
+    }
 
-        let op_id = self.redo_stack.pop_back().unwrap();
-        let mut operations = Vec::new();
+    /// Redo the last undone operation (or group), applying each operation
+    /// with `apply` in the order first recorded.
+    ///
+    /// Operations `apply` rejects are skipped and dropped from the history,
+    /// as in [`undo_with`](Self::undo_with).
+    pub fn redo_with(&mut self, mut apply: impl FnMut(&UndoableOperation) -> bool) -> UndoOutcome {
+        self.trim_history();
+        self.burst = None;
+        let mut outcome = UndoOutcome::default();
+        let Some(op_id) = self.redo_stack.pop_back() else {
+            return outcome;
+        };
 
-        // Find the operation's group id first
-        let group_id = self
-            .history
-            .iter()
-            .find(|o| o.id == op_id)
-            .and_then(|o| o.group_id.clone());
-
-        if let Some(group_id) = group_id {
-            // Find all operations in this group and redo them
-            for op in self.history.iter_mut() {
-                if op.group_id.as_ref() == Some(&group_id) && op.undone {
-                    operations.push(op.operation.clone());
-                    op.undone = false;
-                }
+        let mut undo_id = None;
+        let mut skipped = HashSet::new();
+        for i in self.unit_of(&op_id) {
+            let op = &mut self.history[i];
+            if !op.undone {
+                continue;
             }
-            self.undo_stack.push_back(op_id);
-        } else {
-            if let Some(op) = self.history.iter_mut().find(|o| o.id == op_id) {
-                operations.push(op.operation.clone());
+            if apply(&op.operation) {
                 op.undone = false;
+                undo_id = Some(op.id.clone());
+                outcome.applied.push(op.operation.clone());
+            } else {
+                skipped.insert(op.id.clone());
+                outcome.skipped.push(op.operation.clone());
             }
-            self.undo_stack.push_back(op_id);
         }
 
-        operations
+        self.remove_operations(&skipped);
+        self.undo_stack.extend(undo_id);
+        outcome
+    }
+
+    /// Group of a recorded operation, if it has one.
+    fn group_of(&self, op_id: &OperationId) -> Option<GroupId> {
+        self.history
+            .iter()
+            .find(|o| &o.id == op_id)
+            .and_then(|o| o.group_id.clone())
+    }
+
+    /// Positions in the history of an operation and the rest of its group,
+    /// oldest first.
+    fn unit_of(&self, op_id: &OperationId) -> Vec<usize> {
+        match self.group_of(op_id) {
+            Some(group_id) => (0..self.history.len())
+                .filter(|&i| self.history[i].group_id.as_ref() == Some(&group_id))
+                .collect(),
+            None => self
+                .history
+                .iter()
+                .position(|o| &o.id == op_id)
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// Drop operations from the history and both stacks.
+    fn remove_operations(&mut self, ids: &HashSet<OperationId>) {
+        if ids.is_empty() {
+            return;
+        }
+        self.history.retain(|o| !ids.contains(&o.id));
+        self.undo_stack.retain(|id| !ids.contains(id));
+        self.redo_stack.retain(|id| !ids.contains(id));
     }
 
     /// Get the undo stack size.
@@ -444,23 +631,101 @@ impl UndoManager {
         self.redo_stack.len()
     }
 
+    /// Get the number of operations in the history.
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
     /// Clear all history.
     pub fn clear(&mut self) {
         self.history.clear();
         self.undo_stack.clear();
         self.redo_stack.clear();
+        self.burst = None;
     }
 
-    /// Trim history to max size.
+    /// Capture the history and both stacks, for persisting them.
+    pub fn export(&self) -> UndoHistory {
+        UndoHistory {
+            clock: self.clock,
+            operations: self.history.iter().cloned().collect(),
+            undo_stack: self.undo_stack.iter().cloned().collect(),
+            redo_stack: self.redo_stack.iter().cloned().collect(),
+        }
+    }
+
+    /// Replace the history and both stacks with an exported one.
+    ///
+    /// Stack entries for operations missing from the history are dropped,
+    /// and the history is trimmed to this manager's limits. Operations are
+    /// not checked against the document here but when undone or redone.
+    pub fn import(&mut self, history: UndoHistory) {
+        let known: HashSet<_> = history.operations.iter().map(|o| o.id.clone()).collect();
+        self.clock = self.clock.max(history.clock);
+        self.history = history.operations.into();
+        self.undo_stack = history
+            .undo_stack
+            .into_iter()
+            .filter(|id| known.contains(id))
+            .collect();
+        self.redo_stack = history
+            .redo_stack
+            .into_iter()
+            .filter(|id| known.contains(id))
+            .collect();
+        self.current_group = None;
+        self.burst = None;
+        self.trim_history();
+    }
+
+    /// Evict the oldest operations, a whole group at a time, until the
+    /// history is within its limits.
     fn trim_history(&mut self) {
-        while self.history.len() > self.max_history {
-            if let Some(removed) = self.history.first() {
-                let removed_id = removed.id.clone();
-                self.undo_stack.retain(|id| *id != removed_id);
-                self.redo_stack.retain(|id| *id != removed_id);
+        let now = self.wall_clock.now_millis();
+        let mut groups = self.group_count();
+        while let Some(oldest) = self.history.front() {
+            if oldest.group_id.is_some() && oldest.group_id == self.current_group {
+                break;
+            }
+            let expired = self
+                .config
+                .max_age_ms
+                .is_some_and(|age| now.saturating_sub(oldest.recorded_at) > age);
+            if !expired
+                && self.history.len() <= self.config.max_operations
+                && groups <= self.config.max_groups
+            {
+                break;
+            }
+
+            let evicted: HashSet<_> = match &oldest.group_id {
+                Some(group_id) => self
+                    .history
+                    .iter()
+                    .filter(|o| o.group_id.as_ref() == Some(group_id))
+                    .map(|o| o.id.clone())
+                    .collect(),
+                None => HashSet::from([oldest.id.clone()]),
+            };
+            self.remove_operations(&evicted);
+            groups -= 1;
+        }
+    }
+
+    /// Number of groups in the history, counting each ungrouped operation
+    /// as its own.
+    fn group_count(&self) -> usize {
+        let mut groups = HashSet::new();
+        let mut ungrouped = 0;
+        for op in &self.history {
+            match &op.group_id {
+                Some(group_id) => {
+                    groups.insert(group_id);
+                }
+                None => ungrouped += 1,
             }
-            self.history.remove(0);
         }
+        groups.len() + ungrouped
     }
 }
 
@@ -750,4 +1015,206 @@ mod tests {
         // Remote operations are in history but not in local undo stack
         assert!(!manager.can_undo());
     }
+
+    fn insert(position: usize, text: &str) -> UndoableOperation {
+        UndoableOperation::Text(TextOperation::Insert {
+            position,
+            text: text.to_string(),
+        })
+    }
+
+    /// Texts of the inserts that undoing everything would remove, in order.
+    fn undo_all(manager: &mut UndoManager) -> Vec<String> {
+        let mut undone = Vec::new();
+        while manager.can_undo() {
+            for op in manager.undo() {
+                if let UndoableOperation::Text(TextOperation::Delete { deleted, .. }) = op {
+                    undone.push(deleted);
+                }
+            }
+        }
+        undone
+    }
+
+    #[test]
+    fn test_eviction_keeps_groups_whole() {
+        let config = UndoConfig {
+            max_operations: 5,
+            ..Default::default()
+        };
+        let mut manager = UndoManager::new("doc1", "r1").with_config(config);
+
+        manager.record(insert(0, "a"));
+        manager.start_group();
+        for text in ["b", "c", "d"] {
+            manager.record(insert(0, text));
+        }
+        manager.end_group();
+        manager.record(insert(0, "e"));
+        manager.record(insert(0, "f"));
+        assert_eq!(manager.history_len(), 5);
+
+        // One more evicts the whole group rather than just "b"
+        manager.record(insert(0, "g"));
+        assert_eq!(manager.history_len(), 3);
+        assert_eq!(undo_all(&mut manager), ["g", "f", "e"]);
+
+        let config = UndoConfig {
+            max_groups: 2,
+            ..Default::default()
+        };
+        let mut manager = UndoManager::new("doc1", "r1").with_config(config);
+        for group in [["a", "b"], ["c", "d"], ["e", "f"]] {
+            manager.start_group();
+            for text in group {
+                manager.record(insert(0, text));
+            }
+            // The group being recorded is never evicted
+            assert_eq!(manager.history_len() % 2, 0);
+            manager.end_group();
+        }
+        assert_eq!(undo_all(&mut manager), ["f", "e", "d", "c"]);
+    }
+
+    #[test]
+    fn test_eviction_by_age() {
+        let clock = std::sync::Arc::new(mdcs_core::clock::ManualClock::new(0));
+        let config = UndoConfig {
+            max_age_ms: Some(1000),
+            ..Default::default()
+        };
+        let mut manager = UndoManager::new("doc1", "r1")
+            .with_config(config)
+            .with_clock(clock.clone());
+
+        manager.record(insert(0, "a"));
+        clock.advance(600);
+        manager.start_group();
+        manager.record(insert(0, "b"));
+        clock.advance(600);
+        manager.record(insert(0, "c"));
+        manager.end_group();
+        manager.record(insert(0, "d"));
+
+        // "a" expired
+        assert_eq!(manager.history_len(), 3);
+
+        // "c" hasn't, but its group goes with "b"
+        clock.advance(500);
+        assert_eq!(undo_all(&mut manager), ["d"]);
+        assert_eq!(manager.history_len(), 1);
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let mut manager = UndoManager::new("doc1", "r1");
+        manager.record(insert(0, "a"));
+        manager.start_group();
+        manager.record(insert(1, "b"));
+        manager.record(insert(2, "c"));
+        manager.end_group();
+        manager.record(insert(3, "d"));
+        manager.record(insert(4, "e"));
+        manager.undo();
+
+        let json = serde_json::to_string(&manager.export()).unwrap();
+        let mut restored = UndoManager::new("doc1", "r1");
+        restored.import(serde_json::from_str(&json).unwrap());
+
+        assert!(restored.can_undo() && restored.can_redo());
+        assert_eq!(restored.undo_stack_size(), manager.undo_stack_size());
+        assert_eq!(restored.redo_stack_size(), manager.redo_stack_size());
+        assert!(matches!(
+            &restored.redo()[..],
+            [UndoableOperation::Text(TextOperation::Insert { text, .. })] if text == "e"
+        ));
+        assert_eq!(undo_all(&mut restored), ["e", "d", "c", "b", "a"]);
+
+        // New operations continue the Lamport clock
+        assert!(restored.record(insert(0, "f")).timestamp > 5);
+    }
+
+    #[test]
+    fn test_undo_with_skips_rejected_operations() {
+        let mut manager = UndoManager::new("doc1", "r1");
+        manager.start_group();
+        manager.record(insert(0, "a"));
+        manager.record(insert(1, "b"));
+        manager.end_group();
+
+        // The document lost "a" in the meantime
+        let outcome = manager.undo_with(|op| {
+            !matches!(op, UndoableOperation::Text(TextOperation::Delete { deleted, .. }) if deleted == "a")
+        });
+        assert_eq!(outcome.applied.len(), 1);
+        assert_eq!(outcome.skipped.len(), 1);
+        assert!(!manager.can_undo());
+
+        // Only "b" can be redone
+        let redone = manager.redo();
+        assert!(matches!(
+            &redone[..],
+            [UndoableOperation::Text(TextOperation::Insert { text, .. })] if text == "b"
+        ));
+        assert_eq!(manager.history_len(), 1);
+    }
+
+    #[test]
+    fn test_group_window_joins_bursts() {
+        let clock = std::sync::Arc::new(mdcs_core::clock::ManualClock::new(0));
+        let config = UndoConfig {
+            group_window_ms: Some(500),
+            ..Default::default()
+        };
+        let mut manager = UndoManager::new("doc1", "r1")
+            .with_config(config)
+            .with_clock(clock.clone());
+
+        // Typed within the window of the previous key, however long the burst
+        for (i, c) in ["a", "b", "c"].into_iter().enumerate() {
+            manager.record(insert(i, c));
+            clock.advance(400);
+        }
+        clock.advance(200);
+        manager.record(insert(3, "d"));
+        clock.advance(100);
+        manager.record(insert(4, "e"));
+
+        assert_eq!(undo_all(&mut manager), ["e", "d", "c", "b", "a"]);
+        assert_eq!(manager.undo_stack_size(), 0);
+
+        // Undoing ends the burst
+        manager.redo();
+        manager.redo();
+        manager.undo();
+        manager.record(insert(0, "f"));
+        assert_eq!(undo_all(&mut manager), ["f", "c", "b", "a"]);
+    }
+
+    #[test]
+    fn test_transform_text_follows_remote_edits() {
+        let mut manager = UndoManager::new("doc1", "r1");
+        manager.record(insert(0, "ab"));
+        manager.record(insert(6, "xyz"));
+        manager.record(UndoableOperation::Text(TextOperation::Delete {
+            position: 3,
+            deleted: "q".to_string(),
+        }));
+
+        // A remote "12" at 2, then "123" replaced by "9"
+        manager.transform_text(2, 0, 2);
+        manager.transform_text(4, 3, 1);
+
+        let positions: Vec<_> = manager
+            .undo()
+            .into_iter()
+            .chain(manager.undo())
+            .chain(manager.undo())
+            .map(|op| match op {
+                UndoableOperation::Text(mut op) => *op.position_mut(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(positions, [4, 6, 0]);
+    }
 }
@@ -0,0 +1,1011 @@
+//! Yjs interop for [`RichText`].
+//!
+//! Converts between rich text and the two representations a Yjs bridge
+//! deals with:
+//!
+//! - **Snapshots**: the binary update returned by `Y.encodeStateAsUpdate(doc)`
+//!   (update format v1). Only what a Y.Text needs is interpreted: lib0
+//!   varints and strings, the struct store and the delete set. Other shared
+//!   types in the same document are decoded and skipped.
+//! - **Delta JSON**: the `[{ insert, attributes }]` runs returned by
+//!   `Y.Text.toDelta()` and accepted by `Y.Text.applyDelta()`.
+//!
+//! Formatting attributes map to mark types as follows:
+//!
+//! | Attribute                        | Mark type                    |
+//! |----------------------------------|------------------------------|
+//! | `bold: true`                     | [`MarkType::Bold`]           |
+//! | `italic: true`                   | [`MarkType::Italic`]         |
+//! | `underline: true`                | [`MarkType::Underline`]      |
+//! | `strike: true`                   | [`MarkType::Strikethrough`]  |
+//! | `code: true`                     | [`MarkType::Code`]           |
+//! | `link: "<url>"`                  | [`MarkType::Link`]           |
+//! | `background: "<color>"`          | [`MarkType::Highlight`]      |
+//! | `comment: { author, content }`   | [`MarkType::Comment`]        |
+//!
+//! Any other attribute becomes a [`MarkType::Custom`] with the attribute
+//! name and its value: strings as-is, other JSON values in their JSON
+//! encoding. On export, a custom value that parses as a non-string JSON
+//! value is emitted as that value, so a string attribute that looks like a
+//! number or boolean does not survive a round trip as a string.
+//!
+//! Enabled with the `yjs` feature.
+//!
+//! # Example
+//!
+//! ```rust
+//! use mdcs_db::RichText;
+//!
+//! let mut doc = RichText::new("bridge");
+//! doc.insert(0, "Hello world");
+//! doc.bold(0, 5);
+//!
+//! let delta = doc.to_yjs_compatible_json();
+//! assert_eq!(delta[0]["insert"], "Hello");
+//! assert_eq!(delta[0]["attributes"]["bold"], true);
+//!
+//! let copy = RichText::from_yjs_delta("other", &delta).unwrap();
+//! assert_eq!(copy.text_content(), "Hello world");
+//! ```
+
+use crate::error::DbError;
+use crate::rich_text::{MarkType, RichText};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Formatting attributes of a run.
+type Attributes = BTreeMap<String, Value>;
+
+impl RichText {
+    /// Decode the Y.Text of a Yjs document snapshot.
+    ///
+    /// `bytes` is a v1 update as produced by `Y.encodeStateAsUpdate(doc)`.
+    /// The document must contain exactly one root Y.Text; use
+    /// [`from_yjs_snapshot_named`](Self::from_yjs_snapshot_named) to pick one
+    /// when it has several.
+    pub fn from_yjs_snapshot(replica_id: impl Into<String>, bytes: &[u8]) -> Result<Self, DbError> {
+        let runs = Snapshot::decode(bytes)?.text_runs(None)?;
+        Ok(Self::from_runs(replica_id, runs))
+    }
+
+    /// Decode the root Y.Text called `root` (as in `doc.getText(root)`) of a
+    /// Yjs document snapshot.
+    pub fn from_yjs_snapshot_named(
+        replica_id: impl Into<String>,
+        bytes: &[u8],
+        root: &str,
+    ) -> Result<Self, DbError> {
+        let runs = Snapshot::decode(bytes)?.text_runs(Some(root))?;
+        Ok(Self::from_runs(replica_id, runs))
+    }
+
+    /// Build rich text from Yjs delta-format JSON.
+    ///
+    /// Accepts the output of `Y.Text.toDelta()`: an array of `insert` runs
+    /// with optional `attributes`. Embeds, `retain` and `delete` operations
+    /// are rejected.
+    pub fn from_yjs_delta(replica_id: impl Into<String>, delta: &Value) -> Result<Self, DbError> {
+        let ops = delta
+            .as_array()
+            .ok_or_else(|| DbError::SerializationError("Yjs delta must be an array".into()))?;
+
+        let mut runs = Vec::with_capacity(ops.len());
+        for op in ops {
+            let insert = match op.get("insert") {
+                Some(Value::String(text)) => text.clone(),
+                Some(_) => {
+                    return Err(DbError::UnsupportedOperation(
+                        "embedded content in Yjs delta".into(),
+                    ))
+                }
+                None => {
+                    return Err(DbError::UnsupportedOperation(format!(
+                        "Yjs delta operation other than insert: {}",
+                        op
+                    )))
+                }
+            };
+            let attributes = match op.get("attributes") {
+                None | Some(Value::Null) => Attributes::new(),
+                Some(Value::Object(attributes)) => attributes
+                    .iter()
+                    .filter(|(_, value)| !value.is_null())
+                    .map(|(name, value)| (name.clone(), value.clone()))
+                    .collect(),
+                Some(other) => {
+                    return Err(DbError::SerializationError(format!(
+                        "Yjs delta attributes must be an object, found {}",
+                        other
+                    )))
+                }
+            };
+            runs.push((insert, attributes));
+        }
+
+        Ok(Self::from_runs(replica_id, runs))
+    }
+
+    /// Render as Yjs delta-format JSON, ready for `Y.Text.applyDelta()` on
+    /// an empty Y.Text.
+    ///
+    /// Each run of text with the same formatting becomes one
+    /// `{ "insert": ..., "attributes": ... }` entry. Where marks that map to
+    /// the same attribute overlap, the one with the greatest [`MarkId`]
+    /// wins.
+    ///
+    /// [`MarkId`]: crate::rich_text::MarkId
+    pub fn to_yjs_compatible_json(&self) -> Value {
+        let chars: Vec<char> = self.text_content().chars().collect();
+
+        let mut marks: Vec<_> = self
+            .active_marks()
+            .filter_map(|mark| {
+                let (start, end) = mark.range(self.text())?;
+                let end = end.min(chars.len());
+                (start < end).then_some((start, end, mark))
+            })
+            .collect();
+        marks.sort_by(|a, b| (&a.2.id.replica, &a.2.id.ulid).cmp(&(&b.2.id.replica, &b.2.id.ulid)));
+
+        let mut boundaries: BTreeSet<usize> = BTreeSet::from([0, chars.len()]);
+        for (start, end, _) in &marks {
+            boundaries.insert(*start);
+            boundaries.insert(*end);
+        }
+
+        let mut runs: Vec<(String, Attributes)> = Vec::new();
+        let boundaries: Vec<usize> = boundaries.into_iter().collect();
+        for window in boundaries.windows(2) {
+            let (start, end) = (window[0], window[1]);
+            if start == end {
+                continue;
+            }
+            let attributes: Attributes = marks
+                .iter()
+                .filter(|(s, e, _)| *s <= start && *e >= end)
+                .map(|(_, _, mark)| attribute(&mark.mark_type))
+                .collect();
+            let text: String = chars[start..end].iter().collect();
+            match runs.last_mut() {
+                Some((last, last_attributes)) if *last_attributes == attributes => {
+                    last.push_str(&text)
+                }
+                _ => runs.push((text, attributes)),
+            }
+        }
+
+        Value::Array(
+            runs.into_iter()
+                .map(|(text, attributes)| {
+                    let mut op = Map::new();
+                    op.insert("insert".into(), Value::String(text));
+                    if !attributes.is_empty() {
+                        op.insert(
+                            "attributes".into(),
+                            Value::Object(attributes.into_iter().collect()),
+                        );
+                    }
+                    Value::Object(op)
+                })
+                .collect(),
+        )
+    }
+
+    /// Build rich text from formatted runs, one mark per maximal stretch of
+    /// runs sharing an attribute value.
+    fn from_runs(replica_id: impl Into<String>, runs: Vec<(String, Attributes)>) -> Self {
+        let mut text = String::new();
+        let mut spans: Vec<(usize, usize, String, Value)> = Vec::new();
+        let mut open: BTreeMap<String, (Value, usize)> = BTreeMap::new();
+        let mut position = 0;
+
+        for (insert, attributes) in runs {
+            let len = insert.chars().count();
+            if len == 0 {
+                continue;
+            }
+            open.retain(|name, (value, start)| {
+                if attributes.get(name) == Some(value) {
+                    return true;
+                }
+                spans.push((*start, position, name.clone(), value.clone()));
+                false
+            });
+            for (name, value) in attributes {
+                open.entry(name).or_insert((value, position));
+            }
+            text.push_str(&insert);
+            position += len;
+        }
+        for (name, (value, start)) in open {
+            spans.push((start, position, name, value));
+        }
+        spans.sort_by(|a, b| (a.0, &a.2).cmp(&(b.0, &b.2)));
+
+        let mut rich = RichText::new(replica_id);
+        rich.insert(0, &text);
+        for (start, end, name, value) in spans {
+            rich.add_mark(start, end, mark_type(&name, &value));
+        }
+        rich
+    }
+}
+
+/// The Yjs attribute a mark type is exported as.
+fn attribute(mark_type: &MarkType) -> (String, Value) {
+    let (name, value) = match mark_type {
+        MarkType::Bold => ("bold", Value::Bool(true)),
+        MarkType::Italic => ("italic", Value::Bool(true)),
+        MarkType::Underline => ("underline", Value::Bool(true)),
+        MarkType::Strikethrough => ("strike", Value::Bool(true)),
+        MarkType::Code => ("code", Value::Bool(true)),
+        MarkType::Link { url } => ("link", Value::String(url.clone())),
+        MarkType::Highlight { color } => ("background", Value::String(color.clone())),
+        MarkType::Comment { author, content } => (
+            "comment",
+            serde_json::json!({ "author": author, "content": content }),
+        ),
+        MarkType::Custom { name, value } => {
+            let value = match serde_json::from_str::<Value>(value) {
+                Ok(parsed) if !parsed.is_string() => parsed,
+                _ => Value::String(value.clone()),
+            };
+            return (name.clone(), value);
+        }
+    };
+    (name.to_string(), value)
+}
+
+/// The mark type a Yjs attribute is imported as.
+fn mark_type(name: &str, value: &Value) -> MarkType {
+    match (name, value) {
+        ("bold", Value::Bool(true)) => MarkType::Bold,
+        ("italic", Value::Bool(true)) => MarkType::Italic,
+        ("underline", Value::Bool(true)) => MarkType::Underline,
+        ("strike", Value::Bool(true)) => MarkType::Strikethrough,
+        ("code", Value::Bool(true)) => MarkType::Code,
+        ("link", Value::String(url)) => MarkType::Link { url: url.clone() },
+        ("background", Value::String(color)) => MarkType::Highlight {
+            color: color.clone(),
+        },
+        ("comment", Value::Object(comment)) if comment.len() == 2 => {
+            match (comment.get("author"), comment.get("content")) {
+                (Some(Value::String(author)), Some(Value::String(content))) => MarkType::Comment {
+                    author: author.clone(),
+                    content: content.clone(),
+                },
+                _ => custom(name, value),
+            }
+        }
+        _ => custom(name, value),
+    }
+}
+
+fn custom(name: &str, value: &Value) -> MarkType {
+    MarkType::Custom {
+        name: name.to_string(),
+        value: match value {
+            Value::String(value) => value.clone(),
+            other => other.to_string(),
+        },
+    }
+}
+
+// ============================================================================
+// lib0 decoding
+// ============================================================================
+
+fn malformed(what: impl std::fmt::Display) -> DbError {
+    DbError::SerializationError(format!("malformed Yjs update: {}", what))
+}
+
+/// Reader for lib0's binary encoding.
+struct Decoder<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], DbError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.buf.len())
+            .ok_or_else(|| malformed("unexpected end of input"))?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, DbError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_var_uint(&mut self) -> Result<u64, DbError> {
+        let mut num = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_u8()?;
+            num |= u64::from(byte & 0x7f) << shift;
+            if byte < 0x80 {
+                return Ok(num);
+            }
+            shift += 7;
+            if shift > 63 {
+                return Err(malformed("varint too long"));
+            }
+        }
+    }
+
+    fn read_len(&mut self) -> Result<usize, DbError> {
+        usize::try_from(self.read_var_uint()?).map_err(|_| malformed("length out of range"))
+    }
+
+    /// Signed varint: the first byte carries a sign bit and six value bits.
+    fn read_var_int(&mut self) -> Result<i64, DbError> {
+        let first = self.read_u8()?;
+        let negative = first & 0x40 != 0;
+        let mut num = i64::from(first & 0x3f);
+        let mut shift = 6;
+        let mut byte = first;
+        while byte >= 0x80 {
+            byte = self.read_u8()?;
+            if shift > 56 {
+                return Err(malformed("varint too long"));
+            }
+            num |= i64::from(byte & 0x7f) << shift;
+            shift += 7;
+        }
+        Ok(if negative { -num } else { num })
+    }
+
+    fn read_string(&mut self) -> Result<String, DbError> {
+        let len = self.read_len()?;
+        let bytes = self.read_bytes(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| malformed("invalid UTF-8 in string"))
+    }
+
+    fn read_json(&mut self) -> Result<Value, DbError> {
+        let text = self.read_string()?;
+        if text == "undefined" {
+            return Ok(Value::Null);
+        }
+        serde_json::from_str(&text).map_err(malformed)
+    }
+
+    /// A value in lib0's tagged `any` encoding.
+    fn read_any(&mut self) -> Result<Value, DbError> {
+        Ok(match self.read_u8()? {
+            // undefined, null
+            127 | 126 => Value::Null,
+            125 => Value::from(self.read_var_int()?),
+            124 => {
+                let bytes: [u8; 4] = self.read_bytes(4)?.try_into().expect("4 bytes");
+                float(f64::from(f32::from_be_bytes(bytes)))
+            }
+            123 => {
+                let bytes: [u8; 8] = self.read_bytes(8)?.try_into().expect("8 bytes");
+                float(f64::from_be_bytes(bytes))
+            }
+            122 => {
+                let bytes: [u8; 8] = self.read_bytes(8)?.try_into().expect("8 bytes");
+                Value::from(i64::from_be_bytes(bytes))
+            }
+            121 => Value::Bool(false),
+            120 => Value::Bool(true),
+            119 => Value::String(self.read_string()?),
+            118 => {
+                let len = self.read_len()?;
+                let mut object = Map::new();
+                for _ in 0..len {
+                    let key = self.read_string()?;
+                    object.insert(key, self.read_any()?);
+                }
+                Value::Object(object)
+            }
+            117 => {
+                let len = self.read_len()?;
+                let mut array = Vec::new();
+                for _ in 0..len {
+                    array.push(self.read_any()?);
+                }
+                Value::Array(array)
+            }
+            116 => {
+                let len = self.read_len()?;
+                Value::from(self.read_bytes(len)?.to_vec())
+            }
+            tag => return Err(malformed(format!("unknown value tag {}", tag))),
+        })
+    }
+}
+
+fn float(value: f64) -> Value {
+    serde_json::Number::from_f64(value)
+        .map(Value::Number)
+        .unwrap_or(Value::Null)
+}
+
+// ============================================================================
+// Struct store
+// ============================================================================
+
+/// Struct info bits.
+const HAS_ORIGIN: u8 = 0x80;
+const HAS_RIGHT_ORIGIN: u8 = 0x40;
+const HAS_PARENT_SUB: u8 = 0x20;
+const CONTENT_REF: u8 = 0x1f;
+
+/// Yjs type refs that carry a node name.
+const TYPE_XML_ELEMENT: u64 = 3;
+const TYPE_XML_HOOK: u64 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Id {
+    client: u64,
+    clock: u64,
+}
+
+impl Id {
+    fn read(decoder: &mut Decoder<'_>) -> Result<Self, DbError> {
+        Ok(Self {
+            client: decoder.read_var_uint()?,
+            clock: decoder.read_var_uint()?,
+        })
+    }
+}
+
+enum Parent {
+    Root(String),
+    Item,
+}
+
+/// One clock tick of an item: a UTF-16 code unit of a string, a format
+/// marker, or any other content.
+enum Content {
+    Char(u16),
+    Format(String, Value),
+    Deleted,
+    Other,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Pending,
+    Visiting,
+    /// Garbage collected, nested in another type, or a map entry.
+    Dropped,
+    /// Integrated into the sequence of the root with this index.
+    Placed(usize),
+}
+
+/// An item split into single-tick units, so origins never point into the
+/// middle of one.
+struct Unit {
+    id: Id,
+    origin: Option<Id>,
+    right_origin: Option<Id>,
+    parent: Option<Parent>,
+    keyed: bool,
+    content: Content,
+    deleted: bool,
+    state: State,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+#[derive(Clone, Copy)]
+enum Slot {
+    Unit(usize),
+    Gc,
+}
+
+/// A decoded Yjs document with every root sequence reconstructed.
+struct Snapshot {
+    units: Vec<Unit>,
+    slots: HashMap<Id, Slot>,
+    /// Root types by name, with the first unit of their sequence.
+    roots: Vec<(String, Option<usize>)>,
+}
+
+impl Snapshot {
+    fn decode(bytes: &[u8]) -> Result<Self, DbError> {
+        let mut decoder = Decoder::new(bytes);
+        let mut snapshot = Snapshot {
+            units: Vec::new(),
+            slots: HashMap::new(),
+            roots: Vec::new(),
+        };
+        snapshot.read_structs(&mut decoder)?;
+        snapshot.integrate()?;
+        snapshot.read_delete_set(&mut decoder)?;
+        Ok(snapshot)
+    }
+
+    fn read_structs(&mut self, decoder: &mut Decoder<'_>) -> Result<(), DbError> {
+        for _ in 0..decoder.read_var_uint()? {
+            let structs = decoder.read_var_uint()?;
+            let client = decoder.read_var_uint()?;
+            let mut clock = decoder.read_var_uint()?;
+
+            for _ in 0..structs {
+                let info = decoder.read_u8()?;
+                match info & CONTENT_REF {
+                    // GC and Skip
+                    0 | 10 => {
+                        let len = decoder.read_var_uint()?;
+                        if info & CONTENT_REF == 0 {
+                            for offset in 0..len {
+                                self.slots.insert(
+                                    Id {
+                                        client,
+                                        clock: clock + offset,
+                                    },
+                                    Slot::Gc,
+                                );
+                            }
+                        }
+                        clock += len;
+                    }
+                    content_ref => {
+                        let origin = if info & HAS_ORIGIN != 0 {
+                            Some(Id::read(decoder)?)
+                        } else {
+                            None
+                        };
+                        let right_origin = if info & HAS_RIGHT_ORIGIN != 0 {
+                            Some(Id::read(decoder)?)
+                        } else {
+                            None
+                        };
+                        let mut parent = None;
+                        let mut keyed = false;
+                        if info & (HAS_ORIGIN | HAS_RIGHT_ORIGIN) == 0 {
+                            parent = Some(if decoder.read_var_uint()? == 1 {
+                                Parent::Root(decoder.read_string()?)
+                            } else {
+                                Id::read(decoder)?;
+                                Parent::Item
+                            });
+                            if info & HAS_PARENT_SUB != 0 {
+                                decoder.read_string()?;
+                                keyed = true;
+                            }
+                        }
+
+                        let contents = read_content(decoder, content_ref)?;
+                        for (offset, content) in contents.into_iter().enumerate() {
+                            let id = Id { client, clock };
+                            let first = offset == 0;
+                            self.slots.insert(id, Slot::Unit(self.units.len()));
+                            self.units.push(Unit {
+                                id,
+                                origin: if first {
+                                    origin
+                                } else {
+                                    Some(Id {
+                                        client,
+                                        clock: id.clock - 1,
+                                    })
+                                },
+                                right_origin,
+                                parent: if first { parent.take() } else { None },
+                                keyed,
+                                deleted: matches!(content, Content::Deleted),
+                                content,
+                                state: State::Pending,
+                                left: None,
+                                right: None,
+                            });
+                            clock += 1;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_delete_set(&mut self, decoder: &mut Decoder<'_>) -> Result<(), DbError> {
+        for _ in 0..decoder.read_var_uint()? {
+            let client = decoder.read_var_uint()?;
+            for _ in 0..decoder.read_var_uint()? {
+                let start = decoder.read_var_uint()?;
+                let len = decoder.read_var_uint()?;
+                for clock in start..start.saturating_add(len) {
+                    match self.slots.get(&Id { client, clock }) {
+                        Some(Slot::Unit(unit)) => self.units[*unit].deleted = true,
+                        Some(Slot::Gc) => {}
+                        None => break,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn slot(&self, id: Id) -> Result<Slot, DbError> {
+        self.slots.get(&id).copied().ok_or_else(|| {
+            malformed(format!(
+                "missing struct {}:{} (not a full snapshot?)",
+                id.client, id.clock
+            ))
+        })
+    }
+
+    /// Integrate every unit, each after the units its origins point at.
+    fn integrate(&mut self) -> Result<(), DbError> {
+        for first in 0..self.units.len() {
+            let mut stack = vec![first];
+            while let Some(&unit) = stack.last() {
+                if !matches!(self.units[unit].state, State::Pending | State::Visiting) {
+                    stack.pop();
+                    continue;
+                }
+                self.units[unit].state = State::Visiting;
+
+                let mut next = None;
+                for id in [self.units[unit].origin, self.units[unit].right_origin]
+                    .into_iter()
+                    .flatten()
+                {
+                    if let Slot::Unit(dep) = self.slot(id)? {
+                        match self.units[dep].state {
+                            State::Pending => next = Some(dep),
+                            State::Visiting => return Err(malformed("cyclic origins")),
+                            _ => {}
+                        }
+                    }
+                }
+                match next {
+                    Some(dep) => stack.push(dep),
+                    None => {
+                        self.place(unit)?;
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Place a unit whose origins are settled into its root's sequence,
+    /// resolving concurrent inserts as Yjs does.
+    fn place(&mut self, unit: usize) -> Result<(), DbError> {
+        let left = self.units[unit]
+            .origin
+            .map(|id| self.slot(id))
+            .transpose()?;
+        let right = self.units[unit]
+            .right_origin
+            .map(|id| self.slot(id))
+            .transpose()?;
+        // Next to garbage-collected content, so collected itself
+        let collected = matches!(left, Some(Slot::Gc)) || matches!(right, Some(Slot::Gc));
+        let unit_of = |slot: Option<Slot>| match slot {
+            Some(Slot::Unit(n)) => Some(n),
+            _ => None,
+        };
+        let (mut left, right) = (unit_of(left), unit_of(right));
+
+        let root = match &self.units[unit].parent {
+            _ if collected => None,
+            Some(Parent::Root(name)) if !self.units[unit].keyed => {
+                let name = name.clone();
+                Some(self.root_index(&name))
+            }
+            Some(_) => None,
+            None => left.or(right).and_then(|n| match self.units[n].state {
+                State::Placed(root) => Some(root),
+                _ => None,
+            }),
+        };
+        let Some(root) = root else {
+            self.units[unit].state = State::Dropped;
+            return Ok(());
+        };
+
+        let origin = self.units[unit].origin;
+        let right_origin = self.units[unit].right_origin;
+        let start = self.roots[root].1;
+        let conflict = match left {
+            None => right.is_none_or(|r| self.units[r].left.is_some()),
+            Some(l) => self.units[l].right != right,
+        };
+        if conflict {
+            let mut o = match left {
+                Some(l) => self.units[l].right,
+                None => start,
+            };
+            let mut before_origin = BTreeSet::new();
+            let mut conflicting = BTreeSet::new();
+            while let Some(other) = o.filter(|o| Some(*o) != right) {
+                before_origin.insert(other);
+                conflicting.insert(other);
+                let other_origin = self.units[other].origin;
+                if other_origin == origin {
+                    if self.units[other].id.client < self.units[unit].id.client {
+                        left = Some(other);
+                        conflicting.clear();
+                    } else if self.units[other].right_origin == right_origin {
+                        break;
+                    }
+                } else if let Some(Slot::Unit(o_origin)) =
+                    other_origin.and_then(|id| self.slots.get(&id).copied())
+                {
+                    if !before_origin.contains(&o_origin) {
+                        break;
+                    }
+                    if !conflicting.contains(&o_origin) {
+                        left = Some(other);
+                        conflicting.clear();
+                    }
+                } else {
+                    break;
+                }
+                o = self.units[other].right;
+            }
+        }
+
+        let next = match left {
+            Some(l) => self.units[l].right.replace(unit),
+            None => self.roots[root].1.replace(unit),
+        };
+        if let Some(next) = next {
+            self.units[next].left = Some(unit);
+        }
+        let placed = &mut self.units[unit];
+        placed.left = left;
+        placed.right = next;
+        placed.state = State::Placed(root);
+        Ok(())
+    }
+
+    fn root_index(&mut self, name: &str) -> usize {
+        match self.roots.iter().position(|(root, _)| root == name) {
+            Some(index) => index,
+            None => {
+                self.roots.push((name.to_string(), None));
+                self.roots.len() - 1
+            }
+        }
+    }
+
+    /// The formatted runs of a root Y.Text, as `Y.Text.toDelta()` returns
+    /// them.
+    fn text_runs(&self, name: Option<&str>) -> Result<Vec<(String, Attributes)>, DbError> {
+        let root = match name {
+            Some(name) => self.roots.iter().position(|(root, _)| root == name),
+            None => {
+                let texts: Vec<usize> = (0..self.roots.len())
+                    .filter(|root| {
+                        self.units.iter().any(|unit| {
+                            unit.state == State::Placed(*root)
+                                && matches!(unit.content, Content::Char(_) | Content::Format(..))
+                        })
+                    })
+                    .collect();
+                if texts.len() > 1 {
+                    let names: Vec<&str> = texts
+                        .iter()
+                        .map(|root| self.roots[*root].0.as_str())
+                        .collect();
+                    return Err(DbError::UnsupportedOperation(format!(
+                        "Yjs document has several Y.Text roots ({}); pick one by name",
+                        names.join(", ")
+                    )));
+                }
+                texts.first().copied()
+            }
+        };
+        let Some(root) = root else {
+            return Ok(Vec::new());
+        };
+
+        let mut runs: Vec<(Vec<u16>, Attributes)> = Vec::new();
+        let mut attributes = Attributes::new();
+        let mut next = self.roots[root].1;
+        while let Some(index) = next {
+            let unit = &self.units[index];
+            next = unit.right;
+            if unit.deleted {
+                continue;
+            }
+            match &unit.content {
+                Content::Char(code) => match runs.last_mut() {
+                    Some((text, run)) if *run == attributes => text.push(*code),
+                    _ => runs.push((vec![*code], attributes.clone())),
+                },
+                Content::Format(key, Value::Null) => {
+                    attributes.remove(key);
+                }
+                Content::Format(key, value) => {
+                    attributes.insert(key.clone(), value.clone());
+                }
+                Content::Deleted => {}
+                Content::Other => {
+                    return Err(DbError::UnsupportedOperation(
+                        "embedded content in Y.Text".into(),
+                    ))
+                }
+            }
+        }
+
+        Ok(runs
+            .into_iter()
+            .map(|(text, attributes)| (String::from_utf16_lossy(&text), attributes))
+            .collect())
+    }
+}
+
+/// Read an item's content, one entry per clock tick it spans.
+fn read_content(decoder: &mut Decoder<'_>, content_ref: u8) -> Result<Vec<Content>, DbError> {
+    let repeat = |content: fn() -> Content, len: usize| (0..len).map(|_| content()).collect();
+    Ok(match content_ref {
+        // Deleted
+        1 => repeat(|| Content::Deleted, decoder.read_len()?),
+        // JSON
+        2 => {
+            let len = decoder.read_len()?;
+            for _ in 0..len {
+                decoder.read_string()?;
+            }
+            repeat(|| Content::Other, len)
+        }
+        // Binary
+        3 => {
+            let len = decoder.read_len()?;
+            decoder.read_bytes(len)?;
+            vec![Content::Other]
+        }
+        // String
+        4 => decoder
+            .read_string()?
+            .encode_utf16()
+            .map(Content::Char)
+            .collect(),
+        // Embed
+        5 => {
+            decoder.read_json()?;
+            vec![Content::Other]
+        }
+        // Format
+        6 => {
+            let key = decoder.read_string()?;
+            vec![Content::Format(key, decoder.read_json()?)]
+        }
+        // Type
+        7 => {
+            let type_ref = decoder.read_var_uint()?;
+            if type_ref == TYPE_XML_ELEMENT || type_ref == TYPE_XML_HOOK {
+                decoder.read_string()?;
+            }
+            vec![Content::Other]
+        }
+        // Any
+        8 => {
+            let len = decoder.read_len()?;
+            for _ in 0..len {
+                decoder.read_any()?;
+            }
+            repeat(|| Content::Other, len)
+        }
+        // Doc
+        9 => {
+            decoder.read_string()?;
+            decoder.read_any()?;
+            vec![Content::Other]
+        }
+        other => return Err(malformed(format!("unknown content ref {}", other))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_var_uint() {
+        let mut decoder = Decoder::new(&[0x00, 0x7f, 0x80, 0x01, 0xac, 0x02]);
+        assert_eq!(decoder.read_var_uint().unwrap(), 0);
+        assert_eq!(decoder.read_var_uint().unwrap(), 127);
+        assert_eq!(decoder.read_var_uint().unwrap(), 128);
+        assert_eq!(decoder.read_var_uint().unwrap(), 300);
+        assert!(decoder.read_var_uint().is_err());
+    }
+
+    #[test]
+    fn test_read_any() {
+        // { "n": -3, "ok": true, "tags": ["a"] }
+        let bytes = [
+            118, 3, 1, b'n', 125, 0x43, 2, b'o', b'k', 120, 4, b't', b'a', b'g', b's', 117, 1, 119,
+            1, b'a',
+        ];
+        let value = Decoder::new(&bytes).read_any().unwrap();
+        assert_eq!(value, json!({ "n": -3, "ok": true, "tags": ["a"] }));
+    }
+
+    /// `doc.getText('t').insert(0, 'abc')` on client 7, then `delete(1, 1)`.
+    const DELETED_MIDDLE: &[u8] = &[
+        1, 3, 7, 0, // 3 structs from client 7 at clock 0
+        0x04, 1, 1, b't', 1, b'a', // "a" at the start of root "t"
+        0x81, 7, 0, 1, // deleted, 1 long
+        0x84, 7, 1, 1, b'c', // "c"
+        1, 7, 1, 1, 1, // delete set: client 7, clock 1, length 1
+    ];
+
+    #[test]
+    fn test_snapshot_with_deletion() {
+        let rich = RichText::from_yjs_snapshot("r1", DELETED_MIDDLE).unwrap();
+        assert_eq!(rich.text_content(), "ac");
+        assert_eq!(rich.active_marks().count(), 0);
+    }
+
+    #[test]
+    fn test_truncated_snapshot_is_an_error() {
+        let truncated = &DELETED_MIDDLE[..DELETED_MIDDLE.len() - 6];
+        assert!(matches!(
+            RichText::from_yjs_snapshot("r1", truncated),
+            Err(DbError::SerializationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_attribute_mapping() {
+        for (name, value) in [
+            ("bold", json!(true)),
+            ("strike", json!(true)),
+            ("link", json!("https://example.com")),
+            ("background", json!("#ff0")),
+            ("comment", json!({ "author": "ada", "content": "typo" })),
+            ("color", json!("red")),
+            ("size", json!(12)),
+            ("bold", json!(false)),
+        ] {
+            let mark = mark_type(name, &value);
+            assert_eq!(attribute(&mark), (name.to_string(), value));
+        }
+
+        assert_eq!(
+            mark_type("size", &json!(12)),
+            MarkType::Custom {
+                name: "size".into(),
+                value: "12".into()
+            }
+        );
+    }
+
+    #[test]
+    fn test_overlapping_marks_split_runs() {
+        let mut rich = RichText::new("r1");
+        rich.insert(0, "one two three");
+        rich.bold(0, 7);
+        rich.italic(4, 13);
+
+        assert_eq!(
+            rich.to_yjs_compatible_json(),
+            json!([
+                { "insert": "one ", "attributes": { "bold": true } },
+                { "insert": "two", "attributes": { "bold": true, "italic": true } },
+                { "insert": " three", "attributes": { "italic": true } },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_delta_rejects_non_inserts() {
+        let retain = json!([{ "retain": 3 }]);
+        assert!(RichText::from_yjs_delta("r1", &retain).is_err());
+
+        let embed = json!([{ "insert": { "image": "a.png" } }]);
+        assert!(matches!(
+            RichText::from_yjs_delta("r1", &embed),
+            Err(DbError::UnsupportedOperation(_))
+        ));
+    }
+}
@@ -0,0 +1,205 @@
+//! Delta composition
+//!
+//! Joining two deltas must have the same effect as applying them one after
+//! the other, on replicas with concurrent edits of their own. The last test
+//! checks that `DocumentStore` relies on this to coalesce typing bursts.
+
+use mdcs_core::lattice::Lattice;
+use mdcs_db::{
+    ArrayId, DocumentStore, JsonCrdt, JsonPath, JsonValue, RGAText, RGATextDelta, RichText,
+    StoreChange, DEFAULT_COALESCE_THRESHOLD,
+};
+use proptest::prelude::*;
+
+/// An edit, with positions as fractions of the current length
+#[derive(Clone, Debug)]
+enum Op {
+    Insert(f64, String),
+    Delete(f64, usize),
+    Mark(f64, usize),
+    Unmark,
+    Set(usize, i64),
+    Remove(usize),
+    Push(i64),
+    Pop(f64),
+}
+
+fn op_strategy() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        3 => (0.0..=1.0, "[a-z ]{1,4}").prop_map(|(at, text)| Op::Insert(at, text)),
+        2 => (0.0..=1.0, 1usize..4).prop_map(|(at, len)| Op::Delete(at, len)),
+        1 => (0.0..=1.0, 1usize..4).prop_map(|(at, len)| Op::Mark(at, len)),
+        1 => Just(Op::Unmark),
+        2 => (0usize..3, 0i64..10).prop_map(|(key, value)| Op::Set(key, value)),
+        1 => (0usize..3).prop_map(Op::Remove),
+        2 => (0i64..10).prop_map(Op::Push),
+        1 => (0.0..=1.0).prop_map(Op::Pop),
+    ]
+}
+
+/// Two bursts of edits on the sender, and concurrent edits on the receiver
+fn bursts() -> impl Strategy<Value = (Vec<Op>, Vec<Op>, Vec<Op>)> {
+    let ops = || prop::collection::vec(op_strategy(), 0..12);
+    (ops(), ops(), ops())
+}
+
+fn at(fraction: f64, len: usize) -> usize {
+    ((fraction * len as f64) as usize).min(len)
+}
+
+fn edit_text(text: &mut RGAText, op: &Op) {
+    match op {
+        Op::Insert(f, s) => text.insert(at(*f, text.len()), s),
+        Op::Delete(f, len) if !text.is_empty() => {
+            let start = at(*f, text.len() - 1);
+            text.delete(start, (*len).min(text.len() - start));
+        }
+        _ => {}
+    }
+}
+
+fn edit_rich(text: &mut RichText, op: &Op) {
+    match op {
+        Op::Insert(f, s) => text.insert(at(*f, text.len()), s),
+        Op::Delete(f, len) if !text.is_empty() => {
+            let start = at(*f, text.len() - 1);
+            text.delete(start, (*len).min(text.len() - start));
+        }
+        Op::Mark(f, len) if !text.is_empty() => {
+            let start = at(*f, text.len() - 1);
+            text.bold(start, (start + len).min(text.len()));
+        }
+        Op::Unmark => {
+            let first = text.active_marks().next().map(|m| m.id.clone());
+            if let Some(id) = first {
+                text.remove_mark(&id);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn edit_json(doc: &mut JsonCrdt, list: &ArrayId, op: &Op) {
+    let key = |k: &usize| JsonPath::parse(["a", "b", "c"][*k]);
+    match op {
+        Op::Set(k, value) => doc.set(&key(k), JsonValue::Int(*value)).unwrap(),
+        Op::Remove(k) => {
+            let _ = doc.delete(&key(k));
+        }
+        Op::Push(value) => doc.array_push(list, JsonValue::Int(*value)).unwrap(),
+        Op::Pop(f) => {
+            let len = doc.array_len(list).unwrap();
+            if len > 0 {
+                doc.array_remove(list, at(*f, len - 1)).unwrap();
+            }
+        }
+        _ => {}
+    }
+}
+
+proptest! {
+    #[test]
+    fn prop_text_deltas_compose((first, second, local) in bursts()) {
+        let mut sender = RGAText::new("s");
+        let mut receiver = RGAText::new("r");
+        for op in &local {
+            edit_text(&mut receiver, op);
+        }
+
+        for op in &first {
+            edit_text(&mut sender, op);
+        }
+        let d1 = sender.take_delta().unwrap_or_default();
+        for op in &second {
+            edit_text(&mut sender, op);
+        }
+        let d2 = sender.take_delta().unwrap_or_default();
+
+        let mut stepwise = receiver.clone();
+        stepwise.apply_delta(&d1);
+        stepwise.apply_delta(&d2);
+        let mut joined = receiver.clone();
+        joined.apply_delta(&d1.join(&d2));
+        prop_assert_eq!(joined.to_string(), stepwise.to_string());
+        prop_assert_eq!(d1.join(&RGATextDelta::bottom()), d1);
+    }
+
+    #[test]
+    fn prop_rich_text_deltas_compose((first, second, local) in bursts()) {
+        let mut sender = RichText::new("s");
+        let mut receiver = RichText::new("r");
+        for op in &local {
+            edit_rich(&mut receiver, op);
+        }
+
+        for op in &first {
+            edit_rich(&mut sender, op);
+        }
+        let d1 = sender.take_delta().unwrap_or_default();
+        for op in &second {
+            edit_rich(&mut sender, op);
+        }
+        let d2 = sender.take_delta().unwrap_or_default();
+
+        let mut stepwise = receiver.clone();
+        stepwise.apply_delta(&d1);
+        stepwise.apply_delta(&d2);
+        let mut joined = receiver.clone();
+        joined.apply_delta(&d1.join(&d2));
+        prop_assert_eq!(joined.to_html(), stepwise.to_html());
+        prop_assert_eq!(joined.active_marks().count(), stepwise.active_marks().count());
+    }
+
+    #[test]
+    fn prop_json_deltas_compose((first, second, local) in bursts()) {
+        let mut sender = JsonCrdt::new("s");
+        let list = sender.set_array(&JsonPath::parse("list")).unwrap();
+        let mut receiver = JsonCrdt::new("r");
+        receiver.apply_delta(&sender.take_delta().unwrap());
+        for op in &local {
+            edit_json(&mut receiver, &list, op);
+        }
+
+        for op in &first {
+            edit_json(&mut sender, &list, op);
+        }
+        let d1 = sender.take_delta().unwrap_or_default();
+        for op in &second {
+            edit_json(&mut sender, &list, op);
+        }
+        let d2 = sender.take_delta().unwrap_or_default();
+
+        let mut stepwise = receiver.clone();
+        stepwise.apply_delta(&d1);
+        stepwise.apply_delta(&d2);
+        let mut joined = receiver.clone();
+        joined.apply_delta(&d1.join(&d2));
+        prop_assert_eq!(joined.to_json(), stepwise.to_json());
+    }
+}
+
+#[test]
+fn test_typing_burst_coalesces() {
+    let mut store = DocumentStore::new("r1");
+    let mut replica = DocumentStore::new("r2");
+    let id = store.create_text("notes");
+    let mut expected = String::new();
+
+    for i in 0..500 {
+        let ch = char::from(b'a' + (i % 26) as u8);
+        store.text_insert(&id, i, &ch.to_string()).unwrap();
+        expected.push(ch);
+        assert!(store.pending_len() <= DEFAULT_COALESCE_THRESHOLD + 1);
+    }
+    store.text_delete(&id, 100, 50).unwrap();
+    expected.replace_range(100..150, "");
+
+    store.coalesce_pending();
+    let changes = store.take_changes();
+    assert_eq!(changes.len(), 2);
+    assert!(matches!(changes[0], StoreChange::Create { .. }));
+
+    replica.apply_changes(&changes);
+    assert_eq!(replica.text_content(&id).unwrap(), expected);
+    assert_eq!(store.text_content(&id).unwrap(), expected);
+}
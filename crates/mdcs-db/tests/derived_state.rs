@@ -0,0 +1,198 @@
+//! Derived state maintained through `join_observed`
+//!
+//! Each test keeps a view of every replica up to date from the merge
+//! callbacks alone, through randomized edits and merges, and checks it
+//! against a view recomputed from scratch after every step.
+
+use mdcs_core::lattice::Lattice;
+use mdcs_core::observer::SetObserver;
+use mdcs_core::orset::ORSet;
+use mdcs_db::{JsonCrdt, JsonObserver, JsonPath, JsonValue, PathSegment, RGAText, TextObserver};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const REPLICAS: usize = 3;
+const STEPS: usize = 400;
+
+fn count_words(text: &[char]) -> usize {
+    text.split(|c| c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .count()
+}
+
+/// Word count of a text, updated per edit from the words around it
+struct WordCount {
+    text: Vec<char>,
+    words: usize,
+}
+
+impl WordCount {
+    fn new(text: &RGAText) -> Self {
+        let text: Vec<char> = text.iter().collect();
+        let words = count_words(&text);
+        Self { text, words }
+    }
+
+    /// Words touching `start..end`, counted from the surrounding whitespace
+    fn words_around(&self, start: usize, end: usize) -> usize {
+        let mut start = start;
+        while start > 0 && !self.text[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        let mut end = end;
+        while end < self.text.len() && !self.text[end].is_whitespace() {
+            end += 1;
+        }
+        count_words(&self.text[start..end])
+    }
+}
+
+impl TextObserver for WordCount {
+    fn on_insert(&mut self, position: usize, text: &str) {
+        self.words -= self.words_around(position, position);
+        let len = text.chars().count();
+        self.text.splice(position..position, text.chars());
+        self.words += self.words_around(position, position + len);
+    }
+
+    fn on_delete(&mut self, position: usize, length: usize) {
+        self.words -= self.words_around(position, position + length);
+        self.text.drain(position..position + length);
+        self.words += self.words_around(position, position);
+    }
+}
+
+#[test]
+fn test_word_count_through_random_merges() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut replicas: Vec<RGAText> = (0..REPLICAS)
+        .map(|i| RGAText::new(format!("r{}", i)))
+        .collect();
+    let mut views: Vec<WordCount> = replicas.iter().map(WordCount::new).collect();
+
+    for _ in 0..STEPS {
+        let i = rng.gen_range(0..REPLICAS);
+        match rng.gen_range(0..4) {
+            // Local edits rebuild the view; only merges are observed
+            0 | 1 => {
+                let position = rng.gen_range(0..=replicas[i].len());
+                let word = ["a", "bc", " ", "de f", "  g "][rng.gen_range(0..5)];
+                replicas[i].insert(position, word);
+                views[i] = WordCount::new(&replicas[i]);
+            }
+            2 if !replicas[i].is_empty() => {
+                let start = rng.gen_range(0..replicas[i].len());
+                let length = rng.gen_range(1..=(replicas[i].len() - start).min(4));
+                replicas[i].delete(start, length);
+                views[i] = WordCount::new(&replicas[i]);
+            }
+            _ => {
+                let j = rng.gen_range(0..REPLICAS);
+                let expected = replicas[i].join(&replicas[j]);
+                replicas[i] = replicas[i].join_observed(&replicas[j], &mut views[i]);
+                assert_eq!(replicas[i].to_string(), expected.to_string());
+            }
+        }
+
+        let text: Vec<char> = replicas[i].iter().collect();
+        assert_eq!(views[i].text, text);
+        assert_eq!(views[i].words, count_words(&text));
+    }
+}
+
+/// Number of members, updated from membership changes
+#[derive(Default)]
+struct MemberCount(usize);
+
+impl<T> SetObserver<T> for MemberCount {
+    fn on_added(&mut self, _value: &T) {
+        self.0 += 1;
+    }
+
+    fn on_removed(&mut self, _value: &T) {
+        self.0 -= 1;
+    }
+}
+
+#[test]
+fn test_member_count_through_random_merges() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut replicas: Vec<ORSet<String>> = vec![ORSet::new(); REPLICAS];
+    let mut counts: Vec<MemberCount> = (0..REPLICAS).map(|_| MemberCount::default()).collect();
+
+    for _ in 0..STEPS {
+        let i = rng.gen_range(0..REPLICAS);
+        let member = format!("user{}", rng.gen_range(0..10));
+        match rng.gen_range(0..3) {
+            0 => {
+                replicas[i].add(&format!("r{}", i), member);
+                counts[i] = MemberCount(replicas[i].len());
+            }
+            1 => {
+                replicas[i].remove(&member);
+                counts[i] = MemberCount(replicas[i].len());
+            }
+            _ => {
+                let j = rng.gen_range(0..REPLICAS);
+                replicas[i] = replicas[i].join_observed(&replicas[j], &mut counts[i]);
+            }
+        }
+        assert_eq!(counts[i].0, replicas[i].len());
+    }
+}
+
+/// A copy of the document, updated path by path
+struct Mirror(serde_json::Value);
+
+impl JsonObserver for Mirror {
+    fn on_path_changed(&mut self, path: &JsonPath, value: Option<&serde_json::Value>) {
+        let (last, parents) = path
+            .segments()
+            .split_last()
+            .expect("root is never reported");
+        let mut object = &mut self.0;
+        for segment in parents {
+            let PathSegment::Key(key) = segment else {
+                panic!("index in {}", path);
+            };
+            object = object.get_mut(key.as_str()).expect("parent exists");
+        }
+        let (PathSegment::Key(key), serde_json::Value::Object(map)) = (last, object) else {
+            panic!("{} is not an object field", path);
+        };
+        match value {
+            Some(value) => map.insert(key.clone(), value.clone()),
+            None => map.remove(key),
+        };
+    }
+}
+
+#[test]
+fn test_json_mirror_through_random_merges() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut replicas: Vec<JsonCrdt> = (0..REPLICAS)
+        .map(|i| JsonCrdt::new(format!("r{}", i)))
+        .collect();
+    let mut mirrors: Vec<Mirror> = replicas.iter().map(|r| Mirror(r.to_json())).collect();
+    let paths = ["a", "b", "a.x", "a.y", "b.x", "a.x.z"];
+
+    for _ in 0..STEPS {
+        let i = rng.gen_range(0..REPLICAS);
+        let path = JsonPath::parse(paths[rng.gen_range(0..paths.len())]);
+        match rng.gen_range(0..4) {
+            0 => {
+                let _ = replicas[i].set(&path, JsonValue::Int(rng.gen_range(0..3)));
+                mirrors[i] = Mirror(replicas[i].to_json());
+            }
+            1 => {
+                let _ = replicas[i].set_object(&path);
+                mirrors[i] = Mirror(replicas[i].to_json());
+            }
+            _ => {
+                let j = rng.gen_range(0..REPLICAS);
+                replicas[i] = replicas[i].join_observed(&replicas[j], &mut mirrors[i]);
+            }
+        }
+        assert_eq!(mirrors[i].0, replicas[i].to_json());
+    }
+}
@@ -0,0 +1,52 @@
+// Regenerates the Yjs snapshot fixtures used by tests/yjs_interop.rs.
+//
+//   npm install yjs@13
+//   node generate.mjs
+//
+// Each fixture is `Y.encodeStateAsUpdate(doc)` of the document built below;
+// the expected `toDelta()` of its Y.Text is printed next to it.
+
+import * as Y from 'yjs'
+import { writeFileSync } from 'node:fs'
+
+const sync = (a, b) => {
+  Y.applyUpdate(b, Y.encodeStateAsUpdate(a, Y.encodeStateVector(b)))
+  Y.applyUpdate(a, Y.encodeStateAsUpdate(b, Y.encodeStateVector(a)))
+}
+
+const write = (name, doc) => {
+  writeFileSync(new URL(name, import.meta.url), Y.encodeStateAsUpdate(doc))
+  console.log(name, JSON.stringify(doc.getText('content').toDelta()))
+}
+
+// formatted.bin: one client typing formatted text.
+{
+  const doc = new Y.Doc()
+  doc.clientID = 1
+  const text = doc.getText('content')
+  text.insert(0, 'Hello', { bold: true })
+  text.insert(5, ' world', { italic: true })
+  write('formatted.bin', doc)
+}
+
+// concurrent.bin: two clients editing the same spot offline, a deletion,
+// and a run with a link plus an attribute Carnelia has no mark type for.
+{
+  const alice = new Y.Doc()
+  alice.clientID = 1
+  const bob = new Y.Doc()
+  bob.clientID = 2
+  const a = alice.getText('content')
+  const b = bob.getText('content')
+
+  a.insert(0, 'Hello world')
+  sync(alice, bob)
+
+  a.insert(5, ' there')
+  b.insert(5, ',')
+  b.delete(7, 5)
+  b.insert(7, 'Carnelia', { link: 'https://carnelia.dev', color: '#e11d48' })
+  sync(alice, bob)
+
+  write('concurrent.bin', alice)
+}
@@ -0,0 +1,310 @@
+//! Sanitized HTML import against pasted and hostile markup
+//!
+//! The fixtures in `tests/fixtures/html` are clipboard HTML as Word and
+//! Google Docs write it, a collection of XSS vectors, and tag soup. Each is
+//! imported as-is, cut at random points and randomly mutated; none of that
+//! may panic or let through a mark, URL or piece of markup the policy
+//! doesn't allow.
+
+use mdcs_db::{
+    sanitize_html, BlockKind, MarkKind, MarkType, RichText, SanitizePolicy, SanitizeReport,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeSet;
+
+const FIXTURES: &[(&str, &str)] = &[
+    ("word", include_str!("fixtures/html/word.html")),
+    ("gdocs", include_str!("fixtures/html/gdocs.html")),
+    ("hostile", include_str!("fixtures/html/hostile.html")),
+    ("malformed", include_str!("fixtures/html/malformed.html")),
+];
+
+const MUTATIONS: usize = 200;
+
+/// Active marks as sorted `(start, end, type)` spans.
+fn spans(doc: &RichText) -> Vec<(usize, usize, MarkType)> {
+    let mut spans: Vec<_> = doc
+        .active_marks()
+        .map(|mark| {
+            let (start, end) = mark.range(doc.text()).unwrap();
+            (start, end, mark.mark_type.clone())
+        })
+        .collect();
+    spans.sort_by_key(|(start, end, mark)| (*start, *end, format!("{:?}", mark)));
+    spans
+}
+
+fn text_of(doc: &RichText, start: usize, end: usize) -> String {
+    doc.text_content()
+        .chars()
+        .skip(start)
+        .take(end - start)
+        .collect()
+}
+
+/// Import `html` and check everything the policy promises.
+fn import(html: &str, policy: &SanitizePolicy) -> (RichText, SanitizeReport) {
+    let (doc, report) = RichText::from_html_sanitized("r1", html, policy);
+
+    for (start, end, mark) in spans(&doc) {
+        let kind = MarkKind::of(&mark).expect("import only creates known marks");
+        assert!(policy.allowed_marks.contains(&kind), "{:?}", mark);
+        if policy.strip_empty_marks {
+            assert!(
+                !text_of(&doc, start, end).trim().is_empty(),
+                "{:?} covers only whitespace",
+                mark
+            );
+        }
+        if let MarkType::Link { url } = &mark {
+            let scheme = url.split_once(':').unwrap().0.to_ascii_lowercase();
+            assert!(policy.url_scheme_allowlist.contains(&scheme), "{}", url);
+        }
+    }
+
+    assert_only_mark_tags(&doc.to_html());
+    if let Some(max_length) = policy.max_length {
+        assert!(doc.len() <= max_length);
+    }
+    (doc, report)
+}
+
+/// Every tag in `html` is one `to_html` writes for a mark, with nothing
+/// smuggled into its attributes.
+fn assert_only_mark_tags(html: &str) {
+    const TAGS: &[&str] = &["strong", "em", "u", "s", "code", "a", "mark"];
+    let mut rest = html;
+    while let Some(open) = rest.find('<') {
+        let close = open + rest[open..].find('>').expect("unclosed tag");
+        let tag = &rest[open + 1..close];
+        let (name, attrs) = tag.split_once(' ').unwrap_or((tag, ""));
+        assert!(
+            TAGS.contains(&name.trim_start_matches('/')),
+            "<{}> in {}",
+            tag,
+            html
+        );
+        if let Some(url) = attrs.strip_prefix("href=\"") {
+            let url = url.strip_suffix('"').expect("one quoted href");
+            assert!(!url.contains('"'), "{}", tag);
+        } else if let Some(style) = attrs.strip_prefix("style=\"") {
+            let style = style.strip_suffix('"').expect("one quoted style");
+            assert!(!style.contains('"') && !style.contains(';'), "{}", tag);
+        } else {
+            assert_eq!(attrs, "", "{}", tag);
+        }
+        rest = &rest[close + 1..];
+    }
+}
+
+/// Cut `html` at a random character and change a few random characters.
+fn mutate(html: &str, rng: &mut StdRng) -> String {
+    const ALPHABET: &[char] = &['<', '>', '/', '"', '\'', '=', '&', ';', '#', ' ', 'é', '😀'];
+    let mut chars: Vec<char> = html.chars().collect();
+    chars.truncate(rng.gen_range(0..=chars.len()));
+    for _ in 0..rng.gen_range(0..8) {
+        if chars.is_empty() {
+            break;
+        }
+        let at = rng.gen_range(0..chars.len());
+        match rng.gen_range(0..3) {
+            0 => chars[at] = ALPHABET[rng.gen_range(0..ALPHABET.len())],
+            1 => {
+                chars.remove(at);
+            }
+            _ => chars.insert(at, ALPHABET[rng.gen_range(0..ALPHABET.len())]),
+        }
+    }
+    chars.into_iter().collect()
+}
+
+#[test]
+fn test_word_paste() {
+    let (doc, report) = import(FIXTURES[0].1, &SanitizePolicy::default());
+
+    assert_eq!(
+        doc.text_content(),
+        "Quarterly report\n\
+         Revenue grew by 12\u{a0}%, see the dashboard and the spreadsheet.\n\
+         ·\u{a0}\u{a0}\u{a0}\u{a0}\u{a0}\u{a0}\u{a0} First item with underline\n\
+         ·\u{a0}\u{a0}\u{a0} Second item, struck and bold\n\
+         Highlighted text\u{a0}\n\
+         Cell A\n\
+         Cell B\n\
+         \u{a0}"
+    );
+    let spans = spans(&doc);
+    assert!(spans.contains(&(0, 16, MarkType::Bold)));
+    assert!(spans.iter().any(|(_, _, mark)| matches!(
+        mark,
+        MarkType::Link { url } if url == "https://intranet.example.com/q3"
+    )));
+    assert_eq!(
+        spans
+            .iter()
+            .filter(|(_, _, mark)| matches!(mark, MarkType::Link { .. }))
+            .count(),
+        1,
+        "file: links are dropped"
+    );
+    for tag in ["head", "o:p", "span", "!--"] {
+        assert!(report.removed_tags.contains_key(tag), "{}", tag);
+    }
+    assert_eq!(report.removed_attrs["href"], 1);
+    assert!(!report.truncated);
+}
+
+#[test]
+fn test_google_docs_paste() {
+    let (doc, report) = import(FIXTURES[1].1, &SanitizePolicy::default());
+
+    assert_eq!(
+        doc.text_content(),
+        "Meeting notes\nAttendees: Alice, Bob\nShip the spec\nOld plan\n\nlet x = 1;\u{a0}\u{a0}trailing"
+    );
+    let spans = spans(&doc);
+    assert_eq!(
+        spans,
+        vec![
+            (14, 24, MarkType::Bold),
+            (36, 40, MarkType::Italic),
+            (
+                45,
+                49,
+                MarkType::Link {
+                    url: "https://docs.example.com/spec".into()
+                }
+            ),
+            (45, 49, MarkType::Underline),
+            (50, 58, MarkType::Strikethrough),
+        ],
+        "the font-weight:normal wrapper is not bold"
+    );
+    assert_eq!(report.removed_tags["span"], 9);
+    assert_eq!(report.removed_attrs["dir"], 7);
+}
+
+#[test]
+fn test_hostile_paste() {
+    let (doc, report) = import(FIXTURES[2].1, &SanitizePolicy::default());
+    let text = doc.text_content();
+
+    for dropped in ["pwned", "inside iframe", "svg link", "press", "alert(2)"] {
+        assert!(!text.contains(dropped), "{}", dropped);
+    }
+    // Escaped markup stays text, and is escaped again on the way out
+    assert!(text.contains("bold <script>alert(23)</script>"));
+    assert!(doc.to_html().contains("&lt;script&gt;alert(23)"));
+
+    let links: Vec<_> = spans(&doc)
+        .into_iter()
+        .filter_map(|(_, _, mark)| match mark {
+            MarkType::Link { url } => Some(url),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        links,
+        vec![
+            "https://ok.example/\"onmouseover=\"alert(12)",
+            "https://ok.example/"
+        ]
+    );
+    assert_eq!(report.removed_attrs["href"], 9);
+    assert!(report.removed_attrs.contains_key("onclick"));
+    assert!(report.removed_tags.contains_key("script"));
+}
+
+#[test]
+fn test_policy_is_applied() {
+    let policy = SanitizePolicy {
+        allowed_marks: BTreeSet::from([MarkKind::Italic, MarkKind::Highlight]),
+        allowed_block_types: BTreeSet::from([BlockKind::ListItem]),
+        collapse_whitespace: false,
+        strip_empty_marks: false,
+        url_scheme_allowlist: vec!["file".into()],
+        ..SanitizePolicy::default()
+    };
+
+    let (doc, _) = import(FIXTURES[0].1, &policy);
+    let marks: Vec<_> = spans(&doc).into_iter().map(|(_, _, mark)| mark).collect();
+    assert!(marks.contains(&MarkType::Highlight {
+        color: "yellow".into()
+    }));
+    assert!(!marks.contains(&MarkType::Bold));
+    assert!(!doc.text_content().contains("report\nRevenue"));
+
+    let (doc, _) = import("<ul><li>a <i> </i> b</li><li>c</li></ul>", &policy);
+    assert_eq!(doc.text_content(), "a   b\nc");
+    assert_eq!(spans(&doc), vec![(2, 3, MarkType::Italic)]);
+}
+
+#[test]
+fn test_truncation_lands_on_a_char_boundary() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let html = "<p>héllo <b>wörld</b> 😀😀 <i>ünïcödé</i></p><p>ß∂ƒ © 日本語のテキスト</p>";
+    let full = sanitize_html(html, &SanitizePolicy::default()).0.text;
+    let full_len = full.chars().count();
+
+    for max_length in (0..full_len + 2).chain((0..20).map(|_| rng.gen_range(0..full_len))) {
+        let policy = SanitizePolicy {
+            max_length: Some(max_length),
+            ..SanitizePolicy::default()
+        };
+        let (fragment, report) = sanitize_html(html, &policy);
+        assert!(fragment.text.chars().count() <= max_length);
+        assert!(full.starts_with(&fragment.text), "{:?}", fragment.text);
+        assert_eq!(report.truncated, full_len > max_length);
+        for (_, end, _) in &fragment.marks {
+            assert!(*end <= fragment.text.chars().count());
+        }
+        let (doc, _) = import(html, &policy);
+        assert_eq!(doc.text_content(), fragment.text);
+    }
+}
+
+#[test]
+fn test_mutated_fixtures_never_panic() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let policies = [
+        SanitizePolicy::default(),
+        SanitizePolicy {
+            allowed_marks: BTreeSet::from([MarkKind::Link, MarkKind::Highlight, MarkKind::Code]),
+            allowed_block_types: BTreeSet::new(),
+            max_length: Some(64),
+            collapse_whitespace: false,
+            strip_empty_marks: false,
+            url_scheme_allowlist: vec!["https".into()],
+        },
+    ];
+
+    for (_, html) in FIXTURES {
+        for _ in 0..MUTATIONS {
+            let html = mutate(html, &mut rng);
+            for policy in &policies {
+                import(&html, policy);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_paste_inserts_a_fragment() {
+    let mut doc = RichText::new("r1");
+    doc.insert(0, "Before  after");
+    doc.bold(0, 6);
+
+    let report = doc.paste_html(
+        7,
+        r#"<p style="color:red">new <em>words</em></p><p>here</p> "#,
+        &SanitizePolicy::default(),
+    );
+    assert_eq!(doc.text_content(), "Before new words\nhere after");
+    assert_eq!(
+        spans(&doc),
+        vec![(0, 6, MarkType::Bold), (11, 16, MarkType::Italic)]
+    );
+    assert_eq!(report.removed_attrs["style"], 1);
+    assert!(report.removed_tags.is_empty());
+}
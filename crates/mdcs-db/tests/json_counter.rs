@@ -0,0 +1,178 @@
+//! Counters in JSON documents
+//!
+//! Replicas increment the same counter concurrently and exchange deltas or
+//! full states in every order; the increments must all count. The other
+//! tests pin down how counters resolve against plain values written to the
+//! same field.
+
+use mdcs_core::lattice::Lattice;
+use mdcs_db::{DbError, JsonCrdt, JsonCrdtDelta, JsonPath, JsonType, JsonValue};
+
+fn path(p: &str) -> JsonPath {
+    JsonPath::parse(p)
+}
+
+fn counter(doc: &JsonCrdt, p: &str) -> Option<i64> {
+    match doc.get(&path(p))? {
+        JsonValue::Counter(id) => doc.counter_value(id),
+        _ => None,
+    }
+}
+
+/// Every ordering of `0..n`
+fn permutations(n: usize) -> Vec<Vec<usize>> {
+    if n == 0 {
+        return vec![Vec::new()];
+    }
+    let mut all = Vec::new();
+    for rest in permutations(n - 1) {
+        for i in 0..=rest.len() {
+            let mut order = rest.clone();
+            order.insert(i, n - 1);
+            all.push(order);
+        }
+    }
+    all
+}
+
+#[test]
+fn test_concurrent_increments_all_count() {
+    let mut replicas: Vec<JsonCrdt> = ["r1", "r2", "r3"].into_iter().map(JsonCrdt::new).collect();
+    let mut deltas: Vec<Vec<JsonCrdtDelta>> = vec![Vec::new(); 3];
+    for _ in 0..100 {
+        for (replica, sent) in replicas.iter_mut().zip(&mut deltas) {
+            replica.increment(&path("views"), 1).unwrap();
+            sent.push(replica.take_delta().unwrap());
+        }
+    }
+
+    for order in permutations(3) {
+        // Deltas, one replica's after another's
+        let mut doc = JsonCrdt::new("observer");
+        for &i in &order {
+            for delta in &deltas[i] {
+                doc.apply_delta(delta);
+            }
+        }
+        assert_eq!(counter(&doc, "views"), Some(300));
+        assert_eq!(doc.to_json()["views"], 300);
+
+        // Delivered again, they change nothing
+        for delta in &deltas[order[0]] {
+            doc.apply_delta(delta);
+        }
+        assert_eq!(counter(&doc, "views"), Some(300));
+
+        // Full states
+        let joined = order
+            .iter()
+            .fold(JsonCrdt::new("observer"), |doc, &i| doc.join(&replicas[i]));
+        assert_eq!(joined.to_json(), doc.to_json());
+    }
+
+    // Each replica catches up on the others' deltas, interleaved
+    for (i, replica) in replicas.iter_mut().enumerate() {
+        for n in 0..100 {
+            for (j, sent) in deltas.iter().enumerate() {
+                if i != j {
+                    replica.apply_delta(&sent[n]);
+                }
+            }
+        }
+        assert_eq!(counter(replica, "views"), Some(300));
+    }
+
+    // The joined deltas of a replica carry all its increments
+    let batch = deltas[0]
+        .iter()
+        .fold(JsonCrdtDelta::new(), |batch, delta| batch.join(delta));
+    let mut doc = JsonCrdt::new("observer");
+    doc.apply_delta(&batch);
+    assert_eq!(counter(&doc, "views"), Some(100));
+}
+
+#[test]
+fn test_decrements_and_types() {
+    let mut doc = JsonCrdt::new("r1");
+    doc.increment(&path("stock"), 10).unwrap();
+    doc.decrement(&path("stock"), 13).unwrap();
+    assert_eq!(counter(&doc, "stock"), Some(-3));
+    assert_eq!(doc.type_at(&path("stock")), Some(JsonType::Counter));
+
+    // Counters live in object fields, and only replace integers
+    doc.set(&path("name"), JsonValue::String("Ann".to_string()))
+        .unwrap();
+    assert!(matches!(
+        doc.increment(&path("name"), 1),
+        Err(DbError::TypeMismatch { .. })
+    ));
+    let list = doc.set_array(&path("list")).unwrap();
+    doc.array_push(&list, JsonValue::Int(1)).unwrap();
+    assert!(doc.increment(&path("list.0"), 1).is_err());
+    assert_eq!(doc.to_json()["name"], "Ann");
+}
+
+#[test]
+fn test_counter_starts_from_the_int_it_replaces() {
+    let mut r1 = JsonCrdt::new("r1");
+    r1.set(&path("count"), JsonValue::Int(5)).unwrap();
+    let mut r2 = r1.fork("r2");
+
+    // Both turn the same integer into a counter: they share it
+    r1.increment(&path("count"), 1).unwrap();
+    r2.increment(&path("count"), 1).unwrap();
+    let (d1, d2) = (r1.take_delta().unwrap(), r2.take_delta().unwrap());
+    r1.apply_delta(&d2);
+    r2.apply_delta(&d1);
+    assert_eq!(counter(&r1, "count"), Some(7));
+    assert_eq!(r1.to_json(), r2.to_json());
+}
+
+#[test]
+fn test_plain_write_over_counter_is_last_writer_wins() {
+    let mut r1 = JsonCrdt::new("r1");
+    r1.increment(&path("count"), 10).unwrap();
+    let mut r2 = r1.fork("r2");
+
+    // r1 resets the field while r2 keeps counting: the reset is the later
+    // write to the field, so it wins with r2's increments
+    r1.set(&path("count"), JsonValue::Int(0)).unwrap();
+    r2.increment(&path("count"), 5).unwrap();
+    let (d1, d2) = (r1.take_delta().unwrap(), r2.take_delta().unwrap());
+    r1.apply_delta(&d2);
+    r2.apply_delta(&d1);
+    assert_eq!(r1.get(&path("count")), Some(&JsonValue::Int(0)));
+    assert_eq!(r1.to_json(), r2.to_json());
+
+    // Counting again starts a new counter from the reset value, without
+    // the old counter's increments
+    r1.increment(&path("count"), 1).unwrap();
+    r2.increment(&path("count"), 1).unwrap();
+    let (d1, d2) = (r1.take_delta().unwrap(), r2.take_delta().unwrap());
+    r1.apply_delta(&d2);
+    r2.apply_delta(&d1);
+    assert_eq!(counter(&r1, "count"), Some(2));
+    assert_eq!(r1.to_json(), r2.to_json());
+
+    // A deleted counter restarts at zero
+    r1.delete(&path("count")).unwrap();
+    r1.increment(&path("count"), 1).unwrap();
+    assert_eq!(counter(&r1, "count"), Some(1));
+
+    // Replaced counters are collected
+    assert_eq!(r1.gc().counters_removed, 2);
+    assert_eq!(counter(&r1, "count"), Some(1));
+}
+
+#[test]
+fn test_increments_advance_the_version_vector() {
+    let mut r1 = JsonCrdt::new("r1");
+    r1.increment(&path("count"), 1).unwrap();
+    let before = r1.version_vector();
+    r1.increment(&path("count"), 1).unwrap();
+    assert!(r1.version_vector().strictly_dominates(&before));
+
+    let mut r2 = JsonCrdt::new("r2");
+    r2.apply_delta(&r1.take_delta().unwrap());
+    assert_eq!(r2.version_vector(), r1.version_vector());
+}
@@ -0,0 +1,111 @@
+//! Garbage collection of JSON documents
+//!
+//! Two replicas keep replacing a "layout" subtree on every save and sync by
+//! deltas and by full joins. One collects garbage, the other never does;
+//! the collecting one must stay bounded and both must keep showing the
+//! same JSON, whatever old deltas or states arrive.
+
+use mdcs_core::lattice::Lattice;
+use mdcs_db::{JsonCrdt, JsonCrdtDelta, JsonPath, JsonValue};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const SAVES: usize = 500;
+const MERGED_SAVES: usize = 200;
+
+/// Replace the layout, as a save does
+fn save(doc: &mut JsonCrdt, n: usize) -> JsonCrdtDelta {
+    doc.set_object(&JsonPath::parse("layout")).unwrap();
+    doc.set(&JsonPath::parse("layout.width"), JsonValue::Int(n as i64))
+        .unwrap();
+    let panes = doc.set_array(&JsonPath::parse("layout.panes")).unwrap();
+    for pane in 0..3 {
+        let id = doc.create_object();
+        doc.array_push(&panes, JsonValue::Object(id)).unwrap();
+        doc.set(
+            &JsonPath::parse(&format!("title{}", pane)),
+            JsonValue::Int(n as i64),
+        )
+        .unwrap();
+    }
+    doc.take_delta().unwrap()
+}
+
+#[test]
+fn test_repeated_saves_stay_bounded() {
+    let mut collected = JsonCrdt::new("r1").with_gc_threshold(1.0);
+    let mut uncollected = JsonCrdt::new("r1");
+    let mut peak = 0;
+    for n in 0..SAVES {
+        save(&mut collected, n);
+        save(&mut uncollected, n);
+        peak = peak.max(collected.container_count());
+    }
+
+    assert_eq!(collected.to_json(), uncollected.to_json());
+    assert!(peak <= 20, "peak of {} containers", peak);
+    assert_eq!(uncollected.container_count(), 1 + SAVES * 5);
+    let before = serde_json::to_vec(&collected).unwrap().len();
+    assert!(before * 50 < serde_json::to_vec(&uncollected).unwrap().len());
+
+    // The first collection spares everything created since the last one
+    assert_eq!(uncollected.gc(), Default::default());
+    let stats = uncollected.gc();
+    assert_eq!(
+        stats.objects_removed + stats.arrays_removed,
+        (SAVES - 1) * 5
+    );
+    assert!(stats.bytes_reclaimed > 0);
+    assert_eq!(uncollected.container_count(), 6);
+}
+
+#[test]
+fn test_merging_with_a_peer_that_never_collects() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut a = JsonCrdt::new("a").with_gc_threshold(0.5);
+    let mut b = JsonCrdt::new("b");
+    let mut old_deltas = Vec::new();
+
+    for n in 0..MERGED_SAVES {
+        let (writer, reader) = if rng.gen_bool(0.5) {
+            (&mut a, &mut b)
+        } else {
+            (&mut b, &mut a)
+        };
+        let delta = save(writer, n);
+        match rng.gen_range(0..4) {
+            // Delivered now, later, or with the next full join
+            0 => reader.apply_delta(&delta),
+            1 => old_deltas.push(delta),
+            2 => {}
+            _ => {
+                a = a.join(&b);
+                b = b.join(&a);
+                assert_eq!(a.to_json(), b.to_json());
+            }
+        }
+        if rng.gen_bool(0.1) && !old_deltas.is_empty() {
+            let delta = old_deltas.swap_remove(rng.gen_range(0..old_deltas.len()));
+            a.apply_delta(&delta);
+            b.apply_delta(&delta);
+        }
+        assert!(a.container_count() <= 60, "{}", a.container_count());
+    }
+
+    // Replaying every old delta brings back only garbage
+    for delta in &old_deltas {
+        a.apply_delta(delta);
+        b.apply_delta(delta);
+    }
+    let a = a.join(&b);
+    let b = b.join(&a);
+    assert_eq!(a.to_json(), b.to_json());
+    assert_eq!(a.to_json()["layout"]["panes"].as_array().unwrap().len(), 3);
+
+    let mut a = a;
+    a.gc();
+    a.gc();
+    assert_eq!(a.to_json(), b.to_json());
+    assert_eq!(a.container_count(), 6);
+    assert!(b.container_count() > 100);
+}
@@ -0,0 +1,120 @@
+//! Streaming and projected queries
+//!
+//! `query_iter`, `query_meta` and `count` must agree with `query` on
+//! randomized stores and randomized options.
+
+use mdcs_core::clock::ManualClock;
+use mdcs_db::{CollectionId, DocumentStore, DocumentType, JsonValue, QueryOptions, SortField};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::Arc;
+
+const STORES: u64 = 20;
+const QUERIES: usize = 50;
+
+fn random_store(rng: &mut StdRng) -> (DocumentStore, Vec<CollectionId>) {
+    let clock = Arc::new(ManualClock::new(0));
+    let mut store = DocumentStore::with_clock("r1", clock.clone());
+    let inbox = store.create_collection("inbox", None).unwrap();
+    let archive = store
+        .create_collection("archive", Some(inbox.clone()))
+        .unwrap();
+    let collections = vec![inbox, archive];
+
+    for i in 0..rng.gen_range(0..60) {
+        // Repeated timestamps and titles exercise ties in sorting
+        clock.advance(rng.gen_range(0..3));
+        let title = format!("{}/{}", ["a", "ab", "b"][rng.gen_range(0..3)], i % 7);
+        let content = ["Lorem Ipsum", "dolor sit", "LOREM"][rng.gen_range(0..3)];
+        let id = match rng.gen_range(0..3) {
+            0 => {
+                let id = store.create_text(title);
+                store.text_insert(&id, 0, content).unwrap();
+                id
+            }
+            1 => {
+                let id = store.create_rich_text(title);
+                store.rich_text_insert(&id, 0, content).unwrap();
+                id
+            }
+            _ => {
+                let id = store.create_json(title);
+                let value = JsonValue::String(content.to_string());
+                store.json_set(&id, "body", value).unwrap();
+                id
+            }
+        };
+        let doc = store.get_mut(&id).unwrap();
+        for (key, values) in [("status", ["draft", "done"]), ("owner", ["ann", "bo"])] {
+            if rng.gen_bool(0.7) {
+                doc.set_metadata(key, values[rng.gen_range(0..2)]);
+            }
+        }
+        if rng.gen_bool(0.5) {
+            let collection = collections[rng.gen_range(0..2)].clone();
+            store.move_document(&id, Some(collection)).unwrap();
+        }
+    }
+    (store, collections)
+}
+
+fn random_options(rng: &mut StdRng, collections: &[CollectionId]) -> QueryOptions {
+    QueryOptions {
+        document_type: [
+            None,
+            Some(DocumentType::Text),
+            Some(DocumentType::RichText),
+            Some(DocumentType::Json),
+        ][rng.gen_range(0..4)]
+        .clone(),
+        title_prefix: [None, Some("a"), Some("ab")][rng.gen_range(0..3)].map(String::from),
+        sort_by: [
+            None,
+            Some(SortField::Title),
+            Some(SortField::CreatedAt),
+            Some(SortField::ModifiedAt),
+        ][rng.gen_range(0..4)]
+        .clone(),
+        sort_desc: rng.gen_bool(0.5),
+        limit: rng.gen_bool(0.5).then(|| rng.gen_range(0..20)),
+        offset: rng.gen_bool(0.5).then(|| rng.gen_range(0..20)),
+        collection: rng
+            .gen_bool(0.3)
+            .then(|| collections[rng.gen_range(0..collections.len())].clone()),
+        include_descendants: rng.gen_bool(0.5),
+        metadata_filters: [("status", "draft"), ("owner", "bo")]
+            .into_iter()
+            .filter(|_| rng.gen_bool(0.3))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+        content_contains: [None, Some("lorem"), Some("SIT")][rng.gen_range(0..3)].map(String::from),
+    }
+}
+
+#[test]
+fn test_projections_agree_with_query() {
+    for seed in 0..STORES {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (store, collections) = random_store(&mut rng);
+
+        for _ in 0..QUERIES {
+            let options = random_options(&mut rng, &collections);
+            let expected: Vec<_> = store.query(&options);
+
+            let streamed: Vec<_> = store.query_iter(&options).map(|doc| &doc.id).collect();
+            assert_eq!(
+                streamed,
+                expected.iter().map(|doc| &doc.id).collect::<Vec<_>>()
+            );
+
+            let meta = store.query_meta(&options);
+            assert_eq!(meta.len(), expected.len());
+            for (meta, doc) in meta.iter().zip(&expected) {
+                assert_eq!(meta, &doc.meta());
+                assert_eq!(meta.doc_type, doc.document_type());
+            }
+
+            assert_eq!(store.count(&options), expected.len(), "{:?}", options);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Position lookups on collaborative text
+//!
+//! RGAText finds positions through an index instead of walking the text.
+//! Random edit scripts on three replicas, synced by deltas and joins, check
+//! every indexed lookup against a plain scan of the visible characters; a
+//! long run of appends checks that typing at the end of a large document
+//! stays cheap.
+
+use mdcs_core::lattice::Lattice;
+use mdcs_db::RGAText;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+fn edit(text: &mut RGAText, rng: &mut StdRng) {
+    let len = text.len();
+    if len > 4 && rng.gen_bool(0.4) {
+        let start = rng.gen_range(0..len - 1);
+        text.delete(start, rng.gen_range(1..=(len - start).min(5)));
+    } else {
+        let word: String = (0..rng.gen_range(1..6))
+            .map(|_| match rng.gen_range(0..8) {
+                0 => '\n',
+                _ => rng.gen_range(b'a'..=b'z') as char,
+            })
+            .collect();
+        text.insert(rng.gen_range(0..=len), &word);
+    }
+}
+
+/// Line and column of every position, from a scan of the characters.
+fn scanned_line_cols(chars: &[char]) -> Vec<(usize, usize)> {
+    let (mut line, mut col) = (0, 0);
+    let mut line_cols = Vec::with_capacity(chars.len() + 1);
+    for &ch in chars {
+        line_cols.push((line, col));
+        if ch == '\n' {
+            line += 1;
+            col = 0;
+        } else {
+            col += 1;
+        }
+    }
+    line_cols.push((line, col));
+    line_cols
+}
+
+fn assert_lookups_match_scan(text: &RGAText) {
+    let chars: Vec<char> = text.iter().collect();
+    assert_eq!(text.len(), chars.len());
+
+    for (position, &ch) in chars.iter().enumerate() {
+        assert_eq!(text.char_at(position), Some(ch));
+        let id = text.position_to_id(position).unwrap();
+        assert_eq!(text.id_to_position(&id), Some(position));
+    }
+    assert_eq!(text.char_at(chars.len()), None);
+    assert_eq!(text.position_to_id(chars.len()), None);
+
+    let line_cols = scanned_line_cols(&chars);
+    for (position, &(line, col)) in line_cols.iter().enumerate() {
+        assert_eq!(text.char_to_line_col(position), Some((line, col)));
+        assert_eq!(text.line_col_to_char(line, col), Some(position));
+    }
+    assert_eq!(text.char_to_line_col(chars.len() + 1), None);
+    assert_eq!(text.line_count(), line_cols.last().unwrap().0 + 1);
+    assert_eq!(text.line_col_to_char(text.line_count(), 0), None);
+
+    if chars.len() > 2 {
+        let (start, end) = (chars.len() / 3, chars.len() * 2 / 3);
+        let expected: String = chars[start..end].iter().collect();
+        assert_eq!(text.slice(start, end), expected);
+    }
+}
+
+#[test]
+fn test_indexed_lookups_match_a_scan() {
+    for seed in 0..8 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut replicas: Vec<RGAText> = ["a", "b", "c"].into_iter().map(RGAText::new).collect();
+
+        for _ in 0..60 {
+            for text in replicas.iter_mut() {
+                edit(text, &mut rng);
+            }
+            let (from, to) = (rng.gen_range(0..3), rng.gen_range(0..3));
+            if from != to {
+                if rng.gen_bool(0.5) {
+                    if let Some(delta) = replicas[from].take_delta() {
+                        replicas[to].apply_delta(&delta);
+                    }
+                } else {
+                    replicas[to] = replicas[to].join(&replicas[from]);
+                }
+            }
+            for text in &replicas {
+                assert_lookups_match_scan(text);
+            }
+        }
+
+        let merged = replicas[0].join(&replicas[1]).join(&replicas[2]);
+        assert_lookups_match_scan(&merged);
+    }
+}
+
+#[test]
+fn test_deleted_characters_have_no_position() {
+    let mut text = RGAText::new("r1");
+    text.insert(0, "abc\ndef");
+    let id = text.position_to_id(2).unwrap();
+    text.delete(2, 2);
+
+    assert_eq!(text.to_string(), "abdef");
+    assert_eq!(text.id_to_position(&id), None);
+    assert_eq!(text.line_count(), 1);
+    assert_eq!(text.char_to_line_col(5), Some((0, 5)));
+}
+
+#[test]
+fn test_appends_to_a_large_document_stay_fast() {
+    const CHARS: usize = 100_000;
+    let mut text = RGAText::new("r1");
+    let start = Instant::now();
+    for i in 0..CHARS {
+        let ch = if i % 80 == 79 { "\n" } else { "x" };
+        text.insert(text.len(), ch);
+    }
+    let elapsed = start.elapsed();
+
+    assert_eq!(text.len(), CHARS);
+    assert_eq!(text.line_count(), CHARS / 80 + 1);
+    assert_eq!(text.char_to_line_col(CHARS - 1), Some((CHARS / 80 - 1, 79)));
+    assert_eq!(text.line_col_to_char(CHARS / 80, 0), Some(CHARS));
+    // A scan from the start per keystroke would take minutes; the bound
+    // leaves room for debug builds on slow machines
+    assert!(
+        elapsed < Duration::from_secs(30),
+        "{} appends took {:?}",
+        CHARS,
+        elapsed
+    );
+}
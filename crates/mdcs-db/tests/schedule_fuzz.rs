@@ -0,0 +1,641 @@
+//! Schedule fuzzing for the delta-sync clusters
+//!
+//! Generates random schedules of mutations and network events (out-of-order
+//! delivery, drops, duplicates, crashes, partitions) over [`CausalCluster`]
+//! and [`AntiEntropyCluster`], forces full delivery, then checks that all
+//! replicas converged and that type-specific invariants hold.
+//!
+//! A failing schedule is shrunk to a minimal reproducer and printed as a
+//! test case that can be pasted into this file.
+//!
+//! `cargo test` runs a few seeds plus the regression schedules below. For
+//! longer runs:
+//!
+//! ```text
+//! SCHEDULE_FUZZ_ITERS=5000 cargo test -p mdcs-db --features fuzz --test schedule_fuzz
+//! ```
+
+use mdcs_core::gset::GSet;
+use mdcs_core::lattice::{DeltaCRDT, Lattice};
+use mdcs_core::orset::{ORSet, Tag};
+use mdcs_db::RGAText;
+use mdcs_delta::anti_entropy::{AntiEntropyCluster, NetworkConfig};
+use mdcs_delta::causal::CausalCluster;
+use mdcs_delta::mutators::gset;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+const REPLICAS: usize = 3;
+const SCHEDULE_LEN: usize = 60;
+const SETTLE_ROUNDS: usize = 20;
+
+// ============================================================================
+// Schedules
+// ============================================================================
+
+/// One step of a schedule.
+///
+/// Replica numbers are taken modulo the cluster size and message indices
+/// modulo the number of messages in flight, so every subsequence of a
+/// schedule is itself a valid schedule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Event {
+    /// Apply the model's operation `op` at a replica
+    Mutate { replica: usize, op: u64 },
+    /// Send a replica's pending deltas to all peers
+    Sync { replica: usize },
+    /// Deliver an in-flight message, possibly out of order
+    Deliver { index: usize },
+    /// Lose an in-flight message (retransmitted when the run settles)
+    Drop { index: usize },
+    /// Deliver an in-flight message twice
+    Duplicate { index: usize },
+    /// Crash a replica, losing its volatile state
+    Crash { replica: usize },
+    /// Cut the link between two replicas
+    Partition { a: usize, b: usize },
+    /// Restore all links
+    Heal,
+}
+
+fn generate(seed: u64, replicas: usize, len: usize) -> Vec<Event> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..len)
+        .map(|_| match rng.gen_range(0..100) {
+            0..=34 => Event::Mutate {
+                replica: rng.gen_range(0..replicas),
+                op: rng.gen_range(0..64),
+            },
+            35..=54 => Event::Sync {
+                replica: rng.gen_range(0..replicas),
+            },
+            55..=74 => Event::Deliver {
+                index: rng.gen_range(0..8),
+            },
+            75..=79 => Event::Drop {
+                index: rng.gen_range(0..8),
+            },
+            80..=84 => Event::Duplicate {
+                index: rng.gen_range(0..8),
+            },
+            85..=89 => Event::Crash {
+                replica: rng.gen_range(0..replicas),
+            },
+            90..=94 => Event::Partition {
+                a: rng.gen_range(0..replicas),
+                b: rng.gen_range(0..replicas),
+            },
+            _ => Event::Heal,
+        })
+        .collect()
+}
+
+/// Remove chunks of events while the schedule keeps failing.
+fn shrink(events: &[Event], fails: impl Fn(&[Event]) -> bool) -> Vec<Event> {
+    let mut events = events.to_vec();
+    let mut chunk = events.len() / 2;
+    while chunk > 0 {
+        let mut removed = false;
+        let mut start = 0;
+        while start < events.len() {
+            let end = (start + chunk).min(events.len());
+            let candidate: Vec<Event> = events[..start]
+                .iter()
+                .chain(&events[end..])
+                .copied()
+                .collect();
+            if fails(&candidate) {
+                events = candidate;
+                removed = true;
+            } else {
+                start += chunk;
+            }
+        }
+        if !removed {
+            chunk /= 2;
+        }
+    }
+    events
+}
+
+// ============================================================================
+// Clusters
+// ============================================================================
+
+/// The operations a schedule needs from a simulated cluster.
+trait Sim<S: Lattice + Clone + PartialEq> {
+    const NAME: &'static str;
+    fn with_replicas(n: usize) -> Self;
+    fn mutate_with<F: FnOnce(&S) -> S>(&mut self, idx: usize, mutator: F);
+    fn sync(&mut self, idx: usize);
+    fn deliver(&mut self, index: usize);
+    fn drop_message(&mut self, index: usize);
+    fn duplicate_message(&mut self, index: usize);
+    fn in_flight(&self) -> usize;
+    fn crash(&mut self, idx: usize);
+    fn partition(&mut self, a: usize, b: usize);
+    fn heal(&mut self);
+    fn settle_round(&mut self);
+    fn is_converged(&self) -> bool;
+    fn state(&self, idx: usize) -> &S;
+}
+
+impl<S: Lattice + Clone + PartialEq> Sim<S> for CausalCluster<S> {
+    const NAME: &'static str = "CausalCluster";
+
+    fn with_replicas(n: usize) -> Self {
+        CausalCluster::new(n, 0.0)
+    }
+
+    fn mutate_with<F: FnOnce(&S) -> S>(&mut self, idx: usize, mutator: F) {
+        self.mutate(idx, mutator);
+    }
+
+    fn sync(&mut self, idx: usize) {
+        self.broadcast_intervals(idx);
+    }
+
+    fn deliver(&mut self, index: usize) {
+        CausalCluster::deliver(self, index);
+    }
+
+    fn drop_message(&mut self, index: usize) {
+        CausalCluster::drop_message(self, index);
+    }
+
+    fn duplicate_message(&mut self, index: usize) {
+        CausalCluster::duplicate_message(self, index);
+    }
+
+    fn in_flight(&self) -> usize {
+        self.in_flight_count()
+    }
+
+    fn crash(&mut self, idx: usize) {
+        self.crash_and_recover(idx);
+    }
+
+    fn partition(&mut self, a: usize, b: usize) {
+        CausalCluster::partition(self, a, b);
+    }
+
+    fn heal(&mut self) {
+        CausalCluster::heal(self);
+    }
+
+    fn settle_round(&mut self) {
+        self.full_sync_round();
+        self.retransmit_and_process();
+    }
+
+    fn is_converged(&self) -> bool {
+        CausalCluster::is_converged(self)
+    }
+
+    fn state(&self, idx: usize) -> &S {
+        self.replica(idx).state()
+    }
+}
+
+impl<S: Lattice + Clone + PartialEq> Sim<S> for AntiEntropyCluster<S> {
+    const NAME: &'static str = "AntiEntropyCluster";
+
+    fn with_replicas(n: usize) -> Self {
+        AntiEntropyCluster::new(n, NetworkConfig::default())
+    }
+
+    fn mutate_with<F: FnOnce(&S) -> S>(&mut self, idx: usize, mutator: F) {
+        self.mutate(idx, mutator);
+    }
+
+    fn sync(&mut self, idx: usize) {
+        self.broadcast(idx);
+    }
+
+    fn deliver(&mut self, index: usize) {
+        AntiEntropyCluster::deliver(self, index);
+    }
+
+    fn drop_message(&mut self, index: usize) {
+        AntiEntropyCluster::drop_message(self, index);
+    }
+
+    fn duplicate_message(&mut self, index: usize) {
+        AntiEntropyCluster::duplicate_message(self, index);
+    }
+
+    fn in_flight(&self) -> usize {
+        self.in_flight_count()
+    }
+
+    fn crash(&mut self, idx: usize) {
+        self.crash_and_recover(idx);
+    }
+
+    fn partition(&mut self, a: usize, b: usize) {
+        AntiEntropyCluster::partition(self, a, b);
+    }
+
+    fn heal(&mut self) {
+        AntiEntropyCluster::heal(self);
+    }
+
+    fn settle_round(&mut self) {
+        self.full_sync_round();
+        self.retransmit_and_process();
+    }
+
+    fn is_converged(&self) -> bool {
+        AntiEntropyCluster::is_converged(self)
+    }
+
+    fn state(&self, idx: usize) -> &S {
+        self.replica(idx).state()
+    }
+}
+
+// ============================================================================
+// Models
+// ============================================================================
+
+/// A CRDT under test, plus a record of the operations applied to it.
+trait Model: Default {
+    type State: Lattice + Clone + PartialEq;
+    const NAME: &'static str;
+
+    /// Compute the delta for `op` at `replica` and record its effect.
+    fn mutate(&mut self, replica: usize, op: u64, state: &Self::State) -> Self::State;
+
+    /// Check type-specific invariants on a converged state.
+    fn check(&self, state: &Self::State) -> Result<(), String>;
+
+    /// Short description of a state for failure reports.
+    fn describe(state: &Self::State) -> String;
+}
+
+/// GSet: the converged set holds exactly the inserted elements.
+#[derive(Default)]
+struct GSetModel {
+    inserted: BTreeSet<u64>,
+}
+
+impl Model for GSetModel {
+    type State = GSet<u64>;
+    const NAME: &'static str = "GSetModel";
+
+    fn mutate(&mut self, _replica: usize, op: u64, _state: &GSet<u64>) -> GSet<u64> {
+        let value = op % 16;
+        self.inserted.insert(value);
+        gset::insert_delta(value)
+    }
+
+    fn check(&self, state: &GSet<u64>) -> Result<(), String> {
+        let actual: BTreeSet<u64> = state.iter().copied().collect();
+        if actual != self.inserted {
+            return Err(format!("expected {:?}, got {:?}", self.inserted, actual));
+        }
+        Ok(())
+    }
+
+    fn describe(state: &GSet<u64>) -> String {
+        format!("{:?}", state.iter().collect::<Vec<_>>())
+    }
+}
+
+/// ORSet: an element is present iff one of its add tags was never removed.
+///
+/// In particular, an element whose every add was observed by a remove must
+/// not be resurrected.
+#[derive(Default)]
+struct OrSetModel {
+    tags: BTreeMap<u64, BTreeSet<Tag>>,
+    removed: BTreeSet<Tag>,
+}
+
+impl Model for OrSetModel {
+    type State = ORSet<u64>;
+    const NAME: &'static str = "OrSetModel";
+
+    fn mutate(&mut self, replica: usize, op: u64, state: &ORSet<u64>) -> ORSet<u64> {
+        let value = (op / 3) % 8;
+        if op.is_multiple_of(3) {
+            // Removal tombstones every tag this replica has observed
+            let mut next = state.clone();
+            next.remove(&value);
+            if let Some(delta) = next.split_delta() {
+                self.removed.extend(delta.removals);
+            }
+            next
+        } else {
+            let mut delta = ORSet::new();
+            delta.add(&format!("r{}", replica), value);
+            if let Some(d) = delta.split_delta() {
+                for (value, tags) in d.additions {
+                    self.tags.entry(value).or_default().extend(tags);
+                }
+            }
+            delta
+        }
+    }
+
+    fn check(&self, state: &ORSet<u64>) -> Result<(), String> {
+        let expected: BTreeSet<u64> = self
+            .tags
+            .iter()
+            .filter(|(_, tags)| tags.iter().any(|t| !self.removed.contains(t)))
+            .map(|(value, _)| *value)
+            .collect();
+        let actual: BTreeSet<u64> = state.iter().copied().collect();
+
+        let resurrected: Vec<_> = actual.difference(&expected).collect();
+        if !resurrected.is_empty() {
+            return Err(format!("removed elements resurrected: {:?}", resurrected));
+        }
+        let lost: Vec<_> = expected.difference(&actual).collect();
+        if !lost.is_empty() {
+            return Err(format!("added elements lost: {:?}", lost));
+        }
+        Ok(())
+    }
+
+    fn describe(state: &ORSet<u64>) -> String {
+        format!("{:?}", state.iter().collect::<Vec<_>>())
+    }
+}
+
+/// RGAText: every inserted character survives exactly once unless deleted.
+///
+/// Each insert is a run of two characters that never occur elsewhere, so a
+/// duplicated run shows up as a repeated character.
+#[derive(Default)]
+struct TextModel {
+    next_char: u32,
+    inserted: BTreeSet<char>,
+    deleted: BTreeSet<char>,
+}
+
+impl TextModel {
+    fn fresh_char(&mut self) -> char {
+        let c = char::from_u32(0x4E00 + self.next_char).expect("valid char");
+        self.next_char += 1;
+        self.inserted.insert(c);
+        c
+    }
+}
+
+impl Model for TextModel {
+    type State = RGAText;
+    const NAME: &'static str = "TextModel";
+
+    fn mutate(&mut self, replica: usize, op: u64, state: &RGAText) -> RGAText {
+        let mut text = state.fork(format!("r{}", replica));
+        let len = text.len();
+        if op.is_multiple_of(4) && len > 0 {
+            let position = (op / 4) as usize % len;
+            if let Some(c) = text.char_at(position) {
+                self.deleted.insert(c);
+            }
+            text.delete(position, 1);
+        } else {
+            let position = (op / 4) as usize % (len + 1);
+            let run: String = (0..2).map(|_| self.fresh_char()).collect();
+            text.insert(position, &run);
+        }
+        text
+    }
+
+    fn check(&self, state: &RGAText) -> Result<(), String> {
+        let mut counts: BTreeMap<char, usize> = BTreeMap::new();
+        for c in state.iter() {
+            *counts.entry(c).or_default() += 1;
+        }
+        let duplicated: Vec<_> = counts.iter().filter(|(_, n)| **n > 1).collect();
+        if !duplicated.is_empty() {
+            return Err(format!("duplicated characters: {:?}", duplicated));
+        }
+
+        let expected: BTreeSet<char> = self.inserted.difference(&self.deleted).copied().collect();
+        let actual: BTreeSet<char> = counts.into_keys().collect();
+        if actual != expected {
+            return Err(format!(
+                "expected characters {:?}, got {:?}",
+                expected, actual
+            ));
+        }
+        Ok(())
+    }
+
+    fn describe(state: &RGAText) -> String {
+        format!("{:?}", state.to_string())
+    }
+}
+
+// ============================================================================
+// Driver
+// ============================================================================
+
+/// Run a schedule, force full delivery and check convergence and invariants.
+fn run<C: Sim<M::State>, M: Model>(replicas: usize, events: &[Event]) -> Result<(), String> {
+    let mut cluster = C::with_replicas(replicas);
+    let mut model = M::default();
+
+    for event in events {
+        match *event {
+            Event::Mutate { replica, op } => {
+                let replica = replica % replicas;
+                cluster.mutate_with(replica, |state| model.mutate(replica, op, state));
+            }
+            Event::Sync { replica } => cluster.sync(replica % replicas),
+            Event::Deliver { index } => {
+                if cluster.in_flight() > 0 {
+                    cluster.deliver(index % cluster.in_flight());
+                }
+            }
+            Event::Drop { index } => {
+                if cluster.in_flight() > 0 {
+                    cluster.drop_message(index % cluster.in_flight());
+                }
+            }
+            Event::Duplicate { index } => {
+                if cluster.in_flight() > 0 {
+                    cluster.duplicate_message(index % cluster.in_flight());
+                }
+            }
+            Event::Crash { replica } => cluster.crash(replica % replicas),
+            Event::Partition { a, b } => cluster.partition(a % replicas, b % replicas),
+            Event::Heal => cluster.heal(),
+        }
+    }
+
+    // Force full delivery
+    cluster.heal();
+    for _ in 0..SETTLE_ROUNDS {
+        cluster.settle_round();
+        if cluster.is_converged() {
+            break;
+        }
+    }
+
+    if !cluster.is_converged() {
+        let states: Vec<String> = (0..replicas)
+            .map(|i| format!("replica {}: {}", i, M::describe(cluster.state(i))))
+            .collect();
+        return Err(format!("replicas diverged\n  {}", states.join("\n  ")));
+    }
+    for i in 0..replicas {
+        model
+            .check(cluster.state(i))
+            .map_err(|err| format!("replica {}: {}", i, err))?;
+    }
+    Ok(())
+}
+
+/// Format a failing schedule as a regression test.
+fn reproducer<C: Sim<M::State>, M: Model>(
+    seed: u64,
+    replicas: usize,
+    events: &[Event],
+    error: &str,
+) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{} with {} failed (seed {}, {} replicas): {}",
+        C::NAME,
+        M::NAME,
+        seed,
+        replicas,
+        error
+    )
+    .unwrap();
+    writeln!(out, "\nMinimal reproducer:\n").unwrap();
+    writeln!(out, "#[test]").unwrap();
+    writeln!(out, "fn regression_seed_{}() {{", seed).unwrap();
+    writeln!(out, "    let events = [").unwrap();
+    for event in events {
+        writeln!(out, "        Event::{:?},", event).unwrap();
+    }
+    writeln!(out, "    ];").unwrap();
+    writeln!(
+        out,
+        "    run::<{}<_>, {}>({}, &events).unwrap();",
+        C::NAME,
+        M::NAME,
+        replicas
+    )
+    .unwrap();
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+/// Run one generated schedule; on failure, shrink it and panic with a
+/// reproducer.
+fn fuzz_seed<C: Sim<M::State>, M: Model>(seed: u64) {
+    let events = generate(seed, REPLICAS, SCHEDULE_LEN);
+    if run::<C, M>(REPLICAS, &events).is_ok() {
+        return;
+    }
+
+    let minimal = shrink(&events, |events| run::<C, M>(REPLICAS, events).is_err());
+    let error = run::<C, M>(REPLICAS, &minimal).unwrap_err();
+    panic!("{}", reproducer::<C, M>(seed, REPLICAS, &minimal, &error));
+}
+
+fn fuzz_all(seeds: std::ops::Range<u64>) {
+    for seed in seeds {
+        fuzz_seed::<CausalCluster<_>, GSetModel>(seed);
+        fuzz_seed::<CausalCluster<_>, OrSetModel>(seed);
+        fuzz_seed::<CausalCluster<_>, TextModel>(seed);
+        fuzz_seed::<AntiEntropyCluster<_>, GSetModel>(seed);
+        fuzz_seed::<AntiEntropyCluster<_>, OrSetModel>(seed);
+        fuzz_seed::<AntiEntropyCluster<_>, TextModel>(seed);
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[test]
+fn fuzz_smoke() {
+    fuzz_all(0..4);
+}
+
+#[cfg(feature = "fuzz")]
+#[test]
+fn fuzz_schedules() {
+    let iters = std::env::var("SCHEDULE_FUZZ_ITERS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(500);
+    let start = std::env::var("SCHEDULE_FUZZ_SEED")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1000);
+    fuzz_all(start..start + iters);
+}
+
+/// A duplicated interval buffered ahead of its predecessor used to block
+/// every later interval from the same sender.
+#[test]
+fn regression_seed_0() {
+    let events = [
+        Event::Mutate { replica: 1, op: 41 },
+        Event::Sync { replica: 1 },
+        Event::Drop { index: 1 },
+        Event::Mutate { replica: 2, op: 38 },
+        Event::Mutate { replica: 1, op: 50 },
+        Event::Sync { replica: 2 },
+        Event::Sync { replica: 1 },
+        Event::Deliver { index: 2 },
+        Event::Mutate { replica: 1, op: 61 },
+        Event::Deliver { index: 7 },
+        Event::Duplicate { index: 2 },
+    ];
+    run::<CausalCluster<_>, GSetModel>(3, &events).unwrap();
+}
+
+/// Unsent deltas are lost when a replica crashes inside a partition.
+#[test]
+fn regression_crash_during_partition() {
+    let events = [
+        Event::Mutate { replica: 0, op: 1 },
+        Event::Mutate { replica: 1, op: 5 },
+        Event::Sync { replica: 0 },
+        Event::Sync { replica: 1 },
+        Event::Deliver { index: 0 },
+        Event::Partition { a: 0, b: 2 },
+        Event::Mutate { replica: 0, op: 3 },
+        Event::Mutate { replica: 2, op: 9 },
+        Event::Sync { replica: 0 },
+        Event::Deliver { index: 1 },
+        Event::Crash { replica: 0 },
+        Event::Mutate { replica: 0, op: 4 },
+        Event::Duplicate { index: 0 },
+        Event::Sync { replica: 2 },
+        Event::Deliver { index: 3 },
+        Event::Heal,
+        Event::Sync { replica: 0 },
+        Event::Crash { replica: 2 },
+    ];
+    run::<CausalCluster<_>, OrSetModel>(3, &events).unwrap();
+    run::<CausalCluster<_>, TextModel>(3, &events).unwrap();
+    run::<AntiEntropyCluster<_>, OrSetModel>(3, &events).unwrap();
+    run::<AntiEntropyCluster<_>, TextModel>(3, &events).unwrap();
+}
+
+#[test]
+fn shrink_finds_minimal_schedule() {
+    let events = generate(7, REPLICAS, SCHEDULE_LEN);
+    let fails = |events: &[Event]| {
+        events.iter().any(|e| matches!(e, Event::Crash { .. }))
+            && events.iter().any(|e| matches!(e, Event::Heal))
+    };
+    assert!(fails(&events));
+
+    let minimal = shrink(&events, fails);
+    assert_eq!(minimal.len(), 2);
+    assert!(fails(&minimal));
+}
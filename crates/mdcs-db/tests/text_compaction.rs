@@ -0,0 +1,105 @@
+//! Tombstone compaction of collaborative text
+//!
+//! Three replicas edit at random and sync by deltas. Whenever all of them
+//! have caught up, the first one reports their frontiers to a stability
+//! monitor and compacts up to its stable frontier; the others never do.
+//! Edits keep flowing both ways, old deltas are replayed, and every replica
+//! must keep showing the same text.
+
+use mdcs_compaction::{FrontierUpdate, StabilityMonitor};
+use mdcs_core::lattice::Lattice;
+use mdcs_db::{RGAText, RGATextDelta};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const REPLICAS: usize = 3;
+const ROUNDS: usize = 60;
+
+fn edit(text: &mut RGAText, rng: &mut StdRng) -> RGATextDelta {
+    for _ in 0..rng.gen_range(1..4) {
+        let len = text.len();
+        if len > 3 && rng.gen_bool(0.5) {
+            let start = rng.gen_range(0..len - 1);
+            text.delete(start, rng.gen_range(1..=(len - start).min(4)));
+        } else {
+            let word: String = (0..rng.gen_range(1..5))
+                .map(|_| rng.gen_range(b'a'..=b'z') as char)
+                .collect();
+            text.insert(rng.gen_range(0..=len), &word);
+        }
+    }
+    text.take_delta().unwrap_or_default()
+}
+
+/// Stable frontier of the replicas, as seen from the first one
+fn stable_frontier(monitor: &mut StabilityMonitor, replicas: &[RGAText]) {
+    monitor.update_local_frontier(replicas[0].version_vector(), Vec::new());
+    for (i, text) in replicas.iter().enumerate().skip(1) {
+        monitor.update_peer_frontier(FrontierUpdate {
+            peer_id: format!("r{}", i),
+            version_vector: text.version_vector(),
+            heads: Vec::new(),
+            timestamp: 0,
+            is_delta: false,
+        });
+    }
+}
+
+#[test]
+fn test_compacting_replica_converges_with_the_rest() {
+    let mut rng = StdRng::seed_from_u64(17);
+    let mut replicas: Vec<_> = (0..REPLICAS)
+        .map(|i| RGAText::new(format!("r{}", i)))
+        .collect();
+    let mut monitor = StabilityMonitor::new("r0");
+    let mut history = Vec::new();
+    let mut compacted = 0;
+
+    for round in 0..ROUNDS {
+        // Each replica edits, then its delta reaches one random peer
+        for i in 0..REPLICAS {
+            let delta = edit(&mut replicas[i], &mut rng);
+            let peer = (i + rng.gen_range(1..REPLICAS)) % REPLICAS;
+            replicas[peer].apply_delta(&delta);
+            history.push(delta);
+        }
+
+        if round % 5 == 4 {
+            // Everyone catches up, which delivers every deletion too
+            let merged = replicas
+                .iter()
+                .fold(RGAText::bottom(), |acc, text| acc.join(text));
+            for text in &mut replicas {
+                *text = text.join(&merged);
+            }
+            stable_frontier(&mut monitor, &replicas);
+            compacted += replicas[0].compact(monitor.stable_frontier());
+            assert_eq!(replicas[0].tombstone_count(), 0);
+            assert_eq!(replicas[0].to_string(), replicas[1].to_string());
+        } else {
+            // A late copy of an old delta shows up at the compacting replica
+            let old = &history[rng.gen_range(0..history.len())];
+            replicas[0].apply_delta(old);
+        }
+    }
+
+    let expected = replicas
+        .iter()
+        .fold(RGAText::bottom(), |acc, text| acc.join(text))
+        .to_string();
+    for text in &replicas {
+        assert_eq!(replicas[0].join(text).to_string(), expected);
+        assert_eq!(text.join(&replicas[0]).to_string(), expected);
+    }
+
+    // Replaying every delta, in any order, changes nothing
+    let mut replayed = replicas[0].join(&replicas[1]).join(&replicas[2]);
+    for delta in history.iter().rev() {
+        replayed.apply_delta(delta);
+    }
+    assert_eq!(replayed.to_string(), expected);
+
+    assert!(compacted > 0);
+    assert!(replicas[0].tombstone_count() < replicas[1].tombstone_count());
+    assert_eq!(replicas[0].live_count(), replicas[1].live_count());
+}
@@ -19,32 +19,336 @@
 //! 2. On send to peer j:
 //!    - send D\[acked\[j\]..\] to j
 //!
+//!    By default the unacked deltas are joined into one delta-group, so
+//!    overlapping deltas collapse and one ack covers them all (see
+//!    [`SyncMode`]).
+//!
 //! 3. On receive delta d from peer i:
 //!    - X = X ⊔ d     // apply (idempotent!)
 //!    - send ack(seq) to i
-
-use crate::buffer::{DeltaReplica, ReplicaId, SeqNo};
-use mdcs_core::lattice::Lattice;
-use std::collections::VecDeque;
+//!
+//!    Under [`AckStrategy::Cumulative`] the acks to i are coalesced into
+//!    one covering everything received from i since the last, sent once
+//!    the incoming batch has been processed.
+//!
+//!    With [`AntiEntropyCluster::with_delta_check`], a delta that depends
+//!    on history the receiver hasn't observed is rejected instead, and the
+//!    sender's full state is sent in its place.
+//!
+//! Acks may carry a receive [`Window`]; a sender stops sending to a peer
+//! whose window is full until its next ack (see [`crate::flow`]).
+//!
+//! With a [`RetransmitPolicy`], a sync round only sends deltas a peer has
+//! not been sent yet. Each one gets a deadline that backs off
+//! exponentially, and [`AntiEntropyCluster::tick`] resends just the deltas
+//! still unacked past theirs, to the peer that hasn't acked them.
+//!
+//! A replica joining a running cluster sends each peer a [`Digest`] of
+//! what it already has. The peer counts its deltas covered by the digest
+//! as acked and sends the rest, or its full state if those were already
+//! collected (see [`AntiEntropyCluster::add_replica`]).
+//!
+//! A replica leaving is forgotten by every other member, along with the
+//! messages to and from it, so it no longer holds back garbage collection.
+//! Its id can't join again; a replica coming back takes a fresh one (see
+//! [`AntiEntropyCluster::remove_replica`]).
+//!
+//! On the wire, deltas travel in a [`DeltaEnvelope`]; see
+//! [`DeltaReplica::receive_wire`].
+
+use crate::buffer::{DeltaReplica, Digest, ReplicaId, SeqNo};
+use crate::envelope::{
+    CodecError, CodecRegistry, DecodeError, DeltaEnvelope, PoisonPolicy, Received,
+};
+use crate::flow::{ReceiverConfig, Window};
+use mdcs_core::lattice::{DeltaRejected, Lattice};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 
 /// Message types for the anti-entropy protocol
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum AntiEntropyMessage<D> {
-    /// Delta message: contains delta, source, destination and sequence number
+    /// Delta message: contains delta, source, destination and the range of
+    /// sequence numbers `first_seq..=seq` it covers
     Delta {
         from: ReplicaId,
         to: ReplicaId,
         delta: D,
+        first_seq: SeqNo,
         seq: SeqNo,
     },
-    /// Acknowledgment message: from -> to acknowledges seq
+    /// Acknowledgment message: from -> to acknowledges `first_seq..=seq`,
+    /// and may advertise how much more it will accept
     Ack {
         from: ReplicaId,
         to: ReplicaId,
+        first_seq: SeqNo,
+        seq: SeqNo,
+        #[serde(default)]
+        window: Option<Window>,
+    },
+    /// NACK: from -> to can't decode deltas of `type_id` at `version`
+    Unsupported {
+        from: ReplicaId,
+        to: ReplicaId,
+        type_id: String,
+        version: u16,
+    },
+    /// Handshake: from -> to has every delta `digest` covers, and wants
+    /// the rest
+    Digest {
+        from: ReplicaId,
+        to: ReplicaId,
+        digest: Digest,
+    },
+    /// Full state of `from`, covering its deltas `1..=seq`: the fallback
+    /// for a delta `to` rejected
+    State {
+        from: ReplicaId,
+        to: ReplicaId,
+        state: D,
         seq: SeqNo,
     },
 }
 
+impl<D> AntiEntropyMessage<D> {
+    /// Sender and recipient of the message
+    fn endpoints(&self) -> (&str, &str) {
+        match self {
+            AntiEntropyMessage::Delta { from, to, .. }
+            | AntiEntropyMessage::Ack { from, to, .. }
+            | AntiEntropyMessage::Unsupported { from, to, .. }
+            | AntiEntropyMessage::Digest { from, to, .. }
+            | AntiEntropyMessage::State { from, to, .. } => (from, to),
+        }
+    }
+}
+
+impl<S: Serialize + DeserializeOwned> AntiEntropyMessage<S> {
+    /// Wrap the delta in an envelope for the wire
+    pub fn seal(
+        self,
+        codecs: &CodecRegistry<S>,
+    ) -> Result<AntiEntropyMessage<DeltaEnvelope>, CodecError> {
+        Ok(match self {
+            AntiEntropyMessage::Delta {
+                from,
+                to,
+                delta,
+                first_seq,
+                seq,
+            } => AntiEntropyMessage::Delta {
+                from,
+                to,
+                delta: codecs.encode(&delta)?,
+                first_seq,
+                seq,
+            },
+            AntiEntropyMessage::Ack {
+                from,
+                to,
+                first_seq,
+                seq,
+                window,
+            } => AntiEntropyMessage::Ack {
+                from,
+                to,
+                first_seq,
+                seq,
+                window,
+            },
+            AntiEntropyMessage::Unsupported {
+                from,
+                to,
+                type_id,
+                version,
+            } => AntiEntropyMessage::Unsupported {
+                from,
+                to,
+                type_id,
+                version,
+            },
+            AntiEntropyMessage::Digest { from, to, digest } => {
+                AntiEntropyMessage::Digest { from, to, digest }
+            }
+            AntiEntropyMessage::State {
+                from,
+                to,
+                state,
+                seq,
+            } => AntiEntropyMessage::State {
+                from,
+                to,
+                state: codecs.encode(&state)?,
+                seq,
+            },
+        })
+    }
+}
+
+impl AntiEntropyMessage<DeltaEnvelope> {
+    /// Decode the delta with the receiver's codecs
+    pub fn open<S>(&self, codecs: &CodecRegistry<S>) -> Result<AntiEntropyMessage<S>, DecodeError> {
+        Ok(match self {
+            AntiEntropyMessage::Delta {
+                from,
+                to,
+                delta,
+                first_seq,
+                seq,
+            } => AntiEntropyMessage::Delta {
+                from: from.clone(),
+                to: to.clone(),
+                delta: codecs.decode(delta)?,
+                first_seq: *first_seq,
+                seq: *seq,
+            },
+            AntiEntropyMessage::Ack {
+                from,
+                to,
+                first_seq,
+                seq,
+                window,
+            } => AntiEntropyMessage::Ack {
+                from: from.clone(),
+                to: to.clone(),
+                first_seq: *first_seq,
+                seq: *seq,
+                window: *window,
+            },
+            AntiEntropyMessage::Unsupported {
+                from,
+                to,
+                type_id,
+                version,
+            } => AntiEntropyMessage::Unsupported {
+                from: from.clone(),
+                to: to.clone(),
+                type_id: type_id.clone(),
+                version: *version,
+            },
+            AntiEntropyMessage::Digest { from, to, digest } => AntiEntropyMessage::Digest {
+                from: from.clone(),
+                to: to.clone(),
+                digest: digest.clone(),
+            },
+            AntiEntropyMessage::State {
+                from,
+                to,
+                state,
+                seq,
+            } => AntiEntropyMessage::State {
+                from: from.clone(),
+                to: to.clone(),
+                state: codecs.decode(state)?,
+                seq: *seq,
+            },
+        })
+    }
+
+    /// The `Unsupported` NACK answering this message
+    pub fn nack(&self, error: &DecodeError) -> Self {
+        let (from, to) = self.endpoints();
+        AntiEntropyMessage::Unsupported {
+            from: to.to_string(),
+            to: from.to_string(),
+            type_id: error.type_id().to_string(),
+            version: error.version(),
+        }
+    }
+}
+
+impl<S: Lattice + Clone + Serialize + DeserializeOwned> DeltaReplica<S, S> {
+    /// Handle an enveloped message from a peer
+    ///
+    /// Deltas are decoded with `codecs` and acked. One that can't be
+    /// decoded is not applied: it is answered with an `Unsupported` NACK and
+    /// skipped, or with [`PoisonPolicy::Stop`] returned as an error. A NACK
+    /// from a peer marks it incompatible, and no more deltas are synced to
+    /// it until [`clear_incompatibility`](Self::clear_incompatibility).
+    ///
+    /// A digest is recorded and answered by the deltas of the next sync. A
+    /// full state is joined and acked like a delta covering all of its
+    /// sender's history.
+    ///
+    /// The acks returned advertise no window; a transport that queues
+    /// messages can set one from its queue depth with a [`ReceiverConfig`].
+    pub fn receive_wire(
+        &mut self,
+        msg: AntiEntropyMessage<DeltaEnvelope>,
+        codecs: &CodecRegistry<S>,
+    ) -> Result<Received<AntiEntropyMessage<DeltaEnvelope>>, DecodeError> {
+        let opened = match msg.open(codecs) {
+            Ok(opened) => opened,
+            Err(error) => {
+                return match codecs.poison_policy() {
+                    PoisonPolicy::SkipAndReport => Ok(Received::Rejected {
+                        nack: msg.nack(&error),
+                        error,
+                    }),
+                    PoisonPolicy::Stop => Err(error),
+                };
+            }
+        };
+        let received = match opened {
+            AntiEntropyMessage::Delta {
+                from,
+                delta,
+                first_seq,
+                seq,
+                ..
+            } => {
+                self.receive_delta_range(&from, &delta, first_seq, seq);
+                Received::Handled(Some(AntiEntropyMessage::Ack {
+                    from: self.id.clone(),
+                    to: from,
+                    first_seq,
+                    seq,
+                    window: None,
+                }))
+            }
+            AntiEntropyMessage::Ack {
+                from,
+                first_seq,
+                seq,
+                window,
+                ..
+            } => {
+                self.process_ack_range(&from, first_seq, seq);
+                self.flow_mut().on_ack(&from, window);
+                Received::Handled(None)
+            }
+            AntiEntropyMessage::Unsupported {
+                from,
+                type_id,
+                version,
+                ..
+            } => match self.mark_incompatible(&from, type_id, version) {
+                Some(event) => Received::Incompatible(event),
+                None => Received::Handled(None),
+            },
+            AntiEntropyMessage::Digest { from, digest, .. } => {
+                self.receive_digest(&from, &digest);
+                Received::Handled(None)
+            }
+            AntiEntropyMessage::State {
+                from, state, seq, ..
+            } => {
+                self.receive_state_from(&from, &state, seq);
+                Received::Handled(Some(AntiEntropyMessage::Ack {
+                    from: self.id.clone(),
+                    to: from,
+                    first_seq: 1,
+                    seq,
+                    window: None,
+                }))
+            }
+        };
+        Ok(received)
+    }
+}
+
 /// A network simulator for testing anti-entropy under various conditions
 #[derive(Debug)]
 pub struct NetworkSimulator<D> {
@@ -54,8 +358,30 @@ pub struct NetworkSimulator<D> {
     lost: Vec<AntiEntropyMessage<D>>,
     /// Configuration
     config: NetworkConfig,
-    /// Random seed for deterministic testing
+    /// Random state, starting from the configured seed
     rng_state: u64,
+    /// Groups of replicas cut off from each other
+    partition: Partition,
+    /// Messages sent, lost ones included
+    sent: usize,
+    /// Acks sent, lost ones included
+    acks_sent: usize,
+}
+
+/// When receivers acknowledge deltas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AckStrategy {
+    /// Ack every delta message as it is received
+    #[default]
+    PerDelta,
+    /// Ack what was received from a sender since the last ack, through
+    /// its highest contiguous seq, in one message sent when the network
+    /// drains
+    ///
+    /// With `max_delay_rounds > 0` the ack waits that many sync rounds,
+    /// coalescing more deltas; meanwhile the sender re-sends what it has
+    /// not seen acked.
+    Cumulative { max_delay_rounds: usize },
 }
 
 /// Network configuration for simulation
@@ -65,8 +391,16 @@ pub struct NetworkConfig {
     pub loss_rate: f64,
     /// Probability of message duplication (0.0 - 1.0)
     pub dup_rate: f64,
-    /// Probability of message reordering (0.0 - 1.0)
+    /// Probability of message reordering (0.0 - 1.0): the message
+    /// overtakes one sent earlier, which is delayed behind it
     pub reorder_rate: f64,
+    /// Seed for loss, duplication and reordering; the same seed gives the
+    /// same delivery order
+    pub seed: u64,
+    /// When receivers acknowledge deltas
+    pub ack_strategy: AckStrategy,
+    /// When senders resend unacked deltas, if on timers
+    pub retransmit: Option<RetransmitPolicy>,
 }
 
 impl Default for NetworkConfig {
@@ -75,10 +409,45 @@ impl Default for NetworkConfig {
             loss_rate: 0.0,
             dup_rate: 0.0,
             reorder_rate: 0.0,
+            seed: 12345,
+            ack_strategy: AckStrategy::default(),
+            retransmit: None,
+        }
+    }
+}
+
+/// Retransmission timers with exponential backoff
+///
+/// A delta sent to a peer is due again `base_interval` ticks later if the
+/// peer hasn't acked it; each retransmission multiplies the wait by
+/// `multiplier`, up to `max_interval`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetransmitPolicy {
+    /// Ticks before the first retransmission
+    pub base_interval: u64,
+    /// Factor the interval grows by after each retransmission
+    pub multiplier: f64,
+    /// Longest interval between retransmissions
+    pub max_interval: u64,
+}
+
+impl Default for RetransmitPolicy {
+    fn default() -> Self {
+        Self {
+            base_interval: 2,
+            multiplier: 2.0,
+            max_interval: 32,
         }
     }
 }
 
+impl RetransmitPolicy {
+    /// The interval after one that has just expired
+    fn backoff(&self, interval: u64) -> u64 {
+        ((interval as f64 * self.multiplier).ceil() as u64).clamp(1, self.max_interval.max(1))
+    }
+}
+
 impl NetworkConfig {
     /// Create a lossy network configuration
     pub fn lossy(loss_rate: f64) -> Self {
@@ -96,12 +465,71 @@ impl NetworkConfig {
         }
     }
 
+    /// Create a network that reorders messages
+    pub fn reordering(reorder_rate: f64) -> Self {
+        Self {
+            reorder_rate,
+            ..Default::default()
+        }
+    }
+
     /// Create a chaotic network (all problems)
     pub fn chaotic() -> Self {
         Self {
             loss_rate: 0.1,
             dup_rate: 0.2,
             reorder_rate: 0.3,
+            ..Default::default()
+        }
+    }
+
+    /// Seed the simulator, for reproducible runs
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Set when receivers acknowledge deltas
+    pub fn with_ack_strategy(mut self, ack_strategy: AckStrategy) -> Self {
+        self.ack_strategy = ack_strategy;
+        self
+    }
+
+    /// Resend unacked deltas on timers instead of every sync round
+    pub fn with_retransmit_policy(mut self, policy: RetransmitPolicy) -> Self {
+        self.retransmit = Some(policy);
+        self
+    }
+}
+
+/// Groups of replicas that only reach replicas in the same group
+///
+/// Replicas in no group reach everyone.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Partition {
+    group_of: HashMap<ReplicaId, usize>,
+}
+
+impl Partition {
+    pub(crate) fn new(groups: Vec<Vec<ReplicaId>>) -> Self {
+        let group_of = groups
+            .into_iter()
+            .enumerate()
+            .flat_map(|(group, ids)| ids.into_iter().map(move |id| (id, group)))
+            .collect();
+        Self { group_of }
+    }
+
+    /// Take a replica out of its group
+    pub(crate) fn remove(&mut self, id: &str) {
+        self.group_of.remove(id);
+    }
+
+    /// Check if messages from `from` to `to` are dropped
+    pub(crate) fn cuts(&self, from: &str, to: &str) -> bool {
+        match (self.group_of.get(from), self.group_of.get(to)) {
+            (Some(a), Some(b)) => a != b,
+            _ => false,
         }
     }
 }
@@ -111,8 +539,11 @@ impl<D: Clone> NetworkSimulator<D> {
         Self {
             in_flight: VecDeque::new(),
             lost: Vec::new(),
+            rng_state: config.seed,
             config,
-            rng_state: 12345,
+            partition: Partition::default(),
+            sent: 0,
+            acks_sent: 0,
         }
     }
 
@@ -124,8 +555,13 @@ impl<D: Clone> NetworkSimulator<D> {
 
     /// Send a message through the network
     pub fn send(&mut self, msg: AntiEntropyMessage<D>) {
+        self.sent += 1;
+        if matches!(msg, AntiEntropyMessage::Ack { .. }) {
+            self.acks_sent += 1;
+        }
+
         // Check for loss
-        if self.next_random() < self.config.loss_rate {
+        if self.is_cut(&msg) || self.next_random() < self.config.loss_rate {
             self.lost.push(msg);
             return;
         }
@@ -152,15 +588,95 @@ impl<D: Clone> NetworkSimulator<D> {
     }
 
     /// Receive the next message (if any)
+    ///
+    /// Messages across a partition are lost instead.
     pub fn receive(&mut self) -> Option<AntiEntropyMessage<D>> {
-        self.in_flight.pop_front()
+        while let Some(msg) = self.in_flight.pop_front() {
+            if !self.is_cut(&msg) {
+                return Some(msg);
+            }
+            self.lost.push(msg);
+        }
+        None
+    }
+
+    /// Split the replicas into groups that can't reach each other
+    ///
+    /// Messages between groups, including those already in flight, are
+    /// lost until [`heal`](Self::heal). Replicas in no group reach
+    /// everyone.
+    pub fn partition(&mut self, groups: Vec<Vec<ReplicaId>>) {
+        self.partition = Partition::new(groups);
+    }
+
+    /// Let every replica reach every other again
+    pub fn heal(&mut self) {
+        self.partition = Partition::default();
+    }
+
+    /// Check if a message would cross the partition
+    pub fn is_cut(&self, msg: &AntiEntropyMessage<D>) -> bool {
+        let (from, to) = msg.endpoints();
+        self.partition.cuts(from, to)
+    }
+
+    /// Peek at the message at `index` in the in-flight queue
+    pub fn peek(&self, index: usize) -> Option<&AntiEntropyMessage<D>> {
+        self.in_flight.get(index)
+    }
+
+    /// Receive the message at `index`, out of order
+    pub fn take(&mut self, index: usize) -> Option<AntiEntropyMessage<D>> {
+        self.in_flight.remove(index)
+    }
+
+    /// Lose the message at `index` (it can still be retransmitted)
+    pub fn drop_at(&mut self, index: usize) -> bool {
+        match self.in_flight.remove(index) {
+            Some(msg) => {
+                self.lost.push(msg);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Deliver the message at `index` twice
+    pub fn duplicate_at(&mut self, index: usize) -> bool {
+        match self.in_flight.get(index) {
+            Some(msg) => {
+                self.in_flight.push_back(msg.clone());
+                true
+            }
+            None => false,
+        }
     }
 
     /// Re-send lost messages (simulates retransmission)
+    ///
+    /// Messages across a partition stay lost.
     pub fn retransmit_lost(&mut self) {
-        for msg in self.lost.drain(..) {
-            self.in_flight.push_back(msg);
-        }
+        let (cut, resend): (Vec<_>, Vec<_>) = std::mem::take(&mut self.lost)
+            .into_iter()
+            .partition(|msg| self.is_cut(msg));
+        self.lost = cut;
+        self.in_flight.extend(resend);
+    }
+
+    /// Drop every message to or from `id`, in flight or lost, and take it
+    /// out of the partition
+    ///
+    /// Returns the number of messages dropped.
+    pub fn forget(&mut self, id: &str) -> usize {
+        let before = self.in_flight.len() + self.lost.len();
+        let keep = |msg: &AntiEntropyMessage<D>| {
+            let (from, to) = msg.endpoints();
+            from != id && to != id
+        };
+        self.in_flight.retain(keep);
+        self.lost.retain(keep);
+        self.partition.remove(id);
+        before - self.in_flight.len() - self.lost.len()
     }
 
     /// Check if network is empty
@@ -177,6 +693,87 @@ impl<D: Clone> NetworkSimulator<D> {
     pub fn lost_count(&self) -> usize {
         self.lost.len()
     }
+
+    /// Number of messages sent, lost ones included
+    pub fn sent_count(&self) -> usize {
+        self.sent
+    }
+
+    /// Number of acks sent, lost ones included
+    pub fn ack_count(&self) -> usize {
+        self.acks_sent
+    }
+}
+
+/// How a sync round packs a replica's unacked deltas into messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncMode {
+    /// Join them into delta-groups: one message per peer, or several if a
+    /// byte budget is set
+    #[default]
+    DeltaGroups,
+    /// Send each buffered delta as its own message
+    PerDelta,
+}
+
+/// Measures a delta-group against a byte budget
+pub type SizeFn<S> = fn(&S) -> usize;
+
+/// Checks a delta against the receiver's state before it is joined, e.g.
+/// [`ORSet::check_join`](mdcs_core::orset::ORSet::check_join)
+pub type CheckFn<S> = fn(&S, &S) -> Result<(), DeltaRejected>;
+
+/// The window a receiver advertises to `sender`, given what it has queued
+fn advertised_window<S>(
+    queue: &VecDeque<AntiEntropyMessage<S>>,
+    config: &ReceiverConfig,
+    size: Option<SizeFn<S>>,
+    sender: &str,
+) -> Window {
+    let (mut deltas, mut bytes) = (0, 0);
+    for msg in queue {
+        if let AntiEntropyMessage::Delta { from, delta, .. } = msg {
+            if from == sender {
+                deltas += 1;
+                bytes += size.map_or(0, |size| size(delta));
+            }
+        }
+    }
+    config.advertise(deltas, bytes)
+}
+
+/// What a receiver owes one sender under cumulative acks
+#[derive(Debug, Default)]
+struct PendingAck {
+    /// Everything from the sender through this seq has been received
+    through: SeqNo,
+    /// Lowest seq received since the last ack, if any: where the next
+    /// ack starts
+    from: Option<SeqNo>,
+    /// Sync rounds an ack has been due for, if one is
+    due_for: Option<usize>,
+}
+
+/// A delta waiting for a peer's ack
+#[derive(Debug, Clone, Copy)]
+struct RetransmitTimer {
+    /// Tick at which the delta is resent
+    deadline: u64,
+    /// Ticks until the next deadline after that
+    interval: u64,
+}
+
+/// Retransmission state of one replica's deltas to one peer
+#[derive(Debug, Default)]
+struct Link {
+    /// Every delta through this seq has been sent at least once
+    sent_through: SeqNo,
+    /// Unacked seq -> when to resend it
+    timers: BTreeMap<SeqNo, RetransmitTimer>,
+    /// Acked ranges, first -> last seq, waiting on an earlier delta
+    early_acks: BTreeMap<SeqNo, SeqNo>,
+    /// Deltas resent
+    retransmissions: usize,
 }
 
 /// Anti-entropy coordinator for a cluster of replicas
@@ -184,18 +781,48 @@ impl<D: Clone> NetworkSimulator<D> {
 pub struct AntiEntropyCluster<S: Lattice + Clone> {
     /// All replicas in the cluster
     replicas: Vec<DeltaReplica<S, S>>,
+    /// Replica id -> position in `replicas`, used for message routing
+    index: HashMap<ReplicaId, usize>,
+    /// Ids of replicas that left, which can't join again
+    departed: HashSet<ReplicaId>,
     /// Network simulator
     network: NetworkSimulator<S>,
+    /// Replica pairs that cannot reach each other, stored as (low, high)
+    partitions: HashSet<(usize, usize)>,
+    /// How sync rounds pack deltas
+    mode: SyncMode,
+    /// Maximum size of a delta-group, and how to measure one
+    group_budget: Option<(usize, SizeFn<S>)>,
+    /// (receiver, sender) -> acks to coalesce under cumulative acks
+    pending_acks: BTreeMap<(usize, ReplicaId), PendingAck>,
+    /// Per replica: windows it advertises
+    receivers: Vec<ReceiverConfig>,
+    /// Per replica: messages it processes per sync round, if limited
+    processing_limits: Vec<Option<usize>>,
+    /// Per replica: messages delivered but not yet processed
+    inbound: Vec<VecDeque<AntiEntropyMessage<S>>>,
+    /// Per replica: most deltas it has had queued from one sender
+    peak_queued: Vec<usize>,
+    /// How byte windows measure a delta
+    delta_size: Option<SizeFn<S>>,
+    /// Check run on each delta before it is joined
+    delta_check: Option<CheckFn<S>>,
+    /// (sender, receiver) -> retransmission state, under a retransmit policy
+    links: BTreeMap<(usize, usize), Link>,
+    /// Current tick, as last passed to [`tick`](Self::tick)
+    now: u64,
 }
 
 impl<S: Lattice + Clone> AntiEntropyCluster<S> {
     /// Create a new cluster with n replicas
     pub fn new(n: usize, config: NetworkConfig) -> Self {
         let mut replicas = Vec::with_capacity(n);
+        let mut index = HashMap::with_capacity(n);
 
         // Create replicas
         for i in 0..n {
             let mut replica = DeltaReplica::new(format!("replica_{}", i));
+            index.insert(replica.id.clone(), i);
             // Register all other peers
             for j in 0..n {
                 if i != j {
@@ -207,8 +834,199 @@ impl<S: Lattice + Clone> AntiEntropyCluster<S> {
 
         Self {
             replicas,
+            index,
+            departed: HashSet::new(),
             network: NetworkSimulator::new(config),
+            partitions: HashSet::new(),
+            mode: SyncMode::default(),
+            group_budget: None,
+            pending_acks: BTreeMap::new(),
+            receivers: vec![ReceiverConfig::default(); n],
+            processing_limits: vec![None; n],
+            inbound: (0..n).map(|_| VecDeque::new()).collect(),
+            peak_queued: vec![0; n],
+            delta_size: None,
+            delta_check: None,
+            links: BTreeMap::new(),
+            now: 0,
+        }
+    }
+
+    /// Add an empty replica named `id` to the running cluster
+    ///
+    /// Returns its index. See [`add_replica_from`](Self::add_replica_from).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` is already a member or has left the cluster.
+    pub fn add_replica(&mut self, id: impl Into<ReplicaId>) -> usize {
+        self.join(DeltaReplica::new(id))
+    }
+
+    /// Add a replica named `id` starting from a copy of replica
+    /// `source_idx`'s state, as if restored from its snapshot
+    ///
+    /// The newcomer and the existing replicas register each other, and it
+    /// sends each of them its [`Digest`]. Once the network is drained it
+    /// has been sent just the deltas it was missing, or a peer's full state
+    /// where those were already collected. Returns its index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` is already a member or has left the cluster.
+    pub fn add_replica_from(&mut self, id: impl Into<ReplicaId>, source_idx: usize) -> usize {
+        let source = &self.replicas[source_idx];
+        let replica = DeltaReplica::new(id).with_snapshot(source.state().clone(), source.digest());
+        self.join(replica)
+    }
+
+    /// Register `replica` with every member and send them its digest
+    fn join(&mut self, mut replica: DeltaReplica<S, S>) -> usize {
+        assert!(
+            !self.index.contains_key(&replica.id),
+            "replica {} is already a member",
+            replica.id
+        );
+        assert!(
+            !self.departed.contains(&replica.id),
+            "replica {} has left the cluster and must join under a fresh id",
+            replica.id
+        );
+        let idx = self.replicas.len();
+        let digest = replica.digest();
+        for member in &mut self.replicas {
+            replica.register_peer(member.id.clone());
+            member.register_peer(replica.id.clone());
+        }
+        for member in &self.replicas {
+            self.network.send(AntiEntropyMessage::Digest {
+                from: replica.id.clone(),
+                to: member.id.clone(),
+                digest: digest.clone(),
+            });
+        }
+        self.index.insert(replica.id.clone(), idx);
+        self.replicas.push(replica);
+        self.receivers.push(ReceiverConfig::default());
+        self.processing_limits.push(None);
+        self.inbound.push(VecDeque::new());
+        self.peak_queued.push(0);
+        idx
+    }
+
+    /// Take the replica named `id` out of the running cluster, returning it
+    ///
+    /// The replica leaves gracefully: each other member is first handed
+    /// the deltas it hasn't acked, so writes that only reached some
+    /// members aren't lost to the rest. Then every member forgets it (see
+    /// [`DeltaReplica::remove_peer`]), and the messages to and from it are
+    /// dropped, whether in flight, lost or queued, so nothing is sent to it
+    /// afterwards. Replicas after it move down one index.
+    ///
+    /// Its id can't join again, so nothing a peer still holds of the old
+    /// replica, such as an ack or a buffered delta, can be taken for the
+    /// new one's: a replica coming back joins under a fresh id. What the
+    /// old replica sent before leaving stays in the other members' states.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` is not a member.
+    pub fn remove_replica(&mut self, id: &str) -> DeltaReplica<S, S> {
+        let idx = self
+            .index_of(id)
+            .unwrap_or_else(|| panic!("replica {} is not a member", id));
+        let replica = self.replicas.remove(idx);
+        self.departed.insert(replica.id.clone());
+        let id = replica.id.as_str();
+        for member in &mut self.replicas {
+            if let Some((delta, first_seq, seq)) = replica.delta_group_for_peer(&member.id) {
+                member.receive_delta_range(id, &delta, first_seq, seq);
+            }
+            member.remove_peer(id);
+        }
+        self.network.forget(id);
+
+        self.receivers.remove(idx);
+        self.processing_limits.remove(idx);
+        self.inbound.remove(idx);
+        self.peak_queued.remove(idx);
+        for queue in &mut self.inbound {
+            queue.retain(|msg| msg.endpoints().0 != id);
         }
+
+        // Drop whatever involves `idx` and renumber the rest
+        let shift = |i: usize| if i > idx { i - 1 } else { i };
+        self.pending_acks = std::mem::take(&mut self.pending_acks)
+            .into_iter()
+            .filter(|((receiver, sender), _)| *receiver != idx && sender != id)
+            .map(|((receiver, sender), pending)| ((shift(receiver), sender), pending))
+            .collect();
+        self.links = std::mem::take(&mut self.links)
+            .into_iter()
+            .filter(|((from, to), _)| *from != idx && *to != idx)
+            .map(|((from, to), link)| ((shift(from), shift(to)), link))
+            .collect();
+        self.partitions = std::mem::take(&mut self.partitions)
+            .into_iter()
+            .filter(|&(a, b)| a != idx && b != idx)
+            .map(|(a, b)| (shift(a), shift(b)))
+            .collect();
+        self.index = self
+            .replicas
+            .iter()
+            .enumerate()
+            .map(|(i, member)| (member.id.clone(), i))
+            .collect();
+        replica
+    }
+
+    /// Set how sync rounds pack deltas into messages
+    pub fn with_sync_mode(mut self, mode: SyncMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Cap delta-groups at `max_bytes` as measured by `size`
+    ///
+    /// Unacked deltas that do not fit one group are sent as several, each
+    /// acked on its own.
+    pub fn with_group_budget(mut self, max_bytes: usize, size: SizeFn<S>) -> Self {
+        self.group_budget = Some((max_bytes, size));
+        self
+    }
+
+    /// Advertise windows from `config` in the acks replica `idx` sends
+    pub fn with_receiver_config(mut self, idx: usize, config: ReceiverConfig) -> Self {
+        self.receivers[idx] = config;
+        self
+    }
+
+    /// Let replica `idx` process at most `per_round` messages each sync
+    /// round; until then, messages to it wait in its inbound queue
+    pub fn with_processing_limit(mut self, idx: usize, per_round: usize) -> Self {
+        self.processing_limits[idx] = Some(per_round);
+        self
+    }
+
+    /// Measure deltas with `size` for byte windows
+    pub fn with_delta_size(mut self, size: SizeFn<S>) -> Self {
+        self.delta_size = Some(size);
+        self
+    }
+
+    /// Run every delta through `check` before it is joined
+    ///
+    /// A rejected delta is not joined or acked. The receiver records its
+    /// sender (see [`DeltaReplica::rejected_peers`]) and the sender's full
+    /// state is sent in its place, which is joined unchecked.
+    pub fn with_delta_check(mut self, check: CheckFn<S>) -> Self {
+        self.delta_check = Some(check);
+        self
+    }
+
+    /// Look up a replica's position by its id
+    pub fn index_of(&self, id: &str) -> Option<usize> {
+        self.index.get(id).copied()
     }
 
     /// Get replica by index
@@ -230,63 +1048,501 @@ impl<S: Lattice + Clone> AntiEntropyCluster<S> {
     }
 
     /// Initiate sync from one replica to another
+    ///
+    /// Stops once `to_idx`'s window is full. Under a [`RetransmitPolicy`]
+    /// only deltas not sent to `to_idx` before go out; the rest wait for
+    /// their timers.
     pub fn initiate_sync(&mut self, from_idx: usize, to_idx: usize) {
         let to_id = self.replicas[to_idx].id.clone();
-        if let Some((delta, seq)) = self.replicas[from_idx].prepare_sync(&to_id) {
+        let replica = &self.replicas[from_idx];
+        if replica.incompatibility(&to_id).is_some() || !replica.flow().is_open(&to_id) {
+            return;
+        }
+        let policy = self.network.config.retransmit;
+        let sent_through = self
+            .links
+            .get(&(from_idx, to_idx))
+            .map_or(0, |link| link.sent_through);
+        let payloads = if policy.is_some() && sent_through > 0 {
+            self.unsent_payloads(from_idx, sent_through)
+        } else {
+            self.payloads(from_idx, &to_id)
+        };
+        let from_id = replica.id.clone();
+        for (delta, first_seq, seq) in payloads {
+            let flow = self.replicas[from_idx].flow_mut();
+            if !flow.is_open(&to_id) {
+                break;
+            }
+            flow.record_send(&to_id, self.delta_size.map_or(0, |size| size(&delta)));
+            if let Some(policy) = policy {
+                self.arm_timers(from_idx, to_idx, first_seq, seq, policy);
+            }
             let msg = AntiEntropyMessage::Delta {
-                from: self.replicas[from_idx].id.clone(),
+                from: from_id.clone(),
                 to: to_id.clone(),
                 delta,
+                first_seq,
                 seq,
             };
             self.network.send(msg);
         }
     }
 
-    /// Process one network message
-    pub fn process_one(&mut self) -> bool {
-        if let Some(msg) = self.network.receive() {
-            match msg {
-                AntiEntropyMessage::Delta {
-                    from,
-                    to,
+    /// Everything replica `from_idx` has that `to_id` hasn't acked, packed
+    /// per the sync mode
+    fn payloads(&self, from_idx: usize, to_id: &str) -> Vec<(S, SeqNo, SeqNo)> {
+        let replica = &self.replicas[from_idx];
+        match (self.mode, self.group_budget) {
+            (SyncMode::PerDelta, _) => replica.deltas_for_peer(to_id),
+            (SyncMode::DeltaGroups, Some((max_bytes, size))) => {
+                replica.delta_groups_for_peer(to_id, max_bytes, size)
+            }
+            (SyncMode::DeltaGroups, None) => {
+                replica.delta_group_for_peer(to_id).into_iter().collect()
+            }
+        }
+    }
+
+    /// The deltas of replica `from_idx` after `sent_through`, packed per
+    /// the sync mode
+    fn unsent_payloads(&self, from_idx: usize, sent_through: SeqNo) -> Vec<(S, SeqNo, SeqNo)> {
+        let buffer = self.replicas[from_idx].buffer();
+        match (self.mode, self.group_budget) {
+            (SyncMode::PerDelta, _) => buffer
+                .deltas_since(sent_through)
+                .into_iter()
+                .map(|td| (S::clone(&td.delta), td.seq, td.seq))
+                .collect(),
+            (SyncMode::DeltaGroups, Some((max_bytes, size))) => {
+                buffer.delta_groups_since(sent_through, max_bytes, size)
+            }
+            (SyncMode::DeltaGroups, None) => {
+                buffer.delta_group_range(sent_through).into_iter().collect()
+            }
+        }
+    }
+
+    /// Start a timer for each of `first_seq..=seq` sent from `from_idx` to
+    /// `to_idx` that doesn't have one
+    fn arm_timers(
+        &mut self,
+        from_idx: usize,
+        to_idx: usize,
+        first_seq: SeqNo,
+        seq: SeqNo,
+        policy: RetransmitPolicy,
+    ) {
+        let timer = RetransmitTimer {
+            deadline: self.now + policy.base_interval,
+            interval: policy.base_interval,
+        };
+        let link = self.links.entry((from_idx, to_idx)).or_default();
+        for s in first_seq..=seq {
+            link.timers.entry(s).or_insert(timer);
+        }
+        link.sent_through = link.sent_through.max(seq);
+    }
+
+    /// Advance the clock to `now` and resend every delta whose timer has
+    /// expired
+    ///
+    /// Each delta goes on its own, only to the peer that hasn't acked it,
+    /// and its next deadline backs off per the [`RetransmitPolicy`]. The
+    /// resent messages are left in flight. Does nothing without a policy.
+    pub fn tick(&mut self, now: u64) {
+        self.now = self.now.max(now);
+        let Some(policy) = self.network.config.retransmit else {
+            return;
+        };
+        let mut resend = Vec::new();
+        for (&(from_idx, to_idx), link) in &mut self.links {
+            for (&seq, timer) in &mut link.timers {
+                if timer.deadline > self.now {
+                    continue;
+                }
+                timer.interval = policy.backoff(timer.interval);
+                timer.deadline = self.now + timer.interval;
+                link.retransmissions += 1;
+                resend.push((from_idx, to_idx, seq));
+            }
+        }
+
+        for (from_idx, to_idx, seq) in resend {
+            let replica = &self.replicas[from_idx];
+            let to_id = &self.replicas[to_idx].id;
+            if replica.incompatibility(to_id).is_some() {
+                continue;
+            }
+            let payload = replica
+                .buffer()
+                .deltas_since(seq - 1)
+                .first()
+                .filter(|td| td.seq == seq)
+                .map(|td| (S::clone(&td.delta), seq, seq))
+                // No longer buffered: the peer needs the full state
+                .or_else(|| replica.delta_group_for_peer(to_id));
+            if let Some((delta, first_seq, seq)) = payload {
+                self.network.send(AntiEntropyMessage::Delta {
+                    from: replica.id.clone(),
+                    to: to_id.clone(),
                     delta,
+                    first_seq,
                     seq,
-                } => {
-                    // Deliver delta to the intended recipient only
-                    for replica in &mut self.replicas {
-                        if replica.id == to {
-                            replica.receive_delta(&delta);
-                            // Send ack back to the original sender
-                            let ack = AntiEntropyMessage::Ack {
-                                from: replica.id.clone(),
-                                to: from.clone(),
-                                seq,
-                            };
-                            self.network.send(ack);
-                            break;
+                });
+            }
+        }
+    }
+
+    /// Number of deltas resent to replica `idx` by [`tick`](Self::tick)
+    pub fn retransmission_count(&self, idx: usize) -> usize {
+        self.links
+            .iter()
+            .filter(|((_, to_idx), _)| *to_idx == idx)
+            .map(|(_, link)| link.retransmissions)
+            .sum()
+    }
+
+    /// Record an ack from `peer_idx` at replica `idx` under a retransmit
+    /// policy
+    ///
+    /// Stops the acked deltas' timers. A range acked ahead of a missing
+    /// delta is kept and counted once the gap is acked.
+    fn on_timed_ack(&mut self, idx: usize, peer_idx: usize, first_seq: SeqNo, seq: SeqNo) {
+        let peer_id = self.replicas[peer_idx].id.clone();
+        let replica = &mut self.replicas[idx];
+        let link = self.links.entry((idx, peer_idx)).or_default();
+        if !replica.ack_barrier(&peer_id, first_seq.saturating_sub(1)) {
+            let last = link.early_acks.entry(first_seq).or_insert(seq);
+            *last = (*last).max(seq);
+        }
+        while let Some((&first, &last)) = link
+            .early_acks
+            .iter()
+            .find(|(&first, _)| replica.ack_barrier(&peer_id, first.saturating_sub(1)))
+        {
+            link.early_acks.remove(&first);
+            replica.process_ack(&peer_id, last);
+        }
+        link.timers
+            .retain(|&s, _| !(first_seq..=seq).contains(&s) && !replica.ack_barrier(&peer_id, s));
+    }
+
+    /// Process one network message
+    pub fn process_one(&mut self) -> bool {
+        self.deliver(0)
+    }
+
+    /// Process the message at `index` in the in-flight queue
+    ///
+    /// A message between partitioned replicas is lost instead, and one to a
+    /// replica with a processing limit joins its inbound queue. Returns
+    /// `false` if there is no such message.
+    pub fn deliver(&mut self, index: usize) -> bool {
+        let Some(msg) = self.network.peek(index) else {
+            return false;
+        };
+        let (from, to) = msg.endpoints();
+        if self.is_partitioned(from, to) {
+            return self.network.drop_at(index);
+        }
+        let queue_at = self
+            .index_of(to)
+            .filter(|idx| self.processing_limits[*idx].is_some());
+
+        let Some(msg) = self.network.take(index) else {
+            return false;
+        };
+        match queue_at {
+            Some(idx) => self.enqueue(idx, msg),
+            None => self.handle(msg),
+        }
+        true
+    }
+
+    /// Queue a message until replica `idx` gets to it
+    fn enqueue(&mut self, idx: usize, msg: AntiEntropyMessage<S>) {
+        let sender = msg.endpoints().0.to_string();
+        self.inbound[idx].push_back(msg);
+        let queued = self.queued_from(idx, &sender);
+        self.peak_queued[idx] = self.peak_queued[idx].max(queued);
+    }
+
+    /// Apply a message at its recipient
+    fn handle(&mut self, msg: AntiEntropyMessage<S>) {
+        match msg {
+            AntiEntropyMessage::Delta {
+                from,
+                to,
+                delta,
+                first_seq,
+                seq,
+            } => {
+                // Deliver delta to the intended recipient only
+                if let Some(idx) = self.index_of(&to) {
+                    let replica = &mut self.replicas[idx];
+                    match self.delta_check {
+                        Some(check) => {
+                            if replica
+                                .try_receive_delta_range(&from, &delta, first_seq, seq, check)
+                                .is_err()
+                            {
+                                self.send_full_state(&from, idx);
+                                return;
+                            }
                         }
+                        None => replica.receive_delta_range(&from, &delta, first_seq, seq),
                     }
+                    self.ack_received(idx, from, first_seq, seq);
                 }
-                AntiEntropyMessage::Ack { from, to, seq } => {
-                    // Deliver ack to the intended recipient only
-                    for replica in &mut self.replicas {
-                        if replica.id == to {
-                            replica.process_ack(&from, seq);
-                            break;
+            }
+            AntiEntropyMessage::State {
+                from,
+                to,
+                state,
+                seq,
+            } => {
+                if let Some(idx) = self.index_of(&to) {
+                    self.replicas[idx].receive_state_from(&from, &state, seq);
+                    self.ack_received(idx, from, 1, seq);
+                }
+            }
+            AntiEntropyMessage::Ack {
+                from,
+                to,
+                first_seq,
+                seq,
+                window,
+            } => {
+                // Deliver ack to the intended recipient only
+                if let Some(idx) = self.index_of(&to) {
+                    let replica = &mut self.replicas[idx];
+                    replica.process_ack_range(&from, first_seq, seq);
+                    replica.flow_mut().on_ack(&from, window);
+                    if let Some(peer_idx) = self.index_of(&from) {
+                        if self.network.config.retransmit.is_some() {
+                            self.on_timed_ack(idx, peer_idx, first_seq, seq);
                         }
                     }
                 }
             }
-            true
-        } else {
-            false
+            AntiEntropyMessage::Unsupported {
+                from,
+                to,
+                type_id,
+                version,
+            } => {
+                if let Some(idx) = self.index_of(&to) {
+                    self.replicas[idx].mark_incompatible(&from, type_id, version);
+                }
+            }
+            AntiEntropyMessage::Digest { from, to, digest } => {
+                // Answer with exactly what the digest is missing
+                if let (Some(idx), Some(peer_idx)) = (self.index_of(&to), self.index_of(&from)) {
+                    self.replicas[idx].receive_digest(&from, &digest);
+                    self.initiate_sync(idx, peer_idx);
+                }
+            }
+        }
+    }
+
+    /// Ack `first_seq..=seq` from `from` at replica `idx`, now or, under
+    /// cumulative acks, once the batch is done
+    fn ack_received(&mut self, idx: usize, from: ReplicaId, first_seq: SeqNo, seq: SeqNo) {
+        if let AckStrategy::Cumulative { .. } = self.network.config.ack_strategy {
+            // Owe the sender an ack, sent when the batch is done
+            let pending = self.pending_acks.entry((idx, from)).or_default();
+            if first_seq <= pending.through + 1 {
+                pending.through = pending.through.max(seq);
+                pending.from = Some(pending.from.map_or(first_seq, |from| from.min(first_seq)));
+            }
+            pending.due_for.get_or_insert(0);
+            return;
+        }
+        // Send ack back to the original sender
+        let window = advertised_window(
+            &self.inbound[idx],
+            &self.receivers[idx],
+            self.delta_size,
+            &from,
+        );
+        let ack = AntiEntropyMessage::Ack {
+            from: self.replicas[idx].id.clone(),
+            to: from,
+            first_seq,
+            seq,
+            window: Some(window),
+        };
+        self.network.send(ack);
+    }
+
+    /// Send replica `sender`'s full state to replica `to_idx`, which
+    /// rejected one of its deltas
+    fn send_full_state(&mut self, sender: &str, to_idx: usize) {
+        let Some(sender_idx) = self.index_of(sender) else {
+            return;
+        };
+        let replica = &self.replicas[sender_idx];
+        self.network.send(AntiEntropyMessage::State {
+            from: replica.id.clone(),
+            to: self.replicas[to_idx].id.clone(),
+            state: replica.state().clone(),
+            seq: replica.buffer().current_seq(),
+        });
+    }
+
+    /// Let each replica with a processing limit work through its inbound
+    /// queue, up to the limit
+    fn process_inbound(&mut self) {
+        for idx in 0..self.replicas.len() {
+            let Some(per_round) = self.processing_limits[idx] else {
+                continue;
+            };
+            for _ in 0..per_round {
+                let Some(msg) = self.inbound[idx].pop_front() else {
+                    break;
+                };
+                self.handle(msg);
+            }
+        }
+    }
+
+    /// Number of messages waiting in replica `idx`'s inbound queue
+    pub fn inbound_len(&self, idx: usize) -> usize {
+        self.inbound[idx].len()
+    }
+
+    /// Number of deltas from `sender` waiting in replica `idx`'s inbound
+    /// queue
+    pub fn queued_from(&self, idx: usize, sender: &str) -> usize {
+        self.inbound[idx]
+            .iter()
+            .filter(|msg| matches!(msg, AntiEntropyMessage::Delta { from, .. } if from == sender))
+            .count()
+    }
+
+    /// Most deltas replica `idx` has had queued from any one sender
+    pub fn peak_queued(&self, idx: usize) -> usize {
+        self.peak_queued[idx]
+    }
+
+    /// Lose the message at `index` in the in-flight queue
+    pub fn drop_message(&mut self, index: usize) -> bool {
+        self.network.drop_at(index)
+    }
+
+    /// Duplicate the message at `index` in the in-flight queue
+    pub fn duplicate_message(&mut self, index: usize) -> bool {
+        self.network.duplicate_at(index)
+    }
+
+    /// Number of messages in flight
+    pub fn in_flight_count(&self) -> usize {
+        self.network.in_flight_count()
+    }
+
+    /// Number of messages sent, lost ones included
+    pub fn sent_count(&self) -> usize {
+        self.network.sent_count()
+    }
+
+    /// Number of acks sent, lost ones included
+    pub fn ack_count(&self) -> usize {
+        self.network.ack_count()
+    }
+
+    /// Send the cumulative acks that have waited long enough
+    ///
+    /// Returns whether any was sent.
+    fn send_due_acks(&mut self) -> bool {
+        let AckStrategy::Cumulative { max_delay_rounds } = self.network.config.ack_strategy else {
+            return false;
+        };
+        let mut sent = false;
+        for ((idx, sender), pending) in &mut self.pending_acks {
+            if pending
+                .due_for
+                .is_none_or(|rounds| rounds < max_delay_rounds)
+            {
+                continue;
+            }
+            pending.due_for = None;
+            // Only what arrived since the last ack, so a sender that missed
+            // that ack still sees the gap
+            if let Some(first_seq) = pending.from.take() {
+                let window = advertised_window(
+                    &self.inbound[*idx],
+                    &self.receivers[*idx],
+                    self.delta_size,
+                    sender,
+                );
+                self.network.send(AntiEntropyMessage::Ack {
+                    from: self.replicas[*idx].id.clone(),
+                    to: sender.clone(),
+                    first_seq,
+                    seq: pending.through,
+                    window: Some(window),
+                });
+                sent = true;
+            }
+        }
+        sent
+    }
+
+    /// Cut the link between two replicas; messages across it are lost
+    pub fn partition(&mut self, a: usize, b: usize) {
+        if a != b {
+            self.partitions.insert((a.min(b), a.max(b)));
+        }
+    }
+
+    /// Restore all links, and heal the network's partition
+    pub fn heal(&mut self) {
+        self.partitions.clear();
+        self.network.heal();
+    }
+
+    /// Get the network simulator, e.g. to
+    /// [`partition`](NetworkSimulator::partition) it into groups
+    pub fn network_mut(&mut self) -> &mut NetworkSimulator<S> {
+        &mut self.network
+    }
+
+    fn is_partitioned(&self, from: &str, to: &str) -> bool {
+        if self.network.partition.cuts(from, to) {
+            return true;
+        }
+        match (self.index_of(from), self.index_of(to)) {
+            (Some(a), Some(b)) => self.partitions.contains(&(a.min(b), a.max(b))),
+            _ => false,
+        }
+    }
+
+    /// Simulate a crash and recovery for a replica
+    ///
+    /// See [`DeltaReplica::crash_and_recover`].
+    pub fn crash_and_recover(&mut self, idx: usize) {
+        self.replicas[idx].crash_and_recover();
+        // Its timers go with the buffer
+        self.links.retain(|(from_idx, _), _| *from_idx != idx);
+        // Unprocessed messages and unsent acks are lost; what was
+        // received is durable
+        self.inbound[idx].clear();
+        for ((receiver, _), pending) in &mut self.pending_acks {
+            if *receiver == idx {
+                pending.due_for = None;
+            }
         }
     }
 
-    /// Run until network is empty
+    /// Run until network is empty, sending the cumulative acks that are due
     pub fn drain_network(&mut self) {
-        while self.process_one() {}
+        loop {
+            while self.process_one() {}
+            if !self.send_due_acks() {
+                break;
+            }
+        }
     }
 
     /// Broadcast delta from one replica to all others
@@ -300,6 +1556,9 @@ impl<S: Lattice + Clone> AntiEntropyCluster<S> {
     }
 
     /// Full sync: every replica syncs with every other replica
+    ///
+    /// Replicas with a processing limit then handle that many queued
+    /// messages.
     pub fn full_sync_round(&mut self) {
         let n = self.replicas.len();
         for from_idx in 0..n {
@@ -310,6 +1569,13 @@ impl<S: Lattice + Clone> AntiEntropyCluster<S> {
             }
         }
         self.drain_network();
+        self.process_inbound();
+        self.drain_network();
+        for pending in self.pending_acks.values_mut() {
+            if let Some(rounds) = &mut pending.due_for {
+                *rounds += 1;
+            }
+        }
     }
 
     /// Check if all replicas have converged
@@ -352,6 +1618,7 @@ mod tests {
             from: "r1".to_string(),
             to: "".to_string(),
             delta: 42,
+            first_seq: 1,
             seq: 1,
         });
 
@@ -490,7 +1757,7 @@ mod tests {
         for i in 0..4 {
             for j in 0..4 {
                 for k in 0..5 {
-                    let val = (j * 10 + k) as i32;
+                    let val = j * 10 + k;
                     assert!(
                         cluster.replica(i).state().contains(&val),
                         "Replica {} missing value {}",
@@ -532,4 +1799,412 @@ mod tests {
         // But different from initial
         assert_ne!(initial_state, after_one);
     }
+
+    fn insert_many(cluster: &mut AntiEntropyCluster<GSet<i32>>, replica: usize, count: i32) {
+        for i in 0..count {
+            let val = replica as i32 * 1000 + i;
+            cluster.mutate(replica, move |_| {
+                let mut d = GSet::new();
+                d.insert(val);
+                d
+            });
+        }
+    }
+
+    #[test]
+    fn test_sync_modes_ack_and_gc_alike() {
+        let mut grouped: AntiEntropyCluster<GSet<i32>> =
+            AntiEntropyCluster::new(3, NetworkConfig::default());
+        let mut per_delta: AntiEntropyCluster<GSet<i32>> =
+            AntiEntropyCluster::new(3, NetworkConfig::default()).with_sync_mode(SyncMode::PerDelta);
+
+        for cluster in [&mut grouped, &mut per_delta] {
+            insert_many(cluster, 0, 60);
+            insert_many(cluster, 1, 10);
+        }
+
+        // One message per peer against one per delta
+        grouped.broadcast(0);
+        per_delta.broadcast(0);
+        assert_eq!(grouped.in_flight_count(), 2);
+        assert_eq!(per_delta.in_flight_count(), 120);
+
+        // Deltas, then acks
+        for cluster in [&mut grouped, &mut per_delta] {
+            cluster.drain_network();
+            assert!(cluster.replica(0).ack_barrier("replica_1", 60));
+            assert!(cluster.replica(0).ack_barrier("replica_2", 60));
+            assert!(cluster.replica(0).buffer().is_empty());
+            assert_eq!(cluster.replica(1).buffer().len(), 10);
+        }
+
+        for cluster in [&mut grouped, &mut per_delta] {
+            cluster.full_sync_round();
+            assert!(cluster.is_converged());
+            for i in 0..3 {
+                assert!(cluster.replica(i).buffer().is_empty());
+            }
+        }
+        assert_eq!(grouped.replica(2).state(), per_delta.replica(2).state());
+    }
+
+    #[test]
+    fn test_per_delta_mode_converges_under_loss() {
+        let mut cluster: AntiEntropyCluster<GSet<i32>> =
+            AntiEntropyCluster::new(3, NetworkConfig::chaotic()).with_sync_mode(SyncMode::PerDelta);
+        for i in 0..3 {
+            insert_many(&mut cluster, i, 20);
+        }
+
+        for _ in 0..20 {
+            cluster.full_sync_round();
+            cluster.retransmit_and_process();
+        }
+        assert!(cluster.is_converged());
+        assert_eq!(cluster.replica(0).state().len(), 60);
+    }
+
+    #[test]
+    fn test_group_budget_splits_messages() {
+        let mut cluster: AntiEntropyCluster<GSet<i32>> =
+            AntiEntropyCluster::new(2, NetworkConfig::default()).with_group_budget(25, GSet::len);
+        insert_many(&mut cluster, 0, 100);
+
+        cluster.initiate_sync(0, 1);
+        assert_eq!(cluster.in_flight_count(), 4);
+
+        cluster.drain_network();
+        assert!(cluster.is_converged());
+        assert!(cluster.replica(0).buffer().is_empty());
+    }
+
+    fn cumulative(max_delay_rounds: usize) -> NetworkConfig {
+        NetworkConfig::default().with_ack_strategy(AckStrategy::Cumulative { max_delay_rounds })
+    }
+
+    #[test]
+    fn test_cumulative_acks_cut_message_count() {
+        let mut per_delta: AntiEntropyCluster<GSet<i32>> =
+            AntiEntropyCluster::new(3, NetworkConfig::default()).with_sync_mode(SyncMode::PerDelta);
+        let mut cumulative: AntiEntropyCluster<GSet<i32>> =
+            AntiEntropyCluster::new(3, cumulative(0)).with_sync_mode(SyncMode::PerDelta);
+
+        for cluster in [&mut per_delta, &mut cumulative] {
+            for round in 0..3 {
+                insert_many(cluster, round, 10);
+                cluster.full_sync_round();
+            }
+            assert!(cluster.is_converged());
+            for i in 0..3 {
+                assert!(cluster.replica(i).buffer().is_empty());
+            }
+        }
+
+        // 10 deltas to each of 2 peers per round, acked one by one or once
+        assert_eq!(per_delta.ack_count(), 60);
+        assert_eq!(per_delta.sent_count(), 120);
+        assert_eq!(cumulative.ack_count(), 6);
+        assert_eq!(cumulative.sent_count(), 66);
+        assert_eq!(per_delta.replica(2).state(), cumulative.replica(2).state());
+    }
+
+    #[test]
+    fn test_delayed_cumulative_acks_converge_under_chaos() {
+        let config = NetworkConfig::chaotic().with_ack_strategy(AckStrategy::Cumulative {
+            max_delay_rounds: 1,
+        });
+        let mut cluster: AntiEntropyCluster<GSet<i32>> = AntiEntropyCluster::new(3, config);
+
+        for round in 0..20 {
+            cluster.mutate(round % 3, move |_| {
+                let mut d = GSet::new();
+                d.insert(round as i32);
+                d
+            });
+            cluster.full_sync_round();
+            cluster.retransmit_and_process();
+        }
+        for _ in 0..5 {
+            cluster.full_sync_round();
+            cluster.retransmit_and_process();
+        }
+        assert!(cluster.is_converged());
+        assert_eq!(cluster.replica(0).state().len(), 20);
+    }
+
+    #[test]
+    fn test_lost_cumulative_ack_is_recovered_by_the_next() {
+        let mut cluster: AntiEntropyCluster<GSet<i32>> = AntiEntropyCluster::new(2, cumulative(0));
+        insert_many(&mut cluster, 0, 5);
+
+        // The delta arrives, then the link drops and the ack is lost
+        cluster.initiate_sync(0, 1);
+        assert!(cluster.process_one());
+        cluster.partition(0, 1);
+        cluster.drain_network();
+        assert_eq!(cluster.ack_count(), 1);
+        assert!(!cluster.replica(0).ack_barrier("replica_1", 5));
+        assert_eq!(cluster.replica(0).buffer().len(), 5);
+
+        cluster.heal();
+        insert_many(&mut cluster, 0, 3);
+        cluster.full_sync_round();
+        assert!(cluster.replica(0).ack_barrier("replica_1", 8));
+        assert!(cluster.replica(0).buffer().is_empty());
+
+        // The old ack turning up late doesn't move the watermark back
+        cluster.retransmit_and_process();
+        assert!(cluster.replica(0).ack_barrier("replica_1", 8));
+
+        // Nor does a stale per-delta ack
+        let replica = cluster.replica_mut(0);
+        assert!(replica.process_ack_range("replica_1", 3, 3).is_none());
+        assert!(replica.ack_barrier("replica_1", 8));
+    }
+
+    #[test]
+    fn test_cumulative_ack_starts_at_first_unacked_seq() {
+        let mut cluster: AntiEntropyCluster<GSet<i32>> = AntiEntropyCluster::new(2, cumulative(0));
+        insert_many(&mut cluster, 0, 5);
+        cluster.full_sync_round();
+        assert!(cluster.replica(0).ack_barrier("replica_1", 5));
+
+        insert_many(&mut cluster, 0, 3);
+        cluster.initiate_sync(0, 1);
+        assert!(cluster.process_one());
+        assert!(cluster.send_due_acks());
+        match cluster.network.peek(0) {
+            Some(AntiEntropyMessage::Ack { first_seq, seq, .. }) => {
+                assert_eq!((*first_seq, *seq), (6, 8));
+            }
+            other => panic!("expected an ack, got {:?}", other),
+        }
+    }
+
+    fn settled(cluster: &AntiEntropyCluster<GSet<i32>>) -> bool {
+        cluster.is_converged() && (0..cluster.len()).all(|i| cluster.replica(i).buffer().is_empty())
+    }
+
+    #[test]
+    fn test_retransmit_timers_send_less_than_resending_everything() {
+        let lossy = NetworkConfig::lossy(0.3);
+        let mut baseline: AntiEntropyCluster<GSet<i32>> =
+            AntiEntropyCluster::new(4, lossy.clone()).with_sync_mode(SyncMode::PerDelta);
+        let mut timed: AntiEntropyCluster<GSet<i32>> =
+            AntiEntropyCluster::new(4, lossy.with_retransmit_policy(RetransmitPolicy::default()))
+                .with_sync_mode(SyncMode::PerDelta);
+        for cluster in [&mut baseline, &mut timed] {
+            for i in 0..4 {
+                insert_many(cluster, i, 25);
+            }
+        }
+
+        // Every round resends every unacked delta to every peer
+        let mut rounds = 0;
+        while !settled(&baseline) {
+            baseline.full_sync_round();
+            rounds += 1;
+            assert!(rounds < 100);
+        }
+
+        // Each delta goes out once, then again only when its timer expires
+        for i in 0..4 {
+            timed.broadcast(i);
+        }
+        timed.drain_network();
+        let mut now = 0;
+        while !settled(&timed) {
+            now += 1;
+            timed.tick(now);
+            timed.drain_network();
+            assert!(now < 1000);
+        }
+
+        assert_eq!(baseline.replica(0).state(), timed.replica(0).state());
+        assert!(
+            timed.sent_count() * 2 < baseline.sent_count(),
+            "timed {} vs baseline {}",
+            timed.sent_count(),
+            baseline.sent_count()
+        );
+        assert!((0..4).all(|i| timed.retransmission_count(i) > 0));
+    }
+
+    #[test]
+    fn test_acked_deltas_are_never_retransmitted() {
+        let config = NetworkConfig::lossy(0.3).with_retransmit_policy(RetransmitPolicy {
+            base_interval: 1,
+            multiplier: 1.5,
+            max_interval: 4,
+        });
+        let mut cluster: AntiEntropyCluster<GSet<i32>> =
+            AntiEntropyCluster::new(3, config).with_sync_mode(SyncMode::PerDelta);
+
+        for now in 1..60 {
+            if now < 20 {
+                insert_many(&mut cluster, now as usize % 3, 3);
+                cluster.full_sync_round();
+            }
+            cluster.tick(now);
+            for msg in &cluster.network.in_flight {
+                if let AntiEntropyMessage::Delta { from, to, seq, .. } = msg {
+                    let sender = cluster.index_of(from).unwrap();
+                    assert!(!cluster.replica(sender).ack_barrier(to, *seq));
+                }
+            }
+            cluster.drain_network();
+        }
+        assert!(settled(&cluster));
+
+        // With nothing lost, no timer ever fires
+        let config = NetworkConfig::default().with_retransmit_policy(RetransmitPolicy::default());
+        let mut cluster: AntiEntropyCluster<GSet<i32>> = AntiEntropyCluster::new(3, config);
+        insert_many(&mut cluster, 0, 10);
+        cluster.full_sync_round();
+        cluster.tick(1000);
+        assert_eq!(cluster.in_flight_count(), 0);
+        assert!((0..3).all(|i| cluster.retransmission_count(i) == 0));
+        assert!(settled(&cluster));
+    }
+
+    #[test]
+    fn test_joining_replica_is_sent_only_what_its_digest_misses() {
+        let mut cluster: AntiEntropyCluster<GSet<i32>> =
+            AntiEntropyCluster::new(3, NetworkConfig::default()).with_sync_mode(SyncMode::PerDelta);
+        let mutate = |cluster: &mut AntiEntropyCluster<GSet<i32>>, n: i32| {
+            cluster.mutate(n as usize % cluster.len(), move |_| {
+                let mut d = GSet::new();
+                d.insert(n);
+                d
+            });
+        };
+        // Messages other than acks sent so far
+        let sent =
+            |cluster: &AntiEntropyCluster<GSet<i32>>| cluster.sent_count() - cluster.ack_count();
+
+        for n in 0..50 {
+            mutate(&mut cluster, n);
+            if n % 10 == 9 {
+                cluster.full_sync_round();
+            }
+        }
+        // Replica 1 writes 5 deltas nobody has seen yet
+        for n in 0..5 {
+            cluster.mutate(1, move |_| {
+                let mut d = GSet::new();
+                d.insert(1000 + n);
+                d
+            });
+        }
+
+        // A replica restored from replica 0's snapshot only misses those
+        let before = sent(&cluster);
+        let joined = cluster.add_replica_from("replica_3", 0);
+        cluster.drain_network();
+        // 3 digests, then replica 1's 5 deltas
+        assert_eq!(sent(&cluster) - before, 3 + 5);
+        assert_eq!(cluster.replica(joined).state(), cluster.replica(1).state());
+
+        for n in 50..100 {
+            mutate(&mut cluster, n);
+            if n % 10 == 9 {
+                cluster.full_sync_round();
+            }
+        }
+        cluster.full_sync_round();
+        assert!(settled(&cluster));
+        assert_eq!(cluster.replica(joined).state().len(), 105);
+
+        // An empty replica needs each peer's full state: the deltas are gone
+        let before = sent(&cluster);
+        let empty = cluster.add_replica("replica_4");
+        cluster.drain_network();
+        assert_eq!(sent(&cluster) - before, 4 + 4);
+        assert_eq!(cluster.replica(empty).state().len(), 105);
+        cluster.full_sync_round();
+        assert!(settled(&cluster));
+    }
+
+    #[test]
+    fn test_same_seed_same_delivery_order() {
+        let run = |seed| {
+            let mut net: NetworkSimulator<i32> =
+                NetworkSimulator::new(NetworkConfig::chaotic().with_seed(seed));
+            for seq in 1..=50 {
+                net.send(AntiEntropyMessage::Delta {
+                    from: "r1".to_string(),
+                    to: "r2".to_string(),
+                    delta: seq as i32,
+                    first_seq: seq,
+                    seq,
+                });
+            }
+            let mut order = Vec::new();
+            while let Some(msg) = net.receive() {
+                if let AntiEntropyMessage::Delta { seq, .. } = msg {
+                    order.push(seq);
+                }
+            }
+            (order, net.lost_count())
+        };
+
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7), run(8));
+    }
+
+    #[test]
+    fn test_convergence_under_reordering() {
+        let mut cluster: AntiEntropyCluster<GSet<i32>> =
+            AntiEntropyCluster::new(3, NetworkConfig::reordering(0.5).with_seed(3));
+
+        for round in 0..5 {
+            for i in 0..3 {
+                let val = (round * 3 + i) as i32;
+                cluster.mutate(i, move |_| {
+                    let mut d = GSet::new();
+                    d.insert(val);
+                    d
+                });
+            }
+            cluster.full_sync_round();
+        }
+
+        assert!(cluster.is_converged());
+        assert_eq!(cluster.replica(0).state().len(), 15);
+    }
+
+    #[test]
+    fn test_partition_groups_converge_after_heal() {
+        let mut cluster: AntiEntropyCluster<GSet<i32>> =
+            AntiEntropyCluster::new(4, NetworkConfig::default());
+        let id = |i: usize| format!("replica_{}", i);
+        cluster
+            .network_mut()
+            .partition(vec![vec![id(0), id(1)], vec![id(2), id(3)]]);
+
+        for i in 0..4 {
+            let val = i as i32;
+            cluster.mutate(i, move |_| {
+                let mut d = GSet::new();
+                d.insert(val);
+                d
+            });
+        }
+        for _ in 0..3 {
+            cluster.full_sync_round();
+            cluster.retransmit_and_process();
+        }
+
+        // Each side converged on its own writes only
+        assert_eq!(cluster.replica(0).state(), cluster.replica(1).state());
+        assert_eq!(cluster.replica(2).state(), cluster.replica(3).state());
+        assert!(!cluster.replica(0).state().contains(&2));
+        assert!(cluster.network_mut().lost_count() > 0);
+
+        cluster.heal();
+        cluster.retransmit_and_process();
+        cluster.full_sync_round();
+        assert!(cluster.is_converged());
+        assert_eq!(cluster.replica(0).state().len(), 4);
+    }
 }
@@ -20,9 +20,13 @@
 //!   X = X ⊔ d          // apply (idempotent!)
 //!   ack to i
 
-use mdcs_core::lattice::Lattice;
+use crate::anti_entropy::CheckFn;
+use crate::envelope::Incompatibility;
+use crate::flow::FlowControl;
+use mdcs_core::lattice::{DeltaCRDT, DeltaRejected, Lattice};
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::sync::Arc;
 
 /// Sequence number for delta intervals
 pub type SeqNo = u64;
@@ -30,15 +34,36 @@ pub type SeqNo = u64;
 /// Replica identifier
 pub type ReplicaId = String;
 
+/// Version vector: per origin, the sequence number through which every
+/// delta has been received
+///
+/// Sent by a joining replica so peers only send what it is missing.
+pub type Digest = BTreeMap<ReplicaId, SeqNo>;
+
 /// A delta tagged with sequence information for causal ordering
+///
+/// The delta is shared: buffering the same delta in several places clones
+/// the `Arc`, not the payload.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct TaggedDelta<D> {
     pub seq: SeqNo,
-    pub delta: D,
+    pub delta: Arc<D>,
+}
+
+/// A peer's acknowledgment advanced past what we had recorded
+///
+/// Returned by the replicas' ack handlers so callers waiting on an
+/// [`ack_barrier`](DeltaReplica::ack_barrier) know when to re-check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AckEvent {
+    pub peer_id: ReplicaId,
+    pub acked_seq: SeqNo,
 }
 
 /// Buffer for outgoing deltas with grouping support
-#[derive(Debug, Clone)]
+///
+/// Serializable, so a replica's buffer can be persisted with its state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeltaBuffer<D: Lattice> {
     /// Current sequence number
     current_seq: SeqNo,
@@ -46,6 +71,8 @@ pub struct DeltaBuffer<D: Lattice> {
     deltas: VecDeque<TaggedDelta<D>>,
     /// Maximum deltas to buffer before forcing group-join
     max_buffer_size: usize,
+    /// Deltas up to this sequence number have been dropped from the buffer
+    floor: SeqNo,
 }
 
 impl<D: Lattice> DeltaBuffer<D> {
@@ -54,11 +81,17 @@ impl<D: Lattice> DeltaBuffer<D> {
             current_seq: 0,
             deltas: VecDeque::new(),
             max_buffer_size,
+            floor: 0,
         }
     }
 
     /// Add a new delta to the buffer
     pub fn push(&mut self, delta: D) {
+        self.push_shared(Arc::new(delta));
+    }
+
+    /// Add a delta that may also be buffered elsewhere, without copying it
+    pub fn push_shared(&mut self, delta: Arc<D>) {
         self.current_seq += 1;
         self.deltas.push_back(TaggedDelta {
             seq: self.current_seq,
@@ -90,9 +123,53 @@ impl<D: Lattice> DeltaBuffer<D> {
         Some(group)
     }
 
+    /// Create a delta-group for a peer, with the first and last sequence
+    /// numbers it covers
+    pub fn delta_group_range(&self, acked_seq: SeqNo) -> Option<(D, SeqNo, SeqNo)> {
+        let group = self.delta_group_since(acked_seq)?;
+        Some((group, acked_seq + 1, self.current_seq))
+    }
+
+    /// Split the deltas after `acked_seq` into delta-groups of at most
+    /// `max_bytes` each, as measured by `size`
+    ///
+    /// Groups are returned in order with the first and last sequence numbers
+    /// they cover. A delta that alone exceeds the budget gets a group of its
+    /// own.
+    pub fn delta_groups_since(
+        &self,
+        acked_seq: SeqNo,
+        max_bytes: usize,
+        size: impl Fn(&D) -> usize,
+    ) -> Vec<(D, SeqNo, SeqNo)> {
+        let mut groups = Vec::new();
+        let mut current: Option<(D, SeqNo, SeqNo)> = None;
+        let mut next_seq = acked_seq + 1;
+
+        for td in self.deltas_since(acked_seq) {
+            let first = next_seq;
+            next_seq = td.seq + 1;
+            current = Some(match current.take() {
+                None => (D::clone(&td.delta), first, td.seq),
+                Some((group, group_first, group_last)) => {
+                    let joined = group.join(&td.delta);
+                    if size(&joined) <= max_bytes {
+                        (joined, group_first, td.seq)
+                    } else {
+                        groups.push((group, group_first, group_last));
+                        (D::clone(&td.delta), first, td.seq)
+                    }
+                }
+            });
+        }
+        groups.extend(current);
+        groups
+    }
+
     /// Acknowledge that a peer has received up to `seq`
     /// Deltas before this can be GC'd if all peers have acked
     pub fn ack(&mut self, acked_seq: SeqNo) -> usize {
+        self.floor = self.floor.max(acked_seq.min(self.current_seq));
         let initial_len = self.deltas.len();
         self.deltas.retain(|td| td.seq > acked_seq);
         initial_len - self.deltas.len()
@@ -103,6 +180,13 @@ impl<D: Lattice> DeltaBuffer<D> {
         self.current_seq
     }
 
+    /// Check whether the buffer still holds every delta after `acked_seq`
+    ///
+    /// If not, a peer at `acked_seq` needs the full state instead.
+    pub fn covers(&self, acked_seq: SeqNo) -> bool {
+        acked_seq >= self.floor
+    }
+
     /// Number of buffered deltas
     pub fn len(&self) -> usize {
         self.deltas.len()
@@ -116,6 +200,7 @@ impl<D: Lattice> DeltaBuffer<D> {
     /// Clear all buffered deltas
     pub fn clear(&mut self) {
         self.deltas.clear();
+        self.floor = self.current_seq;
     }
 
     /// Compact oldest deltas by joining them
@@ -124,10 +209,10 @@ impl<D: Lattice> DeltaBuffer<D> {
             return;
         }
 
-        // Join the two oldest deltas
+        // Join the two oldest deltas, copying the second only if it is shared
         let oldest = self.deltas.pop_front().unwrap();
         if let Some(second) = self.deltas.front_mut() {
-            second.delta = oldest.delta.join(&second.delta);
+            Arc::make_mut(&mut second.delta).join_assign(&oldest.delta);
         }
     }
 }
@@ -172,6 +257,18 @@ impl AckTracker {
     pub fn peers(&self) -> impl Iterator<Item = &ReplicaId> {
         self.acked.keys()
     }
+
+    /// Stop tracking a peer, returning whether it was registered
+    pub fn remove_peer(&mut self, peer_id: &str) -> bool {
+        self.acked.remove(peer_id).is_some()
+    }
+
+    /// Forget all acks, keeping the registered peers
+    pub fn reset(&mut self) {
+        for acked in self.acked.values_mut() {
+            *acked = 0;
+        }
+    }
 }
 
 impl Default for AckTracker {
@@ -180,6 +277,66 @@ impl Default for AckTracker {
     }
 }
 
+/// The observers a replica sends to, and the deltas of the current round
+#[derive(Debug, Clone)]
+pub(crate) struct ObserverRound<D> {
+    observers: BTreeSet<ReplicaId>,
+    /// Deltas since the round was last taken, with the first and last
+    /// sequence numbers they cover
+    round: Option<(D, SeqNo, SeqNo)>,
+}
+
+impl<D: Lattice> ObserverRound<D> {
+    pub(crate) fn new() -> Self {
+        Self {
+            observers: BTreeSet::new(),
+            round: None,
+        }
+    }
+
+    pub(crate) fn register(&mut self, observer_id: ReplicaId) {
+        self.observers.insert(observer_id);
+    }
+
+    pub(crate) fn remove(&mut self, observer_id: &str) -> bool {
+        self.observers.remove(observer_id)
+    }
+
+    pub(crate) fn ids(&self) -> impl Iterator<Item = &ReplicaId> {
+        self.observers.iter()
+    }
+
+    /// Join a local delta into the round, if anyone is observing
+    pub(crate) fn push(&mut self, delta: &D, seq: SeqNo) {
+        if self.observers.is_empty() {
+            return;
+        }
+        match &mut self.round {
+            Some((round, _, last)) => {
+                round.join_assign(delta);
+                *last = seq;
+            }
+            None => self.round = Some((delta.clone(), seq, seq)),
+        }
+    }
+
+    /// Take the round's deltas, starting a new round
+    pub(crate) fn take(&mut self) -> Option<(D, SeqNo, SeqNo)> {
+        self.round.take()
+    }
+
+    /// Drop the round's deltas, keeping the observers
+    pub(crate) fn clear(&mut self) {
+        self.round = None;
+    }
+}
+
+impl<D: Lattice> Default for ObserverRound<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// A delta-CRDT replica implementing Algorithm 1
 #[derive(Debug, Clone)]
 pub struct DeltaReplica<S: Lattice, D: Lattice = S> {
@@ -191,6 +348,20 @@ pub struct DeltaReplica<S: Lattice, D: Lattice = S> {
     buffer: DeltaBuffer<D>,
     /// Ack tracker for peers
     acks: AckTracker,
+    /// Per origin: every delta through this seq has been received
+    received: Digest,
+    /// Peers that can't decode our deltas
+    incompatible: HashMap<ReplicaId, Incompatibility>,
+    /// Windows advertised by peers
+    flow: FlowControl,
+    /// Observers and the deltas of their current round
+    observers: ObserverRound<D>,
+    /// Collect garbage after this many new deltas instead of on every ack
+    gc_threshold: Option<usize>,
+    /// Deltas buffered since garbage was last collected
+    since_gc: usize,
+    /// Peers whose delta was rejected, awaiting their full state
+    rejected: BTreeSet<ReplicaId>,
     /// Function to convert state delta to buffer delta (usually identity or subset)
     _phantom: std::marker::PhantomData<D>,
 }
@@ -208,10 +379,62 @@ impl<S: Lattice, D: Lattice> DeltaReplica<S, D> {
             state: S::bottom(),
             buffer: DeltaBuffer::new(buffer_size),
             acks: AckTracker::new(),
+            received: Digest::new(),
+            incompatible: HashMap::new(),
+            flow: FlowControl::new(),
+            observers: ObserverRound::new(),
+            gc_threshold: None,
+            since_gc: 0,
+            rejected: BTreeSet::new(),
             _phantom: std::marker::PhantomData,
         }
     }
 
+    /// Start from a peer's snapshot: its state and the [`Digest`] of what
+    /// that state holds
+    pub fn with_snapshot(mut self, state: S, mut digest: Digest) -> Self {
+        digest.remove(&self.id);
+        self.state = state;
+        self.received = digest;
+        self
+    }
+
+    /// Collect garbage after every `threshold` new deltas instead of on
+    /// every ack
+    ///
+    /// Acks then only record how far each peer got, and the buffer keeps
+    /// the deltas they cover until the next collection.
+    pub fn with_gc_threshold(mut self, threshold: usize) -> Self {
+        self.gc_threshold = Some(threshold.max(1));
+        self
+    }
+
+    /// Number of new deltas between automatic collections, if batched
+    pub fn gc_threshold(&self) -> Option<usize> {
+        self.gc_threshold
+    }
+
+    /// Drop every buffered delta that all peers have acknowledged
+    ///
+    /// Returns the number of deltas removed. Sequence numbers are kept, so
+    /// a peer's acked position still selects the same deltas afterwards.
+    pub fn gc(&mut self) -> usize {
+        self.since_gc = 0;
+        self.buffer.ack(self.acks.min_acked())
+    }
+
+    /// Count a newly buffered delta, collecting garbage once the threshold
+    /// is reached
+    fn count_for_gc(&mut self) {
+        self.since_gc += 1;
+        if self
+            .gc_threshold
+            .is_some_and(|threshold| self.since_gc >= threshold)
+        {
+            self.gc();
+        }
+    }
+
     /// Get current state (read-only)
     pub fn state(&self) -> &S {
         &self.state
@@ -227,10 +450,169 @@ impl<S: Lattice, D: Lattice> DeltaReplica<S, D> {
         self.acks.register_peer(peer_id);
     }
 
+    /// Forget a peer that left, returning whether it was registered
+    ///
+    /// Its ack no longer holds back garbage collection, and everything
+    /// received from it is forgotten, so a new replica later joining
+    /// under the same id starts from scratch. Unless collection is
+    /// batched, garbage is collected right away.
+    pub fn remove_peer(&mut self, peer_id: &str) -> bool {
+        let removed = self.acks.remove_peer(peer_id);
+        self.received.remove(peer_id);
+        self.rejected.remove(peer_id);
+        self.incompatible.remove(peer_id);
+        self.flow.reset(peer_id);
+        if removed && self.gc_threshold.is_none() {
+            self.gc();
+        }
+        removed
+    }
+
+    /// Register a read-only observer
+    ///
+    /// Observers are not tracked for acks, so they never hold back garbage
+    /// collection. Each gets the deltas of the current round from
+    /// [`take_observer_group`](Self::take_observer_group), best-effort; a
+    /// registered peer can't also be an observer.
+    pub fn register_observer(&mut self, observer_id: ReplicaId) {
+        if !self.acks.peers().any(|peer| *peer == observer_id) {
+            self.observers.register(observer_id);
+        }
+    }
+
+    /// Stop sending to an observer
+    pub fn remove_observer(&mut self, observer_id: &str) -> bool {
+        self.observers.remove(observer_id)
+    }
+
+    /// Get all registered observers
+    pub fn observers(&self) -> impl Iterator<Item = &ReplicaId> {
+        self.observers.ids()
+    }
+
+    /// Take the local deltas since the last call, joined into one group
+    /// with the first and last sequence numbers it covers, to send to
+    /// every observer
+    ///
+    /// Nothing is kept once the group is taken, so a lost group is lost for
+    /// good; observers catch up from the full state.
+    pub fn take_observer_group(&mut self) -> Option<(D, SeqNo, SeqNo)> {
+        self.observers.take()
+    }
+
     /// Current sequence number
     pub fn current_seq(&self) -> SeqNo {
         self.buffer.current_seq()
     }
+
+    /// What this replica has received, to send to peers when joining
+    ///
+    /// Covers the local deltas and, per peer, the deltas received from it
+    /// without a gap.
+    pub fn digest(&self) -> Digest {
+        let mut digest = self.received.clone();
+        digest.insert(self.id.clone(), self.buffer.current_seq());
+        digest
+    }
+
+    /// Check whether a peer has acknowledged every local delta up to `seq`
+    ///
+    /// Use with [`current_seq`](Self::current_seq) to wait until a peer has
+    /// seen our writes.
+    pub fn ack_barrier(&self, peer_id: &str, seq: SeqNo) -> bool {
+        self.acks.get_ack(peer_id) >= seq
+    }
+
+    /// Record that a peer can't decode our deltas
+    ///
+    /// Returns the [`Incompatibility`] unless the peer was already marked
+    /// for the same type and version.
+    pub fn mark_incompatible(
+        &mut self,
+        peer_id: &str,
+        type_id: impl Into<String>,
+        version: u16,
+    ) -> Option<Incompatibility> {
+        let event = Incompatibility {
+            peer_id: peer_id.to_string(),
+            type_id: type_id.into(),
+            version,
+        };
+        let previous = self.incompatible.insert(peer_id.to_string(), event.clone());
+        (previous.as_ref() != Some(&event)).then_some(event)
+    }
+
+    /// Why a peer can't decode our deltas, if it can't
+    pub fn incompatibility(&self, peer_id: &str) -> Option<&Incompatibility> {
+        self.incompatible.get(peer_id)
+    }
+
+    /// Peers whose delta was rejected and whose full state hasn't arrived
+    /// since
+    pub fn rejected_peers(&self) -> impl Iterator<Item = &ReplicaId> {
+        self.rejected.iter()
+    }
+
+    /// Resume sending deltas to a peer, e.g. once it was upgraded or got
+    /// the full state another way
+    pub fn clear_incompatibility(&mut self, peer_id: &str) -> Option<Incompatibility> {
+        self.incompatible.remove(peer_id)
+    }
+
+    /// Windows peers have advertised in their acks
+    pub fn flow(&self) -> &FlowControl {
+        &self.flow
+    }
+
+    /// Mutable access to the windows, to record sends and acks
+    pub fn flow_mut(&mut self) -> &mut FlowControl {
+        &mut self.flow
+    }
+
+    /// Simulate a crash and restart
+    ///
+    /// The state, sequence counter and digest are durable; buffered deltas, peer
+    /// acks, windows, known incompatibilities and the observers' round are
+    /// volatile and lost. Peers get the full state on the next sync.
+    pub fn crash_and_recover(&mut self) {
+        self.buffer.clear();
+        self.since_gc = 0;
+        self.observers.clear();
+        self.acks.reset();
+        self.incompatible.clear();
+        self.flow.clear();
+    }
+}
+
+/// Delta-CRDT replica shipping the state's own delta type
+impl<S: DeltaCRDT<Delta = D> + Clone, D: Lattice + Clone> DeltaReplica<S, D> {
+    /// Apply a delta-mutator whose delta is `S::Delta`, buffering the delta
+    pub fn mutate_delta<F>(&mut self, mutator: F) -> D
+    where
+        F: FnOnce(&S) -> D,
+    {
+        let delta = mutator(&self.state);
+        self.state.apply_delta(&delta);
+        self.buffer.push(delta.clone());
+        self.observers.push(&delta, self.buffer.current_seq());
+        self.count_for_gc();
+        delta
+    }
+
+    /// Receive a delta from a peer, unless it depends on history this
+    /// replica has not observed
+    ///
+    /// On [`DeltaRejected`] the state is unchanged and the delta should not
+    /// be acked; catch up with the peer's full state through
+    /// [`receive_state`](Self::receive_state) instead.
+    pub fn try_receive_delta(&mut self, delta: &D) -> Result<(), DeltaRejected> {
+        self.state.try_apply_delta(delta)
+    }
+
+    /// Join a peer's full state, the fallback for a rejected delta
+    pub fn receive_state(&mut self, state: &S) {
+        self.state.join_assign(state);
+    }
 }
 
 /// Delta-CRDT replica where state and delta are the same type
@@ -249,16 +631,106 @@ impl<S: Lattice + Clone> DeltaReplica<S, S> {
 
         // Buffer delta: D = D ⊔ d
         self.buffer.push(delta.clone());
+        self.observers.push(&delta, self.buffer.current_seq());
+        self.count_for_gc();
 
         delta
     }
 
     /// Get delta-group to send to a peer
     pub fn prepare_sync(&self, peer_id: &str) -> Option<(S, SeqNo)> {
+        self.delta_group_for_peer(peer_id)
+            .map(|(group, _, seq)| (group, seq))
+    }
+
+    /// Join every delta a peer has not acked into one delta-group
+    ///
+    /// Returns the group with the first and last sequence numbers it
+    /// covers, so a single ack advances the peer past all of them. A peer
+    /// missing deltas we no longer buffer gets the full state instead.
+    pub fn delta_group_for_peer(&self, peer_id: &str) -> Option<(S, SeqNo, SeqNo)> {
+        let acked = self.acks.get_ack(peer_id);
+        if let Some(full) = self.full_state_for(acked) {
+            return Some(full);
+        }
+        self.buffer.delta_group_range(acked)
+    }
+
+    /// Like [`delta_group_for_peer`](Self::delta_group_for_peer), but split
+    /// into consecutive groups of at most `max_bytes` as measured by `size`
+    pub fn delta_groups_for_peer(
+        &self,
+        peer_id: &str,
+        max_bytes: usize,
+        size: impl Fn(&S) -> usize,
+    ) -> Vec<(S, SeqNo, SeqNo)> {
+        let acked = self.acks.get_ack(peer_id);
+        if let Some(full) = self.full_state_for(acked) {
+            return vec![full];
+        }
+        self.buffer.delta_groups_since(acked, max_bytes, size)
+    }
+
+    /// Every buffered delta a peer has not acked, one entry per delta
+    ///
+    /// Each entry carries the sequence numbers it covers, like a group. A
+    /// peer missing deltas we no longer buffer gets the full state instead.
+    pub fn deltas_for_peer(&self, peer_id: &str) -> Vec<(S, SeqNo, SeqNo)> {
         let acked = self.acks.get_ack(peer_id);
+        if let Some(full) = self.full_state_for(acked) {
+            return vec![full];
+        }
+        let mut first = acked + 1;
         self.buffer
-            .delta_group_since(acked)
-            .map(|d| (d, self.buffer.current_seq()))
+            .deltas_since(acked)
+            .into_iter()
+            .map(|td| {
+                let entry = (S::clone(&td.delta), first, td.seq);
+                first = td.seq + 1;
+                entry
+            })
+            .collect()
+    }
+
+    /// The full state, if a peer at `acked` is missing deltas we no longer
+    /// buffer
+    fn full_state_for(&self, acked: SeqNo) -> Option<(S, SeqNo, SeqNo)> {
+        (!self.buffer.covers(acked))
+            .then(|| (self.state.clone(), acked + 1, self.buffer.current_seq()))
+    }
+
+    /// Receive a delta covering `first_seq..=seq` of `from`'s deltas,
+    /// unless `check` rejects it
+    ///
+    /// Like [`receive_delta_range`](Self::receive_delta_range) for a delta
+    /// `check` accepts. On [`DeltaRejected`] the state is unchanged, the
+    /// delta should not be acked, and `from` is listed in
+    /// [`rejected_peers`](Self::rejected_peers) until its full state
+    /// arrives through [`receive_state_from`](Self::receive_state_from).
+    pub fn try_receive_delta_range(
+        &mut self,
+        from: &str,
+        delta: &S,
+        first_seq: SeqNo,
+        seq: SeqNo,
+        check: CheckFn<S>,
+    ) -> Result<(), DeltaRejected> {
+        if let Err(rejected) = check(&self.state, delta) {
+            self.rejected.insert(from.to_string());
+            return Err(rejected);
+        }
+        self.receive_delta_range(from, delta, first_seq, seq);
+        Ok(())
+    }
+
+    /// Join `from`'s full state, which covers its deltas through `seq`
+    ///
+    /// The fallback for a rejected delta; the state is joined unchecked.
+    pub fn receive_state_from(&mut self, from: &str, state: &S, seq: SeqNo) {
+        self.receive_delta(state);
+        let received = self.received.entry(from.to_string()).or_insert(0);
+        *received = (*received).max(seq);
+        self.rejected.remove(from);
     }
 
     /// Receive and apply a delta from a peer (idempotent!)
@@ -267,13 +739,70 @@ impl<S: Lattice + Clone> DeltaReplica<S, S> {
         self.state.join_assign(delta);
     }
 
+    /// Receive a delta covering `first_seq..=seq` of `from`'s deltas
+    ///
+    /// Like [`receive_delta`](Self::receive_delta), and advances the
+    /// [`digest`](Self::digest) entry for `from` unless an earlier delta is
+    /// missing.
+    pub fn receive_delta_range(&mut self, from: &str, delta: &S, first_seq: SeqNo, seq: SeqNo) {
+        self.receive_delta(delta);
+        let received = self.received.entry(from.to_string()).or_insert(0);
+        if first_seq <= *received + 1 {
+            *received = (*received).max(seq);
+        }
+    }
+
+    /// Handle the digest of a peer joining the cluster
+    ///
+    /// Registers the peer if needed and counts the local deltas the digest
+    /// covers as acked, so the next sync only sends what the peer is
+    /// missing. Returns whether that needs the full state, because the
+    /// deltas after the peer's entry have been collected.
+    pub fn receive_digest(&mut self, peer_id: &str, digest: &Digest) -> bool {
+        self.register_peer(peer_id.to_string());
+        let have = digest
+            .get(&self.id)
+            .map_or(0, |seq| (*seq).min(self.buffer.current_seq()));
+        self.process_ack(peer_id, have);
+        !self.buffer.covers(self.acks.get_ack(peer_id))
+    }
+
     /// Process an ack from a peer
-    pub fn process_ack(&mut self, peer_id: &str, seq: SeqNo) {
+    ///
+    /// Returns an [`AckEvent`] if the peer's ack advanced. Deltas every
+    /// peer has acked are dropped right away, or at the next collection
+    /// with a [GC threshold](Self::with_gc_threshold).
+    pub fn process_ack(&mut self, peer_id: &str, seq: SeqNo) -> Option<AckEvent> {
+        let before = self.acks.get_ack(peer_id);
         self.acks.update_ack(peer_id, seq);
+        let after = self.acks.get_ack(peer_id);
 
-        // GC: remove deltas that all peers have acked
-        let min_acked = self.acks.min_acked();
-        self.buffer.ack(min_acked);
+        // GC: remove deltas that all peers have acked, unless batched
+        if self.gc_threshold.is_none() {
+            self.gc();
+        }
+
+        (after > before).then(|| AckEvent {
+            peer_id: peer_id.to_string(),
+            acked_seq: after,
+        })
+    }
+
+    /// Process an ack for a payload covering `first_seq..=seq`
+    ///
+    /// The ack only counts if the peer had already acked everything before
+    /// `first_seq`; otherwise an earlier payload may have been lost and
+    /// acking past it would garbage-collect deltas the peer never got.
+    pub fn process_ack_range(
+        &mut self,
+        peer_id: &str,
+        first_seq: SeqNo,
+        seq: SeqNo,
+    ) -> Option<AckEvent> {
+        if first_seq > self.acks.get_ack(peer_id) + 1 {
+            return None;
+        }
+        self.process_ack(peer_id, seq)
     }
 
     /// Full state (for initial sync or recovery)
@@ -373,6 +902,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_delta_buffer_serde_round_trip() {
+        let mut buffer: DeltaBuffer<GSet<i32>> = DeltaBuffer::new(3);
+        for i in 1..=5 {
+            let mut d = GSet::new();
+            d.insert(i);
+            buffer.push(d);
+        }
+        buffer.ack(2);
+
+        let json = serde_json::to_string(&buffer).unwrap();
+        let mut restored: DeltaBuffer<GSet<i32>> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.current_seq(), buffer.current_seq());
+        assert_eq!(restored.deltas_since(0), buffer.deltas_since(0));
+        assert_eq!(restored.covers(1), buffer.covers(1));
+        assert_eq!(restored.delta_group_range(2), buffer.delta_group_range(2));
+
+        // And it keeps working the same afterwards
+        let mut d = GSet::new();
+        d.insert(6);
+        restored.push(d.clone());
+        buffer.push(d);
+        assert_eq!(restored.delta_group_range(3), buffer.delta_group_range(3));
+    }
+
     #[test]
     fn test_ack_tracker() {
         let mut tracker = AckTracker::new();
@@ -441,4 +996,304 @@ mod tests {
         assert!(replica2.state().contains(&1));
         assert!(replica2.state().contains(&2));
     }
+
+    #[test]
+    fn test_delta_replica_ack_barrier() {
+        let mut replica: DeltaReplica<GSet<i32>> = DeltaReplica::new("r1");
+        replica.register_peer("r2".to_string());
+
+        for i in 0..3 {
+            replica.mutate(|_| {
+                let mut d = GSet::new();
+                d.insert(i);
+                d
+            });
+        }
+        let target = replica.current_seq();
+        assert!(!replica.ack_barrier("r2", target));
+
+        let event = replica.process_ack("r2", 2).unwrap();
+        assert_eq!(event.acked_seq, 2);
+        assert!(!replica.ack_barrier("r2", target));
+
+        // Stale or duplicate acks produce no event
+        assert!(replica.process_ack("r2", 1).is_none());
+
+        replica.process_ack("r2", target);
+        assert!(replica.ack_barrier("r2", target));
+    }
+
+    #[test]
+    fn test_crash_and_recover_falls_back_to_full_state() {
+        let mut replica: DeltaReplica<GSet<i32>> = DeltaReplica::new("r1");
+        replica.register_peer("r2".to_string());
+
+        replica.mutate(|_| {
+            let mut d = GSet::new();
+            d.insert(1);
+            d
+        });
+        replica.mutate(|_| {
+            let mut d = GSet::new();
+            d.insert(2);
+            d
+        });
+        let (delta, _) = replica.prepare_sync("r2").unwrap();
+        assert_eq!(delta.len(), 2);
+
+        replica.crash_and_recover();
+        assert!(replica.buffer().is_empty());
+        assert_eq!(replica.current_seq(), 2);
+
+        // The buffered deltas are gone, so the peer gets the full state
+        let (delta, seq) = replica.prepare_sync("r2").unwrap();
+        assert_eq!(&delta, replica.state());
+        assert_eq!(seq, 2);
+
+        // Once acked, only new deltas are sent again
+        replica.process_ack("r2", seq);
+        assert!(replica.prepare_sync("r2").is_none());
+        replica.mutate(|_| {
+            let mut d = GSet::new();
+            d.insert(3);
+            d
+        });
+        let (delta, _) = replica.prepare_sync("r2").unwrap();
+        assert_eq!(delta.len(), 1);
+    }
+
+    #[test]
+    fn test_digest_tracks_contiguous_deltas() {
+        let mut replica: DeltaReplica<GSet<i32>> = DeltaReplica::new("r1");
+        replica.mutate(|_| {
+            let mut d = GSet::new();
+            d.insert(1);
+            d
+        });
+        let delta = GSet::new();
+        replica.receive_delta_range("r2", &delta, 1, 3);
+        // 4 is missing, so 5..=6 does not count
+        replica.receive_delta_range("r2", &delta, 5, 6);
+        assert_eq!(
+            replica.digest(),
+            Digest::from([("r1".to_string(), 1), ("r2".to_string(), 3)])
+        );
+
+        // A joining peer that has r1's delta is sent nothing until r1 writes
+        assert!(!replica.receive_digest("r3", &Digest::from([("r1".to_string(), 1)])));
+        assert!(replica.prepare_sync("r3").is_none());
+
+        // One that has nothing needs the full state: the delta was collected
+        assert!(replica.receive_digest("r4", &Digest::new()));
+        let (delta, seq) = replica.prepare_sync("r4").unwrap();
+        assert_eq!((&delta, seq), (replica.state(), 1));
+    }
+
+    /// Serialized size, as a stand-in for what goes on the wire
+    fn wire_size(delta: &GSet<i32>) -> usize {
+        serde_json::to_vec(delta).unwrap().len()
+    }
+
+    #[test]
+    fn test_delta_group_is_smaller_than_individual_deltas() {
+        let mut replica: DeltaReplica<GSet<i32>> = DeltaReplica::with_buffer_size("r1", 1000);
+        replica.register_peer("r2".to_string());
+
+        // Each delta overlaps the previous one
+        for i in 0..1000 {
+            replica.mutate(|_| {
+                let mut d = GSet::new();
+                d.insert(i);
+                d.insert((i - 1).max(0));
+                d
+            });
+        }
+
+        let individual = replica.deltas_for_peer("r2");
+        assert_eq!(individual.len(), 1000);
+        assert_eq!((individual[0].1, individual[999].2), (1, 1000));
+        for pair in individual.windows(2) {
+            assert_eq!(pair[1].1, pair[0].2 + 1);
+        }
+
+        let (group, first, last) = replica.delta_group_for_peer("r2").unwrap();
+        assert_eq!((first, last), (1, 1000));
+        assert_eq!(group.len(), 1000);
+        assert_eq!(&group, replica.state());
+
+        let individual_bytes: usize = individual.iter().map(|(d, _, _)| wire_size(d)).sum();
+        let group_bytes = wire_size(&group);
+        assert!(
+            group_bytes * 4 < individual_bytes,
+            "group {} bytes vs {} bytes individually",
+            group_bytes,
+            individual_bytes
+        );
+    }
+
+    #[test]
+    fn test_delta_groups_within_byte_budget() {
+        let mut replica: DeltaReplica<GSet<i32>> = DeltaReplica::new("r1");
+        replica.register_peer("r2".to_string());
+        for i in 0..50 {
+            replica.mutate(|_| {
+                let mut d = GSet::new();
+                d.insert(i);
+                d
+            });
+        }
+        replica.process_ack("r2", 10);
+
+        let groups = replica.delta_groups_for_peer("r2", 64, wire_size);
+        assert!(groups.len() > 1);
+        assert_eq!(groups[0].1, 11);
+        assert_eq!(groups.last().unwrap().2, 50);
+
+        let mut joined = GSet::new();
+        for (i, (group, first, last)) in groups.iter().enumerate() {
+            assert!(wire_size(group) <= 64);
+            assert_eq!(group.len() as u64, last - first + 1);
+            if i > 0 {
+                assert_eq!(*first, groups[i - 1].2 + 1);
+            }
+            joined.join_assign(group);
+        }
+        assert_eq!(Some(joined), replica.buffer().delta_group_since(10));
+
+        // A budget too small for any delta still makes progress
+        assert_eq!(replica.delta_groups_for_peer("r2", 1, wire_size).len(), 40);
+    }
+
+    #[test]
+    fn test_process_ack_range_ignores_gaps() {
+        let mut replica: DeltaReplica<GSet<i32>> = DeltaReplica::new("r1");
+        replica.register_peer("r2".to_string());
+        for i in 0..5 {
+            replica.mutate(|_| {
+                let mut d = GSet::new();
+                d.insert(i);
+                d
+            });
+        }
+
+        // The ack for delta 3 arrives but delta 2's was lost
+        assert!(replica.process_ack_range("r2", 1, 1).is_some());
+        assert!(replica.process_ack_range("r2", 3, 3).is_none());
+        assert_eq!(replica.buffer().len(), 4);
+
+        let resend = replica.deltas_for_peer("r2");
+        assert_eq!(resend[0].1, 2);
+
+        // A group from the acked point covers the gap
+        let (_, first, last) = replica.delta_group_for_peer("r2").unwrap();
+        assert!(replica.process_ack_range("r2", first, last).is_some());
+        assert!(replica.buffer().is_empty());
+    }
+
+    #[test]
+    fn test_observers_do_not_hold_back_gc() {
+        let mut replica: DeltaReplica<GSet<i32>> = DeltaReplica::new("r1");
+        replica.register_peer("r2".to_string());
+        replica.register_observer("watcher".to_string());
+        replica.register_observer("r2".to_string());
+        assert_eq!(replica.observers().collect::<Vec<_>>(), ["watcher"]);
+
+        for round in 0..3 {
+            for i in 0..4 {
+                replica.mutate(|_| {
+                    let mut d = GSet::new();
+                    d.insert(round * 4 + i);
+                    d
+                });
+            }
+            // Each round's deltas reach the observer as one group, whether
+            // or not it ever gets them
+            let (group, first, last) = replica.take_observer_group().unwrap();
+            assert_eq!((first, last), (round as u64 * 4 + 1, round as u64 * 4 + 4));
+            assert_eq!(group.len(), 4);
+            assert!(replica.take_observer_group().is_none());
+
+            replica.process_ack("r2", replica.current_seq());
+            assert!(replica.buffer().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_gc_threshold_keeps_lagging_peer_deltas() {
+        let mut replica: DeltaReplica<GSet<i32>> = DeltaReplica::new("r1").with_gc_threshold(5);
+        for peer in ["r2", "r3", "r4"] {
+            replica.register_peer(peer.to_string());
+        }
+        let insert = |replica: &mut DeltaReplica<GSet<i32>>, i| {
+            replica.mutate(|_| {
+                let mut d = GSet::new();
+                d.insert(i);
+                d
+            });
+        };
+
+        for i in 0..4 {
+            insert(&mut replica, i);
+        }
+        // r4 lags behind the others; acks alone collect nothing
+        replica.process_ack("r2", 4);
+        replica.process_ack("r3", 4);
+        replica.process_ack("r4", 2);
+        assert_eq!(replica.buffer().len(), 4);
+
+        // The fifth delta triggers a collection up to r4's ack
+        insert(&mut replica, 4);
+        assert_eq!(replica.buffer().len(), 3);
+        assert_eq!(replica.gc(), 0);
+
+        // r4 still gets every delta it hasn't acked, by sequence number
+        let pending = replica.deltas_for_peer("r4");
+        assert_eq!(pending.len(), 3);
+        assert_eq!((pending[0].1, pending[2].2), (3, 5));
+        let (group, first, last) = replica.delta_group_for_peer("r4").unwrap();
+        assert_eq!((first, last), (3, 5));
+        assert_eq!(group.len(), 3);
+        assert!(!group.contains(&1));
+
+        for i in 5..10 {
+            insert(&mut replica, i);
+        }
+        assert_eq!(replica.buffer().len(), 8);
+        let (_, first, last) = replica.delta_group_for_peer("r2").unwrap();
+        assert_eq!((first, last), (5, 10));
+
+        // Once everyone has caught up, a manual collection empties the buffer
+        for peer in ["r2", "r3", "r4"] {
+            replica.process_ack(peer, 10);
+        }
+        assert_eq!(replica.buffer().len(), 8);
+        assert_eq!(replica.gc(), 8);
+        assert!(replica.buffer().is_empty());
+        assert!(replica.delta_group_for_peer("r4").is_none());
+    }
+
+    #[test]
+    fn test_removed_peer_no_longer_holds_back_gc() {
+        let mut replica: DeltaReplica<GSet<i32>> = DeltaReplica::new("r1");
+        replica.register_peer("r2".to_string());
+        replica.register_peer("r3".to_string());
+        for i in 0..3 {
+            replica.mutate(|_| {
+                let mut d = GSet::new();
+                d.insert(i);
+                d
+            });
+        }
+        let mut from_r3 = GSet::new();
+        from_r3.insert(100);
+        replica.receive_delta_range("r3", &from_r3, 1, 1);
+        replica.process_ack("r2", 3);
+        assert_eq!(replica.buffer().len(), 3);
+
+        // r3 never acked; once it leaves, nothing waits for it
+        assert!(replica.remove_peer("r3"));
+        assert!(replica.buffer().is_empty());
+        assert!(!replica.digest().contains_key("r3"));
+        assert!(!replica.remove_peer("r3"));
+    }
 }
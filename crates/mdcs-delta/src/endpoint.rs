@@ -0,0 +1,544 @@
+//! Async endpoints for anti-entropy
+//!
+//! A [`DeltaEndpoint`] links a replica to one peer over any transport that
+//! moves messages, such as a WebSocket. It is a [`Stream`] of messages to
+//! send to the peer and a [`Sink`] for messages received from it. Messages
+//! are the protocol's own [`AntiEntropyMessage`] or [`CausalMessage`];
+//! serializing them is left to the caller.
+//!
+//! Deltas and intervals are queued when the endpoint is pumped, either on
+//! an interval ([`DeltaEndpoint::with_interval`]) or by calling
+//! [`DeltaEndpoint::poll_send`] after local mutations. Received messages
+//! are applied to the replica and answered with acks on the stream. While
+//! the outbound queue is full the sink stops accepting messages, and new
+//! deltas stay in the replica's buffer.
+//!
+//! Closing the sink means the peer is gone: the stream ends, dropping
+//! whatever was still queued.
+//!
+//! ```rust,ignore
+//! let replica = Arc::new(Mutex::new(CausalReplica::<GSet<i32>>::new("server")));
+//! let endpoint = DeltaEndpoint::new(replica.clone(), "client")
+//!     .with_interval(Duration::from_millis(50));
+//! let (sink, stream) = endpoint.split();
+//! // forward `stream` to the socket and socket messages into `sink`
+//! ```
+
+use crate::anti_entropy::AntiEntropyMessage;
+use crate::buffer::{DeltaReplica, ReplicaId, SeqNo};
+use crate::causal::{CausalMessage, CausalReplica};
+use futures::future::Either;
+use futures::{Sink, Stream, StreamExt};
+use mdcs_core::lattice::Lattice;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use tokio::time::{Interval, MissedTickBehavior};
+
+/// Outbound messages buffered before the sink applies backpressure
+pub const DEFAULT_OUTBOUND_CAPACITY: usize = 64;
+
+/// A replica that can drive an anti-entropy link to a peer
+pub trait SyncReplica {
+    /// Message type of the replica's protocol
+    type Message;
+
+    /// Start tracking `peer`
+    fn register(&mut self, peer: &str);
+
+    /// Queue every message that is ready for `peer`
+    ///
+    /// `sent` is the highest sequence number already queued for `peer`
+    /// on this link.
+    fn outbound(&mut self, peer: &str, sent: &mut SeqNo, out: &mut VecDeque<Self::Message>);
+
+    /// Apply a message from a peer, queueing any reply
+    fn inbound(&mut self, msg: Self::Message, out: &mut VecDeque<Self::Message>);
+}
+
+impl<S: Lattice + Clone> SyncReplica for DeltaReplica<S, S> {
+    type Message = AntiEntropyMessage<S>;
+
+    fn register(&mut self, peer: &str) {
+        self.register_peer(peer.to_string());
+    }
+
+    /// Sends one delta-group per batch of new deltas; the group starts at
+    /// the peer's ack, so it also covers anything sent but not yet acked.
+    fn outbound(&mut self, peer: &str, sent: &mut SeqNo, out: &mut VecDeque<Self::Message>) {
+        if self.incompatibility(peer).is_some() || !self.flow().is_open(peer) {
+            return;
+        }
+        let Some((delta, first_seq, seq)) = self.delta_group_for_peer(peer) else {
+            return;
+        };
+        if seq > *sent {
+            *sent = seq;
+            self.flow_mut().record_send(peer, 0);
+            out.push_back(AntiEntropyMessage::Delta {
+                from: self.id.clone(),
+                to: peer.to_string(),
+                delta,
+                first_seq,
+                seq,
+            });
+        }
+    }
+
+    fn inbound(&mut self, msg: Self::Message, out: &mut VecDeque<Self::Message>) {
+        match msg {
+            AntiEntropyMessage::Delta {
+                from,
+                delta,
+                first_seq,
+                seq,
+                ..
+            } => {
+                self.receive_delta_range(&from, &delta, first_seq, seq);
+                out.push_back(AntiEntropyMessage::Ack {
+                    from: self.id.clone(),
+                    to: from,
+                    first_seq,
+                    seq,
+                    window: None,
+                });
+            }
+            AntiEntropyMessage::Ack {
+                from,
+                first_seq,
+                seq,
+                window,
+                ..
+            } => {
+                self.process_ack_range(&from, first_seq, seq);
+                self.flow_mut().on_ack(&from, window);
+            }
+            AntiEntropyMessage::Unsupported {
+                from,
+                type_id,
+                version,
+                ..
+            } => {
+                self.mark_incompatible(&from, type_id, version);
+            }
+            AntiEntropyMessage::Digest { from, digest, .. } => {
+                self.receive_digest(&from, &digest);
+            }
+            AntiEntropyMessage::State {
+                from, state, seq, ..
+            } => {
+                self.receive_state_from(&from, &state, seq);
+                out.push_back(AntiEntropyMessage::Ack {
+                    from: self.id.clone(),
+                    to: from,
+                    first_seq: 1,
+                    seq,
+                    window: None,
+                });
+            }
+        }
+    }
+}
+
+impl<S: Lattice + Clone> SyncReplica for CausalReplica<S> {
+    type Message = CausalMessage<S>;
+
+    fn register(&mut self, peer: &str) {
+        self.register_peer(peer.to_string());
+    }
+
+    fn outbound(&mut self, peer: &str, _sent: &mut SeqNo, out: &mut VecDeque<Self::Message>) {
+        if let Some(interval) = self.prepare_interval(peer) {
+            self.flow_mut().record_send(peer, 0);
+            out.push_back(CausalMessage::DeltaInterval(interval));
+        }
+    }
+
+    fn inbound(&mut self, msg: Self::Message, out: &mut VecDeque<Self::Message>) {
+        match msg {
+            CausalMessage::DeltaInterval(interval) => {
+                if let Some(ack) = self.receive_interval(interval) {
+                    out.push_back(CausalMessage::Ack(ack));
+                }
+            }
+            CausalMessage::Ack(ack) => {
+                self.receive_ack(&ack);
+            }
+            CausalMessage::SnapshotRequest { from, .. } => {
+                let (state, seq) = self.snapshot();
+                out.push_back(CausalMessage::Snapshot {
+                    from: self.id().clone(),
+                    to: from,
+                    state,
+                    seq,
+                });
+            }
+            CausalMessage::Snapshot {
+                from, state, seq, ..
+            } => {
+                self.apply_snapshot(state, seq, &from);
+            }
+            CausalMessage::Unsupported {
+                from,
+                type_id,
+                version,
+                ..
+            } => {
+                self.mark_incompatible(&from, type_id, version);
+            }
+        }
+        out.extend(
+            self.take_released_acks()
+                .into_iter()
+                .map(CausalMessage::Ack),
+        );
+    }
+}
+
+/// Endpoint errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EndpointError {
+    /// The endpoint was closed
+    Closed,
+}
+
+impl std::fmt::Display for EndpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EndpointError::Closed => write!(f, "Endpoint closed"),
+        }
+    }
+}
+
+impl std::error::Error for EndpointError {}
+
+/// One side of an anti-entropy link, as a [`Stream`] and [`Sink`]
+///
+/// The replica is shared, so several endpoints (one per peer) can serve
+/// it while the application keeps mutating it.
+pub struct DeltaEndpoint<R: SyncReplica> {
+    replica: Arc<Mutex<R>>,
+    peer: ReplicaId,
+    /// Messages waiting to be taken from the stream
+    outbound: VecDeque<R::Message>,
+    capacity: usize,
+    /// Highest sequence number queued for the peer
+    sent: SeqNo,
+    ticker: Option<Interval>,
+    stream_waker: Option<Waker>,
+    sink_waker: Option<Waker>,
+    closed: bool,
+}
+
+// Nothing is pinned structurally
+impl<R: SyncReplica> Unpin for DeltaEndpoint<R> {}
+
+impl<R: SyncReplica> DeltaEndpoint<R> {
+    /// Create an endpoint linking `replica` to `peer`
+    pub fn new(replica: Arc<Mutex<R>>, peer: impl Into<ReplicaId>) -> Self {
+        let peer = peer.into();
+        replica.lock().register(&peer);
+        Self {
+            replica,
+            peer,
+            outbound: VecDeque::new(),
+            capacity: DEFAULT_OUTBOUND_CAPACITY,
+            sent: 0,
+            ticker: None,
+            stream_waker: None,
+            sink_waker: None,
+            closed: false,
+        }
+    }
+
+    /// Set how many outbound messages are buffered before the sink applies
+    /// backpressure
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Pump the endpoint every `period` while the stream is polled
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn with_interval(mut self, period: Duration) -> Self {
+        let mut ticker = tokio::time::interval(period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        self.ticker = Some(ticker);
+        self
+    }
+
+    /// The shared replica
+    pub fn replica(&self) -> &Arc<Mutex<R>> {
+        &self.replica
+    }
+
+    /// The peer this endpoint talks to
+    pub fn peer(&self) -> &str {
+        &self.peer
+    }
+
+    /// Number of messages waiting to be taken from the stream
+    pub fn outbound_len(&self) -> usize {
+        self.outbound.len()
+    }
+
+    /// Whether the endpoint was closed
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Queue the deltas or intervals that are ready for the peer
+    ///
+    /// Call after local mutations when the endpoint has no interval.
+    /// Returns the number of messages queued, which is zero while the
+    /// outbound queue is full.
+    pub fn poll_send(&mut self) -> usize {
+        if self.closed || self.outbound.len() >= self.capacity {
+            return 0;
+        }
+        let before = self.outbound.len();
+        self.replica
+            .lock()
+            .outbound(&self.peer, &mut self.sent, &mut self.outbound);
+        let queued = self.outbound.len() - before;
+        if queued > 0 {
+            wake(&mut self.stream_waker);
+        }
+        queued
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        self.outbound.clear();
+        wake(&mut self.stream_waker);
+        wake(&mut self.sink_waker);
+    }
+}
+
+fn wake(waker: &mut Option<Waker>) {
+    if let Some(waker) = waker.take() {
+        waker.wake();
+    }
+}
+
+impl<R: SyncReplica> Stream for DeltaEndpoint<R> {
+    type Item = R::Message;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(msg) = this.outbound.pop_front() {
+                // Room for another inbound message
+                wake(&mut this.sink_waker);
+                return Poll::Ready(Some(msg));
+            }
+            if this.closed {
+                return Poll::Ready(None);
+            }
+            let ticked = match this.ticker.as_mut() {
+                Some(ticker) => ticker.poll_tick(cx).is_ready(),
+                None => false,
+            };
+            if !ticked {
+                break;
+            }
+            this.poll_send();
+        }
+        this.stream_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<R: SyncReplica> Sink<R::Message> for DeltaEndpoint<R> {
+    type Error = EndpointError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        if this.closed {
+            return Poll::Ready(Err(EndpointError::Closed));
+        }
+        if this.outbound.len() >= this.capacity {
+            this.sink_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: R::Message) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        if this.closed {
+            return Err(EndpointError::Closed);
+        }
+        let before = this.outbound.len();
+        this.replica.lock().inbound(msg, &mut this.outbound);
+        if this.outbound.len() > before {
+            wake(&mut this.stream_waker);
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // Messages are applied as they are sent
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Pipe two endpoints into each other
+///
+/// Runs until either stream ends, then closes the other endpoint. Meant
+/// for tests and in-process links; real transports forward each half
+/// through their own framing.
+pub async fn relay<A, B, M>(a: DeltaEndpoint<A>, b: DeltaEndpoint<B>) -> Result<(), EndpointError>
+where
+    A: SyncReplica<Message = M>,
+    B: SyncReplica<Message = M>,
+{
+    let (a_sink, a_stream) = a.split();
+    let (b_sink, b_stream) = b.split();
+    let a_to_b = a_stream.map(Ok).forward(b_sink);
+    let b_to_a = b_stream.map(Ok).forward(a_sink);
+    futures::pin_mut!(a_to_b, b_to_a);
+    // The finished side closed its sink; the other is dropped
+    match futures::future::select(a_to_b, b_to_a).await {
+        Either::Left((result, _)) => result,
+        Either::Right((result, _)) => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::task::noop_waker_ref;
+    use futures::SinkExt;
+    use mdcs_core::gset::GSet;
+
+    fn insert(value: i32) -> impl FnOnce(&GSet<i32>) -> GSet<i32> {
+        move |_| {
+            let mut d = GSet::new();
+            d.insert(value);
+            d
+        }
+    }
+
+    fn next<R: SyncReplica>(endpoint: &mut DeltaEndpoint<R>) -> Option<R::Message> {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        match endpoint.poll_next_unpin(&mut cx) {
+            Poll::Ready(msg) => msg,
+            Poll::Pending => None,
+        }
+    }
+
+    #[test]
+    fn test_poll_send_queues_interval() {
+        let replica = Arc::new(Mutex::new(CausalReplica::<GSet<i32>>::new("a")));
+        let mut endpoint = DeltaEndpoint::new(replica.clone(), "b");
+        assert!(next(&mut endpoint).is_none());
+
+        replica.lock().mutate(insert(1));
+        replica.lock().mutate(insert(2));
+        assert_eq!(endpoint.poll_send(), 1);
+        assert_eq!(endpoint.poll_send(), 0);
+
+        match next(&mut endpoint) {
+            Some(CausalMessage::DeltaInterval(interval)) => {
+                assert_eq!(interval.to, "b");
+                assert_eq!((interval.from_seq, interval.to_seq), (0, 2));
+            }
+            other => panic!("expected an interval, got {:?}", other),
+        }
+        assert!(next(&mut endpoint).is_none());
+    }
+
+    #[test]
+    fn test_delta_group_not_repeated_until_new_deltas() {
+        let replica = Arc::new(Mutex::new(DeltaReplica::<GSet<i32>>::new("a")));
+        let mut endpoint = DeltaEndpoint::new(replica.clone(), "b");
+
+        replica.lock().mutate(insert(1));
+        assert_eq!(endpoint.poll_send(), 1);
+        assert_eq!(endpoint.poll_send(), 0);
+
+        // The next group also covers the unacked first delta
+        replica.lock().mutate(insert(2));
+        assert_eq!(endpoint.poll_send(), 1);
+        next(&mut endpoint);
+        match next(&mut endpoint) {
+            Some(AntiEntropyMessage::Delta {
+                delta,
+                first_seq,
+                seq,
+                ..
+            }) => {
+                assert_eq!((first_seq, seq), (1, 2));
+                assert!(delta.contains(&1) && delta.contains(&2));
+            }
+            other => panic!("expected a delta, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_full_outbound_applies_backpressure() {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let replica = Arc::new(Mutex::new(CausalReplica::<GSet<i32>>::new("a")));
+        let mut endpoint = DeltaEndpoint::new(replica.clone(), "b").with_capacity(1);
+
+        let mut sender = CausalReplica::<GSet<i32>>::new("b");
+        sender.register_peer("a".to_string());
+        sender.mutate(insert(7));
+        let interval = sender.prepare_interval("a").unwrap();
+
+        assert!(matches!(
+            endpoint.poll_ready_unpin(&mut cx),
+            Poll::Ready(Ok(()))
+        ));
+        endpoint
+            .start_send_unpin(CausalMessage::DeltaInterval(interval))
+            .unwrap();
+        assert!(replica.lock().state().contains(&7));
+
+        // The ack fills the queue until the stream is drained
+        assert!(endpoint.poll_ready_unpin(&mut cx).is_pending());
+        replica.lock().mutate(insert(8));
+        assert_eq!(endpoint.poll_send(), 0);
+
+        assert!(matches!(next(&mut endpoint), Some(CausalMessage::Ack(_))));
+        assert!(endpoint.poll_ready_unpin(&mut cx).is_ready());
+        assert_eq!(endpoint.poll_send(), 1);
+    }
+
+    #[test]
+    fn test_close_ends_stream() {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let replica = Arc::new(Mutex::new(DeltaReplica::<GSet<i32>>::new("a")));
+        let mut endpoint = DeltaEndpoint::new(replica.clone(), "b");
+
+        replica.lock().mutate(insert(1));
+        endpoint.poll_send();
+        assert!(endpoint.poll_close_unpin(&mut cx).is_ready());
+
+        assert!(endpoint.is_closed());
+        assert!(matches!(
+            endpoint.poll_next_unpin(&mut cx),
+            Poll::Ready(None)
+        ));
+        assert_eq!(
+            endpoint.start_send_unpin(AntiEntropyMessage::Ack {
+                from: "b".to_string(),
+                to: "a".to_string(),
+                first_seq: 1,
+                seq: 1,
+                window: None,
+            }),
+            Err(EndpointError::Closed)
+        );
+    }
+}
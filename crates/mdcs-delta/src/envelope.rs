@@ -0,0 +1,411 @@
+//! Versioned delta envelopes for mixed-version clusters
+//!
+//! On the wire, deltas travel as a [`DeltaEnvelope`]: a type identifier, a
+//! schema version and opaque bytes. The protocol messages around them
+//! (`AntiEntropyMessage<DeltaEnvelope>`, `CausalMessage<DeltaEnvelope>`)
+//! always deserialize, whatever version the delta inside was written with.
+//!
+//! The receiving replica's [`CodecRegistry`] decides whether it can decode
+//! an envelope. One it can't is answered with an `Unsupported` NACK, so
+//! the sender learns the peer needs a downgrade path or a full state
+//! transfer, and by default it is skipped so the messages behind it are
+//! still processed (see [`PoisonPolicy`]).
+//!
+//! ```rust,ignore
+//! let mut codecs = CodecRegistry::<GSet<i32>>::new("gset-i32", 2);
+//! // Version 1 deltas are read through their old schema
+//! codecs.register_codec::<GSetV1>("gset-i32", 1..=1);
+//!
+//! let wire = message.seal(&codecs)?;
+//! match replica.receive_wire(wire, &codecs)? {
+//!     Received::Handled(reply) => { /* send the ack, if any */ }
+//!     Received::Rejected { nack, error } => { /* send the NACK, log the error */ }
+//!     Received::Incompatible(event) => { /* the peer can't read our deltas */ }
+//! }
+//! ```
+
+use crate::buffer::ReplicaId;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+/// A delta tagged with its type and schema version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeltaEnvelope {
+    /// Identifies the delta type, e.g. `"orset-string"`
+    pub type_id: String,
+    /// Schema version the bytes were written with
+    pub version: u16,
+    /// The encoded delta
+    pub bytes: Vec<u8>,
+}
+
+impl DeltaEnvelope {
+    /// Encode `payload` as `type_id` at `version`
+    pub fn seal<T: Serialize>(
+        type_id: impl Into<String>,
+        version: u16,
+        payload: &T,
+    ) -> Result<Self, CodecError> {
+        Ok(Self {
+            type_id: type_id.into(),
+            version,
+            bytes: serde_json::to_vec(payload)
+                .map_err(|e| CodecError::Unserializable(e.to_string()))?,
+        })
+    }
+}
+
+/// Why a message could not be encoded or decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodecError {
+    /// The message can't be serialized, e.g. a map with non-string keys
+    Unserializable(String),
+    /// The buffer holds no bytes at all
+    Empty,
+    /// The message was written in a format this build can't read
+    UnsupportedVersion(u8),
+    /// The bytes after the version don't form a message
+    Malformed(String),
+}
+
+impl std::fmt::Display for CodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodecError::Unserializable(reason) => {
+                write!(f, "Unserializable message: {}", reason)
+            }
+            CodecError::Empty => write!(f, "Empty message"),
+            CodecError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported wire version {}", version)
+            }
+            CodecError::Malformed(reason) => write!(f, "Malformed message: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+/// Why an envelope could not be decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// No codec is registered for this type and version
+    Unsupported { type_id: String, version: u16 },
+    /// A codec is registered but the bytes don't match its schema
+    Malformed {
+        type_id: String,
+        version: u16,
+        reason: String,
+    },
+}
+
+impl DecodeError {
+    /// Type identifier of the envelope that failed
+    pub fn type_id(&self) -> &str {
+        match self {
+            DecodeError::Unsupported { type_id, .. } | DecodeError::Malformed { type_id, .. } => {
+                type_id
+            }
+        }
+    }
+
+    /// Schema version of the envelope that failed
+    pub fn version(&self) -> u16 {
+        match self {
+            DecodeError::Unsupported { version, .. } | DecodeError::Malformed { version, .. } => {
+                *version
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Unsupported { type_id, version } => {
+                write!(f, "No codec for {} version {}", type_id, version)
+            }
+            DecodeError::Malformed {
+                type_id,
+                version,
+                reason,
+            } => write!(f, "Malformed {} version {}: {}", type_id, version, reason),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Why an enveloped message could not be handled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WireError {
+    /// The delta or snapshot received couldn't be decoded
+    Decode(DecodeError),
+    /// The reply couldn't be encoded
+    Encode(CodecError),
+}
+
+impl From<DecodeError> for WireError {
+    fn from(error: DecodeError) -> Self {
+        WireError::Decode(error)
+    }
+}
+
+impl From<CodecError> for WireError {
+    fn from(error: CodecError) -> Self {
+        WireError::Encode(error)
+    }
+}
+
+impl std::fmt::Display for WireError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WireError::Decode(error) => error.fmt(f),
+            WireError::Encode(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for WireError {}
+
+/// What a receiver does with a message it can't decode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PoisonPolicy {
+    /// NACK it, report it, and go on with the next message
+    #[default]
+    SkipAndReport,
+    /// Return the error without a NACK and leave the decision to the caller
+    Stop,
+}
+
+type DecodeFn<S> = fn(&[u8]) -> Result<S, String>;
+
+/// A schema a registry can decode
+#[derive(Clone)]
+struct Codec<S> {
+    type_id: String,
+    versions: RangeInclusive<u16>,
+    decode: DecodeFn<S>,
+}
+
+/// The delta schemas a replica can read, and the one it writes
+#[derive(Clone)]
+pub struct CodecRegistry<S> {
+    type_id: String,
+    version: u16,
+    codecs: Vec<Codec<S>>,
+    policy: PoisonPolicy,
+}
+
+impl<S> std::fmt::Debug for CodecRegistry<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CodecRegistry")
+            .field("type_id", &self.type_id)
+            .field("version", &self.version)
+            .field(
+                "codecs",
+                &self
+                    .codecs
+                    .iter()
+                    .map(|c| (&c.type_id, &c.versions))
+                    .collect::<Vec<_>>(),
+            )
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+impl<S: Serialize + DeserializeOwned> CodecRegistry<S> {
+    /// Write deltas as `type_id` at `version`, and read that version back
+    pub fn new(type_id: impl Into<String>, version: u16) -> Self {
+        let type_id = type_id.into();
+        let mut registry = Self {
+            type_id: type_id.clone(),
+            version,
+            codecs: Vec::new(),
+            policy: PoisonPolicy::default(),
+        };
+        registry.register_codec::<S>(type_id, version..=version);
+        registry
+    }
+
+    /// Read `type_id` at `versions` through the schema `T`
+    ///
+    /// Registering an older or newer schema that converts into `S` is the
+    /// downgrade path for peers running another version. Later
+    /// registrations take precedence over earlier ones.
+    pub fn register_codec<T>(&mut self, type_id: impl Into<String>, versions: RangeInclusive<u16>)
+    where
+        T: DeserializeOwned + Into<S>,
+    {
+        self.codecs.push(Codec {
+            type_id: type_id.into(),
+            versions,
+            decode: |bytes| {
+                serde_json::from_slice::<T>(bytes)
+                    .map(Into::into)
+                    .map_err(|e| e.to_string())
+            },
+        });
+    }
+
+    /// Encode a local delta
+    pub fn encode(&self, delta: &S) -> Result<DeltaEnvelope, CodecError> {
+        DeltaEnvelope::seal(self.type_id.clone(), self.version, delta)
+    }
+}
+
+impl<S> CodecRegistry<S> {
+    /// Set what happens to messages that can't be decoded
+    pub fn with_poison_policy(mut self, policy: PoisonPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// What happens to messages that can't be decoded
+    pub fn poison_policy(&self) -> PoisonPolicy {
+        self.policy
+    }
+
+    /// The type identifier local deltas are written as
+    pub fn type_id(&self) -> &str {
+        &self.type_id
+    }
+
+    /// The schema version local deltas are written with
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    fn codec(&self, type_id: &str, version: u16) -> Option<&Codec<S>> {
+        self.codecs
+            .iter()
+            .rev()
+            .find(|c| c.type_id == type_id && c.versions.contains(&version))
+    }
+
+    /// Whether envelopes of `type_id` at `version` can be decoded
+    pub fn supports(&self, type_id: &str, version: u16) -> bool {
+        self.codec(type_id, version).is_some()
+    }
+
+    /// Decode an envelope
+    pub fn decode(&self, envelope: &DeltaEnvelope) -> Result<S, DecodeError> {
+        let codec = self
+            .codec(&envelope.type_id, envelope.version)
+            .ok_or_else(|| DecodeError::Unsupported {
+                type_id: envelope.type_id.clone(),
+                version: envelope.version,
+            })?;
+        (codec.decode)(&envelope.bytes).map_err(|reason| DecodeError::Malformed {
+            type_id: envelope.type_id.clone(),
+            version: envelope.version,
+            reason,
+        })
+    }
+}
+
+/// A peer can't decode the deltas we send it
+///
+/// Returned when its `Unsupported` NACK arrives. Until it is cleared, no
+/// more deltas are sent to the peer: it needs a downgrade path or a full
+/// state transfer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Incompatibility {
+    pub peer_id: ReplicaId,
+    pub type_id: String,
+    pub version: u16,
+}
+
+/// What handling an enveloped message did
+#[derive(Debug, Clone, PartialEq)]
+pub enum Received<M> {
+    /// The message was handled; send the reply, if any
+    Handled(Option<M>),
+    /// The message couldn't be decoded and was skipped; send the NACK back
+    Rejected { nack: M, error: DecodeError },
+    /// The sender of a NACK can't decode our deltas
+    Incompatible(Incompatibility),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mdcs_core::gset::GSet;
+    use mdcs_core::orset::ORSet;
+
+    /// An older schema: a plain list of values
+    #[derive(Deserialize)]
+    struct ListV1(Vec<i32>);
+
+    impl From<ListV1> for GSet<i32> {
+        fn from(list: ListV1) -> Self {
+            let mut set = GSet::new();
+            for value in list.0 {
+                set.insert(value);
+            }
+            set
+        }
+    }
+
+    fn set(values: &[i32]) -> GSet<i32> {
+        let mut set = GSet::new();
+        for value in values {
+            set.insert(*value);
+        }
+        set
+    }
+
+    #[test]
+    fn test_round_trip_and_unknown_versions() {
+        let codecs = CodecRegistry::<GSet<i32>>::new("gset", 2);
+        let envelope = codecs.encode(&set(&[1, 2])).unwrap();
+        assert_eq!((envelope.type_id.as_str(), envelope.version), ("gset", 2));
+        assert_eq!(codecs.decode(&envelope), Ok(set(&[1, 2])));
+
+        let old = DeltaEnvelope::seal("gset", 1, &vec![3]).unwrap();
+        assert_eq!(
+            codecs.decode(&old),
+            Err(DecodeError::Unsupported {
+                type_id: "gset".to_string(),
+                version: 1
+            })
+        );
+        let other = DeltaEnvelope::seal("orset", 2, &vec![3]).unwrap();
+        assert!(!codecs.supports("orset", 2));
+        assert!(codecs.decode(&other).is_err());
+    }
+
+    #[test]
+    fn test_unserializable_delta_is_an_error() {
+        // JSON map keys must be strings; an ORSet of tuples has tuple keys
+        let mut delta = ORSet::new();
+        delta.add("r1", (1u32, 2u32));
+
+        assert!(matches!(
+            DeltaEnvelope::seal("orset-pair", 1, &delta),
+            Err(CodecError::Unserializable(_))
+        ));
+        let codecs = CodecRegistry::<ORSet<(u32, u32)>>::new("orset-pair", 1);
+        assert!(codecs.encode(&delta).is_err());
+    }
+
+    #[test]
+    fn test_registered_schema_reads_old_versions() {
+        let mut codecs = CodecRegistry::<GSet<i32>>::new("gset", 2);
+        codecs.register_codec::<ListV1>("gset", 0..=1);
+
+        let old = DeltaEnvelope::seal("gset", 1, &vec![3, 4]).unwrap();
+        assert_eq!(codecs.decode(&old), Ok(set(&[3, 4])));
+
+        let garbage = DeltaEnvelope {
+            type_id: "gset".to_string(),
+            version: 0,
+            bytes: b"{".to_vec(),
+        };
+        assert!(matches!(
+            codecs.decode(&garbage),
+            Err(DecodeError::Malformed { version: 0, .. })
+        ));
+    }
+}
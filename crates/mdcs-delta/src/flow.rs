@@ -0,0 +1,223 @@
+//! Per-peer flow control
+//!
+//! Acks carry a [`Window`]: how many more deltas, and bytes of deltas, the
+//! receiver will take from the sender before its next ack. A receiver
+//! advertises its [`ReceiverConfig`] limits minus what that sender already
+//! has queued with it, so a replica that falls behind closes its windows
+//! instead of letting its inbound queue grow.
+//!
+//! A sender's [`FlowControl`] counts what it sends to each peer since the
+//! peer's last ack and stops sending once that fills the window; the next
+//! ack opens a new one. Acks without a window, from peers that don't
+//! advertise one, leave the link unlimited.
+//!
+//! A window stays closed until an ack arrives. A transport that can lose
+//! acks for good should [`reset`](FlowControl::reset) a window that has
+//! stayed closed past its ack timeout.
+
+use crate::buffer::ReplicaId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How much a receiver will accept before its next ack
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Window {
+    /// Delta messages
+    pub deltas: usize,
+    /// Bytes of deltas, as measured by the sender
+    pub bytes: usize,
+}
+
+impl Window {
+    /// No limit
+    pub const UNLIMITED: Window = Window {
+        deltas: usize::MAX,
+        bytes: usize::MAX,
+    };
+}
+
+/// The most a receiver lets each sender have queued with it
+///
+/// The default is unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceiverConfig {
+    /// Bytes of deltas queued from one sender
+    pub max_inflight_bytes: usize,
+    /// Delta messages queued from one sender
+    pub max_inflight_deltas: usize,
+}
+
+impl Default for ReceiverConfig {
+    fn default() -> Self {
+        Self {
+            max_inflight_bytes: usize::MAX,
+            max_inflight_deltas: usize::MAX,
+        }
+    }
+}
+
+impl ReceiverConfig {
+    /// Set how many bytes of deltas one sender may have queued
+    pub fn with_max_inflight_bytes(mut self, max_inflight_bytes: usize) -> Self {
+        self.max_inflight_bytes = max_inflight_bytes;
+        self
+    }
+
+    /// Set how many delta messages one sender may have queued
+    pub fn with_max_inflight_deltas(mut self, max_inflight_deltas: usize) -> Self {
+        self.max_inflight_deltas = max_inflight_deltas;
+        self
+    }
+
+    /// The window to advertise to a sender with this much still queued
+    pub fn advertise(&self, queued_deltas: usize, queued_bytes: usize) -> Window {
+        Window {
+            deltas: self.max_inflight_deltas.saturating_sub(queued_deltas),
+            bytes: self.max_inflight_bytes.saturating_sub(queued_bytes),
+        }
+    }
+}
+
+/// What has been sent to a peer under its current window
+#[derive(Debug, Clone)]
+struct PeerFlow {
+    window: Window,
+    sent_deltas: usize,
+    sent_bytes: usize,
+}
+
+/// Sender side of flow control: one window per peer
+#[derive(Debug, Clone, Default)]
+pub struct FlowControl {
+    peers: HashMap<ReplicaId, PeerFlow>,
+}
+
+impl FlowControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a new window from an ack, or lift the limit if it had none
+    pub fn on_ack(&mut self, peer_id: &str, window: Option<Window>) {
+        match window {
+            Some(window) => {
+                self.peers.insert(
+                    peer_id.to_string(),
+                    PeerFlow {
+                        window,
+                        sent_deltas: 0,
+                        sent_bytes: 0,
+                    },
+                );
+            }
+            None => {
+                self.peers.remove(peer_id);
+            }
+        }
+    }
+
+    /// Whether another delta may be sent to a peer
+    ///
+    /// The byte window may be overshot by the last delta sent into it, so a
+    /// delta larger than the whole window still goes out.
+    pub fn is_open(&self, peer_id: &str) -> bool {
+        self.peers.get(peer_id).is_none_or(|peer| {
+            peer.sent_deltas < peer.window.deltas && peer.sent_bytes < peer.window.bytes
+        })
+    }
+
+    /// Count a delta of `bytes` sent to a peer
+    pub fn record_send(&mut self, peer_id: &str, bytes: usize) {
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            peer.sent_deltas += 1;
+            peer.sent_bytes = peer.sent_bytes.saturating_add(bytes);
+        }
+    }
+
+    /// The window a peer last advertised, if it limits us
+    pub fn window(&self, peer_id: &str) -> Option<Window> {
+        self.peers.get(peer_id).map(|peer| peer.window)
+    }
+
+    /// Deltas sent to a peer since its last ack
+    pub fn in_flight_deltas(&self, peer_id: &str) -> usize {
+        self.peers.get(peer_id).map_or(0, |peer| peer.sent_deltas)
+    }
+
+    /// Bytes of deltas sent to a peer since its last ack
+    pub fn in_flight_bytes(&self, peer_id: &str) -> usize {
+        self.peers.get(peer_id).map_or(0, |peer| peer.sent_bytes)
+    }
+
+    /// Forget a peer's window, e.g. once its ack has timed out
+    pub fn reset(&mut self, peer_id: &str) {
+        self.peers.remove(peer_id);
+    }
+
+    /// Forget every window
+    pub fn clear(&mut self) {
+        self.peers.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_receiver_advertises_what_is_left() {
+        let config = ReceiverConfig::default()
+            .with_max_inflight_deltas(4)
+            .with_max_inflight_bytes(100);
+        assert_eq!(
+            config.advertise(1, 30),
+            Window {
+                deltas: 3,
+                bytes: 70
+            }
+        );
+        assert_eq!(
+            config.advertise(6, 130),
+            Window {
+                deltas: 0,
+                bytes: 0
+            }
+        );
+        assert_eq!(ReceiverConfig::default().advertise(0, 0), Window::UNLIMITED);
+    }
+
+    #[test]
+    fn test_window_closes_and_reopens_on_ack() {
+        let mut flow = FlowControl::new();
+        assert!(flow.is_open("b"));
+
+        flow.on_ack(
+            "b",
+            Some(Window {
+                deltas: 2,
+                bytes: 50,
+            }),
+        );
+        flow.record_send("b", 10);
+        assert!(flow.is_open("b"));
+        flow.record_send("b", 10);
+        assert!(!flow.is_open("b"));
+        assert_eq!(flow.in_flight_deltas("b"), 2);
+        assert!(flow.is_open("c"));
+
+        flow.on_ack(
+            "b",
+            Some(Window {
+                deltas: 5,
+                bytes: 50,
+            }),
+        );
+        assert_eq!(flow.in_flight_deltas("b"), 0);
+        flow.record_send("b", 60);
+        assert!(!flow.is_open("b"), "an oversized delta fills the window");
+
+        flow.on_ack("b", None);
+        assert!(flow.is_open("b"));
+        assert_eq!(flow.window("b"), None);
+    }
+}
@@ -5,6 +5,11 @@
 //! - Delta-mutators for each CRDT type
 //! - Anti-entropy Algorithm 1 (convergence mode)
 //! - Anti-entropy Algorithm 2 (causal consistency mode)
+//! - Async `Stream`/`Sink` endpoints for both (`async` feature)
+//! - Versioned delta envelopes for mixed-version clusters
+//! - Per-peer flow control through windows advertised in acks
+//! - Read-only observer replicas that verify lattice monotonicity
+//! - Versioned wire encoding and length-prefixed framing for messages
 //!
 //! # δ-CRDT Framework
 //!
@@ -82,17 +87,43 @@
 pub mod anti_entropy;
 pub mod buffer;
 pub mod causal;
+#[cfg(feature = "async")]
+pub mod endpoint;
+pub mod envelope;
+pub mod flow;
 pub mod mutators;
+pub mod observer;
+pub mod wire;
 
 // Re-export main types for convenience
-pub use buffer::{AckTracker, DeltaBuffer, DeltaReplica, ReplicaId, SeqNo, TaggedDelta};
+pub use buffer::{
+    AckEvent, AckTracker, DeltaBuffer, DeltaReplica, Digest, ReplicaId, SeqNo, TaggedDelta,
+};
 
-pub use anti_entropy::{AntiEntropyCluster, AntiEntropyMessage, NetworkConfig, NetworkSimulator};
+pub use anti_entropy::{
+    AckStrategy, AntiEntropyCluster, AntiEntropyMessage, CheckFn, NetworkConfig, NetworkSimulator,
+    RetransmitPolicy, SyncMode,
+};
 
 pub use causal::{
     CausalCluster, CausalMessage, CausalNetworkSimulator, CausalReplica, DeltaInterval,
     DurableState, DurableStorage, IntervalAck, MemoryStorage, PeerDeltaBuffer, StorageError,
-    VolatileState,
+    VolatileState, DEFAULT_GAP_ROUNDS,
 };
 
-pub use mutators::{gset as gset_mutators, orset as orset_mutators};
+pub use envelope::{
+    CodecRegistry, DecodeError, DeltaEnvelope, Incompatibility, PoisonPolicy, Received, WireError,
+};
+
+pub use flow::{FlowControl, ReceiverConfig, Window};
+
+#[cfg(feature = "async")]
+pub use endpoint::{relay, DeltaEndpoint, EndpointError, SyncReplica, DEFAULT_OUTBOUND_CAPACITY};
+
+pub use mutators::{
+    bounded_counter as bounded_counter_mutators, gset as gset_mutators, orset as orset_mutators,
+};
+
+pub use observer::{MonotonicityViolation, ObserverReplica};
+
+pub use wire::{read_frame, write_frame, CodecError, WIRE_VERSION};
@@ -7,10 +7,14 @@
 
 pub mod anti_entropy;
 pub mod buffer;
+pub mod envelope;
+pub mod flow;
 pub mod mutators;
 
 // Re-export main types
-pub use anti_entropy::{AntiEntropyCluster, AntiEntropyMessage, NetworkConfig, NetworkSimulator};
+pub use anti_entropy::{
+    AntiEntropyCluster, AntiEntropyMessage, NetworkConfig, NetworkSimulator, SyncMode,
+};
 pub use buffer::{AckTracker, DeltaBuffer, DeltaReplica, ReplicaId, SeqNo, TaggedDelta};
 
 fn main() {
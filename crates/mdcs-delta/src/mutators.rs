@@ -7,7 +7,7 @@
 //! with the original state.
 
 use mdcs_core::gset::GSet;
-use mdcs_core::lattice::Lattice;
+use mdcs_core::lattice::{DeltaCRDT, Lattice};
 use mdcs_core::orset::{ORSet, ORSetDelta, Tag};
 use std::collections::{BTreeMap, BTreeSet};
 
@@ -75,6 +75,16 @@ pub mod gset {
         delta
     }
 
+    /// Delta-mutator for bulk insert: mδ_insert_all(X, vs) = vs
+    ///
+    /// One delta for the whole batch, so `DeltaReplica::mutate` buffers a
+    /// single entry.
+    pub fn insert_all_delta<T: Ord + Clone>(values: impl IntoIterator<Item = T>) -> GSet<T> {
+        let mut delta = GSet::new();
+        delta.extend(values);
+        delta
+    }
+
     /// Apply insert delta to a GSet
     pub fn apply_insert<T: Ord + Clone>(state: &mut GSet<T>, value: T) -> GSet<T> {
         let delta = insert_delta(value);
@@ -106,18 +116,51 @@ pub mod orset {
         }
     }
 
+    /// Delta-mutator for add, as an ORSet that holds only the new tag
+    /// Property: X.add(v) = X ⊔ mδ_add_element(X, v)
+    ///
+    /// Unlike [`add_delta`] it plugs into `DeltaReplica::mutate` and
+    /// `CausalReplica::mutate`. Its size doesn't depend on the state: the
+    /// tag is fresh, so no tombstone anywhere covers it and the delta needs
+    /// no causal context for add-wins. Building the delta from a clone of
+    /// the state instead would ship every live tag and tombstone with each
+    /// add.
+    pub fn add_element_delta<T: Ord + Clone>(
+        _state: &ORSet<T>,
+        replica_id: &str,
+        value: T,
+    ) -> ORSet<T> {
+        let mut delta = ORSet::new();
+        delta.apply_delta(&add_delta(replica_id, value));
+        delta
+    }
+
+    /// Delta-mutator for bulk add: one fresh tag per element, as a single
+    /// ORSet delta
+    ///
+    /// The state is not consulted (every add gets a new tag, exactly like
+    /// element-wise adds); it is taken so the mutator can be passed to
+    /// `DeltaReplica::mutate` as `|x| add_all_delta(x, replica, values)`.
+    pub fn add_all_delta<T: Ord + Clone>(
+        _state: &ORSet<T>,
+        replica_id: &str,
+        values: impl IntoIterator<Item = T>,
+    ) -> ORSet<T> {
+        let mut delta = ORSet::new();
+        delta.add_all(replica_id, values);
+        // The returned set is itself the delta
+        let _ = delta.split_delta();
+        delta
+    }
+
     /// Delta-mutator for remove: collects tags to tombstone
     /// Property: X.remove(v) = X ⊔ mδ_remove(X, v)
+    ///
+    /// The delta tombstones the tags observed in `state`, so a receiver
+    /// that has not seen them rejects it in `try_apply_delta`.
     pub fn remove_delta<T: Ord + Clone>(state: &ORSet<T>, value: &T) -> ORSetDelta<T> {
-        // Get all tags for this value from the current state
-        // The remove delta contains these tags as tombstones
-        let removals = if state.contains(value) {
-            // We need to access the internal tags - this requires ORSet to expose them
-            // For now, we create an empty removal (the actual implementation uses pending_delta)
-            BTreeSet::new()
-        } else {
-            BTreeSet::new()
-        };
+        // The remove delta contains the value's observed tags as tombstones
+        let removals = state.tags(value).cloned().collect();
 
         ORSetDelta {
             additions: BTreeMap::new(),
@@ -125,6 +168,54 @@ pub mod orset {
         }
     }
 
+    /// Delta-mutator for bulk conditional remove: tombstones every observed
+    /// tag of the elements matching `predicate`, as a single ORSet delta
+    /// Property: X.remove_where(p) = X ⊔ mδ_remove_where(X, p)
+    ///
+    /// Use `|v| !keep(v)` for the equivalent of [`ORSet::retain`].
+    pub fn remove_where_delta<T: Ord + Clone>(
+        state: &ORSet<T>,
+        mut predicate: impl FnMut(&T) -> bool,
+    ) -> ORSet<T> {
+        let removals = state
+            .iter()
+            .filter(|value| predicate(value))
+            .flat_map(|value| state.tags(value))
+            .cloned()
+            .collect();
+        tombstones(removals)
+    }
+
+    /// Delta-mutator for remove, as an ORSet that holds only the observed
+    /// tags of `value` as tombstones
+    /// Property: X.remove(v) = X ⊔ mδ_remove_element(X, v)
+    ///
+    /// Unlike [`remove_delta`] it plugs into `DeltaReplica::mutate` and
+    /// `CausalReplica::mutate`. A concurrent add carries a tag this delta
+    /// has not observed, so the element survives (add-wins).
+    pub fn remove_element_delta<T: Ord + Clone>(state: &ORSet<T>, value: &T) -> ORSet<T> {
+        tombstones(state.tags(value).cloned().collect())
+    }
+
+    /// Delta-mutator for clear: tombstones every observed tag, as a single
+    /// ORSet delta
+    /// Property: X.clear() = X ⊔ mδ_clear(X)
+    ///
+    /// Elements added concurrently survive, as with [`remove_element_delta`].
+    pub fn clear_delta<T: Ord + Clone>(state: &ORSet<T>) -> ORSet<T> {
+        remove_where_delta(state, |_| true)
+    }
+
+    /// An ORSet holding only `removals` as tombstones
+    fn tombstones<T: Ord + Clone>(removals: BTreeSet<Tag>) -> ORSet<T> {
+        let mut delta = ORSet::new();
+        delta.apply_delta(&ORSetDelta {
+            additions: BTreeMap::new(),
+            removals,
+        });
+        delta
+    }
+
     /// Apply add operation using delta-mutator
     pub fn apply_add<T: Ord + Clone>(
         state: &mut ORSet<T>,
@@ -264,6 +355,34 @@ pub mod pncounter {
         DecrementDelta { replica_id, amount }
     }
 
+    /// Delta-mutator for a batch of `(replica_id, adjustment)` pairs, as a
+    /// single PNCounter delta
+    /// Property: X.extend(adj) = X ⊔ mδ_adjust_all(X, adj)
+    ///
+    /// The delta carries the new totals of the replicas adjusted, so it
+    /// plugs into `DeltaReplica::mutate` like the other state deltas.
+    pub fn adjust_all_delta<K: Ord + Clone>(
+        state: &PNCounter<K>,
+        adjustments: impl IntoIterator<Item = (K, i64)>,
+    ) -> PNCounter<K> {
+        let mut next = state.clone();
+        let mut adjusted = BTreeSet::new();
+        next.extend(adjustments.into_iter().inspect(|(replica_id, _)| {
+            adjusted.insert(replica_id.clone());
+        }));
+
+        let mut delta = PNCounter::new();
+        for replica_id in adjusted {
+            if let Some(&count) = next.increments().get(&replica_id) {
+                delta.increment(replica_id.clone(), count);
+            }
+            if let Some(&count) = next.decrements().get(&replica_id) {
+                delta.decrement(replica_id, count);
+            }
+        }
+        delta
+    }
+
     /// Apply increment delta to counter
     pub fn apply_increment<K: Ord + Clone>(state: &mut PNCounter<K>, replica_id: K, amount: u64) {
         state.increment(replica_id, amount);
@@ -275,6 +394,53 @@ pub mod pncounter {
     }
 }
 
+// ============================================================================
+// BoundedCounter Delta Mutators
+// ============================================================================
+
+/// BoundedCounter delta-mutators
+///
+/// Each delta holds the entries the mutating replica's quota depends on
+/// (see [`BoundedCounter::delta_for`]), so joining any set of them never
+/// shows a value below the bound.
+pub mod bounded_counter {
+    use mdcs_core::bounded_counter::{BoundedCounter, InsufficientQuota};
+
+    /// Delta-mutator for increment
+    pub fn increment_delta<K: Ord + Clone>(
+        state: &BoundedCounter<K>,
+        replica_id: K,
+        amount: u64,
+    ) -> BoundedCounter<K> {
+        let mut next = state.clone();
+        next.increment(replica_id.clone(), amount);
+        next.delta_for(&replica_id)
+    }
+
+    /// Delta-mutator for decrement, failing if the replica lacks the quota
+    pub fn decrement_delta<K: Ord + Clone>(
+        state: &BoundedCounter<K>,
+        replica_id: K,
+        amount: u64,
+    ) -> Result<BoundedCounter<K>, InsufficientQuota> {
+        let mut next = state.clone();
+        next.decrement(replica_id.clone(), amount)?;
+        Ok(next.delta_for(&replica_id))
+    }
+
+    /// Delta-mutator for moving quota from one replica to another
+    pub fn transfer_delta<K: Ord + Clone>(
+        state: &BoundedCounter<K>,
+        from: K,
+        to: K,
+        amount: u64,
+    ) -> Result<BoundedCounter<K>, InsufficientQuota> {
+        let mut next = state.clone();
+        next.transfer(from.clone(), to, amount)?;
+        Ok(next.delta_for(&from))
+    }
+}
+
 // ============================================================================
 // MVRegister Delta Mutators
 // ============================================================================
@@ -400,6 +566,217 @@ mod tests {
         assert!(state.contains(&"hello".to_string()));
     }
 
+    #[test]
+    fn test_orset_remove_delta() {
+        let mut state: ORSet<String> = ORSet::new();
+        state.apply_delta(&orset::add_delta("replica1", "hello".to_string()));
+        state.apply_delta(&orset::add_delta("replica2", "hello".to_string()));
+
+        let delta = orset::remove_delta(&state, &"hello".to_string());
+        assert_eq!(delta.removals.len(), 2);
+
+        let mut other = state.clone();
+        other.apply_delta(&delta);
+        assert!(!other.contains(&"hello".to_string()));
+    }
+
+    #[test]
+    fn test_orset_remove_element_and_clear_delta() {
+        use crate::buffer::DeltaReplica;
+
+        let mut replica: DeltaReplica<ORSet<String>> = DeltaReplica::new("r1");
+        replica.register_peer("r2".to_string());
+        replica.mutate(|x| orset::add_all_delta(x, "r1", ["a", "b", "c"].map(String::from)));
+        replica.mutate(|_| {
+            let mut d = ORSet::new();
+            d.add("r2", "a".to_string());
+            d
+        });
+
+        // The delta carries only the removed element's tags, as tombstones
+        let delta = replica.mutate(|x| orset::remove_element_delta(x, &"a".to_string()));
+        assert!(delta.is_empty());
+        assert_eq!(delta.join(&ORSet::new()), delta);
+        assert!(!replica.state().contains(&"a".to_string()));
+        assert_eq!(replica.state().len(), 2);
+
+        // Removing an element that isn't there changes nothing
+        let before = replica.state().clone();
+        replica.mutate(|x| orset::remove_element_delta(x, &"zzz".to_string()));
+        assert_eq!(replica.state(), &before);
+
+        replica.mutate(orset::clear_delta);
+        assert!(replica.state().is_empty());
+
+        // A peer applying the buffered deltas ends up empty too
+        let mut peer = ORSet::new();
+        for (delta, _, _) in replica.deltas_for_peer("r2") {
+            peer.join_assign(&delta);
+        }
+        assert!(peer.is_empty());
+    }
+
+    #[test]
+    fn test_orset_add_delta_size_is_independent_of_state() {
+        use crate::buffer::DeltaReplica;
+
+        let encoded_len = |delta: &ORSet<u64>| serde_json::to_vec(delta).unwrap().len();
+        let mut replica: DeltaReplica<ORSet<u64>> = DeltaReplica::new("r1");
+        let mut sizes = Vec::new();
+        for target in [10, 1_000, 10_000] {
+            let len = replica.state().len() as u64;
+            replica.mutate(|x| orset::add_all_delta(x, "r1", len..target));
+            // Half the elements are removed again, leaving tombstones
+            replica.mutate(|x| orset::remove_where_delta(x, |v| v % 2 == 1));
+
+            let delta = replica.mutate(|x| orset::add_element_delta(x, "r1", 1_000_000 + target));
+            assert_eq!(delta.len(), 1);
+            sizes.push(encoded_len(&delta));
+        }
+        assert_eq!(replica.state().len(), 5_003);
+
+        // Same-width values, so the encodings match byte for byte
+        assert!(sizes.windows(2).all(|w| w[0] == w[1]), "{:?}", sizes);
+        let state_len = serde_json::to_vec(replica.state()).unwrap().len();
+        assert!(sizes[2] * 1_000 < state_len);
+    }
+
+    #[test]
+    fn test_gset_insert_all_delta() {
+        let mut elementwise: GSet<i32> = GSet::new();
+        for i in 0..100 {
+            elementwise.join_assign(&gset::insert_delta(i));
+        }
+
+        let bulk = GSet::new().join(&gset::insert_all_delta(0..100));
+        assert_eq!(bulk, elementwise);
+    }
+
+    #[test]
+    fn test_orset_add_all_delta_buffers_once() {
+        use crate::buffer::DeltaReplica;
+
+        let mut bulk: DeltaReplica<ORSet<i32>> = DeltaReplica::new("r1");
+        bulk.mutate(|x| orset::add_all_delta(x, "r1", 0..200));
+        assert_eq!(bulk.current_seq(), 1);
+        assert_eq!(bulk.buffer().len(), 1);
+
+        let mut single: DeltaReplica<ORSet<i32>> = DeltaReplica::new("r2");
+        for i in 0..200 {
+            single.mutate(|_| {
+                let mut d = ORSet::new();
+                d.add("r2", i);
+                d
+            });
+        }
+        assert_eq!(single.current_seq(), 200);
+
+        // Joined elsewhere, both produce the same elements
+        let (bulk_delta, _) = bulk.prepare_sync("peer").unwrap();
+        let (single_delta, _) = single.prepare_sync("peer").unwrap();
+        let from_bulk = ORSet::new().join(&bulk_delta);
+        let from_single = ORSet::new().join(&single_delta);
+        assert!(from_bulk.iter().eq(from_single.iter()));
+        assert_eq!(from_bulk.len(), 200);
+    }
+
+    #[test]
+    fn test_orset_bulk_ops_with_concurrent_remove() {
+        let items: Vec<String> = ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+        let b = "b".to_string();
+
+        let mut r1: ORSet<String> = ORSet::new();
+        r1.add_all("r1", items.clone());
+        let mut r2 = r1.clone();
+
+        // r2 removes "b" while r1 concurrently re-adds it in a batch
+        r2.remove_all([&b]);
+        r1 = r1.join(&orset::add_all_delta(
+            &r1,
+            "r1",
+            vec![b.clone(), "d".to_string()],
+        ));
+
+        let merged = r1.join(&r2);
+        assert_eq!(merged, r2.join(&r1));
+        // Add wins for the re-added element, exactly as with single adds
+        assert!(merged.contains(&b));
+        assert_eq!(merged.len(), 4);
+
+        // Without the concurrent re-add, the remove sticks
+        let mut r3 = r2.clone();
+        let _ = r3.split_delta();
+        r3.remove_all(&items);
+        let merged = r3.join(&r2);
+        assert!(merged.is_empty());
+
+        // remove_all records one delta for the batch
+        let delta = r3.split_delta().unwrap();
+        assert_eq!(delta.removals.len(), 2);
+    }
+
+    #[test]
+    fn test_orset_remove_where_matches_elementwise_remove() {
+        use crate::buffer::DeltaReplica;
+
+        let sessions: Vec<(u64, String)> = (0..20).map(|i| (i * 100, format!("s{}", i))).collect();
+        let stale = |s: &(u64, String)| s.0 < 1_000;
+
+        let mut base: ORSet<(u64, String)> = ORSet::new();
+        base.add_all("r1", sessions.clone());
+        let _ = base.split_delta();
+        let mut other = base.clone();
+        other.add("r2", (2_500, "s25".to_string()));
+
+        // Element-wise removal vs. one bulk pass
+        let mut elementwise = base.clone();
+        for session in sessions.iter().filter(|s| stale(s)) {
+            elementwise.remove(session);
+        }
+        let mut bulk = base.clone();
+        assert_eq!(bulk.remove_where(stale), 10);
+        assert_eq!(bulk, elementwise);
+        assert_eq!(bulk.join(&other), elementwise.join(&other));
+        assert_eq!(other.join(&bulk), bulk.join(&other));
+
+        // The whole removal is one delta
+        let delta = bulk.split_delta().unwrap();
+        assert_eq!(delta.removals.len(), 10);
+        assert!(bulk.split_delta().is_none());
+
+        let mut kept = base.clone();
+        kept.retain(|s| !stale(s));
+        assert_eq!(kept.split_delta(), Some(delta));
+        assert_eq!(kept, bulk);
+
+        // Through a DeltaReplica it is a single buffered delta
+        let mut replica: DeltaReplica<ORSet<(u64, String)>> = DeltaReplica::new("r1");
+        replica.mutate(|x| orset::add_all_delta(x, "r1", sessions.clone()));
+        assert_eq!(replica.current_seq(), 1);
+        replica.mutate(|x| orset::remove_where_delta(x, stale));
+        assert_eq!(replica.current_seq(), 2);
+        assert_eq!(replica.buffer().len(), 2);
+        assert_eq!(replica.state().len(), 10);
+        assert!(replica.state().iter().all(|s| !stale(s)));
+    }
+
+    #[test]
+    fn test_orset_remove_where_concurrent_add_wins() {
+        let mut r1: ORSet<String> = ORSet::new();
+        r1.add_all("r1", ["tmp-a", "tmp-b", "keep"].map(String::from));
+        let mut r2 = r1.clone();
+
+        // r1 drops every temporary entry while r2 re-adds one of them
+        r1 = r1.join(&orset::remove_where_delta(&r1, |v| v.starts_with("tmp-")));
+        r2.add("r2", "tmp-a".to_string());
+
+        let merged = r1.join(&r2);
+        assert_eq!(merged, r2.join(&r1));
+        let mut elements: Vec<_> = merged.iter().cloned().collect();
+        elements.sort();
+        assert_eq!(elements, vec!["keep".to_string(), "tmp-a".to_string()]);
+    }
+
     #[test]
     fn test_orset_delta_idempotence() {
         let mut state: ORSet<String> = ORSet::new();
@@ -483,6 +860,75 @@ mod tests {
         assert_eq!(merged1.value(), merged2.value());
     }
 
+    #[test]
+    fn test_pncounter_adjust_all_delta_buffers_once() {
+        use crate::buffer::DeltaReplica;
+
+        let adjustments: Vec<(String, i64)> = (0..10_000)
+            .map(|i| (format!("r{}", i % 3), if i % 4 == 0 { -2 } else { 5 }))
+            .collect();
+
+        let mut bulk: DeltaReplica<PNCounter<String>> = DeltaReplica::new("r0");
+        bulk.mutate(|x| pncounter::adjust_all_delta(x, adjustments.iter().cloned()));
+        assert_eq!(bulk.buffer().len(), 1);
+
+        let mut elementwise = PNCounter::new();
+        for (replica_id, amount) in &adjustments {
+            let (replica_id, n) = (replica_id.clone(), amount.unsigned_abs());
+            if *amount >= 0 {
+                pncounter::apply_increment(&mut elementwise, replica_id, n);
+            } else {
+                pncounter::apply_decrement(&mut elementwise, replica_id, n);
+            }
+        }
+        let (delta, _) = bulk.prepare_sync("peer").unwrap();
+        assert_eq!(PNCounter::new().join(&delta), elementwise);
+        assert_eq!(bulk.state(), &elementwise);
+    }
+
+    #[test]
+    fn test_gset_bulk_insert_buffers_once() {
+        use crate::buffer::DeltaReplica;
+
+        let mut bulk: DeltaReplica<GSet<i32>> = DeltaReplica::new("r1");
+        bulk.mutate(|_| gset::insert_all_delta(0..10_000));
+        assert_eq!(bulk.buffer().len(), 1);
+
+        let mut elementwise: DeltaReplica<GSet<i32>> = DeltaReplica::new("r2");
+        for i in 0..10_000 {
+            elementwise.mutate(|_| gset::insert_delta(i));
+        }
+        assert_eq!(bulk.state(), elementwise.state());
+        assert_eq!(bulk.state(), &(0..10_000).collect::<GSet<_>>());
+    }
+
+    #[test]
+    fn test_bounded_counter_deltas_through_replicas() {
+        use crate::buffer::DeltaReplica;
+        use mdcs_core::bounded_counter::BoundedCounter;
+
+        let mut a: DeltaReplica<BoundedCounter<String>> = DeltaReplica::new("a");
+        let mut b: DeltaReplica<BoundedCounter<String>> = DeltaReplica::new("b");
+        let (ra, rb) = ("a".to_string(), "b".to_string());
+
+        let d1 = a.mutate(|s| bounded_counter::increment_delta(s, ra.clone(), 10));
+        let d2 =
+            a.mutate(|s| bounded_counter::transfer_delta(s, ra.clone(), rb.clone(), 4).unwrap());
+        assert!(bounded_counter::decrement_delta(a.state(), ra.clone(), 7).is_err());
+
+        // The transfer's delta alone is enough for b to spend its quota
+        b.receive_delta(&d2);
+        assert_eq!(b.state().quota(&rb), 4);
+        let d3 = b.mutate(|s| bounded_counter::decrement_delta(s, rb.clone(), 4).unwrap());
+        assert!(bounded_counter::decrement_delta(b.state(), rb.clone(), 1).is_err());
+
+        a.receive_delta(&d3);
+        b.receive_delta(&d1);
+        assert_eq!(a.state(), b.state());
+        assert_eq!(a.state().value(), 6);
+        assert_eq!(a.state().quota(&ra), 6);
+    }
+
     #[test]
     fn test_mvreg_write_delta() {
         let mut state: MVRegister<i32> = MVRegister::new();
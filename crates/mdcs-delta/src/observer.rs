@@ -0,0 +1,310 @@
+//! Read-only observer replicas
+//!
+//! An observer receives deltas like a peer but never sends any, and its
+//! senders treat it as best-effort: it gets one joined delta per sync round,
+//! is not tracked for acks, and nothing is kept for it once the round has
+//! been sent. An observer that falls behind or disappears therefore never
+//! grows a sender's buffers or holds back garbage collection; to catch up
+//! it asks for a snapshot.
+//!
+//! [`ObserverReplica`] is the receiving end. Besides applying everything it
+//! receives, it checks that each delta is an inflation — the state after
+//! the join is above both the previous state and the delta — and records a
+//! [`MonotonicityViolation`] otherwise, which makes it useful for verifying
+//! a cluster from the outside. It can later become a full peer through the
+//! snapshot bootstrap path (see [`ObserverReplica::promote`]).
+
+use crate::buffer::{ReplicaId, SeqNo};
+use crate::causal::{CausalMessage, CausalReplica, DeltaInterval, DurableState};
+use mdcs_core::lattice::Lattice;
+use std::collections::HashMap;
+
+/// A delta that did not inflate an observer's state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonotonicityViolation {
+    /// The replica that sent the delta
+    pub from: ReplicaId,
+    /// Sequence number just before the delta's interval
+    pub from_seq: SeqNo,
+    /// Sequence number at the end of the delta's interval
+    pub to_seq: SeqNo,
+    /// The state after the join was not above the state before it
+    pub shrank: bool,
+    /// The state after the join was not above the delta
+    pub lost_delta: bool,
+}
+
+impl std::fmt::Display for MonotonicityViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let what = match (self.shrank, self.lost_delta) {
+            (true, true) => "shrank the state and was not absorbed",
+            (true, false) => "shrank the state",
+            _ => "was not absorbed",
+        };
+        write!(
+            f,
+            "delta ({}, {}] from {} {}",
+            self.from_seq, self.to_seq, self.from, what
+        )
+    }
+}
+
+impl std::error::Error for MonotonicityViolation {}
+
+/// A read-only replica that applies the deltas it observes and verifies
+/// they only ever inflate its state
+#[derive(Debug, Clone)]
+pub struct ObserverReplica<S: Lattice + Clone> {
+    id: ReplicaId,
+    state: S,
+    /// Highest sequence number received from each sender
+    received: HashMap<ReplicaId, SeqNo>,
+    violations: Vec<MonotonicityViolation>,
+}
+
+impl<S: Lattice + Clone> ObserverReplica<S> {
+    /// Create an observer with a bottom state
+    pub fn new(id: impl Into<ReplicaId>) -> Self {
+        Self {
+            id: id.into(),
+            state: S::bottom(),
+            received: HashMap::new(),
+            violations: Vec::new(),
+        }
+    }
+
+    /// Get the observer ID
+    pub fn id(&self) -> &ReplicaId {
+        &self.id
+    }
+
+    /// Get the observed state
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// Highest sequence number received from a sender
+    ///
+    /// Rounds may have been lost before it, so this is not an ack.
+    pub fn received_seq(&self, sender: &str) -> SeqNo {
+        self.received.get(sender).copied().unwrap_or(0)
+    }
+
+    /// Every delta that failed the monotonicity check, oldest first
+    pub fn violations(&self) -> &[MonotonicityViolation] {
+        &self.violations
+    }
+
+    /// Apply a delta-interval sent to this observer
+    ///
+    /// A delta that would not inflate the state is recorded as a
+    /// [`MonotonicityViolation`] and not applied, leaving the state as it
+    /// was. No ack is due either way. A forwarded interval counts against
+    /// its origin.
+    pub fn receive_interval(
+        &mut self,
+        interval: DeltaInterval<S>,
+    ) -> Result<(), MonotonicityViolation> {
+        self.apply(
+            interval.origin(),
+            &interval.delta,
+            interval.from_seq,
+            interval.to_seq,
+        )
+    }
+
+    /// Apply a delta-group covering `first_seq..=seq`, as sent under
+    /// Algorithm 1
+    pub fn receive_delta(
+        &mut self,
+        from: &str,
+        delta: &S,
+        first_seq: SeqNo,
+        seq: SeqNo,
+    ) -> Result<(), MonotonicityViolation> {
+        self.apply(from, delta, first_seq.saturating_sub(1), seq)
+    }
+
+    /// Join a sender's full state, e.g. to catch up after lost rounds
+    pub fn receive_snapshot(
+        &mut self,
+        from: &str,
+        state: &S,
+        seq: SeqNo,
+    ) -> Result<(), MonotonicityViolation> {
+        self.apply(from, state, 0, seq)
+    }
+
+    fn apply(
+        &mut self,
+        from: &str,
+        delta: &S,
+        from_seq: SeqNo,
+        to_seq: SeqNo,
+    ) -> Result<(), MonotonicityViolation> {
+        let previous = self.state.clone();
+        self.state.join_assign(delta);
+        let shrank = !previous.leq(&self.state);
+        let lost_delta = !delta.leq(&self.state);
+        if shrank || lost_delta {
+            self.state = previous;
+            let violation = MonotonicityViolation {
+                from: from.to_string(),
+                from_seq,
+                to_seq,
+                shrank,
+                lost_delta,
+            };
+            self.violations.push(violation.clone());
+            return Err(violation);
+        }
+        let received = self.received.entry(from.to_string()).or_insert(0);
+        *received = (*received).max(to_seq);
+        Ok(())
+    }
+
+    /// Turn this observer into a full causal replica
+    ///
+    /// The replica keeps the observed state with a fresh counter and
+    /// registers `peers`. Since observed rounds may have gaps, it takes
+    /// nothing as acked; send the returned snapshot requests so each peer
+    /// bootstraps it, and register the replica as a peer on each of them.
+    pub fn promote(
+        self,
+        peers: impl IntoIterator<Item = ReplicaId>,
+    ) -> (CausalReplica<S>, Vec<CausalMessage<S>>) {
+        let mut replica = CausalReplica::restore(DurableState {
+            replica_id: self.id,
+            state: self.state,
+            counter: 0,
+        });
+        let mut requests = Vec::new();
+        for peer_id in peers {
+            replica.register_peer(peer_id.clone());
+            requests.push(CausalMessage::SnapshotRequest {
+                from: replica.id().clone(),
+                to: peer_id,
+            });
+        }
+        (replica, requests)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mdcs_core::gset::GSet;
+
+    fn interval(
+        from: &str,
+        delta: GSet<i32>,
+        from_seq: SeqNo,
+        to_seq: SeqNo,
+    ) -> DeltaInterval<GSet<i32>> {
+        DeltaInterval {
+            from: from.to_string(),
+            to: "watcher".to_string(),
+            delta,
+            from_seq,
+            to_seq,
+            origin: None,
+            origins: Default::default(),
+        }
+    }
+
+    fn set(values: &[i32]) -> GSet<i32> {
+        let mut set = GSet::new();
+        for &v in values {
+            set.insert(v);
+        }
+        set
+    }
+
+    /// A version number whose in-place join trusts every delta to be newer,
+    /// as a fast path might
+    #[derive(Debug, Clone, PartialEq)]
+    struct Version(u64);
+
+    impl Lattice for Version {
+        fn bottom() -> Self {
+            Version(0)
+        }
+
+        fn join(&self, other: &Self) -> Self {
+            Version(self.0.max(other.0))
+        }
+
+        fn join_assign(&mut self, other: &Self) {
+            self.0 = other.0;
+        }
+    }
+
+    #[test]
+    fn test_observer_applies_rounds_out_of_order() {
+        let mut observer = ObserverReplica::new("watcher");
+
+        observer
+            .receive_interval(interval("a", set(&[3]), 2, 3))
+            .unwrap();
+        observer
+            .receive_interval(interval("a", set(&[1, 2]), 0, 2))
+            .unwrap();
+        observer.receive_delta("b", &set(&[10]), 1, 1).unwrap();
+
+        assert_eq!(observer.state(), &set(&[1, 2, 3, 10]));
+        assert_eq!(observer.received_seq("a"), 3);
+        assert_eq!(observer.received_seq("b"), 1);
+        assert!(observer.violations().is_empty());
+    }
+
+    #[test]
+    fn test_monotonicity_check_catches_corrupted_delta() {
+        let mut observer = ObserverReplica::new("watcher");
+        let send = |delta, seq| DeltaInterval {
+            from: "a".to_string(),
+            to: "watcher".to_string(),
+            delta: Version(delta),
+            from_seq: seq - 1,
+            to_seq: seq,
+            origin: None,
+            origins: Default::default(),
+        };
+
+        observer.receive_interval(send(5, 1)).unwrap();
+        observer.receive_interval(send(7, 2)).unwrap();
+
+        // A corrupted delta carrying an older version would roll it back
+        let violation = observer.receive_interval(send(3, 3)).unwrap_err();
+        assert!(violation.shrank && !violation.lost_delta);
+        assert_eq!((violation.from_seq, violation.to_seq), (2, 3));
+        assert_eq!(observer.violations(), [violation]);
+        assert_eq!(observer.state(), &Version(7));
+        assert_eq!(observer.received_seq("a"), 2);
+
+        observer.receive_interval(send(9, 4)).unwrap();
+        assert_eq!(observer.state(), &Version(9));
+        assert_eq!(observer.violations().len(), 1);
+    }
+
+    #[test]
+    fn test_promote_requests_snapshots() {
+        let mut observer = ObserverReplica::new("watcher");
+        observer
+            .receive_interval(interval("a", set(&[1]), 0, 1))
+            .unwrap();
+
+        let (mut replica, requests) = observer.promote(["a".to_string(), "b".to_string()]);
+        assert_eq!(replica.id(), "watcher");
+        assert_eq!(replica.state(), &set(&[1]));
+        assert_eq!(replica.peers().count(), 2);
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|r| matches!(
+            r,
+            CausalMessage::SnapshotRequest { from, .. } if from == "watcher"
+        )));
+
+        // The observed seq is not taken as acked, so a snapshot still applies
+        replica.apply_snapshot(set(&[1, 2]), 2, "a");
+        assert_eq!(replica.state(), &set(&[1, 2]));
+    }
+}
@@ -0,0 +1,333 @@
+//! Wire encoding for anti-entropy messages
+//!
+//! [`AntiEntropyMessage`] and [`CausalMessage`] encode to a format version
+//! byte followed by the serialized message, so a peer can reject a format
+//! it doesn't know instead of misreading it. Deltas are serialized with
+//! serde, like the bytes inside a [`DeltaEnvelope`](crate::DeltaEnvelope),
+//! and decode to exactly the value that was encoded.
+//!
+//! Over a byte stream such as a TCP socket, each encoded message goes in a
+//! frame: a 4-byte big-endian length followed by that many bytes.
+//!
+//! ```rust,ignore
+//! write_frame(&mut socket, &message.encode()?)?;
+//!
+//! while let Some(frame) = read_frame(&mut socket)? {
+//!     let message = CausalMessage::<GSet<i32>>::decode(&frame)?;
+//!     // hand it to the replica
+//! }
+//! ```
+
+use crate::anti_entropy::AntiEntropyMessage;
+use crate::causal::CausalMessage;
+pub use crate::envelope::CodecError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{self, Read, Write};
+
+/// Format version written in front of every encoded message
+pub const WIRE_VERSION: u8 = 1;
+
+/// Largest frame [`read_frame`] accepts
+pub const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+fn encode<T: Serialize>(message: &T) -> Result<Vec<u8>, CodecError> {
+    let mut bytes = vec![WIRE_VERSION];
+    serde_json::to_writer(&mut bytes, message)
+        .map_err(|e| CodecError::Unserializable(e.to_string()))?;
+    Ok(bytes)
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, CodecError> {
+    match bytes.split_first() {
+        None => Err(CodecError::Empty),
+        Some((&WIRE_VERSION, body)) => {
+            serde_json::from_slice(body).map_err(|e| CodecError::Malformed(e.to_string()))
+        }
+        Some((&version, _)) => Err(CodecError::UnsupportedVersion(version)),
+    }
+}
+
+impl<D: Serialize> AntiEntropyMessage<D> {
+    /// Encode the message for the wire
+    pub fn encode(&self) -> Result<Vec<u8>, CodecError> {
+        encode(self)
+    }
+}
+
+impl<D: DeserializeOwned> AntiEntropyMessage<D> {
+    /// Decode a message produced by [`encode`](Self::encode)
+    pub fn decode(bytes: &[u8]) -> Result<Self, CodecError> {
+        decode(bytes)
+    }
+}
+
+impl<D: Serialize> CausalMessage<D> {
+    /// Encode the message for the wire
+    pub fn encode(&self) -> Result<Vec<u8>, CodecError> {
+        encode(self)
+    }
+}
+
+impl<D: DeserializeOwned> CausalMessage<D> {
+    /// Decode a message produced by [`encode`](Self::encode)
+    pub fn decode(bytes: &[u8]) -> Result<Self, CodecError> {
+        decode(bytes)
+    }
+}
+
+/// Write `message` as one length-prefixed frame
+pub fn write_frame(writer: &mut impl Write, message: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(message.len())
+        .ok()
+        .filter(|&len| len as usize <= MAX_FRAME_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "frame too large"))?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(message)
+}
+
+/// Read the next length-prefixed frame
+///
+/// Returns `None` if the stream ends cleanly between frames; a stream that
+/// ends inside a frame is an [`UnexpectedEof`](io::ErrorKind::UnexpectedEof)
+/// error.
+pub fn read_frame(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    let mut filled = 0;
+    while filled < len.len() {
+        match reader.read(&mut len[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes exceeds {}", len, MAX_FRAME_LEN),
+        ));
+    }
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::causal::{DeltaInterval, IntervalAck};
+    use crate::flow::Window;
+    use mdcs_core::gset::GSet;
+    use mdcs_core::lattice::DeltaCRDT;
+    use mdcs_core::orset::ORSet;
+    use std::collections::BTreeMap;
+    use std::io::Cursor;
+
+    fn orset() -> ORSet<String> {
+        let mut set = ORSet::new();
+        set.add("r1", "apple".to_string());
+        set.add("r2", "banana".to_string());
+        set.remove(&"banana".to_string());
+        // The pending delta is local and never serialized
+        let _ = set.split_delta();
+        set
+    }
+
+    fn anti_entropy_messages() -> Vec<AntiEntropyMessage<ORSet<String>>> {
+        vec![
+            AntiEntropyMessage::Delta {
+                from: "r1".into(),
+                to: "r2".into(),
+                delta: orset(),
+                first_seq: 3,
+                seq: 7,
+            },
+            AntiEntropyMessage::Ack {
+                from: "r2".into(),
+                to: "r1".into(),
+                first_seq: 3,
+                seq: 7,
+                window: Some(Window {
+                    deltas: 16,
+                    bytes: 4096,
+                }),
+            },
+            AntiEntropyMessage::Ack {
+                from: "r2".into(),
+                to: "r1".into(),
+                first_seq: 1,
+                seq: 2,
+                window: None,
+            },
+            AntiEntropyMessage::Unsupported {
+                from: "r2".into(),
+                to: "r1".into(),
+                type_id: "orset-string".into(),
+                version: 3,
+            },
+            AntiEntropyMessage::Digest {
+                from: "r3".into(),
+                to: "r1".into(),
+                digest: [("r1".to_string(), 7), ("r2".to_string(), 2)].into(),
+            },
+            AntiEntropyMessage::State {
+                from: "r1".into(),
+                to: "r3".into(),
+                state: orset(),
+                seq: 7,
+            },
+        ]
+    }
+
+    fn causal_messages() -> Vec<CausalMessage<ORSet<String>>> {
+        vec![
+            CausalMessage::DeltaInterval(DeltaInterval {
+                from: "r1".into(),
+                to: "r2".into(),
+                delta: orset(),
+                from_seq: 4,
+                to_seq: 9,
+                origin: Some("r0".into()),
+                origins: BTreeMap::from([("r0".into(), 9), ("r3".into(), 2)]),
+            }),
+            CausalMessage::Ack(IntervalAck {
+                from: "r2".into(),
+                to: "r1".into(),
+                acked_seq: 9,
+                window: Some(Window {
+                    deltas: 8,
+                    bytes: 1024,
+                }),
+            }),
+            CausalMessage::SnapshotRequest {
+                from: "r3".into(),
+                to: "r1".into(),
+            },
+            CausalMessage::Snapshot {
+                from: "r1".into(),
+                to: "r3".into(),
+                state: orset(),
+                seq: 9,
+            },
+            CausalMessage::Unsupported {
+                from: "r3".into(),
+                to: "r1".into(),
+                type_id: "orset-string".into(),
+                version: 2,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_every_message_round_trips() {
+        for message in anti_entropy_messages() {
+            let bytes = message.encode().unwrap();
+            assert_eq!(bytes[0], WIRE_VERSION);
+            assert_eq!(AntiEntropyMessage::decode(&bytes), Ok(message));
+        }
+        for message in causal_messages() {
+            let bytes = message.encode().unwrap();
+            assert_eq!(bytes[0], WIRE_VERSION);
+            assert_eq!(CausalMessage::decode(&bytes), Ok(message));
+        }
+    }
+
+    #[test]
+    fn test_unknown_version_is_rejected() {
+        let mut bytes = causal_messages()[0].encode().unwrap();
+        bytes[0] = WIRE_VERSION + 1;
+        assert_eq!(
+            CausalMessage::<ORSet<String>>::decode(&bytes),
+            Err(CodecError::UnsupportedVersion(WIRE_VERSION + 1))
+        );
+        assert_eq!(
+            AntiEntropyMessage::<GSet<i32>>::decode(&[]),
+            Err(CodecError::Empty)
+        );
+    }
+
+    #[test]
+    fn test_truncated_and_corrupted_buffers_fail_cleanly() {
+        let encoded: Vec<Vec<u8>> = anti_entropy_messages()
+            .iter()
+            .map(|m| m.encode().unwrap())
+            .chain(causal_messages().iter().map(|m| m.encode().unwrap()))
+            .collect();
+
+        for bytes in &encoded {
+            for len in 0..bytes.len() {
+                assert!(AntiEntropyMessage::<ORSet<String>>::decode(&bytes[..len]).is_err());
+                assert!(CausalMessage::<ORSet<String>>::decode(&bytes[..len]).is_err());
+
+                let mut framed = Vec::new();
+                write_frame(&mut framed, bytes).unwrap();
+                let mut cut = Cursor::new(&framed[..len]);
+                match read_frame(&mut cut) {
+                    Ok(None) => assert_eq!(len, 0),
+                    Ok(Some(_)) => panic!("read a frame from {} of {} bytes", len, bytes.len()),
+                    Err(e) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+                }
+            }
+
+            // Flipping any byte never panics, whatever it decodes to
+            for i in 0..bytes.len() {
+                let mut corrupted = bytes.clone();
+                corrupted[i] ^= 0x5a;
+                let _ = AntiEntropyMessage::<ORSet<String>>::decode(&corrupted);
+                let _ = CausalMessage::<ORSet<String>>::decode(&corrupted);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unserializable_message_is_an_error() {
+        // JSON map keys must be strings; an ORSet of tuples has tuple keys
+        let mut set = ORSet::new();
+        set.add("r1", (1u32, 2u32));
+        let message = AntiEntropyMessage::State {
+            from: "r1".into(),
+            to: "r2".into(),
+            state: set.clone(),
+            seq: 1,
+        };
+        assert!(matches!(
+            message.encode(),
+            Err(CodecError::Unserializable(_))
+        ));
+        let message = CausalMessage::Snapshot {
+            from: "r1".into(),
+            to: "r2".into(),
+            state: set,
+            seq: 1,
+        };
+        assert!(matches!(
+            message.encode(),
+            Err(CodecError::Unserializable(_))
+        ));
+    }
+
+    #[test]
+    fn test_frames_stream_messages_in_order() {
+        let messages = causal_messages();
+        let mut stream = Vec::new();
+        for message in &messages {
+            write_frame(&mut stream, &message.encode().unwrap()).unwrap();
+        }
+
+        let mut reader = Cursor::new(stream);
+        let mut received = Vec::new();
+        while let Some(frame) = read_frame(&mut reader).unwrap() {
+            received.push(CausalMessage::decode(&frame).unwrap());
+        }
+        assert_eq!(received, messages);
+
+        // A length beyond the limit is refused before allocating
+        let mut oversized = Cursor::new(u32::MAX.to_be_bytes().to_vec());
+        let err = read_frame(&mut oversized).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,199 @@
+//! Async endpoint tests over real transports
+//!
+//! These tests run pairs of endpoints through a tokio duplex channel, one
+//! JSON message per line, with random delays on delivery, and through the
+//! in-process relay.
+
+#![cfg(feature = "async")]
+
+use futures::{SinkExt, StreamExt};
+use mdcs_core::gset::GSet;
+use mdcs_delta::{relay, CausalReplica, DeltaEndpoint, DeltaReplica, SyncReplica};
+use parking_lot::Mutex;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream};
+use tokio::task::JoinHandle;
+
+type Shared<R> = Arc<Mutex<R>>;
+
+fn ms(n: u64) -> Duration {
+    Duration::from_millis(n)
+}
+
+fn insert(value: i32) -> impl FnOnce(&GSet<i32>) -> GSet<i32> {
+    move |_| {
+        let mut d = GSet::new();
+        d.insert(value);
+        d
+    }
+}
+
+/// Serve `endpoint` over `io` until the peer goes away
+///
+/// Each received message is delayed by up to 5ms.
+fn connect<R>(endpoint: DeltaEndpoint<R>, io: DuplexStream, seed: u64) -> JoinHandle<()>
+where
+    R: SyncReplica + Send + 'static,
+    R::Message: Serialize + DeserializeOwned + Send + 'static,
+{
+    let (mut sink, mut stream) = endpoint.split();
+    let (read, mut write) = tokio::io::split(io);
+    tokio::spawn(async move {
+        let outgoing = async move {
+            while let Some(msg) = stream.next().await {
+                let mut line = serde_json::to_vec(&msg).unwrap();
+                line.push(b'\n');
+                if write.write_all(&line).await.is_err() {
+                    break;
+                }
+            }
+        };
+        let incoming = async move {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut lines = BufReader::new(read).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                tokio::time::sleep(ms(rng.gen_range(0..5))).await;
+                let msg = serde_json::from_str(&line).unwrap();
+                if sink.send(msg).await.is_err() {
+                    break;
+                }
+            }
+            // The peer is gone
+            let _ = sink.close().await;
+        };
+        tokio::join!(outgoing, incoming);
+    })
+}
+
+async fn wait_until(mut done: impl FnMut() -> bool) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !done() {
+            tokio::time::sleep(ms(5)).await;
+        }
+    })
+    .await
+    .expect("timed out");
+}
+
+/// Mutate both replicas concurrently over a duplex link until they agree
+async fn converge_over_duplex<R>(
+    new: fn(&str) -> R,
+    mutate: fn(&mut R, i32),
+    state: fn(&R) -> &GSet<i32>,
+) where
+    R: SyncReplica + Send + 'static,
+    R::Message: Serialize + DeserializeOwned + Send + 'static,
+{
+    let a = Arc::new(Mutex::new(new("a")));
+    let b = Arc::new(Mutex::new(new("b")));
+    let (io_a, io_b) = tokio::io::duplex(64 * 1024);
+    let task_a = connect(
+        DeltaEndpoint::new(a.clone(), "b").with_interval(ms(2)),
+        io_a,
+        1,
+    );
+    let task_b = connect(
+        DeltaEndpoint::new(b.clone(), "a").with_interval(ms(2)),
+        io_b,
+        2,
+    );
+
+    for i in 0..20 {
+        mutate(&mut a.lock(), i);
+        mutate(&mut b.lock(), 100 + i);
+        tokio::time::sleep(ms(1)).await;
+    }
+
+    wait_until(|| state(&a.lock()).len() == 40 && state(&b.lock()).len() == 40).await;
+    assert_eq!(state(&a.lock()), state(&b.lock()));
+
+    task_a.abort();
+    task_b.abort();
+}
+
+#[tokio::test]
+async fn test_causal_endpoints_converge_over_duplex() {
+    converge_over_duplex::<CausalReplica<GSet<i32>>>(
+        |id| CausalReplica::new(id),
+        |r, v| {
+            r.mutate(insert(v));
+        },
+        |r| r.state(),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_delta_endpoints_converge_over_duplex() {
+    converge_over_duplex::<DeltaReplica<GSet<i32>>>(
+        |id| DeltaReplica::new(id),
+        |r, v| {
+            r.mutate(insert(v));
+        },
+        |r| r.state(),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_dropped_peer_ends_stream() {
+    let a: Shared<CausalReplica<GSet<i32>>> = Arc::new(Mutex::new(CausalReplica::new("a")));
+    let b: Shared<CausalReplica<GSet<i32>>> = Arc::new(Mutex::new(CausalReplica::new("b")));
+    let (io_a, io_b) = tokio::io::duplex(64 * 1024);
+    let task_a = connect(
+        DeltaEndpoint::new(a.clone(), "b").with_interval(ms(2)),
+        io_a,
+        1,
+    );
+    let task_b = connect(
+        DeltaEndpoint::new(b.clone(), "a").with_interval(ms(2)),
+        io_b,
+        2,
+    );
+
+    a.lock().mutate(insert(1));
+    wait_until(|| b.lock().state().contains(&1)).await;
+
+    // Dropping b's endpoint and its end of the channel
+    task_b.abort();
+    let _ = task_b.await;
+
+    // a's stream ends, so its task finishes instead of hanging
+    tokio::time::timeout(Duration::from_secs(5), task_a)
+        .await
+        .expect("stream did not end")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_relay_converges() {
+    let a: Shared<CausalReplica<GSet<i32>>> = Arc::new(Mutex::new(CausalReplica::new("a")));
+    let b: Shared<CausalReplica<GSet<i32>>> = Arc::new(Mutex::new(CausalReplica::new("b")));
+    let link = tokio::spawn(relay(
+        DeltaEndpoint::new(a.clone(), "b").with_interval(ms(2)),
+        DeltaEndpoint::new(b.clone(), "a").with_interval(ms(2)),
+    ));
+
+    for i in 0..10 {
+        a.lock().mutate(insert(i));
+        b.lock().mutate(insert(-i - 1));
+    }
+    wait_until(|| {
+        let a = a.lock();
+        a.state().len() == 20 && b.lock().state() == a.state()
+    })
+    .await;
+
+    // Every interval gets acked
+    wait_until(|| {
+        let a = a.lock();
+        a.ack_barrier("b", a.counter())
+    })
+    .await;
+    link.abort();
+}
@@ -11,6 +11,7 @@ use mdcs_core::pncounter::PNCounter;
 use mdcs_delta::causal::{
     CausalCluster, CausalReplica, DeltaInterval, DurableStorage, MemoryStorage,
 };
+use mdcs_delta::mutators::orset;
 
 /// Test that delta-intervals maintain causal ordering
 #[test]
@@ -44,6 +45,8 @@ fn test_causal_ordering_strict() {
         },
         from_seq: 2,
         to_seq: 5,
+        origin: None,
+        origins: Default::default(),
     };
 
     // Interval 0-2 arrives later
@@ -58,6 +61,8 @@ fn test_causal_ordering_strict() {
         },
         from_seq: 0,
         to_seq: 2,
+        origin: None,
+        origins: Default::default(),
     };
 
     // Send late interval first - should be buffered
@@ -125,7 +130,7 @@ fn test_crash_loses_volatile_state() {
 
     // r0 creates mutations
     for i in 1..=5 {
-        let val = i as i32;
+        let val = i;
         cluster.mutate(0, move |_| {
             let mut d = GSet::new();
             d.insert(val);
@@ -139,13 +144,17 @@ fn test_crash_loses_volatile_state() {
     // Crash r0
     cluster.crash_and_recover(0);
 
-    // Volatile state (pending deltas) should be lost
-    assert!(!cluster.replica(0).has_pending_deltas());
-
     // But durable state should remain
     for i in 1..=5 {
         assert!(cluster.replica(0).state().contains(&i));
     }
+
+    // The buffered deltas were lost; the full state is sent in their place
+    cluster.full_sync_round();
+    assert!(cluster.is_converged());
+    for i in 1..=5 {
+        assert!(cluster.replica(1).state().contains(&i));
+    }
 }
 
 /// Test convergence under network partitions
@@ -260,7 +269,7 @@ fn test_concurrent_mutations() {
     // All 40 elements should be present
     for replica_idx in 0..4 {
         for j in 0..10 {
-            let val = (replica_idx * 100 + j) as i32;
+            let val = replica_idx * 100 + j;
             assert!(
                 cluster.replica(0).state().contains(&val),
                 "Missing value {} from replica {}",
@@ -307,6 +316,34 @@ fn test_orset_causal() {
     assert!(cluster.replica(0).state().contains(&"world".to_string()));
 }
 
+/// Test ORSet with single-tag add deltas under loss and duplication
+#[test]
+fn test_orset_minimal_deltas_causal() {
+    let mut cluster: CausalCluster<ORSet<String>> = CausalCluster::new(3, 0.3);
+
+    for round in 0..30 {
+        let idx = round % 3;
+        let id = format!("r{}", idx);
+        let value = format!("v{}", round % 7);
+        if round % 4 == 3 {
+            cluster.mutate(idx, |s| orset::remove_element_delta(s, &value));
+        } else {
+            let delta = cluster.mutate(idx, |s| orset::add_element_delta(s, &id, value));
+            assert_eq!(delta.len(), 1);
+        }
+        cluster.broadcast_intervals(idx);
+        cluster.duplicate_message(0);
+        cluster.full_sync_round();
+    }
+
+    for _ in 0..10 {
+        cluster.full_sync_round();
+        cluster.retransmit_and_process();
+    }
+    assert!(cluster.is_converged());
+    assert_eq!(cluster.total_pending(), 0);
+}
+
 /// Test LWWRegister with causal consistency
 #[test]
 fn test_lwwreg_causal() {
@@ -392,11 +429,11 @@ fn test_idempotent_delta_application() {
     assert!(ack1.is_some());
     let state_after_one = r2.state().clone();
 
-    // Applying same interval again should be idempotent
-    // (In causal mode, it would be rejected as out of order,
-    // but the CRDT merge itself is idempotent)
+    // Applying same interval again should be idempotent: it is
+    // acknowledged again (in case the first ack was lost), not buffered
     let ack2 = r2.receive_interval(interval.clone());
-    assert!(ack2.is_none()); // Rejected - already processed
+    assert_eq!(ack2, ack1);
+    assert_eq!(r2.pending_count(), 0);
 
     // State should be unchanged
     assert_eq!(r2.state(), &state_after_one);
@@ -439,7 +476,7 @@ fn test_snapshot_bootstrap() {
 
     // Populate with data
     for i in 0..100 {
-        let val = i as i32;
+        let val = i;
         cluster.mutate(0, move |_| {
             let mut d = GSet::new();
             d.insert(val);
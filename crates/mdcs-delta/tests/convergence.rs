@@ -4,13 +4,14 @@
 //! network conditions including message loss, duplication, and reordering.
 
 use mdcs_core::gset::GSet;
-use mdcs_core::lattice::Lattice;
+use mdcs_core::lattice::{DeltaRejected, Lattice};
 use mdcs_core::lwwreg::LWWRegister;
 use mdcs_core::mvreg::MVRegister;
-use mdcs_core::orset::ORSet;
+use mdcs_core::orset::{ORSet, ORSetDelta};
 use mdcs_core::pncounter::PNCounter;
 use mdcs_delta::anti_entropy::{AntiEntropyCluster, NetworkConfig};
-use mdcs_delta::mutators::gset;
+use mdcs_delta::buffer::DeltaReplica;
+use mdcs_delta::mutators::{gset, orset};
 use rand::seq::SliceRandom;
 use rand::SeedableRng;
 
@@ -83,7 +84,7 @@ fn test_gset_convergence_chaotic_network() {
         AntiEntropyCluster::new(5, NetworkConfig::chaotic());
 
     // Multiple concurrent additions
-    let items = vec!["alpha", "beta", "gamma", "delta", "epsilon"];
+    let items = ["alpha", "beta", "gamma", "delta", "epsilon"];
     for (i, item) in items.iter().enumerate() {
         let item_owned = item.to_string();
         cluster.mutate(i, move |_| gset::insert_delta(item_owned));
@@ -169,6 +170,203 @@ fn test_orset_convergence_add_remove() {
     assert!(cluster.replica(0).state().contains(&"item".to_string()));
 }
 
+/// Sync with retransmission until the cluster converges
+fn sync_until_converged(cluster: &mut AntiEntropyCluster<ORSet<String>>) {
+    let mut rounds = 0;
+    while !cluster.is_converged() && rounds < 50 {
+        cluster.full_sync_round();
+        cluster.retransmit_and_process();
+        rounds += 1;
+    }
+    assert!(
+        cluster.is_converged(),
+        "Failed to converge after {} rounds",
+        rounds
+    );
+}
+
+/// Replica 0 removes (or clears) while replica 1 concurrently re-adds
+fn remove_races_readd(
+    config: NetworkConfig,
+    remove: impl FnOnce(&ORSet<String>) -> ORSet<String>,
+) -> AntiEntropyCluster<ORSet<String>> {
+    let mut cluster: AntiEntropyCluster<ORSet<String>> = AntiEntropyCluster::new(3, config);
+    cluster.mutate(0, |x| {
+        orset::add_all_delta(x, "r0", ["item", "other"].map(String::from))
+    });
+    sync_until_converged(&mut cluster);
+
+    let removal = cluster.mutate(0, remove);
+    assert!(
+        removal.is_empty(),
+        "a removal delta carries only tombstones"
+    );
+    cluster.mutate(1, |_| {
+        let mut set = ORSet::new();
+        set.add("r1", "item".to_string());
+        set
+    });
+
+    sync_until_converged(&mut cluster);
+    cluster
+}
+
+#[test]
+fn test_orset_remove_delta_concurrent_readd_wins() {
+    let cluster = remove_races_readd(NetworkConfig::default(), |x| {
+        orset::remove_element_delta(x, &"item".to_string())
+    });
+    for i in 0..3 {
+        let state = cluster.replica(i).state();
+        assert!(state.contains(&"item".to_string()));
+        assert!(state.contains(&"other".to_string()));
+    }
+}
+
+#[test]
+fn test_orset_clear_delta_concurrent_readd_wins() {
+    let cluster = remove_races_readd(NetworkConfig::chaotic(), orset::clear_delta);
+    for i in 0..3 {
+        let elements: Vec<_> = cluster.replica(i).state().iter().cloned().collect();
+        assert_eq!(elements, ["item".to_string()]);
+    }
+}
+
+#[test]
+fn test_orset_minimal_deltas_converge_on_chaotic_network() {
+    let mut cluster: AntiEntropyCluster<ORSet<String>> =
+        AntiEntropyCluster::new(3, NetworkConfig::chaotic());
+    let mut rng = rand::rngs::StdRng::seed_from_u64(11);
+
+    for round in 0..60 {
+        let idx = round % 3;
+        let id = format!("r{}", idx);
+        let value = format!("v{}", rand::Rng::gen_range(&mut rng, 0..8));
+        if round % 3 == 2 {
+            cluster.mutate(idx, |x| orset::remove_element_delta(x, &value));
+        } else {
+            let delta = cluster.mutate(idx, |x| orset::add_element_delta(x, &id, value));
+            assert_eq!(delta.len(), 1);
+        }
+        if round % 5 == 0 {
+            cluster.full_sync_round();
+        }
+    }
+
+    sync_until_converged(&mut cluster);
+}
+
+type ORSetReplica = DeltaReplica<ORSet<String>, ORSetDelta<String>>;
+
+/// Deliver a delta, falling back to the sender's full state on rejection
+fn deliver_or_resync(to: &mut ORSetReplica, from: &ORSetReplica, delta: &ORSetDelta<String>) {
+    if to.try_receive_delta(delta).is_err() {
+        to.receive_state(from.state());
+    }
+}
+
+#[test]
+fn test_orset_remove_delta_without_its_add_is_rejected() {
+    let x = "x".to_string();
+    let mut a: ORSetReplica = DeltaReplica::new("a");
+    let mut b: ORSetReplica = DeltaReplica::new("b");
+    let mut c: ORSetReplica = DeltaReplica::new("c");
+
+    // a adds x; only b hears about it
+    let add = a.mutate_delta(|_| orset::add_delta("a", x.clone()));
+    b.try_receive_delta(&add).unwrap();
+
+    // a removes x; the removal is retransmitted to both b and c
+    let remove = a.mutate_delta(|s| orset::remove_delta(s, &x));
+    b.try_receive_delta(&remove).unwrap();
+    assert!(!b.state().contains(&x));
+    assert_eq!(
+        c.try_receive_delta(&remove),
+        Err(DeltaRejected { unobserved: 1 })
+    );
+    c.receive_state(a.state());
+
+    // c re-adds and removes x, which must leave x gone everywhere
+    let readd = c.mutate_delta(|_| orset::add_delta("c", x.clone()));
+    let reremove = c.mutate_delta(|s| orset::remove_delta(s, &x));
+    for delta in [&readd, &reremove] {
+        deliver_or_resync(&mut a, &c, delta);
+        deliver_or_resync(&mut b, &c, delta);
+    }
+
+    for replica in [&a, &b, &c] {
+        assert!(
+            !replica.state().contains(&x),
+            "{} resurrected x",
+            replica.id
+        );
+    }
+    assert_eq!(a.state(), b.state());
+    assert_eq!(b.state(), c.state());
+}
+
+#[test]
+fn test_cluster_falls_back_to_full_state_on_rejected_delta() {
+    let x = "x".to_string();
+    let mut cluster: AntiEntropyCluster<ORSet<String>> =
+        AntiEntropyCluster::new(3, NetworkConfig::default()).with_delta_check(ORSet::check_join);
+
+    // replica_0 adds x; only replica_1 hears about it
+    cluster.mutate(0, |s| orset::add_element_delta(s, "replica_0", x.clone()));
+    cluster.initiate_sync(0, 1);
+    cluster.drain_network();
+
+    // replica_1 removes x, and its removal reaches replica_2 first
+    cluster.mutate(1, |s| orset::remove_element_delta(s, &x));
+    cluster.initiate_sync(1, 2);
+    assert!(cluster.process_one());
+    assert_eq!(
+        cluster.replica(2).rejected_peers().collect::<Vec<_>>(),
+        vec!["replica_1"]
+    );
+    assert!(cluster.replica(2).state().is_empty());
+
+    // replica_1's full state replaces the delta and is acked in its place
+    cluster.drain_network();
+    assert_eq!(cluster.replica(2).rejected_peers().count(), 0);
+    assert_eq!(cluster.replica(2).digest()["replica_1"], 1);
+    assert!(cluster.replica(1).ack_barrier("replica_2", 1));
+
+    // The add arriving late can't resurrect x
+    cluster.full_sync_round();
+    assert!(cluster.is_converged());
+    assert!(!cluster.replica(2).state().contains(&x));
+}
+
+#[test]
+fn test_orset_validated_deltas_converge_in_order() {
+    let mut replicas: Vec<ORSetReplica> = (0..3)
+        .map(|i| DeltaReplica::new(format!("r{}", i)))
+        .collect();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+
+    for round in 0..50 {
+        let idx = round % 3;
+        let id = replicas[idx].id.clone();
+        let value = format!("v{}", rand::Rng::gen_range(&mut rng, 0..5));
+        let delta = if round % 4 == 3 {
+            replicas[idx].mutate_delta(|s| orset::remove_delta(s, &value))
+        } else {
+            replicas[idx].mutate_delta(|_| orset::add_delta(&id, value))
+        };
+
+        // Causal delivery: every delta is accepted
+        for (j, replica) in replicas.iter_mut().enumerate() {
+            if j != idx {
+                replica.try_receive_delta(&delta).unwrap();
+            }
+        }
+    }
+
+    assert_eq!(replicas[0].state(), replicas[1].state());
+    assert_eq!(replicas[1].state(), replicas[2].state());
+}
+
 // ============================================================================
 // PNCounter Convergence Tests
 // ============================================================================
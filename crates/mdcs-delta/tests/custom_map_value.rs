@@ -0,0 +1,171 @@
+//! A user-defined CRDT stored in a `CRDTMap`
+//!
+//! `BoundedCounter` lives outside mdcs-core and only uses its public API:
+//! it implements `Lattice` and `CausalResettable`, and the maps holding it
+//! are replicated through `DeltaReplica` like any other delta-CRDT.
+
+use mdcs_core::lattice::Lattice;
+use mdcs_core::map::{CRDTMap, CausalResettable, Dot};
+use mdcs_delta::anti_entropy::{AntiEntropyCluster, NetworkConfig};
+use mdcs_delta::buffer::DeltaReplica;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A counter that reads between 0 and `MAX`
+///
+/// Every increment or decrement is kept under its own dot, so removing the
+/// counter from a map forgets exactly the changes the remover saw.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct BoundedCounter<const MAX: i64> {
+    changes: BTreeMap<Dot, i64>,
+}
+
+impl<const MAX: i64> BoundedCounter<MAX> {
+    fn value(&self) -> i64 {
+        self.changes.values().sum::<i64>().clamp(0, MAX)
+    }
+
+    /// Add `amount`, or as much of it as fits under the bound
+    fn add(&mut self, dot: Dot, amount: i64) {
+        let room = MAX - self.value();
+        let amount = amount.clamp(-self.value(), room);
+        if amount != 0 {
+            self.changes.insert(dot, amount);
+        }
+    }
+}
+
+impl<const MAX: i64> Lattice for BoundedCounter<MAX> {
+    fn bottom() -> Self {
+        Self::default()
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        let mut changes = self.changes.clone();
+        for (dot, amount) in &other.changes {
+            changes.insert(dot.clone(), *amount);
+        }
+        Self { changes }
+    }
+}
+
+impl<const MAX: i64> CausalResettable for BoundedCounter<MAX> {
+    fn dots(&self) -> BTreeSet<Dot> {
+        self.changes.keys().cloned().collect()
+    }
+
+    fn reset(&mut self, dots: &BTreeSet<Dot>) {
+        self.changes.retain(|dot, _| !dots.contains(dot));
+    }
+}
+
+type Scores = CRDTMap<String, BoundedCounter<10>>;
+
+fn add(replica_id: &str, key: &str, amount: i64) -> impl FnOnce(&Scores) -> Scores {
+    let (replica_id, key) = (replica_id.to_string(), key.to_string());
+    move |map| map.update_delta(&replica_id, key, |counter, dot| counter.add(dot, amount))
+}
+
+fn remove(key: &str) -> impl FnOnce(&Scores) -> Scores {
+    let key = key.to_string();
+    move |map| map.remove_delta(&key)
+}
+
+fn score(replica: &DeltaReplica<Scores>, key: &str) -> Option<i64> {
+    replica
+        .state()
+        .value(&key.to_string())
+        .map(BoundedCounter::value)
+}
+
+/// Ship `from`'s unacked deltas to `to` and ack them
+fn sync(from: &mut DeltaReplica<Scores>, to: &mut DeltaReplica<Scores>) {
+    if let Some((delta, seq)) = from.prepare_sync(&to.id) {
+        to.receive_delta(&delta);
+        from.process_ack(&to.id, seq);
+    }
+}
+
+fn pair() -> (DeltaReplica<Scores>, DeltaReplica<Scores>) {
+    let mut a = DeltaReplica::new("a");
+    let mut b = DeltaReplica::new("b");
+    a.register_peer("b".to_string());
+    b.register_peer("a".to_string());
+    (a, b)
+}
+
+#[test]
+fn test_counters_converge_through_delta_replicas() {
+    let (mut a, mut b) = pair();
+    a.mutate(add("a", "alice", 4));
+    b.mutate(add("b", "alice", 3));
+    b.mutate(add("b", "bob", 12));
+
+    sync(&mut a, &mut b);
+    sync(&mut b, &mut a);
+    assert_eq!(a.state(), b.state());
+    assert_eq!(score(&a, "alice"), Some(7));
+    assert_eq!(score(&a, "bob"), Some(10));
+
+    a.mutate(add("a", "alice", -2));
+    sync(&mut a, &mut b);
+    assert_eq!(score(&b, "alice"), Some(5));
+}
+
+#[test]
+fn test_remove_then_concurrent_update_keeps_the_update() {
+    let (mut a, mut b) = pair();
+    a.mutate(add("a", "alice", 4));
+    sync(&mut a, &mut b);
+
+    // a removes the counter it has seen while b adds to it
+    a.mutate(remove("alice"));
+    b.mutate(add("b", "alice", 2));
+    assert_eq!(score(&a, "alice"), None);
+    assert_eq!(score(&b, "alice"), Some(6));
+
+    sync(&mut a, &mut b);
+    sync(&mut b, &mut a);
+    assert_eq!(a.state(), b.state());
+    assert_eq!(score(&a, "alice"), Some(2));
+}
+
+#[test]
+fn test_remove_of_observed_updates_converges() {
+    let (mut a, mut b) = pair();
+    a.mutate(add("a", "alice", 4));
+    b.mutate(add("b", "bob", 1));
+    sync(&mut a, &mut b);
+    sync(&mut b, &mut a);
+
+    b.mutate(remove("alice"));
+    a.mutate(add("a", "bob", 1));
+    sync(&mut b, &mut a);
+    sync(&mut a, &mut b);
+
+    assert_eq!(a.state(), b.state());
+    assert_eq!(score(&a, "alice"), None);
+    assert_eq!(score(&a, "bob"), Some(2));
+}
+
+#[test]
+fn test_counters_converge_over_a_lossy_network() {
+    let mut cluster: AntiEntropyCluster<Scores> =
+        AntiEntropyCluster::new(3, NetworkConfig::chaotic());
+    for round in 0..20 {
+        let idx = round % 3;
+        let replica_id = format!("replica_{}", idx);
+        let key = ["alice", "bob"][round % 2];
+        if round % 7 == 6 {
+            cluster.mutate(idx, remove(key));
+        } else {
+            cluster.mutate(idx, add(&replica_id, key, 3));
+        }
+        cluster.full_sync_round();
+    }
+    for _ in 0..20 {
+        cluster.full_sync_round();
+        cluster.retransmit_and_process();
+    }
+
+    assert!(cluster.is_converged());
+}
@@ -0,0 +1,69 @@
+//! Memory cost of buffering one delta for many peers
+//!
+//! Counts live heap bytes with a wrapping global allocator. This file holds
+//! a single test so no other test allocates while it measures.
+
+use mdcs_core::gset::GSet;
+use mdcs_delta::causal::CausalReplica;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+struct Counting;
+
+static LIVE: AtomicIsize = AtomicIsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LIVE.fetch_add(layout.size() as isize, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size() as isize, Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+const PASTE: usize = 100 * 1024;
+
+/// Live bytes added by one 100KB mutation on a replica with `peers` peers
+fn paste_cost(peers: usize) -> isize {
+    let mut replica: CausalReplica<GSet<String>> = CausalReplica::new("r0");
+    for i in 1..=peers {
+        replica.register_peer(format!("r{}", i));
+    }
+    let text = "x".repeat(PASTE);
+
+    let before = LIVE.load(Ordering::SeqCst);
+    let delta = replica.mutate(move |_| {
+        let mut d = GSet::new();
+        d.insert(text);
+        d
+    });
+    let cost = LIVE.load(Ordering::SeqCst) - before;
+
+    drop(delta);
+    cost
+}
+
+#[test]
+fn test_buffering_for_many_peers_shares_the_delta() {
+    let one = paste_cost(1);
+    let ten = paste_cost(10);
+
+    // The copy joined into the state and the buffered copy (the returned
+    // delta reuses the pasted string)
+    assert!(one >= 2 * PASTE as isize, "one peer cost {} bytes", one);
+    assert!(one < 3 * PASTE as isize, "one peer cost {} bytes", one);
+
+    // Nine more peers cost bookkeeping, not nine more copies
+    assert!(
+        ten - one < (PASTE / 10) as isize,
+        "ten peers cost {} bytes, one peer {}",
+        ten,
+        one
+    );
+}
@@ -0,0 +1,120 @@
+//! Flow control towards a slow replica
+//!
+//! Replica 0 processes one message per sync round while replicas 1 and 2
+//! keep mutating. Its acks advertise a window of `WINDOW` deltas per
+//! sender, which must bound what each sender has queued with it, without
+//! slowing down sync between the fast replicas.
+
+use mdcs_core::gset::GSet;
+use mdcs_delta::{AntiEntropyCluster, CausalCluster, NetworkConfig, ReceiverConfig, SyncMode};
+
+const WINDOW: usize = 3;
+const ROUNDS: i32 = 30;
+
+fn insert(value: i32) -> impl FnOnce(&GSet<i32>) -> GSet<i32> {
+    move |_| {
+        let mut d = GSet::new();
+        d.insert(value);
+        d
+    }
+}
+
+fn slow_receiver() -> ReceiverConfig {
+    ReceiverConfig::default().with_max_inflight_deltas(WINDOW)
+}
+
+fn delta_cluster(flow_control: bool) -> AntiEntropyCluster<GSet<i32>> {
+    let cluster = AntiEntropyCluster::new(3, NetworkConfig::default())
+        .with_sync_mode(SyncMode::PerDelta)
+        .with_processing_limit(0, 1);
+    if flow_control {
+        cluster.with_receiver_config(0, slow_receiver())
+    } else {
+        cluster
+    }
+}
+
+#[test]
+fn test_slow_receiver_never_exceeds_its_window() {
+    let mut cluster = delta_cluster(true);
+    for round in 0..ROUNDS {
+        cluster.mutate(1, insert(round));
+        cluster.mutate(2, insert(-round - 1));
+        cluster.full_sync_round();
+
+        // The fast replicas stay in sync with each other
+        assert_eq!(cluster.replica(1).state(), cluster.replica(2).state());
+        for sender in ["replica_1", "replica_2"] {
+            assert!(cluster.queued_from(0, sender) <= WINDOW);
+        }
+    }
+    assert_eq!(cluster.peak_queued(0), WINDOW);
+    assert!(!cluster.is_converged(), "the slow replica should lag");
+
+    // Once the bursts stop, the slow replica catches up
+    let mut rounds = 0;
+    while !cluster.is_converged() {
+        cluster.full_sync_round();
+        rounds += 1;
+        assert!(rounds < 10 * ROUNDS, "never caught up");
+    }
+    assert!(cluster.peak_queued(0) <= WINDOW);
+    assert_eq!(cluster.replica(0).state().len(), 2 * ROUNDS as usize);
+}
+
+#[test]
+fn test_without_flow_control_the_queue_grows() {
+    let mut cluster = delta_cluster(false);
+    for round in 0..ROUNDS {
+        cluster.mutate(1, insert(round));
+        cluster.mutate(2, insert(-round - 1));
+        cluster.full_sync_round();
+    }
+    assert!(cluster.peak_queued(0) > 10 * WINDOW);
+}
+
+#[test]
+fn test_causal_slow_receiver_never_exceeds_its_window() {
+    let mut cluster = CausalCluster::new(3, 0.0)
+        .with_processing_limit(0, 1)
+        .with_receiver_config(0, slow_receiver());
+    for round in 0..ROUNDS {
+        cluster.mutate(1, insert(round));
+        cluster.mutate(2, insert(-round - 1));
+        cluster.full_sync_round();
+        assert_eq!(cluster.replica(1).state(), cluster.replica(2).state());
+    }
+    assert!(cluster.peak_queued(0) <= WINDOW);
+
+    let mut rounds = 0;
+    while !cluster.is_converged() {
+        cluster.full_sync_round();
+        rounds += 1;
+        assert!(rounds < 10 * ROUNDS, "never caught up");
+    }
+    assert_eq!(cluster.inbound_len(0), 0);
+    assert_eq!(cluster.replica(0).state().len(), 2 * ROUNDS as usize);
+}
+
+#[test]
+fn test_byte_window_converges_on_a_lossy_network() {
+    let mut cluster = AntiEntropyCluster::new(3, NetworkConfig::lossy(0.2))
+        .with_processing_limit(0, 1)
+        .with_delta_size(|delta: &GSet<i32>| delta.len() * 4)
+        .with_receiver_config(0, ReceiverConfig::default().with_max_inflight_bytes(16));
+    for round in 0..ROUNDS {
+        cluster.mutate(1, insert(round));
+        cluster.mutate(2, insert(-round - 1));
+        cluster.full_sync_round();
+    }
+
+    for _ in 0..10 * ROUNDS {
+        if cluster.is_converged() {
+            break;
+        }
+        cluster.retransmit_and_process();
+        cluster.full_sync_round();
+    }
+    assert!(cluster.is_converged());
+    assert_eq!(cluster.replica(0).state().len(), 2 * ROUNDS as usize);
+}
@@ -0,0 +1,179 @@
+//! Membership tests for the simulated clusters
+//!
+//! These tests add and remove replicas while a workload is running, with
+//! messages still in flight, and check that the cluster converges, that
+//! a departed replica holds nothing back, and that a replica coming back
+//! must join under a fresh id.
+
+use mdcs_core::gset::GSet;
+use mdcs_delta::anti_entropy::{AntiEntropyCluster, NetworkConfig};
+use mdcs_delta::causal::CausalCluster;
+use mdcs_delta::mutators::gset;
+
+fn sync_until_converged(cluster: &mut AntiEntropyCluster<GSet<i32>>) {
+    for _ in 0..50 {
+        cluster.full_sync_round();
+        cluster.retransmit_and_process();
+        if cluster.is_converged() {
+            return;
+        }
+    }
+    panic!("cluster failed to converge");
+}
+
+fn causal_sync_until_converged(cluster: &mut CausalCluster<GSet<i32>>) {
+    for _ in 0..50 {
+        cluster.full_sync_round();
+        cluster.retransmit_and_process();
+        if cluster.is_converged() {
+            return;
+        }
+    }
+    panic!("cluster failed to converge");
+}
+
+#[test]
+fn test_anti_entropy_converges_across_membership_changes() {
+    let mut cluster: AntiEntropyCluster<GSet<i32>> =
+        AntiEntropyCluster::new(4, NetworkConfig::lossy(0.2).with_seed(7));
+    let mut next = 0;
+    let mut workload = |cluster: &mut AntiEntropyCluster<GSet<i32>>, rounds: usize| {
+        for _ in 0..rounds {
+            for idx in 0..cluster.len() {
+                cluster.mutate(idx, |_| gset::insert_delta(next));
+                next += 1;
+            }
+            cluster.full_sync_round();
+        }
+    };
+
+    workload(&mut cluster, 5);
+    // replica_1 leaves with its deltas and acks still in flight; the
+    // members it hasn't reached yet are handed its last write directly
+    cluster.mutate(1, |_| gset::insert_delta(-1));
+    cluster.broadcast(1);
+    let left = cluster.remove_replica("replica_1");
+    assert!((0..3).all(|idx| cluster.replica(idx).state().contains(&-1)));
+    assert_eq!(left.id, "replica_1");
+    assert_eq!(cluster.index_of("replica_1"), None);
+    assert_eq!(cluster.index_of("replica_3"), Some(2));
+
+    // A newcomer joins while the others keep writing
+    let joined = cluster.add_replica("replica_4");
+    workload(&mut cluster, 5);
+    sync_until_converged(&mut cluster);
+    assert!(cluster.replica(joined).state().contains(&0));
+
+    // replica_1 comes back under a fresh id, its seqs starting over
+    let rejoined = cluster.add_replica("replica_1b");
+    cluster.mutate(rejoined, |_| gset::insert_delta(-2));
+    workload(&mut cluster, 5);
+    sync_until_converged(&mut cluster);
+
+    let state = cluster.replica(0).state();
+    assert!(state.contains(&-2));
+    assert!(cluster.replica(rejoined).state().contains(&0));
+    assert!((0..next).all(|n| state.contains(&n)));
+}
+
+#[test]
+fn test_departed_replica_does_not_pin_gc() {
+    let mut cluster: AntiEntropyCluster<GSet<i32>> =
+        AntiEntropyCluster::new(3, NetworkConfig::default());
+    // Replica 2 is cut off, so it never acks what replica 0 writes
+    cluster.partition(0, 2);
+    for n in 0..10 {
+        cluster.mutate(0, move |_| gset::insert_delta(n));
+    }
+    cluster.full_sync_round();
+    assert_eq!(cluster.replica(0).buffer().len(), 10);
+
+    cluster.remove_replica("replica_2");
+    assert!(cluster.replica(0).buffer().is_empty());
+
+    // Nothing is sent to it any more, retransmissions included
+    let sent = cluster.sent_count();
+    cluster.full_sync_round();
+    cluster.retransmit_and_process();
+    assert_eq!(cluster.sent_count(), sent);
+    assert!(cluster.is_converged());
+}
+
+#[test]
+#[should_panic(expected = "already a member")]
+fn test_adding_a_member_id_twice_panics() {
+    let mut cluster: AntiEntropyCluster<GSet<i32>> =
+        AntiEntropyCluster::new(2, NetworkConfig::default());
+    cluster.add_replica("replica_1");
+}
+
+#[test]
+#[should_panic(expected = "has left the cluster")]
+fn test_rejoining_under_a_departed_id_panics() {
+    let mut cluster: AntiEntropyCluster<GSet<i32>> =
+        AntiEntropyCluster::new(3, NetworkConfig::default());
+    cluster.mutate(1, |_| gset::insert_delta(1));
+    cluster.full_sync_round();
+    cluster.remove_replica("replica_1");
+    cluster.add_replica("replica_1");
+}
+
+#[test]
+#[should_panic(expected = "has left the cluster")]
+fn test_causal_rejoining_under_a_departed_id_panics() {
+    let mut cluster: CausalCluster<GSet<i32>> = CausalCluster::new(3, 0.0);
+    cluster.mutate(1, |_| gset::insert_delta(1));
+    cluster.full_sync_round();
+    cluster.remove_replica("causal_1");
+    cluster.add_replica("causal_1");
+}
+
+#[test]
+#[should_panic(expected = "not a member")]
+fn test_removing_a_non_member_panics() {
+    let mut cluster: AntiEntropyCluster<GSet<i32>> =
+        AntiEntropyCluster::new(2, NetworkConfig::default());
+    cluster.remove_replica("replica_7");
+}
+
+#[test]
+fn test_causal_converges_across_membership_changes() {
+    let mut cluster: CausalCluster<GSet<i32>> = CausalCluster::new(4, 0.2);
+    let mut next = 0;
+    let mut workload = |cluster: &mut CausalCluster<GSet<i32>>, rounds: usize| {
+        for _ in 0..rounds {
+            for idx in 0..cluster.len() {
+                cluster.mutate(idx, |_| gset::insert_delta(next));
+                next += 1;
+            }
+            cluster.full_sync_round();
+        }
+    };
+
+    workload(&mut cluster, 5);
+    // causal_2 leaves with intervals in flight
+    cluster.mutate(2, |_| gset::insert_delta(-1));
+    cluster.broadcast_intervals(2);
+    cluster.remove_replica("causal_2");
+    assert_eq!(cluster.len(), 3);
+    assert!((0..3).all(|idx| cluster.replica(idx).state().contains(&-1)));
+    assert!(cluster.replica(0).peers().all(|peer| peer != "causal_2"));
+
+    let joined = cluster.add_replica("causal_4");
+    workload(&mut cluster, 5);
+    causal_sync_until_converged(&mut cluster);
+    assert!(cluster.replica(joined).state().contains(&0));
+
+    // causal_2 comes back under a fresh id, its intervals starting over
+    let rejoined = cluster.add_replica("causal_2b");
+    for n in 0..3 {
+        cluster.mutate(rejoined, move |_| gset::insert_delta(-10 - n));
+    }
+    workload(&mut cluster, 5);
+    causal_sync_until_converged(&mut cluster);
+
+    let state = cluster.replica(0).state();
+    assert!((-12..=-10).all(|n| state.contains(&n)));
+    assert!((0..next).all(|n| state.contains(&n)));
+    assert_eq!(cluster.total_pending(), 0);
+}
@@ -0,0 +1,197 @@
+//! Replicas running different delta schema versions
+//!
+//! Every message goes through JSON as it would on the wire. A receiver
+//! that can't decode a delta NACKs it and goes on with the rest of its
+//! queue; the sender learns about the incompatibility from the NACK.
+
+use mdcs_core::gset::GSet;
+use mdcs_delta::{
+    AntiEntropyMessage, CausalMessage, CausalReplica, CodecRegistry, DecodeError, DeltaEnvelope,
+    DeltaReplica, Incompatibility, PoisonPolicy, Received,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+const TYPE_ID: &str = "gset-i32";
+
+fn insert(value: i32) -> impl FnOnce(&GSet<i32>) -> GSet<i32> {
+    move |_| {
+        let mut d = GSet::new();
+        d.insert(value);
+        d
+    }
+}
+
+/// Round-trip a message through its wire encoding
+fn wire<M: Serialize + DeserializeOwned>(msg: M) -> M {
+    serde_json::from_slice(&serde_json::to_vec(&msg).unwrap()).unwrap()
+}
+
+/// Version 2 deltas: a plain list of the inserted values
+#[derive(Serialize, Deserialize)]
+struct ListV2(Vec<i32>);
+
+impl From<ListV2> for GSet<i32> {
+    fn from(list: ListV2) -> Self {
+        let mut set = GSet::new();
+        for value in list.0 {
+            set.insert(value);
+        }
+        set
+    }
+}
+
+/// A delta from `from` to `to`, encoded at version 2
+fn v2_delta(from: &str, to: &str, values: Vec<i32>) -> AntiEntropyMessage<DeltaEnvelope> {
+    AntiEntropyMessage::Delta {
+        from: from.to_string(),
+        to: to.to_string(),
+        delta: DeltaEnvelope::seal(TYPE_ID, 2, &ListV2(values)).unwrap(),
+        first_seq: 1,
+        seq: 1,
+    }
+}
+
+#[test]
+fn test_old_receiver_nacks_new_deltas_and_keeps_going() {
+    let v1 = CodecRegistry::<GSet<i32>>::new(TYPE_ID, 1);
+    let mut a = DeltaReplica::<GSet<i32>>::new("a");
+    let mut b = DeltaReplica::<GSet<i32>>::new("b");
+    let mut c = DeltaReplica::<GSet<i32>>::new("c");
+    a.register_peer("b".to_string());
+    c.register_peer("b".to_string());
+
+    c.mutate(insert(3));
+    let (delta, seq) = c.prepare_sync("b").unwrap();
+    let from_c = AntiEntropyMessage::Delta {
+        from: "c".to_string(),
+        to: "b".to_string(),
+        delta,
+        first_seq: seq,
+        seq,
+    }
+    .seal(&v1)
+    .unwrap();
+
+    // a was upgraded and writes version 2; b's queue has a's delta first
+    let queue = vec![wire(v2_delta("a", "b", vec![1, 2])), wire(from_c)];
+    let mut replies = Vec::new();
+    for msg in queue {
+        replies.push(b.receive_wire(msg, &v1).unwrap());
+    }
+
+    let Received::Rejected { nack, error } = &replies[0] else {
+        panic!("expected a NACK, got {:?}", replies[0]);
+    };
+    assert_eq!(
+        error,
+        &DecodeError::Unsupported {
+            type_id: TYPE_ID.to_string(),
+            version: 2
+        }
+    );
+    assert!(matches!(
+        nack,
+        AntiEntropyMessage::Unsupported { from, to, version: 2, .. } if from == "b" && to == "a"
+    ));
+
+    // c's version 1 delta behind it was applied and acked
+    assert!(matches!(
+        &replies[1],
+        Received::Handled(Some(AntiEntropyMessage::Ack { to, .. })) if to == "c"
+    ));
+    assert!(b.state().contains(&3));
+    assert!(!b.state().contains(&1));
+
+    // a surfaces the incompatibility once and stops syncing to b
+    let v2 = CodecRegistry::<GSet<i32>>::new(TYPE_ID, 2);
+    let Received::Rejected { nack, .. } = replies.remove(0) else {
+        unreachable!()
+    };
+    let expected = Incompatibility {
+        peer_id: "b".to_string(),
+        type_id: TYPE_ID.to_string(),
+        version: 2,
+    };
+    assert!(matches!(
+        a.receive_wire(wire(nack.clone()), &v2).unwrap(),
+        Received::Incompatible(event) if event == expected
+    ));
+    assert_eq!(a.incompatibility("b"), Some(&expected));
+    assert!(matches!(
+        a.receive_wire(wire(nack), &v2).unwrap(),
+        Received::Handled(None)
+    ));
+}
+
+#[test]
+fn test_stop_policy_returns_the_error() {
+    let v1 = CodecRegistry::<GSet<i32>>::new(TYPE_ID, 1).with_poison_policy(PoisonPolicy::Stop);
+    let mut b = DeltaReplica::<GSet<i32>>::new("b");
+
+    let err = b
+        .receive_wire(wire(v2_delta("a", "b", vec![1])), &v1)
+        .unwrap_err();
+    assert_eq!(err.version(), 2);
+    assert!(b.state().is_empty());
+}
+
+#[test]
+fn test_downgrade_codec_reads_new_deltas() {
+    let mut v1 = CodecRegistry::<GSet<i32>>::new(TYPE_ID, 1);
+    v1.register_codec::<ListV2>(TYPE_ID, 2..=2);
+    let mut b = DeltaReplica::<GSet<i32>>::new("b");
+
+    let reply = b
+        .receive_wire(wire(v2_delta("a", "b", vec![1, 2])), &v1)
+        .unwrap();
+    assert!(matches!(
+        reply,
+        Received::Handled(Some(AntiEntropyMessage::Ack { .. }))
+    ));
+    assert!(b.state().contains(&1) && b.state().contains(&2));
+}
+
+#[test]
+fn test_causal_receiver_nacks_new_intervals() {
+    let v1 = CodecRegistry::<GSet<i32>>::new(TYPE_ID, 1);
+    let v2 = CodecRegistry::<GSet<i32>>::new(TYPE_ID, 2);
+    let mut a = CausalReplica::<GSet<i32>>::new("a");
+    let mut b = CausalReplica::<GSet<i32>>::new("b");
+    let mut c = CausalReplica::<GSet<i32>>::new("c");
+    a.register_peer("b".to_string());
+    b.register_peer("a".to_string());
+    b.register_peer("c".to_string());
+    c.register_peer("b".to_string());
+
+    a.mutate(insert(1));
+    c.mutate(insert(3));
+    let from_a = CausalMessage::DeltaInterval(a.prepare_interval("b").unwrap())
+        .seal(&v2)
+        .unwrap();
+    let from_c = CausalMessage::DeltaInterval(c.prepare_interval("b").unwrap())
+        .seal(&v1)
+        .unwrap();
+
+    let nack = match b.receive_wire(wire(from_a), &v1).unwrap() {
+        Received::Rejected { nack, .. } => nack,
+        other => panic!("expected a NACK, got {:?}", other),
+    };
+    assert!(matches!(
+        b.receive_wire(wire(from_c), &v1).unwrap(),
+        Received::Handled(Some(CausalMessage::Ack(_)))
+    ));
+    assert!(b.state().contains(&3));
+    assert!(!b.state().contains(&1));
+
+    assert!(matches!(
+        a.receive_wire(wire(nack), &v2).unwrap(),
+        Received::Incompatible(Incompatibility { ref peer_id, version: 2, .. }) if peer_id == "b"
+    ));
+    a.mutate(insert(2));
+    assert!(a.prepare_interval("b").is_none());
+
+    // Once b is upgraded, clearing the incompatibility resumes syncing
+    a.clear_incompatibility("b");
+    assert!(a.prepare_interval("b").unwrap().delta.contains(&2));
+}
@@ -0,0 +1,421 @@
+//! Bridge between causal δ-CRDT anti-entropy (Algorithm 2) and the Merkle-DAG.
+//!
+//! A [`MerkleCausalReplica`] runs a [`CausalReplica`] whose only transport is
+//! the DAG:
+//! - Each local mutation produces a delta-interval, which is written as a
+//!   [`MerkleNode`] whose parents are the current heads and whose payload is
+//!   the serialized interval.
+//! - Nodes from other replicas (via [`DAGSyncer`](crate::DAGSyncer) or the
+//!   [`Broadcaster`](crate::Broadcaster)) are fed to
+//!   [`CausalReplica::receive_interval`] in topological order, once all of
+//!   their ancestors have been applied.
+//!
+//! The DAG gives every interval a verifiable, content-addressed place in
+//! history, and gap repair (fetching missing ancestors) replaces Algorithm 2's
+//! acks and retransmissions: an interval written to the DAG counts as
+//! delivered to every peer.
+//!
+//! Snapshots and snapshot anchors are not applied; a DAG with them needs the
+//! compaction layer to bootstrap the state below them.
+
+use crate::hash::Hash;
+use crate::node::{MerkleNode, NodeBuilder, Payload};
+use crate::store::{DAGError, DAGStore, MemoryDAGStore};
+use crate::syncer::SyncSimulator;
+use mdcs_core::lattice::Lattice;
+use mdcs_delta::buffer::ReplicaId;
+use mdcs_delta::causal::{CausalReplica, DeltaInterval, IntervalAck};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Peer id under which intervals destined for the DAG are buffered.
+const LOG_PEER: &str = "merkle-log";
+
+/// Errors that can occur while bridging intervals and DAG nodes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BridgeError {
+    /// DAG store error.
+    StoreError(DAGError),
+
+    /// An interval could not be serialized.
+    EncodeFailed(String),
+
+    /// A node's payload is not a serialized delta-interval.
+    DecodeFailed(Hash, String),
+}
+
+impl std::fmt::Display for BridgeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BridgeError::StoreError(e) => write!(f, "Store error: {}", e),
+            BridgeError::EncodeFailed(e) => write!(f, "Failed to encode interval: {}", e),
+            BridgeError::DecodeFailed(h, e) => {
+                write!(f, "Failed to decode interval in {}: {}", h.short(), e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BridgeError {}
+
+impl From<DAGError> for BridgeError {
+    fn from(e: DAGError) -> Self {
+        BridgeError::StoreError(e)
+    }
+}
+
+/// A causal replica that uses a Merkle-DAG as its transport log.
+///
+/// The replica does not own a store: every operation takes the
+/// [`DAGStore`] it reads from or writes to, so the store can be shared with
+/// a [`DAGSyncer`](crate::DAGSyncer).
+#[derive(Debug, Clone)]
+pub struct MerkleCausalReplica<S: Lattice + Clone> {
+    /// The Algorithm 2 replica.
+    replica: CausalReplica<S>,
+
+    /// Nodes whose intervals have been applied (or need no applying).
+    delivered: HashSet<Hash>,
+}
+
+impl<S> MerkleCausalReplica<S>
+where
+    S: Lattice + Clone + Serialize + DeserializeOwned,
+{
+    /// Create a new replica.
+    pub fn new(id: impl Into<ReplicaId>) -> Self {
+        let mut replica = CausalReplica::new(id);
+        replica.register_peer(LOG_PEER.to_string());
+        MerkleCausalReplica {
+            replica,
+            delivered: HashSet::new(),
+        }
+    }
+
+    /// Create a replica from a DAG alone, applying every delivered node.
+    ///
+    /// `id` must not be the creator of any node in the DAG, or its later
+    /// intervals would reuse sequence numbers.
+    pub fn from_dag<T: DAGStore>(id: impl Into<ReplicaId>, store: &T) -> Result<Self, BridgeError> {
+        let mut replica = Self::new(id);
+        replica.deliver(store)?;
+        Ok(replica)
+    }
+
+    /// Replay the DAG in topological order from genesis into a fresh state.
+    pub fn materialize<T: DAGStore>(store: &T) -> Result<S, BridgeError> {
+        let replica = Self::from_dag(ReplicaId::new(), store)?;
+        Ok(replica.replica.state().clone())
+    }
+
+    /// Get the replica ID.
+    pub fn id(&self) -> &ReplicaId {
+        self.replica.id()
+    }
+
+    /// Get the current state.
+    pub fn state(&self) -> &S {
+        self.replica.state()
+    }
+
+    /// Get the underlying causal replica.
+    pub fn causal(&self) -> &CausalReplica<S> {
+        &self.replica
+    }
+
+    /// Check whether a node's interval has been applied.
+    pub fn is_delivered(&self, cid: &Hash) -> bool {
+        self.delivered.contains(cid)
+    }
+
+    /// Apply a local mutation and write its interval to the DAG.
+    ///
+    /// Returns the CID of the new node, which becomes the store's only head.
+    pub fn mutate<T, F>(&mut self, store: &mut T, mutator: F) -> Result<Hash, BridgeError>
+    where
+        T: DAGStore,
+        F: FnOnce(&S) -> S,
+    {
+        self.replica.mutate(mutator);
+
+        // The DAG delivers the interval to every peer, so nothing stays
+        // buffered for them
+        let mut logged = None;
+        for interval in self.replica.prepare_all_intervals() {
+            self.replica.receive_ack(&IntervalAck {
+                from: interval.to.clone(),
+                to: interval.from.clone(),
+                acked_seq: interval.to_seq,
+                window: None,
+            });
+            if interval.to == LOG_PEER {
+                logged = Some(interval);
+            }
+        }
+        let interval = logged.expect("the log peer is registered on creation");
+
+        let bytes =
+            serde_json::to_vec(&interval).map_err(|e| BridgeError::EncodeFailed(e.to_string()))?;
+        let node = NodeBuilder::new()
+            .with_parents(store.heads())
+            .with_payload(Payload::delta(bytes))
+            .with_timestamp(interval.to_seq)
+            .with_creator(self.replica.id().clone())
+            .build();
+
+        let cid = store.put(node)?;
+        self.delivered.insert(cid);
+        Ok(cid)
+    }
+
+    /// Store a node received from a peer and apply every node it makes ready.
+    ///
+    /// A node whose ancestors are missing is kept in the store and applied
+    /// once gap repair fetches them. Returns the number of nodes applied.
+    pub fn integrate_node<T: DAGStore>(
+        &mut self,
+        store: &mut T,
+        node: MerkleNode,
+    ) -> Result<usize, BridgeError> {
+        match store.put(node.clone()) {
+            Ok(_) => {}
+            Err(DAGError::MissingParents(_)) => {
+                store.put_unchecked(node)?;
+            }
+            Err(e) => return Err(e.into()),
+        }
+        self.deliver(store)
+    }
+
+    /// Apply every stored node whose ancestors have all been applied.
+    ///
+    /// Call this after nodes reach the store without going through
+    /// [`integrate_node`](Self::integrate_node), e.g. after
+    /// [`DAGSyncer::apply_response`](crate::DAGSyncer::apply_response).
+    /// Returns the number of nodes applied.
+    pub fn deliver<T: DAGStore>(&mut self, store: &T) -> Result<usize, BridgeError> {
+        let mut applied = 0;
+
+        for cid in store.topological_order() {
+            if self.delivered.contains(&cid) {
+                continue;
+            }
+            let Some(node) = store.get(&cid) else {
+                continue;
+            };
+            if !node.parents.iter().all(|p| self.delivered.contains(p)) {
+                continue;
+            }
+
+            match &node.payload {
+                Payload::Genesis => {}
+                Payload::Delta(bytes) => {
+                    let interval: DeltaInterval<S> = serde_json::from_slice(bytes)
+                        .map_err(|e| BridgeError::DecodeFailed(cid, e.to_string()))?;
+                    // Ancestors come first, so each creator's intervals
+                    // arrive in sequence order and are always ready
+                    self.replica.receive_interval(interval);
+                }
+                Payload::Snapshot(_) | Payload::SnapshotAnchor { .. } => continue,
+            }
+
+            self.delivered.insert(cid);
+            applied += 1;
+        }
+
+        Ok(applied)
+    }
+}
+
+/// Cluster of bridged replicas synchronizing over a [`SyncSimulator`].
+pub struct MerkleCausalCluster<S: Lattice + Clone> {
+    /// All replicas.
+    replicas: Vec<MerkleCausalReplica<S>>,
+
+    /// One DAG store per replica, all sharing a genesis node.
+    network: SyncSimulator,
+
+    /// Replica pairs that cannot reach each other, stored as (low, high).
+    partitions: HashSet<(usize, usize)>,
+}
+
+impl<S> MerkleCausalCluster<S>
+where
+    S: Lattice + Clone + Serialize + DeserializeOwned,
+{
+    /// Create a cluster with n replicas.
+    pub fn new(n: usize) -> Self {
+        let network = SyncSimulator::with_shared_genesis(n);
+        let mut replicas = Vec::with_capacity(n);
+        for i in 0..n {
+            let mut replica = MerkleCausalReplica::new(format!("replica_{}", i));
+            replica
+                .deliver(network.syncer(i).store())
+                .expect("a genesis-only DAG has nothing to decode");
+            replicas.push(replica);
+        }
+
+        MerkleCausalCluster {
+            replicas,
+            network,
+            partitions: HashSet::new(),
+        }
+    }
+
+    /// Get a replica.
+    pub fn replica(&self, idx: usize) -> &MerkleCausalReplica<S> {
+        &self.replicas[idx]
+    }
+
+    /// Get a replica's DAG store.
+    pub fn store(&self, idx: usize) -> &MemoryDAGStore {
+        self.network.syncer(idx).store()
+    }
+
+    /// Perform a mutation on a replica.
+    pub fn mutate<F>(&mut self, idx: usize, mutator: F) -> Result<Hash, BridgeError>
+    where
+        F: FnOnce(&S) -> S,
+    {
+        let store = self.network.syncer_mut(idx).store_mut();
+        self.replicas[idx].mutate(store, mutator)
+    }
+
+    /// Cut the link between two replicas.
+    pub fn partition(&mut self, a: usize, b: usize) {
+        if a != b {
+            self.partitions.insert((a.min(b), a.max(b)));
+        }
+    }
+
+    /// Restore all links.
+    pub fn heal(&mut self) {
+        self.partitions.clear();
+    }
+
+    /// Sync one replica's DAG into another's and apply what arrived.
+    ///
+    /// Does nothing if the two are partitioned.
+    pub fn sync_pair(&mut self, from: usize, to: usize) -> Result<(), BridgeError> {
+        if self.partitions.contains(&(from.min(to), from.max(to))) {
+            return Ok(());
+        }
+        self.network.sync_pair(from, to);
+        self.replicas[to].deliver(self.network.syncer(to).store())?;
+        Ok(())
+    }
+
+    /// Sync every pair of replicas once.
+    pub fn full_sync_round(&mut self) -> Result<(), BridgeError> {
+        let n = self.replicas.len();
+        for i in 0..n {
+            for j in 0..n {
+                if i != j {
+                    self.sync_pair(i, j)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check if all replicas have the same heads and the same state.
+    pub fn is_converged(&self) -> bool {
+        if !self.network.is_converged() {
+            return false;
+        }
+        match self.replicas.first() {
+            Some(first) => self.replicas.iter().all(|r| r.state() == first.state()),
+            None => true,
+        }
+    }
+
+    /// Number of replicas.
+    pub fn len(&self) -> usize {
+        self.replicas.len()
+    }
+
+    /// Check if the cluster has no replicas.
+    pub fn is_empty(&self) -> bool {
+        self.replicas.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mdcs_core::gset::GSet;
+
+    fn insert(value: &str) -> impl FnOnce(&GSet<String>) -> GSet<String> + '_ {
+        move |_| {
+            let mut d = GSet::new();
+            d.insert(value.to_string());
+            d
+        }
+    }
+
+    #[test]
+    fn test_mutate_writes_interval_node() {
+        let (mut store, genesis) = MemoryDAGStore::with_genesis("shared");
+        let mut replica = MerkleCausalReplica::new("r1");
+
+        let first = replica.mutate(&mut store, insert("a")).unwrap();
+        let second = replica.mutate(&mut store, insert("b")).unwrap();
+
+        assert_eq!(store.heads(), vec![second]);
+        assert!(store.get(&first).unwrap().has_parent(&genesis));
+        assert!(store.get(&second).unwrap().has_parent(&first));
+
+        let node = store.get(&second).unwrap();
+        assert_eq!(node.creator, "r1");
+        assert_eq!(node.timestamp, 2);
+        let interval: DeltaInterval<GSet<String>> =
+            serde_json::from_slice(node.payload.as_bytes()).unwrap();
+        assert_eq!((interval.from_seq, interval.to_seq), (1, 2));
+        assert!(interval.delta.contains(&"b".to_string()));
+        assert!(!interval.delta.contains(&"a".to_string()));
+    }
+
+    #[test]
+    fn test_out_of_order_nodes_wait_for_ancestors() {
+        let (mut source, _) = MemoryDAGStore::with_genesis("shared");
+        let mut writer = MerkleCausalReplica::new("r1");
+        let cids: Vec<_> = ["a", "b", "c"]
+            .iter()
+            .map(|v| writer.mutate(&mut source, insert(v)).unwrap())
+            .collect();
+
+        let (mut store, _) = MemoryDAGStore::with_genesis("shared");
+        let mut reader: MerkleCausalReplica<GSet<String>> = MerkleCausalReplica::new("r2");
+        reader.deliver(&store).unwrap();
+
+        // The newest nodes arrive first and wait for the gap to be repaired
+        let node = |i: usize| source.get(&cids[i]).unwrap().clone();
+        assert_eq!(reader.integrate_node(&mut store, node(2)).unwrap(), 0);
+        assert_eq!(reader.integrate_node(&mut store, node(1)).unwrap(), 0);
+        assert!(reader.state().is_empty());
+        assert!(store.missing_nodes().contains(&cids[0]));
+
+        assert_eq!(reader.integrate_node(&mut store, node(0)).unwrap(), 3);
+        assert_eq!(reader.state(), writer.state());
+        assert!(store.missing_nodes().is_empty());
+    }
+
+    #[test]
+    fn test_undecodable_payload_rejected() {
+        let (mut store, genesis) = MemoryDAGStore::with_genesis("shared");
+        let mut replica: MerkleCausalReplica<GSet<String>> = MerkleCausalReplica::new("r1");
+        let node = NodeBuilder::new()
+            .with_parent(genesis)
+            .with_payload(Payload::delta(b"not an interval".to_vec()))
+            .with_creator("r2")
+            .build();
+        let cid = node.cid;
+
+        assert!(matches!(
+            replica.integrate_node(&mut store, node),
+            Err(BridgeError::DecodeFailed(h, _)) if h == cid
+        ));
+        assert!(!replica.is_delivered(&cid));
+    }
+}
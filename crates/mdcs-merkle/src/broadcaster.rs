@@ -2,6 +2,13 @@
 //!
 //! The Broadcaster announces new DAG heads to peers, triggering
 //! the pull-based sync process via DAGSyncer.
+//!
+//! Each message goes to `fanout` random peers rather than all of them, and
+//! a message is only forwarded if it announces a head the node hadn't
+//! seen yet, so one update costs about `fanout` messages per node instead
+//! of one per pair of nodes. A head that gossip misses still spreads
+//! through periodic [`tick`](Broadcaster::tick)s, which send a random peer
+//! a digest of the heads it isn't known to have ("I have these").
 
 use crate::hash::Hash;
 use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
@@ -9,17 +16,27 @@ use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 /// Configuration for the broadcaster.
 #[derive(Clone, Debug)]
 pub struct BroadcastConfig {
-    /// Number of peers to send each message to (fanout).
+    /// Number of random peers to send each message to (fanout).
     pub fanout: usize,
 
-    /// Maximum messages to buffer before dropping old ones.
+    /// Message IDs, and heads, to remember before forgetting the least
+    /// recently seen.
     pub buffer_size: usize,
 
-    /// Whether to deduplicate messages we've already seen.
+    /// Whether to drop messages we've already seen, and not forward
+    /// messages whose heads we've all seen.
     pub deduplicate: bool,
 
     /// Time-to-live: maximum hops a message can travel.
     pub ttl: u8,
+
+    /// Most heads a [`tick`](Broadcaster::tick) digest carries, newest
+    /// first.
+    pub digest_size: usize,
+
+    /// Seed for peer selection, mixed with the replica ID so replicas
+    /// sharing a config pick differently.
+    pub seed: u64,
 }
 
 impl Default for BroadcastConfig {
@@ -29,6 +46,8 @@ impl Default for BroadcastConfig {
             buffer_size: 1000,
             deduplicate: true,
             ttl: 6,
+            digest_size: 32,
+            seed: 0,
         }
     }
 }
@@ -105,6 +124,10 @@ pub enum BroadcastEvent {
         message: BroadcastMessage,
     },
 
+    /// Send a digest of known heads to a peer, for
+    /// [`receive_digest`](Broadcaster::receive_digest).
+    SendDigest { peer: String, heads: Vec<Hash> },
+
     /// New heads received from a peer.
     HeadsReceived { from: String, heads: Vec<Hash> },
 
@@ -128,6 +151,7 @@ pub enum DropReason {
 /// The broadcaster maintains:
 /// - A set of known peers
 /// - A buffer of seen message IDs (for deduplication)
+/// - A buffer of seen heads (to suppress rebroadcasts, and for digests)
 /// - Pending outgoing messages
 pub struct Broadcaster {
     /// Our replica ID.
@@ -145,6 +169,12 @@ pub struct Broadcaster {
     /// Order of seen messages (for LRU eviction).
     seen_order: VecDeque<Hash>,
 
+    /// Heads we've seen, least recently seen first.
+    known_heads: LruSet,
+
+    /// Random state for peer selection.
+    rng_state: u64,
+
     /// Current logical timestamp.
     timestamp: u64,
 
@@ -158,22 +188,20 @@ pub struct Broadcaster {
 impl Broadcaster {
     /// Create a new broadcaster.
     pub fn new(replica_id: impl Into<String>) -> Self {
-        Broadcaster {
-            replica_id: replica_id.into(),
-            config: BroadcastConfig::default(),
-            peers: BTreeSet::new(),
-            seen: HashSet::new(),
-            seen_order: VecDeque::new(),
-            timestamp: 0,
-            pending_events: VecDeque::new(),
-            peer_heads: HashMap::new(),
-        }
+        Self::with_config(replica_id, BroadcastConfig::default())
     }
 
     /// Create a broadcaster with custom configuration.
     pub fn with_config(replica_id: impl Into<String>, config: BroadcastConfig) -> Self {
+        let replica_id = replica_id.into();
+        let id_hash = crate::hash::Hasher::hash(replica_id.as_bytes());
+        let mut id_bits = [0; 8];
+        id_bits.copy_from_slice(&id_hash.as_bytes()[..8]);
+
         Broadcaster {
-            replica_id: replica_id.into(),
+            replica_id,
+            rng_state: config.seed ^ u64::from_le_bytes(id_bits),
+            known_heads: LruSet::new(config.buffer_size),
             config,
             peers: BTreeSet::new(),
             seen: HashSet::new(),
@@ -214,6 +242,9 @@ impl Broadcaster {
 
         // Mark as seen
         self.mark_seen(message.id);
+        for head in &message.heads {
+            self.known_heads.insert(*head);
+        }
 
         // Select peers to send to
         let targets = self.select_peers(self.config.fanout);
@@ -250,6 +281,10 @@ impl Broadcaster {
 
         // Mark as seen
         self.mark_seen(message.id);
+        let mut any_new = false;
+        for head in &message.heads {
+            any_new |= self.known_heads.insert(*head);
+        }
 
         // Update peer's known heads
         self.peer_heads
@@ -257,6 +292,16 @@ impl Broadcaster {
             .or_default()
             .extend(message.heads.iter().copied());
 
+        // Every head was announced before: the syncer already has them,
+        // and the peers we forwarded them to too
+        if !any_new && self.config.deduplicate {
+            self.pending_events.push_back(BroadcastEvent::Dropped {
+                message_id: message.id,
+                reason: DropReason::Duplicate,
+            });
+            return;
+        }
+
         // Emit event for heads received
         self.pending_events
             .push_back(BroadcastEvent::HeadsReceived {
@@ -278,6 +323,56 @@ impl Broadcaster {
         }
     }
 
+    /// Run one round of anti-entropy.
+    ///
+    /// Sends a random peer a digest of up to `digest_size` of the most
+    /// recently seen heads that it isn't known to have, so heads gossip
+    /// missed still reach every replica eventually. Call this
+    /// periodically.
+    pub fn tick(&mut self) {
+        let Some(peer) = self.select_peers(1).pop() else {
+            return;
+        };
+        let has = self.peer_heads.get(&peer);
+        let heads: Vec<Hash> = self
+            .known_heads
+            .newest_first()
+            .filter(|head| !has.is_some_and(|has| has.contains(head)))
+            .take(self.config.digest_size)
+            .collect();
+        if !heads.is_empty() {
+            self.pending_events
+                .push_back(BroadcastEvent::SendDigest { peer, heads });
+        }
+    }
+
+    /// Receive a digest sent by a peer's [`tick`](Self::tick).
+    ///
+    /// Heads in it we hadn't seen are reported as
+    /// [`HeadsReceived`](BroadcastEvent::HeadsReceived) from that peer,
+    /// which has them, and go into our own digests.
+    pub fn receive_digest(&mut self, from: impl Into<String>, heads: Vec<Hash>) {
+        let from = from.into();
+        let new: Vec<Hash> = heads
+            .iter()
+            .copied()
+            .filter(|head| self.known_heads.insert(*head))
+            .collect();
+        self.peer_heads
+            .entry(from.clone())
+            .or_default()
+            .extend(heads);
+        if !new.is_empty() {
+            self.pending_events
+                .push_back(BroadcastEvent::HeadsReceived { from, heads: new });
+        }
+    }
+
+    /// Check whether we've seen a head, from gossip or a digest.
+    pub fn knows_head(&self, head: &Hash) -> bool {
+        self.known_heads.contains(head)
+    }
+
     /// Get the next pending event.
     pub fn poll_event(&mut self) -> Option<BroadcastEvent> {
         self.pending_events.pop_front()
@@ -308,20 +403,38 @@ impl Broadcaster {
     }
 
     /// Select n random peers.
-    fn select_peers(&self, n: usize) -> Vec<String> {
-        // In a real implementation, this would use random selection
-        // For determinism in tests, we just take the first n
-        self.peers.iter().take(n).cloned().collect()
+    fn select_peers(&mut self, n: usize) -> Vec<String> {
+        self.select_peers_excluding(n, &[])
     }
 
     /// Select n random peers, excluding some.
-    fn select_peers_excluding(&self, n: usize, exclude: &[&str]) -> Vec<String> {
-        self.peers
+    ///
+    /// Random but deterministic: the same seed and replica ID pick the
+    /// same peers.
+    fn select_peers_excluding(&mut self, n: usize, exclude: &[&str]) -> Vec<String> {
+        let mut candidates: Vec<String> = self
+            .peers
             .iter()
             .filter(|p| !exclude.contains(&p.as_str()))
-            .take(n)
             .cloned()
-            .collect()
+            .collect();
+        // Partial Fisher-Yates shuffle of the first n
+        let n = n.min(candidates.len());
+        for i in 0..n {
+            let j = i + (self.next_random() % (candidates.len() - i) as u64) as usize;
+            candidates.swap(i, j);
+        }
+        candidates.truncate(n);
+        candidates
+    }
+
+    /// Next value of a linear congruential generator.
+    fn next_random(&mut self) -> u64 {
+        self.rng_state = self
+            .rng_state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        self.rng_state >> 33
     }
 
     /// Get statistics about the broadcaster.
@@ -329,6 +442,7 @@ impl Broadcaster {
         BroadcastStats {
             peer_count: self.peers.len(),
             seen_messages: self.seen.len(),
+            known_heads: self.known_heads.len(),
             pending_events: self.pending_events.len(),
             timestamp: self.timestamp,
         }
@@ -340,28 +454,114 @@ impl Broadcaster {
 pub struct BroadcastStats {
     pub peer_count: usize,
     pub seen_messages: usize,
+    pub known_heads: usize,
     pub pending_events: usize,
     pub timestamp: u64,
 }
 
+/// A set of heads that forgets the least recently seen beyond a capacity.
+struct LruSet {
+    /// Each head's last use.
+    entries: HashMap<Hash, u64>,
+    /// Heads by last use; stale uses are skipped.
+    order: VecDeque<(u64, Hash)>,
+    clock: u64,
+    capacity: usize,
+}
+
+impl LruSet {
+    fn new(capacity: usize) -> Self {
+        LruSet {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            clock: 0,
+            capacity,
+        }
+    }
+
+    /// Mark a head as just seen. Returns whether it was new.
+    fn insert(&mut self, head: Hash) -> bool {
+        self.clock += 1;
+        let new = self.entries.insert(head, self.clock).is_none();
+        self.order.push_back((self.clock, head));
+        while self.entries.len() > self.capacity {
+            let Some((used, old)) = self.order.pop_front() else {
+                break;
+            };
+            if self.entries.get(&old) == Some(&used) {
+                self.entries.remove(&old);
+            }
+        }
+        // Drop stale uses once they outnumber the live ones
+        if self.order.len() > 2 * self.entries.len().max(16) {
+            let entries = &self.entries;
+            self.order
+                .retain(|(used, head)| entries.get(head) == Some(used));
+        }
+        new
+    }
+
+    fn contains(&self, head: &Hash) -> bool {
+        self.entries.contains_key(head)
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Heads, most recently seen first.
+    fn newest_first(&self) -> impl Iterator<Item = Hash> + '_ {
+        self.order
+            .iter()
+            .rev()
+            .filter(|(used, head)| self.entries.get(head) == Some(used))
+            .map(|(_, head)| *head)
+    }
+}
+
+/// Messages a [`BroadcastNetwork`] has carried.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NetworkStats {
+    /// Head announcements sent, forwarded ones included.
+    pub messages_sent: usize,
+    /// Anti-entropy digests sent.
+    pub digests_sent: usize,
+    /// Announcements delivered to a replica that had seen all their heads.
+    pub duplicate_deliveries: usize,
+}
+
 /// Simulates a network of broadcasters for testing.
 pub struct BroadcastNetwork {
     /// Broadcasters indexed by replica ID.
     broadcasters: HashMap<String, Broadcaster>,
 
     /// Message queue: (from, to, message).
-    message_queue: VecDeque<(String, String, BroadcastMessage)>,
+    message_queue: VecDeque<(String, String, Envelope)>,
+
+    /// Messages carried so far.
+    stats: NetworkStats,
+}
+
+/// What a [`BroadcastNetwork`] carries between replicas.
+enum Envelope {
+    Message(BroadcastMessage),
+    Digest(Vec<Hash>),
 }
 
 impl BroadcastNetwork {
     /// Create a fully connected network of n replicas.
     pub fn fully_connected(n: usize) -> Self {
+        Self::fully_connected_with_config(n, BroadcastConfig::default())
+    }
+
+    /// Create a fully connected network of n replicas sharing `config`.
+    pub fn fully_connected_with_config(n: usize, config: BroadcastConfig) -> Self {
         let mut broadcasters = HashMap::new();
 
         // Create broadcasters
         for i in 0..n {
             let id = format!("replica_{}", i);
-            let mut broadcaster = Broadcaster::new(&id);
+            let mut broadcaster = Broadcaster::with_config(&id, config.clone());
 
             // Add all other replicas as peers
             for j in 0..n {
@@ -376,6 +576,7 @@ impl BroadcastNetwork {
         BroadcastNetwork {
             broadcasters,
             message_queue: VecDeque::new(),
+            stats: NetworkStats::default(),
         }
     }
 
@@ -395,8 +596,20 @@ impl BroadcastNetwork {
             for event in events {
                 match event {
                     BroadcastEvent::Send { peer, message } => {
-                        self.message_queue
-                            .push_back((from.to_string(), peer, message));
+                        self.stats.messages_sent += 1;
+                        self.message_queue.push_back((
+                            from.to_string(),
+                            peer,
+                            Envelope::Message(message),
+                        ));
+                    }
+                    BroadcastEvent::SendDigest { peer, heads } => {
+                        self.stats.digests_sent += 1;
+                        self.message_queue.push_back((
+                            from.to_string(),
+                            peer,
+                            Envelope::Digest(heads),
+                        ));
                     }
                     // Put non-Send events back for later retrieval
                     other => broadcaster.pending_events.push_back(other),
@@ -407,9 +620,17 @@ impl BroadcastNetwork {
 
     /// Deliver the next message in the queue.
     pub fn deliver_one(&mut self) -> bool {
-        if let Some((from, to, message)) = self.message_queue.pop_front() {
+        if let Some((from, to, envelope)) = self.message_queue.pop_front() {
             if let Some(broadcaster) = self.broadcasters.get_mut(&to) {
-                broadcaster.receive(&from, message);
+                match envelope {
+                    Envelope::Message(message) => {
+                        if message.heads.iter().all(|h| broadcaster.knows_head(h)) {
+                            self.stats.duplicate_deliveries += 1;
+                        }
+                        broadcaster.receive(&from, message);
+                    }
+                    Envelope::Digest(heads) => broadcaster.receive_digest(&from, heads),
+                }
                 self.collect_send_events(&to);
             }
             true
@@ -448,10 +669,28 @@ impl BroadcastNetwork {
         heads
     }
 
+    /// Run a round of anti-entropy: every replica [`tick`](Broadcaster::tick)s
+    /// once. Deliver the digests with [`deliver_all`](Self::deliver_all).
+    pub fn anti_entropy_round(&mut self) {
+        let mut ids: Vec<String> = self.broadcasters.keys().cloned().collect();
+        ids.sort();
+        for id in ids {
+            if let Some(broadcaster) = self.broadcasters.get_mut(&id) {
+                broadcaster.tick();
+            }
+            self.collect_send_events(&id);
+        }
+    }
+
     /// Check how many messages are pending.
     pub fn pending_messages(&self) -> usize {
         self.message_queue.len()
     }
+
+    /// Get how many messages the network has carried.
+    pub fn stats(&self) -> &NetworkStats {
+        &self.stats
+    }
 }
 
 #[cfg(test)]
@@ -614,4 +853,138 @@ mod tests {
         // (checking that the gossip propagated)
         assert_eq!(network.pending_messages(), 0);
     }
+
+    #[test]
+    fn test_known_heads_are_not_forwarded_again() {
+        let mut broadcaster = Broadcaster::new("receiver");
+        broadcaster.add_peer("a");
+        broadcaster.add_peer("b");
+        let head = Hasher::hash(b"head");
+
+        // Two origins announce the same head
+        broadcaster.receive("a", BroadcastMessage::new("a", vec![head], 5, 1));
+        broadcaster.receive("b", BroadcastMessage::new("b", vec![head], 5, 1));
+
+        let sends = broadcaster
+            .drain_events()
+            .into_iter()
+            .filter(|e| matches!(e, BroadcastEvent::Send { .. }))
+            .count();
+        // Only the first announcement is forwarded, to b
+        assert_eq!(sends, 1);
+    }
+
+    #[test]
+    fn test_known_heads_are_bounded_lru() {
+        let config = BroadcastConfig {
+            buffer_size: 2,
+            ..Default::default()
+        };
+        let mut broadcaster = Broadcaster::with_config("test", config);
+        let [a, b, c] = [b"a", b"b", b"c"].map(|x| Hasher::hash(x));
+
+        broadcaster.broadcast(vec![a]);
+        broadcaster.broadcast(vec![b]);
+        // Seeing a again makes b the least recently seen
+        broadcaster.receive_digest("peer", vec![a]);
+        broadcaster.broadcast(vec![c]);
+
+        assert!(broadcaster.knows_head(&a));
+        assert!(!broadcaster.knows_head(&b));
+        assert!(broadcaster.knows_head(&c));
+        assert_eq!(broadcaster.stats().known_heads, 2);
+    }
+
+    #[test]
+    fn test_digest_reports_missing_heads() {
+        let mut sender = Broadcaster::new("sender");
+        let mut receiver = Broadcaster::new("receiver");
+        sender.add_peer("receiver");
+        let [old, new] = [b"old", b"new"].map(|x| Hasher::hash(x));
+        sender.broadcast(vec![old]);
+        sender.broadcast(vec![new]);
+        sender.drain_events();
+        receiver.receive_digest("other", vec![old]);
+        receiver.drain_events();
+
+        sender.tick();
+        let Some(BroadcastEvent::SendDigest { peer, heads }) = sender.poll_event() else {
+            panic!("expected a digest");
+        };
+        assert_eq!((peer.as_str(), &heads[..]), ("receiver", &[new, old][..]));
+
+        receiver.receive_digest("sender", heads);
+        assert!(matches!(
+            receiver.poll_event(),
+            Some(BroadcastEvent::HeadsReceived { from, heads }) if from == "sender" && heads == [new]
+        ));
+
+        // The receiver now has both, so the next digest is empty
+        sender.receive_digest("receiver", vec![new, old]);
+        sender.tick();
+        assert!(!sender.has_pending_events());
+    }
+
+    #[test]
+    fn test_gossip_reaches_everyone_with_few_messages() {
+        let n = 20;
+        let mut network = BroadcastNetwork::fully_connected(n);
+        let head = Hasher::hash(b"update");
+        network.broadcast("replica_0", vec![head]);
+        network.deliver_all();
+
+        let knows = |network: &BroadcastNetwork| {
+            (0..n)
+                .filter(|i| {
+                    network
+                        .broadcaster(&format!("replica_{}", i))
+                        .unwrap()
+                        .knows_head(&head)
+                })
+                .count()
+        };
+        let mut rounds = 0;
+        while knows(&network) < n {
+            assert!(rounds < 20, "anti-entropy should reach everyone");
+            network.anti_entropy_round();
+            network.deliver_all();
+            rounds += 1;
+        }
+
+        let stats = network.stats().clone();
+        // Each replica forwards a new head to at most `fanout` peers
+        assert!(stats.messages_sent <= 3 * n, "{:?}", stats);
+        assert!(
+            stats.messages_sent + stats.digests_sent < n * n / 4,
+            "{:?}",
+            stats
+        );
+    }
+
+    #[test]
+    fn test_peer_selection_is_seeded() {
+        let picks = |seed| {
+            let config = BroadcastConfig {
+                seed,
+                ..Default::default()
+            };
+            let mut broadcaster = Broadcaster::with_config("origin", config);
+            for i in 0..10 {
+                broadcaster.add_peer(format!("peer_{}", i));
+            }
+            broadcaster.broadcast(vec![Hasher::hash(b"head")]);
+            broadcaster
+                .drain_events()
+                .into_iter()
+                .filter_map(|e| match e {
+                    BroadcastEvent::Send { peer, .. } => Some(peer),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(picks(1), picks(1));
+        assert_eq!(picks(1).len(), 3);
+        assert_ne!(picks(1), picks(2));
+    }
 }
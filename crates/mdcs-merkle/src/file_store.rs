@@ -0,0 +1,399 @@
+//! File-backed DAG storage.
+//!
+//! [`FileDAGStore`] keeps one file per node in a directory, named by the
+//! node's hex CID, so the Merkle-Clock history survives a restart. Pruned
+//! nodes leave an empty marker file in their place. The heads, children
+//! and missing-node indices live in memory and are rebuilt when the store
+//! is opened.
+//!
+//! A node file is written under a temporary name, synced, then renamed
+//! into place, so after a crash each node is either fully written or
+//! absent; leftover temporary files are removed on open.
+
+use crate::hash::Hash;
+use crate::node::MerkleNode;
+use crate::store::{DAGError, DAGStore, MemoryDAGStore};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Extension of node files.
+const NODE_EXT: &str = "node";
+
+/// Extension of pruned-node markers.
+const PRUNED_EXT: &str = "pruned";
+
+/// Extension of files being written.
+const TMP_EXT: &str = "tmp";
+
+/// DAG store persisting each node to its own file.
+#[derive(Debug)]
+pub struct FileDAGStore {
+    /// Directory holding the node files.
+    dir: PathBuf,
+
+    /// In-memory copy of the DAG and its indices.
+    index: MemoryDAGStore,
+}
+
+impl FileDAGStore {
+    /// Open the store in `dir`, creating the directory if needed.
+    ///
+    /// Every node file is read and verified against its CID; one that
+    /// fails is reported as [`io::ErrorKind::InvalidData`]. A node with a
+    /// pruned marker is kept and the marker removed: the prune was
+    /// interrupted before deleting the node.
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let mut pruned = Vec::new();
+        let mut nodes = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let Some(ext) = path.extension().and_then(|ext| ext.to_str()) else {
+                continue;
+            };
+            match ext {
+                TMP_EXT => fs::remove_file(&path)?,
+                PRUNED_EXT => pruned.extend(cid_of(&path)),
+                NODE_EXT => {
+                    let node: MerkleNode = serde_json::from_slice(&fs::read(&path)?)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    if !node.verify() || cid_of(&path) != Some(node.cid) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("corrupt node file {}", path.display()),
+                        ));
+                    }
+                    nodes.push(node);
+                }
+                _ => {}
+            }
+        }
+
+        let mut store = FileDAGStore {
+            dir,
+            index: MemoryDAGStore::new(),
+        };
+        let present: HashSet<Hash> = nodes.iter().map(|node| node.cid).collect();
+        for cid in pruned {
+            if present.contains(&cid) {
+                fs::remove_file(store.path(&cid, PRUNED_EXT))?;
+            } else {
+                store.index.mark_pruned(cid);
+            }
+        }
+        for node in nodes {
+            store
+                .index
+                .put_unchecked(node)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        }
+
+        Ok(store)
+    }
+
+    /// Directory holding the node files.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Remove a node whose history is covered by a snapshot, deleting its
+    /// file.
+    ///
+    /// See [`MemoryDAGStore::prune`].
+    pub fn prune(&mut self, cid: &Hash) -> Result<(), DAGError> {
+        if !self.index.contains(cid) {
+            return Err(DAGError::NotFound(*cid));
+        }
+        // Mark first: a crash before the node file goes keeps the node
+        self.write_file(&self.path(cid, PRUNED_EXT), &[])
+            .and_then(|()| fs::remove_file(self.path(cid, NODE_EXT)))
+            .and_then(|()| self.sync_dir())
+            .map_err(storage_error)?;
+        self.index.prune(cid)
+    }
+
+    /// Store a node, writing its file before indexing it.
+    ///
+    /// With `check_parents`, a node with missing parents is rejected as by
+    /// [`DAGStore::put`]; a rejected node is never written.
+    fn persist(&mut self, node: MerkleNode, check_parents: bool) -> Result<Hash, DAGError> {
+        if !node.verify() {
+            return Err(DAGError::VerificationFailed(node.cid));
+        }
+        if self.index.contains(&node.cid) {
+            return Ok(node.cid);
+        }
+        if check_parents && !node.is_genesis() {
+            let missing = self.index.missing_parents(&node);
+            if !missing.is_empty() {
+                return Err(DAGError::MissingParents(missing));
+            }
+        }
+
+        let cid = node.cid;
+        let bytes = serde_json::to_vec(&node).map_err(storage_error)?;
+        self.write_file(&self.path(&cid, NODE_EXT), &bytes)
+            .map_err(storage_error)?;
+        let marker = self.path(&cid, PRUNED_EXT);
+        if marker.exists() {
+            fs::remove_file(marker)
+                .and_then(|()| self.sync_dir())
+                .map_err(storage_error)?;
+        }
+        if check_parents {
+            self.index.put(node)
+        } else {
+            self.index.put_unchecked(node)
+        }
+    }
+
+    /// Path of the file with `ext` for `cid`.
+    fn path(&self, cid: &Hash, ext: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", cid.to_hex(), ext))
+    }
+
+    /// Write `bytes` to `path` atomically: to a temporary file, synced,
+    /// then renamed into place.
+    fn write_file(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        let tmp = path.with_extension(TMP_EXT);
+        let mut file = File::create(&tmp)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        self.sync_dir()
+    }
+
+    /// Make renames and removals in the directory durable.
+    fn sync_dir(&self) -> io::Result<()> {
+        #[cfg(unix)]
+        File::open(&self.dir)?.sync_all()?;
+        Ok(())
+    }
+}
+
+/// The CID a node or marker file is named after.
+fn cid_of(path: &Path) -> Option<Hash> {
+    Hash::from_hex(path.file_stem()?.to_str()?)
+}
+
+fn storage_error(error: impl std::fmt::Display) -> DAGError {
+    DAGError::Storage(error.to_string())
+}
+
+impl DAGStore for FileDAGStore {
+    fn get(&self, cid: &Hash) -> Option<&MerkleNode> {
+        self.index.get(cid)
+    }
+
+    fn put(&mut self, node: MerkleNode) -> Result<Hash, DAGError> {
+        self.persist(node, true)
+    }
+
+    fn put_unchecked(&mut self, node: MerkleNode) -> Result<Hash, DAGError> {
+        self.persist(node, false)
+    }
+
+    fn heads(&self) -> Vec<Hash> {
+        self.index.heads()
+    }
+
+    fn contains(&self, cid: &Hash) -> bool {
+        self.index.contains(cid)
+    }
+
+    fn ancestors(&self, cid: &Hash) -> HashSet<Hash> {
+        self.index.ancestors(cid)
+    }
+
+    fn children(&self, cid: &Hash) -> Vec<Hash> {
+        self.index.children(cid)
+    }
+
+    fn topological_order(&self) -> Vec<Hash> {
+        self.index.topological_order()
+    }
+
+    fn missing_nodes(&self) -> HashSet<Hash> {
+        self.index.missing_nodes()
+    }
+
+    fn pruned_boundary(&self) -> HashSet<Hash> {
+        self.index.pruned_boundary()
+    }
+
+    fn len(&self) -> usize {
+        self.index.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::{NodeBuilder, Payload};
+
+    /// A fresh directory under the system temp dir, removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "mdcs_file_store_{}_{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A DAG of `count` nodes over three replicas that merge every few
+    /// steps.
+    fn history(count: u64) -> Vec<MerkleNode> {
+        let genesis = NodeBuilder::genesis("r0");
+        let mut tips = [genesis.cid; 3];
+        let mut nodes = vec![genesis];
+        for i in 1..count {
+            let replica = (i % 3) as usize;
+            let parents = if i % 7 == 0 {
+                tips.to_vec()
+            } else {
+                vec![tips[replica]]
+            };
+            let node = NodeBuilder::new()
+                .with_parents(parents)
+                .with_payload(Payload::delta(i.to_le_bytes().to_vec()))
+                .with_timestamp(i)
+                .with_creator(format!("r{}", replica))
+                .build();
+            tips[replica] = node.cid;
+            nodes.push(node);
+        }
+        nodes
+    }
+
+    fn node_files(dir: &Path) -> usize {
+        fs::read_dir(dir)
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().extension() == Some(NODE_EXT.as_ref()))
+            .count()
+    }
+
+    #[test]
+    fn test_reopened_store_matches_memory_store() {
+        let tmp = TempDir::new("reopen");
+        let nodes = history(300);
+        let mut memory = MemoryDAGStore::new();
+        {
+            let mut store = FileDAGStore::open(&tmp.0).unwrap();
+            for node in &nodes {
+                assert_eq!(store.put(node.clone()), memory.put(node.clone()));
+            }
+        }
+
+        let store = FileDAGStore::open(&tmp.0).unwrap();
+        assert_eq!(store.len(), 300);
+        assert_eq!(store.heads(), memory.heads());
+        assert_eq!(store.missing_nodes(), memory.missing_nodes());
+        for node in &nodes {
+            assert_eq!(store.ancestors(&node.cid), memory.ancestors(&node.cid));
+            let mut children = store.children(&node.cid);
+            let mut expected = memory.children(&node.cid);
+            children.sort();
+            expected.sort();
+            assert_eq!(children, expected);
+        }
+        let order = store.topological_order();
+        assert_eq!(order.len(), 300);
+        let position = |cid: &Hash| order.iter().position(|c| c == cid).unwrap();
+        for node in &nodes {
+            assert!(node
+                .parents
+                .iter()
+                .all(|p| position(p) < position(&node.cid)));
+        }
+    }
+
+    #[test]
+    fn test_out_of_order_nodes_survive_reopen() {
+        let tmp = TempDir::new("unchecked");
+        let nodes = history(20);
+        {
+            let mut store = FileDAGStore::open(&tmp.0).unwrap();
+            // A gap: node 5 never arrives
+            for node in nodes.iter().rev().filter(|n| n.cid != nodes[5].cid) {
+                store.put_unchecked(node.clone()).unwrap();
+            }
+            // A rejected node is not written
+            assert!(matches!(
+                store.put(history(40)[39].clone()),
+                Err(DAGError::MissingParents(_))
+            ));
+        }
+
+        let store = FileDAGStore::open(&tmp.0).unwrap();
+        assert_eq!(store.len(), 19);
+        assert_eq!(store.missing_nodes(), HashSet::from([nodes[5].cid]));
+        assert_eq!(node_files(&tmp.0), 19);
+    }
+
+    #[test]
+    fn test_pruning_reclaims_files() {
+        let tmp = TempDir::new("prune");
+        let nodes = history(30);
+        let mut store = FileDAGStore::open(&tmp.0).unwrap();
+        for node in &nodes {
+            store.put(node.clone()).unwrap();
+        }
+        for node in &nodes[..10] {
+            store.prune(&node.cid).unwrap();
+        }
+        assert_eq!(node_files(&tmp.0), 20);
+        drop(store);
+
+        let store = FileDAGStore::open(&tmp.0).unwrap();
+        assert_eq!(store.len(), 20);
+        assert_eq!(store.pruned_boundary().len(), 10);
+        assert!(store.missing_nodes().is_empty());
+        let head = store.heads()[0];
+        assert!(store.ancestors(&head).iter().all(|cid| store.contains(cid)));
+    }
+
+    #[test]
+    fn test_interrupted_writes_are_discarded() {
+        let tmp = TempDir::new("crash");
+        let nodes = history(5);
+        {
+            let mut store = FileDAGStore::open(&tmp.0).unwrap();
+            for node in &nodes[..4] {
+                store.put(node.clone()).unwrap();
+            }
+            // A crash while writing node 4, and one in the middle of a prune
+            let path = store.path(&nodes[4].cid, NODE_EXT);
+            fs::write(path.with_extension(TMP_EXT), b"{\"cid\":").unwrap();
+            fs::write(store.path(&nodes[3].cid, PRUNED_EXT), b"").unwrap();
+        }
+
+        let mut store = FileDAGStore::open(&tmp.0).unwrap();
+        assert_eq!(store.len(), 4);
+        assert!(store.heads().contains(&nodes[3].cid));
+        assert_eq!(fs::read_dir(&tmp.0).unwrap().count(), 4);
+        store.put(nodes[4].clone()).unwrap();
+        assert!(store.heads().contains(&nodes[4].cid));
+
+        // A torn node file is reported, not silently dropped
+        fs::write(store.path(&nodes[2].cid, NODE_EXT), b"{\"cid\":").unwrap();
+        drop(store);
+        let err = FileDAGStore::open(&tmp.0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -3,10 +3,11 @@
 //! Merkle-Clock DAG implementation for the MDCS (Merkle-Delta CRDT Store).
 //!
 //! This crate provides:
-//! - Content-addressed storage for causal history
+//! - Content-addressed storage for causal history, in memory or on disk
 //! - Merkle-DAG structure for verifiable, tamper-proof history
 //! - DAGSyncer for gap-repair and synchronization
 //! - Broadcaster for gossip-based head dissemination
+//! - A bridge running causal δ-CRDT anti-entropy over the DAG
 //!
 //! ## Architecture
 //!
@@ -41,14 +42,23 @@
 //! assert_eq!(store.heads(), vec![child_cid]);
 //! ```
 
+mod bridge;
 mod broadcaster;
+mod file_store;
 mod hash;
 mod node;
 mod store;
 mod syncer;
 
-pub use broadcaster::{BroadcastConfig, BroadcastMessage, BroadcastNetwork, Broadcaster};
+pub use bridge::{BridgeError, MerkleCausalCluster, MerkleCausalReplica};
+pub use broadcaster::{
+    BroadcastConfig, BroadcastMessage, BroadcastNetwork, Broadcaster, NetworkStats,
+};
+pub use file_store::FileDAGStore;
 pub use hash::{Hash, Hasher};
 pub use node::{MerkleNode, NodeBuilder, Payload};
 pub use store::{DAGError, DAGStore, MemoryDAGStore};
-pub use syncer::{DAGSyncer, SyncError, SyncRequest, SyncResponse, SyncSimulator};
+pub use syncer::{
+    DAGSyncer, SyncBatch, SyncConfig, SyncError, SyncPipeline, SyncRequest, SyncResponse,
+    SyncSimulator,
+};
@@ -3,7 +3,7 @@
 //! Each node in the Merkle-DAG contains:
 //! - A content identifier (CID) computed from its contents
 //! - References to parent nodes (causal predecessors)
-//! - A payload (delta-group or snapshot)
+//! - A payload (delta-group, snapshot or snapshot anchor)
 //! - A logical timestamp
 
 use crate::hash::{Hash, Hasher};
@@ -22,6 +22,18 @@ pub enum Payload {
     /// A snapshot of the full state at a point in time.
     /// Used for compaction and bootstrapping new replicas.
     Snapshot(Vec<u8>),
+
+    /// An anchor for a snapshot held by the compaction layer.
+    ///
+    /// The snapshot content stays in the snapshot store; the node only
+    /// anchors and orders it. Its parents are the heads the snapshot covers,
+    /// so a bootstrapping replica can stop fetching history at this node.
+    SnapshotAnchor {
+        /// ID of the snapshot in the snapshot store.
+        snapshot_hash: Hash,
+        /// The serialized version vector the snapshot covers.
+        version_vector_bytes: Vec<u8>,
+    },
 }
 
 impl Payload {
@@ -40,6 +52,14 @@ impl Payload {
         Payload::Snapshot(data)
     }
 
+    /// Create a snapshot anchor payload.
+    pub fn snapshot_anchor(snapshot_hash: Hash, version_vector_bytes: Vec<u8>) -> Self {
+        Payload::SnapshotAnchor {
+            snapshot_hash,
+            version_vector_bytes,
+        }
+    }
+
     /// Check if this is a genesis payload.
     pub fn is_genesis(&self) -> bool {
         matches!(self, Payload::Genesis)
@@ -55,12 +75,30 @@ impl Payload {
         matches!(self, Payload::Snapshot(_))
     }
 
-    /// Get the payload data as bytes (returns empty slice for Genesis).
+    /// Check if this is a snapshot anchor payload.
+    pub fn is_snapshot_anchor(&self) -> bool {
+        matches!(self, Payload::SnapshotAnchor { .. })
+    }
+
+    /// Get the anchored snapshot's ID, if this is a snapshot anchor payload.
+    pub fn snapshot_hash(&self) -> Option<&Hash> {
+        match self {
+            Payload::SnapshotAnchor { snapshot_hash, .. } => Some(snapshot_hash),
+            _ => None,
+        }
+    }
+
+    /// Get the payload data as bytes (returns empty slice for Genesis and
+    /// the version vector bytes for SnapshotAnchor).
     pub fn as_bytes(&self) -> &[u8] {
         match self {
             Payload::Genesis => &[],
             Payload::Delta(data) => data,
             Payload::Snapshot(data) => data,
+            Payload::SnapshotAnchor {
+                version_vector_bytes,
+                ..
+            } => version_vector_bytes,
         }
     }
 
@@ -70,6 +108,7 @@ impl Payload {
             Payload::Genesis => 0,
             Payload::Delta(_) => 1,
             Payload::Snapshot(_) => 2,
+            Payload::SnapshotAnchor { .. } => 3,
         }
     }
 }
@@ -132,6 +171,9 @@ impl MerkleNode {
 
         // Hash the payload type and data
         hasher.update(&[payload.type_byte()]);
+        if let Some(snapshot_hash) = payload.snapshot_hash() {
+            hasher.update(snapshot_hash.as_bytes());
+        }
         hasher.update(payload.as_bytes());
 
         // Hash the timestamp
@@ -337,6 +379,34 @@ mod tests {
         assert!(snapshot.verify());
     }
 
+    #[test]
+    fn test_snapshot_anchor_node() {
+        let genesis = NodeBuilder::genesis("replica_1");
+
+        let anchor = NodeBuilder::new()
+            .with_parent(genesis.cid)
+            .with_payload(Payload::snapshot_anchor(
+                Hasher::hash(b"full state"),
+                b"vv".to_vec(),
+            ))
+            .with_timestamp(100)
+            .with_creator("replica_1")
+            .build();
+
+        assert!(anchor.payload.is_snapshot_anchor());
+        assert!(!anchor.payload.is_snapshot());
+        assert_eq!(
+            anchor.payload.snapshot_hash(),
+            Some(&Hasher::hash(b"full state"))
+        );
+        assert!(anchor.verify());
+
+        // The anchored snapshot is part of the CID
+        let mut tampered = anchor.clone();
+        tampered.payload = Payload::snapshot_anchor(Hasher::hash(b"other state"), b"vv".to_vec());
+        assert!(!tampered.verify());
+    }
+
     #[test]
     fn test_verify_tampered_node() {
         let mut node = NodeBuilder::new()
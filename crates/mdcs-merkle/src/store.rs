@@ -22,6 +22,9 @@ pub enum DAGError {
 
     /// Duplicate node (already exists).
     Duplicate(Hash),
+
+    /// The backing storage failed.
+    Storage(String),
 }
 
 impl std::fmt::Display for DAGError {
@@ -37,6 +40,7 @@ impl std::fmt::Display for DAGError {
                 )
             }
             DAGError::Duplicate(h) => write!(f, "Duplicate node: {}", h.short()),
+            DAGError::Storage(e) => write!(f, "Storage error: {}", e),
         }
     }
 }
@@ -75,8 +79,20 @@ pub trait DAGStore {
     fn topological_order(&self) -> Vec<Hash>;
 
     /// Get nodes that are missing (referenced but not present).
+    ///
+    /// Nodes in the [pruned boundary](DAGStore::pruned_boundary) are not
+    /// missing.
     fn missing_nodes(&self) -> HashSet<Hash>;
 
+    /// Get nodes that were deliberately removed from the store.
+    ///
+    /// Unlike missing nodes, these are known to have existed and are not
+    /// fetched again: their history is covered by a snapshot. Traversals
+    /// stop at them.
+    fn pruned_boundary(&self) -> HashSet<Hash> {
+        HashSet::new()
+    }
+
     /// Get the total number of nodes.
     fn len(&self) -> usize;
 
@@ -100,6 +116,10 @@ pub struct MemoryDAGStore {
 
     /// Referenced but missing nodes.
     missing: HashSet<Hash>,
+
+    /// Nodes removed by pruning.
+    #[serde(default)]
+    pruned: HashSet<Hash>,
 }
 
 impl MemoryDAGStore {
@@ -110,6 +130,7 @@ impl MemoryDAGStore {
             heads: HashSet::new(),
             children_index: HashMap::new(),
             missing: HashSet::new(),
+            pruned: HashSet::new(),
         }
     }
 
@@ -121,10 +142,49 @@ impl MemoryDAGStore {
         (store, cid)
     }
 
+    /// Remove a node whose history is covered by a snapshot.
+    ///
+    /// The node is recorded in the [pruned boundary](DAGStore::pruned_boundary),
+    /// so children referring to it are not left with a missing parent.
+    pub fn prune(&mut self, cid: &Hash) -> Result<(), DAGError> {
+        let node = self.nodes.remove(cid).ok_or(DAGError::NotFound(*cid))?;
+
+        self.heads.remove(cid);
+        for parent in &node.parents {
+            if let Some(children) = self.children_index.get_mut(parent) {
+                children.remove(cid);
+                if children.is_empty() {
+                    self.children_index.remove(parent);
+                }
+            }
+        }
+        self.pruned.insert(*cid);
+
+        Ok(())
+    }
+
+    /// Record a node as pruned without holding it, e.g. when rebuilding
+    /// the index of a persistent store.
+    pub(crate) fn mark_pruned(&mut self, cid: Hash) {
+        self.pruned.insert(cid);
+    }
+
+    /// Parents of `node` that are neither stored nor pruned.
+    pub(crate) fn missing_parents(&self, node: &MerkleNode) -> Vec<Hash> {
+        node.parents
+            .iter()
+            .filter(|p| !self.nodes.contains_key(p) && !self.pruned.contains(p))
+            .copied()
+            .collect()
+    }
+
     /// Update the heads set after adding a node.
     fn update_heads(&mut self, node: &MerkleNode) {
-        // The new node becomes a head
-        self.heads.insert(node.cid);
+        // The new node becomes a head, unless children already refer to it
+        // (a pruned node coming back under its surviving children)
+        if !self.children_index.contains_key(&node.cid) {
+            self.heads.insert(node.cid);
+        }
 
         // Its parents are no longer heads
         for parent in &node.parents {
@@ -206,13 +266,7 @@ impl DAGStore for MemoryDAGStore {
 
         // Check for missing parents (unless this is a genesis node)
         if !node.is_genesis() {
-            let missing: Vec<Hash> = node
-                .parents
-                .iter()
-                .filter(|p| !self.nodes.contains_key(p))
-                .copied()
-                .collect();
-
+            let missing = self.missing_parents(&node);
             if !missing.is_empty() {
                 return Err(DAGError::MissingParents(missing));
             }
@@ -224,8 +278,9 @@ impl DAGStore for MemoryDAGStore {
         self.update_heads(&node);
         self.update_children_index(&node);
 
-        // Remove from missing if it was there
+        // Remove from missing or pruned if it was there
         self.missing.remove(&cid);
+        self.pruned.remove(&cid);
 
         // Store the node
         self.nodes.insert(cid, node);
@@ -248,7 +303,7 @@ impl DAGStore for MemoryDAGStore {
 
         // Track missing parents
         for parent in &node.parents {
-            if !self.nodes.contains_key(parent) {
+            if !self.nodes.contains_key(parent) && !self.pruned.contains(parent) {
                 self.missing.insert(*parent);
             }
         }
@@ -266,8 +321,9 @@ impl DAGStore for MemoryDAGStore {
             self.heads.remove(parent);
         }
 
-        // Remove from missing if it was there
+        // Remove from missing or pruned if it was there
         self.missing.remove(&cid);
+        self.pruned.remove(&cid);
 
         // Store the node
         self.nodes.insert(cid, node);
@@ -294,6 +350,9 @@ impl DAGStore for MemoryDAGStore {
         }
 
         while let Some(current) = queue.pop_front() {
+            if self.pruned.contains(&current) {
+                continue;
+            }
             if result.insert(current) {
                 if let Some(node) = self.nodes.get(&current) {
                     queue.extend(node.parents.iter().copied());
@@ -354,6 +413,10 @@ impl DAGStore for MemoryDAGStore {
         self.missing.clone()
     }
 
+    fn pruned_boundary(&self) -> HashSet<Hash> {
+        self.pruned.clone()
+    }
+
     fn len(&self) -> usize {
         self.nodes.len()
     }
@@ -570,6 +633,53 @@ mod tests {
         assert!(children.contains(&cid2));
     }
 
+    #[test]
+    fn test_prune_records_boundary() {
+        let (mut store, genesis) = MemoryDAGStore::with_genesis("r1");
+
+        let node1 = NodeBuilder::new()
+            .with_parent(genesis)
+            .with_payload(Payload::delta(vec![1]))
+            .with_timestamp(1)
+            .with_creator("r1")
+            .build();
+        let cid1 = store.put(node1).unwrap();
+
+        let node2 = NodeBuilder::new()
+            .with_parent(cid1)
+            .with_payload(Payload::delta(vec![2]))
+            .with_timestamp(2)
+            .with_creator("r1")
+            .build();
+        let cid2 = store.put(node2).unwrap();
+
+        store.prune(&genesis).unwrap();
+        assert_eq!(store.prune(&genesis), Err(DAGError::NotFound(genesis)));
+
+        assert_eq!(store.pruned_boundary(), HashSet::from([genesis]));
+        assert!(store.missing_nodes().is_empty());
+        assert_eq!(store.ancestors(&cid2), HashSet::from([cid1]));
+        assert_eq!(store.topological_order(), vec![cid1, cid2]);
+        assert_eq!(store.heads(), vec![cid2]);
+
+        // Children of pruned nodes are neither rejected nor left missing
+        let sibling = NodeBuilder::new()
+            .with_parent(genesis)
+            .with_payload(Payload::delta(vec![3]))
+            .with_timestamp(1)
+            .with_creator("r2")
+            .build();
+        store.put(sibling.clone()).unwrap();
+        store.put_unchecked(sibling).unwrap();
+        assert!(store.missing_nodes().is_empty());
+
+        // A pruned node stored again leaves the boundary but is not a head
+        store.put(NodeBuilder::genesis("r1")).unwrap();
+        assert!(store.pruned_boundary().is_empty());
+        assert_eq!(store.heads().len(), 2);
+        assert!(!store.heads().contains(&genesis));
+    }
+
     #[test]
     fn test_dag_stats() {
         let (mut store, _genesis) = MemoryDAGStore::with_genesis("r1");
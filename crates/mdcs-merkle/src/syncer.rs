@@ -4,11 +4,33 @@
 //! 1. Discovering missing nodes via head comparison
 //! 2. Fetching missing nodes from peers recursively
 //! 3. Handling concurrent heads (multi-root scenarios)
+//!
+//! Replicas that can bootstrap from snapshots set
+//! [`SyncConfig::snapshot_bootstrap`]. Their requests ask peers to stop at
+//! snapshot anchor nodes, and the history below an anchor is never fetched:
+//! the anchored snapshot is obtained from the compaction layer instead.
+//!
+//! Nodes in a store's [pruned boundary](DAGStore::pruned_boundary) are
+//! never requested. A peer asked for history it pruned reports it as
+//! pruned, and a requester that still needs that history fails with
+//! [`SyncError::SnapshotRequired`]: it must bootstrap from a snapshot.
+//!
+//! # Batched gap repair
+//!
+//! A request that carries the requester's heads is answered with the
+//! nodes it is missing in topological order, so each batch can be stored
+//! as it arrives. When the gap is larger than one response, the response
+//! lines up the rest as [`SyncBatch`]es: each names the nodes it covers and
+//! the frontier the requester will have once the earlier batches are in,
+//! so the batches can be fetched concurrently. A [`SyncPipeline`] keeps up
+//! to [`SyncConfig::max_in_flight`] of them outstanding and holds on to
+//! batches that arrive before the ones they build on.
 
 use crate::hash::Hash;
 use crate::node::MerkleNode;
 use crate::store::{DAGError, DAGStore};
 use std::collections::{HashSet, VecDeque};
+use std::mem;
 
 /// Errors that can occur during synchronization.
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -30,6 +52,12 @@ pub enum SyncError {
 
     /// Maximum depth exceeded during traversal.
     MaxDepthExceeded,
+
+    /// The snapshot behind a snapshot anchor could not be obtained.
+    SnapshotUnavailable(Hash),
+
+    /// The peer pruned history we need; bootstrap from a snapshot instead.
+    SnapshotRequired(Vec<Hash>),
 }
 
 impl std::fmt::Display for SyncError {
@@ -41,6 +69,16 @@ impl std::fmt::Display for SyncError {
             SyncError::NoPeers => write!(f, "No peers available"),
             SyncError::Timeout => write!(f, "Sync timeout"),
             SyncError::MaxDepthExceeded => write!(f, "Maximum traversal depth exceeded"),
+            SyncError::SnapshotUnavailable(h) => {
+                write!(f, "Snapshot unavailable for anchor: {}", h.short())
+            }
+            SyncError::SnapshotRequired(pruned) => {
+                write!(
+                    f,
+                    "Peer pruned needed history, bootstrap from a snapshot: {:?}",
+                    pruned.iter().map(|h| h.short()).collect::<Vec<_>>()
+                )
+            }
         }
     }
 }
@@ -64,6 +102,10 @@ pub struct SyncRequest {
 
     /// Maximum number of nodes to return.
     pub limit: Option<usize>,
+
+    /// Whether the requester bootstraps from snapshots, so history below
+    /// snapshot anchors need not be sent.
+    pub snapshot_bootstrap: bool,
 }
 
 impl SyncRequest {
@@ -73,6 +115,7 @@ impl SyncRequest {
             want: cids,
             have: Vec::new(),
             limit: None,
+            snapshot_bootstrap: false,
         }
     }
 
@@ -87,6 +130,12 @@ impl SyncRequest {
         self.limit = Some(limit);
         self
     }
+
+    /// Indicate whether the requester can bootstrap from snapshots.
+    pub fn with_snapshot_bootstrap(mut self, enabled: bool) -> Self {
+        self.snapshot_bootstrap = enabled;
+        self
+    }
 }
 
 /// Response containing nodes from a peer.
@@ -100,6 +149,23 @@ pub struct SyncResponse {
 
     /// Peer's current heads.
     pub heads: Vec<Hash>,
+
+    /// Requested or referenced nodes the peer pruned; their history is
+    /// only available from a snapshot.
+    pub pruned: Vec<Hash>,
+
+    /// Follow-up batches covering `more`, in topological order.
+    pub batches: Vec<SyncBatch>,
+}
+
+/// A batch of nodes a peer has lined up for a follow-up request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyncBatch {
+    /// The batch's heads; the batch is their ancestry not covered by `have`.
+    pub want: Vec<Hash>,
+
+    /// The requester's heads once the earlier batches are stored.
+    pub have: Vec<Hash>,
 }
 
 impl SyncResponse {
@@ -109,6 +175,8 @@ impl SyncResponse {
             nodes: Vec::new(),
             more: Vec::new(),
             heads: Vec::new(),
+            pruned: Vec::new(),
+            batches: Vec::new(),
         }
     }
 
@@ -118,6 +186,8 @@ impl SyncResponse {
             nodes,
             more: Vec::new(),
             heads: Vec::new(),
+            pruned: Vec::new(),
+            batches: Vec::new(),
         }
     }
 }
@@ -131,8 +201,19 @@ pub struct SyncConfig {
     /// Maximum number of nodes to fetch in a single request.
     pub batch_size: usize,
 
+    /// Maximum number of nodes to send in a single response, whatever the
+    /// request's limit.
+    pub max_nodes_per_response: usize,
+
+    /// Maximum number of requests a [`SyncPipeline`] keeps outstanding.
+    pub max_in_flight: usize,
+
     /// Whether to verify nodes before storing.
     pub verify_nodes: bool,
+
+    /// Whether to bootstrap from snapshot anchors instead of fetching the
+    /// history below them.
+    pub snapshot_bootstrap: bool,
 }
 
 impl Default for SyncConfig {
@@ -140,7 +221,10 @@ impl Default for SyncConfig {
         SyncConfig {
             max_depth: 1000,
             batch_size: 100,
+            max_nodes_per_response: 1000,
+            max_in_flight: 4,
             verify_nodes: true,
+            snapshot_bootstrap: false,
         }
     }
 }
@@ -188,10 +272,12 @@ impl<S: DAGStore> DAGSyncer<S> {
         self.store.heads()
     }
 
-    /// Determine which of the given CIDs we need (don't have locally).
+    /// Determine which of the given CIDs we need (neither have locally nor
+    /// pruned).
     pub fn need(&self, cids: &[Hash]) -> Vec<Hash> {
+        let boundary = self.store.pruned_boundary();
         cids.iter()
-            .filter(|cid| !self.store.contains(cid))
+            .filter(|cid| !self.store.contains(cid) && !boundary.contains(cid))
             .copied()
             .collect()
     }
@@ -202,63 +288,257 @@ impl<S: DAGStore> DAGSyncer<S> {
         SyncRequest::want(need)
             .with_heads(self.heads())
             .with_limit(self.config.batch_size)
+            .with_snapshot_bootstrap(self.config.snapshot_bootstrap)
     }
 
     /// Handle an incoming sync request from a peer.
+    ///
+    /// A request without heads gets the nodes it wants. A request with
+    /// heads (or one bootstrapping from snapshots) gets the missing
+    /// ancestry of the nodes it wants, or everything it is missing if it
+    /// wants nothing in particular, in topological order. Nodes beyond the
+    /// limit are listed in `more` and lined up as `batches`.
     pub fn handle_request(&self, request: &SyncRequest) -> SyncResponse {
-        let mut nodes = Vec::new();
-        let mut more = Vec::new();
-        let limit = request.limit.unwrap_or(self.config.batch_size);
-
-        // Collect requested nodes
+        let limit = request
+            .limit
+            .unwrap_or(self.config.batch_size)
+            .min(self.config.max_nodes_per_response);
+        let boundary = self.store.pruned_boundary();
+        let mut pruned = Vec::new();
         for cid in &request.want {
-            if let Some(node) = self.store.get(cid) {
-                if nodes.len() < limit {
-                    nodes.push(node.clone());
-                } else {
-                    more.push(*cid);
+            if !self.store.contains(cid) && boundary.contains(cid) && !pruned.contains(cid) {
+                pruned.push(*cid);
+            }
+        }
+
+        let mut peer_has: HashSet<_> = self.collect_known(&request.have);
+
+        let mut response = if request.have.is_empty() && !request.snapshot_bootstrap {
+            // Without heads we can't tell what the peer is missing
+            let mut response = SyncResponse::empty();
+            for cid in &request.want {
+                if let Some(node) = self.store.get(cid) {
+                    if response.nodes.len() < limit {
+                        response.nodes.push(node.clone());
+                    } else {
+                        response.more.push(*cid);
+                    }
                 }
             }
+            response
+        } else {
+            self.missing_batches(request, &mut peer_has, &boundary, limit)
+        };
+
+        // Parents we pruned can't be sent: report them instead
+        for parent in response.nodes.iter().flat_map(|n| &n.parents) {
+            if boundary.contains(parent) && !peer_has.contains(parent) && !pruned.contains(parent) {
+                pruned.push(*parent);
+            }
         }
 
-        // If peer provided their heads, we can proactively send nodes they're missing
-        if !request.have.is_empty() && nodes.len() < limit {
-            let peer_has: HashSet<_> = self.collect_known(&request.have);
+        response.heads = self.heads();
+        response.pruned = pruned;
+        response
+    }
 
-            // Find nodes we have that the peer doesn't
-            for cid in self.store.topological_order() {
-                if !peer_has.contains(&cid) {
+    /// Collect the nodes a peer with the given knowledge is missing, in
+    /// topological order: the first `limit` as nodes, the rest in batches.
+    fn missing_batches(
+        &self,
+        request: &SyncRequest,
+        peer_has: &mut HashSet<Hash>,
+        boundary: &HashSet<Hash>,
+        limit: usize,
+    ) -> SyncResponse {
+        // A bootstrapping peer takes the history below snapshot anchors
+        // from the snapshot: send nothing below an anchor and treat the
+        // anchor's parents as known
+        let reachable = request.snapshot_bootstrap.then(|| {
+            let reachable = self.reachable_above_anchors();
+            for node in reachable.iter().filter_map(|cid| self.store.get(cid)) {
+                if node.payload.is_snapshot_anchor() {
+                    peer_has.extend(node.parents.iter().copied());
+                }
+            }
+            reachable
+        });
+        let sendable = |cid: &Hash| {
+            !peer_has.contains(cid)
+                && !boundary.contains(cid)
+                && reachable.as_ref().is_none_or(|r| r.contains(cid))
+                && self.store.contains(cid)
+        };
+
+        // The missing ancestry of what the peer wants, or everything it is
+        // missing
+        let candidates: HashSet<Hash> = if request.want.is_empty() {
+            self.store
+                .topological_order()
+                .into_iter()
+                .filter(|cid| sendable(cid))
+                .collect()
+        } else {
+            let mut candidates = HashSet::new();
+            let mut queue: VecDeque<Hash> = request.want.iter().copied().collect();
+            while let Some(cid) = queue.pop_front() {
+                if sendable(&cid) && candidates.insert(cid) {
                     if let Some(node) = self.store.get(&cid) {
-                        if nodes.len() < limit {
-                            // Check if peer has the parents
-                            let has_parents = node
-                                .parents
-                                .iter()
-                                .all(|p| peer_has.contains(p) || nodes.iter().any(|n| n.cid == *p));
-
-                            if has_parents && !nodes.iter().any(|n| n.cid == cid) {
-                                nodes.push(node.clone());
-                            }
-                        } else {
-                            more.push(cid);
-                        }
+                        queue.extend(node.parents.iter().copied());
                     }
                 }
             }
+            candidates
+        };
+
+        // Skip nodes the peer couldn't store for lack of a parent we don't
+        // have either, unless it asked for them
+        let mut missing: Vec<&MerkleNode> = Vec::new();
+        let mut included = HashSet::new();
+        for cid in self.store.topological_order() {
+            if !candidates.contains(&cid) {
+                continue;
+            }
+            let Some(node) = self.store.get(&cid) else {
+                continue;
+            };
+            let has_parents = node
+                .parents
+                .iter()
+                .all(|p| peer_has.contains(p) || boundary.contains(p) || included.contains(p));
+            if has_parents || request.want.contains(&cid) {
+                included.insert(cid);
+                missing.push(node);
+            }
         }
 
-        SyncResponse {
-            nodes,
-            more,
-            heads: self.heads(),
+        let mut response = SyncResponse::empty();
+        let mut rest = missing.into_iter();
+        response.nodes = rest.by_ref().take(limit).cloned().collect();
+        let rest: Vec<&MerkleNode> = rest.collect();
+        response.more = rest.iter().map(|node| node.cid).collect();
+
+        // The peer's heads once it has stored everything before a batch
+        let mut frontier: HashSet<Hash> = request.have.iter().copied().collect();
+        for node in &response.nodes {
+            advance_frontier(&mut frontier, node);
+        }
+        for chunk in rest.chunks(limit.max(1)) {
+            let parents: HashSet<&Hash> = chunk.iter().flat_map(|n| &n.parents).collect();
+            let want = chunk
+                .iter()
+                .map(|node| node.cid)
+                .filter(|cid| !parents.contains(cid))
+                .collect();
+            response.batches.push(SyncBatch {
+                want,
+                have: frontier.iter().copied().collect(),
+            });
+            for node in chunk {
+                advance_frontier(&mut frontier, node);
+            }
+        }
+
+        response
+    }
+
+    /// Start a pipelined catch-up with a peer that has the given heads.
+    pub fn pipeline(&self, peer_heads: &[Hash]) -> SyncPipeline {
+        SyncPipeline {
+            queued: VecDeque::from([self.create_request(peer_heads)]),
+            in_flight: 0,
+            max_in_flight: self.config.max_in_flight.max(1),
+            limit: self.config.batch_size,
+            snapshot_bootstrap: self.config.snapshot_bootstrap,
+            parked: Vec::new(),
         }
     }
 
+    /// Apply a response to a pipelined request.
+    ///
+    /// The response's follow-up batches are queued on the pipeline. Nodes
+    /// whose parents haven't arrived yet, because the response overtook
+    /// the one before it, are kept on the pipeline and stored once the
+    /// parents are in.
+    pub fn apply_pipelined(
+        &mut self,
+        pipeline: &mut SyncPipeline,
+        response: SyncResponse,
+    ) -> Result<Vec<Hash>, SyncError> {
+        self.apply_pipelined_with_snapshots(pipeline, response, |_| Ok(()))
+    }
+
+    /// Apply a response to a pipelined request, fetching snapshots for
+    /// anchors we bootstrap from (see
+    /// [`apply_response_with_snapshots`](Self::apply_response_with_snapshots)).
+    pub fn apply_pipelined_with_snapshots<F>(
+        &mut self,
+        pipeline: &mut SyncPipeline,
+        mut response: SyncResponse,
+        fetch_snapshot: F,
+    ) -> Result<Vec<Hash>, SyncError>
+    where
+        F: FnMut(&MerkleNode) -> Result<(), String>,
+    {
+        pipeline.in_flight = pipeline.in_flight.saturating_sub(1);
+        for batch in mem::take(&mut response.batches) {
+            pipeline.queued.push_back(
+                SyncRequest::want(batch.want)
+                    .with_heads(batch.have)
+                    .with_limit(pipeline.limit)
+                    .with_snapshot_bootstrap(pipeline.snapshot_bootstrap),
+            );
+        }
+
+        response.nodes.append(&mut pipeline.parked);
+        let (stored, parked) = self.store_nodes(response, fetch_snapshot)?;
+        pipeline.parked = parked;
+        Ok(stored)
+    }
+
     /// Apply a sync response, storing received nodes.
     ///
     /// Returns the CIDs of successfully stored nodes.
     pub fn apply_response(&mut self, response: SyncResponse) -> Result<Vec<Hash>, SyncError> {
+        self.apply_response_with_snapshots(response, |_| Ok(()))
+    }
+
+    /// Apply a sync response, fetching snapshots for anchors we bootstrap
+    /// from.
+    ///
+    /// With [`SyncConfig::snapshot_bootstrap`] set, a snapshot anchor whose
+    /// ancestors we don't have is stored as the base of our history.
+    /// `fetch_snapshot` is called with the anchor node before it is stored;
+    /// if it fails the anchor is rejected with
+    /// [`SyncError::SnapshotUnavailable`].
+    ///
+    /// If the peer pruned history we need and neither have nor pruned
+    /// ourselves, this fails with [`SyncError::SnapshotRequired`] after
+    /// storing what it can.
+    pub fn apply_response_with_snapshots<F>(
+        &mut self,
+        response: SyncResponse,
+        fetch_snapshot: F,
+    ) -> Result<Vec<Hash>, SyncError>
+    where
+        F: FnMut(&MerkleNode) -> Result<(), String>,
+    {
+        self.store_nodes(response, fetch_snapshot)
+            .map(|(stored, _)| stored)
+    }
+
+    /// Store a response's nodes, returning the stored CIDs and the nodes
+    /// still waiting for parents.
+    fn store_nodes<F>(
+        &mut self,
+        response: SyncResponse,
+        mut fetch_snapshot: F,
+    ) -> Result<(Vec<Hash>, Vec<MerkleNode>), SyncError>
+    where
+        F: FnMut(&MerkleNode) -> Result<(), String>,
+    {
         let mut stored = Vec::new();
+        let peer_pruned = response.pruned;
         let mut pending: VecDeque<MerkleNode> = response.nodes.into_iter().collect();
         let mut attempts = 0;
         let max_attempts = pending.len() * 2;
@@ -267,6 +547,7 @@ impl<S: DAGStore> DAGSyncer<S> {
         while let Some(node) = pending.pop_front() {
             attempts += 1;
             if attempts > max_attempts {
+                pending.push_front(node);
                 break;
             }
 
@@ -282,6 +563,13 @@ impl<S: DAGStore> DAGSyncer<S> {
             // Try to store with parent check
             match self.store.put(node.clone()) {
                 Ok(cid) => stored.push(cid),
+                Err(DAGError::MissingParents(_))
+                    if self.config.snapshot_bootstrap && node.payload.is_snapshot_anchor() =>
+                {
+                    // Bootstrap from the snapshot instead of its ancestors
+                    fetch_snapshot(&node).map_err(|_| SyncError::SnapshotUnavailable(node.cid))?;
+                    stored.push(self.store.put_unchecked(node)?);
+                }
                 Err(DAGError::MissingParents(_)) => {
                     // Parents not yet available, retry later
                     pending.push_back(node);
@@ -290,7 +578,24 @@ impl<S: DAGStore> DAGSyncer<S> {
             }
         }
 
-        Ok(stored)
+        // Pruned history we still need can't be fetched from anyone who
+        // pruned it
+        if !peer_pruned.is_empty() {
+            let boundary = self.store.pruned_boundary();
+            let missing = self.missing_history();
+            let needed: Vec<Hash> = peer_pruned
+                .into_iter()
+                .filter(|cid| !self.store.contains(cid) && !boundary.contains(cid))
+                .filter(|cid| {
+                    missing.contains(cid) || pending.iter().any(|n| n.parents.contains(cid))
+                })
+                .collect();
+            if !needed.is_empty() {
+                return Err(SyncError::SnapshotRequired(needed));
+            }
+        }
+
+        Ok((stored, pending.into()))
     }
 
     /// Apply nodes without strict parent checking.
@@ -330,11 +635,48 @@ impl<S: DAGStore> DAGSyncer<S> {
         known
     }
 
+    /// Collect the nodes reachable from our heads without passing below a
+    /// snapshot anchor (anchors included).
+    fn reachable_above_anchors(&self) -> HashSet<Hash> {
+        let mut reachable = HashSet::new();
+        let mut queue: VecDeque<Hash> = self.heads().into_iter().collect();
+
+        while let Some(cid) = queue.pop_front() {
+            let Some(node) = self.store.get(&cid) else {
+                continue;
+            };
+            if reachable.insert(cid) && !node.payload.is_snapshot_anchor() {
+                queue.extend(node.parents.iter().copied());
+            }
+        }
+
+        reachable
+    }
+
+    /// Nodes we are missing, excluding history covered by snapshot anchors
+    /// when bootstrapping from snapshots.
+    fn missing_history(&self) -> HashSet<Hash> {
+        let mut missing = self.store.missing_nodes();
+        if self.config.snapshot_bootstrap {
+            missing.retain(|cid| {
+                !self.store.children(cid).iter().all(|child| {
+                    self.store
+                        .get(child)
+                        .is_some_and(|node| node.payload.is_snapshot_anchor())
+                })
+            });
+        }
+        missing
+    }
+
     /// Find the missing ancestors of the given CIDs.
     ///
     /// This performs gap detection by traversing backwards from the given CIDs
-    /// and identifying nodes that aren't in our store.
+    /// and identifying nodes that aren't in our store. Traversal stops at
+    /// pruned nodes and, when bootstrapping from snapshots, at snapshot
+    /// anchors.
     pub fn find_missing_ancestors(&self, cids: &[Hash]) -> Vec<Hash> {
+        let boundary = self.store.pruned_boundary();
         let mut missing = Vec::new();
         let mut visited = HashSet::new();
         let mut queue: VecDeque<(Hash, usize)> = cids.iter().map(|cid| (*cid, 0)).collect();
@@ -344,13 +686,16 @@ impl<S: DAGStore> DAGSyncer<S> {
                 continue;
             }
 
-            if !visited.insert(cid) {
+            if !visited.insert(cid) || boundary.contains(&cid) {
                 continue;
             }
 
             if !self.store.contains(&cid) {
                 missing.push(cid);
             } else if let Some(node) = self.store.get(&cid) {
+                if self.config.snapshot_bootstrap && node.payload.is_snapshot_anchor() {
+                    continue;
+                }
                 // Traverse to parents
                 for parent in &node.parents {
                     if !visited.contains(parent) {
@@ -373,14 +718,14 @@ impl<S: DAGStore> DAGSyncer<S> {
         }
 
         // Check we have no missing nodes
-        self.store.missing_nodes().is_empty()
+        self.missing_history().is_empty()
     }
 
     /// Get statistics about sync status.
     pub fn sync_status(&self) -> SyncStatus {
         SyncStatus {
             local_heads: self.heads().len(),
-            missing_nodes: self.store.missing_nodes().len(),
+            missing_nodes: self.missing_history().len(),
             total_nodes: self.store.len(),
         }
     }
@@ -394,6 +739,74 @@ pub struct SyncStatus {
     pub total_nodes: usize,
 }
 
+/// Record that `node` is stored: it replaces its parents among the heads.
+fn advance_frontier(frontier: &mut HashSet<Hash>, node: &MerkleNode) {
+    for parent in &node.parents {
+        frontier.remove(parent);
+    }
+    frontier.insert(node.cid);
+}
+
+/// A pipelined catch-up with one peer.
+///
+/// Created by [`DAGSyncer::pipeline`] with the first request queued. Send
+/// what [`next_requests`](Self::next_requests) returns and hand each
+/// response, in whatever order they arrive, to
+/// [`DAGSyncer::apply_pipelined`], which queues the batches it lines up.
+/// The catch-up is over once [`is_done`](Self::is_done).
+#[derive(Clone, Debug)]
+pub struct SyncPipeline {
+    /// Requests not sent yet.
+    queued: VecDeque<SyncRequest>,
+
+    /// Requests sent and not answered yet.
+    in_flight: usize,
+
+    /// Maximum number of requests outstanding at once.
+    max_in_flight: usize,
+
+    /// Node limit for follow-up requests.
+    limit: usize,
+
+    /// Whether follow-up requests bootstrap from snapshots.
+    snapshot_bootstrap: bool,
+
+    /// Nodes received before their parents.
+    parked: Vec<MerkleNode>,
+}
+
+impl SyncPipeline {
+    /// Take the requests to send now, up to the in-flight budget.
+    pub fn next_requests(&mut self) -> Vec<SyncRequest> {
+        let n = self
+            .max_in_flight
+            .saturating_sub(self.in_flight)
+            .min(self.queued.len());
+        self.in_flight += n;
+        self.queued.drain(..n).collect()
+    }
+
+    /// Get the number of requests sent and not answered yet.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    /// Get the number of requests waiting for room in the budget.
+    pub fn queued(&self) -> usize {
+        self.queued.len()
+    }
+
+    /// Get the number of nodes waiting for their parents.
+    pub fn parked(&self) -> usize {
+        self.parked.len()
+    }
+
+    /// Check whether every request has been sent and answered.
+    pub fn is_done(&self) -> bool {
+        self.queued.is_empty() && self.in_flight == 0
+    }
+}
+
 /// Simulator for testing sync between multiple replicas.
 pub struct SyncSimulator {
     /// Syncers for each replica.
@@ -449,6 +862,23 @@ impl SyncSimulator {
         let _ = self.syncers[to].apply_response(response);
     }
 
+    /// Catch one replica up with another through a [`SyncPipeline`].
+    ///
+    /// Each round sends every request the in-flight budget allows and
+    /// delivers the responses in order. Returns the number of rounds.
+    pub fn sync_pair_pipelined(&mut self, from: usize, to: usize) -> usize {
+        let mut pipeline = self.syncers[to].pipeline(&self.syncers[from].heads());
+        let mut rounds = 0;
+        while !pipeline.is_done() {
+            rounds += 1;
+            for request in pipeline.next_requests() {
+                let response = self.syncers[from].handle_request(&request);
+                let _ = self.syncers[to].apply_pipelined(&mut pipeline, response);
+            }
+        }
+        rounds
+    }
+
     /// Perform a full sync round (all pairs).
     pub fn full_sync_round(&mut self) {
         let n = self.syncers.len();
@@ -662,4 +1092,142 @@ mod tests {
         sim.sync_pair(0, 1);
         assert!(sim.syncer(1).is_synced_with(&sim.syncer(0).heads()));
     }
+
+    #[test]
+    fn test_pruned_nodes_are_reported_not_requested() {
+        let (mut store, genesis) = MemoryDAGStore::with_genesis("r1");
+        let node = NodeBuilder::new()
+            .with_parent(genesis)
+            .with_payload(Payload::delta(vec![1]))
+            .with_timestamp(1)
+            .with_creator("r1")
+            .build();
+        let cid = store.put(node).unwrap();
+        store.prune(&genesis).unwrap();
+        let syncer = DAGSyncer::new(store);
+
+        // We never ask for what we pruned
+        assert!(syncer.need(&[genesis]).is_empty());
+        assert!(syncer.find_missing_ancestors(&[cid]).is_empty());
+
+        // A peer asking for it is told it was pruned
+        let response = syncer.handle_request(&SyncRequest::want(vec![genesis]));
+        assert!(response.nodes.is_empty());
+        assert_eq!(response.pruned, vec![genesis]);
+
+        // A peer that needs it can't get it anywhere but from a snapshot
+        let (other, _) = MemoryDAGStore::with_genesis("r2");
+        let mut peer = DAGSyncer::new(other);
+        let request = peer.create_request(&syncer.heads());
+        let response = syncer.handle_request(&request);
+        assert_eq!(response.pruned, vec![genesis]);
+        assert_eq!(
+            peer.apply_response(response),
+            Err(SyncError::SnapshotRequired(vec![genesis]))
+        );
+    }
+
+    /// Grow a chain of `len` nodes on top of a replica's head.
+    fn grow_chain(sim: &mut SyncSimulator, idx: usize, len: usize) {
+        let mut head = sim.syncer(idx).heads()[0];
+        for i in 0..len {
+            let node = NodeBuilder::new()
+                .with_parent(head)
+                .with_payload(Payload::delta(i.to_le_bytes().to_vec()))
+                .with_timestamp(i as u64 + 1)
+                .with_creator("replica_0")
+                .build();
+            head = sim.syncer_mut(idx).store_mut().put(node).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_pipelined_sync_cuts_round_trips() {
+        let mut sim = SyncSimulator::with_shared_genesis(3);
+        grow_chain(&mut sim, 0, 1000);
+
+        // Walking the gap back one node per request
+        let mut want = sim.syncer(0).heads();
+        let mut naive_rounds = 0;
+        while !want.is_empty() {
+            naive_rounds += 1;
+            let response = sim.syncer(0).handle_request(&SyncRequest::want(want));
+            sim.syncer_mut(1)
+                .apply_nodes_unchecked(response.nodes)
+                .unwrap();
+            want = sim.syncer(1).store().missing_nodes().into_iter().collect();
+        }
+        assert_eq!(naive_rounds, 1000);
+
+        // Batches of 100, four in flight: the first response lines up the
+        // other nine batches, fetched over three more rounds
+        let rounds = sim.sync_pair_pipelined(0, 2);
+        assert_eq!(rounds, 4);
+        assert!(rounds * 10 <= naive_rounds);
+        assert_eq!(sim.syncer(2).store().len(), 1001);
+        assert!(sim.syncer(2).is_synced_with(&sim.syncer(0).heads()));
+        assert!(sim.is_converged());
+    }
+
+    #[test]
+    fn test_batches_arrive_in_topological_order() {
+        let mut sim = SyncSimulator::with_shared_genesis(2);
+        grow_chain(&mut sim, 0, 250);
+
+        let request = sim.syncer(1).create_request(&sim.syncer(0).heads());
+        let response = sim.syncer(0).handle_request(&request);
+        assert_eq!(response.nodes.len(), 100);
+        assert_eq!(response.more.len(), 150);
+        assert_eq!(response.batches.len(), 2);
+
+        // Each batch is stored as it arrives, without waiting on another
+        let batches = response.batches.clone();
+        let stored = sim.syncer_mut(1).apply_response(response).unwrap();
+        assert_eq!(stored.len(), 100);
+        for batch in batches {
+            let request = SyncRequest::want(batch.want).with_heads(batch.have);
+            let response = sim.syncer(0).handle_request(&request);
+            assert!(response.batches.is_empty());
+            let n = response.nodes.len();
+            assert_eq!(sim.syncer_mut(1).apply_response(response).unwrap().len(), n);
+        }
+        assert!(sim.is_converged());
+    }
+
+    #[test]
+    fn test_pipelined_sync_with_responses_out_of_order() {
+        let config = SyncConfig {
+            batch_size: 50,
+            max_in_flight: 3,
+            ..SyncConfig::default()
+        };
+        let mut sim = SyncSimulator::with_shared_genesis(2);
+        grow_chain(&mut sim, 0, 1000);
+        let genesis = sim.syncer(1).store().clone();
+        *sim.syncer_mut(1) = DAGSyncer::with_config(genesis, config);
+
+        let mut pipeline = sim.syncer(1).pipeline(&sim.syncer(0).heads());
+        let mut parked = 0;
+        while !pipeline.is_done() {
+            let requests = pipeline.next_requests();
+            assert!(requests.len() <= 3);
+            assert!(pipeline.in_flight() <= 3);
+            let mut responses: Vec<SyncResponse> = requests
+                .iter()
+                .map(|request| sim.syncer(0).handle_request(request))
+                .collect();
+            responses.reverse();
+            for response in responses {
+                sim.syncer_mut(1)
+                    .apply_pipelined(&mut pipeline, response)
+                    .unwrap();
+                parked = parked.max(pipeline.parked());
+            }
+        }
+
+        assert!(parked > 0);
+        assert_eq!(pipeline.parked(), 0);
+        assert_eq!(sim.syncer(1).store().len(), 1001);
+        assert!(sim.is_converged());
+    }
 }
@@ -8,7 +8,7 @@
 
 use mdcs_merkle::{
     BroadcastNetwork, DAGStore, DAGSyncer, Hasher, MemoryDAGStore, MerkleNode, NodeBuilder,
-    Payload, SyncResponse, SyncSimulator,
+    Payload, SyncConfig, SyncResponse, SyncSimulator,
 };
 
 /// Test bootstrapping a new replica from a root CID.
@@ -367,6 +367,75 @@ fn test_snapshot_bootstrap() {
     }
 }
 
+/// Test bootstrapping a new replica from a snapshot anchor.
+#[test]
+fn test_snapshot_anchor_bootstrap() {
+    let (mut store, genesis) = MemoryDAGStore::with_genesis("source");
+
+    // Add many updates
+    let mut last = genesis;
+    for i in 1..=20 {
+        let node = NodeBuilder::new()
+            .with_parent(last)
+            .with_payload(Payload::delta(format!("data_{}", i).into_bytes()))
+            .with_timestamp(i as u64)
+            .with_creator("source")
+            .build();
+        last = store.put(node).unwrap();
+    }
+
+    // Anchor a snapshot at the current head, then keep writing
+    let snapshot_id = Hasher::hash(b"serialized_full_state");
+    let snapshot = NodeBuilder::new()
+        .with_parent(last)
+        .with_payload(Payload::snapshot_anchor(snapshot_id, b"vv".to_vec()))
+        .with_timestamp(21)
+        .with_creator("source")
+        .build();
+    let snapshot_cid = store.put(snapshot).unwrap();
+    assert!(store
+        .get(&snapshot_cid)
+        .unwrap()
+        .payload
+        .is_snapshot_anchor());
+
+    let after = NodeBuilder::new()
+        .with_parent(snapshot_cid)
+        .with_payload(Payload::delta(b"after_snapshot".to_vec()))
+        .with_timestamp(22)
+        .with_creator("source")
+        .build();
+    store.put(after).unwrap();
+
+    // New replica bootstraps from the anchor instead of the full history
+    let source = DAGSyncer::new(store);
+    let config = SyncConfig {
+        snapshot_bootstrap: true,
+        ..SyncConfig::default()
+    };
+    let mut new_replica = DAGSyncer::with_config(MemoryDAGStore::new(), config);
+
+    let request = new_replica.create_request(&source.heads());
+    assert!(request.snapshot_bootstrap);
+    let response = source.handle_request(&request);
+    let mut fetched = Vec::new();
+    new_replica
+        .apply_response_with_snapshots(response, |anchor| {
+            fetched.push(*anchor.payload.snapshot_hash().unwrap());
+            Ok(())
+        })
+        .unwrap();
+
+    assert_eq!(fetched, vec![snapshot_id]);
+    assert_eq!(new_replica.heads(), source.heads());
+    assert_eq!(new_replica.store().len(), 2);
+    assert!(!new_replica.store().contains(&genesis));
+    assert!(new_replica.is_synced_with(&source.heads()));
+    assert!(new_replica
+        .find_missing_ancestors(&new_replica.heads())
+        .is_empty());
+}
+
 /// Test DAG statistics and depth calculation.
 #[test]
 fn test_dag_statistics() {
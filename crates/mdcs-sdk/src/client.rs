@@ -1,14 +1,34 @@
 //! High-level client for the MDCS SDK.
 
-use crate::error::SdkError;
-use crate::network::{MemoryTransport, NetworkTransport, Peer, PeerId};
-use crate::session::Session;
+use crate::error::{ConfigError, SdkError};
+use crate::network::{MemoryTransport, Message, NetworkTransport, Peer, PeerId};
+use crate::session::{Session, SessionEvent};
+use crate::storage::DocStorage;
+use crate::sync::{SessionMembers, SyncConfig};
+use mdcs_core::clock::SharedClock;
 use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+
+/// How long a dropped client keeps trying to send its final edits.
+const DROP_FLUSH_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Most reconnection attempts allowed.
+pub const MAX_RECONNECT_ATTEMPTS: u32 = 100;
+
+/// Longest shutdown timeout, in milliseconds.
+pub const MAX_SHUTDOWN_TIMEOUT_MS: u64 = 600_000;
 
 /// Configuration for the MDCS client.
-#[derive(Clone, Debug)]
+///
+/// Build one with [`ClientConfigBuilder`] or [`from_env`](Self::from_env),
+/// or call [`validate`](Self::validate) on one made some other way, such
+/// as deserialized from a file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ClientConfig {
     /// User name for presence.
     pub user_name: String,
@@ -16,6 +36,9 @@ pub struct ClientConfig {
     pub auto_reconnect: bool,
     /// Maximum reconnection attempts.
     pub max_reconnect_attempts: u32,
+    /// How long shutdown waits for peers to acknowledge final edits (in
+    /// milliseconds).
+    pub shutdown_timeout_ms: u64,
 }
 
 impl Default for ClientConfig {
@@ -24,10 +47,88 @@ impl Default for ClientConfig {
             user_name: "Anonymous".to_string(),
             auto_reconnect: true,
             max_reconnect_attempts: 5,
+            shutdown_timeout_ms: 5000,
+        }
+    }
+}
+
+impl ClientConfig {
+    /// Check every field. The user name becomes the replica's name, so it
+    /// can't be blank, and reconnecting needs at least one attempt.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.user_name.trim().is_empty() {
+            return Err(ConfigError::Empty { field: "user_name" });
+        }
+        if self.auto_reconnect {
+            ConfigError::check_range(
+                "max_reconnect_attempts",
+                self.max_reconnect_attempts.into(),
+                1,
+                MAX_RECONNECT_ATTEMPTS.into(),
+            )?;
+        }
+        ConfigError::check_range(
+            "shutdown_timeout_ms",
+            self.shutdown_timeout_ms,
+            0,
+            MAX_SHUTDOWN_TIMEOUT_MS,
+        )
+    }
+
+    /// Read a configuration from the environment, checked like
+    /// [`ClientConfigBuilder::build`].
+    ///
+    /// Unset variables keep their defaults:
+    ///
+    /// | Variable                      | Field                    | Values                 |
+    /// |-------------------------------|--------------------------|------------------------|
+    /// | `MDCS_USER_NAME`              | `user_name`              | any non-blank text     |
+    /// | `MDCS_AUTO_RECONNECT`         | `auto_reconnect`         | `true`/`false`/`1`/`0` |
+    /// | `MDCS_MAX_RECONNECT_ATTEMPTS` | `max_reconnect_attempts` | 1 to 100               |
+    /// | `MDCS_SHUTDOWN_TIMEOUT_MS`    | `shutdown_timeout_ms`    | 0 to 600000            |
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_vars(|var| std::env::var(var).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let mut builder = ClientConfigBuilder::new();
+        if let Some(name) = var("MDCS_USER_NAME") {
+            builder = builder.user_name(name);
+        }
+        if let Some(value) = var("MDCS_AUTO_RECONNECT") {
+            let enabled = match value.trim().to_ascii_lowercase().as_str() {
+                "true" | "1" => true,
+                "false" | "0" => false,
+                _ => {
+                    return Err(ConfigError::InvalidEnv {
+                        var: "MDCS_AUTO_RECONNECT",
+                        value,
+                        expected: "true, false, 1 or 0",
+                    })
+                }
+            };
+            builder = builder.auto_reconnect(enabled);
+        }
+        if let Some(value) = var("MDCS_MAX_RECONNECT_ATTEMPTS") {
+            builder =
+                builder.max_reconnect_attempts(parse_var("MDCS_MAX_RECONNECT_ATTEMPTS", value)?);
         }
+        if let Some(value) = var("MDCS_SHUTDOWN_TIMEOUT_MS") {
+            builder = builder.shutdown_timeout(parse_var("MDCS_SHUTDOWN_TIMEOUT_MS", value)?);
+        }
+        builder.build()
     }
 }
 
+/// Parse a number from an environment variable.
+fn parse_var<N: std::str::FromStr>(var: &'static str, value: String) -> Result<N, ConfigError> {
+    value.trim().parse().map_err(|_| ConfigError::InvalidEnv {
+        var,
+        value,
+        expected: "a whole number",
+    })
+}
+
 /// Builder for client configuration.
 pub struct ClientConfigBuilder {
     config: ClientConfig,
@@ -55,8 +156,16 @@ impl ClientConfigBuilder {
         self
     }
 
-    pub fn build(self) -> ClientConfig {
-        self.config
+    pub fn shutdown_timeout(mut self, ms: u64) -> Self {
+        self.config.shutdown_timeout_ms = ms;
+        self
+    }
+
+    /// Check the configuration with [`ClientConfig::validate`] and return
+    /// it.
+    pub fn build(self) -> Result<ClientConfig, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
     }
 }
 
@@ -89,11 +198,25 @@ impl Default for ClientConfigBuilder {
 /// let doc = session.open_text_doc("shared-doc");
 /// doc.write().insert(0, "Hello, world!");
 /// ```
+///
+/// Sessions joined with [`join_session`](Self::join_session) share the
+/// client's transport: their messages are addressed to them and only reach
+/// peers that joined the same session. Feed incoming messages to
+/// [`handle_message`](Self::handle_message) to route them.
+///
+/// Call [`shutdown`](Self::shutdown) before dropping a client so final edits
+/// reach peers. A client dropped without it still sends pending edits and a
+/// goodbye from a background task, but does not wait for acknowledgments.
 pub struct Client<T: NetworkTransport> {
     peer_id: PeerId,
     config: ClientConfig,
     transport: Arc<T>,
     sessions: Arc<RwLock<HashMap<String, Arc<Session<T>>>>>,
+    /// Peers that joined each session, as they announced it.
+    memberships: RwLock<HashMap<String, SessionMembers>>,
+    storage: Option<Arc<dyn DocStorage>>,
+    shut_down: AtomicBool,
+    clock: SharedClock,
 }
 
 impl Client<MemoryTransport> {
@@ -107,6 +230,10 @@ impl Client<MemoryTransport> {
             config,
             transport,
             sessions: Arc::new(RwLock::new(HashMap::new())),
+            memberships: RwLock::new(HashMap::new()),
+            storage: None,
+            shut_down: AtomicBool::new(false),
+            clock: SharedClock::default(),
         }
     }
 }
@@ -119,9 +246,25 @@ impl<T: NetworkTransport> Client<T> {
             config,
             transport,
             sessions: Arc::new(RwLock::new(HashMap::new())),
+            memberships: RwLock::new(HashMap::new()),
+            storage: None,
+            shut_down: AtomicBool::new(false),
+            clock: SharedClock::default(),
         }
     }
 
+    /// Checkpoint session documents to `storage` when sessions close.
+    pub fn with_storage(mut self, storage: Arc<dyn DocStorage>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// Use `clock` for the time in sessions created by this client.
+    pub fn with_clock(mut self, clock: impl Into<SharedClock>) -> Self {
+        self.clock = clock.into();
+        self
+    }
+
     /// Get the local peer ID.
     pub fn peer_id(&self) -> &PeerId {
         &self.peer_id
@@ -138,19 +281,173 @@ impl<T: NetworkTransport> Client<T> {
     }
 
     /// Create a new collaborative session.
+    ///
+    /// The session talks to every connected peer and takes every message
+    /// not addressed to another session. To run several sessions over one
+    /// connection, use [`join_session`](Self::join_session) instead.
     pub fn create_session(&self, session_id: impl Into<String>) -> Arc<Session<T>> {
+        self.open_session(session_id.into(), None)
+    }
+
+    /// Join a session shared with other sessions over the client's
+    /// transport.
+    ///
+    /// Tells connected peers, which answer if they joined it too. The
+    /// session's messages only go to peers that joined it, and its presence
+    /// is its own.
+    pub async fn join_session(
+        &self,
+        session_id: impl Into<String>,
+    ) -> Result<Arc<Session<T>>, SdkError> {
         let session_id = session_id.into();
+        let members = self.members_of(&session_id);
+        let session = self.open_session(session_id.clone(), Some(members));
+        self.transport
+            .broadcast(Message::Join { session_id })
+            .await?;
+        Ok(session)
+    }
+
+    /// Leave a session joined with [`join_session`](Self::join_session).
+    ///
+    /// Closes the session (see [`Session::close`]) and tells connected
+    /// peers the client left it. Leaving a session not joined is a no-op.
+    pub async fn leave_session(&self, session_id: &str) -> Result<(), SdkError> {
+        let Some(session) = self.sessions.write().remove(session_id) else {
+            return Ok(());
+        };
+        let closed = session.close().await;
+        let left = self
+            .transport
+            .broadcast(Message::Leave {
+                session_id: session_id.to_string(),
+            })
+            .await
+            .map_err(SdkError::from);
+        closed.and(left)
+    }
+
+    /// List the peers known to have joined a session.
+    pub fn session_members(&self, session_id: &str) -> Vec<PeerId> {
+        self.memberships
+            .read()
+            .get(session_id)
+            .map(SessionMembers::to_vec)
+            .unwrap_or_default()
+    }
+
+    /// Route a message received from a peer.
+    ///
+    /// Joins and leaves update session membership; a peer joining a session
+    /// the client joined too is answered so both know of each other.
+    /// Messages addressed to a session go to it, goodbyes go to every
+    /// session, and other messages go to the sessions made with
+    /// [`create_session`](Self::create_session). Returns the session event
+    /// the message produced, if any.
+    pub async fn handle_message(
+        &self,
+        from: &PeerId,
+        message: &Message,
+    ) -> Result<Option<SessionEvent>, SdkError> {
+        match message {
+            Message::Join { session_id } => {
+                let new = self.members_of(session_id).insert(from.clone());
+                if new && self.is_joined(session_id) {
+                    let answer = Message::Join {
+                        session_id: session_id.clone(),
+                    };
+                    self.transport.send(from, answer).await?;
+                }
+                Ok(None)
+            }
+            Message::Leave { session_id } => {
+                if self.members_of(session_id).remove(from) {
+                    if let Some(session) = self.get_session(session_id) {
+                        session.peer_left(from);
+                    }
+                }
+                Ok(None)
+            }
+            Message::Session { session_id, .. } => match self.get_session(session_id) {
+                Some(session) => session.handle_message(from, message).await,
+                None => Ok(None),
+            },
+            Message::Goodbye { .. } => {
+                for members in self.memberships.read().values() {
+                    members.remove(from);
+                }
+                self.dispatch(from, message, |_| true).await
+            }
+            _ => {
+                self.dispatch(from, message, |session| session.members().is_none())
+                    .await
+            }
+        }
+    }
+
+    /// Hand a message to each session `accepts`, returning the first event.
+    async fn dispatch(
+        &self,
+        from: &PeerId,
+        message: &Message,
+        accepts: impl Fn(&Session<T>) -> bool,
+    ) -> Result<Option<SessionEvent>, SdkError> {
+        let sessions: Vec<_> = self
+            .sessions
+            .read()
+            .values()
+            .filter(|session| accepts(session))
+            .cloned()
+            .collect();
+        let mut first = None;
+        for session in sessions {
+            let event = session.handle_message(from, message).await?;
+            first = first.or(event);
+        }
+        Ok(first)
+    }
+
+    /// The membership of a session, tracked from the first join seen.
+    fn members_of(&self, session_id: &str) -> SessionMembers {
+        self.memberships
+            .write()
+            .entry(session_id.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Check whether a session was joined with
+    /// [`join_session`](Self::join_session).
+    fn is_joined(&self, session_id: &str) -> bool {
+        self.get_session(session_id)
+            .is_some_and(|session| session.members().is_some())
+    }
+
+    fn open_session(&self, session_id: String, members: Option<SessionMembers>) -> Arc<Session<T>> {
         let mut sessions = self.sessions.write();
 
         if let Some(session) = sessions.get(&session_id) {
             session.clone()
         } else {
-            let session = Arc::new(Session::new(
+            let sync_config = SyncConfig {
+                sync_timeout_ms: self.config.shutdown_timeout_ms,
+                ..Default::default()
+            };
+            let mut session = Session::new(
                 session_id.clone(),
                 self.peer_id.clone(),
                 self.config.user_name.clone(),
                 self.transport.clone(),
-            ));
+            )
+            .with_clock(self.clock.clone())
+            .with_sync_config(sync_config);
+            if let Some(storage) = &self.storage {
+                session = session.with_storage(storage.clone());
+            }
+            if let Some(members) = members {
+                session = session.with_members(members);
+            }
+            let session = Arc::new(session);
             sessions.insert(session_id, session.clone());
             session
         }
@@ -171,12 +468,25 @@ impl<T: NetworkTransport> Client<T> {
         self.sessions.read().keys().cloned().collect()
     }
 
-    /// Connect to a peer.
+    /// Connect to a peer, telling it which sessions the client joined.
     pub async fn connect_peer(&self, peer_id: &PeerId) -> Result<(), SdkError> {
         self.transport
             .connect(peer_id)
             .await
-            .map_err(|e| SdkError::ConnectionFailed(e.to_string()))
+            .map_err(|e| SdkError::ConnectionFailed(e.to_string()))?;
+        let joined: Vec<_> = self
+            .sessions
+            .read()
+            .values()
+            .filter(|session| session.members().is_some())
+            .map(|session| session.session_id().to_string())
+            .collect();
+        for session_id in joined {
+            self.transport
+                .send(peer_id, Message::Join { session_id })
+                .await?;
+        }
+        Ok(())
     }
 
     /// Disconnect from a peer.
@@ -184,13 +494,99 @@ impl<T: NetworkTransport> Client<T> {
         self.transport
             .disconnect(peer_id)
             .await
-            .map_err(|e| SdkError::NetworkError(e.to_string()))
+            .map_err(SdkError::from)
     }
 
     /// Get list of connected peers.
     pub async fn connected_peers(&self) -> Vec<Peer> {
         self.transport.connected_peers().await
     }
+
+    /// Check whether [`shutdown`](Self::shutdown) has been called.
+    pub fn is_shut_down(&self) -> bool {
+        self.shut_down.load(Ordering::SeqCst)
+    }
+
+    /// Shut the client down gracefully.
+    ///
+    /// Closes every session (see [`Session::close`]), which stops local
+    /// edits, flushes pending ones for up to `shutdown_timeout_ms`, marks the
+    /// user offline and checkpoints documents. Then sends peers a goodbye so
+    /// they see the client leave at once, and disconnects from them.
+    ///
+    /// Resolves to [`SdkError::PartialFlush`] listing the edits peers did
+    /// not acknowledge in time; the client is shut down either way. Calling
+    /// it again is a no-op.
+    pub async fn shutdown(&self) -> Result<(), SdkError> {
+        if self.shut_down.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let sessions: Vec<_> = self.sessions.read().values().cloned().collect();
+        let closed = futures::future::join_all(sessions.iter().map(|s| s.close())).await;
+
+        let goodbye = Message::Goodbye {
+            replica_id: self.peer_id.0.clone(),
+        };
+        let said_goodbye = self
+            .transport
+            .broadcast(goodbye)
+            .await
+            .map_err(SdkError::from);
+        for peer in self.transport.connected_peers().await {
+            let _ = self.transport.disconnect(&peer.id).await;
+        }
+
+        merge_close_results(closed).and(said_goodbye)
+    }
+}
+
+/// Combine the results of closing several sessions, merging unacknowledged
+/// edits into one [`SdkError::PartialFlush`].
+fn merge_close_results(results: Vec<Result<(), SdkError>>) -> Result<(), SdkError> {
+    let mut unacked = Vec::new();
+    let mut first_error = None;
+    for result in results {
+        match result {
+            Ok(()) => {}
+            Err(SdkError::PartialFlush(missing)) => unacked.extend(missing),
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    match first_error {
+        Some(e) => Err(e),
+        None if !unacked.is_empty() => Err(SdkError::PartialFlush(unacked)),
+        None => Ok(()),
+    }
+}
+
+impl<T: NetworkTransport> Drop for Client<T> {
+    fn drop(&mut self) {
+        if self.shut_down.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        // Without a runtime there is nothing to send with
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+
+        let sessions: Vec<_> = self.sessions.read().values().cloned().collect();
+        let transport = self.transport.clone();
+        let goodbye = Message::Goodbye {
+            replica_id: self.peer_id.0.clone(),
+        };
+        runtime.spawn(async move {
+            let flush = async {
+                for session in &sessions {
+                    session.close_unacked().await;
+                }
+                let _ = transport.broadcast(goodbye).await;
+            };
+            let _ = tokio::time::timeout(DROP_FLUSH_TIMEOUT, flush).await;
+        });
+    }
 }
 
 /// Simple UUID-like string generator.
@@ -232,6 +628,13 @@ pub mod quick {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::document::CollaborativeDoc;
+    use crate::network::create_network;
+    use crate::presence::AwarenessEvent;
+    use crate::session::SessionEvent;
+    use mdcs_core::clock::ManualClock;
+    use std::time::Instant;
+    use tokio::task::JoinHandle;
 
     #[test]
     fn test_client_creation() {
@@ -263,19 +666,132 @@ mod tests {
         assert_eq!(client.session_ids().len(), 1);
     }
 
+    #[test]
+    fn test_sessions_use_client_clock() {
+        let clock = Arc::new(ManualClock::new(42_000));
+        let client =
+            Client::new_with_memory_transport(ClientConfig::default()).with_clock(clock.clone());
+        let session = client.create_session("session-1");
+
+        clock.advance(5);
+        session.awareness().set_cursor("doc-1", 3);
+        let delta = session.awareness().take_delta().unwrap();
+        assert_eq!(delta.updates.last().unwrap().last_updated, 42_005);
+    }
+
     #[test]
     fn test_config_builder() {
         let config = ClientConfigBuilder::new()
             .user_name("Bob")
             .auto_reconnect(false)
             .max_reconnect_attempts(3)
-            .build();
+            .build()
+            .unwrap();
 
         assert_eq!(config.user_name, "Bob");
         assert!(!config.auto_reconnect);
         assert_eq!(config.max_reconnect_attempts, 3);
     }
 
+    #[test]
+    fn test_config_validation() {
+        ClientConfig::default().validate().unwrap();
+
+        for name in ["", "  "] {
+            assert_eq!(
+                ClientConfigBuilder::new()
+                    .user_name(name)
+                    .build()
+                    .unwrap_err(),
+                ConfigError::Empty { field: "user_name" }
+            );
+        }
+
+        let no_attempts = ClientConfigBuilder::new().max_reconnect_attempts(0);
+        assert_eq!(
+            no_attempts.build().unwrap_err().to_string(),
+            "max_reconnect_attempts is 0, expected 1 to 100"
+        );
+        // Without reconnection the attempts don't matter
+        ClientConfigBuilder::new()
+            .auto_reconnect(false)
+            .max_reconnect_attempts(0)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            ClientConfigBuilder::new()
+                .max_reconnect_attempts(1000)
+                .build(),
+            Err(ConfigError::OutOfRange {
+                field: "max_reconnect_attempts",
+                ..
+            })
+        ));
+
+        assert!(matches!(
+            ClientConfigBuilder::new()
+                .shutdown_timeout(3_600_000)
+                .build(),
+            Err(ConfigError::OutOfRange {
+                field: "shutdown_timeout_ms",
+                ..
+            })
+        ));
+
+        let config: ClientConfig = serde_json::from_str(r#"{"user_name": ""}"#).unwrap();
+        assert_eq!(config.shutdown_timeout_ms, 5000);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_from_env() {
+        let vars = [
+            ("MDCS_USER_NAME", "Dana"),
+            ("MDCS_AUTO_RECONNECT", "false"),
+            ("MDCS_MAX_RECONNECT_ATTEMPTS", "7"),
+            ("MDCS_SHUTDOWN_TIMEOUT_MS", "1500"),
+        ];
+        for (var, value) in vars {
+            std::env::set_var(var, value);
+        }
+        let config = ClientConfig::from_env();
+        for (var, _) in vars {
+            std::env::remove_var(var);
+        }
+
+        let config = config.unwrap();
+        assert_eq!(config.user_name, "Dana");
+        assert!(!config.auto_reconnect);
+        assert_eq!(config.max_reconnect_attempts, 7);
+        assert_eq!(config.shutdown_timeout_ms, 1500);
+
+        // Unset variables keep the defaults
+        let config = ClientConfig::from_vars(|_| None).unwrap();
+        assert_eq!(config.user_name, ClientConfig::default().user_name);
+
+        let only = |name: &'static str, value: &'static str| {
+            move |var: &str| (var == name).then(|| value.to_string())
+        };
+        assert_eq!(
+            ClientConfig::from_vars(only("MDCS_AUTO_RECONNECT", "yes")).unwrap_err(),
+            ConfigError::InvalidEnv {
+                var: "MDCS_AUTO_RECONNECT",
+                value: "yes".to_string(),
+                expected: "true, false, 1 or 0",
+            }
+        );
+        assert_eq!(
+            ClientConfig::from_vars(only("MDCS_SHUTDOWN_TIMEOUT_MS", "5s"))
+                .unwrap_err()
+                .to_string(),
+            "MDCS_SHUTDOWN_TIMEOUT_MS=\"5s\" is not a whole number"
+        );
+        assert_eq!(
+            ClientConfig::from_vars(only("MDCS_USER_NAME", "")).unwrap_err(),
+            ConfigError::Empty { field: "user_name" }
+        );
+    }
+
     #[test]
     fn test_quick_collaborative_clients() {
         let clients = quick::create_collaborative_clients(&["Alice", "Bob", "Charlie"]);
@@ -285,4 +801,273 @@ mod tests {
         assert_eq!(clients[1].user_name(), "Bob");
         assert_eq!(clients[2].user_name(), "Charlie");
     }
+
+    /// Two clients on a memory network, each with the session "s" and the
+    /// text document "doc" open.
+    fn pair(
+        shutdown_timeout_ms: u64,
+    ) -> Vec<(Client<MemoryTransport>, Arc<Session<MemoryTransport>>)> {
+        create_network(2)
+            .into_iter()
+            .zip(["Alice", "Bob"])
+            .map(|(transport, name)| {
+                let config = ClientConfigBuilder::new()
+                    .user_name(name)
+                    .shutdown_timeout(shutdown_timeout_ms)
+                    .build()
+                    .unwrap();
+                let client = Client::new(transport.local_id().clone(), Arc::new(transport), config);
+                let session = client.create_session("s");
+                session.open_text_doc("doc");
+                (client, session)
+            })
+            .collect()
+    }
+
+    /// Feed everything arriving at a client into its session, recording the
+    /// message kinds in arrival order.
+    fn pump(
+        client: &Client<MemoryTransport>,
+        session: &Arc<Session<MemoryTransport>>,
+    ) -> (JoinHandle<()>, Arc<RwLock<Vec<&'static str>>>) {
+        let mut inbox = client.transport().subscribe();
+        let session = session.clone();
+        let seen = Arc::new(RwLock::new(Vec::new()));
+        let log = seen.clone();
+        let task = tokio::spawn(async move {
+            while let Some((from, message)) = inbox.recv().await {
+                log.write().push(match message {
+                    Message::Update { .. } => "update",
+                    Message::Awareness { .. } => "awareness",
+                    Message::Goodbye { .. } => "goodbye",
+                    _ => "other",
+                });
+                session.handle_message(&from, &message).await.unwrap();
+            }
+        });
+        (task, seen)
+    }
+
+    /// Feed everything arriving at a client through its router.
+    fn route(client: &Arc<Client<MemoryTransport>>) -> JoinHandle<()> {
+        let mut inbox = client.transport().subscribe();
+        let client = client.clone();
+        tokio::spawn(async move {
+            while let Some((from, message)) = inbox.recv().await {
+                client.handle_message(&from, &message).await.unwrap();
+            }
+        })
+    }
+
+    async fn wait_until(condition: impl Fn() -> bool) {
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while !condition() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sessions_share_transport_but_stay_isolated() {
+        let clients: Vec<_> = create_network(2)
+            .into_iter()
+            .zip(["Alice", "Bob"])
+            .map(|(transport, name)| {
+                let config = ClientConfigBuilder::new().user_name(name).build().unwrap();
+                Arc::new(Client::new(
+                    transport.local_id().clone(),
+                    Arc::new(transport),
+                    config,
+                ))
+            })
+            .collect();
+        let _routers: Vec<_> = clients.iter().map(route).collect();
+        let (alice, bob) = (&clients[0], &clients[1]);
+
+        let mut sessions = Vec::new();
+        for client in &clients {
+            for id in ["a", "b"] {
+                sessions.push(client.join_session(id).await.unwrap());
+            }
+        }
+        wait_until(|| {
+            ["a", "b"].iter().all(|id| {
+                alice.session_members(id) == [bob.peer_id().clone()]
+                    && bob.session_members(id) == [alice.peer_id().clone()]
+            })
+        })
+        .await;
+
+        // The same document name in both sessions, edited concurrently
+        let docs: Vec<_> = sessions.iter().map(|s| s.open_text_doc("board")).collect();
+        docs[0].write().insert(0, "alpha;");
+        docs[1].write().insert(0, "beta;");
+        docs[2].write().insert(0, "gamma;");
+        docs[3].write().insert(0, "delta;");
+        for session in &sessions {
+            session.publish().await.unwrap();
+        }
+
+        wait_until(|| {
+            docs[0].read().get_text().len() == 12
+                && docs[0].read().get_text() == docs[2].read().get_text()
+                && docs[1].read().get_text().len() == 11
+                && docs[1].read().get_text() == docs[3].read().get_text()
+        })
+        .await;
+        let (text_a, text_b) = (docs[0].read().get_text(), docs[1].read().get_text());
+        assert!(text_a.contains("alpha;") && text_a.contains("gamma;"));
+        assert!(text_b.contains("beta;") && text_b.contains("delta;"));
+
+        // Presence is per session too
+        sessions[0].awareness().set_cursor("board", 3);
+        sessions[0].publish_presence().await.unwrap();
+        wait_until(|| !sessions[2].awareness().get_cursors("board").is_empty()).await;
+        assert!(sessions[3].awareness().get_cursors("board").is_empty());
+
+        // Leaving one session leaves the other untouched
+        alice.leave_session("b").await.unwrap();
+        wait_until(|| bob.session_members("b").is_empty()).await;
+        assert_eq!(bob.session_members("a"), [alice.peer_id().clone()]);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_open_by_name_shares_one_document() {
+        let mut clients = pair(2000);
+        let (bob, bob_session) = clients.pop().unwrap();
+        let (alice, alice_session) = clients.pop().unwrap();
+
+        // Both open and edit the same name before anything is synced
+        let alice_doc = alice_session.open_text_doc("meeting-notes");
+        let bob_doc = bob_session.open_text_doc("meeting-notes");
+        alice_doc.write().insert(0, "agenda;");
+        bob_doc.write().insert(0, "minutes;");
+
+        let (_alice_pump, _) = pump(&alice, &alice_session);
+        let (_bob_pump, _) = pump(&bob, &bob_session);
+        alice_session.publish().await.unwrap();
+        bob_session.publish().await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while alice_doc.read().len() != 15 || bob_doc.read().len() != 15 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let text = alice_doc.read().get_text();
+        assert_eq!(bob_doc.read().get_text(), text);
+        assert!(text.contains("agenda;") && text.contains("minutes;"));
+        for (session, doc) in [(&alice_session, &alice_doc), (&bob_session, &bob_doc)] {
+            let reopened = session.open_text_doc("meeting-notes");
+            assert!(Arc::ptr_eq(&reopened, doc));
+            assert_eq!(
+                session
+                    .open_documents()
+                    .iter()
+                    .filter(|id| *id == "meeting-notes")
+                    .count(),
+                1
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_flushes_final_edit_and_says_goodbye() {
+        let mut clients = pair(2000);
+        let (bob, bob_session) = clients.pop().unwrap();
+        let (alice, alice_session) = clients.pop().unwrap();
+        let (_alice_pump, _) = pump(&alice, &alice_session);
+        let (_bob_pump, bob_seen) = pump(&bob, &bob_session);
+        let mut bob_events = bob_session.subscribe();
+        let mut bob_awareness = bob_session.awareness().subscribe();
+
+        let doc = alice_session.open_text_doc("doc");
+        doc.write().insert(0, "final words");
+        alice.shutdown().await.unwrap();
+
+        // The edit was acknowledged, so Bob has it before the goodbye
+        let bob_doc = bob_session.open_text_doc("doc");
+        assert_eq!(bob_doc.read().get_text(), "final words");
+        assert!(alice.connected_peers().await.is_empty());
+        assert!(alice.is_shut_down());
+
+        let event = tokio::time::timeout(Duration::from_secs(1), bob_events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(event, SessionEvent::PeerLeft { peer_id } if &peer_id == alice.peer_id()));
+        let event = bob_awareness.recv().await.unwrap();
+        assert!(matches!(event, AwarenessEvent::UserOffline(id) if id == alice.peer_id().0));
+        // Control is read before presence, so only the edit's position is fixed
+        let mut seen = bob_seen.read().clone();
+        assert_eq!(seen.remove(0), "update");
+        seen.sort();
+        assert_eq!(seen, ["awareness", "goodbye"]);
+
+        // Further local edits are refused
+        doc.write().insert(0, "late ");
+        assert_eq!(doc.read().get_text(), "final words");
+        assert!(!doc.read().is_writable());
+        assert!(matches!(
+            alice_session.publish().await,
+            Err(SdkError::ShuttingDown(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_with_unreachable_peer_times_out() {
+        let mut clients = pair(200);
+        let (bob, _bob_session) = clients.pop().unwrap();
+        let (alice, alice_session) = clients.pop().unwrap();
+        // Bob never reads his inbox, so nothing is acknowledged
+        let (_alice_pump, _) = pump(&alice, &alice_session);
+
+        alice_session
+            .open_text_doc("doc")
+            .write()
+            .insert(0, "lost?");
+        let start = Instant::now();
+        let result = alice.shutdown().await;
+        let elapsed = start.elapsed();
+
+        let Err(SdkError::PartialFlush(unacked)) = result else {
+            panic!("expected a partial flush, got {:?}", result);
+        };
+        assert_eq!(unacked.len(), 1);
+        assert_eq!(&unacked[0].peer_id, bob.peer_id());
+        assert_eq!(unacked[0].document_id, "doc");
+        assert_eq!(unacked[0].versions, vec![1]);
+        assert!(elapsed >= Duration::from_millis(200));
+        assert!(elapsed < Duration::from_secs(2));
+        assert!(alice.connected_peers().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_drop_sends_pending_edits() {
+        let mut clients = pair(2000);
+        let (bob, bob_session) = clients.pop().unwrap();
+        let (alice, alice_session) = clients.pop().unwrap();
+        let (_bob_pump, bob_seen) = pump(&bob, &bob_session);
+
+        alice_session
+            .open_text_doc("doc")
+            .write()
+            .insert(0, "dropped");
+        drop(alice_session);
+        drop(alice);
+
+        let bob_doc = bob_session.open_text_doc("doc");
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while !bob_seen.read().contains(&"goodbye") {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(bob_doc.read().get_text(), "dropped");
+    }
 }
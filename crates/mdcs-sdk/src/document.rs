@@ -1,22 +1,427 @@
 //! Document wrappers for collaborative editing.
 
+use crate::error::SdkError;
+use mdcs_core::clock::SharedClock;
 use mdcs_core::lattice::Lattice;
 use mdcs_db::{
-    json_crdt::{JsonCrdt, JsonPath, JsonValue},
-    rga_text::RGAText,
-    rich_text::{MarkType, RichText},
+    json_crdt::{ArrayId, JsonCrdt, JsonCrdtDelta, JsonObserver, JsonPath, JsonValue, PathSegment},
+    presence::CursorLocation,
+    rga_text::{RGAText, RGATextDelta, TextObserver},
+    rich_text::{BlockType, MarkType, RichText, RichTextDelta},
+    undo::{
+        JsonOperation, TextOperation, UndoConfig, UndoHistory, UndoManager, UndoOutcome,
+        UndoableOperation,
+    },
+    VersionVector,
 };
-use tokio::sync::broadcast;
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc};
 
-/// Events emitted when a document changes.
-#[derive(Clone, Debug)]
-pub enum DocEvent {
+/// Events retained per document for [`CollaborativeDoc::resync_events`].
+pub const DEFAULT_EVENT_HISTORY: usize = 256;
+
+/// Capacity of a document's live event channel.
+const EVENT_CHANNEL_CAPACITY: usize = 100;
+
+/// A change to a document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DocChange {
     /// Text was inserted.
     Insert { position: usize, text: String },
     /// Text was deleted.
     Delete { position: usize, length: usize },
     /// Remote changes were applied.
     RemoteUpdate,
+    /// An undo or redo skipped operations the document no longer allows,
+    /// e.g. because a remote edit changed the text they would delete.
+    UndoSkipped { operations: usize },
+}
+
+/// Event emitted when a document changes.
+///
+/// Events of a document are numbered from 1 without gaps, so a subscriber
+/// that sees `seq` jump past the one it expected has missed events and
+/// should call [`CollaborativeDoc::resync_events`]. A subscriber that falls
+/// behind the live channel also gets
+/// [`RecvError::Lagged`](broadcast::error::RecvError::Lagged) rather than
+/// silently skipping ahead.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DocEvent {
+    /// Position of the event in the document's event stream.
+    pub seq: u64,
+    /// What changed.
+    pub change: DocChange,
+}
+
+impl DocEvent {
+    /// Get the event's sequence number.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+}
+
+/// Result of [`CollaborativeDoc::resync_events`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Resync {
+    /// The requested events, oldest first.
+    Events(Vec<DocEvent>),
+    /// Some requested events are no longer retained. Rebuild the view from
+    /// the document's current state and continue after `seq`.
+    Snapshot(DocSummary),
+}
+
+/// Current state of a document, for rebuilding a view after a resync.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DocSummary {
+    /// Sequence number of the latest event; the state includes it.
+    pub seq: u64,
+    /// Length of the document: characters for text, top-level keys for JSON.
+    pub len: usize,
+}
+
+/// Numbers a document's events, publishes them and keeps the most recent
+/// ones for replay. Shared by clones of the document.
+#[derive(Clone)]
+struct EventLog(Arc<Mutex<EventLogState>>);
+
+struct EventLogState {
+    tx: broadcast::Sender<DocEvent>,
+    history: VecDeque<DocEvent>,
+    capacity: usize,
+    last_seq: u64,
+}
+
+impl EventLog {
+    fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self(Arc::new(Mutex::new(EventLogState {
+            tx,
+            history: VecDeque::new(),
+            capacity,
+            last_seq: 0,
+        })))
+    }
+
+    /// Number and publish a change.
+    fn emit(&self, change: DocChange) {
+        let mut state = self.0.lock();
+        state.last_seq += 1;
+        let event = DocEvent {
+            seq: state.last_seq,
+            change,
+        };
+        if state.capacity > 0 {
+            if state.history.len() == state.capacity {
+                state.history.pop_front();
+            }
+            state.history.push_back(event.clone());
+        }
+        let _ = state.tx.send(event);
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<DocEvent> {
+        self.0.lock().tx.subscribe()
+    }
+
+    fn set_capacity(&self, capacity: usize) {
+        let mut state = self.0.lock();
+        state.capacity = capacity;
+        let excess = state.history.len().saturating_sub(capacity);
+        state.history.drain(..excess);
+    }
+
+    fn last_seq(&self) -> u64 {
+        self.0.lock().last_seq
+    }
+
+    /// Events from `from_seq` on, or a snapshot instruction if any of them
+    /// were evicted.
+    fn resync(&self, from_seq: u64, len: usize) -> Resync {
+        let state = self.0.lock();
+        let from_seq = from_seq.max(1);
+        if from_seq > state.last_seq {
+            return Resync::Events(Vec::new());
+        }
+        match state.history.front() {
+            Some(oldest) if oldest.seq <= from_seq => Resync::Events(
+                state
+                    .history
+                    .iter()
+                    .filter(|event| event.seq >= from_seq)
+                    .cloned()
+                    .collect(),
+            ),
+            _ => Resync::Snapshot(DocSummary {
+                seq: state.last_seq,
+                len,
+            }),
+        }
+    }
+}
+
+/// Switch shared by a session and its documents that stops local edits once
+/// the session is closing.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct WriteGate(Arc<AtomicBool>);
+
+impl WriteGate {
+    /// Reject all further local edits.
+    pub(crate) fn close(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Check whether local edits are still accepted.
+    pub(crate) fn is_open(&self) -> bool {
+        !self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Serialize a CRDT delta for [`CollaborativeDoc::take_pending_deltas`].
+fn encode_delta<D: Serialize>(delta: Option<D>) -> Option<Vec<u8>> {
+    serde_json::to_vec(&delta?).ok()
+}
+
+/// Deserialize a delta produced by [`encode_delta`].
+fn decode_delta<D: DeserializeOwned>(bytes: &[u8]) -> Option<D> {
+    serde_json::from_slice(bytes).ok()
+}
+
+/// Plain text shared by the text documents, for applying undo operations.
+trait UndoText {
+    fn undo_len(&self) -> usize;
+    fn undo_slice(&self, start: usize, end: usize) -> String;
+    fn undo_insert(&mut self, position: usize, text: &str);
+    fn undo_delete(&mut self, position: usize, length: usize);
+}
+
+impl UndoText for RGAText {
+    fn undo_len(&self) -> usize {
+        self.len()
+    }
+
+    fn undo_slice(&self, start: usize, end: usize) -> String {
+        self.slice(start, end)
+    }
+
+    fn undo_insert(&mut self, position: usize, text: &str) {
+        self.insert(position, text);
+    }
+
+    fn undo_delete(&mut self, position: usize, length: usize) {
+        self.delete(position, length);
+    }
+}
+
+impl UndoText for RichText {
+    fn undo_len(&self) -> usize {
+        self.len()
+    }
+
+    fn undo_slice(&self, start: usize, end: usize) -> String {
+        self.text().slice(start, end)
+    }
+
+    fn undo_insert(&mut self, position: usize, text: &str) {
+        self.insert(position, text);
+    }
+
+    fn undo_delete(&mut self, position: usize, length: usize) {
+        self.delete(position, length);
+    }
+}
+
+/// Record a local text insert for undo.
+fn record_insert(undo: &mut UndoManager, text: &impl UndoText, position: usize, inserted: &str) {
+    if inserted.is_empty() {
+        return;
+    }
+    // Text inserted past the end lands at the start
+    let position = if position > text.undo_len() {
+        0
+    } else {
+        position
+    };
+    undo.record(UndoableOperation::Text(TextOperation::Insert {
+        position,
+        text: inserted.to_string(),
+    }));
+}
+
+/// Record a local text delete for undo; call it before deleting.
+fn record_delete(undo: &mut UndoManager, text: &impl UndoText, position: usize, length: usize) {
+    let len = text.undo_len();
+    let deleted = text.undo_slice(position.min(len), position.saturating_add(length).min(len));
+    if deleted.is_empty() {
+        return;
+    }
+    undo.record(UndoableOperation::Text(TextOperation::Delete {
+        position,
+        deleted,
+    }));
+}
+
+/// Shifts an undo history past the edits a merge reports.
+struct RebaseUndo<'a>(&'a mut UndoManager);
+
+impl TextObserver for RebaseUndo<'_> {
+    fn on_insert(&mut self, position: usize, text: &str) {
+        self.0.transform_text(position, 0, text.chars().count());
+    }
+
+    fn on_delete(&mut self, position: usize, length: usize) {
+        self.0.transform_text(position, length, 0);
+    }
+}
+
+/// Shift the undo history past the edits that turned `before` into
+/// `after`, which must contain it.
+fn rebase_undo(undo: &mut UndoManager, before: &RGAText, after: &RGAText) {
+    before.join_observed(after, &mut RebaseUndo(undo));
+}
+
+/// Check that `expected` is still the text at `position`.
+fn text_matches(text: &impl UndoText, position: usize, expected: &str) -> bool {
+    let end = position + expected.chars().count();
+    end <= text.undo_len() && text.undo_slice(position, end) == expected
+}
+
+/// Apply an undo or redo operation to text, if it still fits.
+///
+/// An insert needs its position to exist, and a delete or replace needs
+/// the text it removes to be unchanged. Returns `false` without touching
+/// the text otherwise.
+fn apply_text_undo(text: &mut impl UndoText, events: &EventLog, op: &UndoableOperation) -> bool {
+    let UndoableOperation::Text(op) = op else {
+        return false;
+    };
+    match op {
+        TextOperation::Insert {
+            position,
+            text: inserted,
+        } => {
+            if *position > text.undo_len() {
+                return false;
+            }
+            text.undo_insert(*position, inserted);
+            events.emit(DocChange::Insert {
+                position: *position,
+                text: inserted.clone(),
+            });
+        }
+        TextOperation::Delete { position, deleted } => {
+            if !text_matches(text, *position, deleted) {
+                return false;
+            }
+            let length = deleted.chars().count();
+            text.undo_delete(*position, length);
+            events.emit(DocChange::Delete {
+                position: *position,
+                length,
+            });
+        }
+        TextOperation::Replace {
+            position,
+            deleted,
+            inserted,
+        } => {
+            if !text_matches(text, *position, deleted) {
+                return false;
+            }
+            let length = deleted.chars().count();
+            text.undo_delete(*position, length);
+            events.emit(DocChange::Delete {
+                position: *position,
+                length,
+            });
+            text.undo_insert(*position, inserted);
+            events.emit(DocChange::Insert {
+                position: *position,
+                text: inserted.clone(),
+            });
+        }
+    }
+    true
+}
+
+/// Convert a scalar value for the undo history; arrays, objects and
+/// counters aren't undoable.
+fn scalar_to_json(value: &JsonValue) -> Option<serde_json::Value> {
+    match value {
+        JsonValue::Null => Some(serde_json::Value::Null),
+        JsonValue::Bool(b) => Some(serde_json::Value::Bool(*b)),
+        JsonValue::Int(i) => Some(serde_json::Value::from(*i)),
+        JsonValue::Float(f) => serde_json::Number::from_f64(*f).map(serde_json::Value::Number),
+        JsonValue::String(s) => Some(serde_json::Value::String(s.clone())),
+        JsonValue::Array(_) | JsonValue::Object(_) | JsonValue::Counter(_) => None,
+    }
+}
+
+/// Convert a scalar from the undo history back.
+fn scalar_from_json(value: &serde_json::Value) -> Option<JsonValue> {
+    match value {
+        serde_json::Value::Null => Some(JsonValue::Null),
+        serde_json::Value::Bool(b) => Some(JsonValue::Bool(*b)),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Some(JsonValue::Int(i)),
+            None => n.as_f64().map(JsonValue::Float),
+        },
+        serde_json::Value::String(s) => Some(JsonValue::String(s.clone())),
+        _ => None,
+    }
+}
+
+/// The value at a path as the undo history sees it: `None` if unset
+/// (deleted keys read as null), `Some(None)` for an array, object or
+/// counter.
+fn undo_value_at(doc: &JsonCrdt, path: &JsonPath) -> Option<Option<serde_json::Value>> {
+    match doc.get(path) {
+        None | Some(JsonValue::Null) => None,
+        Some(value) => Some(scalar_to_json(value)),
+    }
+}
+
+/// Apply an undo or redo operation to a JSON document, if the value it
+/// replaces is unchanged. Returns `false` without touching the document
+/// otherwise.
+fn apply_json_undo(doc: &mut Arc<JsonCrdt>, op: &UndoableOperation) -> bool {
+    match op {
+        UndoableOperation::Json(JsonOperation::Set {
+            path,
+            old_value,
+            new_value,
+        }) => {
+            let path = JsonPath::parse(path);
+            let Some(value) = scalar_from_json(new_value) else {
+                return false;
+            };
+            if undo_value_at(doc, &path) != old_value.clone().map(Some) {
+                return false;
+            }
+            Arc::make_mut(doc).set(&path, value).is_ok()
+        }
+        UndoableOperation::Json(JsonOperation::Delete { path, old_value }) => {
+            let path = JsonPath::parse(path);
+            if undo_value_at(doc, &path) != Some(Some(old_value.clone())) {
+                return false;
+            }
+            Arc::make_mut(doc).delete(&path).is_ok()
+        }
+        _ => false,
+    }
+}
+
+/// Report an undo or redo, returning whether it changed anything.
+fn finish_undo(events: &EventLog, outcome: UndoOutcome) -> bool {
+    if !outcome.skipped.is_empty() {
+        events.emit(DocChange::UndoSkipped {
+            operations: outcome.skipped.len(),
+        });
+    }
+    !outcome.applied.is_empty()
 }
 
 /// Trait for collaborative documents.
@@ -30,11 +435,45 @@ pub trait CollaborativeDoc {
     /// Subscribe to document events.
     fn subscribe(&self) -> broadcast::Receiver<DocEvent>;
 
+    /// Get the sequence number of the latest event (0 before the first).
+    fn last_event_seq(&self) -> u64;
+
+    /// Replay events from `from_seq` on, to recover from a gap.
+    ///
+    /// Returns [`Resync::Snapshot`] if some of them are no longer retained.
+    fn resync_events(&self, from_seq: u64) -> Resync;
+
     /// Take pending deltas for sync.
     fn take_pending_deltas(&mut self) -> Vec<Vec<u8>>;
 
     /// Apply a remote delta.
+    ///
+    /// A delta is applied as a whole under `&mut self`, so a reader holding
+    /// the document's read guard never sees part of one.
     fn apply_remote(&mut self, delta: &[u8]);
+
+    /// Check whether local edits are accepted.
+    ///
+    /// Edits to a document whose session is closing are ignored.
+    fn is_writable(&self) -> bool;
+
+    /// Highest sequence number seen from each replica.
+    ///
+    /// Compare it with a peer's to tell whether this replica has seen that
+    /// peer's changes; [`last_event_seq`](Self::last_event_seq) only counts
+    /// what happened locally.
+    fn version_vector(&self) -> VersionVector;
+
+    /// Check whether this document has seen everything `other` has, and more.
+    fn is_newer_than(&self, other: &VersionVector) -> bool {
+        self.version_vector().strictly_dominates(other)
+    }
+
+    /// Replicas where `other` is ahead, as `(replica_id, first, last)`
+    /// ranges of sequence numbers this document hasn't seen.
+    fn missing_from(&self, other: &VersionVector) -> Vec<(String, u64, u64)> {
+        other.diff(&self.version_vector())
+    }
 }
 
 /// A collaborative plain text document.
@@ -43,30 +482,37 @@ pub struct TextDoc {
     id: String,
     replica_id: String,
     text: RGAText,
-    #[allow(dead_code)]
-    event_tx: broadcast::Sender<DocEvent>,
+    events: EventLog,
     pending_deltas: Vec<Vec<u8>>,
+    gate: WriteGate,
+    undo: UndoManager,
 }
 
 impl TextDoc {
     /// Create a new text document.
     pub fn new(id: impl Into<String>, replica_id: impl Into<String>) -> Self {
+        let id = id.into();
         let replica_id = replica_id.into();
-        let (event_tx, _) = broadcast::channel(100);
 
         Self {
-            id: id.into(),
+            undo: UndoManager::new(&id, &replica_id),
+            id,
             replica_id: replica_id.clone(),
             text: RGAText::new(&replica_id),
-            event_tx,
+            events: EventLog::new(DEFAULT_EVENT_HISTORY),
             pending_deltas: Vec::new(),
+            gate: WriteGate::default(),
         }
     }
 
     /// Insert text at position.
     pub fn insert(&mut self, position: usize, text: &str) {
+        if !self.gate.is_open() {
+            return;
+        }
+        record_insert(&mut self.undo, &self.text, position, text);
         self.text.insert(position, text);
-        let _ = self.event_tx.send(DocEvent::Insert {
+        self.events.emit(DocChange::Insert {
             position,
             text: text.to_string(),
         });
@@ -74,8 +520,12 @@ impl TextDoc {
 
     /// Delete text at position.
     pub fn delete(&mut self, position: usize, length: usize) {
+        if !self.gate.is_open() {
+            return;
+        }
+        record_delete(&mut self.undo, &self.text, position, length);
         self.text.delete(position, length);
-        let _ = self.event_tx.send(DocEvent::Delete { position, length });
+        self.events.emit(DocChange::Delete { position, length });
     }
 
     /// Get the current text content.
@@ -96,8 +546,114 @@ impl TextDoc {
     /// Merge another document's state into this one (CRDT merge).
     /// This applies changes from the other document while preserving local changes.
     pub fn merge(&mut self, other: &TextDoc) {
-        self.text = self.text.join(&other.text);
-        let _ = self.event_tx.send(DocEvent::RemoteUpdate);
+        self.text = self
+            .text
+            .join_observed(&other.text, &mut RebaseUndo(&mut self.undo));
+        self.events.emit(DocChange::RemoteUpdate);
+    }
+
+    /// Encode the full document state for sending to another replica.
+    pub fn encode_state(&self) -> Vec<u8> {
+        serde_json::to_vec(&self.text).unwrap_or_default()
+    }
+
+    /// Merge a state produced by [`encode_state`](Self::encode_state).
+    pub fn merge_encoded(&mut self, bytes: &[u8]) -> Result<(), SdkError> {
+        let other: RGAText = serde_json::from_slice(bytes)?;
+        self.text = self
+            .text
+            .join_observed(&other, &mut RebaseUndo(&mut self.undo));
+        self.events.emit(DocChange::RemoteUpdate);
+        Ok(())
+    }
+
+    /// Undo the last local edit, or group of edits.
+    ///
+    /// Remote edits are never undone; the positions of local ones follow
+    /// them as they are merged. Each edit is checked against the current
+    /// text first: one whose text has changed since, e.g. by a remote edit
+    /// while the document was closed, is skipped and reported as
+    /// [`DocChange::UndoSkipped`] instead of applied where it no longer
+    /// fits. Returns whether anything was undone.
+    pub fn undo(&mut self) -> bool {
+        if !self.gate.is_open() {
+            return false;
+        }
+        let (text, events) = (&mut self.text, &self.events);
+        let outcome = self.undo.undo_with(|op| apply_text_undo(text, events, op));
+        finish_undo(&self.events, outcome)
+    }
+
+    /// Redo the last undone edit, or group of edits.
+    ///
+    /// Edits are checked as in [`undo`](Self::undo).
+    pub fn redo(&mut self) -> bool {
+        if !self.gate.is_open() {
+            return false;
+        }
+        let (text, events) = (&mut self.text, &self.events);
+        let outcome = self.undo.redo_with(|op| apply_text_undo(text, events, op));
+        finish_undo(&self.events, outcome)
+    }
+
+    /// Check if there is an edit to undo.
+    pub fn can_undo(&self) -> bool {
+        self.undo.can_undo()
+    }
+
+    /// Check if there is an edit to redo.
+    pub fn can_redo(&self) -> bool {
+        self.undo.can_redo()
+    }
+
+    /// Start a group of edits that are undone together.
+    pub fn start_undo_group(&mut self) {
+        self.undo.start_group();
+    }
+
+    /// End the current group of edits.
+    pub fn end_undo_group(&mut self) {
+        self.undo.end_group();
+    }
+
+    /// Set the limits on the undo history, and how long a pause ends a
+    /// burst of typing that is undone as one edit.
+    pub fn with_undo_config(mut self, config: UndoConfig) -> Self {
+        self.undo.set_config(config);
+        self
+    }
+
+    /// Use `clock` to time edits in the undo history.
+    pub fn with_clock(mut self, clock: impl Into<SharedClock>) -> Self {
+        self.undo = self.undo.with_clock(clock);
+        self
+    }
+
+    /// Export the undo history, e.g. to store it with the document.
+    pub fn export_undo(&self) -> UndoHistory {
+        self.undo.export()
+    }
+
+    /// Replace the undo history with one from
+    /// [`export_undo`](Self::export_undo).
+    pub fn import_undo(&mut self, history: UndoHistory) {
+        self.undo.import(history);
+    }
+
+    /// Set how many recent events are kept for
+    /// [`resync_events`](CollaborativeDoc::resync_events).
+    ///
+    /// Defaults to [`DEFAULT_EVENT_HISTORY`]. The setting is shared with
+    /// clones of the document.
+    pub fn with_event_history(self, capacity: usize) -> Self {
+        self.events.set_capacity(capacity);
+        self
+    }
+
+    /// Attach the document to a session's write gate.
+    pub(crate) fn with_gate(mut self, gate: WriteGate) -> Self {
+        self.gate = gate;
+        self
     }
 
     /// Clone this document's state for syncing to another replica.
@@ -106,8 +662,11 @@ impl TextDoc {
             id: self.id.clone(),
             replica_id: self.replica_id.clone(),
             text: self.text.clone(),
-            event_tx: self.event_tx.clone(),
+            events: self.events.clone(),
             pending_deltas: Vec::new(),
+            gate: WriteGate::default(),
+            undo: UndoManager::new(&self.id, &self.replica_id)
+                .with_config(self.undo.config().clone()),
         }
     }
 }
@@ -122,16 +681,40 @@ impl CollaborativeDoc for TextDoc {
     }
 
     fn subscribe(&self) -> broadcast::Receiver<DocEvent> {
-        self.event_tx.subscribe()
+        self.events.subscribe()
+    }
+
+    fn last_event_seq(&self) -> u64 {
+        self.events.last_seq()
+    }
+
+    fn resync_events(&self, from_seq: u64) -> Resync {
+        self.events.resync(from_seq, self.len())
     }
 
     fn take_pending_deltas(&mut self) -> Vec<Vec<u8>> {
+        self.pending_deltas
+            .extend(encode_delta(self.text.take_delta()));
         std::mem::take(&mut self.pending_deltas)
     }
 
-    fn apply_remote(&mut self, _delta: &[u8]) {
-        // In a real implementation, deserialize and apply delta
-        let _ = self.event_tx.send(DocEvent::RemoteUpdate);
+    fn apply_remote(&mut self, delta: &[u8]) {
+        if let Some(delta) = decode_delta::<RGATextDelta>(delta) {
+            let before = (self.undo.history_len() > 0).then(|| self.text.clone());
+            self.text.apply_delta(&delta);
+            if let Some(before) = before {
+                rebase_undo(&mut self.undo, &before, &self.text);
+            }
+        }
+        self.events.emit(DocChange::RemoteUpdate);
+    }
+
+    fn is_writable(&self) -> bool {
+        self.gate.is_open()
+    }
+
+    fn version_vector(&self) -> VersionVector {
+        self.text.version_vector()
     }
 }
 
@@ -141,30 +724,37 @@ pub struct RichTextDoc {
     id: String,
     replica_id: String,
     text: RichText,
-    #[allow(dead_code)]
-    event_tx: broadcast::Sender<DocEvent>,
+    events: EventLog,
     pending_deltas: Vec<Vec<u8>>,
+    gate: WriteGate,
+    undo: UndoManager,
 }
 
 impl RichTextDoc {
     /// Create a new rich text document.
     pub fn new(id: impl Into<String>, replica_id: impl Into<String>) -> Self {
+        let id = id.into();
         let replica_id = replica_id.into();
-        let (event_tx, _) = broadcast::channel(100);
 
         Self {
-            id: id.into(),
+            undo: UndoManager::new(&id, &replica_id),
+            id,
             replica_id: replica_id.clone(),
             text: RichText::new(&replica_id),
-            event_tx,
+            events: EventLog::new(DEFAULT_EVENT_HISTORY),
             pending_deltas: Vec::new(),
+            gate: WriteGate::default(),
         }
     }
 
     /// Insert text at position.
     pub fn insert(&mut self, position: usize, text: &str) {
+        if !self.gate.is_open() {
+            return;
+        }
+        record_insert(&mut self.undo, &self.text, position, text);
         self.text.insert(position, text);
-        let _ = self.event_tx.send(DocEvent::Insert {
+        self.events.emit(DocChange::Insert {
             position,
             text: text.to_string(),
         });
@@ -172,20 +762,44 @@ impl RichTextDoc {
 
     /// Delete text at position.
     pub fn delete(&mut self, position: usize, length: usize) {
+        if !self.gate.is_open() {
+            return;
+        }
+        record_delete(&mut self.undo, &self.text, position, length);
         self.text.delete(position, length);
-        let _ = self.event_tx.send(DocEvent::Delete { position, length });
+        self.events.emit(DocChange::Delete { position, length });
     }
 
     /// Apply formatting to a range.
     pub fn format(&mut self, start: usize, end: usize, mark: MarkType) {
+        if !self.gate.is_open() {
+            return;
+        }
         self.text.add_mark(start, end, mark);
     }
 
     /// Remove formatting by mark ID.
     pub fn unformat_by_id(&mut self, mark_id: &mdcs_db::rich_text::MarkId) {
+        if !self.gate.is_open() {
+            return;
+        }
         self.text.remove_mark(mark_id);
     }
 
+    /// Set the block type of the paragraph containing `position`, e.g. to
+    /// make it a heading or list item.
+    pub fn set_block_type(&mut self, position: usize, block_type: BlockType) {
+        if !self.gate.is_open() {
+            return;
+        }
+        self.text.set_block_type(position, block_type);
+    }
+
+    /// Get the block type of the paragraph containing `position`.
+    pub fn block_type_at(&self, position: usize) -> BlockType {
+        self.text.block_type_at(position)
+    }
+
     /// Get the plain text content.
     pub fn get_text(&self) -> String {
         self.text.to_string()
@@ -197,6 +811,11 @@ impl RichTextDoc {
         self.text.to_string()
     }
 
+    /// Render as HTML, marks and block types included.
+    pub fn to_html(&self) -> String {
+        self.text.to_html()
+    }
+
     /// Get the text length.
     pub fn len(&self) -> usize {
         self.text.len()
@@ -210,8 +829,114 @@ impl RichTextDoc {
     /// Merge another document's state into this one (CRDT merge).
     /// This applies changes from the other document while preserving local changes.
     pub fn merge(&mut self, other: &RichTextDoc) {
-        self.text = self.text.join(&other.text);
-        let _ = self.event_tx.send(DocEvent::RemoteUpdate);
+        self.text = self
+            .text
+            .join_observed(&other.text, &mut RebaseUndo(&mut self.undo));
+        self.events.emit(DocChange::RemoteUpdate);
+    }
+
+    /// Encode the full document state for sending to another replica.
+    pub fn encode_state(&self) -> Vec<u8> {
+        serde_json::to_vec(&self.text).unwrap_or_default()
+    }
+
+    /// Merge a state produced by [`encode_state`](Self::encode_state).
+    pub fn merge_encoded(&mut self, bytes: &[u8]) -> Result<(), SdkError> {
+        let other: RichText = serde_json::from_slice(bytes)?;
+        self.text = self
+            .text
+            .join_observed(&other, &mut RebaseUndo(&mut self.undo));
+        self.events.emit(DocChange::RemoteUpdate);
+        Ok(())
+    }
+
+    /// Undo the last local edit, or group of edits.
+    ///
+    /// Remote edits are never undone; the positions of local ones follow
+    /// them as they are merged. Each edit is checked against the current
+    /// text first: one whose text has changed since, e.g. by a remote edit
+    /// while the document was closed, is skipped and reported as
+    /// [`DocChange::UndoSkipped`] instead of applied where it no longer
+    /// fits. Returns whether anything was undone.
+    pub fn undo(&mut self) -> bool {
+        if !self.gate.is_open() {
+            return false;
+        }
+        let (text, events) = (&mut self.text, &self.events);
+        let outcome = self.undo.undo_with(|op| apply_text_undo(text, events, op));
+        finish_undo(&self.events, outcome)
+    }
+
+    /// Redo the last undone edit, or group of edits.
+    ///
+    /// Edits are checked as in [`undo`](Self::undo).
+    pub fn redo(&mut self) -> bool {
+        if !self.gate.is_open() {
+            return false;
+        }
+        let (text, events) = (&mut self.text, &self.events);
+        let outcome = self.undo.redo_with(|op| apply_text_undo(text, events, op));
+        finish_undo(&self.events, outcome)
+    }
+
+    /// Check if there is an edit to undo.
+    pub fn can_undo(&self) -> bool {
+        self.undo.can_undo()
+    }
+
+    /// Check if there is an edit to redo.
+    pub fn can_redo(&self) -> bool {
+        self.undo.can_redo()
+    }
+
+    /// Start a group of edits that are undone together.
+    pub fn start_undo_group(&mut self) {
+        self.undo.start_group();
+    }
+
+    /// End the current group of edits.
+    pub fn end_undo_group(&mut self) {
+        self.undo.end_group();
+    }
+
+    /// Set the limits on the undo history, and how long a pause ends a
+    /// burst of typing that is undone as one edit.
+    pub fn with_undo_config(mut self, config: UndoConfig) -> Self {
+        self.undo.set_config(config);
+        self
+    }
+
+    /// Use `clock` to time edits in the undo history.
+    pub fn with_clock(mut self, clock: impl Into<SharedClock>) -> Self {
+        self.undo = self.undo.with_clock(clock);
+        self
+    }
+
+    /// Export the undo history, e.g. to store it with the document.
+    pub fn export_undo(&self) -> UndoHistory {
+        self.undo.export()
+    }
+
+    /// Replace the undo history with one from
+    /// [`export_undo`](Self::export_undo).
+    pub fn import_undo(&mut self, history: UndoHistory) {
+        self.undo.import(history);
+    }
+
+    /// Set how many recent events are kept for
+    /// [`resync_events`](CollaborativeDoc::resync_events).
+    ///
+    /// Defaults to [`DEFAULT_EVENT_HISTORY`]. The setting is shared with
+    /// clones of the document.
+    pub fn with_event_history(self, capacity: usize) -> Self {
+        self.events.set_capacity(capacity);
+        self
+    }
+
+    /// Attach the document to a session's write gate.
+    pub(crate) fn with_gate(mut self, gate: WriteGate) -> Self {
+        self.gate = gate;
+        self
     }
 
     /// Clone this document's state for syncing to another replica.
@@ -220,8 +945,11 @@ impl RichTextDoc {
             id: self.id.clone(),
             replica_id: self.replica_id.clone(),
             text: self.text.clone(),
-            event_tx: self.event_tx.clone(),
+            events: self.events.clone(),
             pending_deltas: Vec::new(),
+            gate: WriteGate::default(),
+            undo: UndoManager::new(&self.id, &self.replica_id)
+                .with_config(self.undo.config().clone()),
         }
     }
 }
@@ -236,16 +964,203 @@ impl CollaborativeDoc for RichTextDoc {
     }
 
     fn subscribe(&self) -> broadcast::Receiver<DocEvent> {
-        self.event_tx.subscribe()
+        self.events.subscribe()
+    }
+
+    fn last_event_seq(&self) -> u64 {
+        self.events.last_seq()
+    }
+
+    fn resync_events(&self, from_seq: u64) -> Resync {
+        self.events.resync(from_seq, self.len())
     }
 
     fn take_pending_deltas(&mut self) -> Vec<Vec<u8>> {
+        self.pending_deltas
+            .extend(encode_delta(self.text.take_delta()));
         std::mem::take(&mut self.pending_deltas)
     }
 
-    fn apply_remote(&mut self, _delta: &[u8]) {
-        let _ = self.event_tx.send(DocEvent::RemoteUpdate);
+    fn apply_remote(&mut self, delta: &[u8]) {
+        if let Some(delta) = decode_delta::<RichTextDelta>(delta) {
+            let before = (self.undo.history_len() > 0).then(|| self.text.text().clone());
+            self.text.apply_delta(&delta);
+            if let Some(before) = before {
+                rebase_undo(&mut self.undo, &before, self.text.text());
+            }
+        }
+        self.events.emit(DocChange::RemoteUpdate);
+    }
+
+    fn is_writable(&self) -> bool {
+        self.gate.is_open()
     }
+
+    fn version_vector(&self) -> VersionVector {
+        self.text.version_vector()
+    }
+}
+
+/// Where a change to a [`JsonDoc`] came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeOrigin {
+    /// An edit on this replica, including undo and redo.
+    Local,
+    /// A merge or delta from another replica.
+    Remote,
+}
+
+/// A value that changed under a path watched with [`JsonDoc::watch`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonChangeEvent {
+    /// Path of the changed value.
+    pub path: String,
+    /// The value before the change, or `None` if the path was unset.
+    pub old_value: Option<serde_json::Value>,
+    /// The value after the change, or `None` if the path was removed.
+    pub new_value: Option<serde_json::Value>,
+    /// Where the change came from.
+    pub origin: ChangeOrigin,
+}
+
+/// Watchers of a JSON document's paths. Shared by clones of the document.
+#[derive(Clone, Default)]
+struct JsonWatchers(Arc<Mutex<Vec<JsonWatcher>>>);
+
+struct JsonWatcher {
+    prefix: JsonPath,
+    tx: mpsc::UnboundedSender<Vec<JsonChangeEvent>>,
+}
+
+/// Collects the paths a change touched.
+struct ChangedPaths(Vec<(JsonPath, Option<serde_json::Value>)>);
+
+impl JsonObserver for ChangedPaths {
+    fn on_path_changed(&mut self, path: &JsonPath, value: Option<&serde_json::Value>) {
+        self.0.push((path.clone(), value.cloned()));
+    }
+}
+
+impl JsonWatchers {
+    fn add(&self, prefix: JsonPath) -> mpsc::UnboundedReceiver<Vec<JsonChangeEvent>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.0.lock().push(JsonWatcher { prefix, tx });
+        rx
+    }
+
+    /// The state to compare against after a change, if anyone is watching.
+    /// Watchers whose receiver was dropped are removed.
+    fn before(&self, doc: &Arc<JsonCrdt>) -> Option<Arc<JsonCrdt>> {
+        let mut watchers = self.0.lock();
+        watchers.retain(|watcher| !watcher.tx.is_closed());
+        (!watchers.is_empty()).then(|| Arc::clone(doc))
+    }
+
+    /// Send each watcher the changes from `before` to `after` at, under or
+    /// above its prefix, as one batch.
+    fn notify(&self, before: Option<Arc<JsonCrdt>>, after: &Arc<JsonCrdt>, origin: ChangeOrigin) {
+        let Some(before) = before else {
+            return;
+        };
+        if Arc::ptr_eq(&before, after) {
+            return;
+        }
+        let mut changed = ChangedPaths(Vec::new());
+        before.report_changes(after, &mut changed);
+        if changed.0.is_empty() {
+            return;
+        }
+
+        let old_root = before.to_json();
+        let mut events = Vec::new();
+        for (path, new_value) in changed.0 {
+            leaf_changes(
+                &path,
+                json_at(&old_root, &path),
+                new_value.as_ref(),
+                origin,
+                &mut events,
+            );
+        }
+        self.0.lock().retain(|watcher| {
+            let batch: Vec<JsonChangeEvent> = events
+                .iter()
+                .filter(|(path, _)| {
+                    path.starts_with(&watcher.prefix) || watcher.prefix.starts_with(path)
+                })
+                .map(|(_, event)| event.clone())
+                .collect();
+            batch.is_empty() || watcher.tx.send(batch).is_ok()
+        });
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.0.lock().len()
+    }
+}
+
+/// Report a change from `old` to `new` at `path` value by value: objects
+/// are reported through their fields, so a watcher sees `settings.theme`
+/// change even when all of `settings` was created or replaced.
+fn leaf_changes(
+    path: &JsonPath,
+    old: Option<&serde_json::Value>,
+    new: Option<&serde_json::Value>,
+    origin: ChangeOrigin,
+    events: &mut Vec<(JsonPath, JsonChangeEvent)>,
+) {
+    let fields = |value: Option<&serde_json::Value>| match value {
+        Some(serde_json::Value::Object(map)) => Some(map.clone()),
+        _ => None,
+    };
+    let (old_fields, new_fields) = (fields(old), fields(new));
+    if old_fields.is_none() && new_fields.is_none() {
+        if old != new {
+            let event = JsonChangeEvent {
+                path: path.to_string(),
+                old_value: old.cloned(),
+                new_value: new.cloned(),
+                origin,
+            };
+            events.push((path.clone(), event));
+        }
+        return;
+    }
+
+    // A value replaced by an object, or the reverse, goes first
+    let scalar = |value, fields: &Option<_>| if fields.is_some() { None } else { value };
+    if let Some(old) = scalar(old, &old_fields) {
+        leaf_changes(path, Some(old), None, origin, events);
+    }
+    if let Some(new) = scalar(new, &new_fields) {
+        leaf_changes(path, None, Some(new), origin, events);
+    }
+    let (old_fields, new_fields) = (
+        old_fields.unwrap_or_default(),
+        new_fields.unwrap_or_default(),
+    );
+    let keys: std::collections::BTreeSet<&String> =
+        old_fields.keys().chain(new_fields.keys()).collect();
+    for key in keys {
+        leaf_changes(
+            &path.child_key(key.as_str()),
+            old_fields.get(key),
+            new_fields.get(key),
+            origin,
+            events,
+        );
+    }
+}
+
+/// The value at `path` in a document rendered as JSON.
+fn json_at<'a>(root: &'a serde_json::Value, path: &JsonPath) -> Option<&'a serde_json::Value> {
+    path.segments()
+        .iter()
+        .try_fold(root, |value, segment| match segment {
+            PathSegment::Key(key) => value.get(key.as_str()),
+            PathSegment::Index(index) => value.get(index),
+        })
 }
 
 /// A collaborative JSON document.
@@ -253,31 +1168,68 @@ impl CollaborativeDoc for RichTextDoc {
 pub struct JsonDoc {
     id: String,
     replica_id: String,
-    doc: JsonCrdt,
-    #[allow(dead_code)]
-    event_tx: broadcast::Sender<DocEvent>,
+    doc: Arc<JsonCrdt>,
+    events: EventLog,
+    watchers: JsonWatchers,
     pending_deltas: Vec<Vec<u8>>,
+    gate: WriteGate,
+    undo: UndoManager,
 }
 
 impl JsonDoc {
     /// Create a new JSON document.
     pub fn new(id: impl Into<String>, replica_id: impl Into<String>) -> Self {
+        let id = id.into();
         let replica_id = replica_id.into();
-        let (event_tx, _) = broadcast::channel(100);
 
         Self {
-            id: id.into(),
+            undo: UndoManager::new(&id, &replica_id),
+            id,
             replica_id: replica_id.clone(),
-            doc: JsonCrdt::new(&replica_id),
-            event_tx,
+            doc: Arc::new(JsonCrdt::new(&replica_id)),
+            events: EventLog::new(DEFAULT_EVENT_HISTORY),
+            watchers: JsonWatchers::default(),
             pending_deltas: Vec::new(),
+            gate: WriteGate::default(),
         }
     }
 
+    /// Watch for changes at or under `path_prefix`.
+    ///
+    /// Each edit, undo, merge or remote delta that changes values under
+    /// the prefix sends one batch holding all of them, so a merge is seen
+    /// whole. A change above the prefix, such as its parent object being
+    /// replaced, is included too. An empty prefix watches the whole
+    /// document. Drop the receiver to stop watching.
+    pub fn watch(&self, path_prefix: &str) -> mpsc::UnboundedReceiver<Vec<JsonChangeEvent>> {
+        self.watchers.add(JsonPath::parse(path_prefix))
+    }
+
     /// Set a value at a path.
     pub fn set(&mut self, path: &str, value: JsonValue) {
+        if !self.gate.is_open() {
+            return;
+        }
         let json_path = JsonPath::parse(path);
-        let _ = self.doc.set(&json_path, value);
+        let old_value = match undo_value_at(&self.doc, &json_path) {
+            Some(old) => old.map(Some),
+            None => Some(None),
+        };
+        let new_value = scalar_to_json(&value);
+        let before = self.watchers.before(&self.doc);
+        if self.crdt_mut().set(&json_path, value).is_err() {
+            return;
+        }
+        self.watchers.notify(before, &self.doc, ChangeOrigin::Local);
+        // Only scalars are undoable
+        if let (Some(old_value), Some(new_value)) = (old_value, new_value) {
+            self.undo
+                .record(UndoableOperation::Json(JsonOperation::Set {
+                    path: path.to_string(),
+                    old_value,
+                    new_value,
+                }));
+        }
     }
 
     /// Get a value at a path.
@@ -286,10 +1238,77 @@ impl JsonDoc {
         self.doc.get(&json_path).cloned()
     }
 
+    /// Get the values at several paths, all from the same state.
+    ///
+    /// A remote delta cannot land between two of the reads, since applying
+    /// one needs `&mut self`.
+    pub fn read_many(&self, paths: &[&str]) -> Vec<Option<JsonValue>> {
+        paths.iter().map(|path| self.get(path)).collect()
+    }
+
+    /// Capture an immutable view of the document.
+    ///
+    /// The snapshot shares the current state instead of copying it, and
+    /// does not change while held: the next edit or remote delta copies the
+    /// state before changing it. With the document behind a lock, as
+    /// [`Session::open_json_doc`](crate::Session::open_json_doc) returns it,
+    /// a snapshot taken under a read guard reflects whole deltas only.
+    pub fn read_snapshot(&self) -> JsonSnapshot {
+        JsonSnapshot {
+            doc: Arc::clone(&self.doc),
+        }
+    }
+
     /// Delete a value at a path.
     pub fn delete(&mut self, path: &str) {
+        if !self.gate.is_open() {
+            return;
+        }
+        let json_path = JsonPath::parse(path);
+        let old_value = undo_value_at(&self.doc, &json_path).flatten();
+        let before = self.watchers.before(&self.doc);
+        if self.crdt_mut().delete(&json_path).is_err() {
+            return;
+        }
+        self.watchers.notify(before, &self.doc, ChangeOrigin::Local);
+        if let Some(old_value) = old_value {
+            self.undo
+                .record(UndoableOperation::Json(JsonOperation::Delete {
+                    path: path.to_string(),
+                    old_value,
+                }));
+        }
+    }
+
+    /// Add `n` to the counter at a path, or subtract it if `n` is
+    /// negative.
+    ///
+    /// Concurrent increments on different replicas all count; see
+    /// [`JsonCrdt::increment`] for how a counter starts and how it
+    /// resolves against plain values. Increments aren't undoable.
+    pub fn increment(&mut self, path: &str, n: i64) {
+        if !self.gate.is_open() {
+            return;
+        }
         let json_path = JsonPath::parse(path);
-        let _ = self.doc.delete(&json_path);
+        let before = self.watchers.before(&self.doc);
+        let result = if n < 0 {
+            self.crdt_mut().decrement(&json_path, n.unsigned_abs())
+        } else {
+            self.crdt_mut().increment(&json_path, n.unsigned_abs())
+        };
+        if result.is_err() {
+            return;
+        }
+        self.watchers.notify(before, &self.doc, ChangeOrigin::Local);
+    }
+
+    /// Get the value of the counter at a path.
+    pub fn counter(&self, path: &str) -> Option<i64> {
+        match self.doc.get(&JsonPath::parse(path)) {
+            Some(JsonValue::Counter(id)) => self.doc.counter_value(id),
+            _ => None,
+        }
     }
 
     /// Get the root value as a serde JSON Value.
@@ -302,11 +1321,245 @@ impl JsonDoc {
         self.doc.keys()
     }
 
+    /// Get the array at a path, creating it if the path is unset.
+    ///
+    /// Replicas creating the same array concurrently each get their own;
+    /// only one survives the merge. Create shared arrays on one replica
+    /// and sync before editing them elsewhere.
+    fn array_at(&mut self, path: &str) -> Option<ArrayId> {
+        let json_path = JsonPath::parse(path);
+        match self.doc.get(&json_path) {
+            Some(JsonValue::Array(id)) => Some(id.clone()),
+            Some(_) => None,
+            None => self.crdt_mut().set_array(&json_path).ok(),
+        }
+    }
+
+    /// Create an empty object at a path, replacing any existing value.
+    pub fn set_object(&mut self, path: &str) {
+        if !self.gate.is_open() {
+            return;
+        }
+        let before = self.watchers.before(&self.doc);
+        let _ = self.crdt_mut().set_object(&JsonPath::parse(path));
+        self.watchers.notify(before, &self.doc, ChangeOrigin::Local);
+    }
+
+    /// Create an empty array at a path, replacing any existing value.
+    pub fn set_array(&mut self, path: &str) {
+        if !self.gate.is_open() {
+            return;
+        }
+        let before = self.watchers.before(&self.doc);
+        let _ = self.crdt_mut().set_array(&JsonPath::parse(path));
+        self.watchers.notify(before, &self.doc, ChangeOrigin::Local);
+    }
+
+    /// Append a value to the array at a path.
+    pub fn array_push(&mut self, path: &str, value: JsonValue) {
+        if !self.gate.is_open() {
+            return;
+        }
+        let before = self.watchers.before(&self.doc);
+        if let Some(id) = self.array_at(path) {
+            let _ = self.crdt_mut().array_push(&id, value);
+        }
+        self.watchers.notify(before, &self.doc, ChangeOrigin::Local);
+    }
+
+    /// Insert a value into the array at a path.
+    pub fn array_insert(&mut self, path: &str, index: usize, value: JsonValue) {
+        if !self.gate.is_open() {
+            return;
+        }
+        let before = self.watchers.before(&self.doc);
+        if let Some(id) = self.array_at(path) {
+            let _ = self.crdt_mut().array_insert(&id, index, value);
+        }
+        self.watchers.notify(before, &self.doc, ChangeOrigin::Local);
+    }
+
+    /// Remove and return the element at `index` of the array at a path.
+    pub fn array_remove(&mut self, path: &str, index: usize) -> Option<JsonValue> {
+        if !self.gate.is_open() {
+            return None;
+        }
+        let id = match self.doc.get(&JsonPath::parse(path)) {
+            Some(JsonValue::Array(id)) => id.clone(),
+            _ => return None,
+        };
+        let before = self.watchers.before(&self.doc);
+        let removed = self.crdt_mut().array_remove(&id, index).ok();
+        self.watchers.notify(before, &self.doc, ChangeOrigin::Local);
+        removed
+    }
+
+    /// Move the element at `from` of the array at a path so that it ends up
+    /// at `to`. Returns `false` if either index is out of bounds.
+    pub fn array_move(&mut self, path: &str, from: usize, to: usize) -> bool {
+        if !self.gate.is_open() {
+            return false;
+        }
+        let id = match self.doc.get(&JsonPath::parse(path)) {
+            Some(JsonValue::Array(id)) => id.clone(),
+            _ => return false,
+        };
+        let before = self.watchers.before(&self.doc);
+        let moved = self.crdt_mut().array_move(&id, from, to).is_ok();
+        self.watchers.notify(before, &self.doc, ChangeOrigin::Local);
+        moved
+    }
+
+    /// Get the length of the array at a path (0 if there is none).
+    pub fn array_len(&self, path: &str) -> usize {
+        match self.doc.get(&JsonPath::parse(path)) {
+            Some(JsonValue::Array(id)) => self.doc.array_len(id).unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    /// Get a presence location for the element at `index` of the array at
+    /// a path. The location follows the element as the array changes.
+    pub fn element_location(&self, path: &str, index: usize) -> Option<CursorLocation> {
+        match self.doc.get(&JsonPath::parse(path)) {
+            Some(JsonValue::Array(id)) => {
+                let element = self.doc.array_element_id(id, index)?;
+                Some(CursorLocation::ListElement {
+                    array_id: id.clone(),
+                    element,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Resolve a presence location to a path in this document.
+    ///
+    /// See [`CursorLocation::resolve_path`] for how deleted list elements
+    /// resolve.
+    pub fn resolve_location(&self, location: &CursorLocation) -> Option<JsonPath> {
+        location.resolve_path(&self.doc)
+    }
+
+    /// The underlying CRDT.
+    pub(crate) fn crdt(&self) -> &JsonCrdt {
+        &self.doc
+    }
+
+    /// The underlying CRDT for an edit, copied first if a snapshot holds it.
+    fn crdt_mut(&mut self) -> &mut JsonCrdt {
+        Arc::make_mut(&mut self.doc)
+    }
+
+    /// Encode the full document state for sending to another replica.
+    pub fn encode_state(&self) -> Vec<u8> {
+        serde_json::to_vec(&*self.doc).unwrap_or_default()
+    }
+
+    /// Merge a state produced by [`encode_state`](Self::encode_state).
+    pub fn merge_encoded(&mut self, bytes: &[u8]) -> Result<(), SdkError> {
+        let other: JsonCrdt = serde_json::from_slice(bytes)?;
+        let before = self.watchers.before(&self.doc);
+        self.doc = Arc::new(self.doc.join(&other));
+        self.watchers
+            .notify(before, &self.doc, ChangeOrigin::Remote);
+        self.events.emit(DocChange::RemoteUpdate);
+        Ok(())
+    }
+
     /// Merge another document's state into this one (CRDT merge).
     /// This applies changes from the other document while preserving local changes.
     pub fn merge(&mut self, other: &JsonDoc) {
-        self.doc = self.doc.join(&other.doc);
-        let _ = self.event_tx.send(DocEvent::RemoteUpdate);
+        let before = self.watchers.before(&self.doc);
+        self.doc = Arc::new(self.doc.join(&other.doc));
+        self.watchers
+            .notify(before, &self.doc, ChangeOrigin::Remote);
+        self.events.emit(DocChange::RemoteUpdate);
+    }
+
+    /// Undo the last local edit, or group of edits.
+    ///
+    /// Each edit is checked against the current document first: one whose
+    /// value has changed since, e.g. by a remote edit while the document
+    /// was closed, is skipped and reported as [`DocChange::UndoSkipped`]
+    /// instead of applied where it no longer fits. Returns whether
+    /// anything was undone.
+    pub fn undo(&mut self) -> bool {
+        if !self.gate.is_open() {
+            return false;
+        }
+        let before = self.watchers.before(&self.doc);
+        let doc = &mut self.doc;
+        let outcome = self.undo.undo_with(|op| apply_json_undo(doc, op));
+        self.watchers.notify(before, &self.doc, ChangeOrigin::Local);
+        finish_undo(&self.events, outcome)
+    }
+
+    /// Redo the last undone edit, or group of edits.
+    ///
+    /// Edits are checked as in [`undo`](Self::undo).
+    pub fn redo(&mut self) -> bool {
+        if !self.gate.is_open() {
+            return false;
+        }
+        let before = self.watchers.before(&self.doc);
+        let doc = &mut self.doc;
+        let outcome = self.undo.redo_with(|op| apply_json_undo(doc, op));
+        self.watchers.notify(before, &self.doc, ChangeOrigin::Local);
+        finish_undo(&self.events, outcome)
+    }
+
+    /// Check if there is an edit to undo.
+    pub fn can_undo(&self) -> bool {
+        self.undo.can_undo()
+    }
+
+    /// Check if there is an edit to redo.
+    pub fn can_redo(&self) -> bool {
+        self.undo.can_redo()
+    }
+
+    /// Start a group of edits that are undone together.
+    pub fn start_undo_group(&mut self) {
+        self.undo.start_group();
+    }
+
+    /// End the current group of edits.
+    pub fn end_undo_group(&mut self) {
+        self.undo.end_group();
+    }
+
+    /// Set the limits on the undo history.
+    pub fn with_undo_config(mut self, config: UndoConfig) -> Self {
+        self.undo.set_config(config);
+        self
+    }
+
+    /// Export the undo history, e.g. to store it with the document.
+    pub fn export_undo(&self) -> UndoHistory {
+        self.undo.export()
+    }
+
+    /// Replace the undo history with one from
+    /// [`export_undo`](Self::export_undo).
+    pub fn import_undo(&mut self, history: UndoHistory) {
+        self.undo.import(history);
+    }
+
+    /// Set how many recent events are kept for
+    /// [`resync_events`](CollaborativeDoc::resync_events).
+    ///
+    /// Defaults to [`DEFAULT_EVENT_HISTORY`]. The setting is shared with
+    /// clones of the document.
+    pub fn with_event_history(self, capacity: usize) -> Self {
+        self.events.set_capacity(capacity);
+        self
+    }
+
+    /// Attach the document to a session's write gate.
+    pub(crate) fn with_gate(mut self, gate: WriteGate) -> Self {
+        self.gate = gate;
+        self
     }
 
     /// Clone this document's state for syncing to another replica.
@@ -315,8 +1568,12 @@ impl JsonDoc {
             id: self.id.clone(),
             replica_id: self.replica_id.clone(),
             doc: self.doc.clone(),
-            event_tx: self.event_tx.clone(),
+            events: self.events.clone(),
+            watchers: self.watchers.clone(),
             pending_deltas: Vec::new(),
+            gate: WriteGate::default(),
+            undo: UndoManager::new(&self.id, &self.replica_id)
+                .with_config(self.undo.config().clone()),
         }
     }
 }
@@ -331,15 +1588,97 @@ impl CollaborativeDoc for JsonDoc {
     }
 
     fn subscribe(&self) -> broadcast::Receiver<DocEvent> {
-        self.event_tx.subscribe()
+        self.events.subscribe()
+    }
+
+    fn last_event_seq(&self) -> u64 {
+        self.events.last_seq()
+    }
+
+    fn resync_events(&self, from_seq: u64) -> Resync {
+        self.events.resync(from_seq, self.keys().len())
     }
 
     fn take_pending_deltas(&mut self) -> Vec<Vec<u8>> {
+        let delta = self.crdt_mut().take_delta();
+        self.pending_deltas.extend(encode_delta(delta));
         std::mem::take(&mut self.pending_deltas)
     }
 
-    fn apply_remote(&mut self, _delta: &[u8]) {
-        let _ = self.event_tx.send(DocEvent::RemoteUpdate);
+    fn apply_remote(&mut self, delta: &[u8]) {
+        if let Some(delta) = decode_delta::<JsonCrdtDelta>(delta) {
+            let before = self.watchers.before(&self.doc);
+            self.crdt_mut().apply_delta(&delta);
+            self.watchers
+                .notify(before, &self.doc, ChangeOrigin::Remote);
+        }
+        self.events.emit(DocChange::RemoteUpdate);
+    }
+
+    fn is_writable(&self) -> bool {
+        self.gate.is_open()
+    }
+
+    fn version_vector(&self) -> VersionVector {
+        self.doc.version_vector()
+    }
+}
+
+/// An immutable view of a [`JsonDoc`], from [`JsonDoc::read_snapshot`].
+#[derive(Clone, Debug)]
+pub struct JsonSnapshot {
+    doc: Arc<JsonCrdt>,
+}
+
+impl JsonSnapshot {
+    /// Get a value at a path.
+    pub fn get(&self, path: &str) -> Option<JsonValue> {
+        self.doc.get(&JsonPath::parse(path)).cloned()
+    }
+
+    /// Get the values at several paths.
+    pub fn read_many(&self, paths: &[&str]) -> Vec<Option<JsonValue>> {
+        paths.iter().map(|path| self.get(path)).collect()
+    }
+
+    /// Get the root value as a serde JSON Value.
+    pub fn to_value(&self) -> serde_json::Value {
+        self.doc.to_json()
+    }
+
+    /// Get every path nested under `prefix`, in sorted order.
+    ///
+    /// Objects are listed along with their fields; arrays are listed but
+    /// not their elements. An empty prefix lists the whole document.
+    pub fn paths_under(&self, prefix: &str) -> Vec<String> {
+        let prefix = JsonPath::parse(prefix);
+        let mut value = self.doc.to_json();
+        for segment in prefix.segments() {
+            value = match (value, segment) {
+                (serde_json::Value::Object(mut map), PathSegment::Key(key)) => {
+                    match map.remove(key) {
+                        Some(child) => child,
+                        None => return Vec::new(),
+                    }
+                }
+                _ => return Vec::new(),
+            };
+        }
+
+        let mut paths = Vec::new();
+        collect_paths(&prefix, &value, &mut paths);
+        paths.sort();
+        paths
+    }
+}
+
+fn collect_paths(path: &JsonPath, value: &serde_json::Value, paths: &mut Vec<String>) {
+    if let serde_json::Value::Object(map) = value {
+        for (key, child) in map {
+            let child_path = path.child_key(key.as_str());
+            paths.push(child_path.to_string());
+            collect_paths(&child_path, child, paths);
+        }
     }
 }
 
@@ -377,4 +1716,486 @@ mod tests {
             Some(JsonValue::String("Alice".to_string()))
         );
     }
+
+    #[test]
+    fn test_undo_skips_edits_changed_remotely() {
+        let mut doc = TextDoc::new("doc-1", "a");
+        doc.insert(0, "Hello world");
+        doc.delete(5, 6);
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), "Hello world");
+        assert!(doc.redo());
+        assert_eq!(doc.get_text(), "Hello");
+        assert!(doc.undo());
+
+        // Another replica changes the text the insert would remove
+        let mut other = TextDoc::new("doc-1", "b");
+        other.merge_encoded(&doc.encode_state()).unwrap();
+        other.delete(0, 1);
+        other.insert(0, "J");
+        doc.merge_encoded(&other.encode_state()).unwrap();
+
+        let mut events = doc.subscribe();
+        assert!(!doc.undo());
+        assert_eq!(doc.get_text(), "Jello world");
+        assert_eq!(
+            events.try_recv().unwrap().change,
+            DocChange::UndoSkipped { operations: 1 }
+        );
+        assert!(!doc.can_undo());
+
+        let mut json = JsonDoc::new("doc-2", "a");
+        json.set("title", JsonValue::String("Draft".to_string()));
+        json.set("title", JsonValue::String("Final".to_string()));
+        assert!(json.undo());
+        assert_eq!(
+            json.get("title"),
+            Some(JsonValue::String("Draft".to_string()))
+        );
+        assert!(json.undo());
+        assert!(json.get("title").unwrap_or_default().is_null());
+        assert!(json.redo());
+        assert_eq!(
+            json.get("title"),
+            Some(JsonValue::String("Draft".to_string()))
+        );
+    }
+
+    /// Deliver each document's pending deltas to the other.
+    fn exchange(a: &mut impl CollaborativeDoc, b: &mut impl CollaborativeDoc) {
+        for delta in a.take_pending_deltas() {
+            b.apply_remote(&delta);
+        }
+        for delta in b.take_pending_deltas() {
+            a.apply_remote(&delta);
+        }
+    }
+
+    #[test]
+    fn test_undo_reverts_only_local_edits() {
+        let mut a = TextDoc::new("doc-1", "a");
+        let mut b = TextDoc::new("doc-1", "b");
+        a.insert(0, "world");
+        exchange(&mut a, &mut b);
+
+        // b types in front of a's edits and between them
+        b.insert(0, "Hello ");
+        a.insert(5, "!");
+        exchange(&mut a, &mut b);
+        b.insert(11, "?");
+        exchange(&mut a, &mut b);
+        assert_eq!(a.get_text(), "Hello world?!");
+
+        assert!(a.undo());
+        assert!(a.undo());
+        assert!(!a.can_undo());
+        exchange(&mut a, &mut b);
+        assert_eq!(a.get_text(), "Hello ?");
+        assert_eq!(b.get_text(), a.get_text());
+
+        // A new local edit drops what is left to redo
+        assert!(a.redo());
+        a.insert(0, ">");
+        assert!(!a.can_redo());
+        exchange(&mut a, &mut b);
+        assert_eq!(b.get_text(), ">Hello world?");
+
+        // b's undo leaves a's edits alone
+        assert!(b.undo());
+        exchange(&mut a, &mut b);
+        assert_eq!(a.get_text(), ">Hello world");
+        assert_eq!(b.get_text(), a.get_text());
+
+        let mut a = RichTextDoc::new("doc-2", "a");
+        let mut b = RichTextDoc::new("doc-2", "b");
+        a.insert(0, "draft");
+        exchange(&mut a, &mut b);
+        b.insert(0, "first ");
+        exchange(&mut a, &mut b);
+        assert!(a.undo());
+        exchange(&mut a, &mut b);
+        assert_eq!(a.get_text(), "first ");
+        assert_eq!(b.get_text(), a.get_text());
+    }
+
+    #[test]
+    fn test_rich_text_block_types_sync() {
+        let mut a = RichTextDoc::new("doc-1", "a");
+        let mut b = RichTextDoc::new("doc-1", "b");
+        a.insert(0, "Notes\nitem");
+        a.set_block_type(0, BlockType::Heading(1));
+        a.set_block_type(6, BlockType::BulletList);
+        exchange(&mut a, &mut b);
+
+        b.insert(10, "\nmore");
+        a.set_block_type(0, BlockType::Heading(2));
+        exchange(&mut a, &mut b);
+        assert_eq!(b.block_type_at(0), BlockType::Heading(2));
+        assert_eq!(a.block_type_at(12), BlockType::BulletList);
+        assert_eq!(
+            a.to_html(),
+            "<h2>Notes</h2><ul><li>item</li><li>more</li></ul>"
+        );
+        assert_eq!(b.to_html(), a.to_html());
+    }
+
+    #[test]
+    fn test_typing_burst_undoes_as_one_edit() {
+        let clock = Arc::new(mdcs_core::clock::ManualClock::new(0));
+        let config = UndoConfig {
+            group_window_ms: Some(300),
+            ..Default::default()
+        };
+        let mut doc = TextDoc::new("doc-1", "a")
+            .with_undo_config(config)
+            .with_clock(clock.clone());
+
+        for (i, c) in "Hello".chars().enumerate() {
+            doc.insert(i, &c.to_string());
+            clock.advance(100);
+        }
+        clock.advance(1000);
+        for (i, c) in " you".chars().enumerate() {
+            doc.insert(5 + i, &c.to_string());
+            clock.advance(250);
+        }
+
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), "Hello");
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), "");
+        assert!(!doc.can_undo());
+        assert!(doc.redo());
+        assert_eq!(doc.get_text(), "Hello");
+    }
+
+    #[test]
+    fn test_json_doc_arrays_sync() {
+        let mut doc1 = JsonDoc::new("doc-1", "replica-1");
+        doc1.array_push("items", JsonValue::String("a".to_string()));
+        doc1.array_push("items", JsonValue::String("c".to_string()));
+
+        let mut doc2 = JsonDoc::new("doc-1", "replica-2");
+        doc2.merge_encoded(&doc1.encode_state()).unwrap();
+
+        doc2.array_insert("items", 1, JsonValue::String("b".to_string()));
+        assert_eq!(
+            doc1.array_remove("items", 0),
+            Some(JsonValue::String("a".to_string()))
+        );
+
+        doc1.merge_encoded(&doc2.encode_state()).unwrap();
+        doc2.merge_encoded(&doc1.encode_state()).unwrap();
+
+        assert_eq!(doc1.array_len("items"), 2);
+        assert_eq!(doc1.root(), doc2.root());
+        assert_eq!(doc1.root()["items"], serde_json::json!(["b", "c"]));
+    }
+
+    #[test]
+    fn test_json_doc_counters_sync() {
+        let mut doc1 = JsonDoc::new("doc-1", "replica-1");
+        let mut doc2 = JsonDoc::new("doc-1", "replica-2");
+        doc1.increment("likes", 2);
+        doc2.increment("likes", 3);
+        doc2.increment("likes", -1);
+
+        for delta in doc1.take_pending_deltas() {
+            doc2.apply_remote(&delta);
+        }
+        for delta in doc2.take_pending_deltas() {
+            doc1.apply_remote(&delta);
+        }
+        assert_eq!(doc1.counter("likes"), Some(4));
+        assert_eq!(doc1.root(), doc2.root());
+        assert_eq!(doc1.root()["likes"], 4);
+
+        // Plain values aren't counters
+        doc1.set("title", JsonValue::String("Hi".to_string()));
+        doc1.increment("title", 1);
+        assert_eq!(doc1.counter("title"), None);
+    }
+
+    #[test]
+    fn test_version_vectors_track_remote_updates() {
+        let mut a = RichTextDoc::new("doc-1", "a");
+        let mut b = RichTextDoc::new("doc-1", "b");
+        a.insert(0, "Hi");
+        let seen = a.version_vector();
+
+        for delta in a.take_pending_deltas() {
+            b.apply_remote(&delta);
+        }
+        b.insert(2, "!");
+        assert!(b.is_newer_than(&seen));
+        assert!(b.missing_from(&seen).is_empty());
+
+        a.insert(0, ">");
+        assert_eq!(
+            a.missing_from(&b.version_vector()),
+            vec![("b".to_string(), 1, 1)]
+        );
+        assert_eq!(
+            b.missing_from(&a.version_vector()),
+            vec![("a".to_string(), 3, 3)]
+        );
+
+        // JSON deletions stamp a value ID, so they advance the vector too
+        let mut json = JsonDoc::new("doc-2", "a");
+        json.set("name", JsonValue::String("Alice".to_string()));
+        let before = json.version_vector();
+        json.delete("name");
+        assert!(json.is_newer_than(&before));
+
+        let mut copy = JsonDoc::new("doc-2", "b");
+        copy.merge_encoded(&json.encode_state()).unwrap();
+        assert_eq!(copy.version_vector(), json.version_vector());
+        assert!(!copy.is_newer_than(&json.version_vector()));
+    }
+
+    #[test]
+    fn test_watch_reports_changes_under_prefix() {
+        let mut local = JsonDoc::new("doc-1", "a");
+        let mut remote = JsonDoc::new("doc-1", "b");
+        let mut settings = local.watch("settings");
+
+        local.set("settings.theme", JsonValue::String("dark".to_string()));
+        assert_eq!(
+            settings.try_recv().unwrap(),
+            vec![JsonChangeEvent {
+                path: "settings.theme".to_string(),
+                old_value: None,
+                new_value: Some(serde_json::json!("dark")),
+                origin: ChangeOrigin::Local,
+            }]
+        );
+
+        // A remote change elsewhere is not reported
+        remote.set("user.name", JsonValue::String("Bob".to_string()));
+        local.merge(&remote);
+        assert_eq!(
+            local.get("user.name"),
+            Some(JsonValue::String("Bob".to_string()))
+        );
+        assert!(settings.try_recv().is_err());
+
+        // One merge is one batch
+        remote.merge(&local);
+        remote.set("settings.theme", JsonValue::String("light".to_string()));
+        remote.set("settings.font", JsonValue::Int(12));
+        local.apply_remote(&remote.take_pending_deltas().concat());
+        let batch = settings.try_recv().unwrap();
+        assert!(settings.try_recv().is_err());
+        assert_eq!(
+            batch
+                .iter()
+                .map(|e| (e.path.as_str(), e.old_value.clone(), e.origin))
+                .collect::<Vec<_>>(),
+            vec![
+                ("settings.font", None, ChangeOrigin::Remote),
+                (
+                    "settings.theme",
+                    Some(serde_json::json!("dark")),
+                    ChangeOrigin::Remote
+                ),
+            ]
+        );
+
+        // Dropping the receiver unsubscribes
+        drop(settings);
+        local.set("settings.theme", JsonValue::Null);
+        assert_eq!(local.watchers.len(), 0);
+    }
+
+    #[test]
+    fn test_json_snapshot_is_frozen() {
+        let mut doc = JsonDoc::new("doc-1", "replica-1");
+        doc.set("config.min", JsonValue::Int(1));
+        doc.set("config.max", JsonValue::Int(5));
+        doc.set_array("config.steps");
+
+        let snapshot = doc.read_snapshot();
+        doc.set("config.min", JsonValue::Int(10));
+        doc.set("config.max", JsonValue::Int(20));
+
+        assert_eq!(snapshot.get("config.min"), Some(JsonValue::Int(1)));
+        assert_eq!(
+            snapshot.to_value(),
+            serde_json::json!({"config": {"min": 1, "max": 5, "steps": []}})
+        );
+        assert_eq!(
+            snapshot.paths_under("config"),
+            vec!["config.max", "config.min", "config.steps"]
+        );
+        assert_eq!(
+            snapshot.paths_under(""),
+            vec!["config", "config.max", "config.min", "config.steps"]
+        );
+        assert!(snapshot.paths_under("missing").is_empty());
+
+        assert_eq!(
+            doc.read_many(&["config.min", "config.max"]),
+            vec![Some(JsonValue::Int(10)), Some(JsonValue::Int(20))]
+        );
+        assert_eq!(
+            doc.read_snapshot().get("config.min"),
+            Some(JsonValue::Int(10))
+        );
+    }
+
+    #[test]
+    fn test_snapshot_reads_never_see_torn_deltas() {
+        use parking_lot::RwLock;
+        use std::thread;
+
+        fn check(min: Option<JsonValue>, max: Option<JsonValue>) {
+            match (min, max) {
+                (None, None) => {}
+                (Some(JsonValue::Int(min)), Some(JsonValue::Int(max))) => {
+                    assert!(min <= max, "torn read: min {min} > max {max}")
+                }
+                other => panic!("torn read: {other:?}"),
+            }
+        }
+
+        // Each delta raises both bounds, so applying half of one would
+        // leave min above max.
+        let mut source = JsonDoc::new("doc-1", "writer");
+        let deltas: Vec<Vec<u8>> = (0..500)
+            .flat_map(|i| {
+                source.set("config.min", JsonValue::Int(2 * i));
+                source.set("config.max", JsonValue::Int(2 * i + 1));
+                source.take_pending_deltas()
+            })
+            .collect();
+
+        let doc = Arc::new(RwLock::new(JsonDoc::new("doc-1", "reader")));
+        let applier = {
+            let doc = doc.clone();
+            thread::spawn(move || {
+                for delta in &deltas {
+                    doc.write().apply_remote(delta);
+                }
+            })
+        };
+
+        while !applier.is_finished() {
+            let snapshot = doc.read().read_snapshot();
+            check(snapshot.get("config.min"), snapshot.get("config.max"));
+
+            let mut values = doc.read().read_many(&["config.min", "config.max"]);
+            let max = values.pop().unwrap();
+            check(values.pop().unwrap(), max);
+        }
+        applier.join().unwrap();
+
+        let snapshot = doc.read().read_snapshot();
+        assert_eq!(snapshot.get("config.max"), Some(JsonValue::Int(999)));
+    }
+
+    fn insert_events(doc: &mut TextDoc, count: usize) {
+        for _ in 0..count {
+            let position = doc.len();
+            doc.insert(position, "x");
+        }
+    }
+
+    #[test]
+    fn test_event_seq_is_contiguous() {
+        let mut doc = TextDoc::new("doc-1", "replica-1");
+        let mut rx = doc.subscribe();
+        doc.insert(0, "Hello");
+        doc.delete(0, 1);
+        doc.apply_remote(b"not a delta");
+
+        let seqs: Vec<u64> = std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|event| event.seq())
+            .collect();
+        assert_eq!(seqs, vec![1, 2, 3]);
+        assert_eq!(doc.last_event_seq(), 3);
+        assert_eq!(doc.resync_events(4), Resync::Events(Vec::new()));
+        assert_eq!(
+            doc.resync_events(2),
+            Resync::Events(vec![
+                DocEvent {
+                    seq: 2,
+                    change: DocChange::Delete {
+                        position: 0,
+                        length: 1
+                    }
+                },
+                DocEvent {
+                    seq: 3,
+                    change: DocChange::RemoteUpdate
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_lagged_subscriber_resyncs_missing_events() {
+        let mut doc = TextDoc::new("doc-1", "replica-1").with_event_history(300);
+        let mut rx = doc.subscribe();
+        insert_events(&mut doc, 300);
+
+        // The overflowed subscriber is told, and the next event shows the gap
+        let Err(broadcast::error::TryRecvError::Lagged(skipped)) = rx.try_recv() else {
+            panic!("subscriber should lag");
+        };
+        let next = rx.try_recv().unwrap();
+        assert_eq!(next.seq(), skipped + 1);
+
+        let Resync::Events(missing) = doc.resync_events(1) else {
+            panic!("events should still be retained");
+        };
+        let seqs: Vec<u64> = missing.iter().map(DocEvent::seq).collect();
+        assert_eq!(seqs, (1..=300).collect::<Vec<_>>());
+        assert_eq!(missing[skipped as usize], next);
+    }
+
+    #[test]
+    fn test_evicted_events_resync_as_snapshot() {
+        let mut doc = RichTextDoc::new("doc-1", "replica-1").with_event_history(4);
+        let mut rx = doc.subscribe();
+        doc.insert(0, "ab");
+        assert_eq!(rx.try_recv().unwrap().seq(), 1);
+        drop(rx);
+
+        for position in 2..10 {
+            doc.insert(position, "c");
+        }
+        assert_eq!(
+            doc.resync_events(2),
+            Resync::Snapshot(DocSummary { seq: 9, len: 10 })
+        );
+        // The retained tail still replays
+        let Resync::Events(tail) = doc.resync_events(6) else {
+            panic!("events 6..=9 should be retained");
+        };
+        assert_eq!(tail.first().map(DocEvent::seq), Some(6));
+        assert_eq!(tail.len(), 4);
+
+        // Without history every gap is a snapshot
+        let mut json = JsonDoc::new("doc-2", "replica-1").with_event_history(0);
+        json.merge_encoded(&JsonDoc::new("doc-2", "replica-2").encode_state())
+            .unwrap();
+        assert_eq!(
+            json.resync_events(1),
+            Resync::Snapshot(DocSummary { seq: 1, len: 0 })
+        );
+    }
+
+    #[test]
+    fn test_event_log_shared_with_clones() {
+        let mut doc = TextDoc::new("doc-1", "replica-1");
+        let mut rx = doc.subscribe();
+        let mut copy = doc.clone_state();
+        doc.insert(0, "a");
+        copy.merge(&doc);
+
+        let seqs: Vec<u64> = std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|event| event.seq())
+            .collect();
+        assert_eq!(seqs, vec![1, 2]);
+    }
 }
@@ -1,8 +1,14 @@
 //! Error types for the MDCS SDK.
 
+use crate::network::NetworkError;
+use crate::sync::UnackedUpdates;
 use std::fmt;
 
 /// Error type for SDK operations.
+///
+/// Each variant has a stable [`code`](SdkError::code), and errors raised
+/// by JSON encoding or the transport keep them as their
+/// [`source`](std::error::Error::source).
 #[derive(Debug)]
 pub enum SdkError {
     /// Document not found.
@@ -17,6 +23,21 @@ pub enum SdkError {
     NetworkError(String),
     /// Serialization error.
     SerializationError(String),
+    /// Encoding or decoding JSON failed.
+    Json(serde_json::Error),
+    /// The transport failed to connect, send or disconnect.
+    Transport(NetworkError),
+    /// Operation did not complete in time.
+    Timeout(String),
+    /// The client or session is shutting down and accepts no new work.
+    ShuttingDown(String),
+    /// A flush ended before every update was acknowledged.
+    PartialFlush(Vec<UnackedUpdates>),
+    /// A disconnected peer's offline queue has no room for an update.
+    QueueFull(String),
+    /// The local peer isn't allowed to do this, such as assigning roles
+    /// in a session it doesn't own.
+    PermissionDenied(String),
     /// Internal error.
     Internal(String),
 }
@@ -30,12 +51,191 @@ impl fmt::Display for SdkError {
             SdkError::SyncError(e) => write!(f, "Sync error: {}", e),
             SdkError::NetworkError(e) => write!(f, "Network error: {}", e),
             SdkError::SerializationError(e) => write!(f, "Serialization error: {}", e),
+            SdkError::Json(e) => write!(f, "Serialization error: {}", e),
+            SdkError::Transport(e) => write!(f, "Network error: {}", e),
+            SdkError::Timeout(e) => write!(f, "Timed out: {}", e),
+            SdkError::ShuttingDown(e) => write!(f, "Shutting down: {}", e),
+            SdkError::PartialFlush(unacked) => {
+                write!(f, "Flush incomplete:")?;
+                for u in unacked {
+                    write!(
+                        f,
+                        " {} missing {} {:?};",
+                        u.peer_id, u.document_id, u.versions
+                    )?;
+                }
+                Ok(())
+            }
+            SdkError::QueueFull(e) => write!(f, "Offline queue full: {}", e),
+            SdkError::PermissionDenied(e) => write!(f, "Permission denied: {}", e),
             SdkError::Internal(e) => write!(f, "Internal error: {}", e),
         }
     }
 }
 
-impl std::error::Error for SdkError {}
+impl SdkError {
+    /// A stable identifier for the kind of error, e.g.
+    /// `"DOCUMENT_NOT_FOUND"`.
+    ///
+    /// Codes don't change when messages are reworded. An error with a
+    /// source shares the code of the variant that carries a message
+    /// instead: [`Json`](SdkError::Json) is `"SERIALIZATION_ERROR"` and
+    /// [`Transport`](SdkError::Transport) is `"NETWORK_ERROR"`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SdkError::DocumentNotFound(_) => "DOCUMENT_NOT_FOUND",
+            SdkError::PeerNotFound(_) => "PEER_NOT_FOUND",
+            SdkError::ConnectionFailed(_) => "CONNECTION_FAILED",
+            SdkError::SyncError(_) => "SYNC_ERROR",
+            SdkError::NetworkError(_) | SdkError::Transport(_) => "NETWORK_ERROR",
+            SdkError::SerializationError(_) | SdkError::Json(_) => "SERIALIZATION_ERROR",
+            SdkError::Timeout(_) => "TIMEOUT",
+            SdkError::ShuttingDown(_) => "SHUTTING_DOWN",
+            SdkError::PartialFlush(_) => "PARTIAL_FLUSH",
+            SdkError::QueueFull(_) => "QUEUE_FULL",
+            SdkError::PermissionDenied(_) => "PERMISSION_DENIED",
+            SdkError::Internal(_) => "INTERNAL",
+        }
+    }
+}
+
+impl std::error::Error for SdkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SdkError::Json(e) => Some(e),
+            SdkError::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for SdkError {
+    fn from(err: serde_json::Error) -> Self {
+        SdkError::Json(err)
+    }
+}
+
+impl From<NetworkError> for SdkError {
+    fn from(err: NetworkError) -> Self {
+        SdkError::Transport(err)
+    }
+}
+
+/// A configuration that can't work, naming the field at fault.
+///
+/// Returned by the `build` and `validate` methods of
+/// [`ClientConfig`](crate::ClientConfig) and [`SyncConfig`](crate::SyncConfig).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// A text field is empty or only whitespace.
+    Empty { field: &'static str },
+    /// A number outside the range that works.
+    OutOfRange {
+        field: &'static str,
+        value: u64,
+        min: u64,
+        max: u64,
+    },
+    /// A field that must be less than another is not.
+    NotBelow {
+        field: &'static str,
+        value: u64,
+        limit_field: &'static str,
+        limit: u64,
+    },
+    /// An environment variable that doesn't parse.
+    InvalidEnv {
+        var: &'static str,
+        value: String,
+        expected: &'static str,
+    },
+}
+
+impl ConfigError {
+    /// Check that `value` of `field` lies in `min..=max`.
+    pub(crate) fn check_range(
+        field: &'static str,
+        value: u64,
+        min: u64,
+        max: u64,
+    ) -> std::result::Result<(), Self> {
+        if (min..=max).contains(&value) {
+            Ok(())
+        } else {
+            Err(ConfigError::OutOfRange {
+                field,
+                value,
+                min,
+                max,
+            })
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Empty { field } => write!(f, "{} must not be empty", field),
+            ConfigError::OutOfRange {
+                field,
+                value,
+                min,
+                max,
+            } => write!(f, "{} is {}, expected {} to {}", field, value, min, max),
+            ConfigError::NotBelow {
+                field,
+                value,
+                limit_field,
+                limit,
+            } => write!(
+                f,
+                "{} ({}) must be less than {} ({})",
+                field, value, limit_field, limit
+            ),
+            ConfigError::InvalidEnv {
+                var,
+                value,
+                expected,
+            } => write!(f, "{}={:?} is not {}", var, value, expected),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
 
 /// Result type for SDK operations.
 pub type Result<T> = std::result::Result<T, SdkError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn test_codes_are_stable() {
+        assert_eq!(
+            SdkError::DocumentNotFound("doc".into()).code(),
+            "DOCUMENT_NOT_FOUND"
+        );
+        assert_eq!(SdkError::Timeout("flush".into()).code(), "TIMEOUT");
+        assert_eq!(SdkError::PartialFlush(Vec::new()).code(), "PARTIAL_FLUSH");
+    }
+
+    #[test]
+    fn test_sources_are_preserved() {
+        let json = SdkError::from(serde_json::from_slice::<u32>(b"{").unwrap_err());
+        assert_eq!(json.code(), "SERIALIZATION_ERROR");
+        assert!(json
+            .source()
+            .is_some_and(|source| source.is::<serde_json::Error>()));
+
+        let transport = SdkError::from(NetworkError::Disconnected);
+        assert_eq!(transport.code(), "NETWORK_ERROR");
+        assert_eq!(transport.to_string(), "Network error: Disconnected");
+        assert!(transport
+            .source()
+            .is_some_and(|source| source.is::<NetworkError>()));
+
+        assert!(SdkError::SyncError("stuck".into()).source().is_none());
+    }
+}
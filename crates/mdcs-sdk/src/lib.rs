@@ -38,6 +38,10 @@
 //! - [`sync`] - Network synchronization and peer management
 //! - [`network`] - Network transport abstractions
 //! - [`session`] - Session management for collaborative editing
+//! - [`roles`] - Editor and viewer roles of session participants
+//! - [`storage`] - Document checkpoint storage
+//! - `websocket` - WebSocket transport to a relay (`websocket` feature)
+//! - `tcp` - TCP transport between peers (`tokio-net` feature)
 //! - [`error`] - Error types
 
 pub mod client;
@@ -45,23 +49,48 @@ pub mod document;
 pub mod error;
 pub mod network;
 pub mod presence;
+pub mod roles;
 pub mod session;
+pub mod storage;
 pub mod sync;
+#[cfg(feature = "tokio-net")]
+pub mod tcp;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
 // Re-exports for convenience
 pub use client::{Client, ClientConfig, ClientConfigBuilder};
-pub use document::{CollaborativeDoc, DocEvent, JsonDoc, RichTextDoc, TextDoc};
-pub use error::{Result, SdkError};
-pub use network::{MemoryTransport, Message, NetworkTransport, Peer, PeerId, PeerState};
-pub use presence::{Awareness, AwarenessEvent, CursorInfo, UserPresenceInfo};
+pub use document::{
+    ChangeOrigin, CollaborativeDoc, DocChange, DocEvent, DocSummary, JsonChangeEvent, JsonDoc,
+    JsonSnapshot, Resync, RichTextDoc, TextDoc, DEFAULT_EVENT_HISTORY,
+};
+pub use error::{ConfigError, Result, SdkError};
+pub use network::{
+    Channel, ChannelCapacity, Frame, Inbox, InboxSender, MemoryTransport, Message,
+    NetworkTransport, Peer, PeerId, PeerState,
+};
+pub use presence::{Awareness, AwarenessEvent, CursorInfo, FollowEndReason, UserPresenceInfo};
+pub use roles::{ParticipantRole, RoleTable};
 pub use session::{Session, SessionEvent};
-pub use sync::{SyncConfig, SyncConfigBuilder, SyncEvent, SyncManager};
+pub use storage::{DocStorage, MemoryDocStorage};
+pub use sync::{
+    DeltaJoiner, OverflowPolicy, PeerSyncStats, SessionMembers, SyncConfig, SyncConfigBuilder,
+    SyncEvent, SyncManager, UnackedUpdates,
+};
+#[cfg(feature = "tokio-net")]
+pub use tcp::{TcpConfig, TcpTransport};
+#[cfg(feature = "websocket")]
+pub use websocket::WebSocketTransport;
 
 // Re-export commonly used types from mdcs-db
 pub use mdcs_db::{
     json_crdt::{JsonPath, JsonValue},
-    presence::{Cursor, UserId, UserInfo, UserStatus},
-    rich_text::MarkType,
+    presence::{
+        Cursor, CursorLocation, ElementRef, UserId, UserInfo, UserStatus, Viewport, ViewportAnchor,
+    },
+    rich_text::{BlockType, MarkType},
+    undo::{UndoConfig, UndoHistory},
+    VersionVector,
 };
 
 /// Prelude module for convenient imports.
@@ -4,8 +4,10 @@ use async_trait::async_trait;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::{broadcast, mpsc};
 
 /// Unique identifier for a peer.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -61,26 +63,274 @@ pub enum Message {
         delta: Vec<u8>,
         version: u64,
     },
+    /// Full state of a document, as its `encode_state` produces it.
+    Snapshot { document_id: String, state: Vec<u8> },
     /// Presence update.
     Presence {
         user_id: String,
         document_id: String,
         cursor_pos: Option<usize>,
     },
+    /// Serialized awareness delta.
+    Awareness { delta: Vec<u8> },
+    /// Serialized role table of a session, from its owner.
+    Roles { state: Vec<u8> },
+    /// The sender is shutting down and closing its connections.
+    Goodbye { replica_id: String },
+    /// Join a relayed session; from a relay, the sender joined it.
+    Join { session_id: String },
+    /// Leave a relayed session; from a relay, the sender left it.
+    Leave { session_id: String },
+    /// A message for one of several sessions sharing a connection.
+    Session {
+        session_id: String,
+        message: Box<Message>,
+    },
     /// Acknowledgment.
     Ack { message_id: u64 },
+    /// Acknowledgment of an `Update` for a document version.
+    DeltaAck { document_id: String, version: u64 },
     /// Ping for keepalive.
     Ping,
     /// Pong response.
     Pong,
 }
 
+impl Message {
+    /// Address `message` to one session of a shared connection.
+    pub fn for_session(session_id: impl Into<String>, message: Message) -> Self {
+        Message::Session {
+            session_id: session_id.into(),
+            message: Box::new(message),
+        }
+    }
+
+    /// The session the message is addressed to, if it is.
+    pub fn session_id(&self) -> Option<&str> {
+        match self {
+            Message::Session { session_id, .. } => Some(session_id),
+            _ => None,
+        }
+    }
+
+    /// The logical channel this message travels on.
+    pub fn channel(&self) -> Channel {
+        match self {
+            Message::Session { message, .. } => message.channel(),
+            Message::SyncRequest { .. }
+            | Message::SyncResponse { .. }
+            | Message::Update { .. }
+            | Message::Snapshot { .. } => Channel::Document,
+            Message::Presence { .. } | Message::Awareness { .. } => Channel::Presence,
+            Message::Hello { .. }
+            | Message::Goodbye { .. }
+            | Message::Join { .. }
+            | Message::Leave { .. }
+            | Message::Roles { .. }
+            | Message::Ack { .. }
+            | Message::DeltaAck { .. }
+            | Message::Ping
+            | Message::Pong => Channel::Control,
+        }
+    }
+}
+
+/// A message as it travels over a relay connection.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Frame {
+    /// The sending peer. A relay fills this in for the frames it forwards.
+    pub from: PeerId,
+    /// The receiving peer, or `None` for every other member of the session.
+    pub to: Option<PeerId>,
+    pub message: Message,
+}
+
+impl Frame {
+    /// Encode for the wire.
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap_or_default()
+    }
+
+    /// Decode a frame produced by [`encode`](Self::encode).
+    pub fn decode(bytes: &[u8]) -> Result<Self, NetworkError> {
+        serde_json::from_slice(bytes).map_err(|e| NetworkError::InvalidFrame(e.to_string()))
+    }
+}
+
+/// Logical channels multiplexed over a peer connection.
+///
+/// Each channel has its own bounded queue on the receiving side, so a burst
+/// on one channel neither delays nor blocks the others.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Channel {
+    /// Handshakes, acks and keepalives. Read first; senders wait when full.
+    Control,
+    /// Document deltas. Never dropped by a full queue; senders wait.
+    Document,
+    /// Cursor and awareness updates. When full, the oldest update is dropped.
+    Presence,
+}
+
+impl Channel {
+    /// All channels, in the order they are read.
+    pub const ALL: [Channel; 3] = [Channel::Control, Channel::Document, Channel::Presence];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Queue sizes of the per-channel inbox queues.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChannelCapacity {
+    pub control: usize,
+    pub document: usize,
+    pub presence: usize,
+}
+
+impl Default for ChannelCapacity {
+    fn default() -> Self {
+        Self {
+            control: 100,
+            document: 100,
+            presence: 32,
+        }
+    }
+}
+
+/// A message together with the peer that sent it.
+type Envelope = (PeerId, Message);
+
+/// Create the sending and receiving halves of a per-channel inbox.
+///
+/// Transports hand the [`Inbox`] out from
+/// [`subscribe`](NetworkTransport::subscribe) and push incoming messages
+/// into the [`InboxSender`].
+pub fn inbox(capacity: ChannelCapacity) -> (InboxSender, Inbox) {
+    let (control_tx, control) = mpsc::channel(capacity.control);
+    let (document_tx, document) = mpsc::channel(capacity.document);
+    let (presence_tx, presence) = broadcast::channel(capacity.presence);
+    let sender = InboxSender {
+        control: control_tx,
+        document: document_tx,
+        presence: presence_tx,
+    };
+    let inbox = Inbox {
+        control,
+        document,
+        presence,
+        presence_dropped: 0,
+    };
+    (sender, inbox)
+}
+
+/// Sending half of an [`Inbox`].
+#[derive(Clone, Debug)]
+pub struct InboxSender {
+    control: mpsc::Sender<Envelope>,
+    document: mpsc::Sender<Envelope>,
+    presence: broadcast::Sender<Envelope>,
+}
+
+impl InboxSender {
+    /// Queue a message on its channel.
+    ///
+    /// Waits while the control or document queue is full. Presence updates
+    /// never wait; a full presence queue drops its oldest update instead.
+    pub async fn send(&self, from: PeerId, message: Message) -> Result<(), NetworkError> {
+        let envelope = (from, message);
+        match envelope.1.channel() {
+            Channel::Control => self.control.send(envelope).await,
+            Channel::Document => self.document.send(envelope).await,
+            Channel::Presence => {
+                // Fails only once the inbox is dropped; presence is best-effort
+                let _ = self.presence.send(envelope);
+                return Ok(());
+            }
+        }
+        .map_err(|e| NetworkError::SendFailed(e.to_string()))
+    }
+}
+
+/// Receiving half of a transport's per-channel queues.
+///
+/// Messages are read by priority: control first, then document, then
+/// presence. Order is preserved within a channel.
+#[derive(Debug)]
+pub struct Inbox {
+    control: mpsc::Receiver<Envelope>,
+    document: mpsc::Receiver<Envelope>,
+    presence: broadcast::Receiver<Envelope>,
+    presence_dropped: u64,
+}
+
+impl Inbox {
+    /// Wait for the next message. Returns `None` once every channel is
+    /// closed.
+    pub async fn recv(&mut self) -> Option<(PeerId, Message)> {
+        let presence_dropped = &mut self.presence_dropped;
+        tokio::select! {
+            biased;
+            Some(envelope) = self.control.recv() => Some(envelope),
+            Some(envelope) = self.document.recv() => Some(envelope),
+            Some(envelope) = recv_presence(&mut self.presence, presence_dropped) => Some(envelope),
+            else => None,
+        }
+    }
+
+    /// Take the next queued message without waiting.
+    pub fn try_recv(&mut self) -> Result<(PeerId, Message), TryRecvError> {
+        let mut open = false;
+        for queue in [&mut self.control, &mut self.document] {
+            match queue.try_recv() {
+                Ok(envelope) => return Ok(envelope),
+                Err(TryRecvError::Empty) => open = true,
+                Err(TryRecvError::Disconnected) => {}
+            }
+        }
+        loop {
+            match self.presence.try_recv() {
+                Ok(envelope) => return Ok(envelope),
+                Err(broadcast::error::TryRecvError::Lagged(n)) => self.presence_dropped += n,
+                Err(broadcast::error::TryRecvError::Empty) => return Err(TryRecvError::Empty),
+                Err(broadcast::error::TryRecvError::Closed) if open => {
+                    return Err(TryRecvError::Empty)
+                }
+                Err(broadcast::error::TryRecvError::Closed) => {
+                    return Err(TryRecvError::Disconnected)
+                }
+            }
+        }
+    }
+
+    /// Presence updates dropped so far because the presence queue was full.
+    pub fn presence_dropped(&self) -> u64 {
+        self.presence_dropped
+    }
+}
+
+/// Wait for the next presence update, counting updates skipped because the
+/// queue overflowed.
+async fn recv_presence(
+    presence: &mut broadcast::Receiver<Envelope>,
+    dropped: &mut u64,
+) -> Option<Envelope> {
+    loop {
+        match presence.recv().await {
+            Ok(envelope) => return Some(envelope),
+            Err(broadcast::error::RecvError::Lagged(n)) => *dropped += n,
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
 /// Network error type.
 #[derive(Clone, Debug)]
 pub enum NetworkError {
     ConnectionFailed(String),
     PeerNotFound(String),
     SendFailed(String),
+    InvalidFrame(String),
     Disconnected,
 }
 
@@ -90,6 +340,7 @@ impl std::fmt::Display for NetworkError {
             NetworkError::ConnectionFailed(e) => write!(f, "Connection failed: {}", e),
             NetworkError::PeerNotFound(id) => write!(f, "Peer not found: {}", id),
             NetworkError::SendFailed(e) => write!(f, "Send failed: {}", e),
+            NetworkError::InvalidFrame(e) => write!(f, "Invalid frame: {}", e),
             NetworkError::Disconnected => write!(f, "Disconnected"),
         }
     }
@@ -116,32 +367,43 @@ pub trait NetworkTransport: Send + Sync + 'static {
     async fn connected_peers(&self) -> Vec<Peer>;
 
     /// Subscribe to incoming messages.
-    fn subscribe(&self) -> mpsc::Receiver<(PeerId, Message)>;
+    fn subscribe(&self) -> Inbox;
 }
 
-/// Type alias for the message receiver shared across threads.
-type SharedMessageReceiver = Arc<RwLock<Option<mpsc::Receiver<(PeerId, Message)>>>>;
+/// Type alias for the message inbox shared across threads.
+type SharedInbox = Arc<RwLock<Option<Inbox>>>;
 /// Type alias for the outgoing message senders shared across threads.
-type SharedOutgoing = Arc<RwLock<HashMap<PeerId, mpsc::Sender<(PeerId, Message)>>>>;
+type SharedOutgoing = Arc<RwLock<HashMap<PeerId, InboxSender>>>;
 
 /// In-memory transport for testing and simulation.
 pub struct MemoryTransport {
     local_id: PeerId,
     peers: Arc<RwLock<HashMap<PeerId, Peer>>>,
-    message_tx: mpsc::Sender<(PeerId, Message)>,
-    message_rx: SharedMessageReceiver,
+    message_tx: InboxSender,
+    message_rx: SharedInbox,
     outgoing: SharedOutgoing,
+    /// Outgoing messages still to be dropped on any channel (fault injection).
+    drop_budget: Arc<AtomicUsize>,
+    /// Outgoing messages still to be dropped per channel (fault injection).
+    channel_drop_budget: Arc<[AtomicUsize; 3]>,
 }
 
 impl MemoryTransport {
     pub fn new(local_id: PeerId) -> Self {
-        let (tx, rx) = mpsc::channel(100);
+        Self::with_capacity(local_id, ChannelCapacity::default())
+    }
+
+    /// Create a transport with custom inbox queue sizes.
+    pub fn with_capacity(local_id: PeerId, capacity: ChannelCapacity) -> Self {
+        let (tx, rx) = inbox(capacity);
         Self {
             local_id,
             peers: Arc::new(RwLock::new(HashMap::new())),
             message_tx: tx,
             message_rx: Arc::new(RwLock::new(Some(rx))),
             outgoing: Arc::new(RwLock::new(HashMap::new())),
+            drop_budget: Arc::new(AtomicUsize::new(0)),
+            channel_drop_budget: Arc::new(Default::default()),
         }
     }
 
@@ -149,6 +411,56 @@ impl MemoryTransport {
         &self.local_id
     }
 
+    /// Silently drop the next `count` outgoing messages (for testing).
+    ///
+    /// Dropped sends still report success, like a lossy network.
+    pub fn drop_next(&self, count: usize) {
+        self.drop_budget.fetch_add(count, Ordering::SeqCst);
+    }
+
+    /// Silently drop the next `count` outgoing messages on one channel
+    /// (for testing).
+    pub fn drop_next_on(&self, channel: Channel, count: usize) {
+        self.channel_drop_budget[channel.index()].fetch_add(count, Ordering::SeqCst);
+    }
+
+    /// Cut the link to a peer without forgetting it (for testing).
+    ///
+    /// Until [`resume`](Self::resume), the peer is not listed as connected
+    /// and messages to it fail, as if it had gone offline.
+    pub fn pause(&self, peer_id: &PeerId) {
+        self.set_state(peer_id, PeerState::Disconnected);
+    }
+
+    /// Restore the link to a peer cut with [`pause`](Self::pause).
+    pub fn resume(&self, peer_id: &PeerId) {
+        self.set_state(peer_id, PeerState::Connected);
+    }
+
+    fn set_state(&self, peer_id: &PeerId, state: PeerState) {
+        if let Some(peer) = self.peers.write().get_mut(peer_id) {
+            peer.state = state;
+        }
+    }
+
+    fn is_paused(&self, peer_id: &PeerId) -> bool {
+        self.peers
+            .read()
+            .get(peer_id)
+            .is_some_and(|peer| peer.state == PeerState::Disconnected)
+    }
+
+    /// Consume one unit of the channel's or the shared drop budget, if any
+    /// is left.
+    fn should_drop(&self, channel: Channel) -> bool {
+        let take = |budget: &AtomicUsize| {
+            budget
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok()
+        };
+        take(&self.channel_drop_budget[channel.index()]) || take(&self.drop_budget)
+    }
+
     /// Connect two memory transports together (for testing).
     pub fn connect_to(&self, other: &MemoryTransport) {
         // Add peer to our list
@@ -211,10 +523,13 @@ impl NetworkTransport for MemoryTransport {
         };
 
         if let Some(tx) = tx {
-            tx.send((self.local_id.clone(), message))
-                .await
-                .map_err(|e| NetworkError::SendFailed(e.to_string()))?;
-            Ok(())
+            if self.is_paused(peer_id) {
+                return Err(NetworkError::Disconnected);
+            }
+            if self.should_drop(message.channel()) {
+                return Ok(());
+            }
+            tx.send(self.local_id.clone(), message).await
         } else {
             Err(NetworkError::PeerNotFound(peer_id.to_string()))
         }
@@ -223,20 +538,32 @@ impl NetworkTransport for MemoryTransport {
     async fn broadcast(&self, message: Message) -> Result<(), NetworkError> {
         let senders: Vec<_> = {
             let outgoing = self.outgoing.read();
-            outgoing.values().cloned().collect()
+            outgoing
+                .iter()
+                .filter(|(peer_id, _)| !self.is_paused(peer_id))
+                .map(|(_, tx)| tx.clone())
+                .collect()
         };
 
         for tx in senders {
-            let _ = tx.send((self.local_id.clone(), message.clone())).await;
+            if self.should_drop(message.channel()) {
+                continue;
+            }
+            let _ = tx.send(self.local_id.clone(), message.clone()).await;
         }
         Ok(())
     }
 
     async fn connected_peers(&self) -> Vec<Peer> {
-        self.peers.read().values().cloned().collect()
+        self.peers
+            .read()
+            .values()
+            .filter(|peer| peer.state == PeerState::Connected)
+            .cloned()
+            .collect()
     }
 
-    fn subscribe(&self) -> mpsc::Receiver<(PeerId, Message)> {
+    fn subscribe(&self) -> Inbox {
         self.message_rx
             .write()
             .take()
@@ -289,4 +616,164 @@ mod tests {
             assert_eq!(peers.len(), 2);
         }
     }
+
+    #[tokio::test]
+    async fn test_drop_next() {
+        let transport1 = MemoryTransport::new(PeerId::new("peer-1"));
+        let transport2 = MemoryTransport::new(PeerId::new("peer-2"));
+        transport1.connect_to(&transport2);
+        let mut rx = transport2.subscribe();
+
+        transport1.drop_next(1);
+        transport1.broadcast(Message::Ping).await.unwrap();
+        transport1
+            .send(transport2.local_id(), Message::Pong)
+            .await
+            .unwrap();
+
+        assert!(matches!(rx.try_recv(), Ok((_, Message::Pong))));
+        assert!(rx.try_recv().is_err());
+    }
+
+    fn presence(n: usize) -> Message {
+        Message::Presence {
+            user_id: "alice".to_string(),
+            document_id: "doc".to_string(),
+            cursor_pos: Some(n),
+        }
+    }
+
+    fn update(version: u64) -> Message {
+        Message::Update {
+            document_id: "doc".to_string(),
+            delta: vec![version as u8],
+            version,
+        }
+    }
+
+    #[test]
+    fn test_message_channels() {
+        assert_eq!(Message::Ping.channel(), Channel::Control);
+        assert_eq!(
+            Message::DeltaAck {
+                document_id: "doc".to_string(),
+                version: 1
+            }
+            .channel(),
+            Channel::Control
+        );
+        assert_eq!(
+            Message::Goodbye {
+                replica_id: "peer-1".to_string()
+            }
+            .channel(),
+            Channel::Control
+        );
+        assert_eq!(update(1).channel(), Channel::Document);
+        assert_eq!(presence(0).channel(), Channel::Presence);
+        assert_eq!(
+            Message::Awareness { delta: Vec::new() }.channel(),
+            Channel::Presence
+        );
+    }
+
+    #[tokio::test]
+    async fn test_inbox_reads_by_priority() {
+        let transport1 = MemoryTransport::new(PeerId::new("peer-1"));
+        let transport2 = MemoryTransport::new(PeerId::new("peer-2"));
+        transport1.connect_to(&transport2);
+        let mut rx = transport2.subscribe();
+
+        transport1.broadcast(presence(0)).await.unwrap();
+        transport1.broadcast(update(1)).await.unwrap();
+        transport1.broadcast(Message::Ping).await.unwrap();
+
+        let order: Vec<_> = (0..3).map(|_| rx.try_recv().unwrap().1.channel()).collect();
+        assert_eq!(order, Channel::ALL);
+        assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_presence_flood_does_not_delay_documents() {
+        let capacity = ChannelCapacity {
+            control: 4,
+            document: 4,
+            presence: 8,
+        };
+        let sender = Arc::new(MemoryTransport::with_capacity(PeerId::new("a"), capacity));
+        let receiver = MemoryTransport::with_capacity(PeerId::new("b"), capacity);
+        sender.connect_to(&receiver);
+        let mut rx = receiver.subscribe();
+        let b = PeerId::new("b");
+
+        let flood = {
+            let (sender, b) = (sender.clone(), b.clone());
+            tokio::spawn(async move {
+                for n in 0..10_000 {
+                    sender.send(&b, presence(n)).await.unwrap();
+                }
+            })
+        };
+        let documents = {
+            let (sender, b) = (sender.clone(), b.clone());
+            tokio::spawn(async move {
+                for version in 1..=200 {
+                    sender.send(&b, update(version)).await.unwrap();
+                    tokio::task::yield_now().await;
+                }
+            })
+        };
+
+        // The document queue is far smaller than the number of updates, so
+        // the document sender relies on the reader keeping up despite the flood
+        let mut versions = Vec::new();
+        let mut cursors = Vec::new();
+        while versions.len() < 200 {
+            let next = tokio::time::timeout(std::time::Duration::from_millis(100), rx.recv())
+                .await
+                .expect("document delta delayed behind presence flood");
+            match next {
+                Some((_, Message::Update { version, .. })) => versions.push(version),
+                Some((_, Message::Presence { cursor_pos, .. })) => cursors.push(cursor_pos),
+                other => panic!("unexpected message {:?}", other),
+            }
+        }
+        flood.await.unwrap();
+        documents.await.unwrap();
+        while let Ok((_, message)) = rx.try_recv() {
+            assert_eq!(message.channel(), Channel::Presence);
+            cursors.push(None);
+        }
+
+        assert_eq!(versions, (1..=200).collect::<Vec<_>>());
+        assert!(rx.presence_dropped() > 0);
+        assert_eq!(cursors.len() as u64 + rx.presence_dropped(), 10_000);
+    }
+
+    #[tokio::test]
+    async fn test_drop_next_on_channel() {
+        let transport1 = MemoryTransport::new(PeerId::new("peer-1"));
+        let transport2 = MemoryTransport::new(PeerId::new("peer-2"));
+        transport1.connect_to(&transport2);
+        let mut rx = transport2.subscribe();
+
+        transport1.drop_next_on(Channel::Presence, 2);
+        for n in 0..3 {
+            transport1.broadcast(presence(n)).await.unwrap();
+        }
+        transport1.broadcast(update(1)).await.unwrap();
+
+        assert!(matches!(rx.try_recv(), Ok((_, Message::Update { .. }))));
+        assert!(matches!(
+            rx.try_recv(),
+            Ok((
+                _,
+                Message::Presence {
+                    cursor_pos: Some(2),
+                    ..
+                }
+            ))
+        ));
+        assert!(rx.try_recv().is_err());
+    }
 }
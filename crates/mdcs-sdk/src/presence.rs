@@ -1,12 +1,22 @@
 //! Presence and awareness for collaborative editing.
 
-use mdcs_db::presence::{Cursor, PresenceTracker, UserId, UserInfo, UserStatus};
+use crate::document::{CollaborativeDoc, JsonDoc};
+use mdcs_core::clock::SharedClock;
+use mdcs_db::json_crdt::JsonPath;
+use mdcs_db::presence::{
+    Cursor, CursorLocation, DeviceId, PresenceDelta, PresenceEvent, PresenceTracker, UserId,
+    UserInfo, UserPresence, UserStatus, Viewport,
+};
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
 
+/// Default minimum interval between follow updates.
+const DEFAULT_FOLLOW_INTERVAL: Duration = Duration::from_millis(50);
+
 /// Cursor information for a user.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CursorInfo {
@@ -17,6 +27,9 @@ pub struct CursorInfo {
     pub selection_start: Option<usize>,
     pub selection_end: Option<usize>,
     pub color: String,
+    /// Structured location, for cursors that are not a text offset.
+    #[serde(default)]
+    pub location: Option<CursorLocation>,
 }
 
 /// User presence information.
@@ -27,6 +40,9 @@ pub struct UserPresenceInfo {
     pub status: UserStatus,
     pub color: String,
     pub cursors: HashMap<String, CursorInfo>,
+    /// Custom application state.
+    #[serde(default)]
+    pub state: HashMap<String, String>,
 }
 
 /// Events for presence changes.
@@ -36,8 +52,43 @@ pub enum AwarenessEvent {
     UserUpdated(UserPresenceInfo),
     /// A user went offline.
     UserOffline(String),
+    /// A user has not been heard from within the away timeout.
+    UserAway(String),
+    /// A user's presence expired and they were dropped.
+    UserLeft(String),
     /// Cursor moved.
     CursorMoved(CursorInfo),
+    /// The followed user's viewport changed.
+    FollowTarget {
+        user_id: String,
+        document_id: String,
+        viewport: Viewport,
+    },
+    /// Following stopped. This is always the last event for a follow.
+    FollowEnded {
+        user_id: String,
+        reason: FollowEndReason,
+    },
+}
+
+/// Why a follow ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FollowEndReason {
+    /// The local user called [`Awareness::unfollow`] or followed someone else.
+    Unfollowed,
+    /// The followed user went offline, left, or expired.
+    TargetLeft,
+}
+
+/// State of an active follow.
+struct FollowState {
+    user_id: String,
+    /// Last viewport seen per document.
+    seen: HashMap<String, Viewport>,
+    /// When the last update was emitted (clock milliseconds).
+    last_emit: Option<u64>,
+    /// Newest viewport per document held back by rate limiting.
+    held: HashMap<String, Viewport>,
 }
 
 /// Awareness manager for a document or session.
@@ -47,6 +98,9 @@ pub struct Awareness {
     local_color: String,
     tracker: Arc<RwLock<PresenceTracker>>,
     event_tx: broadcast::Sender<AwarenessEvent>,
+    follow: RwLock<Option<FollowState>>,
+    follow_interval: RwLock<Duration>,
+    clock: RwLock<SharedClock>,
 }
 
 impl Awareness {
@@ -65,9 +119,22 @@ impl Awareness {
             local_color: "#0066cc".to_string(),
             tracker: Arc::new(RwLock::new(PresenceTracker::new(user_id, info))),
             event_tx,
+            follow: RwLock::new(None),
+            follow_interval: RwLock::new(DEFAULT_FOLLOW_INTERVAL),
+            clock: RwLock::new(SharedClock::default()),
         }
     }
 
+    /// Track the local user on `device_id`, so that several devices of the
+    /// same user show up separately.
+    pub fn with_device(self, device_id: impl Into<String>) -> Self {
+        {
+            let mut tracker = self.tracker.write();
+            *tracker = tracker.clone().with_device(DeviceId::new(device_id));
+        }
+        self
+    }
+
     /// Get the local user ID.
     pub fn local_user_id(&self) -> &str {
         &self.local_user_id
@@ -91,6 +158,7 @@ impl Awareness {
             selection_start: None,
             selection_end: None,
             color: self.local_color.clone(),
+            location: None,
         };
 
         let _ = self.event_tx.send(AwarenessEvent::CursorMoved(cursor_info));
@@ -109,57 +177,268 @@ impl Awareness {
             selection_start: Some(start),
             selection_end: Some(end),
             color: self.local_color.clone(),
+            location: None,
+        };
+
+        let _ = self.event_tx.send(AwarenessEvent::CursorMoved(cursor_info));
+    }
+
+    /// Set the local user's location in a document, e.g. a JSON path or a
+    /// list element.
+    pub fn set_location(&self, document_id: &str, location: CursorLocation) {
+        let cursor = Cursor::at_location(location);
+
+        let cursor_info = CursorInfo {
+            user_id: self.local_user_id.clone(),
+            user_name: self.local_user_name.clone(),
+            document_id: document_id.to_string(),
+            position: cursor.position,
+            selection_start: None,
+            selection_end: None,
+            color: self.local_color.clone(),
+            location: cursor.location.clone(),
         };
+        self.tracker.write().set_cursor(document_id, cursor);
 
         let _ = self.event_tx.send(AwarenessEvent::CursorMoved(cursor_info));
     }
 
+    /// Set the local user's viewport (anchor, extent and zoom) for a
+    /// document.
+    ///
+    /// Viewports ride the presence channel and expire with the user.
+    pub fn set_viewport(&self, document_id: &str, viewport: Viewport) {
+        self.tracker.write().set_viewport(document_id, viewport);
+    }
+
     /// Set the local user's status.
     pub fn set_status(&self, status: UserStatus) {
         self.tracker.write().set_status(status);
     }
 
+    /// Set a custom state entry for the local user (e.g. what they are editing).
+    pub fn set_state(&self, key: &str, value: &str) {
+        self.tracker.write().set_state(key, value);
+    }
+
+    /// Take the pending presence delta for broadcasting to peers.
+    pub fn take_delta(&self) -> Option<PresenceDelta> {
+        self.tracker.write().take_delta()
+    }
+
+    /// Apply a presence delta received from a peer.
+    pub fn apply_delta(&self, delta: &PresenceDelta) {
+        self.tracker.write().apply_delta(delta);
+
+        // A device going offline or leaving only takes its user offline
+        // once none of their other devices is active
+        let mut departed: Vec<&UserId> = Vec::new();
+        let candidates = delta
+            .updates
+            .iter()
+            .filter(|p| p.status == UserStatus::Offline)
+            .map(|p| &p.user_id)
+            .chain(delta.removals.iter().map(|r| &r.user_id));
+        for user_id in candidates {
+            if !departed.contains(&user_id) {
+                departed.push(user_id);
+            }
+        }
+        {
+            let tracker = self.tracker.read();
+            for user_id in departed {
+                if tracker.user_status(user_id) == UserStatus::Offline {
+                    let _ = self
+                        .event_tx
+                        .send(AwarenessEvent::UserOffline(user_id.0.clone()));
+                }
+            }
+        }
+
+        self.update_follow();
+    }
+
+    /// Follow another user: emit [`AwarenessEvent::FollowTarget`] whenever
+    /// their viewport changes.
+    ///
+    /// Updates are rate-limited (see [`set_follow_interval`](Self::set_follow_interval)).
+    /// Following ends with [`AwarenessEvent::FollowEnded`] when the target
+    /// goes offline or leaves, or when [`unfollow`](Self::unfollow) is called.
+    pub fn follow(&self, user_id: &str) {
+        self.unfollow();
+        *self.follow.write() = Some(FollowState {
+            user_id: user_id.to_string(),
+            seen: HashMap::new(),
+            last_emit: None,
+            held: HashMap::new(),
+        });
+        self.update_follow();
+    }
+
+    /// Stop following. Returns `true` if a follow was active.
+    pub fn unfollow(&self) -> bool {
+        let ended = self.follow.write().take();
+        match ended {
+            Some(state) => {
+                let _ = self.event_tx.send(AwarenessEvent::FollowEnded {
+                    user_id: state.user_id,
+                    reason: FollowEndReason::Unfollowed,
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The user currently being followed, if any.
+    pub fn following(&self) -> Option<String> {
+        self.follow.read().as_ref().map(|f| f.user_id.clone())
+    }
+
+    /// Set the minimum interval between follow updates.
+    pub fn set_follow_interval(&self, interval: Duration) {
+        *self.follow_interval.write() = interval;
+    }
+
+    /// Use `clock` for presence times, expiry and follow rate limiting.
+    pub fn set_clock(&self, clock: impl Into<SharedClock>) {
+        let clock = clock.into();
+        self.tracker.write().set_clock(clock.clone());
+        *self.clock.write() = clock;
+    }
+
+    /// Emit the viewport updates held back by rate limiting, the newest for
+    /// each document, if the interval has elapsed.
+    pub fn flush_follow(&self) {
+        let interval = *self.follow_interval.read();
+        let now = self.clock.read().now_millis();
+        let mut follow = self.follow.write();
+        if let Some(state) = follow.as_mut() {
+            let ready = state
+                .last_emit
+                .is_none_or(|t| now.saturating_sub(t) >= interval.as_millis() as u64);
+            if ready && !state.held.is_empty() {
+                let mut held: Vec<_> = state.held.drain().collect();
+                held.sort_by(|a, b| a.0.cmp(&b.0));
+                state.last_emit = Some(now);
+                for (document_id, viewport) in held {
+                    let _ = self.event_tx.send(AwarenessEvent::FollowTarget {
+                        user_id: state.user_id.clone(),
+                        document_id,
+                        viewport,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Check the followed user's presence and emit follow events.
+    fn update_follow(&self) {
+        let interval = *self.follow_interval.read();
+        let now = self.clock.read().now_millis();
+        let tracker = self.tracker.read();
+        let mut follow = self.follow.write();
+        let Some(state) = follow.as_mut() else {
+            return;
+        };
+
+        let target = tracker.get_user(&UserId::new(&state.user_id));
+        let departed = target.is_none_or(|p| p.status == UserStatus::Offline);
+        if departed {
+            // Only end the follow once the target has actually been seen,
+            // so following a user before their presence arrives is allowed.
+            if target.is_some() || !state.seen.is_empty() {
+                let state = follow.take().unwrap();
+                let _ = self.event_tx.send(AwarenessEvent::FollowEnded {
+                    user_id: state.user_id,
+                    reason: FollowEndReason::TargetLeft,
+                });
+            }
+            return;
+        }
+
+        let mut changed: Vec<(String, Viewport)> = target
+            .map(|p| {
+                p.viewports
+                    .iter()
+                    .filter(|(doc, vp)| state.seen.get(*doc) != Some(*vp))
+                    .map(|(doc, vp)| (doc.clone(), vp.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        changed.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (document_id, viewport) in changed {
+            state.seen.insert(document_id.clone(), viewport.clone());
+            let ready = state
+                .last_emit
+                .is_none_or(|t| now.saturating_sub(t) >= interval.as_millis() as u64);
+            if ready {
+                state.held.remove(&document_id);
+                state.last_emit = Some(now);
+                let _ = self.event_tx.send(AwarenessEvent::FollowTarget {
+                    user_id: state.user_id.clone(),
+                    document_id,
+                    viewport,
+                });
+            } else {
+                state.held.insert(document_id, viewport);
+            }
+        }
+    }
+
     /// Get all users' presence information.
+    ///
+    /// A user quiet for the away timeout shows as [`UserStatus::Away`].
     pub fn get_users(&self) -> Vec<UserPresenceInfo> {
         let tracker = self.tracker.read();
 
         tracker
             .all_users()
-            .map(|presence| {
-                let cursors: HashMap<String, CursorInfo> = presence
-                    .cursors
-                    .iter()
-                    .map(|(doc_id, cursor): (&String, &Cursor)| {
-                        let (sel_start, sel_end) = cursor
-                            .selection_range()
-                            .map(|(s, e)| (Some(s), Some(e)))
-                            .unwrap_or((None, None));
-                        (
-                            doc_id.clone(),
-                            CursorInfo {
-                                user_id: presence.user_id.0.clone(),
-                                user_name: presence.info.name.clone(),
-                                document_id: doc_id.clone(),
-                                position: cursor.position,
-                                selection_start: sel_start,
-                                selection_end: sel_end,
-                                color: presence.info.color.clone(),
-                            },
-                        )
-                    })
-                    .collect();
-
-                UserPresenceInfo {
-                    user_id: presence.user_id.0.clone(),
-                    name: presence.info.name.clone(),
-                    status: presence.status.clone(),
-                    color: presence.info.color.clone(),
-                    cursors,
-                }
+            .map(|presence| UserPresenceInfo {
+                status: tracker.user_status(&presence.user_id),
+                ..presence_info(presence)
             })
             .collect()
     }
 
+    /// Get the IDs of a user's live devices, sorted.
+    pub fn devices(&self, user_id: &str) -> Vec<String> {
+        self.tracker
+            .read()
+            .devices(&UserId::new(user_id))
+            .into_iter()
+            .map(|device| device.0)
+            .collect()
+    }
+
+    /// Get online users located at or under `path` in a JSON document.
+    ///
+    /// Matches locations set as [`CursorLocation::JsonPath`], including
+    /// nested paths. Use [`users_at_path_in`](Self::users_at_path_in) to
+    /// also match list element locations.
+    pub fn users_at_path(&self, document_id: &str, path: &JsonPath) -> Vec<UserPresenceInfo> {
+        let tracker = self.tracker.read();
+
+        tracker
+            .users_at_path(document_id, path)
+            .into_iter()
+            .map(presence_info)
+            .collect()
+    }
+
+    /// Get online users located at or under `path` in `doc`, resolving list
+    /// element locations against its current state.
+    pub fn users_at_path_in(&self, doc: &JsonDoc, path: &JsonPath) -> Vec<UserPresenceInfo> {
+        let tracker = self.tracker.read();
+
+        tracker
+            .users_at_path_in(doc.id(), path, doc.crdt())
+            .into_iter()
+            .map(presence_info)
+            .collect()
+    }
+
     /// Get cursors for a specific document.
     pub fn get_cursors(&self, document_id: &str) -> Vec<CursorInfo> {
         self.get_users()
@@ -178,15 +457,93 @@ impl Awareness {
         self.event_tx.subscribe()
     }
 
+    /// Expire quiet users: emit [`AwarenessEvent::UserAway`] for those who
+    /// went quiet, [`AwarenessEvent::UserUpdated`] for those back from
+    /// away, and [`AwarenessEvent::UserLeft`] for those whose presence
+    /// expired.
+    ///
+    /// See [`PresenceTracker::expire_stale`].
+    pub fn expire_stale(&self) {
+        let events = self.tracker.write().expire_stale();
+        let mut left = false;
+        {
+            let tracker = self.tracker.read();
+            for event in events {
+                let event = match event {
+                    PresenceEvent::Away(user_id) => AwarenessEvent::UserAway(user_id.0),
+                    PresenceEvent::Active(user_id) => match tracker.get_user(&user_id) {
+                        Some(presence) => AwarenessEvent::UserUpdated(presence_info(presence)),
+                        None => continue,
+                    },
+                    PresenceEvent::Left(user_id) => {
+                        left = true;
+                        AwarenessEvent::UserLeft(user_id.0)
+                    }
+                };
+                let _ = self.event_tx.send(event);
+            }
+        }
+        if left {
+            self.update_follow();
+        }
+    }
+
     /// Remove stale users who haven't been active.
     pub fn cleanup_stale(&self) {
-        self.tracker.write().cleanup_stale();
+        let removed = self.tracker.write().cleanup_stale();
+        for user_id in &removed {
+            let _ = self
+                .event_tx
+                .send(AwarenessEvent::UserOffline(user_id.0.clone()));
+        }
+        if !removed.is_empty() {
+            self.update_follow();
+        }
+    }
+}
+
+/// Convert a tracked presence into its public form.
+fn presence_info(presence: &UserPresence) -> UserPresenceInfo {
+    let cursors: HashMap<String, CursorInfo> = presence
+        .cursors
+        .iter()
+        .map(|(doc_id, cursor): (&String, &Cursor)| {
+            let (sel_start, sel_end) = cursor
+                .selection_range()
+                .map(|(s, e)| (Some(s), Some(e)))
+                .unwrap_or((None, None));
+            (
+                doc_id.clone(),
+                CursorInfo {
+                    user_id: presence.user_id.0.clone(),
+                    user_name: presence.info.name.clone(),
+                    document_id: doc_id.clone(),
+                    position: cursor.position,
+                    selection_start: sel_start,
+                    selection_end: sel_end,
+                    color: presence.info.color.clone(),
+                    location: cursor.location.clone(),
+                },
+            )
+        })
+        .collect();
+
+    UserPresenceInfo {
+        user_id: presence.user_id.0.clone(),
+        name: presence.info.name.clone(),
+        status: presence.status.clone(),
+        color: presence.info.color.clone(),
+        cursors,
+        state: presence.state.clone(),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use mdcs_core::clock::ManualClock;
+    use mdcs_db::json_crdt::JsonValue;
+    use mdcs_db::presence::ViewportAnchor;
 
     #[test]
     fn test_awareness_basic() {
@@ -210,4 +567,340 @@ mod tests {
         let cursors = awareness.get_cursors("doc-1");
         assert_eq!(cursors.len(), 1);
     }
+
+    #[test]
+    fn test_users_at_parent_path() {
+        let alice = Awareness::new("user-1", "Alice");
+        let bob = Awareness::new("user-2", "Bob");
+        let carol = Awareness::new("user-3", "Carol");
+
+        let title = JsonPath::parse("columns.todo.cards.0.title");
+        alice.set_location("board", CursorLocation::JsonPath(title.clone()));
+        bob.set_location(
+            "board",
+            CursorLocation::JsonPath(JsonPath::parse("columns.todo.name")),
+        );
+        carol.apply_delta(&alice.take_delta().unwrap());
+        carol.apply_delta(&bob.take_delta().unwrap());
+
+        let mut names: Vec<_> = carol
+            .users_at_path("board", &JsonPath::parse("columns.todo"))
+            .into_iter()
+            .map(|u| u.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["Alice", "Bob"]);
+
+        let at_title = carol.users_at_path("board", &title);
+        assert_eq!(at_title.len(), 1);
+        assert_eq!(
+            at_title[0].cursors["board"].location,
+            Some(CursorLocation::JsonPath(title))
+        );
+        assert!(carol
+            .users_at_path("board", &JsonPath::parse("columns.done"))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_users_at_list_element() {
+        let mut doc = JsonDoc::new("board", "r1");
+        doc.array_push("cards", JsonValue::String("write docs".into()));
+        doc.array_push("cards", JsonValue::String("ship it".into()));
+
+        let alice = Awareness::new("user-1", "Alice");
+        let carol = Awareness::new("user-3", "Carol");
+        alice.set_location("board", doc.element_location("cards", 1).unwrap());
+        carol.apply_delta(&alice.take_delta().unwrap());
+
+        let card = JsonPath::parse("cards.1");
+        assert_eq!(carol.users_at_path_in(&doc, &card).len(), 1);
+        // Only plain JSON path locations match without the document
+        assert!(carol.users_at_path("board", &card).is_empty());
+
+        // Once the card is gone Alice is still shown on the list
+        doc.array_remove("cards", 1);
+        assert!(carol.users_at_path_in(&doc, &card).is_empty());
+        assert_eq!(
+            carol
+                .users_at_path_in(&doc, &JsonPath::parse("cards"))
+                .len(),
+            1
+        );
+    }
+
+    fn follow_events(rx: &mut broadcast::Receiver<AwarenessEvent>) -> Vec<AwarenessEvent> {
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            if matches!(
+                event,
+                AwarenessEvent::FollowTarget { .. } | AwarenessEvent::FollowEnded { .. }
+            ) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn test_follow_receives_viewports_in_order() {
+        let leader = Awareness::new("user-1", "Alice");
+        let follower = Awareness::new("user-2", "Bob");
+        follower.set_follow_interval(Duration::ZERO);
+        let mut rx = follower.subscribe();
+
+        follower.follow("user-1");
+        for offset in [0, 40, 80] {
+            leader.set_viewport("doc-1", Viewport::at_offset(offset, 40));
+            follower.apply_delta(&leader.take_delta().unwrap());
+        }
+
+        let offsets: Vec<_> = follow_events(&mut rx)
+            .into_iter()
+            .map(|e| match e {
+                AwarenessEvent::FollowTarget { viewport, .. } => viewport.anchor,
+                other => panic!("unexpected event {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            offsets,
+            vec![
+                ViewportAnchor::Offset(0),
+                ViewportAnchor::Offset(40),
+                ViewportAnchor::Offset(80)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_follow_rate_limited_keeps_latest() {
+        let leader = Awareness::new("user-1", "Alice");
+        let follower = Awareness::new("user-2", "Bob");
+        let clock = Arc::new(ManualClock::new(0));
+        follower.set_clock(clock.clone());
+        follower.set_follow_interval(Duration::from_secs(1));
+        let mut rx = follower.subscribe();
+
+        follower.follow("user-1");
+        for offset in [0, 40, 80] {
+            leader.set_viewport("doc-1", Viewport::at_offset(offset, 40));
+            follower.apply_delta(&leader.take_delta().unwrap());
+        }
+
+        // Only the first update gets through inside the interval
+        assert_eq!(follow_events(&mut rx).len(), 1);
+
+        // Not before the interval has passed
+        clock.advance(999);
+        follower.flush_follow();
+        assert!(follow_events(&mut rx).is_empty());
+
+        // Then the newest held viewport is emitted
+        clock.advance(1);
+        follower.flush_follow();
+        match follow_events(&mut rx).as_slice() {
+            [AwarenessEvent::FollowTarget { viewport, .. }] => {
+                assert_eq!(viewport.anchor, ViewportAnchor::Offset(80));
+            }
+            other => panic!("unexpected events {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_follow_shares_zoom() {
+        let leader = Awareness::new("user-1", "Alice");
+        let follower = Awareness::new("user-2", "Bob");
+        follower.set_follow_interval(Duration::ZERO);
+        let mut rx = follower.subscribe();
+
+        follower.follow("user-1");
+        leader.set_viewport("doc-1", Viewport::at_offset(10, 20).with_zoom(150));
+        follower.apply_delta(&leader.take_delta().unwrap());
+
+        match follow_events(&mut rx).as_slice() {
+            [AwarenessEvent::FollowTarget { viewport, .. }] => {
+                assert_eq!(viewport.anchor, ViewportAnchor::Offset(10));
+                assert_eq!(viewport.zoom, 150);
+            }
+            other => panic!("unexpected events {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_follow_rate_limited_keeps_latest_per_document() {
+        let leader = Awareness::new("user-1", "Alice");
+        let follower = Awareness::new("user-2", "Bob");
+        let clock = Arc::new(ManualClock::new(0));
+        follower.set_clock(clock.clone());
+        follower.set_follow_interval(Duration::from_secs(1));
+        let mut rx = follower.subscribe();
+
+        follower.follow("user-1");
+        leader.set_viewport("doc-1", Viewport::at_offset(0, 40));
+        follower.apply_delta(&leader.take_delta().unwrap());
+        assert_eq!(follow_events(&mut rx).len(), 1);
+
+        // Held back inside the interval: doc-1 moves, then doc-2 does
+        leader.set_viewport("doc-1", Viewport::at_offset(80, 40));
+        follower.apply_delta(&leader.take_delta().unwrap());
+        leader.set_viewport("doc-2", Viewport::at_offset(5, 10));
+        follower.apply_delta(&leader.take_delta().unwrap());
+        assert!(follow_events(&mut rx).is_empty());
+
+        // Neither document's latest viewport is lost
+        clock.advance(1000);
+        follower.flush_follow();
+        let latest: Vec<_> = follow_events(&mut rx)
+            .into_iter()
+            .map(|e| match e {
+                AwarenessEvent::FollowTarget {
+                    document_id,
+                    viewport,
+                    ..
+                } => (document_id, viewport.anchor),
+                other => panic!("unexpected event {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            latest,
+            vec![
+                ("doc-1".to_string(), ViewportAnchor::Offset(80)),
+                ("doc-2".to_string(), ViewportAnchor::Offset(5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unfollow_stops_updates() {
+        let leader = Awareness::new("user-1", "Alice");
+        let follower = Awareness::new("user-2", "Bob");
+        follower.set_follow_interval(Duration::ZERO);
+        let mut rx = follower.subscribe();
+
+        follower.follow("user-1");
+        leader.set_viewport("doc-1", Viewport::at_offset(10, 20));
+        follower.apply_delta(&leader.take_delta().unwrap());
+        assert!(follower.unfollow());
+
+        leader.set_viewport("doc-1", Viewport::at_offset(30, 20));
+        follower.apply_delta(&leader.take_delta().unwrap());
+
+        let events = follow_events(&mut rx);
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            &events[1],
+            AwarenessEvent::FollowEnded {
+                reason: FollowEndReason::Unfollowed,
+                ..
+            }
+        ));
+        assert_eq!(follower.following(), None);
+    }
+
+    #[test]
+    fn test_follow_ends_when_target_disconnects() {
+        let leader = Awareness::new("user-1", "Alice");
+        let follower = Awareness::new("user-2", "Bob");
+        follower.set_follow_interval(Duration::ZERO);
+        let mut rx = follower.subscribe();
+
+        follower.follow("user-1");
+        leader.set_viewport("doc-1", Viewport::at_offset(10, 20));
+        follower.apply_delta(&leader.take_delta().unwrap());
+
+        leader.set_status(UserStatus::Offline);
+        follower.apply_delta(&leader.take_delta().unwrap());
+
+        let events = follow_events(&mut rx);
+        assert!(matches!(
+            events.last(),
+            Some(AwarenessEvent::FollowEnded {
+                reason: FollowEndReason::TargetLeft,
+                ..
+            })
+        ));
+        assert_eq!(follower.following(), None);
+    }
+
+    #[test]
+    fn test_user_online_while_any_device_is() {
+        let laptop = Awareness::new("user-1", "Alice").with_device("laptop");
+        let phone = Awareness::new("user-1", "Alice").with_device("phone");
+        let observer = Awareness::new("user-2", "Bob");
+        let mut rx = observer.subscribe();
+
+        laptop.set_cursor("doc-1", 3);
+        phone.set_cursor("doc-1", 8);
+        observer.apply_delta(&laptop.take_delta().unwrap());
+        observer.apply_delta(&phone.take_delta().unwrap());
+        assert_eq!(observer.devices("user-1"), vec!["laptop", "phone"]);
+        assert_eq!(observer.get_users().len(), 2);
+
+        // The laptop going offline leaves the user online on the phone
+        laptop.set_status(UserStatus::Offline);
+        observer.apply_delta(&laptop.take_delta().unwrap());
+        assert!(std::iter::from_fn(|| rx.try_recv().ok())
+            .all(|e| !matches!(e, AwarenessEvent::UserOffline(_))));
+        let alice = observer
+            .get_users()
+            .into_iter()
+            .find(|u| u.user_id == "user-1")
+            .unwrap();
+        assert_eq!(alice.status, UserStatus::Online);
+
+        phone.set_status(UserStatus::Offline);
+        observer.apply_delta(&phone.take_delta().unwrap());
+        assert!(std::iter::from_fn(|| rx.try_recv().ok())
+            .any(|e| matches!(e, AwarenessEvent::UserOffline(ref id) if id == "user-1")));
+    }
+
+    #[test]
+    fn test_quiet_user_goes_away_then_leaves() {
+        let clock = Arc::new(ManualClock::new(1_000));
+        let alice = Awareness::new("user-1", "Alice");
+        let observer = Awareness::new("user-2", "Bob");
+        alice.set_clock(clock.clone());
+        observer.set_clock(clock.clone());
+        let mut rx = observer.subscribe();
+
+        alice.set_cursor("doc-1", 3);
+        observer.apply_delta(&alice.take_delta().unwrap());
+        let events = |rx: &mut broadcast::Receiver<AwarenessEvent>| {
+            std::iter::from_fn(|| rx.try_recv().ok())
+                .filter(|e| !matches!(e, AwarenessEvent::CursorMoved(_)))
+                .collect::<Vec<_>>()
+        };
+
+        clock.advance(15_001);
+        observer.expire_stale();
+        assert!(matches!(
+            events(&mut rx).as_slice(),
+            [AwarenessEvent::UserAway(id)] if id == "user-1"
+        ));
+        let status = |observer: &Awareness| {
+            observer
+                .get_users()
+                .into_iter()
+                .find(|u| u.user_id == "user-1")
+                .map(|u| u.status)
+        };
+        assert_eq!(status(&observer), Some(UserStatus::Away));
+
+        // A heartbeat brings her back
+        alice.set_status(UserStatus::Online);
+        observer.apply_delta(&alice.take_delta().unwrap());
+        observer.expire_stale();
+        assert!(matches!(
+            events(&mut rx).as_slice(),
+            [AwarenessEvent::UserUpdated(info)] if info.status == UserStatus::Online
+        ));
+
+        clock.advance(30_001);
+        observer.expire_stale();
+        assert!(matches!(
+            events(&mut rx).as_slice(),
+            [AwarenessEvent::UserLeft(id)] if id == "user-1"
+        ));
+        assert_eq!(status(&observer), None);
+    }
 }
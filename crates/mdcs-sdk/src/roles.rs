@@ -0,0 +1,170 @@
+//! Participant roles for sessions with read-only members.
+//!
+//! A session with an owner keeps a [`RoleTable`] saying which peers may
+//! edit. Updates and snapshots from a [`Viewer`](ParticipantRole::Viewer)
+//! are dropped by every participant, while their presence still goes
+//! through.
+//!
+//! # Trust model
+//!
+//! Every participant is configured with the same owner, and only the owner
+//! assigns roles. Each assignment is an [`LWWRegister`] write stamped with
+//! the owner's next epoch, so assignments are totally ordered and tables
+//! converge whatever order they arrive in. Tables are only taken from the
+//! owner's peer ID: epochs aren't signed, so this relies on the transport
+//! to tell who sent a message. Roles are enforced by the receivers; a
+//! viewer can still edit its own replica, it just doesn't spread.
+
+use crate::network::PeerId;
+use mdcs_core::lwwreg::LWWRegister;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// What a participant may do in a session.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum ParticipantRole {
+    /// Sees documents and presence, and edits documents.
+    #[default]
+    Editor,
+    /// Sees documents and presence; edits are dropped.
+    Viewer,
+}
+
+impl ParticipantRole {
+    /// Check whether the role may edit documents.
+    pub fn can_edit(self) -> bool {
+        self == ParticipantRole::Editor
+    }
+}
+
+/// The roles the owner of a session assigned to its participants.
+///
+/// Peers without an assignment are editors, and so is the owner.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoleTable {
+    owner: PeerId,
+    roles: BTreeMap<String, LWWRegister<ParticipantRole, String>>,
+}
+
+impl RoleTable {
+    /// Create an empty table for a session owned by `owner`.
+    pub fn new(owner: PeerId) -> Self {
+        Self {
+            owner,
+            roles: BTreeMap::new(),
+        }
+    }
+
+    /// Get the peer that assigns roles.
+    pub fn owner(&self) -> &PeerId {
+        &self.owner
+    }
+
+    /// Get a peer's role.
+    pub fn role(&self, peer_id: &PeerId) -> ParticipantRole {
+        if *peer_id == self.owner {
+            return ParticipantRole::Editor;
+        }
+        self.roles
+            .get(&peer_id.0)
+            .and_then(|register| register.get().copied())
+            .unwrap_or_default()
+    }
+
+    /// Get the latest epoch assigned, 0 if none.
+    pub fn epoch(&self) -> u64 {
+        self.roles
+            .values()
+            .map(LWWRegister::timestamp)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Assign a role to a peer at the next epoch, returning the epoch.
+    ///
+    /// Only the owner's replica should call this; see the
+    /// [module docs](self).
+    pub fn assign(&mut self, peer_id: &PeerId, role: ParticipantRole) -> u64 {
+        let epoch = self.epoch() + 1;
+        self.roles
+            .entry(peer_id.0.clone())
+            .or_insert_with(|| LWWRegister::new(self.owner.0.clone()))
+            .set(role, epoch, self.owner.0.clone());
+        epoch
+    }
+
+    /// List the assigned roles.
+    pub fn assignments(&self) -> Vec<(PeerId, ParticipantRole)> {
+        self.roles
+            .iter()
+            .filter_map(|(peer, register)| Some((PeerId::new(peer.clone()), *register.get()?)))
+            .collect()
+    }
+
+    /// Merge a table received from the owner, keeping the latest
+    /// assignment for each peer.
+    ///
+    /// A table of another owner is ignored. Returns whether any role
+    /// changed.
+    pub fn merge(&mut self, other: &RoleTable) -> bool {
+        if other.owner != self.owner {
+            return false;
+        }
+        let mut changed = false;
+        for (peer, theirs) in &other.roles {
+            let ours = self
+                .roles
+                .entry(peer.clone())
+                .or_insert_with(|| LWWRegister::new(self.owner.0.clone()));
+            if let Some(&role) = theirs.get() {
+                let before = ours.get().copied();
+                ours.set(role, theirs.timestamp(), theirs.replica_id().clone());
+                changed |= ours.get().copied() != before;
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assignments_converge_by_epoch() {
+        let alice = PeerId::new("alice");
+        let bob = PeerId::new("bob");
+        let mut owner = RoleTable::new(alice.clone());
+        assert_eq!(owner.role(&bob), ParticipantRole::Editor);
+
+        assert_eq!(owner.assign(&bob, ParticipantRole::Viewer), 1);
+        let demoted = owner.clone();
+        assert_eq!(owner.assign(&bob, ParticipantRole::Editor), 2);
+        let promoted = owner.clone();
+
+        // The promotion wins whichever table arrives first
+        let mut replica = RoleTable::new(alice.clone());
+        assert!(replica.merge(&promoted));
+        assert!(!replica.merge(&demoted));
+        assert_eq!(replica.role(&bob), ParticipantRole::Editor);
+        assert_eq!(replica, promoted);
+
+        // The owner can't be demoted
+        owner.assign(&alice, ParticipantRole::Viewer);
+        assert_eq!(owner.role(&alice), ParticipantRole::Editor);
+        assert_eq!(owner.epoch(), 3);
+    }
+
+    #[test]
+    fn test_tables_of_another_owner_are_ignored() {
+        let bob = PeerId::new("bob");
+        let mut table = RoleTable::new(PeerId::new("alice"));
+        let mut forged = RoleTable::new(bob.clone());
+        forged.assign(&PeerId::new("carol"), ParticipantRole::Viewer);
+
+        assert!(!table.merge(&forged));
+        assert!(table.assignments().is_empty());
+    }
+}
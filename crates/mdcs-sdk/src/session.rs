@@ -1,9 +1,15 @@
 //! Session management for collaborative editing sessions.
 
-use crate::document::{JsonDoc, RichTextDoc, TextDoc};
+use crate::document::{CollaborativeDoc, JsonDoc, RichTextDoc, TextDoc, WriteGate};
 use crate::error::SdkError;
 use crate::network::{Message, NetworkTransport, Peer, PeerId};
 use crate::presence::Awareness;
+use crate::roles::{ParticipantRole, RoleTable};
+use crate::storage::DocStorage;
+use crate::sync::{SessionMembers, SyncConfig, SyncManager};
+use mdcs_core::clock::SharedClock;
+use mdcs_db::presence::UserStatus;
+use mdcs_db::undo::UndoHistory;
 use parking_lot::RwLock;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -24,9 +30,23 @@ pub enum SessionEvent {
     Connected,
     /// Session disconnected.
     Disconnected,
+    /// An update or snapshot from a viewer was dropped.
+    UnauthorizedEdit {
+        peer_id: PeerId,
+        document_id: String,
+    },
 }
 
 /// A collaborative session that manages documents and peers.
+///
+/// Local edits are sent with [`publish`](Self::publish) and messages from
+/// peers are applied with [`handle_message`](Self::handle_message), which
+/// the application calls for everything arriving on the transport's inbox.
+/// [`close`](Self::close) ends the session: later edits are ignored, pending
+/// edits are flushed, and peers see the local user go offline.
+///
+/// A session given an owner with [`with_owner`](Self::with_owner) drops
+/// edits from the peers the owner made viewers; see [`crate::roles`].
 pub struct Session<T: NetworkTransport> {
     session_id: String,
     local_peer_id: PeerId,
@@ -37,6 +57,16 @@ pub struct Session<T: NetworkTransport> {
     rich_text_docs: Arc<RwLock<HashMap<String, Arc<RwLock<RichTextDoc>>>>>,
     json_docs: Arc<RwLock<HashMap<String, Arc<RwLock<JsonDoc>>>>>,
     event_tx: broadcast::Sender<SessionEvent>,
+    sync: SyncManager<T>,
+    /// Who joined the session, if it shares its transport with others.
+    members: Option<SessionMembers>,
+    /// Who may edit, if the session has an owner.
+    roles: Option<RwLock<RoleTable>>,
+    /// Last version published for each document.
+    versions: RwLock<HashMap<String, u64>>,
+    storage: Option<Arc<dyn DocStorage>>,
+    gate: WriteGate,
+    clock: SharedClock,
 }
 
 impl<T: NetworkTransport> Session<T> {
@@ -57,15 +87,70 @@ impl<T: NetworkTransport> Session<T> {
             session_id,
             local_peer_id,
             user_name,
+            sync: SyncManager::new(transport.clone(), SyncConfig::default()),
+            members: None,
+            roles: None,
             transport,
             awareness,
             text_docs: Arc::new(RwLock::new(HashMap::new())),
             rich_text_docs: Arc::new(RwLock::new(HashMap::new())),
             json_docs: Arc::new(RwLock::new(HashMap::new())),
             event_tx,
+            versions: RwLock::new(HashMap::new()),
+            storage: None,
+            gate: WriteGate::default(),
+            clock: SharedClock::default(),
         }
     }
 
+    /// Use a custom sync configuration.
+    ///
+    /// `sync_timeout_ms` bounds how long [`close`](Self::close) waits for
+    /// peers to acknowledge the final edits.
+    pub fn with_sync_config(mut self, config: SyncConfig) -> Self {
+        self.sync = SyncManager::new(self.transport.clone(), config).with_clock(self.clock.clone());
+        if let Some(members) = self.members.clone() {
+            self.sync = self.sync.with_session(self.session_id.clone(), members);
+        }
+        self
+    }
+
+    /// Share the transport with other sessions.
+    ///
+    /// Everything the session sends is addressed to it and only goes to
+    /// `members`; messages addressed to other sessions are ignored.
+    pub fn with_members(mut self, members: SessionMembers) -> Self {
+        self.sync = self
+            .sync
+            .with_session(self.session_id.clone(), members.clone());
+        self.members = Some(members);
+        self
+    }
+
+    /// Use `clock` for presence times, sync bookkeeping and undo grouping.
+    pub fn with_clock(mut self, clock: impl Into<SharedClock>) -> Self {
+        self.clock = clock.into();
+        self.awareness.set_clock(self.clock.clone());
+        self.sync = self.sync.with_clock(self.clock.clone());
+        self
+    }
+
+    /// Let `owner` decide who may edit.
+    ///
+    /// Every participant must be given the same owner. Until the owner
+    /// says otherwise, everyone is an editor.
+    pub fn with_owner(mut self, owner: PeerId) -> Self {
+        self.roles = Some(RwLock::new(RoleTable::new(owner)));
+        self
+    }
+
+    /// Checkpoint documents to `storage` when the session closes, and
+    /// restore them from it when they are opened.
+    pub fn with_storage(mut self, storage: Arc<dyn DocStorage>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
     /// Get the session ID.
     pub fn session_id(&self) -> &str {
         &self.session_id
@@ -86,6 +171,73 @@ impl<T: NetworkTransport> Session<T> {
         &self.awareness
     }
 
+    /// Get the sync manager.
+    pub fn sync(&self) -> &SyncManager<T> {
+        &self.sync
+    }
+
+    /// Get the peers that joined the session, if it shares its transport.
+    pub fn members(&self) -> Option<&SessionMembers> {
+        self.members.as_ref()
+    }
+
+    /// Get the session's role table, if it has an owner.
+    pub fn roles(&self) -> Option<RoleTable> {
+        self.roles.as_ref().map(|roles| roles.read().clone())
+    }
+
+    /// Get a peer's role; without an owner, everyone is an editor.
+    pub fn role(&self, peer_id: &PeerId) -> ParticipantRole {
+        self.roles
+            .as_ref()
+            .map_or(ParticipantRole::Editor, |roles| roles.read().role(peer_id))
+    }
+
+    /// Assign a role to a peer and send the role table to the session.
+    ///
+    /// Fails with [`SdkError::PermissionDenied`] unless the session has an
+    /// owner and it is the local peer.
+    pub async fn set_role(&self, peer_id: &PeerId, role: ParticipantRole) -> Result<(), SdkError> {
+        match &self.roles {
+            Some(roles) if *roles.read().owner() == self.local_peer_id => {
+                roles.write().assign(peer_id, role);
+            }
+            _ => {
+                return Err(SdkError::PermissionDenied(format!(
+                    "only the owner of {} assigns roles",
+                    self.session_id
+                )))
+            }
+        }
+        self.publish_roles().await
+    }
+
+    /// Send the role table to the session, if the local peer owns it.
+    pub async fn publish_roles(&self) -> Result<(), SdkError> {
+        match self.owned_roles()? {
+            Some(message) => self.broadcast(message).await,
+            None => Ok(()),
+        }
+    }
+
+    /// The role table as a message, if the local peer owns the session.
+    fn owned_roles(&self) -> Result<Option<Message>, SdkError> {
+        let Some(roles) = &self.roles else {
+            return Ok(None);
+        };
+        let roles = roles.read();
+        if *roles.owner() != self.local_peer_id {
+            return Ok(None);
+        }
+        let state = serde_json::to_vec(&*roles)?;
+        Ok(Some(Message::Roles { state }))
+    }
+
+    /// Check whether the session has been closed.
+    pub fn is_closed(&self) -> bool {
+        !self.gate.is_open()
+    }
+
     /// Subscribe to session events.
     pub fn subscribe(&self) -> broadcast::Receiver<SessionEvent> {
         self.event_tx.subscribe()
@@ -99,10 +251,8 @@ impl<T: NetworkTransport> Session<T> {
         };
 
         // Send hello to all connected peers
-        self.transport
-            .broadcast(message)
-            .await
-            .map_err(|e| SdkError::NetworkError(e.to_string()))?;
+        self.broadcast(message).await?;
+        self.publish_roles().await?;
 
         let _ = self.event_tx.send(SessionEvent::Connected);
 
@@ -116,6 +266,13 @@ impl<T: NetworkTransport> Session<T> {
     }
 
     /// Create or open a text document.
+    ///
+    /// The name is the document's ID on every replica, so clients opening
+    /// the same name before they sync still edit one document: their
+    /// updates are routed by that ID and merge when they meet.
+    ///
+    /// With storage configured, a document that isn't open yet starts from
+    /// its last checkpoint, undo history included.
     pub fn open_text_doc(&self, document_id: impl Into<String>) -> Arc<RwLock<TextDoc>> {
         let document_id = document_id.into();
         let mut docs = self.text_docs.write();
@@ -123,10 +280,17 @@ impl<T: NetworkTransport> Session<T> {
         if let Some(doc) = docs.get(&document_id) {
             doc.clone()
         } else {
-            let doc = Arc::new(RwLock::new(TextDoc::new(
-                document_id.clone(),
-                self.local_peer_id.0.clone(),
-            )));
+            let mut doc = TextDoc::new(document_id.clone(), self.local_peer_id.0.clone())
+                .with_gate(self.gate.clone())
+                .with_clock(self.clock.clone());
+            let (state, history) = self.load_checkpoint(&document_id);
+            if let Some(state) = state {
+                let _ = doc.merge_encoded(&state);
+            }
+            if let Some(history) = history {
+                doc.import_undo(history);
+            }
+            let doc = Arc::new(RwLock::new(doc));
             docs.insert(document_id.clone(), doc.clone());
 
             let _ = self
@@ -138,6 +302,8 @@ impl<T: NetworkTransport> Session<T> {
     }
 
     /// Create or open a rich text document.
+    ///
+    /// Named like [`open_text_doc`](Self::open_text_doc).
     pub fn open_rich_text_doc(&self, document_id: impl Into<String>) -> Arc<RwLock<RichTextDoc>> {
         let document_id = document_id.into();
         let mut docs = self.rich_text_docs.write();
@@ -145,10 +311,17 @@ impl<T: NetworkTransport> Session<T> {
         if let Some(doc) = docs.get(&document_id) {
             doc.clone()
         } else {
-            let doc = Arc::new(RwLock::new(RichTextDoc::new(
-                document_id.clone(),
-                self.local_peer_id.0.clone(),
-            )));
+            let mut doc = RichTextDoc::new(document_id.clone(), self.local_peer_id.0.clone())
+                .with_gate(self.gate.clone())
+                .with_clock(self.clock.clone());
+            let (state, history) = self.load_checkpoint(&document_id);
+            if let Some(state) = state {
+                let _ = doc.merge_encoded(&state);
+            }
+            if let Some(history) = history {
+                doc.import_undo(history);
+            }
+            let doc = Arc::new(RwLock::new(doc));
             docs.insert(document_id.clone(), doc.clone());
 
             let _ = self
@@ -160,6 +333,8 @@ impl<T: NetworkTransport> Session<T> {
     }
 
     /// Create or open a JSON document.
+    ///
+    /// Named like [`open_text_doc`](Self::open_text_doc).
     pub fn open_json_doc(&self, document_id: impl Into<String>) -> Arc<RwLock<JsonDoc>> {
         let document_id = document_id.into();
         let mut docs = self.json_docs.write();
@@ -167,10 +342,16 @@ impl<T: NetworkTransport> Session<T> {
         if let Some(doc) = docs.get(&document_id) {
             doc.clone()
         } else {
-            let doc = Arc::new(RwLock::new(JsonDoc::new(
-                document_id.clone(),
-                self.local_peer_id.0.clone(),
-            )));
+            let mut doc = JsonDoc::new(document_id.clone(), self.local_peer_id.0.clone())
+                .with_gate(self.gate.clone());
+            let (state, history) = self.load_checkpoint(&document_id);
+            if let Some(state) = state {
+                let _ = doc.merge_encoded(&state);
+            }
+            if let Some(history) = history {
+                doc.import_undo(history);
+            }
+            let doc = Arc::new(RwLock::new(doc));
             docs.insert(document_id.clone(), doc.clone());
 
             let _ = self
@@ -201,16 +382,375 @@ impl<T: NetworkTransport> Session<T> {
         docs
     }
 
-    /// Get connected peers.
+    /// Get connected peers, only those that joined if the transport is
+    /// shared.
     pub async fn peers(&self) -> Vec<Peer> {
-        self.transport.connected_peers().await
+        let mut peers = self.transport.connected_peers().await;
+        if let Some(members) = &self.members {
+            peers.retain(|peer| members.contains(&peer.id));
+        }
+        peers
+    }
+
+    /// Send a message to one peer, addressed to the session if the
+    /// transport is shared.
+    async fn send(&self, peer_id: &PeerId, message: Message) -> Result<(), SdkError> {
+        self.transport
+            .send(peer_id, self.address(message))
+            .await
+            .map_err(SdkError::from)
+    }
+
+    /// Send a message to every peer in the session.
+    async fn broadcast(&self, message: Message) -> Result<(), SdkError> {
+        if self.members.is_none() {
+            return self
+                .transport
+                .broadcast(message)
+                .await
+                .map_err(SdkError::from);
+        }
+        for peer in self.peers().await {
+            self.send(&peer.id, message.clone()).await?;
+        }
+        Ok(())
+    }
+
+    fn address(&self, message: Message) -> Message {
+        match self.members {
+            Some(_) => Message::for_session(self.session_id.clone(), message),
+            None => message,
+        }
+    }
+
+    /// Send the pending edits of every open document to connected peers.
+    ///
+    /// Peers back from a disconnection that overflowed their offline queue
+    /// get the full state of every open document first.
+    ///
+    /// Fails with [`SdkError::ShuttingDown`] once the session is closed.
+    pub async fn publish(&self) -> Result<(), SdkError> {
+        if self.is_closed() {
+            return Err(SdkError::ShuttingDown(self.session_id.clone()));
+        }
+        self.publish_pending().await
+    }
+
+    async fn publish_pending(&self) -> Result<(), SdkError> {
+        // Peers whose offline queue overflowed catch up from the full state
+        for peer_id in self.sync.reconnect_peers().await {
+            for (document_id, state) in self.snapshots() {
+                self.send(&peer_id, Message::Snapshot { document_id, state })
+                    .await?;
+            }
+        }
+
+        let mut pending = Vec::new();
+        collect_deltas(&self.text_docs, &mut pending);
+        collect_deltas(&self.rich_text_docs, &mut pending);
+        collect_deltas(&self.json_docs, &mut pending);
+
+        for (document_id, delta) in pending {
+            let version = {
+                let mut versions = self.versions.write();
+                let version = versions.entry(document_id.clone()).or_default();
+                *version += 1;
+                *version
+            };
+            self.sync
+                .broadcast_update(&document_id, delta, version)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Send the full state of every open document to connected peers.
+    ///
+    /// A relay keeps the latest snapshot of each document and hands it to
+    /// clients that join the session later, even once everyone else left.
+    pub async fn publish_snapshots(&self) -> Result<(), SdkError> {
+        for (document_id, state) in self.snapshots() {
+            self.broadcast(Message::Snapshot { document_id, state })
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// The full state of every open document.
+    fn snapshots(&self) -> Vec<(String, Vec<u8>)> {
+        let mut snapshots = Vec::new();
+        for (id, doc) in self.text_docs.read().iter() {
+            snapshots.push((id.clone(), doc.read().encode_state()));
+        }
+        for (id, doc) in self.rich_text_docs.read().iter() {
+            snapshots.push((id.clone(), doc.read().encode_state()));
+        }
+        for (id, doc) in self.json_docs.read().iter() {
+            snapshots.push((id.clone(), doc.read().encode_state()));
+        }
+        snapshots
+    }
+
+    /// Handle a message received from a peer.
+    ///
+    /// Updates for open documents are applied and acknowledged; updates for
+    /// other documents are ignored so the sender retransmits them. Snapshots
+    /// are merged into open documents and otherwise dropped. Returns
+    /// the session event the message produced, if any, which is also sent
+    /// to subscribers.
+    ///
+    /// A session sharing its transport takes the messages addressed to it
+    /// and ignores those addressed to other sessions.
+    ///
+    /// In a session with an owner, updates and snapshots from viewers are
+    /// dropped, with updates still acknowledged so they aren't resent,
+    /// and reported as [`SessionEvent::UnauthorizedEdit`]. Role tables are
+    /// taken from the owner only, and the owner answers a hello with its
+    /// own.
+    pub async fn handle_message(
+        &self,
+        from: &PeerId,
+        message: &Message,
+    ) -> Result<Option<SessionEvent>, SdkError> {
+        let message = match message {
+            Message::Session {
+                session_id,
+                message,
+            } if *session_id == self.session_id => message,
+            Message::Session { .. } => return Ok(None),
+            message => message,
+        };
+        let event = match message {
+            Message::Hello { user_name, .. } => {
+                if let Some(roles) = self.owned_roles()? {
+                    self.send(from, roles).await?;
+                }
+                Some(SessionEvent::PeerJoined {
+                    peer_id: from.clone(),
+                    user_name: user_name.clone(),
+                })
+            }
+            Message::Goodbye { .. } => Some(self.forget(from)),
+            Message::Update { document_id, .. } | Message::Snapshot { document_id, .. }
+                if !self.role(from).can_edit() =>
+            {
+                if matches!(message, Message::Update { .. }) {
+                    self.sync.handle_message(from, message).await?;
+                }
+                Some(SessionEvent::UnauthorizedEdit {
+                    peer_id: from.clone(),
+                    document_id: document_id.clone(),
+                })
+            }
+            Message::Update {
+                document_id, delta, ..
+            } => {
+                if self.apply_remote(document_id, delta) {
+                    self.sync.handle_message(from, message).await?;
+                }
+                None
+            }
+            Message::Snapshot { document_id, state } => {
+                self.merge_snapshot(document_id, state)?;
+                None
+            }
+            Message::Roles { state } => {
+                if let Some(roles) = &self.roles {
+                    if *roles.read().owner() == *from {
+                        let table: RoleTable = serde_json::from_slice(state)?;
+                        roles.write().merge(&table);
+                    }
+                }
+                None
+            }
+            Message::DeltaAck { .. } => {
+                self.sync.handle_message(from, message).await?;
+                None
+            }
+            Message::Awareness { delta } => {
+                let delta = serde_json::from_slice(delta)?;
+                self.awareness.apply_delta(&delta);
+                None
+            }
+            _ => None,
+        };
+
+        if let Some(event) = &event {
+            let _ = self.event_tx.send(event.clone());
+        }
+        Ok(event)
+    }
+
+    /// Stop syncing with a peer that left the session.
+    pub(crate) fn peer_left(&self, peer_id: &PeerId) {
+        let event = self.forget(peer_id);
+        let _ = self.event_tx.send(event);
+    }
+
+    fn forget(&self, peer_id: &PeerId) -> SessionEvent {
+        self.sync.forget_peer(peer_id);
+        SessionEvent::PeerLeft {
+            peer_id: peer_id.clone(),
+        }
+    }
+
+    /// Apply a remote delta to the open document it belongs to.
+    fn apply_remote(&self, document_id: &str, delta: &[u8]) -> bool {
+        if let Some(doc) = self.text_docs.read().get(document_id) {
+            doc.write().apply_remote(delta);
+        } else if let Some(doc) = self.rich_text_docs.read().get(document_id) {
+            doc.write().apply_remote(delta);
+        } else if let Some(doc) = self.json_docs.read().get(document_id) {
+            doc.write().apply_remote(delta);
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// Merge a snapshot into the open document it belongs to.
+    fn merge_snapshot(&self, document_id: &str, state: &[u8]) -> Result<(), SdkError> {
+        if let Some(doc) = self.text_docs.read().get(document_id) {
+            doc.write().merge_encoded(state)
+        } else if let Some(doc) = self.rich_text_docs.read().get(document_id) {
+            doc.write().merge_encoded(state)
+        } else if let Some(doc) = self.json_docs.read().get(document_id) {
+            doc.write().merge_encoded(state)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Close the session.
+    ///
+    /// Stops accepting local edits, publishes the pending ones and waits up
+    /// to the sync timeout for peers to acknowledge them. Then tells peers
+    /// the local user went offline and checkpoints documents to storage, if
+    /// configured. Every step runs even if an earlier one failed; edits
+    /// still unacknowledged at the timeout are reported as
+    /// [`SdkError::PartialFlush`]. Closing again is a no-op.
+    pub async fn close(&self) -> Result<(), SdkError> {
+        if self.is_closed() {
+            return Ok(());
+        }
+        self.gate.close();
+
+        let flushed = self.flush().await;
+        let announced = self.announce_offline().await;
+        let saved = self.checkpoint();
+        let _ = self.event_tx.send(SessionEvent::Disconnected);
+
+        flushed.and(announced).and(saved)
+    }
+
+    /// Publish pending edits and wait for every peer to acknowledge them.
+    async fn flush(&self) -> Result<(), SdkError> {
+        self.publish_pending().await?;
+
+        let flushes = self
+            .sync
+            .unacked()
+            .into_iter()
+            .map(|u| self.sync.flush_to(&u.peer_id, &u.document_id));
+        futures::future::join_all(flushes).await;
+
+        let unacked = self.sync.unacked();
+        if unacked.is_empty() {
+            Ok(())
+        } else {
+            Err(SdkError::PartialFlush(unacked))
+        }
+    }
+
+    /// Tell peers the local user went offline.
+    async fn announce_offline(&self) -> Result<(), SdkError> {
+        self.awareness.set_status(UserStatus::Offline);
+        self.publish_presence().await
+    }
+
+    /// Send local presence changes, such as cursor moves, to the peers in
+    /// the session.
+    pub async fn publish_presence(&self) -> Result<(), SdkError> {
+        let Some(delta) = self.awareness.take_delta() else {
+            return Ok(());
+        };
+        let delta = serde_json::to_vec(&delta)?;
+        self.broadcast(Message::Awareness { delta }).await
+    }
+
+    /// Save every open document and its undo history to storage, if
+    /// configured.
+    fn checkpoint(&self) -> Result<(), SdkError> {
+        let Some(storage) = &self.storage else {
+            return Ok(());
+        };
+        for (id, doc) in self.text_docs.read().iter() {
+            let doc = doc.read();
+            storage.save(&self.session_id, id, &doc.encode_state())?;
+            storage.save_undo(&self.session_id, id, &encode_undo(&doc.export_undo())?)?;
+        }
+        for (id, doc) in self.rich_text_docs.read().iter() {
+            let doc = doc.read();
+            storage.save(&self.session_id, id, &doc.encode_state())?;
+            storage.save_undo(&self.session_id, id, &encode_undo(&doc.export_undo())?)?;
+        }
+        for (id, doc) in self.json_docs.read().iter() {
+            let doc = doc.read();
+            storage.save(&self.session_id, id, &doc.encode_state())?;
+            storage.save_undo(&self.session_id, id, &encode_undo(&doc.export_undo())?)?;
+        }
+        Ok(())
+    }
+
+    /// Load a document's last checkpoint and undo history from storage, if
+    /// configured. Whatever is missing or unreadable is left out, and the
+    /// document opens without it.
+    fn load_checkpoint(&self, document_id: &str) -> (Option<Vec<u8>>, Option<UndoHistory>) {
+        let Some(storage) = &self.storage else {
+            return (None, None);
+        };
+        let state = storage.load(&self.session_id, document_id).ok().flatten();
+        let history = storage
+            .load_undo(&self.session_id, document_id)
+            .ok()
+            .flatten()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok());
+        (state, history)
+    }
+
+    /// Best-effort close for a client dropped without shutting down: send
+    /// pending edits and go offline without waiting for acknowledgments.
+    pub(crate) async fn close_unacked(&self) {
+        self.gate.close();
+        let _ = self.publish_pending().await;
+        let _ = self.announce_offline().await;
+    }
+}
+
+/// Serialize an undo history for [`DocStorage::save_undo`].
+fn encode_undo(history: &UndoHistory) -> Result<Vec<u8>, SdkError> {
+    serde_json::to_vec(history).map_err(SdkError::from)
+}
+
+/// Take the pending deltas of every document in a map.
+fn collect_deltas<D: CollaborativeDoc>(
+    docs: &RwLock<HashMap<String, Arc<RwLock<D>>>>,
+    out: &mut Vec<(String, Vec<u8>)>,
+) {
+    for (id, doc) in docs.read().iter() {
+        for delta in doc.write().take_pending_deltas() {
+            out.push((id.clone(), delta));
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::document::DocChange;
     use crate::network::MemoryTransport;
+    use crate::storage::MemoryDocStorage;
+    use mdcs_db::json_crdt::JsonValue;
 
     #[tokio::test]
     async fn test_session_creation() {
@@ -244,4 +784,222 @@ mod tests {
         let docs = session.open_documents();
         assert_eq!(docs.len(), 2);
     }
+
+    #[tokio::test]
+    async fn test_close_checkpoints_and_rejects_edits() {
+        let peer_id = PeerId::new("peer-1");
+        let transport = Arc::new(MemoryTransport::new(peer_id.clone()));
+        let storage = Arc::new(MemoryDocStorage::new());
+
+        let session =
+            Session::new("session-1", peer_id, "Alice", transport).with_storage(storage.clone());
+        let text = session.open_text_doc("doc-1");
+        let json = session.open_json_doc("doc-2");
+        text.write().insert(0, "Hello");
+        json.write()
+            .set("title", JsonValue::String("Notes".to_string()));
+
+        session.close().await.unwrap();
+        assert!(session.is_closed());
+        assert_eq!(storage.len(), 2);
+        let saved = storage.load("session-1", "doc-1").unwrap().unwrap();
+        let saved: mdcs_db::RGAText = serde_json::from_slice(&saved).unwrap();
+        assert_eq!(saved.to_string(), "Hello");
+
+        text.write().insert(5, "!");
+        json.write().set("title", JsonValue::Null);
+        assert_eq!(text.read().get_text(), "Hello");
+        assert_eq!(
+            json.read().get("title"),
+            Some(JsonValue::String("Notes".to_string()))
+        );
+        assert!(matches!(
+            session.publish().await,
+            Err(SdkError::ShuttingDown(_))
+        ));
+        // Closing again is a no-op
+        session.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reopened_documents_restore_undo_history() {
+        let storage = Arc::new(MemoryDocStorage::new());
+        let open = |storage: &Arc<MemoryDocStorage>| {
+            let peer_id = PeerId::new("peer-1");
+            let transport = Arc::new(MemoryTransport::new(peer_id.clone()));
+            Session::new("session-1", peer_id, "Alice", transport).with_storage(storage.clone())
+        };
+
+        let session = open(&storage);
+        let text = session.open_text_doc("doc-1");
+        text.write().insert(0, "Hello");
+        text.write().insert(5, " world");
+        session.close().await.unwrap();
+
+        // Undo picks up where the last session left off
+        let session = open(&storage);
+        let text = session.open_text_doc("doc-1");
+        assert_eq!(text.read().get_text(), "Hello world");
+        assert!(text.write().undo());
+        assert_eq!(text.read().get_text(), "Hello");
+        session.close().await.unwrap();
+
+        // While the document is closed, a remote edit lands inside the text
+        // the remaining insert would remove
+        let mut remote = TextDoc::new("doc-1", "peer-2");
+        let saved = storage.load("session-1", "doc-1").unwrap().unwrap();
+        remote.merge_encoded(&saved).unwrap();
+        remote.insert(2, "y");
+
+        let session = open(&storage);
+        let text = session.open_text_doc("doc-1");
+        text.write().merge_encoded(&remote.encode_state()).unwrap();
+        let mut events = text.read().subscribe();
+        assert!(text.read().can_undo());
+        assert!(!text.write().undo());
+        assert_eq!(text.read().get_text(), "Heyllo");
+        assert_eq!(
+            events.try_recv().unwrap().change,
+            DocChange::UndoSkipped { operations: 1 }
+        );
+        assert!(!text.read().can_undo());
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_merge_into_open_documents() {
+        let alice = Arc::new(MemoryTransport::new(PeerId::new("alice")));
+        let bob = Arc::new(MemoryTransport::new(PeerId::new("bob")));
+        alice.connect_to(&bob);
+        let mut inbox = bob.subscribe();
+
+        let sender = Session::new("s", PeerId::new("alice"), "Alice", alice);
+        sender.open_text_doc("notes").write().insert(0, "Hello");
+        sender
+            .open_rich_text_doc("draft")
+            .write()
+            .insert(0, "Draft");
+        sender
+            .open_json_doc("meta")
+            .write()
+            .set("title", JsonValue::String("Notes".to_string()));
+        sender.publish_snapshots().await.unwrap();
+
+        let receiver = Session::new("s", PeerId::new("bob"), "Bob", bob);
+        let notes = receiver.open_text_doc("notes");
+        notes.write().insert(0, ">> ");
+        let meta = receiver.open_json_doc("meta");
+        for _ in 0..3 {
+            let (from, message) = inbox.recv().await.unwrap();
+            assert!(matches!(message, Message::Snapshot { .. }));
+            receiver.handle_message(&from, &message).await.unwrap();
+        }
+
+        assert_eq!(notes.read().get_text(), ">> Hello");
+        assert_eq!(
+            meta.read().get("title"),
+            Some(JsonValue::String("Notes".to_string()))
+        );
+        // Snapshots of documents that aren't open are dropped
+        assert_eq!(receiver.open_documents().len(), 2);
+
+        let garbage = Message::Snapshot {
+            document_id: "notes".to_string(),
+            state: b"not a snapshot".to_vec(),
+        };
+        assert!(matches!(
+            receiver
+                .handle_message(&PeerId::new("alice"), &garbage)
+                .await,
+            Err(SdkError::Json(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_viewer_edits_apply_only_once_promoted() {
+        let alice = Arc::new(MemoryTransport::new(PeerId::new("alice")));
+        let bob = Arc::new(MemoryTransport::new(PeerId::new("bob")));
+        let carol = Arc::new(MemoryTransport::new(PeerId::new("carol")));
+        alice.connect_to(&bob);
+        alice.connect_to(&carol);
+        bob.connect_to(&carol);
+        let mut alice_inbox = alice.subscribe();
+        let mut bob_inbox = bob.subscribe();
+        let mut carol_inbox = carol.subscribe();
+
+        let owner = PeerId::new("alice");
+        let open = |peer: &str, name: &str, transport: Arc<MemoryTransport>| {
+            Session::new("s", PeerId::new(peer), name, transport).with_owner(owner.clone())
+        };
+        let alice = open("alice", "Alice", alice);
+        let bob = open("bob", "Bob", bob);
+        let carol = open("carol", "Carol", carol);
+        let docs = [alice.open_text_doc("notes"), carol.open_text_doc("notes")];
+        let bob_doc = bob.open_text_doc("notes");
+
+        // Only the owner assigns roles
+        assert!(matches!(
+            carol
+                .set_role(&PeerId::new("bob"), ParticipantRole::Editor)
+                .await,
+            Err(SdkError::PermissionDenied(_))
+        ));
+        alice
+            .set_role(&PeerId::new("bob"), ParticipantRole::Viewer)
+            .await
+            .unwrap();
+        let (from, message) = carol_inbox.recv().await.unwrap();
+        carol.handle_message(&from, &message).await.unwrap();
+        assert_eq!(carol.role(&PeerId::new("bob")), ParticipantRole::Viewer);
+
+        bob_doc.write().insert(0, "spam");
+        bob.publish().await.unwrap();
+        for (session, inbox) in [(&alice, &mut alice_inbox), (&carol, &mut carol_inbox)] {
+            let (from, message) = inbox.recv().await.unwrap();
+            let event = session.handle_message(&from, &message).await.unwrap();
+            assert!(matches!(
+                event,
+                Some(SessionEvent::UnauthorizedEdit { peer_id, .. }) if peer_id.0 == "bob"
+            ));
+        }
+        for doc in &docs {
+            assert_eq!(doc.read().get_text(), "");
+        }
+        // Dropped updates are still acknowledged
+        while !bob.sync().unacked().is_empty() {
+            let (from, message) = bob_inbox.recv().await.unwrap();
+            bob.handle_message(&from, &message).await.unwrap();
+        }
+
+        // Presence from a viewer still goes through
+        bob.awareness().set_status(UserStatus::Away);
+        bob.publish_presence().await.unwrap();
+        let (from, message) = alice_inbox.recv().await.unwrap();
+        alice.handle_message(&from, &message).await.unwrap();
+        assert!(alice
+            .awareness()
+            .get_users()
+            .iter()
+            .any(|user| user.user_id == "bob"));
+
+        alice
+            .set_role(&PeerId::new("bob"), ParticipantRole::Editor)
+            .await
+            .unwrap();
+        let (from, message) = carol_inbox.recv().await.unwrap();
+        carol.handle_message(&from, &message).await.unwrap();
+
+        bob_doc.write().insert(0, "Hi ");
+        bob.publish().await.unwrap();
+        for (session, inbox) in [(&alice, &mut alice_inbox), (&carol, &mut carol_inbox)] {
+            let (from, message) = inbox.recv().await.unwrap();
+            assert!(session
+                .handle_message(&from, &message)
+                .await
+                .unwrap()
+                .is_none());
+        }
+        for doc in &docs {
+            assert_eq!(doc.read().get_text(), "Hi ");
+        }
+    }
 }
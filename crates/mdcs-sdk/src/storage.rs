@@ -0,0 +1,150 @@
+//! Document checkpoint storage.
+
+use crate::error::SdkError;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+
+/// Persists encoded document state, e.g. when a session closes.
+pub trait DocStorage: Send + Sync {
+    /// Store the encoded state of a document, replacing any earlier one.
+    fn save(&self, session_id: &str, document_id: &str, state: &[u8]) -> Result<(), SdkError>;
+
+    /// Load the last stored state of a document.
+    fn load(&self, session_id: &str, document_id: &str) -> Result<Option<Vec<u8>>, SdkError>;
+
+    /// IDs of the documents stored for a session.
+    ///
+    /// Storage that can't list its contents returns none.
+    fn documents(&self, _session_id: &str) -> Result<Vec<String>, SdkError> {
+        Ok(Vec::new())
+    }
+
+    /// Store the encoded undo history of a document, replacing any earlier
+    /// one.
+    ///
+    /// Storage that doesn't keep undo history drops it.
+    fn save_undo(
+        &self,
+        _session_id: &str,
+        _document_id: &str,
+        _history: &[u8],
+    ) -> Result<(), SdkError> {
+        Ok(())
+    }
+
+    /// Load the last stored undo history of a document.
+    fn load_undo(
+        &self,
+        _session_id: &str,
+        _document_id: &str,
+    ) -> Result<Option<Vec<u8>>, SdkError> {
+        Ok(None)
+    }
+}
+
+/// In-memory document storage (for testing).
+#[derive(Debug, Default)]
+pub struct MemoryDocStorage {
+    docs: RwLock<HashMap<(String, String), Vec<u8>>>,
+    undo: RwLock<HashMap<(String, String), Vec<u8>>>,
+}
+
+impl MemoryDocStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of stored documents.
+    pub fn len(&self) -> usize {
+        self.docs.read().len()
+    }
+
+    /// Check if nothing is stored.
+    pub fn is_empty(&self) -> bool {
+        self.docs.read().is_empty()
+    }
+}
+
+impl DocStorage for MemoryDocStorage {
+    fn save(&self, session_id: &str, document_id: &str, state: &[u8]) -> Result<(), SdkError> {
+        self.docs.write().insert(
+            (session_id.to_string(), document_id.to_string()),
+            state.to_vec(),
+        );
+        Ok(())
+    }
+
+    fn load(&self, session_id: &str, document_id: &str) -> Result<Option<Vec<u8>>, SdkError> {
+        Ok(self
+            .docs
+            .read()
+            .get(&(session_id.to_string(), document_id.to_string()))
+            .cloned())
+    }
+
+    fn documents(&self, session_id: &str) -> Result<Vec<String>, SdkError> {
+        let mut documents: Vec<_> = self
+            .docs
+            .read()
+            .keys()
+            .filter(|(session, _)| session == session_id)
+            .map(|(_, document)| document.clone())
+            .collect();
+        documents.sort();
+        Ok(documents)
+    }
+
+    fn save_undo(
+        &self,
+        session_id: &str,
+        document_id: &str,
+        history: &[u8],
+    ) -> Result<(), SdkError> {
+        self.undo.write().insert(
+            (session_id.to_string(), document_id.to_string()),
+            history.to_vec(),
+        );
+        Ok(())
+    }
+
+    fn load_undo(&self, session_id: &str, document_id: &str) -> Result<Option<Vec<u8>>, SdkError> {
+        Ok(self
+            .undo
+            .read()
+            .get(&(session_id.to_string(), document_id.to_string()))
+            .cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_doc_storage() {
+        let storage = MemoryDocStorage::new();
+        assert!(storage.is_empty());
+
+        storage.save("session-1", "doc-1", b"one").unwrap();
+        storage.save("session-1", "doc-1", b"two").unwrap();
+        storage.save("session-2", "doc-1", b"other").unwrap();
+
+        assert_eq!(storage.len(), 2);
+        assert_eq!(
+            storage.load("session-1", "doc-1").unwrap(),
+            Some(b"two".to_vec())
+        );
+        assert_eq!(storage.load("session-1", "doc-2").unwrap(), None);
+        assert_eq!(storage.documents("session-1").unwrap(), vec!["doc-1"]);
+        assert!(storage.documents("session-3").unwrap().is_empty());
+
+        // Undo history is kept apart from the documents
+        storage.save_undo("session-1", "doc-1", b"history").unwrap();
+        assert_eq!(storage.len(), 2);
+        assert_eq!(
+            storage.load_undo("session-1", "doc-1").unwrap(),
+            Some(b"history".to_vec())
+        );
+        assert_eq!(storage.load_undo("session-2", "doc-1").unwrap(), None);
+    }
+}
@@ -1,13 +1,46 @@
 //! Synchronization primitives for the SDK.
 
-use crate::error::SdkError;
-use crate::network::{Message, NetworkTransport, PeerId};
-use std::collections::HashMap;
+use crate::error::{ConfigError, SdkError};
+use crate::network::{Inbox, Message, NetworkTransport, Peer, PeerId};
+use mdcs_core::clock::SharedClock;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::future::Future;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+
+/// Shortest sync or presence interval, in milliseconds. Anything faster
+/// retransmits before a round trip over a real network could complete.
+pub const MIN_SYNC_INTERVAL_MS: u64 = 10;
+
+/// Longest sync or presence interval, in milliseconds.
+pub const MAX_SYNC_INTERVAL_MS: u64 = 60_000;
+
+/// Longest sync timeout, in milliseconds.
+pub const MAX_SYNC_TIMEOUT_MS: u64 = 600_000;
+
+/// Largest delta batch.
+pub const MAX_BATCH_SIZE: usize = 10_000;
+
+/// Most updates an offline queue can be configured to hold.
+pub const MAX_OFFLINE_QUEUE_UPDATES: usize = 1_000_000;
+
+/// Most bytes an offline queue can be configured to hold.
+pub const MAX_OFFLINE_QUEUE_BYTES: usize = 1 << 30;
+
+/// Weight of the previous estimate when a new round-trip sample arrives,
+/// out of 8 (as in TCP's smoothed RTT).
+const RTT_HISTORY_WEIGHT: u64 = 7;
 
 /// Configuration for sync behavior.
-#[derive(Clone, Debug)]
+///
+/// Build one with [`SyncConfigBuilder`], or call
+/// [`validate`](Self::validate) on one made some other way, such as
+/// deserialized from a file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SyncConfig {
     /// How often to send sync requests (in milliseconds).
     pub sync_interval_ms: u64,
@@ -19,6 +52,15 @@ pub struct SyncConfig {
     pub max_batch_size: usize,
     /// Enable automatic background sync.
     pub auto_sync: bool,
+    /// Unacknowledged updates a peer may have before it counts as lagging.
+    pub lag_threshold: usize,
+    /// Most updates queued for a disconnected peer, or `None` for no limit.
+    pub offline_queue_max_updates: Option<usize>,
+    /// Most bytes of updates queued for a disconnected peer, or `None` for
+    /// no limit.
+    pub offline_queue_max_bytes: Option<usize>,
+    /// What to do when a disconnected peer's queue would go over a limit.
+    pub overflow_policy: OverflowPolicy,
 }
 
 impl Default for SyncConfig {
@@ -29,10 +71,70 @@ impl Default for SyncConfig {
             sync_timeout_ms: 5000,
             max_batch_size: 100,
             auto_sync: true,
+            lag_threshold: 100,
+            offline_queue_max_updates: Some(1_000),
+            offline_queue_max_bytes: Some(16 << 20),
+            overflow_policy: OverflowPolicy::default(),
         }
     }
 }
 
+impl SyncConfig {
+    /// Check every field, and that `sync_interval_ms` is below
+    /// `sync_timeout_ms` so a flush retransmits before it gives up.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        ConfigError::check_range(
+            "sync_interval_ms",
+            self.sync_interval_ms,
+            MIN_SYNC_INTERVAL_MS,
+            MAX_SYNC_INTERVAL_MS,
+        )?;
+        ConfigError::check_range(
+            "presence_interval_ms",
+            self.presence_interval_ms,
+            MIN_SYNC_INTERVAL_MS,
+            MAX_SYNC_INTERVAL_MS,
+        )?;
+        ConfigError::check_range(
+            "sync_timeout_ms",
+            self.sync_timeout_ms,
+            1,
+            MAX_SYNC_TIMEOUT_MS,
+        )?;
+        ConfigError::check_range(
+            "max_batch_size",
+            self.max_batch_size as u64,
+            1,
+            MAX_BATCH_SIZE as u64,
+        )?;
+        if let Some(updates) = self.offline_queue_max_updates {
+            ConfigError::check_range(
+                "offline_queue_max_updates",
+                updates as u64,
+                1,
+                MAX_OFFLINE_QUEUE_UPDATES as u64,
+            )?;
+        }
+        if let Some(bytes) = self.offline_queue_max_bytes {
+            ConfigError::check_range(
+                "offline_queue_max_bytes",
+                bytes as u64,
+                1,
+                MAX_OFFLINE_QUEUE_BYTES as u64,
+            )?;
+        }
+        if self.sync_interval_ms >= self.sync_timeout_ms {
+            return Err(ConfigError::NotBelow {
+                field: "sync_interval_ms",
+                value: self.sync_interval_ms,
+                limit_field: "sync_timeout_ms",
+                limit: self.sync_timeout_ms,
+            });
+        }
+        Ok(())
+    }
+}
+
 /// Builder for sync configuration.
 pub struct SyncConfigBuilder {
     config: SyncConfig,
@@ -70,8 +172,30 @@ impl SyncConfigBuilder {
         self
     }
 
-    pub fn build(self) -> SyncConfig {
-        self.config
+    pub fn lag_threshold(mut self, updates: usize) -> Self {
+        self.config.lag_threshold = updates;
+        self
+    }
+
+    pub fn offline_queue_max_updates(mut self, updates: Option<usize>) -> Self {
+        self.config.offline_queue_max_updates = updates;
+        self
+    }
+
+    pub fn offline_queue_max_bytes(mut self, bytes: Option<usize>) -> Self {
+        self.config.offline_queue_max_bytes = bytes;
+        self
+    }
+
+    pub fn overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.config.overflow_policy = policy;
+        self
+    }
+
+    /// Check the configuration with [`SyncConfig::validate`] and return it.
+    pub fn build(self) -> Result<SyncConfig, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
     }
 }
 
@@ -81,6 +205,27 @@ impl Default for SyncConfigBuilder {
     }
 }
 
+/// What a [`SyncManager`] does when a disconnected peer's offline queue
+/// would go over `offline_queue_max_updates` or `offline_queue_max_bytes`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OverflowPolicy {
+    /// Join the oldest queued update with the next one of the same
+    /// document, using the joiner given to
+    /// [`SyncManager::with_delta_joiner`]. Joining state-based deltas is
+    /// always safe. Falls back to `DropAndResyncFull` without a joiner, or
+    /// once there is nothing left to join.
+    CoalesceOldest,
+    /// Drop the queue; the peer gets the full state when it reconnects
+    /// (see [`SyncManager::reconnect_peers`]).
+    #[default]
+    DropAndResyncFull,
+    /// Refuse the update with [`SdkError::QueueFull`].
+    Error,
+}
+
+/// Joins two encoded deltas of a document, older first, into one.
+pub type DeltaJoiner = Arc<dyn Fn(&str, &[u8], &[u8]) -> Result<Vec<u8>, SdkError> + Send + Sync>;
+
 /// Events emitted by the sync manager.
 #[derive(Clone, Debug)]
 pub enum SyncEvent {
@@ -98,8 +243,22 @@ pub enum SyncEvent {
         peer_id: PeerId,
         document_id: String,
     },
+    /// Peer acknowledged an update we sent.
+    UpdateAcked {
+        peer_id: PeerId,
+        document_id: String,
+        version: u64,
+    },
     /// Sync error occurred.
     SyncError { peer_id: PeerId, error: String },
+    /// A peer's unacknowledged updates went over `lag_threshold`.
+    PeerLagging { peer_id: PeerId, outstanding: usize },
+    /// A lagging peer's unacknowledged updates are back within
+    /// `lag_threshold`.
+    PeerCaughtUp(PeerId),
+    /// A peer whose offline queue overflowed is back and needs the full
+    /// state of every document.
+    FullSyncNeeded(PeerId),
 }
 
 /// Sync state for a peer.
@@ -107,15 +266,263 @@ pub enum SyncEvent {
 pub struct PeerSyncState {
     /// Last known version for each document.
     pub document_versions: HashMap<String, u64>,
-    /// Last sync time.
-    pub last_sync: Option<Instant>,
+    /// Last sync time (milliseconds, by the sync manager's clock).
+    pub last_sync: Option<u64>,
+}
+
+/// How far behind a peer is, from [`SyncManager::peer_stats`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PeerSyncStats {
+    /// When the peer last acknowledged an update (milliseconds, by the
+    /// sync manager's clock).
+    pub last_ack: Option<u64>,
+    /// Updates sent to the peer and not acknowledged yet, over all
+    /// documents.
+    pub outstanding_updates: usize,
+    /// Size of those updates, in bytes.
+    pub outstanding_bytes: usize,
+    /// Smoothed round-trip time in milliseconds, from updates acknowledged
+    /// without being retransmitted.
+    pub rtt_ms: Option<u64>,
+    /// Updates retransmitted to the peer.
+    pub retransmissions: u64,
+    /// Whether the peer is over `lag_threshold`.
+    pub lagging: bool,
+    /// Updates queued while the peer is disconnected.
+    pub queued_updates: usize,
+    /// Size of those updates, in bytes.
+    pub queued_bytes: usize,
+    /// Whether queued updates were dropped, so the peer needs the full
+    /// state when it reconnects.
+    pub needs_full_sync: bool,
+}
+
+impl PeerSyncStats {
+    /// Count an update sent to the peer.
+    fn on_send(&mut self, bytes: usize) {
+        self.outstanding_updates += 1;
+        self.outstanding_bytes += bytes;
+    }
+
+    /// Count an acknowledged update, with its round trip unless it was
+    /// retransmitted.
+    fn on_ack(&mut self, bytes: usize, now: u64, rtt: Option<u64>) {
+        self.outstanding_updates = self.outstanding_updates.saturating_sub(1);
+        self.outstanding_bytes = self.outstanding_bytes.saturating_sub(bytes);
+        self.last_ack = Some(now);
+        if let Some(sample) = rtt {
+            self.rtt_ms = Some(match self.rtt_ms {
+                Some(rtt) => (rtt * RTT_HISTORY_WEIGHT + sample) / 8,
+                None => sample,
+            });
+        }
+    }
+
+    /// Copy the size of the peer's offline queue.
+    fn on_queue(&mut self, queue: &OfflineQueue) {
+        self.queued_updates = queue.updates.len();
+        self.queued_bytes = queue.bytes;
+        self.needs_full_sync = queue.needs_full_sync;
+    }
+
+    /// Note whether the backlog crossed `threshold`, returning the event
+    /// for the crossing.
+    fn check_lag(&mut self, peer_id: &PeerId, threshold: usize) -> Option<SyncEvent> {
+        let lagging = self.outstanding_updates > threshold;
+        if lagging == self.lagging {
+            return None;
+        }
+        self.lagging = lagging;
+        Some(if lagging {
+            SyncEvent::PeerLagging {
+                peer_id: peer_id.clone(),
+                outstanding: self.outstanding_updates,
+            }
+        } else {
+            SyncEvent::PeerCaughtUp(peer_id.clone())
+        })
+    }
+}
+
+/// Versions of a document that a peer has not acknowledged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnackedUpdates {
+    pub peer_id: PeerId,
+    pub document_id: String,
+    pub versions: Vec<u64>,
+}
+
+/// Updates of one document that some peer has not acknowledged yet.
+#[derive(Debug, Default)]
+struct DocOutbox {
+    /// Sent deltas by version, kept for retransmission.
+    log: BTreeMap<u64, Vec<u8>>,
+    /// Versions each peer still has to acknowledge.
+    unacked: HashMap<PeerId, BTreeSet<u64>>,
+    /// When each logged version was first sent.
+    sent_at: BTreeMap<u64, u64>,
+    /// Versions retransmitted to each peer, whose acks say nothing about
+    /// the round-trip time.
+    resent: HashMap<PeerId, BTreeSet<u64>>,
+}
+
+impl DocOutbox {
+    /// Drop logged deltas that no peer is waiting for.
+    fn gc(&mut self) {
+        let unacked = &self.unacked;
+        self.log
+            .retain(|version, _| unacked.values().any(|v| v.contains(version)));
+        let log = &self.log;
+        self.sent_at.retain(|version, _| log.contains_key(version));
+        self.resent.retain(|peer_id, resent| {
+            resent.retain(|v| unacked.get(peer_id).is_some_and(|u| u.contains(v)));
+            !resent.is_empty()
+        });
+    }
+}
+
+/// An update waiting for a disconnected peer.
+#[derive(Debug)]
+struct QueuedUpdate {
+    document_id: String,
+    version: u64,
+    delta: Vec<u8>,
+}
+
+/// Updates waiting for a disconnected peer, oldest first.
+#[derive(Debug, Default)]
+struct OfflineQueue {
+    updates: VecDeque<QueuedUpdate>,
+    bytes: usize,
+    /// Updates were dropped, so the peer needs the full state.
+    needs_full_sync: bool,
+}
+
+impl OfflineQueue {
+    /// Check whether `updates` more updates of `bytes` in all fit.
+    fn fits(&self, updates: usize, bytes: usize, config: &SyncConfig) -> bool {
+        config
+            .offline_queue_max_updates
+            .is_none_or(|max| self.updates.len() + updates <= max)
+            && config
+                .offline_queue_max_bytes
+                .is_none_or(|max| self.bytes + bytes <= max)
+    }
+
+    /// Queue an update, coalescing or dropping updates as `config` says if
+    /// that goes over a limit.
+    fn push(&mut self, update: QueuedUpdate, config: &SyncConfig, joiner: Option<&DeltaJoiner>) {
+        if self.needs_full_sync {
+            // The full state will carry it
+            return;
+        }
+        self.bytes += update.delta.len();
+        self.updates.push_back(update);
+        while !self.fits(0, 0, config) {
+            let coalesced = config.overflow_policy == OverflowPolicy::CoalesceOldest
+                && joiner.is_some_and(|join| self.coalesce_oldest(join));
+            if !coalesced {
+                self.updates.clear();
+                self.bytes = 0;
+                self.needs_full_sync = true;
+                return;
+            }
+        }
+    }
+
+    /// Join the oldest update that has a later one of the same document
+    /// into that one. Returns `false` if there is no such pair or the join
+    /// fails.
+    fn coalesce_oldest(&mut self, join: &DeltaJoiner) -> bool {
+        let Some((older, newer)) = (0..self.updates.len()).find_map(|i| {
+            let document_id = &self.updates[i].document_id;
+            (i + 1..self.updates.len())
+                .find(|&j| self.updates[j].document_id == *document_id)
+                .map(|j| (i, j))
+        }) else {
+            return false;
+        };
+        let (old, new) = (&self.updates[older], &self.updates[newer]);
+        let Ok(joined) = join(&old.document_id, &old.delta, &new.delta) else {
+            return false;
+        };
+        self.bytes = self.bytes - old.delta.len() - new.delta.len() + joined.len();
+        self.updates[newer].delta = joined;
+        self.updates.remove(older);
+        true
+    }
+}
+
+/// Type alias for the per-document outboxes shared with flush futures.
+type SharedOutbox = Arc<RwLock<HashMap<String, DocOutbox>>>;
+
+/// Type alias for the per-peer statistics shared with flush futures.
+type SharedStats = Arc<RwLock<HashMap<PeerId, PeerSyncStats>>>;
+
+/// The peers that joined a session on a shared transport.
+///
+/// Cloning shares the set, so the client tracking joins and leaves and the
+/// session's [`SyncManager`] see the same members.
+#[derive(Clone, Debug, Default)]
+pub struct SessionMembers(Arc<RwLock<HashSet<PeerId>>>);
+
+impl SessionMembers {
+    /// Add a peer, returning whether it was new.
+    pub fn insert(&self, peer_id: PeerId) -> bool {
+        self.0.write().insert(peer_id)
+    }
+
+    /// Remove a peer, returning whether it was a member.
+    pub fn remove(&self, peer_id: &PeerId) -> bool {
+        self.0.write().remove(peer_id)
+    }
+
+    /// Check whether a peer is a member.
+    pub fn contains(&self, peer_id: &PeerId) -> bool {
+        self.0.read().contains(peer_id)
+    }
+
+    /// List the members.
+    pub fn to_vec(&self) -> Vec<PeerId> {
+        self.0.read().iter().cloned().collect()
+    }
 }
 
 /// Manages synchronization between peers.
+///
+/// Updates sent with [`broadcast_update`](Self::broadcast_update) are kept
+/// until each peer that was connected at the time acknowledges them with
+/// [`Message::DeltaAck`]. [`flush_to`](Self::flush_to) waits for those
+/// acks, retransmitting every `sync_interval_ms` and giving up after
+/// `sync_timeout_ms`.
+///
+/// [`peer_stats`](Self::peer_stats) tells how far behind each peer is.
+/// A peer crossing `lag_threshold` either way is reported to
+/// [`subscribe`](Self::subscribe) receivers, as it can happen on a
+/// broadcast as well as on an ack.
+///
+/// Updates for a peer that was connected once and isn't any more are
+/// queued until it is back, up to the `offline_queue_*` limits; past those
+/// the [`OverflowPolicy`] decides. Queued updates go out with the next
+/// broadcast after the peer reconnects, or on
+/// [`reconnect_peers`](Self::reconnect_peers).
 pub struct SyncManager<T: NetworkTransport> {
     transport: Arc<T>,
+    /// The session synced, and its members, on a shared transport.
+    scope: Option<(String, SessionMembers)>,
     config: SyncConfig,
     peer_states: HashMap<PeerId, PeerSyncState>,
+    outbox: SharedOutbox,
+    /// Kept apart from the outbox so reading it never waits on a flush.
+    stats: SharedStats,
+    /// Bumped on every ack so flush futures re-check their barrier.
+    acks: watch::Sender<u64>,
+    /// Peers seen connected, which get an offline queue while away.
+    known_peers: RwLock<HashSet<PeerId>>,
+    offline: RwLock<HashMap<PeerId, OfflineQueue>>,
+    joiner: Option<DeltaJoiner>,
+    event_tx: broadcast::Sender<SyncEvent>,
+    clock: SharedClock,
 }
 
 impl<T: NetworkTransport> SyncManager<T> {
@@ -123,33 +530,289 @@ impl<T: NetworkTransport> SyncManager<T> {
     pub fn new(transport: Arc<T>, config: SyncConfig) -> Self {
         Self {
             transport,
+            scope: None,
             config,
             peer_states: HashMap::new(),
+            outbox: Arc::new(RwLock::new(HashMap::new())),
+            stats: Arc::new(RwLock::new(HashMap::new())),
+            acks: watch::channel(0).0,
+            known_peers: RwLock::new(HashSet::new()),
+            offline: RwLock::new(HashMap::new()),
+            joiner: None,
+            event_tx: broadcast::channel(100).0,
+            clock: SharedClock::default(),
         }
     }
 
+    /// Record sync times with `clock`.
+    pub fn with_clock(mut self, clock: impl Into<SharedClock>) -> Self {
+        self.clock = clock.into();
+        self
+    }
+
+    /// Sync one session of a transport shared by several.
+    ///
+    /// Messages are addressed to the session and only go to its `members`;
+    /// [`handle_message`](Self::handle_message) ignores those addressed to
+    /// other sessions.
+    pub fn with_session(mut self, session_id: impl Into<String>, members: SessionMembers) -> Self {
+        self.scope = Some((session_id.into(), members));
+        self
+    }
+
+    /// Join queued deltas with `joiner` under
+    /// [`OverflowPolicy::CoalesceOldest`].
+    ///
+    /// It is given the document ID and two encoded deltas of that
+    /// document, older first, and returns one that has the effect of both.
+    pub fn with_delta_joiner<F>(mut self, joiner: F) -> Self
+    where
+        F: Fn(&str, &[u8], &[u8]) -> Result<Vec<u8>, SdkError> + Send + Sync + 'static,
+    {
+        self.joiner = Some(Arc::new(joiner));
+        self
+    }
+
     /// Get the sync configuration.
     pub fn config(&self) -> &SyncConfig {
         &self.config
     }
 
+    /// Subscribe to peers starting and ending to lag.
+    pub fn subscribe(&self) -> broadcast::Receiver<SyncEvent> {
+        self.event_tx.subscribe()
+    }
+
     /// Broadcast a document update to all connected peers.
+    ///
+    /// `version` identifies the update in acks and must increase with each
+    /// update of a document. Known peers that are disconnected get it
+    /// queued; under [`OverflowPolicy::Error`], if one of their queues is
+    /// full, nothing is sent and this fails with [`SdkError::QueueFull`].
     pub async fn broadcast_update(
-        &mut self,
+        &self,
         document_id: &str,
         delta: Vec<u8>,
         version: u64,
     ) -> Result<(), SdkError> {
+        let peers = self.peers().await;
+        self.deliver_queued(&peers).await;
+        let offline = self.offline_peers(&peers);
+        self.queue_update(&offline, document_id, &delta, version)?;
+
+        if !peers.is_empty() {
+            let mut outbox = self.outbox.write();
+            let doc = outbox.entry(document_id.to_string()).or_default();
+            doc.log.insert(version, delta.clone());
+            doc.sent_at.insert(version, self.clock.now_millis());
+            let mut stats = self.stats.write();
+            for peer in &peers {
+                if doc
+                    .unacked
+                    .entry(peer.id.clone())
+                    .or_default()
+                    .insert(version)
+                {
+                    let peer_stats = stats.entry(peer.id.clone()).or_default();
+                    peer_stats.on_send(delta.len());
+                    self.publish(peer_stats.check_lag(&peer.id, self.config.lag_threshold));
+                }
+            }
+        }
+
         let message = Message::Update {
             document_id: document_id.to_string(),
             delta,
             version,
         };
 
-        self.transport
-            .broadcast(message)
-            .await
-            .map_err(|e| SdkError::SyncError(e.to_string()))
+        let sent = match &self.scope {
+            Some((session_id, _)) => {
+                let message = Message::for_session(session_id.clone(), message);
+                let sends = peers
+                    .iter()
+                    .map(|peer| self.transport.send(&peer.id, message.clone()));
+                futures::future::try_join_all(sends).await.map(drop)
+            }
+            None => self.transport.broadcast(message).await,
+        };
+        sent.map_err(|e| SdkError::SyncError(e.to_string()))
+    }
+
+    /// The connected peers this manager syncs with: the session's members
+    /// on a shared transport, every peer otherwise.
+    async fn peers(&self) -> Vec<Peer> {
+        let mut peers = self.transport.connected_peers().await;
+        if let Some((_, members)) = &self.scope {
+            peers.retain(|peer| members.contains(&peer.id));
+        }
+        peers
+    }
+
+    /// Address a message to the session synced, if the transport is shared.
+    fn address(&self, message: Message) -> Message {
+        address(self.scope.as_ref().map(|(id, _)| id.as_str()), message)
+    }
+
+    /// Deliver the updates queued for peers that are connected again.
+    ///
+    /// [`broadcast_update`](Self::broadcast_update) does this too; call
+    /// this to catch peers up without a new update. Returns the reconnected
+    /// peers whose queue was dropped under
+    /// [`OverflowPolicy::DropAndResyncFull`], also reported as
+    /// [`SyncEvent::FullSyncNeeded`]. They need the full state of every
+    /// document, e.g. as [`Message::Snapshot`]s, and are only returned
+    /// once.
+    pub async fn reconnect_peers(&self) -> Vec<PeerId> {
+        let peers = self.peers().await;
+        self.offline_peers(&peers);
+        self.deliver_queued(&peers).await;
+
+        let mut resync = Vec::new();
+        {
+            let mut offline = self.offline.write();
+            let mut stats = self.stats.write();
+            for peer in &peers {
+                if offline.get(&peer.id).is_some_and(|q| q.needs_full_sync) {
+                    offline.remove(&peer.id);
+                    stats.entry(peer.id.clone()).or_default().needs_full_sync = false;
+                    resync.push(peer.id.clone());
+                }
+            }
+        }
+        for peer_id in &resync {
+            self.publish(Some(SyncEvent::FullSyncNeeded(peer_id.clone())));
+        }
+        resync
+    }
+
+    /// Stop queueing updates for a peer that left for good, dropping those
+    /// queued for it and the logged deltas only it had yet to acknowledge.
+    pub fn forget_peer(&self, peer_id: &PeerId) {
+        self.known_peers.write().remove(peer_id);
+        for doc in self.outbox.write().values_mut() {
+            doc.unacked.remove(peer_id);
+            doc.resent.remove(peer_id);
+            doc.gc();
+        }
+        if let Some(queue) = self.offline.write().remove(peer_id) {
+            if let Some(stats) = self.stats.write().get_mut(peer_id) {
+                stats.on_queue(&OfflineQueue {
+                    needs_full_sync: false,
+                    ..queue
+                });
+            }
+        }
+    }
+
+    /// Note `peers` as known and return the known peers not among them.
+    fn offline_peers(&self, peers: &[Peer]) -> Vec<PeerId> {
+        let mut known = self.known_peers.write();
+        known.extend(peers.iter().map(|peer| peer.id.clone()));
+        known
+            .iter()
+            .filter(|id| !peers.iter().any(|peer| peer.id == **id))
+            .cloned()
+            .collect()
+    }
+
+    /// Queue an update for disconnected peers.
+    fn queue_update(
+        &self,
+        peers: &[PeerId],
+        document_id: &str,
+        delta: &[u8],
+        version: u64,
+    ) -> Result<(), SdkError> {
+        if peers.is_empty() {
+            return Ok(());
+        }
+        let mut offline = self.offline.write();
+        if self.config.overflow_policy == OverflowPolicy::Error {
+            for peer_id in peers {
+                let queue = offline.entry(peer_id.clone()).or_default();
+                if !queue.fits(1, delta.len(), &self.config) {
+                    return Err(SdkError::QueueFull(format!(
+                        "{} has {} updates ({} bytes) queued",
+                        peer_id,
+                        queue.updates.len(),
+                        queue.bytes
+                    )));
+                }
+            }
+        }
+
+        let mut stats = self.stats.write();
+        for peer_id in peers {
+            let queue = offline.entry(peer_id.clone()).or_default();
+            let update = QueuedUpdate {
+                document_id: document_id.to_string(),
+                version,
+                delta: delta.to_vec(),
+            };
+            queue.push(update, &self.config, self.joiner.as_ref());
+            stats.entry(peer_id.clone()).or_default().on_queue(queue);
+        }
+        Ok(())
+    }
+
+    /// Send what is queued for peers in `peers` as ordinary unacknowledged
+    /// updates, retransmitted by flushes like any other.
+    async fn deliver_queued(&self, peers: &[Peer]) {
+        let mut delivered = Vec::new();
+        {
+            let mut offline = self.offline.write();
+            for peer in peers {
+                if let Some(queue) = offline.get_mut(&peer.id) {
+                    if !queue.updates.is_empty() {
+                        delivered.push((peer.id.clone(), std::mem::take(&mut queue.updates)));
+                        queue.bytes = 0;
+                    }
+                    if !queue.needs_full_sync {
+                        offline.remove(&peer.id);
+                    }
+                }
+            }
+        }
+
+        for (peer_id, updates) in delivered {
+            {
+                let now = self.clock.now_millis();
+                let mut outbox = self.outbox.write();
+                let mut stats = self.stats.write();
+                let peer_stats = stats.entry(peer_id.clone()).or_default();
+                for update in &updates {
+                    let doc = outbox.entry(update.document_id.clone()).or_default();
+                    // A coalesced delta covers the logged one of its version
+                    doc.log.insert(update.version, update.delta.clone());
+                    doc.sent_at.entry(update.version).or_insert(now);
+                    // Queued for a while, so the ack tells nothing of the round trip
+                    doc.resent
+                        .entry(peer_id.clone())
+                        .or_default()
+                        .insert(update.version);
+                    if doc
+                        .unacked
+                        .entry(peer_id.clone())
+                        .or_default()
+                        .insert(update.version)
+                    {
+                        peer_stats.on_send(update.delta.len());
+                    }
+                }
+                peer_stats.queued_updates = 0;
+                peer_stats.queued_bytes = 0;
+                self.publish(peer_stats.check_lag(&peer_id, self.config.lag_threshold));
+            }
+            for update in updates {
+                let message = self.address(Message::Update {
+                    document_id: update.document_id,
+                    delta: update.delta,
+                    version: update.version,
+                });
+                let _ = self.transport.send(&peer_id, message).await;
+            }
+        }
     }
 
     /// Send a sync request to a specific peer.
@@ -159,10 +822,10 @@ impl<T: NetworkTransport> SyncManager<T> {
         document_id: &str,
         version: u64,
     ) -> Result<(), SdkError> {
-        let message = Message::SyncRequest {
+        let message = self.address(Message::SyncRequest {
             document_id: document_id.to_string(),
             version,
-        };
+        });
 
         self.transport
             .send(peer_id, message)
@@ -170,13 +833,258 @@ impl<T: NetworkTransport> SyncManager<T> {
             .map_err(|e| SdkError::SyncError(e.to_string()))
     }
 
+    /// Handle an incoming sync message.
+    ///
+    /// Updates are acknowledged to the sender; the caller still applies the
+    /// delta. Acks are recorded and wake pending flushes. On a shared
+    /// transport, messages addressed to other sessions are ignored.
+    pub async fn handle_message(
+        &self,
+        from: &PeerId,
+        message: &Message,
+    ) -> Result<Option<SyncEvent>, SdkError> {
+        let message = match message {
+            Message::Session {
+                session_id,
+                message,
+            } if self.scope.as_ref().is_some_and(|(id, _)| id == session_id) => message,
+            Message::Session { .. } => return Ok(None),
+            message => message,
+        };
+        match message {
+            Message::Update {
+                document_id,
+                version,
+                ..
+            } => {
+                let ack = self.address(Message::DeltaAck {
+                    document_id: document_id.clone(),
+                    version: *version,
+                });
+                self.transport
+                    .send(from, ack)
+                    .await
+                    .map_err(|e| SdkError::SyncError(e.to_string()))?;
+                Ok(Some(SyncEvent::ReceivedUpdate {
+                    peer_id: from.clone(),
+                    document_id: document_id.clone(),
+                }))
+            }
+            Message::DeltaAck {
+                document_id,
+                version,
+            } => Ok(self.handle_ack(from, document_id, *version)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Receive and handle the next message from a transport's inbox.
+    ///
+    /// Channels are read by priority (control, then document, then
+    /// presence), so acks and deltas are never stuck behind a burst of
+    /// presence updates. The message is returned with the event it produced
+    /// so the caller can still apply deltas and presence. Returns `None`
+    /// once the inbox is closed.
+    pub async fn recv(&self, inbox: &mut Inbox) -> Option<(PeerId, Message, Option<SyncEvent>)> {
+        let (from, message) = inbox.recv().await?;
+        let event = self
+            .handle_message(&from, &message)
+            .await
+            .unwrap_or_else(|e| {
+                Some(SyncEvent::SyncError {
+                    peer_id: from.clone(),
+                    error: e.to_string(),
+                })
+            });
+        Some((from, message, event))
+    }
+
+    /// Record a peer's acknowledgment of a document version.
+    ///
+    /// Returns `None` for duplicate or unknown acks.
+    pub fn handle_ack(
+        &self,
+        peer_id: &PeerId,
+        document_id: &str,
+        version: u64,
+    ) -> Option<SyncEvent> {
+        {
+            let mut outbox = self.outbox.write();
+            let doc = outbox.get_mut(document_id)?;
+            let pending = doc.unacked.get_mut(peer_id)?;
+            if !pending.remove(&version) {
+                return None;
+            }
+            if pending.is_empty() {
+                doc.unacked.remove(peer_id);
+            }
+
+            let now = self.clock.now_millis();
+            let resent = doc
+                .resent
+                .get_mut(peer_id)
+                .is_some_and(|resent| resent.remove(&version));
+            let rtt = match doc.sent_at.get(&version) {
+                Some(sent) if !resent => Some(now.saturating_sub(*sent)),
+                _ => None,
+            };
+            let bytes = doc.log.get(&version).map_or(0, Vec::len);
+            let mut stats = self.stats.write();
+            let peer_stats = stats.entry(peer_id.clone()).or_default();
+            peer_stats.on_ack(bytes, now, rtt);
+            self.publish(peer_stats.check_lag(peer_id, self.config.lag_threshold));
+            doc.gc();
+        }
+        self.acks.send_modify(|n| *n += 1);
+
+        Some(SyncEvent::UpdateAcked {
+            peer_id: peer_id.clone(),
+            document_id: document_id.to_string(),
+            version,
+        })
+    }
+
+    /// How far behind a peer is; all zero for a peer never synced with.
+    ///
+    /// Reading the statistics doesn't wait for sends, acks or flushes in
+    /// progress.
+    pub fn peer_stats(&self, peer_id: &PeerId) -> PeerSyncStats {
+        self.stats.read().get(peer_id).cloned().unwrap_or_default()
+    }
+
+    /// Send a lag change to subscribers, if there is one.
+    fn publish(&self, event: Option<SyncEvent>) {
+        if let Some(event) = event {
+            let _ = self.event_tx.send(event);
+        }
+    }
+
+    /// Versions of a document a peer has not acknowledged yet.
+    pub fn unacked_versions(&self, peer_id: &PeerId, document_id: &str) -> Vec<u64> {
+        pending_versions(&self.outbox, document_id, peer_id, u64::MAX)
+    }
+
+    /// Every update some peer has not acknowledged yet, sorted by document
+    /// and peer.
+    pub fn unacked(&self) -> Vec<UnackedUpdates> {
+        let mut unacked: Vec<_> = self
+            .outbox
+            .read()
+            .iter()
+            .flat_map(|(document_id, doc)| {
+                doc.unacked
+                    .iter()
+                    .map(|(peer_id, versions)| UnackedUpdates {
+                        peer_id: peer_id.clone(),
+                        document_id: document_id.clone(),
+                        versions: versions.iter().copied().collect(),
+                    })
+            })
+            .collect();
+        unacked.sort_by(|a, b| (&a.document_id, &a.peer_id.0).cmp(&(&b.document_id, &b.peer_id.0)));
+        unacked
+    }
+
+    /// Wait until a peer has acknowledged every update of a document sent
+    /// so far.
+    ///
+    /// Updates sent after this call are not waited for. Missing updates are
+    /// retransmitted every `sync_interval_ms`; if the peer has not caught up
+    /// after `sync_timeout_ms` (for example because it disconnected) the
+    /// future resolves to [`SdkError::Timeout`].
+    pub fn flush_to(
+        &self,
+        peer_id: &PeerId,
+        document_id: &str,
+    ) -> impl Future<Output = Result<(), SdkError>> + Send + 'static {
+        let transport = self.transport.clone();
+        let session_id = self.scope.as_ref().map(|(id, _)| id.clone());
+        let outbox = self.outbox.clone();
+        let stats = self.stats.clone();
+        let mut acks = self.acks.subscribe();
+        let retry = Duration::from_millis(self.config.sync_interval_ms);
+        let deadline =
+            tokio::time::Instant::now() + Duration::from_millis(self.config.sync_timeout_ms);
+        let target = pending_versions(&outbox, document_id, peer_id, u64::MAX)
+            .last()
+            .copied();
+        let peer_id = peer_id.clone();
+        let document_id = document_id.to_string();
+
+        async move {
+            let Some(target) = target else {
+                return Ok(());
+            };
+            loop {
+                let pending = pending_versions(&outbox, &document_id, &peer_id, target);
+                if pending.is_empty() {
+                    return Ok(());
+                }
+                let now = tokio::time::Instant::now();
+                if now >= deadline {
+                    return Err(SdkError::Timeout(format!(
+                        "{} did not acknowledge {} up to version {}",
+                        peer_id, document_id, target
+                    )));
+                }
+
+                match tokio::time::timeout((deadline - now).min(retry), acks.changed()).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(_)) => {
+                        return Err(SdkError::Internal("sync manager dropped".to_string()))
+                    }
+                    Err(_) => {
+                        // No ack within a retry interval: resend what is missing
+                        let deltas: Vec<_> = {
+                            let mut outbox = outbox.write();
+                            let Some(doc) = outbox.get_mut(&document_id) else {
+                                continue;
+                            };
+                            doc.resent
+                                .entry(peer_id.clone())
+                                .or_default()
+                                .extend(&pending);
+                            pending
+                                .iter()
+                                .filter_map(|v| doc.log.get(v).map(|d| (*v, d.clone())))
+                                .collect()
+                        };
+                        stats
+                            .write()
+                            .entry(peer_id.clone())
+                            .or_default()
+                            .retransmissions += deltas.len() as u64;
+                        for (version, delta) in deltas {
+                            let message = Message::Update {
+                                document_id: document_id.clone(),
+                                delta,
+                                version,
+                            };
+                            let message = address(session_id.as_deref(), message);
+                            let _ = transport.send(&peer_id, message).await;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Wait until every connected peer has acknowledged every update of a
+    /// document sent so far. See [`flush_to`](Self::flush_to).
+    pub async fn flush_all(&self, document_id: &str) -> Result<(), SdkError> {
+        let peers = self.peers().await;
+        let flushes = peers.iter().map(|p| self.flush_to(&p.id, document_id));
+        futures::future::try_join_all(flushes).await?;
+        Ok(())
+    }
+
     /// Update sync state for a peer.
     pub fn update_peer_state(&mut self, peer_id: &PeerId, document_id: &str, version: u64) {
         let state = self.peer_states.entry(peer_id.clone()).or_default();
         state
             .document_versions
             .insert(document_id.to_string(), version);
-        state.last_sync = Some(Instant::now());
+        state.last_sync = Some(self.clock.now_millis());
     }
 
     /// Get sync state for a peer.
@@ -185,10 +1093,35 @@ impl<T: NetworkTransport> SyncManager<T> {
     }
 }
 
+/// Versions up to `max` of a document that a peer has not acknowledged.
+/// Address a message to a session, if there is one.
+fn address(session_id: Option<&str>, message: Message) -> Message {
+    match session_id {
+        Some(session_id) => Message::for_session(session_id, message),
+        None => message,
+    }
+}
+
+fn pending_versions(
+    outbox: &SharedOutbox,
+    document_id: &str,
+    peer_id: &PeerId,
+    max: u64,
+) -> Vec<u64> {
+    outbox
+        .read()
+        .get(document_id)
+        .and_then(|doc| doc.unacked.get(peer_id))
+        .map(|versions| versions.range(..=max).copied().collect())
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::network::MemoryTransport;
+    use crate::network::{Channel, MemoryTransport};
+    use mdcs_core::lattice::Lattice;
+    use mdcs_core::pncounter::PNCounter;
 
     #[test]
     fn test_sync_config_builder() {
@@ -198,7 +1131,8 @@ mod tests {
             .sync_timeout(3000)
             .max_batch_size(50)
             .auto_sync(false)
-            .build();
+            .build()
+            .unwrap();
 
         assert_eq!(config.sync_interval_ms, 500);
         assert_eq!(config.presence_interval_ms, 250);
@@ -215,4 +1149,430 @@ mod tests {
 
         assert!(manager.config().auto_sync);
     }
+
+    #[test]
+    fn test_sync_config_validation() {
+        SyncConfig::default().validate().unwrap();
+
+        let build = |builder: SyncConfigBuilder| builder.build().unwrap_err();
+        assert_eq!(
+            build(SyncConfigBuilder::new().sync_interval(0)),
+            ConfigError::OutOfRange {
+                field: "sync_interval_ms",
+                value: 0,
+                min: MIN_SYNC_INTERVAL_MS,
+                max: MAX_SYNC_INTERVAL_MS,
+            }
+        );
+        assert!(matches!(
+            build(SyncConfigBuilder::new().sync_interval(120_000)),
+            ConfigError::OutOfRange {
+                field: "sync_interval_ms",
+                ..
+            }
+        ));
+        assert!(matches!(
+            build(SyncConfigBuilder::new().presence_interval(1)),
+            ConfigError::OutOfRange {
+                field: "presence_interval_ms",
+                ..
+            }
+        ));
+        assert!(matches!(
+            build(SyncConfigBuilder::new().sync_timeout(0)),
+            ConfigError::OutOfRange {
+                field: "sync_timeout_ms",
+                ..
+            }
+        ));
+        assert!(matches!(
+            build(SyncConfigBuilder::new().max_batch_size(0)),
+            ConfigError::OutOfRange {
+                field: "max_batch_size",
+                ..
+            }
+        ));
+        assert!(matches!(
+            build(SyncConfigBuilder::new().offline_queue_max_updates(Some(0))),
+            ConfigError::OutOfRange {
+                field: "offline_queue_max_updates",
+                ..
+            }
+        ));
+        SyncConfigBuilder::new()
+            .offline_queue_max_updates(None)
+            .offline_queue_max_bytes(None)
+            .overflow_policy(OverflowPolicy::Error)
+            .build()
+            .unwrap();
+
+        let error = build(
+            SyncConfigBuilder::new()
+                .sync_interval(2000)
+                .sync_timeout(2000),
+        );
+        assert_eq!(
+            error,
+            ConfigError::NotBelow {
+                field: "sync_interval_ms",
+                value: 2000,
+                limit_field: "sync_timeout_ms",
+                limit: 2000,
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "sync_interval_ms (2000) must be less than sync_timeout_ms (2000)"
+        );
+
+        // Configs from files get the same checks
+        let config: SyncConfig = serde_json::from_str(r#"{"max_batch_size": 0}"#).unwrap();
+        assert_eq!(config.sync_interval_ms, 1000);
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "max_batch_size is 0, expected 1 to 10000"
+        );
+    }
+
+    fn flush_config() -> SyncConfig {
+        SyncConfigBuilder::new()
+            .sync_interval(10)
+            .sync_timeout(300)
+            .build()
+            .unwrap()
+    }
+
+    /// A manager with its transport and inbox.
+    type Node = (
+        Arc<SyncManager<MemoryTransport>>,
+        Arc<MemoryTransport>,
+        Inbox,
+    );
+
+    /// Two connected managers, "a" and "b".
+    fn manager_pair() -> (Node, Node) {
+        manager_pair_with(flush_config(), SharedClock::default())
+    }
+
+    /// Two connected managers with `config`, "a" timing with `clock`.
+    fn manager_pair_with(config: SyncConfig, clock: SharedClock) -> (Node, Node) {
+        let a = Arc::new(MemoryTransport::new(PeerId::new("a")));
+        let b = Arc::new(MemoryTransport::new(PeerId::new("b")));
+        a.connect_to(&b);
+        let (a_rx, b_rx) = (a.subscribe(), b.subscribe());
+        let a_mgr = Arc::new(SyncManager::new(a.clone(), config.clone()).with_clock(clock));
+        let b_mgr = Arc::new(SyncManager::new(b.clone(), config));
+        ((a_mgr, a, a_rx), (b_mgr, b, b_rx))
+    }
+
+    /// Feed incoming messages to a manager, skipping those `drop` rejects.
+    fn pump(manager: Arc<SyncManager<MemoryTransport>>, mut rx: Inbox, drop: fn(&Message) -> bool) {
+        tokio::spawn(async move {
+            while let Some((from, message)) = rx.recv().await {
+                if !drop(&message) {
+                    let _ = manager.handle_message(&from, &message).await;
+                }
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn test_flush_to_waits_for_retransmission() {
+        let ((a_mgr, a, a_rx), (b_mgr, _b, b_rx)) = manager_pair();
+        let b_id = PeerId::new("b");
+
+        // The first broadcast is lost on the wire
+        a.drop_next(1);
+        a_mgr.broadcast_update("doc", vec![1], 1).await.unwrap();
+        assert_eq!(a_mgr.unacked_versions(&b_id, "doc"), vec![1]);
+
+        pump(a_mgr.clone(), a_rx, |_| false);
+        pump(b_mgr, b_rx, |_| false);
+
+        a_mgr.flush_to(&b_id, "doc").await.unwrap();
+        assert!(a_mgr.unacked_versions(&b_id, "doc").is_empty());
+        a_mgr.flush_all("doc").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_flush_to_disconnected_peer_times_out() {
+        let ((a_mgr, a, a_rx), _b) = manager_pair();
+        let b_id = PeerId::new("b");
+        pump(a_mgr.clone(), a_rx, |_| false);
+
+        a_mgr.broadcast_update("doc", vec![1], 1).await.unwrap();
+        a.disconnect(&b_id).await.unwrap();
+
+        let result = a_mgr.flush_to(&b_id, "doc").await;
+        assert!(matches!(result, Err(SdkError::Timeout(_))));
+    }
+
+    #[tokio::test]
+    async fn test_forget_peer_empties_the_log() {
+        let ((a_mgr, _a, _a_rx), _b) = manager_pair();
+        let b_id = PeerId::new("b");
+
+        // b never acknowledges, then leaves
+        a_mgr.broadcast_update("doc", vec![1], 1).await.unwrap();
+        a_mgr.broadcast_update("doc", vec![2], 2).await.unwrap();
+        assert_eq!(a_mgr.unacked_versions(&b_id, "doc"), vec![1, 2]);
+        assert_eq!(a_mgr.outbox.read()["doc"].log.len(), 2);
+
+        a_mgr.forget_peer(&b_id);
+        assert!(a_mgr.unacked().is_empty());
+        let outbox = a_mgr.outbox.read();
+        assert!(outbox["doc"].log.is_empty());
+        assert!(outbox["doc"].sent_at.is_empty());
+        assert!(outbox["doc"].resent.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_flush_barrier_is_per_document() {
+        let ((a_mgr, _a, a_rx), (b_mgr, _b, b_rx)) = manager_pair();
+        let b_id = PeerId::new("b");
+
+        // b never acknowledges updates of "slow"
+        pump(a_mgr.clone(), a_rx, |_| false);
+        pump(
+            b_mgr,
+            b_rx,
+            |m| matches!(m, Message::Update { document_id, .. } if document_id == "slow"),
+        );
+
+        a_mgr.broadcast_update("slow", vec![0], 1).await.unwrap();
+        for version in 1..=3 {
+            a_mgr
+                .broadcast_update("fast", vec![version as u8], version)
+                .await
+                .unwrap();
+        }
+
+        a_mgr.flush_to(&b_id, "fast").await.unwrap();
+        let slow = a_mgr.flush_to(&b_id, "slow").await;
+        assert!(matches!(slow, Err(SdkError::Timeout(_))));
+        assert_eq!(a_mgr.unacked_versions(&b_id, "slow"), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn test_recv_handles_acks_before_presence() {
+        let ((a_mgr, a, mut a_rx), (b_mgr, b, mut b_rx)) = manager_pair();
+        let b_id = PeerId::new("b");
+
+        a_mgr.broadcast_update("doc", vec![1], 1).await.unwrap();
+
+        // b's cursor updates reach a before b's ack
+        for n in 0..50 {
+            let presence = Message::Presence {
+                user_id: "b".to_string(),
+                document_id: "doc".to_string(),
+                cursor_pos: Some(n),
+            };
+            b.send(a.local_id(), presence).await.unwrap();
+        }
+        let (_, update, event) = b_mgr.recv(&mut b_rx).await.unwrap();
+        assert!(matches!(update, Message::Update { version: 1, .. }));
+        assert!(matches!(event, Some(SyncEvent::ReceivedUpdate { .. })));
+
+        let (from, message, event) = a_mgr.recv(&mut a_rx).await.unwrap();
+        assert_eq!(from, b_id);
+        assert_eq!(message.channel(), Channel::Control);
+        assert!(matches!(
+            event,
+            Some(SyncEvent::UpdateAcked { version: 1, .. })
+        ));
+        assert!(a_mgr.unacked_versions(&b_id, "doc").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stalled_peer_lags_then_catches_up() {
+        let config = SyncConfig {
+            lag_threshold: 2,
+            ..flush_config()
+        };
+        let ((a_mgr, a, a_rx), (b_mgr, _b, b_rx)) =
+            manager_pair_with(config, SharedClock::default());
+        let b_id = PeerId::new("b");
+        let mut events = a_mgr.subscribe();
+        pump(a_mgr.clone(), a_rx, |_| false);
+
+        // Nothing reaches b for a while
+        a.drop_next(3);
+        for version in 1..=3 {
+            a_mgr
+                .broadcast_update("doc", vec![0; 10], version)
+                .await
+                .unwrap();
+        }
+        let stats = a_mgr.peer_stats(&b_id);
+        assert_eq!(stats.outstanding_updates, 3);
+        assert_eq!(stats.outstanding_bytes, 30);
+        assert!(stats.lagging);
+        assert_eq!(stats.last_ack, None);
+
+        pump(b_mgr, b_rx, |_| false);
+        a_mgr.flush_to(&b_id, "doc").await.unwrap();
+
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            SyncEvent::PeerLagging { peer_id, outstanding: 3 } if peer_id == b_id
+        ));
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            SyncEvent::PeerCaughtUp(peer_id) if peer_id == b_id
+        ));
+        assert!(events.try_recv().is_err());
+
+        let stats = a_mgr.peer_stats(&b_id);
+        assert_eq!((stats.outstanding_updates, stats.outstanding_bytes), (0, 0));
+        assert!(!stats.lagging && stats.last_ack.is_some());
+        assert!(stats.retransmissions >= 3);
+        // Acks of retransmitted updates don't tell the round trip
+        assert_eq!(stats.rtt_ms, None);
+    }
+
+    #[tokio::test]
+    async fn test_peer_stats_track_round_trips() {
+        let clock = Arc::new(mdcs_core::clock::ManualClock::new(1_000));
+        let ((a_mgr, _a, _a_rx), _b) = manager_pair_with(flush_config(), clock.clone().into());
+        let b_id = PeerId::new("b");
+        assert_eq!(a_mgr.peer_stats(&b_id), PeerSyncStats::default());
+
+        a_mgr.broadcast_update("doc", vec![1], 1).await.unwrap();
+        a_mgr.broadcast_update("doc", vec![2], 2).await.unwrap();
+        clock.advance(40);
+        a_mgr.handle_ack(&b_id, "doc", 1);
+        assert_eq!(a_mgr.peer_stats(&b_id).rtt_ms, Some(40));
+
+        clock.advance(80);
+        a_mgr.handle_ack(&b_id, "doc", 2);
+        let stats = a_mgr.peer_stats(&b_id);
+        // A 120ms sample moves the estimate an eighth of the way
+        assert_eq!(stats.rtt_ms, Some(50));
+        assert_eq!(stats.last_ack, Some(1_120));
+        assert_eq!(stats.outstanding_updates, 0);
+    }
+
+    /// Join two encoded counter deltas.
+    fn join_counters(_: &str, older: &[u8], newer: &[u8]) -> Result<Vec<u8>, SdkError> {
+        let decode =
+            |bytes| serde_json::from_slice::<PNCounter<String>>(bytes).map_err(SdkError::from);
+        serde_json::to_vec(&decode(older)?.join(&decode(newer)?)).map_err(SdkError::from)
+    }
+
+    #[tokio::test]
+    async fn test_offline_queue_coalesces_while_peer_paused() {
+        let config = SyncConfig {
+            offline_queue_max_updates: Some(8),
+            offline_queue_max_bytes: Some(1024),
+            overflow_policy: OverflowPolicy::CoalesceOldest,
+            ..flush_config()
+        };
+        let a = Arc::new(MemoryTransport::new(PeerId::new("a")));
+        let b = Arc::new(MemoryTransport::new(PeerId::new("b")));
+        a.connect_to(&b);
+        let (a_rx, mut b_rx) = (a.subscribe(), b.subscribe());
+        let a_mgr =
+            Arc::new(SyncManager::new(a.clone(), config.clone()).with_delta_joiner(join_counters));
+        let b_mgr = SyncManager::new(b.clone(), config);
+        let b_id = PeerId::new("b");
+
+        // Each delta carries a's running total
+        let mut total = 0;
+        let mut next_delta = || {
+            total += 1;
+            let mut delta = PNCounter::new();
+            delta.increment("a".to_string(), total);
+            serde_json::to_vec(&delta).unwrap()
+        };
+
+        a_mgr
+            .broadcast_update("counter", next_delta(), 1)
+            .await
+            .unwrap();
+        a.pause(&b_id);
+        for version in 2..=10_001 {
+            a_mgr
+                .broadcast_update("counter", next_delta(), version)
+                .await
+                .unwrap();
+            let stats = a_mgr.peer_stats(&b_id);
+            assert!(stats.queued_updates <= 8 && stats.queued_bytes <= 1024);
+        }
+        assert!(!a_mgr.peer_stats(&b_id).needs_full_sync);
+
+        let b_state = Arc::new(parking_lot::Mutex::new(PNCounter::<String>::new()));
+        let applied = b_state.clone();
+        tokio::spawn(async move {
+            while let Some((from, message)) = b_rx.recv().await {
+                if let Message::Update { delta, .. } = &message {
+                    let delta: PNCounter<String> = serde_json::from_slice(delta).unwrap();
+                    applied.lock().join_assign(&delta);
+                }
+                let _ = b_mgr.handle_message(&from, &message).await;
+            }
+        });
+        pump(a_mgr.clone(), a_rx, |_| false);
+
+        a.resume(&b_id);
+        assert!(a_mgr.reconnect_peers().await.is_empty());
+        a_mgr.flush_to(&b_id, "counter").await.unwrap();
+
+        assert_eq!(b_state.lock().value(), 10_001);
+        let stats = a_mgr.peer_stats(&b_id);
+        assert_eq!((stats.queued_updates, stats.queued_bytes), (0, 0));
+        assert_eq!(stats.outstanding_updates, 0);
+    }
+
+    #[tokio::test]
+    async fn test_offline_queue_overflow_policies() {
+        let config = SyncConfig {
+            offline_queue_max_updates: Some(2),
+            ..flush_config()
+        };
+        let ((a_mgr, a, _a_rx), _b) = manager_pair_with(config.clone(), SharedClock::default());
+        let b_id = PeerId::new("b");
+        let mut events = a_mgr.subscribe();
+
+        // Dropping the queue leaves b to be bootstrapped
+        a_mgr.broadcast_update("doc", vec![0], 1).await.unwrap();
+        a.pause(&b_id);
+        for version in 2..=4 {
+            a_mgr
+                .broadcast_update("doc", vec![0], version)
+                .await
+                .unwrap();
+        }
+        let stats = a_mgr.peer_stats(&b_id);
+        assert!(stats.needs_full_sync);
+        assert_eq!(stats.queued_updates, 0);
+        assert!(a_mgr.reconnect_peers().await.is_empty());
+
+        a.resume(&b_id);
+        assert_eq!(a_mgr.reconnect_peers().await, vec![b_id.clone()]);
+        assert!(a_mgr.reconnect_peers().await.is_empty());
+        assert!(!a_mgr.peer_stats(&b_id).needs_full_sync);
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            SyncEvent::FullSyncNeeded(peer_id) if peer_id == b_id
+        ));
+
+        // Refusing the update leaves the queue as it was
+        let config = SyncConfig {
+            overflow_policy: OverflowPolicy::Error,
+            ..config
+        };
+        let ((a_mgr, a, _a_rx), _b) = manager_pair_with(config, SharedClock::default());
+        a_mgr.broadcast_update("doc", vec![0], 1).await.unwrap();
+        a.pause(&b_id);
+        for version in 2..=3 {
+            a_mgr
+                .broadcast_update("doc", vec![0], version)
+                .await
+                .unwrap();
+        }
+        let result = a_mgr.broadcast_update("doc", vec![0], 4).await;
+        assert!(matches!(result, Err(SdkError::QueueFull(_))));
+        assert_eq!(a_mgr.peer_stats(&b_id).queued_updates, 2);
+        assert!(!a_mgr.peer_stats(&b_id).needs_full_sync);
+    }
 }
@@ -0,0 +1,550 @@
+//! TCP transport between peers.
+//!
+//! Every transport listens on an address and dials peers at theirs, so
+//! clients can sync directly without a relay. A connection carries
+//! [`Frame`]s, each preceded by its length as a big-endian `u32` like
+//! [`mdcs_delta::write_frame`] writes them; the first frame each end sends
+//! is its [`PeerId`].
+//!
+//! A peer dialed with [`connect`](NetworkTransport::connect) is dialed
+//! again, with exponential backoff, whenever its connection drops, and
+//! [`connected_peers`](NetworkTransport::connected_peers) shows it as
+//! [`PeerState::Connecting`] meanwhile. Once redialing gives up it stays
+//! [`PeerState::Disconnected`] until connected again. Peers that dialed in
+//! are dropped from the list when their connection closes; redialing is up
+//! to them.
+//!
+//! Enabled with the `tokio-net` feature.
+
+use crate::network::{
+    inbox, ChannelCapacity, Frame, Inbox, InboxSender, Message, NetworkError, NetworkTransport,
+    Peer, PeerId, PeerState,
+};
+use async_trait::async_trait;
+use mdcs_delta::wire::MAX_FRAME_LEN;
+use parking_lot::{Mutex, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::mpsc;
+use tokio::task::AbortHandle;
+
+/// Frames queued per connection before senders wait.
+const OUTGOING_CAPACITY: usize = 256;
+
+/// How long dialing, or exchanging peer IDs on a new connection, may take.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Settings of a [`TcpTransport`].
+#[derive(Clone, Debug)]
+pub struct TcpConfig {
+    /// Sizes of the inbox queues.
+    pub capacity: ChannelCapacity,
+    /// Delay before the first redial of a dropped peer (in milliseconds).
+    /// Doubles with every failed attempt.
+    pub min_backoff_ms: u64,
+    /// Longest delay between redials (in milliseconds).
+    pub max_backoff_ms: u64,
+    /// Failed redials in a row before a dropped peer is given up on.
+    pub max_reconnect_attempts: u32,
+}
+
+impl Default for TcpConfig {
+    fn default() -> Self {
+        Self {
+            capacity: ChannelCapacity::default(),
+            min_backoff_ms: 100,
+            max_backoff_ms: 5000,
+            max_reconnect_attempts: 5,
+        }
+    }
+}
+
+impl TcpConfig {
+    /// Delay before redial number `attempt`, counting from zero.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let ms = self.min_backoff_ms.saturating_mul(1 << attempt.min(32));
+        Duration::from_millis(ms.min(self.max_backoff_ms))
+    }
+}
+
+/// Transport that connects to peers directly over TCP.
+pub struct TcpTransport {
+    shared: Arc<Shared>,
+    local_addr: SocketAddr,
+    message_rx: RwLock<Option<Inbox>>,
+}
+
+impl TcpTransport {
+    /// Listen for peers on `addr`, e.g. `127.0.0.1:0` for any free port.
+    pub async fn bind(addr: impl ToSocketAddrs, local_id: PeerId) -> Result<Self, NetworkError> {
+        Self::bind_with_config(addr, local_id, TcpConfig::default()).await
+    }
+
+    /// Listen with custom settings.
+    pub async fn bind_with_config(
+        addr: impl ToSocketAddrs,
+        local_id: PeerId,
+        config: TcpConfig,
+    ) -> Result<Self, NetworkError> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| NetworkError::ConnectionFailed(e.to_string()))?;
+        let local_addr = listener
+            .local_addr()
+            .map_err(|e| NetworkError::ConnectionFailed(e.to_string()))?;
+
+        let (message_tx, message_rx) = inbox(config.capacity);
+        let shared = Arc::new(Shared {
+            local_id,
+            config,
+            inbox: message_tx,
+            peers: RwLock::new(HashMap::new()),
+            links: RwLock::new(HashMap::new()),
+            addrs: RwLock::new(HashMap::new()),
+            dialed: RwLock::new(HashSet::new()),
+            next_link: AtomicU64::new(0),
+            tasks: Mutex::new(Vec::new()),
+        });
+        shared.spawn(shared.clone().accept(listener));
+
+        Ok(Self {
+            shared,
+            local_addr,
+            message_rx: RwLock::new(Some(message_rx)),
+        })
+    }
+
+    pub fn local_id(&self) -> &PeerId {
+        &self.shared.local_id
+    }
+
+    /// The address peers dial to reach this transport.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Record the address a peer listens on, for
+    /// [`connect`](NetworkTransport::connect).
+    pub fn add_peer_addr(&self, peer_id: PeerId, addr: SocketAddr) {
+        self.shared.addrs.write().insert(peer_id, addr);
+    }
+
+    /// Current state of a peer, or `None` for peers never connected.
+    pub fn peer_state(&self, peer_id: &PeerId) -> Option<PeerState> {
+        self.shared
+            .peers
+            .read()
+            .get(peer_id)
+            .map(|p| p.state.clone())
+    }
+}
+
+impl Drop for TcpTransport {
+    fn drop(&mut self) {
+        for task in self.shared.tasks.lock().drain(..) {
+            task.abort();
+        }
+    }
+}
+
+#[async_trait]
+impl NetworkTransport for TcpTransport {
+    /// Dial a peer at the address given to
+    /// [`add_peer_addr`](TcpTransport::add_peer_addr), and keep redialing
+    /// it whenever the connection drops. Succeeds at once for peers already
+    /// connected, including those that dialed in.
+    async fn connect(&self, peer_id: &PeerId) -> Result<(), NetworkError> {
+        if self.shared.links.read().contains_key(peer_id) {
+            return Ok(());
+        }
+        self.shared.dialed.write().insert(peer_id.clone());
+        self.shared.set_state(peer_id, PeerState::Connecting);
+
+        match self.shared.dial(peer_id).await {
+            Ok(stream) => {
+                let connection = self.shared.attach(peer_id, stream);
+                self.shared
+                    .spawn(self.shared.clone().supervise(peer_id.clone(), connection));
+                Ok(())
+            }
+            Err(e) => {
+                self.shared.dialed.write().remove(peer_id);
+                self.shared.peers.write().remove(peer_id);
+                Err(e)
+            }
+        }
+    }
+
+    /// Close the connection to a peer and stop redialing it.
+    async fn disconnect(&self, peer_id: &PeerId) -> Result<(), NetworkError> {
+        self.shared.dialed.write().remove(peer_id);
+        self.shared.peers.write().remove(peer_id);
+        // Dropping the link's sender ends the connection
+        self.shared.links.write().remove(peer_id);
+        Ok(())
+    }
+
+    /// Fails with [`NetworkError::Disconnected`] while a known peer is
+    /// being redialed.
+    async fn send(&self, peer_id: &PeerId, message: Message) -> Result<(), NetworkError> {
+        let outgoing = self
+            .shared
+            .links
+            .read()
+            .get(peer_id)
+            .map(|link| link.outgoing.clone());
+        let Some(outgoing) = outgoing else {
+            return if self.shared.peers.read().contains_key(peer_id) {
+                Err(NetworkError::Disconnected)
+            } else {
+                Err(NetworkError::PeerNotFound(peer_id.to_string()))
+            };
+        };
+        let frame = Frame {
+            from: self.shared.local_id.clone(),
+            to: Some(peer_id.clone()),
+            message,
+        };
+        outgoing
+            .send(frame)
+            .await
+            .map_err(|_| NetworkError::Disconnected)
+    }
+
+    async fn broadcast(&self, message: Message) -> Result<(), NetworkError> {
+        let senders: Vec<_> = {
+            let links = self.shared.links.read();
+            links.values().map(|link| link.outgoing.clone()).collect()
+        };
+
+        for outgoing in senders {
+            let frame = Frame {
+                from: self.shared.local_id.clone(),
+                to: None,
+                message: message.clone(),
+            };
+            let _ = outgoing.send(frame).await;
+        }
+        Ok(())
+    }
+
+    async fn connected_peers(&self) -> Vec<Peer> {
+        self.shared.peers.read().values().cloned().collect()
+    }
+
+    fn subscribe(&self) -> Inbox {
+        self.message_rx
+            .write()
+            .take()
+            .expect("subscribe can only be called once")
+    }
+}
+
+/// The open connection to a peer, as seen by senders.
+struct Link {
+    /// Tells this connection apart from later ones to the same peer.
+    id: u64,
+    outgoing: mpsc::Sender<Frame>,
+}
+
+/// A connection registered as a peer's link, waiting to be carried.
+struct Connection {
+    id: u64,
+    stream: TcpStream,
+    outgoing: mpsc::Receiver<Frame>,
+}
+
+/// State shared by a transport and its background tasks.
+struct Shared {
+    local_id: PeerId,
+    config: TcpConfig,
+    inbox: InboxSender,
+    peers: RwLock<HashMap<PeerId, Peer>>,
+    links: RwLock<HashMap<PeerId, Link>>,
+    addrs: RwLock<HashMap<PeerId, SocketAddr>>,
+    /// Peers connected to with `connect` and not disconnected since; only
+    /// these are redialed.
+    dialed: RwLock<HashSet<PeerId>>,
+    next_link: AtomicU64,
+    tasks: Mutex<Vec<AbortHandle>>,
+}
+
+impl Shared {
+    /// Run a task until the transport is dropped.
+    fn spawn(&self, task: impl Future<Output = ()> + Send + 'static) {
+        let handle = tokio::spawn(task).abort_handle();
+        let mut tasks = self.tasks.lock();
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(handle);
+    }
+
+    fn set_state(&self, peer_id: &PeerId, state: PeerState) {
+        self.peers.write().insert(
+            peer_id.clone(),
+            Peer {
+                id: peer_id.clone(),
+                name: peer_id.0.clone(),
+                state,
+            },
+        );
+    }
+
+    fn is_dialed(&self, peer_id: &PeerId) -> bool {
+        self.dialed.read().contains(peer_id)
+    }
+
+    /// Accept peers until the transport is dropped.
+    async fn accept(self: Arc<Self>, listener: TcpListener) {
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                // Such as running out of file descriptors; wait for some to free up
+                tokio::time::sleep(Duration::from_millis(self.config.min_backoff_ms)).await;
+                continue;
+            };
+            let shared = self.clone();
+            self.spawn(async move {
+                let handshake = handshake(&mut stream, &shared.local_id);
+                let Ok(Ok(peer_id)) = tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake).await
+                else {
+                    return;
+                };
+                if peer_id != shared.local_id {
+                    let connection = shared.attach(&peer_id, stream);
+                    shared.carry(&peer_id, connection).await;
+                }
+            });
+        }
+    }
+
+    /// Open a connection to a dialed peer's address.
+    async fn dial(&self, peer_id: &PeerId) -> Result<TcpStream, NetworkError> {
+        let addr = self
+            .addrs
+            .read()
+            .get(peer_id)
+            .copied()
+            .ok_or_else(|| NetworkError::PeerNotFound(peer_id.to_string()))?;
+        let dial = async {
+            let mut stream = TcpStream::connect(addr).await?;
+            let remote = handshake(&mut stream, &self.local_id).await?;
+            Ok::<_, io::Error>((stream, remote))
+        };
+        let (stream, remote) = tokio::time::timeout(HANDSHAKE_TIMEOUT, dial)
+            .await
+            .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into()))
+            .map_err(|e| NetworkError::ConnectionFailed(format!("{}: {}", addr, e)))?;
+
+        if &remote != peer_id {
+            return Err(NetworkError::ConnectionFailed(format!(
+                "{} is {}, not {}",
+                addr, remote, peer_id
+            )));
+        }
+        Ok(stream)
+    }
+
+    /// Make a new connection the link to a peer, replacing any older one.
+    fn attach(&self, peer_id: &PeerId, stream: TcpStream) -> Connection {
+        let (outgoing, outgoing_rx) = mpsc::channel(OUTGOING_CAPACITY);
+        let id = self.next_link.fetch_add(1, Ordering::Relaxed);
+        self.links
+            .write()
+            .insert(peer_id.clone(), Link { id, outgoing });
+        self.set_state(peer_id, PeerState::Connected);
+        Connection {
+            id,
+            stream,
+            outgoing: outgoing_rx,
+        }
+    }
+
+    /// Carry frames both ways until the connection closes or is no longer
+    /// the peer's link.
+    async fn carry(&self, peer_id: &PeerId, connection: Connection) {
+        let Connection {
+            id,
+            stream,
+            mut outgoing,
+        } = connection;
+        let (mut reader, mut writer) = stream.into_split();
+
+        let write = async {
+            while let Some(frame) = outgoing.recv().await {
+                if write_frame(&mut writer, &frame.encode()).await.is_err() {
+                    break;
+                }
+            }
+        };
+        let read = async {
+            while let Ok(Some(bytes)) = read_frame(&mut reader).await {
+                let Ok(frame) = Frame::decode(&bytes) else {
+                    continue;
+                };
+                if self
+                    .inbox
+                    .send(peer_id.clone(), frame.message)
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        };
+        tokio::select! {
+            _ = write => {}
+            _ = read => {}
+        }
+
+        let current = {
+            let mut links = self.links.write();
+            let current = links.get(peer_id).is_some_and(|link| link.id == id);
+            if current {
+                links.remove(peer_id);
+            }
+            current
+        };
+        if !current {
+            return;
+        }
+        if self.is_dialed(peer_id) {
+            self.set_state(peer_id, PeerState::Disconnected);
+        } else {
+            self.peers.write().remove(peer_id);
+        }
+    }
+
+    /// Carry a dialed peer's connections, redialing whenever one drops.
+    async fn supervise(self: Arc<Self>, peer_id: PeerId, mut connection: Connection) {
+        loop {
+            self.carry(&peer_id, connection).await;
+            match self.redial(&peer_id).await {
+                Some(next) => connection = next,
+                None => return,
+            }
+        }
+    }
+
+    /// Dial a dropped peer again, with backoff. Returns `None` once the
+    /// peer is disconnected or every attempt failed.
+    async fn redial(&self, peer_id: &PeerId) -> Option<Connection> {
+        for attempt in 0..self.config.max_reconnect_attempts {
+            if !self.is_dialed(peer_id) {
+                return None;
+            }
+            self.set_state(peer_id, PeerState::Connecting);
+            tokio::time::sleep(self.config.backoff(attempt)).await;
+            if !self.is_dialed(peer_id) {
+                return None;
+            }
+            if let Ok(stream) = self.dial(peer_id).await {
+                return Some(self.attach(peer_id, stream));
+            }
+        }
+        if self.is_dialed(peer_id) {
+            self.set_state(peer_id, PeerState::Disconnected);
+        }
+        None
+    }
+}
+
+/// Exchange peer IDs over a new connection, returning the remote one.
+async fn handshake(stream: &mut TcpStream, local_id: &PeerId) -> io::Result<PeerId> {
+    write_frame(stream, local_id.0.as_bytes()).await?;
+    let bytes = read_frame(stream)
+        .await?
+        .ok_or(io::ErrorKind::UnexpectedEof)?;
+    String::from_utf8(bytes)
+        .map(PeerId)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Write a length-prefixed frame.
+async fn write_frame(writer: &mut (impl AsyncWrite + Unpin), frame: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(frame.len())
+        .ok()
+        .filter(|&len| len as usize <= MAX_FRAME_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "frame too large"))?;
+    writer.write_all(&len.to_be_bytes()).await?;
+    writer.write_all(frame).await
+}
+
+/// Read the next length-prefixed frame, or `None` if the connection closed
+/// between frames.
+async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> io::Result<Option<Vec<u8>>> {
+    let len = match reader.read_u32().await {
+        Ok(len) => len as usize,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes exceeds {}", len, MAX_FRAME_LEN),
+        ));
+    }
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame).await?;
+    Ok(Some(frame))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_frames_round_trip() {
+        let (mut a, mut b) = tokio::io::duplex(64);
+        let frame = Frame {
+            from: PeerId::new("a"),
+            to: None,
+            message: Message::Ping,
+        };
+        write_frame(&mut a, &frame.encode()).await.unwrap();
+        drop(a);
+
+        let bytes = read_frame(&mut b).await.unwrap().unwrap();
+        assert!(matches!(
+            Frame::decode(&bytes).unwrap().message,
+            Message::Ping
+        ));
+        assert!(read_frame(&mut b).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_frame_is_rejected() {
+        let (mut a, mut b) = tokio::io::duplex(64);
+        a.write_all(&u32::MAX.to_be_bytes()).await.unwrap();
+
+        let err = read_frame(&mut b).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_limit() {
+        let config = TcpConfig {
+            min_backoff_ms: 100,
+            max_backoff_ms: 1000,
+            ..Default::default()
+        };
+        let delays: Vec<_> = (0..6).map(|n| config.backoff(n).as_millis()).collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+        assert_eq!(config.backoff(u32::MAX), Duration::from_millis(1000));
+    }
+
+    #[tokio::test]
+    async fn test_connect_to_unknown_peer_fails() {
+        let transport = TcpTransport::bind("127.0.0.1:0", PeerId::new("a"))
+            .await
+            .unwrap();
+        let result = transport.connect(&PeerId::new("b")).await;
+        assert!(matches!(result, Err(NetworkError::PeerNotFound(_))));
+        assert!(transport.connected_peers().await.is_empty());
+    }
+}
@@ -0,0 +1,223 @@
+//! WebSocket transport to a relay server.
+//!
+//! A relay groups connections by session and forwards [`Frame`]s between
+//! the members of a session, such as `carnelia-relay`. The transport joins
+//! its session when it connects; the relay then announces every other
+//! member with a [`Message::Join`] from that member and a
+//! [`Message::Leave`] once it goes, which is how
+//! [`connected_peers`](NetworkTransport::connected_peers) is kept.
+//!
+//! Enabled with the `websocket` feature.
+
+use crate::network::{
+    inbox, ChannelCapacity, Frame, Inbox, InboxSender, Message, NetworkError, NetworkTransport,
+    Peer, PeerId, PeerState,
+};
+use async_trait::async_trait;
+use futures::{SinkExt, StreamExt};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+/// Frames queued for the connection before senders wait.
+const OUTGOING_CAPACITY: usize = 256;
+
+/// What the writer task sends next.
+enum Outgoing {
+    Frame(Frame),
+    Close,
+}
+
+/// Transport that talks to the members of one session through a relay.
+pub struct WebSocketTransport {
+    local_id: PeerId,
+    session_id: String,
+    peers: Arc<RwLock<HashMap<PeerId, Peer>>>,
+    outgoing: mpsc::Sender<Outgoing>,
+    message_rx: RwLock<Option<Inbox>>,
+    tasks: [JoinHandle<()>; 2],
+}
+
+impl WebSocketTransport {
+    /// Connect to the relay at `url`, e.g. `ws://localhost:9090/ws`, and
+    /// join `session_id`.
+    pub async fn connect(
+        url: &str,
+        local_id: PeerId,
+        session_id: impl Into<String>,
+    ) -> Result<Self, NetworkError> {
+        Self::connect_with_capacity(url, local_id, session_id, ChannelCapacity::default()).await
+    }
+
+    /// Connect with custom inbox queue sizes.
+    pub async fn connect_with_capacity(
+        url: &str,
+        local_id: PeerId,
+        session_id: impl Into<String>,
+        capacity: ChannelCapacity,
+    ) -> Result<Self, NetworkError> {
+        let session_id = session_id.into();
+        let (socket, _) = tokio_tungstenite::connect_async(url)
+            .await
+            .map_err(|e| NetworkError::ConnectionFailed(e.to_string()))?;
+        let (mut sink, mut stream) = socket.split();
+
+        let (outgoing, mut outgoing_rx) = mpsc::channel(OUTGOING_CAPACITY);
+        let writer = tokio::spawn(async move {
+            while let Some(outgoing) = outgoing_rx.recv().await {
+                let sent = match outgoing {
+                    Outgoing::Frame(frame) => sink.send(WsMessage::Binary(frame.encode())).await,
+                    Outgoing::Close => {
+                        let _ = sink.close().await;
+                        return;
+                    }
+                };
+                if sent.is_err() {
+                    return;
+                }
+            }
+        });
+
+        let peers = Arc::new(RwLock::new(HashMap::new()));
+        let (message_tx, message_rx) = inbox(capacity);
+        let members = peers.clone();
+        let reader = tokio::spawn(async move {
+            while let Some(Ok(message)) = stream.next().await {
+                let WsMessage::Binary(bytes) = message else {
+                    continue;
+                };
+                let Ok(frame) = Frame::decode(&bytes) else {
+                    continue;
+                };
+                if !receive(&members, &message_tx, frame).await {
+                    break;
+                }
+            }
+            members.write().clear();
+        });
+
+        let transport = Self {
+            local_id,
+            session_id,
+            peers,
+            outgoing,
+            message_rx: RwLock::new(Some(message_rx)),
+            tasks: [writer, reader],
+        };
+        transport
+            .queue(
+                None,
+                Message::Join {
+                    session_id: transport.session_id.clone(),
+                },
+            )
+            .await?;
+        Ok(transport)
+    }
+
+    pub fn local_id(&self) -> &PeerId {
+        &self.local_id
+    }
+
+    /// The session joined on connect.
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Leave the session and close the connection.
+    pub async fn close(&self) -> Result<(), NetworkError> {
+        let leave = Message::Leave {
+            session_id: self.session_id.clone(),
+        };
+        self.queue(None, leave).await?;
+        self.outgoing
+            .send(Outgoing::Close)
+            .await
+            .map_err(|_| NetworkError::Disconnected)
+    }
+
+    async fn queue(&self, to: Option<PeerId>, message: Message) -> Result<(), NetworkError> {
+        let frame = Frame {
+            from: self.local_id.clone(),
+            to,
+            message,
+        };
+        self.outgoing
+            .send(Outgoing::Frame(frame))
+            .await
+            .map_err(|_| NetworkError::Disconnected)
+    }
+}
+
+/// Track membership from a relayed frame and queue its message. Returns
+/// `false` once the inbox is gone.
+async fn receive(peers: &RwLock<HashMap<PeerId, Peer>>, inbox: &InboxSender, frame: Frame) -> bool {
+    match &frame.message {
+        Message::Join { .. } => {
+            peers.write().insert(
+                frame.from.clone(),
+                Peer {
+                    id: frame.from.clone(),
+                    name: frame.from.0.clone(),
+                    state: PeerState::Connected,
+                },
+            );
+        }
+        Message::Leave { .. } => {
+            peers.write().remove(&frame.from);
+        }
+        _ => {}
+    }
+    inbox.send(frame.from, frame.message).await.is_ok()
+}
+
+impl Drop for WebSocketTransport {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+#[async_trait]
+impl NetworkTransport for WebSocketTransport {
+    /// Succeeds for members of the session; the relay decides who they are.
+    async fn connect(&self, peer_id: &PeerId) -> Result<(), NetworkError> {
+        if self.peers.read().contains_key(peer_id) {
+            Ok(())
+        } else {
+            Err(NetworkError::PeerNotFound(peer_id.to_string()))
+        }
+    }
+
+    /// Stop sending to a member until it joins again.
+    async fn disconnect(&self, peer_id: &PeerId) -> Result<(), NetworkError> {
+        self.peers.write().remove(peer_id);
+        Ok(())
+    }
+
+    async fn send(&self, peer_id: &PeerId, message: Message) -> Result<(), NetworkError> {
+        if !self.peers.read().contains_key(peer_id) {
+            return Err(NetworkError::PeerNotFound(peer_id.to_string()));
+        }
+        self.queue(Some(peer_id.clone()), message).await
+    }
+
+    async fn broadcast(&self, message: Message) -> Result<(), NetworkError> {
+        self.queue(None, message).await
+    }
+
+    async fn connected_peers(&self) -> Vec<Peer> {
+        self.peers.read().values().cloned().collect()
+    }
+
+    fn subscribe(&self) -> Inbox {
+        self.message_rx
+            .write()
+            .take()
+            .expect("subscribe can only be called once")
+    }
+}
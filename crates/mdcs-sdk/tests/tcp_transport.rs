@@ -0,0 +1,158 @@
+//! Clients syncing over real TCP connections on localhost
+//!
+//! Each client listens on its own loopback port; one dials the other, both
+//! open the same session and edit the same text document, and the edits
+//! must meet on both sides.
+
+use mdcs_sdk::{
+    Client, ClientConfig, NetworkTransport, PeerId, PeerState, Session, TcpConfig, TcpTransport,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Redial quickly so the tests don't wait on backoff.
+fn fast_redial() -> TcpConfig {
+    TcpConfig {
+        min_backoff_ms: 10,
+        max_backoff_ms: 50,
+        max_reconnect_attempts: 3,
+        ..Default::default()
+    }
+}
+
+async fn client(name: &str) -> Client<TcpTransport> {
+    let peer_id = PeerId::new(name);
+    let transport = TcpTransport::bind_with_config("127.0.0.1:0", peer_id.clone(), fast_redial())
+        .await
+        .unwrap();
+    let config = ClientConfig {
+        user_name: name.to_string(),
+        ..Default::default()
+    };
+    Client::new(peer_id, Arc::new(transport), config)
+}
+
+/// Feed everything arriving at a client into its session.
+fn pump(client: &Client<TcpTransport>, session: &Arc<Session<TcpTransport>>) -> JoinHandle<()> {
+    let mut inbox = client.transport().subscribe();
+    let session = session.clone();
+    tokio::spawn(async move {
+        while let Some((from, message)) = inbox.recv().await {
+            session.handle_message(&from, &message).await.unwrap();
+        }
+    })
+}
+
+/// Wait until `condition` holds, failing the test after [`TIMEOUT`].
+async fn eventually(what: &str, mut condition: impl FnMut() -> bool) {
+    tokio::time::timeout(TIMEOUT, async {
+        while !condition() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("timed out waiting for {}", what));
+}
+
+/// Have `from` dial `to` and wait until both see the connection.
+async fn dial(from: &Client<TcpTransport>, to: &Client<TcpTransport>) {
+    from.transport()
+        .add_peer_addr(to.peer_id().clone(), to.transport().local_addr());
+    from.connect_peer(to.peer_id()).await.unwrap();
+    eventually("the dialed peer to see the connection", || {
+        to.transport().peer_state(from.peer_id()) == Some(PeerState::Connected)
+    })
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_text_edits_converge_over_tcp() {
+    let alice = client("alice").await;
+    let bob = client("bob").await;
+    dial(&alice, &bob).await;
+
+    let alice_session = alice.create_session("notes");
+    let bob_session = bob.create_session("notes");
+    let alice_doc = alice_session.open_text_doc("doc");
+    let bob_doc = bob_session.open_text_doc("doc");
+    let _alice_pump = pump(&alice, &alice_session);
+    let _bob_pump = pump(&bob, &bob_session);
+    alice_session.connect().await.unwrap();
+    bob_session.connect().await.unwrap();
+
+    alice_doc.write().insert(0, "from alice;");
+    bob_doc.write().insert(0, "from bob;");
+    alice_session.publish().await.unwrap();
+    bob_session.publish().await.unwrap();
+
+    eventually("both documents to converge", || {
+        let text = alice_doc.read().get_text();
+        text.len() == 20 && bob_doc.read().get_text() == text
+    })
+    .await;
+    let text = alice_doc.read().get_text();
+    assert!(text.contains("from alice;") && text.contains("from bob;"));
+
+    // Every update was acknowledged over the same connections
+    alice_session.sync().flush_all("doc").await.unwrap();
+    bob_session.sync().flush_all("doc").await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_dropped_peer_is_redialed() {
+    let alice = client("alice").await;
+    let bob = client("bob").await;
+    dial(&alice, &bob).await;
+
+    let alice_session = alice.create_session("notes");
+    let bob_session = bob.create_session("notes");
+    let alice_doc = alice_session.open_text_doc("doc");
+    let bob_doc = bob_session.open_text_doc("doc");
+    let _alice_pump = pump(&alice, &alice_session);
+    let _bob_pump = pump(&bob, &bob_session);
+
+    // Bob hangs up; Alice dialed, so she dials him again
+    bob.disconnect_peer(alice.peer_id()).await.unwrap();
+    eventually("the connection to come back", || {
+        bob.transport().peer_state(alice.peer_id()) == Some(PeerState::Connected)
+            && alice.transport().peer_state(bob.peer_id()) == Some(PeerState::Connected)
+    })
+    .await;
+
+    alice_doc.write().insert(0, "after the drop");
+    alice_session.publish().await.unwrap();
+    eventually("bob to receive the edit", || {
+        bob_doc.read().get_text() == "after the drop"
+    })
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_redialing_gives_up_on_a_peer_that_is_gone() {
+    let alice = client("alice").await;
+    let bob = client("bob").await;
+    dial(&alice, &bob).await;
+    let bob_id = bob.peer_id().clone();
+
+    drop(bob);
+    eventually("alice to give up on bob", || {
+        alice.transport().peer_state(&bob_id) == Some(PeerState::Disconnected)
+    })
+    .await;
+
+    let peers = alice.connected_peers().await;
+    assert_eq!(peers.len(), 1);
+    assert_eq!(peers[0].state, PeerState::Disconnected);
+    assert!(alice
+        .transport()
+        .send(&bob_id, mdcs_sdk::Message::Ping)
+        .await
+        .is_err());
+
+    // Disconnecting forgets the peer
+    alice.disconnect_peer(&bob_id).await.unwrap();
+    assert!(alice.connected_peers().await.is_empty());
+}
@@ -0,0 +1,136 @@
+//! Errors thrown to JavaScript.
+//!
+//! Every fallible method throws an [`MdcsError`] rather than a bare string,
+//! so callers can branch on its `code` instead of matching messages.
+
+use mdcs_db::DbError;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fmt;
+use wasm_bindgen::prelude::*;
+
+/// An error thrown by this package, read from JavaScript as
+/// `{ code, message, details }`.
+///
+/// `code` is stable across releases; `message` is for people and may be
+/// reworded. `details` is an object with the values the error is about, or
+/// `undefined`. Besides the codes of the database layer, such as
+/// `DOCUMENT_NOT_FOUND` and `TYPE_MISMATCH`, the codes are:
+///
+/// - `TRANSFERRED`: the document was handed to another thread with
+///   `export_transferable`
+/// - `INVALID_ARGUMENT`: an argument isn't the object or array expected
+/// - `SERIALIZATION_ERROR`: state couldn't be encoded
+/// - `DESERIALIZATION_ERROR`: encoded state, a delta or a worker message
+///   couldn't be decoded
+/// - `QUOTA_EXCEEDED`: IndexedDB has no room for the document
+/// - `STORAGE_ERROR`: any other IndexedDB failure
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MdcsError {
+    code: &'static str,
+    message: String,
+    details: Option<Value>,
+}
+
+#[wasm_bindgen]
+impl MdcsError {
+    /// The stable error code, e.g. `"DESERIALIZATION_ERROR"`.
+    #[wasm_bindgen(getter)]
+    pub fn code(&self) -> String {
+        self.code.to_string()
+    }
+
+    /// What went wrong, for people.
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    /// The values the error is about, or `undefined`.
+    #[wasm_bindgen(getter)]
+    pub fn details(&self) -> JsValue {
+        self.details
+            .as_ref()
+            .and_then(|details| {
+                details
+                    .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+                    .ok()
+            })
+            .unwrap_or(JsValue::UNDEFINED)
+    }
+
+    /// `"CODE: message"`, as shown when the error is logged.
+    #[wasm_bindgen(js_name = toString)]
+    pub fn to_js_string(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl MdcsError {
+    pub(crate) fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub(crate) fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// State that couldn't be encoded.
+    pub(crate) fn serialization(cause: impl fmt::Display) -> Self {
+        Self::new(
+            "SERIALIZATION_ERROR",
+            format!("Serialization error: {}", cause),
+        )
+    }
+
+    /// Encoded state, a delta or a message that couldn't be decoded.
+    pub(crate) fn deserialization(cause: impl fmt::Display) -> Self {
+        Self::new(
+            "DESERIALIZATION_ERROR",
+            format!("Deserialization error: {}", cause),
+        )
+    }
+
+    /// An argument that isn't the `what` expected.
+    pub(crate) fn invalid_argument(what: &str, cause: impl fmt::Display) -> Self {
+        Self::new("INVALID_ARGUMENT", format!("Invalid {}: {}", what, cause))
+    }
+}
+
+impl fmt::Display for MdcsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for MdcsError {}
+
+impl From<DbError> for MdcsError {
+    fn from(error: DbError) -> Self {
+        let details = match &error {
+            DbError::DocumentNotFound(id) => json!({ "document_id": id }),
+            DbError::CollectionNotFound(name) | DbError::CollectionNotEmpty(name) => {
+                json!({ "collection": name })
+            }
+            DbError::PathNotFound(path) | DbError::InvalidPath(path) => json!({ "path": path }),
+            DbError::TypeMismatch { expected, found } => {
+                json!({ "expected": expected, "found": found })
+            }
+            DbError::IndexOutOfBounds { index, length } => {
+                json!({ "index": index, "length": length })
+            }
+            _ => Value::Null,
+        };
+        let error = Self::new(error.code(), error.to_string());
+        match details {
+            Value::Null => error,
+            details => error.with_details(details),
+        }
+    }
+}
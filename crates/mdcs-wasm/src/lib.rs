@@ -7,6 +7,7 @@
 //!
 //! - **CollaborativeDocument**: Rich text document with CRDT-based conflict resolution
 //! - **UserPresence**: Cursor and selection tracking for collaborative UIs
+//! - **WorkerProtocol**: Byte-blob messages for running documents in a Web Worker
 //! - **Offline-first**: All operations work locally, sync when connected
 //!
 //! ## Usage
@@ -23,11 +24,75 @@
 //! console.log(doc.get_text());  // "Hello, World!"
 //! console.log(doc.get_html());  // "<b>Hello</b>, World!"
 //! ```
+//!
+//! ## Web Workers
+//!
+//! A document can live in a Web Worker so edits never block the main thread.
+//! `export_transferable()` hands its state over as a `Uint8Array` whose buffer
+//! can go in `postMessage`'s transfer list; the exporting instance can't be
+//! edited afterwards, so only one thread ever owns the document. Edits go to
+//! the worker and change events come back as `WorkerProtocol` messages:
+//!
+//! ```javascript
+//! // main thread
+//! const state = doc.export_transferable();
+//! worker.postMessage(state, [state.buffer]);
+//! const op = WorkerProtocol.encode_op({ kind: 'insert', position: 0, text: 'Hi' });
+//! worker.postMessage(op, [op.buffer]);
+//! worker.onmessage = (e) => render(WorkerProtocol.decode_event(e.data));
+//!
+//! // worker
+//! doc = CollaborativeDocument.import_transferable(state);
+//! doc.on_change((event) => {
+//!   const bytes = WorkerProtocol.encode_event(event);
+//!   postMessage(bytes, [bytes.buffer]);
+//! });
+//! WorkerProtocol.apply_op(doc, op);
+//! ```
+//!
+//! Nothing here needs `SharedArrayBuffer`, atomics or blocking waits, so the
+//! page doesn't have to be cross-origin isolated. Documents are only shared
+//! by copying bytes between threads.
+//!
+//! ## Errors
+//!
+//! Fallible methods throw an [`MdcsError`], which JavaScript reads as
+//! `{ code, message, details }`: branch on the stable `code`, e.g.
+//! `"DESERIALIZATION_ERROR"`, rather than on the message.
+//!
+//! ## Persistence
+//!
+//! Documents can be kept in IndexedDB, so they survive a page reload and
+//! aren't bound by `localStorage`'s size limit:
+//!
+//! ```javascript
+//! await doc.save_to_storage('my-app');
+//! const saved = await CollaborativeDocument.load_from_storage('my-app', 'doc-123');
+//!
+//! // or save after every 20 local edits
+//! doc.enable_auto_save('my-app', 20, (error) => console.warn(error));
+//! ```
 
-use mdcs_core::lattice::Lattice;
-use mdcs_db::{MarkType, RichText};
+use mdcs_core::clock::SharedClock;
+use mdcs_db::{
+    sanitize_html, BlockType, JsonPath, MarkType, RichText, RichTextDelta, SanitizePolicy,
+    SanitizeReport, TextObserver, TextOperation, UndoConfig, UndoManager, UndoableOperation,
+    VersionVector,
+};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, VecDeque};
+use std::future::Future;
 use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::future_to_promise;
+
+mod error;
+mod storage;
+
+pub use error::MdcsError;
+
+/// Change events a document retains for `resync_events` by default.
+const DEFAULT_EVENT_HISTORY: usize = 256;
 
 // Initialize panic hook for better error messages in browser console
 #[wasm_bindgen(start)]
@@ -44,12 +109,22 @@ pub fn init_panic_hook() {
 ///
 /// This is the main entry point for document editing. All operations are
 /// conflict-free and can be merged with remote changes.
+///
+/// Edits throw once the document has been handed to another thread with
+/// `export_transferable`.
 #[wasm_bindgen]
 pub struct CollaborativeDocument {
     id: String,
     replica_id: String,
     text: RichText,
     version: u64,
+    event_seq: u64,
+    history: VecDeque<ChangeEvent>,
+    history_capacity: usize,
+    change_callback: Option<js_sys::Function>,
+    transferred: bool,
+    undo: UndoManager,
+    auto_save: Option<AutoSave>,
 }
 
 #[wasm_bindgen]
@@ -66,6 +141,13 @@ impl CollaborativeDocument {
             replica_id: replica_id.to_string(),
             text: RichText::new(replica_id),
             version: 0,
+            event_seq: 0,
+            history: VecDeque::new(),
+            history_capacity: DEFAULT_EVENT_HISTORY,
+            change_callback: None,
+            transferred: false,
+            undo: undo_manager(doc_id, replica_id),
+            auto_save: None,
         }
     }
 
@@ -75,10 +157,17 @@ impl CollaborativeDocument {
     /// * `position` - Character index to insert at (0-based)
     /// * `text` - Text to insert
     #[wasm_bindgen]
-    pub fn insert(&mut self, position: usize, text: &str) {
+    pub fn insert(&mut self, position: usize, text: &str) -> Result<(), MdcsError> {
+        self.ensure_live()?;
         let pos = position.min(self.text.len());
+        record_insert(&mut self.undo, pos, text);
         self.text.insert(pos, text);
         self.version += 1;
+        self.emit(Change::Insert {
+            position: pos,
+            text: text.to_string(),
+        });
+        Ok(())
     }
 
     /// Delete text at a position.
@@ -87,13 +176,95 @@ impl CollaborativeDocument {
     /// * `position` - Starting character index (0-based)
     /// * `length` - Number of characters to delete
     #[wasm_bindgen]
-    pub fn delete(&mut self, position: usize, length: usize) {
+    pub fn delete(&mut self, position: usize, length: usize) -> Result<(), MdcsError> {
+        self.ensure_live()?;
         let pos = position.min(self.text.len());
         let len = length.min(self.text.len().saturating_sub(pos));
         if len > 0 {
+            self.undo
+                .record(UndoableOperation::Text(TextOperation::Delete {
+                    position: pos,
+                    deleted: self.text.text().slice(pos, pos + len),
+                }));
             self.text.delete(pos, len);
             self.version += 1;
+            self.emit(Change::Delete {
+                position: pos,
+                length: len,
+            });
+        }
+        Ok(())
+    }
+
+    /// Replace the document's text with `new_text`, e.g. a `<textarea>`'s
+    /// value on `input`.
+    ///
+    /// Only what lies between the common prefix and suffix of the two
+    /// texts is deleted and inserted, so concurrent edits elsewhere merge
+    /// cleanly. Returns the number of edits applied (0, 1 or 2), which are
+    /// undone together and emit the usual change events.
+    ///
+    /// Texts are compared by character (Unicode scalar value), the unit of
+    /// every position in this API, so an emoji is never split. A JS string
+    /// counts UTF-16 code units instead, in which an emoji can take two;
+    /// convert selection offsets with `utf16_to_position` and
+    /// `position_to_utf16`.
+    #[wasm_bindgen]
+    pub fn apply_text_diff(&mut self, new_text: &str) -> Result<u32, MdcsError> {
+        self.ensure_live()?;
+        let old: Vec<char> = self.text.to_string().chars().collect();
+        let new: Vec<char> = new_text.chars().collect();
+        let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let deleted = old.len() - prefix - suffix;
+        let inserted: String = new[prefix..new.len() - suffix].iter().collect();
+
+        let edits = u32::from(deleted > 0) + u32::from(!inserted.is_empty());
+        let group = edits > 1 && !self.undo.in_group();
+        if group {
+            self.undo.start_group();
+        }
+        self.delete(prefix, deleted)?;
+        if !inserted.is_empty() {
+            self.insert(prefix, &inserted)?;
         }
+        if group {
+            self.undo.end_group();
+        }
+        Ok(edits)
+    }
+
+    /// Convert a UTF-16 offset into the text, as JS strings count them, to
+    /// a character position. An offset inside a surrogate pair maps to the
+    /// character it belongs to.
+    #[wasm_bindgen]
+    pub fn utf16_to_position(&self, offset: usize) -> usize {
+        let mut units = 0;
+        self.text
+            .to_string()
+            .chars()
+            .take_while(|c| {
+                units += c.len_utf16();
+                units <= offset
+            })
+            .count()
+    }
+
+    /// Convert a character position to a UTF-16 offset into the text, as
+    /// JS strings count them.
+    #[wasm_bindgen]
+    pub fn position_to_utf16(&self, position: usize) -> usize {
+        self.text
+            .to_string()
+            .chars()
+            .take(position)
+            .map(char::len_utf16)
+            .sum()
     }
 
     /// Apply bold formatting to a range.
@@ -102,26 +273,26 @@ impl CollaborativeDocument {
     /// * `start` - Starting character index (inclusive)
     /// * `end` - Ending character index (exclusive)
     #[wasm_bindgen]
-    pub fn apply_bold(&mut self, start: usize, end: usize) {
-        self.apply_mark(start, end, MarkType::Bold);
+    pub fn apply_bold(&mut self, start: usize, end: usize) -> Result<(), MdcsError> {
+        self.apply_mark(start, end, MarkType::Bold)
     }
 
     /// Apply italic formatting to a range.
     #[wasm_bindgen]
-    pub fn apply_italic(&mut self, start: usize, end: usize) {
-        self.apply_mark(start, end, MarkType::Italic);
+    pub fn apply_italic(&mut self, start: usize, end: usize) -> Result<(), MdcsError> {
+        self.apply_mark(start, end, MarkType::Italic)
     }
 
     /// Apply underline formatting to a range.
     #[wasm_bindgen]
-    pub fn apply_underline(&mut self, start: usize, end: usize) {
-        self.apply_mark(start, end, MarkType::Underline);
+    pub fn apply_underline(&mut self, start: usize, end: usize) -> Result<(), MdcsError> {
+        self.apply_mark(start, end, MarkType::Underline)
     }
 
     /// Apply strikethrough formatting to a range.
     #[wasm_bindgen]
-    pub fn apply_strikethrough(&mut self, start: usize, end: usize) {
-        self.apply_mark(start, end, MarkType::Strikethrough);
+    pub fn apply_strikethrough(&mut self, start: usize, end: usize) -> Result<(), MdcsError> {
+        self.apply_mark(start, end, MarkType::Strikethrough)
     }
 
     /// Apply a link to a range.
@@ -131,7 +302,8 @@ impl CollaborativeDocument {
     /// * `end` - Ending character index (exclusive)
     /// * `url` - The URL to link to
     #[wasm_bindgen]
-    pub fn apply_link(&mut self, start: usize, end: usize, url: &str) {
+    pub fn apply_link(&mut self, start: usize, end: usize, url: &str) -> Result<(), MdcsError> {
+        self.ensure_live()?;
         let s = start.min(self.text.len());
         let e = end.min(self.text.len());
         if s < e {
@@ -143,7 +315,105 @@ impl CollaborativeDocument {
                 },
             );
             self.version += 1;
+            self.emit(Change::Format { start: s, end: e });
+        }
+        Ok(())
+    }
+
+    /// Comment on a range.
+    ///
+    /// Returns the new comment's ID, or `undefined` if the range is empty.
+    ///
+    /// # Arguments
+    /// * `start` - Starting character index (inclusive)
+    /// * `end` - Ending character index (exclusive)
+    /// * `author` - Who wrote the comment
+    /// * `text` - The comment itself
+    #[wasm_bindgen]
+    pub fn add_comment(
+        &mut self,
+        start: usize,
+        end: usize,
+        author: &str,
+        text: &str,
+    ) -> Result<Option<String>, MdcsError> {
+        self.ensure_live()?;
+        let s = start.min(self.text.len());
+        let e = end.min(self.text.len());
+        if s >= e {
+            return Ok(None);
+        }
+        let id = self.text.comment(s, e, author, text);
+        self.version += 1;
+        self.emit(Change::Format { start: s, end: e });
+        Ok(Some(id.to_string()))
+    }
+
+    /// Remove a comment by the ID `add_comment` returned.
+    ///
+    /// Returns `false` if no comment has that ID.
+    #[wasm_bindgen]
+    pub fn remove_comment(&mut self, id: &str) -> Result<bool, MdcsError> {
+        self.ensure_live()?;
+        Ok(self.delete_comment(id))
+    }
+
+    /// Get the comments as `{ id, author, text, start, end }` objects,
+    /// ordered by where they start.
+    #[wasm_bindgen]
+    pub fn get_comments(&self) -> Result<JsValue, MdcsError> {
+        serde_wasm_bindgen::to_value(&self.comments()).map_err(MdcsError::serialization)
+    }
+
+    /// Set the block type of the paragraph containing a position.
+    ///
+    /// Concurrent changes to the same paragraph resolve last-writer-wins,
+    /// and `get_html` wraps each paragraph in its block's tags.
+    ///
+    /// # Arguments
+    /// * `position` - Any character index in the paragraph
+    /// * `block_type` - `"paragraph"`, `"heading1"` to `"heading6"`,
+    ///   `"bullet_list"`, `"ordered_list"`, `"quote"` or `"code_block"`
+    #[wasm_bindgen]
+    pub fn set_block_type(&mut self, position: usize, block_type: &str) -> Result<(), MdcsError> {
+        self.ensure_live()?;
+        let block_type = parse_block_type(block_type)?;
+        let position = position.min(self.text.len());
+        self.text.set_block_type(position, block_type);
+        if let Some(&(start, end, _)) = self
+            .text
+            .blocks()
+            .iter()
+            .find(|(start, end, _)| (*start..=*end).contains(&position))
+        {
+            self.version += 1;
+            self.emit(Change::Format { start, end });
         }
+        Ok(())
+    }
+
+    /// Get the block type of the paragraph containing a position, as
+    /// accepted by `set_block_type`.
+    #[wasm_bindgen]
+    pub fn get_block_type(&self, position: usize) -> String {
+        block_type_name(&self.text.block_type_at(position))
+    }
+
+    /// Paste HTML from the clipboard at a position.
+    ///
+    /// The HTML is sanitized with the default policy: scripts, styles,
+    /// event handlers and unsafe links are dropped, and only basic
+    /// formatting survives. Returns what was removed, as
+    /// `{ removed_tags, removed_attrs, truncated }`.
+    ///
+    /// # Arguments
+    /// * `position` - Character index to paste at (0-based)
+    /// * `html` - The `text/html` clipboard contents
+    #[wasm_bindgen]
+    pub fn paste_html(&mut self, position: usize, html: &str) -> Result<JsValue, MdcsError> {
+        self.ensure_live()?;
+        let report = self.paste(position, html);
+        serde_wasm_bindgen::to_value(&report).map_err(MdcsError::serialization)
     }
 
     /// Get the plain text content (without formatting).
@@ -172,13 +442,83 @@ impl CollaborativeDocument {
 
     /// Get the current version number.
     ///
-    /// This increments with each local operation and can be used
-    /// to track changes for sync purposes.
+    /// This increments with each local operation and each merge on this
+    /// replica only. It says nothing about which remote changes have been
+    /// seen; compare `version_vector()`s for that.
     #[wasm_bindgen]
     pub fn version(&self) -> u64 {
         self.version
     }
 
+    /// Get the highest sequence number seen from each replica.
+    ///
+    /// Returns an object mapping replica IDs to sequence numbers. Inserted
+    /// characters advance it; deletions and formatting don't.
+    #[wasm_bindgen]
+    pub fn version_vector(&self) -> Result<JsValue, MdcsError> {
+        let vv = self.text.version_vector();
+        let entries: BTreeMap<&String, &u64> = vv.iter().collect();
+        entries
+            .serialize(&serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true))
+            .map_err(MdcsError::serialization)
+    }
+
+    /// Check whether this document has seen everything in `other_vv`, and more.
+    ///
+    /// # Arguments
+    /// * `other_vv` - Another replica's `version_vector()`
+    #[wasm_bindgen]
+    pub fn is_newer_than(&self, other_vv: JsValue) -> Result<bool, MdcsError> {
+        let other = version_vector_from_js(other_vv)?;
+        Ok(self.text.version_vector().strictly_dominates(&other))
+    }
+
+    /// List the replicas where `other_vv` is ahead of this document.
+    ///
+    /// Returns `[{ replica_id, from_seq, to_seq }, ...]`, the ranges of
+    /// sequence numbers not seen yet; empty when this document is up to date.
+    #[wasm_bindgen]
+    pub fn missing_from(&self, other_vv: JsValue) -> Result<JsValue, MdcsError> {
+        let other = version_vector_from_js(other_vv)?;
+        serde_wasm_bindgen::to_value(&self.missing(&other)).map_err(MdcsError::serialization)
+    }
+
+    /// Register a callback for document changes.
+    ///
+    /// Called with `{ seq, kind, ... }` after every change: `kind` is
+    /// `"insert"` (with `position`, `text`), `"delete"` (`position`,
+    /// `length`), `"format"` (`start`, `end`) or `"remote"` (`edits`, as
+    /// returned by `merge`). `seq` counts up from 1 without gaps; if it
+    /// jumps, call `resync_events`.
+    #[wasm_bindgen]
+    pub fn on_change(&mut self, callback: js_sys::Function) {
+        self.change_callback = Some(callback);
+    }
+
+    /// Get the sequence number of the latest change event.
+    #[wasm_bindgen]
+    pub fn event_seq(&self) -> u64 {
+        self.event_seq
+    }
+
+    /// Set how many recent change events are kept for `resync_events`.
+    #[wasm_bindgen]
+    pub fn set_event_history(&mut self, capacity: usize) {
+        self.history_capacity = capacity;
+        let excess = self.history.len().saturating_sub(capacity);
+        self.history.drain(..excess);
+    }
+
+    /// Replay change events from `from_seq` on.
+    ///
+    /// Returns `{ events: [...] }`, or `{ snapshot: { seq, len } }` if some
+    /// of them are no longer retained; the view should then be rebuilt from
+    /// `get_text()` / `get_html()`.
+    #[wasm_bindgen]
+    pub fn resync_events(&self, from_seq: u64) -> Result<JsValue, MdcsError> {
+        serde_wasm_bindgen::to_value(&self.replay(from_seq)).map_err(MdcsError::serialization)
+    }
+
     /// Get the document ID.
     #[wasm_bindgen]
     pub fn doc_id(&self) -> String {
@@ -196,15 +536,20 @@ impl CollaborativeDocument {
     /// Returns a base64-encoded binary string that can be sent to other replicas.
     /// Binary format is more efficient and handles complex key types.
     #[wasm_bindgen]
-    pub fn serialize(&self) -> Result<String, JsValue> {
+    pub fn serialize(&self) -> Result<String, MdcsError> {
         // Use serde_wasm_bindgen which handles HashMap with non-string keys
-        let js_value = serde_wasm_bindgen::to_value(&self.text)
-            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))?;
+        let js_value =
+            serde_wasm_bindgen::to_value(&self.text).map_err(MdcsError::serialization)?;
 
         // Convert JsValue to JSON string using js_sys
         js_sys::JSON::stringify(&js_value)
             .map(|s| s.into())
-            .map_err(|e| JsValue::from_str(&format!("JSON stringify error: {:?}", e)))
+            .map_err(|e| {
+                MdcsError::new(
+                    "SERIALIZATION_ERROR",
+                    format!("JSON stringify error: {:?}", e),
+                )
+            })
     }
 
     /// Merge remote state into this document.
@@ -212,33 +557,78 @@ impl CollaborativeDocument {
     /// This is the core CRDT operation - merging is commutative,
     /// associative, and idempotent, so the order of merges doesn't matter.
     ///
+    /// Returns the text edits the merge made, as `{ position, deleted,
+    /// inserted }` objects: `deleted` characters at `position` were
+    /// replaced by `inserted` new ones. Edits are in document order and each
+    /// position already counts the earlier edits, so they can be passed to
+    /// `UserPresence.transform` or `transform_position` to move cursors.
+    ///
     /// # Arguments
     /// * `remote_state` - JSON string from another replica's `serialize()`
     #[wasm_bindgen]
-    pub fn merge(&mut self, remote_state: &str) -> Result<(), JsValue> {
+    pub fn merge(&mut self, remote_state: &str) -> Result<JsValue, MdcsError> {
+        self.ensure_live()?;
+
         // Parse the JSON string back to JsValue
-        let js_value = js_sys::JSON::parse(remote_state)
-            .map_err(|e| JsValue::from_str(&format!("JSON parse error: {:?}", e)))?;
+        let js_value = js_sys::JSON::parse(remote_state).map_err(|e| {
+            MdcsError::new(
+                "DESERIALIZATION_ERROR",
+                format!("JSON parse error: {:?}", e),
+            )
+        })?;
 
         // Deserialize using serde_wasm_bindgen
-        let remote: RichText = serde_wasm_bindgen::from_value(js_value)
-            .map_err(|e| JsValue::from_str(&format!("Deserialization error: {}", e)))?;
+        let remote: RichText =
+            serde_wasm_bindgen::from_value(js_value).map_err(MdcsError::deserialization)?;
 
-        self.text = self.text.join(&remote);
-        self.version += 1;
-        Ok(())
+        let edits = self.merge_text(&remote);
+        serde_wasm_bindgen::to_value(&edits).map_err(MdcsError::serialization)
+    }
+
+    /// Take the changes made since the last call, for `apply_delta` on
+    /// other replicas.
+    ///
+    /// Returns a JSON string, or `undefined` if nothing changed. A delta
+    /// only carries the edits themselves, so a replica that lacks the state
+    /// they build on should first catch up with `serialize()` + `merge()`.
+    /// Changes can still be taken after `export_transferable`.
+    #[wasm_bindgen]
+    pub fn take_delta(&mut self) -> Result<Option<String>, MdcsError> {
+        self.encode_delta().map_err(MdcsError::serialization)
+    }
+
+    /// Apply a delta from another replica's `take_delta()`.
+    ///
+    /// Applying the same delta again, or deltas in a different order,
+    /// leaves the document as it was. Returns the text edits it made, like
+    /// `merge`.
+    ///
+    /// # Arguments
+    /// * `delta_json` - JSON string from another replica's `take_delta()`
+    #[wasm_bindgen]
+    pub fn apply_delta(&mut self, delta_json: &str) -> Result<JsValue, MdcsError> {
+        self.ensure_live()?;
+        let edits = self
+            .apply_encoded_delta(delta_json)
+            .map_err(MdcsError::deserialization)?;
+        serde_wasm_bindgen::to_value(&edits).map_err(MdcsError::serialization)
     }
 
     /// Create a snapshot of the current state.
     ///
     /// This returns a JSON object with full document state.
     #[wasm_bindgen]
-    pub fn snapshot(&self) -> Result<JsValue, JsValue> {
-        let state_js = serde_wasm_bindgen::to_value(&self.text)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    pub fn snapshot(&self) -> Result<JsValue, MdcsError> {
+        let state_js =
+            serde_wasm_bindgen::to_value(&self.text).map_err(MdcsError::serialization)?;
         let state_str: String = js_sys::JSON::stringify(&state_js)
             .map(|s| s.into())
-            .map_err(|e| JsValue::from_str(&format!("JSON stringify error: {:?}", e)))?;
+            .map_err(|e| {
+                MdcsError::new(
+                    "SERIALIZATION_ERROR",
+                    format!("JSON stringify error: {:?}", e),
+                )
+            })?;
 
         let snapshot = DocumentSnapshot {
             doc_id: self.id.clone(),
@@ -246,41 +636,682 @@ impl CollaborativeDocument {
             version: self.version,
             state: state_str,
         };
-        serde_wasm_bindgen::to_value(&snapshot).map_err(|e| JsValue::from_str(&e.to_string()))
+        serde_wasm_bindgen::to_value(&snapshot).map_err(MdcsError::serialization)
     }
 
     /// Restore from a snapshot.
     #[wasm_bindgen]
-    pub fn restore(snapshot_js: JsValue) -> Result<CollaborativeDocument, JsValue> {
-        let snapshot: DocumentSnapshot = serde_wasm_bindgen::from_value(snapshot_js)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    pub fn restore(snapshot_js: JsValue) -> Result<CollaborativeDocument, MdcsError> {
+        let snapshot: DocumentSnapshot =
+            serde_wasm_bindgen::from_value(snapshot_js).map_err(MdcsError::deserialization)?;
 
         // Parse the state JSON string
-        let state_js = js_sys::JSON::parse(&snapshot.state)
-            .map_err(|e| JsValue::from_str(&format!("JSON parse error: {:?}", e)))?;
+        let state_js = js_sys::JSON::parse(&snapshot.state).map_err(|e| {
+            MdcsError::new(
+                "DESERIALIZATION_ERROR",
+                format!("JSON parse error: {:?}", e),
+            )
+        })?;
 
-        let text: RichText = serde_wasm_bindgen::from_value(state_js)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let text: RichText =
+            serde_wasm_bindgen::from_value(state_js).map_err(MdcsError::deserialization)?;
 
         Ok(Self {
+            undo: undo_manager(&snapshot.doc_id, &snapshot.replica_id),
             id: snapshot.doc_id,
             replica_id: snapshot.replica_id,
             text,
             version: snapshot.version,
+            event_seq: 0,
+            history: VecDeque::new(),
+            history_capacity: DEFAULT_EVENT_HISTORY,
+            change_callback: None,
+            transferred: false,
+            auto_save: None,
+        })
+    }
+
+    /// Save the document to IndexedDB.
+    ///
+    /// Stores the content, marks, version and replica ID in the database
+    /// `db_name` under this document's ID, replacing whatever was saved
+    /// there before. Returns a Promise that resolves once the write is
+    /// committed, or rejects with an `MdcsError` saying what went wrong,
+    /// e.g. `QUOTA_EXCEEDED` when the storage quota is exceeded.
+    #[wasm_bindgen]
+    pub fn save_to_storage(&self, db_name: &str) -> js_sys::Promise {
+        let save = self.save(db_name);
+        future_to_promise(async move {
+            save.await
+                .map(|()| JsValue::UNDEFINED)
+                .map_err(JsValue::from)
         })
     }
 
+    /// Load a document saved with `save_to_storage`.
+    ///
+    /// Returns a Promise of the document, or of `undefined` if nothing was
+    /// saved under `doc_id` in the database `db_name`. Like `restore`, the
+    /// content, version and replica ID carry over; change callbacks, undo
+    /// history and auto-save don't.
+    #[wasm_bindgen]
+    pub fn load_from_storage(db_name: &str, doc_id: &str) -> js_sys::Promise {
+        let (db_name, doc_id) = (db_name.to_string(), doc_id.to_string());
+        future_to_promise(async move {
+            let doc = Self::load(&db_name, &doc_id).await?;
+            Ok(doc.map_or(JsValue::UNDEFINED, JsValue::from))
+        })
+    }
+
+    /// Save to IndexedDB after every `every_ops` local changes.
+    ///
+    /// Local changes are the `insert`, `delete` and `format` events
+    /// `on_change` sees; merges don't count. Saves run in the background:
+    /// one that fails is passed to `on_error`, or logged to the console
+    /// without it, and the next is tried `every_ops` changes later.
+    ///
+    /// # Arguments
+    /// * `db_name` - IndexedDB database to save to
+    /// * `every_ops` - Local changes between saves (at least 1)
+    /// * `on_error` - Called with the `MdcsError` of a failed save
+    #[wasm_bindgen]
+    pub fn enable_auto_save(
+        &mut self,
+        db_name: &str,
+        every_ops: u32,
+        on_error: Option<js_sys::Function>,
+    ) {
+        self.auto_save = Some(AutoSave {
+            db_name: db_name.to_string(),
+            every_ops: every_ops.max(1),
+            unsaved: 0,
+            on_error,
+        });
+    }
+
+    /// Stop saving automatically. Changes since the last save stay unsaved.
+    #[wasm_bindgen]
+    pub fn disable_auto_save(&mut self) {
+        self.auto_save = None;
+    }
+
+    /// Hand the document over to another thread.
+    ///
+    /// Returns the full state as bytes for `import_transferable`; pass their
+    /// buffer in `postMessage`'s transfer list to move rather than copy it.
+    /// This instance can still be read, but every later edit, merge or
+    /// export throws, so two threads never edit copies they both take for
+    /// the document.
+    #[wasm_bindgen]
+    pub fn export_transferable(&mut self) -> Result<Vec<u8>, MdcsError> {
+        self.ensure_live()?;
+        let bytes = self.to_transferable().map_err(MdcsError::serialization)?;
+        self.transferred = true;
+        Ok(bytes)
+    }
+
+    /// Take over a document from `export_transferable`.
+    ///
+    /// The content, version vector, version and event sequence carry over;
+    /// change callbacks and retained events don't.
+    #[wasm_bindgen]
+    pub fn import_transferable(bytes: &[u8]) -> Result<CollaborativeDocument, MdcsError> {
+        Self::from_transferable(bytes).map_err(MdcsError::deserialization)
+    }
+
+    /// Whether this instance was handed over with `export_transferable`.
+    #[wasm_bindgen]
+    pub fn is_transferred(&self) -> bool {
+        self.transferred
+    }
+
+    /// Undo the last local edit, or burst of edits.
+    ///
+    /// Only this replica's insertions and deletions are undone; remote
+    /// edits merged since stay, and the undone text is found where they
+    /// moved it. An edit whose text a remote edit has changed is skipped.
+    /// Emits the usual change events and returns whether anything changed.
+    #[wasm_bindgen]
+    pub fn undo(&mut self) -> Result<bool, MdcsError> {
+        self.ensure_live()?;
+        let mut changes = Vec::new();
+        let text = &mut self.text;
+        let outcome = self.undo.undo_with(|op| apply_undo(text, op, &mut changes));
+        self.apply_changes(changes);
+        Ok(!outcome.applied.is_empty())
+    }
+
+    /// Redo the last undone edit, or burst of edits.
+    ///
+    /// A new local edit clears what is left to redo.
+    #[wasm_bindgen]
+    pub fn redo(&mut self) -> Result<bool, MdcsError> {
+        self.ensure_live()?;
+        let mut changes = Vec::new();
+        let text = &mut self.text;
+        let outcome = self.undo.redo_with(|op| apply_undo(text, op, &mut changes));
+        self.apply_changes(changes);
+        Ok(!outcome.applied.is_empty())
+    }
+
+    /// Check if there is a local edit to undo.
+    #[wasm_bindgen]
+    pub fn can_undo(&self) -> bool {
+        self.undo.can_undo()
+    }
+
+    /// Check if there is an undone edit to redo.
+    #[wasm_bindgen]
+    pub fn can_redo(&self) -> bool {
+        self.undo.can_redo()
+    }
+
+    /// Undo edits made less than `window_ms` apart together, as one burst
+    /// of typing. 0 undoes every edit on its own, the default.
+    #[wasm_bindgen]
+    pub fn set_undo_group_window(&mut self, window_ms: u32) {
+        self.undo.set_config(UndoConfig {
+            group_window_ms: (window_ms > 0).then_some(u64::from(window_ms)),
+            ..self.undo.config().clone()
+        });
+    }
+
+    /// Start a group of edits that are undone together.
+    #[wasm_bindgen]
+    pub fn start_undo_group(&mut self) {
+        self.undo.start_group();
+    }
+
+    /// End the current group of edits.
+    #[wasm_bindgen]
+    pub fn end_undo_group(&mut self) {
+        self.undo.end_group();
+    }
+
     // Internal helper
-    fn apply_mark(&mut self, start: usize, end: usize, mark: MarkType) {
+    fn apply_mark(&mut self, start: usize, end: usize, mark: MarkType) -> Result<(), MdcsError> {
+        self.ensure_live()?;
         let s = start.min(self.text.len());
         let e = end.min(self.text.len());
         if s < e {
             self.text.add_mark(s, e, mark);
             self.version += 1;
+            self.emit(Change::Format { start: s, end: e });
         }
+        Ok(())
     }
 }
 
+impl CollaborativeDocument {
+    /// Save the document to IndexedDB; see `save_to_storage`.
+    ///
+    /// The state is captured now, so later edits don't end up in this save.
+    pub fn save(&self, db_name: &str) -> impl Future<Output = Result<(), MdcsError>> + 'static {
+        let record = self.snapshot();
+        let (db_name, key) = (db_name.to_string(), self.id.clone());
+        async move {
+            storage::put(&db_name, &key, &record?)
+                .await
+                .map_err(MdcsError::from)
+        }
+    }
+
+    /// Load a document from IndexedDB; see `load_from_storage`.
+    pub async fn load(db_name: &str, doc_id: &str) -> Result<Option<Self>, MdcsError> {
+        match storage::get(db_name, doc_id).await? {
+            Some(record) => Self::restore(record).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn ensure_live(&self) -> Result<(), Transferred> {
+        if self.transferred {
+            Err(Transferred {
+                doc_id: self.id.clone(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn to_transferable(&self) -> Result<Vec<u8>, serde_json::Error> {
+        serde_json::to_vec(&TransferableState {
+            doc_id: Cow::Borrowed(&self.id),
+            replica_id: Cow::Borrowed(&self.replica_id),
+            version: self.version,
+            event_seq: self.event_seq,
+            text: Cow::Borrowed(&self.text),
+        })
+    }
+
+    fn from_transferable(bytes: &[u8]) -> Result<Self, serde_json::Error> {
+        let state: TransferableState<'static> = serde_json::from_slice(bytes)?;
+        Ok(Self {
+            undo: undo_manager(&state.doc_id, &state.replica_id),
+            id: state.doc_id.into_owned(),
+            replica_id: state.replica_id.into_owned(),
+            text: state.text.into_owned(),
+            version: state.version,
+            event_seq: state.event_seq,
+            history: VecDeque::new(),
+            history_capacity: DEFAULT_EVENT_HISTORY,
+            change_callback: None,
+            transferred: false,
+            auto_save: None,
+        })
+    }
+
+    fn encode_delta(&mut self) -> Result<Option<String>, serde_json::Error> {
+        self.text
+            .take_delta()
+            .map(|delta| serde_json::to_string(&delta))
+            .transpose()
+    }
+
+    fn apply_encoded_delta(
+        &mut self,
+        delta_json: &str,
+    ) -> Result<Vec<TextEdit>, serde_json::Error> {
+        let delta: RichTextDelta = serde_json::from_str(delta_json)?;
+        let before = self.text.text().clone();
+        self.text.apply_delta(&delta);
+
+        // The applied text contains the old one, so joining reports the edits
+        let mut edits = EditRecorder::default();
+        before.join_observed(self.text.text(), &mut edits);
+        Ok(self.remote_change(edits.0))
+    }
+
+    fn merge_text(&mut self, remote: &RichText) -> Vec<TextEdit> {
+        let mut edits = EditRecorder::default();
+        self.text = self.text.join_observed(remote, &mut edits);
+        self.remote_change(edits.0)
+    }
+
+    /// Count and emit the changes an undo or redo made.
+    fn apply_changes(&mut self, changes: Vec<Change>) {
+        for change in changes {
+            self.version += 1;
+            self.emit(change);
+        }
+    }
+
+    fn remote_change(&mut self, edits: Vec<TextEdit>) -> Vec<TextEdit> {
+        for edit in &edits {
+            self.undo
+                .transform_text(edit.position, edit.deleted, edit.inserted);
+        }
+        self.version += 1;
+        self.emit(Change::Remote {
+            edits: edits.clone(),
+        });
+        edits
+    }
+
+    fn comments(&self) -> Vec<Comment> {
+        self.text
+            .comments()
+            .into_iter()
+            .filter_map(|(start, end, mark)| match &mark.mark_type {
+                MarkType::Comment { author, content } => Some(Comment {
+                    id: mark.id.to_string(),
+                    author: author.clone(),
+                    text: content.clone(),
+                    start,
+                    end,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn delete_comment(&mut self, id: &str) -> bool {
+        let Some(mark_id) = self
+            .text
+            .active_marks()
+            .map(|mark| &mark.id)
+            .find(|mark_id| mark_id.to_string() == id)
+            .cloned()
+        else {
+            return false;
+        };
+        // A comment whose text was all deleted covers nothing to reformat
+        let range = self
+            .text
+            .comments()
+            .into_iter()
+            .find(|(_, _, mark)| mark.id == mark_id)
+            .map(|(start, end, _)| (start, end));
+        if !self.text.remove_comment(&mark_id) {
+            return false;
+        }
+        self.version += 1;
+        if let Some((start, end)) = range {
+            self.emit(Change::Format { start, end });
+        }
+        true
+    }
+
+    fn paste(&mut self, position: usize, html: &str) -> SanitizeReport {
+        let (fragment, report) = sanitize_html(html, &SanitizePolicy::default());
+        let pos = position.min(self.text.len());
+        if !fragment.text.is_empty() {
+            record_insert(&mut self.undo, pos, &fragment.text);
+            self.text.insert_fragment(pos, &fragment);
+            self.version += 1;
+            self.emit(Change::Insert {
+                position: pos,
+                text: fragment.text.clone(),
+            });
+            if !fragment.marks.is_empty() {
+                self.emit(Change::Format {
+                    start: pos,
+                    end: pos + fragment.text.chars().count(),
+                });
+            }
+        }
+        report
+    }
+
+    fn apply_op(&mut self, op: Op) -> Result<(), MdcsError> {
+        match op {
+            Op::Insert { position, text } => self.insert(position, &text),
+            Op::Delete { position, length } => self.delete(position, length),
+            Op::Bold { start, end } => self.apply_bold(start, end),
+            Op::Italic { start, end } => self.apply_italic(start, end),
+            Op::Underline { start, end } => self.apply_underline(start, end),
+            Op::Strikethrough { start, end } => self.apply_strikethrough(start, end),
+            Op::Link { start, end, url } => self.apply_link(start, end, &url),
+            Op::SetBlockType {
+                position,
+                block_type,
+            } => self.set_block_type(position, &block_type),
+            Op::Merge { state } => self.merge(&state).map(|_| ()),
+        }
+    }
+
+    /// Number a change, retain it, pass it to the change callback and count
+    /// it towards auto-save.
+    fn emit(&mut self, change: Change) {
+        let local = !matches!(change, Change::Remote { .. });
+        self.event_seq += 1;
+        let event = ChangeEvent {
+            seq: self.event_seq,
+            change,
+        };
+        if let Some(callback) = &self.change_callback {
+            if let Ok(value) = serde_wasm_bindgen::to_value(&event) {
+                let _ = callback.call1(&JsValue::NULL, &value);
+            }
+        }
+        if self.history_capacity > 0 {
+            if self.history.len() == self.history_capacity {
+                self.history.pop_front();
+            }
+            self.history.push_back(event);
+        }
+        if local {
+            self.count_unsaved();
+        }
+    }
+
+    /// Count a local change towards auto-save, saving in the background
+    /// once enough have been made.
+    fn count_unsaved(&mut self) {
+        let Some(auto_save) = &mut self.auto_save else {
+            return;
+        };
+        auto_save.unsaved += 1;
+        if auto_save.unsaved < auto_save.every_ops {
+            return;
+        }
+        auto_save.unsaved = 0;
+        let on_error = auto_save.on_error.clone();
+        let db_name = auto_save.db_name.clone();
+        let save = self.save(&db_name);
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Err(error) = save.await {
+                match on_error {
+                    Some(callback) => {
+                        let _ = callback.call1(&JsValue::NULL, &error.into());
+                    }
+                    None => web_sys::console::error_1(&error.into()),
+                }
+            }
+        });
+    }
+
+    fn missing(&self, other: &VersionVector) -> Vec<MissingRange> {
+        other
+            .diff(&self.text.version_vector())
+            .into_iter()
+            .map(|(replica_id, from_seq, to_seq)| MissingRange {
+                replica_id,
+                from_seq,
+                to_seq,
+            })
+            .collect()
+    }
+
+    fn replay(&self, from_seq: u64) -> Resync {
+        let from_seq = from_seq.max(1);
+        if from_seq > self.event_seq {
+            return Resync::Events(Vec::new());
+        }
+        match self.history.front() {
+            Some(oldest) if oldest.seq <= from_seq => Resync::Events(
+                self.history
+                    .iter()
+                    .filter(|event| event.seq >= from_seq)
+                    .cloned()
+                    .collect(),
+            ),
+            _ => Resync::Snapshot {
+                seq: self.event_seq,
+                len: self.text.len(),
+            },
+        }
+    }
+}
+
+/// Undo history for a new document.
+fn undo_manager(doc_id: &str, replica_id: &str) -> UndoManager {
+    UndoManager::new(doc_id, replica_id).with_clock(undo_clock())
+}
+
+/// Time source for grouping undo bursts; `SystemTime` isn't available in
+/// the browser.
+#[cfg(target_arch = "wasm32")]
+fn undo_clock() -> SharedClock {
+    struct BrowserClock;
+
+    impl mdcs_core::clock::Clock for BrowserClock {
+        fn now_millis(&self) -> u64 {
+            js_sys::Date::now() as u64
+        }
+    }
+
+    SharedClock::new(BrowserClock)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn undo_clock() -> SharedClock {
+    SharedClock::default()
+}
+
+/// Record a local insert for undo.
+fn record_insert(undo: &mut UndoManager, position: usize, text: &str) {
+    if !text.is_empty() {
+        undo.record(UndoableOperation::Text(TextOperation::Insert {
+            position,
+            text: text.to_string(),
+        }));
+    }
+}
+
+/// Apply an undo or redo operation if the text it removes is unchanged,
+/// noting the change it made.
+fn apply_undo(text: &mut RichText, op: &UndoableOperation, changes: &mut Vec<Change>) -> bool {
+    let UndoableOperation::Text(op) = op else {
+        return false;
+    };
+    let (position, deleted, inserted) = match op {
+        TextOperation::Insert { position, text } => (*position, "", text.as_str()),
+        TextOperation::Delete { position, deleted } => (*position, deleted.as_str(), ""),
+        TextOperation::Replace {
+            position,
+            deleted,
+            inserted,
+        } => (*position, deleted.as_str(), inserted.as_str()),
+    };
+    let length = deleted.chars().count();
+    if position + length > text.len() || text.text().slice(position, position + length) != deleted {
+        return false;
+    }
+    if length > 0 {
+        text.delete(position, length);
+        changes.push(Change::Delete { position, length });
+    }
+    if !inserted.is_empty() {
+        text.insert(position, inserted);
+        changes.push(Change::Insert {
+            position,
+            text: inserted.to_string(),
+        });
+    }
+    true
+}
+
+/// A numbered document change, as passed to `on_change`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ChangeEvent {
+    seq: u64,
+    #[serde(flatten)]
+    change: Change,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Change {
+    Insert {
+        position: usize,
+        text: String,
+    },
+    Delete {
+        position: usize,
+        length: usize,
+    },
+    Format {
+        start: usize,
+        end: usize,
+    },
+    Remote {
+        #[serde(default)]
+        edits: Vec<TextEdit>,
+    },
+}
+
+/// A run of text replaced by a merge: `deleted` characters at `position`
+/// gave way to `inserted` new ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct TextEdit {
+    position: usize,
+    deleted: usize,
+    inserted: usize,
+}
+
+impl TextEdit {
+    /// Map a position in the text before this edit to the text after it.
+    ///
+    /// A position at the edit stays in front of the inserted text unless
+    /// `after_insert` is set, and one inside the deleted run collapses to
+    /// its start.
+    fn map(&self, position: usize, after_insert: bool) -> usize {
+        if position < self.position {
+            position
+        } else if position == self.position {
+            if after_insert {
+                position + self.inserted
+            } else {
+                position
+            }
+        } else if position < self.position + self.deleted {
+            self.position
+        } else {
+            position - self.deleted + self.inserted
+        }
+    }
+}
+
+/// Map a position through edits, in order.
+fn map_position(position: usize, edits: &[TextEdit], after_insert: bool) -> usize {
+    edits
+        .iter()
+        .fold(position, |position, edit| edit.map(position, after_insert))
+}
+
+/// Collects the edits reported by `join_observed`.
+#[derive(Default)]
+struct EditRecorder(Vec<TextEdit>);
+
+impl TextObserver for EditRecorder {
+    fn on_insert(&mut self, position: usize, text: &str) {
+        let inserted = text.chars().count();
+        match self.0.last_mut() {
+            // A replacement is reported as a delete then an insert
+            Some(edit) if edit.position == position && edit.inserted == 0 => {
+                edit.inserted = inserted;
+            }
+            _ => self.0.push(TextEdit {
+                position,
+                deleted: 0,
+                inserted,
+            }),
+        }
+    }
+
+    fn on_delete(&mut self, position: usize, length: usize) {
+        self.0.push(TextEdit {
+            position,
+            deleted: length,
+            inserted: 0,
+        });
+    }
+}
+
+/// Result of `resync_events`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Resync {
+    Events(Vec<ChangeEvent>),
+    Snapshot { seq: u64, len: usize },
+}
+
+/// Sequence numbers from a replica a document hasn't seen, as returned
+/// by `missing_from`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct MissingRange {
+    replica_id: String,
+    from_seq: u64,
+    to_seq: u64,
+}
+
+/// A comment and the range it covers, as returned by `get_comments`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct Comment {
+    id: String,
+    author: String,
+    text: String,
+    start: usize,
+    end: usize,
+}
+
+fn version_vector_from_js(js: JsValue) -> Result<VersionVector, MdcsError> {
+    let entries: BTreeMap<String, u64> = serde_wasm_bindgen::from_value(js)
+        .map_err(|e| MdcsError::invalid_argument("version vector", e))?;
+    Ok(VersionVector::from_entries(entries))
+}
+
 /// Document snapshot for persistence/sync
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct DocumentSnapshot {
@@ -290,14 +1321,229 @@ struct DocumentSnapshot {
     state: String,
 }
 
+/// Where and how often a document saves itself, set by `enable_auto_save`.
+struct AutoSave {
+    db_name: String,
+    every_ops: u32,
+    unsaved: u32,
+    on_error: Option<js_sys::Function>,
+}
+
+/// Document state moved between threads by `export_transferable`.
+#[derive(Serialize, Deserialize)]
+struct TransferableState<'a> {
+    doc_id: Cow<'a, str>,
+    replica_id: Cow<'a, str>,
+    version: u64,
+    event_seq: u64,
+    text: Cow<'a, RichText>,
+}
+
+/// An edit on a document that has been handed to another thread.
+#[derive(Debug, Clone, PartialEq)]
+struct Transferred {
+    doc_id: String,
+}
+
+impl From<Transferred> for MdcsError {
+    fn from(error: Transferred) -> Self {
+        MdcsError::new(
+            "TRANSFERRED",
+            format!(
+                "Document {} was moved to another thread with export_transferable and can no longer be edited here",
+                error.doc_id
+            ),
+        )
+        .with_details(serde_json::json!({ "doc_id": error.doc_id }))
+    }
+}
+
+// ============================================================================
+// WorkerProtocol
+// ============================================================================
+
+/// Messages between a Web Worker that owns documents and the main thread.
+///
+/// The main thread encodes edits with `encode_op` and the worker applies
+/// them with `apply_op`; the worker encodes the events its documents pass
+/// to `on_change` with `encode_event` and the main thread reads them with
+/// `decode_event`. Messages are `Uint8Array`s, so their buffers can be
+/// transferred rather than copied by `postMessage`.
+#[wasm_bindgen]
+pub struct WorkerProtocol;
+
+#[wasm_bindgen]
+impl WorkerProtocol {
+    /// Encode an edit.
+    ///
+    /// `op` is `{ kind, ... }`: `"insert"` (with `position`, `text`),
+    /// `"delete"` (`position`, `length`), `"bold"`, `"italic"`,
+    /// `"underline"` or `"strikethrough"` (`start`, `end`), `"link"`
+    /// (`start`, `end`, `url`), `"set_block_type"` (`position`,
+    /// `block_type`) or `"merge"` (`state`, from `serialize()`).
+    #[wasm_bindgen]
+    pub fn encode_op(op: JsValue) -> Result<Vec<u8>, MdcsError> {
+        let op: Op =
+            serde_wasm_bindgen::from_value(op).map_err(|e| MdcsError::invalid_argument("op", e))?;
+        Ok(WorkerMessage::Op(op).encode())
+    }
+
+    /// Decode an edit from `encode_op`.
+    #[wasm_bindgen]
+    pub fn decode_op(bytes: &[u8]) -> Result<JsValue, MdcsError> {
+        let op = WorkerMessage::decode_op(bytes)?;
+        serde_wasm_bindgen::to_value(&op).map_err(MdcsError::serialization)
+    }
+
+    /// Apply an edit from `encode_op` to a document.
+    #[wasm_bindgen]
+    pub fn apply_op(doc: &mut CollaborativeDocument, bytes: &[u8]) -> Result<(), MdcsError> {
+        let op = WorkerMessage::decode_op(bytes)?;
+        doc.apply_op(op)
+    }
+
+    /// Encode a change event, as passed to `on_change`.
+    #[wasm_bindgen]
+    pub fn encode_event(event: JsValue) -> Result<Vec<u8>, MdcsError> {
+        let event: ChangeEvent = serde_wasm_bindgen::from_value(event)
+            .map_err(|e| MdcsError::invalid_argument("event", e))?;
+        Ok(WorkerMessage::Event(event).encode())
+    }
+
+    /// Decode a change event from `encode_event`.
+    #[wasm_bindgen]
+    pub fn decode_event(bytes: &[u8]) -> Result<JsValue, MdcsError> {
+        let event = WorkerMessage::decode_event(bytes)?;
+        serde_wasm_bindgen::to_value(&event).map_err(MdcsError::serialization)
+    }
+}
+
+/// Parse a block type name as `set_block_type` takes it.
+fn parse_block_type(name: &str) -> Result<BlockType, MdcsError> {
+    let block_type = match name {
+        "paragraph" => BlockType::Paragraph,
+        "bullet_list" => BlockType::BulletList,
+        "ordered_list" => BlockType::OrderedList,
+        "quote" => BlockType::Quote,
+        "code_block" => BlockType::CodeBlock,
+        _ => match name.strip_prefix("heading").and_then(|l| l.parse().ok()) {
+            Some(level @ 1..=6) => BlockType::Heading(level),
+            _ => {
+                return Err(MdcsError::invalid_argument(
+                    "block type",
+                    format!("unknown block type {:?}", name),
+                )
+                .with_details(serde_json::json!({ "block_type": name })))
+            }
+        },
+    };
+    Ok(block_type)
+}
+
+/// The name `set_block_type` takes for a block type.
+fn block_type_name(block_type: &BlockType) -> String {
+    match block_type {
+        BlockType::Paragraph => "paragraph".to_string(),
+        BlockType::Heading(level) => format!("heading{}", level),
+        BlockType::BulletList => "bullet_list".to_string(),
+        BlockType::OrderedList => "ordered_list".to_string(),
+        BlockType::Quote => "quote".to_string(),
+        BlockType::CodeBlock => "code_block".to_string(),
+    }
+}
+
+/// An edit, as encoded by `WorkerProtocol::encode_op`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Op {
+    Insert {
+        position: usize,
+        text: String,
+    },
+    Delete {
+        position: usize,
+        length: usize,
+    },
+    Bold {
+        start: usize,
+        end: usize,
+    },
+    Italic {
+        start: usize,
+        end: usize,
+    },
+    Underline {
+        start: usize,
+        end: usize,
+    },
+    Strikethrough {
+        start: usize,
+        end: usize,
+    },
+    Link {
+        start: usize,
+        end: usize,
+        url: String,
+    },
+    SetBlockType {
+        position: usize,
+        block_type: String,
+    },
+    Merge {
+        state: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum WorkerMessage {
+    Op(Op),
+    Event(ChangeEvent),
+}
+
+impl WorkerMessage {
+    fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("worker messages serialize")
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, MdcsError> {
+        serde_json::from_slice(bytes).map_err(|e| {
+            MdcsError::new(
+                "DESERIALIZATION_ERROR",
+                format!("Malformed worker message: {}", e),
+            )
+        })
+    }
+
+    fn decode_op(bytes: &[u8]) -> Result<Op, MdcsError> {
+        match Self::decode(bytes)? {
+            WorkerMessage::Op(op) => Ok(op),
+            WorkerMessage::Event(_) => Err(MdcsError::new(
+                "DESERIALIZATION_ERROR",
+                "Expected an op message, got an event",
+            )),
+        }
+    }
+
+    fn decode_event(bytes: &[u8]) -> Result<ChangeEvent, MdcsError> {
+        match Self::decode(bytes)? {
+            WorkerMessage::Event(event) => Ok(event),
+            WorkerMessage::Op(_) => Err(MdcsError::new(
+                "DESERIALIZATION_ERROR",
+                "Expected an event message, got an op",
+            )),
+        }
+    }
+}
+
 // ============================================================================
 // UserPresence
 // ============================================================================
 
 /// User presence information for collaborative UI.
 ///
-/// Tracks cursor position, selection, and user metadata for
-/// rendering remote user cursors.
+/// Tracks cursor position, selection, viewport, and user metadata for
+/// rendering remote user cursors and following collaborators.
 #[wasm_bindgen]
 pub struct UserPresence {
     user_id: String,
@@ -306,6 +1552,12 @@ pub struct UserPresence {
     cursor_position: Option<usize>,
     selection_start: Option<usize>,
     selection_end: Option<usize>,
+    json_location: Option<String>,
+    viewport_start: Option<usize>,
+    viewport_end: Option<usize>,
+    following: Option<String>,
+    follow_viewport: Option<(usize, usize)>,
+    follow_callback: Option<js_sys::Function>,
 }
 
 #[wasm_bindgen]
@@ -325,6 +1577,12 @@ impl UserPresence {
             cursor_position: None,
             selection_start: None,
             selection_end: None,
+            json_location: None,
+            viewport_start: None,
+            viewport_end: None,
+            following: None,
+            follow_viewport: None,
+            follow_callback: None,
         }
     }
 
@@ -334,6 +1592,7 @@ impl UserPresence {
         self.cursor_position = Some(position);
         self.selection_start = None;
         self.selection_end = None;
+        self.json_location = None;
     }
 
     /// Set selection range.
@@ -342,14 +1601,40 @@ impl UserPresence {
         self.cursor_position = Some(end);
         self.selection_start = Some(start.min(end));
         self.selection_end = Some(start.max(end));
+        self.json_location = None;
+    }
+
+    /// Set the location in a JSON document as a dot path (e.g.
+    /// `"columns.0.cards.2"`). Clears the text cursor and selection.
+    #[wasm_bindgen]
+    pub fn set_json_location(&mut self, path: &str) {
+        self.cursor_position = None;
+        self.selection_start = None;
+        self.selection_end = None;
+        self.json_location = Some(JsonPath::parse(path).to_string());
+    }
+
+    /// Get the JSON location.
+    #[wasm_bindgen(getter)]
+    pub fn json_location(&self) -> Option<String> {
+        self.json_location.clone()
+    }
+
+    /// Check if the JSON location is at or under `path`.
+    #[wasm_bindgen]
+    pub fn is_at_json_path(&self, path: &str) -> bool {
+        self.json_location
+            .as_deref()
+            .is_some_and(|at| JsonPath::parse(at).starts_with(&JsonPath::parse(path)))
     }
 
-    /// Clear cursor and selection.
+    /// Clear cursor, selection and JSON location.
     #[wasm_bindgen]
     pub fn clear(&mut self) {
         self.cursor_position = None;
         self.selection_start = None;
         self.selection_end = None;
+        self.json_location = None;
     }
 
     /// Get user ID.
@@ -394,9 +1679,113 @@ impl UserPresence {
         self.selection_start.is_some() && self.selection_end.is_some()
     }
 
+    /// Move the cursor, selection and viewport to where they are after
+    /// remote edits.
+    ///
+    /// Text inserted right at the cursor goes after it; a cursor inside
+    /// deleted text moves to where the deletion started, and a selection
+    /// shrinks by whatever part of it was deleted.
+    ///
+    /// # Arguments
+    /// * `edits` - Edits returned by `merge` or `apply_delta`, or carried by
+    ///   a `"remote"` change event
+    #[wasm_bindgen]
+    pub fn transform(&mut self, edits: JsValue) -> Result<(), MdcsError> {
+        let edits: Vec<TextEdit> = serde_wasm_bindgen::from_value(edits)
+            .map_err(|e| MdcsError::invalid_argument("edits", e))?;
+        self.transform_edits(&edits);
+        Ok(())
+    }
+
+    /// Set the visible range of the document.
+    #[wasm_bindgen]
+    pub fn set_viewport(&mut self, start: usize, end: usize) {
+        self.viewport_start = Some(start.min(end));
+        self.viewport_end = Some(start.max(end));
+    }
+
+    /// Get viewport start.
+    #[wasm_bindgen(getter)]
+    pub fn viewport_start(&self) -> Option<usize> {
+        self.viewport_start
+    }
+
+    /// Get viewport end.
+    #[wasm_bindgen(getter)]
+    pub fn viewport_end(&self) -> Option<usize> {
+        self.viewport_end
+    }
+
+    /// Follow another user's viewport.
+    ///
+    /// Remote presence passed to `receive_remote` for that user triggers
+    /// the `on_follow_update` callback.
+    #[wasm_bindgen]
+    pub fn follow(&mut self, user_id: &str) {
+        self.unfollow();
+        self.following = Some(user_id.to_string());
+    }
+
+    /// Stop following. The callback receives `null`.
+    #[wasm_bindgen]
+    pub fn unfollow(&mut self) {
+        if self.following.take().is_some() {
+            self.follow_viewport = None;
+            self.notify_follow(JsValue::NULL);
+        }
+    }
+
+    /// Get the user being followed.
+    #[wasm_bindgen(getter)]
+    pub fn following(&self) -> Option<String> {
+        self.following.clone()
+    }
+
+    /// Register a callback for follow updates.
+    ///
+    /// Called with `{ user_id, start, end }` when the followed user's
+    /// viewport changes, and with `null` when the follow ends.
+    #[wasm_bindgen]
+    pub fn on_follow_update(&mut self, callback: js_sys::Function) {
+        self.follow_callback = Some(callback);
+    }
+
+    /// Feed a remote user's presence (as produced by `to_json`).
+    ///
+    /// Returns `true` if it produced a follow update.
+    #[wasm_bindgen]
+    pub fn receive_remote(&mut self, js: JsValue) -> Result<bool, MdcsError> {
+        let data: PresenceData = serde_wasm_bindgen::from_value(js)
+            .map_err(|e| MdcsError::invalid_argument("presence", e))?;
+
+        match self.observe_remote(&data) {
+            Some((start, end)) => {
+                let update = FollowUpdate {
+                    user_id: data.user_id,
+                    start,
+                    end,
+                };
+                let value =
+                    serde_wasm_bindgen::to_value(&update).map_err(MdcsError::serialization)?;
+                self.notify_follow(value);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Notify that a remote user disconnected. Ends the follow if they
+    /// were the target.
+    #[wasm_bindgen]
+    pub fn remote_left(&mut self, user_id: &str) {
+        if self.following.as_deref() == Some(user_id) {
+            self.unfollow();
+        }
+    }
+
     /// Serialize to JSON for network transmission.
     #[wasm_bindgen]
-    pub fn to_json(&self) -> Result<JsValue, JsValue> {
+    pub fn to_json(&self) -> Result<JsValue, MdcsError> {
         let data = PresenceData {
             user_id: self.user_id.clone(),
             user_name: self.user_name.clone(),
@@ -404,15 +1793,18 @@ impl UserPresence {
             cursor: self.cursor_position,
             selection_start: self.selection_start,
             selection_end: self.selection_end,
+            json_location: self.json_location.clone(),
+            viewport_start: self.viewport_start,
+            viewport_end: self.viewport_end,
         };
-        serde_wasm_bindgen::to_value(&data).map_err(|e| JsValue::from_str(&e.to_string()))
+        serde_wasm_bindgen::to_value(&data).map_err(MdcsError::serialization)
     }
 
     /// Deserialize from JSON.
     #[wasm_bindgen]
-    pub fn from_json(js: JsValue) -> Result<UserPresence, JsValue> {
-        let data: PresenceData =
-            serde_wasm_bindgen::from_value(js).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    pub fn from_json(js: JsValue) -> Result<UserPresence, MdcsError> {
+        let data: PresenceData = serde_wasm_bindgen::from_value(js)
+            .map_err(|e| MdcsError::invalid_argument("presence", e))?;
 
         Ok(Self {
             user_id: data.user_id,
@@ -421,10 +1813,49 @@ impl UserPresence {
             cursor_position: data.cursor,
             selection_start: data.selection_start,
             selection_end: data.selection_end,
+            json_location: data.json_location,
+            viewport_start: data.viewport_start,
+            viewport_end: data.viewport_end,
+            following: None,
+            follow_viewport: None,
+            follow_callback: None,
         })
     }
 }
 
+impl UserPresence {
+    fn transform_edits(&mut self, edits: &[TextEdit]) {
+        for position in [
+            &mut self.cursor_position,
+            &mut self.selection_start,
+            &mut self.selection_end,
+            &mut self.viewport_start,
+            &mut self.viewport_end,
+        ] {
+            *position = position.map(|p| map_position(p, edits, false));
+        }
+    }
+
+    /// Track a followed user's viewport, returning it if it changed.
+    fn observe_remote(&mut self, data: &PresenceData) -> Option<(usize, usize)> {
+        if self.following.as_deref() != Some(data.user_id.as_str()) {
+            return None;
+        }
+        let viewport = data.viewport_start.zip(data.viewport_end)?;
+        if self.follow_viewport == Some(viewport) {
+            return None;
+        }
+        self.follow_viewport = Some(viewport);
+        Some(viewport)
+    }
+
+    fn notify_follow(&self, value: JsValue) {
+        if let Some(callback) = &self.follow_callback {
+            let _ = callback.call1(&JsValue::NULL, &value);
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct PresenceData {
     user_id: String,
@@ -433,6 +1864,19 @@ struct PresenceData {
     cursor: Option<usize>,
     selection_start: Option<usize>,
     selection_end: Option<usize>,
+    #[serde(default)]
+    json_location: Option<String>,
+    #[serde(default)]
+    viewport_start: Option<usize>,
+    #[serde(default)]
+    viewport_end: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct FollowUpdate {
+    user_id: String,
+    start: usize,
+    end: usize,
 }
 
 // ============================================================================
@@ -460,6 +1904,24 @@ pub fn generate_user_color() -> String {
     colors[idx % colors.len()].to_string()
 }
 
+/// Map a character position through the edits of a merge.
+///
+/// # Arguments
+/// * `position` - Character index before the edits
+/// * `edits` - Edits returned by `merge` or `apply_delta`
+/// * `after_insert` - Move past text inserted right at `position` instead
+///   of staying in front of it
+#[wasm_bindgen]
+pub fn transform_position(
+    position: usize,
+    edits: JsValue,
+    after_insert: bool,
+) -> Result<usize, MdcsError> {
+    let edits: Vec<TextEdit> = serde_wasm_bindgen::from_value(edits)
+        .map_err(|e| MdcsError::invalid_argument("edits", e))?;
+    Ok(map_position(position, &edits, after_insert))
+}
+
 /// Log a message to the browser console.
 #[wasm_bindgen]
 pub fn console_log(message: &str) {
@@ -473,6 +1935,8 @@ pub fn console_log(message: &str) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use mdcs_core::lattice::Lattice;
+    use mdcs_db::DbError;
 
     #[test]
     fn test_document_creation() {
@@ -487,11 +1951,11 @@ mod tests {
     fn test_insert_and_delete() {
         let mut doc = CollaborativeDocument::new("doc-1", "replica-1");
 
-        doc.insert(0, "Hello, World!");
+        doc.insert(0, "Hello, World!").unwrap();
         assert_eq!(doc.get_text(), "Hello, World!");
         assert_eq!(doc.len(), 13);
 
-        doc.delete(5, 2); // Delete ", "
+        doc.delete(5, 2).unwrap(); // Delete ", "
         assert_eq!(doc.get_text(), "HelloWorld!");
     }
 
@@ -499,9 +1963,9 @@ mod tests {
     fn test_formatting() {
         let mut doc = CollaborativeDocument::new("doc-1", "replica-1");
 
-        doc.insert(0, "Hello World");
-        doc.apply_bold(0, 5);
-        doc.apply_italic(6, 11);
+        doc.insert(0, "Hello World").unwrap();
+        doc.apply_bold(0, 5).unwrap();
+        doc.apply_italic(6, 11).unwrap();
 
         let html = doc.get_html();
         assert!(html.contains("<b>") || html.contains("<strong>"));
@@ -518,8 +1982,8 @@ mod tests {
         let mut doc1 = CollaborativeDocument::new("doc-1", "replica-1");
         let mut doc2 = CollaborativeDocument::new("doc-1", "replica-2");
 
-        doc1.insert(0, "Hello");
-        doc2.insert(0, "World");
+        doc1.insert(0, "Hello").unwrap();
+        doc2.insert(0, "World").unwrap();
 
         // Use the Lattice join directly (no JSON serialization needed)
         let text1_clone = doc1.text.clone();
@@ -535,6 +1999,30 @@ mod tests {
         assert!(final_text.contains("Hello") || final_text.contains("World"));
     }
 
+    #[test]
+    fn test_missing_ranges() {
+        let mut doc1 = CollaborativeDocument::new("doc-1", "replica-1");
+        let mut doc2 = CollaborativeDocument::new("doc-1", "replica-2");
+        doc1.insert(0, "Hello").unwrap();
+        let before = doc1.text.version_vector();
+
+        doc2.text = doc2.text.join(&doc1.text);
+        doc2.insert(5, "!").unwrap();
+        assert!(doc2.text.version_vector().strictly_dominates(&before));
+        assert!(doc2.missing(&before).is_empty());
+
+        doc1.insert(0, ">").unwrap();
+        assert_eq!(
+            doc1.missing(&doc2.text.version_vector()),
+            vec![MissingRange {
+                replica_id: "replica-2".to_string(),
+                from_seq: 1,
+                to_seq: 6
+            }]
+        );
+        assert_eq!(doc2.missing(&doc1.text.version_vector())[0].from_seq, 6);
+    }
+
     #[test]
     fn test_user_presence() {
         let mut presence = UserPresence::new("user-1", "Alice", "#FF6B6B");
@@ -552,4 +2040,531 @@ mod tests {
         assert_eq!(presence.selection_start(), Some(5));
         assert_eq!(presence.selection_end(), Some(15));
     }
+
+    #[test]
+    fn test_json_location() {
+        let mut presence = UserPresence::new("user-1", "Alice", "#FF6B6B");
+        presence.set_cursor(10);
+
+        presence.set_json_location("columns.0.cards.2");
+        assert_eq!(
+            presence.json_location().as_deref(),
+            Some("columns.0.cards.2")
+        );
+        assert_eq!(presence.cursor(), None);
+        assert!(presence.is_at_json_path("columns.0"));
+        assert!(presence.is_at_json_path(""));
+        assert!(!presence.is_at_json_path("columns.1"));
+        assert!(!presence.is_at_json_path("columns.0.cards.20"));
+
+        presence.set_cursor(3);
+        assert_eq!(presence.json_location(), None);
+    }
+
+    fn remote_data(user_id: &str, start: usize, end: usize) -> PresenceData {
+        PresenceData {
+            user_id: user_id.to_string(),
+            user_name: "Alice".to_string(),
+            color: "#FF6B6B".to_string(),
+            cursor: None,
+            selection_start: None,
+            selection_end: None,
+            json_location: None,
+            viewport_start: Some(start),
+            viewport_end: Some(end),
+        }
+    }
+
+    #[test]
+    fn test_follow_viewport() {
+        let mut presence = UserPresence::new("user-2", "Bob", "#4ECDC4");
+        presence.set_viewport(40, 0);
+        assert_eq!(presence.viewport_start(), Some(0));
+        assert_eq!(presence.viewport_end(), Some(40));
+
+        // Not following yet
+        assert_eq!(presence.observe_remote(&remote_data("user-1", 0, 40)), None);
+
+        presence.follow("user-1");
+        assert_eq!(
+            presence.observe_remote(&remote_data("user-1", 0, 40)),
+            Some((0, 40))
+        );
+        // Unchanged viewport does not re-notify
+        assert_eq!(presence.observe_remote(&remote_data("user-1", 0, 40)), None);
+        // Other users are ignored
+        assert_eq!(presence.observe_remote(&remote_data("user-3", 5, 9)), None);
+        assert_eq!(
+            presence.observe_remote(&remote_data("user-1", 40, 80)),
+            Some((40, 80))
+        );
+
+        presence.remote_left("user-1");
+        assert_eq!(presence.following(), None);
+        assert_eq!(
+            presence.observe_remote(&remote_data("user-1", 80, 120)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_change_events_resync() {
+        let mut doc = CollaborativeDocument::new("doc-1", "replica-1");
+        doc.set_event_history(3);
+        doc.insert(0, "Hello World").unwrap();
+        doc.apply_bold(0, 5).unwrap();
+        doc.delete(5, 6).unwrap();
+        // Out-of-range edits change nothing and emit nothing
+        doc.delete(10, 3).unwrap();
+        assert_eq!(doc.event_seq(), 3);
+
+        assert_eq!(
+            doc.replay(2),
+            Resync::Events(vec![
+                ChangeEvent {
+                    seq: 2,
+                    change: Change::Format { start: 0, end: 5 }
+                },
+                ChangeEvent {
+                    seq: 3,
+                    change: Change::Delete {
+                        position: 5,
+                        length: 6
+                    }
+                },
+            ])
+        );
+        assert_eq!(doc.replay(4), Resync::Events(Vec::new()));
+
+        doc.insert(5, "!").unwrap();
+        assert_eq!(doc.replay(1), Resync::Snapshot { seq: 4, len: 6 });
+        assert!(matches!(doc.replay(2), Resync::Events(events) if events.len() == 3));
+    }
+
+    #[test]
+    fn test_transferable_round_trip() {
+        let mut doc = CollaborativeDocument::new("doc-1", "replica-1");
+        let mut other = CollaborativeDocument::new("doc-1", "replica-2");
+        doc.insert(0, "Hello World").unwrap();
+        doc.apply_bold(0, 5).unwrap();
+        other.insert(0, ">> ").unwrap();
+        doc.text = doc.text.join(&other.text);
+
+        let bytes = doc.export_transferable().unwrap();
+        assert!(doc.is_transferred());
+        assert_eq!(
+            doc.ensure_live(),
+            Err(Transferred {
+                doc_id: "doc-1".to_string()
+            })
+        );
+
+        let mut imported = CollaborativeDocument::from_transferable(&bytes).unwrap();
+        assert!(!imported.is_transferred());
+        assert_eq!(imported.doc_id(), "doc-1");
+        assert_eq!(imported.replica_id(), "replica-1");
+        assert_eq!(imported.get_html(), doc.get_html());
+        assert_eq!(imported.version(), doc.version());
+        assert_eq!(imported.event_seq(), doc.event_seq());
+        assert_eq!(imported.text.version_vector(), doc.text.version_vector());
+
+        imported.insert(0, "!").unwrap();
+        assert_eq!(imported.event_seq(), 3);
+        assert_eq!(imported.get_text(), format!("!{}", doc.get_text()));
+    }
+
+    #[test]
+    fn test_apply_text_diff() {
+        let mut doc = CollaborativeDocument::new("doc-1", "replica-1");
+        assert_eq!(doc.apply_text_diff("Hello World").unwrap(), 1);
+        assert_eq!(doc.apply_text_diff("Hello World").unwrap(), 0);
+
+        // Replacement in the middle: one delete, one insert, undone together
+        assert_eq!(doc.apply_text_diff("Hello Rust World").unwrap(), 1);
+        assert_eq!(doc.apply_text_diff("Hello Wasm World").unwrap(), 2);
+        assert_eq!(
+            doc.history.back().unwrap().change,
+            Change::Insert {
+                position: 6,
+                text: "Wasm".to_string()
+            }
+        );
+        doc.undo().unwrap();
+        assert_eq!(doc.get_text(), "Hello Rust World");
+
+        // Full clear
+        assert_eq!(doc.apply_text_diff("").unwrap(), 1);
+        assert!(doc.is_empty());
+    }
+
+    #[test]
+    fn test_apply_text_diff_emoji() {
+        let mut doc = CollaborativeDocument::new("doc-1", "replica-1");
+        doc.apply_text_diff("a😀b").unwrap();
+        assert_eq!(doc.len(), 3);
+
+        // Swapping one emoji for another sharing its high surrogate only
+        // replaces that character
+        assert_eq!(doc.apply_text_diff("a😃b").unwrap(), 2);
+        assert_eq!(
+            doc.history.back().unwrap().change,
+            Change::Insert {
+                position: 1,
+                text: "😃".to_string()
+            }
+        );
+        doc.apply_text_diff("a😃👍🏽b").unwrap();
+        assert_eq!(doc.get_text(), "a😃👍🏽b");
+
+        // "a😃👍🏽" is 7 UTF-16 code units but 4 characters
+        assert_eq!(doc.position_to_utf16(4), 7);
+        assert_eq!(doc.utf16_to_position(7), 4);
+        assert_eq!(doc.utf16_to_position(2), 1);
+        assert_eq!(doc.utf16_to_position(100), doc.len());
+    }
+
+    #[test]
+    fn test_apply_text_diff_concurrently() {
+        let mut alice = CollaborativeDocument::new("doc-1", "alice");
+        let mut bob = CollaborativeDocument::new("doc-1", "bob");
+        alice.apply_text_diff("The quick fox").unwrap();
+        bob.apply_encoded_delta(&alice.take_delta().unwrap().unwrap())
+            .unwrap();
+
+        alice.apply_text_diff("The quick brown fox").unwrap();
+        bob.apply_text_diff("The slow fox!").unwrap();
+        let from_alice = alice.take_delta().unwrap().unwrap();
+        let from_bob = bob.take_delta().unwrap().unwrap();
+        alice.apply_encoded_delta(&from_bob).unwrap();
+        bob.apply_encoded_delta(&from_alice).unwrap();
+
+        // Both edits survive, whatever order the inserts land in
+        let text = alice.get_text();
+        assert_eq!(bob.get_text(), text);
+        assert!(text.contains("slow") && text.contains("brown") && text.ends_with("fox!"));
+        assert!(!text.contains("quick"));
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let mut doc = CollaborativeDocument::new("doc-1", "replica-1");
+        let error = doc.apply_delta("{").unwrap_err();
+        assert_eq!(error.code(), "DESERIALIZATION_ERROR");
+        assert!(error.message().starts_with("Deserialization error: "));
+
+        let error = WorkerProtocol::decode_op(b"{\"event\":{}}").unwrap_err();
+        assert_eq!(error.code(), "DESERIALIZATION_ERROR");
+
+        doc.export_transferable().unwrap();
+        let error = doc.insert(0, "x").unwrap_err();
+        assert_eq!(error.code(), "TRANSFERRED");
+        assert_eq!(
+            error.to_string(),
+            format!("TRANSFERRED: {}", error.message())
+        );
+
+        let error = MdcsError::from(DbError::IndexOutOfBounds {
+            index: 7,
+            length: 3,
+        });
+        assert_eq!(error.code(), "INDEX_OUT_OF_BOUNDS");
+        assert_eq!(error.message(), "Invalid index: 7 (length: 3)");
+    }
+
+    #[test]
+    fn test_delta_sync() {
+        let mut doc1 = CollaborativeDocument::new("doc-1", "replica-1");
+        let mut doc2 = CollaborativeDocument::new("doc-1", "replica-2");
+        assert_eq!(doc1.take_delta().unwrap(), None);
+
+        doc1.insert(0, "Hello World").unwrap();
+        doc1.apply_bold(0, 5).unwrap();
+        let delta = doc1.take_delta().unwrap().unwrap();
+        assert_eq!(doc1.take_delta().unwrap(), None);
+
+        // Applying a delta twice changes nothing
+        let edits = doc2.apply_encoded_delta(&delta).unwrap();
+        assert_eq!(edits, [edit(0, 0, 11)]);
+        assert!(doc2.apply_encoded_delta(&delta).unwrap().is_empty());
+        assert_eq!(doc2.get_html(), "<strong>Hello</strong> World");
+        assert_eq!(
+            doc2.history.back().unwrap().change,
+            Change::Remote { edits: Vec::new() }
+        );
+
+        // Concurrent edits exchanged as deltas converge
+        doc1.insert(11, "!").unwrap();
+        doc2.delete(0, 1).unwrap();
+        let delta1 = doc1.take_delta().unwrap().unwrap();
+        let delta2 = doc2.take_delta().unwrap().unwrap();
+        doc1.apply_encoded_delta(&delta2).unwrap();
+        doc2.apply_encoded_delta(&delta1).unwrap();
+        assert_eq!(doc1.get_text(), "ello World!");
+        assert_eq!(doc1.get_html(), doc2.get_html());
+        assert!(doc1.apply_encoded_delta("{").is_err());
+    }
+
+    fn edit(position: usize, deleted: usize, inserted: usize) -> TextEdit {
+        TextEdit {
+            position,
+            deleted,
+            inserted,
+        }
+    }
+
+    #[test]
+    fn test_map_position_edge_cases() {
+        // Insertion at the position stays after it unless asked otherwise
+        let insert = [edit(3, 0, 2)];
+        assert_eq!(map_position(2, &insert, false), 2);
+        assert_eq!(map_position(3, &insert, false), 3);
+        assert_eq!(map_position(3, &insert, true), 5);
+        assert_eq!(map_position(4, &insert, false), 6);
+
+        // Inside a deletion collapses to its start
+        let delete = [edit(2, 4, 0)];
+        assert_eq!(map_position(2, &delete, false), 2);
+        assert_eq!(map_position(4, &delete, true), 2);
+        assert_eq!(map_position(6, &delete, false), 2);
+        assert_eq!(map_position(9, &delete, false), 5);
+
+        // Later edits are relative to the earlier ones
+        let both = [edit(0, 1, 0), edit(3, 0, 1)];
+        assert_eq!(map_position(5, &both, false), 5);
+    }
+
+    #[test]
+    fn test_remote_edits_transform_cursors() {
+        let mut doc1 = CollaborativeDocument::new("doc-1", "replica-1");
+        doc1.insert(0, "Hello brave new world").unwrap();
+        let mut doc2 = CollaborativeDocument::new("doc-1", "replica-2");
+        doc2.merge_text(&doc1.text);
+
+        let mut cursor = UserPresence::new("user-1", "Alice", "#FF6B6B");
+        cursor.set_cursor(16);
+        let mut at_insert = UserPresence::new("user-2", "Bob", "#4ECDC4");
+        at_insert.set_cursor(6);
+        let mut in_deletion = UserPresence::new("user-3", "Carol", "#45B7D1");
+        in_deletion.set_cursor(8);
+        let mut selection = UserPresence::new("user-4", "Dan", "#96CEB4");
+        selection.set_selection(4, 18);
+
+        // Concurrently, doc1 deletes "brave " and doc2 inserts "so "
+        doc1.delete(6, 6).unwrap();
+        doc2.insert(6, "so ").unwrap();
+        let edits = doc2.merge_text(&doc1.text);
+        assert_eq!(edits, [edit(9, 6, 0)]);
+        assert_eq!(doc2.get_text(), "Hello so new world");
+
+        // doc1's users see doc2's insertion
+        let edits = doc1.merge_text(&doc2.text);
+        assert_eq!(edits, [edit(6, 0, 3)]);
+        assert_eq!(doc1.get_text(), doc2.get_text());
+        assert_eq!(
+            doc1.history.back().unwrap().change,
+            Change::Remote {
+                edits: edits.clone()
+            }
+        );
+
+        // Positions from before either edit go through both
+        let both = [edit(6, 6, 0), edit(6, 0, 3)];
+        for presence in [
+            &mut cursor,
+            &mut at_insert,
+            &mut in_deletion,
+            &mut selection,
+        ] {
+            presence.transform_edits(&both);
+        }
+        assert_eq!(cursor.cursor(), Some(13));
+        assert_eq!(&doc2.get_text()[13..], "world");
+        assert_eq!(at_insert.cursor(), Some(6));
+        assert_eq!(in_deletion.cursor(), Some(6));
+        assert_eq!(selection.selection_start(), Some(4));
+        assert_eq!(selection.selection_end(), Some(15));
+        assert_eq!(&doc2.get_text()[4..15], "o so new wo");
+    }
+
+    #[test]
+    fn test_undo_leaves_remote_edits() {
+        let mut doc1 = CollaborativeDocument::new("doc-1", "replica-1");
+        let mut doc2 = CollaborativeDocument::new("doc-1", "replica-2");
+        doc1.insert(0, "world").unwrap();
+        doc2.apply_encoded_delta(&doc1.take_delta().unwrap().unwrap())
+            .unwrap();
+
+        // doc2 types in front while doc1 types at the end
+        doc2.insert(0, "Hello ").unwrap();
+        doc1.insert(5, "!").unwrap();
+        let delta1 = doc1.take_delta().unwrap().unwrap();
+        let delta2 = doc2.take_delta().unwrap().unwrap();
+        doc1.apply_encoded_delta(&delta2).unwrap();
+        doc2.apply_encoded_delta(&delta1).unwrap();
+
+        assert!(doc1.undo().unwrap());
+        assert_eq!(doc1.get_text(), "Hello world");
+        assert_eq!(
+            doc1.history.back().unwrap().change,
+            Change::Delete {
+                position: 11,
+                length: 1
+            }
+        );
+        assert!(doc1.undo().unwrap());
+        assert!(!doc1.undo().unwrap());
+        doc2.apply_encoded_delta(&doc1.take_delta().unwrap().unwrap())
+            .unwrap();
+        assert_eq!(doc1.get_text(), "Hello ");
+        assert_eq!(doc2.get_text(), doc1.get_text());
+
+        // Redo, then a new edit drops the rest
+        assert!(doc1.redo().unwrap());
+        assert_eq!(doc1.get_text(), "Hello world");
+        doc1.insert(0, ">").unwrap();
+        assert!(!doc1.can_redo());
+
+        // A burst of typing undoes at once
+        doc1.set_undo_group_window(60_000);
+        doc1.insert(12, "s").unwrap();
+        doc1.insert(13, "!").unwrap();
+        assert!(doc1.undo().unwrap());
+        assert_eq!(doc1.get_text(), ">Hello world");
+    }
+
+    #[test]
+    fn test_paste_html() {
+        let mut doc = CollaborativeDocument::new("doc-1", "replica-1");
+        doc.insert(0, "ab").unwrap();
+
+        let report = doc.paste(1, r#"<b onclick="x()">bold</b><script>alert(1)</script>"#);
+        assert_eq!(doc.get_text(), "aboldb");
+        assert_eq!(doc.get_html(), "a<strong>bold</strong>b");
+        assert_eq!(report.removed_attrs["onclick"], 1);
+        assert_eq!(report.removed_tags["script"], 1);
+        assert_eq!(
+            doc.replay(2),
+            Resync::Events(vec![
+                ChangeEvent {
+                    seq: 2,
+                    change: Change::Insert {
+                        position: 1,
+                        text: "bold".to_string()
+                    }
+                },
+                ChangeEvent {
+                    seq: 3,
+                    change: Change::Format { start: 1, end: 5 }
+                },
+            ])
+        );
+
+        // Nothing left after sanitizing changes nothing
+        doc.paste(0, "<script>alert(1)</script>");
+        assert_eq!(doc.event_seq(), 3);
+    }
+
+    #[test]
+    fn test_comments() {
+        let mut doc1 = CollaborativeDocument::new("doc-1", "replica-1");
+        doc1.insert(0, "Hello World").unwrap();
+        assert_eq!(doc1.add_comment(3, 3, "alice", "empty").unwrap(), None);
+        let id = doc1
+            .add_comment(6, 11, "alice", "Which world?")
+            .unwrap()
+            .unwrap();
+        assert!(doc1
+            .get_html()
+            .contains(&format!("data-comment-id=\"{}\"", id)));
+
+        // The comment reaches the other replica, and so does an edit inside it
+        let mut doc2 = CollaborativeDocument::new("doc-1", "replica-2");
+        doc2.apply_encoded_delta(&doc1.encode_delta().unwrap().unwrap())
+            .unwrap();
+        doc1.insert(8, "-").unwrap();
+        doc2.apply_encoded_delta(&doc1.encode_delta().unwrap().unwrap())
+            .unwrap();
+        assert_eq!(doc2.get_text(), "Hello Wo-rld");
+        let expected = vec![Comment {
+            id: id.clone(),
+            author: "alice".to_string(),
+            text: "Which world?".to_string(),
+            start: 6,
+            end: 12,
+        }];
+        assert_eq!(doc1.comments(), expected);
+        assert_eq!(doc2.comments(), expected);
+
+        let seq = doc1.event_seq();
+        assert!(!doc1.remove_comment("replica-1:nope").unwrap());
+        assert!(doc1.remove_comment(&id).unwrap());
+        assert!(doc1.comments().is_empty());
+        assert!(!doc1.remove_comment(&id).unwrap());
+        assert_eq!(
+            doc1.replay(seq + 1),
+            Resync::Events(vec![ChangeEvent {
+                seq: seq + 1,
+                change: Change::Format { start: 6, end: 12 }
+            }])
+        );
+    }
+
+    #[test]
+    fn test_block_types() {
+        let mut a = CollaborativeDocument::new("doc-1", "a");
+        a.insert(0, "Title\nitem").unwrap();
+        a.set_block_type(0, "heading1").unwrap();
+        a.apply_op(Op::SetBlockType {
+            position: 8,
+            block_type: "bullet_list".to_string(),
+        })
+        .unwrap();
+        assert_eq!(a.get_html(), "<h1>Title</h1><ul><li>item</li></ul>");
+        assert_eq!(a.get_block_type(2), "heading1");
+        assert_eq!(
+            a.history.back().unwrap().change,
+            Change::Format { start: 6, end: 10 }
+        );
+
+        let error = a.set_block_type(0, "heading7").unwrap_err();
+        assert_eq!(error.code(), "INVALID_ARGUMENT");
+
+        let mut b = CollaborativeDocument::new("doc-1", "b");
+        b.merge_text(&a.text);
+        a.set_block_type(0, "quote").unwrap();
+        b.set_block_type(0, "heading2").unwrap();
+        let a_text = a.text.clone();
+        a.merge_text(&b.text);
+        b.merge_text(&a_text);
+        assert_eq!(a.get_html(), b.get_html());
+        assert_eq!(b.get_block_type(0), "heading2");
+    }
+
+    #[test]
+    fn test_worker_messages() {
+        let op = Op::Link {
+            start: 0,
+            end: 5,
+            url: "https://example.com".to_string(),
+        };
+        let bytes = WorkerMessage::Op(op.clone()).encode();
+        assert_eq!(WorkerMessage::decode_op(&bytes), Ok(op));
+        assert!(WorkerMessage::decode_event(&bytes).is_err());
+
+        let mut doc = CollaborativeDocument::new("doc-1", "replica-1");
+        doc.apply_op(Op::Insert {
+            position: 0,
+            text: "Hello".to_string(),
+        })
+        .unwrap();
+        doc.apply_op(Op::Italic { start: 1, end: 3 }).unwrap();
+        assert_eq!(doc.get_text(), "Hello");
+        let event = doc.history.back().cloned().unwrap();
+        assert_eq!(event.change, Change::Format { start: 1, end: 3 });
+
+        let bytes = WorkerMessage::Event(event.clone()).encode();
+        assert_eq!(WorkerMessage::decode_event(&bytes), Ok(event));
+        assert!(WorkerMessage::decode_op(&bytes).is_err());
+        assert!(WorkerMessage::decode_op(b"{").is_err());
+    }
 }
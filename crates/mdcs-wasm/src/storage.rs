@@ -0,0 +1,171 @@
+//! IndexedDB persistence for documents.
+//!
+//! Each database holds one object store of document snapshots, keyed by
+//! document ID. A database is opened for every save or load and closed
+//! again once it is done, so no connection outlives a call.
+
+use crate::error::MdcsError;
+use js_sys::{Function, Promise};
+use serde_json::json;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    DomException, IdbDatabase, IdbFactory, IdbRequest, IdbTransaction, IdbTransactionMode,
+};
+
+/// Object store holding the snapshots.
+const STORE: &str = "documents";
+
+/// Schema version of the databases.
+const DB_VERSION: u32 = 1;
+
+/// Write `record` under `key`, resolving once the write is committed.
+///
+/// Writes to the same database commit in the order they were started:
+/// connections open in request order, and read-write transactions on the
+/// same store run in the order they were created.
+pub(crate) async fn put(db_name: &str, key: &str, record: &JsValue) -> Result<(), StorageError> {
+    let db = open(db_name)
+        .await
+        .map_err(|e| StorageError::new(db_name, e))?;
+    let result: Result<JsValue, JsValue> = async {
+        let tx = db.transaction_with_str_and_mode(STORE, IdbTransactionMode::Readwrite)?;
+        tx.object_store(STORE)?
+            .put_with_key(record, &JsValue::from_str(key))?;
+        JsFuture::from(completion(&tx)).await
+    }
+    .await;
+    db.close();
+    result.map(drop).map_err(|e| StorageError::new(db_name, e))
+}
+
+/// Read the record under `key`, if there is one.
+pub(crate) async fn get(db_name: &str, key: &str) -> Result<Option<JsValue>, StorageError> {
+    let db = open(db_name)
+        .await
+        .map_err(|e| StorageError::new(db_name, e))?;
+    let result: Result<JsValue, JsValue> = async {
+        let tx = db.transaction_with_str(STORE)?;
+        let request = tx.object_store(STORE)?.get(&JsValue::from_str(key))?;
+        JsFuture::from(settle(&request)).await
+    }
+    .await;
+    db.close();
+    let record = result.map_err(|e| StorageError::new(db_name, e))?;
+    Ok((!record.is_undefined()).then_some(record))
+}
+
+/// Open a database, creating its object store the first time.
+async fn open(db_name: &str) -> Result<IdbDatabase, JsValue> {
+    let request = factory()?.open_with_u32(db_name, DB_VERSION)?;
+    let upgrading = request.clone();
+    request.set_onupgradeneeded(Some(&callback(move || {
+        if let Ok(db) = upgrading
+            .result()
+            .and_then(|db| db.dyn_into::<IdbDatabase>())
+        {
+            if !db.object_store_names().contains(STORE) {
+                let _ = db.create_object_store(STORE);
+            }
+        }
+    })));
+    let db = JsFuture::from(settle(&request)).await?;
+    Ok(db.unchecked_into())
+}
+
+/// The `indexedDB` of the window or worker this runs in.
+fn factory() -> Result<IdbFactory, JsValue> {
+    js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("indexedDB"))?
+        .dyn_into::<IdbFactory>()
+        .map_err(|_| JsValue::from_str("IndexedDB is not available in this context"))
+}
+
+/// Resolve with a request's result, or reject with its error.
+fn settle(request: &IdbRequest) -> Promise {
+    Promise::new(&mut |resolve, reject| {
+        let succeeded = request.clone();
+        request.set_onsuccess(Some(&callback(move || {
+            let result = succeeded.result().unwrap_or(JsValue::UNDEFINED);
+            let _ = resolve.call1(&JsValue::UNDEFINED, &result);
+        })));
+        let failed = request.clone();
+        request.set_onerror(Some(&callback(move || {
+            let error = failed.error().ok().flatten().map(JsValue::from);
+            let _ = reject.call1(&JsValue::UNDEFINED, &error.unwrap_or(JsValue::UNDEFINED));
+        })));
+    })
+}
+
+/// Resolve once a transaction commits, or reject with the error that
+/// aborted it. A failed request aborts its transaction, so this also
+/// catches errors like an exceeded quota that only show up on commit.
+fn completion(tx: &IdbTransaction) -> Promise {
+    Promise::new(&mut |resolve, reject| {
+        tx.set_oncomplete(Some(&callback(move || {
+            let _ = resolve.call0(&JsValue::UNDEFINED);
+        })));
+        let aborted = tx.clone();
+        tx.set_onabort(Some(&callback(move || {
+            let error = aborted.error().map(JsValue::from);
+            let _ = reject.call1(&JsValue::UNDEFINED, &error.unwrap_or(JsValue::UNDEFINED));
+        })));
+    })
+}
+
+/// An event handler that runs `f` once.
+fn callback(f: impl FnOnce() + 'static) -> Function {
+    Closure::once_into_js(f).unchecked_into()
+}
+
+/// A failed IndexedDB call.
+#[derive(Debug, Clone)]
+pub(crate) struct StorageError {
+    db_name: String,
+    cause: JsValue,
+}
+
+impl StorageError {
+    fn new(db_name: &str, cause: JsValue) -> Self {
+        Self {
+            db_name: db_name.to_string(),
+            cause,
+        }
+    }
+}
+
+impl From<StorageError> for MdcsError {
+    fn from(error: StorageError) -> Self {
+        let details = json!({ "db_name": error.db_name });
+        match error.cause.dyn_ref::<DomException>() {
+            Some(cause) if cause.name() == "QuotaExceededError" => MdcsError::new(
+                "QUOTA_EXCEEDED",
+                format!(
+                    "Storage quota exceeded: IndexedDB database {} has no room for this document. Free up space, e.g. by deleting documents that are no longer needed, and save again",
+                    error.db_name
+                ),
+            ),
+            Some(cause) => MdcsError::new(
+                "STORAGE_ERROR",
+                format!(
+                    "IndexedDB error in database {}: {}: {}",
+                    error.db_name,
+                    cause.name(),
+                    cause.message()
+                ),
+            ),
+            None => MdcsError::new(
+                "STORAGE_ERROR",
+                format!(
+                    "IndexedDB error in database {}: {}",
+                    error.db_name,
+                    error
+                        .cause
+                        .as_string()
+                        .unwrap_or_else(|| format!("{:?}", error.cause))
+                ),
+            ),
+        }
+        .with_details(details)
+    }
+}
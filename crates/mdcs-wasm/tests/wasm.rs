@@ -3,7 +3,10 @@
 //! These tests run in a headless browser environment using wasm-bindgen-test.
 //! Run with: `wasm-pack test --headless --chrome`
 
+use mdcs_db::DbError;
 use mdcs_wasm::*;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::JsFuture;
 use wasm_bindgen_test::*;
 
 wasm_bindgen_test_configure!(run_in_browser);
@@ -18,52 +21,52 @@ fn test_document_basic_operations() {
     assert_eq!(doc.version(), 0);
 
     // Test insert
-    doc.insert(0, "Hello");
+    doc.insert(0, "Hello").unwrap();
     assert_eq!(doc.get_text(), "Hello");
     assert_eq!(doc.len(), 5);
     assert_eq!(doc.version(), 1);
 
     // Test append
-    doc.insert(5, " World");
+    doc.insert(5, " World").unwrap();
     assert_eq!(doc.get_text(), "Hello World");
     assert_eq!(doc.len(), 11);
 
     // Test insert in middle
-    doc.insert(5, ",");
+    doc.insert(5, ",").unwrap();
     assert_eq!(doc.get_text(), "Hello, World");
 }
 
 #[wasm_bindgen_test]
 fn test_document_delete() {
     let mut doc = CollaborativeDocument::new("test-doc", "test-replica");
-    doc.insert(0, "Hello, World!");
+    doc.insert(0, "Hello, World!").unwrap();
 
     // Delete from middle
-    doc.delete(5, 2); // Remove ", "
+    doc.delete(5, 2).unwrap(); // Remove ", "
     assert_eq!(doc.get_text(), "HelloWorld!");
 
     // Delete from start
-    doc.delete(0, 5); // Remove "Hello"
+    doc.delete(0, 5).unwrap(); // Remove "Hello"
     assert_eq!(doc.get_text(), "World!");
 
     // Delete from end
-    doc.delete(5, 1); // Remove "!"
+    doc.delete(5, 1).unwrap(); // Remove "!"
     assert_eq!(doc.get_text(), "World");
 }
 
 #[wasm_bindgen_test]
 fn test_document_formatting() {
     let mut doc = CollaborativeDocument::new("test-doc", "test-replica");
-    doc.insert(0, "Hello World");
+    doc.insert(0, "Hello World").unwrap();
 
     // Apply bold to "Hello"
-    doc.apply_bold(0, 5);
+    doc.apply_bold(0, 5).unwrap();
     let html = doc.get_html();
     // HTML should contain bold tags (either <b> or <strong>)
     assert!(html.contains("<b>") || html.contains("<strong>") || html.contains("Hello"));
 
     // Apply italic to "World"
-    doc.apply_italic(6, 11);
+    doc.apply_italic(6, 11).unwrap();
     let html2 = doc.get_html();
     assert!(html2.contains("<i>") || html2.contains("<em>") || html2.contains("World"));
 }
@@ -71,8 +74,8 @@ fn test_document_formatting() {
 #[wasm_bindgen_test]
 fn test_document_link() {
     let mut doc = CollaborativeDocument::new("test-doc", "test-replica");
-    doc.insert(0, "Click here for more");
-    doc.apply_link(0, 10, "https://example.com");
+    doc.insert(0, "Click here for more").unwrap();
+    doc.apply_link(0, 10, "https://example.com").unwrap();
 
     let html = doc.get_html();
     assert!(html.contains("href") || html.contains("example.com") || html.contains("Click"));
@@ -81,8 +84,8 @@ fn test_document_link() {
 #[wasm_bindgen_test]
 fn test_document_serialize_deserialize() {
     let mut doc1 = CollaborativeDocument::new("test-doc", "replica-1");
-    doc1.insert(0, "Hello from replica 1");
-    doc1.apply_bold(0, 5);
+    doc1.insert(0, "Hello from replica 1").unwrap();
+    doc1.apply_bold(0, 5).unwrap();
 
     // Serialize
     let state = doc1.serialize().expect("Serialization should succeed");
@@ -96,6 +99,26 @@ fn test_document_serialize_deserialize() {
     assert_eq!(doc1.get_text(), doc2.get_text());
 }
 
+#[wasm_bindgen_test]
+fn test_delta_sync() {
+    let mut doc1 = CollaborativeDocument::new("test-doc", "replica-1");
+    doc1.insert(0, "Hello from replica 1").unwrap();
+    doc1.apply_bold(0, 5).unwrap();
+
+    let delta = doc1
+        .take_delta()
+        .unwrap()
+        .expect("Edits should produce a delta");
+    assert!(doc1.take_delta().unwrap().is_none());
+
+    // Applying the same delta twice is harmless
+    let mut doc2 = CollaborativeDocument::new("test-doc", "replica-2");
+    doc2.apply_delta(&delta).expect("Apply should succeed");
+    doc2.apply_delta(&delta).expect("Reapply should succeed");
+
+    assert_eq!(doc1.get_html(), doc2.get_html());
+}
+
 #[wasm_bindgen_test]
 fn test_concurrent_edits_convergence() {
     // Simulate two users editing concurrently
@@ -103,15 +126,15 @@ fn test_concurrent_edits_convergence() {
     let mut doc_bob = CollaborativeDocument::new("shared-doc", "bob");
 
     // Both start with same base
-    doc_alice.insert(0, "Base text");
+    doc_alice.insert(0, "Base text").unwrap();
     let base_state = doc_alice.serialize().unwrap();
     doc_bob.merge(&base_state).unwrap();
 
     // Alice adds " - edited by Alice" at the end
-    doc_alice.insert(9, " - Alice");
+    doc_alice.insert(9, " - Alice").unwrap();
 
     // Bob adds " - edited by Bob" at the end
-    doc_bob.insert(9, " - Bob");
+    doc_bob.insert(9, " - Bob").unwrap();
 
     // Exchange states
     let alice_state = doc_alice.serialize().unwrap();
@@ -129,11 +152,80 @@ fn test_concurrent_edits_convergence() {
     assert!(final_text.contains("Alice") || final_text.contains("Bob"));
 }
 
+#[wasm_bindgen_test]
+fn test_merge_edits_move_remote_cursors() {
+    let mut doc1 = CollaborativeDocument::new("test-doc", "replica-1");
+    doc1.insert(0, "Hello World").unwrap();
+    let mut doc2 = CollaborativeDocument::new("test-doc", "replica-2");
+    doc2.merge(&doc1.serialize().unwrap()).unwrap();
+
+    let mut presence = UserPresence::new("user-1", "Alice", "#FF6B6B");
+    presence.set_selection(2, 8);
+
+    doc1.delete(0, 4).unwrap();
+    doc1.insert(0, "J").unwrap();
+    let edits = doc2.merge(&doc1.serialize().unwrap()).unwrap();
+    assert_eq!(doc2.get_text(), "Jo World");
+
+    // The selection loses the deleted "ll"
+    presence.transform(edits.clone()).unwrap();
+    assert_eq!(presence.selection_start(), Some(1));
+    assert_eq!(presence.selection_end(), Some(5));
+    assert_eq!(transform_position(0, edits.clone(), false).unwrap(), 0);
+    assert_eq!(transform_position(0, edits, true).unwrap(), 1);
+}
+
+#[wasm_bindgen_test]
+fn test_version_vectors_across_merges() {
+    let mut doc_a = CollaborativeDocument::new("shared-doc", "alice");
+    let mut doc_b = CollaborativeDocument::new("shared-doc", "bob");
+    doc_a.insert(0, "Hello").unwrap();
+    let before = doc_a.version_vector().unwrap();
+
+    doc_b.merge(&doc_a.serialize().unwrap()).unwrap();
+    assert!(!doc_b.is_newer_than(before.clone()).unwrap());
+    doc_b.insert(5, "!").unwrap();
+    assert!(doc_b.is_newer_than(before.clone()).unwrap());
+
+    // Each side of a divergent pair misses the other's inserts
+    doc_a.insert(0, ">").unwrap();
+    let missing: js_sys::Array = doc_a
+        .missing_from(doc_b.version_vector().unwrap())
+        .unwrap()
+        .into();
+    assert_eq!(missing.length(), 1);
+    let range = missing.get(0);
+    let replica = js_sys::Reflect::get(&range, &"replica_id".into()).unwrap();
+    assert_eq!(replica.as_string().as_deref(), Some("bob"));
+    let missing: js_sys::Array = doc_b
+        .missing_from(doc_a.version_vector().unwrap())
+        .unwrap()
+        .into();
+    assert_eq!(missing.length(), 1);
+
+    // Vectors are plain objects and survive the serialize/merge round trip
+    let vv = doc_a.version_vector().unwrap();
+    let alice = js_sys::Reflect::get(&vv, &"alice".into()).unwrap();
+    assert_eq!(alice.as_f64(), Some(6.0));
+    doc_b.merge(&doc_a.serialize().unwrap()).unwrap();
+    doc_a.merge(&doc_b.serialize().unwrap()).unwrap();
+    assert!(!doc_a
+        .is_newer_than(doc_b.version_vector().unwrap())
+        .unwrap());
+    assert!(!doc_b
+        .is_newer_than(doc_a.version_vector().unwrap())
+        .unwrap());
+    assert_eq!(
+        js_sys::Array::from(&doc_a.missing_from(doc_b.version_vector().unwrap()).unwrap()).length(),
+        0
+    );
+}
+
 #[wasm_bindgen_test]
 fn test_document_snapshot_restore() {
     let mut original = CollaborativeDocument::new("test-doc", "test-replica");
-    original.insert(0, "Important content");
-    original.apply_bold(0, 9);
+    original.insert(0, "Important content").unwrap();
+    original.apply_bold(0, 9).unwrap();
 
     // Create snapshot
     let snapshot = original.snapshot().expect("Snapshot should succeed");
@@ -243,34 +335,34 @@ fn test_edge_cases_empty_operations() {
     let mut doc = CollaborativeDocument::new("test-doc", "test-replica");
 
     // Insert empty string should be no-op
-    doc.insert(0, "");
+    doc.insert(0, "").unwrap();
     assert_eq!(doc.len(), 0);
 
     // Delete from empty document
-    doc.delete(0, 10);
+    doc.delete(0, 10).unwrap();
     assert_eq!(doc.len(), 0);
 
     // Insert then delete same
-    doc.insert(0, "Hello");
-    doc.delete(0, 5);
+    doc.insert(0, "Hello").unwrap();
+    doc.delete(0, 5).unwrap();
     assert!(doc.is_empty());
 }
 
 #[wasm_bindgen_test]
 fn test_edge_cases_out_of_bounds() {
     let mut doc = CollaborativeDocument::new("test-doc", "test-replica");
-    doc.insert(0, "Hello");
+    doc.insert(0, "Hello").unwrap();
 
     // Insert past end should append
-    doc.insert(1000, " World");
+    doc.insert(1000, " World").unwrap();
     assert_eq!(doc.get_text(), "Hello World");
 
     // Delete past end should be bounded
-    doc.delete(5, 1000);
+    doc.delete(5, 1000).unwrap();
     assert_eq!(doc.get_text(), "Hello");
 
     // Apply formatting past end should be bounded
-    doc.apply_bold(0, 1000);
+    doc.apply_bold(0, 1000).unwrap();
     // Should not panic, formatting bounded to actual content
 }
 
@@ -281,9 +373,9 @@ fn test_multiple_replicas_three_way_merge() {
     let mut doc_c = CollaborativeDocument::new("doc", "replica-c");
 
     // Each replica makes an edit
-    doc_a.insert(0, "A");
-    doc_b.insert(0, "B");
-    doc_c.insert(0, "C");
+    doc_a.insert(0, "A").unwrap();
+    doc_b.insert(0, "B").unwrap();
+    doc_c.insert(0, "C").unwrap();
 
     // Get all states
     let state_a = doc_a.serialize().unwrap();
@@ -312,3 +404,264 @@ fn test_multiple_replicas_three_way_merge() {
     assert!(result.contains('B'));
     assert!(result.contains('C'));
 }
+
+#[wasm_bindgen_test]
+fn test_resync_events_payload() {
+    let mut doc = CollaborativeDocument::new("test-doc", "test-replica");
+    doc.set_event_history(1);
+    doc.insert(0, "Hello").unwrap();
+    doc.delete(0, 1).unwrap();
+    assert_eq!(doc.event_seq(), 2);
+
+    let resync = doc.resync_events(2).unwrap();
+    let events = js_sys::Reflect::get(&resync, &"events".into()).unwrap();
+    let event = js_sys::Array::from(&events).get(0);
+    let kind = js_sys::Reflect::get(&event, &"kind".into()).unwrap();
+    assert_eq!(kind.as_string(), Some("delete".to_string()));
+
+    // Evicted events resync as a snapshot instruction
+    let resync = doc.resync_events(1).unwrap();
+    let snapshot = js_sys::Reflect::get(&resync, &"snapshot".into()).unwrap();
+    assert!(snapshot.is_object());
+}
+
+#[wasm_bindgen_test]
+fn test_transferable_round_trip() {
+    let mut doc = CollaborativeDocument::new("test-doc", "alice");
+    let mut remote = CollaborativeDocument::new("test-doc", "bob");
+    doc.insert(0, "Hello World").unwrap();
+    doc.apply_bold(0, 5).unwrap();
+    remote.insert(0, ">> ").unwrap();
+    doc.merge(&remote.serialize().unwrap()).unwrap();
+    let vv = doc.version_vector().unwrap();
+
+    let bytes = doc.export_transferable().unwrap();
+    let imported = CollaborativeDocument::import_transferable(&bytes).unwrap();
+    assert_eq!(imported.get_text(), doc.get_text());
+    assert_eq!(imported.get_html(), doc.get_html());
+    assert_eq!(imported.version(), doc.version());
+    assert!(!imported.is_newer_than(vv.clone()).unwrap());
+    assert!(!doc
+        .is_newer_than(imported.version_vector().unwrap())
+        .unwrap());
+    assert_eq!(
+        js_sys::JSON::stringify(&imported.version_vector().unwrap()).unwrap(),
+        js_sys::JSON::stringify(&vv).unwrap()
+    );
+}
+
+#[wasm_bindgen_test]
+fn test_transferred_document_rejects_edits() {
+    let mut doc = CollaborativeDocument::new("test-doc", "alice");
+    doc.insert(0, "Hello").unwrap();
+    let bytes = doc.export_transferable().unwrap();
+    assert!(doc.is_transferred());
+
+    let error = doc.insert(0, "x").unwrap_err();
+    assert_eq!(error.code(), "TRANSFERRED");
+    assert!(error.message().contains("export_transferable"), "{}", error);
+    assert!(doc.delete(0, 1).is_err());
+    assert!(doc.apply_italic(0, 2).is_err());
+    assert!(doc.merge(&doc.serialize().unwrap()).is_err());
+    assert!(doc.export_transferable().is_err());
+    // Reads still see the state as it was handed over
+    assert_eq!(doc.get_text(), "Hello");
+
+    let mut imported = CollaborativeDocument::import_transferable(&bytes).unwrap();
+    imported.insert(5, "!").unwrap();
+    assert_eq!(imported.get_text(), "Hello!");
+}
+
+#[wasm_bindgen_test]
+fn test_worker_protocol_op_round_trip() {
+    let op = js_sys::JSON::parse(r#"{"kind":"insert","position":0,"text":"Hello"}"#).unwrap();
+    let bytes = WorkerProtocol::encode_op(op.clone()).unwrap();
+    let decoded = WorkerProtocol::decode_op(&bytes).unwrap();
+    assert_eq!(
+        js_sys::JSON::stringify(&decoded).unwrap(),
+        js_sys::JSON::stringify(&op).unwrap()
+    );
+
+    let mut doc = CollaborativeDocument::new("test-doc", "worker");
+    WorkerProtocol::apply_op(&mut doc, &bytes).unwrap();
+    let link =
+        js_sys::JSON::parse(r#"{"kind":"link","start":0,"end":5,"url":"https://a.b"}"#).unwrap();
+    WorkerProtocol::apply_op(&mut doc, &WorkerProtocol::encode_op(link).unwrap()).unwrap();
+    assert_eq!(doc.get_text(), "Hello");
+    assert!(doc.get_html().contains("https://a.b"));
+
+    // Events come back out through the same helper
+    let resync = doc.resync_events(2).unwrap();
+    let events = js_sys::Reflect::get(&resync, &"events".into()).unwrap();
+    let event = js_sys::Array::from(&events).get(0);
+    let bytes = WorkerProtocol::encode_event(event).unwrap();
+    let decoded = WorkerProtocol::decode_event(&bytes).unwrap();
+    let kind = js_sys::Reflect::get(&decoded, &"kind".into()).unwrap();
+    assert_eq!(kind.as_string().as_deref(), Some("format"));
+
+    assert!(WorkerProtocol::decode_op(&bytes).is_err());
+    assert!(WorkerProtocol::encode_op(js_sys::JSON::parse(r#"{"kind":"nope"}"#).unwrap()).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_paste_html_reports_removed_markup() {
+    let mut doc = CollaborativeDocument::new("test-doc", "test-replica");
+    doc.insert(0, "Hello ").unwrap();
+    let report = doc
+        .paste_html(6, r#"<a href="javascript:alert(1)">world</a><img src=x>"#)
+        .unwrap();
+    assert_eq!(doc.get_text(), "Hello world");
+    assert!(!doc.get_html().contains("javascript"));
+
+    let attrs = js_sys::Reflect::get(&report, &"removed_attrs".into()).unwrap();
+    let href = js_sys::Reflect::get(&attrs, &"href".into()).unwrap();
+    assert_eq!(href.as_f64(), Some(1.0));
+    let truncated = js_sys::Reflect::get(&report, &"truncated".into()).unwrap();
+    assert_eq!(truncated.as_bool(), Some(false));
+
+    doc.export_transferable().unwrap();
+    assert!(doc.paste_html(0, "<b>late</b>").is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_undo_keeps_remote_edits() {
+    let mut doc1 = CollaborativeDocument::new("test-doc", "replica-1");
+    let mut doc2 = CollaborativeDocument::new("test-doc", "replica-2");
+    doc1.set_undo_group_window(60_000);
+    doc1.insert(0, "wor").unwrap();
+    doc1.insert(3, "ld").unwrap();
+    doc2.merge(&doc1.serialize().unwrap()).unwrap();
+
+    doc2.insert(0, "Hello ").unwrap();
+    doc1.merge(&doc2.serialize().unwrap()).unwrap();
+
+    // Both inserts were one burst; the remote greeting stays
+    assert!(doc1.undo().unwrap());
+    assert!(!doc1.can_undo());
+    assert_eq!(doc1.get_text(), "Hello ");
+    doc2.merge(&doc1.serialize().unwrap()).unwrap();
+    assert_eq!(doc2.get_text(), "Hello ");
+
+    assert!(doc1.redo().unwrap());
+    assert_eq!(doc1.get_text(), "Hello world");
+}
+
+#[wasm_bindgen_test]
+fn test_comments_round_trip() {
+    let mut doc = CollaborativeDocument::new("test-doc", "test-replica");
+    doc.insert(0, "Hello World").unwrap();
+    let id = doc
+        .add_comment(6, 11, "alice", "Which world?")
+        .unwrap()
+        .unwrap();
+    assert!(doc.add_comment(4, 4, "alice", "empty").unwrap().is_none());
+
+    let comments = js_sys::Array::from(&doc.get_comments().unwrap());
+    assert_eq!(comments.length(), 1);
+    let comment = comments.get(0);
+    let field = |name: &str| js_sys::Reflect::get(&comment, &name.into()).unwrap();
+    assert_eq!(field("id").as_string(), Some(id.clone()));
+    assert_eq!(field("author").as_string().as_deref(), Some("alice"));
+    assert_eq!(field("text").as_string().as_deref(), Some("Which world?"));
+    assert_eq!(field("start").as_f64(), Some(6.0));
+    assert_eq!(field("end").as_f64(), Some(11.0));
+
+    assert!(doc.remove_comment(&id).unwrap());
+    assert_eq!(
+        js_sys::Array::from(&doc.get_comments().unwrap()).length(),
+        0
+    );
+}
+
+#[wasm_bindgen_test]
+async fn test_storage_round_trip() {
+    let mut doc = CollaborativeDocument::new("stored-doc", "test-replica");
+    doc.insert(0, "Hello World").unwrap();
+    doc.apply_bold(0, 5).unwrap();
+    doc.apply_link(6, 11, "https://example.com").unwrap();
+
+    JsFuture::from(doc.save_to_storage("mdcs-test-storage"))
+        .await
+        .expect("Save should succeed");
+    let loaded = CollaborativeDocument::load("mdcs-test-storage", "stored-doc")
+        .await
+        .expect("Load should succeed")
+        .expect("Document should be saved");
+
+    assert_eq!(loaded.get_text(), "Hello World");
+    assert_eq!(loaded.get_html(), doc.get_html());
+    assert_eq!(loaded.version(), doc.version());
+    assert_eq!(loaded.replica_id(), "test-replica");
+
+    let missing = CollaborativeDocument::load("mdcs-test-storage", "never-saved")
+        .await
+        .expect("Load should succeed");
+    assert!(missing.is_none());
+}
+
+#[wasm_bindgen_test]
+async fn test_auto_save_every_n_ops() {
+    let mut doc = CollaborativeDocument::new("auto-saved-doc", "test-replica");
+    doc.enable_auto_save("mdcs-test-auto-save", 2, None);
+    doc.insert(0, "Hello").unwrap();
+    doc.apply_italic(0, 5).unwrap();
+    doc.insert(5, "!").unwrap();
+
+    // Let the background save started by the second edit open the database
+    // first; requests to it are then served in order
+    JsFuture::from(js_sys::Promise::resolve(&wasm_bindgen::JsValue::UNDEFINED))
+        .await
+        .unwrap();
+    let loaded = CollaborativeDocument::load("mdcs-test-auto-save", "auto-saved-doc")
+        .await
+        .expect("Load should succeed")
+        .expect("Document should be auto-saved");
+    assert_eq!(loaded.get_text(), "Hello");
+    assert_eq!(loaded.version(), 2);
+    assert!(loaded.get_html().contains("<em>Hello</em>"));
+}
+
+/// Read a property of a thrown error, as JS code would.
+fn property(error: &JsValue, name: &str) -> JsValue {
+    js_sys::Reflect::get(error, &name.into()).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn test_document_not_found_error_shape() {
+    let error: JsValue = MdcsError::from(DbError::DocumentNotFound("notes".into())).into();
+
+    assert_eq!(
+        property(&error, "code").as_string().as_deref(),
+        Some("DOCUMENT_NOT_FOUND")
+    );
+    assert_eq!(
+        property(&error, "message").as_string().as_deref(),
+        Some("Document not found: notes")
+    );
+    let details = property(&error, "details");
+    assert_eq!(
+        property(&details, "document_id").as_string().as_deref(),
+        Some("notes")
+    );
+}
+
+#[wasm_bindgen_test]
+fn test_deserialization_error_shape() {
+    let mut doc = CollaborativeDocument::new("test-doc", "test-replica");
+    let error: JsValue = doc.apply_delta("not json").unwrap_err().into();
+
+    assert_eq!(
+        property(&error, "code").as_string().as_deref(),
+        Some("DESERIALIZATION_ERROR")
+    );
+    let message = property(&error, "message").as_string().unwrap();
+    assert!(
+        message.starts_with("Deserialization error: "),
+        "{}",
+        message
+    );
+    assert!(property(&error, "details").is_undefined());
+    let to_string: js_sys::Function = property(&error, "toString").into();
+    let shown = to_string.call0(&error).unwrap().as_string().unwrap();
+    assert_eq!(shown, format!("DESERIALIZATION_ERROR: {}", message));
+}
@@ -0,0 +1,126 @@
+//! Benchmark: rendering a 50k-character RichText with 5k marks
+//!
+//! Builds one document with marks of mixed types at random ranges, then
+//! times `to_html` and `marks_at` against resolving every mark's anchors one
+//! at a time, which is what both did before marks were indexed.
+//!
+//! Run with: `cargo run --release --example rich_text_render_bench`
+
+use mdcs_db::{MarkType, RichText};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+const CHARS: usize = 50_000;
+const MARKS: usize = 5_000;
+const QUERIES: usize = 1_000;
+const SCANNED_QUERIES: usize = 10;
+const WORD: &str = "carnelia, ";
+
+fn main() {
+    println!("═══════════════════════════════════════════════════════════════");
+    println!(
+        "  RichText render benchmark ({} chars, {} marks)",
+        CHARS, MARKS
+    );
+    println!("═══════════════════════════════════════════════════════════════\n");
+
+    let mut rng = StdRng::seed_from_u64(1);
+    let start = Instant::now();
+    let mut doc = RichText::new("bench");
+    doc.insert(0, &WORD.repeat(CHARS / WORD.len()));
+    for _ in 0..MARKS {
+        let from = rng.gen_range(0..CHARS);
+        let to = (from + rng.gen_range(1..200)).min(CHARS);
+        let mark_type = match rng.gen_range(0..4) {
+            0 => MarkType::Bold,
+            1 => MarkType::Italic,
+            2 => MarkType::Link {
+                url: "https://example.com".to_string(),
+            },
+            _ => MarkType::Comment {
+                author: "bench".to_string(),
+                content: "note".to_string(),
+            },
+        };
+        doc.add_mark(from, to, mark_type);
+    }
+    doc.take_delta();
+    report("build document", start.elapsed());
+
+    let start = Instant::now();
+    let scanned = scanned_spans(&doc);
+    report("resolve every mark", start.elapsed());
+
+    let start = Instant::now();
+    let html = doc.to_html();
+    report("to_html, indexed", start.elapsed());
+    assert_eq!(indexed_spans(&doc), scanned);
+
+    let positions: Vec<usize> = (0..QUERIES).map(|_| rng.gen_range(0..CHARS)).collect();
+
+    let start = Instant::now();
+    for &position in &positions[..SCANNED_QUERIES] {
+        let mut indexed: Vec<_> = doc.marks_at(position).iter().map(|m| &m.id.ulid).collect();
+        let mut scanned: Vec<_> = doc
+            .all_marks()
+            .filter(|m| m.covers(doc.text(), position))
+            .map(|m| &m.id.ulid)
+            .collect();
+        indexed.sort();
+        scanned.sort();
+        assert_eq!(indexed, scanned);
+    }
+    report(
+        "marks_at, scanning marks",
+        start.elapsed() / SCANNED_QUERIES as u32,
+    );
+
+    let start = Instant::now();
+    let found: usize = positions.iter().map(|&p| doc.marks_at(p).len()).sum();
+    report("marks_at, indexed", start.elapsed() / QUERIES as u32);
+
+    let start = Instant::now();
+    doc.insert(CHARS / 2, WORD);
+    doc.delete(CHARS / 4, WORD.len());
+    report("insert and delete", start.elapsed());
+
+    let start = Instant::now();
+    doc.to_html();
+    report("to_html after the edits", start.elapsed());
+    assert_eq!(indexed_spans(&doc), scanned_spans(&doc));
+
+    println!(
+        "\n  {} bytes of HTML, {:.1} marks per position on average",
+        html.len(),
+        found as f64 / QUERIES as f64
+    );
+}
+
+/// Every active mark's range, resolving its anchors on their own as
+/// rendering used to.
+fn scanned_spans(doc: &RichText) -> Vec<(usize, usize, &str)> {
+    let mut spans: Vec<_> = doc
+        .active_marks()
+        .filter_map(|mark| {
+            let (start, end) = mark.range(doc.text())?;
+            Some((start, end, mark.id.ulid.as_str()))
+        })
+        .collect();
+    spans.sort();
+    spans
+}
+
+fn indexed_spans(doc: &RichText) -> Vec<(usize, usize, &str)> {
+    let mut spans: Vec<_> = doc
+        .spans()
+        .into_iter()
+        .map(|(start, end, mark)| (start, end, mark.id.ulid.as_str()))
+        .collect();
+    spans.sort();
+    spans
+}
+
+fn report(label: &str, elapsed: Duration) {
+    println!("  {:<28} {:>12.2?}", label, elapsed);
+}
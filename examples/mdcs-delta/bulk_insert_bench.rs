@@ -0,0 +1,126 @@
+//! Benchmark: 50k single adds vs one bulk add
+//!
+//! Compares wall time and the number of buffered deltas for GSet and ORSet
+//! when importing many elements either one at a time or as one batch.
+//!
+//! Run with: `cargo run --release --example bulk_insert_bench`
+
+use mdcs_core::gset::GSet;
+use mdcs_core::lattice::DeltaCRDT;
+use mdcs_core::orset::{ORSet, ORSetDelta};
+use mdcs_delta::buffer::{DeltaBuffer, DeltaReplica};
+use mdcs_delta::mutators::gset as gset_mutators;
+use mdcs_delta::mutators::orset as orset_mutators;
+use std::time::{Duration, Instant};
+
+const N: u64 = 50_000;
+
+fn main() {
+    println!("═══════════════════════════════════════════════════════════════");
+    println!("  Bulk insert benchmark ({} elements)", N);
+    println!("═══════════════════════════════════════════════════════════════\n");
+
+    bench_gset();
+    bench_orset();
+}
+
+fn report(label: &str, elapsed: Duration, deltas: u64, buffered: usize) {
+    println!(
+        "  {:<28} {:>10.2?}   deltas: {:>6}   buffered: {:>6}",
+        label, elapsed, deltas, buffered
+    );
+}
+
+fn bench_gset() {
+    println!("GSet<u64>");
+
+    // Single inserts: one delta per element
+    let start = Instant::now();
+    let mut state: GSet<u64> = GSet::new();
+    let mut buffer: DeltaBuffer<GSet<u64>> = DeltaBuffer::new(usize::MAX);
+    for i in 0..N {
+        let delta = gset_mutators::insert_delta(i);
+        state.insert(i);
+        buffer.push(delta);
+    }
+    report(
+        "single insert_delta",
+        start.elapsed(),
+        buffer.current_seq(),
+        buffer.len(),
+    );
+
+    // Bulk insert through DeltaReplica: one delta for the batch
+    let start = Instant::now();
+    let mut replica: DeltaReplica<GSet<u64>> = DeltaReplica::new("r1");
+    replica.mutate(|_| gset_mutators::insert_all_delta(0..N));
+    report(
+        "bulk insert_all_delta",
+        start.elapsed(),
+        replica.current_seq(),
+        replica.buffer().len(),
+    );
+    assert_eq!(replica.state(), &state);
+    println!();
+}
+
+fn bench_orset() {
+    println!("ORSet<u64>");
+
+    // Single adds: one tag and one buffered delta per element
+    let start = Instant::now();
+    let mut state: ORSet<u64> = ORSet::new();
+    let mut buffer: DeltaBuffer<ORSetDelta<u64>> = DeltaBuffer::new(usize::MAX);
+    for i in 0..N {
+        state.add("r1", i);
+        if let Some(delta) = state.split_delta() {
+            buffer.push(delta);
+        }
+    }
+    report(
+        "single add",
+        start.elapsed(),
+        buffer.current_seq(),
+        buffer.len(),
+    );
+
+    // Bulk add on the same set type: one delta for the batch
+    let start = Instant::now();
+    let mut bulk: ORSet<u64> = ORSet::new();
+    let mut buffer: DeltaBuffer<ORSetDelta<u64>> = DeltaBuffer::new(usize::MAX);
+    bulk.add_all("r1", 0..N);
+    if let Some(delta) = bulk.split_delta() {
+        buffer.push(delta);
+    }
+    report(
+        "bulk add_all",
+        start.elapsed(),
+        buffer.current_seq(),
+        buffer.len(),
+    );
+
+    // Bulk add through DeltaReplica (includes joining into the state)
+    let start = Instant::now();
+    let mut replica: DeltaReplica<ORSet<u64>> = DeltaReplica::new("r1");
+    replica.mutate(|x| orset_mutators::add_all_delta(x, "r1", 0..N));
+    report(
+        "bulk add_all_delta",
+        start.elapsed(),
+        replica.current_seq(),
+        replica.buffer().len(),
+    );
+    assert!(replica.state().iter().eq(state.iter()));
+
+    // Bulk remove: one delta for the batch
+    let start = Instant::now();
+    let mut set = replica.state().clone();
+    let to_remove: Vec<u64> = (0..N).step_by(2).collect();
+    set.remove_all(&to_remove);
+    let removed = set.split_delta().map_or(0, |d| d.removals.len());
+    println!(
+        "  {:<28} {:>10.2?}   tags removed: {}",
+        "bulk remove_all (N/2)",
+        start.elapsed(),
+        removed
+    );
+}
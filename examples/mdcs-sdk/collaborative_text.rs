@@ -43,8 +43,8 @@ fn main() {
     println!("  [SYNC] Broadcasting Alice's changes to all peers...");
     {
         let alice_state = docs[0].read().clone_state();
-        for i in 1..docs.len() {
-            docs[i].write().merge(&alice_state);
+        for doc in docs.iter().skip(1) {
+            doc.write().merge(&alice_state);
         }
     }
     println!("  [SYNC] Complete\n");
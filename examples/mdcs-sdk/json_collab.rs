@@ -55,8 +55,8 @@ fn main() {
     // Sync to others via CRDT merge
     {
         let pm_state = docs[0].read().clone_state();
-        for i in 1..docs.len() {
-            docs[i].write().merge(&pm_state);
+        for doc in docs.iter().skip(1) {
+            doc.write().merge(&pm_state);
         }
     }
     println!("\n  [SYNC] → Developer, Designer\n");
@@ -211,8 +211,8 @@ fn main() {
     // Sync the update
     {
         let pm_state = docs[0].read().clone_state();
-        for i in 1..docs.len() {
-            docs[i].write().merge(&pm_state);
+        for doc in docs.iter().skip(1) {
+            doc.write().merge(&pm_state);
         }
     }
 
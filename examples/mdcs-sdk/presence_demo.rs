@@ -45,8 +45,8 @@ fn main() {
     // Sync to all clients via CRDT merge
     {
         let alice_state = docs[0].read().clone_state();
-        for i in 1..docs.len() {
-            docs[i].write().merge(&alice_state);
+        for doc in docs.iter().skip(1) {
+            doc.write().merge(&alice_state);
         }
     }
 
@@ -2,10 +2,23 @@
 //!
 //! This binary provides a command-line interface for running various
 //! stress tests and benchmarks for the MDCS crate family.
+//!
+//! With `--format json` or `--format csv` a run ends in one machine-readable
+//! [`SuiteReport`] of every test it ran, written to `--output` or to stdout;
+//! in the second case the progress output moves to stderr.
 
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use soak::{run_soak, SoakConfig};
+use std::path::PathBuf;
 use stress_test::{
+    master_seed,
+    progress,
+    set_master_seed,
+    set_progress_to_stderr,
     stress_test_all_core_crdts,
     stress_test_all_db_crdts,
+    stress_test_cluster_routing,
+    stress_test_delta_suite,
     stress_test_document_store,
     // Core CRDT stress tests (async, 3 args)
     stress_test_gset,
@@ -18,297 +31,489 @@ use stress_test::{
     stress_test_rga_text,
     stress_test_rich_text,
     stress_test_scaling,
+    StressTestStats,
+    SuiteReport,
+    TestRecord,
 };
+pub mod soak;
 pub mod stress_test;
 
-fn main() {
-    let rt = tokio::runtime::Runtime::new().unwrap();
+#[derive(Parser)]
+#[command(
+    name = "carnelia",
+    about = "MDCS stress test suite",
+    after_help = "Every suite derives its randomness from one seed, random unless --seed is \
+                  given and printed before the run and in every header.\n\n\
+                  Examples:\n  \
+                  cargo run                                # Run quick tests\n  \
+                  cargo run -- core --seed 1234            # Repeat a run\n  \
+                  cargo run -- db --replicas 3 --ops 50    # Smaller database tests\n  \
+                  cargo run -- full --format json --output logs/full.json\n  \
+                  cargo run -- soak --duration 600 --replicas 8 --profile mixed"
+)]
+struct Cli {
+    #[command(subcommand)]
+    suite: Option<Suite>,
+
+    /// Replicas in every test, instead of each suite's own sizes
+    /// (the document count for DocumentStore, the largest count for scaling)
+    #[arg(long, global = true)]
+    replicas: Option<usize>,
+
+    /// Operations per replica in every test (messages in total for routing)
+    #[arg(long, global = true)]
+    ops: Option<usize>,
+
+    /// Sync rounds in every core CRDT test
+    #[arg(long, global = true)]
+    syncs: Option<usize>,
+
+    /// Master seed (default random)
+    #[arg(long, global = true)]
+    seed: Option<u64>,
+
+    /// How to report the results
+    #[arg(long, global = true, value_enum, default_value_t = Format::Pretty)]
+    format: Format,
+
+    /// File to write the json or csv report to, instead of stdout
+    #[arg(long, global = true)]
+    output: Option<PathBuf>,
+}
 
-    // Parse command line args for test selection
-    let args: Vec<String> = std::env::args().collect();
-
-    if args.len() > 1 {
-        match args[1].as_str() {
-            "core" => rt.block_on(run_core_tests()),
-            "db" => run_db_tests(),
-            "quick" => rt.block_on(run_quick_tests()),
-            "full" => rt.block_on(run_full_suite()),
-            "scaling" => rt.block_on(run_scaling_analysis()),
-            "help" | "--help" | "-h" => print_usage(),
-            _ => {
-                println!("Unknown test suite: {}", args[1]);
-                print_usage();
-            }
+#[derive(Subcommand, Clone, Debug, PartialEq, Eq)]
+enum Suite {
+    /// Quick smoke tests (default)
+    Quick,
+    /// Core CRDT stress tests (GSet, ORSet, PNCounter, etc.)
+    Core,
+    /// Database layer tests (RGAText, RichText, JsonCrdt)
+    Db,
+    /// Delta sync over lossy, duplicating and reordering networks
+    Delta,
+    /// Scaling analysis with performance metrics
+    Scaling,
+    /// Causal cluster message routing benchmark
+    Routing,
+    /// Long-running workload with memory growth tracking
+    #[command(after_help = "Soak options:\n  \
+                  --duration SECS       Wall time to run (default 600)\n  \
+                  --profile NAME        mixed, text, json, set, presence or sync (default mixed)\n  \
+                  --sample SECS         Time between samples (default 10)\n  \
+                  --warmup SECS         Samples ignored for growth (default duration / 5)\n  \
+                  --max-slope BYTES     Allowed growth per minute of any series (default 262144)\n  \
+                  --ops-per-sec N       Operations per replica per second (default 200)\n  \
+                  --csv PATH            Where to write the samples (default logs/soak.csv)\n\n\
+                  --replicas (default 8) and --seed apply as for the other suites.")]
+    Soak {
+        /// Soak options, see below
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        options: Vec<String>,
+    },
+    /// Complete benchmark suite (takes longer)
+    Full,
+}
+
+impl Suite {
+    fn name(&self) -> &'static str {
+        match self {
+            Suite::Quick => "quick",
+            Suite::Core => "core",
+            Suite::Db => "db",
+            Suite::Delta => "delta",
+            Suite::Scaling => "scaling",
+            Suite::Routing => "routing",
+            Suite::Soak { .. } => "soak",
+            Suite::Full => "full",
         }
-    } else {
-        // Default: run quick tests
-        rt.block_on(run_quick_tests());
     }
 }
 
-fn print_usage() {
-    println!("╔════════════════════════════════════════════════════════════╗");
-    println!("║            MDCS STRESS TEST SUITE                          ║");
-    println!("╚════════════════════════════════════════════════════════════╝");
-    println!();
-    println!("Usage: cargo run [test_suite]");
-    println!();
-    println!("Available test suites:");
-    println!("  quick    - Quick smoke tests (default)");
-    println!("  core     - Core CRDT stress tests (GSet, ORSet, PNCounter, etc.)");
-    println!("  db       - Database layer tests (RGAText, RichText, JsonCrdt)");
-    println!("  scaling  - Scaling analysis with performance metrics");
-    println!("  full     - Complete benchmark suite (takes longer)");
-    println!("  help     - Show this help message");
-    println!();
-    println!("Examples:");
-    println!("  cargo run              # Run quick tests");
-    println!("  cargo run quick        # Run quick tests");
-    println!("  cargo run core         # Run core CRDT tests");
-    println!("  cargo run db           # Run database layer tests");
-    println!("  cargo run full         # Run complete suite");
-    println!();
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    /// Result boxes and tables
+    Pretty,
+    /// One JSON document with a record per test
+    Json,
+    /// A row per test
+    Csv,
 }
 
-async fn run_quick_tests() {
-    println!("\n╔════════════════════════════════════════════════════════════╗");
-    println!("║            QUICK SMOKE TESTS                               ║");
-    println!("╚════════════════════════════════════════════════════════════╝\n");
+/// The sizes given on the command line, and the records of the tests run
+/// so far
+struct Runner {
+    replicas: Option<usize>,
+    ops: Option<usize>,
+    syncs: Option<usize>,
+    report: SuiteReport,
+}
 
-    // Quick core CRDT tests (async, 3 args: replicas, ops, syncs)
-    println!("── Core CRDTs ──────────────────────────────────────────────");
-    let stats = stress_test_gset(4, 100, 200).await;
-    stats.print();
+impl Runner {
+    /// A test's sizes: the suite's own, unless given on the command line
+    fn sizes(&self, replicas: usize, ops: usize, syncs: usize) -> (usize, usize, usize) {
+        (
+            self.replicas.unwrap_or(replicas),
+            self.ops.unwrap_or(ops),
+            self.syncs.unwrap_or(syncs),
+        )
+    }
 
-    let stats = stress_test_orset(4, 100, 200).await;
-    stats.print();
+    /// Replica counts to sweep, capped at `--replicas`
+    fn sweep(&self, counts: &[usize]) -> Vec<usize> {
+        let cap = self.replicas.unwrap_or(usize::MAX).max(1);
+        let mut counts: Vec<usize> = counts.iter().map(|&n| n.min(cap)).collect();
+        counts.dedup();
+        counts
+    }
 
-    let stats = stress_test_pncounter(4, 100, 200).await;
-    stats.print();
+    /// Print a test's results and keep them for the report
+    fn record(&mut self, stats: StressTestStats) {
+        stats.print();
+        self.report.add(TestRecord::Stress(stats));
+    }
 
-    // Quick DB layer tests (sync, 2 args: replicas, ops)
-    println!("\n── Database Layer ──────────────────────────────────────────");
-    let stats = stress_test_rga_text(3, 50);
-    stats.print();
+    /// Keep a test's results for the report without printing them, for
+    /// tests summarized in a table
+    fn record_quietly(&mut self, stats: StressTestStats) {
+        self.report.add(TestRecord::Stress(stats));
+    }
+}
 
-    let stats = stress_test_rich_text(3, 30);
-    stats.print();
+fn main() {
+    let cli = Cli::parse();
+    let suite = cli.suite.clone().unwrap_or(Suite::Quick);
+
+    if cli.format == Format::Pretty && cli.output.is_some() {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--output needs --format json or --format csv",
+            )
+            .exit();
+    }
+    if cli.format != Format::Pretty && matches!(suite, Suite::Soak { .. }) {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "soak writes its samples with --csv and takes no --format",
+            )
+            .exit();
+    }
+    // Keep stdout for the report when that's where it goes
+    set_progress_to_stderr(cli.format != Format::Pretty && cli.output.is_none());
 
-    let stats = stress_test_json_crdt(3, 30);
-    stats.print();
+    if let Some(seed) = cli.seed {
+        set_master_seed(seed);
+    }
+    progress!(
+        "Seed: {} (repeat this run with --seed {})",
+        master_seed(),
+        master_seed()
+    );
+
+    let mut runner = Runner {
+        replicas: cli.replicas,
+        ops: cli.ops,
+        syncs: cli.syncs,
+        report: SuiteReport::new(suite.name()),
+    };
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    match &suite {
+        Suite::Quick => rt.block_on(run_quick_tests(&mut runner)),
+        Suite::Core => rt.block_on(run_core_tests(&mut runner)),
+        Suite::Db => run_db_tests(&mut runner),
+        Suite::Delta => rt.block_on(run_delta_tests(&mut runner)),
+        Suite::Scaling => rt.block_on(run_scaling_analysis(&mut runner)),
+        Suite::Routing => run_routing_benchmark(&mut runner),
+        Suite::Soak { options } => run_soak_test(&runner, options),
+        Suite::Full => rt.block_on(run_full_suite(&mut runner)),
+    }
 
-    println!("\n✓ Quick tests completed successfully!");
+    let report = match cli.format {
+        Format::Pretty => return,
+        Format::Json => runner.report.to_json() + "\n",
+        Format::Csv => runner.report.to_csv(),
+    };
+    match &cli.output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, report) {
+                eprintln!("Failed to write {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+            progress!("Report written to {}", path.display());
+        }
+        None => print!("{}", report),
+    }
 }
 
-async fn run_core_tests() {
-    println!("\n╔════════════════════════════════════════════════════════════╗");
-    println!("║            CORE CRDT STRESS TESTS                          ║");
-    println!("╚════════════════════════════════════════════════════════════╝\n");
-
-    // Small scale tests
-    println!("── Small Scale (4 replicas, 100 ops) ─────────────────────────");
-    let stats = stress_test_gset(4, 100, 200).await;
-    stats.print();
+async fn run_quick_tests(runner: &mut Runner) {
+    progress!("\n╔════════════════════════════════════════════════════════════╗");
+    progress!("║            QUICK SMOKE TESTS                               ║");
+    progress!("╚════════════════════════════════════════════════════════════╝\n");
 
-    let stats = stress_test_orset(4, 100, 200).await;
-    stats.print();
+    // Quick core CRDT tests (async, 3 args: replicas, ops, syncs)
+    progress!("── Core CRDTs ──────────────────────────────────────────────");
+    let (replicas, ops, syncs) = runner.sizes(4, 100, 200);
+    runner.record(stress_test_gset(replicas, ops, syncs).await);
+    runner.record(stress_test_orset(replicas, ops, syncs).await);
+    runner.record(stress_test_pncounter(replicas, ops, syncs).await);
 
-    let stats = stress_test_pncounter(4, 100, 200).await;
-    stats.print();
+    // Quick DB layer tests (sync, 2 args: replicas, ops)
+    progress!("\n── Database Layer ──────────────────────────────────────────");
+    let (replicas, ops, _) = runner.sizes(3, 50, 0);
+    runner.record(stress_test_rga_text(replicas, ops));
+    let (replicas, ops, _) = runner.sizes(3, 30, 0);
+    runner.record(stress_test_rich_text(replicas, ops));
+    runner.record(stress_test_json_crdt(replicas, ops));
+
+    // Keep the soak harness itself working
+    progress!("\n── Soak Smoke ──────────────────────────────────────────────");
+    let report = run_soak(SoakConfig::smoke());
+    report.print();
+    assert!(report.passed(), "soak smoke run failed");
+    assert!(report.crashes > 0 && report.partitions > 0);
+
+    progress!("\n✓ Quick tests completed successfully!");
+}
 
-    let stats = stress_test_lwwreg(4, 100, 200).await;
-    stats.print();
+async fn run_core_tests(runner: &mut Runner) {
+    progress!("\n╔════════════════════════════════════════════════════════════╗");
+    progress!("║            CORE CRDT STRESS TESTS                          ║");
+    progress!("╚════════════════════════════════════════════════════════════╝\n");
 
-    let stats = stress_test_mvreg(4, 100, 200).await;
-    stats.print();
+    // Small scale tests
+    let (replicas, ops, syncs) = runner.sizes(4, 100, 200);
+    progress!(
+        "── Small Scale ({} replicas, {} ops) ─────────────────────────",
+        replicas,
+        ops
+    );
+    runner.record(stress_test_gset(replicas, ops, syncs).await);
+    runner.record(stress_test_orset(replicas, ops, syncs).await);
+    runner.record(stress_test_pncounter(replicas, ops, syncs).await);
+    runner.record(stress_test_lwwreg(replicas, ops, syncs).await);
+    runner.record(stress_test_mvreg(replicas, ops, syncs).await);
 
     // Medium scale tests
-    println!("\n── Medium Scale (10 replicas, 500 ops) ─────────────────────");
-    let stats = stress_test_gset(10, 500, 1000).await;
-    stats.print();
-
-    let stats = stress_test_orset(10, 500, 1000).await;
-    stats.print();
-
-    let stats = stress_test_pncounter(10, 500, 1000).await;
-    stats.print();
+    let (replicas, ops, syncs) = runner.sizes(10, 500, 1000);
+    progress!(
+        "\n── Medium Scale ({} replicas, {} ops) ─────────────────────",
+        replicas,
+        ops
+    );
+    runner.record(stress_test_gset(replicas, ops, syncs).await);
+    runner.record(stress_test_orset(replicas, ops, syncs).await);
+    runner.record(stress_test_pncounter(replicas, ops, syncs).await);
 
     // Combined test
-    println!("\n── Combined Core CRDT Test ─────────────────────────────────");
-    stress_test_all_core_crdts(6, 200, 400).await;
+    progress!("\n── Combined Core CRDT Test ─────────────────────────────────");
+    let (replicas, ops, syncs) = runner.sizes(6, 200, 400);
+    for stats in stress_test_all_core_crdts(replicas, ops, syncs).await {
+        runner.record_quietly(stats);
+    }
 
-    println!("\n✓ Core CRDT tests completed successfully!");
+    progress!("\n✓ Core CRDT tests completed successfully!");
 }
 
-fn run_db_tests() {
-    println!("\n╔════════════════════════════════════════════════════════════╗");
-    println!("║            DATABASE LAYER STRESS TESTS                     ║");
-    println!("╚════════════════════════════════════════════════════════════╝\n");
+fn run_db_tests(runner: &mut Runner) {
+    progress!("\n╔════════════════════════════════════════════════════════════╗");
+    progress!("║            DATABASE LAYER STRESS TESTS                     ║");
+    progress!("╚════════════════════════════════════════════════════════════╝\n");
 
     // RGA Text tests
-    println!("── RGA Text ────────────────────────────────────────────────");
-    let stats = stress_test_rga_text(4, 100);
-    stats.print();
-
-    let stats = stress_test_rga_text(8, 300);
-    stats.print();
+    progress!("── RGA Text ────────────────────────────────────────────────");
+    for (replicas, ops) in [(4, 100), (8, 300)] {
+        let (replicas, ops, _) = runner.sizes(replicas, ops, 0);
+        runner.record(stress_test_rga_text(replicas, ops));
+    }
 
     // Rich Text tests
-    println!("\n── Rich Text ───────────────────────────────────────────────");
-    let stats = stress_test_rich_text(4, 100);
-    stats.print();
-
-    let stats = stress_test_rich_text(6, 200);
-    stats.print();
+    progress!("\n── Rich Text ───────────────────────────────────────────────");
+    for (replicas, ops) in [(4, 100), (6, 200)] {
+        let (replicas, ops, _) = runner.sizes(replicas, ops, 0);
+        runner.record(stress_test_rich_text(replicas, ops));
+    }
 
     // JSON CRDT tests
-    println!("\n── JSON CRDT ───────────────────────────────────────────────");
-    let stats = stress_test_json_crdt(4, 100);
-    stats.print();
-
-    let stats = stress_test_json_crdt(6, 200);
-    stats.print();
+    progress!("\n── JSON CRDT ───────────────────────────────────────────────");
+    for (replicas, ops) in [(4, 100), (6, 200)] {
+        let (replicas, ops, _) = runner.sizes(replicas, ops, 0);
+        runner.record(stress_test_json_crdt(replicas, ops));
+    }
 
     // Document Store tests
-    println!("\n── Document Store ──────────────────────────────────────────");
-    let stats = stress_test_document_store(50, 200);
-    stats.print();
-
-    let stats = stress_test_document_store(100, 500);
-    stats.print();
+    progress!("\n── Document Store ──────────────────────────────────────────");
+    for (docs, ops) in [(50, 200), (100, 500)] {
+        let (docs, ops, _) = runner.sizes(docs, ops, 0);
+        runner.record(stress_test_document_store(docs, ops));
+    }
 
     // Combined DB test
-    println!("\n── Combined Database Test ──────────────────────────────────");
-    stress_test_all_db_crdts(4, 100);
+    progress!("\n── Combined Database Test ──────────────────────────────────");
+    let (replicas, ops, _) = runner.sizes(4, 100, 0);
+    for stats in stress_test_all_db_crdts(replicas, ops) {
+        runner.record_quietly(stats);
+    }
+
+    progress!("\n✓ Database layer tests completed successfully!");
+}
 
-    println!("\n✓ Database layer tests completed successfully!");
+async fn run_delta_tests(runner: &mut Runner) {
+    let (replicas, ops, _) = runner.sizes(4, 50, 0);
+    for stats in stress_test_delta_suite(replicas, ops).await {
+        runner.report.add(TestRecord::Delta(stats));
+    }
 }
 
-async fn run_scaling_analysis() {
-    println!("\n╔════════════════════════════════════════════════════════════╗");
-    println!("║            SCALING ANALYSIS                                ║");
-    println!("╚════════════════════════════════════════════════════════════╝\n");
+async fn run_scaling_analysis(runner: &mut Runner) {
+    progress!("\n╔════════════════════════════════════════════════════════════╗");
+    progress!("║            SCALING ANALYSIS                                ║");
+    progress!("╚════════════════════════════════════════════════════════════╝\n");
 
-    println!("── GSet Scaling ────────────────────────────────────────────");
-    stress_test_scaling(20, 2).await;
+    progress!("── GSet Scaling ────────────────────────────────────────────");
+    let max_replicas = runner.replicas.unwrap_or(20).max(2);
+    for stats in stress_test_scaling(max_replicas, 2).await {
+        runner.record_quietly(stats);
+    }
 
-    println!("\n── ORSet Scaling ───────────────────────────────────────────");
-    println!("  Replicas │    Ops │  Time (ms) │  ms/op │  Syncs │ Converged");
-    println!("  ─────────┼────────┼────────────┼────────┼────────┼──────────");
-    for num_replicas in [2, 4, 8, 16] {
+    progress!("\n── ORSet Scaling ───────────────────────────────────────────");
+    print_scaling_header();
+    for num_replicas in runner.sweep(&[2, 4, 8, 16]) {
         let ops = num_replicas * 50;
         let syncs = ops * 2;
         let stats = stress_test_orset(num_replicas, ops, syncs).await;
-        let total_ops = stats.num_replicas * stats.operations_per_replica;
-        println!(
-            "  {:>8} │ {:>6} │ {:>10.2} │ {:>6.4} │ {:>6} │ {:>8}",
-            stats.num_replicas,
-            total_ops,
-            stats.total_time.as_secs_f64() * 1000.0,
-            stats.total_time.as_secs_f64() * 1000.0 / total_ops as f64,
-            stats.total_syncs,
-            if stats.converged { "✓" } else { "✗" }
-        );
+        print_scaling_row(&stats);
+        runner.record_quietly(stats);
     }
 
-    println!("\n── RGA Text Scaling ────────────────────────────────────────");
-    println!("  Replicas │    Ops │  Time (ms) │  ms/op │  Syncs │ Converged");
-    println!("  ─────────┼────────┼────────────┼────────┼────────┼──────────");
-    for num_replicas in [2, 4, 8] {
-        let ops = num_replicas * 25;
-        let stats = stress_test_rga_text(num_replicas, ops);
-        let total_ops = stats.num_replicas * stats.operations_per_replica;
-        println!(
-            "  {:>8} │ {:>6} │ {:>10.2} │ {:>6.4} │ {:>6} │ {:>8}",
-            stats.num_replicas,
-            total_ops,
-            stats.total_time.as_secs_f64() * 1000.0,
-            stats.total_time.as_secs_f64() * 1000.0 / total_ops as f64,
-            stats.total_syncs,
-            if stats.converged { "✓" } else { "✗" }
-        );
+    progress!("\n── RGA Text Scaling ────────────────────────────────────────");
+    print_scaling_header();
+    for num_replicas in runner.sweep(&[2, 4, 8]) {
+        let stats = stress_test_rga_text(num_replicas, num_replicas * 25);
+        print_scaling_row(&stats);
+        runner.record_quietly(stats);
     }
 
-    println!("\n── Rich Text Scaling ───────────────────────────────────────");
-    println!("  Replicas │    Ops │  Time (ms) │  ms/op │  Syncs │ Converged");
-    println!("  ─────────┼────────┼────────────┼────────┼────────┼──────────");
-    for num_replicas in [2, 4, 6] {
-        let ops = num_replicas * 20;
-        let stats = stress_test_rich_text(num_replicas, ops);
-        let total_ops = stats.num_replicas * stats.operations_per_replica;
-        println!(
-            "  {:>8} │ {:>6} │ {:>10.2} │ {:>6.4} │ {:>6} │ {:>8}",
-            stats.num_replicas,
-            total_ops,
-            stats.total_time.as_secs_f64() * 1000.0,
-            stats.total_time.as_secs_f64() * 1000.0 / total_ops as f64,
-            stats.total_syncs,
-            if stats.converged { "✓" } else { "✗" }
-        );
+    progress!("\n── Rich Text Scaling ───────────────────────────────────────");
+    print_scaling_header();
+    for num_replicas in runner.sweep(&[2, 4, 6]) {
+        let stats = stress_test_rich_text(num_replicas, num_replicas * 20);
+        print_scaling_row(&stats);
+        runner.record_quietly(stats);
     }
 
-    println!("\n── JSON CRDT Scaling ───────────────────────────────────────");
-    println!("  Replicas │    Ops │  Time (ms) │  ms/op │  Syncs │ Converged");
-    println!("  ─────────┼────────┼────────────┼────────┼────────┼──────────");
-    for num_replicas in [2, 4, 6] {
-        let ops = num_replicas * 20;
-        let stats = stress_test_json_crdt(num_replicas, ops);
-        let total_ops = stats.num_replicas * stats.operations_per_replica;
-        println!(
-            "  {:>8} │ {:>6} │ {:>10.2} │ {:>6.4} │ {:>6} │ {:>8}",
-            stats.num_replicas,
-            total_ops,
-            stats.total_time.as_secs_f64() * 1000.0,
-            stats.total_time.as_secs_f64() * 1000.0 / total_ops as f64,
-            stats.total_syncs,
-            if stats.converged { "✓" } else { "✗" }
-        );
+    progress!("\n── JSON CRDT Scaling ───────────────────────────────────────");
+    print_scaling_header();
+    for num_replicas in runner.sweep(&[2, 4, 6]) {
+        let stats = stress_test_json_crdt(num_replicas, num_replicas * 20);
+        print_scaling_row(&stats);
+        runner.record_quietly(stats);
     }
 
-    println!("\n✓ Scaling analysis completed!");
+    progress!("\n✓ Scaling analysis completed!");
 }
 
-async fn run_full_suite() {
-    println!("\n╔════════════════════════════════════════════════════════════╗");
-    println!("║            FULL BENCHMARK SUITE                            ║");
-    println!("╚════════════════════════════════════════════════════════════╝\n");
+fn print_scaling_header() {
+    progress!("  Replicas │    Ops │  Time (ms) │  ms/op │  Syncs │ Converged");
+    progress!("  ─────────┼────────┼────────────┼────────┼────────┼──────────");
+}
 
-    println!("This will run all tests - this may take several minutes...\n");
+fn print_scaling_row(stats: &StressTestStats) {
+    let total_ops = stats.num_replicas * stats.operations_per_replica;
+    progress!(
+        "  {:>8} │ {:>6} │ {:>10.2} │ {:>6.4} │ {:>6} │ {:>8}",
+        stats.num_replicas,
+        total_ops,
+        stats.total_time.as_secs_f64() * 1000.0,
+        stats.total_time.as_secs_f64() * 1000.0 / total_ops as f64,
+        stats.total_syncs,
+        if stats.converged { "✓" } else { "✗" }
+    );
+}
+
+fn run_soak_test(runner: &Runner, options: &[String]) {
+    let mut config = match SoakConfig::from_args(options) {
+        Ok(config) => config,
+        Err(e) => Cli::command()
+            .error(clap::error::ErrorKind::InvalidValue, e)
+            .exit(),
+    };
+    if let Some(replicas) = runner.replicas {
+        config.replicas = replicas.max(2);
+    }
+
+    let report = run_soak(config);
+    report.print();
+
+    if report.passed() {
+        progress!("\n✓ Soak test completed: memory growth within limits");
+    } else {
+        progress!("\n✗ Soak test failed");
+        std::process::exit(1);
+    }
+}
+
+fn run_routing_benchmark(runner: &mut Runner) {
+    progress!("\n╔════════════════════════════════════════════════════════════╗");
+    progress!("║            CLUSTER ROUTING BENCHMARK                       ║");
+    progress!("╚════════════════════════════════════════════════════════════╝\n");
+
+    let (replicas, messages, _) = runner.sizes(100, 10_000, 0);
+    let report = stress_test_cluster_routing(replicas, messages);
+    report.print();
+    runner.report.add(TestRecord::Benchmark(report));
+
+    progress!("\n✓ Routing benchmark completed!");
+}
+
+async fn run_full_suite(runner: &mut Runner) {
+    progress!("\n╔════════════════════════════════════════════════════════════╗");
+    progress!("║            FULL BENCHMARK SUITE                            ║");
+    progress!("╚════════════════════════════════════════════════════════════╝\n");
+
+    progress!("This will run all tests - this may take several minutes...\n");
 
     // Core CRDT tests
-    println!("════════════════════════════════════════════════════════════");
-    println!("                    PHASE 1: CORE CRDTs");
-    println!("════════════════════════════════════════════════════════════\n");
-    run_core_tests().await;
+    progress!("════════════════════════════════════════════════════════════");
+    progress!("                    PHASE 1: CORE CRDTs");
+    progress!("════════════════════════════════════════════════════════════\n");
+    run_core_tests(runner).await;
 
     // Database layer tests
-    println!("\n════════════════════════════════════════════════════════════");
-    println!("                   PHASE 2: DATABASE LAYER");
-    println!("════════════════════════════════════════════════════════════\n");
-    run_db_tests();
+    progress!("\n════════════════════════════════════════════════════════════");
+    progress!("                   PHASE 2: DATABASE LAYER");
+    progress!("════════════════════════════════════════════════════════════\n");
+    run_db_tests(runner);
+
+    // Delta sync over faulty networks
+    progress!("\n════════════════════════════════════════════════════════════");
+    progress!("                  PHASE 3: DELTA NETWORKS");
+    progress!("════════════════════════════════════════════════════════════\n");
+    run_delta_tests(runner).await;
 
     // Scaling analysis
-    println!("\n════════════════════════════════════════════════════════════");
-    println!("                  PHASE 3: SCALING ANALYSIS");
-    println!("════════════════════════════════════════════════════════════\n");
-    run_scaling_analysis().await;
+    progress!("\n════════════════════════════════════════════════════════════");
+    progress!("                  PHASE 4: SCALING ANALYSIS");
+    progress!("════════════════════════════════════════════════════════════\n");
+    run_scaling_analysis(runner).await;
 
     // Summary
-    println!("\n════════════════════════════════════════════════════════════");
-    println!("                        SUMMARY");
-    println!("════════════════════════════════════════════════════════════");
-    println!();
-    println!("  ✓ All core CRDT tests passed");
-    println!("  ✓ All database layer tests passed");
-    println!("  ✓ Scaling analysis completed");
-    println!();
-    println!("  All tests verify:");
-    println!("    • Idempotence: join(a, a) = a");
-    println!("    • Commutativity: join(a, b) = join(b, a)");
-    println!("    • Associativity: join(join(a, b), c) = join(a, join(b, c))");
-    println!("    • Convergence: all replicas reach identical state");
-    println!();
-    println!("╔════════════════════════════════════════════════════════════╗");
-    println!("║            ✓ FULL SUITE COMPLETED SUCCESSFULLY             ║");
-    println!("╚════════════════════════════════════════════════════════════╝");
+    progress!("\n════════════════════════════════════════════════════════════");
+    progress!("                        SUMMARY");
+    progress!("════════════════════════════════════════════════════════════");
+    progress!();
+    progress!("  ✓ All core CRDT tests passed");
+    progress!("  ✓ All database layer tests passed");
+    progress!("  ✓ All delta network tests passed");
+    progress!("  ✓ Scaling analysis completed");
+    progress!();
+    progress!("  All tests verify:");
+    progress!("    • Idempotence: join(a, a) = a");
+    progress!("    • Commutativity: join(a, b) = join(b, a)");
+    progress!("    • Associativity: join(join(a, b), c) = join(a, join(b, c))");
+    progress!("    • Convergence: all replicas reach identical state");
+    progress!();
+    progress!("╔════════════════════════════════════════════════════════════╗");
+    progress!("║            ✓ FULL SUITE COMPLETED SUCCESSFULLY             ║");
+    progress!("╚════════════════════════════════════════════════════════════╝");
 }
@@ -0,0 +1,817 @@
+//! Long-running soak test with memory growth tracking
+//!
+//! Runs a mixed workload for a fixed wall time and answers one question:
+//! does memory keep growing? Every sample records the process RSS and a
+//! size estimate for each structure under load:
+//! - RGAText replicas (tombstones, pending deltas)
+//! - JsonCrdt replicas (concurrent field values)
+//! - ORSet replicas (tombstones)
+//! - Presence trackers (stale device records, expired by `cleanup_stale`)
+//! - Anti-entropy delta buffers (garbage-collected on ack)
+//! - Causal replicas (per-peer buffers and pending interval queues)
+//!
+//! After a warmup, the growth of each series is fitted with a least-squares
+//! line; a slope above the configured limit fails the run. The samples are
+//! written as CSV for plotting.
+
+use crate::stress_test::{master_seed, progress, seed_line};
+use mdcs_core::clock::ManualClock;
+use mdcs_core::lattice::Lattice;
+use mdcs_core::orset::ORSet;
+use mdcs_core::pncounter::PNCounter;
+use mdcs_db::{
+    Cursor, DeviceId, JsonCrdt, JsonPath, JsonValue, PresenceTracker, RGAText, UserId, UserInfo,
+};
+use mdcs_delta::anti_entropy::{AntiEntropyCluster, NetworkConfig};
+use mdcs_delta::causal::CausalCluster;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+/// Which structures the soak puts under load
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SoakProfile {
+    /// Everything below
+    Mixed,
+    /// RGAText inserts and deletes
+    Text,
+    /// JsonCrdt field overwrites
+    Json,
+    /// ORSet adds and removes
+    Set,
+    /// Presence heartbeats with devices coming and going
+    Presence,
+    /// Counter deltas through the anti-entropy and causal clusters
+    Sync,
+}
+
+impl SoakProfile {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "mixed" => Some(Self::Mixed),
+            "text" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            "set" => Some(Self::Set),
+            "presence" => Some(Self::Presence),
+            "sync" => Some(Self::Sync),
+            _ => None,
+        }
+    }
+
+    fn includes(self, other: SoakProfile) -> bool {
+        self == SoakProfile::Mixed || self == other
+    }
+}
+
+impl fmt::Display for SoakProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Mixed => "mixed",
+            Self::Text => "text",
+            Self::Json => "json",
+            Self::Set => "set",
+            Self::Presence => "presence",
+            Self::Sync => "sync",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Soak test configuration
+#[derive(Clone, Debug)]
+pub struct SoakConfig {
+    /// Wall time to run for
+    pub duration: Duration,
+    /// Number of replicas of each structure
+    pub replicas: usize,
+    pub profile: SoakProfile,
+    /// Time between samples
+    pub sample_interval: Duration,
+    /// Samples taken before this are not used for the slope
+    pub warmup: Duration,
+    /// Maximum post-warmup growth of any series, in bytes per minute;
+    /// `None` only reports the slopes
+    pub max_slope: Option<f64>,
+    /// Operations per replica per second
+    pub ops_per_sec: u64,
+    /// Ticks (one operation per replica) between sync rounds
+    pub sync_every: u64,
+    /// Ticks between replica crashes
+    pub crash_every: u64,
+    /// Ticks between partitioning and healing
+    pub partition_every: u64,
+    /// Where to write the samples
+    pub csv_path: PathBuf,
+    /// Seed of the workload; the suite's master seed by default
+    pub seed: u64,
+}
+
+impl Default for SoakConfig {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_secs(600),
+            replicas: 8,
+            profile: SoakProfile::Mixed,
+            sample_interval: Duration::from_secs(10),
+            warmup: Duration::from_secs(120),
+            max_slope: Some(256.0 * 1024.0),
+            ops_per_sec: 200,
+            sync_every: 10,
+            crash_every: 500,
+            partition_every: 1_000,
+            csv_path: PathBuf::from("logs/soak.csv"),
+            seed: master_seed(),
+        }
+    }
+}
+
+impl SoakConfig {
+    /// A few seconds of everything, to keep the harness itself working
+    pub fn smoke() -> Self {
+        Self {
+            duration: Duration::from_secs(2),
+            replicas: 3,
+            sample_interval: Duration::from_millis(200),
+            warmup: Duration::from_millis(400),
+            max_slope: None,
+            crash_every: 50,
+            partition_every: 100,
+            csv_path: std::env::temp_dir().join("mdcs_soak_smoke.csv"),
+            ..Self::default()
+        }
+    }
+
+    /// Parse `--flag value` pairs on top of the defaults
+    ///
+    /// `--warmup` defaults to a fifth of `--duration`.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut warmup = None;
+        let mut iter = args.iter();
+        while let Some(flag) = iter.next() {
+            let value = iter
+                .next()
+                .ok_or_else(|| format!("missing value for {}", flag))?;
+            let number = || {
+                value
+                    .parse::<f64>()
+                    .map_err(|_| format!("invalid value for {}: {}", flag, value))
+            };
+            match flag.as_str() {
+                "--duration" => config.duration = Duration::from_secs_f64(number()?),
+                "--replicas" => config.replicas = (number()? as usize).max(2),
+                "--profile" => {
+                    config.profile = SoakProfile::parse(value)
+                        .ok_or_else(|| format!("unknown profile: {}", value))?
+                }
+                "--sample" => config.sample_interval = Duration::from_secs_f64(number()?),
+                "--warmup" => warmup = Some(Duration::from_secs_f64(number()?)),
+                "--max-slope" => config.max_slope = Some(number()?),
+                "--ops-per-sec" => config.ops_per_sec = (number()? as u64).max(1),
+                "--csv" => config.csv_path = PathBuf::from(value),
+                "--seed" => config.seed = number()? as u64,
+                _ => return Err(format!("unknown option: {}", flag)),
+            }
+        }
+        config.warmup = warmup.unwrap_or(config.duration / 5);
+        Ok(config)
+    }
+}
+
+// ============================================================================
+// Size Estimates
+// ============================================================================
+
+/// Approximate memory held by a structure, in bytes
+///
+/// Serializable structures are measured by their encoded size, which
+/// tracks the number of nodes, tags and values they hold. The rest use
+/// their debug representation, which lists every record.
+pub trait SizeOf {
+    fn size_of(&self) -> usize;
+}
+
+fn encoded_len<T: Serialize>(value: &T) -> usize {
+    serde_json::to_vec(value).map_or(0, |bytes| bytes.len())
+}
+
+impl SizeOf for RGAText {
+    fn size_of(&self) -> usize {
+        encoded_len(self)
+    }
+}
+
+impl SizeOf for JsonCrdt {
+    fn size_of(&self) -> usize {
+        encoded_len(self)
+    }
+}
+
+impl SizeOf for ORSet<String> {
+    fn size_of(&self) -> usize {
+        encoded_len(self)
+    }
+}
+
+impl SizeOf for PresenceTracker {
+    fn size_of(&self) -> usize {
+        format!("{:?}", self).len()
+    }
+}
+
+impl SizeOf for AntiEntropyCluster<PNCounter<String>> {
+    /// Buffered deltas only; the counters themselves are bounded
+    fn size_of(&self) -> usize {
+        (0..self.len())
+            .flat_map(|i| self.replica(i).buffer().deltas_since(0))
+            .map(|td| encoded_len(&*td.delta))
+            .sum()
+    }
+}
+
+impl SizeOf for CausalCluster<PNCounter<String>> {
+    fn size_of(&self) -> usize {
+        (0..self.len())
+            .map(|i| format!("{:?}", self.replica(i)).len())
+            .sum()
+    }
+}
+
+impl<T: SizeOf> SizeOf for Vec<T> {
+    fn size_of(&self) -> usize {
+        self.iter().map(SizeOf::size_of).sum()
+    }
+}
+
+/// Resident set size of this process, where the platform reports it
+fn process_rss() -> Option<usize> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kb: usize = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+// ============================================================================
+// Workload
+// ============================================================================
+
+/// The replicated structures under load
+struct Workload {
+    profile: SoakProfile,
+    rng: StdRng,
+    texts: Vec<RGAText>,
+    jsons: Vec<JsonCrdt>,
+    sets: Vec<ORSet<String>>,
+    trackers: Vec<PresenceTracker>,
+    /// Device generation of each tracker
+    devices: Vec<usize>,
+    clock: Arc<ManualClock>,
+    delta_cluster: AntiEntropyCluster<PNCounter<String>>,
+    causal_cluster: CausalCluster<PNCounter<String>>,
+    /// Replicas `0..split` and `split..` cannot reach each other
+    split: Option<usize>,
+    ops: u64,
+    syncs: u64,
+    crashes: u64,
+    partitions: u64,
+}
+
+/// Simulated milliseconds per tick, for presence staleness
+const TICK_MILLIS: u64 = 100;
+/// Presence records expire after this many simulated milliseconds
+const STALE_MILLIS: u64 = 5_000;
+/// Visible characters each text is kept around
+const TEXT_TARGET: usize = 200;
+/// Distinct keys and elements, so live data stays bounded
+const KEYS: usize = 32;
+
+impl Workload {
+    fn new(config: &SoakConfig) -> Self {
+        let n = config.replicas;
+        let clock = Arc::new(ManualClock::new(0));
+        let trackers = (0..n)
+            .map(|i| Self::tracker(i, 0, &clock))
+            .collect::<Vec<_>>();
+        Self {
+            profile: config.profile,
+            rng: StdRng::seed_from_u64(config.seed),
+            texts: (0..n)
+                .map(|i| RGAText::new(format!("text_{}", i)))
+                .collect(),
+            jsons: (0..n)
+                .map(|i| JsonCrdt::new(format!("json_{}", i)))
+                .collect(),
+            sets: (0..n).map(|_| ORSet::new()).collect(),
+            trackers,
+            devices: vec![0; n],
+            clock,
+            delta_cluster: AntiEntropyCluster::new(n, NetworkConfig::lossy(0.05)),
+            causal_cluster: CausalCluster::new(n, 0.05),
+            split: None,
+            ops: 0,
+            syncs: 0,
+            crashes: 0,
+            partitions: 0,
+        }
+    }
+
+    fn tracker(idx: usize, device: usize, clock: &Arc<ManualClock>) -> PresenceTracker {
+        let user = UserId::new(format!("user_{}", idx));
+        let info = UserInfo::new(format!("User {}", idx), "#3366ff");
+        let mut tracker =
+            PresenceTracker::new(user, info).with_device(DeviceId(format!("device_{}", device)));
+        tracker.set_clock(Arc::clone(clock));
+        tracker.set_stale_timeout(STALE_MILLIS);
+        tracker
+    }
+
+    fn len(&self) -> usize {
+        self.texts.len()
+    }
+
+    /// One operation on one replica of every structure in the profile
+    fn op(&mut self, idx: usize) {
+        let profile = self.profile;
+        if profile.includes(SoakProfile::Text) {
+            let text = &mut self.texts[idx];
+            if text.len() < TEXT_TARGET || self.rng.gen_bool(0.4) {
+                let pos = self.rng.gen_range(0..=text.len());
+                text.insert(pos, "ab");
+            } else {
+                let len = self.rng.gen_range(1..=4).min(text.len());
+                let pos = self.rng.gen_range(0..=text.len() - len);
+                text.delete(pos, len);
+            }
+        }
+        if profile.includes(SoakProfile::Json) {
+            let key = format!("k{}", self.rng.gen_range(0..KEYS));
+            let value = JsonValue::Int(self.rng.gen_range(0..1000));
+            self.jsons[idx].set(&JsonPath::parse(&key), value).unwrap();
+        }
+        if profile.includes(SoakProfile::Set) {
+            let element = format!("e{}", self.rng.gen_range(0..KEYS));
+            let set = &mut self.sets[idx];
+            if set.contains(&element) {
+                set.remove(&element);
+            } else {
+                set.add(&format!("set_{}", idx), element);
+            }
+        }
+        if profile.includes(SoakProfile::Presence) {
+            let pos = self.rng.gen_range(0..TEXT_TARGET);
+            let tracker = &mut self.trackers[idx];
+            tracker.set_cursor("doc", Cursor::at(pos));
+            tracker.heartbeat();
+        }
+        if profile.includes(SoakProfile::Sync) {
+            let amount = self.rng.gen_range(1..10);
+            let id = self.delta_cluster.replica(idx).id.clone();
+            self.delta_cluster
+                .mutate(idx, move |state| increment(state, id, amount));
+            let id = self.causal_cluster.replica(idx).id().clone();
+            self.causal_cluster
+                .mutate(idx, move |state| increment(state, id, amount));
+        }
+        self.ops += 1;
+    }
+
+    /// Exchange deltas between reachable replicas and expire stale presence
+    fn sync_round(&mut self) {
+        let n = self.len();
+        let profile = self.profile;
+        let split = self.split;
+
+        if profile.includes(SoakProfile::Text) {
+            let deltas: Vec<_> = self.texts.iter_mut().map(|t| t.take_delta()).collect();
+            for (from, delta) in deltas.iter().enumerate() {
+                let Some(delta) = delta else { continue };
+                for to in (0..n).filter(|&to| to != from && reachable(split, from, to)) {
+                    self.texts[to].apply_delta(delta);
+                }
+            }
+        }
+        if profile.includes(SoakProfile::Json) {
+            let deltas: Vec<_> = self.jsons.iter_mut().map(|j| j.take_delta()).collect();
+            for (from, delta) in deltas.iter().enumerate() {
+                let Some(delta) = delta else { continue };
+                for to in (0..n).filter(|&to| to != from && reachable(split, from, to)) {
+                    self.jsons[to].apply_delta(delta);
+                }
+            }
+        }
+        if profile.includes(SoakProfile::Set) {
+            for from in 0..n {
+                let to = (from + 1) % n;
+                if reachable(split, from, to) {
+                    self.sets[to] = self.sets[to].join(&self.sets[from]);
+                }
+            }
+        }
+        if profile.includes(SoakProfile::Presence) {
+            let deltas: Vec<_> = self.trackers.iter_mut().map(|t| t.take_delta()).collect();
+            for (from, delta) in deltas.iter().enumerate() {
+                let Some(delta) = delta else { continue };
+                for to in (0..n).filter(|&to| to != from && reachable(split, from, to)) {
+                    self.trackers[to].apply_delta(delta);
+                }
+            }
+            for tracker in &mut self.trackers {
+                tracker.cleanup_stale();
+            }
+        }
+        if profile.includes(SoakProfile::Sync) {
+            self.delta_cluster.full_sync_round();
+            self.delta_cluster.retransmit_and_process();
+            self.causal_cluster.full_sync_round();
+            self.causal_cluster.retransmit_and_process();
+        }
+        self.syncs += 1;
+    }
+
+    /// Restart one replica, losing its volatile state
+    ///
+    /// Text, JSON and set replicas reload from their serialized state and
+    /// catch up by a state join with a reachable peer; presence comes back
+    /// on a new device, leaving the old one to expire.
+    fn crash(&mut self, idx: usize) {
+        let n = self.len();
+        let peer = (1..n)
+            .map(|k| (idx + k) % n)
+            .find(|&p| reachable(self.split, idx, p));
+        let profile = self.profile;
+
+        if profile.includes(SoakProfile::Text) {
+            let restored: RGAText = reload(&self.texts[idx]);
+            self.texts[idx] = match peer {
+                Some(p) => restored.join(&self.texts[p]),
+                None => restored,
+            };
+        }
+        if profile.includes(SoakProfile::Json) {
+            let restored: JsonCrdt = reload(&self.jsons[idx]);
+            self.jsons[idx] = match peer {
+                Some(p) => restored.join(&self.jsons[p]),
+                None => restored,
+            };
+        }
+        if profile.includes(SoakProfile::Presence) {
+            self.devices[idx] += 1;
+            self.trackers[idx] = Self::tracker(idx, self.devices[idx], &self.clock);
+        }
+        if profile.includes(SoakProfile::Sync) {
+            self.delta_cluster.crash_and_recover(idx);
+            self.causal_cluster.crash_and_recover(idx);
+        }
+        self.crashes += 1;
+    }
+
+    /// Split the replicas in two
+    fn partition(&mut self) {
+        let n = self.len();
+        let split = self.rng.gen_range(1..n);
+        for a in 0..split {
+            for b in split..n {
+                self.delta_cluster.partition(a, b);
+                self.causal_cluster.partition(a, b);
+            }
+        }
+        self.split = Some(split);
+        self.partitions += 1;
+    }
+
+    fn heal(&mut self) {
+        self.split = None;
+        self.delta_cluster.heal();
+        self.causal_cluster.heal();
+        // Deltas exchanged during the partition never crossed it
+        let n = self.len();
+        for i in 1..n {
+            self.texts[0] = self.texts[0].join(&self.texts[i]);
+            self.jsons[0] = self.jsons[0].join(&self.jsons[i]);
+        }
+        for i in 1..n {
+            self.texts[i] = self.texts[i].join(&self.texts[0]);
+            self.jsons[i] = self.jsons[i].join(&self.jsons[0]);
+        }
+    }
+
+    /// Heal, sync until quiet and check every structure agrees
+    fn settle(&mut self) -> bool {
+        self.heal();
+        for _ in 0..self.len() + 2 {
+            self.sync_round();
+        }
+        let texts = self
+            .texts
+            .iter()
+            .all(|t| t.to_string() == self.texts[0].to_string());
+        let jsons = self
+            .jsons
+            .iter()
+            .all(|j| j.to_json() == self.jsons[0].to_json());
+        let sets = self.sets.iter().all(|s| s == &self.sets[0]);
+        texts
+            && jsons
+            && sets
+            && self.delta_cluster.is_converged()
+            && self.causal_cluster.is_converged()
+    }
+
+    fn sample(&self, elapsed: Duration) -> Sample {
+        Sample {
+            elapsed,
+            rss: process_rss(),
+            sizes: [
+                self.texts.size_of(),
+                self.jsons.size_of(),
+                self.sets.size_of(),
+                self.trackers.size_of(),
+                self.delta_cluster.size_of(),
+                self.causal_cluster.size_of(),
+            ],
+            causal_pending: self.causal_cluster.total_pending(),
+        }
+    }
+}
+
+/// Whether replicas `a` and `b` are on the same side of a partition
+fn reachable(split: Option<usize>, a: usize, b: usize) -> bool {
+    match split {
+        Some(split) => (a < split) == (b < split),
+        None => true,
+    }
+}
+
+fn increment(state: &PNCounter<String>, id: String, amount: u64) -> PNCounter<String> {
+    let mut delta = PNCounter::new();
+    let total = state.get_increment(&id) + amount;
+    delta.increment(id, total);
+    delta
+}
+
+/// Round-trip through serde, dropping everything not persisted
+fn reload<T: Serialize + serde::de::DeserializeOwned>(value: &T) -> T {
+    serde_json::from_slice(&serde_json::to_vec(value).unwrap()).unwrap()
+}
+
+// ============================================================================
+// Samples & Report
+// ============================================================================
+
+const SERIES: [&str; 6] = [
+    "text_bytes",
+    "json_bytes",
+    "set_bytes",
+    "presence_bytes",
+    "delta_buffer_bytes",
+    "causal_bytes",
+];
+
+/// One measurement of memory use
+#[derive(Clone, Debug)]
+pub struct Sample {
+    pub elapsed: Duration,
+    pub rss: Option<usize>,
+    /// Size estimates, in the order of the CSV columns
+    pub sizes: [usize; 6],
+    pub causal_pending: usize,
+}
+
+/// Post-warmup growth of one series
+#[derive(Clone, Debug)]
+pub struct Growth {
+    pub series: String,
+    /// Least-squares slope in bytes per minute
+    pub slope: f64,
+    pub passed: bool,
+}
+
+/// Result of a soak run
+#[derive(Debug)]
+pub struct SoakReport {
+    pub config: SoakConfig,
+    pub samples: Vec<Sample>,
+    pub growth: Vec<Growth>,
+    pub converged: bool,
+    pub ops: u64,
+    pub syncs: u64,
+    pub crashes: u64,
+    pub partitions: u64,
+}
+
+impl SoakReport {
+    /// Converged, and no series grew faster than allowed
+    pub fn passed(&self) -> bool {
+        self.converged && self.growth.iter().all(|g| g.passed)
+    }
+
+    pub fn print(&self) {
+        progress!(
+            "\n── Soak Result ({}) ──────────────────────────────────",
+            self.config.profile
+        );
+        progress!(
+            "  Ops: {} │ Syncs: {} │ Crashes: {} │ Partitions: {} │ Samples: {}",
+            self.ops,
+            self.syncs,
+            self.crashes,
+            self.partitions,
+            self.samples.len()
+        );
+        if let Some(last) = self.samples.last() {
+            if let Some(rss) = last.rss {
+                progress!("  Final RSS: {:.1} MiB", rss as f64 / (1024.0 * 1024.0));
+            }
+            for (name, size) in SERIES.iter().zip(last.sizes) {
+                progress!("  {:<20} {:>12} bytes", name, size);
+            }
+        }
+        progress!("  Post-warmup growth (bytes/minute):");
+        for g in &self.growth {
+            progress!(
+                "    {:<20} {:>14.1} {}",
+                g.series,
+                g.slope,
+                if g.passed { "✓" } else { "✗" }
+            );
+        }
+        progress!("  Converged: {}", if self.converged { "✓" } else { "✗" });
+        progress!("  Samples written to {}", self.config.csv_path.display());
+    }
+
+    fn write_csv(&self) -> std::io::Result<()> {
+        if let Some(dir) = self.config.csv_path.parent() {
+            if !dir.as_os_str().is_empty() {
+                fs::create_dir_all(dir)?;
+            }
+        }
+        let mut out = String::from("elapsed_secs,rss_bytes");
+        for name in SERIES {
+            out.push(',');
+            out.push_str(name);
+        }
+        out.push_str(",causal_pending,seed\n");
+        for s in &self.samples {
+            out.push_str(&format!("{:.3},", s.elapsed.as_secs_f64()));
+            if let Some(rss) = s.rss {
+                out.push_str(&rss.to_string());
+            }
+            for size in s.sizes {
+                out.push_str(&format!(",{}", size));
+            }
+            out.push_str(&format!(",{},{}\n", s.causal_pending, self.config.seed));
+        }
+        fs::write(&self.config.csv_path, out)
+    }
+}
+
+/// Least-squares slope of `(minutes, bytes)` points
+fn slope(points: &[(f64, f64)]) -> f64 {
+    let n = points.len() as f64;
+    if points.len() < 2 {
+        return 0.0;
+    }
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let cov: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    let var: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    if var == 0.0 {
+        0.0
+    } else {
+        cov / var
+    }
+}
+
+fn growth(config: &SoakConfig, samples: &[Sample]) -> Vec<Growth> {
+    let steady: Vec<_> = samples
+        .iter()
+        .filter(|s| s.elapsed >= config.warmup)
+        .collect();
+    let minutes = |s: &Sample| s.elapsed.as_secs_f64() / 60.0;
+
+    let mut series = Vec::new();
+    if steady.iter().all(|s| s.rss.is_some()) {
+        let points: Vec<_> = steady
+            .iter()
+            .map(|s| (minutes(s), s.rss.unwrap_or(0) as f64))
+            .collect();
+        series.push(("rss_bytes".to_string(), points));
+    }
+    for (i, name) in SERIES.iter().enumerate() {
+        let points: Vec<_> = steady
+            .iter()
+            .map(|s| (minutes(s), s.sizes[i] as f64))
+            .collect();
+        series.push((name.to_string(), points));
+    }
+
+    series
+        .into_iter()
+        .map(|(name, points)| {
+            let slope = slope(&points);
+            Growth {
+                series: name,
+                slope,
+                passed: config.max_slope.is_none_or(|max| slope <= max),
+            }
+        })
+        .collect()
+}
+
+// ============================================================================
+// Runner
+// ============================================================================
+
+/// Run the soak workload for `config.duration` of wall time
+pub fn run_soak(config: SoakConfig) -> SoakReport {
+    progress!("\n╔════════════════════════════════════════════════════════════╗");
+    progress!("║  Soak Test                                                 ║");
+    progress!(
+        "║  Profile: {:<8} │ Replicas: {:>3} │ Duration: {:>6.0}s       ║",
+        config.profile.to_string(),
+        config.replicas,
+        config.duration.as_secs_f64()
+    );
+    progress!("{}", seed_line(config.seed));
+    progress!("╚════════════════════════════════════════════════════════════╝");
+
+    let mut workload = Workload::new(&config);
+    let n = workload.len();
+    let tick = Duration::from_secs_f64(1.0 / config.ops_per_sec as f64);
+
+    let start = Instant::now();
+    let mut samples = vec![workload.sample(Duration::ZERO)];
+    let mut next_sample = config.sample_interval;
+    let mut ticks: u64 = 0;
+
+    while start.elapsed() < config.duration {
+        for idx in 0..n {
+            workload.op(idx);
+        }
+        workload.clock.advance(TICK_MILLIS);
+        ticks += 1;
+
+        if ticks.is_multiple_of(config.sync_every) {
+            workload.sync_round();
+        }
+        if ticks.is_multiple_of(config.crash_every) {
+            let idx = workload.rng.gen_range(0..n);
+            workload.crash(idx);
+        }
+        if ticks.is_multiple_of(config.partition_every) {
+            if workload.split.is_some() {
+                workload.heal();
+            } else {
+                workload.partition();
+            }
+        }
+
+        let elapsed = start.elapsed();
+        if elapsed >= next_sample {
+            let sample = workload.sample(elapsed);
+            if let Some(rss) = sample.rss {
+                progress!(
+                    "  [{:>7.1}s] rss {:>8.1} MiB │ ops {:>9}",
+                    elapsed.as_secs_f64(),
+                    rss as f64 / (1024.0 * 1024.0),
+                    workload.ops
+                );
+            }
+            samples.push(sample);
+            next_sample += config.sample_interval;
+        }
+
+        // Hold the configured rate
+        let target = tick * ticks as u32;
+        if let Some(wait) = target.checked_sub(start.elapsed()) {
+            std::thread::sleep(wait);
+        }
+    }
+
+    let converged = workload.settle();
+    samples.push(workload.sample(start.elapsed()));
+
+    let report = SoakReport {
+        growth: growth(&config, &samples),
+        samples,
+        converged,
+        ops: workload.ops,
+        syncs: workload.syncs,
+        crashes: workload.crashes,
+        partitions: workload.partitions,
+        config,
+    };
+    if let Err(e) = report.write_csv() {
+        progress!("  Failed to write samples: {}", e);
+    }
+    report
+}
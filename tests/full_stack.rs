@@ -0,0 +1,980 @@
+//! End-to-end tests across the workspace crates.
+//!
+//! Each crate tests its own pieces; these wire the real pieces together so
+//! that a change breaking the contract between two crates fails here:
+//! - SDK clients editing text, rich text and JSON documents over a lossy
+//!   in-memory transport, through a partition, a late join, a restart and
+//!   a crash, with presence on top
+//! - Causal delta replicas persisting to files, crashing and recovering
+//! - Merkle-DAG replicas compacting behind a snapshot anchor, and a new
+//!   replica bootstrapping from it
+//!
+//! Every scenario is seeded and runs a few seeds under `cargo test`. The
+//! ignored variants run more seeds at larger sizes:
+//!
+//! ```text
+//! cargo test --test full_stack -- --ignored
+//! ```
+
+use mdcs_compaction::{Compactor, Pruner, PruningPolicy, Snapshot, VersionVector};
+use mdcs_core::clock::ManualClock;
+use mdcs_core::lattice::Lattice;
+use mdcs_core::pncounter::PNCounter;
+use mdcs_delta::causal::{
+    CausalMessage, CausalNetworkSimulator, CausalReplica, DurableState, DurableStorage,
+    StorageError,
+};
+use mdcs_merkle::{DAGStore, DAGSyncer, MemoryDAGStore, NodeBuilder, Payload, SyncConfig};
+use mdcs_sdk::{
+    Channel, Client, ClientConfigBuilder, Inbox, JsonValue, MarkType, MemoryDocStorage,
+    MemoryTransport, Message, NetworkTransport, PeerId, SdkError, Session,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Seeds run by the default tests
+const SEEDS: u64 = 4;
+/// Seeds run by the `--ignored` variants
+const EXTENDED_SEEDS: u64 = 32;
+
+type Counter = PNCounter<String>;
+
+/// A delta raising `id`'s share of `state` by `amount`
+///
+/// PN-counter entries join by max, so the delta carries the new total.
+fn bump(state: &Counter, id: &str, amount: i64) -> Counter {
+    let id = id.to_string();
+    let mut delta = Counter::new();
+    if amount >= 0 {
+        let total = state.get_increment(&id) + amount as u64;
+        delta.increment(id, total);
+    } else {
+        let total = state.get_decrement(&id) + amount.unsigned_abs();
+        delta.decrement(id, total);
+    }
+    delta
+}
+
+/// A non-zero amount to add to a counter
+fn amount(rng: &mut StdRng) -> i64 {
+    match rng.gen_range(-5..5) {
+        0 => 5,
+        n => n,
+    }
+}
+
+// ============================================================================
+// SDK Clients
+// ============================================================================
+
+const SESSION: &str = "full-stack";
+const TEXT: &str = "notes";
+const RICH: &str = "report";
+const JSON: &str = "settings";
+
+/// One SDK client with its session and inbox
+struct Node {
+    client: Client<MemoryTransport>,
+    session: Arc<Session<MemoryTransport>>,
+    inbox: Inbox,
+}
+
+impl Node {
+    /// Start a client on `storage` and open the documents, restoring them
+    /// from their last checkpoint there
+    fn start(name: &str, storage: &Arc<MemoryDocStorage>, clock: &Arc<ManualClock>) -> Self {
+        let config = ClientConfigBuilder::new()
+            .user_name(name)
+            .shutdown_timeout(50)
+            .build()
+            .unwrap();
+        let transport = Arc::new(MemoryTransport::new(PeerId::new(name)));
+        let inbox = transport.subscribe();
+        let client = Client::new(PeerId::new(name), transport, config)
+            .with_storage(storage.clone())
+            .with_clock(clock.clone());
+        let session = client.create_session(SESSION);
+        session.open_text_doc(TEXT);
+        session.open_rich_text_doc(RICH);
+        session.open_json_doc(JSON);
+        Self {
+            client,
+            session,
+            inbox,
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.client.user_name()
+    }
+
+    fn text(&self) -> String {
+        self.session.open_text_doc(TEXT).read().get_text()
+    }
+
+    fn rich_text(&self) -> String {
+        self.session.open_rich_text_doc(RICH).read().get_content()
+    }
+
+    fn json(&self) -> serde_json::Value {
+        self.session.open_json_doc(JSON).read().root()
+    }
+
+    /// Status and text cursor of every user, by user ID
+    fn presence(&self) -> BTreeMap<String, (String, Option<usize>)> {
+        self.session
+            .awareness()
+            .get_users()
+            .into_iter()
+            .map(|user| {
+                let cursor = user.cursors.get(TEXT).map(|c| c.position);
+                (user.user_id, (format!("{:?}", user.status), cursor))
+            })
+            .collect()
+    }
+
+    /// Make one random edit to one of the documents
+    fn edit(&self, rng: &mut StdRng) {
+        match rng.gen_range(0..3) {
+            0 => {
+                let doc = self.session.open_text_doc(TEXT);
+                let mut doc = doc.write();
+                let len = doc.len();
+                if len > 0 && rng.gen_bool(0.3) {
+                    let at = rng.gen_range(0..len);
+                    doc.delete(at, rng.gen_range(1..=3).min(len - at));
+                } else {
+                    doc.insert(rng.gen_range(0..=len), &word(rng));
+                }
+                if rng.gen_bool(0.1) {
+                    doc.undo();
+                }
+            }
+            1 => {
+                let doc = self.session.open_rich_text_doc(RICH);
+                let mut doc = doc.write();
+                let len = doc.len();
+                if len > 1 && rng.gen_bool(0.3) {
+                    let start = rng.gen_range(0..len - 1);
+                    let end = rng.gen_range(start + 1..=len);
+                    let mark = if rng.gen_bool(0.5) {
+                        MarkType::Bold
+                    } else {
+                        MarkType::Italic
+                    };
+                    doc.format(start, end, mark);
+                } else {
+                    doc.insert(rng.gen_range(0..=len), &word(rng));
+                }
+            }
+            _ => {
+                let doc = self.session.open_json_doc(JSON);
+                let mut doc = doc.write();
+                let key = format!("k{}", rng.gen_range(0..6));
+                match rng.gen_range(0..4) {
+                    0 => doc.delete(&key),
+                    1 => doc.set(&key, JsonValue::String(word(rng))),
+                    _ => doc.set(&key, JsonValue::Int(rng.gen_range(0..100))),
+                }
+            }
+        }
+    }
+
+    /// Publish pending edits, losing one update now and then
+    async fn publish(&self, rng: &mut StdRng) {
+        if rng.gen_bool(0.15) && !self.session.peers().await.is_empty() {
+            self.client.transport().drop_next_on(Channel::Document, 1);
+        }
+        self.session.publish().await.unwrap();
+    }
+
+    /// Move the text cursor and tell the peers
+    async fn announce(&self, rng: &mut StdRng) {
+        let len = self.session.open_text_doc(TEXT).read().len();
+        let awareness = self.session.awareness();
+        awareness.set_cursor(TEXT, rng.gen_range(0..=len));
+        if let Some(delta) = awareness.take_delta() {
+            let delta = serde_json::to_vec(&delta).unwrap();
+            self.client
+                .transport()
+                .broadcast(Message::Awareness { delta })
+                .await
+                .unwrap();
+        }
+    }
+}
+
+fn word(rng: &mut StdRng) -> String {
+    let len = rng.gen_range(1..4);
+    (0..len)
+        .map(|_| rng.gen_range(b'a'..=b'z') as char)
+        .collect()
+}
+
+fn link(a: &Node, b: &Node) {
+    a.client.transport().connect_to(b.client.transport());
+}
+
+async fn unlink(a: &Node, b: &Node) {
+    a.client.disconnect_peer(b.client.peer_id()).await.unwrap();
+    b.client.disconnect_peer(a.client.peer_id()).await.unwrap();
+}
+
+/// Handle every queued message until all inboxes are empty
+///
+/// Acks to a peer that left in the meantime fail to send, as they would on
+/// a real network; any other error fails the test.
+async fn pump(nodes: &mut [Node]) {
+    loop {
+        let mut delivered = false;
+        for node in nodes.iter_mut() {
+            while let Ok((from, message)) = node.inbox.try_recv() {
+                delivered = true;
+                match node.session.handle_message(&from, &message).await {
+                    Ok(_) | Err(SdkError::SyncError(_)) => {}
+                    Err(e) => panic!("{} failed to handle a message: {e}", node.name()),
+                }
+            }
+        }
+        if !delivered {
+            break;
+        }
+    }
+}
+
+/// Random edits on every node, published and delivered at random
+async fn edit_rounds(nodes: &mut [Node], steps: usize, rng: &mut StdRng, clock: &ManualClock) {
+    for _ in 0..steps {
+        let node = &nodes[rng.gen_range(0..nodes.len())];
+        node.edit(rng);
+        node.publish(rng).await;
+        if rng.gen_bool(0.5) {
+            pump(nodes).await;
+        }
+        clock.advance(rng.gen_range(1..10));
+    }
+    pump(nodes).await;
+}
+
+/// Anti-entropy: exchange full states until every node agrees
+async fn settle(nodes: &mut [Node]) {
+    for _ in 0..3 {
+        for node in nodes.iter() {
+            node.session.publish().await.unwrap();
+            node.session.publish_snapshots().await.unwrap();
+        }
+        pump(nodes).await;
+        if converged(nodes) {
+            return;
+        }
+    }
+}
+
+fn converged(nodes: &[Node]) -> bool {
+    let first = &nodes[0];
+    nodes.iter().all(|node| {
+        node.text() == first.text()
+            && node.rich_text() == first.rich_text()
+            && node.json() == first.json()
+    })
+}
+
+fn assert_converged(nodes: &[Node], seed: u64) {
+    let (first, rest) = nodes.split_first().unwrap();
+    for node in rest {
+        let (a, b) = (first.name(), node.name());
+        assert_eq!(
+            node.text(),
+            first.text(),
+            "seed {seed}: text of {a} and {b}"
+        );
+        assert_eq!(
+            node.rich_text(),
+            first.rich_text(),
+            "seed {seed}: rich text of {a} and {b}"
+        );
+        assert_eq!(
+            node.json(),
+            first.json(),
+            "seed {seed}: JSON of {a} and {b}"
+        );
+    }
+}
+
+/// Everyone sees everyone online, at the same cursor
+fn assert_presence_converged(nodes: &[Node], seed: u64) {
+    let names: Vec<_> = nodes.iter().map(Node::name).collect();
+    let first = nodes[0].presence();
+    assert_eq!(
+        first.keys().map(String::as_str).collect::<Vec<_>>(),
+        names,
+        "seed {seed}: users seen by {}",
+        nodes[0].name()
+    );
+    assert!(
+        first.values().all(|(status, _)| status == "Online"),
+        "seed {seed}: {first:?}"
+    );
+    for node in &nodes[1..] {
+        assert_eq!(
+            node.presence(),
+            first,
+            "seed {seed}: presence seen by {} and {}",
+            nodes[0].name(),
+            node.name()
+        );
+    }
+}
+
+async fn sdk_scenario(seed: u64, steps: usize) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let clock = Arc::new(ManualClock::new(1_000));
+    let storage: Vec<_> = (0..3).map(|_| Arc::new(MemoryDocStorage::new())).collect();
+    let carol_storage = &storage[2];
+
+    let mut nodes = vec![
+        Node::start("alice", &storage[0], &clock),
+        Node::start("bob", &storage[1], &clock),
+    ];
+    link(&nodes[0], &nodes[1]);
+    edit_rounds(&mut nodes, steps, &mut rng, &clock).await;
+
+    // Both sides of a partition keep editing
+    unlink(&nodes[0], &nodes[1]).await;
+    edit_rounds(&mut nodes, steps, &mut rng, &clock).await;
+    link(&nodes[0], &nodes[1]);
+    settle(&mut nodes).await;
+    assert_converged(&nodes, seed);
+
+    // A late joiner catches up from snapshots
+    nodes.push(Node::start("carol", carol_storage, &clock));
+    link(&nodes[0], &nodes[2]);
+    link(&nodes[1], &nodes[2]);
+    settle(&mut nodes).await;
+    assert_converged(&nodes, seed);
+    edit_rounds(&mut nodes, steps, &mut rng, &clock).await;
+
+    // Carol leaves cleanly, checkpointing her documents, and comes back to
+    // exactly what she left with
+    let carol = nodes.pop().unwrap();
+    let left_with = (carol.text(), carol.rich_text(), carol.json());
+    match carol.client.shutdown().await {
+        Ok(()) | Err(SdkError::PartialFlush(_)) => {}
+        Err(e) => panic!("seed {seed}: carol failed to shut down: {e}"),
+    }
+    for node in &nodes {
+        node.client
+            .disconnect_peer(carol.client.peer_id())
+            .await
+            .unwrap();
+    }
+    drop(carol);
+    pump(&mut nodes).await;
+    edit_rounds(&mut nodes, steps / 2, &mut rng, &clock).await;
+
+    let carol = Node::start("carol", carol_storage, &clock);
+    assert_eq!(
+        (carol.text(), carol.rich_text(), carol.json()),
+        left_with,
+        "seed {seed}: carol's documents after restarting"
+    );
+    nodes.push(carol);
+    link(&nodes[0], &nodes[2]);
+    link(&nodes[1], &nodes[2]);
+    settle(&mut nodes).await;
+    edit_rounds(&mut nodes, steps, &mut rng, &clock).await;
+
+    // Carol crashes: nothing is flushed or checkpointed, so she restarts
+    // from her previous checkpoint and catches up from her peers
+    let carol = nodes.pop().unwrap();
+    for node in &nodes {
+        unlink(node, &carol).await;
+    }
+    drop(carol);
+    edit_rounds(&mut nodes, steps / 2, &mut rng, &clock).await;
+
+    nodes.push(Node::start("carol", carol_storage, &clock));
+    link(&nodes[0], &nodes[2]);
+    link(&nodes[1], &nodes[2]);
+    settle(&mut nodes).await;
+    edit_rounds(&mut nodes, steps, &mut rng, &clock).await;
+
+    for node in &nodes {
+        node.announce(&mut rng).await;
+    }
+    settle(&mut nodes).await;
+    assert_converged(&nodes, seed);
+    assert_presence_converged(&nodes, seed);
+}
+
+#[tokio::test]
+async fn test_sdk_clients_converge_through_faults() {
+    for seed in 0..SEEDS {
+        sdk_scenario(seed, 20).await;
+    }
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_sdk_clients_converge_through_faults_extended() {
+    for seed in 0..EXTENDED_SEEDS {
+        sdk_scenario(seed, 200).await;
+    }
+}
+
+// ============================================================================
+// Causal Delta Replicas
+// ============================================================================
+
+/// A temporary directory, removed on drop
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "carnelia-{name}-{}-{:?}",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Durable storage keeping each replica's state in a JSON file
+struct FileStorage {
+    dir: PathBuf,
+}
+
+impl FileStorage {
+    fn path(&self, replica_id: &str) -> PathBuf {
+        self.dir.join(format!("{replica_id}.json"))
+    }
+}
+
+fn io_error(e: std::io::Error) -> StorageError {
+    StorageError::IoError(e.to_string())
+}
+
+impl DurableStorage<Counter> for FileStorage {
+    fn persist(&mut self, state: &DurableState<Counter>) -> Result<(), StorageError> {
+        let bytes = serde_json::to_vec(state)
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        // Replace the file in one step, so a crash never leaves half of it
+        let path = self.path(&state.replica_id);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes).map_err(io_error)?;
+        fs::rename(&tmp, &path).map_err(io_error)
+    }
+
+    fn load(&self, replica_id: &str) -> Result<Option<DurableState<Counter>>, StorageError> {
+        match fs::read(self.path(replica_id)) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|e| StorageError::SerializationError(e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(io_error(e)),
+        }
+    }
+
+    fn sync(&mut self) -> Result<(), StorageError> {
+        Ok(())
+    }
+}
+
+/// Causal replicas persisting every state change before acting on it
+struct DeltaCluster {
+    replicas: Vec<CausalReplica<Counter>>,
+    storage: FileStorage,
+    network: CausalNetworkSimulator<Counter>,
+}
+
+impl DeltaCluster {
+    fn new(n: usize, dir: PathBuf) -> Self {
+        let ids: Vec<String> = (0..n).map(|i| format!("replica_{i}")).collect();
+        let replicas = ids
+            .iter()
+            .map(|id| {
+                let mut replica = CausalReplica::new(id.clone());
+                for peer in ids.iter().filter(|peer| *peer != id) {
+                    replica.register_peer(peer.clone());
+                }
+                replica
+            })
+            .collect();
+        Self {
+            replicas,
+            storage: FileStorage { dir },
+            network: CausalNetworkSimulator::new(0.0),
+        }
+    }
+
+    fn index_of(&self, id: &str) -> usize {
+        self.replicas.iter().position(|r| r.id() == id).unwrap()
+    }
+
+    fn persist(&mut self, idx: usize) {
+        self.storage
+            .persist(self.replicas[idx].durable_state())
+            .unwrap();
+        self.storage.sync().unwrap();
+    }
+
+    fn mutate(&mut self, idx: usize, amount: i64) {
+        let id = self.replicas[idx].id().clone();
+        self.replicas[idx].mutate(|state| bump(state, &id, amount));
+        self.persist(idx);
+    }
+
+    fn broadcast(&mut self, idx: usize) {
+        let mut intervals = self.replicas[idx].prepare_all_intervals();
+        intervals.sort_by(|a, b| a.to.cmp(&b.to));
+        for interval in intervals {
+            self.network.send(CausalMessage::DeltaInterval(interval));
+        }
+    }
+
+    fn handle(&mut self, msg: CausalMessage<Counter>) {
+        match msg {
+            CausalMessage::DeltaInterval(interval) => {
+                let idx = self.index_of(&interval.to);
+                let ack = self.replicas[idx].receive_interval(interval);
+                self.persist(idx);
+                if let Some(ack) = ack {
+                    self.network.send(CausalMessage::Ack(ack));
+                }
+            }
+            CausalMessage::Ack(ack) => {
+                let idx = self.index_of(&ack.to);
+                self.replicas[idx].receive_ack(&ack);
+            }
+            CausalMessage::SnapshotRequest { from, to } => {
+                let (state, seq) = self.replicas[self.index_of(&to)].snapshot();
+                self.network.send(CausalMessage::Snapshot {
+                    from: to,
+                    to: from,
+                    state,
+                    seq,
+                });
+            }
+            CausalMessage::Snapshot {
+                from,
+                to,
+                state,
+                seq,
+            } => {
+                let idx = self.index_of(&to);
+                self.replicas[idx].apply_snapshot(state, seq, &from);
+                self.persist(idx);
+            }
+            msg => panic!("unexpected message {msg:?}"),
+        }
+    }
+
+    /// Lose the replica's volatile state and restart it from its file
+    fn crash(&mut self, idx: usize) {
+        let id = self.replicas[idx].id().clone();
+        let durable = self
+            .storage
+            .load(&id)
+            .unwrap()
+            .unwrap_or_else(|| DurableState::new(id.clone()));
+        assert_eq!(durable.state, *self.replicas[idx].state());
+        assert_eq!(durable.counter, self.replicas[idx].counter());
+
+        let mut recovered = CausalReplica::restore(durable);
+        let peers: Vec<_> = self
+            .replicas
+            .iter()
+            .map(|r| r.id().clone())
+            .filter(|peer| *peer != id)
+            .collect();
+        for peer in peers {
+            recovered.register_peer(peer.clone());
+            self.network.send(CausalMessage::SnapshotRequest {
+                from: id.clone(),
+                to: peer,
+            });
+        }
+        self.replicas[idx] = recovered;
+    }
+
+    fn converged(&self) -> bool {
+        let first = self.replicas[0].state();
+        self.replicas.iter().all(|r| r.state() == first)
+    }
+
+    /// Resend what was lost and run sync rounds until every replica agrees
+    fn settle(&mut self) {
+        self.network.retransmit_lost();
+        for _ in 0..20 {
+            for idx in 0..self.replicas.len() {
+                self.broadcast(idx);
+            }
+            while let Some(msg) = self.network.receive() {
+                self.handle(msg);
+            }
+            if self.converged() {
+                return;
+            }
+        }
+    }
+}
+
+fn delta_scenario(seed: u64, replicas: usize, steps: usize) {
+    let dir = TempDir::new(&format!("delta-{seed}"));
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut cluster = DeltaCluster::new(replicas, dir.0.clone());
+    let mut total = 0;
+
+    for _ in 0..steps {
+        let idx = rng.gen_range(0..replicas);
+        let in_flight = cluster.network.in_flight_count();
+        match rng.gen_range(0..100) {
+            0..=34 => {
+                let amount = amount(&mut rng);
+                total += amount;
+                cluster.mutate(idx, amount);
+            }
+            35..=54 => cluster.broadcast(idx),
+            55..=84 if in_flight > 0 => {
+                let msg = cluster.network.take(rng.gen_range(0..in_flight)).unwrap();
+                cluster.handle(msg);
+            }
+            85..=92 if in_flight > 0 => {
+                let at = rng.gen_range(0..in_flight);
+                if matches!(
+                    cluster.network.peek(at),
+                    Some(CausalMessage::DeltaInterval(_))
+                ) {
+                    cluster.network.drop_at(at);
+                }
+            }
+            93..=95 => cluster.crash(idx),
+            _ => cluster.network.retransmit_lost(),
+        }
+    }
+
+    cluster.settle();
+    for replica in &cluster.replicas {
+        assert_eq!(
+            replica.state(),
+            cluster.replicas[0].state(),
+            "seed {seed}: {} diverged",
+            replica.id()
+        );
+        assert_eq!(
+            replica.state().value(),
+            total,
+            "seed {seed}: {}",
+            replica.id()
+        );
+        let stored = cluster.storage.load(replica.id()).unwrap().unwrap();
+        assert_eq!(
+            stored.state,
+            *replica.state(),
+            "seed {seed}: {}",
+            replica.id()
+        );
+    }
+}
+
+#[test]
+fn test_delta_replicas_recover_from_files() {
+    for seed in 0..SEEDS {
+        delta_scenario(seed, 3, 300);
+    }
+}
+
+#[test]
+#[ignore]
+fn test_delta_replicas_recover_from_files_extended() {
+    for seed in 0..EXTENDED_SEEDS {
+        delta_scenario(seed, 5, 5_000);
+    }
+}
+
+// ============================================================================
+// Merkle-DAG Replicas with Compaction
+// ============================================================================
+
+/// A replica storing counter deltas in a Merkle-DAG
+struct DagReplica {
+    id: String,
+    syncer: DAGSyncer<MemoryDAGStore>,
+    compactor: Compactor,
+    vv: VersionVector,
+    /// The counter as of the last write or pull
+    state: Counter,
+}
+
+impl DagReplica {
+    fn new(id: &str, store: MemoryDAGStore, config: SyncConfig) -> Self {
+        Self {
+            id: id.to_string(),
+            syncer: DAGSyncer::with_config(store, config),
+            compactor: Compactor::new(id),
+            vv: VersionVector::new(),
+            state: Counter::new(),
+        }
+    }
+
+    fn write(&mut self, amount: i64) {
+        let delta = bump(&self.state, &self.id, amount);
+        let seq = self.vv.increment(self.id.clone());
+        let node = NodeBuilder::new()
+            .with_parents(self.syncer.heads())
+            .with_payload(Payload::delta(serde_json::to_vec(&delta).unwrap()))
+            .with_timestamp(seq)
+            .with_creator(&self.id)
+            .build();
+        self.syncer.store_mut().put(node).unwrap();
+        self.state.join_assign(&delta);
+    }
+
+    /// Rebuild the counter from the DAG: every delta joined with the state
+    /// of every snapshot anchor
+    fn materialize(&self) -> Counter {
+        let store = self.syncer.store();
+        let mut state = Counter::new();
+        for cid in store.topological_order() {
+            match &store.get(&cid).unwrap().payload {
+                Payload::Delta(bytes) => state.join_assign(&serde_json::from_slice(bytes).unwrap()),
+                Payload::SnapshotAnchor { snapshot_hash, .. } => {
+                    let snapshot = self
+                        .compactor
+                        .snapshots()
+                        .get(snapshot_hash)
+                        .expect("anchored snapshot is known");
+                    state.join_assign(&serde_json::from_slice(&snapshot.state_data).unwrap());
+                }
+                Payload::Genesis | Payload::Snapshot(_) => {}
+            }
+        }
+        state
+    }
+
+    fn frontier(&mut self) {
+        let (vv, heads) = (self.vv.clone(), self.syncer.heads());
+        self.compactor.update_local_frontier(vv, heads);
+    }
+}
+
+/// Borrow the replica pulling and the one it pulls from
+fn pair(replicas: &mut [DagReplica], to: usize, from: usize) -> (&mut DagReplica, &DagReplica) {
+    assert_ne!(to, from);
+    if to < from {
+        let (left, right) = replicas.split_at_mut(from);
+        (&mut left[to], &right[0])
+    } else {
+        let (left, right) = replicas.split_at_mut(to);
+        (&mut right[0], &left[from])
+    }
+}
+
+/// Pull everything `from` has into `to`, repairing gaps and fetching the
+/// snapshot behind any anchor `to` bootstraps from
+fn pull(to: &mut DagReplica, from: &DagReplica, seed: u64) {
+    let heads = from.syncer.heads();
+    // Responses come in batches. `from` can only tell what we have from
+    // the heads it knows, so a batch may bring nothing new when our heads
+    // are our own writes: then we list everything we have.
+    let mut list_all = false;
+    while !to.syncer.is_synced_with(&heads) {
+        let before = to.syncer.store().len();
+        let mut request = to.syncer.create_request(&heads);
+        request
+            .want
+            .extend(to.syncer.find_missing_ancestors(&to.syncer.heads()));
+        if list_all {
+            request.have = to.syncer.store().topological_order();
+        }
+        let response = from.syncer.handle_request(&request);
+
+        let DagReplica {
+            syncer, compactor, ..
+        } = to;
+        syncer
+            .apply_response_with_snapshots(response, |node| {
+                let (id, _vv) = Snapshot::read_anchor(node).map_err(|e| e.to_string())?;
+                let snapshot = from
+                    .compactor
+                    .snapshots()
+                    .get(&id)
+                    .ok_or("unknown snapshot")?;
+                compactor
+                    .bootstrap_from_snapshot(snapshot.clone())
+                    .map_err(|e| e.to_string())?;
+                Ok(())
+            })
+            .unwrap();
+        if to.syncer.store().len() == before {
+            if list_all {
+                break;
+            }
+            list_all = true;
+        }
+    }
+    assert!(
+        to.syncer.is_synced_with(&heads),
+        "seed {seed}: {} did not catch up with {}",
+        to.id,
+        from.id
+    );
+    to.vv.merge(&from.vv);
+    to.state.join_assign(&from.state);
+}
+
+/// Random writes on every replica with random pulls between them
+fn write_rounds(replicas: &mut [DagReplica], steps: usize, rng: &mut StdRng, seed: u64) -> i64 {
+    let mut total = 0;
+    for _ in 0..steps {
+        let to = rng.gen_range(0..replicas.len());
+        if rng.gen_bool(0.6) {
+            let amount = amount(rng);
+            total += amount;
+            replicas[to].write(amount);
+        } else {
+            let from = (to + rng.gen_range(1..replicas.len())) % replicas.len();
+            let (to, from) = pair(replicas, to, from);
+            pull(to, from, seed);
+        }
+    }
+    total
+}
+
+/// Pull in a ring twice, so everyone has everything
+fn full_sync(replicas: &mut [DagReplica], seed: u64) {
+    let n = replicas.len();
+    for i in 0..2 * n {
+        let (to, from) = pair(replicas, (i + 1) % n, i % n);
+        pull(to, from, seed);
+    }
+}
+
+fn merkle_scenario(seed: u64, replicas: usize, steps: usize) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (genesis_store, genesis) = MemoryDAGStore::with_genesis("shared");
+    let mut cluster: Vec<_> = (0..replicas)
+        .map(|i| {
+            DagReplica::new(
+                &format!("replica_{i}"),
+                genesis_store.clone(),
+                SyncConfig::default(),
+            )
+        })
+        .collect();
+
+    let mut total = write_rounds(&mut cluster, steps, &mut rng, seed);
+    full_sync(&mut cluster, seed);
+
+    // Replica 0 snapshots the shared history and everyone learns of it
+    let origin = &mut cluster[0];
+    origin.frontier();
+    let state = serde_json::to_vec(&origin.state).unwrap();
+    let (snapshot_id, anchor) = origin
+        .compactor
+        .create_anchored_snapshot(origin.syncer.store_mut(), || Ok(state))
+        .unwrap();
+    full_sync(&mut cluster, seed);
+    let snapshot = cluster[0]
+        .compactor
+        .snapshots()
+        .get(&snapshot_id)
+        .unwrap()
+        .clone();
+    for replica in &mut cluster[1..] {
+        replica
+            .compactor
+            .bootstrap_from_snapshot(snapshot.clone())
+            .unwrap();
+    }
+
+    // Once every replica reports having seen it, each prunes below it
+    for replica in &mut cluster {
+        replica.frontier();
+    }
+    let updates: Vec<_> = cluster
+        .iter()
+        .map(|r| r.compactor.create_frontier_update())
+        .collect();
+    let pruner = Pruner::with_policy(PruningPolicy {
+        min_node_age: 0,
+        preserve_depth: 1,
+        preserve_genesis_path: false,
+        ..Default::default()
+    });
+    for replica in &mut cluster {
+        for update in &updates {
+            if update.peer_id != replica.id {
+                replica.compactor.process_peer_update(update.clone());
+            }
+        }
+        assert!(
+            replica
+                .compactor
+                .stability()
+                .is_stable(&snapshot.version_vector),
+            "seed {seed}: snapshot not stable at {}",
+            replica.id
+        );
+        let result = pruner.execute_prune(replica.syncer.store_mut(), &snapshot, u64::MAX);
+        assert!(result.completed, "seed {seed}: pruning {}", replica.id);
+        assert!(replica.syncer.store().contains(&anchor));
+        assert!(!replica.syncer.store().contains(&genesis));
+    }
+
+    total += write_rounds(&mut cluster, steps / 2, &mut rng, seed);
+    full_sync(&mut cluster, seed);
+
+    // A new replica bootstraps from the anchor, pulling from random peers
+    let config = SyncConfig {
+        snapshot_bootstrap: true,
+        ..Default::default()
+    };
+    let mut joiner = DagReplica::new("joiner", MemoryDAGStore::new(), config);
+    for _ in 0..replicas {
+        let from = &cluster[rng.gen_range(0..replicas)];
+        pull(&mut joiner, from, seed);
+    }
+    assert!(!joiner.syncer.store().contains(&genesis));
+    cluster.push(joiner);
+
+    for replica in &cluster {
+        assert_eq!(
+            replica.syncer.heads(),
+            cluster[0].syncer.heads(),
+            "seed {seed}: heads of {}",
+            replica.id
+        );
+        let state = replica.materialize();
+        assert_eq!(state, replica.state, "seed {seed}: {} rebuilt", replica.id);
+        assert_eq!(state.value(), total, "seed {seed}: value at {}", replica.id);
+    }
+}
+
+#[test]
+fn test_merkle_replicas_compact_and_bootstrap() {
+    for seed in 0..SEEDS {
+        merkle_scenario(seed, 3, 60);
+    }
+}
+
+#[test]
+#[ignore]
+fn test_merkle_replicas_compact_and_bootstrap_extended() {
+    for seed in 0..EXTENDED_SEEDS {
+        merkle_scenario(seed, 5, 600);
+    }
+}
@@ -0,0 +1,142 @@
+//! The stress runner's machine-readable output
+//!
+//! Runs the binary the way a regression-tracking script would and checks
+//! the JSON and CSV it prints: one record per test with its sizes,
+//! timings, throughput and convergence, and the same results for the same
+//! seed.
+
+use serde_json::Value;
+use std::process::Command;
+
+fn run(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_carnelia"))
+        .args(args)
+        .output()
+        .expect("failed to start the stress runner");
+    assert!(
+        output.status.success(),
+        "stress runner failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).expect("output is UTF-8")
+}
+
+fn run_json(args: &[&str]) -> Value {
+    let stdout = run(args);
+    serde_json::from_str(&stdout).unwrap_or_else(|e| panic!("{}: {}", e, stdout))
+}
+
+#[test]
+fn test_json_report_schema() {
+    let report = run_json(&[
+        "core",
+        "--replicas",
+        "3",
+        "--ops",
+        "20",
+        "--syncs",
+        "30",
+        "--seed",
+        "42",
+        "--format",
+        "json",
+    ]);
+    assert_eq!(report["suite"], "core");
+    assert_eq!(report["seed"], 42);
+
+    let records = report["records"].as_array().unwrap();
+    // Two scales of five and three tests, and the combined five
+    assert_eq!(records.len(), 13);
+    for record in records {
+        assert_eq!(record["kind"], "stress");
+        assert!(record["test_name"].is_string());
+        assert_eq!(record["num_replicas"], 3);
+        assert_eq!(record["operations_per_replica"], 20);
+        assert!(record["total_syncs"].is_u64());
+        assert!(record["total_time_ms"].as_f64().unwrap() >= 0.0);
+        assert!(record["avg_sync_time_ms"].as_f64().unwrap() >= 0.0);
+        assert!(record["ops_per_second"].as_f64().unwrap() > 0.0);
+        assert_eq!(record["converged"], true);
+        assert_eq!(record["seed"], 42);
+        assert_eq!(record["state_digests"].as_array().unwrap().len(), 3);
+    }
+}
+
+#[test]
+fn test_delta_and_benchmark_records() {
+    let args = ["--replicas", "2", "--ops", "10", "--format", "json"];
+    let delta = run_json(&[&["delta"], &args[..]].concat());
+    for record in delta["records"].as_array().unwrap() {
+        assert_eq!(record["kind"], "delta");
+        assert!(record["network_config"].is_string());
+        assert!(record["sync_rounds"].is_u64());
+        assert!(record["total_time_ms"].is_f64());
+        assert_eq!(record["converged"], true);
+    }
+
+    let routing = run_json(&[&["routing"], &args[..]].concat());
+    let records = routing["records"].as_array().unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["kind"], "benchmark");
+    for result in records[0]["results"].as_array().unwrap() {
+        assert!(result["name"].is_string());
+        assert!(result["total_time_ms"].is_f64());
+        assert!(result["avg_op_time_ms"].is_f64());
+        assert!(result["ops_per_second"].is_f64());
+    }
+}
+
+#[test]
+fn test_same_seed_same_results() {
+    let args = [
+        "db",
+        "--replicas",
+        "2",
+        "--ops",
+        "15",
+        "--seed",
+        "7",
+        "--format",
+        "json",
+    ];
+    let outcome = |report: &Value| -> Vec<Value> {
+        report["records"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|record| record["state_digests"].clone())
+            .collect()
+    };
+    assert_eq!(outcome(&run_json(&args)), outcome(&run_json(&args)));
+}
+
+#[test]
+fn test_csv_report_to_file() {
+    let path = std::env::temp_dir().join(format!("mdcs_stress_cli_{}.csv", std::process::id()));
+    let stdout = run(&[
+        "db",
+        "--replicas",
+        "2",
+        "--ops",
+        "10",
+        "--format",
+        "csv",
+        "--output",
+        path.to_str().unwrap(),
+    ]);
+    // The progress output stays on stdout when the report goes to a file
+    assert!(stdout.contains("Database layer tests completed"));
+
+    let csv = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+    let mut lines = csv.lines();
+    let header: Vec<&str> = lines.next().unwrap().split(',').collect();
+    assert_eq!(header[0], "kind");
+    assert!(header.contains(&"total_time_ms") && header.contains(&"converged"));
+    let rows: Vec<&str> = lines.collect();
+    assert_eq!(rows.len(), 12);
+    for row in rows {
+        assert_eq!(row.split(',').count(), header.len(), "{}", row);
+        assert!(row.starts_with("stress,"));
+    }
+}